@@ -25,6 +25,67 @@ impl Default for LogRecord {
     }
 }
 
+/// Level value in a `FilterRecord` that removes the filter for the module.
+pub const FILTER_REMOVE: u32 = u32::MAX;
+
+/// Sets the most verbose level that is shown and kept for the modules whose path starts with `module`.
+#[repr(C, align(4096))]
+pub struct FilterRecord {
+    pub module_length: u32,
+    pub module: [u8; 128],
+    /// a `log::LevelFilter` as `u32`, or `FILTER_REMOVE`
+    pub level: u32,
+    pub _padding: [u8; 3960],
+}
+
+impl Default for FilterRecord {
+    fn default() -> Self {
+        FilterRecord { module_length: 0, module: [0u8; 128], level: 0, _padding: [0u8; 3960] }
+    }
+}
+
+/// A page of messages read back from the log server's RAM buffer.
+///
+/// `data` holds one message per line, each starting with its `log::Level` as a digit and a space.
+#[repr(C, align(4096))]
+pub struct LogBuffer {
+    /// sequence number of the first message to read. On return, the sequence number to continue from.
+    pub start: u32,
+    /// on return, the sequence number that the next message logged will get
+    pub end: u32,
+    pub data_length: u32,
+    pub data: [u8; 4084],
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self { LogBuffer { start: 0, end: 0, data_length: 0, data: [0u8; 4084] } }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub enum CrashKind {
+    /// no crash report was pending
+    None = 0,
+    /// a process panicked
+    Panic = 1,
+    /// the watchdog armed, because the ticktimer wasn't serviced for a whole watchdog period
+    Watchdog = 2,
+}
+
+/// A crash report taken from the log server, to be stored persistently by a service that can use the PDDB.
+#[repr(C, align(4096))]
+pub struct CrashRecord {
+    /// a `CrashKind` as `u32`
+    pub kind: u32,
+    /// PID of the process that crashed, or 0 if unknown
+    pub pid: u32,
+    pub text_length: u32,
+    pub text: [u8; 4084],
+}
+
+impl Default for CrashRecord {
+    fn default() -> Self { CrashRecord { kind: 0, pid: 0, text_length: 0, text: [0u8; 4084] } }
+}
+
 #[derive(Debug, PartialEq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub enum Opcode {
     /// A `LogRecord` message, delivering structured log output
@@ -44,6 +105,19 @@ pub enum Opcode {
     TryHookUsbMirror = 4,
     UnhookUsbMirror = 5,
 
+    /// A `FilterRecord` that sets or removes a severity filter
+    SetFilter = 6,
+
+    /// A mutably lent `LogBuffer` that is filled with messages from the RAM buffer
+    ReadBuffer = 7,
+
+    /// The watchdog armed. Sent by the ticktimer as a scalar message.
+    WatchdogEvent = 8,
+
+    /// A mutably lent `CrashRecord` that is filled with the oldest pending crash report, which is then
+    /// removed from the log server
+    TakeCrashReport = 9,
+
     /// A panic occurred, and a panic log is forthcoming
     PanicStarted = 1000,
 
@@ -98,3 +98,128 @@ pub fn init_wait() -> Result<(), ()> {
 }
 
 pub fn resume() { XOUS_LOGGER.resume(); }
+
+fn logger_connection() -> Result<xous::CID, LogError> {
+    match XOUS_LOGGER_CONNECTION.load(Ordering::Relaxed) {
+        0 => Err(LogError::NoConnection),
+        cid => Ok(cid),
+    }
+}
+
+/// Sets the most verbose level that is shown on the console and kept in the RAM buffer for the modules
+/// whose path starts with `module`. The longest matching filter applies; an empty `module` matches
+/// everything. Note that a filter can't show messages that a process doesn't send, and processes only
+/// send `Info` and above unless they raise their own maximum level.
+pub fn set_filter(module: &str, level: log::LevelFilter) -> Result<(), LogError> {
+    send_filter(module, level as u32)
+}
+
+/// Removes the filter set for exactly `module`.
+pub fn remove_filter(module: &str) -> Result<(), LogError> { send_filter(module, api::FILTER_REMOVE) }
+
+fn send_filter(module: &str, level: u32) -> Result<(), LogError> {
+    let mut record = api::FilterRecord::default();
+    let module = module.as_bytes();
+    record.module_length = module.len().min(record.module.len()) as u32;
+    for (dest, src) in record.module.iter_mut().zip(module) {
+        *dest = *src;
+    }
+    record.level = level;
+    let buf = unsafe {
+        xous::MemoryRange::new(
+            &record as *const api::FilterRecord as usize,
+            core::mem::size_of::<api::FilterRecord>(),
+        )
+        .unwrap()
+    };
+    xous::send_message(
+        logger_connection()?,
+        xous::Message::new_lend(api::Opcode::SetFilter.to_usize().unwrap(), buf, None, None),
+    )
+    .map(|_| ())
+    .or(Err(LogError::NoConnection))
+}
+
+/// Reads back the messages kept in the log server's RAM buffer, starting from sequence number `start`,
+/// and calls `f` on each of them. Only as many messages as fit in a page are read per call; the return
+/// value is the sequence number to continue from, and the sequence number that the next message logged
+/// will get. Messages that have dropped out of the buffer are skipped.
+pub fn read_buffer(start: u32, mut f: impl FnMut(log::Level, &str)) -> Result<(u32, u32), LogError> {
+    let mut buffer = api::LogBuffer::default();
+    buffer.start = start;
+    let buf = unsafe {
+        xous::MemoryRange::new(
+            &mut buffer as *mut api::LogBuffer as usize,
+            core::mem::size_of::<api::LogBuffer>(),
+        )
+        .unwrap()
+    };
+    xous::send_message(
+        logger_connection()?,
+        xous::Message::new_lend_mut(api::Opcode::ReadBuffer.to_usize().unwrap(), buf, None, None),
+    )
+    .or(Err(LogError::NoConnection))?;
+    let data = &buffer.data[..(buffer.data_length as usize).min(buffer.data.len())];
+    for line in data.split(|&c| c == b'\n') {
+        let level = match line.first() {
+            Some(b'1') => log::Level::Error,
+            Some(b'2') => log::Level::Warn,
+            Some(b'3') => log::Level::Info,
+            Some(b'4') => log::Level::Debug,
+            Some(b'5') => log::Level::Trace,
+            _ => continue,
+        };
+        f(level, core::str::from_utf8(line.get(2..).unwrap_or_default()).unwrap_or("(invalid UTF-8)"));
+    }
+    Ok((buffer.start, buffer.end))
+}
+
+/// Reads back every message kept in the log server's RAM buffer, oldest first, calling `f` on each of
+/// them.
+pub fn read_all(mut f: impl FnMut(log::Level, &str)) -> Result<(), LogError> {
+    let mut start = 0;
+    loop {
+        let (next, end) = read_buffer(start, &mut f)?;
+        if next == end || next == start {
+            return Ok(());
+        }
+        start = next;
+    }
+}
+
+/// Reports that the watchdog armed. Only the ticktimer, which services the watchdog, should call this.
+pub fn report_watchdog_event() -> Result<(), LogError> {
+    xous::send_message(
+        logger_connection()?,
+        xous::Message::new_scalar(api::Opcode::WatchdogEvent.to_usize().unwrap(), 0, 0, 0, 0),
+    )
+    .map(|_| ())
+    .or(Err(LogError::NoConnection))
+}
+
+/// Takes the oldest crash report that the log server is holding, and calls `f` with its kind, the PID
+/// of the process concerned, and its text. The log server can't use the PDDB, so it only keeps a few
+/// reports until a service that can store them takes them. Returns `false` if no report was pending.
+pub fn take_crash_report(f: impl FnOnce(api::CrashKind, u32, &str)) -> Result<bool, LogError> {
+    let mut record = api::CrashRecord::default();
+    let buf = unsafe {
+        xous::MemoryRange::new(
+            &mut record as *mut api::CrashRecord as usize,
+            core::mem::size_of::<api::CrashRecord>(),
+        )
+        .unwrap()
+    };
+    xous::send_message(
+        logger_connection()?,
+        xous::Message::new_lend_mut(api::Opcode::TakeCrashReport.to_usize().unwrap(), buf, None, None),
+    )
+    .or(Err(LogError::NoConnection))?;
+    match num_traits::FromPrimitive::from_u32(record.kind) {
+        Some(api::CrashKind::None) | None => Ok(false),
+        Some(kind) => {
+            let text = &record.text[..(record.text_length as usize).min(record.text.len())];
+            f(kind, record.pid, core::str::from_utf8(text).unwrap_or("(invalid UTF-8)"));
+            Ok(true)
+        }
+    }
+}
@@ -81,12 +81,96 @@ pub enum Opcode {
     /// }
     /// ```
     TryConnect = 7,
+
+    /// Revoke the capability tokens a client holds for a server. The caller proves it owns
+    /// the server by presenting its SID, and the client is refused further connections to
+    /// that server until the server registers again, or the client's PID goes to a new
+    /// process. The connections themselves are revoked by the server, through the kernel.
+    ///
+    /// # Message Types
+    ///
+    ///     * MutableLend
+    ///
+    /// # Arguments
+    ///
+    /// A `Revocation` naming the server and the client.
+    ///
+    /// # Return Values
+    ///
+    /// `Return::Success` once the client is revoked, or `Return::Failure` if no server has the SID.
+    Revoke = 8,
+
+    /// Internal: sent by the name server to itself once a second, to expire tokens whose
+    /// lifetime has run out and to notice servers that stopped sending heartbeats.
+    ClockTick = 9,
+
+    /// Start tracking the liveness of a server, which promises a heartbeat at least every
+    /// `interval_secs`. Registering again, as a restarted server does, counts as a restart.
+    ///
+    /// # Message Types
+    ///
+    ///     * MutableLend
+    ///
+    /// # Arguments
+    ///
+    /// A `LivenessRegistration`. The SID proves the caller owns the server.
+    ///
+    /// # Return Values
+    ///
+    /// `Return::Success`, or `Return::Failure` if no server has the SID.
+    RegisterLiveness = 10,
+
+    /// A heartbeat from a server whose liveness is tracked.
+    ///
+    /// # Message Types
+    ///
+    ///     * Scalar
+    ///
+    /// # Arguments
+    ///
+    /// The four words of the server's SID.
+    Heartbeat = 11,
+
+    /// Query the liveness of a server by name.
+    ///
+    /// # Message Types
+    ///
+    ///     * MutableLend
+    ///
+    /// # Arguments
+    ///
+    /// The name of the server, as a `xous_ipc::String<64>`.
+    ///
+    /// # Return Values
+    ///
+    /// `Return::Liveness`, with `None` if the server never registered for liveness tracking.
+    QueryLiveness = 12,
+
+    /// Wait for a client's tokens for a server to expire. The server's own thread waits on
+    /// this, and revokes the client's connections when it returns, since only the owner of
+    /// a server may revoke connections to it.
+    ///
+    /// # Message Types
+    ///
+    ///     * BlockingScalar
+    ///
+    /// # Arguments
+    ///
+    /// The four words of the server's SID.
+    ///
+    /// # Return Values
+    ///
+    /// A Scalar2 with the PID and generation of the client whose tokens lapsed, or a PID of 0
+    /// once no server has the SID.
+    WaitLapsed = 13,
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct Registration {
     pub name: xous_ipc::String<64>,
     pub conn_limit: Option<u32>,
+    /// seconds that a connection token stays valid for; `None` for tokens that never expire
+    pub token_lifetime: Option<u32>,
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
@@ -95,6 +179,40 @@ pub struct Disconnect {
     pub token: [u32; 4],
 }
 
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Revocation {
+    /// the SID of the server, which proves the caller owns it
+    pub sid: [u32; 4],
+    /// the PID of the client to revoke
+    pub pid: u8,
+    /// the generation of the PID, which tells the client apart from later processes that get it
+    pub generation: u32,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct LivenessRegistration {
+    /// the SID of the server, which proves the caller owns it
+    pub sid: [u32; 4],
+    /// the longest the server may go between heartbeats
+    pub interval_secs: u32,
+}
+
+/// What the name server knows of a server's health. Times are counted by the name server's clock,
+/// which runs while any server's liveness is tracked.
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct ServiceLiveness {
+    /// the server's name is currently registered
+    pub registered: bool,
+    /// the server has sent a heartbeat within twice its interval
+    pub up: bool,
+    /// seconds since the server came up, or since it went down if it is not up
+    pub since_secs: u32,
+    /// seconds since the last heartbeat
+    pub last_heartbeat_secs: u32,
+    /// how many times the server has registered for liveness again after its first registration
+    pub restarts: u32,
+}
+
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct AuthenticatedLookup {
     pub name: xous_ipc::String<64>,
@@ -11,7 +11,7 @@
 // for 1.0") release until we rework the entire system to chase the latest rkyv.
 // As of now, the current version is 0.7.x and there isn't a timeline yet for 0.8.
 #![allow(dead_code)]
-use crate::api::AuthenticateRequest;
+use crate::api::{AuthenticateRequest, ServiceLiveness};
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 #[repr(C)]
@@ -28,7 +28,10 @@ pub enum Return {
     /// A connection was successfully made with the given CID; an optional "disconnect token" is provided
     CID((xous::CID, Option<[u32; 4]>)),
 
-    /// Operation requested was otherwise successful (currently only used by disconnect to ack the
-    /// disconnect)
+    /// Operation requested was otherwise successful (used to ack a disconnect, a revocation or a
+    /// liveness registration)
     Success,
+
+    /// The liveness of a server, if it is tracked
+    Liveness(Option<ServiceLiveness>),
 }
@@ -62,7 +62,62 @@ impl XousNames {
     /// effectively blocks further services from connecting to the server in a
     /// Trust-On-First-Use (TOFU) model.
     pub fn register_name(&self, name: &str, max_conns: Option<u32>) -> Result<xous::SID, xous::Error> {
-        let mut registration = api::Registration { name: String::<64>::new(), conn_limit: max_conns };
+        self.register(name, max_conns, None)
+    }
+
+    /// Register a server like `register_name()`, but with connection tokens that expire
+    /// `token_lifetime_secs` seconds after they are granted. Once a token expires, the
+    /// kernel refuses messages on the connection it granted and its slot in `max_conns`
+    /// is freed, so clients that need the server for longer must connect again.
+    ///
+    /// Only the owner of a server may revoke connections to it, so this starts a thread in
+    /// the calling process that waits for tokens to expire and revokes their connections.
+    /// The thread exits when the server unregisters.
+    pub fn register_name_with_token_lifetime(
+        &self,
+        name: &str,
+        max_conns: Option<u32>,
+        token_lifetime_secs: u32,
+    ) -> Result<xous::SID, xous::Error> {
+        let sid = self.register(name, max_conns, Some(token_lifetime_secs))?;
+        std::thread::spawn(move || {
+            let xns = XousNames::new().unwrap();
+            let s = sid.to_array();
+            loop {
+                let lapsed = xous::send_message(
+                    xns.conn,
+                    xous::Message::new_blocking_scalar(
+                        api::Opcode::WaitLapsed.to_usize().unwrap(),
+                        s[0] as usize,
+                        s[1] as usize,
+                        s[2] as usize,
+                        s[3] as usize,
+                    ),
+                );
+                let (pid, generation) = match lapsed {
+                    Ok(xous::Result::Scalar2(pid, generation)) => match xous::PID::new(pid as u8) {
+                        Some(pid) => (pid, generation as u32),
+                        None => break,
+                    },
+                    _ => break,
+                };
+                // the client may have exited, and its PID gone to another process, since its token lapsed
+                if xous::process_generation(pid) == Ok(generation) {
+                    xous::revoke_connection(pid, sid).ok();
+                }
+            }
+        });
+        Ok(sid)
+    }
+
+    fn register(
+        &self,
+        name: &str,
+        max_conns: Option<u32>,
+        token_lifetime: Option<u32>,
+    ) -> Result<xous::SID, xous::Error> {
+        let mut registration =
+            api::Registration { name: String::<64>::new(), conn_limit: max_conns, token_lifetime };
         // could also do String::from_str() but in this case we want things to fail if the string is too long.
         write!(registration.name, "{}", name).expect("name probably too long");
 
@@ -83,9 +138,11 @@ impl XousNames {
     }
 
     /// Request a connection to the server with `name`. If the connection is allowed,
-    /// a 128-bit token is provided (in the form of a `[u32; 4]`) which can be used
-    /// later on to disconnect from the server, effectively decrementing the total
-    /// number of counts in against the `max_count` limit.
+    /// a 128-bit capability token is provided (in the form of a `[u32; 4]`) which can be
+    /// used later on to disconnect from the server, effectively decrementing the total
+    /// number of counts in against the `max_count` limit. Each connection gets its own
+    /// token, which lapses if the server revokes it or if it outlives the server's
+    /// token lifetime.
     pub fn request_connection_with_token(
         &self,
         name: &str,
@@ -117,6 +174,96 @@ impl XousNames {
         }
     }
 
+    /// Revokes the connections that the process `pid` holds to the server whose SID is `sid`.
+    /// The kernel refuses any further message on them, and the process can't connect to the
+    /// server again until it registers anew. This must be called by the process that owns
+    /// the server, and is how a server cuts off a misbehaving client without a reboot.
+    ///
+    /// A process that has already exited has no connections left to revoke, and a later
+    /// process that is given its PID is not affected.
+    pub fn revoke_connection(&self, sid: xous::SID, pid: xous::PID) -> Result<(), xous::Error> {
+        let generation = match xous::process_generation(pid) {
+            Ok(generation) => generation,
+            Err(xous::Error::ProcessNotFound) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        match xous::revoke_connection(pid, sid) {
+            // the client may have exited in the meantime
+            Ok(_) | Err(xous::Error::ProcessNotFound) => (),
+            Err(e) => return Err(e),
+        }
+        let revocation = api::Revocation { sid: sid.to_array(), pid: pid.get(), generation };
+        let mut buf = Buffer::into_buf(revocation).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::Revoke.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+
+        match buf.to_original().unwrap() {
+            api::Return::Success => Ok(()),
+            _ => Err(xous::Error::ServerNotFound),
+        }
+    }
+
+    /// Starts tracking the liveness of the server whose SID is `sid`. The server promises a
+    /// `heartbeat()` at least every `interval_secs`, and is reported down once it misses two.
+    /// A server that registers again, as it does when it is restarted, is counted as restarted.
+    pub fn register_liveness(&self, sid: xous::SID, interval_secs: u32) -> Result<(), xous::Error> {
+        let registration = api::LivenessRegistration { sid: sid.to_array(), interval_secs };
+        let mut buf = Buffer::into_buf(registration).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::RegisterLiveness.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+
+        match buf.to_original().unwrap() {
+            api::Return::Success => Ok(()),
+            _ => Err(xous::Error::ServerNotFound),
+        }
+    }
+
+    /// Tells the name server that the server whose SID is `sid` is alive. This does not block.
+    pub fn heartbeat(&self, sid: xous::SID) -> Result<(), xous::Error> {
+        let s = sid.to_array();
+        xous::send_message(
+            self.conn,
+            xous::Message::new_scalar(
+                api::Opcode::Heartbeat.to_usize().unwrap(),
+                s[0] as usize,
+                s[1] as usize,
+                s[2] as usize,
+                s[3] as usize,
+            ),
+        )
+        .map(|_| ())
+    }
+
+    /// Registers the server whose SID is `sid` for liveness tracking, and sends its heartbeats
+    /// from a thread of its own for as long as the process runs. A server that can hang without
+    /// exiting should rather call `heartbeat()` from its main loop, so that a hang shows.
+    pub fn start_heartbeat(&self, sid: xous::SID, interval_secs: u32) -> Result<(), xous::Error> {
+        self.register_liveness(sid, interval_secs)?;
+        std::thread::spawn(move || {
+            let xns = XousNames::new().unwrap();
+            loop {
+                std::thread::sleep(std::time::Duration::from_secs(interval_secs as u64));
+                xns.heartbeat(sid).ok();
+            }
+        });
+        Ok(())
+    }
+
+    /// Returns the liveness of the server with `name`, or `None` if it never registered for
+    /// liveness tracking. Clients can use this to wait for a server at startup, or to tell a
+    /// server that silently died from one that is merely slow.
+    pub fn liveness(&self, name: &str) -> Result<Option<api::ServiceLiveness>, xous::Error> {
+        let mut lookup_name = xous_ipc::String::<64>::new();
+        write!(lookup_name, "{}", name).expect("name probably too long");
+        let mut buf = Buffer::into_buf(lookup_name).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::QueryLiveness.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+
+        match buf.to_original().unwrap() {
+            api::Return::Liveness(liveness) => Ok(liveness),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
     /// Requests a permanent connection to server with `name`. Xous names brokers the
     /// entire connection, so the return value is the process-local CID (connection ID);
     /// the 128-bit server ID is never revealed.
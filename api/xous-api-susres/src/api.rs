@@ -5,6 +5,15 @@ use utralib::generated::*;
 
 pub const SERVER_NAME_SUSRES: &str = "_Suspend/resume manager_";
 
+/// Maximum length of the name of the process holding a suspend inhibitor
+pub const INHIBITOR_NAME_LEN: usize = 32;
+/// Maximum length of the reason a suspend inhibitor is held
+pub const INHIBITOR_REASON_LEN: usize = 64;
+/// Maximum number of suspend inhibitors held at any one time
+pub const MAX_INHIBITORS: usize = 16;
+/// Maximum number of suspend inhibitors one process can hold, so that it can't take every slot
+pub const MAX_INHIBITORS_PER_PROCESS: usize = 4;
+
 /// Note: there must be at least one subscriber to the `Last` suspend order event, otherwise
 /// the logic will never terminate. There may be multiple `Last` subscribers, but the order at
 /// which they finish would be indeterminate. Currently, the `Last` subscriber is the `spinor`
@@ -67,6 +76,14 @@ pub enum Opcode {
     /// used to power off the system without suspend
     PowerOff,
 
+    /// takes a named suspend inhibitor (memory message, lend_mut of `Inhibitor`; the handle is filled in)
+    Inhibit,
+    /// releases the caller's inhibitor with the handle in arg1
+    ReleaseInhibitor,
+    /// lists the inhibitors currently keeping the system awake (memory message, lend_mut of
+    /// `InhibitorList`)
+    ListInhibitors,
+
     /// exit the server
     Quit,
 }
@@ -81,6 +98,26 @@ pub struct ScalarHook {
     pub order: SuspendOrder,
 }
 
+/// A named hold on suspend, shown to the user as something keeping the device awake.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct Inhibitor {
+    /// Name the holding process was loaded with, filled in by the server so that it can't be
+    /// spoofed. Empty if the process has no name.
+    pub name: xous_ipc::String<INHIBITOR_NAME_LEN>,
+    pub reason: xous_ipc::String<INHIBITOR_REASON_LEN>,
+    /// handle that releases the inhibitor, assigned by the server; 0 if no more inhibitors can be held,
+    /// and for a process that denied suspend with `set_suspendable(false)`
+    pub handle: u32,
+    /// PID of the holder, assigned by the server
+    pub pid: u8,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct InhibitorList {
+    /// one more entry than `MAX_INHIBITORS`, for a process that denied suspend with `set_suspendable(false)`
+    pub inhibitors: [Option<Inhibitor>; MAX_INHIBITORS + 1],
+}
+
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub enum SuspendEventCallback {
     Event, // this contains a token as well which must be returned to indicate you're ready for the suspend
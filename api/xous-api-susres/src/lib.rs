@@ -177,6 +177,40 @@ impl Susres {
         }
     }
 
+    /// Keeps the system from suspending until the returned inhibitor is dropped. `reason` says why it
+    /// is held, e.g. "writing the SoC image"; it is shown to the user in the list of what is keeping
+    /// the device awake, next to the name the caller's process was loaded with. Fails with
+    /// `OutOfMemory` if `MAX_INHIBITORS` are already held, or the caller already holds
+    /// `MAX_INHIBITORS_PER_PROCESS`.
+    pub fn inhibit(&self, reason: &str) -> Result<SuspendInhibitor, xous::Error> {
+        if reason.len() > INHIBITOR_REASON_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        let inhibitor = Inhibitor {
+            name: xous_ipc::String::new(),
+            reason: xous_ipc::String::from_str(reason),
+            handle: 0,
+            pid: 0,
+        };
+        let mut buf = Buffer::into_buf(inhibitor).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::Inhibit.to_u32().unwrap())?;
+        let inhibitor = buf.to_original::<Inhibitor, _>().or(Err(xous::Error::InternalError))?;
+        if inhibitor.handle == 0 {
+            return Err(xous::Error::OutOfMemory);
+        }
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(SuspendInhibitor { conn: self.conn, handle: inhibitor.handle })
+    }
+
+    /// Lists what is currently keeping the system from suspending.
+    pub fn inhibitors(&self) -> Result<Vec<Inhibitor>, xous::Error> {
+        let list = InhibitorList { inhibitors: [None; MAX_INHIBITORS + 1] };
+        let mut buf = Buffer::into_buf(list).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::ListInhibitors.to_u32().unwrap())?;
+        let list = buf.to_original::<InhibitorList, _>().or(Err(xous::Error::InternalError))?;
+        Ok(list.inhibitors.iter().flatten().copied().collect())
+    }
+
     /// Passing `true` causes the whole SOC including peripherals to receive a reset signal
     /// `false` causes only the CPU to reboot, while the peripherals retain state. Generally you want `true`.
     pub fn reboot(&self, whole_soc: bool) -> Result<(), xous::Error> {
@@ -204,6 +238,29 @@ impl Susres {
             .map(|_| ())
     }
 }
+/// A named hold on suspend, taken with `Susres::inhibit()`. Suspend is allowed again once every
+/// inhibitor is dropped.
+#[derive(Debug)]
+pub struct SuspendInhibitor {
+    conn: CID,
+    handle: u32,
+}
+impl Drop for SuspendInhibitor {
+    fn drop(&mut self) {
+        send_message(
+            self.conn,
+            Message::new_scalar(Opcode::ReleaseInhibitor.to_usize().unwrap(), self.handle as usize, 0, 0, 0),
+        )
+        .ok();
+        // the inhibitor shares the connection of the `Susres` object that made it
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
+
 fn drop_conn(sid: xous::SID) {
     let cid = xous::connect(sid).unwrap();
     xous::send_message(cid, Message::new_scalar(SuspendEventCallback::Drop.to_usize().unwrap(), 0, 0, 0, 0))
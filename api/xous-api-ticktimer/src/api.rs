@@ -71,11 +71,55 @@ pub enum Opcode {
     /// *arg1*: The integer that matches the Condition value
     FreeCondition = 11,
 
+    /// Sample how many times each process has been switched in, along with the time of the sample.
+    /// Takes a `ProcessActivations` memory message.
+    GetProcessActivations = 12,
+
+    /// Record that an `Activity` happened just now. Fed by the services that see it, so that every idle
+    /// policy works from the same notion of "idle".
+    ///
+    /// # Arguments
+    ///
+    /// *arg1*: The `Activity`
+    ///
+    /// Only the first process to report a kind may report it again; other reports are ignored.
+    ReportActivity = 13,
+
+    /// Return the milliseconds since an `Activity` was last reported, or since boot if it never was.
+    ///
+    /// # Arguments
+    ///
+    /// *arg1*: The `Activity`
+    IdleMs = 14,
+
     /// Invalid call -- an error occurred decoding the opcode
     InvalidCall = u32::MAX as usize,
 }
 
+/// The kinds of activity tracked for idle policies
+#[repr(usize)]
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Activity {
+    /// A key was pressed; reported by the keyboard service
+    UserInput = 0,
+    /// Data moved on the network; reported by the net service, at most about once a second
+    Network = 1,
+}
+/// Number of variants in `Activity`
+pub const ACTIVITY_KINDS: usize = 2;
+
 #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct VersionString {
     pub version: xous_ipc::String<512>,
 }
+
+/// Largest PID that is sampled by `GetProcessActivations`.
+pub const MAX_SAMPLED_PID: usize = 64;
+
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct ProcessActivations {
+    /// time of the sample, in ms since boot
+    pub elapsed_ms: u64,
+    /// activation count of each process, indexed by PID - 1. PIDs that are not in use are `None`.
+    pub counts: [Option<u32>; MAX_SAMPLED_PID],
+}
@@ -1,11 +1,40 @@
 #![cfg_attr(target_os = "none", no_std)]
 
 pub mod api;
+pub mod timer;
+pub use timer::{Countdown, Stopwatch};
 
 use num_traits::ToPrimitive;
 use xous::{send_message, Error, CID};
 use xous_semver::SemVer;
 
+/// A sample of how many times each process has been switched in by the kernel.
+#[derive(Debug, Clone)]
+pub struct ActivationSample {
+    /// time of the sample, in ms since boot
+    pub elapsed_ms: u64,
+    /// (PID, activation count) of each process in use at the time of the sample
+    pub counts: Vec<(u8, u32)>,
+}
+impl ActivationSample {
+    /// Returns the activations per second of each process between `earlier` and this sample, busiest
+    /// first. Processes that were started in between are counted from zero.
+    pub fn rates_since(&self, earlier: &ActivationSample) -> Vec<(u8, u32)> {
+        let interval_ms = self.elapsed_ms.saturating_sub(earlier.elapsed_ms).max(1);
+        let mut rates: Vec<(u8, u32)> = self
+            .counts
+            .iter()
+            .map(|&(pid, count)| {
+                let previous = earlier.counts.iter().find(|(p, _)| *p == pid).map_or(0, |(_, c)| *c);
+                let delta = if count >= previous { count - previous } else { count };
+                (pid, (delta as u64 * 1000 / interval_ms) as u32)
+            })
+            .collect();
+        rates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        rates
+    }
+}
+
 #[derive(Debug)]
 pub struct Ticktimer {
     conn: CID,
@@ -163,6 +192,65 @@ impl Ticktimer {
         .map(|r| r == xous::Result::Scalar1(0))
         .expect("couldn't notify condition");
     }
+
+    /// Record that `activity` happened just now. This does not block, and is cheap enough to call on
+    /// every key press; callers reporting a busier source should rate-limit themselves to about once a
+    /// second, as idle policies only work in seconds. Each kind is only taken from the first process to
+    /// report it, which is the service that sees that activity; reports from anyone else are ignored.
+    pub fn report_activity(&self, activity: api::Activity) {
+        send_message(
+            self.conn,
+            xous::Message::new_scalar(
+                api::Opcode::ReportActivity.to_usize().unwrap(),
+                activity.to_usize().unwrap(),
+                0,
+                0,
+                0,
+            ),
+        )
+        .expect("couldn't report activity");
+    }
+
+    /// Return the number of milliseconds since `activity` was last reported, or since boot if it never
+    /// was. Use this rather than keeping a private notion of "idle", so that the screen, the power
+    /// manager and anything else with an idle timeout agree on when the user was last around.
+    pub fn idle_ms(&self, activity: api::Activity) -> u64 {
+        let response = send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(
+                api::Opcode::IdleMs.to_usize().unwrap(),
+                activity.to_usize().unwrap(),
+                0,
+                0,
+                0,
+            ),
+        )
+        .expect("Ticktimer: failure to send message to Ticktimer");
+        if let xous::Result::Scalar2(lower, upper) = response {
+            lower as u64 | ((upper as u64) << 32)
+        } else {
+            panic!("Ticktimer idle_ms(): unexpected return value.");
+        }
+    }
+
+    /// Sample how many times each process has been switched in since boot. Compare two samples with
+    /// `ActivationSample::rates_since()` to find the processes that wake up the most, which are the
+    /// likely culprits for sluggishness and battery drain.
+    pub fn process_activations(&self) -> Result<ActivationSample, Error> {
+        let alloc = api::ProcessActivations { elapsed_ms: 0, counts: [None; api::MAX_SAMPLED_PID] };
+        let mut buf = xous_ipc::Buffer::into_buf(alloc).or(Err(Error::InternalError))?;
+        buf.lend_mut(self.conn, api::Opcode::GetProcessActivations.to_u32().unwrap())?;
+        let sample = buf.to_original::<api::ProcessActivations, _>().or(Err(Error::InternalError))?;
+        Ok(ActivationSample {
+            elapsed_ms: sample.elapsed_ms,
+            counts: sample
+                .counts
+                .iter()
+                .enumerate()
+                .filter_map(|(i, count)| count.map(|c| (i as u8 + 1, c)))
+                .collect(),
+        })
+    }
 }
 
 use core::sync::atomic::{AtomicU32, Ordering};
@@ -0,0 +1,382 @@
+//! Stopwatches and countdowns for apps.
+//!
+//! Both remember when they were started and measure against the system clock, rather than adding up
+//! sleeps, so they don't drift however long they run. A handle can be cloned and passed to other
+//! threads, and every clone controls the same timer. As with `Ticktimer::elapsed_ms()`, time spent in
+//! suspend is not counted.
+//!
+//! Subscriptions to countdowns are all served by one ticker thread per process, which is started by the
+//! first `Countdown::subscribe()`.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use xous::{send_message, Message, CID};
+
+/// Time accumulated over any number of start/pause cycles
+#[derive(Debug, Default)]
+struct Clock {
+    /// time counted up to the last pause
+    accumulated: Duration,
+    /// when the clock was last started, if it is running
+    started: Option<Instant>,
+}
+impl Clock {
+    fn elapsed(&self) -> Duration { self.accumulated + self.started.map_or(Duration::ZERO, |s| s.elapsed()) }
+
+    fn start(&mut self) {
+        if self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+    }
+
+    fn pause(&mut self) {
+        if let Some(started) = self.started.take() {
+            self.accumulated += started.elapsed();
+        }
+    }
+}
+
+/// Counts up from zero while it is running
+#[derive(Debug, Clone, Default)]
+pub struct Stopwatch {
+    clock: Arc<Mutex<Clock>>,
+}
+impl Stopwatch {
+    /// A stopwatch at zero, not yet running
+    pub fn new() -> Self { Self::default() }
+
+    /// Starts counting, or carries on after a `pause()`. Does nothing if it is already running.
+    pub fn start(&self) { self.lock().start() }
+
+    /// Stops counting, keeping the time so far
+    pub fn pause(&self) { self.lock().pause() }
+
+    /// Stops the stopwatch, and sets it back to zero
+    pub fn reset(&self) { *self.lock() = Clock::default() }
+
+    pub fn elapsed(&self) -> Duration { self.lock().elapsed() }
+
+    pub fn is_running(&self) -> bool { self.lock().started.is_some() }
+
+    fn lock(&self) -> MutexGuard<'_, Clock> { self.clock.lock().unwrap() }
+}
+
+#[derive(Debug)]
+struct CountdownState {
+    clock: Clock,
+    duration: Duration,
+    /// bumped by `reset()`, and when the last handle is dropped, to call off pending waits
+    generation: u32,
+    /// the number of `Countdown` handles; subscriptions don't count
+    handles: usize,
+}
+impl CountdownState {
+    fn remaining(&self) -> Duration { self.duration.saturating_sub(self.clock.elapsed()) }
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<CountdownState>,
+    /// signalled whenever the countdown is started, paused or reset
+    changed: Condvar,
+}
+
+/// Counts down from a duration while it is running, and can tell other threads or servers when it
+/// reaches zero.
+#[derive(Debug)]
+pub struct Countdown {
+    shared: Arc<Shared>,
+}
+impl Countdown {
+    /// A countdown of `duration`, not yet running
+    pub fn new(duration: Duration) -> Self {
+        Countdown {
+            shared: Arc::new(Shared {
+                state: Mutex::new(CountdownState {
+                    clock: Clock::default(),
+                    duration,
+                    generation: 0,
+                    handles: 1,
+                }),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Starts counting down, or carries on after a `pause()`. Does nothing if it is already running.
+    pub fn start(&self) { self.update(|state| state.clock.start()) }
+
+    /// Stops counting down, keeping the time that is left
+    pub fn pause(&self) { self.update(|state| state.clock.pause()) }
+
+    /// Stops the countdown and sets it to `duration`. Anything waiting on the countdown from before is
+    /// called off: `wait()` returns `false`, and subscribers aren't told.
+    pub fn reset(&self, duration: Duration) {
+        self.update(|state| {
+            state.clock = Clock::default();
+            state.duration = duration;
+            state.generation = state.generation.wrapping_add(1);
+        })
+    }
+
+    /// The time left, which is zero once the countdown has expired
+    pub fn remaining(&self) -> Duration { self.lock().remaining() }
+
+    pub fn is_expired(&self) -> bool { self.remaining().is_zero() }
+
+    pub fn is_running(&self) -> bool { self.lock().clock.started.is_some() }
+
+    /// Blocks until the countdown expires, and returns `true`; or returns `false` if it is reset first.
+    /// While the countdown is paused, this keeps waiting.
+    pub fn wait(&self) -> bool { wait_for_expiry(&self.shared) }
+
+    /// Sends a scalar message with `opcode` and `token` as its first argument to `cid` when the countdown
+    /// expires. The message is sent once, from the ticker thread, and not at all if the countdown is reset
+    /// or every handle to it is dropped before then.
+    pub fn subscribe(&self, cid: CID, opcode: u32, token: usize) {
+        self.on_expiry(move || {
+            send_message(cid, Message::new_scalar(opcode as usize, token, 0, 0, 0))
+                .map(|_| ())
+                .unwrap_or_else(|e| log::warn!("couldn't report an expired countdown: {:?}", e));
+        })
+    }
+
+    /// Has the ticker call `notify` when the countdown expires, on the terms of `subscribe()`
+    fn on_expiry(&self, notify: impl FnOnce() + Send + 'static) {
+        let generation = self.lock().generation;
+        let ticker = ticker();
+        ticker.subscriptions.lock().unwrap().push(Subscription {
+            shared: self.shared.clone(),
+            generation,
+            notify: Box::new(notify),
+        });
+        ticker.changed.notify_one();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CountdownState> { self.shared.state.lock().unwrap() }
+
+    fn update(&self, f: impl FnOnce(&mut CountdownState)) {
+        f(&mut self.lock());
+        self.shared.changed.notify_all();
+        if let Some(ticker) = TICKER.get() {
+            // taking the lock makes sure the ticker isn't between looking at the countdowns and waiting,
+            // where it would miss the notification
+            drop(ticker.subscriptions.lock().unwrap());
+            ticker.changed.notify_one();
+        }
+    }
+}
+
+impl Clone for Countdown {
+    fn clone(&self) -> Self {
+        self.lock().handles += 1;
+        Countdown { shared: self.shared.clone() }
+    }
+}
+
+impl Drop for Countdown {
+    fn drop(&mut self) {
+        self.update(|state| {
+            state.handles -= 1;
+            if state.handles == 0 {
+                // nobody is left to start a paused countdown, so waits on it would never end
+                state.generation = state.generation.wrapping_add(1);
+            }
+        })
+    }
+}
+
+/// A call to make when a countdown expires
+struct Subscription {
+    shared: Arc<Shared>,
+    /// the countdown's generation when subscribed; the subscription is called off if it changes
+    generation: u32,
+    notify: Box<dyn FnOnce() + Send>,
+}
+
+/// The subscriptions of every countdown in the process, and the thread that serves them
+struct Ticker {
+    subscriptions: Mutex<Vec<Subscription>>,
+    /// signalled whenever a subscription is added, or a countdown changes
+    changed: Condvar,
+}
+
+static TICKER: OnceLock<Ticker> = OnceLock::new();
+
+fn ticker() -> &'static Ticker {
+    let mut started = false;
+    let ticker = TICKER.get_or_init(|| {
+        started = true;
+        Ticker { subscriptions: Mutex::new(Vec::new()), changed: Condvar::new() }
+    });
+    if started {
+        std::thread::spawn(move || tick(ticker));
+    }
+    ticker
+}
+
+/// Serves the subscriptions: sleeps until the soonest running countdown is due, or until a countdown
+/// changes, and makes the calls for the ones that have expired.
+fn tick(ticker: &'static Ticker) {
+    let mut subscriptions = ticker.subscriptions.lock().unwrap();
+    loop {
+        let mut expired = Vec::new();
+        let mut soonest: Option<Duration> = None;
+        let mut i = 0;
+        while i < subscriptions.len() {
+            let state = subscriptions[i].shared.state.lock().unwrap();
+            let called_off = state.generation != subscriptions[i].generation;
+            let remaining = state.remaining();
+            let running = state.clock.started.is_some();
+            drop(state);
+            if called_off {
+                subscriptions.swap_remove(i);
+            } else if remaining.is_zero() {
+                expired.push(subscriptions.swap_remove(i).notify);
+            } else {
+                if running {
+                    soonest = Some(soonest.map_or(remaining, |s| s.min(remaining)));
+                }
+                i += 1;
+            }
+        }
+        if !expired.is_empty() {
+            // make the calls without holding the lock, so they may use countdowns themselves
+            drop(subscriptions);
+            for notify in expired {
+                notify();
+            }
+            subscriptions = ticker.subscriptions.lock().unwrap();
+            continue;
+        }
+        subscriptions = match soonest {
+            Some(timeout) => ticker.changed.wait_timeout(subscriptions, timeout).unwrap().0,
+            None => ticker.changed.wait(subscriptions).unwrap(),
+        };
+    }
+}
+
+fn wait_for_expiry(shared: &Shared) -> bool {
+    let mut state = shared.state.lock().unwrap();
+    let generation = state.generation;
+    loop {
+        if state.generation != generation {
+            return false;
+        }
+        let remaining = state.remaining();
+        if remaining.is_zero() {
+            return true;
+        }
+        state = if state.clock.started.is_some() {
+            shared.changed.wait_timeout(state, remaining).unwrap().0
+        } else {
+            shared.changed.wait(state).unwrap()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn stopwatch_keeps_time_across_pauses() {
+        let stopwatch = Stopwatch::new();
+        assert_eq!(stopwatch.elapsed(), Duration::ZERO);
+        stopwatch.start();
+        assert!(stopwatch.is_running());
+        std::thread::sleep(TICK);
+        stopwatch.pause();
+        let paused = stopwatch.elapsed();
+        assert!(paused >= TICK);
+        std::thread::sleep(TICK);
+        assert_eq!(stopwatch.elapsed(), paused);
+        // a clone runs the same stopwatch
+        stopwatch.clone().start();
+        std::thread::sleep(TICK);
+        assert!(stopwatch.elapsed() >= paused + TICK);
+        stopwatch.reset();
+        assert!(!stopwatch.is_running());
+        assert_eq!(stopwatch.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn countdown_expires() {
+        let countdown = Countdown::new(TICK);
+        assert_eq!(countdown.remaining(), TICK);
+        countdown.start();
+        assert!(countdown.wait());
+        assert!(countdown.is_expired());
+        assert_eq!(countdown.remaining(), Duration::ZERO);
+    }
+
+    #[test]
+    fn paused_countdown_keeps_its_time() {
+        let countdown = Countdown::new(TICK * 10);
+        countdown.start();
+        std::thread::sleep(TICK);
+        countdown.pause();
+        let left = countdown.remaining();
+        assert!(left <= TICK * 9);
+        std::thread::sleep(TICK);
+        assert_eq!(countdown.remaining(), left);
+        assert!(!countdown.is_expired());
+    }
+
+    #[test]
+    fn reset_calls_off_waits() {
+        let countdown = Countdown::new(Duration::from_secs(60));
+        let waiter = {
+            let countdown = countdown.clone();
+            std::thread::spawn(move || countdown.wait())
+        };
+        std::thread::sleep(TICK);
+        countdown.reset(TICK);
+        assert!(!waiter.join().unwrap());
+        // the countdown can be used again after the reset
+        countdown.start();
+        assert!(countdown.wait());
+    }
+
+    #[test]
+    fn dropping_every_handle_calls_off_waits() {
+        let countdown = Countdown::new(Duration::from_secs(60));
+        let shared = countdown.shared.clone();
+        let waiter = std::thread::spawn(move || wait_for_expiry(&shared));
+        let other = countdown.clone();
+        drop(countdown);
+        std::thread::sleep(TICK);
+        drop(other);
+        assert!(!waiter.join().unwrap());
+    }
+
+    #[test]
+    fn subscriptions_are_told_once_in_order() {
+        let (tx, rx) = channel();
+        let later = Countdown::new(TICK * 4);
+        let sooner = Countdown::new(TICK * 2);
+        let called_off = Countdown::new(TICK);
+        let paused = Countdown::new(TICK);
+        for (countdown, name) in
+            [(&later, "later"), (&sooner, "sooner"), (&called_off, "off"), (&paused, "paused")]
+        {
+            let tx = tx.clone();
+            countdown.on_expiry(move || tx.send(name).unwrap());
+            countdown.start();
+        }
+        paused.pause();
+        called_off.reset(TICK);
+        called_off.start();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("sooner"));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("later"));
+        assert!(rx.recv_timeout(TICK * 4).is_err());
+        // the paused one is told once it has run out
+        paused.start();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("paused"));
+    }
+}
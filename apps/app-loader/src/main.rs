@@ -6,6 +6,7 @@ const SERVER_NAME_APP_LOADER: &str = "_App Loader_";
 
 fn main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
@@ -37,6 +37,7 @@ pub(crate) enum PumpOp {
 
 fn main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
@@ -110,6 +110,7 @@ impl Hello {
 
 fn main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("Hello world PID is {}", xous::process::id());
 
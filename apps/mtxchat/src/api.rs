@@ -20,7 +20,7 @@ pub enum MenuOp {
     Login,
     Logout,
     Noop,
-    Room,
+    Conversation,
 }
 
 #[allow(dead_code)]
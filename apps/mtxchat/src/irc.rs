@@ -0,0 +1,436 @@
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chat::{Chat, Transport};
+use locales::t;
+use modals::Modals;
+use pddb::Pddb;
+use xous::CID;
+
+use crate::{now_ms, split_lines, wifi, MTXCHAT_DIALOGUE};
+
+/// PDDB Dict for irc keys
+const IRC_STATE: &str = "mtxchat.irc";
+
+const CHANNEL_KEY: &str = "channel";
+const NICK_KEY: &str = "nick";
+const PASSWORD_KEY: &str = "password";
+const SERVER_KEY: &str = "server";
+
+/// IRC over TLS
+const IRC_PORT: u16 = 6697;
+/// how long to wait for the server to welcome us
+const IRC_REGISTER_TIMEOUT_MS: u64 = 30_000;
+/// how long a read holds the stream before letting a writer in
+const IRC_POLL_MS: u64 = 500;
+/// leaves room for the command and the prefix the server adds when relaying, within the 512 byte limit
+const IRC_TEXT_MAX: usize = 400;
+
+trait Stream: Read + Write + Send {}
+impl<T: Read + Write + Send> Stream for T {}
+
+type SharedStream = Arc<Mutex<Box<dyn Stream>>>;
+
+/// A received IRC message: `[:prefix] COMMAND [params] [:trailing]`
+#[derive(Debug, PartialEq)]
+struct Message<'a> {
+    prefix: Option<&'a str>,
+    command: &'a str,
+    params: Vec<&'a str>,
+}
+
+impl<'a> Message<'a> {
+    fn parse(line: &'a str) -> Option<Message<'a>> {
+        let mut rest = line.trim_end_matches(|c| c == '\r' || c == '\n');
+        let prefix = match rest.strip_prefix(':') {
+            Some(tail) => {
+                let (prefix, tail) = tail.split_once(' ')?;
+                rest = tail;
+                Some(prefix)
+            }
+            None => None,
+        };
+        let (head, trailing) = match rest.split_once(" :") {
+            Some((head, trailing)) => (head, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut words = head.split(' ').filter(|w| !w.is_empty());
+        let command = words.next()?;
+        let mut params: Vec<&str> = words.collect();
+        params.extend(trailing);
+        Some(Message { prefix, command, params })
+    }
+
+    /// The nick of the sender, from a `nick!user@host` prefix
+    fn nick(&self) -> Option<&'a str> { self.prefix.map(|p| p.split('!').next().unwrap_or(p)) }
+}
+
+/// A `#channel@server` Dialogue key
+fn conversation_key(channel: &str, server: &str) -> String { format!("{}@{}", channel, server) }
+
+fn send(stream: &SharedStream, line: &str) -> bool {
+    let mut stream = stream.lock().unwrap();
+    match stream.write_all(format!("{}\r\n", line).as_bytes()).and_then(|_| stream.flush()) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("irc write failed: {:?}", e);
+            false
+        }
+    }
+}
+
+/// Read whatever has arrived, appending complete lines to `lines`.
+///
+/// Returns false if the connection has closed.
+fn receive(stream: &SharedStream, pending: &mut Vec<u8>, lines: &mut Vec<String>) -> bool {
+    let mut buf = [0u8; 1024];
+    let read = stream.lock().unwrap().read(&mut buf);
+    match read {
+        Ok(0) => return false,
+        Ok(len) => pending.extend_from_slice(&buf[..len]),
+        Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+            // give a writer the chance to take the stream
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        Err(e) => {
+            log::warn!("irc read failed: {:?}", e);
+            return false;
+        }
+    }
+    while let Some(i) = pending.iter().position(|&b| b == b'\n') {
+        let line: Vec<u8> = pending.drain(..=i).collect();
+        lines.push(String::from_utf8_lossy(&line).into_owned());
+    }
+    true
+}
+
+/// Relay the messages on `channel` to the Chat UI until `listening` is cleared or the connection drops
+fn listen(stream: SharedStream, channel: String, listening: Arc<AtomicBool>, chat_cid: CID) {
+    let mut pending = Vec::new();
+    while listening.load(Ordering::SeqCst) {
+        let mut lines = Vec::new();
+        if !receive(&stream, &mut pending, &mut lines) {
+            if listening.load(Ordering::SeqCst) {
+                log::warn!("irc connection closed");
+                chat::cf_set_status_text(chat_cid, t!("mtxchat.irc.closed", locales::LANG));
+            }
+            break;
+        }
+        let mut posts = 0;
+        for line in lines.iter() {
+            match Message::parse(line) {
+                Some(msg) if msg.command == "PING" => {
+                    send(&stream, &format!("PONG :{}", msg.params.first().unwrap_or(&"")));
+                }
+                Some(msg) if msg.command == "PRIVMSG" && msg.params.len() == 2 => {
+                    if msg.params[0].eq_ignore_ascii_case(&channel) {
+                        let text = match msg.params[1].strip_prefix("\u{1}ACTION ") {
+                            Some(action) => format!("* {}", action.trim_end_matches('\u{1}')),
+                            None => msg.params[1].to_string(),
+                        };
+                        chat::cf_post_add(chat_cid, msg.nick().unwrap_or("anon"), now_ms(), &text);
+                        posts += 1;
+                    }
+                }
+                Some(msg) => log::debug!("irc {:?}", msg),
+                None => (),
+            }
+        }
+        if posts > 0 {
+            chat::cf_dialogue_save(chat_cid);
+        }
+    }
+    listening.store(false, Ordering::SeqCst);
+}
+
+pub struct Irc<'a> {
+    chat: &'a Chat,
+    modals: Modals,
+    pddb: Pddb,
+    server: Option<String>,
+    nick: Option<String>,
+    channel: Option<String>,
+    stream: Option<SharedStream>,
+    listening: Arc<AtomicBool>,
+}
+
+impl<'a> Irc<'a> {
+    pub fn new(chat: &'a Chat) -> Irc<'a> {
+        let xns = xous_names::XousNames::new().unwrap();
+        let modals = Modals::new(&xns).expect("can't connect to Modals server");
+        Irc {
+            chat,
+            modals,
+            pddb: Pddb::new(),
+            server: None,
+            nick: None,
+            channel: None,
+            stream: None,
+            listening: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        let mut value = Vec::new();
+        match self.pddb.get(IRC_STATE, key, None, false, false, None, None::<fn()>) {
+            Ok(mut pddb_key) => pddb_key.read_to_end(&mut value).ok()?,
+            Err(_) => return None,
+        };
+        String::from_utf8(value).ok().filter(|v| !v.is_empty())
+    }
+
+    fn set(&self, key: &str, value: &str) {
+        // delete key first to ensure data in a prior longer key is gone
+        self.pddb.delete_key(IRC_STATE, key, None).ok();
+        match self.pddb.get(IRC_STATE, key, None, true, true, None, None::<fn()>) {
+            Ok(mut pddb_key) => match pddb_key.write_all(value.as_bytes()) {
+                Ok(()) => {
+                    self.pddb.sync().ok();
+                }
+                Err(e) => log::warn!("Error writing {}:{} {:?}", IRC_STATE, key, e),
+            },
+            Err(e) => log::warn!("failed to set pddb {}:{}  {:?}", IRC_STATE, key, e),
+        }
+    }
+
+    pub fn login_modal(&mut self) {
+        const HIDE: &str = "*****";
+        let mut builder = self.modals.alert_builder(t!("mtxchat.irc.login.title", locales::LANG));
+        let builder = match self.get(NICK_KEY) {
+            Some(nick) => builder.field_placeholder_persist(Some(nick), None),
+            None => builder.field(Some(t!("mtxchat.irc.nick", locales::LANG).to_string()), None),
+        };
+        let builder = match self.get(SERVER_KEY) {
+            Some(server) => builder.field_placeholder_persist(Some(server), None),
+            None => builder.field(Some(t!("mtxchat.irc.server", locales::LANG).to_string()), None),
+        };
+        let builder = match self.get(PASSWORD_KEY) {
+            Some(_pwd) => builder.field_placeholder_persist(Some(HIDE.to_string()), None),
+            None => builder.field(Some(t!("mtxchat.irc.password", locales::LANG).to_string()), None),
+        };
+        if let Ok(payloads) = builder.build() {
+            if let Ok(content) = payloads.content()[0].content.as_str() {
+                self.set(NICK_KEY, content.trim());
+            }
+            if let Ok(content) = payloads.content()[1].content.as_str() {
+                self.set(SERVER_KEY, content.trim());
+            }
+            if let Ok(content) = payloads.content()[2].content.as_str() {
+                if content.ne(HIDE) {
+                    self.set(PASSWORD_KEY, content);
+                }
+            }
+        }
+        self.nick = self.get(NICK_KEY);
+        self.server = self.get(SERVER_KEY);
+    }
+
+    fn channel_modal(&mut self) -> Option<String> {
+        let mut builder = self.modals.alert_builder(t!("mtxchat.irc.channel.title", locales::LANG));
+        let builder = match self.get(CHANNEL_KEY) {
+            Some(channel) => builder.field_placeholder_persist(Some(channel), None),
+            None => builder.field(Some(t!("mtxchat.irc.channel", locales::LANG).to_string()), None),
+        };
+        let payloads = builder.build().ok()?;
+        let channel = payloads.first().content.as_str().ok()?.trim().to_string();
+        if channel.is_empty() {
+            return None;
+        }
+        let channel = if channel.starts_with('#') { channel } else { format!("#{}", channel) };
+        self.set(CHANNEL_KEY, &channel);
+        Some(channel)
+    }
+
+    /// Open a TLS connection to `server` and register our nick
+    ///
+    /// Returns the reason for a failure, to be shown to the user
+    fn register(&self, server: &str, nick: &str) -> Result<SharedStream, String> {
+        let tls = tls::Tls::new();
+        if !tls.accessible(server, true) {
+            log::warn!("no trusted certificate for {}", server);
+            return Err(tls::diag::Failure::Untrusted.to_string());
+        }
+        let stream = match tls.connect(server, IRC_PORT) {
+            (Ok(stream), _) => stream,
+            (Err(_), diag) => return Err(diag.to_string()),
+        };
+        stream.sock.set_read_timeout(Some(Duration::from_millis(IRC_POLL_MS))).map_err(|e| e.to_string())?;
+        let stream: SharedStream = Arc::new(Mutex::new(Box::new(stream)));
+        if let Some(password) = self.get(PASSWORD_KEY) {
+            send(&stream, &format!("PASS {}", password));
+        }
+        send(&stream, &format!("NICK {}", nick));
+        if !send(&stream, &format!("USER {} 0 * :{}", nick, nick)) {
+            return Err(String::new());
+        }
+
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        let start = tt.elapsed_ms();
+        let mut pending = Vec::new();
+        while tt.elapsed_ms() - start < IRC_REGISTER_TIMEOUT_MS {
+            let mut lines = Vec::new();
+            if !receive(&stream, &mut pending, &mut lines) {
+                return Err(String::new());
+            }
+            for line in lines.iter() {
+                match Message::parse(line) {
+                    Some(msg) if msg.command == "PING" => {
+                        send(&stream, &format!("PONG :{}", msg.params.first().unwrap_or(&"")));
+                    }
+                    // RPL_WELCOME
+                    Some(msg) if msg.command == "001" => return Ok(stream),
+                    // ERR_NICKNAMEINUSE, ERR_PASSWDMISMATCH, ERR_YOUREBANNEDCREEP
+                    Some(msg) if ["433", "464", "465", "ERROR"].contains(&msg.command) => {
+                        log::warn!("irc registration refused: {}", line.trim_end());
+                        return Err(msg.params.last().unwrap_or(&"").to_string());
+                    }
+                    _ => (),
+                }
+            }
+        }
+        log::warn!("irc registration timed out");
+        Err(t!("mtxchat.irc.timeout", locales::LANG).to_string())
+    }
+
+    fn link(&mut self, server: &str) -> bool {
+        if self.stream.is_some() && self.server.as_deref() == Some(server) {
+            return true;
+        }
+        self.quit();
+        let nick = match &self.nick {
+            Some(nick) => nick.clone(),
+            None => return false,
+        };
+        self.chat.set_status_text(t!("mtxchat.busy.connecting", locales::LANG));
+        self.chat.set_busy_state(true);
+        let registered = self.register(server, &nick);
+        self.chat.set_busy_state(false);
+        match registered {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.server = Some(server.to_string());
+                true
+            }
+            Err(reason) => {
+                self.stream = None;
+                self.modals
+                    .show_notification(
+                        format!("{}\n{}", t!("mtxchat.irc.failed", locales::LANG), reason).trim_end(),
+                        None,
+                    )
+                    .expect("notification failed");
+                false
+            }
+        }
+    }
+
+    fn quit(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            send(&stream, "QUIT");
+        }
+    }
+}
+
+impl<'a> Transport for Irc<'a> {
+    fn name(&self) -> &'static str { "irc" }
+
+    fn connect(&mut self) -> bool {
+        if !wifi(self.chat, &self.modals) {
+            return false;
+        }
+        self.nick = self.get(NICK_KEY);
+        self.server = self.get(SERVER_KEY);
+        if self.nick.is_none() || self.server.is_none() {
+            self.login_modal();
+        }
+        match self.server.clone() {
+            Some(server) => self.link(&server),
+            None => false,
+        }
+    }
+
+    fn logout(&mut self) {
+        self.quit();
+        self.pddb.delete_key(IRC_STATE, PASSWORD_KEY, None).ok();
+        self.login_modal();
+    }
+
+    fn join(&mut self, conversation: Option<&str>) -> Option<String> {
+        let channel = match conversation.and_then(|key| key.split_once('@')) {
+            Some((channel, server)) => {
+                if !self.link(server) {
+                    return None;
+                }
+                channel.to_string()
+            }
+            None => self.channel_modal()?,
+        };
+        let server = self.server.clone()?;
+        let stream = self.stream.clone()?;
+        if !send(&stream, &format!("JOIN {}", channel)) {
+            self.stream = None;
+            return None;
+        }
+        let key = conversation_key(&channel, &server);
+        self.chat.dialogue_set(MTXCHAT_DIALOGUE, Some(&key)).expect("failed to set dialogue");
+        self.channel = Some(channel.clone());
+        self.listening.store(true, Ordering::SeqCst);
+        std::thread::spawn({
+            let listening = self.listening.clone();
+            let chat_cid = self.chat.cid();
+            move || listen(stream, channel, listening, chat_cid)
+        });
+        Some(key)
+    }
+
+    fn leave(&mut self) {
+        self.listening.store(false, Ordering::SeqCst);
+        if let (Some(stream), Some(channel)) = (&self.stream, self.channel.take()) {
+            send(stream, &format!("PART {}", channel));
+        }
+        self.quit();
+    }
+
+    fn post(&mut self, text: &str) -> bool {
+        let (stream, channel, nick) = match (&self.stream, &self.channel, &self.nick) {
+            (Some(stream), Some(channel), Some(nick)) => (stream, channel, nick),
+            _ => return false,
+        };
+        self.chat.set_status_text(t!("mtxchat.busy.sending", locales::LANG));
+        self.chat.set_busy_state(true);
+        let sent = split_lines(text, IRC_TEXT_MAX)
+            .iter()
+            .all(|line| send(stream, &format!("PRIVMSG {} :{}", channel, line)));
+        if sent {
+            // IRC servers don't echo our own messages back to us
+            self.chat.post_add(nick, now_ms(), text, None).expect("failed to add post");
+            chat::cf_dialogue_save(self.chat.cid());
+        }
+        self.chat.set_busy_state(false);
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_privmsg() {
+        let msg = Message::parse(":alice!a@example.com PRIVMSG #xous :hello: world\r\n").unwrap();
+        assert_eq!(msg.nick(), Some("alice"));
+        assert_eq!(msg.command, "PRIVMSG");
+        assert_eq!(msg.params, vec!["#xous", "hello: world"]);
+    }
+
+    #[test]
+    fn parses_without_prefix() {
+        let msg = Message::parse("PING :irc.example.com\r\n").unwrap();
+        assert_eq!(msg, Message { prefix: None, command: "PING", params: vec!["irc.example.com"] });
+        let msg = Message::parse(":irc.example.com 001 me :Welcome").unwrap();
+        assert_eq!(msg.command, "001");
+        assert_eq!(msg.params, vec!["me", "Welcome"]);
+    }
+}
@@ -1,5 +1,7 @@
 pub mod api;
+mod irc;
 mod listen;
+mod lora;
 mod web;
 
 use std::fmt::Write as _;
@@ -8,12 +10,13 @@ use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub use api::*;
-use chat::Chat;
+use chat::{Chat, Transport};
+pub use irc::Irc;
 use listen::listen;
 use locales::t;
+pub use lora::Lora;
 use modals::Modals;
 use pddb::Pddb;
-use ticktimer_server::Ticktimer;
 use tls::xtls::TlsConnector;
 use trng::*;
 use ureq::Agent;
@@ -23,7 +26,10 @@ use crate::web::get_username;
 
 /// PDDB Dict for mtxchat keys
 const MTXCHAT_STATE: &str = "mtxchat.state";
-const MTXCHAT_DIALOGUE: &str = "mtxchat.dialogue";
+/// PDDB Dict for the Dialogues of every transport
+pub(crate) const MTXCHAT_DIALOGUE: &str = "mtxchat.dialogue";
+/// PDDB Dict for the transport bound to each Dialogue
+pub const MTXCHAT_TRANSPORT: &str = "mtxchat.transport";
 
 const FILTER_KEY: &str = "_filter";
 const PASSWORD_KEY: &str = "password";
@@ -71,7 +77,6 @@ pub struct MtxChat<'a> {
     chat: &'a Chat,
     trng: Trng,
     pddb: Pddb,
-    netmgr: net::NetManager,
     user_id: Option<String>,
     user_name: Option<String>,
     user_domain: Option<String>,
@@ -87,7 +92,6 @@ pub struct MtxChat<'a> {
     modals: Modals,
     new_username: bool,
     new_room: bool,
-    tt: Ticktimer,
 }
 impl<'a> MtxChat<'a> {
     pub fn new(chat: &Chat) -> MtxChat {
@@ -102,7 +106,6 @@ impl<'a> MtxChat<'a> {
             chat,
             trng,
             pddb,
-            netmgr: net::NetManager::new(),
             user_id: None,
             user_name: None,
             user_domain: Some(DOMAIN_MATRIX.to_string()),
@@ -118,7 +121,6 @@ impl<'a> MtxChat<'a> {
             modals,
             new_username: false,
             new_room: false,
-            tt: ticktimer_server::Ticktimer::new().unwrap(),
         }
     }
 
@@ -233,42 +235,6 @@ impl<'a> MtxChat<'a> {
         Ok(value)
     }
 
-    pub fn connect(&mut self) -> bool {
-        log::info!("Attempting connect to Matrix server");
-        if self.wifi() {
-            if self.login() {
-                if let Some(_room_id) = self.get_room_id() {
-                    self.dialogue_set(self.room_alias().as_deref());
-                    self.listen();
-                    if self.new_room {
-                        self.new_room = false;
-                        self.chat.set_status_text(t!("mtxchat.busy.new_listen", locales::LANG));
-                        self.chat.set_busy_state(true);
-                    }
-                    return true;
-                } else {
-                    self.modals
-                        .show_notification(t!("mtxchat.roomid.failed", locales::LANG), None)
-                        .expect("notification failed");
-                }
-            } else {
-                self.modals
-                    .show_notification(t!("mtxchat.login.failed", locales::LANG), None)
-                    .expect("notification failed");
-            }
-            if self.new_username {
-                self.new_username = false;
-                self.help();
-            }
-        } else {
-            self.modals
-                .show_notification(t!("mtxchat.wifi.warning", locales::LANG), None)
-                .expect("notification failed");
-        }
-        self.dialogue_set(None);
-        false
-    }
-
     pub fn login(&mut self) -> bool {
         self.chat.set_status_text(t!("mtxchat.busy.login", locales::LANG));
         self.chat.set_busy_state(true);
@@ -402,6 +368,11 @@ impl<'a> MtxChat<'a> {
 
     pub fn get_room_id(&mut self) -> Option<String> {
         self.room_modal();
+        self.room_id()
+    }
+
+    /// Look up the room id of the current room alias, without prompting
+    fn room_id(&mut self) -> Option<String> {
         let log_entry = match (self.logged_in, &self.token, &self.user_domain, &self.room_alias()) {
             (true, Some(token), Some(user_domain), Some(room_alias)) => {
                 let mut url = Url::parse("https://matrix.org").unwrap();
@@ -555,8 +526,9 @@ impl<'a> MtxChat<'a> {
         txn_id.to_string()
     }
 
-    pub fn post(&mut self, text: &str) {
+    pub fn post(&mut self, text: &str) -> bool {
         let txn_id = self.gen_txn_id();
+        let mut success = false;
         let log_entry = match (self.logged_in, &self.token, &self.user_domain, &self.room_id) {
             (true, Some(token), Some(user_domain), Some(room_id)) => {
                 self.chat.set_status_text(t!("mtxchat.busy.sending", locales::LANG));
@@ -564,7 +536,6 @@ impl<'a> MtxChat<'a> {
                 log::info!("txn_id = {}", txn_id);
                 let mut url = Url::parse("https://matrix.org").unwrap();
                 url.set_host(Some(user_domain)).expect("failed to set host");
-                let mut success = false;
                 for _ in 0..SEND_RETRIES {
                     if web::send_message(&mut url, &room_id, &text, &txn_id, token, &mut self.agent) {
                         success = true;
@@ -581,56 +552,152 @@ impl<'a> MtxChat<'a> {
             (_, _, _, None) => "No room id set",
         };
         log::info!("{log_entry}");
+        success
     }
 
     // returns true is wifi is connected
-    //
-    // If wifi is not connected then a modal offers to "Connect to wifi?"
-    // and tries for 10 seconds before representing.
-    //
-    pub fn wifi(&self) -> bool {
-        if HOSTED_MODE {
+    pub fn wifi(&self) -> bool { wifi(self.chat, &self.modals) }
+}
+
+impl<'a> Transport for MtxChat<'a> {
+    fn name(&self) -> &'static str { "matrix" }
+
+    fn connect(&mut self) -> bool {
+        log::info!("Attempting connect to Matrix server");
+        if !self.wifi() {
+            self.modals
+                .show_notification(t!("mtxchat.wifi.warning", locales::LANG), None)
+                .expect("notification failed");
+            return false;
+        }
+        if self.login() {
             return true;
         }
+        self.modals
+            .show_notification(t!("mtxchat.login.failed", locales::LANG), None)
+            .expect("notification failed");
+        if self.new_username {
+            self.new_username = false;
+            self.help();
+        }
+        self.dialogue_set(None);
+        false
+    }
 
-        if let Some(conf) = self.netmgr.get_ipv4_config() {
-            if conf.dhcp == com_rs::DhcpState::Bound {
-                return true;
+    fn logout(&mut self) { MtxChat::logout(self); }
+
+    fn join(&mut self, conversation: Option<&str>) -> Option<String> {
+        let room_id = match conversation.and_then(|alias| alias.strip_prefix('#')?.split_once(':')) {
+            Some((room_name, room_domain)) => {
+                let old_room = self.get(ROOM_NAME_KEY).unwrap_or(None);
+                let old_domain = self.get(ROOM_DOMAIN_KEY).unwrap_or(None);
+                if old_room.as_deref() != Some(room_name) || old_domain.as_deref() != Some(room_domain) {
+                    self.unset_debug(ROOM_ID_KEY);
+                    self.unset_debug(SINCE_KEY);
+                    self.unset_debug(FILTER_KEY);
+                }
+                // (re-)set the room even if unchanged, as this also fills the cached values
+                self.set_debug(ROOM_NAME_KEY, room_name);
+                self.set_debug(ROOM_DOMAIN_KEY, room_domain);
+                self.room_id()
             }
+            None => self.get_room_id(),
+        };
+        if room_id.is_none() {
+            self.modals
+                .show_notification(t!("mtxchat.roomid.failed", locales::LANG), None)
+                .expect("notification failed");
+            self.dialogue_set(None);
+            return None;
         }
-
-        while self.wifi_try_modal() {
-            self.netmgr.connection_manager_wifi_on_and_run().unwrap();
-            self.chat.set_status_text(t!("mtxchat.busy.connecting", locales::LANG));
+        let room_alias = self.room_alias();
+        self.dialogue_set(room_alias.as_deref());
+        self.listen();
+        if self.new_room {
+            self.new_room = false;
+            self.chat.set_status_text(t!("mtxchat.busy.new_listen", locales::LANG));
             self.chat.set_busy_state(true);
-            let start = self.tt.elapsed_ms();
-            while self.tt.elapsed_ms() - start < WIFI_TIMEOUT_MS as u64 {
-                if let Some(conf) = self.netmgr.get_ipv4_config() {
-                    if conf.dhcp == com_rs::DhcpState::Bound {
-                        self.chat.set_busy_state(false);
-                        return true;
-                    }
+        }
+        room_alias
+    }
+
+    fn leave(&mut self) { self.listen_over(""); }
+
+    fn post(&mut self, text: &str) -> bool { MtxChat::post(self, text) }
+}
+
+// returns true is wifi is connected
+//
+// If wifi is not connected then a modal offers to "Connect to wifi?"
+// and tries for 10 seconds before representing.
+//
+pub(crate) fn wifi(chat: &Chat, modals: &Modals) -> bool {
+    if HOSTED_MODE {
+        return true;
+    }
+
+    let netmgr = net::NetManager::new();
+    if let Some(conf) = netmgr.get_ipv4_config() {
+        if conf.dhcp == com_rs::DhcpState::Bound {
+            return true;
+        }
+    }
+
+    let tt = ticktimer_server::Ticktimer::new().unwrap();
+    while wifi_try_modal(modals) {
+        netmgr.connection_manager_wifi_on_and_run().unwrap();
+        chat.set_status_text(t!("mtxchat.busy.connecting", locales::LANG));
+        chat.set_busy_state(true);
+        let start = tt.elapsed_ms();
+        while tt.elapsed_ms() - start < WIFI_TIMEOUT_MS as u64 {
+            if let Some(conf) = netmgr.get_ipv4_config() {
+                if conf.dhcp == com_rs::DhcpState::Bound {
+                    chat.set_busy_state(false);
+                    return true;
                 }
-                self.tt.sleep_ms(1000).unwrap();
             }
-            self.modals.show_notification(t!("mtxchat.wifi.warning", locales::LANG), None).unwrap();
+            tt.sleep_ms(1000).unwrap();
         }
-        self.chat.set_busy_state(false);
-        self.chat.set_status_text(t!("mtxchat.wifi.warning_status", locales::LANG));
-        false
+        modals.show_notification(t!("mtxchat.wifi.warning", locales::LANG), None).unwrap();
     }
+    chat.set_busy_state(false);
+    chat.set_status_text(t!("mtxchat.wifi.warning_status", locales::LANG));
+    false
+}
+
+// returns true if "Connect to WiFi?" yes option is chosen
+//
+fn wifi_try_modal(modals: &Modals) -> bool {
+    modals.add_list_item("yes").expect("failed radio yes");
+    modals.add_list_item("no").expect("failed radio no");
+    modals.get_radiobutton("Connect to WiFi?").expect("failed radiobutton modal");
+    match modals.get_radio_index() {
+        Ok(button) => button == 0,
+        _ => false,
+    }
+}
 
-    // returns true if "Connect to WiFi?" yes option is chosen
-    //
-    fn wifi_try_modal(&self) -> bool {
-        self.modals.add_list_item("yes").expect("failed radio yes");
-        self.modals.add_list_item("no").expect("failed radio no");
-        self.modals.get_radiobutton("Connect to WiFi?").expect("failed radiobutton modal");
-        match self.modals.get_radio_index() {
-            Ok(button) => button == 0,
-            _ => false,
+/// The current time in ms, as used for the timestamp of a Post
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis() as u64
+}
+
+/// Split `text` into non-blank lines of at most `max` bytes, for protocols with a short frame
+pub(crate) fn split_lines(text: &str, max: usize) -> Vec<&str> {
+    let mut lines = Vec::new();
+    for mut line in text.lines().filter(|l| !l.trim().is_empty()) {
+        while line.len() > max {
+            let mut i = max;
+            while !line.is_char_boundary(i) {
+                i -= 1;
+            }
+            let (head, tail) = line.split_at(i);
+            lines.push(head);
+            line = tail;
         }
+        lines.push(line);
     }
+    lines
 }
 
 pub(crate) fn heap_usage() -> usize {
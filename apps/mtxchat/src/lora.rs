@@ -0,0 +1,417 @@
+//! A LoRa radio bridge attached to the USB serial port.
+//!
+//! The bridge relays text frames between the serial port and the radio, one per line, in both
+//! directions:
+//!
+//! ```text
+//! MSG <channel> <sealed>
+//! ```
+//!
+//! `channel` may not contain spaces. Any other line (ie console or log output) is ignored. `sealed` is
+//! the base64 of a random 12-byte nonce followed by the AES-GCM-SIV ciphertext of
+//! `<stamp> <sender> <text>`, with the channel name as associated data. The key is derived from a
+//! passphrase shared out of band by everyone on the channel, so neither the bridge nor anyone else
+//! listening to the radio can read or forge Posts. `stamp` is the sender's clock in ms: frames that
+//! are not newer than the last one from the same sender, or that are too far from our own clock, are
+//! dropped as replays. Everyone holding the passphrase can still post under any `sender`.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use aes_gcm_siv::aead::{Aead, KeyInit, Payload};
+use aes_gcm_siv::{Aes256GcmSiv, Nonce};
+use base64::Engine;
+use chat::{Chat, Transport};
+use gam::{TextEntryPayload, ValidatorErr};
+use hkdf::Hkdf;
+use locales::t;
+use modals::Modals;
+use pddb::Pddb;
+use sha2::Sha256;
+use trng::Trng;
+use usb_device_xous::{UsbDeviceType, UsbHid};
+use xous::CID;
+
+use crate::{now_ms, split_lines, MTXCHAT_DIALOGUE};
+
+/// PDDB Dict for lora keys
+const LORA_STATE: &str = "mtxchat.lora";
+
+const CHANNEL_KEY: &str = "channel";
+const HANDLE_KEY: &str = "handle";
+/// the passphrase of a channel is kept under this prefix, followed by the channel name
+const PASSPHRASE_KEY: &str = "passphrase.";
+
+const FRAME_TAG: &str = "MSG";
+/// a typical LoRa payload is at most 255 bytes, including the frame header, nonce, tag and base64
+const LORA_TEXT_MAX: usize = 120;
+/// the Dialogue key of a LoRa channel is prefixed to keep it apart from those of the other transports
+const KEY_PREFIX: &str = "lora:";
+/// anyone who records the radio can attempt to guess the passphrase offline, so it must be long
+const PASSPHRASE_MIN_LEN: usize = 16;
+const HKDF_SALT: &[u8] = b"xous mtxchat lora v1";
+const NONCE_LEN: usize = 12;
+/// how far a frame's stamp may be from our own clock, to allow for the skew between devices
+const FRESHNESS_MS: u64 = 10 * 60 * 1000;
+
+const BASE64: base64::engine::GeneralPurpose = base64::engine::general_purpose::STANDARD_NO_PAD;
+
+/// A received `MSG` frame, still sealed
+#[derive(Debug, PartialEq)]
+struct Frame<'a> {
+    channel: &'a str,
+    sealed: &'a str,
+}
+
+impl<'a> Frame<'a> {
+    fn parse(line: &'a str) -> Option<Frame<'a>> {
+        let mut fields = line.trim_end_matches(|c| c == '\r' || c == '\n').splitn(3, ' ');
+        if fields.next()? != FRAME_TAG {
+            return None;
+        }
+        let channel = fields.next().filter(|c| !c.is_empty())?;
+        let sealed = fields.next().filter(|s| !s.is_empty())?;
+        Some(Frame { channel, sealed })
+    }
+}
+
+/// An opened `MSG` frame
+#[derive(Debug, PartialEq)]
+struct Post {
+    stamp: u64,
+    sender: String,
+    text: String,
+}
+
+/// Seals and opens the frames of one channel
+struct Sealer {
+    channel: String,
+    cipher: Aes256GcmSiv,
+}
+
+impl Sealer {
+    fn new(channel: &str, passphrase: &str) -> Sealer {
+        let hk = Hkdf::<Sha256>::new(Some(HKDF_SALT), passphrase.as_bytes());
+        let mut key = [0u8; 32];
+        hk.expand(channel.as_bytes(), &mut key).expect("32 bytes is a valid hkdf length");
+        Sealer { channel: channel.to_string(), cipher: Aes256GcmSiv::new_from_slice(&key).unwrap() }
+    }
+
+    /// The frame carrying `text` from `sender`, without the line ending
+    fn seal(&self, nonce: [u8; NONCE_LEN], stamp: u64, sender: &str, text: &str) -> Option<String> {
+        let plaintext = format!("{} {} {}", stamp, sender, text);
+        let payload = Payload { msg: plaintext.as_bytes(), aad: self.channel.as_bytes() };
+        let mut sealed = nonce.to_vec();
+        sealed.extend(self.cipher.encrypt(Nonce::from_slice(&nonce), payload).ok()?);
+        Some(format!("{} {} {}", FRAME_TAG, self.channel, BASE64.encode(sealed)))
+    }
+
+    /// The Post in `frame`, if it is on our channel and was sealed with our key
+    fn open(&self, frame: &Frame) -> Option<Post> {
+        if frame.channel != self.channel {
+            return None;
+        }
+        let sealed = BASE64.decode(frame.sealed).ok()?;
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let payload = Payload { msg: ciphertext, aad: self.channel.as_bytes() };
+        let plaintext =
+            String::from_utf8(self.cipher.decrypt(Nonce::from_slice(nonce), payload).ok()?).ok()?;
+        let mut fields = plaintext.splitn(3, ' ');
+        let stamp = fields.next()?.parse().ok()?;
+        let sender = fields.next().filter(|s| !s.is_empty())?.to_string();
+        let text = fields.next()?.to_string();
+        Some(Post { stamp, sender, text })
+    }
+}
+
+/// Drops frames that were seen before, or that are too old to tell
+#[derive(Default)]
+struct Replays {
+    last: HashMap<String, u64>,
+}
+
+impl Replays {
+    fn accept(&mut self, post: &Post, now: u64) -> bool {
+        if post.stamp.abs_diff(now) > FRESHNESS_MS {
+            return false;
+        }
+        match self.last.get(&post.sender) {
+            Some(&last) if post.stamp <= last => false,
+            _ => {
+                self.last.insert(post.sender.clone(), post.stamp);
+                true
+            }
+        }
+    }
+}
+
+/// Relay the Posts sealed for `sealer` to the Chat UI until `listening` is cleared
+fn listen(sealer: Sealer, listening: Arc<AtomicBool>, chat_cid: CID) {
+    let usb = UsbHid::new();
+    let mut replays = Replays::default();
+    while listening.load(Ordering::SeqCst) {
+        let line = usb.serial_wait_ascii(Some('\n'));
+        if !listening.load(Ordering::SeqCst) {
+            break;
+        }
+        match Frame::parse(&line).and_then(|frame| sealer.open(&frame)) {
+            Some(post) if replays.accept(&post, now_ms()) => {
+                chat::cf_post_add(chat_cid, &post.sender, post.stamp, &post.text);
+                chat::cf_dialogue_save(chat_cid);
+            }
+            Some(post) => log::warn!("lora dropped a replayed frame from {}", post.sender),
+            None => log::debug!("lora ignored: {}", line.trim_end()),
+        }
+    }
+}
+
+pub struct Lora<'a> {
+    chat: &'a Chat,
+    modals: Modals,
+    pddb: Pddb,
+    usb: UsbHid,
+    trng: Trng,
+    handle: Option<String>,
+    sealer: Option<Sealer>,
+    listening: Arc<AtomicBool>,
+}
+
+impl<'a> Lora<'a> {
+    pub fn new(chat: &'a Chat) -> Lora<'a> {
+        let xns = xous_names::XousNames::new().unwrap();
+        let modals = Modals::new(&xns).expect("can't connect to Modals server");
+        Lora {
+            chat,
+            modals,
+            pddb: Pddb::new(),
+            usb: UsbHid::new(),
+            trng: Trng::new(&xns).unwrap(),
+            handle: None,
+            sealer: None,
+            listening: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        let mut value = Vec::new();
+        match self.pddb.get(LORA_STATE, key, None, false, false, None, None::<fn()>) {
+            Ok(mut pddb_key) => pddb_key.read_to_end(&mut value).ok()?,
+            Err(_) => return None,
+        };
+        String::from_utf8(value).ok().filter(|v| !v.is_empty())
+    }
+
+    fn set(&self, key: &str, value: &str) {
+        // delete key first to ensure data in a prior longer key is gone
+        self.pddb.delete_key(LORA_STATE, key, None).ok();
+        match self.pddb.get(LORA_STATE, key, None, true, true, None, None::<fn()>) {
+            Ok(mut pddb_key) => match pddb_key.write_all(value.as_bytes()) {
+                Ok(()) => {
+                    self.pddb.sync().ok();
+                }
+                Err(e) => log::warn!("Error writing {}:{} {:?}", LORA_STATE, key, e),
+            },
+            Err(e) => log::warn!("failed to set pddb {}:{}  {:?}", LORA_STATE, key, e),
+        }
+    }
+
+    /// Prompt for a single word, as used in a frame header
+    fn word_modal(&self, title: &str, key: &str, placeholder: &str) -> Option<String> {
+        let mut builder = self.modals.alert_builder(title);
+        let builder = match self.get(key) {
+            Some(word) => builder.field_placeholder_persist(Some(word), None),
+            None => builder.field(Some(placeholder.to_string()), None),
+        };
+        let payloads = builder.build().ok()?;
+        let word: String = payloads.first().content.as_str().ok()?.split_whitespace().collect();
+        if word.is_empty() {
+            return None;
+        }
+        self.set(key, &word);
+        Some(word)
+    }
+
+    /// The passphrase of `channel`, prompting for it the first time the channel is joined
+    fn passphrase(&self, channel: &str) -> Option<String> {
+        let key = format!("{}{}", PASSPHRASE_KEY, channel);
+        if let Some(passphrase) = self.get(&key) {
+            return Some(passphrase);
+        }
+        let payloads = self
+            .modals
+            .alert_builder(t!("mtxchat.lora.passphrase.title", locales::LANG))
+            .field(None, Some(passphrase_validator))
+            .build()
+            .ok()?;
+        let passphrase = payloads.first().content.as_str().ok()?.to_string();
+        self.set(&key, &passphrase);
+        Some(passphrase)
+    }
+
+    fn nonce(&self) -> [u8; NONCE_LEN] {
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[..8].copy_from_slice(&self.trng.get_u64().expect("unable to generate nonce").to_le_bytes());
+        nonce[8..].copy_from_slice(&self.trng.get_u32().expect("unable to generate nonce").to_le_bytes());
+        nonce
+    }
+}
+
+fn passphrase_validator(input: TextEntryPayload) -> Option<ValidatorErr> {
+    if input.as_str().chars().count() < PASSPHRASE_MIN_LEN {
+        Some(ValidatorErr::from_str(t!("mtxchat.lora.passphrase.short", locales::LANG)))
+    } else {
+        None
+    }
+}
+
+impl<'a> Transport for Lora<'a> {
+    fn name(&self) -> &'static str { "lora" }
+
+    fn connect(&mut self) -> bool {
+        if let Err(e) = self.usb.ensure_core(UsbDeviceType::Serial) {
+            log::warn!("failed to switch USB to serial: {:?}", e);
+            self.modals.show_notification(t!("mtxchat.lora.failed", locales::LANG), None).ok();
+            return false;
+        }
+        self.handle = match self.get(HANDLE_KEY) {
+            Some(handle) => Some(handle),
+            None => self.word_modal(
+                t!("mtxchat.lora.handle.title", locales::LANG),
+                HANDLE_KEY,
+                t!("mtxchat.lora.handle", locales::LANG),
+            ),
+        };
+        self.handle.is_some()
+    }
+
+    fn logout(&mut self) {
+        self.handle = self.word_modal(
+            t!("mtxchat.lora.handle.title", locales::LANG),
+            HANDLE_KEY,
+            t!("mtxchat.lora.handle", locales::LANG),
+        );
+    }
+
+    fn join(&mut self, conversation: Option<&str>) -> Option<String> {
+        let channel = match conversation.and_then(|key| key.strip_prefix(KEY_PREFIX)) {
+            Some(channel) => channel.to_string(),
+            None => self.word_modal(
+                t!("mtxchat.lora.channel.title", locales::LANG),
+                CHANNEL_KEY,
+                t!("mtxchat.lora.channel", locales::LANG),
+            )?,
+        };
+        let passphrase = self.passphrase(&channel)?;
+        let key = format!("{}{}", KEY_PREFIX, channel);
+        self.chat.dialogue_set(MTXCHAT_DIALOGUE, Some(&key)).expect("failed to set dialogue");
+        self.sealer = Some(Sealer::new(&channel, &passphrase));
+        if !self.listening.swap(true, Ordering::SeqCst) {
+            std::thread::spawn({
+                let sealer = Sealer::new(&channel, &passphrase);
+                let listening = self.listening.clone();
+                let chat_cid = self.chat.cid();
+                move || listen(sealer, listening, chat_cid)
+            });
+        }
+        Some(key)
+    }
+
+    fn leave(&mut self) {
+        self.sealer = None;
+        if self.listening.swap(false, Ordering::SeqCst) {
+            // releases the listener blocked on the serial port
+            self.usb.serial_flush().ok();
+        }
+    }
+
+    fn post(&mut self, text: &str) -> bool {
+        let (sealer, handle) = match (&self.sealer, &self.handle) {
+            (Some(sealer), Some(handle)) => (sealer, handle),
+            _ => return false,
+        };
+        // each line gets its own stamp, so the receivers don't take the later ones for replays
+        let stamp = now_ms();
+        let sent = split_lines(text, LORA_TEXT_MAX).iter().zip(stamp..).all(|(line, stamp)| {
+            match sealer.seal(self.nonce(), stamp, handle, line) {
+                Some(frame) => self.usb.serial_write(&format!("{}\n", frame)).is_ok(),
+                None => false,
+            }
+        });
+        if sent {
+            // the bridge doesn't echo our own frames back to us
+            self.chat.post_add(handle, now_ms(), text, None).expect("failed to add post");
+            chat::cf_dialogue_save(self.chat.cid());
+        }
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSPHRASE: &str = "correct horse battery staple";
+    const NOW: u64 = 1_700_000_000_000;
+
+    fn sealed(sealer: &Sealer, stamp: u64, sender: &str, text: &str) -> String {
+        sealer.seal([7u8; NONCE_LEN], stamp, sender, text).unwrap()
+    }
+
+    #[test]
+    fn parses_frames() {
+        let frame = Frame::parse("MSG xous AAECAw\r\n").unwrap();
+        assert_eq!(frame, Frame { channel: "xous", sealed: "AAECAw" });
+        assert!(Frame::parse("INFO: radio ready").is_none());
+        assert!(Frame::parse("MSG xous").is_none());
+    }
+
+    #[test]
+    fn sealed_frames_open() {
+        let sealer = Sealer::new("xous", PASSPHRASE);
+        let line = sealed(&sealer, NOW, "alice", "hello there");
+        assert!(!line.contains("hello"));
+        let post = sealer.open(&Frame::parse(&line).unwrap()).unwrap();
+        assert_eq!(post, Post { stamp: NOW, sender: "alice".to_string(), text: "hello there".to_string() });
+    }
+
+    #[test]
+    fn foreign_frames_are_refused() {
+        let sealer = Sealer::new("xous", PASSPHRASE);
+        let line = sealed(&sealer, NOW, "alice", "hello");
+        // another passphrase
+        assert!(
+            Sealer::new("xous", "not the passphrase at all").open(&Frame::parse(&line).unwrap()).is_none()
+        );
+        // the same passphrase on another channel
+        let other = Sealer::new("other", PASSPHRASE);
+        let moved = line.replacen("MSG xous", "MSG other", 1);
+        assert!(other.open(&Frame::parse(&moved).unwrap()).is_none());
+        // a flipped bit
+        let frame = Frame::parse(&line).unwrap();
+        let mut bytes = BASE64.decode(frame.sealed).unwrap();
+        bytes[NONCE_LEN] ^= 1;
+        let tampered = BASE64.encode(bytes);
+        assert!(sealer.open(&Frame { channel: "xous", sealed: &tampered }).is_none());
+        // a plaintext frame, as sent by an older bridge
+        assert!(sealer.open(&Frame::parse("MSG xous alice hello").unwrap()).is_none());
+    }
+
+    #[test]
+    fn replays_are_dropped() {
+        let post = |sender: &str, stamp| Post { stamp, sender: sender.to_string(), text: String::new() };
+        let mut replays = Replays::default();
+        assert!(replays.accept(&post("alice", NOW), NOW));
+        assert!(!replays.accept(&post("alice", NOW), NOW));
+        assert!(!replays.accept(&post("alice", NOW - 1), NOW));
+        assert!(replays.accept(&post("alice", NOW + 1), NOW));
+        assert!(replays.accept(&post("bob", NOW), NOW));
+        // too old to tell, or from too far in the future
+        assert!(!replays.accept(&post("carol", NOW - FRESHNESS_MS - 1), NOW));
+        assert!(!replays.accept(&post("carol", NOW + FRESHNESS_MS + 1), NOW));
+    }
+}
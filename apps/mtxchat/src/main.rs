@@ -4,13 +4,59 @@
 mod api;
 
 use api::*;
-use chat::{Chat, Event, POST_TEXT_MAX};
+use chat::{Chat, Event, Transports, POST_TEXT_MAX};
 use gam::{MenuItem, MenuPayload};
 use locales::t;
-use mtxchat::MtxChat;
+use modals::Modals;
+use mtxchat::{Irc, Lora, MtxChat, MTXCHAT_TRANSPORT};
 use num_traits::*;
 use xous_ipc::Buffer;
 
+/// Ask which registered transport to use; skipped if there is only the one
+fn choose_transport(transports: &Transports, modals: &Modals) -> Option<&'static str> {
+    let names = transports.names();
+    if names.len() < 2 {
+        return names.first().copied();
+    }
+    for name in names.iter() {
+        modals.add_list_item(name).expect("failed radio item");
+    }
+    modals.get_radiobutton(t!("mtxchat.transport.title", locales::LANG)).expect("failed radiobutton modal");
+    modals.get_radio_index().ok().and_then(|i| names.get(i).copied())
+}
+
+/// Ask which known conversation to open, or which transport to start a new one on
+///
+/// Returns the transport name and the Dialogue key of a known conversation
+fn choose_conversation(transports: &Transports, modals: &Modals) -> Option<(String, Option<String>)> {
+    let conversations = transports.conversations();
+    if !conversations.is_empty() {
+        for (key, name) in conversations.iter() {
+            modals.add_list_item(&format!("{} ({})", key, name)).expect("failed radio item");
+        }
+        modals.add_list_item(t!("mtxchat.conversation.new", locales::LANG)).expect("failed radio item");
+        modals
+            .get_radiobutton(t!("mtxchat.conversation.title", locales::LANG))
+            .expect("failed radiobutton modal");
+        let index = modals.get_radio_index().ok()?;
+        if let Some((key, name)) = conversations.get(index) {
+            return Some((name.to_string(), Some(key.to_string())));
+        }
+    }
+    choose_transport(transports, modals).map(|name| (name.to_string(), None))
+}
+
+/// Re-open the most recent conversation, or start a new one
+fn resume(transports: &mut Transports, modals: &Modals) {
+    let conversation = match transports.last() {
+        Some((key, name)) => Some((name, Some(key))),
+        None => choose_transport(transports, modals).map(|name| (name.to_string(), None)),
+    };
+    if let Some((name, key)) = conversation {
+        transports.open(&name, key.as_deref());
+    }
+}
+
 fn main() -> ! {
     let stack_size = 1024 * 1024;
     std::thread::Builder::new().stack_size(stack_size).spawn(wrapped_main).unwrap().join().unwrap()
@@ -18,6 +64,7 @@ fn main() -> ! {
 
 fn wrapped_main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
@@ -53,10 +100,10 @@ fn wrapped_main() -> ! {
 
     let cid = xous::connect(sid).unwrap();
     chat.menu_add(MenuItem {
-        name: xous_ipc::String::from_str(t!("mtxchat.room.item", locales::LANG)),
+        name: xous_ipc::String::from_str(t!("mtxchat.conversation.item", locales::LANG)),
         action_conn: Some(cid),
         action_opcode: MtxchatOp::Menu as u32,
-        action_payload: MenuPayload::Scalar([MenuOp::Room as u32, 0, 0, 0]),
+        action_payload: MenuPayload::Scalar([MenuOp::Conversation as u32, 0, 0, 0]),
         close_on_select: true,
     })
     .expect("failed add menu");
@@ -85,7 +132,11 @@ fn wrapped_main() -> ! {
     })
     .expect("failed add menu");
 
-    let mut mtxchat = MtxChat::new(&chat);
+    let modals = Modals::new(&xns).expect("can't connect to Modals server");
+    let mut transports = Transports::new(MTXCHAT_TRANSPORT);
+    transports.register(Box::new(MtxChat::new(&chat)));
+    transports.register(Box::new(Irc::new(&chat)));
+    transports.register(Box::new(Lora::new(&chat)));
     let mut first_focus = true;
     let mut user_post: Option<String> = None;
     loop {
@@ -99,9 +150,9 @@ fn wrapped_main() -> ! {
                         Some(Event::Focus) => {
                             if first_focus {
                                 first_focus = false;
-                                mtxchat.connect();
+                                resume(&mut transports, &modals);
                             }
-                            mtxchat.redraw();
+                            chat.redraw();
                         }
                         _ => (),
                     }
@@ -112,18 +163,18 @@ fn wrapped_main() -> ! {
                 xous::msg_scalar_unpack!(msg, menu_code, _, _, _, {
                     match FromPrimitive::from_usize(menu_code) {
                         Some(MenuOp::Login) => {
-                            mtxchat.connect();
+                            resume(&mut transports, &modals);
                         }
                         Some(MenuOp::Logout) => {
-                            mtxchat.logout();
-                            mtxchat.connect();
+                            if let Some(transport) = transports.current() {
+                                transport.logout();
+                            }
+                            resume(&mut transports, &modals);
                         }
                         Some(MenuOp::Noop) => {}
-                        Some(MenuOp::Room) => {
-                            if let Some(room) = mtxchat.get_room_id() {
-                                mtxchat.listen_over("");
-                                mtxchat.dialogue_set(Some(room.as_str()));
-                                mtxchat.listen();
+                        Some(MenuOp::Conversation) => {
+                            if let Some((name, key)) = choose_conversation(&transports, &modals) {
+                                transports.open(&name, key.as_deref());
                             }
                         }
                         _ => (),
@@ -142,13 +193,22 @@ fn wrapped_main() -> ! {
             Some(MtxchatOp::Rawkeys) => log::info!("got mtxchat rawkeys"),
             Some(MtxchatOp::Quit) => {
                 log::error!("got Quit");
-                mtxchat.listen_over("");
+                if let Some(transport) = transports.current() {
+                    transport.leave();
+                }
                 break;
             }
             _ => (),
         }
         if let Some(post) = user_post {
-            mtxchat.post(&post);
+            match transports.current() {
+                Some(transport) => {
+                    if !transport.post(&post) {
+                        log::warn!("failed to send over {}", transport.name());
+                    }
+                }
+                None => log::warn!("no conversation open to post to"),
+            }
             user_post = None;
         }
     }
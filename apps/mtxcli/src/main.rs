@@ -30,6 +30,7 @@ fn main() -> ! {
 
 fn wrapped_main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
@@ -25,6 +25,7 @@ pub(crate) const SERVER_NAME_REPL: &str = "_REPL demo application_";
 
 fn main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use vault::ctap::command::Command;
+
+fuzz_target!(|data: &[u8]| {
+    // an assembled CTAP2 message: the command byte, then its CBOR parameters
+    let _ = Command::deserialize(data);
+});
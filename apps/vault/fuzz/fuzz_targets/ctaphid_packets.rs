@@ -0,0 +1,17 @@
+#![no_main]
+use std::time::Instant;
+
+use libfuzzer_sys::fuzz_target;
+use vault::api::customization::{Customization, DEFAULT_CUSTOMIZATION};
+use vault::ctap::hid::{HidPacket, MessageAssembler};
+
+fuzz_target!(|data: &[u8]| {
+    // a stream of 64 byte HID reports, as they arrive from the host
+    let mut assembler = MessageAssembler::new();
+    let now = Instant::now();
+    for chunk in data.chunks(64) {
+        let mut packet: HidPacket = [0; 64];
+        packet[..chunk.len()].copy_from_slice(chunk);
+        let _ = assembler.parse_packet_with_limit(DEFAULT_CUSTOMIZATION.max_msg_size(), &packet, now);
+    }
+});
@@ -1,5 +1,6 @@
 use core::convert::TryFrom;
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::io::ErrorKind;
 use std::io::{Read, Write};
 use std::sync::{
@@ -22,6 +23,12 @@ use vault::{
 };
 use xous::{send_message, Message};
 
+#[cfg(feature = "breach-check")]
+use crate::breach;
+use crate::otpauth::OtpAuth;
+use crate::seed;
+use crate::shares::{self, Share};
+use crate::ssh;
 use crate::storage::{self, PasswordRecord, StorageContent};
 use crate::totp::TotpAlgorithm;
 use crate::{storage::TotpRecord, ListItem, ListKey};
@@ -41,13 +48,19 @@ pub enum ActionOp {
     MenuAddnew,
     MenuEditStage2,
     MenuDeleteStage2,
+    MenuSplitStage2,
+    MenuRecoverShares,
     MenuClose,
     MenuUnlockBasis,
     MenuManageBasis,
+    #[cfg(feature = "breach-check")]
+    MenuBreachCheck,
     /// Internal ops
     UpdateMode,
     UpdateOneItem,
     ReloadDb,
+    /// A code was scanned by the optical input service
+    ScanAdd,
     Quit,
     #[cfg(feature = "vault-testing")]
     /// Testing
@@ -56,9 +69,9 @@ pub enum ActionOp {
 
 pub struct ActionManager<'a> {
     modals: modals::Modals,
+    gam: gam::Gam,
     storage: RefCell<storage::Manager>,
 
-    #[cfg(feature = "vault-testing")]
     trng: RefCell<trng::Trng>,
 
     mode: Arc<Mutex<VaultMode>>,
@@ -69,6 +82,10 @@ pub struct ActionManager<'a> {
     opensk_mutex: Arc<Mutex<i32>>,
     mode_cache: VaultMode,
     main_conn: xous::CID,
+    /// guids of the password entries found in the breach list; always empty without `breach-check`
+    breached: HashSet<String>,
+    /// shares collected so far towards rebuilding a split entry, while a recovery is under way
+    recovery: Option<shares::Recovery>,
     #[cfg(feature = "vaultperf")]
     perfbuf: xous::MemoryRange,
     #[cfg(feature = "vaultperf")]
@@ -106,9 +123,9 @@ impl<'a> ActionManager<'a> {
         let mc = (*mode.lock().unwrap()).clone();
         ActionManager {
             modals: modals::Modals::new(&xns).unwrap(),
+            gam: gam::Gam::new(&xns).unwrap(),
             storage: RefCell::new(storage_manager),
 
-            #[cfg(feature = "vault-testing")]
             trng: RefCell::new(trng::Trng::new(&xns).unwrap()),
 
             mode_cache: mc,
@@ -119,6 +136,8 @@ impl<'a> ActionManager<'a> {
             action_active,
             opensk_mutex,
             main_conn,
+            breached: HashSet::new(),
+            recovery: None,
             #[cfg(feature = "vaultperf")]
             perfbuf,
             #[cfg(feature = "vaultperf")]
@@ -190,6 +209,7 @@ impl<'a> ActionManager<'a> {
                 self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
                 let mut approved = false;
                 let mut bip39 = false;
+                let mut ssh_key = false;
                 // Security note about PasswordGenerator. This is a 3rd party crate. It relies on `rand`'s
                 // implementation of ThreadRng to generate passwords. As of the version
                 // committed to the lockfile, I have evidenced the ThreadRng to request 8
@@ -337,9 +357,13 @@ impl<'a> ActionManager<'a> {
                         bip39 = true;
                         approved = true;
                         match self.modals.input_bip39(Some(t!("vault.bip39.input", locales::LANG))) {
-                            Ok(data) => hex::encode(data),
+                            Ok(data) => seed::format_record(&data, &self.input_seed_passphrase()),
                             _ => "".to_string(),
                         }
+                    } else if maybe_password == "ssh" {
+                        ssh_key = true;
+                        approved = true;
+                        ssh::generate_record(&self.trng.borrow())
                     } else {
                         approved = true;
                         maybe_password
@@ -352,6 +376,8 @@ impl<'a> ActionManager<'a> {
                     password,
                     notes: if bip39 {
                         "bip39".to_string()
+                    } else if ssh_key {
+                        ssh::SSH_KEY_NOTES.to_string()
                     } else {
                         t!("vault.notes", locales::LANG).to_string()
                     },
@@ -367,8 +393,14 @@ impl<'a> ActionManager<'a> {
                         self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
                     }
                 };
+                if ssh_key {
+                    self.show_ssh_public_key(&record);
+                }
+                let guid = storage::hex(record.hash());
+                #[cfg(feature = "breach-check")]
+                breach::recheck(&self.pddb.borrow(), &mut self.breached, None, &guid, &record.password);
                 // update the ux cache
-                let li = make_pw_item_from_record(&storage::hex(record.hash()), record);
+                let li = make_pw_item_from_record(&guid, record, self.breached.contains(&guid));
                 self.item_lists.lock().unwrap().insert_unique(self.mode_cache, li);
             }
             VaultMode::Fido => {
@@ -376,62 +408,31 @@ impl<'a> ActionManager<'a> {
                 // no DB entry update because it's an error to even get here
             }
             VaultMode::Totp => {
-                let description = match self
+                let form = match self
                     .modals
-                    .alert_builder(t!("vault.newitem.name", locales::LANG))
-                    .field(None, Some(password_validator))
+                    .form_builder(t!("vault.newitem.otp_form", locales::LANG))
+                    .text(t!("vault.form.name", locales::LANG), None, Some(password_validator))
+                    .radio(
+                        t!("vault.form.otp_type", locales::LANG),
+                        &[t!("vault.newitem.totp", locales::LANG), t!("vault.newitem.hotp", locales::LANG)],
+                        0,
+                    )
+                    .text(t!("vault.form.secret", locales::LANG), None, Some(totp_ss_validator))
+                    .number(t!("vault.form.hotp_count", locales::LANG), Some(0), Some(count_validator))
                     .build()
                 {
-                    Ok(text) => text.content()[0].content.as_str().unwrap_or("UTF-8 error").to_string(),
+                    Ok(form) => form,
                     _ => {
-                        log::error!("Name entry failed");
+                        log::error!("OTP entry form failed");
                         self.action_active.store(false, Ordering::SeqCst);
                         return;
                     }
                 };
-
-                #[cfg(feature = "ux-swap-delay")]
-                self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                self.modals
-                    .add_list(vec![
-                        t!("vault.newitem.totp", locales::LANG),
-                        t!("vault.newitem.hotp", locales::LANG),
-                    ])
-                    .expect("couldn't create configuration modal");
-                let is_totp: bool;
-                match self.modals.get_radiobutton(t!("vault.newitem.is_t_or_h_otp", locales::LANG)) {
-                    Ok(response) => {
-                        if &response == t!("vault.newitem.totp", locales::LANG) {
-                            is_totp = true;
-                        } else {
-                            is_totp = false;
-                        }
-                    }
-                    _ => {
-                        log::error!("Modal selection error");
-                        self.action_active.store(false, Ordering::SeqCst);
-                        return;
-                    }
-                }
-
-                #[cfg(feature = "ux-swap-delay")]
-                self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                let secret = match self
-                    .modals
-                    .alert_builder(t!("vault.newitem.totp_ss", locales::LANG))
-                    .field(None, Some(totp_ss_validator))
-                    .build()
-                {
-                    Ok(text) => text.content()[0].content.as_str().unwrap_or("UTF-8 error").to_string(),
-                    _ => {
-                        log::error!("TOTP ss entry failed");
-                        self.action_active.store(false, Ordering::SeqCst);
-                        return;
-                    }
-                };
-                #[cfg(feature = "ux-swap-delay")]
-                self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                let ss = secret.to_uppercase();
+                // the fields are in the order they were added above
+                let field = |index: usize| form.get(index).expect("form lost a field");
+                let description = field(0).as_str().to_string();
+                let is_totp = field(1).choice() == 0;
+                let ss = field(2).as_str().to_uppercase();
                 let ss_vec = if let Some(ss) =
                     base32::decode(base32::Alphabet::RFC4648 { padding: false }, &ss)
                 {
@@ -450,23 +451,9 @@ impl<'a> ActionManager<'a> {
                 };
                 let validated_secret = base32::encode(base32::Alphabet::RFC4648 { padding: false }, &ss_vec);
 
+                // the count is validated as a u64, and is only used by HOTP records
                 let timestep = if !is_totp {
-                    // get the initial count if it's an HOTP record
-                    #[cfg(feature = "ux-swap-delay")]
-                    self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                    match self
-                        .modals
-                        .alert_builder(t!("vault.hotp.count", locales::LANG))
-                        .field(Some("0".to_string()), Some(count_validator))
-                        .build()
-                    {
-                        Ok(entry) => entry.content()[0].content.as_str().unwrap().parse::<u64>().unwrap(),
-                        _ => {
-                            log::error!("Count entry failed");
-                            self.action_active.store(false, Ordering::SeqCst);
-                            return;
-                        }
-                    }
+                    field(3).as_str().parse::<u64>().unwrap_or(0)
                 } else {
                     30 // default TOTP timestep otherwise
                 };
@@ -498,6 +485,282 @@ impl<'a> ActionManager<'a> {
         }
     }
 
+    /// Adds the TOTP or HOTP entry of a scanned `otpauth://` code, once the user confirms it
+    pub(crate) fn scan_add(&mut self, entry: OtpAuth) {
+        if !self.yes_no_approval(&format!("{}\n{}", t!("vault.scan.confirm", locales::LANG), entry.name)) {
+            return;
+        }
+        let mut totp = storage::TotpRecord {
+            version: VAULT_TOTP_REC_VERSION,
+            name: entry.name,
+            secret: entry.secret,
+            algorithm: entry.algorithm,
+            digits: entry.digits,
+            timestep: entry.timestep,
+            ctime: 0,
+            is_hotp: entry.is_hotp,
+            notes: t!("vault.scan.notes", locales::LANG).to_string(),
+        };
+        match self.storage.borrow_mut().new_record(&mut totp, None, true) {
+            Ok(_) => (),
+            Err(error) => {
+                self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
+                return;
+            }
+        };
+        let li = make_totp_item_from_record(&storage::hex(totp.hash()), totp);
+        self.item_lists.lock().unwrap().insert_unique(VaultMode::Totp, li);
+        self.modals.show_notification(t!("vault.scan.added", locales::LANG), None).ok();
+    }
+
+    /// Splits a password entry into K-of-N shares, each shown as a QR code to be scanned by another
+    /// device or printed. The entry itself can be deleted afterwards.
+    pub(crate) fn menu_split(&mut self, entry: SelectedEntry) {
+        if entry.mode != VaultMode::Password {
+            self.report_err(t!("vault.shares.password_only", locales::LANG), None::<std::io::Error>);
+            return;
+        }
+        let pw: PasswordRecord = match self
+            .storage
+            .borrow_mut()
+            .get_record(&storage::ContentKind::Password, entry.key_guid.as_str().unwrap_or("UTF8-error"))
+        {
+            Ok(record) => record,
+            Err(error) => {
+                self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
+                return;
+            }
+        };
+        if shares::is_share_record(&pw.notes) {
+            self.report_err(t!("vault.shares.already_share", locales::LANG), None::<std::io::Error>);
+            return;
+        }
+        let params = match self
+            .modals
+            .alert_builder(t!("vault.shares.parameters", locales::LANG))
+            .field(Some("5".to_string()), Some(share_count_validator))
+            .field(Some("3".to_string()), Some(share_count_validator))
+            .build()
+        {
+            Ok(params) => params,
+            _ => {
+                log::error!("share count entry failed");
+                return;
+            }
+        };
+        let count = params.content()[0].content.as_str().unwrap().parse::<u8>().unwrap();
+        let threshold = params.content()[1].content.as_str().unwrap().parse::<u8>().unwrap();
+        let secret = shares::Secret {
+            description: pw.description.clone(),
+            username: pw.username.clone(),
+            password: pw.password.clone(),
+            // seeds and SSH keys keep their kind through the split
+            kind: if seed::is_seed_record(&pw.notes) || ssh::is_ssh_record(&pw.notes) {
+                pw.notes.clone()
+            } else {
+                String::new()
+            },
+        };
+        let split = match shares::split(&secret, threshold, count, &self.trng.borrow()) {
+            Ok(split) => split,
+            Err(e) => {
+                self.report_err(t!("vault.shares.split_error", locales::LANG), Some(e));
+                return;
+            }
+        };
+        for share in split.iter() {
+            #[cfg(feature = "ux-swap-delay")]
+            self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+            self.modals
+                .show_notification(
+                    &format!(
+                        "{}\n{}",
+                        t!("vault.shares.show", locales::LANG)
+                            .replace("{label}", &share.label())
+                            .replace("{k}", &threshold.to_string()),
+                        pw.description
+                    ),
+                    Some(&share.encode()),
+                )
+                .ok();
+        }
+        self.modals.show_notification(t!("vault.shares.split_done", locales::LANG), None).ok();
+        self.menu_delete(entry);
+    }
+
+    /// A scanned share goes towards the recovery under way, if there is one. Otherwise it is kept as an
+    /// entry of its own, once the user confirms it, until it's needed to rebuild the entry it came from.
+    pub(crate) fn scan_share(&mut self, share: Share) {
+        if self.recovery.is_some() {
+            self.recover_add(share);
+            return;
+        }
+        if !self.yes_no_approval(&format!(
+            "{}\n{}",
+            t!("vault.shares.hold_confirm", locales::LANG),
+            share.label()
+        )) {
+            return;
+        }
+        let mut record = storage::PasswordRecord {
+            version: VAULT_PASSWORD_REC_VERSION,
+            description: format!("{} {}", t!("vault.shares.description", locales::LANG), share.label()),
+            username: String::new(),
+            password: share.encode(),
+            notes: format!(
+                "{} {}",
+                shares::SHARE_NOTES,
+                t!("vault.shares.needed", locales::LANG).replace("{k}", &share.threshold.to_string())
+            ),
+            ctime: 0,
+            atime: 0,
+            count: 0,
+        };
+        match self.storage.borrow_mut().new_record(&mut record, None, true) {
+            Ok(_) => (),
+            Err(error) => {
+                self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
+                return;
+            }
+        };
+        let guid = storage::hex(record.hash());
+        let li = make_pw_item_from_record(&guid, record, self.breached.contains(&guid));
+        self.item_lists.lock().unwrap().insert_unique(VaultMode::Password, li);
+        self.modals.show_notification(t!("vault.shares.held", locales::LANG), None).ok();
+    }
+
+    /// Rebuilds a split entry from its shares. Shares are typed in here, or scanned at any time while the
+    /// recovery is under way.
+    pub(crate) fn menu_recover(&mut self) {
+        if self.recovery.is_none() {
+            self.recovery = Some(shares::Recovery::new());
+        }
+        while let Some(recovery) = self.recovery.as_ref() {
+            let (have, need) = recovery.progress();
+            let status = if need == 0 {
+                t!("vault.shares.recover_start", locales::LANG).to_string()
+            } else {
+                t!("vault.shares.recover_progress", locales::LANG)
+                    .replace("{have}", &have.to_string())
+                    .replace("{need}", &need.to_string())
+            };
+            self.modals
+                .add_list(vec![
+                    t!("vault.shares.type", locales::LANG),
+                    t!("vault.shares.scan", locales::LANG),
+                    t!("vault.shares.abandon", locales::LANG),
+                ])
+                .expect("couldn't build recovery dialog");
+            match self.modals.get_radiobutton(&status) {
+                Ok(choice) if choice == t!("vault.shares.type", locales::LANG) => {
+                    #[cfg(feature = "ux-swap-delay")]
+                    self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+                    match self
+                        .modals
+                        .alert_builder(t!("vault.shares.type_prompt", locales::LANG))
+                        .field(None, Some(share_validator))
+                        .build()
+                    {
+                        Ok(text) => {
+                            let share = Share::parse(text.content()[0].content.as_str().unwrap()).unwrap();
+                            self.recover_add(share);
+                        }
+                        _ => return,
+                    }
+                }
+                Ok(choice) if choice == t!("vault.shares.abandon", locales::LANG) => {
+                    self.recovery = None;
+                }
+                // the recovery stays open for shares to be scanned
+                _ => return,
+            }
+        }
+    }
+
+    fn recover_add(&mut self, share: Share) {
+        let label = share.label();
+        let recovery = self.recovery.as_mut().expect("no recovery under way");
+        match recovery.add(share) {
+            Ok(None) => {
+                let (have, need) = recovery.progress();
+                self.modals
+                    .show_notification(
+                        &t!("vault.shares.accepted", locales::LANG)
+                            .replace("{label}", &label)
+                            .replace("{have}", &have.to_string())
+                            .replace("{need}", &need.to_string()),
+                        None,
+                    )
+                    .ok();
+            }
+            Ok(Some(secret)) => {
+                self.recovery = None;
+                self.save_recovered(secret);
+            }
+            Err(shares::ShareError::Corrupt) => {
+                // the share that completed the set wasn't kept, so another one can be tried instead
+                self.report_err(t!("vault.shares.corrupt", locales::LANG), None::<std::io::Error>);
+            }
+            Err(e) => self.report_err(t!("vault.shares.rejected", locales::LANG), Some(e)),
+        }
+    }
+
+    fn save_recovered(&mut self, mut secret: shares::Secret) {
+        if !self.yes_no_approval(&format!(
+            "{}\n{}",
+            t!("vault.shares.recovered_confirm", locales::LANG),
+            secret.description
+        )) {
+            return;
+        }
+        let mut record = storage::PasswordRecord {
+            version: VAULT_PASSWORD_REC_VERSION,
+            description: std::mem::take(&mut secret.description),
+            username: std::mem::take(&mut secret.username),
+            password: std::mem::take(&mut secret.password),
+            notes: if secret.kind.is_empty() {
+                t!("vault.notes", locales::LANG).to_string()
+            } else {
+                std::mem::take(&mut secret.kind)
+            },
+            ctime: 0,
+            atime: 0,
+            count: 0,
+        };
+        match self.storage.borrow_mut().new_record(&mut record, None, true) {
+            Ok(_) => (),
+            Err(error) => {
+                self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
+                return;
+            }
+        };
+        let guid = storage::hex(record.hash());
+        #[cfg(feature = "breach-check")]
+        breach::recheck(&self.pddb.borrow(), &mut self.breached, None, &guid, &record.password);
+        let li = make_pw_item_from_record(&guid, record, self.breached.contains(&guid));
+        self.item_lists.lock().unwrap().insert_unique(VaultMode::Password, li);
+        self.modals.show_notification(t!("vault.shares.recovered", locales::LANG), None).ok();
+    }
+
+    /// Shows a held share as a QR code, to be scanned by the device rebuilding its entry
+    fn show_share(&self, pw: &PasswordRecord) {
+        match Share::parse(&pw.password) {
+            Some(share) => {
+                self.modals
+                    .show_notification(
+                        &t!("vault.shares.show_held", locales::LANG)
+                            .replace("{label}", &share.label())
+                            .replace("{k}", &share.threshold.to_string()),
+                        Some(&share.encode()),
+                    )
+                    .ok();
+            }
+            None => {
+                self.modals.show_notification(t!("vault.shares.illegal_share", locales::LANG), None).ok();
+            }
+        }
+    }
+
     pub(crate) fn menu_delete(&mut self, entry: SelectedEntry) {
         if self.yes_no_approval(&format!(
             "{}\n{}",
@@ -613,7 +876,7 @@ impl<'a> ActionManager<'a> {
                         return;
                     }
                 };
-                let li = make_pw_item_from_record(guid, pw);
+                let li = make_pw_item_from_record(guid, pw, self.breached.contains(guid));
                 log::debug!("updating {} to list item {}", li.extra, li.key());
                 let exists = self.item_lists.lock().unwrap().insert_unique(entry.mode, li).is_some();
                 assert!(exists, "Somehow, the autotyped record isn't in the UX list for updating!");
@@ -855,22 +1118,22 @@ impl<'a> ActionManager<'a> {
 
                 // if the notes field starts with the word "bip39" (case insensitive), use BIP39 to
                 // display/edit the password field
-                if pw.notes.to_ascii_lowercase().starts_with("bip39") {
+                if seed::is_seed_record(&pw.notes) {
                     if pw.password.len() == 0 {
                         match self.modals.input_bip39(Some(t!("vault.bip39.input", locales::LANG))) {
                             Ok(data) => {
-                                pw.password = hex::encode(data);
+                                pw.password = seed::format_record(&data, &self.input_seed_passphrase());
                             }
                             _ => pw.password = "".to_string(), // leave it blank if invalid or aborted
                         }
                     } else {
-                        match hex::decode(&pw.password) {
-                            Ok(data) => {
+                        match seed::parse_record(&pw.password) {
+                            Some((data, passphrase)) => {
                                 match self
                                     .modals
                                     .show_bip39(Some(t!("vault.bip39.output", locales::LANG)), &data)
                                 {
-                                    Ok(_) => {}
+                                    Ok(_) => self.seed_tools(&data, &passphrase),
                                     Err(_) => {
                                         self.modals
                                             .show_notification(
@@ -881,14 +1144,21 @@ impl<'a> ActionManager<'a> {
                                     }
                                 }
                             }
-                            Err(_) => {
+                            None => {
                                 self.modals
                                     .show_notification(t!("vault.bip39.output_error", locales::LANG), None)
                                     .unwrap();
                             }
                         }
                     }
-                } else if pw.password.len() == 0 && !pw.notes.to_ascii_lowercase().starts_with("bip39") {
+                } else if ssh::is_ssh_record(&pw.notes) {
+                    if pw.password.len() == 0 {
+                        pw.password = ssh::generate_record(&self.trng.borrow());
+                    }
+                    self.show_ssh_public_key(&pw);
+                } else if shares::is_share_record(&pw.notes) {
+                    self.show_share(&pw);
+                } else if pw.password.len() == 0 && !seed::is_seed_record(&pw.notes) {
                     // if the password is empty, prompt to generate a new password
                     let pg = PasswordGenerator {
                         length: 20,
@@ -1012,8 +1282,17 @@ impl<'a> ActionManager<'a> {
                 // update disk
                 let ret = storage.update(&choice, key_guid, &mut pw);
                 if ret.is_ok() {
+                    let guid = storage::hex(pw.hash());
+                    #[cfg(feature = "breach-check")]
+                    breach::recheck(
+                        &self.pddb.borrow(),
+                        &mut self.breached,
+                        Some(key_guid),
+                        &guid,
+                        &pw.password,
+                    );
                     // update item cache
-                    let li = make_pw_item_from_record(&storage::hex(pw.hash()), pw);
+                    let li = make_pw_item_from_record(&guid, pw, self.breached.contains(&guid));
                     self.item_lists.lock().unwrap().insert_unique(self.mode_cache, li);
                 }
                 ret
@@ -1026,6 +1305,120 @@ impl<'a> ActionManager<'a> {
         }
     }
 
+    /// The optional BIP-39 passphrase (the "25th word") that goes with a seed phrase. Blank for none.
+    fn input_seed_passphrase(&self) -> String {
+        match self
+            .modals
+            .alert_builder(t!("vault.bip39.passphrase", locales::LANG))
+            .field(None, Some(password_validator))
+            .build()
+        {
+            Ok(text) => text.content()[0].content.as_str().unwrap_or("").to_string(),
+            _ => "".to_string(),
+        }
+    }
+
+    /// Derives keys from a seed record for use with a watch-only wallet. Everything derived here is
+    /// public, and is only ever shown on the screen.
+    fn seed_tools(&self, entropy: &Vec<u8>, passphrase: &str) {
+        let mut account: Option<seed::ExtendedKey> = None;
+        let mut next_address = 0;
+        loop {
+            self.modals
+                .add_list(vec![
+                    t!("vault.bip39.show_zpub", locales::LANG),
+                    t!("vault.bip39.show_addresses", locales::LANG),
+                    t!("vault.bip39.done", locales::LANG),
+                ])
+                .expect("couldn't build seed tools dialog");
+            let response = match self.modals.get_radiobutton(t!("vault.bip39.tools", locales::LANG)) {
+                Ok(response) => response,
+                _ => return,
+            };
+            if &response == t!("vault.bip39.done", locales::LANG) {
+                return;
+            }
+            if account.is_none() {
+                // PBKDF2 with 2048 rounds of SHA-512 takes a moment
+                self.modals.dynamic_notification(Some(t!("vault.bip39.deriving", locales::LANG)), None).ok();
+                account = self.gam.bytes_to_bip39(entropy).ok().and_then(|words| {
+                    let words = zeroize::Zeroizing::new(words);
+                    let seed = seed::mnemonic_to_seed(&words, passphrase);
+                    seed::ExtendedKey::master(&seed[..])?.derive(&seed::BIP84_ACCOUNT)
+                });
+                self.modals.dynamic_notification_close().ok();
+            }
+            let account = match account.as_ref() {
+                Some(account) => account,
+                None => {
+                    self.modals.show_notification(t!("vault.bip39.derive_error", locales::LANG), None).ok();
+                    return;
+                }
+            };
+            #[cfg(feature = "ux-swap-delay")]
+            self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+            if &response == t!("vault.bip39.show_zpub", locales::LANG) {
+                let zpub = account.to_zpub();
+                self.modals
+                    .show_notification(
+                        &format!("{}\n{}", t!("vault.bip39.zpub", locales::LANG), zpub),
+                        Some(&zpub),
+                    )
+                    .ok();
+            } else {
+                // one address per screen, so each gets its own QR code
+                loop {
+                    let address = match seed::receive_address(account, next_address) {
+                        Some(address) => address,
+                        None => {
+                            next_address += 1;
+                            continue;
+                        }
+                    };
+                    self.modals
+                        .show_notification(
+                            &format!(
+                                "{} {}\n{}",
+                                t!("vault.bip39.address", locales::LANG),
+                                next_address,
+                                address
+                            ),
+                            Some(&address),
+                        )
+                        .ok();
+                    next_address += 1;
+                    if !self.yes_no_approval(t!("vault.bip39.next_address", locales::LANG)) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Shows an SSH key record's public key as an `authorized_keys` line, and as a QR code
+    fn show_ssh_public_key(&self, pw: &PasswordRecord) {
+        #[cfg(feature = "ed25519")]
+        match ssh::keypair(&pw.password) {
+            Some(kp) => {
+                let line = ssh::openssh_public_key(&kp.pk, &pw.description);
+                self.modals
+                    .show_notification(
+                        &format!("{}\n{}", t!("vault.ssh.public_key", locales::LANG), line),
+                        Some(&line),
+                    )
+                    .ok();
+            }
+            None => {
+                self.modals.show_notification(t!("vault.ssh.key_error", locales::LANG), None).ok();
+            }
+        }
+        #[cfg(not(feature = "ed25519"))]
+        {
+            let _ = pw;
+            self.modals.show_notification(t!("vault.ssh.key_error", locales::LANG), None).ok();
+        }
+    }
+
     fn yes_no_approval(&self, query: &str) -> bool {
         self.modals
             .add_list(vec![t!("vault.yes", locales::LANG), t!("vault.no", locales::LANG)])
@@ -1088,6 +1481,10 @@ impl<'a> ActionManager<'a> {
                     .dynamic_notification(Some(t!("vault.reloading_database", locales::LANG)), None)
                     .ok();
                 let start = self.tt.elapsed_ms();
+                #[cfg(feature = "breach-check")]
+                {
+                    self.breached = breach::load_flagged(&self.pddb.borrow());
+                }
                 #[cfg(feature = "vaultperf")]
                 self.perfentry(&self.pm, PERFMETA_STARTBLOCK, 1, std::line!());
                 let mut klen = 0;
@@ -1138,6 +1535,10 @@ impl<'a> ActionManager<'a> {
                                             extra.push_str("; ");
                                             extra.push_str(t!("vault.u2f.appinfo.authcount", locales::LANG));
                                             extra.push_str(&pw_rec.count.to_string());
+                                            if self.breached.contains(&key.name) {
+                                                extra.push_str("; ");
+                                                extra.push_str(t!("vault.breach.flag", locales::LANG));
+                                            }
                                             prev_entry.extra.clear();
                                             prev_entry.extra.push_str(&extra);
                                         }
@@ -1166,6 +1567,10 @@ impl<'a> ActionManager<'a> {
                                         extra.push_str("; ");
                                         extra.push_str(t!("vault.u2f.appinfo.authcount", locales::LANG));
                                         extra.push_str(&pw_rec.count.to_string());
+                                        if self.breached.contains(&key.name) {
+                                            extra.push_str("; ");
+                                            extra.push_str(t!("vault.breach.flag", locales::LANG));
+                                        }
 
                                         let li = ListItem::new(
                                             desc.to_string(), /* these allocs will be slow, but we do it
@@ -1545,6 +1950,137 @@ impl<'a> ActionManager<'a> {
         }
     }
 
+    /// Checks the stored passwords against the downloaded breach list, after optionally downloading a new
+    /// list. The check runs entirely on the device; only the list download uses the network.
+    #[cfg(feature = "breach-check")]
+    pub(crate) fn breach_check(&mut self) {
+        let have_filter = breach::Filter::open(&self.pddb.borrow()).is_some();
+        let mut options = Vec::new();
+        if have_filter {
+            options.push(t!("vault.breach.check", locales::LANG));
+        }
+        options.push(t!("vault.breach.download", locales::LANG));
+        if have_filter {
+            options.push(t!("vault.breach.remove", locales::LANG));
+        }
+        options.push(t!("vault.breach.cancel", locales::LANG));
+        self.modals.add_list(options).expect("couldn't create breach check modal");
+        let choice = match self.modals.get_radiobutton(t!("vault.breach.title", locales::LANG)) {
+            Ok(choice) => choice,
+            Err(e) => {
+                self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e));
+                return;
+            }
+        };
+        #[cfg(feature = "ux-swap-delay")]
+        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+        if choice == t!("vault.breach.remove", locales::LANG) {
+            breach::remove(&self.pddb.borrow());
+            self.breached.clear();
+            return;
+        } else if choice == t!("vault.breach.download", locales::LANG) {
+            if !self.breach_download() {
+                return;
+            }
+        } else if choice != t!("vault.breach.check", locales::LANG) {
+            return;
+        }
+        self.breach_scan();
+    }
+
+    /// Prompts for the URL of a breach list and downloads it. Returns true if a new list was stored.
+    #[cfg(feature = "breach-check")]
+    fn breach_download(&self) -> bool {
+        let pddb = self.pddb.borrow();
+        let mut builder = self.modals.alert_builder(t!("vault.breach.url", locales::LANG));
+        let builder = match breach::load_url(&pddb) {
+            Some(url) => builder.field_placeholder_persist(Some(url), None),
+            None => builder.field(None, None),
+        };
+        let url = match builder.build() {
+            Ok(text) => text.content()[0].content.as_str().unwrap_or("").trim().to_string(),
+            _ => return false,
+        };
+        if url.is_empty() {
+            return false;
+        }
+        breach::save_url(&pddb, &url);
+
+        // the size of the list isn't known until its header arrives, so progress is kept in permille
+        self.modals
+            .start_progress(t!("vault.breach.downloading", locales::LANG), 0, 1000, 0)
+            .expect("couldn't raise progress bar");
+        let mut permille = 0;
+        let result = breach::download(&pddb, &url, |received, total| {
+            let now = (received * 1000 / total) as u32;
+            if now != permille {
+                permille = now;
+                self.modals.update_progress(permille).ok();
+            }
+        });
+        self.modals.finish_progress().ok();
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                self.report_err(t!("vault.breach.download_failed", locales::LANG), Some(e));
+                false
+            }
+        }
+    }
+
+    /// Checks every stored password against the breach list, and records and reports the results
+    #[cfg(feature = "breach-check")]
+    fn breach_scan(&mut self) {
+        let pddb = self.pddb.borrow();
+        let mut filter = match breach::Filter::open(&pddb) {
+            Some(filter) => filter,
+            None => return,
+        };
+        let keys = match pddb.read_dict(VAULT_PASSWORD_DICT, None, Some(256 * 1024)) {
+            Ok(keys) => keys,
+            Err(e) => {
+                self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e));
+                return;
+            }
+        };
+        self.modals
+            .start_progress(t!("vault.breach.checking", locales::LANG), 0, keys.len() as u32, 0)
+            .expect("couldn't raise progress bar");
+        let mut flagged = HashSet::new();
+        let mut checked = 0;
+        let mut pw_rec = PasswordRecord::alloc();
+        for (i, key) in keys.into_iter().enumerate() {
+            if let Some(data) = key.data {
+                if pw_rec.from_vec(data).is_ok() {
+                    match filter.contains(&pw_rec.password) {
+                        Ok(true) => {
+                            flagged.insert(key.name);
+                        }
+                        Ok(false) => (),
+                        Err(e) => log::warn!("couldn't read the breach filter: {:?}", e),
+                    }
+                    checked += 1;
+                }
+            }
+            self.modals.update_progress(i as u32 + 1).ok();
+        }
+        self.modals.finish_progress().ok();
+        breach::save_flagged(&pddb, &flagged);
+        log::info!("{} of {} passwords found in the breach list", flagged.len(), checked);
+
+        let report = if flagged.is_empty() {
+            t!("vault.breach.none_found", locales::LANG).replace("{checked}", &checked.to_string())
+        } else {
+            t!("vault.breach.found", locales::LANG)
+                .replace("{found}", &flagged.len().to_string())
+                .replace("{checked}", &checked.to_string())
+        };
+        self.breached = flagged;
+        #[cfg(feature = "ux-swap-delay")]
+        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+        self.modals.show_notification(&report, None).ok();
+    }
+
     #[cfg(feature = "vault-testing")]
     pub(crate) fn populate_tests(&mut self) {
         self.modals.dynamic_notification(Some("Creating test entries..."), None).ok();
@@ -1708,6 +2244,7 @@ impl<'a> ActionManager<'a> {
                     user_icon: None,
                     cred_blob: None,
                     large_blob_key: None,
+                    signature_counter: None,
                 };
                 let shortid = &cred_id;
                 match self.pddb.borrow().get(
@@ -1841,6 +2378,18 @@ pub(crate) fn password_validator(input: TextEntryPayload) -> Option<xous_ipc::St
         None
     }
 }
+fn share_count_validator(input: TextEntryPayload) -> Option<xous_ipc::String<256>> {
+    match input.as_str().parse::<u8>() {
+        Ok(count) if count >= 2 && count <= shares::MAX_SHARES => None,
+        _ => Some(xous_ipc::String::<256>::from_str(t!("vault.shares.illegal_count", locales::LANG))),
+    }
+}
+fn share_validator(input: TextEntryPayload) -> Option<xous_ipc::String<256>> {
+    match Share::parse(input.as_str()) {
+        Some(_) => None,
+        None => Some(xous_ipc::String::<256>::from_str(t!("vault.shares.illegal_share", locales::LANG))),
+    }
+}
 fn length_validator(input: TextEntryPayload) -> Option<xous_ipc::String<256>> {
     let text_str = input.as_str();
     match text_str.parse::<u32>() {
@@ -1945,7 +2494,7 @@ fn make_totp_item_from_record(guid: &str, totp: TotpRecord) -> ListItem {
     let desc = format!("{}", totp.name);
     ListItem::new(desc, extra, true, guid.to_owned(), 0, 0)
 }
-fn make_pw_item_from_record(guid: &str, pw: PasswordRecord) -> ListItem {
+fn make_pw_item_from_record(guid: &str, pw: PasswordRecord, breached: bool) -> ListItem {
     // create the list item from the updated entry
     let mut desc = String::with_capacity(256);
     make_pw_name(&pw.description, &pw.username, &mut desc);
@@ -1955,6 +2504,10 @@ fn make_pw_item_from_record(guid: &str, pw: PasswordRecord) -> ListItem {
     extra.push_str("; ");
     extra.push_str(t!("vault.u2f.appinfo.authcount", locales::LANG));
     extra.push_str(&pw.count.to_string());
+    if breached {
+        extra.push_str("; ");
+        extra.push_str(t!("vault.breach.flag", locales::LANG));
+    }
     ListItem::new(
         desc.to_string(), // these allocs will be slow, but we do it only once on boot
         extra.to_string(),
@@ -42,10 +42,22 @@ pub enum Error {
 
 /// Keys of the environment store reserved for the attestation store.
 pub const STORAGE_KEYS: &[usize] = &[1, 2];
+/// Keys of the environment store reserved for an enterprise attestation, for attestation stores
+/// that keep it apart from the batch attestation.
+pub const ENTERPRISE_STORAGE_KEYS: &[usize] = &[4, 5];
 
 pub fn helper_get(env: &mut impl Env) -> Result<Option<Attestation>, Error> {
-    let private_key = env.store().find(PRIVATE_KEY_STORAGE_KEY)?;
-    let certificate = env.store().find(CERTIFICATE_STORAGE_KEY)?;
+    get_at(env, STORAGE_KEYS)
+}
+
+/// Like `helper_get`, for an enterprise attestation kept apart from the batch attestation.
+pub fn helper_get_enterprise(env: &mut impl Env) -> Result<Option<Attestation>, Error> {
+    get_at(env, ENTERPRISE_STORAGE_KEYS)
+}
+
+fn get_at(env: &mut impl Env, keys: &[usize]) -> Result<Option<Attestation>, Error> {
+    let private_key = env.store().find(keys[0])?;
+    let certificate = env.store().find(keys[1])?;
     let (private_key, certificate) = match (private_key, certificate) {
         (Some(x), Some(y)) => (x, y),
         (None, None) => return Ok(None),
@@ -61,22 +73,34 @@ pub fn helper_get(env: &mut impl Env) -> Result<Option<Attestation>, Error> {
 }
 
 pub fn helper_set(env: &mut impl Env, attestation: Option<&Attestation>) -> Result<(), Error> {
+    set_at(env, STORAGE_KEYS, attestation)
+}
+
+/// Like `helper_set`, for an enterprise attestation kept apart from the batch attestation.
+pub fn helper_set_enterprise(
+    env: &mut impl Env,
+    attestation: Option<&Attestation>,
+) -> Result<(), Error> {
+    set_at(env, ENTERPRISE_STORAGE_KEYS, attestation)
+}
+
+fn set_at(
+    env: &mut impl Env,
+    keys: &[usize],
+    attestation: Option<&Attestation>,
+) -> Result<(), Error> {
     let updates = match attestation {
         None => [
-            StoreUpdate::Remove {
-                key: PRIVATE_KEY_STORAGE_KEY,
-            },
-            StoreUpdate::Remove {
-                key: CERTIFICATE_STORAGE_KEY,
-            },
+            StoreUpdate::Remove { key: keys[0] },
+            StoreUpdate::Remove { key: keys[1] },
         ],
         Some(attestation) => [
             StoreUpdate::Insert {
-                key: PRIVATE_KEY_STORAGE_KEY,
+                key: keys[0],
                 value: &attestation.private_key[..],
             },
             StoreUpdate::Insert {
-                key: CERTIFICATE_STORAGE_KEY,
+                key: keys[1],
                 value: &attestation.certificate[..],
             },
         ],
@@ -84,9 +108,6 @@ pub fn helper_set(env: &mut impl Env, attestation: Option<&Attestation>) -> Resu
     Ok(env.store().transaction(&updates)?)
 }
 
-const PRIVATE_KEY_STORAGE_KEY: usize = STORAGE_KEYS[0];
-const CERTIFICATE_STORAGE_KEY: usize = STORAGE_KEYS[1];
-
 impl From<StoreError> for Error {
     fn from(error: StoreError) -> Self {
         match error {
@@ -0,0 +1,280 @@
+//! Offline breach checking of stored passwords.
+//!
+//! Passwords are checked against a bloom filter of breached password hashes (ie built from the
+//! HIBP "Pwned Passwords" SHA-1 list) that the user downloads once into the PDDB. The check itself
+//! never touches the network, so no part of a password or its hash ever leaves the device.
+//!
+//! The filter is a single PDDB key, laid out as:
+//!
+//! ```text
+//! offset  size  field
+//! 0       4     magic, "XBLM"
+//! 4       1     version, 1
+//! 5       1     k, the number of bits set per hash
+//! 6       2     reserved, 0
+//! 8       8     m, the number of bits in the filter, u64 little endian
+//! 16      m/8   the bits; bit i is (1 << (i % 8)) of byte i / 8
+//! ```
+//!
+//! A password with SHA-1 digest `d` sets bits `(h1 + i * h2) mod m` for `i` in `0..k`, where `h1` and
+//! `h2` are bytes 0..8 and 8..16 of `d` read as little endian u64 (with wrapping arithmetic). Since
+//! only the digest is needed, a filter can be built straight from a published list of SHA-1 hashes.
+//! A filter of the most common few million breached passwords at a 1% false positive rate fits in a
+//! few MiB; the full list does not fit in the PDDB. Filters larger than `MAX_FILTER_LEN` are refused.
+
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::sync::Arc;
+
+use pddb::Pddb;
+use sha1::{Digest, Sha1};
+use tls::xtls::TlsConnector;
+
+/// PDDB dict for the breach filter and the results of the last check
+const VAULT_BREACH_DICT: &str = "vault.breach";
+/// the filter, kept in the system basis as it holds nothing private
+const FILTER_KEY: &str = "filter";
+/// guids of the password entries found in the filter, one per line
+const FLAGGED_KEY: &str = "flagged";
+/// the URL the filter was last downloaded from
+const URL_KEY: &str = "url";
+
+const MAGIC: &[u8; 4] = b"XBLM";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 16;
+/// more bits per hash than this only ever comes from a corrupt header
+const MAX_HASHES: u8 = 32;
+/// the largest filter that is accepted, header included; a few million passwords at a 1% false positive
+/// rate take under 8 MiB
+const MAX_FILTER_LEN: u64 = 16 * 1024 * 1024;
+const DOWNLOAD_CHUNK: usize = 4096;
+
+/// Parses a filter header, returning the number of bits in the filter and the number of bits set per hash
+fn parse_header(header: &[u8]) -> Option<(u64, u8)> {
+    if header.len() < HEADER_LEN || &header[..4] != MAGIC || header[4] != VERSION {
+        return None;
+    }
+    let hashes = header[5];
+    let bits = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    if hashes == 0 || hashes > MAX_HASHES || bits == 0 {
+        return None;
+    }
+    if filter_len(bits)? > MAX_FILTER_LEN {
+        log::warn!("breach filter of {} bits is larger than the largest accepted", bits);
+        return None;
+    }
+    Some((bits, hashes))
+}
+
+/// The length in bytes of a filter of `bits` bits, header included; None if it doesn't fit a u64
+fn filter_len(bits: u64) -> Option<u64> { (bits / 8 + (bits % 8 != 0) as u64).checked_add(HEADER_LEN as u64) }
+
+/// The bits a password sets in a filter of `bits` bits
+fn bit_indices(password: &str, bits: u64, hashes: u8) -> impl Iterator<Item = u64> {
+    let digest = Sha1::digest(password.as_bytes());
+    let h1 = u64::from_le_bytes(digest[0..8].try_into().unwrap());
+    let h2 = u64::from_le_bytes(digest[8..16].try_into().unwrap());
+    (0..hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % bits)
+}
+
+/// A breach filter stored in the PDDB. Bits are read from the PDDB key as they are needed, so the
+/// filter is never loaded into RAM.
+pub(crate) struct Filter<'a> {
+    key: pddb::PddbKey<'a>,
+    bits: u64,
+    hashes: u8,
+}
+
+impl<'a> Filter<'a> {
+    /// Opens the stored filter; None if there is none, or it is not a valid filter
+    pub(crate) fn open(pddb: &'a Pddb) -> Option<Filter<'a>> {
+        let mut key = pddb
+            .get(
+                VAULT_BREACH_DICT,
+                FILTER_KEY,
+                Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS),
+                false,
+                false,
+                None,
+                None::<fn()>,
+            )
+            .ok()?;
+        let mut header = [0u8; HEADER_LEN];
+        key.read_exact(&mut header).ok()?;
+        let (bits, hashes) = parse_header(&header)?;
+        if key.seek(SeekFrom::End(0)).ok()? < filter_len(bits)? {
+            log::warn!("breach filter is truncated");
+            return None;
+        }
+        Some(Filter { key, bits, hashes })
+    }
+
+    /// True if the password is (probably) in the filter. False positives happen at the rate the filter
+    /// was built for; there are no false negatives.
+    pub(crate) fn contains(&mut self, password: &str) -> Result<bool> {
+        let mut byte = [0u8; 1];
+        for bit in bit_indices(password, self.bits, self.hashes) {
+            self.key.seek(SeekFrom::Start(HEADER_LEN as u64 + bit / 8))?;
+            self.key.read_exact(&mut byte)?;
+            if byte[0] & (1 << (bit % 8)) == 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Downloads a filter into the PDDB, replacing any stored filter. The size the filter claims in its header
+/// is checked against `MAX_FILTER_LEN` and the free space in the PDDB before anything is stored.
+///
+/// # Arguments
+///
+/// * `pddb` - the PDDB to store the filter in
+/// * `url` - where to download the filter from
+/// * `progress` - called with the number of bytes received so far, and the total expected
+pub(crate) fn download(pddb: &Pddb, url: &str, mut progress: impl FnMut(u64, u64)) -> Result<()> {
+    let agent = ureq::builder().tls_connector(Arc::new(TlsConnector {})).build();
+    let response = agent.get(url).call().map_err(|e| Error::new(ErrorKind::Other, e.to_string()))?;
+    let mut reader = response.into_reader();
+    let mut header = [0u8; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    let (bits, _hashes) =
+        parse_header(&header).ok_or(Error::new(ErrorKind::InvalidData, "not a breach filter"))?;
+    let total = filter_len(bits).ok_or(Error::new(ErrorKind::InvalidData, "not a breach filter"))?;
+    // the stored filter is removed before the new one is written, so its space counts as free
+    let stored = Filter::open(pddb).and_then(|filter| filter_len(filter.bits)).unwrap_or(0);
+    if total > (pddb.free_space()? as u64).saturating_add(stored) {
+        return Err(Error::new(ErrorKind::OutOfMemory, "not enough free space in the PDDB for the filter"));
+    }
+
+    remove(pddb);
+    let mut key = pddb.get(
+        VAULT_BREACH_DICT,
+        FILTER_KEY,
+        Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS),
+        true,
+        true,
+        Some(total as usize),
+        None::<fn()>,
+    )?;
+    key.write_all(&header)?;
+    let mut received = HEADER_LEN as u64;
+    let mut chunk = vec![0u8; DOWNLOAD_CHUNK];
+    while received < total {
+        let want = DOWNLOAD_CHUNK.min((total - received) as usize);
+        let result = reader.read_exact(&mut chunk[..want]).and_then(|_| key.write_all(&chunk[..want]));
+        if let Err(e) = result {
+            // don't leave a truncated filter behind
+            drop(key);
+            remove(pddb);
+            return Err(e);
+        }
+        received += want as u64;
+        progress(received, total);
+    }
+    drop(key);
+    pddb.sync()?;
+    log::info!("downloaded a breach filter of {} bits from {}", bits, url);
+    Ok(())
+}
+
+/// Checks a single password entry as it is added or edited, updating the stored results of the last
+/// check. Nothing is done if there is no stored filter.
+///
+/// # Arguments
+///
+/// * `pddb` - the PDDB holding the filter
+/// * `flagged` - the guids found in the filter so far, as returned by `load_flagged()`
+/// * `previous` - the guid the entry had before it was edited, if any
+/// * `guid` - the guid of the entry
+/// * `password` - the password of the entry
+pub(crate) fn recheck(
+    pddb: &Pddb,
+    flagged: &mut HashSet<String>,
+    previous: Option<&str>,
+    guid: &str,
+    password: &str,
+) {
+    let mut filter = match Filter::open(pddb) {
+        Some(filter) => filter,
+        None => return,
+    };
+    let mut changed = previous.map(|previous| flagged.remove(previous)).unwrap_or(false);
+    match filter.contains(password) {
+        Ok(true) => changed |= flagged.insert(guid.to_string()),
+        Ok(false) => changed |= flagged.remove(guid),
+        Err(e) => log::warn!("couldn't read the breach filter: {:?}", e),
+    }
+    if changed {
+        save_flagged(pddb, flagged);
+    }
+}
+
+/// Removes the stored filter and the results of the last check
+pub(crate) fn remove(pddb: &Pddb) {
+    pddb.delete_key(VAULT_BREACH_DICT, FILTER_KEY, Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS)).ok();
+    pddb.delete_key(VAULT_BREACH_DICT, FLAGGED_KEY, None).ok();
+    pddb.sync().ok();
+}
+
+/// The guids of the password entries found in the filter by the last check
+pub(crate) fn load_flagged(pddb: &Pddb) -> HashSet<String> {
+    let mut flagged = String::new();
+    if let Ok(mut key) = pddb.get(VAULT_BREACH_DICT, FLAGGED_KEY, None, false, false, None, None::<fn()>) {
+        key.read_to_string(&mut flagged).ok();
+    }
+    flagged.lines().map(|guid| guid.to_string()).collect()
+}
+
+/// Stores the guids of the password entries found in the filter. They are kept alongside the passwords
+/// (in the most recently unlocked basis), as they reveal which passwords are weak.
+pub(crate) fn save_flagged(pddb: &Pddb, flagged: &HashSet<String>) {
+    // delete key first to ensure data in a prior longer key is gone
+    pddb.delete_key(VAULT_BREACH_DICT, FLAGGED_KEY, None).ok();
+    if flagged.is_empty() {
+        pddb.sync().ok();
+        return;
+    }
+    let mut list = String::new();
+    for guid in flagged.iter() {
+        list.push_str(guid);
+        list.push('\n');
+    }
+    match pddb.get(VAULT_BREACH_DICT, FLAGGED_KEY, None, true, true, None, None::<fn()>) {
+        Ok(mut key) => match key.write_all(list.as_bytes()) {
+            Ok(()) => {
+                pddb.sync().ok();
+            }
+            Err(e) => log::warn!("error writing {}:{} {:?}", VAULT_BREACH_DICT, FLAGGED_KEY, e),
+        },
+        Err(e) => log::warn!("failed to set pddb {}:{} {:?}", VAULT_BREACH_DICT, FLAGGED_KEY, e),
+    }
+}
+
+/// The URL the filter was last downloaded from
+pub(crate) fn load_url(pddb: &Pddb) -> Option<String> {
+    let mut url = String::new();
+    let mut key = pddb
+        .get(
+            VAULT_BREACH_DICT,
+            URL_KEY,
+            Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS),
+            false,
+            false,
+            None,
+            None::<fn()>,
+        )
+        .ok()?;
+    key.read_to_string(&mut url).ok()?;
+    Some(url).filter(|url| !url.is_empty())
+}
+
+pub(crate) fn save_url(pddb: &Pddb, url: &str) {
+    let basis = Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS);
+    pddb.delete_key(VAULT_BREACH_DICT, URL_KEY, basis).ok();
+    if let Ok(mut key) = pddb.get(VAULT_BREACH_DICT, URL_KEY, basis, true, true, None, None::<fn()>) {
+        key.write_all(url.as_bytes()).ok();
+        pddb.sync().ok();
+    }
+}
@@ -568,6 +568,8 @@ impl TryFrom<cbor::Value> for AuthenticatorCredentialManagementParameters {
 pub struct AuthenticatorVendorConfigureParameters {
     pub lockdown: bool,
     pub attestation_material: Option<AuthenticatorAttestationMaterial>,
+    /// The attestation material is an enterprise attestation, rather than the batch attestation.
+    pub enterprise: bool,
 }
 
 impl TryFrom<cbor::Value> for AuthenticatorVendorConfigureParameters {
@@ -578,15 +580,18 @@ impl TryFrom<cbor::Value> for AuthenticatorVendorConfigureParameters {
             let {
                 0x01 => lockdown,
                 0x02 => attestation_material,
+                0x03 => enterprise,
             } = extract_map(cbor_value)?;
         }
         let lockdown = lockdown.map_or(Ok(false), extract_bool)?;
         let attestation_material = attestation_material
             .map(AuthenticatorAttestationMaterial::try_from)
             .transpose()?;
+        let enterprise = enterprise.map_or(Ok(false), extract_bool)?;
         Ok(AuthenticatorVendorConfigureParameters {
             lockdown,
             attestation_material,
+            enterprise,
         })
     }
 }
@@ -990,7 +995,8 @@ mod test {
             Ok(Command::AuthenticatorVendorConfigure(
                 AuthenticatorVendorConfigureParameters {
                     lockdown: true,
-                    attestation_material: None
+                    attestation_material: None,
+                    enterprise: false,
                 }
             ))
         );
@@ -1051,6 +1057,27 @@ mod test {
                     certificate: dummy_cert.to_vec(),
                     private_key: dummy_pkey
                 }),
+                enterprise: false,
+            })
+        );
+
+        // Valid enterprise attestation
+        let cbor_value = cbor_map! {
+            0x02 => cbor_map! {
+                0x01 => dummy_cert,
+                0x02 => dummy_pkey
+            },
+            0x03 => true,
+        };
+        assert_eq!(
+            AuthenticatorVendorConfigureParameters::try_from(cbor_value),
+            Ok(AuthenticatorVendorConfigureParameters {
+                lockdown: false,
+                attestation_material: Some(AuthenticatorAttestationMaterial {
+                    certificate: dummy_cert.to_vec(),
+                    private_key: dummy_pkey
+                }),
+                enterprise: true,
             })
         );
     }
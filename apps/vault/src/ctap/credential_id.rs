@@ -264,6 +264,7 @@ pub fn decrypt_credential_id(
         user_icon: None,
         cred_blob: credential_source.cred_blob,
         large_blob_key: None,
+        signature_counter: None,
     }))
 }
 
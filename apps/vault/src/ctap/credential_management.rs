@@ -32,6 +32,8 @@ use std::vec;
 use std::vec::Vec;
 use ctap_crypto::sha256::Sha256;
 use ctap_crypto::Hash256;
+#[cfg(feature="xous")]
+use locales::t;
 
 /// Generates a set with all existing RP IDs.
 fn get_stored_rp_ids(env: &mut impl Env) -> Result<BTreeSet<String>, Ctap2StatusCode> {
@@ -82,6 +84,7 @@ fn enumerate_credentials_response(
         user_icon,
         cred_blob: _,
         large_blob_key,
+        signature_counter: _,
     } = credential;
     let user = PublicKeyCredentialUserEntity {
         user_id: user_handle,
@@ -227,12 +230,31 @@ fn process_delete_credential(
     env: &mut impl Env,
     client_pin: &mut ClientPin,
     sub_command_params: CredentialManagementSubCommandParameters,
+    channel: Channel,
 ) -> Result<(), Ctap2StatusCode> {
     let credential_id = sub_command_params
         .credential_id
         .ok_or(Ctap2StatusCode::CTAP2_ERR_MISSING_PARAMETER)?
         .key_id;
     check_rp_id_permissions(env, client_pin, &credential_id)?;
+    // A PIN token is enough to delete a resident key under CTAP2.1, but a key can't be
+    // recovered once it's gone, so the user confirms each deletion on the device.
+    #[cfg(feature="xous")]
+    {
+        let (_, credential) = storage::find_credential_item(env, &credential_id)?;
+        let mut desc = String::from(t!("vault.fido.delete_credential", locales::LANG));
+        desc.push_str(
+            &format!("\n\nRelying Party: {}\nUser name: {}",
+                credential.rp_id,
+                credential.user_display_name
+                    .or(credential.user_name)
+                    .unwrap_or("*Unspecified*".to_string()),
+            )
+        );
+        super::check_user_presence(env, channel, Some(desc))?;
+    }
+    #[cfg(not(feature="xous"))]
+    let _ = channel;
     storage::delete_credential(env, &credential_id)
 }
 
@@ -344,6 +366,7 @@ pub fn process_credential_management(
                 env,
                 client_pin,
                 sub_command_params.ok_or(Ctap2StatusCode::CTAP2_ERR_MISSING_PARAMETER)?,
+                channel,
             )?;
             None
         }
@@ -386,6 +409,7 @@ mod test {
             user_icon: Some("icon".to_string()),
             cred_blob: None,
             large_blob_key: None,
+            signature_counter: None,
         }
     }
 
@@ -289,6 +289,9 @@ pub struct MakeCredentialExtensions {
     pub min_pin_length: bool,
     pub cred_blob: Option<Vec<u8>>,
     pub large_blob_key: Option<bool>,
+    // CTAP 2.2 hmac-secret-mc: evaluates hmac-secret for the new credential, as used by the
+    // WebAuthn PRF extension at registration.
+    pub hmac_secret_mc: Option<GetAssertionHmacSecretInput>,
 }
 
 impl TryFrom<cbor::Value> for MakeCredentialExtensions {
@@ -302,6 +305,7 @@ impl TryFrom<cbor::Value> for MakeCredentialExtensions {
                 "hmac-secret" => hmac_secret,
                 "largeBlobKey" => large_blob_key,
                 "minPinLength" => min_pin_length,
+                "hmac-secret-mc" => hmac_secret_mc,
             } = extract_map(cbor_value)?;
         }
 
@@ -317,12 +321,16 @@ impl TryFrom<cbor::Value> for MakeCredentialExtensions {
                 return Err(Ctap2StatusCode::CTAP2_ERR_INVALID_OPTION);
             }
         }
+        let hmac_secret_mc = hmac_secret_mc
+            .map(GetAssertionHmacSecretInput::try_from)
+            .transpose()?;
         Ok(Self {
             hmac_secret,
             cred_protect,
             min_pin_length,
             cred_blob,
             large_blob_key,
+            hmac_secret_mc,
         })
     }
 }
@@ -596,6 +604,9 @@ pub struct PublicKeyCredentialSource {
     pub user_icon: Option<String>,
     pub cred_blob: Option<Vec<u8>>,
     pub large_blob_key: Option<Vec<u8>>,
+    /// The credential's own signature counter. It is `None` for credentials that aren't stored,
+    /// and for those stored before credentials had their own counter.
+    pub signature_counter: Option<u32>,
 }
 
 // We serialize credentials for the persistent storage using CBOR maps. Each field of a credential
@@ -614,6 +625,7 @@ enum PublicKeyCredentialSourceField {
     CredBlob = 10,
     LargeBlobKey = 11,
     PrivateKey = 12,
+    SignatureCounter = 13,
     // When a field is removed, its tag should be reserved and not used for new fields. We document
     // those reserved tags below.
     // Reserved tags:
@@ -640,6 +652,8 @@ impl From<PublicKeyCredentialSource> for cbor::Value {
             PublicKeyCredentialSourceField::CredBlob => credential.cred_blob,
             PublicKeyCredentialSourceField::LargeBlobKey => credential.large_blob_key,
             PublicKeyCredentialSourceField::PrivateKey => credential.private_key,
+            PublicKeyCredentialSourceField::SignatureCounter =>
+                credential.signature_counter.map(|counter| counter as u64),
         }
     }
 }
@@ -662,6 +676,7 @@ impl TryFrom<cbor::Value> for PublicKeyCredentialSource {
                 PublicKeyCredentialSourceField::CredBlob => cred_blob,
                 PublicKeyCredentialSourceField::LargeBlobKey => large_blob_key,
                 PublicKeyCredentialSourceField::PrivateKey => private_key,
+                PublicKeyCredentialSourceField::SignatureCounter => signature_counter,
             } = extract_map(cbor_value)?;
         }
 
@@ -677,6 +692,12 @@ impl TryFrom<cbor::Value> for PublicKeyCredentialSource {
         let user_icon = user_icon.map(extract_text_string).transpose()?;
         let cred_blob = cred_blob.map(extract_byte_string).transpose()?;
         let large_blob_key = large_blob_key.map(extract_byte_string).transpose()?;
+        let signature_counter = signature_counter
+            .map(extract_unsigned)
+            .transpose()?
+            .map(u32::try_from)
+            .transpose()
+            .map_err(|_| Ctap2StatusCode::CTAP2_ERR_VENDOR_INTERNAL_ERROR)?;
 
         // Parse the private key from the deprecated field if necessary.
         let ecdsa_private_key = ecdsa_private_key.map(extract_byte_string).transpose()?;
@@ -712,6 +733,7 @@ impl TryFrom<cbor::Value> for PublicKeyCredentialSource {
             user_icon,
             cred_blob,
             large_blob_key,
+            signature_counter,
         })
     }
 }
@@ -1730,6 +1752,37 @@ mod test {
             min_pin_length: true,
             cred_blob: Some(vec![0xCB]),
             large_blob_key: Some(true),
+            hmac_secret_mc: None,
+        };
+        assert_eq!(extensions, Ok(expected_extensions));
+    }
+
+    #[test]
+    fn test_from_make_credential_extensions_hmac_secret_mc() {
+        let mut env = TestEnv::new();
+        let sk = ctap_crypto::ecdh::SecKey::gensk(env.rng());
+        let pk = sk.genpk();
+        let cose_key = CoseKey::from(pk);
+        let cbor_extensions = cbor_map! {
+            "hmac-secret" => true,
+            "hmac-secret-mc" => cbor_map! {
+                1 => cbor::Value::from(cose_key.clone()),
+                2 => vec![0x02; 32],
+                3 => vec![0x03; 16],
+                4 => 2,
+            },
+        };
+        let extensions = MakeCredentialExtensions::try_from(cbor_extensions);
+        let expected_input = GetAssertionHmacSecretInput {
+            key_agreement: cose_key,
+            salt_enc: vec![0x02; 32],
+            salt_auth: vec![0x03; 16],
+            pin_uv_auth_protocol: PinUvAuthProtocol::V2,
+        };
+        let expected_extensions = MakeCredentialExtensions {
+            hmac_secret: true,
+            hmac_secret_mc: Some(expected_input),
+            ..Default::default()
         };
         assert_eq!(extensions, Ok(expected_extensions));
     }
@@ -2126,6 +2179,7 @@ mod test {
             user_icon: None,
             cred_blob: None,
             large_blob_key: None,
+            signature_counter: None,
         };
 
         assert_eq!(
@@ -2188,6 +2242,16 @@ mod test {
             ..credential
         };
 
+        assert_eq!(
+            PublicKeyCredentialSource::try_from(cbor::Value::from(credential.clone())),
+            Ok(credential.clone())
+        );
+
+        let credential = PublicKeyCredentialSource {
+            signature_counter: Some(0x1234_5678),
+            ..credential
+        };
+
         assert_eq!(
             PublicKeyCredentialSource::try_from(cbor::Value::from(credential.clone())),
             Ok(credential)
@@ -2212,6 +2276,7 @@ mod test {
             user_icon: None,
             cred_blob: None,
             large_blob_key: None,
+            signature_counter: None,
         };
 
         let source_cbor = cbor_map! {
@@ -2244,6 +2309,7 @@ mod test {
             user_icon: None,
             cred_blob: None,
             large_blob_key: None,
+            signature_counter: None,
         };
 
         let source_cbor = cbor_map! {
@@ -33,7 +33,7 @@ use std::time::Duration;
 use enum_iterator::IntoEnumIterator;
 
 use crate::vault_api::{
-    COMMAND_BACKUP_TOTP_CODES, COMMAND_RESTORE_TOTP_CODES, COMMAND_RESET_SESSION
+    COMMAND_BACKUP_TOTP_CODES, COMMAND_RESTORE_TOTP_CODES, COMMAND_RESET_SESSION, COMMAND_SSH_AGENT
 };
 
 pub type HidPacket = [u8; 64];
@@ -59,6 +59,7 @@ pub enum CtapHidCommand {
     RestoreTotpCodes = COMMAND_RESTORE_TOTP_CODES as _,
     BackupTotpCodes = COMMAND_BACKUP_TOTP_CODES as _,
     ResetSession = COMMAND_RESET_SESSION as _,
+    SshAgent = COMMAND_SSH_AGENT as _,
 }
 
 impl From<u8> for CtapHidCommand {
@@ -75,6 +76,7 @@ impl From<u8> for CtapHidCommand {
             x if x == CtapHidCommand::RestoreTotpCodes as u8 => CtapHidCommand::RestoreTotpCodes,
             x if x == CtapHidCommand::BackupTotpCodes as u8 => CtapHidCommand::BackupTotpCodes,
             x if x == CtapHidCommand::ResetSession as u8 => CtapHidCommand::ResetSession,
+            x if x == CtapHidCommand::SshAgent as u8 => CtapHidCommand::SshAgent,
             // This includes the actual error code 0x3F. Error is not used for incoming packets in
             // the specification, so we can safely reuse it for unknown bytes.
             _ => CtapHidCommand::Error,
@@ -328,7 +330,8 @@ impl CtapHid {
             CtapHidCommand::Wink => Some(message),
             CtapHidCommand::BackupTotpCodes |
             CtapHidCommand::RestoreTotpCodes |
-            CtapHidCommand::ResetSession => {
+            CtapHidCommand::ResetSession |
+            CtapHidCommand::SshAgent => {
                 Some(message)
             }
             _ => {
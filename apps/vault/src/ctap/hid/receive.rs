@@ -77,6 +77,17 @@ impl MessageAssembler {
         env: &mut impl Env,
         packet: &HidPacket,
         timestamp: Instant,
+    ) -> Result<Option<Message>, (ChannelID, CtapHidError)> {
+        self.parse_packet_with_limit(env.customization().max_msg_size(), packet, timestamp)
+    }
+
+    // Same as parse_packet(), with the longest message to accept given directly instead of taken from
+    // the environment's customization. This lets the fuzzer run the assembler without an Env.
+    pub fn parse_packet_with_limit(
+        &mut self,
+        max_msg_size: usize,
+        packet: &HidPacket,
+        timestamp: Instant,
     ) -> Result<Option<Message>, (ChannelID, CtapHidError)> {
         // TODO: Support non-full-speed devices (i.e. packet len != 64)? This isn't recommended by
         // section 8.8.1
@@ -99,7 +110,7 @@ impl MessageAssembler {
             // Expecting an initialization packet.
             match processed_packet {
                 ProcessedPacket::InitPacket { cmd, len, data } => {
-                    self.parse_init_packet(env, cid, cmd, len, data, timestamp)
+                    self.parse_init_packet(max_msg_size, cid, cmd, len, data, timestamp)
                 }
                 ProcessedPacket::ContinuationPacket { .. } => {
                     // CTAP specification (version 20190130) section 8.1.5.4
@@ -121,7 +132,7 @@ impl MessageAssembler {
                 ProcessedPacket::InitPacket { cmd, len, data } => {
                     self.reset();
                     if cmd == CtapHidCommand::Init as u8 {
-                        self.parse_init_packet(env, cid, cmd, len, data, timestamp)
+                        self.parse_init_packet(max_msg_size, cid, cmd, len, data, timestamp)
                     } else {
                         Err((cid, CtapHidError::InvalidSeq))
                     }
@@ -145,7 +156,7 @@ impl MessageAssembler {
 
     fn parse_init_packet(
         &mut self,
-        env: &mut impl Env,
+        max_msg_size: usize,
         cid: ChannelID,
         cmd: u8,
         len: usize,
@@ -154,7 +165,7 @@ impl MessageAssembler {
     ) -> Result<Option<Message>, (ChannelID, CtapHidError)> {
         // Reject invalid lengths early to reduce the risk of running out of memory.
         // TODO: also reject invalid commands early?
-        if len > env.customization().max_msg_size() {
+        if len > max_msg_size {
             return Err((cid, CtapHidError::InvalidLen));
         }
         self.cid = cid;
@@ -68,7 +68,8 @@ impl MainHid {
             match processed_message.cmd {
                 CtapHidCommand::RestoreTotpCodes |
                 CtapHidCommand::BackupTotpCodes |
-                CtapHidCommand::ResetSession => {
+                CtapHidCommand::ResetSession |
+                CtapHidCommand::SshAgent => {
                     HidIterType::Vendor(processed_message)
                 }
                 _ => {
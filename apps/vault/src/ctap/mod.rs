@@ -223,6 +223,12 @@ fn truncate_to_char_boundary(s: &str, mut max: usize) -> &str {
     }
 }
 
+// Overwrites the signature counter of authenticator data from generate_auth_data. The counter
+// follows the 32 byte RP ID hash and the flags byte.
+fn write_signature_counter(auth_data: &mut [u8], signature_counter: u32) {
+    BigEndian::write_u32(&mut auth_data[33..37], signature_counter);
+}
+
 // Sends keepalive packet during user presence checking. If user agent replies with CANCEL response,
 // returns Err(UserPresenceError::Canceled).
 #[cfg(not(feature="xous"))]
@@ -935,6 +941,7 @@ impl CtapState {
                     .map(|s| truncate_to_char_boundary(&s, 64).to_string()),
                 cred_blob,
                 large_blob_key: large_blob_key.clone(),
+                signature_counter: Some(INITIAL_SIGNATURE_COUNTER),
             };
             storage::store_credential(env, credential_source)?;
             random_id
@@ -949,6 +956,10 @@ impl CtapState {
         };
 
         let mut auth_data = self.generate_auth_data(env, &rp_id_hash, flags)?;
+        if options.rk && env.customization().use_signature_counter() {
+            // A stored credential starts its own signature counter.
+            write_signature_counter(&mut auth_data, INITIAL_SIGNATURE_COUNTER);
+        }
         auth_data.extend(&storage::aaguid(env)?);
         // The length is fixed to 0x20 or 0x80 and fits one byte.
         if credential_id.len() > 0xFF {
@@ -969,12 +980,26 @@ impl CtapState {
             } else {
                 None
             };
+            // hmac-secret-mc is only evaluated alongside hmac-secret, so the credential keeps
+            // answering hmac-secret requests in later assertions.
+            let hmac_secret_mc_output = match extensions.hmac_secret_mc {
+                Some(hmac_secret_input) if extensions.hmac_secret => {
+                    let cred_random = self.generate_cred_random(env, &private_key, has_uv)?;
+                    Some(self.client_pin.process_hmac_secret(
+                        env.rng(),
+                        hmac_secret_input,
+                        &cred_random,
+                    )?)
+                }
+                _ => None,
+            };
             let cred_protect_output = extensions.cred_protect.and(cred_protect_policy);
             let extensions_output = cbor_map_options! {
                 "credBlob" => cred_blob_output,
                 "credProtect" => cred_protect_output,
                 "hmac-secret" => hmac_secret_output,
                 "minPinLength" => min_pin_length_output,
+                "hmac-secret-mc" => hmac_secret_mc_output,
             };
             cbor_write(extensions_output, &mut auth_data)?;
         }
@@ -1056,6 +1081,19 @@ impl CtapState {
             has_uv,
         } = assertion_input;
 
+        // Stored credentials count their own signatures, so that relying parties can't correlate
+        // them through the global counter. Other credentials keep reporting the global counter.
+        if env.customization().use_signature_counter() {
+            let increment = env.rng().gen_uniform_u32x8()[0] % 8 + 1;
+            if let Some(signature_counter) = storage::incr_credential_signature_counter(
+                env,
+                &credential.credential_id,
+                increment,
+            )? {
+                write_signature_counter(&mut auth_data, signature_counter);
+            }
+        }
+
         // Process extensions.
         if extensions.hmac_secret.is_some() || extensions.cred_blob {
             let encrypted_output = if let Some(hmac_secret_input) = extensions.hmac_secret {
@@ -1353,6 +1391,7 @@ impl CtapState {
                     String::from("minPinLength"),
                     String::from("credBlob"),
                     String::from("largeBlobKey"),
+                    String::from("hmac-secret-mc"),
                 ]),
                 aaguid: storage::aaguid(env)?,
                 options: Some(options),
@@ -1442,21 +1481,34 @@ impl CtapState {
         params: AuthenticatorVendorConfigureParameters,
         channel: Channel,
     ) -> Result<ResponseData, Ctap2StatusCode> {
+        // Lockdown only considers the batch attestation.
+        if params.enterprise && params.lockdown {
+            return Err(Ctap2StatusCode::CTAP1_ERR_INVALID_PARAMETER);
+        }
         if params.attestation_material.is_some() || params.lockdown {
             #[cfg(feature="xous")]
             {
+                // An enterprise attestation identifies this device to the enterprise's RPs, so the
+                // user is told which kind of attestation they are letting in.
+                let prompt = if params.enterprise {
+                    t!("vault.fido.enterprise_attestation_configure", locales::LANG)
+                } else {
+                    t!("vault.u2f.vendor_configure", locales::LANG)
+                };
                 check_user_presence(env, channel, Some(
-                    format!("{}\n{:x?}",
-                        t!("vault.u2f.vendor_configure", locales::LANG),
-                        channel
-                    )
+                    format!("{}\n{:x?}", prompt, channel)
                 ))?;
             }
             #[cfg(not(feature="xous"))]
             check_user_presence(env, channel)?;
         }
-        // This command is for U2F support and we use the batch attestation there.
-        let attestation_id = attestation_store::Id::Batch;
+        // This command is for U2F support and we use the batch attestation there, unless the
+        // material is for enterprise attestation.
+        let attestation_id = if params.enterprise {
+            attestation_store::Id::Enterprise
+        } else {
+            attestation_store::Id::Batch
+        };
 
         // Sanity checks
         let current_attestation = env.attestation_store().get(&attestation_id)?;
@@ -1540,13 +1592,17 @@ impl CtapState {
         let mut auth_data = vec![];
         auth_data.extend(rp_id_hash);
         auth_data.push(flag_byte);
-        // The global counter is only increased if use_signature_counter() is true.
+        // The global counter is only increased if use_signature_counter() is true,
+        // and a zero counter is reported otherwise. Stored credentials replace it with their own
+        // counter.
         // It uses a big-endian representation.
         let mut signature_counter = [0u8; 4];
-        BigEndian::write_u32(
-            &mut signature_counter,
-            storage::global_signature_counter(env)?,
-        );
+        if env.customization().use_signature_counter() {
+            BigEndian::write_u32(
+                &mut signature_counter,
+                storage::global_signature_counter(env)?,
+            );
+        }
         auth_data.extend(&signature_counter);
         Ok(auth_data)
     }
@@ -1653,6 +1709,7 @@ mod test {
                     String::from("minPinLength"),
                     String::from("credBlob"),
                     String::from("largeBlobKey"),
+                    String::from("hmac-secret-mc"),
                 ],
             0x03 => storage::aaguid(&mut env).unwrap(),
             0x04 => cbor_map_options! {
@@ -1854,6 +1911,7 @@ mod test {
             user_icon: None,
             cred_blob: None,
             large_blob_key: None,
+            signature_counter: None,
         };
         assert!(storage::store_credential(&mut env, excluded_credential_source).is_ok());
 
@@ -2487,6 +2545,17 @@ mod test {
         );
     }
 
+    // Returns the signature counter of the stored credential of `user_handle`.
+    fn stored_signature_counter(env: &mut TestEnv, user_handle: &[u8]) -> u32 {
+        let mut iter_result = Ok(());
+        let signature_counter = storage::iter_credentials(env, &mut iter_result)
+            .unwrap()
+            .find(|(_, credential)| credential.user_handle == user_handle)
+            .and_then(|(_, credential)| credential.signature_counter);
+        iter_result.unwrap();
+        signature_counter.unwrap()
+    }
+
     #[test]
     fn test_resident_process_get_assertion() {
         let mut env = TestEnv::new();
@@ -2515,7 +2584,47 @@ mod test {
             DUMMY_CHANNEL,
             Instant::new(0),
         );
-        let signature_counter = storage::global_signature_counter(&mut env).unwrap();
+        let signature_counter = stored_signature_counter(&mut env, &[0x1D]);
+        check_assertion_response(get_assertion_response, vec![0x1D], signature_counter, None);
+    }
+
+    #[test]
+    fn test_resident_signature_counter_is_per_credential() {
+        let mut env = TestEnv::new();
+        let mut ctap_state = CtapState::new(&mut env, Instant::new(0));
+
+        let make_credential_params = create_minimal_make_credential_parameters();
+        assert!(ctap_state
+            .process_make_credential(&mut env, make_credential_params, DUMMY_CHANNEL)
+            .is_ok());
+        // Signatures with other credentials only move the global counter.
+        for _ in 0..10 {
+            assert!(ctap_state
+                .increment_global_signature_counter(&mut env)
+                .is_ok());
+        }
+
+        let get_assertion_params = AuthenticatorGetAssertionParameters {
+            rp_id: String::from("example.com"),
+            client_data_hash: vec![0xCD],
+            allow_list: None,
+            extensions: GetAssertionExtensions::default(),
+            options: GetAssertionOptions {
+                up: false,
+                uv: false,
+            },
+            pin_uv_auth_param: None,
+            pin_uv_auth_protocol: None,
+        };
+        let get_assertion_response = ctap_state.process_get_assertion(
+            &mut env,
+            get_assertion_params,
+            DUMMY_CHANNEL,
+            Instant::new(0),
+        );
+        let signature_counter = stored_signature_counter(&mut env, &[0x1D]);
+        assert!(signature_counter > INITIAL_SIGNATURE_COUNTER);
+        assert!(signature_counter < storage::global_signature_counter(&mut env).unwrap());
         check_assertion_response(get_assertion_response, vec![0x1D], signature_counter, None);
     }
 
@@ -2631,6 +2740,63 @@ mod test {
         test_helper_process_get_assertion_hmac_secret(PinUvAuthProtocol::V2);
     }
 
+    #[test]
+    fn test_process_make_credential_hmac_secret_mc() {
+        let mut env = TestEnv::new();
+        let key_agreement_key = ctap_crypto::ecdh::SecKey::gensk(env.rng());
+        let mut ctap_state = CtapState::new(&mut env, Instant::new(0));
+
+        let client_pin_params = AuthenticatorClientPinParameters {
+            pin_uv_auth_protocol: PinUvAuthProtocol::V1,
+            sub_command: ClientPinSubCommand::GetKeyAgreement,
+            key_agreement: None,
+            pin_uv_auth_param: None,
+            new_pin_enc: None,
+            pin_hash_enc: None,
+            permissions: None,
+            permissions_rp_id: None,
+        };
+        let key_agreement_response =
+            ctap_state
+                .client_pin
+                .process_command(&mut env, client_pin_params, Instant::new(0));
+        let get_assertion_params = get_assertion_hmac_secret_params(
+            key_agreement_key,
+            key_agreement_response.unwrap(),
+            None,
+            PinUvAuthProtocol::V1,
+        );
+
+        let make_extensions = MakeCredentialExtensions {
+            hmac_secret: true,
+            hmac_secret_mc: get_assertion_params.extensions.hmac_secret.clone(),
+            ..Default::default()
+        };
+        let mut make_credential_params = create_minimal_make_credential_parameters();
+        make_credential_params.extensions = make_extensions;
+        let make_auth_data = match ctap_state
+            .process_make_credential(&mut env, make_credential_params, DUMMY_CHANNEL)
+            .unwrap()
+        {
+            ResponseData::AuthenticatorMakeCredential(response) => response.auth_data,
+            _ => panic!("Invalid response type"),
+        };
+        let get_auth_data = match ctap_state
+            .process_get_assertion(&mut env, get_assertion_params, DUMMY_CHANNEL, Instant::new(0))
+            .unwrap()
+        {
+            ResponseData::AuthenticatorGetAssertion(response) => response.auth_data,
+            _ => panic!("Invalid response type"),
+        };
+        // Both end with the encrypted output for the same salt. PIN protocol 1 encrypts without
+        // an IV, so the outputs are only equal if the same secret was derived.
+        let output_len = 32;
+        assert_eq!(
+            make_auth_data[make_auth_data.len() - output_len..],
+            get_auth_data[get_auth_data.len() - output_len..]
+        );
+    }
+
     fn test_helper_resident_process_get_assertion_hmac_secret(
         pin_uv_auth_protocol: PinUvAuthProtocol,
     ) {
@@ -2714,6 +2880,7 @@ mod test {
             user_icon: None,
             cred_blob: None,
             large_blob_key: None,
+            signature_counter: None,
         };
         assert!(storage::store_credential(&mut env, credential).is_ok());
 
@@ -2758,7 +2925,7 @@ mod test {
             DUMMY_CHANNEL,
             Instant::new(0),
         );
-        let signature_counter = storage::global_signature_counter(&mut env).unwrap();
+        let signature_counter = stored_signature_counter(&mut env, &[0x1D]);
         check_assertion_response(get_assertion_response, vec![0x1D], signature_counter, None);
 
         let credential = PublicKeyCredentialSource {
@@ -2774,6 +2941,7 @@ mod test {
             user_icon: None,
             cred_blob: None,
             large_blob_key: None,
+            signature_counter: None,
         };
         assert!(storage::store_credential(&mut env, credential).is_ok());
 
@@ -2902,6 +3070,7 @@ mod test {
             user_icon: None,
             cred_blob: Some(vec![0xCB]),
             large_blob_key: None,
+            signature_counter: None,
         };
         assert!(storage::store_credential(&mut env, credential).is_ok());
 
@@ -2927,7 +3096,7 @@ mod test {
             DUMMY_CHANNEL,
             Instant::new(0),
         );
-        let signature_counter = storage::global_signature_counter(&mut env).unwrap();
+        let signature_counter = stored_signature_counter(&mut env, &[0x1D]);
         let expected_extension_cbor = [
             0xA1, 0x68, 0x63, 0x72, 0x65, 0x64, 0x42, 0x6C, 0x6F, 0x62, 0x41, 0xCB,
         ];
@@ -3029,6 +3198,7 @@ mod test {
             user_icon: None,
             cred_blob: None,
             large_blob_key: Some(vec![0x1C; 32]),
+            signature_counter: None,
         };
         assert!(storage::store_credential(&mut env, credential).is_ok());
 
@@ -3125,12 +3295,11 @@ mod test {
             DUMMY_CHANNEL,
             Instant::new(0),
         );
-        let signature_counter = storage::global_signature_counter(&mut env).unwrap();
         check_assertion_response_with_user(
             get_assertion_response,
             Some(user2),
             0x04,
-            signature_counter,
+            stored_signature_counter(&mut env, &[0x02]),
             Some(2),
             &[],
         );
@@ -3140,7 +3309,7 @@ mod test {
             get_assertion_response,
             Some(user1),
             0x04,
-            signature_counter,
+            stored_signature_counter(&mut env, &[0x01]),
             None,
             &[],
         );
@@ -3210,18 +3379,19 @@ mod test {
             DUMMY_CHANNEL,
             Instant::new(0),
         );
-        let signature_counter = storage::global_signature_counter(&mut env).unwrap();
         check_assertion_response(
             get_assertion_response,
             vec![0x03],
-            signature_counter,
+            stored_signature_counter(&mut env, &[0x03]),
             Some(3),
         );
 
         let get_assertion_response = ctap_state.process_get_next_assertion(&mut env);
+        let signature_counter = stored_signature_counter(&mut env, &[0x02]);
         check_assertion_response(get_assertion_response, vec![0x02], signature_counter, None);
 
         let get_assertion_response = ctap_state.process_get_next_assertion(&mut env);
+        let signature_counter = stored_signature_counter(&mut env, &[0x01]);
         check_assertion_response(get_assertion_response, vec![0x01], signature_counter, None);
 
         let get_assertion_response = ctap_state.process_get_next_assertion(&mut env);
@@ -3315,6 +3485,7 @@ mod test {
             user_icon: None,
             cred_blob: None,
             large_blob_key: None,
+            signature_counter: None,
         };
         assert!(storage::store_credential(&mut env, credential_source).is_ok());
         assert!(storage::count_credentials(&mut env).unwrap() > 0);
@@ -3408,6 +3579,7 @@ mod test {
             AuthenticatorVendorConfigureParameters {
                 lockdown: false,
                 attestation_material: None,
+                enterprise: false,
             },
             DUMMY_CHANNEL,
         );
@@ -3432,6 +3604,7 @@ mod test {
                     certificate: dummy_cert.to_vec(),
                     private_key: dummy_key,
                 }),
+                enterprise: false,
             },
             DUMMY_CHANNEL,
         );
@@ -3462,6 +3635,7 @@ mod test {
                     certificate: dummy_cert.to_vec(),
                     private_key: other_dummy_key,
                 }),
+                enterprise: false,
             },
             DUMMY_CHANNEL,
         );
@@ -3488,6 +3662,7 @@ mod test {
             AuthenticatorVendorConfigureParameters {
                 lockdown: true,
                 attestation_material: None,
+                enterprise: false,
             },
             DUMMY_CHANNEL,
         );
@@ -3502,6 +3677,61 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_vendor_configure_enterprise() {
+        let mut env = TestEnv::new();
+        let mut ctap_state = CtapState::new(&mut env, Instant::new(0));
+
+        let dummy_key = [0x41u8; key_material::ATTESTATION_PRIVATE_KEY_LENGTH];
+        let dummy_cert = [0xddu8; 20];
+        let response = ctap_state.process_vendor_configure(
+            &mut env,
+            AuthenticatorVendorConfigureParameters {
+                lockdown: false,
+                attestation_material: Some(AuthenticatorAttestationMaterial {
+                    certificate: dummy_cert.to_vec(),
+                    private_key: dummy_key,
+                }),
+                enterprise: true,
+            },
+            DUMMY_CHANNEL,
+        );
+        assert_eq!(
+            response,
+            Ok(ResponseData::AuthenticatorVendorConfigure(
+                AuthenticatorVendorConfigureResponse {
+                    cert_programmed: true,
+                    pkey_programmed: true,
+                }
+            ))
+        );
+        // The enterprise attestation is kept apart from the batch attestation used by U2F.
+        assert_eq!(
+            env.attestation_store().get(&attestation_store::Id::Enterprise),
+            Ok(Some(Attestation {
+                private_key: dummy_key,
+                certificate: dummy_cert.to_vec(),
+            }))
+        );
+        assert_eq!(
+            env.attestation_store().get(&attestation_store::Id::Batch),
+            Ok(None)
+        );
+        assert_eq!(storage::enable_enterprise_attestation(&mut env), Ok(()));
+
+        // Lockdown is only for the batch attestation.
+        let response = ctap_state.process_vendor_configure(
+            &mut env,
+            AuthenticatorVendorConfigureParameters {
+                lockdown: true,
+                attestation_material: None,
+                enterprise: true,
+            },
+            DUMMY_CHANNEL,
+        );
+        assert_eq!(response, Err(Ctap2StatusCode::CTAP1_ERR_INVALID_PARAMETER));
+    }
+
     #[test]
     fn test_vendor_upgrade() {
         // The test partition storage has size 0x40000.
@@ -3707,6 +3937,7 @@ mod test {
             user_icon: Some("icon".to_string()),
             cred_blob: None,
             large_blob_key: None,
+            signature_counter: None,
         };
 
         let mut ctap_state = CtapState::new(&mut env, Instant::new(0));
@@ -263,6 +263,34 @@ pub fn incr_global_signature_counter(
     Ok(())
 }
 
+/// Increments the signature counter of a stored credential, and returns its new value.
+///
+/// Credentials stored before they had their own counter continue from the global counter, so the
+/// counter a relying party sees never goes backwards.
+///
+/// Returns `None` if the credential is not stored, as it then has no counter of its own.
+pub fn incr_credential_signature_counter(
+    env: &mut impl Env,
+    credential_id: &[u8],
+    increment: u32,
+) -> Result<Option<u32>, Ctap2StatusCode> {
+    let (key, mut credential) = match find_credential_item(env, credential_id) {
+        Err(Ctap2StatusCode::CTAP2_ERR_NO_CREDENTIALS) => return Ok(None),
+        Err(e) => return Err(e),
+        Ok(item) => item,
+    };
+    let old_value = match credential.signature_counter {
+        Some(value) => value,
+        None => global_signature_counter(env)?,
+    };
+    // In hopes that servers handle the wrapping gracefully.
+    let new_value = old_value.wrapping_add(increment);
+    credential.signature_counter = Some(new_value);
+    let value = serialize_credential(credential)?;
+    env.store().insert(key, &value)?;
+    Ok(Some(new_value))
+}
+
 /// Returns the CredRandom secret.
 pub fn cred_random_secret(env: &mut impl Env, has_uv: bool) -> Result<[u8; 32], Ctap2StatusCode> {
     let cred_random_secret = env
@@ -676,6 +704,7 @@ mod test {
             user_icon: None,
             cred_blob: None,
             large_blob_key: None,
+            signature_counter: None,
         }
     }
 
@@ -872,6 +901,7 @@ mod test {
             user_icon: None,
             cred_blob: None,
             large_blob_key: None,
+            signature_counter: None,
         };
         assert_eq!(found_credential, Some(expected_credential));
     }
@@ -1100,6 +1130,55 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_credential_signature_counter() {
+        let mut env = TestEnv::new();
+        assert!(incr_global_signature_counter(&mut env, 100).is_ok());
+
+        // A credential stored before it had its own counter continues from the global one.
+        let legacy = create_credential_source(&mut env, "example.com", vec![0x00]);
+        let legacy_id = legacy.credential_id.clone();
+        assert!(store_credential(&mut env, legacy).is_ok());
+        assert_eq!(
+            incr_credential_signature_counter(&mut env, &legacy_id, 5),
+            Ok(Some(106))
+        );
+
+        let credential = PublicKeyCredentialSource {
+            signature_counter: Some(0),
+            ..create_credential_source(&mut env, "example.com", vec![0x01])
+        };
+        let credential_id = credential.credential_id.clone();
+        assert!(store_credential(&mut env, credential).is_ok());
+        assert_eq!(
+            incr_credential_signature_counter(&mut env, &credential_id, 3),
+            Ok(Some(3))
+        );
+        assert_eq!(
+            incr_credential_signature_counter(&mut env, &credential_id, 4),
+            Ok(Some(7))
+        );
+        assert_eq!(
+            find_credential(&mut env, "example.com", &credential_id)
+                .unwrap()
+                .unwrap()
+                .signature_counter,
+            Some(7)
+        );
+        // Counters are kept apart, and the global counter is left alone.
+        assert_eq!(
+            incr_credential_signature_counter(&mut env, &legacy_id, 1),
+            Ok(Some(107))
+        );
+        assert_eq!(global_signature_counter(&mut env).unwrap(), 101);
+
+        // A credential that isn't stored has no counter of its own.
+        assert_eq!(
+            incr_credential_signature_counter(&mut env, &[0x55; 32], 1),
+            Ok(None)
+        );
+    }
+
     #[test]
     fn test_force_pin_change() {
         let mut env = TestEnv::new();
@@ -1166,6 +1245,7 @@ mod test {
             user_icon: Some(String::from("icon")),
             cred_blob: Some(vec![0xCB]),
             large_blob_key: Some(vec![0x1B]),
+            signature_counter: None,
         };
         let serialized = serialize_credential(credential.clone()).unwrap();
         let reconstructed = deserialize_credential(&serialized).unwrap();
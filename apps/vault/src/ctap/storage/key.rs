@@ -68,6 +68,10 @@ make_partition! {
     /// The aaguid.
     AAGUID = 3;
 
+    /// Reserved for the enterprise attestation, for attestation store implementations that keep it
+    /// apart from the batch attestation.
+    _RESERVED_ENTERPRISE_ATTESTATION_STORE = 4..6;
+
     // This is the persistent key limit:
     // - When adding a (persistent) key above this message, make sure its value is smaller than
     //   NUM_PERSISTENT_KEYS.
@@ -147,17 +147,25 @@ impl key_store::Helper for TestEnv {}
 impl AttestationStore for TestEnv {
     fn get(
         &mut self,
-        _id: &attestation_store::Id,
+        id: &attestation_store::Id,
     ) -> Result<Option<attestation_store::Attestation>, attestation_store::Error> {
-        attestation_store::helper_get(self)
+        match id {
+            attestation_store::Id::Batch => attestation_store::helper_get(self),
+            attestation_store::Id::Enterprise => attestation_store::helper_get_enterprise(self),
+        }
     }
 
     fn set(
         &mut self,
-        _id: &attestation_store::Id,
+        id: &attestation_store::Id,
         attestation: Option<&attestation_store::Attestation>,
     ) -> Result<(), attestation_store::Error> {
-        attestation_store::helper_set(self, attestation)
+        match id {
+            attestation_store::Id::Batch => attestation_store::helper_set(self, attestation),
+            attestation_store::Id::Enterprise => {
+                attestation_store::helper_set_enterprise(self, attestation)
+            }
+        }
     }
 }
 
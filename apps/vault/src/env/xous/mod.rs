@@ -4,6 +4,7 @@ use crate::api::connection::{HidConnection, SendOrRecvError, SendOrRecvResult, S
 use crate::api::customization::{CustomizationImpl, DEFAULT_CUSTOMIZATION};
 use crate::api::firmware_protection::FirmwareProtection;
 use crate::api::user_presence::{UserPresence, UserPresenceError, UserPresenceResult};
+use crate::ctap::data_formats::EnterpriseAttestationMode;
 use crate::api::{attestation_store, key_store};
 use crate::KEEPALIVE_DELAY_MS;
 use crate::env::Env;
@@ -104,12 +105,13 @@ pub struct XousEnv {
     last_user_presence_request: Option::<Instant>,
     ctap1_cid: xous::CID,
     lefty_mode: Arc<AtomicBool>,
+    signature_counter: Arc<AtomicBool>,
 }
 
 impl XousEnv {
     /// Returns the unique instance of the Xous environment.
     /// Blocks until the PDDB is mounted
-    pub fn new(conn: xous::CID, lefty_mode: Arc<AtomicBool>) -> Self {
+    pub fn new(conn: xous::CID, lefty_mode: Arc<AtomicBool>, signature_counter: Arc<AtomicBool>) -> Self {
         // We rely on `take_storage` to ensure that this function is called only once.
         let storage = XousStorage {};
         let store = Store::new(storage).ok().unwrap();
@@ -523,6 +525,7 @@ impl XousEnv {
             last_user_presence_request: None,
             ctap1_cid,
             lefty_mode,
+            signature_counter,
         }
     }
     /// Checks if the SoC is compatible with USB drivers (older versions of Precursor's FPGA don't have the USB device core)
@@ -734,10 +737,11 @@ impl AttestationStore for XousEnv {
         &mut self,
         id: &attestation_store::Id,
     ) -> Result<Option<attestation_store::Attestation>, attestation_store::Error> {
-        if !matches!(id, attestation_store::Id::Batch) {
-            return Err(attestation_store::Error::NoSupport);
+        // U2F uses the batch attestation, so an enterprise attestation is kept apart from it
+        match id {
+            attestation_store::Id::Batch => attestation_store::helper_get(self),
+            attestation_store::Id::Enterprise => attestation_store::helper_get_enterprise(self),
         }
-        attestation_store::helper_get(self)
     }
 
     fn set(
@@ -745,10 +749,12 @@ impl AttestationStore for XousEnv {
         id: &attestation_store::Id,
         attestation: Option<&attestation_store::Attestation>,
     ) -> Result<(), attestation_store::Error> {
-        if !matches!(id, attestation_store::Id::Batch) {
-            return Err(attestation_store::Error::NoSupport);
+        match id {
+            attestation_store::Id::Batch => attestation_store::helper_set(self, attestation),
+            attestation_store::Id::Enterprise => {
+                attestation_store::helper_set_enterprise(self, attestation)
+            }
         }
-        attestation_store::helper_set(self, attestation)
     }
 }
 
@@ -812,7 +818,11 @@ impl Env for XousEnv {
     }
 
     fn customization(&self) -> &Self::Customization {
-        &DEFAULT_CUSTOMIZATION
+        if self.signature_counter.load(Ordering::SeqCst) {
+            &XOUS_CUSTOMIZATION
+        } else {
+            &NO_COUNTER_CUSTOMIZATION
+        }
     }
 
     fn main_hid_connection(&mut self) -> &mut Self::HidConnection {
@@ -825,5 +835,20 @@ impl Env for XousEnv {
     }
 }
 
+/// A fixed counter keeps RPs from correlating a user by how many signatures the device has made
+/// for other sites. WebAuthn reads a zero counter as "no counter support".
+const NO_COUNTER_CUSTOMIZATION: CustomizationImpl = CustomizationImpl {
+    use_signature_counter: false,
+    ..XOUS_CUSTOMIZATION
+};
+
+/// Enterprise attestation is left to the platform: a managed browser asks for it on the RPs its
+/// enterprise policy lists. It stays off until an enterprise attestation is loaded with the vendor
+/// configure command, and then enabled through authenticatorConfig.
+const XOUS_CUSTOMIZATION: CustomizationImpl = CustomizationImpl {
+    enterprise_attestation_mode: Some(EnterpriseAttestationMode::PlatformManaged),
+    ..DEFAULT_CUSTOMIZATION
+};
+
 pub const KEEPALIVE_DELAY_XOUS: Duration = Duration::from_millis(KEEPALIVE_DELAY_MS);
 
@@ -2,9 +2,17 @@
 #![cfg_attr(target_os = "none", no_main)]
 
 mod actions;
+#[cfg(feature = "breach-check")]
+mod breach;
 mod itemcache;
 mod migration_v1;
+#[cfg(feature = "ed25519")]
+mod openpgp;
+mod otpauth;
 mod prereqs;
+mod seed;
+mod shares;
+mod ssh;
 mod storage;
 mod submenu;
 mod totp;
@@ -108,6 +116,7 @@ const ERR_TIMEOUT_MS: usize = 5000;
 
 fn main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
@@ -116,6 +125,11 @@ fn main() -> ! {
     // TODO: fix this so there is a uniform public API for the time server
     let time_conn = xous::connect(xous::SID::from_bytes(b"timeserverpublic").unwrap()).unwrap();
     let conn = xous::connect(sid).unwrap();
+    // the FIDO counters belong to the first process to claim them; stake the claim before other apps can
+    match counters::Counters::new(&xns).and_then(|c| c.claim(counters::FIDO_NAMESPACE)) {
+        Ok(Ok(())) => {}
+        e => log::error!("couldn't claim the FIDO counters: {:?}", e),
+    }
 
     // global shared state between threads.
     let mode = Arc::new(Mutex::new(VaultMode::Fido));
@@ -128,6 +142,8 @@ fn main() -> ! {
     let opensk_mutex = Arc::new(Mutex::new(0));
     // storage for lefty mode
     let lefty_mode = Arc::new(AtomicBool::new(false));
+    // whether FIDO signatures carry a signature counter
+    let signature_counter = Arc::new(AtomicBool::new(true));
 
     // spawn the actions server. This is responsible for grooming the UX elements. It
     // has to be in its own thread because it uses blocking modal calls that would cause
@@ -163,6 +179,20 @@ fn main() -> ! {
                         manager.retrieve_db();
                         manager.deactivate();
                     }
+                    Some(ActionOp::MenuSplitStage2) => {
+                        let buffer =
+                            unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                        let entry = buffer.to_original::<SelectedEntry, _>().unwrap();
+                        manager.activate();
+                        manager.menu_split(entry);
+                        manager.retrieve_db();
+                        manager.deactivate();
+                    }
+                    Some(ActionOp::MenuRecoverShares) => {
+                        manager.activate();
+                        manager.menu_recover(); // this is responsible for updating the item cache
+                        manager.deactivate();
+                    }
                     Some(ActionOp::MenuEditStage2) => {
                         let buffer =
                             unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
@@ -185,6 +215,14 @@ fn main() -> ! {
                         manager.retrieve_db();
                         manager.deactivate();
                     }
+                    #[cfg(feature = "breach-check")]
+                    Some(ActionOp::MenuBreachCheck) => {
+                        manager.activate();
+                        manager.breach_check();
+                        manager.item_lists.lock().unwrap().clear(VaultMode::Password); // clear the cached item list for passwords, to refresh the breach flags
+                        manager.retrieve_db();
+                        manager.deactivate();
+                    }
                     Some(ActionOp::MenuClose) => {
                         // dummy activate/de-activate cycle because we have to trigger a redraw of the
                         // underlying UX
@@ -210,6 +248,20 @@ fn main() -> ! {
                         manager.retrieve_db();
                         xous::return_scalar(msg.sender, 1).unwrap();
                     }),
+                    Some(ActionOp::ScanAdd) => {
+                        // every scan is delivered here; only otpauth:// codes and shares are of interest
+                        let text = optical::scan_from_message(&msg)
+                            .and_then(|scan| scan.text().map(|text| text.to_string()));
+                        if let Some(entry) = text.as_deref().and_then(otpauth::OtpAuth::parse) {
+                            manager.activate();
+                            manager.scan_add(entry);
+                            manager.deactivate();
+                        } else if let Some(share) = text.as_deref().and_then(shares::Share::parse) {
+                            manager.activate();
+                            manager.scan_share(share); // this is responsible for updating the item cache
+                            manager.deactivate();
+                        }
+                    }
                     Some(ActionOp::Quit) => {
                         break;
                     }
@@ -228,6 +280,16 @@ fn main() -> ! {
     });
 
     let actions_conn = xous::connect(actions_sid).unwrap();
+    // otpauth:// codes scanned by a camera or an attached scanner become new TOTP entries. Scans are only
+    // delivered to one process, so this has to be done at boot, before any other process takes the receiver.
+    match optical::ScanReceiver::new(&xns) {
+        Ok(receiver) => {
+            if let Err(e) = receiver.subscribe(actions_sid, ActionOp::ScanAdd.to_u32().unwrap()) {
+                log::warn!("couldn't subscribe to optical scans: {:?}", e);
+            }
+        }
+        Err(e) => log::warn!("no optical input service: {:?}", e),
+    }
 
     // spawn the FIDO USB->UX update kicker thread. It is responsible for issuing a UX refresh command
     // a few moments after FIDO traffic ceases. The purpose is to get the UX to reflect credential
@@ -292,6 +354,7 @@ fn main() -> ! {
         let opensk_mutex = opensk_mutex.clone();
         let conn = conn.clone();
         let lefty_mode = lefty_mode.clone();
+        let signature_counter = signature_counter.clone();
         move || {
             let xns = xous_names::XousNames::new().unwrap();
             let mut vendor_session = VendorSession::default();
@@ -314,7 +377,7 @@ fn main() -> ! {
                 }
             };
 
-            let env = XousEnv::new(conn, lefty_mode); // lefty_mode is now owned by env
+            let env = XousEnv::new(conn, lefty_mode, signature_counter); // lefty_mode is now owned by env
             // only run the main loop if the SoC is compatible
             if env.is_soc_compatible() {
                 let mut ctap = vault::Ctap::new(env, Instant::now());
@@ -438,6 +501,14 @@ fn main() -> ! {
         }
     });
 
+    // spawn the OpenPGP card, served over the USB smart card reader
+    #[cfg(feature = "ed25519")]
+    let _ = thread::spawn({
+        move || {
+            crate::openpgp::card_server();
+        }
+    });
+
     // spawn the icontray handler
     let _ = thread::spawn({
         move || {
@@ -475,9 +546,12 @@ fn main() -> ! {
     let mut autotype_delay_ms = prefs.autotype_rate_or_value(30).unwrap();
     vaultux.set_autotype_delay_ms(autotype_delay_ms);
     lefty_mode.store(prefs.lefty_mode_or_value(false).unwrap(), Ordering::SeqCst);
+    signature_counter.store(prefs.fido_signature_counter_or_value(true).unwrap(), Ordering::SeqCst);
 
     let modals = modals::Modals::new(&xns).unwrap();
     let tt = ticktimer_server::Ticktimer::new().unwrap();
+    // holding an arrow key scrolls faster the longer it is held
+    let mut scroll = gam::ScrollAccel::new();
     let mut first_time = true;
     loop {
         let msg = xous::receive_message(sid).unwrap();
@@ -563,10 +637,14 @@ fn main() -> ! {
                         vaultux.raise_menu();
                     }
                     "↓" => {
-                        vaultux.nav(NavDir::Down);
+                        for _ in 0..scroll.lines('↓', vaultux.items_per_screen()) {
+                            vaultux.nav(NavDir::Down);
+                        }
                     }
                     "↑" => {
-                        vaultux.nav(NavDir::Up);
+                        for _ in 0..scroll.lines('↑', vaultux.items_per_screen()) {
+                            vaultux.nav(NavDir::Up);
+                        }
                     }
                     "←" => {
                         vaultux.nav(NavDir::PageUp);
@@ -696,6 +774,16 @@ fn main() -> ! {
                             .ok();
                         tt.sleep_ms(ERR_TIMEOUT_MS).unwrap();
                     }
+                    Err(xous::Error::AccessDenied) => {
+                        // seed and SSH key records can't be autotyped
+                        modals
+                            .dynamic_notification_update(
+                                Some(t!("vault.error.seed_autotype", locales::LANG)),
+                                None,
+                            )
+                            .ok();
+                        tt.sleep_ms(ERR_TIMEOUT_MS).unwrap();
+                    }
                     Err(xous::Error::InvalidPID) => {
                         // nothing was selected
                         modals
@@ -741,6 +829,24 @@ fn main() -> ! {
                         .expect("messaging error");
                 }
             }),
+            Some(VaultOp::MenuCopyCode) => {
+                let status = match vaultux.copy_code() {
+                    Ok(_) => t!("vault.code_copied", locales::LANG).to_string(),
+                    Err(xous::Error::InvalidPID) => {
+                        t!("vault.error.nothing_selected", locales::LANG).to_string()
+                    }
+                    Err(xous::Error::InvalidString) => {
+                        t!("vault.error.record_error", locales::LANG).to_string()
+                    }
+                    Err(xous::Error::AccessDenied) => t!("vault.error.hotp_copy", locales::LANG).to_string(),
+                    Err(xous::Error::UseBeforeInit) => t!("vault.error.time_init", locales::LANG).to_string(),
+                    Err(e) => format!("{}\n{:?}", t!("vault.error.internal_error", locales::LANG), e),
+                };
+                // this will block redraws
+                allow_totp_rendering.store(false, Ordering::SeqCst);
+                modals.show_notification(&status, None).ok();
+                allow_totp_rendering.store(true, Ordering::SeqCst);
+            }
             Some(VaultOp::MenuDeleteStage1) => {
                 // stage 1 happens here because the filtered list and selection entry are in the responsive UX
                 // section.
@@ -755,6 +861,20 @@ fn main() -> ! {
                     allow_totp_rendering.store(true, Ordering::SeqCst);
                 }
             }
+            Some(VaultOp::MenuSplitStage1) => {
+                // stage 1 happens here because the filtered list and selection entry are in the responsive UX
+                // section.
+                if let Some(entry) = vaultux.selected_entry() {
+                    let buf = Buffer::into_buf(entry).expect("IPC error");
+                    buf.send(actions_conn, ActionOp::MenuSplitStage2.to_u32().unwrap())
+                        .expect("messaging error");
+                } else {
+                    // this will block redraws
+                    allow_totp_rendering.store(false, Ordering::SeqCst);
+                    modals.show_notification(t!("vault.error.nothing_selected", locales::LANG), None).ok();
+                    allow_totp_rendering.store(true, Ordering::SeqCst);
+                }
+            }
             Some(VaultOp::MenuEditStage1) => {
                 // stage 1 happens here because the filtered list and selection entry are in the responsive UX
                 // section.
@@ -835,6 +955,26 @@ fn main() -> ! {
                 prefs.set_lefty_mode(mode).unwrap();
                 lefty_mode.store(mode, Ordering::SeqCst);
             }
+            Some(VaultOp::MenuSignatureCounter) => {
+                let cv = prefs.fido_signature_counter_or_value(true).unwrap();
+
+                modals.add_list(vec![t!("prefs.yes", locales::LANG), t!("prefs.no", locales::LANG)]).unwrap();
+                allow_totp_rendering.store(false, Ordering::SeqCst);
+                let mode = yes_no_to_bool(
+                    modals
+                        .get_radiobutton(&format!(
+                            "{}\n\n{} {}",
+                            t!("vault.signature_counter_help", locales::LANG),
+                            t!("prefs.current_setting", locales::LANG),
+                            bool_to_yes_no(cv)
+                        ))
+                        .unwrap()
+                        .as_str(),
+                );
+                allow_totp_rendering.store(true, Ordering::SeqCst);
+                prefs.set_fido_signature_counter(mode).unwrap();
+                signature_counter.store(mode, Ordering::SeqCst);
+            }
             Some(VaultOp::Quit) => {
                 log::error!("got Quit");
                 break;
@@ -0,0 +1,901 @@
+//! An OpenPGP card (version 3.4 of the specification) backed by the vault. The USB driver presents a CCID
+//! smart card reader with the card permanently inserted, so GnuPG's scdaemon can use the device like any
+//! other OpenPGP card, through pcscd or its own CCID driver.
+//!
+//! The card holds an Ed25519 signature key, a Cv25519 decryption key and an Ed25519 authentication key.
+//! They can be generated on the device or imported with `keytocard`, and never leave it. On top of the
+//! PIN checks of the specification, every signature, decryption and authentication has to be confirmed
+//! on the device.
+//!
+//! Only short APDUs are supported. Longer commands use command chaining, and longer responses are
+//! fetched with GET RESPONSE.
+
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+use locales::t;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+const OPENPGP_DICT: &str = "vault.openpgp";
+/// RID and PIX of the OpenPGP application; SELECT matches on this prefix
+const AID_PREFIX: [u8; 6] = [0xD2, 0x76, 0x00, 0x01, 0x24, 0x01];
+/// Specification version 3.4, and a manufacturer ID from the range for unregistered manufacturers
+const AID_VERSION_MANUFACTURER: [u8; 4] = [0x03, 0x04, 0xFF, 0x00];
+/// Same as in the ATR: command chaining, no extended length
+const HISTORICAL_BYTES: [u8; 10] = [0x00, 0x31, 0xC5, 0x73, 0xC0, 0x01, 0x80, 0x05, 0x90, 0x00];
+/// EdDSA on Ed25519
+const ALGO_ED25519: [u8; 10] = [0x16, 0x2B, 0x06, 0x01, 0x04, 0x01, 0xDA, 0x47, 0x0F, 0x01];
+/// ECDH on Curve25519
+const ALGO_CV25519: [u8; 11] = [0x12, 0x2B, 0x06, 0x01, 0x04, 0x01, 0x97, 0x55, 0x01, 0x05, 0x01];
+/// Key import and changeable PW status; special DOs of up to 255 bytes
+const EXTENDED_CAPABILITIES: [u8; 10] = [0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00];
+const MAX_DO_LEN: usize = 255;
+/// The longest command that can be assembled by chaining
+const MAX_CHAIN_LEN: usize = 1024;
+/// The most response data that fits in a short APDU
+const MAX_RESPONSE_LEN: usize = 256;
+
+const DEFAULT_PW1: &[u8] = b"123456";
+const DEFAULT_PW3: &[u8] = b"12345678";
+const PW1_MIN_LEN: usize = 6;
+const PW3_MIN_LEN: usize = 8;
+const PW_MAX_LEN: usize = 127;
+const PW_TRIES: u8 = 3;
+
+const SW_OK: u16 = 0x9000;
+const SW_MORE_DATA: u16 = 0x6100;
+const SW_WRONG_PW: u16 = 0x63C0;
+const SW_MEMORY_FAILURE: u16 = 0x6581;
+const SW_WRONG_LENGTH: u16 = 0x6700;
+const SW_SECURITY_STATUS: u16 = 0x6982;
+const SW_AUTH_BLOCKED: u16 = 0x6983;
+const SW_CONDITIONS: u16 = 0x6985;
+const SW_WRONG_DATA: u16 = 0x6A80;
+const SW_FILE_NOT_FOUND: u16 = 0x6A82;
+const SW_NOT_FOUND: u16 = 0x6A88;
+const SW_WRONG_P1P2: u16 = 0x6B00;
+const SW_INS_NOT_SUPPORTED: u16 = 0x6D00;
+const SW_CLA_NOT_SUPPORTED: u16 = 0x6E00;
+
+/// Key status, as reported in the key information DO
+const KEY_GENERATED: u8 = 1;
+const KEY_IMPORTED: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum KeySlot {
+    Sig,
+    Dec,
+    Aut,
+}
+const KEY_SLOTS: [KeySlot; 3] = [KeySlot::Sig, KeySlot::Dec, KeySlot::Aut];
+
+impl KeySlot {
+    /// From the tag of the control reference template that names a key
+    fn from_crt(tag: u8) -> Option<KeySlot> {
+        match tag {
+            0xB6 => Some(KeySlot::Sig),
+            0xB8 => Some(KeySlot::Dec),
+            0xA4 => Some(KeySlot::Aut),
+            _ => None,
+        }
+    }
+
+    /// PDDB key holding the key status and the 32-byte secret
+    fn key_name(&self) -> &'static str {
+        match self {
+            KeySlot::Sig => "sig.key",
+            KeySlot::Dec => "dec.key",
+            KeySlot::Aut => "aut.key",
+        }
+    }
+
+    fn fingerprint_tag(&self) -> u16 {
+        match self {
+            KeySlot::Sig => 0xC7,
+            KeySlot::Dec => 0xC8,
+            KeySlot::Aut => 0xC9,
+        }
+    }
+
+    fn generation_time_tag(&self) -> u16 {
+        match self {
+            KeySlot::Sig => 0xCE,
+            KeySlot::Dec => 0xCF,
+            KeySlot::Aut => 0xD0,
+        }
+    }
+
+    fn algorithm(&self) -> &'static [u8] {
+        match self {
+            KeySlot::Dec => &ALGO_CV25519,
+            _ => &ALGO_ED25519,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Pw {
+    /// the user PIN
+    Pw1,
+    /// the admin PIN
+    Pw3,
+}
+
+impl Pw {
+    fn key_name(&self) -> &'static str {
+        match self {
+            Pw::Pw1 => "pw1",
+            Pw::Pw3 => "pw3",
+        }
+    }
+
+    fn default(&self) -> &'static [u8] {
+        match self {
+            Pw::Pw1 => DEFAULT_PW1,
+            Pw::Pw3 => DEFAULT_PW3,
+        }
+    }
+
+    /// Index of the retry counter in the PW status bytes, after the first four
+    fn tries_index(&self) -> usize {
+        match self {
+            Pw::Pw1 => 0,
+            Pw::Pw3 => 2,
+        }
+    }
+}
+
+struct Apdu<'a> {
+    cla: u8,
+    ins: u8,
+    p1: u8,
+    p2: u8,
+    data: &'a [u8],
+}
+
+impl<'a> Apdu<'a> {
+    fn parse(raw: &'a [u8]) -> Option<Apdu<'a>> {
+        let body = raw.get(4..)?;
+        let data = match body.len() {
+            // no data, with or without Le
+            0 | 1 => &body[..0],
+            _ => {
+                let lc = body[0] as usize;
+                // Lc of 0 starts an extended length field
+                if lc == 0 || (body.len() != lc + 1 && body.len() != lc + 2) {
+                    return None;
+                }
+                &body[1..1 + lc]
+            }
+        };
+        Some(Apdu { cla: raw[0], ins: raw[1], p1: raw[2], p2: raw[3], data })
+    }
+}
+
+pub(crate) struct Card {
+    pddb: pddb::Pddb,
+    trng: trng::Trng,
+    modals: modals::Modals,
+    /// PW1 verified for signing (mode 81)
+    pw1_sign: bool,
+    /// PW1 verified for everything else (mode 82)
+    pw1_other: bool,
+    pw3: bool,
+    /// data of a chained command, collected so far
+    chain: Vec<u8>,
+    /// response data still to be fetched with GET RESPONSE
+    pending: Vec<u8>,
+}
+
+impl Card {
+    pub(crate) fn new(xns: &xous_names::XousNames, pddb: pddb::Pddb) -> Card {
+        Card {
+            pddb,
+            trng: trng::Trng::new(xns).unwrap(),
+            modals: modals::Modals::new(xns).unwrap(),
+            pw1_sign: false,
+            pw1_other: false,
+            pw3: false,
+            chain: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Answers one command APDU with a response APDU
+    pub(crate) fn process(&mut self, raw: &[u8]) -> Vec<u8> {
+        let (mut response, mut sw) = match Apdu::parse(raw) {
+            Some(apdu) => self.dispatch(apdu),
+            None => (Vec::new(), SW_WRONG_LENGTH),
+        };
+        if sw == SW_OK && response.len() > MAX_RESPONSE_LEN {
+            self.pending = response.split_off(MAX_RESPONSE_LEN);
+            // 61 00 stands for 256 bytes or more
+            sw = SW_MORE_DATA | self.pending.len().min(MAX_RESPONSE_LEN) as u16 & 0xFF;
+        }
+        response.extend_from_slice(&sw.to_be_bytes());
+        response
+    }
+
+    fn dispatch(&mut self, apdu: Apdu) -> (Vec<u8>, u16) {
+        if apdu.cla & !0x10 != 0 {
+            return (Vec::new(), SW_CLA_NOT_SUPPORTED);
+        }
+        if apdu.ins != 0xC0 {
+            self.pending.clear();
+        }
+        let mut data = std::mem::take(&mut self.chain);
+        data.extend_from_slice(apdu.data);
+        if data.len() > MAX_CHAIN_LEN {
+            return (Vec::new(), SW_WRONG_LENGTH);
+        }
+        if apdu.cla & 0x10 != 0 {
+            // more of the command is to follow
+            self.chain = data;
+            return (Vec::new(), SW_OK);
+        }
+
+        let p1p2 = (apdu.p1 as u16) << 8 | apdu.p2 as u16;
+        let no_data = |sw| (Vec::new(), sw);
+        match apdu.ins {
+            // SELECT
+            0xA4 => {
+                if apdu.p1 == 0x04 && data.starts_with(&AID_PREFIX) {
+                    // a new session: nothing stays verified from the last one
+                    self.pw1_sign = false;
+                    self.pw1_other = false;
+                    self.pw3 = false;
+                    no_data(SW_OK)
+                } else {
+                    no_data(SW_FILE_NOT_FOUND)
+                }
+            }
+            // GET DATA
+            0xCA => match self.get_data(p1p2) {
+                Some(value) => (value, SW_OK),
+                None => no_data(SW_NOT_FOUND),
+            },
+            // VERIFY
+            0x20 => no_data(self.verify(apdu.p1, apdu.p2, &data)),
+            // CHANGE REFERENCE DATA
+            0x24 => no_data(self.change_pw(apdu.p1, apdu.p2, &data)),
+            // RESET RETRY COUNTER
+            0x2C => no_data(self.reset_retry_counter(apdu.p1, apdu.p2, &data)),
+            // PUT DATA
+            0xDA => no_data(self.put_data(p1p2, &data)),
+            // PUT DATA with an extended header list, for key import
+            0xDB if p1p2 == 0x3FFF => no_data(self.import_key(&data)),
+            // GENERATE ASYMMETRIC KEY PAIR
+            0x47 => self.generate(apdu.p1, &data),
+            // PERFORM SECURITY OPERATION
+            0x2A => match p1p2 {
+                0x9E9A => self.sign(&data),
+                0x8086 => self.decipher(&data),
+                _ => no_data(SW_WRONG_P1P2),
+            },
+            // INTERNAL AUTHENTICATE
+            0x88 => self.authenticate(&data),
+            // GET RESPONSE
+            0xC0 => (std::mem::take(&mut self.pending), SW_OK),
+            // TERMINATE DF
+            0xE6 => no_data(self.terminate()),
+            // ACTIVATE FILE: terminating resets the card right away, so there's nothing left to do
+            0x44 => no_data(SW_OK),
+            _ => no_data(SW_INS_NOT_SUPPORTED),
+        }
+    }
+
+    fn get_data(&mut self, tag: u16) -> Option<Vec<u8>> {
+        match tag {
+            0x4F => Some(self.aid()),
+            0x5F52 => Some(HISTORICAL_BYTES.to_vec()),
+            0xC4 => Some(self.pw_status()),
+            0x5E | 0x5F50 => Some(self.load_do(tag)),
+            0x65 => {
+                let mut cardholder = Vec::new();
+                for tag in [0x5B, 0x5F2D, 0x5F35] {
+                    put_tlv(&mut cardholder, tag, &self.load_do(tag));
+                }
+                Some(tlv(0x65, &cardholder))
+            }
+            0x6E => {
+                let mut discretionary = Vec::new();
+                put_tlv(&mut discretionary, 0xC0, &EXTENDED_CAPABILITIES);
+                for (tag, slot) in (0xC1..).zip(KEY_SLOTS.iter()) {
+                    put_tlv(&mut discretionary, tag, slot.algorithm());
+                }
+                put_tlv(&mut discretionary, 0xC4, &self.pw_status());
+                put_tlv(&mut discretionary, 0xC5, &self.load_dos(&[0xC7, 0xC8, 0xC9], 20));
+                put_tlv(&mut discretionary, 0xC6, &self.load_dos(&[0xCA, 0xCB, 0xCC], 20));
+                put_tlv(&mut discretionary, 0xCD, &self.load_dos(&[0xCE, 0xCF, 0xD0], 4));
+                put_tlv(&mut discretionary, 0xDE, &self.key_information());
+                let mut application = Vec::new();
+                put_tlv(&mut application, 0x4F, &self.aid());
+                put_tlv(&mut application, 0x5F52, &HISTORICAL_BYTES);
+                put_tlv(&mut application, 0x73, &discretionary);
+                Some(tlv(0x6E, &application))
+            }
+            0x7A => Some(tlv(0x7A, &tlv(0x93, &self.signature_count().to_be_bytes()[1..]))),
+            0xC5 => Some(self.load_dos(&[0xC7, 0xC8, 0xC9], 20)),
+            0xDE => Some(self.key_information()),
+            // no cardholder certificate
+            0x7F21 => Some(Vec::new()),
+            _ => None,
+        }
+    }
+
+    fn put_data(&mut self, tag: u16, data: &[u8]) -> u16 {
+        if !self.pw3 {
+            return SW_SECURITY_STATUS;
+        }
+        let valid = match tag {
+            0x5B => data.len() <= 39,
+            0x5E | 0x5F50 => data.len() <= MAX_DO_LEN,
+            0x5F2D => data.len() <= 8,
+            0x5F35 => data.len() <= 1,
+            // fingerprints and generation times
+            0xC7..=0xCC => data.len() == 20,
+            0xCE..=0xD0 => data.len() == 4,
+            // only the first byte, whether PW1 stays verified for more than one signature, can be changed
+            0xC4 => {
+                return match data.first() {
+                    Some(&status) if status <= 1 => self.store_do(tag, &[status]),
+                    _ => SW_WRONG_DATA,
+                };
+            }
+            _ => return SW_NOT_FOUND,
+        };
+        if !valid {
+            return SW_WRONG_DATA;
+        }
+        self.store_do(tag, data)
+    }
+
+    fn verify(&mut self, p1: u8, p2: u8, data: &[u8]) -> u16 {
+        let pw = match p2 {
+            0x81 | 0x82 => Pw::Pw1,
+            0x83 => Pw::Pw3,
+            _ => return SW_WRONG_P1P2,
+        };
+        match p1 {
+            0x00 => (),
+            // reset the verification status
+            0xFF => {
+                self.set_verified(p2, false);
+                return SW_OK;
+            }
+            _ => return SW_WRONG_P1P2,
+        }
+        if data.is_empty() {
+            return if self.is_verified(p2) { SW_OK } else { SW_WRONG_PW | self.tries(pw) as u16 };
+        }
+        let sw = self.check_pw(pw, data);
+        self.set_verified(p2, sw == SW_OK);
+        sw
+    }
+
+    fn change_pw(&mut self, p1: u8, p2: u8, data: &[u8]) -> u16 {
+        let (pw, min_len) = match (p1, p2) {
+            (0x00, 0x81) => (Pw::Pw1, PW1_MIN_LEN),
+            (0x00, 0x83) => (Pw::Pw3, PW3_MIN_LEN),
+            _ => return SW_WRONG_P1P2,
+        };
+        // the old and the new PIN are simply concatenated; the length of the old one tells them apart
+        let old_len = self.pw_record(pw).0;
+        if data.len() < old_len {
+            return self.check_pw(pw, data);
+        }
+        let (old, new) = data.split_at(old_len);
+        let sw = self.check_pw(pw, old);
+        if sw != SW_OK {
+            return sw;
+        }
+        if new.len() < min_len || new.len() > PW_MAX_LEN {
+            return SW_WRONG_DATA;
+        }
+        match pw {
+            Pw::Pw1 => {
+                self.pw1_sign = false;
+                self.pw1_other = false;
+            }
+            Pw::Pw3 => self.pw3 = false,
+        }
+        self.store_pw(pw, new)
+    }
+
+    fn reset_retry_counter(&mut self, p1: u8, p2: u8, data: &[u8]) -> u16 {
+        match (p1, p2) {
+            (0x02, 0x81) => {
+                if !self.pw3 {
+                    return SW_SECURITY_STATUS;
+                }
+                if data.len() < PW1_MIN_LEN || data.len() > PW_MAX_LEN {
+                    return SW_WRONG_DATA;
+                }
+                self.store_pw(Pw::Pw1, data)
+            }
+            // there is no resetting code
+            (0x00, 0x81) => SW_AUTH_BLOCKED,
+            _ => SW_WRONG_P1P2,
+        }
+    }
+
+    /// Imports a private key sent as an extended header list:
+    /// 4D { CRT, 7F48 { tags and lengths }, 5F48 { the values, concatenated } }
+    fn import_key(&mut self, data: &[u8]) -> u16 {
+        if !self.pw3 {
+            return SW_SECURITY_STATUS;
+        }
+        let mut pos = 0;
+        let template = match get_tlv(data, &mut pos) {
+            Some((0x4D, template)) => template,
+            _ => return SW_WRONG_DATA,
+        };
+        let slot = match template.first().and_then(|&tag| KeySlot::from_crt(tag)) {
+            Some(slot) => slot,
+            None => return SW_WRONG_DATA,
+        };
+        let (mut header, mut values) = (None, None);
+        let mut pos = 0;
+        while let Some((tag, value)) = get_tlv(template, &mut pos) {
+            match tag {
+                0x7F48 => header = Some(value),
+                0x5F48 => values = Some(value),
+                _ => (),
+            }
+        }
+        let secret = match (header, values) {
+            (Some(header), Some(values)) => find_in_header_list(header, values, 0x92),
+            _ => None,
+        };
+        let secret = match secret {
+            Some(secret) if secret.len() <= 32 => secret,
+            _ => return SW_WRONG_DATA,
+        };
+        let mut key = [0u8; 32];
+        key[32 - secret.len()..].copy_from_slice(secret);
+        if slot == KeySlot::Dec {
+            // OpenPGP keeps Curve25519 secrets big-endian; X25519 takes them little-endian
+            key.reverse();
+        }
+        self.store_key(slot, KEY_IMPORTED, &key)
+    }
+
+    fn generate(&mut self, p1: u8, data: &[u8]) -> (Vec<u8>, u16) {
+        let slot = match data.first().and_then(|&tag| KeySlot::from_crt(tag)) {
+            Some(slot) => slot,
+            None => return (Vec::new(), SW_WRONG_DATA),
+        };
+        match p1 {
+            // generate a new key
+            0x80 => {
+                if !self.pw3 {
+                    return (Vec::new(), SW_SECURITY_STATUS);
+                }
+                let mut key = [0u8; 32];
+                for chunk in key.chunks_mut(8) {
+                    chunk.copy_from_slice(&self.trng.get_u64().unwrap().to_le_bytes());
+                }
+                let sw = self.store_key(slot, KEY_GENERATED, &key);
+                if sw != SW_OK {
+                    return (Vec::new(), sw);
+                }
+            }
+            // read out the public key
+            0x81 => (),
+            _ => return (Vec::new(), SW_WRONG_P1P2),
+        }
+        match self.public_key(slot) {
+            Some(pk) => (tlv(0x7F49, &tlv(0x86, &pk)), SW_OK),
+            None => (Vec::new(), SW_NOT_FOUND),
+        }
+    }
+
+    fn sign(&mut self, data: &[u8]) -> (Vec<u8>, u16) {
+        if !self.pw1_sign {
+            return (Vec::new(), SW_SECURITY_STATUS);
+        }
+        if self.load_do(0xC4).first() != Some(&1) {
+            // PW1 is good for one signature only
+            self.pw1_sign = false;
+        }
+        let kp = match self.load_key(KeySlot::Sig) {
+            Some(secret) => ed25519_keypair(&secret),
+            None => return (Vec::new(), SW_NOT_FOUND),
+        };
+        if !self.confirm(KeySlot::Sig, t!("vault.openpgp.confirm_sign", locales::LANG)) {
+            return (Vec::new(), SW_CONDITIONS);
+        }
+        let count = self.signature_count().saturating_add(1).min(0xFF_FFFF);
+        self.store("sig.count", &count.to_be_bytes());
+        (kp.sk.sign(data, None)[..].to_vec(), SW_OK)
+    }
+
+    /// Computes the ECDH shared secret for a cipher DO: A6 { 7F49 { 86 { public key } } }
+    fn decipher(&mut self, data: &[u8]) -> (Vec<u8>, u16) {
+        if !self.pw1_other {
+            return (Vec::new(), SW_SECURITY_STATUS);
+        }
+        let point = get_tlv(data, &mut 0)
+            .filter(|&(tag, _)| tag == 0xA6)
+            .and_then(|(_, value)| get_tlv(value, &mut 0))
+            .filter(|&(tag, _)| tag == 0x7F49)
+            .and_then(|(_, value)| get_tlv(value, &mut 0))
+            .filter(|&(tag, _)| tag == 0x86)
+            .map(|(_, point)| point);
+        let point: [u8; 32] = match point {
+            Some(point) if point.len() == 32 => point.try_into().unwrap(),
+            // GnuPG may prefix the native point format
+            Some(point) if point.len() == 33 && point[0] == 0x40 => point[1..].try_into().unwrap(),
+            _ => return (Vec::new(), SW_WRONG_DATA),
+        };
+        let secret = match self.load_key(KeySlot::Dec) {
+            Some(secret) => x25519_dalek::StaticSecret::from(secret),
+            None => return (Vec::new(), SW_NOT_FOUND),
+        };
+        if !self.confirm(KeySlot::Dec, t!("vault.openpgp.confirm_decrypt", locales::LANG)) {
+            return (Vec::new(), SW_CONDITIONS);
+        }
+        let shared = secret.diffie_hellman(&x25519_dalek::PublicKey::from(point));
+        (shared.as_bytes().to_vec(), SW_OK)
+    }
+
+    fn authenticate(&mut self, data: &[u8]) -> (Vec<u8>, u16) {
+        if !self.pw1_other {
+            return (Vec::new(), SW_SECURITY_STATUS);
+        }
+        let kp = match self.load_key(KeySlot::Aut) {
+            Some(secret) => ed25519_keypair(&secret),
+            None => return (Vec::new(), SW_NOT_FOUND),
+        };
+        if !self.confirm(KeySlot::Aut, t!("vault.openpgp.confirm_auth", locales::LANG)) {
+            return (Vec::new(), SW_CONDITIONS);
+        }
+        (kp.sk.sign(data, None)[..].to_vec(), SW_OK)
+    }
+
+    /// Erases all keys and data and restores the default PINs
+    fn terminate(&mut self) -> u16 {
+        if !self.pw3 && self.tries(Pw::Pw3) != 0 {
+            return SW_SECURITY_STATUS;
+        }
+        self.modals
+            .add_list(vec![t!("vault.yes", locales::LANG), t!("vault.no", locales::LANG)])
+            .expect("couldn't build confirmation dialog");
+        match self.modals.get_radiobutton(t!("vault.openpgp.confirm_reset", locales::LANG)) {
+            Ok(response) if &response == t!("vault.yes", locales::LANG) => (),
+            _ => return SW_CONDITIONS,
+        }
+        log::info!("resetting the OpenPGP card");
+        self.pddb.delete_dict(OPENPGP_DICT, None).ok();
+        self.pddb.sync().ok();
+        self.pw1_sign = false;
+        self.pw1_other = false;
+        self.pw3 = false;
+        SW_OK
+    }
+
+    fn confirm(&self, slot: KeySlot, prompt: &str) -> bool {
+        // the long key ID is the tail of the fingerprint
+        let fingerprint = self.load_do(slot.fingerprint_tag());
+        let key_id = match fingerprint.len() {
+            20 => hex::encode_upper(&fingerprint[12..]),
+            _ => "?".to_string(),
+        };
+        self.modals
+            .add_list(vec![t!("vault.yes", locales::LANG), t!("vault.no", locales::LANG)])
+            .expect("couldn't build confirmation dialog");
+        match self.modals.get_radiobutton(&format!(
+            "{}\n\n{}: {}",
+            prompt,
+            t!("vault.openpgp.key_id", locales::LANG),
+            key_id
+        )) {
+            Ok(response) if &response == t!("vault.yes", locales::LANG) => true,
+            _ => {
+                log::info!("OpenPGP card operation declined");
+                false
+            }
+        }
+    }
+
+    fn aid(&mut self) -> Vec<u8> {
+        let serial = match self.load("serial") {
+            Some(serial) if serial.len() == 4 => serial,
+            _ => {
+                let serial = self.trng.get_u32().unwrap().to_be_bytes().to_vec();
+                self.store("serial", &serial);
+                serial
+            }
+        };
+        let mut aid = AID_PREFIX.to_vec();
+        aid.extend_from_slice(&AID_VERSION_MANUFACTURER);
+        aid.extend_from_slice(&serial);
+        aid.extend_from_slice(&[0, 0]);
+        aid
+    }
+
+    fn pw_status(&self) -> Vec<u8> {
+        let pw1_multiple = self.load_do(0xC4).first().copied().unwrap_or(1);
+        // there is no resetting code, so its retry counter is always 0
+        vec![
+            pw1_multiple,
+            PW_MAX_LEN as u8,
+            PW_MAX_LEN as u8,
+            PW_MAX_LEN as u8,
+            self.tries(Pw::Pw1),
+            0,
+            self.tries(Pw::Pw3),
+        ]
+    }
+
+    fn key_information(&self) -> Vec<u8> {
+        let mut info = Vec::new();
+        for (key_ref, slot) in (1..).zip(KEY_SLOTS.iter()) {
+            let status = self.load(slot.key_name()).and_then(|key| key.first().copied()).unwrap_or(0);
+            info.extend_from_slice(&[key_ref, status]);
+        }
+        info
+    }
+
+    fn signature_count(&self) -> u32 {
+        match self.load("sig.count") {
+            Some(count) if count.len() == 4 => u32::from_be_bytes(count[..].try_into().unwrap()),
+            _ => 0,
+        }
+    }
+
+    fn is_verified(&self, p2: u8) -> bool {
+        match p2 {
+            0x81 => self.pw1_sign,
+            0x82 => self.pw1_other,
+            _ => self.pw3,
+        }
+    }
+
+    fn set_verified(&mut self, p2: u8, verified: bool) {
+        match p2 {
+            0x81 => self.pw1_sign = verified,
+            0x82 => self.pw1_other = verified,
+            _ => self.pw3 = verified,
+        }
+    }
+
+    fn tries(&self, pw: Pw) -> u8 {
+        match self.load("tries") {
+            Some(tries) if tries.len() == 3 => tries[pw.tries_index()],
+            _ => PW_TRIES,
+        }
+    }
+
+    fn set_tries(&mut self, pw: Pw, count: u8) {
+        let mut tries = [self.tries(Pw::Pw1), 0, self.tries(Pw::Pw3)];
+        tries[pw.tries_index()] = count;
+        self.store("tries", &tries);
+    }
+
+    /// The length of a PIN and its hash
+    fn pw_record(&self, pw: Pw) -> (usize, Vec<u8>) {
+        match self.load(pw.key_name()) {
+            Some(record) if record.len() == 33 => (record[0] as usize, record[1..].to_vec()),
+            _ => (pw.default().len(), Sha256::digest(pw.default()).to_vec()),
+        }
+    }
+
+    fn store_pw(&mut self, pw: Pw, new: &[u8]) -> u16 {
+        let mut record = vec![new.len() as u8];
+        record.extend_from_slice(&Sha256::digest(new));
+        if !self.store(pw.key_name(), &record) {
+            return SW_MEMORY_FAILURE;
+        }
+        self.set_tries(pw, PW_TRIES);
+        SW_OK
+    }
+
+    /// Checks a PIN, counting down its retries on a mismatch
+    fn check_pw(&mut self, pw: Pw, candidate: &[u8]) -> u16 {
+        let tries = self.tries(pw);
+        if tries == 0 {
+            return SW_AUTH_BLOCKED;
+        }
+        let (len, hash) = self.pw_record(pw);
+        if candidate.len() == len && bool::from(Sha256::digest(candidate)[..].ct_eq(&hash[..])) {
+            if tries != PW_TRIES {
+                self.set_tries(pw, PW_TRIES);
+            }
+            SW_OK
+        } else {
+            self.set_tries(pw, tries - 1);
+            SW_WRONG_PW | (tries - 1) as u16
+        }
+    }
+
+    fn load_key(&self, slot: KeySlot) -> Option<[u8; 32]> {
+        match self.load(slot.key_name()) {
+            Some(key) if key.len() == 33 => key[1..].try_into().ok(),
+            _ => None,
+        }
+    }
+
+    fn store_key(&mut self, slot: KeySlot, status: u8, key: &[u8; 32]) -> u16 {
+        let mut record = vec![status];
+        record.extend_from_slice(key);
+        if !self.store(slot.key_name(), &record) {
+            return SW_MEMORY_FAILURE;
+        }
+        // the host sets the fingerprint and the generation time of the new key
+        self.store_do(slot.fingerprint_tag(), &[]);
+        self.store_do(slot.generation_time_tag(), &[]);
+        if slot == KeySlot::Sig {
+            self.store("sig.count", &[]);
+        }
+        SW_OK
+    }
+
+    fn public_key(&self, slot: KeySlot) -> Option<Vec<u8>> {
+        let secret = self.load_key(slot)?;
+        Some(match slot {
+            KeySlot::Dec => {
+                x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(secret)).as_bytes().to_vec()
+            }
+            _ => ed25519_keypair(&secret).pk[..].to_vec(),
+        })
+    }
+
+    fn load_do(&self, tag: u16) -> Vec<u8> { self.load(&format!("do.{:04X}", tag)).unwrap_or_default() }
+
+    /// Concatenates fixed-length DOs, zero-filling the ones that aren't set
+    fn load_dos(&self, tags: &[u16], len: usize) -> Vec<u8> {
+        let mut dos = Vec::new();
+        for &tag in tags {
+            let mut value = self.load_do(tag);
+            value.resize(len, 0);
+            dos.extend_from_slice(&value);
+        }
+        dos
+    }
+
+    fn store_do(&mut self, tag: u16, value: &[u8]) -> u16 {
+        if self.store(&format!("do.{:04X}", tag), value) { SW_OK } else { SW_MEMORY_FAILURE }
+    }
+
+    fn load(&self, name: &str) -> Option<Vec<u8>> {
+        let mut key =
+            self.pddb.get(OPENPGP_DICT, name, None, false, false, None, Some(vault::basis_change)).ok()?;
+        let mut value = Vec::new();
+        key.read_to_end(&mut value).ok()?;
+        Some(value)
+    }
+
+    /// Replaces a value; an empty one is deleted
+    fn store(&self, name: &str, value: &[u8]) -> bool {
+        self.pddb.delete_key(OPENPGP_DICT, name, None).ok();
+        let stored = if value.is_empty() {
+            true
+        } else {
+            match self.pddb.get(
+                OPENPGP_DICT,
+                name,
+                None,
+                true,
+                true,
+                Some(value.len()),
+                Some(vault::basis_change),
+            ) {
+                Ok(mut key) => key.write_all(value).is_ok(),
+                Err(e) => {
+                    log::error!("couldn't store OpenPGP card data {}: {:?}", name, e);
+                    false
+                }
+            }
+        };
+        self.pddb.sync().ok();
+        stored
+    }
+}
+
+/// Serves the card on the USB driver's CCID slot. Returns only if another process holds the slot, or the
+/// USB driver was built without the `ccid` feature.
+pub(crate) fn card_server() {
+    let xns = xous_names::XousNames::new().unwrap();
+    let pddb = pddb::Pddb::new();
+    pddb.is_mounted_blocking();
+    let usb = usb_device_xous::UsbHid::new();
+    let mut card = Card::new(&xns, pddb);
+    loop {
+        match usb.ccid_wait_apdu() {
+            Ok((id, apdu)) => {
+                let response = card.process(&apdu);
+                if let Err(e) = usb.ccid_respond(id, &response) {
+                    log::error!("couldn't send APDU response: {:?}", e);
+                }
+            }
+            Err(xous::Error::AccessDenied) => {
+                log::error!(
+                    "CCID slot is unavailable or belongs to another process, the OpenPGP card is not started"
+                );
+                return;
+            }
+            Err(e) => log::warn!("OpenPGP card got an error: {:?}", e),
+        }
+    }
+}
+
+fn ed25519_keypair(secret: &[u8; 32]) -> ed25519_compact::KeyPair {
+    ed25519_compact::KeyPair::from_seed(ed25519_compact::Seed::new(*secret))
+}
+
+/// Finds the value of `tag` in an extended header list, given the tag and length list and the values
+fn find_in_header_list<'a>(header: &[u8], values: &'a [u8], tag: u16) -> Option<&'a [u8]> {
+    let (mut pos, mut offset) = (0, 0);
+    while pos < header.len() {
+        let this_tag = get_tag(header, &mut pos)?;
+        let len = get_len(header, &mut pos)?;
+        if this_tag == tag {
+            return values.get(offset..offset + len);
+        }
+        offset += len;
+    }
+    None
+}
+
+fn get_tag(buf: &[u8], pos: &mut usize) -> Option<u16> {
+    let first = *buf.get(*pos)?;
+    *pos += 1;
+    if first & 0x1F != 0x1F {
+        return Some(first as u16);
+    }
+    let second = *buf.get(*pos)?;
+    *pos += 1;
+    Some((first as u16) << 8 | second as u16)
+}
+
+fn get_len(buf: &[u8], pos: &mut usize) -> Option<usize> {
+    let first = *buf.get(*pos)?;
+    *pos += 1;
+    match first {
+        0..=0x7F => Some(first as usize),
+        0x81 => {
+            let len = *buf.get(*pos)? as usize;
+            *pos += 1;
+            Some(len)
+        }
+        0x82 => {
+            let len = u16::from_be_bytes(buf.get(*pos..*pos + 2)?.try_into().ok()?) as usize;
+            *pos += 2;
+            Some(len)
+        }
+        _ => None,
+    }
+}
+
+fn get_tlv<'a>(buf: &'a [u8], pos: &mut usize) -> Option<(u16, &'a [u8])> {
+    let tag = get_tag(buf, pos)?;
+    let len = get_len(buf, pos)?;
+    let value = buf.get(*pos..(*pos).checked_add(len)?)?;
+    *pos += len;
+    Some((tag, value))
+}
+
+fn put_tlv(buf: &mut Vec<u8>, tag: u16, value: &[u8]) {
+    if tag > 0xFF {
+        buf.extend_from_slice(&tag.to_be_bytes());
+    } else {
+        buf.push(tag as u8);
+    }
+    match value.len() {
+        0..=0x7F => buf.push(value.len() as u8),
+        0x80..=0xFF => buf.extend_from_slice(&[0x81, value.len() as u8]),
+        _ => {
+            buf.push(0x82);
+            buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        }
+    }
+    buf.extend_from_slice(value);
+}
+
+fn tlv(tag: u16, value: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    put_tlv(&mut buf, tag, value);
+    buf
+}
@@ -0,0 +1,193 @@
+//! Parser for `otpauth://` URIs, the de-facto format of the QR codes sites show when enrolling an
+//! authenticator:
+//! `otpauth://totp/Issuer:account?secret=BASE32&issuer=Issuer&algorithm=SHA1&digits=6&period=30`
+//!
+//! See https://github.com/google/google-authenticator/wiki/Key-Uri-Format
+
+use core::convert::TryFrom;
+
+use crate::totp::TotpAlgorithm;
+
+/// A TOTP or HOTP enrollment, as read from an `otpauth://` URI
+pub(crate) struct OtpAuth {
+    /// `Issuer:account` from the label, or the label alone
+    pub name: String,
+    /// base32 (RFC4648, no padding), upper case
+    pub secret: String,
+    pub algorithm: TotpAlgorithm,
+    pub digits: u32,
+    /// the period for TOTP, or the initial counter for HOTP
+    pub timestep: u64,
+    pub is_hotp: bool,
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = core::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+impl OtpAuth {
+    /// Returns `None` if `uri` is not an `otpauth://` URI, or if its secret or parameters are invalid
+    pub(crate) fn parse(uri: &str) -> Option<OtpAuth> {
+        let rest = uri.trim().strip_prefix("otpauth://")?;
+        let (kind, rest) = rest.split_once('/')?;
+        let is_hotp = if kind.eq_ignore_ascii_case("totp") {
+            false
+        } else if kind.eq_ignore_ascii_case("hotp") {
+            true
+        } else {
+            return None;
+        };
+        let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let label = percent_decode(label)?;
+
+        let mut secret = None;
+        let mut issuer = None;
+        let mut algorithm = TotpAlgorithm::HmacSha1;
+        let mut digits = 6;
+        let mut period = 30;
+        let mut counter = 0;
+        for param in query.split('&') {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let value = percent_decode(value)?;
+            match key.to_ascii_lowercase().as_str() {
+                "secret" => secret = Some(value),
+                "issuer" => issuer = Some(value),
+                "algorithm" => {
+                    algorithm = TotpAlgorithm::try_from(value.to_ascii_uppercase().as_str()).ok()?
+                }
+                "digits" => digits = value.parse::<u32>().ok().filter(|d| (6..=8).contains(d))?,
+                "period" => period = value.parse::<u64>().ok().filter(|p| *p > 0)?,
+                "counter" => counter = value.parse::<u64>().ok()?,
+                _ => (),
+            }
+        }
+
+        // authenticators are lenient about the case, spacing and padding of the secret
+        let secret: String = secret?
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '=')
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        match base32::decode(base32::Alphabet::RFC4648 { padding: false }, &secret) {
+            Some(ss) if !ss.is_empty() => (),
+            _ => return None,
+        }
+
+        let label = label.trim();
+        let name = match issuer.as_deref().map(str::trim) {
+            // the label usually already starts with the issuer
+            Some(issuer) if !issuer.is_empty() && !label.starts_with(issuer) => {
+                if label.is_empty() {
+                    issuer.to_string()
+                } else {
+                    format!("{}:{}", issuer, label)
+                }
+            }
+            _ => label.to_string(),
+        };
+        if name.is_empty() {
+            return None;
+        }
+        Some(OtpAuth {
+            name,
+            secret,
+            algorithm,
+            digits,
+            timestep: if is_hotp { counter } else { period },
+            is_hotp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totp_defaults() {
+        let otp = OtpAuth::parse("otpauth://totp/Example:alice@example.com?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(otp.name, "Example:alice@example.com");
+        assert_eq!(otp.secret, "JBSWY3DPEHPK3PXP");
+        assert!(matches!(otp.algorithm, TotpAlgorithm::HmacSha1));
+        assert_eq!(otp.digits, 6);
+        assert_eq!(otp.timestep, 30);
+        assert!(!otp.is_hotp);
+    }
+
+    #[test]
+    fn totp_parameters() {
+        let otp = OtpAuth::parse(
+            "otpauth://TOTP/alice?secret=JBSWY3DPEHPK3PXP&algorithm=sha256&digits=8&period=60&issuer=ACME",
+        )
+        .unwrap();
+        assert!(matches!(otp.algorithm, TotpAlgorithm::HmacSha256));
+        assert_eq!(otp.digits, 8);
+        assert_eq!(otp.timestep, 60);
+        // the issuer is put in front of a label that doesn't already start with it
+        assert_eq!(otp.name, "ACME:alice");
+    }
+
+    #[test]
+    fn hotp_takes_the_counter() {
+        let otp = OtpAuth::parse("otpauth://hotp/Bank?secret=JBSWY3DPEHPK3PXP&counter=42&period=60").unwrap();
+        assert!(otp.is_hotp);
+        assert_eq!(otp.timestep, 42);
+    }
+
+    #[test]
+    fn labels_and_issuers_are_decoded() {
+        let otp = OtpAuth::parse(
+            "otpauth://totp/Big%20Corp:bob%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=Big+Corp",
+        )
+        .unwrap();
+        assert_eq!(otp.name, "Big Corp:bob@example.com");
+        // an empty label takes the issuer alone
+        let otp = OtpAuth::parse("otpauth://totp/?secret=JBSWY3DPEHPK3PXP&issuer=Solo").unwrap();
+        assert_eq!(otp.name, "Solo");
+        assert!(OtpAuth::parse("otpauth://totp/?secret=JBSWY3DPEHPK3PXP").is_none());
+        assert!(OtpAuth::parse("otpauth://totp/bad%2?secret=JBSWY3DPEHPK3PXP").is_none());
+    }
+
+    #[test]
+    fn secrets_are_normalized() {
+        let otp = OtpAuth::parse("otpauth://totp/x?secret=jbsw%20y3dp%20ehpk%203pxp====").unwrap();
+        assert_eq!(otp.secret, "JBSWY3DPEHPK3PXP");
+    }
+
+    #[test]
+    fn invalid_uris_are_refused() {
+        for uri in [
+            "https://example.com/",
+            "otpauth://motp/x?secret=JBSWY3DPEHPK3PXP",
+            "otpauth://totp/x",
+            "otpauth://totp/x?secret=",
+            "otpauth://totp/x?secret=NOT-BASE32!",
+            "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&digits=5",
+            "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&digits=9",
+            "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&period=0",
+            "otpauth://totp/x?secret=JBSWY3DPEHPK3PXP&algorithm=MD5",
+        ] {
+            assert!(OtpAuth::parse(uri).is_none(), "accepted {}", uri);
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! BIP-39 seed phrases stored as password records, and the BIP-32 derivations needed to use the
+//! device as a cold-storage aid: the BIP-84 account key can be exported as a `zpub`, and receive
+//! addresses can be displayed, all on-screen. The seed itself only leaves the device in the password
+//! backups the user allows host readout for, so that it can be restored along with everything else.
+//!
+//! A seed record is a password record whose notes start with "bip39". The password field holds the
+//! mnemonic's entropy in hex, optionally followed by a `:` and the BIP-39 passphrase.
+//!
+//! The seed, and the private keys derived from it, are zeroized once they're dropped.
+
+use std::convert::TryInto;
+
+use hmac::{Hmac, Mac};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::{NonZeroScalar, SecretKey};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256, Sha512};
+use unicode_normalization::UnicodeNormalization;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Indices at or above this are hardened
+const HARDENED: u32 = 0x8000_0000;
+/// SLIP-132 version bytes for a BIP-84 (native segwit) account public key
+const ZPUB_VERSION: [u8; 4] = [0x04, 0xb2, 0x47, 0x46];
+/// m/84'/0'/0': the first native segwit bitcoin account
+pub(crate) const BIP84_ACCOUNT: [u32; 3] = [84 | HARDENED, HARDENED, HARDENED];
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Returns true if the notes of a password record mark it as a BIP-39 seed record
+pub(crate) fn is_seed_record(notes: &str) -> bool { notes.to_ascii_lowercase().starts_with("bip39") }
+
+/// Splits the password field of a seed record into the entropy and the passphrase
+pub(crate) fn parse_record(password: &str) -> Option<(Zeroizing<Vec<u8>>, Zeroizing<String>)> {
+    let (entropy, passphrase) = match password.split_once(':') {
+        Some((entropy, passphrase)) => (entropy, passphrase),
+        None => (password, ""),
+    };
+    hex::decode(entropy).ok().map(|entropy| (Zeroizing::new(entropy), Zeroizing::new(passphrase.to_string())))
+}
+
+/// Inverse of `parse_record`
+pub(crate) fn format_record(entropy: &[u8], passphrase: &str) -> String {
+    if passphrase.len() == 0 {
+        hex::encode(entropy)
+    } else {
+        format!("{}:{}", hex::encode(entropy), passphrase)
+    }
+}
+
+/// Stretches a mnemonic and its passphrase into the 64-byte BIP-39 seed
+pub(crate) fn mnemonic_to_seed(words: &[String], passphrase: &str) -> Zeroizing<[u8; 64]> {
+    let mnemonic = Zeroizing::new(Zeroizing::new(words.join(" ")).nfkd().collect::<String>());
+    let salt = Zeroizing::new(format!("mnemonic{}", passphrase.nfkd().collect::<String>()));
+    let mut seed = Zeroizing::new([0u8; 64]);
+    pbkdf2::pbkdf2_hmac::<Sha512>(mnemonic.as_bytes(), salt.as_bytes(), 2048, &mut *seed);
+    seed
+}
+
+/// A BIP-32 extended private key. The `SecretKey` zeroizes itself, and the chain code is zeroized on
+/// drop.
+pub(crate) struct ExtendedKey {
+    key: SecretKey,
+    chain_code: [u8; 32],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+}
+
+impl Drop for ExtendedKey {
+    fn drop(&mut self) { self.chain_code.zeroize(); }
+}
+
+impl ExtendedKey {
+    pub(crate) fn master(seed: &[u8]) -> Option<ExtendedKey> {
+        let i = hmac_sha512(b"Bitcoin seed", &[seed]);
+        Some(ExtendedKey {
+            key: SecretKey::from_be_bytes(&i[..32]).ok()?,
+            chain_code: i[32..].try_into().unwrap(),
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+        })
+    }
+
+    /// CKDpriv. Returns `None` in the (astronomically unlikely) case that the index yields an invalid
+    /// key; BIP-32 says to skip to the next index.
+    pub(crate) fn child(&self, index: u32) -> Option<ExtendedKey> {
+        let i = if index >= HARDENED {
+            let mut key = self.key.to_be_bytes();
+            let i = hmac_sha512(&self.chain_code, &[&[0], &key, &index.to_be_bytes()]);
+            key.as_mut_slice().zeroize();
+            i
+        } else {
+            hmac_sha512(&self.chain_code, &[&self.public_key(), &index.to_be_bytes()])
+        };
+        let tweak = SecretKey::from_be_bytes(&i[..32]).ok()?;
+        let sum = *tweak.to_nonzero_scalar() + *self.key.to_nonzero_scalar();
+        let key: Option<NonZeroScalar> = NonZeroScalar::new(sum).into();
+        Some(ExtendedKey {
+            key: SecretKey::from(key?),
+            chain_code: i[32..].try_into().unwrap(),
+            depth: self.depth.checked_add(1)?,
+            parent_fingerprint: hash160(&self.public_key())[..4].try_into().unwrap(),
+            child_number: index,
+        })
+    }
+
+    pub(crate) fn derive(&self, path: &[u32]) -> Option<ExtendedKey> {
+        let mut key = self.child(*path.first()?)?;
+        for &index in &path[1..] {
+            key = key.child(index)?;
+        }
+        Some(key)
+    }
+
+    /// SEC1 compressed public key
+    pub(crate) fn public_key(&self) -> Vec<u8> {
+        self.key.public_key().to_encoded_point(true).as_bytes().to_vec()
+    }
+
+    /// The extended public key, serialized with the `zpub` version bytes
+    pub(crate) fn to_zpub(&self) -> String { self.to_extended_public_key(ZPUB_VERSION) }
+
+    fn to_extended_public_key(&self, version: [u8; 4]) -> String {
+        let mut data = Vec::with_capacity(78);
+        data.extend_from_slice(&version);
+        data.push(self.depth);
+        data.extend_from_slice(&self.parent_fingerprint);
+        data.extend_from_slice(&self.child_number.to_be_bytes());
+        data.extend_from_slice(&self.chain_code);
+        data.extend_from_slice(&self.public_key());
+        base58check(&data)
+    }
+
+    /// Native segwit (P2WPKH) mainnet address of this key
+    pub(crate) fn to_p2wpkh_address(&self) -> String { segwit_v0_address("bc", &hash160(&self.public_key())) }
+}
+
+/// The `index`th receive address of a BIP-84 account (m/84'/0'/0'/0/index)
+pub(crate) fn receive_address(account: &ExtendedKey, index: u32) -> Option<String> {
+    Some(account.derive(&[0, index])?.to_p2wpkh_address())
+}
+
+/// The output is a private key and a chain code, so it's zeroized once dropped
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> Zeroizing<[u8; 64]> {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).unwrap();
+    for d in data {
+        mac.update(d);
+    }
+    let mut out = Zeroizing::new([0u8; 64]);
+    out.copy_from_slice(&mac.finalize().into_bytes());
+    out
+}
+
+fn hash160(data: &[u8]) -> [u8; 20] {
+    let mut out = [0u8; 20];
+    out.copy_from_slice(&Ripemd160::digest(Sha256::digest(data)));
+    out
+}
+
+fn base58check(data: &[u8]) -> String {
+    let checksum = Sha256::digest(Sha256::digest(data));
+    let mut payload = data.to_vec();
+    payload.extend_from_slice(&checksum[..4]);
+
+    // base-58 digits, least significant first
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in payload.iter() {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let mut encoded: String = payload.iter().take_while(|&&b| b == 0).map(|_| '1').collect();
+    encoded.extend(digits.iter().rev().map(|&d| BASE58_ALPHABET[d as usize] as char));
+    encoded
+}
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GEN: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut chk: u32 = 1;
+    for &v in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ff_ffff) << 5) ^ v as u32;
+        for (i, g) in GEN.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+/// BIP-173 encoding of a version 0 witness program
+fn segwit_v0_address(hrp: &str, program: &[u8]) -> String {
+    let mut data = vec![0u8];
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for &b in program {
+        acc = (acc << 8) | b as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            data.push(((acc >> bits) & 31) as u8);
+        }
+    }
+    if bits > 0 {
+        data.push(((acc << (5 - bits)) & 31) as u8);
+    }
+
+    let mut values: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|c| c & 31));
+    values.extend_from_slice(&data);
+    values.extend_from_slice(&[0; 6]);
+    let polymod = bech32_polymod(&values) ^ 1;
+
+    let mut address = format!("{}1", hrp);
+    address.extend(data.iter().map(|&d| BECH32_CHARSET[d as usize] as char));
+    address.extend((0..6).map(|i| BECH32_CHARSET[((polymod >> (5 * (5 - i))) & 31) as usize] as char));
+    address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(mnemonic: &str) -> Vec<String> { mnemonic.split(' ').map(|w| w.to_string()).collect() }
+
+    const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+    const ABANDON_ABOUT: &str =
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn record_round_trip() {
+        let entropy = [0x7fu8; 16];
+        let (parsed, passphrase) = parse_record(&format_record(&entropy, "")).unwrap();
+        assert_eq!(&parsed[..], &entropy[..]);
+        assert_eq!(passphrase.as_str(), "");
+        // the passphrase may itself contain colons
+        let (parsed, passphrase) = parse_record(&format_record(&entropy, "a:b")).unwrap();
+        assert_eq!(&parsed[..], &entropy[..]);
+        assert_eq!(passphrase.as_str(), "a:b");
+        assert!(parse_record("not hex").is_none());
+        assert!(is_seed_record("BIP39 cold wallet"));
+        assert!(!is_seed_record("my bip39 notes"));
+    }
+
+    #[test]
+    fn bip39_seed() {
+        // from the BIP-39 reference vectors, which all use the passphrase "TREZOR"
+        assert_eq!(
+            hex::encode(&mnemonic_to_seed(&words(ABANDON_ABOUT), "TREZOR")[..]),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn bip32_test_vector_1() {
+        let master = ExtendedKey::master(&hex::decode("000102030405060708090a0b0c0d0e0f").unwrap()).unwrap();
+        assert_eq!(
+            master.to_extended_public_key(XPUB_VERSION),
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8"
+        );
+        let vectors = [
+            (
+                &[HARDENED][..],
+                "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+            ),
+            (
+                &[HARDENED, 1][..],
+                "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ",
+            ),
+            (
+                &[HARDENED, 1, 2 | HARDENED][..],
+                "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5",
+            ),
+            (
+                &[HARDENED, 1, 2 | HARDENED, 2][..],
+                "xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjTAwm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV",
+            ),
+            (
+                &[HARDENED, 1, 2 | HARDENED, 2, 1_000_000_000][..],
+                "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy",
+            ),
+        ];
+        for (path, xpub) in vectors.iter() {
+            assert_eq!(master.derive(path).unwrap().to_extended_public_key(XPUB_VERSION), *xpub);
+        }
+    }
+
+    #[test]
+    fn bip84_account() {
+        // from the BIP-84 test vectors
+        let seed = mnemonic_to_seed(&words(ABANDON_ABOUT), "");
+        let account = ExtendedKey::master(&seed[..]).unwrap().derive(&BIP84_ACCOUNT).unwrap();
+        assert_eq!(
+            account.to_zpub(),
+            "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs"
+        );
+        assert_eq!(receive_address(&account, 0).unwrap(), "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+    }
+
+    #[test]
+    fn base58check_keeps_leading_zeros() {
+        // the version 0 address of an all-zero hash
+        assert_eq!(base58check(&[0u8; 21]), "1111111111111111111114oLvT2");
+    }
+
+    #[test]
+    fn bech32_segwit_address() {
+        // from the BIP-173 examples: the P2WPKH address of the secp256k1 generator point
+        let public_key =
+            hex::decode("0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798").unwrap();
+        let program = hash160(&public_key);
+        assert_eq!(hex::encode(program), "751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(segwit_v0_address("bc", &program), "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+    }
+}
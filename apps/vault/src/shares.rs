@@ -0,0 +1,440 @@
+//! Shamir secret sharing of single password entries, so that a high-value credential (a recovery key,
+//! a seed) can be handed out as K-of-N shares: to other Precursors, which keep a scanned share as an
+//! entry of their own, or printed as QR codes. Any K shares rebuild the entry on a device; fewer
+//! reveal nothing about it.
+//!
+//! The sharing is byte-wise over GF(2^8) with the AES polynomial. What is split is the entry's
+//! description, user name, password and kind (seed or SSH key), followed by a 4-byte SHA-256 tag, so
+//! that a wrong or damaged share is caught instead of silently rebuilding garbage. A share is written as
+//!
+//!   `VSSS1:<set>:<K>:<N>:<index>:<data>`
+//!
+//! with the set id in hex and the data in base32, both upper case: that keeps the QR codes in
+//! alphanumeric mode, and a share short enough to be typed in. A held share is a password record whose
+//! notes start with "vsss-share", and whose password is the share.
+
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+pub(crate) const SHARE_NOTES: &str = "vsss-share";
+const SHARE_PREFIX: &str = "VSSS1";
+/// The most shares one entry is split into. They are numbered from 1, as x = 0 is where the secret sits
+pub(crate) const MAX_SHARES: u8 = 16;
+/// Longest entry that can be split, in bytes; it keeps a share within a text entry field
+pub(crate) const MAX_SECRET_LEN: usize = 128;
+const DATA_ENCODING: base32::Alphabet = base32::Alphabet::RFC4648 { padding: false };
+const TAG_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ShareError {
+    /// The entry is longer than `MAX_SECRET_LEN`
+    TooLong,
+    /// K and N are out of range, or K is larger than N
+    BadParameters,
+    /// The share belongs to another set, or disagrees with the others about K and N
+    Mismatch,
+    /// The share has been given already
+    Duplicate,
+    /// The shares don't rebuild the secret they were split from
+    Corrupt,
+}
+
+/// One share of a split entry
+#[derive(Debug, Clone)]
+pub(crate) struct Share {
+    /// Random, and common to all shares of one split
+    pub set: u32,
+    /// K: how many shares rebuild the entry
+    pub threshold: u8,
+    /// N: how many shares were made
+    pub count: u8,
+    /// 1..=N
+    pub index: u8,
+    pub data: Vec<u8>,
+}
+
+impl Share {
+    pub(crate) fn parse(text: &str) -> Option<Share> {
+        let mut fields = text.trim().split(':');
+        if !fields.next()?.eq_ignore_ascii_case(SHARE_PREFIX) {
+            return None;
+        }
+        let set = u32::from_str_radix(fields.next()?, 16).ok()?;
+        let threshold = fields.next()?.parse::<u8>().ok()?;
+        let count = fields.next()?.parse::<u8>().ok()?;
+        let index = fields.next()?.parse::<u8>().ok()?;
+        let data = base32::decode(DATA_ENCODING, &fields.next()?.to_ascii_uppercase())?;
+        if fields.next().is_some()
+            || threshold < 2
+            || count > MAX_SHARES
+            || threshold > count
+            || index == 0
+            || index > count
+            || data.len() <= TAG_LEN
+        {
+            return None;
+        }
+        Some(Share { set, threshold, count, index, data })
+    }
+
+    pub(crate) fn encode(&self) -> String {
+        format!(
+            "{}:{:08X}:{}:{}:{}:{}",
+            SHARE_PREFIX,
+            self.set,
+            self.threshold,
+            self.count,
+            self.index,
+            base32::encode(DATA_ENCODING, &self.data)
+        )
+    }
+
+    /// Names the share for the user, ie "3F2A91C0 2/5"
+    pub(crate) fn label(&self) -> String { format!("{:08X} {}/{}", self.set, self.index, self.count) }
+}
+
+/// Returns true if the notes of a password record mark it as a held share
+pub(crate) fn is_share_record(notes: &str) -> bool { notes.to_ascii_lowercase().starts_with(SHARE_NOTES) }
+
+/// What is split: an entry's description, user name and password, and the notes marker that makes it a
+/// seed or SSH key (empty for an ordinary password). Cleared when dropped.
+pub(crate) struct Secret {
+    pub description: String,
+    pub username: String,
+    pub password: String,
+    pub kind: String,
+}
+
+impl Secret {
+    /// The fields, one per line, with room left for the tag so that appending it doesn't leave a copy
+    /// behind in a freed buffer
+    fn to_bytes(&self) -> Vec<u8> {
+        let fields = [&self.description, &self.username, &self.password, &self.kind];
+        let len = fields.iter().map(|f| f.len() + 1).sum::<usize>() - 1;
+        let mut bytes = Vec::with_capacity(len + TAG_LEN);
+        for (i, field) in fields.iter().enumerate() {
+            if i != 0 {
+                bytes.push(b'\n');
+            }
+            bytes.extend_from_slice(field.as_bytes());
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Secret> {
+        let text = std::str::from_utf8(bytes).ok()?;
+        let mut fields = text.splitn(4, '\n');
+        Some(Secret {
+            description: fields.next()?.to_string(),
+            username: fields.next()?.to_string(),
+            password: fields.next()?.to_string(),
+            kind: fields.next()?.to_string(),
+        })
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.description.zeroize();
+        self.username.zeroize();
+        self.password.zeroize();
+        self.kind.zeroize();
+    }
+}
+
+/// Splits `secret` into `count` shares, any `threshold` of which rebuild it
+pub(crate) fn split(
+    secret: &Secret,
+    threshold: u8,
+    count: u8,
+    trng: &trng::Trng,
+) -> Result<Vec<Share>, ShareError> {
+    split_with(secret, threshold, count, || trng.get_u32().unwrap())
+}
+
+/// `split()`, with the set id and the coefficients drawn from `random`
+fn split_with(
+    secret: &Secret,
+    threshold: u8,
+    count: u8,
+    mut random: impl FnMut() -> u32,
+) -> Result<Vec<Share>, ShareError> {
+    if threshold < 2 || count > MAX_SHARES || threshold > count {
+        return Err(ShareError::BadParameters);
+    }
+    let mut payload = secret.to_bytes();
+    if payload.len() > MAX_SECRET_LEN {
+        payload.zeroize();
+        return Err(ShareError::TooLong);
+    }
+    let tag = Sha256::digest(&payload);
+    payload.extend_from_slice(&tag[..TAG_LEN]);
+
+    let set = random();
+    let mut shares: Vec<Share> = (1..=count)
+        .map(|index| Share { set, threshold, count, index, data: Vec::with_capacity(payload.len()) })
+        .collect();
+    // one polynomial of degree K-1 per byte, with the byte as its constant term
+    let mut coefficients = vec![0u8; threshold as usize];
+    for &byte in payload.iter() {
+        coefficients[0] = byte;
+        for c in coefficients[1..].iter_mut() {
+            *c = random() as u8;
+        }
+        for share in shares.iter_mut() {
+            share.data.push(evaluate(&coefficients, share.index));
+        }
+    }
+    coefficients.zeroize();
+    payload.zeroize();
+    Ok(shares)
+}
+
+/// Rebuilds the secret from exactly `threshold` shares of one set
+fn combine(shares: &[Share]) -> Result<Secret, ShareError> {
+    let len = shares[0].data.len();
+    if shares.iter().any(|s| s.data.len() != len) {
+        return Err(ShareError::Mismatch);
+    }
+    let mut payload = vec![0u8; len];
+    for (i, share) in shares.iter().enumerate() {
+        // the Lagrange basis polynomial of this share, evaluated at x = 0
+        let mut basis = 1u8;
+        for (j, other) in shares.iter().enumerate() {
+            if i != j {
+                basis = mul(basis, div(other.index, other.index ^ share.index));
+            }
+        }
+        for (p, &y) in payload.iter_mut().zip(share.data.iter()) {
+            *p ^= mul(basis, y);
+        }
+    }
+    let (secret, tag) = payload.split_at(len - TAG_LEN);
+    let result = if Sha256::digest(secret)[..TAG_LEN] != *tag {
+        Err(ShareError::Corrupt)
+    } else {
+        Secret::from_bytes(secret).ok_or(ShareError::Corrupt)
+    };
+    payload.zeroize();
+    result
+}
+
+/// Shares collected towards rebuilding one entry
+pub(crate) struct Recovery {
+    shares: Vec<Share>,
+}
+
+impl Recovery {
+    pub(crate) fn new() -> Recovery { Recovery { shares: Vec::new() } }
+
+    /// (shares held, shares needed); nothing is needed until the first share says how many
+    pub(crate) fn progress(&self) -> (usize, usize) {
+        (self.shares.len(), self.shares.first().map(|s| s.threshold as usize).unwrap_or(0))
+    }
+
+    /// Adds a share. Once enough shares are in, the secret is rebuilt and returned. If they don't rebuild
+    /// it, the share just added is dropped again, so that another share can be tried in its place.
+    pub(crate) fn add(&mut self, share: Share) -> Result<Option<Secret>, ShareError> {
+        if let Some(first) = self.shares.first() {
+            if share.set != first.set || share.threshold != first.threshold || share.count != first.count {
+                return Err(ShareError::Mismatch);
+            }
+            if self.shares.iter().any(|s| s.index == share.index) {
+                return Err(ShareError::Duplicate);
+            }
+        }
+        self.shares.push(share);
+        let (have, need) = self.progress();
+        if have < need {
+            return Ok(None);
+        }
+        match combine(&self.shares) {
+            Ok(secret) => Ok(Some(secret)),
+            Err(e) => {
+                if let Some(mut share) = self.shares.pop() {
+                    share.data.zeroize();
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Drop for Recovery {
+    fn drop(&mut self) {
+        for share in self.shares.iter_mut() {
+            share.data.zeroize();
+        }
+    }
+}
+
+/// Evaluates the polynomial at `x` with Horner's rule
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients.iter().rev().fold(0u8, |acc, &c| mul(acc, x) ^ c)
+}
+
+/// a * b. The operands are secret, so there are no branches on them: every bit is gone through, and
+/// the conditional steps are done with masks.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & (b & 1).wrapping_neg();
+        // multiply a by x, reducing by x^8 + x^4 + x^3 + x + 1
+        a = (a << 1) ^ (0x1b & (a >> 7).wrapping_neg());
+        b >>= 1;
+    }
+    product
+}
+
+/// a / b, with b non-zero: the inverse of b is b^254. The exponent is fixed, so the steps don't depend
+/// on b either.
+fn div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1u8;
+    let mut square = b;
+    let mut exponent = 254u8;
+    while exponent != 0 {
+        if exponent & 1 != 0 {
+            inverse = mul(inverse, square);
+        }
+        square = mul(square, square);
+        exponent >>= 1;
+    }
+    mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A repeatable stand-in for the TRNG (xorshift32)
+    fn rng(seed: u32) -> impl FnMut() -> u32 {
+        let mut state = seed;
+        move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        }
+    }
+
+    fn secret() -> Secret {
+        Secret {
+            description: "bank".to_string(),
+            username: "alice".to_string(),
+            password: "correct horse battery staple".to_string(),
+            kind: String::new(),
+        }
+    }
+
+    fn assert_same(a: &Secret, b: &Secret) {
+        assert_eq!(a.description, b.description);
+        assert_eq!(a.username, b.username);
+        assert_eq!(a.password, b.password);
+        assert_eq!(a.kind, b.kind);
+    }
+
+    #[test]
+    fn field_axioms() {
+        // the worked example of FIPS-197, section 4.2
+        assert_eq!(mul(0x57, 0x83), 0xc1);
+        assert_eq!(mul(0x57, 0x13), 0xfe);
+        for a in 0..=255u8 {
+            assert_eq!(mul(a, 0), 0);
+            assert_eq!(mul(a, 1), a);
+            for b in 0..=255u8 {
+                assert_eq!(mul(a, b), mul(b, a));
+                if b != 0 {
+                    assert_eq!(div(mul(a, b), b), a);
+                }
+            }
+            if a != 0 {
+                assert_eq!(mul(a, div(1, a)), 1);
+            }
+        }
+        for (a, b, c) in [(0x02, 0x03, 0x05), (0x57, 0x83, 0x13), (0xff, 0x80, 0x1b), (0xca, 0x53, 0x01)] {
+            assert_eq!(mul(mul(a, b), c), mul(a, mul(b, c)));
+            assert_eq!(mul(a, b ^ c), mul(a, b) ^ mul(a, c));
+        }
+    }
+
+    #[test]
+    fn k_of_n_round_trip() {
+        for (threshold, count) in [(2, 2), (2, 3), (3, 5), (5, 16), (16, 16)] {
+            let shares = split_with(&secret(), threshold, count, rng(0x1234_5678)).unwrap();
+            assert_eq!(shares.len(), count as usize);
+            let k = threshold as usize;
+            let subsets: [Vec<Share>; 3] = [
+                shares[..k].to_vec(),
+                shares[shares.len() - k..].to_vec(),
+                shares.iter().rev().step_by((shares.len() / k).max(1)).take(k).cloned().collect(),
+            ];
+            for subset in subsets.iter() {
+                assert_eq!(subset.len(), k);
+                assert_same(&combine(subset).unwrap(), &secret());
+            }
+        }
+    }
+
+    #[test]
+    fn recovery_through_text() {
+        let shares = split_with(&secret(), 3, 5, rng(42)).unwrap();
+        let mut recovery = Recovery::new();
+        assert_eq!(recovery.progress(), (0, 0));
+        assert!(recovery.add(Share::parse(&shares[4].encode()).unwrap()).unwrap().is_none());
+        assert!(recovery.add(Share::parse(&shares[1].encode().to_lowercase()).unwrap()).unwrap().is_none());
+        assert_eq!(recovery.progress(), (2, 3));
+        assert_eq!(recovery.add(shares[1].clone()).err(), Some(ShareError::Duplicate));
+        let other = split_with(&secret(), 3, 5, rng(7)).unwrap();
+        assert_eq!(recovery.add(other[0].clone()).err(), Some(ShareError::Mismatch));
+        assert_same(&recovery.add(shares[0].clone()).unwrap().unwrap(), &secret());
+    }
+
+    #[test]
+    fn corrupt_share_is_set_aside() {
+        let shares = split_with(&secret(), 2, 3, rng(99)).unwrap();
+        let mut damaged = shares[1].clone();
+        damaged.data[0] ^= 0x01;
+        let mut recovery = Recovery::new();
+        assert!(recovery.add(shares[0].clone()).unwrap().is_none());
+        assert_eq!(recovery.add(damaged).err(), Some(ShareError::Corrupt));
+        assert_eq!(recovery.progress(), (1, 2));
+        assert_same(&recovery.add(shares[2].clone()).unwrap().unwrap(), &secret());
+    }
+
+    #[test]
+    fn split_rejects() {
+        assert_eq!(split_with(&secret(), 1, 3, rng(1)).err(), Some(ShareError::BadParameters));
+        assert_eq!(split_with(&secret(), 4, 3, rng(1)).err(), Some(ShareError::BadParameters));
+        assert_eq!(split_with(&secret(), 2, MAX_SHARES + 1, rng(1)).err(), Some(ShareError::BadParameters));
+        let mut long = secret();
+        long.password = "x".repeat(MAX_SECRET_LEN);
+        assert_eq!(split_with(&long, 2, 3, rng(1)).err(), Some(ShareError::TooLong));
+    }
+
+    #[test]
+    fn parse_rejects() {
+        let good = split_with(&secret(), 2, 3, rng(5)).unwrap()[0].encode();
+        assert!(Share::parse(&good).is_some());
+        let fields: Vec<&str> = good.split(':').collect();
+        let with = |i: usize, value: &str| {
+            let mut fields = fields.clone();
+            fields[i] = value;
+            fields.join(":")
+        };
+        for bad in [
+            with(0, "VSSS2"),
+            with(1, "NOTHEX"),
+            with(2, "1"),
+            with(2, "4"),
+            with(3, "17"),
+            with(4, "0"),
+            with(4, "4"),
+            with(5, "1!"),
+            with(5, "AAAA"),
+            format!("{}:EXTRA", good),
+            fields[..5].join(":"),
+        ] {
+            assert!(Share::parse(&bad).is_none(), "{} was accepted", bad);
+        }
+    }
+}
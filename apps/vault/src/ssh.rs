@@ -0,0 +1,293 @@
+//! An SSH agent for ed25519 keys kept in the vault. The host side is `vaultbackup-rs ssh-agent`, which
+//! listens on a Unix socket and forwards each agent request (RFC draft-miller-ssh-agent) over the
+//! `COMMAND_SSH_AGENT` vendor command. Private keys never leave the device, and every key listing and
+//! every signature has to be confirmed on the device.
+//!
+//! An SSH key is a password record whose notes start with "ssh-ed25519". The password field holds the
+//! 32-byte ed25519 seed in hex, and the description is used as the key's comment.
+
+#[cfg(feature = "ed25519")]
+use std::convert::TryInto;
+
+#[cfg(feature = "ed25519")]
+use base64::Engine;
+#[cfg(feature = "ed25519")]
+use locales::t;
+
+#[cfg(feature = "ed25519")]
+use crate::storage::{ContentKind, PasswordRecord};
+
+pub(crate) const SSH_KEY_NOTES: &str = "ssh-ed25519";
+
+const SSH_AGENT_FAILURE: u8 = 5;
+#[cfg(feature = "ed25519")]
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+#[cfg(feature = "ed25519")]
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+#[cfg(feature = "ed25519")]
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+#[cfg(feature = "ed25519")]
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+/// the first byte of the data signed for public key authentication (RFC 4252, section 7)
+#[cfg(feature = "ed25519")]
+const SSH_MSG_USERAUTH_REQUEST: u8 = 50;
+
+/// Returns true if the notes of a password record mark it as an SSH key
+pub(crate) fn is_ssh_record(notes: &str) -> bool { notes.to_ascii_lowercase().starts_with(SSH_KEY_NOTES) }
+
+/// Generates the password field for a new SSH key
+pub(crate) fn generate_record(trng: &trng::Trng) -> String {
+    let mut seed = [0u8; 32];
+    for chunk in seed.chunks_mut(8) {
+        chunk.copy_from_slice(&trng.get_u64().unwrap().to_le_bytes());
+    }
+    hex::encode(seed)
+}
+
+#[cfg(feature = "ed25519")]
+pub(crate) fn keypair(password: &str) -> Option<ed25519_compact::KeyPair> {
+    let seed = ed25519_compact::Seed::from_slice(&hex::decode(password).ok()?).ok()?;
+    Some(ed25519_compact::KeyPair::from_seed(seed))
+}
+
+/// The key blob that identifies a public key in the SSH wire format
+#[cfg(feature = "ed25519")]
+fn public_key_blob(pk: &ed25519_compact::PublicKey) -> Vec<u8> {
+    let mut blob = Vec::new();
+    put_string(&mut blob, SSH_KEY_NOTES.as_bytes());
+    put_string(&mut blob, &pk[..]);
+    blob
+}
+
+/// The public key in the format of an `authorized_keys` line
+#[cfg(feature = "ed25519")]
+pub(crate) fn openssh_public_key(pk: &ed25519_compact::PublicKey, comment: &str) -> String {
+    format!(
+        "{} {} {}",
+        SSH_KEY_NOTES,
+        base64::engine::general_purpose::STANDARD.encode(public_key_blob(pk)),
+        comment
+    )
+}
+
+/// Answers one agent request, given and returned without the length prefix. Anything that can't be
+/// answered, including a signature the user declines, gets `SSH_AGENT_FAILURE`.
+#[cfg_attr(not(feature = "ed25519"), allow(unused_variables))]
+pub(crate) fn handle_agent_message(request: &[u8], xns: &xous_names::XousNames) -> Vec<u8> {
+    let response = match request.first() {
+        #[cfg(feature = "ed25519")]
+        Some(&SSH_AGENTC_REQUEST_IDENTITIES) => list_identities(xns),
+        #[cfg(feature = "ed25519")]
+        Some(&SSH_AGENTC_SIGN_REQUEST) => sign(&request[1..], xns),
+        _ => None,
+    };
+    response.unwrap_or_else(|| vec![SSH_AGENT_FAILURE])
+}
+
+#[cfg(feature = "ed25519")]
+fn ssh_keys(xns: &xous_names::XousNames) -> Option<Vec<(PasswordRecord, ed25519_compact::KeyPair)>> {
+    let storage = crate::storage::Manager::new(xns);
+    let records: Vec<PasswordRecord> = match storage.all(ContentKind::Password) {
+        Ok(records) => records,
+        Err(e) => {
+            log::error!("couldn't read SSH keys: {:?}", e);
+            return None;
+        }
+    };
+    Some(
+        records
+            .into_iter()
+            .filter(|pw| is_ssh_record(&pw.notes))
+            .filter_map(|pw| {
+                let kp = keypair(&pw.password)?;
+                Some((pw, kp))
+            })
+            .collect(),
+    )
+}
+
+/// Asks the user to allow an agent request. The host can't learn anything about the keys without it.
+#[cfg(feature = "ed25519")]
+fn confirm(xns: &xous_names::XousNames, prompt: &str) -> bool {
+    let modals = modals::Modals::new(xns).unwrap();
+    modals
+        .add_list(vec![t!("vault.yes", locales::LANG), t!("vault.no", locales::LANG)])
+        .expect("couldn't build confirmation dialog");
+    match modals.get_radiobutton(prompt) {
+        Ok(response) => &response == t!("vault.yes", locales::LANG),
+        _ => false,
+    }
+}
+
+#[cfg(feature = "ed25519")]
+fn list_identities(xns: &xous_names::XousNames) -> Option<Vec<u8>> {
+    let keys = ssh_keys(xns)?;
+    if keys.len() > 0 && !confirm(xns, t!("vault.ssh.confirm_list", locales::LANG)) {
+        log::info!("SSH key listing declined");
+        return None;
+    }
+    Some(identities_answer(&keys))
+}
+
+#[cfg(feature = "ed25519")]
+fn identities_answer(keys: &[(PasswordRecord, ed25519_compact::KeyPair)]) -> Vec<u8> {
+    let mut response = vec![SSH_AGENT_IDENTITIES_ANSWER];
+    response.extend_from_slice(&(keys.len() as u32).to_be_bytes());
+    for (pw, kp) in keys.iter() {
+        put_string(&mut response, &public_key_blob(&kp.pk));
+        put_string(&mut response, pw.description.as_bytes());
+    }
+    response
+}
+
+#[cfg(feature = "ed25519")]
+fn sign(request: &[u8], xns: &xous_names::XousNames) -> Option<Vec<u8>> {
+    let mut pos = 0;
+    let key_blob = get_string(request, &mut pos)?;
+    let data = get_string(request, &mut pos)?;
+    let (pw, kp) = ssh_keys(xns)?.into_iter().find(|(_, kp)| public_key_blob(&kp.pk) == key_blob)?;
+
+    let user = login_user(data).unwrap_or_else(|| t!("vault.ssh.unknown_user", locales::LANG).to_string());
+    let prompt = format!(
+        "{}\n\n{}\n{}: {}",
+        t!("vault.ssh.confirm_sign", locales::LANG),
+        pw.description,
+        t!("vault.ssh.user", locales::LANG),
+        user
+    );
+    if !confirm(xns, &prompt) {
+        log::info!("SSH signature declined");
+        return None;
+    }
+    Some(sign_response(&kp, data))
+}
+
+/// The user a public key authentication request logs in as. That request signs the session ID
+/// followed by the user's login request.
+#[cfg(feature = "ed25519")]
+fn login_user(data: &[u8]) -> Option<String> {
+    let mut pos = 0;
+    let _session_id = get_string(data, &mut pos)?;
+    if data.get(pos) != Some(&SSH_MSG_USERAUTH_REQUEST) {
+        return None;
+    }
+    pos += 1;
+    get_string(data, &mut pos).map(|user| String::from_utf8_lossy(user).to_string())
+}
+
+#[cfg(feature = "ed25519")]
+fn sign_response(kp: &ed25519_compact::KeyPair, data: &[u8]) -> Vec<u8> {
+    let mut signature = Vec::new();
+    put_string(&mut signature, SSH_KEY_NOTES.as_bytes());
+    put_string(&mut signature, &kp.sk.sign(data, None)[..]);
+    let mut response = vec![SSH_AGENT_SIGN_RESPONSE];
+    put_string(&mut response, &signature);
+    response
+}
+
+#[cfg(feature = "ed25519")]
+fn get_string<'a>(buf: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    let len = u32::from_be_bytes(buf.get(*pos..*pos + 4)?.try_into().ok()?) as usize;
+    let string = buf.get(*pos + 4..(*pos + 4).checked_add(len)?)?;
+    *pos += 4 + len;
+    Some(string)
+}
+
+#[cfg(feature = "ed25519")]
+fn put_string(buf: &mut Vec<u8>, string: &[u8]) {
+    buf.extend_from_slice(&(string.len() as u32).to_be_bytes());
+    buf.extend_from_slice(string);
+}
+
+#[cfg(all(test, feature = "ed25519"))]
+mod tests {
+    use super::*;
+
+    // the keys of the first ed25519 test vector of RFC 8032
+    const SEED: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+    const PUBLIC_KEY: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+
+    fn record(description: &str) -> PasswordRecord {
+        PasswordRecord {
+            version: 1,
+            description: description.to_string(),
+            username: String::new(),
+            password: SEED.to_string(),
+            notes: SSH_KEY_NOTES.to_string(),
+            ctime: 0,
+            atime: 0,
+            count: 0,
+        }
+    }
+
+    #[test]
+    fn strings_round_trip() {
+        let mut buf = Vec::new();
+        put_string(&mut buf, b"hello");
+        put_string(&mut buf, b"");
+        let mut pos = 0;
+        assert_eq!(get_string(&buf, &mut pos), Some(&b"hello"[..]));
+        assert_eq!(get_string(&buf, &mut pos), Some(&b""[..]));
+        assert_eq!(pos, buf.len());
+        assert_eq!(get_string(&buf, &mut pos), None);
+        // a length running past the end of the buffer
+        assert_eq!(get_string(&[0xff, 0xff, 0xff, 0xff, 0], &mut 0), None);
+    }
+
+    #[test]
+    fn public_key_formats() {
+        let kp = keypair(SEED).unwrap();
+        assert_eq!(hex::encode(&kp.pk[..]), PUBLIC_KEY);
+        assert_eq!(
+            openssh_public_key(&kp.pk, "me@precursor"),
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAINdamAGCsQq31Uv+08lkBzoO4XLz2qYjJa8CGmj3B1Ea me@precursor"
+        );
+        assert!(keypair("not hex").is_none());
+        assert!(keypair("00").is_none());
+    }
+
+    #[test]
+    fn identities_answer_lists_every_key() {
+        let kp = keypair(SEED).unwrap();
+        let answer = identities_answer(&[(record("laptop"), kp.clone()), (record("server"), kp.clone())]);
+        assert_eq!(answer[0], SSH_AGENT_IDENTITIES_ANSWER);
+        assert_eq!(answer[1..5], 2u32.to_be_bytes());
+        let mut pos = 5;
+        for comment in [&b"laptop"[..], &b"server"[..]] {
+            assert_eq!(get_string(&answer, &mut pos), Some(&public_key_blob(&kp.pk)[..]));
+            assert_eq!(get_string(&answer, &mut pos), Some(comment));
+        }
+        assert_eq!(pos, answer.len());
+    }
+
+    #[test]
+    fn login_user_is_read_from_the_request() {
+        let mut data = Vec::new();
+        put_string(&mut data, &[0x55; 32]);
+        data.push(SSH_MSG_USERAUTH_REQUEST);
+        put_string(&mut data, b"alice");
+        put_string(&mut data, b"ssh-connection");
+        assert_eq!(login_user(&data).as_deref(), Some("alice"));
+
+        // anything else is signed without naming a user
+        let mut data = Vec::new();
+        put_string(&mut data, &[0x55; 32]);
+        data.push(SSH_MSG_USERAUTH_REQUEST + 1);
+        assert_eq!(login_user(&data), None);
+        assert_eq!(login_user(&[]), None);
+    }
+
+    #[test]
+    fn sign_response_verifies() {
+        let kp = keypair(SEED).unwrap();
+        let response = sign_response(&kp, b"data to sign");
+        assert_eq!(response[0], SSH_AGENT_SIGN_RESPONSE);
+        let mut pos = 1;
+        let signature = get_string(&response, &mut pos).unwrap();
+        let mut pos = 0;
+        assert_eq!(get_string(signature, &mut pos), Some(SSH_KEY_NOTES.as_bytes()));
+        let signature =
+            ed25519_compact::Signature::from_slice(get_string(signature, &mut pos).unwrap()).unwrap();
+        assert!(kp.pk.verify(b"data to sign", &signature).is_ok());
+    }
+}
@@ -22,6 +22,20 @@ pub fn create_submenu(vault_conn: xous::CID, actions_conn: xous::CID, menu_mgr:
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
+    menu_items.push(MenuItem {
+        name: xous_ipc::String::from_str(t!("vault.menu_split", locales::LANG)),
+        action_conn: Some(vault_conn),
+        action_opcode: VaultOp::MenuSplitStage1.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+    menu_items.push(MenuItem {
+        name: xous_ipc::String::from_str(t!("vault.menu_recover", locales::LANG)),
+        action_conn: Some(actions_conn),
+        action_opcode: ActionOp::MenuRecoverShares.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
     menu_items.push(MenuItem {
         name: xous_ipc::String::from_str(t!("vault.menu_unlock_basis", locales::LANG)),
         action_conn: Some(actions_conn),
@@ -36,6 +50,14 @@ pub fn create_submenu(vault_conn: xous::CID, actions_conn: xous::CID, menu_mgr:
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
+    #[cfg(feature = "breach-check")]
+    menu_items.push(MenuItem {
+        name: xous_ipc::String::from_str(t!("vault.menu_breach_check", locales::LANG)),
+        action_conn: Some(actions_conn),
+        action_opcode: ActionOp::MenuBreachCheck.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
     menu_items.push(MenuItem {
         name: xous_ipc::String::from_str(t!("vault.menu_change_font", locales::LANG)),
         action_conn: Some(vault_conn),
@@ -72,6 +94,13 @@ pub fn create_submenu(vault_conn: xous::CID, actions_conn: xous::CID, menu_mgr:
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
+    menu_items.push(MenuItem {
+        name: xous_ipc::String::from_str(t!("vault.menu_signature_counter", locales::LANG)),
+        action_conn: Some(vault_conn),
+        action_opcode: VaultOp::MenuSignatureCounter.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
     menu_items.push(MenuItem {
         name: xous_ipc::String::from_str(t!("vault.menu_close", locales::LANG)),
         action_conn: Some(actions_conn),
@@ -1,142 +1,37 @@
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::{
-    convert::TryFrom,
-    time::{SystemTime, SystemTimeError},
-};
+use std::time::{SystemTime, SystemTimeError};
 
-use hmac::{Hmac, Mac};
 use num_traits::*;
-use sha1::Sha1;
+pub use vault_totp::{generate_totp_code, TotpAlgorithm, TotpEntry};
 use xous::{send_message, Message};
 
 use crate::VaultMode;
 
-// Derived from https://github.com/blakesmith/xous-core/blob/xtotp-time/apps/xtotp/src/main.rs
-#[derive(Clone, Copy)]
-pub enum TotpAlgorithm {
-    HmacSha1,
-    HmacSha256,
-    HmacSha512,
-    None,
-}
-
-impl Default for TotpAlgorithm {
-    fn default() -> Self { Self::None }
-}
-
-impl std::fmt::Debug for TotpAlgorithm {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            TotpAlgorithm::HmacSha1 => write!(f, "SHA1"),
-            TotpAlgorithm::HmacSha256 => write!(f, "SHA256"),
-            TotpAlgorithm::HmacSha512 => write!(f, "SHA512"),
-            TotpAlgorithm::None => write!(f, "None"),
-        }
-    }
-}
-
-impl TryFrom<&str> for TotpAlgorithm {
-    type Error = xous::Error;
-
-    fn try_from(s: &str) -> Result<Self, Self::Error> {
-        match s {
-            "SHA1" => Ok(TotpAlgorithm::HmacSha1),
-            "SHA256" => Ok(TotpAlgorithm::HmacSha256),
-            "SHA512" => Ok(TotpAlgorithm::HmacSha512),
-            _ => Err(xous::Error::InvalidString),
-        }
-    }
-}
-impl core::fmt::Display for TotpAlgorithm {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        match self {
-            TotpAlgorithm::HmacSha1 => write!(f, "SHA1"),
-            TotpAlgorithm::HmacSha256 => write!(f, "SHA256"),
-            TotpAlgorithm::HmacSha512 => write!(f, "SHA512"),
-            TotpAlgorithm::None => write!(f, "None"),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct TotpEntry {
-    pub step_seconds: u64,
-    pub shared_secret: Vec<u8>,
-    pub digit_count: u8,
-    pub algorithm: TotpAlgorithm,
-}
-
 pub fn get_current_unix_time() -> Result<u64, SystemTimeError> {
     SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|duration| duration.as_secs())
 }
 
-fn unpack_u64(v: u64) -> [u8; 8] {
-    let mask = 0x00000000000000ff;
-    let mut bytes: [u8; 8] = [0; 8];
-    (0..8).for_each(|i| bytes[7 - i] = (mask & (v >> (i * 8))) as u8);
-    bytes
-}
-
-fn generate_hmac_bytes(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result<Vec<u8>, xous::Error> {
-    let mut computed_hmac = Vec::new();
-    let checked_step = if totp_entry.step_seconds == 0 {
-        log::warn!(
-            "totp step_seconds was 0, this would cause a div-by-zero; forcing to 1. Check that this is not an HOTP record?"
-        );
-        1
-    } else {
-        totp_entry.step_seconds
-    };
-    match totp_entry.algorithm {
-        // The OpenTitan HMAC core does not support hmac-sha1. Fall back to
-        // a software implementation.
-        TotpAlgorithm::HmacSha1 => {
-            let mut mac: Hmac<Sha1> =
-                Hmac::new_from_slice(&totp_entry.shared_secret).map_err(|_| xous::Error::InternalError)?;
-            mac.update(&unpack_u64(unix_timestamp / checked_step));
-            let hash: &[u8] = &mac.finalize().into_bytes();
-            computed_hmac.extend_from_slice(hash);
-        }
-        // note: sha256/sha512 implementations not yet tested, as we have yet to find a site that uses this to
-        // test against.
-        TotpAlgorithm::HmacSha256 => {
-            let mut mac: Hmac<sha2::Sha256> =
-                Hmac::new_from_slice(&totp_entry.shared_secret).map_err(|_| xous::Error::InternalError)?;
-            mac.update(&unpack_u64(unix_timestamp / checked_step));
-            let hash: &[u8] = &mac.finalize().into_bytes();
-            computed_hmac.extend_from_slice(hash);
-        }
-        TotpAlgorithm::HmacSha512 => {
-            let mut mac: Hmac<sha2::Sha512> =
-                Hmac::new_from_slice(&totp_entry.shared_secret).map_err(|_| xous::Error::InternalError)?;
-            mac.update(&unpack_u64(unix_timestamp / checked_step));
-            let hash: &[u8] = &mac.finalize().into_bytes();
-            computed_hmac.extend_from_slice(hash);
-        }
-        TotpAlgorithm::None => {
-            panic!("cannot generate hmac bytes for None algorithm")
-        }
+/// The step of a TOTP entry, from the `extra` field of its list item (`secret:digits:step:algorithm:kind`).
+/// HOTP and malformed entries don't have one.
+pub fn totp_step_seconds(extra: &str) -> Option<u64> {
+    let fields = extra.split(':').collect::<Vec<&str>>();
+    if fields.len() != 5 || fields[4].to_uppercase() == "HOTP" {
+        return None;
     }
-
-    Ok(computed_hmac)
+    u64::from_str_radix(fields[2], 10).ok().filter(|&step| step != 0)
 }
 
-pub fn generate_totp_code(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result<String, xous::Error> {
-    let hash = generate_hmac_bytes(unix_timestamp, totp_entry)?;
-    let offset: usize = (hash.last().unwrap_or(&0) & 0xf) as usize;
-    let binary: u64 = (((hash[offset] & 0x7f) as u64) << 24)
-        | ((hash[offset + 1] as u64) << 16)
-        | ((hash[offset + 2] as u64) << 8)
-        | (hash[offset + 3] as u64);
-
-    let truncated_code = format!(
-        "{:01$}",
-        binary % (10_u64.pow(totp_entry.digit_count as u32)),
-        totp_entry.digit_count as usize
-    );
+/// A margin past the second, so that the clock is sure to read the new second on waking
+const TICK_GUARD_MS: usize = 10;
 
-    Ok(truncated_code)
+/// The time to sleep until just after the next whole second of wall-clock time. Every TOTP step
+/// boundary falls on a whole second, so waking on each one turns codes over as soon as they change.
+fn ms_to_next_second() -> usize {
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(now) => 1000 - now.subsec_millis() as usize + TICK_GUARD_MS,
+        Err(_) => 1000,
+    }
 }
 
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
@@ -173,7 +68,10 @@ pub(crate) fn pumper(
                         {
                             // we really want mode.lock() to be in a different scope so...
                             if mode_cache == VaultMode::Totp {
-                                tt.sleep_ms(2000).unwrap();
+                                // the ticktimer has no wall-clock alarms, so sleep up to the next second
+                                // instead: the redraw then only repaints the countdown bar's strip, and the
+                                // codes that have reached a step boundary.
+                                tt.sleep_ms(ms_to_next_second()).unwrap();
                                 send_message(
                                     self_conn,
                                     Message::new_scalar(PumpOp::Pump.to_usize().unwrap(), 0, 0, 0, 0),
@@ -15,7 +15,7 @@ use usb_device_xous::UsbDeviceType;
 use vault::{utc_now, VaultOp};
 
 use crate::actions::ActionOp;
-use crate::totp::{generate_totp_code, get_current_unix_time, TotpAlgorithm, TotpEntry};
+use crate::totp::{generate_totp_code, get_current_unix_time, totp_step_seconds, TotpAlgorithm, TotpEntry};
 use crate::{ItemLists, SelectedEntry, VaultMode};
 
 pub enum NavDir {
@@ -64,10 +64,13 @@ pub struct VaultUx {
     /// usb interface
     usb_dev: usb_device_xous::UsbHid,
     usb_type: UsbDeviceType,
+    /// clipboard, for one-time codes
+    clipboard: clipboard::Clipboard,
 
-    /// totp redraw state
-    last_epoch: u64,
-    current_time: u64,
+    /// totp redraw state: the time of the last redraw, and the (seconds left, step) of the code on the
+    /// current page that is next to change
+    last_time: u64,
+    countdown: Option<(u64, u64)>,
 }
 
 pub const DEFAULT_FONT: GlyphStyle = GlyphStyle::Regular;
@@ -127,8 +130,6 @@ impl VaultUx {
         let items_per_screen = available_height / item_height;
         item_lists.lock().unwrap().set_items_per_screen(items_per_screen);
 
-        let current_time = get_current_unix_time().unwrap_or(0);
-
         VaultUx {
             content,
             gam,
@@ -147,8 +148,9 @@ impl VaultUx {
             actions_conn,
             action_active,
             usb_dev: usb_device_xous::UsbHid::new(),
-            last_epoch: current_time / 30,
-            current_time,
+            clipboard: clipboard::Clipboard::new(xns, "vault").expect("can't connect to clipboard"),
+            last_time: get_current_unix_time().unwrap_or(0),
+            countdown: None,
             last_query: String::new(),
             usb_type: UsbDeviceType::FidoKbd,
         }
@@ -184,7 +186,9 @@ impl VaultUx {
         self.menu_mgr.delete_item(t!("vault.menu_autotype", locales::LANG));
         self.menu_mgr.delete_item(t!("vault.menu_autotype_username", locales::LANG));
         self.menu_mgr.delete_item(t!("vault.menu_addnew", locales::LANG));
-        match *self.mode.lock().unwrap() {
+        self.menu_mgr.delete_item(t!("vault.menu_copy_code", locales::LANG));
+        let mode = *self.mode.lock().unwrap();
+        match mode {
             VaultMode::Fido => (),
             VaultMode::Password | VaultMode::Totp => {
                 self.menu_mgr.insert_item(
@@ -217,6 +221,18 @@ impl VaultUx {
                     },
                     0,
                 );
+                if mode == VaultMode::Totp {
+                    self.menu_mgr.insert_item(
+                        MenuItem {
+                            name: xous_ipc::String::from_str(t!("vault.menu_copy_code", locales::LANG)),
+                            action_conn: Some(self.main_conn),
+                            action_opcode: VaultOp::MenuCopyCode.to_u32().unwrap(),
+                            action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+                            close_on_select: true,
+                        },
+                        0,
+                    );
+                }
             }
         }
     }
@@ -292,6 +308,8 @@ impl VaultUx {
         self.item_lists.lock().unwrap().nav((*self.mode.lock().unwrap()).clone(), dir);
     }
 
+    pub(crate) fn items_per_screen(&self) -> usize { self.items_per_screen.max(1) as usize }
+
     /// accept a new input string
     pub(crate) fn input(&mut self, line: &str) -> Result<(), xous::Error> {
         self.title_dirty = true;
@@ -419,6 +437,48 @@ impl VaultUx {
         }
     }
 
+    /// Draws the countdown bar under the title, which runs out as the next code on the page changes.
+    /// `clear` blanks the bar's strip first, for when the title around it is not being redrawn.
+    fn draw_countdown(&self, clear: bool) {
+        const BAR_HEIGHT: i16 = 5;
+        const BAR_GAP: i16 = -10;
+        let tl = Point::new(self.margin.x, TITLE_HEIGHT - (BAR_HEIGHT + BAR_GAP));
+        if clear {
+            self.gam
+                .draw_rectangle(
+                    self.content,
+                    Rectangle::new_with_style(
+                        tl,
+                        Point::new(self.screensize.x - self.margin.x, TITLE_HEIGHT - BAR_GAP),
+                        DrawStyle {
+                            fill_color: Some(PixelColor::Light),
+                            stroke_color: None,
+                            stroke_width: 0,
+                        },
+                    ),
+                )
+                .ok();
+        }
+        if let Some((left, step)) = self.countdown {
+            let width = (self.screensize.x - (self.margin.x * 2)) as u64;
+            let left_width = (left * width / step) as i16;
+            self.gam
+                .draw_rectangle(
+                    self.content,
+                    Rectangle {
+                        tl,
+                        br: Point::new(self.margin.x + left_width, TITLE_HEIGHT - BAR_GAP),
+                        style: DrawStyle {
+                            fill_color: Some(PixelColor::Dark),
+                            stroke_color: None,
+                            stroke_width: 0,
+                        },
+                    },
+                )
+                .ok();
+        }
+    }
+
     pub(crate) fn redraw(&mut self) -> Result<(), xous::Error> {
         // to reduce locking thrash, we cache a copy of the current mode at the top of redraw.
         // this could lead to some race conditions that lead to awkward problems, we'll see, but it
@@ -426,18 +486,23 @@ impl VaultUx {
         let mode_at_entry = (*self.mode.lock().unwrap()).clone();
 
         if mode_at_entry == VaultMode::Totp {
-            // always redraw the title in TOTP mode
-            self.title_dirty = true;
-            // always grab the current time, regardless of the mode? saves an extra call to get time...
-            self.current_time = get_current_unix_time().unwrap_or(0);
-            // duration bar is hard-coded to once every 30 seconds, even if the keys may not change that
-            // often.
-            let epoch = self.current_time / 30;
-            if self.last_epoch != epoch {
-                self.last_epoch = epoch;
-                // force a redraw of all the items if the epoch has changed
-                self.item_lists.lock().unwrap().mark_all_dirty();
+            let now = get_current_unix_time().unwrap_or(0);
+            // entries can have different steps: only redraw those whose code has turned over since the last
+            // redraw, and count down to the next code to change on this page.
+            self.countdown = None;
+            let mut guarded_list = self.item_lists.lock().unwrap();
+            for item in guarded_list.selected_page(mode_at_entry).iter_mut() {
+                if let Some(step) = totp_step_seconds(&item.extra) {
+                    if now / step != self.last_time / step {
+                        item.dirty = true;
+                    }
+                    let left = step - now % step;
+                    if self.countdown.map(|(next, _)| left < next).unwrap_or(true) {
+                        self.countdown = Some((left, step));
+                    }
+                }
             }
+            self.last_time = now;
         }
         self.clear_area();
 
@@ -460,31 +525,12 @@ impl VaultUx {
             };
             self.gam.post_textview(&mut title_text).expect("couldn't post title");
             if mode_at_entry == VaultMode::Totp {
-                const BAR_HEIGHT: i16 = 5;
-                const BAR_GAP: i16 = -10;
-                // draw the duration bar
-                let delta = (self.current_time - (self.last_epoch * 30)) as i32;
-                let width = (self.screensize.x - (self.margin.x * 2)) as i32;
-                let delta_width = (delta * width * 100) / (30 * 100);
-                self.gam
-                    .draw_rectangle(
-                        self.content,
-                        Rectangle {
-                            tl: Point::new(self.margin.x, TITLE_HEIGHT - (BAR_HEIGHT + BAR_GAP)),
-                            br: Point::new(
-                                self.screensize.x - self.margin.x - delta_width as i16,
-                                TITLE_HEIGHT - BAR_GAP,
-                            ),
-                            style: DrawStyle {
-                                fill_color: Some(PixelColor::Dark),
-                                stroke_color: None,
-                                stroke_width: 0,
-                            },
-                        },
-                    )
-                    .ok();
+                self.draw_countdown(false);
             }
             self.title_dirty = false;
+        } else if mode_at_entry == VaultMode::Totp {
+            // the title itself is intact, so only the countdown bar under it has to move
+            self.draw_countdown(true);
         }
         if self.action_active.load(AtomicOrdering::SeqCst) {
             // don't redraw the list in the back when menus are active above
@@ -562,7 +608,7 @@ impl VaultUx {
                                 algorithm,
                             };
                             if !is_hotp {
-                                let code = generate_totp_code(get_current_unix_time().unwrap_or(0), &totp)
+                                let code = generate_totp_code(self.last_time, &totp)
                                     .unwrap_or(t!("vault.error.record_error", locales::LANG).to_string());
                                 // why code on top? because the item.name can be very long, and it can wrap
                                 // which would cause the code to become
@@ -628,6 +674,13 @@ impl VaultUx {
                         match record.read_to_end(&mut data) {
                             Ok(_len) => {
                                 if let Some(mut pw) = crate::storage::PasswordRecord::try_from(data).ok() {
+                                    if !type_username
+                                        && (crate::seed::is_seed_record(&pw.notes)
+                                            || crate::ssh::is_ssh_record(&pw.notes))
+                                    {
+                                        // seed phrases and SSH keys are never typed out
+                                        return Err(xous::Error::AccessDenied);
+                                    }
                                     let to_type = if type_username { &pw.username } else { &pw.password };
                                     match self.usb_dev.send_str(to_type) {
                                         Ok(_) => {
@@ -840,6 +893,37 @@ impl VaultUx {
         Ok(())
     }
 
+    /// Places the selected TOTP code on the clipboard, set to clear itself when the code rolls over.
+    /// HOTP codes are not copied, as the counter can only be advanced once the code is known to be used.
+    pub(crate) fn copy_code(&mut self) -> Result<(), xous::Error> {
+        if self.selected_entry().is_none() {
+            return Err(xous::Error::InvalidPID);
+        }
+        let extra = self.item_lists.lock().unwrap().selected_extra(VaultMode::Totp);
+        let fields = extra.split(':').collect::<Vec<&str>>();
+        if fields.len() != 5 {
+            return Err(xous::Error::InvalidString);
+        }
+        if fields[4].to_uppercase() == "HOTP" {
+            return Err(xous::Error::AccessDenied);
+        }
+        let totp = TotpEntry {
+            step_seconds: u64::from_str_radix(fields[2], 10).unwrap_or(30).max(1),
+            shared_secret: base32::decode(base32::Alphabet::RFC4648 { padding: false }, fields[0])
+                .unwrap_or(vec![]),
+            digit_count: u8::from_str_radix(fields[1], 10).unwrap_or(6),
+            algorithm: TotpAlgorithm::try_from(fields[3]).unwrap_or(TotpAlgorithm::HmacSha1),
+        };
+        let now = get_current_unix_time().or(Err(xous::Error::UseBeforeInit))?;
+        let code = generate_totp_code(now, &totp).or(Err(xous::Error::InvalidString))?;
+        let ttl = totp.step_seconds - now % totp.step_seconds;
+        // AccessDenied is ours to report HOTP entries with, and here means another app took our name
+        self.clipboard.copy_text(&code, Some(ttl as u32)).map_err(|e| match e {
+            xous::Error::AccessDenied => xous::Error::ShareViolation,
+            e => e,
+        })
+    }
+
     pub(crate) fn selected_entry(&self) -> Option<SelectedEntry> {
         let mode = (*self.mode.lock().unwrap()).clone();
         self.item_lists.lock().unwrap().selected_entry(mode)
@@ -10,6 +10,8 @@ use num_traits::*;
 pub const COMMAND_RESTORE_TOTP_CODES: u8 = 0x71;
 pub const COMMAND_BACKUP_TOTP_CODES: u8 = 0x72;
 pub const COMMAND_RESET_SESSION: u8 = 0x74;
+/// Carries one SSH agent request to the vault, and its response back to the host
+pub const COMMAND_SSH_AGENT: u8 = 0x75;
 
 pub const VAULT_PASSWORD_DICT: &'static str = "vault.passwords";
 pub const VAULT_TOTP_DICT: &'static str = "vault.totp";
@@ -35,10 +37,13 @@ pub enum VaultOp {
     MenuChangeFont,
     MenuDeleteStage1,
     MenuEditStage1,
+    MenuSplitStage1,
     MenuAutotype,
+    MenuCopyCode,
     MenuReadoutMode,
     MenuAutotypeRate,
     MenuLeftyMode,
+    MenuSignatureCounter,
 
     /// PDDB basis change
     BasisChange,
@@ -3,7 +3,9 @@ use core::convert::TryFrom;
 use cbor::reader::DecoderError;
 use locales::t;
 use vault::ctap::hid::{send::HidPacketIterator, ChannelID, CtapHidCommand, Message};
-use vault::vault_api::{COMMAND_BACKUP_TOTP_CODES, COMMAND_RESET_SESSION, COMMAND_RESTORE_TOTP_CODES};
+use vault::vault_api::{
+    COMMAND_BACKUP_TOTP_CODES, COMMAND_RESET_SESSION, COMMAND_RESTORE_TOTP_CODES, COMMAND_SSH_AGENT,
+};
 
 use crate::storage::{Error, PasswordRecord, TotpRecord};
 use crate::totp::TotpAlgorithm;
@@ -98,6 +100,13 @@ pub fn handle_vendor_data(
     current_state.command = cmd;
     current_state.channel_id = channel_id;
 
+    // agent requests always fit in one CTAPHID message, so they aren't split into `backup::Wire`s
+    if cmd == COMMAND_SSH_AGENT {
+        current_state.data = payload;
+        current_state.finished = true;
+        return Ok(None);
+    }
+
     // if we received a backup::PayloadType, we are being requested a backup.
     let backup_type = match backup::PayloadType::try_from(&payload) {
         Ok(t) => Some(t),
@@ -157,7 +166,15 @@ pub fn handle_vendor_command(session: &mut VendorSession, allow_host: bool) -> H
     log::debug!("got vendor command: {}", cmd);
     let xns = xous_names::XousNames::new().unwrap();
 
-    let payload = if allow_host {
+    // the SSH agent doesn't need host readout: it only hands out public keys and signatures the user
+    // confirms on the device
+    let payload = if cmd == COMMAND_SSH_AGENT {
+        Message {
+            cid: channel_id,
+            cmd: cmd.into(),
+            payload: crate::ssh::handle_agent_message(&payload, &xns),
+        }
+    } else if allow_host {
         match session.command {
             COMMAND_RESTORE_TOTP_CODES => match handle_restore(payload, &xns) {
                 Ok(payload) => Message { cid: channel_id, cmd: cmd.into(), payload },
@@ -342,6 +359,11 @@ fn handle_backup(xns: &xous_names::XousNames, session: &mut VendorSession) -> Re
             let mut ret = vec![];
 
             for raw_pass in passwords {
+                if crate::ssh::is_ssh_record(&raw_pass.notes) {
+                    // SSH keys never leave the device over USB
+                    log::info!("leaving a device-bound record out of the backup");
+                    continue;
+                }
                 ret.push(backup::PasswordEntry {
                     description: raw_pass.description,
                     username: raw_pass.username,
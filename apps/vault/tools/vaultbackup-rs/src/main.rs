@@ -1,6 +1,8 @@
 mod authenticator;
 mod bitwarden;
 mod csvpass;
+#[cfg(unix)]
+mod sshagent;
 use std::io::BufRead;
 use std::time::Instant;
 
@@ -62,6 +64,13 @@ struct FormatFields {
     path: String,
 }
 
+#[derive(Debug, PartialEq, clap::Args, Clone)]
+struct SshAgentFields {
+    /// The path of the Unix socket to listen on
+    #[clap(required = true, value_parser)]
+    socket: String,
+}
+
 #[derive(Debug, Subcommand)]
 enum Commands {
     /// Backup data from device
@@ -75,6 +84,10 @@ enum Commands {
     /// Format a known password manager export for Vault.
     #[clap(arg_required_else_help = true)]
     Format(FormatFields),
+
+    /// Run an SSH agent backed by the SSH keys stored on the device
+    #[clap(arg_required_else_help = true)]
+    SshAgent(SshAgentFields),
 }
 
 #[derive(Debug)]
@@ -239,6 +252,13 @@ fn main() -> Result<()> {
                 }
             }
         }
+        #[cfg(unix)]
+        Commands::SshAgent(params) => sshagent::serve(&params.socket),
+        #[cfg(not(unix))]
+        Commands::SshAgent(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "the SSH agent needs Unix domain sockets",
+        ))?,
         Commands::Backup(params) => {
             let device = open_precursor()?;
             log::info!("receiving data...");
@@ -0,0 +1,64 @@
+use std::io::{Read, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use anyhow::Result;
+
+const SSH_AGENT_FAILURE: u8 = 5;
+/// ssh-agent caps messages at 256KiB; ours are far smaller, as they have to fit in one CTAPHID message
+const MAX_MESSAGE_LEN: usize = 7609;
+
+/// Serves the SSH agent protocol on `socket`, forwarding every request to the vault app.
+pub fn serve(socket: &str) -> Result<()> {
+    // clean up after an agent that didn't exit cleanly, but don't clobber anything else
+    if std::fs::metadata(socket).map(|m| m.file_type().is_socket()).unwrap_or(false) {
+        std::fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    log::info!("listening on {}", socket);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(error) = serve_client(stream) {
+                    log::error!("agent connection ended: {}", error);
+                }
+            }
+            Err(error) => log::error!("couldn't accept agent connection: {}", error),
+        }
+    }
+
+    Ok(())
+}
+
+fn serve_client(mut stream: UnixStream) -> Result<()> {
+    // ssh connects once per login, so the device is opened per connection and can be unplugged in
+    // between
+    let device = crate::open_precursor()?;
+
+    loop {
+        let mut len = [0u8; 4];
+        match stream.read_exact(&mut len) {
+            Ok(()) => (),
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(error) => return Err(error)?,
+        }
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_MESSAGE_LEN {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "agent request too long"))?;
+        }
+        let mut request = vec![0u8; len];
+        stream.read_exact(&mut request)?;
+
+        let response = match device.vendor_command(ctaphid::command::VendorCommand::H75, &request) {
+            Ok(response) => response,
+            Err(error) => {
+                log::error!("device error: {:?}", error);
+                vec![SSH_AGENT_FAILURE]
+            }
+        };
+
+        stream.write_all(&(response.len() as u32).to_be_bytes())?;
+        stream.write_all(&response)?;
+    }
+}
@@ -478,7 +478,8 @@ pub fn hand_page_to_user(virt: *mut u8) -> Result<(), xous_kernel::Error> {
     Ok(())
 }
 
-#[cfg(feature = "gdb-stub")]
+/// Read a value from the current process's memory, failing if it isn't mapped
+/// and readable.
 pub fn peek_memory<T>(addr: *mut T) -> Result<T, xous_kernel::Error> {
     let virt = addr as usize;
     let vpn1 = (virt >> 22) & ((1 << 10) - 1);
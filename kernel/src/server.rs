@@ -479,6 +479,18 @@ impl Server {
     //     }
     // }
 
+    /// Count the occupied queue slots: messages waiting to be received, and
+    /// messages received but not yet returned.
+    pub fn queue_counts(&self) -> (usize, usize) {
+        self.queue.iter().fold((0, 0), |(queued, outstanding), entry| match entry {
+            QueuedMessage::Empty => (queued, outstanding),
+            QueuedMessage::WaitingReturnMemory(..)
+            | QueuedMessage::WaitingForget(..)
+            | QueuedMessage::WaitingReturnScalar(..) => (queued, outstanding + 1),
+            _ => (queued + 1, outstanding),
+        })
+    }
+
     /// When a process terminates, there may be memory that is lent to us.
     /// Mark all of that memory to be discarded when it is returned, rather than
     /// giving it back to the previous process space.
@@ -7,7 +7,8 @@ use xous_kernel::arch::ProcessStartup;
 use xous_kernel::MemoryRange;
 // use core::mem;
 use xous_kernel::{
-    pid_from_usize, Error, MemoryAddress, Message, ProcessInit, ThreadInit, CID, PID, SID, TID,
+    pid_from_usize, Error, MemoryAddress, Message, ProcessInit, ThreadInit, CID, MAX_GATHER_SEGMENTS, PID,
+    SID, TID,
 };
 
 use crate::arch;
@@ -21,6 +22,9 @@ use crate::server::Server;
 
 const MAX_SERVER_COUNT: usize = 128;
 
+/// How many gathered lends may be outstanding across the system at once
+const MAX_GATHERED_LENDS: usize = 32;
+
 pub use crate::arch::process::{INITIAL_TID, MAX_PROCESS_COUNT};
 
 #[allow(dead_code)]
@@ -50,6 +54,60 @@ pub struct ExceptionHandler {
     pub sp: usize,
 }
 
+/// A lend of several segments of a client, which the server sees laid end to end
+/// in one window. The segments are remembered so that each of them can be
+/// returned to where it came from.
+#[derive(Copy, Clone)]
+#[allow(dead_code)] // suppresses unused warnings in hosted mode
+pub struct GatheredLend {
+    /// The client that made the lend
+    pid: PID,
+
+    /// The thread of the client that is waiting for the lend to be returned
+    tid: TID,
+
+    /// How many entries of `segments` are in use
+    count: usize,
+
+    /// Address and length of each segment in the client, in the order they
+    /// appear in the window
+    segments: [(usize, usize); MAX_GATHER_SEGMENTS],
+}
+
+/// Check the segments of a gathered lend, and return the size of the window they
+/// are laid out in.
+///
+/// # Errors
+///
+/// * **InvalidLimit**: There are no segments, or more than `MAX_GATHER_SEGMENTS`
+/// * **BadAddress**: A segment was outside of user memory
+/// * **BadAlignment**: A segment did not start and end on a page boundary
+/// * **ShareViolation**: Two segments overlap, so a page would be lent twice
+pub fn gathered_window_len(segments: &[MemoryRange]) -> Result<usize, xous_kernel::Error> {
+    if segments.is_empty() || segments.len() > MAX_GATHER_SEGMENTS {
+        return Err(xous_kernel::Error::InvalidLimit);
+    }
+    let mut len = 0usize;
+    for (idx, segment) in segments.iter().enumerate() {
+        let addr = segment.as_ptr() as usize;
+        if addr & 0xfff != 0 || segment.len() & 0xfff != 0 {
+            return Err(xous_kernel::Error::BadAlignment);
+        }
+        let end = addr
+            .checked_add(segment.len())
+            .filter(|&end| end <= crate::arch::mem::USER_AREA_END)
+            .ok_or(xous_kernel::Error::BadAddress)?;
+        if segments[..idx].iter().any(|other| {
+            let other_addr = other.as_ptr() as usize;
+            addr < other_addr + other.len() && other_addr < end
+        }) {
+            return Err(xous_kernel::Error::ShareViolation);
+        }
+        len = len.checked_add(segment.len()).ok_or(xous_kernel::Error::BadAddress)?;
+    }
+    Ok(len)
+}
+
 // fn log_process_update(f: &str, l: u32, process: &Process, old_state: ProcessState) {
 //     if process.pid.get() == 3 {
 //         println!("[{}:{}] Updated PID {:?} state: {:?} -> {:?}", f, l, process.pid, old_state,
@@ -64,6 +122,9 @@ pub struct SystemServices {
 
     /// A table of all servers in the system
     pub servers: [Option<Server>; MAX_SERVER_COUNT],
+
+    /// Gathered lends that have not been returned yet
+    gathered_lends: [Option<GatheredLend>; MAX_GATHERED_LENDS],
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -162,6 +223,18 @@ pub struct Process {
 
     /// When an exception is hit, the kernel will switch to this Thread.
     exception_handler: Option<ExceptionHandler>,
+
+    /// How many times this process has been switched in. Wraps on overflow.
+    activations: u32,
+
+    /// How many messages this process has sent. Wraps on overflow.
+    messages_sent: u32,
+
+    /// How many messages this process' servers have been sent. Wraps on overflow.
+    messages_received: u32,
+
+    /// How many times this PID has been given to a new process. Wraps on overflow.
+    generation: u32,
 }
 
 impl Default for Process {
@@ -174,6 +247,10 @@ impl Default for Process {
             previous_thread: 0,
             exception_handler: None,
             mapping: Default::default(),
+            activations: 0,
+            messages_sent: 0,
+            messages_received: 0,
+            generation: 0,
         }
     }
 }
@@ -283,10 +360,15 @@ std::thread_local!(static SYSTEM_SERVICES: core::cell::RefCell<SystemServices> =
         current_thread: 0_usize,
         previous_thread: INITIAL_TID as TID,
         exception_handler: None,
+        activations: 0,
+        messages_sent: 0,
+        messages_received: 0,
+        generation: 0,
     }; MAX_PROCESS_COUNT],
     // Note we can't use MAX_SERVER_COUNT here because of how Rust's
     // macro tokenization works
     servers: filled_array![None; 128],
+    gathered_lends: [None; MAX_GATHERED_LENDS],
 }));
 
 #[cfg(baremetal)]
@@ -300,20 +382,26 @@ static mut SYSTEM_SERVICES: SystemServices = SystemServices {
         current_thread: INITIAL_TID,
         previous_thread: INITIAL_TID as TID,
         exception_handler: None,
+        activations: 0,
+        messages_sent: 0,
+        messages_received: 0,
+        generation: 0,
     }; MAX_PROCESS_COUNT],
     // Note we can't use MAX_SERVER_COUNT here because of how Rust's
     // macro tokenization works
     servers: filled_array![None; 128],
+    gathered_lends: [None; MAX_GATHERED_LENDS],
 };
 
 impl core::fmt::Debug for Process {
     fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
         write!(
             fmt,
-            "Process {} state: {:?}  TID: {}  Memory mapping: {:?}",
+            "Process {} state: {:?}  TID: {}  Activations: {}  Memory mapping: {:?}",
             self.pid.get(),
             self.state,
             self.current_thread,
+            self.activations,
             self.mapping
         )
     }
@@ -473,6 +561,10 @@ impl SystemServices {
             entry.pid = new_pid.unwrap();
             entry.ppid = PID::new(1).unwrap();
             entry.state = ProcessState::Allocated;
+            entry.activations = 0;
+            entry.messages_sent = 0;
+            entry.messages_received = 0;
+            entry.generation = entry.generation.wrapping_add(1);
             unsafe { entry.mapping.allocate(new_pid.unwrap()).or(Err(xous_kernel::Error::InternalError))? };
             break;
         }
@@ -522,6 +614,77 @@ impl SystemServices {
 
     pub fn current_pid(&self) -> PID { arch::process::current_pid() }
 
+    /// Return the number of times the given process has been switched in.
+    pub fn process_activations(&self, pid: PID) -> Result<u32, xous_kernel::Error> {
+        if pid.get() as usize > self.processes.len() {
+            return Err(xous_kernel::Error::ProcessNotFound);
+        }
+        self.get_process(pid).map(|process| process.activations)
+    }
+
+    /// Return the generation of the given PID, which changes whenever it is reused.
+    pub fn process_generation(&self, pid: PID) -> Result<u32, xous_kernel::Error> {
+        if pid.get() as usize > self.processes.len() {
+            return Err(xous_kernel::Error::ProcessNotFound);
+        }
+        self.get_process(pid).map(|process| process.generation)
+    }
+
+    /// Return the name the given PID was loaded with, packed into the words of a
+    /// `Scalar5`: the length in bytes, then up to four words of UTF-8. Longer
+    /// names are cut short, and a process without a name has a length of zero.
+    pub fn process_name_words(&self, pid: PID) -> Result<[usize; 5], xous_kernel::Error> {
+        if pid.get() as usize > self.processes.len() {
+            return Err(xous_kernel::Error::ProcessNotFound);
+        }
+        self.get_process(pid)?;
+        let mut words = [0usize; 5];
+        if let Some(name) = self.process_name(pid) {
+            let mut bytes = [0u8; 4 * core::mem::size_of::<usize>()];
+            let mut len = name.len().min(bytes.len());
+            while !name.is_char_boundary(len) {
+                len -= 1;
+            }
+            bytes[..len].copy_from_slice(&name.as_bytes()[..len]);
+            words[0] = len;
+            let chunks = bytes.chunks_exact(core::mem::size_of::<usize>());
+            for (word, chunk) in words[1..].iter_mut().zip(chunks) {
+                *word = chunk.iter().rev().fold(0, |word, &byte| (word << 8) | byte as usize);
+            }
+        }
+        Ok(words)
+    }
+
+    /// Count a message from `sender` that was delivered or queued to a server of `receiver`.
+    pub fn count_message(&mut self, sender: PID, receiver: PID) {
+        if let Ok(process) = self.get_process_mut(sender) {
+            process.messages_sent = process.messages_sent.wrapping_add(1);
+        }
+        if let Ok(process) = self.get_process_mut(receiver) {
+            process.messages_received = process.messages_received.wrapping_add(1);
+        }
+    }
+
+    /// Return the message counts of the given process, and the state of its servers' queues.
+    pub fn process_messages(&self, pid: PID) -> Result<xous_kernel::ProcessMessages, xous_kernel::Error> {
+        if pid.get() as usize > self.processes.len() {
+            return Err(xous_kernel::Error::ProcessNotFound);
+        }
+        let process = self.get_process(pid)?;
+        let mut counts = xous_kernel::ProcessMessages {
+            sent: process.messages_sent,
+            received: process.messages_received,
+            ..Default::default()
+        };
+        for server in self.servers.iter().flatten().filter(|server| server.pid == pid) {
+            let (queued, outstanding) = server.queue_counts();
+            counts.servers += 1;
+            counts.queued += queued;
+            counts.outstanding += outstanding;
+        }
+        Ok(counts)
+    }
+
     /// Must be called from the swapper's context. Resets the runnable states of the swapper.
     #[cfg(feature = "swap")]
     pub fn finish_swap(&mut self) {
@@ -1109,6 +1272,7 @@ impl SystemServices {
             // point onward, we will need to activate the previous memory space
             // if we encounter an error.
             new.mapping.activate()?;
+            new.activations = new.activations.wrapping_add(1);
 
             // Set up the new process, if necessary.  Remove the new thread from
             // the list of ready threads.
@@ -1500,6 +1664,152 @@ impl SystemServices {
         Ok(src_virt)
     }
 
+    /// Lend several segments of the current process to the target process, laid
+    /// end to end in a single window of its address space. Thread `tid` is the
+    /// one waiting on the lend, and the segments are remembered so that returning
+    /// the window gives each of them back to where it came from.
+    ///
+    /// # Returns
+    ///
+    /// Returns the virtual address of the window in the target process.
+    ///
+    /// # Errors
+    ///
+    /// * **InvalidLimit**: There are no segments, or more than `MAX_GATHER_SEGMENTS`
+    /// * **BadAddress**: A segment was outside of user memory, or a page of one isn't ours
+    /// * **BadAlignment**: A segment did not start and end on a page boundary
+    /// * **ShareViolation**: Two segments overlap, a page is already lent, or the target is the current
+    ///   process, which can't see its own segments as one
+    /// * **OutOfMemory**: Too many gathered lends are outstanding
+    #[cfg(baremetal)]
+    pub fn lend_gathered_memory(
+        &mut self,
+        tid: TID,
+        segments: &[MemoryRange],
+        dest_pid: PID,
+        mutable: bool,
+    ) -> Result<*mut usize, xous_kernel::Error> {
+        let len = gathered_window_len(segments)?;
+
+        let current_pid = self.current_pid();
+        if current_pid == dest_pid {
+            return Err(xous_kernel::Error::ShareViolation);
+        }
+        // A thread has at most one lend outstanding, so a record it left behind
+        // can be reused.
+        let slot = self
+            .gathered_lends
+            .iter()
+            .position(|lend| lend.map_or(false, |lend| lend.pid == current_pid && lend.tid == tid))
+            .or_else(|| self.gathered_lends.iter().position(|lend| lend.is_none()))
+            .ok_or(xous_kernel::Error::OutOfMemory)?;
+
+        let src_mapping = self.get_process(current_pid)?.mapping;
+        let dest_mapping = self.get_process(dest_pid)?.mapping;
+        use crate::mem::MemoryManager;
+        let dest_virt = MemoryManager::with_mut(|mm| {
+            // Locate an address to fit all of the segments.
+            dest_mapping.activate()?;
+            let dest_virt = mm
+                .find_virtual_address(core::ptr::null_mut(), len, xous_kernel::MemoryType::Messages)
+                .map_err(|e| {
+                    src_mapping.activate().unwrap();
+                    e
+                })? as usize;
+            src_mapping.activate().unwrap();
+
+            // Fault in every page before any of them is lent, so that a segment
+            // that isn't ours is refused before anything has changed hands.
+            for segment in segments {
+                for src in (segment.as_ptr() as usize..segment.as_ptr() as usize + segment.len())
+                    .step_by(crate::mem::PAGE_SIZE)
+                {
+                    mm.ensure_page_exists(src)?;
+                }
+            }
+
+            // Lend the pages of each segment in turn. If one can't be lent, take
+            // back the ones that were, so the client gets its memory back along
+            // with the error.
+            let mut dest = dest_virt;
+            for segment in segments {
+                for src in (segment.as_ptr() as usize..segment.as_ptr() as usize + segment.len())
+                    .step_by(crate::mem::PAGE_SIZE)
+                {
+                    if let Err(e) = mm.lend_page(
+                        &src_mapping,
+                        src as *mut u8,
+                        dest_pid,
+                        &dest_mapping,
+                        dest as *mut u8,
+                        mutable,
+                    ) {
+                        let lent = (dest - dest_virt) / crate::mem::PAGE_SIZE;
+                        let pages = segments.iter().flat_map(|segment| {
+                            (segment.as_ptr() as usize..segment.as_ptr() as usize + segment.len())
+                                .step_by(crate::mem::PAGE_SIZE)
+                        });
+                        dest_mapping.activate()?;
+                        for (page, src) in pages.take(lent).enumerate() {
+                            mm.unlend_page(
+                                &dest_mapping,
+                                (dest_virt + page * crate::mem::PAGE_SIZE) as *mut u8,
+                                current_pid,
+                                &src_mapping,
+                                src as *mut u8,
+                            )
+                            .expect("couldn't take back a page of a failed gathered lend");
+                        }
+                        src_mapping.activate().unwrap();
+                        return Err(e);
+                    }
+                    dest += crate::mem::PAGE_SIZE;
+                }
+            }
+            Ok(dest_virt)
+        })?;
+
+        let mut lend = GatheredLend {
+            pid: current_pid,
+            tid,
+            count: segments.len(),
+            segments: [(0, 0); MAX_GATHER_SEGMENTS],
+        };
+        for (entry, segment) in lend.segments.iter_mut().zip(segments) {
+            *entry = (segment.as_ptr() as usize, segment.len());
+        }
+        self.gathered_lends[slot] = Some(lend);
+        Ok(dest_virt as *mut usize)
+    }
+
+    #[cfg(not(baremetal))]
+    pub fn lend_gathered_memory(
+        &mut self,
+        _tid: TID,
+        segments: &[MemoryRange],
+        _dest_pid: PID,
+        _mutable: bool,
+    ) -> Result<*mut usize, xous_kernel::Error> {
+        // Hosted clients copy their segments into one buffer and lend that instead.
+        gathered_window_len(segments)?;
+        Err(xous_kernel::Error::UnhandledSyscall)
+    }
+
+    /// Remove the record of the gathered lend that `pid:tid` is waiting on, if
+    /// it is the one whose first segment is at `addr` and that is `len` bytes in all.
+    #[cfg(baremetal)]
+    fn take_gathered_lend(&mut self, pid: PID, tid: TID, addr: usize, len: usize) -> Option<GatheredLend> {
+        let entry = self.gathered_lends.iter_mut().find(|lend| {
+            lend.map_or(false, |lend| {
+                lend.pid == pid
+                    && lend.tid == tid
+                    && lend.segments[0].0 == addr
+                    && lend.segments[..lend.count].iter().map(|(_, len)| len).sum::<usize>() == len
+            })
+        })?;
+        entry.take()
+    }
+
     /// Return memory from one process back to another
     ///
     /// During this process, memory is unmapped from the source process.
@@ -1516,7 +1826,7 @@ impl SystemServices {
         &mut self,
         src_virt: *mut usize,
         dest_pid: PID,
-        _dest_tid: TID,
+        dest_tid: TID,
         dest_virt: *mut usize,
         len: usize,
     ) -> Result<*mut usize, xous_kernel::Error> {
@@ -1548,6 +1858,23 @@ impl SystemServices {
         // borrowed but
         if dest_pid.get() == 1 {}
 
+        // The segments of a gathered lend each go back to where they came from.
+        if let Some(lend) = self.take_gathered_lend(dest_pid, dest_tid, dest_virt as usize, len) {
+            let mut result = Ok(dest_virt);
+            let mut src = src_virt as usize;
+            for &(addr, len) in &lend.segments[..lend.count] {
+                if let Err(e) =
+                    self.return_memory(src as *mut usize, dest_pid, dest_tid, addr as *mut usize, len)
+                {
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+                src += len;
+            }
+            return result;
+        }
+
         // Iterators and `ptr.wrapping_add()` operate on `usize` types,
         // which effectively lowers the `len`.
         let usize_len = len / core::mem::size_of::<usize>();
@@ -1878,6 +2205,47 @@ impl SystemServices {
         result
     }
 
+    /// Turn every connection `target_pid` has to the server `sid` into a
+    /// tombstone, so that messages sent on it fail. Only `pid`, the owner of
+    /// the server, may do this. Returns the number of connections that were
+    /// revoked.
+    pub fn revoke_connection(
+        &mut self,
+        pid: PID,
+        target_pid: PID,
+        sid: SID,
+    ) -> Result<usize, xous_kernel::Error> {
+        let sidx = self
+            .servers
+            .iter()
+            .position(|server| server.as_ref().map_or(false, |server| server.sid == sid))
+            .ok_or(xous_kernel::Error::ServerNotFound)?;
+        if self.servers[sidx].as_ref().map(|server| server.pid) != Some(pid) {
+            return Err(xous_kernel::Error::AccessDenied);
+        }
+        let original_pid = crate::arch::process::current_pid();
+
+        let process = self.get_process_mut(target_pid)?;
+        process.activate()?;
+
+        // Connection map entries are offset by two, because 0 == free and 1 == "tombstone".
+        let revoked = ArchProcess::with_inner_mut(|process_inner| {
+            let mut revoked = 0;
+            for mapping in process_inner.connection_map.iter_mut().flatten() {
+                if mapping.get() as usize == sidx + 2 {
+                    *mapping = NonZeroU8::new(1).unwrap();
+                    revoked += 1;
+                }
+            }
+            revoked
+        });
+
+        let process = self.get_process_mut(original_pid)?;
+        process.activate().unwrap();
+
+        Ok(revoked)
+    }
+
     /// Allocate a new server ID for this process and return the address. If the
     /// server table is full, return an error.
     pub fn connect_to_server(&mut self, sid: SID) -> Result<CID, xous_kernel::Error> {
@@ -2148,6 +2516,14 @@ impl SystemServices {
             }
         }
 
+        // Gathered lends of this process will be discarded rather than returned, so
+        // forget them.
+        for lend in self.gathered_lends.iter_mut() {
+            if lend.map_or(false, |lend| lend.pid == target_pid) {
+                *lend = None;
+            }
+        }
+
         // Now that the server has been "Disconnected", free the server entry.
         #[allow(clippy::manual_flatten)]
         for server in self.servers.iter_mut() {
@@ -2365,4 +2741,8 @@ impl SystemServices {
         }
         None
     }
+
+    /// Hosted processes aren't loaded from an image, so they have no name.
+    #[cfg(not(baremetal))]
+    pub fn process_name(&self, _pid: PID) -> Option<&str> { None }
 }
@@ -77,7 +77,16 @@ fn do_yield(_pid: PID, tid: TID) -> SysCallResult {
     })
 }
 
-fn send_message(pid: PID, tid: TID, cid: CID, message: Message) -> SysCallResult {
+/// Send `message` on `cid`. If `segments` is given, the message is a lend whose
+/// buffer is made of those segments, and its own `buf` spans the first segment
+/// and the total length.
+fn send_message(
+    pid: PID,
+    tid: TID,
+    cid: CID,
+    message: Message,
+    segments: Option<&[MemoryRange]>,
+) -> SysCallResult {
     SystemServices::with_mut(|ss| {
         let sidx = ss.sidx_from_cid(cid).ok_or(xous_kernel::Error::ServerNotFound)?;
 
@@ -113,13 +122,16 @@ fn send_message(pid: PID, tid: TID, cid: CID, message: Message) -> SysCallResult
                 })
             }
             Message::MutableBorrow(msg) => {
-                let new_virt = ss.lend_memory(
-                    msg.buf.as_mut_ptr() as *mut usize,
-                    server_pid,
-                    core::ptr::null_mut(),
-                    msg.buf.len(),
-                    true,
-                )?;
+                let new_virt = match segments {
+                    Some(segments) => ss.lend_gathered_memory(tid, segments, server_pid, true)?,
+                    None => ss.lend_memory(
+                        msg.buf.as_mut_ptr() as *mut usize,
+                        server_pid,
+                        core::ptr::null_mut(),
+                        msg.buf.len(),
+                        true,
+                    )?,
+                };
                 Message::MutableBorrow(MemoryMessage {
                     id: msg.id,
                     buf: unsafe { MemoryRange::new(new_virt as usize, msg.buf.len()) }?,
@@ -128,13 +140,16 @@ fn send_message(pid: PID, tid: TID, cid: CID, message: Message) -> SysCallResult
                 })
             }
             Message::Borrow(msg) => {
-                let new_virt = ss.lend_memory(
-                    msg.buf.as_mut_ptr() as *mut usize,
-                    server_pid,
-                    core::ptr::null_mut(),
-                    msg.buf.len(),
-                    false,
-                )?;
+                let new_virt = match segments {
+                    Some(segments) => ss.lend_gathered_memory(tid, segments, server_pid, false)?,
+                    None => ss.lend_memory(
+                        msg.buf.as_mut_ptr() as *mut usize,
+                        server_pid,
+                        core::ptr::null_mut(),
+                        msg.buf.len(),
+                        false,
+                    )?,
+                };
                 // println!(
                 //     "Lending {} bytes from {:08x} in PID {} to {:08x} in PID {}",
                 //     msg.buf.len(),
@@ -172,6 +187,7 @@ fn send_message(pid: PID, tid: TID, cid: CID, message: Message) -> SysCallResult
                 0
             };
             let sender = SenderID::new(sidx, sender_idx, Some(pid));
+            ss.count_message(pid, server_pid);
             klog!("server connection data: sidx: {}, idx: {}, server pid: {}", sidx, sender_idx, server_pid);
             let envelope = MessageEnvelope { sender: sender.into(), body: message };
 
@@ -250,6 +266,8 @@ fn send_message(pid: PID, tid: TID, cid: CID, message: Message) -> SysCallResult
         // returns an error.
         let _queue_idx = ss.queue_server_message(sidx, pid, tid, message, client_address)?;
         klog!("queued into index {:x}", _queue_idx);
+        // counted only once queued, as a full queue retries the whole call
+        ss.count_message(pid, server_pid);
 
         // Park this context if it's blocking.  This is roughly
         // equivalent to a "Yield".
@@ -912,7 +930,7 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
         SysCall::ReplyAndReceiveNext(sender, a0, a1, a2, a3, a4, scalar_type) => {
             reply_and_receive_next(pid, tid, in_irq, sender, a0, a1, a2, a3, a4, scalar_type)
         }
-        SysCall::TrySendMessage(cid, message) => send_message(pid, tid, cid, message),
+        SysCall::TrySendMessage(cid, message) => send_message(pid, tid, cid, message, None),
         SysCall::TerminateProcess(_ret) => SystemServices::with_mut(|ss| {
             ss.unschedule_thread(pid, tid)?;
             ss.terminate_process(pid)?;
@@ -944,8 +962,70 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
                 Err(e) => Err(e),
             }
         }
+        SysCall::RevokeConnection(target_pid, sid) => SystemServices::with_mut(|ss| {
+            ss.revoke_connection(pid, target_pid, sid).map(xous_kernel::Result::Scalar1)
+        }),
+        // What other processes are sending and receiving says what they are doing, so only
+        // kernels built for profiling hand out any counts but the caller's own.
+        SysCall::GetProcessMessages(target_pid)
+            if target_pid != pid && !cfg!(feature = "message-profile") =>
+        {
+            Err(xous_kernel::Error::AccessDenied)
+        }
+        SysCall::GetProcessMessages(target_pid) => SystemServices::with(|ss| {
+            ss.process_messages(target_pid).map(|m| {
+                xous_kernel::Result::Scalar5(
+                    m.sent as usize,
+                    m.received as usize,
+                    m.servers,
+                    m.queued,
+                    m.outstanding,
+                )
+            })
+        }),
         SysCall::SendMessage(cid, message) => {
-            let result = send_message(pid, tid, cid, message);
+            let result = send_message(pid, tid, cid, message, None);
+            match result {
+                Ok(o) => Ok(o),
+                Err(xous_kernel::Error::ServerQueueFull) => retry_syscall(pid, tid),
+                Err(e) => Err(e),
+            }
+        }
+        #[cfg(all(baremetal, target_arch = "riscv32"))]
+        SysCall::LendGathered(cid, msg, mutable) => {
+            // The message's buffer is the caller's table of segments, as pairs of
+            // address and length, which is read out of its memory before the
+            // segments can be lent.
+            let table = msg.buf.as_ptr() as usize;
+            let count = msg.buf.len() / (2 * core::mem::size_of::<usize>());
+            if count == 0 || count > MAX_GATHER_SEGMENTS {
+                return Err(xous_kernel::Error::InvalidLimit);
+            }
+            if table % core::mem::align_of::<usize>() != 0 {
+                return Err(xous_kernel::Error::BadAlignment);
+            }
+            if table.checked_add(msg.buf.len()).map_or(true, |end| end > arch::mem::USER_AREA_END) {
+                return Err(xous_kernel::Error::BadAddress);
+            }
+            let read_segment = |idx: usize| -> core::result::Result<MemoryRange, xous_kernel::Error> {
+                let entry = (table as *mut usize).wrapping_add(idx * 2);
+                let addr = arch::mem::peek_memory(entry)?;
+                let size = arch::mem::peek_memory(entry.wrapping_add(1))?;
+                unsafe { MemoryRange::new(addr, size) }
+            };
+            let mut segments = [read_segment(0)?; MAX_GATHER_SEGMENTS];
+            for (idx, segment) in segments.iter_mut().enumerate().take(count).skip(1) {
+                *segment = read_segment(idx)?;
+            }
+            let segments = &segments[..count];
+
+            // The server sees an ordinary lend of all the segments, and the client
+            // address it is returned to is that of the first one.
+            let len: usize = segments.iter().map(|segment| segment.len()).sum();
+            let buf = unsafe { MemoryRange::new(segments[0].as_ptr() as usize, len) }?;
+            let message = MemoryMessage { id: msg.id, buf, offset: msg.offset, valid: msg.valid };
+            let message = if mutable { Message::MutableBorrow(message) } else { Message::Borrow(message) };
+            let result = send_message(pid, tid, cid, message, Some(segments));
             match result {
                 Ok(o) => Ok(o),
                 Err(xous_kernel::Error::ServerQueueFull) => retry_syscall(pid, tid),
@@ -1104,6 +1184,17 @@ pub fn handle_inner(pid: PID, tid: TID, in_irq: bool, call: SysCall) -> SysCallR
                 }
             }
         }
+        SysCall::GetProcessActivations(target_pid) => SystemServices::with(|ss| {
+            ss.process_activations(target_pid).map(|count| xous_kernel::Result::Scalar1(count as usize))
+        }),
+        SysCall::GetProcessGeneration(target_pid) => SystemServices::with(|ss| {
+            ss.process_generation(target_pid)
+                .map(|generation| xous_kernel::Result::Scalar1(generation as usize))
+        }),
+        SysCall::GetProcessName(target_pid) => SystemServices::with(|ss| {
+            ss.process_name_words(target_pid)
+                .map(|[len, a, b, c, d]| xous_kernel::Result::Scalar5(len, a, b, c, d))
+        }),
         #[cfg(feature = "raw-trng")]
         SysCall::RawTrng(_a1, _a2, _a3, _a4, _a5, _a6, _a7) => {
             // TODO: implement this platform call for other targets
@@ -396,6 +396,187 @@ fn try_receive_message() {
     main_thread.join().expect("couldn't join kernel process");
 }
 
+#[test]
+fn process_messages() {
+    // Start the server in another thread
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let (server_addr_send, server_addr_recv) = unbounded();
+
+    // The server checks its own counts while it holds the client's blocking
+    // message, and again once it has replied to both.
+    let xous_server = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "process_messages server",
+        move || {
+            let sid = xous_kernel::create_server().expect("couldn't create test server");
+            server_addr_send.send(sid).unwrap();
+            let own_pid = xous_kernel::current_pid().unwrap();
+            for count in 1..=2 {
+                let envelope = xous_kernel::receive_message(sid).expect("couldn't receive messages");
+                let held = xous_kernel::process_messages(own_pid).unwrap();
+                assert_eq!((held.received, held.servers, held.outstanding), (count, 1, 1));
+                xous_kernel::return_scalar(envelope.sender, 0).expect("couldn't return scalar");
+            }
+            let done = xous_kernel::process_messages(own_pid).unwrap();
+            assert_eq!((done.received, done.queued, done.outstanding), (2, 0, 0));
+        },
+    ))
+    .expect("couldn't spawn server process");
+
+    let xous_client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "process_messages client",
+        move || {
+            let sid = server_addr_recv.recv().unwrap();
+            let conn = xous_kernel::try_connect(sid).expect("couldn't connect to server");
+            for _ in 0..2 {
+                xous_kernel::send_message(conn, xous_kernel::Message::new_blocking_scalar(1, 0, 0, 0, 0))
+                    .expect("couldn't send message");
+            }
+            let counts = xous_kernel::process_messages(xous_kernel::current_pid().unwrap()).unwrap();
+            assert_eq!((counts.sent, counts.received, counts.servers), (2, 0, 0));
+            // Other processes' counts are only handed out by profiling kernels.
+            #[cfg(not(feature = "message-profile"))]
+            assert_eq!(
+                xous_kernel::process_messages(xous_kernel::PID::new(1).unwrap()),
+                Err(xous_kernel::Error::AccessDenied)
+            );
+        },
+    ))
+    .expect("couldn't spawn client process");
+
+    // Wait for both processes to finish
+    crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+    crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn process_generation() {
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    // A process that has exited leaves its PID behind, and the process that gets
+    // it next has a different generation.
+    let (pid_send, pid_recv) = unbounded();
+    let first = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "process_generation first",
+        move || {
+            let pid = xous_kernel::current_pid().unwrap();
+            pid_send.send((pid, xous_kernel::process_generation(pid).unwrap())).unwrap();
+        },
+    ))
+    .expect("couldn't spawn first process");
+    crate::wait_process_as_thread(first).expect("couldn't join first process");
+    let (first_pid, first_generation) = pid_recv.recv().unwrap();
+
+    let (pid_send, pid_recv) = unbounded();
+    let second = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "process_generation second",
+        move || {
+            let pid = xous_kernel::current_pid().unwrap();
+            pid_send.send((pid, xous_kernel::process_generation(pid).unwrap())).unwrap();
+        },
+    ))
+    .expect("couldn't spawn second process");
+    crate::wait_process_as_thread(second).expect("couldn't join second process");
+    let (second_pid, second_generation) = pid_recv.recv().unwrap();
+
+    if second_pid == first_pid {
+        assert_ne!(second_generation, first_generation);
+    }
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn process_name() {
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    // Hosted processes aren't loaded from an image, so they have no name, but
+    // asking for one still tells a live PID from a free one.
+    let xous_process = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "process_name",
+        move || {
+            let pid = xous_kernel::current_pid().unwrap();
+            assert_eq!(xous_kernel::process_name(pid).unwrap().as_str(), "");
+            let free = xous_kernel::pid_from_usize(crate::services::MAX_PROCESS_COUNT).unwrap();
+            assert_eq!(xous_kernel::process_name(free), Err(xous_kernel::Error::ProcessNotFound));
+        },
+    ))
+    .expect("couldn't spawn process");
+    crate::wait_process_as_thread(xous_process).expect("couldn't join process");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
+#[test]
+fn revoke_connection() {
+    // Start the server in another thread
+    let main_thread = start_kernel(SERVER_SPEC);
+
+    let (server_addr_send, server_addr_recv) = unbounded();
+    let (revoked_send, revoked_recv) = unbounded();
+    let (client_done_send, client_done_recv) = unbounded();
+
+    // The server revokes the client's connection after its first message, and
+    // stays up until the client is done so that the server going away can't be
+    // mistaken for the revocation.
+    let xous_server = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "revoke_connection server",
+        move || {
+            let sid = xous_kernel::create_server().expect("couldn't create test server");
+            server_addr_send.send(sid).unwrap();
+            let envelope = xous_kernel::receive_message(sid).expect("couldn't receive messages");
+            assert_eq!(
+                envelope.body,
+                xous_kernel::Message::Scalar(xous_kernel::ScalarMessage {
+                    id: 1,
+                    arg1: 0,
+                    arg2: 0,
+                    arg3: 0,
+                    arg4: 0
+                })
+            );
+            let client_pid = envelope.sender.pid().expect("message had no sender");
+            assert_eq!(xous_kernel::revoke_connection(client_pid, sid), Ok(1));
+            revoked_send.send(()).unwrap();
+            client_done_recv.recv().unwrap();
+        },
+    ))
+    .expect("couldn't spawn server process");
+
+    // Spawn the client "process" and wait for the server address.
+    let xous_client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "revoke_connection client",
+        move || {
+            let sid = server_addr_recv.recv().unwrap();
+            let conn = xous_kernel::try_connect(sid).expect("couldn't connect to server");
+            // Only the server's owner may revoke connections to it.
+            let pid = xous_kernel::current_pid().unwrap();
+            assert_eq!(xous_kernel::revoke_connection(pid, sid), Err(xous_kernel::Error::AccessDenied));
+            xous_kernel::try_send_message(conn, xous_kernel::Message::new_scalar(1, 0, 0, 0, 0))
+                .expect("couldn't send message");
+            revoked_recv.recv().unwrap();
+            assert_eq!(
+                xous_kernel::try_send_message(conn, xous_kernel::Message::new_scalar(2, 0, 0, 0, 0)),
+                Err(xous_kernel::Error::ServerNotFound)
+            );
+            client_done_send.send(()).unwrap();
+        },
+    ))
+    .expect("couldn't spawn client process");
+
+    // Wait for both processes to finish
+    crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+    crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
 #[test]
 fn send_blocking_scalar_message() {
     // Start the server in another thread
@@ -890,6 +1071,109 @@ fn send_mutableborrow_message() {
     main_thread.join().expect("couldn't join kernel process");
 }
 
+#[test]
+fn gathered_lend_segments() {
+    use xous_kernel::{Error, MemoryRange, MAX_GATHER_SEGMENTS};
+
+    use crate::services::gathered_window_len;
+    let range = |addr, len| unsafe { MemoryRange::new(addr, len) }.unwrap();
+
+    // Segments are laid end to end, in whatever order they are given.
+    assert_eq!(gathered_window_len(&[range(0x1000_0000, 0x2000), range(0x0800_0000, 0x1000)]), Ok(0x3000));
+
+    // There must be at least one segment, and not too many.
+    assert_eq!(gathered_window_len(&[]), Err(Error::InvalidLimit));
+    let too_many: Vec<MemoryRange> =
+        (0..=MAX_GATHER_SEGMENTS).map(|i| range(0x1000_0000 + i * 0x1000, 0x1000)).collect();
+    assert_eq!(gathered_window_len(&too_many), Err(Error::InvalidLimit));
+    assert_eq!(gathered_window_len(&too_many[..MAX_GATHER_SEGMENTS]), Ok(MAX_GATHER_SEGMENTS * 0x1000));
+
+    // Each segment starts and ends on a page, within user memory.
+    assert_eq!(gathered_window_len(&[range(0x1000_0800, 0x1000)]), Err(Error::BadAlignment));
+    assert_eq!(gathered_window_len(&[range(0x1000_0000, 0x800)]), Err(Error::BadAlignment));
+    assert_eq!(
+        gathered_window_len(&[range(crate::arch::mem::USER_AREA_END - 0x1000, 0x2000)]),
+        Err(Error::BadAddress)
+    );
+    assert_eq!(gathered_window_len(&[range(usize::MAX & !0xfff, 0x1000)]), Err(Error::BadAddress));
+
+    // A page can't be lent twice in one window.
+    assert_eq!(
+        gathered_window_len(&[range(0x1000_0000, 0x3000), range(0x1000_2000, 0x1000)]),
+        Err(Error::ShareViolation)
+    );
+    assert_eq!(
+        gathered_window_len(&[range(0x1000_0000, 0x1000), range(0x1000_0000, 0x1000)]),
+        Err(Error::ShareViolation)
+    );
+    assert_eq!(gathered_window_len(&[range(0x1000_0000, 0x1000), range(0x1000_1000, 0x1000)]), Ok(0x2000));
+}
+
+#[test]
+fn send_gathered_mutableborrow_message() {
+    let main_thread = start_kernel(SERVER_SPEC);
+    let (server_addr_send, server_addr_recv) = unbounded();
+
+    let xous_server = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "send_gathered_mutableborrow_message server",
+        move || {
+            let sid = xous_kernel::create_server().expect("couldn't create test server");
+            server_addr_send.send(sid).unwrap();
+            let envelope = xous_kernel::receive_message(sid).expect("couldn't receive messages");
+            if let xous_kernel::Message::MutableBorrow(m) = envelope.body {
+                // The server sees both segments, one after the other.
+                let bt = unsafe { core::slice::from_raw_parts_mut(m.buf.as_mut_ptr(), m.buf.len()) };
+                assert_eq!(bt.len(), 0x2000);
+                assert!(bt[..0x1000].iter().all(|&b| b == 1));
+                assert!(bt[0x1000..].iter().all(|&b| b == 2));
+                for b in bt.iter_mut() {
+                    *b += 1;
+                }
+                xous_kernel::return_memory(envelope.sender, m.buf).unwrap();
+            } else {
+                panic!("unexpected message type");
+            }
+        },
+    ))
+    .expect("couldn't start server");
+
+    let xous_client = xous_kernel::create_process_as_thread(xous_kernel::ProcessArgsAsThread::new(
+        "send_gathered_mutableborrow_message client",
+        move || {
+            let sid = server_addr_recv.recv().unwrap();
+            let conn = xous_kernel::connect(sid).expect("couldn't connect to server");
+
+            let flags = xous_kernel::MemoryFlags::R | xous_kernel::MemoryFlags::W;
+            let mut first = xous_kernel::map_memory(None, None, 0x1000, flags).unwrap();
+            let mut second = xous_kernel::map_memory(None, None, 0x1000, flags).unwrap();
+            unsafe {
+                first.as_slice_mut::<u8>().fill(1);
+                second.as_slice_mut::<u8>().fill(2);
+            }
+
+            assert_eq!(
+                xous_kernel::lend_gathered(conn, 0, &[], None, None, true),
+                Err(xous_kernel::Error::InvalidLimit)
+            );
+            xous_kernel::lend_gathered(conn, 0, &[first, second], None, None, true)
+                .expect("couldn't lend segments");
+
+            // The server's changes land in each segment.
+            assert!(unsafe { first.as_slice::<u8>() }.iter().all(|&b| b == 2));
+            assert!(unsafe { second.as_slice::<u8>() }.iter().all(|&b| b == 3));
+            xous_kernel::unmap_memory(first).unwrap();
+            xous_kernel::unmap_memory(second).unwrap();
+        },
+    ))
+    .expect("couldn't start client");
+
+    crate::wait_process_as_thread(xous_server).expect("couldn't join server process");
+    crate::wait_process_as_thread(xous_client).expect("couldn't join client process");
+    shutdown_kernel();
+
+    main_thread.join().expect("couldn't join kernel process");
+}
+
 #[test]
 fn send_repeat_mutableborrow_message() {
     let main_thread = start_kernel(SERVER_SPEC);
@@ -0,0 +1,194 @@
+//! Opt-in heap profiling. A process that wants its heap watched wraps its allocator in a `Profiler`,
+//! and calls `serve()` once it is up:
+//!
+//! ```rust,ignore
+//! #[global_allocator]
+//! static ALLOC: alloc_profile::Profiler<std::alloc::System> = alloc_profile::Profiler::system();
+//!
+//! fn main() -> ! {
+//!     log_server::init_wait().unwrap();
+//!     ALLOC.serve();
+//!     // ...
+//! }
+//! ```
+//!
+//! Any other process can then read the counts with `heap_stats(&xns, pid)`; shellchat's `prof`
+//! command does. The wrapper costs four atomic operations per allocation, so it is meant for
+//! debugging builds, behind a feature, and not for release images.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use num_traits::*;
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+enum Opcode {
+    /// Returns the `HeapStats` (blocking scalar)
+    Stats = 0,
+}
+
+/// The counts kept by a `Profiler`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct HeapStats {
+    /// Allocations since the process started, including reallocations; wraps on overflow
+    pub allocations: u32,
+    /// Deallocations since the process started; wraps on overflow
+    pub frees: u32,
+    /// Bytes currently allocated
+    pub live_bytes: usize,
+    /// The most bytes that were allocated at once
+    pub peak_bytes: usize,
+}
+
+/// A `GlobalAlloc` that passes every call through to `A`, and counts it
+pub struct Profiler<A> {
+    inner: A,
+    allocations: AtomicU32,
+    frees: AtomicU32,
+    live_bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+}
+
+impl Profiler<System> {
+    pub const fn system() -> Self { Profiler::new(System) }
+}
+
+impl<A> Profiler<A> {
+    pub const fn new(inner: A) -> Self {
+        Profiler {
+            inner,
+            allocations: AtomicU32::new(0),
+            frees: AtomicU32::new(0),
+            live_bytes: AtomicUsize::new(0),
+            peak_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        HeapStats {
+            allocations: self.allocations.load(Ordering::Relaxed),
+            frees: self.frees.load(Ordering::Relaxed),
+            live_bytes: self.live_bytes.load(Ordering::Relaxed),
+            peak_bytes: self.peak_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn grew(&self, bytes: usize) {
+        let live = self.live_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.peak_bytes.fetch_max(live, Ordering::Relaxed);
+    }
+
+    fn shrank(&self, bytes: usize) { self.live_bytes.fetch_sub(bytes, Ordering::Relaxed); }
+}
+
+impl<A: Sync> Profiler<A> {
+    /// Starts a thread that answers `heap_stats()` queries for this process. Call it once, after the
+    /// log and name servers are up.
+    pub fn serve(&'static self) {
+        std::thread::spawn(move || {
+            let xns = xous_names::XousNames::new().unwrap();
+            let name = server_name(xous::process::id());
+            let sid = xns.register_name(&name, None).expect("can't register the heap profiler");
+            log::info!("heap profile available as {}", name);
+            loop {
+                let msg = xous::receive_message(sid).unwrap();
+                match FromPrimitive::from_usize(msg.body.id()) {
+                    Some(Opcode::Stats) => {
+                        let stats = self.stats();
+                        xous::return_scalar5(
+                            msg.sender,
+                            stats.allocations as usize,
+                            stats.frees as usize,
+                            stats.live_bytes,
+                            stats.peak_bytes,
+                            0,
+                        )
+                        .ok();
+                    }
+                    None => log::error!("couldn't convert opcode: {:?}", msg),
+                }
+            }
+        });
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for Profiler<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            self.allocations.fetch_add(1, Ordering::Relaxed);
+            self.grew(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            self.allocations.fetch_add(1, Ordering::Relaxed);
+            self.grew(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        self.frees.fetch_add(1, Ordering::Relaxed);
+        self.shrank(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = self.inner.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            self.allocations.fetch_add(1, Ordering::Relaxed);
+            if new_size > layout.size() {
+                self.grew(new_size - layout.size());
+            } else {
+                self.shrank(layout.size() - new_size);
+            }
+        }
+        new
+    }
+}
+
+/// The name a profiled process registers its heap profiler under
+fn server_name(pid: u32) -> String { format!("_Heap profile {}_", pid) }
+
+/// Reads the heap counts of process `pid`. Fails with `ServerNotFound` if the process isn't profiled.
+pub fn heap_stats(xns: &xous_names::XousNames, pid: u32) -> Result<HeapStats, xous::Error> {
+    let conn = xns.request_connection(&server_name(pid)).or(Err(xous::Error::ServerNotFound))?;
+    let response = xous::send_message(
+        conn,
+        xous::Message::new_blocking_scalar(Opcode::Stats.to_usize().unwrap(), 0, 0, 0, 0),
+    );
+    unsafe { xous::disconnect(conn).ok() };
+    match response? {
+        xous::Result::Scalar5(allocations, frees, live_bytes, peak_bytes, _) => {
+            Ok(HeapStats { allocations: allocations as u32, frees: frees as u32, live_bytes, peak_bytes })
+        }
+        _ => Err(xous::Error::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_follow_the_heap() {
+        let profiler = Profiler::system();
+        let small = Layout::from_size_align(64, 8).unwrap();
+        unsafe {
+            let a = profiler.alloc(small);
+            let b = profiler.alloc_zeroed(small);
+            let b = profiler.realloc(b, small, 256);
+            profiler.dealloc(a, small);
+            assert_eq!(
+                profiler.stats(),
+                HeapStats { allocations: 3, frees: 1, live_bytes: 256, peak_bytes: 320 }
+            );
+            profiler.dealloc(b, Layout::from_size_align(256, 8).unwrap());
+        }
+        assert_eq!(profiler.stats().live_bytes, 0);
+    }
+}
@@ -15,6 +15,8 @@ pub const POST_SELECTED_PREV: usize = usize::MAX - 1;
 pub enum ChatOp {
     // Save the Dialogue to pddb (ie after PostAdd, PostDelete)
     DialogueSave = 0,
+    /// Set the number of most recent Posts kept when a Dialogue is saved
+    DialogueRetention,
     /// Set the current Dialogue to be displayed
     DialogueSet,
     /// change the Chat UI in/out of focus
@@ -36,6 +38,10 @@ pub enum ChatOp {
     /// Find a Post by timestamp and Author
     PostFind,
     PostFlag,
+    /// Search the Posts in the Dialogue
+    PostSearch,
+    /// Offer the attachments of the Posts in the Dialogue
+    PostAttachments,
     /// Set status bar text
     SetStatusText,
     /// Run or stop the busy animation.
@@ -67,6 +73,9 @@ pub enum IconOp {
 
 pub const POST_TEXT_MAX: usize = 3072;
 
+/// a line starting with this searches the current Dialogue, rather than being sent as a Post
+pub const SEARCH_COMMAND: &str = "/search ";
+
 #[derive(Archive, Serialize, Deserialize, Debug)]
 pub struct Find {
     pub author: xous_ipc::String<128>,
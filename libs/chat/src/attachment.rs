@@ -0,0 +1,377 @@
+//! Attachments (images, files...) that arrive with, or are sent with, a Post.
+//!
+//! The attachment data is kept out of the Dialogue, which is rkyv archived and bounded by
+//! `dialogue::MAX_BYTES`. Each Chat App has one attachment dict in the pddb, named after its Dialogue
+//! dict with `ATTACHMENT_DICT_SUFFIX` appended. In the attachment dict:
+//! * the key of a Dialogue holds an index of its attachments, one per line, and
+//! * the id of an attachment holds its data.
+//!
+//! A Post with an attachment carries a `caption()` of the attachment as text, so that the Post still
+//! reads sensibly in the Chat UI; the attachment itself is reached from the Post's msg menu (→ key).
+
+use std::io::{Error, ErrorKind, Read, Write};
+
+use pddb::Pddb;
+use usb_device_xous::{UsbDeviceType, UsbHid};
+
+use crate::Transport;
+
+/// appended to the Dialogue dict of a Chat App to name its attachment dict
+pub const ATTACHMENT_DICT_SUFFIX: &str = ".attach";
+/// the largest attachment that will be kept in the pddb
+pub const ATTACHMENT_MAX_BYTES: usize = 512 * 1024;
+/// the number of characters of a text attachment shown in its preview
+const PREVIEW_CHARS: usize = 240;
+/// the number of attachment bytes on each line of a USB serial export
+const EXPORT_LINE_BYTES: usize = 32;
+/// the id of an attachment is this prefix followed by 16 hex digits, so it can't collide with a Dialogue key
+const ID_PREFIX: &str = "#";
+
+/// The description of an attachment to a Post in a Dialogue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// unique in the attachment dict, and the pddb key holding the attachment data
+    pub id: String,
+    /// the name of the Author of the Post
+    pub author: String,
+    /// the timestamp of the Post
+    pub timestamp: u64,
+    /// the file name, as given by the sender
+    pub name: String,
+    /// the MIME type (ie "image/png"), as given by the sender
+    pub mime: String,
+    /// the size of the attachment data in bytes
+    pub size: usize,
+}
+
+impl Attachment {
+    /// A one-line description of the attachment (ie "[photo.jpg, 23.4 kB]") to stand in for it in a Post
+    pub fn caption(&self) -> String { format!("[{}, {}]", self.name, human_size(self.size)) }
+
+    /// true if the attachment data is expected to be readable text
+    pub fn is_text(&self) -> bool {
+        self.mime.starts_with("text/") || self.mime == "application/json" || self.mime == "application/xml"
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.id,
+            self.timestamp,
+            self.size,
+            clean(&self.author),
+            clean(&self.mime),
+            clean(&self.name)
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Attachment> {
+        let mut fields = line.splitn(6, '\t');
+        Some(Attachment {
+            id: fields.next()?.to_string(),
+            timestamp: fields.next()?.parse().ok()?,
+            size: fields.next()?.parse().ok()?,
+            author: fields.next()?.to_string(),
+            mime: fields.next()?.to_string(),
+            name: fields.next()?.to_string(),
+        })
+    }
+}
+
+/// The attachments of all the Dialogues of a Chat App
+pub struct Attachments {
+    pddb: Pddb,
+    dict: String,
+}
+
+impl Attachments {
+    /// Open the attachments of the Dialogues in `pddb_dict`
+    ///
+    /// # Arguments
+    ///
+    /// * `pddb_dict` - the pddb dict holding the Dialogues of the Chat App
+    pub fn new(pddb_dict: &str) -> Self {
+        Attachments { pddb: Pddb::new(), dict: format!("{}{}", pddb_dict, ATTACHMENT_DICT_SUFFIX) }
+    }
+
+    /// Keep an attachment to a Post in a Dialogue
+    ///
+    /// # Arguments
+    ///
+    /// * `dialogue` - the pddb key of the Dialogue
+    /// * `author` - the name of the Author of the Post
+    /// * `timestamp` - the timestamp of the Post
+    /// * `name` - the file name of the attachment
+    /// * `mime` - the MIME type of the attachment
+    /// * `data` - the attachment itself
+    ///
+    /// Returns the description of the stored attachment
+    pub fn store(
+        &self,
+        dialogue: &str,
+        author: &str,
+        timestamp: u64,
+        name: &str,
+        mime: &str,
+        data: &[u8],
+    ) -> Result<Attachment, Error> {
+        if data.len() > ATTACHMENT_MAX_BYTES {
+            return Err(Error::new(ErrorKind::InvalidInput, "attachment too large"));
+        }
+        let xns = xous_names::XousNames::new().unwrap();
+        let trng = trng::Trng::new(&xns).map_err(|_| Error::new(ErrorKind::Other, "no trng"))?;
+        let id = format!(
+            "{}{:016x}",
+            ID_PREFIX,
+            trng.get_u64().map_err(|_| Error::new(ErrorKind::Other, "no trng"))?
+        );
+        let attachment = Attachment {
+            id,
+            author: author.to_string(),
+            timestamp,
+            name: name.to_string(),
+            mime: mime.to_string(),
+            size: data.len(),
+        };
+        self.write(&attachment.id, data)?;
+        let mut index = self.list(dialogue);
+        index.push(attachment.clone());
+        if let Err(e) = self.write_index(dialogue, &index) {
+            self.pddb.delete_key(&self.dict, &attachment.id, None).ok();
+            return Err(e);
+        }
+        self.pddb.sync().ok();
+        log::info!("stored attachment {} of {} bytes", attachment.id, attachment.size);
+        Ok(attachment)
+    }
+
+    /// Fetch an attachment to a Post over a Transport, and keep it
+    ///
+    /// # Arguments
+    ///
+    /// * `transport` - the Transport the Post arrived on
+    /// * `reference` - the Platform's reference to the attachment (ie a URL)
+    /// * `dialogue`, `author`, `timestamp`, `name`, `mime` - as for `store()`
+    pub fn receive(
+        &self,
+        transport: &mut dyn Transport,
+        reference: &str,
+        dialogue: &str,
+        author: &str,
+        timestamp: u64,
+        name: &str,
+        mime: &str,
+    ) -> Result<Attachment, Error> {
+        match transport.download(reference) {
+            Some(data) => self.store(dialogue, author, timestamp, name, mime, &data),
+            None => Err(Error::new(ErrorKind::Unsupported, "download failed")),
+        }
+    }
+
+    /// Send a kept attachment to the current conversation of a Transport
+    ///
+    /// Returns true if the attachment was accepted by the Platform
+    pub fn send(&self, transport: &mut dyn Transport, attachment: &Attachment) -> bool {
+        match self.read(attachment) {
+            Ok(data) => transport.upload(&attachment.name, &attachment.mime, &data),
+            Err(e) => {
+                log::warn!("failed to read attachment {}: {:?}", attachment.id, e);
+                false
+            }
+        }
+    }
+
+    /// The attachments to all Posts in a Dialogue, oldest first
+    pub fn list(&self, dialogue: &str) -> Vec<Attachment> {
+        let mut index = Vec::new();
+        match self.pddb.get(&self.dict, dialogue, None, false, false, None, None::<fn()>) {
+            Ok(mut pddb_key) => {
+                if let Err(e) = pddb_key.read_to_end(&mut index) {
+                    log::warn!("failed to read attachment index {}: {:?}", dialogue, e);
+                }
+            }
+            Err(_) => return Vec::new(),
+        }
+        String::from_utf8_lossy(&index).lines().filter_map(Attachment::from_line).collect()
+    }
+
+    /// The attachments to one Post in a Dialogue
+    ///
+    /// # Arguments
+    ///
+    /// * `dialogue` - the pddb key of the Dialogue
+    /// * `author` - the name of the Author of the Post
+    /// * `timestamp` - the timestamp of the Post
+    pub fn of_post(&self, dialogue: &str, author: &str, timestamp: u64) -> Vec<Attachment> {
+        self.list(dialogue).into_iter().filter(|a| a.author == author && a.timestamp == timestamp).collect()
+    }
+
+    /// The attachment data
+    pub fn read(&self, attachment: &Attachment) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        let mut pddb_key =
+            self.pddb.get(&self.dict, &attachment.id, None, false, false, None, None::<fn()>)?;
+        pddb_key.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Forget an attachment to a Post in a Dialogue, and delete its data
+    pub fn delete(&self, dialogue: &str, attachment: &Attachment) -> Result<(), Error> {
+        let index: Vec<Attachment> =
+            self.list(dialogue).into_iter().filter(|a| a.id != attachment.id).collect();
+        if index.is_empty() {
+            self.pddb.delete_key(&self.dict, dialogue, None).ok();
+        } else {
+            self.write_index(dialogue, &index)?;
+        }
+        match self.pddb.delete_key(&self.dict, &attachment.id, None) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+        self.pddb.sync().ok();
+        Ok(())
+    }
+
+    /// A short description of an attachment, followed by the start of its text for a text attachment
+    pub fn preview(&self, attachment: &Attachment) -> String {
+        let mut preview =
+            format!("{}\n{}\n{}", attachment.name, attachment.mime, human_size(attachment.size));
+        if attachment.is_text() {
+            match self.read(attachment) {
+                Ok(data) => {
+                    let text: String = String::from_utf8_lossy(&data).chars().take(PREVIEW_CHARS).collect();
+                    preview.push_str("\n\n");
+                    preview.push_str(&text);
+                    if text.len() < data.len() {
+                        preview.push('…');
+                    }
+                }
+                Err(e) => log::warn!("failed to read attachment {}: {:?}", attachment.id, e),
+            }
+        }
+        preview
+    }
+
+    /// Write an attachment out of the USB serial port, as hex between a header and a footer line:
+    ///
+    /// ```text
+    /// BEGIN ATTACHMENT <size> <mime> <name>
+    /// 89504e470d0a1a0a...
+    /// END ATTACHMENT
+    /// ```
+    ///
+    /// On the host, the lines between the header and footer can be turned back into the file with
+    /// `xxd -r -p`. The USB port is switched back to its previous core (ie FIDO) afterwards.
+    pub fn export_usb(&self, attachment: &Attachment) -> Result<(), Error> {
+        let data = self.read(attachment)?;
+        let usb = UsbHid::new();
+        let previous = usb.get_current_core().ok();
+        usb.ensure_core(UsbDeviceType::Serial)
+            .map_err(|_| Error::new(ErrorKind::Other, "failed to switch USB to serial"))?;
+        let result = Self::write_usb(&usb, attachment, &data);
+        if let Some(previous) = previous.filter(|&core| core != UsbDeviceType::Serial) {
+            if usb.ensure_core(previous).is_err() {
+                log::warn!("failed to switch USB back from serial");
+            }
+        }
+        result
+    }
+
+    fn write_usb(usb: &UsbHid, attachment: &Attachment, data: &[u8]) -> Result<(), Error> {
+        let usb_err = |_: xous::Error| Error::new(ErrorKind::BrokenPipe, "USB serial write failed");
+        usb.serial_write(&format!(
+            "\r\nBEGIN ATTACHMENT {} {} {}\r\n",
+            attachment.size, attachment.mime, attachment.name
+        ))
+        .map_err(usb_err)?;
+        for chunk in data.chunks(EXPORT_LINE_BYTES) {
+            let mut line = String::with_capacity(chunk.len() * 2 + 2);
+            for byte in chunk {
+                line.push_str(&format!("{:02x}", byte));
+            }
+            line.push_str("\r\n");
+            usb.serial_write(&line).map_err(usb_err)?;
+        }
+        usb.serial_write("END ATTACHMENT\r\n").map_err(usb_err)?;
+        log::info!("exported attachment {} over USB serial", attachment.id);
+        Ok(())
+    }
+
+    fn write_index(&self, dialogue: &str, index: &[Attachment]) -> Result<(), Error> {
+        let lines: Vec<String> = index.iter().map(|a| a.to_line()).collect();
+        self.write(dialogue, lines.join("\n").as_bytes())
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> Result<(), Error> {
+        // delete key first to ensure data in a prior longer key is gone
+        self.pddb.delete_key(&self.dict, key, None).ok();
+        let mut pddb_key =
+            self.pddb.get(&self.dict, key, None, true, true, Some(data.len()), None::<fn()>)?;
+        pddb_key.write_all(data)
+    }
+}
+
+/// Keep tabs and newlines out of the fields of the attachment index
+fn clean(field: &str) -> String {
+    field.chars().map(|c| if c == '\t' || c == '\n' || c == '\r' { ' ' } else { c }).collect()
+}
+
+/// A size in bytes, for people (ie "23.4 kB")
+fn human_size(size: usize) -> String {
+    match size {
+        0..=999 => format!("{} B", size),
+        1000..=999_999 => format!("{:.1} kB", size as f32 / 1000.0),
+        _ => format!("{:.1} MB", size as f32 / 1_000_000.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attachment(name: &str, mime: &str, size: usize) -> Attachment {
+        Attachment {
+            id: format!("{}{:016x}", ID_PREFIX, 42),
+            author: "alice".to_string(),
+            timestamp: 1_700_000_000,
+            name: name.to_string(),
+            mime: mime.to_string(),
+            size,
+        }
+    }
+
+    #[test]
+    fn index_lines_round_trip() {
+        let a = attachment("photo.jpg", "image/jpeg", 23_456);
+        assert_eq!(Attachment::from_line(&a.to_line()), Some(a));
+    }
+
+    #[test]
+    fn index_fields_are_cleaned() {
+        let a = attachment("two\tpart\nname.txt", "text/plain", 3);
+        let read = Attachment::from_line(&a.to_line()).unwrap();
+        assert_eq!(read.name, "two part name.txt");
+        assert!(!a.to_line().contains('\n'));
+    }
+
+    #[test]
+    fn malformed_index_lines_are_skipped() {
+        assert_eq!(Attachment::from_line(""), None);
+        assert_eq!(Attachment::from_line("#00\tnot a time\t3\talice\ttext/plain\ta.txt"), None);
+        assert_eq!(Attachment::from_line("#00\t1\t3\talice\ttext/plain"), None);
+    }
+
+    #[test]
+    fn captions_and_sizes() {
+        assert_eq!(attachment("a.txt", "text/plain", 999).caption(), "[a.txt, 999 B]");
+        assert_eq!(human_size(23_456), "23.5 kB");
+        assert_eq!(human_size(2_500_000), "2.5 MB");
+    }
+
+    #[test]
+    fn text_mime_types() {
+        assert!(attachment("a.txt", "text/plain", 1).is_text());
+        assert!(attachment("a.json", "application/json", 1).is_text());
+        assert!(!attachment("a.png", "image/png", 1).is_text());
+    }
+}
@@ -12,7 +12,7 @@ use post::Post;
 use rkyv::{Archive, Deserialize, Serialize};
 
 use crate::ui::VisualProperties;
-use crate::{default_textview, now};
+use crate::{default_textview, now, PostFlag};
 
 // TODO do better than just allocate lots!
 pub const MAX_BYTES: usize = 65536;
@@ -55,7 +55,7 @@ impl Dialogue {
 
     /// Add a new Post to the Dialogue
     ///
-    /// TODO protect against Dialogue::MAX_BYTES overflow
+    /// note: the oldest posts are dropped when the Dialogue is saved, if it would not fit in MAX_BYTES
     ///
     /// note: posts are sorted by timestamp, so:
     /// - `post_add` at beginning or end is fast (middle triggers a binary partition)
@@ -169,6 +169,43 @@ impl Dialogue {
         if self.posts.len() == 0 { None } else { Some(self.posts.len() - 1) }
     }
 
+    /// Drop the oldest Posts to keep at most `max_posts`
+    ///
+    /// Returns the number of Posts dropped
+    ///
+    /// # Arguments
+    ///
+    /// * `max_posts` - the number of most recent Posts to keep
+    pub fn prune(&mut self, max_posts: usize) -> usize {
+        let excess = self.posts.len().saturating_sub(max_posts);
+        self.posts.drain(..excess);
+        excess
+    }
+
+    /// Return the indices of the Posts containing every word in `query`, ignoring case (most recent first).
+    /// Deleted and hidden Posts are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - the words to search for
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let words: Vec<String> = query.split_whitespace().map(|word| word.to_lowercase()).collect();
+        if words.is_empty() {
+            return Vec::new();
+        }
+        self.posts
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, post)| !post.flag_is(PostFlag::Deleted) && !post.flag_is(PostFlag::Hidden))
+            .filter(|(_, post)| {
+                let text = post.text().to_lowercase();
+                words.iter().all(|word| text.contains(word.as_str()))
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
     /// Return a slice of posts
     pub fn posts_as_slice(&self) -> &[Post] { &self.posts }
 
@@ -217,3 +254,44 @@ impl Dialogue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dialogue(texts: &[&str]) -> Dialogue {
+        let mut dialogue = Dialogue::new("room");
+        for (timestamp, text) in texts.iter().enumerate() {
+            dialogue.post_add("alice", timestamp as u64, text, None, None).unwrap();
+        }
+        dialogue
+    }
+
+    #[test]
+    fn prune_keeps_the_most_recent_posts() {
+        let mut dialogue = dialogue(&["one", "two", "three"]);
+        assert_eq!(dialogue.prune(5), 0);
+        assert_eq!(dialogue.prune(2), 1);
+        let texts: Vec<&str> = dialogue.posts_as_slice().iter().map(|post| post.text()).collect();
+        assert_eq!(texts, ["two", "three"]);
+    }
+
+    #[test]
+    fn search_matches_every_word_ignoring_case() {
+        let dialogue = dialogue(&["Lunch at noon", "noon is fine", "see you at LUNCH"]);
+        // most recent first
+        assert_eq!(dialogue.search("lunch"), vec![2, 0]);
+        assert_eq!(dialogue.search("lunch noon"), vec![0]);
+        assert!(dialogue.search("dinner").is_empty());
+        assert!(dialogue.search("  ").is_empty());
+    }
+
+    #[test]
+    fn search_skips_deleted_and_hidden_posts() {
+        let mut dialogue = dialogue(&["secret plan", "another plan"]);
+        dialogue.posts[0].flags_set(PostFlag::Deleted.into());
+        assert_eq!(dialogue.search("plan"), vec![1]);
+        dialogue.posts[1].flags_set(PostFlag::Hidden.into());
+        assert!(dialogue.search("plan").is_empty());
+    }
+}
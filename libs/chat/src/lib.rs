@@ -1,6 +1,8 @@
 pub mod api;
+pub mod attachment;
 pub mod dialogue;
 pub mod icontray;
+pub mod transport;
 pub mod ui;
 
 use std::convert::TryInto;
@@ -9,10 +11,12 @@ use std::thread;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub use api::*;
+pub use attachment::{Attachment, Attachments};
 use gam::MenuItem;
 use graphics_server::api::GlyphStyle;
 use graphics_server::{Point, Rectangle, TextBounds, TextView};
 use num_traits::FromPrimitive;
+pub use transport::{Transport, Transports};
 use ui::VisualProperties;
 pub use ui::BUSY_ANIMATION_RATE_MS;
 use xous::{msg_scalar_unpack, Error, CID, SID};
@@ -185,6 +189,22 @@ impl Chat {
         }
     }
 
+    /// Set the number of most recent Posts kept in each Dialogue saved to the pddb
+    ///
+    /// Older Posts are dropped when the Dialogue is saved. Regardless of this setting, the oldest
+    /// Posts are also dropped if the Dialogue would not fit in `dialogue::MAX_BYTES`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_posts` - the number of Posts to keep, or None to keep as many as fit
+    pub fn dialogue_retention(&self, max_posts: Option<usize>) -> Result<(), Error> {
+        xous::send_message(
+            self.cid,
+            xous::Message::new_scalar(ChatOp::DialogueRetention as usize, max_posts.unwrap_or(0), 0, 0, 0),
+        )
+        .map(|_| ())
+    }
+
     /// Show some user help
     pub fn help(&self) {
         xous::send_message(self.cid, xous::Message::new_scalar(ChatOp::Help as usize, 0, 0, 0, 0))
@@ -414,6 +434,8 @@ pub fn server(
     let mut ui = ui::Ui::new(sid, app_name, app_menu, app_cid, opcode_event);
     let mut dialogue_key = None;
     let mut allow_redraw = false;
+    // holding an arrow key moves through the posts faster the longer it is held
+    let mut scroll = gam::ScrollAccel::new();
     loop {
         let msg = xous::receive_message(sid).unwrap();
         log::debug!("got message {:?}", msg);
@@ -459,6 +481,10 @@ pub fn server(
                     ui.redraw().expect("CHAT couldn't redraw");
                 }
             }
+            Some(ChatOp::DialogueRetention) => msg_scalar_unpack!(msg, max_posts, _, _, _, {
+                log::info!("ChatOp::DialogueRetention {max_posts}");
+                ui.dialogue_retention(if max_posts == 0 { None } else { Some(max_posts) });
+            }),
             Some(ChatOp::DialogueSet) => {
                 log::info!("ChatOp::DialogueSet");
                 let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
@@ -476,6 +502,7 @@ pub fn server(
                     match new_state {
                         gam::FocusState::Background => {
                             allow_redraw = false;
+                            ui.dialogue_flush();
                         }
                         gam::FocusState::Foreground => {
                             allow_redraw = true;
@@ -497,6 +524,10 @@ pub fn server(
                     "↓" => {}
                     "←" => {}
                     "→" => {}
+                    text if text.starts_with(SEARCH_COMMAND) => {
+                        let query = text[SEARCH_COMMAND.len()..].to_string();
+                        ui.post_search(&query);
+                    }
                     _ => {
                         drop(buffer);
                         if let Some(cid) = app_cid {
@@ -532,13 +563,13 @@ pub fn server(
                         '↑' => {
                             log::info!("click ↑ : previous post");
                             ui.set_menu_mode(true); // ← & → activate menus
-                            ui.post_select(POST_SELECTED_PREV);
+                            ui.post_select_by(POST_SELECTED_PREV, scroll.lines('↑', ui.posts_on_screen()));
                             ui.redraw().expect("failed to redraw chat");
                             ui.event(Event::Up);
                         }
                         '↓' => {
                             log::info!("click ↓ : next post");
-                            ui.post_select(POST_SELECTED_NEXT);
+                            ui.post_select_by(POST_SELECTED_NEXT, scroll.lines('↓', ui.posts_on_screen()));
                             ui.redraw().expect("failed to redraw chat");
                             ui.event(Event::Down);
                         }
@@ -619,6 +650,14 @@ pub fn server(
             Some(ChatOp::PostFlag) => {
                 log::warn!("ChatOp::PostFlag not implemented");
             }
+            Some(ChatOp::PostSearch) => {
+                log::info!("ChatOp::PostSearch");
+                ui.post_search_modal();
+            }
+            Some(ChatOp::PostAttachments) => {
+                log::info!("ChatOp::PostAttachments");
+                ui.attachment_menu(false);
+            }
             Some(ChatOp::MenuAdd) => {
                 log::warn!("ChatOp::MenuAdd not implemented");
                 let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
@@ -630,6 +669,7 @@ pub fn server(
             }
             Some(ChatOp::Quit) => {
                 log::error!("got Quit");
+                ui.dialogue_flush();
                 break;
             }
             _ => log::warn!("got unknown message"),
@@ -663,3 +703,25 @@ pub fn cf_set_busy_state(chat_cid: xous::CID, run: bool) {
     .map(|_| ())
     .expect("internal error");
 }
+
+/// Add a Post to the current Dialogue, ie from a Transport's listener thread
+pub fn cf_post_add(chat_cid: xous::CID, author: &str, timestamp: u64, text: &str) {
+    let post = api::Post {
+        dialogue_id: xous_ipc::String::new(),
+        author: xous_ipc::String::from_str(author),
+        timestamp,
+        text: xous_ipc::String::from_str(text),
+        attach_url: None,
+    };
+    Buffer::into_buf(post)
+        .expect("internal error")
+        .send(chat_cid, ChatOp::PostAdd as u32)
+        .expect("internal error");
+}
+
+/// Trigger the chat ui to save the current Dialogue to the pddb
+pub fn cf_dialogue_save(chat_cid: xous::CID) {
+    xous::send_message(chat_cid, xous::Message::new_scalar(ChatOp::DialogueSave as usize, 0, 0, 0, 0))
+        .map(|_| ())
+        .expect("internal error");
+}
@@ -0,0 +1,178 @@
+use std::io::{ErrorKind, Read, Write};
+
+use pddb::Pddb;
+
+/// pddb key holding the most recently opened conversation
+const LAST_KEY: &str = "_last";
+
+/// A Transport carries Posts between the Chat UI and one chat Platform (Matrix, IRC, a radio bridge...)
+///
+/// A Chat App registers one or more Transports with a `Transports` registry, which remembers which
+/// Transport each conversation was opened with. The Chat UI itself is unaware of the protocol in use.
+///
+/// A conversation is identified by the pddb key of its Dialogue, so each Transport must produce keys
+/// that cannot collide with those of the other Transports in the same Chat App.
+pub trait Transport {
+    /// A short, stable name for the Transport (ie "matrix"), stored against each conversation
+    fn name(&self) -> &'static str;
+
+    /// Bring the link to the Platform up, logging in if required
+    ///
+    /// Returns true if the Transport is ready to `join()` a conversation
+    fn connect(&mut self) -> bool;
+
+    /// Forget any credentials held for the Platform
+    fn logout(&mut self);
+
+    /// Join a conversation, set it as the current Chat UI Dialogue, and start listening for new Posts
+    ///
+    /// # Arguments
+    ///
+    /// * `conversation` - the Dialogue key of a previous conversation, or None to prompt the user
+    ///
+    /// Returns the Dialogue key of the joined conversation
+    fn join(&mut self, conversation: Option<&str>) -> Option<String>;
+
+    /// Stop listening for new Posts in the current conversation
+    fn leave(&mut self);
+
+    /// Send a user Post to the current conversation
+    ///
+    /// Returns true if the Post was accepted by the Platform
+    fn post(&mut self, text: &str) -> bool;
+
+    /// Send an attachment to the current conversation
+    ///
+    /// Returns true if the attachment was accepted by the Platform. By default, a Transport carries no
+    /// attachments.
+    fn upload(&mut self, _name: &str, _mime: &str, _data: &[u8]) -> bool { false }
+
+    /// Fetch the attachment of a received Post
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - the Platform's reference to the attachment (ie a URL)
+    ///
+    /// Returns the attachment data, or None if it could not be fetched. By default, a Transport carries
+    /// no attachments.
+    fn download(&mut self, _reference: &str) -> Option<Vec<u8>> { None }
+}
+
+/// A registry of the Transports available to a Chat App, and of the Transport bound to each conversation.
+///
+/// The bindings are kept in a pddb dict of the Chat App's choosing: the key is the Dialogue key of the
+/// conversation, and the value is the `Transport::name()` it was opened with.
+pub struct Transports<'a> {
+    pddb: Pddb,
+    dict: &'static str,
+    transports: Vec<Box<dyn Transport + 'a>>,
+    current: Option<usize>,
+}
+
+impl<'a> Transports<'a> {
+    /// Create an empty registry
+    ///
+    /// # Arguments
+    ///
+    /// * `pddb_dict` - the pddb dict to hold the conversation to Transport bindings
+    pub fn new(pddb_dict: &'static str) -> Self {
+        Transports { pddb: Pddb::new(), dict: pddb_dict, transports: Vec::new(), current: None }
+    }
+
+    /// Make a Transport available to conversations. The first registered is the default.
+    pub fn register(&mut self, transport: Box<dyn Transport + 'a>) { self.transports.push(transport); }
+
+    /// The names of the registered Transports, in order of registration
+    pub fn names(&self) -> Vec<&'static str> { self.transports.iter().map(|t| t.name()).collect() }
+
+    /// The Transport carrying the current conversation, if any
+    pub fn current(&mut self) -> Option<&mut (dyn Transport + 'a)> {
+        match self.current {
+            Some(i) => Some(self.transports[i].as_mut()),
+            None => None,
+        }
+    }
+
+    /// The known conversations, as (Dialogue key, Transport name) pairs
+    pub fn conversations(&self) -> Vec<(String, String)> {
+        let mut keys = self.pddb.list_keys(self.dict, None).unwrap_or_default();
+        keys.sort();
+        keys.into_iter()
+            .filter(|key| key.ne(LAST_KEY))
+            .filter_map(|key| self.read(&key).map(|name| (key, name)))
+            .collect()
+    }
+
+    /// The Dialogue key and Transport name of the most recently opened conversation
+    pub fn last(&self) -> Option<(String, String)> {
+        let key = self.read(LAST_KEY)?;
+        let name = self.read(&key)?;
+        Some((key, name))
+    }
+
+    /// Open a conversation on the named Transport, leaving the current conversation first
+    ///
+    /// # Arguments
+    ///
+    /// * `transport` - the name of a registered Transport
+    /// * `conversation` - the Dialogue key of a known conversation, or None to let the Transport prompt
+    ///
+    /// Returns the Dialogue key of the opened conversation
+    pub fn open(&mut self, transport: &str, conversation: Option<&str>) -> Option<String> {
+        let index = match self.transports.iter().position(|t| t.name() == transport) {
+            Some(index) => index,
+            None => {
+                log::warn!("no transport named {}", transport);
+                return None;
+            }
+        };
+        if let Some(current) = self.current() {
+            current.leave();
+        }
+        self.current = Some(index);
+        let transport = &mut self.transports[index];
+        if !transport.connect() {
+            return None;
+        }
+        let key = transport.join(conversation)?;
+        let name = transport.name();
+        self.write(&key, name);
+        self.write(LAST_KEY, &key);
+        log::info!("opened {} over {}", key, name);
+        Some(key)
+    }
+
+    /// Forget a conversation's Transport binding. The Dialogue itself is left in the pddb.
+    pub fn forget(&self, conversation: &str) {
+        match self.pddb.delete_key(self.dict, conversation, None) {
+            Ok(_) => {
+                self.pddb.sync().ok();
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => log::warn!("failed to forget {}: {:?}", conversation, e),
+        }
+    }
+
+    fn read(&self, key: &str) -> Option<String> {
+        let mut value = Vec::new();
+        match self.pddb.get(self.dict, key, None, false, false, None, None::<fn()>) {
+            Ok(mut pddb_key) => pddb_key.read_to_end(&mut value).ok()?,
+            Err(_) => return None,
+        };
+        String::from_utf8(value).ok()
+    }
+
+    fn write(&self, key: &str, value: &str) {
+        // delete key first to ensure data in a prior longer key is gone
+        self.pddb.delete_key(self.dict, key, None).ok();
+        match self.pddb.get(self.dict, key, None, true, true, None, None::<fn()>) {
+            Ok(mut pddb_key) => match pddb_key.write_all(value.as_bytes()) {
+                Ok(()) => {
+                    self.pddb.sync().ok();
+                }
+                Err(e) => log::warn!("error writing {}:{} {:?}", self.dict, key, e),
+            },
+            Err(e) => log::warn!("failed to set pddb {}:{} {:?}", self.dict, key, e),
+        }
+    }
+}
@@ -4,7 +4,7 @@ use std::fmt::Write as TextWrite;
 use std::io::{Error, ErrorKind, Read, Write};
 
 use dialogue::{post::Post, Dialogue};
-use gam::{menu_matic, MenuMatic, UxRegistration};
+use gam::{menu_matic, MenuMatic, MenuPayload, UxRegistration};
 use graphics_server::api::GlyphStyle;
 use graphics_server::{DrawStyle, Gid, Line, PixelColor, Point, Rectangle, TextBounds, TextView};
 use locales::t;
@@ -22,6 +22,12 @@ use super::*;
 use crate::icontray::Icontray;
 
 pub const BUSY_ANIMATION_RATE_MS: usize = 200;
+/// the Dialogue is saved to pddb after this many Posts have been added, without waiting to be asked
+const AUTOSAVE_POSTS: usize = 8;
+/// the most search results offered to choose from
+const SEARCH_RESULTS_MAX: usize = 12;
+/// the length of the Post text shown in a search result
+const SEARCH_SNIPPET_CHARS: usize = 40;
 
 /// Variables that define the visual properties of the layout
 pub struct VisualProperties {
@@ -49,6 +55,10 @@ pub(crate) struct Ui {
     pddb_dict: Option<String>,
     pddb_key: Option<String>,
     dialogue: Option<Dialogue>,
+    /// the number of most recent Posts kept when the Dialogue is saved
+    retention: Option<usize>,
+    /// the number of Posts added since the Dialogue was last saved
+    unsaved: usize,
 
     // Callbacks:
     // callback to our own server
@@ -61,6 +71,8 @@ pub(crate) struct Ui {
     gam: gam::Gam,
     modals: Modals,
     tt: Ticktimer,
+    /// the address book, to put names to authors; `None` if it isn't in this image
+    contacts: Option<contacts::Contacts>,
 
     /// These variables are managed exclusively by the layout routine.
     /// the selected post is highlighted onscreen and the focus of the msg menu
@@ -120,12 +132,28 @@ impl Ui {
             .unwrap();
         let xns = XousNames::new().unwrap();
         let modals = Modals::new(&xns).unwrap();
+        let contacts = contacts::Contacts::new(&xns).ok();
         let canvas = gam.request_content_canvas(token).expect("couldn't get content canvas");
         let screensize = gam.get_canvas_bounds(canvas).expect("couldn't get dimensions of content canvas");
         // TODO this is a stub - implement F1-4 actions and autocompletes
         let _icontray = Icontray::new(Some(xous::connect(sid).unwrap()), ["F1", "F2", "F3", "F4"]);
         let menu_mgr = menu_matic(Vec::<MenuItem>::new(), app_menu, Some(xous::create_server().unwrap()))
             .expect("couldn't create MenuMatic manager");
+        let self_cid = xous::connect(sid).unwrap();
+        menu_mgr.add_item(MenuItem {
+            name: xous_ipc::String::from_str(t!("chat.menu.search", locales::LANG)),
+            action_conn: Some(self_cid),
+            action_opcode: ChatOp::PostSearch as u32,
+            action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+            close_on_select: true,
+        });
+        menu_mgr.add_item(MenuItem {
+            name: xous_ipc::String::from_str(t!("chat.menu.attachments", locales::LANG)),
+            action_conn: Some(self_cid),
+            action_opcode: ChatOp::PostAttachments as u32,
+            action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+            close_on_select: true,
+        });
         let pddb = pddb::Pddb::new();
         pddb.try_mount();
 
@@ -165,12 +193,15 @@ impl Ui {
             pddb_dict: None,
             pddb_key: None,
             dialogue: None,
-            self_cid: xous::connect(sid).unwrap(),
+            retention: None,
+            unsaved: 0,
+            self_cid,
             app_cid,
             opcode_event,
             gam,
             modals,
             tt,
+            contacts,
             status_tv,
             status_last_update_ms,
             layout_selected: None,
@@ -194,12 +225,7 @@ impl Ui {
                         let mut bytes = [0u8; dialogue::MAX_BYTES + 2];
                         match pddb_key.read(&mut bytes) {
                             Ok(_) => {
-                                // extract pos u16 from the first 2 bytes
-                                let pos: u16 = u16::from_be_bytes([bytes[0], bytes[1]]);
-                                let pos: usize = pos.into();
-                                // deserialize the Dialogue
-                                let archive = unsafe { rkyv::archived_value::<Dialogue>(&bytes, pos) };
-                                self.dialogue = match archive.deserialize(&mut AllocDeserializer {}) {
+                                self.dialogue = match dialogue_deserialize(&bytes) {
                                     Ok(dialogue) => {
                                         // show most recent posts onscreen
                                         self.layout_selected = dialogue.post_last();
@@ -232,38 +258,49 @@ impl Ui {
     }
 
     /// Save the current Dialogue to pddb
-    pub fn dialogue_save(&self) -> Result<(), Error> {
-        match (&self.dialogue, &self.pddb_dict, &self.pddb_key) {
+    ///
+    /// The oldest Posts are dropped first, to keep within the retention limit and within MAX_BYTES.
+    pub fn dialogue_save(&mut self) -> Result<(), Error> {
+        match (&mut self.dialogue, &self.pddb_dict, &self.pddb_key) {
             (Some(dialogue), Some(dict), Some(key)) => {
+                let mut dropped = match self.retention {
+                    Some(max_posts) => dialogue.prune(max_posts),
+                    None => 0,
+                };
+                let serialized = loop {
+                    let count = dialogue.posts_as_slice().len();
+                    match dialogue_serialize(dialogue) {
+                        Ok(bytes) if bytes.len() <= dialogue::MAX_BYTES => break Ok(bytes),
+                        Ok(_) if count == 0 => {
+                            break Err(Error::new(ErrorKind::InvalidData, "dialogue > MAX_BYTES"));
+                        }
+                        Err(e) if count == 0 => break Err(e),
+                        // drop an eighth of the oldest posts at a time, rather than re-serializing per post
+                        _ => dropped += dialogue.prune(count - (count / 8).max(1)),
+                    }
+                };
+                if dropped > 0 {
+                    log::info!("dropped {} old posts from {}:{}", dropped, dict, key);
+                    self.layout_selected =
+                        self.layout_selected.map(|selected| selected.saturating_sub(dropped));
+                    self.layout_range.clear();
+                }
+                let bytes = serialized.map_err(|e| {
+                    log::warn!("failed to serialize {}:{}: {}", dict, key, e);
+                    e
+                })?;
                 let hint = Some(dialogue::MAX_BYTES + 2);
                 match self.pddb.get(&dict, &key, None, true, true, hint, None::<fn()>) {
-                    Ok(mut pddb_key) => {
-                        let mut buf = Vec::<u8>::new();
-                        // reserve 2 bytes to hold a u16 (see below)
-                        let reserved = 2;
-                        buf.push(0u8);
-                        buf.push(0u8);
-
-                        // serialize the Dialogue
-                        let mut serializer = WriteSerializer::with_pos(buf, reserved);
-                        let pos = serializer.serialize_value(dialogue).unwrap();
-                        let mut bytes = serializer.into_inner();
-
-                        // copy pop u16 into the first 2 bytes to enable the rkyv archive to be deserialised
-                        let pos: u16 = u16::try_from(pos).expect("data > u16");
-                        let pos_bytes = pos.to_be_bytes();
-                        bytes[0] = pos_bytes[0];
-                        bytes[1] = pos_bytes[1];
-                        match pddb_key.write(&bytes) {
-                            Ok(len) => {
-                                self.pddb.sync().ok();
-                                log::info!("Wrote {} bytes to {}:{}", len, dict, key);
-                            }
-                            Err(e) => {
-                                log::warn!("Error writing {}:{}: {:?}", dict, key, e);
-                            }
+                    Ok(mut pddb_key) => match pddb_key.write(&bytes) {
+                        Ok(len) => {
+                            self.pddb.sync().ok();
+                            self.unsaved = 0;
+                            log::info!("Wrote {} bytes to {}:{}", len, dict, key);
                         }
-                    }
+                        Err(e) => {
+                            log::warn!("Error writing {}:{}: {:?}", dict, key, e);
+                        }
+                    },
                     Err(e) => log::warn!("failed to create {}:{}\n{}", dict, key, e),
                 }
                 Ok(())
@@ -275,6 +312,17 @@ impl Ui {
         }
     }
 
+    /// Save the current Dialogue to pddb, if any Posts have been added since it was last saved
+    pub fn dialogue_flush(&mut self) {
+        if self.unsaved > 0 {
+            self.dialogue_save().ok();
+        }
+    }
+
+    /// Set the number of most recent Posts kept when a Dialogue is saved, or None to keep them all
+    /// (up to MAX_BYTES).
+    pub fn dialogue_retention(&mut self, max_posts: Option<usize>) { self.retention = max_posts; }
+
     /// Set the current Dialogue
     ///
     /// # Arguments
@@ -282,6 +330,7 @@ impl Ui {
     /// * `pddb_dict` - the pddb dict holding all Dialogues for this Chat App
     /// * `pddb_key` - the pddb key holding a Dialogue
     pub fn dialogue_set(&mut self, pddb_dict: &str, pddb_key: Option<&str>) {
+        self.dialogue_flush();
         self.pddb_dict = Some(pddb_dict.to_string());
         self.pddb_key = pddb_key.map(|key| key.to_string());
         if self.pddb_key.is_none() {
@@ -374,6 +423,10 @@ impl Ui {
                     dialogue
                         .post_add(author, timestamp, text, attach_url, Some((&self.vp, &self.gam)))
                         .unwrap();
+                    self.unsaved += 1;
+                    if self.unsaved >= AUTOSAVE_POSTS {
+                        self.dialogue_save()?;
+                    }
                 } else {
                     log::warn!(
                         "dropping Post as dialogue_id does not match pddb_key: '{}' vs '{}'",
@@ -410,6 +463,82 @@ impl Ui {
         }
     }
 
+    /// The name in the address book of the contact with the handle `author`, or else `author` itself
+    ///
+    /// # Arguments
+    ///
+    /// * `author` - the name of an Author, as the chat protocol has it
+    pub fn contact_name(&self, author: &str) -> String {
+        match self.contacts.as_ref().map(|contacts| contacts.find_handle(author)) {
+            Some(Ok(Ok(contact))) => contact.name.to_string(),
+            _ => author.to_string(),
+        }
+    }
+
+    /// Search the current Dialogue for Posts containing every word in `query`, and select the Post
+    /// chosen from the results
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - the words to search for
+    pub fn post_search(&mut self, query: &str) {
+        let (hits, results) = match &self.dialogue {
+            Some(dialogue) => {
+                let hits: Vec<usize> = dialogue.search(query).into_iter().take(SEARCH_RESULTS_MAX).collect();
+                let results: Vec<String> = hits
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(n, &index)| {
+                        let post = dialogue.post_get(index)?;
+                        let author =
+                            dialogue.author(post.author_id()).map_or("", |author| author.name.as_str());
+                        let snippet: String = post.text().chars().take(SEARCH_SNIPPET_CHARS).collect();
+                        Some(format!("{}. {}: {}", n + 1, self.contact_name(author), snippet))
+                    })
+                    .collect();
+                (hits, results)
+            }
+            None => (Vec::new(), Vec::new()),
+        };
+        log::info!("search found {} posts", hits.len());
+        let chosen = match hits.len() {
+            0 => {
+                self.modals
+                    .show_notification(t!("chat.search.none", locales::LANG), None)
+                    .expect("notification failed");
+                None
+            }
+            1 => Some(hits[0]),
+            _ => {
+                self.modals
+                    .add_list(results.iter().map(|s| s.as_str()).collect())
+                    .expect("failed modal add_list");
+                match self.modals.get_radiobutton(t!("chat.search.results", locales::LANG)) {
+                    Ok(choice) => results.iter().position(|result| *result == choice).map(|n| hits[n]),
+                    Err(e) => {
+                        log::warn!("failed to get search result: {:?}", e);
+                        None
+                    }
+                }
+            }
+        };
+        if let Some(index) = chosen {
+            self.post_select(index);
+            self.redraw().expect("couldn't redraw screen");
+        }
+    }
+
+    /// Present a Modal to enter a search of the current Dialogue
+    pub fn post_search_modal(&mut self) {
+        match self.modals.alert_builder(t!("chat.search.prompt", locales::LANG)).field(None, None).build() {
+            Ok(text) => {
+                let query = text.first().as_str().to_string();
+                self.post_search(&query);
+            }
+            Err(e) => log::warn!("failed to get search text: {:?}", e),
+        }
+    }
+
     /// Return Some<Post> from the current Dialogue, or None
     ///
     /// # Arguments
@@ -467,6 +596,21 @@ impl Ui {
         }
     }
 
+    /// Moves the selection `lines` posts towards the newest (`POST_SELECTED_NEXT`) or the oldest
+    /// (`POST_SELECTED_PREV`), stopping at either end.
+    pub fn post_select_by(&mut self, direction: usize, lines: usize) {
+        for _ in 0..lines {
+            let before = self.layout_selected;
+            self.post_select(direction);
+            if self.layout_selected == before {
+                break;
+            }
+        }
+    }
+
+    /// The number of posts on screen at the last redraw
+    pub fn posts_on_screen(&self) -> usize { self.layout_range.len().max(1) }
+
     pub fn get_menu_mode(&self) -> bool { self.menu_mode }
 
     pub fn set_menu_mode(&mut self, menu_mode: bool) { self.menu_mode = menu_mode; }
@@ -514,7 +658,91 @@ impl Ui {
 
     /// Show the Msg Menu (→ key)
     pub(crate) fn raise_msg_menu(&mut self) {
-        log::warn!("msg menu not implemented - pull-requests welcome");
+        match self.layout_selected {
+            Some(_) => self.attachment_menu(true),
+            None => log::warn!("msg menu not implemented - pull-requests welcome"),
+        }
+    }
+
+    /// Offer the attachments in the current Dialogue to preview, export over USB, or delete
+    ///
+    /// # Arguments
+    ///
+    /// * `selected` - offer only the attachments of the selected Post
+    pub fn attachment_menu(&mut self, selected: bool) {
+        let (dict, key) = match (&self.pddb_dict, &self.pddb_key) {
+            (Some(dict), Some(key)) => (dict.to_string(), key.to_string()),
+            _ => return,
+        };
+        let attachments = Attachments::new(&dict);
+        let post = match (selected, &self.dialogue, self.layout_selected) {
+            (false, _, _) => None,
+            (true, Some(dialogue), Some(index)) => dialogue.post_get(index).map(|post| {
+                let author = dialogue.author(post.author_id()).map_or("", |author| author.name.as_str());
+                (author.to_string(), post.timestamp())
+            }),
+            (true, _, _) => return,
+        };
+        let list = match &post {
+            Some((author, timestamp)) => attachments.of_post(&key, author, *timestamp),
+            None => attachments.list(&key),
+        };
+        let attachment = match list.len() {
+            0 => {
+                if post.is_none() {
+                    self.modals
+                        .show_notification(t!("chat.attach.none", locales::LANG), None)
+                        .expect("notification failed");
+                } else {
+                    log::info!("no attachments to the selected post");
+                }
+                return;
+            }
+            1 => &list[0],
+            _ => {
+                let captions: Vec<String> = list.iter().map(|a| a.caption()).collect();
+                self.modals
+                    .add_list(captions.iter().map(|s| s.as_str()).collect())
+                    .expect("failed modal add_list");
+                match self.modals.get_radiobutton(t!("chat.attach.title", locales::LANG)) {
+                    Ok(choice) => match captions.iter().position(|caption| *caption == choice) {
+                        Some(n) => &list[n],
+                        None => return,
+                    },
+                    Err(e) => {
+                        log::warn!("failed to get attachment: {:?}", e);
+                        return;
+                    }
+                }
+            }
+        };
+        let actions = [
+            t!("chat.attach.preview", locales::LANG),
+            t!("chat.attach.export", locales::LANG),
+            t!("chat.attach.delete", locales::LANG),
+        ];
+        self.modals.add_list(actions.to_vec()).expect("failed modal add_list");
+        let result = match self.modals.get_radiobutton(&attachment.caption()) {
+            Ok(action) if action.as_str() == actions[0] => {
+                self.modals
+                    .show_notification(&attachments.preview(attachment), None)
+                    .expect("notification failed");
+                Ok(())
+            }
+            Ok(action) if action.as_str() == actions[1] => attachments.export_usb(attachment).map(|_| {
+                self.modals
+                    .show_notification(t!("chat.attach.exported", locales::LANG), None)
+                    .expect("notification failed");
+            }),
+            Ok(action) if action.as_str() == actions[2] => attachments.delete(&key, attachment),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            log::warn!("attachment {} failed: {:?}", attachment.id, e);
+            self.modals
+                .show_notification(&format!("{} {}", t!("chat.attach.failed", locales::LANG), e), None)
+                .expect("notification failed");
+        }
     }
 
     /// Redraw posts on the screen.
@@ -806,3 +1034,76 @@ impl Ui {
         }
     }
 }
+
+/// Serialize a Dialogue for the pddb: a u16 holding the position of the rkyv archive root, then the archive
+/// Serialize a Dialogue, prefixed by the u16 position of its rkyv archive
+///
+/// Fails if the position doesn't fit in the u16 prefix
+fn dialogue_serialize(dialogue: &Dialogue) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::<u8>::new();
+    // reserve 2 bytes to hold a u16 (see below)
+    let reserved = 2;
+    buf.push(0u8);
+    buf.push(0u8);
+
+    // serialize the Dialogue
+    let mut serializer = WriteSerializer::with_pos(buf, reserved);
+    let pos = serializer
+        .serialize_value(dialogue)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("failed to serialize: {:?}", e)))?;
+    let mut bytes = serializer.into_inner();
+
+    // copy pop u16 into the first 2 bytes to enable the rkyv archive to be deserialised
+    let pos: u16 = u16::try_from(pos).map_err(|_| Error::new(ErrorKind::InvalidData, "data > u16"))?;
+    let pos_bytes = pos.to_be_bytes();
+    bytes[0] = pos_bytes[0];
+    bytes[1] = pos_bytes[1];
+    Ok(bytes)
+}
+
+/// Deserialize a Dialogue written by `dialogue_serialize()`
+fn dialogue_deserialize(bytes: &[u8]) -> Result<Dialogue, Error> {
+    if bytes.len() < 2 {
+        return Err(Error::new(ErrorKind::InvalidData, "missing position"));
+    }
+    // extract pos u16 from the first 2 bytes
+    let pos: usize = u16::from_be_bytes([bytes[0], bytes[1]]).into();
+    // deserialize the Dialogue
+    let archive = unsafe { rkyv::archived_value::<Dialogue>(bytes, pos) };
+    archive
+        .deserialize(&mut AllocDeserializer {})
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dialogue_round_trips() {
+        let mut dialogue = Dialogue::new("room");
+        dialogue.post_add("alice", 10, "hello", None, None).unwrap();
+        dialogue.post_add("bob", 20, "hi alice", None, None).unwrap();
+        let bytes = dialogue_serialize(&dialogue).unwrap();
+        let read = dialogue_deserialize(&bytes).unwrap();
+        assert_eq!(read.title, "room");
+        let texts: Vec<&str> = read.posts_as_slice().iter().map(|post| post.text()).collect();
+        assert_eq!(texts, ["hello", "hi alice"]);
+    }
+
+    #[test]
+    fn oversized_dialogue_is_refused() {
+        let mut dialogue = Dialogue::new("room");
+        let text = "x".repeat(POST_TEXT_MAX);
+        for timestamp in 0..32 {
+            dialogue.post_add("alice", timestamp, &text, None, None).unwrap();
+        }
+        // the archive position no longer fits in the u16 prefix
+        assert_eq!(dialogue_serialize(&dialogue).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_dialogue_is_refused() {
+        assert!(dialogue_deserialize(&[0u8]).is_err());
+    }
+}
@@ -4,6 +4,7 @@
 pub mod debug;
 pub mod ifram;
 pub mod iox;
+pub mod pwm;
 pub mod sce;
 pub mod udma;
 pub mod usb;
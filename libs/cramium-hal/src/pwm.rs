@@ -0,0 +1,211 @@
+//! PWM outputs, from the advanced timer block.
+//!
+//! The block has four 16-bit timers, each driving four output channels. A timer's counter, period and
+//! prescaler are shared by its channels, so the channels of one timer always run at the same frequency;
+//! each channel has its own compare threshold, and so its own duty cycle.
+//!
+//! The generated `utra` has no register description for this block, so its layout is given here. It
+//! is the PULP `apb_adv_timer`.
+//!
+//! Routing a channel to a pin is done separately, by selecting the pin's alternate function in the IOX.
+
+/// Number of timers in the block
+pub const PWM_TIMERS: usize = 4;
+/// Number of output channels of each timer
+pub const PWM_CHANNELS: usize = 4;
+/// The timers count perclk, as set up by the loader
+pub const PWM_CLOCK_HZ: u32 = 100_000_000;
+/// Duty cycles are given in parts per `PWM_DUTY_MAX`
+pub const PWM_DUTY_MAX: u32 = 1000;
+
+/// The counter is 16 bits
+const MAX_PERIOD: u32 = 1 << 16;
+/// The prescaler is 8 bits, and divides by its value plus one
+const MAX_PRESCALER: u32 = 1 << 8;
+
+// register offsets, in words, from the base of a timer
+const TIMER_STRIDE: usize = 0x40 / 4;
+const REG_CMD: usize = 0x00 / 4;
+const REG_CONFIG: usize = 0x04 / 4;
+const REG_THRESHOLD: usize = 0x08 / 4;
+const REG_TH_CHANNEL0: usize = 0x0C / 4;
+// register offsets, in words, from the base of the block
+const REG_CLOCK_EN: usize = 0x104 / 4;
+
+const CMD_START: u32 = 1 << 0;
+const CMD_STOP: u32 = 1 << 1;
+const CMD_UPDATE: u32 = 1 << 2;
+const CMD_RESET: u32 = 1 << 3;
+
+/// count up from the low threshold to the high threshold, then restart (as opposed to counting back down)
+const CONFIG_SAWTOOTH: u32 = 1 << 12;
+const CONFIG_PRESCALER_SHIFT: u32 = 16;
+
+const CHANNEL_ACTION_SHIFT: u32 = 16;
+
+/// What a channel output does when the counter reaches its threshold, and then at the end of the period
+#[derive(Debug, Copy, Clone)]
+#[repr(u32)]
+enum ChannelAction {
+    /// set, and stay set
+    Set = 0,
+    /// clear, and stay clear
+    Clear = 4,
+    /// clear, then set again at the end of the period
+    ClearSet = 6,
+}
+
+/// The prescaler and period for a timer to run at `freq_hz`, or None if it can't be reached.
+///
+/// The smallest prescaler that lets the period fit in the counter is used, which keeps the most duty
+/// cycle resolution.
+pub fn pwm_divisors(clock_hz: u32, freq_hz: u32) -> Option<(u32, u32)> {
+    if freq_hz == 0 || clock_hz / freq_hz < 2 {
+        return None;
+    }
+    let ticks = clock_hz / freq_hz;
+    let prescaler = (ticks + MAX_PERIOD - 1) / MAX_PERIOD;
+    if prescaler > MAX_PRESCALER {
+        return None;
+    }
+    let period = ((clock_hz / prescaler) + freq_hz / 2) / freq_hz;
+    Some((prescaler, period.clamp(2, MAX_PERIOD)))
+}
+
+pub struct Pwm {
+    base: *mut u32,
+    /// the period of each timer, in counts
+    periods: [u32; PWM_TIMERS],
+    /// the duty cycle of each channel, in parts per `PWM_DUTY_MAX`
+    duties: [[u32; PWM_CHANNELS]; PWM_TIMERS],
+}
+
+impl Pwm {
+    /// `base` is the virtual address that `HW_PWM_BASE` is mapped to. All timers start stopped.
+    pub fn new(base: *mut u32) -> Self {
+        let mut pwm =
+            Pwm { base, periods: [MAX_PERIOD; PWM_TIMERS], duties: [[0; PWM_CHANNELS]; PWM_TIMERS] };
+        for timer in 0..PWM_TIMERS {
+            pwm.write(timer, REG_CMD, CMD_STOP | CMD_RESET);
+        }
+        pwm.write_block(REG_CLOCK_EN, 0);
+        pwm
+    }
+
+    fn write(&mut self, timer: usize, reg: usize, value: u32) {
+        assert!(timer < PWM_TIMERS, "timer out of range");
+        // safety: the offset is within the block, as the timer is range checked and the register
+        // offsets are constants within the timer's stride
+        unsafe { self.base.add(timer * TIMER_STRIDE + reg).write_volatile(value) }
+    }
+
+    fn write_block(&mut self, reg: usize, value: u32) {
+        // safety: `reg` is one of the constant offsets within the block
+        unsafe { self.base.add(reg).write_volatile(value) }
+    }
+
+    fn read_block(&self, reg: usize) -> u32 {
+        // safety: `reg` is one of the constant offsets within the block
+        unsafe { self.base.add(reg).read_volatile() }
+    }
+
+    /// Sets the frequency of a timer, and so of all of its channels. The duty cycle of each channel is
+    /// kept.
+    ///
+    /// Returns the frequency actually set, which is the nearest that the dividers can reach, or None if
+    /// the frequency is out of range (at most `PWM_CLOCK_HZ / 2`, and at least about 6 Hz).
+    pub fn set_frequency(&mut self, timer: usize, freq_hz: u32) -> Option<u32> {
+        let (prescaler, period) = pwm_divisors(PWM_CLOCK_HZ, freq_hz)?;
+        self.write(timer, REG_CONFIG, CONFIG_SAWTOOTH | ((prescaler - 1) << CONFIG_PRESCALER_SHIFT));
+        // the counter runs from the low threshold (bits 15:0) to the high threshold (bits 31:16)
+        self.write(timer, REG_THRESHOLD, (period - 1) << 16);
+        self.periods[timer] = period;
+        for channel in 0..PWM_CHANNELS {
+            self.set_duty(timer, channel, self.duties[timer][channel]);
+        }
+        Some(PWM_CLOCK_HZ / prescaler / period)
+    }
+
+    /// Sets the duty cycle of a channel, in parts per `PWM_DUTY_MAX`. The output is high for the first
+    /// part of each period.
+    pub fn set_duty(&mut self, timer: usize, channel: usize, duty: u32) {
+        assert!(channel < PWM_CHANNELS, "channel out of range");
+        let (action, threshold) = if duty == 0 {
+            (ChannelAction::Clear, 0)
+        } else if duty >= PWM_DUTY_MAX {
+            (ChannelAction::Set, 0)
+        } else {
+            (ChannelAction::ClearSet, self.periods[timer] * duty / PWM_DUTY_MAX)
+        };
+        self.write(
+            timer,
+            REG_TH_CHANNEL0 + channel,
+            ((action as u32) << CHANNEL_ACTION_SHIFT) | (threshold & 0xFFFF),
+        );
+        self.write(timer, REG_CMD, CMD_UPDATE);
+        self.duties[timer][channel] = duty.min(PWM_DUTY_MAX);
+    }
+
+    /// The duty cycle a channel was last set to
+    pub fn duty(&self, timer: usize, channel: usize) -> u32 { self.duties[timer][channel] }
+
+    /// Starts or stops a timer. A stopped timer's outputs hold their level.
+    pub fn enable(&mut self, timer: usize, enable: bool) {
+        assert!(timer < PWM_TIMERS, "timer out of range");
+        let clocks = self.read_block(REG_CLOCK_EN);
+        if enable {
+            self.write_block(REG_CLOCK_EN, clocks | (1 << timer));
+            self.write(timer, REG_CMD, CMD_START);
+        } else {
+            self.write(timer, REG_CMD, CMD_STOP);
+            self.write_block(REG_CLOCK_EN, clocks & !(1 << timer));
+        }
+    }
+}
+
+// safety: the block is only ever touched through `&mut self`
+unsafe impl Send for Pwm {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn divisors() {
+        assert_eq!(pwm_divisors(PWM_CLOCK_HZ, 25_000), Some((1, 4000)));
+        assert_eq!(pwm_divisors(PWM_CLOCK_HZ, 1_000), Some((2, 50_000)));
+        // the fastest, at two counts per period
+        assert_eq!(pwm_divisors(PWM_CLOCK_HZ, PWM_CLOCK_HZ / 2), Some((1, 2)));
+        // the slowest, just within the prescaler
+        assert_eq!(pwm_divisors(PWM_CLOCK_HZ, 6), Some((255, 65_359)));
+    }
+
+    #[test]
+    fn unreachable() {
+        assert_eq!(pwm_divisors(PWM_CLOCK_HZ, 0), None);
+        assert_eq!(pwm_divisors(PWM_CLOCK_HZ, PWM_CLOCK_HZ / 2 + 1), None);
+        assert_eq!(pwm_divisors(PWM_CLOCK_HZ, 5), None);
+    }
+
+    #[test]
+    fn in_range_and_close() {
+        let mut freq_hz = 6;
+        while freq_hz <= 1_000_000 {
+            let (prescaler, period) = pwm_divisors(PWM_CLOCK_HZ, freq_hz).unwrap();
+            assert!((1..=MAX_PRESCALER).contains(&prescaler), "prescaler {} at {} Hz", prescaler, freq_hz);
+            assert!((2..=MAX_PERIOD).contains(&period), "period {} at {} Hz", period, freq_hz);
+            // within 1% of the frequency asked for
+            let counts = prescaler as u64 * period as u64;
+            let error = (PWM_CLOCK_HZ as u64).abs_diff(freq_hz as u64 * counts);
+            assert!(error * 100 <= PWM_CLOCK_HZ as u64, "{} counts for {} Hz", counts, freq_hz);
+            // a smaller prescaler would overflow the period
+            assert!(
+                (prescaler - 1) * MAX_PERIOD < PWM_CLOCK_HZ / freq_hz,
+                "prescaler {} at {} Hz",
+                prescaler,
+                freq_hz
+            );
+            freq_hz = freq_hz * 3 / 2 + 1;
+        }
+    }
+}
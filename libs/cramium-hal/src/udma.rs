@@ -26,7 +26,7 @@ impl Into<usize> for GlobalReg {
     fn into(self) -> usize { self as usize }
 }
 #[repr(u32)]
-#[derive(Copy, Clone, num_derive::FromPrimitive)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, num_derive::FromPrimitive)]
 pub enum PeriphId {
     Uart0 = 1 << 0,
     Uart1 = 1 << 1,
@@ -49,6 +49,31 @@ pub enum PeriphId {
     Spis1 = 1 << 18,
     Adc = 1 << 19,
 }
+impl PeriphId {
+    /// Every peripheral with a clock gate, in the order of their gate bits
+    pub const ALL: [PeriphId; 20] = [
+        PeriphId::Uart0,
+        PeriphId::Uart1,
+        PeriphId::Uart2,
+        PeriphId::Uart3,
+        PeriphId::Spim0,
+        PeriphId::Spim1,
+        PeriphId::Spim2,
+        PeriphId::Spim3,
+        PeriphId::I2c0,
+        PeriphId::I2c1,
+        PeriphId::I2c2,
+        PeriphId::I2c3,
+        PeriphId::Sdio,
+        PeriphId::I2s,
+        PeriphId::Cam,
+        PeriphId::Filter,
+        PeriphId::Scif,
+        PeriphId::Spis0,
+        PeriphId::Spis1,
+        PeriphId::Adc,
+    ];
+}
 impl Into<u32> for PeriphId {
     fn into(self) -> u32 { self as u32 }
 }
@@ -873,8 +898,8 @@ pub enum SpimCmd {
     EndRepeat,
     /// mode, use byte alignment, check type, size of comparison (4 bits), comparison data
     RxCheck(SpimMode, SpimByteAlign, SpimCheckType, u8, u16),
-    /// use byte alignment, size of data
-    FullDuplex(SpimByteAlign, u16),
+    /// words per xfer, bits per word, endianness, number of words to send and receive
+    FullDuplex(SpimWordsPerXfer, u8, SpimEndian, u32),
 }
 impl Into<u32> for SpimCmd {
     fn into(self) -> u32 {
@@ -923,7 +948,13 @@ impl Into<u32> for SpimCmd {
                     | (size as u32 & 0xF) << 16
                     | data as u32
             }
-            SpimCmd::FullDuplex(align, len) => 12 << 28 | (align as u32) << 26 | len as u32,
+            SpimCmd::FullDuplex(words_per_xfer, bits_per_word, endian, len) => {
+                12 << 28
+                    | ((words_per_xfer as u32) & 0x3) << 21
+                    | (bits_per_word as u32 - 1) << 16
+                    | (len as u32 - 1)
+                    | (endian as u32) << 26
+            }
         }
     }
 }
@@ -1349,6 +1380,49 @@ impl Spim {
         }
     }
 
+    /// Full-duplex transfer: clocks out `tx` while clocking the same number of bytes into `rx`, with CS
+    /// asserted for the whole transfer. Transfers longer than the DMA buffers are split into chunks of
+    /// the smaller of the two buffers. Blocks until the transfer is done.
+    ///
+    /// Panics: if `tx` and `rx` are not the same length.
+    pub fn transfer(&mut self, tx: &[u8], rx: &mut [u8]) {
+        assert!(tx.len() == rx.len(), "tx and rx must be the same length");
+        let chunk_len = self.tx_buf_len_bytes.min(self.rx_buf_len_bytes);
+        self.mem_cs(true);
+        for (tx_chunk, rx_chunk) in tx.chunks(chunk_len).zip(rx.chunks_mut(chunk_len)) {
+            let len = tx_chunk.len();
+            self.tx_buf_mut()[..len].copy_from_slice(tx_chunk);
+            self.send_cmd_list(&[SpimCmd::FullDuplex(
+                SpimWordsPerXfer::Words1,
+                8,
+                SpimEndian::MsbFirst,
+                len as u32,
+            )]);
+            // safety: this is safe because the rx_buf_phys() and tx_buf_phys() slices are only used as a
+            // base/bounds reference
+            unsafe {
+                self.udma_enqueue(Bank::Rx, &self.rx_buf_phys::<u8>()[..len], CFG_EN | CFG_SIZE_8);
+                self.udma_enqueue(Bank::Tx, &self.tx_buf_phys::<u8>()[..len], CFG_EN | CFG_SIZE_8);
+            }
+            while self.udma_busy(Bank::Tx) || self.udma_busy(Bank::Rx) {
+                #[cfg(feature = "std")]
+                xous::yield_slice();
+            }
+            rx_chunk.copy_from_slice(&self.rx_buf()[..len]);
+        }
+        self.mem_cs(false);
+    }
+
+    /// Unmaps the controller's registers, for a `Spim` created with `new()` that is about to be dropped.
+    ///
+    /// Safety: the `Spim` must not be used afterwards.
+    #[cfg(feature = "std")]
+    pub unsafe fn unmap_csr(&mut self) {
+        if let Ok(range) = xous::MemoryRange::new(self.csr.base() as usize, 4096) {
+            xous::unmap_memory(range).ok();
+        }
+    }
+
     pub fn rx_data<T: UdmaWidths + Copy>(&mut self, _rx_data: &mut [T], _cs: Option<SpimCs>) {
         todo!("Not yet done...let's see if tx_data works first before templating");
     }
@@ -1720,6 +1720,27 @@ impl CorigineUsb {
 
     pub fn ccs(&self) -> bool { self.csr.rf(PORTSC_CCS) != 0 }
 
+    /// Signals remote wakeup to a suspended host by moving the port link to Resume. Returns `false` if
+    /// the link isn't suspended. The host has to have enabled remote wakeup on the device beforehand.
+    pub fn remote_wakeup(&mut self) -> bool {
+        let portsc = self.csr.r(PORTSC);
+        if !matches!(PortLinkState::from_portsc(portsc), PortLinkState::U3) {
+            return false;
+        }
+        // leave the write-1-to-clear change bits alone, they belong to the event handler
+        let change_bits = self.csr.ms(PORTSC_CSC, 1)
+            | self.csr.ms(PORTSC_PPC, 1)
+            | self.csr.ms(PORTSC_PRC, 1)
+            | self.csr.ms(PORTSC_PLC, 1)
+            | self.csr.ms(PORTSC_CEC, 1);
+        let portsc = portsc & !change_bits & !self.csr.ms(PORTSC_PLS, 0xF);
+        self.csr.wo(
+            PORTSC,
+            portsc | self.csr.ms(PORTSC_PLS, PortLinkState::Resume as u32) | self.csr.ms(PORTSC_LWS, 1),
+        );
+        true
+    }
+
     pub fn print_status(&self, status: u32) {
         let bitflags = [
             (0u32, "CCS"),
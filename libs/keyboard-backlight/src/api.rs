@@ -0,0 +1,26 @@
+/// Name of the keyboard backlight server. The server is run by the status service, which owns the
+/// automatic backlight timer.
+pub const SERVER_NAME_KEYBOARD_BACKLIGHT: &str = "_Keyboard backlight_";
+
+/// Longest automatic backlight timeout that can be set, in seconds
+pub const MAX_TIMEOUT_SECS: u32 = 3600;
+/// Most blinks that can be requested at once
+pub const MAX_BLINKS: u32 = 16;
+/// Shortest on (and off) time of a blink, in milliseconds
+pub const MIN_BLINK_MS: u32 = 50;
+/// Longest on (and off) time of a blink, in milliseconds
+pub const MAX_BLINK_MS: u32 = 2000;
+
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub enum Opcode {
+    /// Light the backlight on each keypress, and turn it off after the timeout (scalar)
+    EnableAuto = 0,
+    /// Stop managing the backlight, and turn it off (scalar)
+    DisableAuto = 1,
+    /// Set the automatic backlight timeout (scalar: seconds)
+    SetTimeout = 2,
+    /// Blink the backlight (scalar: count, on time in ms)
+    Blink = 3,
+    /// Exit the server; only accepted from the status service itself
+    Quit = 255,
+}
@@ -0,0 +1,93 @@
+#![cfg_attr(target_os = "none", no_std)]
+
+//! Client API for the keyboard backlight.
+//!
+//! The backlight itself is driven by the EC, via `com::Com::set_backlight()`, but its automatic
+//! behavior (lighting on a keypress, and going dark after a timeout) is managed by the status service.
+//! This crate lets other services adjust that behavior without hand-rolling messages to either.
+
+pub mod api;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+pub use api::*;
+use num_traits::*;
+use xous::{send_message, Message, CID};
+
+#[derive(Debug)]
+pub struct KeyboardBacklight {
+    conn: CID,
+}
+impl KeyboardBacklight {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns
+            .request_connection_blocking(api::SERVER_NAME_KEYBOARD_BACKLIGHT)
+            .expect("Can't connect to keyboard backlight server");
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(KeyboardBacklight { conn })
+    }
+
+    /// Light the backlight on each keypress, and turn it off again once no key has been hit for the
+    /// timeout. This is the default, unless the user has turned it off in the preferences.
+    pub fn enable_auto(&self) -> Result<(), xous::Error> {
+        send_message(self.conn, Message::new_scalar(Opcode::EnableAuto.to_usize().unwrap(), 0, 0, 0, 0))
+            .map(|_| ())
+    }
+
+    /// Stop lighting the backlight on keypresses. The backlight is turned off, and can then only be
+    /// turned on from the main menu, or with `com::Com::set_backlight()`.
+    pub fn disable_auto(&self) -> Result<(), xous::Error> {
+        send_message(self.conn, Message::new_scalar(Opcode::DisableAuto.to_usize().unwrap(), 0, 0, 0, 0))
+            .map(|_| ())
+    }
+
+    /// Sets how long the backlight stays on after the last keypress, from the next time it is lit. The
+    /// timeout lasts until the user changes the preference, or the device reboots; it is not saved.
+    ///
+    /// # Arguments
+    ///
+    /// * `secs` - the timeout, between 1 and `MAX_TIMEOUT_SECS` seconds
+    pub fn set_timeout(&self, secs: u32) -> Result<(), xous::Error> {
+        if secs == 0 || secs > MAX_TIMEOUT_SECS {
+            return Err(xous::Error::InvalidLimit);
+        }
+        send_message(
+            self.conn,
+            Message::new_scalar(Opcode::SetTimeout.to_usize().unwrap(), secs as usize, 0, 0, 0),
+        )
+        .map(|_| ())
+    }
+
+    /// Blinks the backlight, ie to draw attention to the device. The backlight is left off afterwards,
+    /// and lights again on the next keypress if automatic backlight is enabled. A blink that is still
+    /// going is cut short by the next one.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - the number of blinks, between 1 and `MAX_BLINKS`
+    /// * `on_ms` - how long each blink lasts, and the gap between them, between `MIN_BLINK_MS` and
+    ///   `MAX_BLINK_MS`
+    pub fn blink(&self, count: u32, on_ms: u32) -> Result<(), xous::Error> {
+        if count == 0 || count > MAX_BLINKS || on_ms < MIN_BLINK_MS || on_ms > MAX_BLINK_MS {
+            return Err(xous::Error::InvalidLimit);
+        }
+        send_message(
+            self.conn,
+            Message::new_scalar(Opcode::Blink.to_usize().unwrap(), count as usize, on_ms as usize, 0, 0),
+        )
+        .map(|_| ())
+    }
+}
+
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for KeyboardBacklight {
+    fn drop(&mut self) {
+        // the connection to the server side must be reference counted, so that multiple instances of this
+        // object within a single process do not end up de-allocating the CID on other threads before
+        // they go out of scope.
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+/// Name of the quick settings server. The server is run by the status service, which renders the page.
+pub const SERVER_NAME_QUICK_SETTINGS: &str = "_Quick settings_";
+
+/// Maximum length of the identifier used to refer to a quick setting
+pub const QUICK_SETTING_ID_LEN: usize = 32;
+/// Maximum length of the label shown on the page
+pub const QUICK_SETTING_LABEL_LEN: usize = 64;
+/// Most quick settings that can be registered at once
+pub const MAX_QUICK_SETTINGS: usize = 16;
+
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub enum Opcode {
+    /// Add a setting to the page, or replace the one with the same id if the sender registered it (memory
+    /// message, `QuickSetting`). A refusal is returned as an error code in `valid`.
+    Register = 0,
+    /// Remove a setting the sender registered from the page (memory message, `QuickSettingId`)
+    Unregister = 1,
+    /// Show the page (scalar)
+    Show = 2,
+    /// Exit the server
+    Quit = 255,
+}
+
+/// A toggle on the quick settings page, and where its state lives.
+///
+/// The service that registers it answers two opcodes on the server `sid`:
+///  - `get_opcode`, a blocking scalar, returns 1 if the setting is on and 0 if it is off
+///  - `set_opcode`, a blocking scalar with 1 (on) or 0 (off) in arg1, returns the state the setting ended up
+///    in, which can differ if the change was refused
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct QuickSetting {
+    /// Unique identifier for the setting, e.g. "net.wifi". It belongs to the process that registered it
+    /// until that process exits.
+    pub id: xous_ipc::String<QUICK_SETTING_ID_LEN>,
+    /// What the user sees, in the current language. No two settings on the page may share a label.
+    pub label: xous_ipc::String<QUICK_SETTING_LABEL_LEN>,
+    pub sid: [u32; 4],
+    pub get_opcode: u32,
+    pub set_opcode: u32,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct QuickSettingId {
+    pub id: xous_ipc::String<QUICK_SETTING_ID_LEN>,
+}
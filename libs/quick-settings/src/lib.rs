@@ -0,0 +1,102 @@
+#![cfg_attr(target_os = "none", no_std)]
+
+//! Client API for the quick settings page.
+//!
+//! The page lists on/off settings owned by different services (the automatic backlight, Wi-Fi, airplane
+//! mode...) and is rendered by the status service. A service puts a setting on the page by registering
+//! it along with the opcodes that read and change it; the page then always shows the current state, as
+//! reported by the service itself.
+
+pub mod api;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+pub use api::*;
+use num_traits::*;
+use xous::{CID, Message, send_message};
+use xous_ipc::Buffer;
+
+#[derive(Debug)]
+pub struct QuickSettings {
+    conn: CID,
+}
+impl QuickSettings {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns
+            .request_connection_blocking(api::SERVER_NAME_QUICK_SETTINGS)
+            .expect("Can't connect to quick settings server");
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(QuickSettings { conn })
+    }
+
+    /// Puts a setting on the page, replacing any this process previously registered with the same `id`.
+    /// See `QuickSetting` for what the server `sid` has to answer to `get_opcode` and `set_opcode`.
+    ///
+    /// Fails with `AccessDenied` if another process has a setting with this `id`, with `ServerExists` if
+    /// another setting already has this `label`, and with `OutOfMemory` if the page is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - a unique identifier, up to `QUICK_SETTING_ID_LEN` bytes, e.g. "net.wifi"
+    /// * `label` - the name of the setting on the page, up to `QUICK_SETTING_LABEL_LEN` bytes
+    /// * `sid` - the server that owns the setting
+    pub fn register(
+        &self,
+        id: &str,
+        label: &str,
+        sid: xous::SID,
+        get_opcode: u32,
+        set_opcode: u32,
+    ) -> Result<(), xous::Error> {
+        if id.len() > QUICK_SETTING_ID_LEN || label.len() > QUICK_SETTING_LABEL_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        let setting = QuickSetting {
+            id: xous_ipc::String::from_str(id),
+            label: xous_ipc::String::from_str(label),
+            sid: sid.to_array(),
+            get_opcode,
+            set_opcode,
+        };
+        let buf = Buffer::into_buf(setting).or(Err(xous::Error::InternalError))?;
+        outcome(buf.lend(self.conn, Opcode::Register.to_u32().unwrap())?)
+    }
+
+    /// Removes the setting identified by `id` from the page. Removing a setting that is not there is not
+    /// an error; removing one that another process registered fails with `AccessDenied`.
+    pub fn unregister(&self, id: &str) -> Result<(), xous::Error> {
+        if id.len() > QUICK_SETTING_ID_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        let setting_id = QuickSettingId { id: xous_ipc::String::from_str(id) };
+        let buf = Buffer::into_buf(setting_id).or(Err(xous::Error::InternalError))?;
+        outcome(buf.lend(self.conn, Opcode::Unregister.to_u32().unwrap())?)
+    }
+
+    /// Shows the page, as the main menu does.
+    pub fn show(&self) -> Result<(), xous::Error> {
+        send_message(self.conn, Message::new_scalar(Opcode::Show.to_usize().unwrap(), 0, 0, 0, 0)).map(|_| ())
+    }
+}
+
+/// The server hands back the region with the error code in `valid` if it refused the request.
+fn outcome(result: xous::Result) -> Result<(), xous::Error> {
+    match result {
+        xous::Result::MemoryReturned(_, None) => Ok(()),
+        xous::Result::MemoryReturned(_, Some(code)) => Err(xous::Error::from_usize(code.get())),
+        _ => Err(xous::Error::InternalError),
+    }
+}
+
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for QuickSettings {
+    fn drop(&mut self) {
+        // the connection to the server side must be reference counted, so that multiple instances of this
+        // object within a single process do not end up de-allocating the CID on other threads before
+        // they go out of scope.
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rustls::pki_types::TrustAnchor;
+use tls::ota::OwnedTrustAnchor;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+fuzz_target!(|data: &[u8]| {
+    // a certificate offered by a host, as it is saved to the trust store
+    if let Ok((_, x509)) = X509Certificate::from_der(data) {
+        if let Ok(ota) = OwnedTrustAnchor::from_x509(&x509) {
+            let _ = ota.subject();
+            let _ = ota.pddb_key();
+            let _: TrustAnchor = ota.into();
+        }
+    }
+    // a trust anchor read back from the store, or received from another device, with arbitrary fields
+    if data.len() >= 2 {
+        let split = (data[0] as usize).min(data.len() - 1);
+        let (subject, spki) = data[1..].split_at(split);
+        let ota = OwnedTrustAnchor::from_subject_spki_name_constraints(subject, spki, None::<&[u8]>);
+        let _ = ota.subject();
+        let _ = ota.pddb_key();
+    }
+});
@@ -0,0 +1,183 @@
+//! Where certificate validity checks get the time from.
+//!
+//! rustls asks the system clock, which on a device is the RTC. A `Tls` can be given a `Clock` to use
+//! instead, so that tests can pin the time against fixtures with fixed validity dates (see `testing`).
+
+use std::fmt;
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, Error, RootCertStore, SignatureScheme};
+
+/// A source of the current time, as seconds since the unix epoch
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> UnixTime;
+}
+
+/// The system clock, as rustls uses by default
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> UnixTime { UnixTime::now() }
+}
+
+/// The rustls WebPkiServerVerifier, checking validity dates against a `Clock` rather than the time
+/// that rustls passes in
+#[derive(Debug)]
+pub struct ClockedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ClockedVerifier {
+    /// # Arguments
+    ///
+    /// * `roots` - the trusted CA certificates
+    /// * `clock` - the time source for validity checks
+    ///
+    /// # Returns
+    ///
+    /// * the verifier, or an Error if `roots` is empty
+    pub fn new(roots: RootCertStore, clock: Arc<dyn Clock>) -> Result<ClockedVerifier, Error> {
+        match WebPkiServerVerifier::builder(Arc::new(roots)).build() {
+            Ok(inner) => Ok(ClockedVerifier { inner, clock }),
+            Err(e) => {
+                log::warn!("failed to build WebPkiServerVerifier: {e}");
+                Err(Error::General("failed to build WebPkiServerVerifier".to_string()))
+            }
+        }
+    }
+
+    /// Verifies a certificate chain as the handshake would, without a connection: that it chains to one
+    /// of the roots, that every certificate is valid at the clock's time, and that the end-entity is
+    /// valid for `host`.
+    ///
+    /// # Arguments
+    ///
+    /// * `chain` - the certificates as a host offers them, end-entity first
+    /// * `host` - the host name, as typed (it is passed through `name::server_name()`)
+    pub fn verify_chain(&self, chain: &[CertificateDer], host: &str) -> Result<(), Error> {
+        let server_name = crate::name::server_name(host)
+            .map_err(|_| Error::General(format!("invalid host name {host}")))?;
+        let (end_entity, intermediates) = chain.split_first().ok_or(Error::NoCertificatesPresented)?;
+        self.verify_server_cert(end_entity, intermediates, &server_name, &[], self.clock.now()).map(|_| ())
+    }
+}
+
+impl ServerCertVerifier for ClockedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer,
+        intermediates: &[CertificateDer],
+        server_name: &ServerName,
+        ocsp: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp, self.clock.now())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> { self.inner.supported_verify_schemes() }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustls::CertificateError;
+
+    use super::*;
+    use crate::testing::{self, Chain, MockClock};
+
+    fn verify(chain: &Chain, host: &str, clock: &Arc<MockClock>) -> Result<(), Error> {
+        ClockedVerifier::new(chain.roots(), clock.clone()).unwrap().verify_chain(&chain.offered(), host)
+    }
+
+    fn invalid(e: CertificateError) -> Result<(), Error> { Err(Error::InvalidCertificate(e)) }
+
+    #[test]
+    fn valid_chain() {
+        let clock = Arc::new(MockClock::default());
+        assert_eq!(verify(&testing::valid(), testing::VALID_HOST, &clock), Ok(()));
+    }
+
+    #[test]
+    fn wrong_host() {
+        let clock = Arc::new(MockClock::default());
+        assert_eq!(
+            verify(&testing::valid(), "other.example", &clock),
+            invalid(CertificateError::NotValidForName)
+        );
+        assert_eq!(
+            verify(&testing::valid(), testing::EXPIRED_HOST, &clock),
+            invalid(CertificateError::NotValidForName)
+        );
+    }
+
+    #[test]
+    fn missing_intermediate() {
+        let clock = Arc::new(MockClock::default());
+        let chain = testing::valid();
+        let verifier = ClockedVerifier::new(chain.roots(), clock).unwrap();
+        assert_eq!(
+            verifier.verify_chain(&[chain.leaf.clone()], testing::VALID_HOST),
+            invalid(CertificateError::UnknownIssuer)
+        );
+        assert_eq!(verifier.verify_chain(&[], testing::VALID_HOST), Err(Error::NoCertificatesPresented));
+    }
+
+    #[test]
+    fn expiry_follows_the_clock() {
+        let clock = Arc::new(MockClock::default());
+        let chain = testing::expired();
+        assert_eq!(verify(&chain, testing::EXPIRED_HOST, &clock), invalid(CertificateError::Expired));
+        clock.set(testing::EXPIRED_UNTIL - 24 * 60 * 60);
+        assert_eq!(verify(&chain, testing::EXPIRED_HOST, &clock), Ok(()));
+    }
+
+    #[test]
+    fn not_yet_valid_follows_the_clock() {
+        let clock = Arc::new(MockClock::default());
+        let chain = testing::future();
+        assert_eq!(verify(&chain, testing::FUTURE_HOST, &clock), invalid(CertificateError::NotValidYet));
+        clock.set(testing::FUTURE_FROM);
+        assert_eq!(verify(&chain, testing::FUTURE_HOST, &clock), Ok(()));
+        clock.advance(10 * 365 * 24 * 60 * 60);
+        assert_eq!(verify(&chain, testing::FUTURE_HOST, &clock), invalid(CertificateError::Expired));
+    }
+
+    #[test]
+    fn name_constraints() {
+        let clock = Arc::new(MockClock::default());
+        assert_eq!(
+            verify(&testing::constrained_allowed(), testing::CONSTRAINED_ALLOWED_HOST, &clock),
+            Ok(())
+        );
+        assert!(verify(&testing::constrained_denied(), testing::CONSTRAINED_DENIED_HOST, &clock).is_err());
+    }
+
+    #[test]
+    fn chain_length() {
+        let clock = Arc::new(MockClock::default());
+        assert_eq!(verify(&testing::long(), testing::LONG_HOST, &clock), Ok(()));
+        assert!(verify(&testing::too_long(), testing::TOO_LONG_HOST, &clock).is_err());
+    }
+}
@@ -1,12 +1,11 @@
-use std::convert::TryFrom;
 use std::io::Read;
 use std::io::Write;
 use std::net::TcpStream;
 use std::str::from_utf8;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use locales::t;
-use rustls::pki_types::ServerName;
 #[cfg(feature = "rootCA")]
 use {modals::Modals, std::convert::TryInto, xous_names::XousNames};
 
@@ -88,13 +87,14 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
                 Some(target) => target,
                 None => "bunnyfoo.com",
             };
+            let target = &crate::name::to_ascii(target).unwrap_or(target.to_string());
             log::info!("point TLS to {}", target);
             log::info!("connect TCPstream to {}", target);
-            match TcpStream::connect((target, 443)) {
+            match TcpStream::connect((target.as_str(), 443)) {
                 Ok(mut sock) => {
                     log::info!("tcp connected");
                     write!(ret, "{}", t!("tls.test_success_tcp", locales::LANG)).ok();
-                    match ServerName::try_from(target.to_owned()) {
+                    match crate::name::server_name(target) {
                         Ok(server_name) => {
                             match rustls::ClientConnection::new(Arc::new(config), server_name) {
                                 Ok(mut conn) => {
@@ -153,6 +153,95 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
 
             log::set_max_level(log::LevelFilter::Info);
         }
+        // bench times each phase of a number of tls connections to the supplied host, to help tell
+        // whether slowness is down to the network, or to the cost of the tls handshake.
+        Some("bench") => {
+            let target = match tokens.next() {
+                Some(target) => target,
+                None => "betrusted.io",
+            };
+            let count =
+                tokens.next().and_then(|c| c.parse::<u32>().ok()).unwrap_or(3).clamp(1, MAX_BENCH_COUNT);
+            let tls = Tls::new();
+            let config = Arc::new(
+                rustls::ClientConfig::builder()
+                    .with_root_certificates(tls.root_store())
+                    .with_no_client_auth(),
+            );
+            let mut runs: Vec<[Duration; 3]> = Vec::new();
+            for _ in 0..count {
+                match bench_connection(target, config.clone()) {
+                    Ok(timing) => runs.push(timing),
+                    Err(e) => {
+                        log::warn!("tls bench connection to {target} failed: {e}");
+                        write!(ret, "{e}\n").ok();
+                    }
+                }
+            }
+            write!(ret, "{target}: {}/{count} ok\n", runs.len()).ok();
+            let phases = [
+                t!("tls.bench_tcp", locales::LANG),
+                t!("tls.bench_handshake", locales::LANG),
+                t!("tls.bench_response", locales::LANG),
+            ];
+            for (i, phase) in phases.iter().enumerate() {
+                if let (Some(min), Some(max)) =
+                    (runs.iter().map(|r| r[i]).min(), runs.iter().map(|r| r[i]).max())
+                {
+                    let avg = runs.iter().map(|r| r[i]).sum::<Duration>() / runs.len() as u32;
+                    write!(
+                        ret,
+                        "{phase}: min/avg/max {}/{}/{} ms\n",
+                        min.as_millis(),
+                        avg.as_millis(),
+                        max.as_millis()
+                    )
+                    .ok();
+                } else {
+                    write!(ret, "{phase}: n/a\n").ok();
+                }
+            }
+        }
+        // diag makes a single tls connection to the supplied host, and reports how long each phase took,
+        // what was negotiated, and if it failed, why.
+        Some("diag") => {
+            let target = match tokens.next() {
+                Some(target) => target,
+                None => "betrusted.io",
+            };
+            let options: Vec<&str> = tokens.collect();
+            let port = options.iter().find_map(|p| p.parse::<u16>().ok()).unwrap_or(443);
+            let post_quantum = options.contains(&"pq");
+            let (stream, diag) = Tls::new().with_post_quantum(post_quantum).connect(target, port);
+            if let Ok(mut stream) = stream {
+                stream.conn.send_close_notify();
+                stream.conn.complete_io(&mut stream.sock).ok();
+            }
+            write!(ret, "{diag}").ok();
+        }
+        // share trusted CA Certificates with another Precursor
+        Some("sync") => {
+            log::set_max_level(log::LevelFilter::Info);
+            let tls = Tls::new();
+            let result = match (tokens.next(), tokens.next(), tokens.next()) {
+                (Some("host"), _, _) => Some(tls.sync_host()),
+                (Some("join"), Some(host), Some(code)) => Some(tls.sync_join(host, code)),
+                _ => None,
+            };
+            match result {
+                Some(Ok((offered, saved))) => write!(
+                    ret,
+                    "{} {}\n{} {}",
+                    offered,
+                    t!("tls.sync_offered", locales::LANG),
+                    saved,
+                    t!("tls.sync_done", locales::LANG)
+                )
+                .ok(),
+                Some(Err(e)) => write!(ret, "{} {e}", t!("tls.sync_fail", locales::LANG)).ok(),
+                None => write!(ret, "net tls sync host\nnet tls sync join <host> <code>").ok(),
+            };
+        }
         None | _ => {
             write!(ret, "{}\n", t!("tls.cmd", locales::LANG)).ok();
             write!(ret, "\tdeleteall\t{}\n", t!("tls.deleteall_cmd", locales::LANG)).ok();
@@ -162,7 +251,41 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
             write!(ret, "\tmozilla\t{}\n", t!("tls.mozilla_cmd", locales::LANG)).ok();
             write!(ret, "\tinspect <host>\t{}\n", t!("tls.inspect_cmd", locales::LANG)).ok();
             write!(ret, "\ttest <host>\t{}\n", t!("tls.test_cmd", locales::LANG)).ok();
+            write!(ret, "\tbench <host> [count]\t{}\n", t!("tls.bench_cmd", locales::LANG)).ok();
+            write!(ret, "\tdiag <host> [port] [pq]\t{}\n", t!("tls.diag_cmd", locales::LANG)).ok();
+            write!(ret, "\tsync host | join <host> <code>\t{}\n", t!("tls.sync_cmd", locales::LANG)).ok();
         }
     }
     Ok(Some(ret))
 }
+
+/// Most connections `bench` makes; larger counts are cut down to this, so that a typo can't tie up the
+/// shell for hours
+const MAX_BENCH_COUNT: u32 = 20;
+
+/// Makes a single tls connection to port 443 of `target`, and returns the time taken to establish
+/// the tcp connection (including name resolution), to complete the tls handshake, and to receive
+/// the first byte of the response to a `HEAD` request.
+fn bench_connection(target: &str, config: Arc<rustls::ClientConfig>) -> Result<[Duration; 3], String> {
+    let target = &crate::name::to_ascii(target).map_err(|e| e.to_string())?;
+    let start = Instant::now();
+    let mut sock = TcpStream::connect((target.as_str(), 443)).map_err(|e| e.to_string())?;
+    let tcp = start.elapsed();
+
+    let server_name = crate::name::server_name(target).map_err(|e| e.to_string())?;
+    let mut conn = rustls::ClientConnection::new(config, server_name).map_err(|e| e.to_string())?;
+    let start = Instant::now();
+    while conn.is_handshaking() {
+        conn.complete_io(&mut sock).map_err(|e| e.to_string())?;
+    }
+    let handshake = start.elapsed();
+
+    let start = Instant::now();
+    let mut tls = rustls::Stream::new(&mut conn, &mut sock);
+    tls.write_all(format!("HEAD / HTTP/1.1\r\nHost: {target}\r\nConnection: close\r\n\r\n").as_bytes())
+        .map_err(|e| e.to_string())?;
+    let mut first = [0u8; 1];
+    tls.read_exact(&mut first).map_err(|e| e.to_string())?;
+    let response = start.elapsed();
+    Ok([tcp, handshake, response])
+}
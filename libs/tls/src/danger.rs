@@ -55,19 +55,18 @@ impl ServerCertVerifier for StifledCertificateVerification {
     ) -> Result<ServerCertVerified, rustls::Error> {
         match WebPkiServerVerifier::builder(Arc::new(self.roots.clone())).build() {
             Ok(rustls_default_verifier) => {
-                match rustls_default_verifier.verify_server_cert(
+                let result = rustls_default_verifier.verify_server_cert(
                     end_entity,
                     intermediates,
                     server_name,
                     ocsp,
                     now,
-                ) {
-                    Ok(ok) => Ok(ok),
+                );
+                match stifle(result, end_entity, server_name) {
                     Err(Error::InvalidCertificate(e)) => {
                         let xns = XousNames::new().unwrap();
                         let modals = Modals::new(&xns).unwrap();
                         match e {
-                            CertificateError::UnknownIssuer => Ok(ServerCertVerified::assertion()),
                             CertificateError::NotValidYet => {
                                 modals
                                     .show_notification(
@@ -94,7 +93,7 @@ impl ServerCertVerifier for StifledCertificateVerification {
                             }
                         }
                     }
-                    Err(e) => Err(e),
+                    other => other,
                 }
             }
             Err(e) => {
@@ -124,3 +123,66 @@ impl ServerCertVerifier for StifledCertificateVerification {
 
     fn supported_verify_schemes(&self) -> Vec<SignatureScheme> { self.supported.supported_schemes() }
 }
+
+/// Lets a chain through if the only fault found by the default verifier is an unknown issuer. webpki
+/// checks the chain before the name, so an unknown issuer hides a certificate for the wrong host: the
+/// name is checked before letting it through.
+fn stifle(
+    result: Result<ServerCertVerified, Error>,
+    end_entity: &CertificateDer,
+    server_name: &ServerName,
+) -> Result<ServerCertVerified, Error> {
+    match result {
+        Err(Error::InvalidCertificate(CertificateError::UnknownIssuer)) => {
+            match crate::name::verify(end_entity, server_name) {
+                Ok(()) => Ok(ServerCertVerified::assertion()),
+                Err(e) => Err(Error::InvalidCertificate(e)),
+            }
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Chain};
+
+    /// What the probe makes of a chain, without the modals
+    fn probe(roots: RootCertStore, chain: &Chain, host: &str) -> Result<ServerCertVerified, Error> {
+        let verifier = WebPkiServerVerifier::builder(Arc::new(roots)).build().unwrap();
+        let server_name = crate::name::server_name(host).unwrap();
+        let result = verifier.verify_server_cert(
+            &chain.leaf,
+            &chain.intermediates,
+            &server_name,
+            &[],
+            UnixTime::since_unix_epoch(std::time::Duration::from_secs(testing::FIXTURE_NOW)),
+        );
+        stifle(result, &chain.leaf, &server_name)
+    }
+
+    #[test]
+    fn untrusted_chain_is_let_through() {
+        let untrusted = StifledCertificateVerification::new().roots;
+        assert!(probe(untrusted, &testing::valid(), testing::VALID_HOST).is_ok());
+    }
+
+    #[test]
+    fn untrusted_chain_for_another_host_is_not() {
+        let untrusted = StifledCertificateVerification::new().roots;
+        assert!(matches!(
+            probe(untrusted, &testing::valid(), "other.example"),
+            Err(Error::InvalidCertificate(CertificateError::NotValidForName))
+        ));
+    }
+
+    #[test]
+    fn other_faults_are_not_stifled() {
+        let chain = testing::expired();
+        assert!(matches!(
+            probe(chain.roots(), &chain, testing::EXPIRED_HOST),
+            Err(Error::InvalidCertificate(CertificateError::Expired))
+        ));
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::time::Duration;
+
+use locales::t;
+use rustls::{CertificateError, ClientConnection};
+
+use crate::pq::PostQuantum;
+
+/// Why a tls connection could not be established, in terms a user can act on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// the host is not a valid tls server name
+    InvalidName,
+    /// the host name could not be resolved
+    Dns,
+    /// the host refused the tcp connection, or could not be reached
+    Refused,
+    /// the tcp connection or the handshake timed out
+    Timeout,
+    /// the host closed the connection during the handshake
+    Closed,
+    /// none of the certificates offered chain to a trusted CA
+    Untrusted,
+    /// the host certificate has expired
+    Expired,
+    /// the host certificate is not valid yet, which usually means the clock is wrong
+    NotYetValid,
+    /// the host certificate is not valid for the host name
+    WrongHost,
+    /// the host certificate was rejected for another reason
+    BadCertificate(String),
+    /// the host aborted the handshake with a tls alert
+    Alert(String),
+    /// the host and client have no protocol version or cipher suite in common
+    Incompatible,
+    /// anything else
+    Other(String),
+}
+
+impl Failure {
+    /// Classifies an error from connecting or from driving a handshake. rustls errors arrive wrapped
+    /// in an `io::Error` of kind `InvalidData`.
+    pub fn from_io(e: &Error) -> Failure {
+        if let Some(e) = e.get_ref().and_then(|inner| inner.downcast_ref::<rustls::Error>()) {
+            return Failure::from(e);
+        }
+        match e.kind() {
+            ErrorKind::ConnectionRefused | ErrorKind::AddrNotAvailable => Failure::Refused,
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => Failure::Timeout,
+            ErrorKind::UnexpectedEof | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => {
+                Failure::Closed
+            }
+            _ => Failure::Other(e.to_string()),
+        }
+    }
+}
+
+impl From<&rustls::Error> for Failure {
+    fn from(e: &rustls::Error) -> Failure {
+        match e {
+            rustls::Error::InvalidCertificate(cert) => match cert {
+                CertificateError::UnknownIssuer => Failure::Untrusted,
+                CertificateError::Expired => Failure::Expired,
+                CertificateError::NotValidYet => Failure::NotYetValid,
+                CertificateError::NotValidForName => Failure::WrongHost,
+                other => Failure::BadCertificate(format!("{:?}", other)),
+            },
+            rustls::Error::NoCertificatesPresented => Failure::BadCertificate(e.to_string()),
+            rustls::Error::AlertReceived(alert) => Failure::Alert(format!("{:?}", alert)),
+            rustls::Error::PeerIncompatible(_) => Failure::Incompatible,
+            other => Failure::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::InvalidName => write!(f, "{}", t!("tls.fail_name", locales::LANG)),
+            Failure::Dns => write!(f, "{}", t!("tls.fail_dns", locales::LANG)),
+            Failure::Refused => write!(f, "{}", t!("tls.fail_refused", locales::LANG)),
+            Failure::Timeout => write!(f, "{}", t!("tls.fail_timeout", locales::LANG)),
+            Failure::Closed => write!(f, "{}", t!("tls.fail_closed", locales::LANG)),
+            Failure::Untrusted => write!(f, "{}", t!("tls.fail_untrusted", locales::LANG)),
+            Failure::Expired => write!(f, "{}", t!("tls.fail_expired", locales::LANG)),
+            Failure::NotYetValid => write!(
+                f,
+                "{} {}",
+                t!("tls.fail_not_yet_valid", locales::LANG),
+                t!("tls.probe_help_not_valid_yet", locales::LANG)
+            ),
+            Failure::WrongHost => write!(f, "{}", t!("tls.fail_wrong_host", locales::LANG)),
+            Failure::BadCertificate(why) => {
+                write!(f, "{}{}", t!("tls.probe_invalid_certificate", locales::LANG), why)
+            }
+            Failure::Alert(alert) => write!(f, "{} {}", t!("tls.fail_alert", locales::LANG), alert),
+            Failure::Incompatible => write!(f, "{}", t!("tls.fail_incompatible", locales::LANG)),
+            Failure::Other(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+/// What happened while establishing a tls connection: how long each phase took, what was negotiated,
+/// and, if it failed, why.
+///
+/// A phase that was not reached is left as `None`.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    pub host: String,
+    pub port: u16,
+    /// time to resolve the host name
+    pub dns: Option<Duration>,
+    /// time to establish the tcp connection
+    pub tcp: Option<Duration>,
+    /// time to complete the tls handshake
+    pub handshake: Option<Duration>,
+    /// the negotiated protocol version (ie "TLSv1_3")
+    pub version: Option<String>,
+    /// the negotiated cipher suite (ie "TLS13_AES_256_GCM_SHA384")
+    pub cipher: Option<String>,
+    /// the number of certificates the host offered, once they have been verified
+    pub chain_len: usize,
+    /// what came of asking for a post-quantum key exchange, if the connection asked
+    pub post_quantum: PostQuantum,
+    /// true if the host name was hidden by Encrypted Client Hello. It is never set yet: rustls 0.22 has
+    /// no ECH, so the host name goes out in the clear, where anyone on the network can read it.
+    pub ech: bool,
+    /// true if an open connection was reused from a `pool::Pool`, so that no phase was needed
+    pub reused: bool,
+    pub failure: Option<Failure>,
+}
+
+impl Diagnostics {
+    pub fn new(host: &str, port: u16) -> Diagnostics {
+        Diagnostics { host: host.to_string(), port, ..Default::default() }
+    }
+
+    /// Records what was negotiated on a connection that has completed its handshake
+    pub fn negotiated(&mut self, conn: &ClientConnection) { self.session(conn); }
+
+    /// Records what was negotiated on a connection reused from a pool
+    pub fn reused_connection(&mut self, conn: &ClientConnection) {
+        self.reused = true;
+        self.session(conn);
+    }
+
+    fn session(&mut self, conn: &ClientConnection) {
+        self.version = conn.protocol_version().map(|v| format!("{:?}", v));
+        self.cipher = conn.negotiated_cipher_suite().map(|cs| format!("{:?}", cs.suite()));
+        self.chain_len = conn.peer_certificates().map(|certs| certs.len()).unwrap_or(0);
+    }
+
+    /// Records the failure, and passes the error on
+    pub fn fail(&mut self, e: Error) -> Error {
+        self.failure = Some(Failure::from_io(&e));
+        e
+    }
+
+    pub fn ok(&self) -> bool { self.failure.is_none() }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:{}", self.host, self.port)?;
+        let phases = [
+            (t!("tls.diag_dns", locales::LANG), self.dns),
+            (t!("tls.bench_tcp", locales::LANG), self.tcp),
+            (t!("tls.bench_handshake", locales::LANG), self.handshake),
+        ];
+        for (phase, time) in phases.iter() {
+            if let Some(time) = time {
+                writeln!(f, "{}: {} ms", phase, time.as_millis())?;
+            }
+        }
+        if self.reused {
+            writeln!(f, "{}", t!("tls.diag_reused", locales::LANG))?;
+        }
+        if let (Some(version), Some(cipher)) = (&self.version, &self.cipher) {
+            writeln!(f, "{} {}", version, cipher)?;
+            let post_quantum = match self.post_quantum {
+                PostQuantum::NotRequested => None,
+                PostQuantum::Unsupported => Some(t!("tls.diag_pq_unsupported", locales::LANG)),
+                PostQuantum::Offered => Some(t!("tls.diag_pq_offered", locales::LANG)),
+            };
+            if let Some(post_quantum) = post_quantum {
+                writeln!(f, "{}: {}", t!("tls.diag_pq", locales::LANG), post_quantum)?;
+            }
+            if !self.ech {
+                writeln!(f, "{}", t!("tls.diag_no_ech", locales::LANG))?;
+            }
+            writeln!(f, "{}: {}", t!("tls.diag_chain", locales::LANG), self.chain_len)?;
+        }
+        match &self.failure {
+            Some(failure) => write!(f, "{}", failure),
+            None => write!(f, "{}", t!("tls.diag_ok", locales::LANG)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::clock::ClockedVerifier;
+    use crate::testing::{self, Chain, MockClock};
+
+    fn failure(chain: &Chain, host: &str) -> Option<Failure> {
+        let verifier = ClockedVerifier::new(chain.roots(), Arc::new(MockClock::default())).unwrap();
+        let e = verifier.verify_chain(&chain.offered(), host).err()?;
+        // as the handshake reports it
+        Some(Failure::from_io(&Error::new(ErrorKind::InvalidData, e)))
+    }
+
+    #[test]
+    fn certificate_failures() {
+        assert_eq!(failure(&testing::valid(), testing::VALID_HOST), None);
+        assert_eq!(failure(&testing::valid(), "other.example"), Some(Failure::WrongHost));
+        assert_eq!(failure(&testing::expired(), testing::EXPIRED_HOST), Some(Failure::Expired));
+        assert_eq!(failure(&testing::future(), testing::FUTURE_HOST), Some(Failure::NotYetValid));
+        assert!(matches!(
+            failure(&testing::constrained_denied(), testing::CONSTRAINED_DENIED_HOST),
+            Some(Failure::BadCertificate(_))
+        ));
+        let mut chain = testing::valid();
+        chain.intermediates.clear();
+        assert_eq!(failure(&chain, testing::VALID_HOST), Some(Failure::Untrusted));
+    }
+}
@@ -1,13 +1,23 @@
+pub mod clock;
 pub mod cmd;
 mod danger;
+pub mod diag;
+pub mod name;
 pub mod ota;
+pub mod pool;
+pub mod pq;
+pub mod sync;
+#[cfg(any(test, feature = "tls-test"))]
+pub mod testing;
 pub mod xtls;
 
 use std::convert::{Into, TryFrom, TryInto};
 use std::io::{Error, ErrorKind, Read, Write};
-use std::net::TcpStream;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use diag::{Diagnostics, Failure};
 use locales::t;
 use modals::Modals;
 use ota::OwnedTrustAnchor;
@@ -16,7 +26,7 @@ use rkyv::{
     ser::{serializers::WriteSerializer, Serializer},
     Deserialize,
 };
-use rustls::pki_types::{CertificateDer, TrustAnchor};
+use rustls::pki_types::{CertificateDer, ServerName, TrustAnchor};
 use rustls::{ClientConfig, ClientConnection, RootCertStore};
 use x509_parser::prelude::{parse_x509_certificate, FromDer, X509Certificate};
 use xous_names::XousNames;
@@ -26,10 +36,30 @@ const TLS_TRUSTED_DICT: &str = "tls.trusted";
 
 pub struct Tls {
     pddb: pddb::Pddb,
+    clock: Option<Arc<dyn clock::Clock>>,
+    post_quantum: bool,
 }
 
 impl Tls {
-    pub fn new() -> Tls { Tls { pddb: pddb::Pddb::new() } }
+    pub fn new() -> Tls { Tls { pddb: pddb::Pddb::new(), clock: None, post_quantum: false } }
+
+    /// Checks certificate validity dates against `clock` rather than the system clock, on the
+    /// connections made by `client_config()`, `stream_owned()` and `connect()`.
+    pub fn with_clock(mut self, clock: Arc<dyn clock::Clock>) -> Tls {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Opts in to a post-quantum hybrid key exchange on the connections made by `client_config()`,
+    /// `stream_owned()` and `connect()`, when the crypto provider supports one (see `pq`).
+    /// `Diagnostics::post_quantum` tells how the request went.
+    pub fn with_post_quantum(mut self, post_quantum: bool) -> Tls {
+        self.post_quantum = post_quantum;
+        self
+    }
+
+    /// What comes of the post-quantum request on this `Tls`'s connections
+    pub fn post_quantum(&self) -> pq::PostQuantum { pq::PostQuantum::of_request(self.post_quantum) }
 
     /// Presents a modal to the user to select trusted tls certificates
     /// and saves the selected certificates to the pddb
@@ -304,6 +334,51 @@ impl Tls {
         root_store
     }
 
+    /// Returns a RootCertStore containing only the pinned TrustAnchors
+    ///
+    /// Pins that are not (or are no longer) saved in the pddb are skipped, so an
+    /// empty store is returned if none of the pins are trusted.
+    ///
+    /// # Arguments
+    ///
+    /// * `pins` - pddb-keys of the trust-anchors to be pinned
+    ///
+    /// # Returns
+    ///
+    /// a RootCertStore suitable for rustls
+    pub fn pinned_root_store(&self, pins: &[String]) -> RootCertStore {
+        let mut root_store = RootCertStore::empty();
+        let pinned = pins
+            .iter()
+            .map(|key| self.get_ota(&key))
+            .filter_map(|ota| ota)
+            .map(|ota| ota.into())
+            .collect::<Vec<TrustAnchor>>();
+        root_store.extend(pinned);
+        root_store
+    }
+
+    /// Returns the pddb-keys of the trusted trust-anchors offered by the host
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the target tls site (i.e. betrusted.io)
+    ///
+    /// # Returns
+    ///
+    /// * A Vec of pddb-keys suitable for `pinned_root_store()`
+    /// * Error if the communication with the host fails
+    pub fn pins(&self, host: &str) -> Result<Vec<String>, Error> {
+        let certs = self.probe(host)?;
+        Ok(certs
+            .iter()
+            .filter(|&cert| self.is_trusted_cert(cert.clone()))
+            .filter_map(|cert| X509Certificate::from_der(cert).ok())
+            .filter_map(|(_, x509)| OwnedTrustAnchor::from_x509(&x509).ok())
+            .filter_map(|ta| ta.pddb_key().ok())
+            .collect())
+    }
+
     /// Probes the host and returns the TLS chain of trust for a host
     ///
     /// Establishes a tls connection to the host, extracts the
@@ -324,6 +399,7 @@ impl Tls {
     /// * Error if the communication with the host fails
     pub fn probe(&self, host: &str) -> Result<Vec<CertificateDer>, Error> {
         log::info!("starting TLS probe");
+        let host = &name::to_ascii(host)?;
         match host.to_owned().try_into() {
             Ok(server_name) => {
                 // Stifle the default rustls certificate verification's complaint about an
@@ -335,7 +411,7 @@ impl Tls {
                 match rustls::ClientConnection::new(Arc::new(config), server_name) {
                     Ok(mut conn) => {
                         log::info!("connect TCPstream to {}", host);
-                        match TcpStream::connect((host, 443)) {
+                        match TcpStream::connect((host.as_str(), 443)) {
                             Ok(mut sock) => match conn.complete_io(&mut sock) {
                                 Ok(_) => log::info!("handshake complete"),
                                 Err(e) => log::warn!("{e}"),
@@ -421,7 +497,23 @@ impl Tls {
     }
 
     pub fn client_config(&self) -> ClientConfig {
-        rustls::ClientConfig::builder().with_root_certificates(self.root_store()).with_no_client_auth()
+        let builder = if self.post_quantum {
+            rustls::ClientConfig::builder_with_provider(Arc::new(pq::provider(true)))
+                .with_safe_default_protocol_versions()
+                .expect("the default protocol versions are supported by the ring provider")
+        } else {
+            rustls::ClientConfig::builder()
+        };
+        let verifier = self
+            .clock
+            .as_ref()
+            .and_then(|clock| clock::ClockedVerifier::new(self.root_store(), clock.clone()).ok());
+        match verifier {
+            Some(verifier) => {
+                builder.dangerous().with_custom_certificate_verifier(Arc::new(verifier)).with_no_client_auth()
+            }
+            None => builder.with_root_certificates(self.root_store()).with_no_client_auth(),
+        }
     }
 
     /// Construct a tls-stream on the tcp-stream provided
@@ -439,7 +531,7 @@ impl Tls {
         host: &str,
         sock: TcpStream,
     ) -> Result<rustls::StreamOwned<ClientConnection, TcpStream>, Error> {
-        match host.to_owned().try_into() {
+        match name::server_name(host) {
             Ok(server_name) => {
                 match rustls::ClientConnection::new(Arc::new(self.client_config()), server_name) {
                     Ok(conn) => Ok(rustls::StreamOwned::new(conn, sock)),
@@ -452,6 +544,183 @@ impl Tls {
             }
         }
     }
+
+    /// Connect to a host and complete the tls handshake, recording what happened along the way
+    ///
+    /// Unlike `stream_owned()`, the handshake is driven to completion here, so that a failure is
+    /// reported against the phase it happened in rather than on the first read or write.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the target tls site (i.e. betrusted.io)
+    /// * `port` - the target tcp port (i.e. 443)
+    ///
+    /// # Returns
+    ///
+    /// * an owned rustls stream with the handshake complete, or the Error that stopped it
+    /// * the Diagnostics of the attempt, suitable for showing to the user either way
+    pub fn connect(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> (Result<rustls::StreamOwned<ClientConnection, TcpStream>, Error>, Diagnostics) {
+        self.connect_within(host, port, None)
+    }
+
+    /// Like `connect()`, but gives up with an error of kind `TimedOut` once `timeout` has passed,
+    /// whether in the tcp connection or in the tls handshake. Name resolution is bounded by the
+    /// resolver's own timeout.
+    pub fn connect_timeout(
+        &self,
+        host: &str,
+        port: u16,
+        timeout: Duration,
+    ) -> (Result<rustls::StreamOwned<ClientConnection, TcpStream>, Error>, Diagnostics) {
+        self.connect_within(host, port, Some(timeout))
+    }
+
+    fn connect_within(
+        &self,
+        host: &str,
+        port: u16,
+        timeout: Option<Duration>,
+    ) -> (Result<rustls::StreamOwned<ClientConnection, TcpStream>, Error>, Diagnostics) {
+        let mut diag = Diagnostics::new(host, port);
+        let result = self.connect_diag(host, port, timeout, &mut diag);
+        match &result {
+            Ok(_) => log::info!("tls connected to {host}:{port}"),
+            Err(e) => log::warn!("tls connection to {host}:{port} failed: {e}"),
+        }
+        (result, diag)
+    }
+
+    fn connect_diag(
+        &self,
+        host: &str,
+        port: u16,
+        timeout: Option<Duration>,
+        diag: &mut Diagnostics,
+    ) -> Result<rustls::StreamOwned<ClientConnection, TcpStream>, Error> {
+        diag.post_quantum = self.post_quantum();
+        let (host, server_name) = check_name(host, diag)?;
+        let addrs = resolve(&host, port, diag)?;
+        handshake(&addrs, server_name, Arc::new(self.client_config()), timeout, diag)
+    }
+}
+
+/// The ascii form of `host`, and the name its certificate has to be valid for
+pub(crate) fn check_name(host: &str, diag: &mut Diagnostics) -> Result<(String, ServerName<'static>), Error> {
+    let checked = name::to_ascii(host).and_then(|host| name::server_name(&host).map(|sn| (host, sn)));
+    checked.map_err(|e| {
+        diag.failure = Some(Failure::InvalidName);
+        e
+    })
+}
+
+pub(crate) fn resolve(host: &str, port: u16, diag: &mut Diagnostics) -> Result<Vec<SocketAddr>, Error> {
+    let start = Instant::now();
+    let addrs = match (host, port).to_socket_addrs() {
+        Ok(addrs) => addrs.collect::<Vec<SocketAddr>>(),
+        Err(e) => {
+            diag.failure = Some(Failure::Dns);
+            return Err(e);
+        }
+    };
+    diag.dns = Some(start.elapsed());
+    if addrs.is_empty() {
+        diag.failure = Some(Failure::Dns);
+        return Err(Error::from(ErrorKind::NotFound));
+    }
+    Ok(addrs)
+}
+
+/// The time left until `deadline`, or a `TimedOut` error if it has passed
+pub(crate) fn remaining(deadline: Instant) -> Result<Duration, Error> {
+    deadline
+        .checked_duration_since(Instant::now())
+        .filter(|remaining| !remaining.is_zero())
+        .ok_or_else(|| Error::from(ErrorKind::TimedOut))
+}
+
+/// Connects to the first of `addrs` that answers before `deadline`
+pub(crate) fn connect_before(addrs: &[SocketAddr], deadline: Instant) -> Result<TcpStream, Error> {
+    let mut last_error = Error::from(ErrorKind::NotFound);
+    for addr in addrs {
+        match TcpStream::connect_timeout(addr, remaining(deadline)?) {
+            Ok(sock) => return Ok(sock),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// A socket whose every read and write may only take the time left until `deadline`, so that a host
+/// that trickles its replies can't stretch the handshake
+struct Bounded<'a> {
+    sock: &'a mut TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Bounded<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.sock.set_read_timeout(Some(remaining(self.deadline)?))?;
+        self.sock.read(buf)
+    }
+}
+
+impl Write for Bounded<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.sock.set_write_timeout(Some(remaining(self.deadline)?))?;
+        self.sock.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> { self.sock.flush() }
+}
+
+/// Connects to one of `addrs`, and drives the tls handshake to completion, within `timeout` if one is
+/// given
+pub(crate) fn handshake(
+    addrs: &[SocketAddr],
+    server_name: ServerName<'static>,
+    config: Arc<ClientConfig>,
+    timeout: Option<Duration>,
+    diag: &mut Diagnostics,
+) -> Result<rustls::StreamOwned<ClientConnection, TcpStream>, Error> {
+    let start = Instant::now();
+    let deadline = timeout.map(|timeout| start + timeout);
+    let sock = match deadline {
+        Some(deadline) => connect_before(addrs, deadline),
+        None => TcpStream::connect(addrs),
+    };
+    let mut sock = sock.map_err(|e| diag.fail(e))?;
+    diag.tcp = Some(start.elapsed());
+
+    let mut conn = match rustls::ClientConnection::new(config, server_name) {
+        Ok(conn) => conn,
+        Err(e) => {
+            diag.failure = Some(Failure::from(&e));
+            return Err(Error::new(ErrorKind::Other, "failed to configure client connection"));
+        }
+    };
+    let start = Instant::now();
+    match deadline {
+        Some(deadline) => {
+            let mut bounded = Bounded { sock: &mut sock, deadline };
+            while conn.is_handshaking() {
+                conn.complete_io(&mut bounded).map_err(|e| diag.fail(e))?;
+            }
+            sock.set_read_timeout(None).ok();
+            sock.set_write_timeout(None).ok();
+        }
+        None => {
+            while conn.is_handshaking() {
+                conn.complete_io(&mut sock).map_err(|e| diag.fail(e))?;
+            }
+        }
+    }
+    diag.handshake = Some(start.elapsed());
+    diag.negotiated(&conn);
+    Ok(rustls::StreamOwned::new(conn, sock))
 }
 
 // https://stackoverflow.com/questions/57029974/how-to-split-string-into-chunks-in-rust-to-insert-spaces
@@ -464,3 +733,76 @@ fn open_hex(text: &str) -> String {
         })
         .collect::<String>()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    /// A config that trusts nothing: the tests below never get as far as a certificate
+    fn config() -> Arc<ClientConfig> {
+        Arc::new(ClientConfig::builder().with_root_certificates(RootCertStore::empty()).with_no_client_auth())
+    }
+
+    #[test]
+    fn remaining_time_runs_out() {
+        assert!(remaining(Instant::now() + Duration::from_secs(60)).unwrap() > Duration::from_secs(59));
+        assert_eq!(remaining(Instant::now()).unwrap_err().kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn connect_before_a_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert!(connect_before(&[addr], Instant::now() + Duration::from_secs(5)).is_ok());
+        // no time left to try
+        let late = connect_before(&[addr], Instant::now()).unwrap_err();
+        assert_eq!(late.kind(), ErrorKind::TimedOut);
+        let nowhere = connect_before(&[], Instant::now() + Duration::from_secs(5)).unwrap_err();
+        assert_eq!(nowhere.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn silent_host_times_out_the_handshake() {
+        // accepts the tcp connection, then never answers the client hello
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let silent = std::thread::spawn(move || listener.accept().map(|(sock, _)| sock));
+
+        let mut diag = Diagnostics::new("localhost", addr.port());
+        let server_name = ServerName::try_from("localhost").unwrap();
+        let start = Instant::now();
+        let result = handshake(&[addr], server_name, config(), Some(Duration::from_millis(300)), &mut diag);
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(diag.tcp.is_some());
+        assert_eq!(diag.failure, Some(Failure::Timeout));
+        drop(silent.join());
+    }
+
+    #[test]
+    fn trickling_host_can_not_stretch_the_handshake() {
+        // sends a byte of garbage just inside every read timeout
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            if let Ok((mut sock, _)) = listener.accept() {
+                // the start of a tls record header, so the client keeps waiting for the rest
+                for byte in [0x16u8, 0x03, 0x03, 0x40, 0x00].iter().cycle().take(50) {
+                    std::thread::sleep(Duration::from_millis(100));
+                    if sock.write_all(&[*byte]).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let mut diag = Diagnostics::new("localhost", addr.port());
+        let server_name = ServerName::try_from("localhost").unwrap();
+        let start = Instant::now();
+        let result = handshake(&[addr], server_name, config(), Some(Duration::from_millis(500)), &mut diag);
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_secs(3), "took {:?}", start.elapsed());
+    }
+}
@@ -0,0 +1,229 @@
+//! Host names, as typed by the user, to names that can be resolved and checked against certificates.
+//!
+//! Certificates and DNS only know ASCII host names, so an internationalized domain name (ie
+//! `bücher.example`) has to be converted to its punycode A-label form (`xn--bcher-kva.example`) before
+//! it is used. The conversion here folds case and the CJK full stops to `.`, but does not apply the full
+//! UTS #46 mapping tables; text typed on the device is already in NFC form.
+
+use std::io::{Error, ErrorKind};
+use std::net::IpAddr;
+
+use rustls::pki_types::{CertificateDer, ServerName};
+use rustls::CertificateError;
+
+/// The longest label allowed in a DNS name
+const LABEL_MAX: usize = 63;
+/// The longest DNS name, without a trailing dot
+const NAME_MAX: usize = 253;
+/// The prefix marking a punycode label
+const ACE_PREFIX: &str = "xn--";
+
+// RFC 3492 bootstring parameters for punycode
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 0x80;
+
+/// Converts a host name to the lower case ASCII form used by DNS and certificates.
+///
+/// IP addresses are passed through unchanged. A trailing dot is dropped.
+///
+/// # Arguments
+///
+/// * `host` - the host name, possibly with non-ASCII labels (i.e. bücher.example)
+///
+/// # Returns
+///
+/// * the ASCII host name (i.e. xn--bcher-kva.example)
+/// * InvalidInput if a label is empty or too long, or the name is too long
+pub fn to_ascii(host: &str) -> Result<String, Error> {
+    if host.parse::<IpAddr>().is_ok() {
+        return Ok(host.to_string());
+    }
+    // U+3002 ideographic, U+FF0E fullwidth and U+FF61 halfwidth ideographic full stops are label
+    // separators too
+    let host = host.replace(|c| c == '\u{3002}' || c == '\u{FF0E}' || c == '\u{FF61}', ".").to_lowercase();
+    let host = host.strip_suffix('.').unwrap_or(&host);
+    let mut labels = Vec::new();
+    for label in host.split('.') {
+        let label = if label.is_ascii() {
+            label.to_string()
+        } else {
+            match punycode(label) {
+                Some(encoded) => format!("{}{}", ACE_PREFIX, encoded),
+                None => return Err(Error::from(ErrorKind::InvalidInput)),
+            }
+        };
+        if label.is_empty() || label.len() > LABEL_MAX {
+            return Err(Error::from(ErrorKind::InvalidInput));
+        }
+        labels.push(label);
+    }
+    let ascii = labels.join(".");
+    if ascii.len() > NAME_MAX {
+        return Err(Error::from(ErrorKind::InvalidInput));
+    }
+    Ok(ascii)
+}
+
+/// Converts a host name to a rustls ServerName, by way of `to_ascii()`
+pub fn server_name(host: &str) -> Result<ServerName<'static>, Error> {
+    ServerName::try_from(to_ascii(host)?).map_err(|_| Error::from(ErrorKind::InvalidInput))
+}
+
+/// Checks that a certificate is valid for a host name, without checking its chain of trust.
+///
+/// Names are matched by webpki, as in a full verification: against the dNSName and iPAddress entries of
+/// the subjectAltName extension only (the subject CN is ignored), case insensitively, and with a
+/// wildcard only as the whole left-most label, standing for exactly one label.
+///
+/// # Arguments
+///
+/// * `end_entity` - the certificate offered by the host
+/// * `server_name` - the host name, as returned by `server_name()`
+pub fn verify(end_entity: &CertificateDer, server_name: &ServerName) -> Result<(), CertificateError> {
+    let cert = webpki::EndEntityCert::try_from(end_entity).map_err(|_| CertificateError::BadEncoding)?;
+    cert.verify_is_valid_for_subject_name(server_name).map_err(|e| match e {
+        webpki::Error::CertNotValidForName => CertificateError::NotValidForName,
+        // a name in the certificate that could not be parsed
+        _ => CertificateError::BadEncoding,
+    })
+}
+
+fn adapt(delta: u32, points: u32, first: bool) -> u32 {
+    let mut delta = if first { delta / DAMP } else { delta / 2 };
+    delta += delta / points;
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+    k + (((BASE - T_MIN + 1) * delta) / (delta + SKEW))
+}
+
+fn digit(d: u32) -> char {
+    match d {
+        0..=25 => (b'a' + d as u8) as char,
+        _ => (b'0' + (d - 26) as u8) as char,
+    }
+}
+
+/// RFC 3492 punycode encoding of a single label, without the `xn--` prefix
+fn punycode(label: &str) -> Option<String> {
+    let input: Vec<u32> = label.chars().map(|c| c as u32).collect();
+    let mut output: String = label.chars().filter(|c| c.is_ascii()).collect();
+    let basic = output.len() as u32;
+    if basic > 0 {
+        output.push('-');
+    }
+    let mut n = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut handled = basic;
+    while (handled as usize) < input.len() {
+        let m = *input.iter().filter(|&&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+        n = m;
+        for &c in input.iter() {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = if k <= bias {
+                        T_MIN
+                    } else if k >= bias + T_MAX {
+                        T_MAX
+                    } else {
+                        k - bias
+                    };
+                    if q < t {
+                        break;
+                    }
+                    output.push(digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(digit(q));
+                bias = adapt(delta, handled + 1, handled == basic);
+                delta = 0;
+                handled += 1;
+            }
+        }
+        delta += 1;
+        n += 1;
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAN_WILDCARD: &[u8] = include_bytes!("../tests/fixtures/san_wildcard.der");
+    const SAN_IDN: &[u8] = include_bytes!("../tests/fixtures/san_idn.der");
+    const SAN_PARTIAL: &[u8] = include_bytes!("../tests/fixtures/san_partial.der");
+    const CN_ONLY: &[u8] = include_bytes!("../tests/fixtures/cn_only.der");
+
+    fn valid_for(fixture: &[u8], host: &str) -> bool {
+        let cert = CertificateDer::from(fixture);
+        verify(&cert, &server_name(host).unwrap()).is_ok()
+    }
+
+    #[test]
+    fn punycode_vectors() {
+        assert_eq!(punycode("bücher").unwrap(), "bcher-kva");
+        assert_eq!(punycode("münchen").unwrap(), "mnchen-3ya");
+        assert_eq!(punycode("例え").unwrap(), "r8jz45g");
+        assert_eq!(punycode("ドメイン名例").unwrap(), "eckwd4c7cu47r2wf");
+        assert_eq!(punycode("ü").unwrap(), "tda");
+    }
+
+    #[test]
+    fn ascii_names() {
+        assert_eq!(to_ascii("Bücher.Example").unwrap(), "xn--bcher-kva.example");
+        assert_eq!(to_ascii("例え。テスト").unwrap(), "xn--r8jz45g.xn--zckzah");
+        assert_eq!(to_ascii("betrusted.io.").unwrap(), "betrusted.io");
+        assert_eq!(to_ascii("XN--BCHER-KVA.example").unwrap(), "xn--bcher-kva.example");
+        assert_eq!(to_ascii("192.0.2.1").unwrap(), "192.0.2.1");
+        assert!(to_ascii("").is_err());
+        assert!(to_ascii("a..example").is_err());
+        assert!(to_ascii(&format!("{}.example", "a".repeat(64))).is_err());
+        assert!(to_ascii(&format!("{}.example", "ü".repeat(60))).is_err());
+        assert!(server_name("not a host").is_err());
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(valid_for(SAN_WILDCARD, "example.com"));
+        assert!(valid_for(SAN_WILDCARD, "www.example.com"));
+        assert!(valid_for(SAN_WILDCARD, "WWW.Example.COM."));
+        // a wildcard stands for exactly one label
+        assert!(!valid_for(SAN_WILDCARD, "a.b.example.com"));
+        assert!(!valid_for(SAN_WILDCARD, "example.org"));
+        assert!(!valid_for(SAN_WILDCARD, "wwwexample.com"));
+        // only a whole-label wildcard is honoured
+        assert!(!valid_for(SAN_PARTIAL, "foo.example.org"));
+        assert!(!valid_for(SAN_PARTIAL, "f.example.org"));
+    }
+
+    #[test]
+    fn idn_names() {
+        assert!(valid_for(SAN_IDN, "bücher.example"));
+        assert!(valid_for(SAN_IDN, "BÜCHER.example"));
+        assert!(valid_for(SAN_IDN, "xn--bcher-kva.example"));
+        assert!(valid_for(SAN_IDN, "例え。テスト"));
+        assert!(!valid_for(SAN_IDN, "buecher.example"));
+        assert!(!valid_for(SAN_IDN, "bucher.example"));
+    }
+
+    #[test]
+    fn subject_cn_is_ignored() {
+        assert!(!valid_for(CN_ONLY, "cn-only.example"));
+    }
+}
@@ -95,13 +95,25 @@ impl OwnedTrustAnchor {
                 }
                 .to_string();
                 // grab a few arbitrary bytes from spki so pddb_key is deterministic & unique
-                let k = &self.spki;
-                pddb_key.push_str(&format!(" {:X}{:X}{:X}{:X}", k[6], k[7], k[8], k[9]));
+                let k = match self.spki.get(6..10) {
+                    Some(k) => k,
+                    None => {
+                        log::warn!("spki too short for a pddb_key: {} bytes", self.spki.len());
+                        return Err(Error::from(ErrorKind::InvalidData));
+                    }
+                };
+                pddb_key.push_str(&format!(" {:X}{:X}{:X}{:X}", k[0], k[1], k[2], k[3]));
                 // mirror of pddb::KEY_NAME_LEN
                 // u64: vaddr/len/resvd, u32: flags, age = 95
                 // would this be better as a pddb pub?
                 const KEY_NAME_LEN: usize = 127 - 8 - 8 - 8 - 4 - 4;
-                Ok(pddb_key[..min(pddb_key.len(), KEY_NAME_LEN - 1)].to_string())
+                // the subject is arbitrary UTF-8, so back off to the start of a char
+                let mut end = min(pddb_key.len(), KEY_NAME_LEN - 1);
+                while !pddb_key.is_char_boundary(end) {
+                    end -= 1;
+                }
+                pddb_key.truncate(end);
+                Ok(pddb_key)
             }
             Err(e) => {
                 log::warn!("failed to construct pddb_key: {e}");
@@ -190,3 +202,52 @@ fn rm_der_header(der: &[u8]) -> Result<Vec<u8>, Error> {
         Err(_) => Err(Error::new(ErrorKind::InvalidData, "der decode failed: reader")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rustls::RootCertStore;
+
+    use super::*;
+    use crate::clock::ClockedVerifier;
+    use crate::testing::{self, MockClock};
+
+    #[test]
+    fn saved_root_still_anchors_its_chains() {
+        let chain = testing::valid();
+        let (_, x509) = X509Certificate::from_der(&chain.root).unwrap();
+        let ota = OwnedTrustAnchor::from_x509(&x509).unwrap();
+        assert_eq!(ota.subject().unwrap(), "O=Xous test fixtures, CN=Fixture Root");
+        assert!(ota.pddb_key().unwrap().starts_with("Fixture Root "));
+
+        let mut roots = RootCertStore::empty();
+        roots.extend(std::iter::once(ota.into()));
+        let verifier = ClockedVerifier::new(roots, Arc::new(MockClock::default())).unwrap();
+        assert!(verifier.verify_chain(&chain.offered(), testing::VALID_HOST).is_ok());
+        assert!(verifier.verify_chain(&testing::long().offered(), testing::LONG_HOST).is_ok());
+    }
+
+    #[test]
+    fn pddb_key_rejects_a_short_spki() {
+        let (_, x509) = X509Certificate::from_der(&testing::valid().root).unwrap();
+        let mut ota = OwnedTrustAnchor::from_x509(&x509).unwrap();
+        ota.spki.truncate(9);
+        assert!(ota.pddb_key().is_err());
+    }
+
+    #[test]
+    fn pddb_key_is_cut_between_chars() {
+        let (_, x509) = X509Certificate::from_der(&testing::valid().root).unwrap();
+        let mut ota = OwnedTrustAnchor::from_x509(&x509).unwrap();
+        // a CN of 2-byte chars, offset by one so that the key length lands inside a char
+        let cn = format!("a{}", "é".repeat(50));
+        let mut atv = vec![0x06, 0x03, 0x55, 0x04, 0x03, 0x0C, cn.len() as u8];
+        atv.extend_from_slice(cn.as_bytes());
+        let mut rdn = vec![0x30, atv.len() as u8];
+        rdn.extend_from_slice(&atv);
+        ota.subject = [&[0x31, rdn.len() as u8][..], &rdn].concat();
+        let key = ota.pddb_key().unwrap();
+        assert_eq!(key, format!("a{}", "é".repeat(46)));
+    }
+}
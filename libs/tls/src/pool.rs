@@ -0,0 +1,326 @@
+//! A pool of tls connections, for apps that make bursts of requests to the same servers.
+//!
+//! `Pool::connect()` hands out an idle connection to the same host, port and ALPN protocols if there is
+//! one, and makes a new one otherwise, giving up once the pool's connect timeout has passed. Once the app
+//! has read the whole of the last response, it gives the connection back with `Pool::release()` for the
+//! next request to use; a connection that is dropped instead, or given back with data left unread, is
+//! closed. Idle connections are closed after the idle timeout, and any that the server closed
+//! in the meantime are passed over.
+//!
+//! New connections are cheaper too. Host names are resolved once per `DNS_TTL`, and the connections
+//! share one `ClientConfig` per set of ALPN protocols, so rustls can resume an earlier tls session with
+//! the host rather than make a full handshake.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rustls::{ClientConfig, ClientConnection, StreamOwned};
+
+use crate::diag::Diagnostics;
+use crate::Tls;
+
+/// How long a connection may sit in the pool by default. Servers commonly close idle connections
+/// after a minute or less, so this gives up on them first.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a resolved host name is used for. `to_socket_addrs()` does not tell the TTL of the record.
+pub const DNS_TTL: Duration = Duration::from_secs(60);
+/// The most idle connections kept to each (host, port, alpn)
+const MAX_IDLE: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    /// the ascii form of the host name
+    host: String,
+    port: u16,
+    alpn: Vec<Vec<u8>>,
+}
+
+/// Items that are only good for `timeout` after they were put away, by key
+struct Idle<K, T> {
+    timeout: Duration,
+    /// oldest first
+    items: HashMap<K, Vec<(T, Instant)>>,
+}
+
+impl<K: Eq + Hash, T> Idle<K, T> {
+    fn new(timeout: Duration) -> Self { Idle { timeout, items: HashMap::new() } }
+
+    /// Keeps `item` under `key`, dropping the oldest item there if it is full
+    fn put(&mut self, key: K, item: T, now: Instant) {
+        let items = self.items.entry(key).or_default();
+        if items.len() >= MAX_IDLE {
+            items.remove(0);
+        }
+        items.push((item, now));
+    }
+
+    /// The newest item under `key`
+    fn take(&mut self, key: &K) -> Option<T> {
+        let items = self.items.get_mut(key)?;
+        let item = items.pop().map(|(item, _)| item);
+        if items.is_empty() {
+            self.items.remove(key);
+        }
+        item
+    }
+
+    /// Drops the items that have timed out
+    fn prune(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        for items in self.items.values_mut() {
+            items.retain(|(_, since)| now.duration_since(*since) < timeout);
+        }
+        self.items.retain(|_, items| !items.is_empty());
+    }
+
+    fn clear(&mut self) { self.items.clear(); }
+}
+
+/// A connection from a `Pool`. Give it back with `Pool::release()` once the last response on it has
+/// been read in full; drop it to close it.
+pub struct PooledStream {
+    key: Key,
+    stream: StreamOwned<ClientConnection, TcpStream>,
+}
+
+impl PooledStream {
+    /// Takes the connection out of the pool's keeping for good
+    pub fn into_inner(self) -> StreamOwned<ClientConnection, TcpStream> { self.stream }
+}
+
+impl Deref for PooledStream {
+    type Target = StreamOwned<ClientConnection, TcpStream>;
+
+    fn deref(&self) -> &Self::Target { &self.stream }
+}
+
+impl DerefMut for PooledStream {
+    fn deref_mut(&mut self) -> &mut Self::Target { &mut self.stream }
+}
+
+impl Read for PooledStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> { self.stream.read(buf) }
+}
+
+impl Write for PooledStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> { self.stream.write(buf) }
+
+    fn flush(&mut self) -> std::io::Result<()> { self.stream.flush() }
+}
+
+pub struct Pool {
+    tls: Tls,
+    /// how long making a new connection may take, tcp connection and tls handshake together
+    connect_timeout: Duration,
+    /// one per set of ALPN protocols, each with its own tls session cache
+    configs: RefCell<HashMap<Vec<Vec<u8>>, Arc<ClientConfig>>>,
+    dns: RefCell<HashMap<(String, u16), (Vec<SocketAddr>, Instant)>>,
+    idle: RefCell<Idle<Key, StreamOwned<ClientConnection, TcpStream>>>,
+}
+
+impl Pool {
+    /// A pool of connections made with the trust store and options of `tls`. Making a new connection
+    /// fails with an error of kind `TimedOut` once `connect_timeout` has passed, as with
+    /// `Tls::connect_timeout()`.
+    pub fn new(tls: Tls, connect_timeout: Duration) -> Pool {
+        Pool {
+            tls,
+            connect_timeout,
+            configs: RefCell::new(HashMap::new()),
+            dns: RefCell::new(HashMap::new()),
+            idle: RefCell::new(Idle::new(IDLE_TIMEOUT)),
+        }
+    }
+
+    /// Closes connections after `timeout` in the pool, rather than after `IDLE_TIMEOUT`
+    pub fn with_idle_timeout(self, timeout: Duration) -> Pool {
+        self.idle.borrow_mut().timeout = timeout;
+        self
+    }
+
+    /// Connects to a host, reusing an idle connection to it if there is one
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the target tls site (i.e. betrusted.io)
+    /// * `port` - the target tcp port (i.e. 443)
+    /// * `alpn` - the application protocols to offer, most preferred first (i.e. `b"http/1.1"`)
+    ///
+    /// # Returns
+    ///
+    /// * a connection with the handshake complete, or the Error that stopped it
+    /// * the Diagnostics of the attempt. For a reused connection, `reused` is set and there are no timings.
+    pub fn connect(
+        &self,
+        host: &str,
+        port: u16,
+        alpn: &[&[u8]],
+    ) -> (Result<PooledStream, Error>, Diagnostics) {
+        let mut diag = Diagnostics::new(host, port);
+        diag.post_quantum = self.tls.post_quantum();
+        let result = self.connect_diag(host, port, alpn, &mut diag);
+        match &result {
+            Ok(_) if diag.reused => log::debug!("tls reusing a connection to {host}:{port}"),
+            Ok(_) => log::info!("tls connected to {host}:{port}"),
+            Err(e) => log::warn!("tls connection to {host}:{port} failed: {e}"),
+        }
+        (result, diag)
+    }
+
+    /// Gives a connection back to the pool, for the next `connect()` to the same host to use. Only do
+    /// so with nothing left unread on the connection; a connection with data left over is closed, as
+    /// the next request would read it as the start of its response.
+    pub fn release(&self, mut stream: PooledStream) {
+        if stream.stream.conn.is_handshaking() || stream.stream.flush().is_err() || has_unread(&mut stream) {
+            log::debug!("tls closing a connection to {} instead of keeping it", stream.key.host);
+            return;
+        }
+        let PooledStream { key, stream } = stream;
+        self.idle.borrow_mut().put(key, stream, Instant::now());
+    }
+
+    /// Closes the idle connections, and forgets resolved names and tls sessions. Call this after the
+    /// trust store changes, for the next connections to be checked against it.
+    pub fn clear(&self) {
+        self.idle.borrow_mut().clear();
+        self.dns.borrow_mut().clear();
+        self.configs.borrow_mut().clear();
+    }
+
+    fn connect_diag(
+        &self,
+        host: &str,
+        port: u16,
+        alpn: &[&[u8]],
+        diag: &mut Diagnostics,
+    ) -> Result<PooledStream, Error> {
+        let (host, server_name) = crate::check_name(host, diag)?;
+        let key = Key { host, port, alpn: alpn.iter().map(|protocol| protocol.to_vec()).collect() };
+        let now = Instant::now();
+        {
+            let mut idle = self.idle.borrow_mut();
+            idle.prune(now);
+            while let Some(stream) = idle.take(&key) {
+                if is_open(&stream.sock) {
+                    diag.reused_connection(&stream.conn);
+                    return Ok(PooledStream { key, stream });
+                }
+            }
+        }
+        let addrs = self.resolve(&key.host, port, now, diag)?;
+        let config = self.config(&key.alpn);
+        match crate::handshake(&addrs, server_name, config, Some(self.connect_timeout), diag) {
+            Ok(stream) => Ok(PooledStream { key, stream }),
+            Err(e) => {
+                if diag.tcp.is_none() {
+                    // the host may have moved
+                    self.dns.borrow_mut().remove(&(key.host, port));
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+        now: Instant,
+        diag: &mut Diagnostics,
+    ) -> Result<Vec<SocketAddr>, Error> {
+        let key = (host.to_string(), port);
+        if let Some((addrs, resolved)) = self.dns.borrow().get(&key) {
+            if now.duration_since(*resolved) < DNS_TTL {
+                return Ok(addrs.clone());
+            }
+        }
+        let addrs = crate::resolve(host, port, diag)?;
+        self.dns.borrow_mut().insert(key, (addrs.clone(), now));
+        Ok(addrs)
+    }
+
+    fn config(&self, alpn: &[Vec<u8>]) -> Arc<ClientConfig> {
+        self.configs
+            .borrow_mut()
+            .entry(alpn.to_vec())
+            .or_insert_with(|| {
+                let mut config = self.tls.client_config();
+                config.alpn_protocols = alpn.to_vec();
+                Arc::new(config)
+            })
+            .clone()
+    }
+}
+
+/// true if rustls holds plaintext that the app hasn't read, or records it hasn't processed yet, or if
+/// anything more has arrived on the socket
+fn has_unread(stream: &mut PooledStream) -> bool {
+    match stream.stream.conn.process_new_packets() {
+        Ok(state) if state.plaintext_bytes_to_read() == 0 && !state.peer_has_closed() => {
+            !is_open(&stream.stream.sock)
+        }
+        _ => true,
+    }
+}
+
+/// false if the server closed the connection while it was idle, or sent something unasked for
+fn is_open(sock: &TcpStream) -> bool {
+    if sock.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let mut byte = [0u8; 1];
+    let quiet = matches!(sock.peek(&mut byte), Err(e) if e.kind() == ErrorKind::WouldBlock);
+    sock.set_nonblocking(false).is_ok() && quiet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newest_idle_item_is_taken_first() {
+        let now = Instant::now();
+        let mut idle = Idle::new(IDLE_TIMEOUT);
+        idle.put("a", 1, now);
+        idle.put("a", 2, now);
+        idle.put("b", 3, now);
+        assert_eq!(idle.take(&"a"), Some(2));
+        assert_eq!(idle.take(&"a"), Some(1));
+        assert_eq!(idle.take(&"a"), None);
+        assert_eq!(idle.take(&"b"), Some(3));
+    }
+
+    #[test]
+    fn idle_items_time_out() {
+        let now = Instant::now();
+        let mut idle = Idle::new(Duration::from_secs(10));
+        idle.put("a", 1, now);
+        idle.put("a", 2, now + Duration::from_secs(5));
+        idle.prune(now + Duration::from_secs(12));
+        assert_eq!(idle.take(&"a"), Some(2));
+        assert_eq!(idle.take(&"a"), None);
+        idle.put("b", 3, now);
+        idle.prune(now + Duration::from_secs(10));
+        assert!(idle.items.is_empty());
+    }
+
+    #[test]
+    fn oldest_idle_item_makes_room() {
+        let now = Instant::now();
+        let mut idle = Idle::new(IDLE_TIMEOUT);
+        for i in 0..MAX_IDLE + 1 {
+            idle.put("a", i, now);
+        }
+        let mut taken = Vec::new();
+        while let Some(i) = idle.take(&"a") {
+            taken.push(i);
+        }
+        assert_eq!(taken, (1..MAX_IDLE + 1).rev().collect::<Vec<_>>());
+    }
+}
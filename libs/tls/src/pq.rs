@@ -0,0 +1,85 @@
+//! Opt-in post-quantum hybrid key exchange.
+//!
+//! A hybrid group combines X25519 with the Kyber768 (ML-KEM-768) KEM, so that the session keys of a
+//! connection recorded today stay secret from an adversary who gets hold of a quantum computer later,
+//! while remaining as strong as X25519 alone should the KEM turn out to be weak.
+//!
+//! Whether a hybrid group is on offer depends on the crypto provider; the patched `ring` provider has
+//! none yet. Asking for post-quantum then falls back to the classical groups, and
+//! `Diagnostics::post_quantum` says so rather than leaving the user to assume otherwise.
+
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::NamedGroup;
+
+/// X25519Kyber768Draft00, as first deployed by browsers and CDNs
+const X25519_KYBER768_DRAFT00: u16 = 0x6399;
+/// X25519MLKEM768, the standardised successor of X25519Kyber768Draft00
+const X25519_MLKEM768: u16 = 0x11ec;
+
+/// What came of asking for a post-quantum key exchange on a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PostQuantum {
+    /// the connection didn't ask for one
+    #[default]
+    NotRequested,
+    /// the crypto provider has no hybrid group, so a classical group was negotiated
+    Unsupported,
+    /// a hybrid group was offered ahead of the classical ones. rustls 0.22 doesn't report the group a
+    /// handshake settled on, so whether the host took it isn't known.
+    Offered,
+}
+
+impl PostQuantum {
+    /// The outcome for a connection that did or didn't ask for post-quantum
+    pub fn of_request(post_quantum: bool) -> PostQuantum {
+        match (post_quantum, available()) {
+            (false, _) => PostQuantum::NotRequested,
+            (true, false) => PostQuantum::Unsupported,
+            (true, true) => PostQuantum::Offered,
+        }
+    }
+}
+
+/// true if `group` is a post-quantum hybrid
+pub fn is_hybrid(group: NamedGroup) -> bool {
+    matches!(group.get_u16(), X25519_KYBER768_DRAFT00 | X25519_MLKEM768)
+}
+
+/// true if the crypto provider offers at least one post-quantum hybrid group
+pub fn available() -> bool { ring::default_provider().kx_groups.iter().any(|group| is_hybrid(group.name())) }
+
+/// The crypto provider for client connections. Hybrid groups are only offered when `post_quantum` is
+/// set, and then in preference to the classical groups.
+pub fn provider(post_quantum: bool) -> CryptoProvider {
+    let provider = ring::default_provider();
+    let (hybrid, classical): (Vec<_>, Vec<_>) =
+        provider.kx_groups.iter().partition(|group| is_hybrid(group.name()));
+    if post_quantum && hybrid.is_empty() {
+        log::warn!("no post-quantum key exchange available, falling back to classical");
+    }
+    let kx_groups = if post_quantum { hybrid.into_iter().chain(classical).collect() } else { classical };
+    CryptoProvider { kx_groups, ..provider }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_classical() {
+        let classical = ring::default_provider().kx_groups.len();
+        assert_eq!(provider(true).kx_groups.len(), classical);
+        assert_eq!(provider(false).kx_groups.len(), classical);
+        if !available() {
+            assert_eq!(PostQuantum::of_request(true), PostQuantum::Unsupported);
+        }
+        assert_eq!(PostQuantum::of_request(false), PostQuantum::NotRequested);
+    }
+
+    #[test]
+    fn hybrid_groups() {
+        assert!(is_hybrid(NamedGroup::from(X25519_MLKEM768)));
+        assert!(is_hybrid(NamedGroup::from(X25519_KYBER768_DRAFT00)));
+        assert!(!is_hybrid(NamedGroup::X25519));
+    }
+}
@@ -0,0 +1,362 @@
+//! Shares trusted Certificate Authorities between two Precursors, for users who look after more
+//! than one device. One device hosts the sync and shows a pairing code; the other device joins with
+//! that code, and each device then offers its user the trust anchors it was missing.
+//!
+//! The pairing code is the first 8 bytes of the SHA-256 of the host's ephemeral X25519 key,
+//! followed by a 4-byte random token. The joiner pins the host key against the code, and proves
+//! that it was shown the code by sending the token as its first encrypted message, so the code
+//! alone authenticates both ends (a Noise NN handshake with the host key pinned out of band). The
+//! host accepts a single connection attempt per pairing code, and gives up if none comes within five
+//! minutes.
+//!
+//! On a Wi-Fi network marked metered, each device asks its user before it syncs.
+
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use aes_gcm_siv::aead::{Aead, KeyInit};
+use aes_gcm_siv::{Aes256GcmSiv, Nonce};
+use hkdf::Hkdf;
+use locales::t;
+use modals::Modals;
+use rand_core::RngCore;
+use sha2::{Digest, Sha256};
+use x25519_dalek::{EphemeralSecret, PublicKey, SharedSecret};
+use xous_names::XousNames;
+
+use crate::ota::OwnedTrustAnchor;
+use crate::{Tls, TLS_TRUSTED_DICT};
+
+/// TCP port a sync host listens on
+pub const SYNC_PORT: u16 = 7443;
+/// prefix of the QR code shown by a sync host
+const SYNC_URI: &str = "xous-tls-sync";
+const FINGERPRINT_LEN: usize = 8;
+const TOKEN_LEN: usize = 4;
+const HKDF_SALT: &[u8] = b"xous tls trust sync v1";
+/// generous for a few hundred trust anchors, and stops a peer from exhausting our heap
+const MAX_FRAME_BYTES: usize = 512 * 1024;
+/// how long either end waits for the other to respond
+const READ_TIMEOUT: Duration = Duration::from_secs(120);
+/// how long the host waits for the other device to join
+const ACCEPT_TIMEOUT: Duration = Duration::from_secs(300);
+/// how often the host checks whether the other device has joined
+const ACCEPT_POLL: Duration = Duration::from_millis(250);
+/// the number of trust anchors offered per checkbox modal, so the list fits on the screen
+const OFFER_PAGE: usize = 8;
+
+impl Tls {
+    /// Hosts a trust store sync. Shows the address and pairing code to enter on the other device,
+    /// then waits for it to connect.
+    ///
+    /// # Returns
+    ///
+    /// the number of trust anchors offered to the user, and the number they chose to trust
+    pub fn sync_host(&self) -> Result<(usize, usize), Error> {
+        let xns = XousNames::new().unwrap();
+        let modals = Modals::new(&xns).unwrap();
+        let net = net::NetManager::new();
+        let addr = match net.get_ipv4_config() {
+            Some(conf) if conf.addr != [0u8; 4] => Ipv4Addr::from(conf.addr),
+            _ => return Err(Error::new(ErrorKind::NotConnected, "no network connection")),
+        };
+        confirm_if_metered(&net, &modals)?;
+        let mut trng = trng::Trng::new(&xns).unwrap();
+        let secret = EphemeralSecret::random_from_rng(&mut trng);
+        let public = PublicKey::from(&secret);
+        let mut token = [0u8; TOKEN_LEN];
+        trng.fill_bytes(&mut token);
+        let code = pairing_code(&public, &token);
+
+        // bind before showing the code, so a peer that connects while the modal is up is queued
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, SYNC_PORT))?;
+        modals
+            .show_notification(
+                &format!("{}\n\n{addr}\n{code}", t!("tls.sync_host_prompt", locales::LANG)),
+                Some(&format!("{SYNC_URI}:{addr}:{code}")),
+            )
+            .expect("modal failed");
+        let (mut stream, peer_addr) = accept_with_timeout(&listener, ACCEPT_TIMEOUT)?;
+        drop(listener);
+        log::info!("tls sync peer connected from {peer_addr}");
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+        stream.write_all(public.as_bytes())?;
+        let mut peer = [0u8; 32];
+        stream.read_exact(&mut peer)?;
+        let peer = PublicKey::from(peer);
+        let shared = secret.diffie_hellman(&peer);
+        let mut channel = Channel::new(stream, shared, &public, &peer, true)?;
+        let proof = channel.recv()?;
+        // constant time, so the token can't be recovered a byte at a time
+        if proof.len() != TOKEN_LEN
+            || proof.iter().zip(token.iter()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) != 0
+        {
+            return Err(Error::new(ErrorKind::PermissionDenied, "peer doesn't know the pairing code"));
+        }
+        let received = decode_anchors(&channel.recv()?)?;
+        channel.send(&encode_anchors(&self.trusted()))?;
+        drop(channel);
+        self.offer_anchors(&modals, received)
+    }
+
+    /// Joins a trust store sync hosted on another device.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the address shown by the hosting device
+    /// * `code` - the pairing code shown by the hosting device
+    ///
+    /// # Returns
+    ///
+    /// the number of trust anchors offered to the user, and the number they chose to trust
+    pub fn sync_join(&self, host: &str, code: &str) -> Result<(usize, usize), Error> {
+        let (expected, token) =
+            parse_pairing_code(code).ok_or(Error::new(ErrorKind::InvalidInput, "malformed pairing code"))?;
+        let xns = XousNames::new().unwrap();
+        let modals = Modals::new(&xns).unwrap();
+        let mut trng = trng::Trng::new(&xns).unwrap();
+        confirm_if_metered(&net::NetManager::new(), &modals)?;
+
+        let mut stream = TcpStream::connect((host, SYNC_PORT))?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut host_key = [0u8; 32];
+        stream.read_exact(&mut host_key)?;
+        let host_key = PublicKey::from(host_key);
+        if fingerprint(&host_key) != expected {
+            return Err(Error::new(ErrorKind::PermissionDenied, "host key doesn't match the pairing code"));
+        }
+        let secret = EphemeralSecret::random_from_rng(&mut trng);
+        let public = PublicKey::from(&secret);
+        stream.write_all(public.as_bytes())?;
+        let shared = secret.diffie_hellman(&host_key);
+        let mut channel = Channel::new(stream, shared, &host_key, &public, false)?;
+        channel.send(&token)?;
+        // the joiner sends first and the host replies, so neither end blocks on a full socket buffer
+        channel.send(&encode_anchors(&self.trusted()))?;
+        let received = decode_anchors(&channel.recv()?)?;
+        drop(channel);
+        self.offer_anchors(&modals, received)
+    }
+
+    /// Offers the user each of the `received` trust anchors that isn't already trusted, a page at a
+    /// time, and saves the ones they select.
+    fn offer_anchors(
+        &self,
+        modals: &Modals,
+        received: Vec<OwnedTrustAnchor>,
+    ) -> Result<(usize, usize), Error> {
+        let mut known = self.pddb.list_keys(TLS_TRUSTED_DICT, None).unwrap_or_default();
+        let mut missing: Vec<OwnedTrustAnchor> = Vec::new();
+        for ta in received {
+            // pddb_key() samples bytes 6..10 of the spki
+            if ta.spki.len() < 10 {
+                continue;
+            }
+            if let Ok(key) = ta.pddb_key() {
+                if !known.contains(&key) {
+                    known.push(key);
+                    missing.push(ta);
+                }
+            }
+        }
+        let pages = (missing.len() + OFFER_PAGE - 1) / OFFER_PAGE;
+        let mut saved = 0;
+        for (page, anchors) in missing.chunks(OFFER_PAGE).enumerate() {
+            let items: Vec<String> = anchors.iter().map(|ta| format!("🏛 {}", ta)).collect();
+            modals.add_list(items.iter().map(AsRef::as_ref).collect()).expect("couldn't build checkbox list");
+            let prompt = format!("{} ({}/{})", t!("tls.sync_trust_prompt", locales::LANG), page + 1, pages);
+            modals.get_checkbox(&prompt).map_err(|e| Error::new(ErrorKind::Other, format!("{:?}", e)))?;
+            for i in modals.get_check_index().unwrap_or_default() {
+                if let Some(ta) = anchors.get(i) {
+                    self.save_ta(ta)?;
+                    saved += 1;
+                }
+            }
+        }
+        Ok((missing.len(), saved))
+    }
+}
+
+/// An encrypted, authenticated connection to the other device. Each frame is a big-endian u32
+/// length followed by the AES-GCM-SIV ciphertext, with a per-direction counter as the nonce.
+struct Channel {
+    stream: TcpStream,
+    tx_cipher: Aes256GcmSiv,
+    rx_cipher: Aes256GcmSiv,
+    sent: u64,
+    received: u64,
+}
+
+impl Channel {
+    fn new(
+        stream: TcpStream,
+        shared: SharedSecret,
+        host_key: &PublicKey,
+        joiner_key: &PublicKey,
+        is_host: bool,
+    ) -> Result<Self, Error> {
+        if !shared.was_contributory() {
+            return Err(Error::new(ErrorKind::InvalidData, "peer sent a low order key"));
+        }
+        let mut transcript = Vec::with_capacity(64);
+        transcript.extend_from_slice(host_key.as_bytes());
+        transcript.extend_from_slice(joiner_key.as_bytes());
+        let hk = Hkdf::<Sha256>::new(Some(HKDF_SALT), shared.as_bytes());
+        let mut okm = [0u8; 64];
+        hk.expand(&transcript, &mut okm).expect("64 bytes is a valid hkdf length");
+        let (to_joiner, to_host) = okm.split_at(32);
+        let (tx, rx) = if is_host { (to_joiner, to_host) } else { (to_host, to_joiner) };
+        Ok(Channel {
+            stream,
+            tx_cipher: Aes256GcmSiv::new_from_slice(tx).unwrap(),
+            rx_cipher: Aes256GcmSiv::new_from_slice(rx).unwrap(),
+            sent: 0,
+            received: 0,
+        })
+    }
+
+    fn send(&mut self, plaintext: &[u8]) -> Result<(), Error> {
+        let ciphertext = self
+            .tx_cipher
+            .encrypt(&frame_nonce(self.sent), plaintext)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "failed to encrypt sync frame"))?;
+        self.sent += 1;
+        let len: u32 = ciphertext.len().try_into().map_err(|_| Error::from(ErrorKind::InvalidData))?;
+        self.stream.write_all(&len.to_be_bytes())?;
+        self.stream.write_all(&ciphertext)
+    }
+
+    fn recv(&mut self) -> Result<Vec<u8>, Error> {
+        let mut len = [0u8; 4];
+        self.stream.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_FRAME_BYTES {
+            return Err(Error::new(ErrorKind::InvalidData, "sync frame too large"));
+        }
+        let mut ciphertext = vec![0u8; len];
+        self.stream.read_exact(&mut ciphertext)?;
+        let plaintext = self
+            .rx_cipher
+            .decrypt(&frame_nonce(self.received), ciphertext.as_ref())
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "sync frame failed to authenticate"))?;
+        self.received += 1;
+        Ok(plaintext)
+    }
+}
+
+/// A sync waits for an unmetered network, unless the user says to go ahead on this one
+fn confirm_if_metered(net: &net::NetManager, modals: &Modals) -> Result<(), Error> {
+    if !net.is_metered().unwrap_or(false) {
+        return Ok(());
+    }
+    modals.add_list_item(t!("tls.yes", locales::LANG)).expect("modal failed");
+    modals.add_list_item(t!("tls.no", locales::LANG)).expect("modal failed");
+    match modals.get_radiobutton(t!("tls.sync_metered_prompt", locales::LANG)) {
+        Ok(answer) if answer.as_str() == t!("tls.yes", locales::LANG) => Ok(()),
+        _ => Err(Error::new(ErrorKind::Interrupted, "deferred on a metered connection")),
+    }
+}
+
+/// Waits up to `timeout` for a connection on `listener`, so a host whose peer never shows up doesn't
+/// hold the port and the calling thread forever.
+fn accept_with_timeout(listener: &TcpListener, timeout: Duration) -> Result<(TcpStream, SocketAddr), Error> {
+    listener.set_nonblocking(true)?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                stream.set_nonblocking(false)?;
+                return Ok((stream, addr));
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(Error::new(ErrorKind::TimedOut, "no device joined the sync"));
+                }
+                std::thread::sleep(ACCEPT_POLL);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn frame_nonce(counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    *Nonce::from_slice(&nonce)
+}
+
+fn fingerprint(key: &PublicKey) -> [u8; FINGERPRINT_LEN] {
+    Sha256::digest(key.as_bytes())[..FINGERPRINT_LEN].try_into().unwrap()
+}
+
+/// The pairing code in base32, in groups of four characters for legibility
+fn pairing_code(key: &PublicKey, token: &[u8; TOKEN_LEN]) -> String {
+    let mut raw = fingerprint(key).to_vec();
+    raw.extend_from_slice(token);
+    let code = base32::encode(base32::Alphabet::RFC4648 { padding: false }, &raw);
+    code.as_bytes().chunks(4).map(|c| std::str::from_utf8(c).unwrap()).collect::<Vec<&str>>().join("-")
+}
+
+/// Splits a pairing code into the host key fingerprint and the token. Separators and case are
+/// ignored, as the code is typed by hand.
+fn parse_pairing_code(code: &str) -> Option<([u8; FINGERPRINT_LEN], [u8; TOKEN_LEN])> {
+    let code: String =
+        code.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_ascii_uppercase();
+    let raw = base32::decode(base32::Alphabet::RFC4648 { padding: false }, &code)?;
+    if raw.len() != FINGERPRINT_LEN + TOKEN_LEN {
+        return None;
+    }
+    Some((raw[..FINGERPRINT_LEN].try_into().unwrap(), raw[FINGERPRINT_LEN..].try_into().unwrap()))
+}
+
+/// Encodes trust anchors as a u32 count, then the subject, spki & name constraints of each anchor,
+/// each as a u32 length and the bytes. No name constraints are sent as an empty field.
+fn encode_anchors(anchors: &[OwnedTrustAnchor]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(anchors.len() as u32).to_be_bytes());
+    for ta in anchors {
+        for field in [&ta.subject[..], &ta.spki[..], ta.name_constraints.as_deref().unwrap_or(&[])] {
+            buf.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            buf.extend_from_slice(field);
+        }
+    }
+    buf
+}
+
+fn decode_anchors(mut bytes: &[u8]) -> Result<Vec<OwnedTrustAnchor>, Error> {
+    let count = take_u32(&mut bytes)?;
+    let mut anchors = Vec::new();
+    for _ in 0..count {
+        let subject = take_field(&mut bytes)?;
+        let spki = take_field(&mut bytes)?;
+        let name_constraints = take_field(&mut bytes)?;
+        anchors.push(OwnedTrustAnchor::from_subject_spki_name_constraints(
+            subject,
+            spki,
+            if name_constraints.is_empty() { None } else { Some(name_constraints) },
+        ));
+    }
+    Ok(anchors)
+}
+
+fn take_u32(bytes: &mut &[u8]) -> Result<u32, Error> {
+    if bytes.len() < 4 {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "truncated trust anchor list"));
+    }
+    let (value, rest) = bytes.split_at(4);
+    *bytes = rest;
+    Ok(u32::from_be_bytes(value.try_into().unwrap()))
+}
+
+fn take_field<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let len = take_u32(bytes)? as usize;
+    if bytes.len() < len {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "truncated trust anchor list"));
+    }
+    let (field, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(field)
+}
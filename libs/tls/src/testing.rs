@@ -0,0 +1,135 @@
+//! Offline fixtures for testing trust logic and certificate verifiers (feature `tls-test`).
+//!
+//! Every fixture chain descends from one root, and has fixed validity dates, so that it can be checked
+//! against a `MockClock` without a network, a device or a PDDB. The certificates are generated by
+//! `tests/fixtures/chains/generate.py`; all are valid from 2019 to 2034 unless noted.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use rustls::pki_types::{CertificateDer, UnixTime};
+use rustls::RootCertStore;
+
+use crate::clock::Clock;
+
+/// 2026-01-01, the time fixtures are checked at unless a test moves the clock
+pub const FIXTURE_NOW: u64 = 1_767_225_600;
+/// 2021-01-01, when the `expired()` leaf stopped being valid. It was valid during 2020.
+pub const EXPIRED_UNTIL: u64 = 1_609_459_200;
+/// 2030-01-01, when the `future()` leaf starts being valid
+pub const FUTURE_FROM: u64 = 1_893_456_000;
+
+pub const VALID_HOST: &str = "valid.example";
+pub const EXPIRED_HOST: &str = "expired.example";
+pub const FUTURE_HOST: &str = "future.example";
+/// The constrained intermediate may only issue for names under `allowed.example`
+pub const CONSTRAINED_ALLOWED_HOST: &str = "host.allowed.example";
+pub const CONSTRAINED_DENIED_HOST: &str = "host.denied.example";
+pub const LONG_HOST: &str = "long4.example";
+pub const TOO_LONG_HOST: &str = "long8.example";
+
+const ROOT: &[u8] = include_bytes!("../tests/fixtures/chains/root.der");
+const CA: &[u8] = include_bytes!("../tests/fixtures/chains/ca.der");
+const VALID_LEAF: &[u8] = include_bytes!("../tests/fixtures/chains/valid_leaf.der");
+const EXPIRED_LEAF: &[u8] = include_bytes!("../tests/fixtures/chains/expired_leaf.der");
+const FUTURE_LEAF: &[u8] = include_bytes!("../tests/fixtures/chains/future_leaf.der");
+const CONSTRAINED_CA: &[u8] = include_bytes!("../tests/fixtures/chains/constrained_ca.der");
+const CONSTRAINED_ALLOWED_LEAF: &[u8] =
+    include_bytes!("../tests/fixtures/chains/constrained_allowed_leaf.der");
+const CONSTRAINED_DENIED_LEAF: &[u8] = include_bytes!("../tests/fixtures/chains/constrained_denied_leaf.der");
+/// intermediates of the long chains, the first issued by the root and each following by the one before
+const LONG_CAS: [&[u8]; 8] = [
+    include_bytes!("../tests/fixtures/chains/long_ca1.der"),
+    include_bytes!("../tests/fixtures/chains/long_ca2.der"),
+    include_bytes!("../tests/fixtures/chains/long_ca3.der"),
+    include_bytes!("../tests/fixtures/chains/long_ca4.der"),
+    include_bytes!("../tests/fixtures/chains/long_ca5.der"),
+    include_bytes!("../tests/fixtures/chains/long_ca6.der"),
+    include_bytes!("../tests/fixtures/chains/long_ca7.der"),
+    include_bytes!("../tests/fixtures/chains/long_ca8.der"),
+];
+const LONG4_LEAF: &[u8] = include_bytes!("../tests/fixtures/chains/long4_leaf.der");
+const LONG8_LEAF: &[u8] = include_bytes!("../tests/fixtures/chains/long8_leaf.der");
+
+/// A clock that only moves when told to. Starts at `FIXTURE_NOW` by default.
+#[derive(Debug)]
+pub struct MockClock {
+    secs: AtomicU64,
+}
+
+impl MockClock {
+    /// A clock stopped at `secs` since the unix epoch
+    pub fn at(secs: u64) -> MockClock { MockClock { secs: AtomicU64::new(secs) } }
+
+    pub fn set(&self, secs: u64) { self.secs.store(secs, Ordering::SeqCst); }
+
+    pub fn advance(&self, secs: u64) { self.secs.fetch_add(secs, Ordering::SeqCst); }
+}
+
+impl Default for MockClock {
+    fn default() -> Self { MockClock::at(FIXTURE_NOW) }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> UnixTime {
+        UnixTime::since_unix_epoch(Duration::from_secs(self.secs.load(Ordering::SeqCst)))
+    }
+}
+
+/// A certificate chain, as a host would offer it, and the root it descends from
+#[derive(Debug, Clone)]
+pub struct Chain {
+    pub root: CertificateDer<'static>,
+    /// nearest the end-entity first
+    pub intermediates: Vec<CertificateDer<'static>>,
+    pub leaf: CertificateDer<'static>,
+}
+
+impl Chain {
+    fn new(intermediates: &[&'static [u8]], leaf: &'static [u8]) -> Chain {
+        Chain {
+            root: CertificateDer::from(ROOT),
+            intermediates: intermediates.iter().map(|&der| CertificateDer::from(der)).collect(),
+            leaf: CertificateDer::from(leaf),
+        }
+    }
+
+    /// A root store trusting the fixture root, and nothing else
+    pub fn roots(&self) -> RootCertStore {
+        let mut roots = RootCertStore::empty();
+        roots.add(self.root.clone()).expect("fixture root is a valid trust anchor");
+        roots
+    }
+
+    /// The certificates in the order a host offers them: the end-entity first
+    pub fn offered(&self) -> Vec<CertificateDer<'static>> {
+        std::iter::once(self.leaf.clone()).chain(self.intermediates.iter().cloned()).collect()
+    }
+}
+
+/// root, intermediate, and a leaf for `VALID_HOST`
+pub fn valid() -> Chain { Chain::new(&[CA], VALID_LEAF) }
+
+/// as `valid()`, with a leaf for `EXPIRED_HOST` that was only valid during 2020
+pub fn expired() -> Chain { Chain::new(&[CA], EXPIRED_LEAF) }
+
+/// as `valid()`, with a leaf for `FUTURE_HOST` that is only valid from 2030
+pub fn future() -> Chain { Chain::new(&[CA], FUTURE_LEAF) }
+
+/// an intermediate with a name constraint permitting `allowed.example`, and a leaf within it
+pub fn constrained_allowed() -> Chain { Chain::new(&[CONSTRAINED_CA], CONSTRAINED_ALLOWED_LEAF) }
+
+/// the constrained intermediate, with a leaf for a name outside of its constraint
+pub fn constrained_denied() -> Chain { Chain::new(&[CONSTRAINED_CA], CONSTRAINED_DENIED_LEAF) }
+
+/// four intermediates between the root and the leaf, within what webpki accepts
+pub fn long() -> Chain {
+    let intermediates: Vec<&'static [u8]> = LONG_CAS[..4].iter().rev().copied().collect();
+    Chain::new(&intermediates, LONG4_LEAF)
+}
+
+/// eight intermediates between the root and the leaf, more than webpki will build a path through
+pub fn too_long() -> Chain {
+    let intermediates: Vec<&'static [u8]> = LONG_CAS.iter().rev().copied().collect();
+    Chain::new(&intermediates, LONG8_LEAF)
+}
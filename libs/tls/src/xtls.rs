@@ -1,6 +1,5 @@
-use std::{convert::TryFrom, fmt::Debug, io, net::TcpStream, result::Result, sync::Arc};
+use std::{fmt::Debug, io, net::TcpStream, result::Result, sync::Arc};
 
-use rustls::pki_types::ServerName;
 use rustls::{ClientConnection, StreamOwned};
 use ureq::{ReadWrite, Response};
 
@@ -15,7 +14,7 @@ pub struct TlsConnector {}
 impl ureq::TlsConnector for TlsConnector {
     fn connect(&self, dns_name: &str, mut io: Box<dyn ReadWrite>) -> Result<Box<dyn ReadWrite>, ureq::Error> {
         log::info!("Commencing tls connection setup");
-        match ServerName::try_from(dns_name.to_owned()) {
+        match crate::name::server_name(dns_name) {
             Ok(server_name) => {
                 loop {
                     // refresh rustls client config with current root_store
@@ -87,6 +86,49 @@ impl ureq::TlsConnector for TlsConnector {
     }
 }
 
+/// Set up tls with rustls::ClientConnection trusting ONLY the pinned
+/// trust-anchors - and never prompt the user: an untrusted certificate
+/// chain is simply an error. Suitable for unattended background services.
+pub struct PinnedTlsConnector {
+    pub pins: Vec<String>,
+}
+
+impl ureq::TlsConnector for PinnedTlsConnector {
+    fn connect(&self, dns_name: &str, mut io: Box<dyn ReadWrite>) -> Result<Box<dyn ReadWrite>, ureq::Error> {
+        log::info!("Commencing pinned tls connection setup");
+        match crate::name::server_name(dns_name) {
+            Ok(server_name) => {
+                let config = rustls::ClientConfig::builder()
+                    .with_root_certificates(Tls::new().pinned_root_store(&self.pins))
+                    .with_no_client_auth();
+                match rustls::ClientConnection::new(Arc::new(config), server_name) {
+                    Ok(mut connection) => match connection.complete_io(&mut io) {
+                        Ok(_) if connection.peer_certificates().is_some() => {
+                            return Ok(Box::new(TlsStream(StreamOwned::new(connection, io))));
+                        }
+                        Ok(_) => log::warn!("no peer certificates offered"),
+                        Err(e) => log::warn!("{e}"),
+                    },
+                    Err(e) => log::warn!("{e}"),
+                }
+                log::warn!("failed to establish pinned tls connection");
+                Err(ureq::Error::Status(
+                    526,
+                    Response::new(526, "tls", "certificate chain does not match pins").unwrap(),
+                ))
+            }
+            Err(e) => {
+                log::warn!("failed to convert dns_name into a valid server name: {e}");
+                Err(ureq::Error::Status(
+                    400,
+                    Response::new(526, "http", "failed to convert dns_name into a valid server name")
+                        .unwrap(),
+                ))
+            }
+        }
+    }
+}
+
 // TlsStream wraps StreamOwned and implements ReadWrite for use in TlsConnect::connect()
 #[derive(Debug)]
 pub struct TlsStream(StreamOwned<ClientConnection, Box<dyn ReadWrite>>);
@@ -0,0 +1,118 @@
+#!/usr/bin/env python3
+"""Generates the certificate chain fixtures used by `tls::testing`.
+
+Every certificate has fixed validity dates, so that tests checking them against `testing::FIXTURE_NOW`
+(2026-01-01) give the same answer on any day. Keys are fresh on every run, so the files change, but
+what they test does not. Needs the `cryptography` package:
+
+    python3 generate.py
+"""
+
+import datetime
+import os
+
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec
+from cryptography.x509.oid import NameOID
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+VALID_FROM = datetime.datetime(2019, 1, 1, tzinfo=datetime.timezone.utc)
+VALID_UNTIL = datetime.datetime(2034, 1, 1, tzinfo=datetime.timezone.utc)
+# the expired leaf was only valid during 2020
+EXPIRED_FROM = datetime.datetime(2020, 1, 1, tzinfo=datetime.timezone.utc)
+EXPIRED_UNTIL = datetime.datetime(2021, 1, 1, tzinfo=datetime.timezone.utc)
+# and the future leaf only becomes valid in 2030
+FUTURE_FROM = datetime.datetime(2030, 1, 1, tzinfo=datetime.timezone.utc)
+
+# the longest chain; leaves are issued at depth 4 (accepted) and 8 (too deep for webpki)
+LONG_DEPTH = 8
+
+
+def name(cn):
+    return x509.Name([x509.NameAttribute(NameOID.ORGANIZATION_NAME, "Xous test fixtures"),
+                      x509.NameAttribute(NameOID.COMMON_NAME, cn)])
+
+
+def issue(subject, key, issuer, issuer_key, ca, hosts=None, permitted=None,
+          valid_from=VALID_FROM, valid_until=VALID_UNTIL):
+    builder = (x509.CertificateBuilder()
+               .subject_name(subject)
+               .issuer_name(issuer)
+               .public_key(key.public_key())
+               .serial_number(x509.random_serial_number())
+               .not_valid_before(valid_from)
+               .not_valid_after(valid_until)
+               .add_extension(x509.BasicConstraints(ca=ca, path_length=None), critical=True)
+               .add_extension(x509.SubjectKeyIdentifier.from_public_key(key.public_key()), critical=False))
+    if ca:
+        builder = builder.add_extension(
+            x509.KeyUsage(digital_signature=False, content_commitment=False, key_encipherment=False,
+                          data_encipherment=False, key_agreement=False, key_cert_sign=True,
+                          crl_sign=True, encipher_only=False, decipher_only=False), critical=True)
+    else:
+        builder = builder.add_extension(
+            x509.ExtendedKeyUsage([x509.oid.ExtendedKeyUsageOID.SERVER_AUTH]), critical=False)
+    if hosts:
+        builder = builder.add_extension(
+            x509.SubjectAlternativeName([x509.DNSName(h) for h in hosts]), critical=False)
+    if permitted:
+        builder = builder.add_extension(
+            x509.NameConstraints(permitted_subtrees=[x509.DNSName(p) for p in permitted],
+                                 excluded_subtrees=None), critical=True)
+    return builder.sign(issuer_key, hashes.SHA256())
+
+
+def key():
+    return ec.generate_private_key(ec.SECP256R1())
+
+
+def write(filename, cert):
+    with open(os.path.join(HERE, filename), "wb") as f:
+        f.write(cert.public_bytes(serialization.Encoding.DER))
+
+
+def main():
+    root_key = key()
+    root = issue(name("Fixture Root"), root_key, name("Fixture Root"), root_key, True)
+    write("root.der", root)
+
+    ca_key = key()
+    ca = issue(name("Fixture Intermediate"), ca_key, root.subject, root_key, True)
+    write("ca.der", ca)
+    write("valid_leaf.der",
+          issue(name("valid.example"), key(), ca.subject, ca_key, False, hosts=["valid.example"]))
+    write("expired_leaf.der",
+          issue(name("expired.example"), key(), ca.subject, ca_key, False, hosts=["expired.example"],
+                valid_from=EXPIRED_FROM, valid_until=EXPIRED_UNTIL))
+    write("future_leaf.der",
+          issue(name("future.example"), key(), ca.subject, ca_key, False, hosts=["future.example"],
+                valid_from=FUTURE_FROM))
+
+    constrained_key = key()
+    constrained = issue(name("Fixture Constrained Intermediate"), constrained_key, root.subject, root_key,
+                        True, permitted=["allowed.example"])
+    write("constrained_ca.der", constrained)
+    write("constrained_allowed_leaf.der",
+          issue(name("host.allowed.example"), key(), constrained.subject, constrained_key, False,
+                hosts=["host.allowed.example"]))
+    write("constrained_denied_leaf.der",
+          issue(name("host.denied.example"), key(), constrained.subject, constrained_key, False,
+                hosts=["host.denied.example"]))
+
+    issuer, issuer_key = root, root_key
+    for depth in range(1, LONG_DEPTH + 1):
+        long_key = key()
+        long_ca = issue(name("Fixture Long Intermediate {}".format(depth)), long_key, issuer.subject,
+                        issuer_key, True)
+        write("long_ca{}.der".format(depth), long_ca)
+        if depth in (4, LONG_DEPTH):
+            host = "long{}.example".format(depth)
+            write("long{}_leaf.der".format(depth),
+                  issue(name(host), key(), long_ca.subject, long_key, False, hosts=[host]))
+        issuer, issuer_key = long_ca, long_key
+
+
+if __name__ == "__main__":
+    main()
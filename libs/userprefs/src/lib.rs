@@ -45,11 +45,19 @@ pub struct UserPrefs {
     pub autobacklight_timeout: u64,
     pub autosleep_timeout: u64,
     pub reboot_on_autosleep: bool,
+    /// Minutes without input before the screensaver comes up; 0 turns it off.
+    pub screensaver_timeout: u64,
     pub audio_enabled: bool,
     pub earpiece_volume: u32,
     pub headset_volume: u32,
     pub autotype_rate: usize,
     pub lefty_mode: bool,
+    /// Whether FIDO signatures carry the (randomly incremented) signature counter, or a zero counter.
+    pub fido_signature_counter: bool,
+    /// IANA name of the display time zone, e.g. "Europe/Paris". Empty if a fixed offset is used instead.
+    pub timezone_name: String,
+    /// Language code of the display language, e.g. "fr". Empty to use the build's default language.
+    pub language: String,
 }
 
 pub struct Manager {
@@ -0,0 +1,427 @@
+//! A WebSocket client (RFC 6455), over tcp (`ws://`) or tls (`wss://`).
+//!
+//! The framing, masking, fragmented message reassembly and replies to pings from the server are done by
+//! `tungstenite`. This crate adds the connection itself, with tls from the `tls` library (and so the
+//! user's own trusted certificates), and a keepalive: when the link has been quiet for
+//! `Config::ping_interval` a ping is sent, and if nothing comes back within `Config::pong_timeout` the
+//! connection is declared dead.
+//!
+//! ```no_run
+//! let mut ws = websocket::WebSocket::connect("wss://example.org/socket")?;
+//! ws.send_text("hello")?;
+//! loop {
+//!     match ws.recv()? {
+//!         Some(websocket::Message::Text(text)) => log::info!("received {text}"),
+//!         Some(websocket::Message::Binary(data)) => log::info!("received {} bytes", data.len()),
+//!         None => (), // nothing arrived within Config::poll_interval: do some other work
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use rustls::{ClientConnection, StreamOwned};
+use tungstenite::HandshakeError;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::{HeaderName, HeaderValue};
+use tungstenite::protocol::WebSocketConfig;
+
+/// How the connection is kept alive and how much it may hold in memory
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// A ping is sent when nothing has been received for this long. None to never ping.
+    pub ping_interval: Option<Duration>,
+    /// The connection is dead if nothing is received this long after a ping
+    pub pong_timeout: Duration,
+    /// The longest `WebSocket::recv()` blocks before returning `Ok(None)`
+    pub poll_interval: Duration,
+    /// The longest the opening handshake (tcp, tls and http upgrade) may take
+    pub connect_timeout: Duration,
+    /// The largest message accepted, after the reassembly of its fragments
+    pub max_message_size: usize,
+    /// Sub-protocols offered to the server, in order of preference (`Sec-WebSocket-Protocol`)
+    pub protocols: Vec<String>,
+    /// Extra headers for the http upgrade request (ie `Authorization`)
+    pub headers: Vec<(String, String)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            ping_interval: Some(Duration::from_secs(30)),
+            pong_timeout: Duration::from_secs(10),
+            poll_interval: Duration::from_secs(1),
+            connect_timeout: Duration::from_secs(20),
+            max_message_size: 1 << 20,
+            protocols: Vec::new(),
+            headers: Vec::new(),
+        }
+    }
+}
+
+/// A complete message from the server. Control frames (ping, pong, close) are handled internally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// The tcp connection under the WebSocket, with or without tls
+pub enum Stream {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Stream {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Stream::Plain(sock) => sock,
+            Stream::Tls(stream) => &stream.sock,
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(sock) => sock.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(sock) => sock.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Plain(sock) => sock.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// The stream under the WebSocket. While the connection is being opened, every read and write may only
+/// take the time left until `deadline`, so that a server that trickles its reply can't stretch the
+/// opening handshake past `Config::connect_timeout`.
+struct Bounded {
+    stream: Stream,
+    deadline: Option<Instant>,
+}
+
+impl Bounded {
+    fn bound(&mut self) -> std::io::Result<()> {
+        if let Some(deadline) = self.deadline {
+            let remaining = remaining(deadline)?;
+            self.stream.tcp().set_read_timeout(Some(remaining))?;
+            self.stream.tcp().set_write_timeout(Some(remaining))?;
+        }
+        Ok(())
+    }
+}
+
+impl Read for Bounded {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.bound()?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Bounded {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.bound()?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> { self.stream.flush() }
+}
+
+/// The time left until `deadline`, or a `TimedOut` error if it has passed
+fn remaining(deadline: Instant) -> Result<Duration, Error> {
+    deadline
+        .checked_duration_since(Instant::now())
+        .filter(|remaining| !remaining.is_zero())
+        .ok_or_else(|| Error::new(ErrorKind::TimedOut, "websocket connection timed out"))
+}
+
+/// Connects to the first address of `host` that answers before `deadline`
+fn tcp_connect(host: &str, port: u16, deadline: Instant) -> Result<TcpStream, Error> {
+    let mut last_error = Error::new(ErrorKind::NotFound, "host has no address");
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, remaining(deadline)?) {
+            Ok(sock) => return Ok(sock),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+pub struct WebSocket {
+    ws: tungstenite::WebSocket<Bounded>,
+    config: Config,
+    /// when anything (data or control) was last received
+    last_rx: Instant,
+    /// when the unanswered ping was sent, if any
+    ping_sent: Option<Instant>,
+    /// the sub-protocol chosen by the server, if any
+    protocol: Option<String>,
+}
+
+impl WebSocket {
+    /// Connect to a `ws://` or `wss://` url with the default `Config`
+    pub fn connect(url: &str) -> Result<WebSocket, Error> { WebSocket::connect_with(url, Config::default()) }
+
+    /// Connect to a `ws://` or `wss://` url
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - the WebSocket end-point (ie wss://example.org/socket)
+    /// * `config` - keepalive, size limits and extra headers
+    pub fn connect_with(url: &str, config: Config) -> Result<WebSocket, Error> {
+        let parsed = url::Url::parse(url).map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+        let host = parsed.host_str().ok_or_else(|| Error::new(ErrorKind::InvalidInput, "no host in url"))?;
+        // the tcp connection, the tls handshake and the http upgrade all come out of connect_timeout
+        let deadline = Instant::now() + config.connect_timeout;
+        let stream = match parsed.scheme() {
+            "ws" => Stream::Plain(tcp_connect(host, parsed.port().unwrap_or(80), deadline)?),
+            "wss" => {
+                let port = parsed.port().unwrap_or(443);
+                let (result, diag) = tls::Tls::new().connect_timeout(host, port, remaining(deadline)?);
+                match result {
+                    Ok(stream) => Stream::Tls(Box::new(stream)),
+                    Err(e) => {
+                        log::warn!("{}", diag);
+                        return Err(e);
+                    }
+                }
+            }
+            scheme => {
+                return Err(Error::new(ErrorKind::InvalidInput, format!("not a websocket url: {}", scheme)));
+            }
+        };
+
+        let mut request = url.into_client_request().map_err(to_io_error)?;
+        if !config.protocols.is_empty() {
+            let protocols = HeaderValue::from_str(&config.protocols.join(", "))
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+            request.headers_mut().insert("Sec-WebSocket-Protocol", protocols);
+        }
+        for (name, value) in config.headers.iter() {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+            request.headers_mut().insert(name, value);
+        }
+        let mut ws_config = WebSocketConfig::default();
+        ws_config.max_message_size = Some(config.max_message_size);
+        ws_config.max_frame_size = Some(config.max_message_size);
+
+        let stream = Bounded { stream, deadline: Some(deadline) };
+        let (mut ws, response) = match tungstenite::client_with_config(request, stream, Some(ws_config)) {
+            Ok(connected) => connected,
+            Err(HandshakeError::Failure(e)) => return Err(to_io_error(e)),
+            Err(HandshakeError::Interrupted(_)) => {
+                return Err(Error::new(ErrorKind::TimedOut, "websocket handshake timed out"));
+            }
+        };
+        let protocol = response
+            .headers()
+            .get("Sec-WebSocket-Protocol")
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        ws.get_mut().deadline = None;
+        ws.get_ref().stream.tcp().set_write_timeout(None)?;
+        ws.get_ref().stream.tcp().set_read_timeout(Some(config.poll_interval))?;
+        log::info!("websocket connected to {} ({})", url, response.status());
+        Ok(WebSocket { ws, config, last_rx: Instant::now(), ping_sent: None, protocol })
+    }
+
+    /// The sub-protocol chosen by the server from `Config::protocols`, if any
+    pub fn protocol(&self) -> Option<&str> { self.protocol.as_deref() }
+
+    pub fn send_text(&mut self, text: &str) -> Result<(), Error> {
+        self.ws.send(tungstenite::Message::Text(text.to_string())).map_err(to_io_error)
+    }
+
+    pub fn send_binary(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.ws.send(tungstenite::Message::Binary(data.to_vec())).map_err(to_io_error)
+    }
+
+    /// Wait up to `Config::poll_interval` for the next complete message
+    ///
+    /// Returns `Ok(None)` if no message arrived in time, and an error of kind `ConnectionAborted` once
+    /// the server has closed the connection, or `TimedOut` if it stopped answering pings.
+    pub fn recv(&mut self) -> Result<Option<Message>, Error> {
+        let start = Instant::now();
+        loop {
+            match self.ws.read() {
+                Ok(message) => {
+                    self.last_rx = Instant::now();
+                    self.ping_sent = None;
+                    match message {
+                        tungstenite::Message::Text(text) => return Ok(Some(Message::Text(text))),
+                        tungstenite::Message::Binary(data) => return Ok(Some(Message::Binary(data))),
+                        tungstenite::Message::Close(frame) => {
+                            log::info!("websocket closed by server: {:?}", frame);
+                            // tungstenite replies to the close, and reports ConnectionClosed on the next read
+                        }
+                        // a reply to a ping is queued by tungstenite, and sent on the next read or write
+                        _ => (),
+                    }
+                }
+                Err(tungstenite::Error::Io(e))
+                    if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut =>
+                {
+                    self.keepalive()?;
+                    if start.elapsed() >= self.config.poll_interval {
+                        return Ok(None);
+                    }
+                }
+                Err(e) => return Err(to_io_error(e)),
+            }
+        }
+    }
+
+    /// Close the connection with a close handshake, if the server is still listening
+    pub fn close(mut self) {
+        self.ws.close(None).ok();
+        // give the server a moment to acknowledge the close before the tcp connection is dropped
+        let deadline = Instant::now() + self.config.poll_interval;
+        while Instant::now() < deadline {
+            match self.ws.read() {
+                Ok(_) => (),
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => (),
+                Err(_) => break,
+            }
+        }
+    }
+
+    fn keepalive(&mut self) -> Result<(), Error> {
+        let interval = match self.config.ping_interval {
+            Some(interval) => interval,
+            None => return Ok(()),
+        };
+        match self.ping_sent {
+            Some(sent) if sent.elapsed() >= self.config.pong_timeout => {
+                log::warn!("websocket peer stopped answering pings");
+                Err(Error::new(ErrorKind::TimedOut, "no reply to websocket ping"))
+            }
+            Some(_) => Ok(()),
+            None if self.last_rx.elapsed() >= interval => {
+                log::debug!("websocket idle, sending ping");
+                self.ping_sent = Some(Instant::now());
+                self.ws.send(tungstenite::Message::Ping(Vec::new())).map_err(to_io_error)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+fn to_io_error(e: tungstenite::Error) -> Error {
+    match e {
+        tungstenite::Error::Io(e) => e,
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+            Error::new(ErrorKind::ConnectionAborted, "websocket closed")
+        }
+        tungstenite::Error::Capacity(e) => Error::new(ErrorKind::InvalidData, e.to_string()),
+        tungstenite::Error::Protocol(e) => Error::new(ErrorKind::InvalidData, e.to_string()),
+        tungstenite::Error::Utf8 => Error::new(ErrorKind::InvalidData, "websocket text is not utf-8"),
+        tungstenite::Error::Url(e) => Error::new(ErrorKind::InvalidInput, e.to_string()),
+        tungstenite::Error::Http(response) => Error::new(
+            ErrorKind::ConnectionRefused,
+            format!("websocket upgrade refused: {}", response.status()),
+        ),
+        e => Error::new(ErrorKind::Other, e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    fn quick() -> Config { Config { connect_timeout: Duration::from_millis(500), ..Config::default() } }
+
+    /// A local server that runs `serve` on the first connection made to it
+    fn server(serve: impl FnOnce(TcpStream) + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            if let Ok((sock, _)) = listener.accept() {
+                serve(sock);
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn only_websocket_urls() {
+        assert_eq!(WebSocket::connect("ftp://example.org/").err().unwrap().kind(), ErrorKind::InvalidInput);
+        assert_eq!(WebSocket::connect("not a url").err().unwrap().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let url = server(|sock| {
+            let mut ws = tungstenite::accept(sock).unwrap();
+            let message = ws.read().unwrap();
+            ws.send(message).unwrap();
+        });
+        let mut ws = WebSocket::connect_with(&url, quick()).unwrap();
+        ws.send_text("hello").unwrap();
+        let mut reply = None;
+        for _ in 0..5 {
+            reply = ws.recv().unwrap();
+            if reply.is_some() {
+                break;
+            }
+        }
+        assert_eq!(reply, Some(Message::Text("hello".to_string())));
+    }
+
+    #[test]
+    fn silent_server_times_out_the_upgrade() {
+        let url = server(|sock| {
+            // hold the connection open without answering
+            std::thread::sleep(Duration::from_secs(3));
+            drop(sock);
+        });
+        let start = Instant::now();
+        let e = WebSocket::connect_with(&url, quick()).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(2), "took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn trickling_server_can_not_stretch_the_upgrade() {
+        let url = server(|mut sock| {
+            for byte in b"HTTP/1.1 101 Switching Protocols\r\n".iter() {
+                std::thread::sleep(Duration::from_millis(100));
+                if sock.write_all(&[*byte]).is_err() {
+                    break;
+                }
+            }
+        });
+        let start = Instant::now();
+        assert!(WebSocket::connect_with(&url, quick()).is_err());
+        assert!(start.elapsed() < Duration::from_secs(2), "took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn errors_keep_their_meaning() {
+        assert_eq!(to_io_error(tungstenite::Error::ConnectionClosed).kind(), ErrorKind::ConnectionAborted);
+        assert_eq!(to_io_error(tungstenite::Error::Utf8).kind(), ErrorKind::InvalidData);
+        let timed_out = tungstenite::Error::Io(Error::from(ErrorKind::TimedOut));
+        assert_eq!(to_io_error(timed_out).kind(), ErrorKind::TimedOut);
+    }
+}
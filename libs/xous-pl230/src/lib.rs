@@ -59,6 +59,57 @@ impl Pl230 {
     }
 }
 
+/// The most transfers that one DMA cycle can do
+pub const MAX_CYCLE_TRANSFERS: usize = 1024;
+
+impl Pl230 {
+    /// Runs one auto-request cycle of word transfers on `channel`, and waits for it to finish.
+    ///
+    /// The caller keeps the data cache coherent with the transfer.
+    ///
+    /// # Arguments
+    ///
+    /// * `cc` - the control structure, whose physical address has been set with `CTRLBASEPTR`
+    /// * `src` - physical address of the first source word; with `fill`, the one word that is copied to every
+    ///   destination word
+    /// * `dst` - physical address of the first destination word
+    /// * `words` - the number of words, at most `MAX_CYCLE_TRANSFERS`
+    pub fn mem_cycle(
+        &mut self,
+        cc: &mut ControlChannels,
+        channel: usize,
+        src: u32,
+        dst: u32,
+        words: usize,
+        fill: bool,
+    ) {
+        assert!(words > 0 && words <= MAX_CYCLE_TRANSFERS, "transfer count out of range");
+        let last = (words as u32 - 1) * 4;
+        cc.channels[channel].src_end_ptr = if fill { src } else { src + last };
+        cc.channels[channel].dst_end_ptr = dst + last;
+        let mut control = DmaChanControl(0);
+        control.set_src_size(DmaWidth::Word as u32);
+        control.set_src_inc(if fill { DmaWidth::NoInc as u32 } else { DmaWidth::Word as u32 });
+        control.set_dst_size(DmaWidth::Word as u32);
+        control.set_dst_inc(DmaWidth::Word as u32);
+        control.set_r_power(ArbitrateAfter::Xfer1024 as u32);
+        control.set_n_minus_1(words as u32 - 1);
+        control.set_cycle_ctrl(DmaCycleControl::AutoRequest as u32);
+        // safety: the control word is read back by the DMA engine, so it must not be cached in a register
+        unsafe { core::ptr::write_volatile(&mut cc.channels[channel].control, control.0) };
+
+        let mask = 1 << channel;
+        self.csr.wo(utra::pl230::CHNLREQMASKSET, mask);
+        self.csr.wo(utra::pl230::CHNLENABLESET, mask);
+        self.csr.wo(utra::pl230::CHNLSWREQUEST, mask);
+        // the engine clears the channel's enable once the cycle is done
+        while self.csr.r(utra::pl230::CHNLENABLESET) & mask != 0 {
+            #[cfg(target_os = "xous")]
+            xous::yield_slice();
+        }
+    }
+}
+
 #[repr(u32)]
 pub enum DmaWidth {
     Byte = 0b00,
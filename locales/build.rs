@@ -84,6 +84,8 @@ fn generate_code(translations: Translations) -> proc_macro2::TokenStream {
         let mut langs = Vec::<TokenStream>::new();
         let mut needs_interpolation = false;
         let mut vars = Vec::new();
+        // the language is selected at runtime, so a key that lacks a translation falls back to english
+        let mut fallback = quote! { e => panic!("Missing language: {}", e) };
         for (lang, tr) in trs {
             let lang_vars = extract_vars(&tr);
             needs_interpolation = lang_vars.len() > 0;
@@ -95,10 +97,16 @@ fn generate_code(translations: Translations) -> proc_macro2::TokenStream {
                 langs.push(quote! {
                     #lang => #tr#(.replace(#lang_vars, $#idents))*,
                 });
+                if lang == "en" {
+                    fallback = quote! { _ => #tr#(.replace(#lang_vars, $#idents))* };
+                }
             } else {
                 langs.push(quote! {
                     #lang => #tr,
                 });
+                if lang == "en" {
+                    fallback = quote! { _ => #tr };
+                }
             }
         }
 
@@ -110,7 +118,7 @@ fn generate_code(translations: Translations) -> proc_macro2::TokenStream {
                 (#key, #(#vars_ident: $#vars_ident:expr, )*$lang:expr) => {
                     match $lang.as_ref() {
                         #(#langs)*
-                        e => panic!("Missing language: {}", e)
+                        #fallback
                     }
                 };
             });
@@ -124,7 +132,7 @@ fn generate_code(translations: Translations) -> proc_macro2::TokenStream {
                 (#key, $lang:expr) => {
                     match $lang.as_ref() {
                         #(#langs)*
-                        e => panic!("Missing language: {}", e)
+                        #fallback
                     }
                 };
             });
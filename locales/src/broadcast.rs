@@ -0,0 +1,78 @@
+//! Keeps every process in step with the language the user selects. The status service persists the
+//! selection and broadcasts changes to the processes that follow the language; each follower runs a
+//! small thread that switches `LANG` for its own process, and then tells the process's servers to
+//! re-render, if they asked to be told.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use xous::{Message, CID};
+
+/// Name of the language broadcast server, hosted by the status service
+pub const SERVER_NAME_LANGUAGE: &str = "_Language broadcast_";
+
+/// Subscribes the SID in the four arguments to language changes. A blocking scalar that returns the
+/// index of the active language in `LANGUAGES`, or `usize::MAX` if the user hasn't chosen one yet.
+pub const LANGUAGE_OP_SUBSCRIBE: usize = 0;
+/// Sent to subscribers with the index of the new language in `LANGUAGES`
+pub const LANGUAGE_OP_CHANGED: usize = 1;
+/// Selects the language by its index in `LANGUAGES`. Only accepted from the status service itself.
+pub const LANGUAGE_OP_SET: usize = 2;
+
+static FOLLOWING: AtomicBool = AtomicBool::new(false);
+/// servers in this process to notify of a language change, and the opcode to notify them with
+static RENDERERS: Mutex<Vec<(CID, usize)>> = Mutex::new(Vec::new());
+
+/// Follows the language selected by the user. If `notify` is given, a scalar message with the opcode
+/// is sent to the connection after each language change, so the server can re-render its strings.
+/// Can be called any number of times in a process; only the first call starts the listener.
+pub fn follow_language(notify: Option<(CID, usize)>) {
+    if let Some(renderer) = notify {
+        RENDERERS.lock().unwrap().push(renderer);
+    }
+    if FOLLOWING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    std::thread::spawn(|| {
+        let xns = xous_names::XousNames::new().unwrap();
+        let sid = xous::create_server().unwrap();
+        let conn = xns
+            .request_connection_blocking(SERVER_NAME_LANGUAGE)
+            .expect("can't connect to language broadcast");
+        let s = sid.to_array();
+        if let Ok(xous::Result::Scalar1(index)) = xous::send_message(
+            conn,
+            Message::new_blocking_scalar(
+                LANGUAGE_OP_SUBSCRIBE,
+                s[0] as usize,
+                s[1] as usize,
+                s[2] as usize,
+                s[3] as usize,
+            ),
+        ) {
+            switch_to(index);
+        }
+        loop {
+            let msg = xous::receive_message(sid).unwrap();
+            if msg.body.id() == LANGUAGE_OP_CHANGED {
+                if let Some(scalar) = msg.body.scalar_message() {
+                    switch_to(scalar.arg1);
+                }
+            }
+        }
+    });
+}
+
+fn switch_to(index: usize) {
+    let lang = match crate::LANGUAGES.get(index) {
+        Some(lang) => lang,
+        None => return,
+    };
+    if crate::LANG.get() == *lang {
+        return;
+    }
+    crate::set_lang(lang);
+    for &(cid, opcode) in RENDERERS.lock().unwrap().iter() {
+        xous::send_message(cid, Message::new_scalar(opcode, 0, 0, 0, 0)).ok();
+    }
+}
@@ -0,0 +1,17 @@
+// The language a process starts out in, until the language selected by the user is known. Selected
+// with the `lang-*` features, or overridden by `xtask`.
+#[cfg(feature="lang-ja")]
+pub const DEFAULT_LANG: &str = "ja";
+#[cfg(feature="lang-zh")]
+pub const DEFAULT_LANG: &str = "zh";
+#[cfg(feature="lang-en-tts")]
+pub const DEFAULT_LANG: &str = "en-tts";
+#[cfg(feature="lang-fr")]
+pub const DEFAULT_LANG: &str = "fr";
+#[cfg(not(any(
+    feature="lang-ja",
+    feature="lang-zh",
+    feature="lang-en-tts",
+    feature="lang-fr"
+)))]
+pub const DEFAULT_LANG: &str = "en";
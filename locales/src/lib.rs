@@ -1,6 +1,7 @@
-#![no_std]
-
+mod default_lang;
 pub mod locale;
-pub use locale::LANG;
+pub use locale::{lang_index, set_lang, DEFAULT_LANG, LANG, LANGUAGES};
+pub mod broadcast;
+pub use broadcast::follow_language;
 
 pub mod generated;
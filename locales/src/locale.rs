@@ -1,15 +1,49 @@
-#[cfg(feature="lang-ja")]
-pub const LANG: &str = "ja";
-#[cfg(feature="lang-zh")]
-pub const LANG: &str = "zh";
-#[cfg(feature="lang-en-tts")]
-pub const LANG: &str = "en-tts";
-#[cfg(feature="lang-fr")]
-pub const LANG: &str = "fr";
-#[cfg(not(any(
-    feature="lang-ja",
-    feature="lang-zh",
-    feature="lang-en-tts",
-    feature="lang-fr"
-)))]
-pub const LANG: &str = "en";
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub use crate::default_lang::DEFAULT_LANG;
+
+/// Every language that has translations, by language code
+pub const LANGUAGES: [&str; 5] = ["en", "en-tts", "fr", "ja", "zh"];
+
+/// index into `LANGUAGES` of the active language, or `usize::MAX` for `DEFAULT_LANG`
+static CURRENT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// The active language of this process. It dereferences to the language code, so it can be passed to
+/// `t!` and compared against language codes like a `&str`.
+pub struct Lang;
+pub static LANG: Lang = Lang;
+
+impl Lang {
+    pub fn get(&self) -> &'static str {
+        LANGUAGES.get(CURRENT.load(Ordering::Relaxed)).copied().unwrap_or(DEFAULT_LANG)
+    }
+}
+impl AsRef<str> for Lang {
+    fn as_ref(&self) -> &str { self.get() }
+}
+impl core::ops::Deref for Lang {
+    type Target = str;
+
+    fn deref(&self) -> &str { self.get() }
+}
+impl PartialEq<&str> for Lang {
+    fn eq(&self, other: &&str) -> bool { self.get() == *other }
+}
+impl core::fmt::Display for Lang {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result { f.write_str(self.get()) }
+}
+
+/// Index of `lang` in `LANGUAGES`
+pub fn lang_index(lang: &str) -> Option<usize> { LANGUAGES.iter().position(|&l| l == lang) }
+
+/// Switches this process to `lang`. Returns `false`, leaving the language as it was, if there are
+/// no translations for `lang`. Other processes are switched through the language broadcast.
+pub fn set_lang(lang: &str) -> bool {
+    match lang_index(lang) {
+        Some(index) => {
+            CURRENT.store(index, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
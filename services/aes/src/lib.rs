@@ -43,3 +43,6 @@ pub use vex::{Aes128, Aes256};
 
 /// Size of an AES block (128-bits; 16-bytes)
 pub const BLOCK_SIZE: usize = 16;
+
+mod xts;
+pub use xts::{Aes128Xts, Aes256Xts, Xts};
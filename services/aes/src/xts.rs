@@ -0,0 +1,156 @@
+/*
+  XTS mode, per IEEE P1619 / NIST SP 800-38E, for encrypting storage a sector at a time.
+
+  XTS is length preserving and has no room for a nonce or a tag: a sector always encrypts to the same
+  ciphertext at the same index, and a modified ciphertext decrypts to garbage rather than to an error.
+  It is meant for block devices, which have nowhere to put either. Where there is space for a tag, an
+  AEAD (such as the AES-GCM-SIV the PDDB uses) is the better choice.
+
+  The PDDB does not use this mode. Its pages carry a nonce and a tag alongside the data, and the tag is
+  what catches a tampered or stale page; encrypting them with XTS would give that up, and would need a
+  new on-disk version and a migration of every existing database. Only the primitive is provided here,
+  for storage that has no room for a tag.
+
+  The block cipher is whatever `Aes128`/`Aes256` resolve to for the target, so the Vex-accelerated
+  primitives are used where they exist, and the soft ones everywhere else.
+*/
+
+use cipher::{consts::U16, BlockDecrypt, BlockEncrypt, BlockSizeUser, KeyInit};
+
+use crate::{Aes128, Aes256, Block, BLOCK_SIZE};
+
+/// XTS-AES-128; takes two 16-byte keys
+pub type Aes128Xts = Xts<Aes128>;
+/// XTS-AES-256; takes two 32-byte keys
+pub type Aes256Xts = Xts<Aes256>;
+
+/// XTS over a 128-bit block cipher `C`. Sectors can be any length from one block up; lengths that
+/// aren't a multiple of the block size are handled with ciphertext stealing.
+pub struct Xts<C> {
+    data: C,
+    tweak: C,
+}
+
+impl<C> Xts<C>
+where
+    C: BlockEncrypt + BlockDecrypt + BlockSizeUser<BlockSize = U16> + KeyInit,
+{
+    /// `data_key` encrypts the data, and `tweak_key` the sector index. They must be independent keys;
+    /// using the same key for both weakens the mode.
+    pub fn new(data_key: &cipher::Key<C>, tweak_key: &cipher::Key<C>) -> Self {
+        Xts { data: C::new(data_key), tweak: C::new(tweak_key) }
+    }
+
+    /// Encrypts `sector` in place. `index` is the sector's position on the medium, which must be
+    /// unique for every sector encrypted under the key pair.
+    ///
+    /// Panics if `sector` is shorter than one block.
+    pub fn encrypt_sector(&self, sector: &mut [u8], index: u128) {
+        assert!(sector.len() >= BLOCK_SIZE, "XTS sectors must be at least one block long");
+        let mut tweak = self.first_tweak(index);
+        let tail = sector.len() % BLOCK_SIZE;
+        // with ciphertext stealing, the last whole block is done together with the partial one
+        let whole = if tail == 0 { sector.len() } else { sector.len() - tail - BLOCK_SIZE };
+        for block in sector[..whole].chunks_exact_mut(BLOCK_SIZE) {
+            xex(block, &tweak, |b| self.data.encrypt_block(b));
+            next_tweak(&mut tweak);
+        }
+        if tail != 0 {
+            let (last, partial) = sector[whole..].split_at_mut(BLOCK_SIZE);
+            xex(last, &tweak, |b| self.data.encrypt_block(b));
+            next_tweak(&mut tweak);
+            // the partial block's ciphertext is stolen from the front of the last whole one, and the
+            // partial plaintext takes its place to be encrypted again
+            for (p, c) in partial.iter_mut().zip(last.iter_mut()) {
+                core::mem::swap(p, c);
+            }
+            xex(last, &tweak, |b| self.data.encrypt_block(b));
+        }
+    }
+
+    /// Decrypts `sector` in place; `index` is the one it was encrypted with.
+    ///
+    /// Panics if `sector` is shorter than one block.
+    pub fn decrypt_sector(&self, sector: &mut [u8], index: u128) {
+        assert!(sector.len() >= BLOCK_SIZE, "XTS sectors must be at least one block long");
+        let mut tweak = self.first_tweak(index);
+        let tail = sector.len() % BLOCK_SIZE;
+        let whole = if tail == 0 { sector.len() } else { sector.len() - tail - BLOCK_SIZE };
+        for block in sector[..whole].chunks_exact_mut(BLOCK_SIZE) {
+            xex(block, &tweak, |b| self.data.decrypt_block(b));
+            next_tweak(&mut tweak);
+        }
+        if tail != 0 {
+            // undo the stealing: the last whole block was encrypted with the tweak after its own
+            let own_tweak = tweak;
+            next_tweak(&mut tweak);
+            let (last, partial) = sector[whole..].split_at_mut(BLOCK_SIZE);
+            xex(last, &tweak, |b| self.data.decrypt_block(b));
+            for (p, c) in partial.iter_mut().zip(last.iter_mut()) {
+                core::mem::swap(p, c);
+            }
+            xex(last, &own_tweak, |b| self.data.decrypt_block(b));
+        }
+    }
+
+    fn first_tweak(&self, index: u128) -> Block {
+        let mut tweak = Block::clone_from_slice(&index.to_le_bytes());
+        self.tweak.encrypt_block(&mut tweak);
+        tweak
+    }
+}
+
+/// Runs `cipher` over `block` whitened with `tweak` on both sides
+fn xex(block: &mut [u8], tweak: &Block, cipher: impl FnOnce(&mut Block)) {
+    let mut b = Block::clone_from_slice(block);
+    b.iter_mut().zip(tweak.iter()).for_each(|(b, t)| *b ^= t);
+    cipher(&mut b);
+    b.iter_mut().zip(tweak.iter()).for_each(|(b, t)| *b ^= t);
+    block.copy_from_slice(&b);
+}
+
+/// Multiplies the tweak by x in GF(2^128), with the little-endian convention of the standard
+fn next_tweak(tweak: &mut Block) {
+    let carry = tweak[BLOCK_SIZE - 1] >> 7;
+    for i in (1..BLOCK_SIZE).rev() {
+        tweak[i] = (tweak[i] << 1) | (tweak[i - 1] >> 7);
+    }
+    tweak[0] = (tweak[0] << 1) ^ (0x87 * carry);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // keys 0x00..0x3f, sector 5; ciphertexts computed with pyca/cryptography
+    const CT_32: [u8; 32] = [
+        0x40, 0xcc, 0x38, 0x3b, 0x87, 0x74, 0x3f, 0xa5, 0x96, 0x99, 0xcd, 0x7e, 0x0c, 0xe9, 0x51, 0x75, 0x60,
+        0x75, 0x9b, 0xe7, 0x6f, 0xe1, 0xac, 0x0f, 0xee, 0x0e, 0x01, 0x3a, 0x21, 0x38, 0x8a, 0x92,
+    ];
+    const CT_17: [u8; 17] = [
+        0x7c, 0xc4, 0x35, 0xbe, 0x1b, 0xd5, 0xc4, 0x20, 0xaf, 0xa6, 0x88, 0x31, 0x94, 0x1c, 0x4c, 0x4d, 0x40,
+    ];
+
+    fn check(expected: &[u8]) {
+        let mut keys = [0u8; 64];
+        keys.iter_mut().enumerate().for_each(|(i, k)| *k = i as u8);
+        let xts = Aes256Xts::new((&keys[..32]).into(), (&keys[32..]).into());
+        let mut plaintext = [0u8; 32];
+        plaintext.iter_mut().enumerate().for_each(|(i, p)| *p = (i * 13 + 5) as u8);
+        let plaintext = &plaintext[..expected.len()];
+
+        let mut sector = [0u8; 32];
+        let sector = &mut sector[..expected.len()];
+        sector.copy_from_slice(plaintext);
+        xts.encrypt_sector(sector, 5);
+        assert_eq!(sector, expected);
+        xts.decrypt_sector(sector, 5);
+        assert_eq!(sector, plaintext);
+    }
+
+    #[test]
+    fn whole_blocks() { check(&CT_32); }
+
+    #[test]
+    fn ciphertext_stealing() { check(&CT_17); }
+}
@@ -0,0 +1,57 @@
+pub(crate) const SERVER_NAME_CLIPBOARD: &str = "_System clipboard_";
+
+/// Largest item the clipboard will hold, in bytes
+pub const CLIPBOARD_MAX_LEN: usize = 3072;
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// Place an item on the clipboard, replacing the previous one. Memory, lend_mut of `ClipItem`;
+    /// `granted` is cleared if the caller's app name was refused.
+    Copy = 0,
+    /// Read the item on the clipboard. Memory, lend_mut of `ClipItem`. If the item was placed by another
+    /// process, the user is asked before it is handed over.
+    Paste = 1,
+    /// Clear the clipboard. Blocking scalar, returns 1 if the caller neither placed the item nor was
+    /// allowed to paste it, and 0 otherwise.
+    Clear = 2,
+    /// Internal: the TTL of the item with the generation in arg1 has run out. Scalar, only accepted
+    /// from the clipboard's own process.
+    Expire = 3,
+    /// Exits the server. Only accepted from the clipboard's own process.
+    Quit = 4,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub enum ClipKind {
+    Empty,
+    /// UTF-8 text
+    Text,
+    /// Opaque bytes
+    Binary,
+}
+
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct ClipItem {
+    pub kind: ClipKind,
+    pub data: [u8; CLIPBOARD_MAX_LEN],
+    pub len: u32,
+    /// Seconds after which the item is cleared; 0 keeps it until it is replaced
+    pub ttl_secs: u32,
+    /// Name of the app placing the item (Copy) or asking for it (Paste), for the permission prompt. A
+    /// process is held to the first name it gives, and can't take a name another process has given.
+    pub app: xous_ipc::String<64>,
+    /// Return value: false if the app name was refused, or if the user refused to hand the item over
+    pub granted: bool,
+}
+impl ClipItem {
+    pub fn new(app: &str) -> Self {
+        ClipItem {
+            kind: ClipKind::Empty,
+            data: [0u8; CLIPBOARD_MAX_LEN],
+            len: 0,
+            ttl_secs: 0,
+            app: xous_ipc::String::from_str(app),
+            granted: false,
+        }
+    }
+}
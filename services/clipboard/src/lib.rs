@@ -0,0 +1,102 @@
+pub mod api;
+pub use api::*;
+use num_traits::*;
+use xous::{send_message, Message, CID};
+use xous_ipc::Buffer;
+
+/// The contents of the clipboard, as returned by `paste()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipContent {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+pub struct Clipboard {
+    conn: CID,
+    app: String,
+}
+impl Clipboard {
+    /// `app` names the caller in the prompt shown when another app pastes what it copied, or when
+    /// it pastes what another app copied.
+    pub fn new(xns: &xous_names::XousNames, app: &str) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_CLIPBOARD)?;
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(Clipboard { conn, app: app.to_string() })
+    }
+
+    /// Places `text` on the clipboard. With a `ttl_secs`, the clipboard clears itself after that many
+    /// seconds: use this for anything sensitive. `AccessDenied` is returned if another process already
+    /// goes by this `app` name.
+    pub fn copy_text(&self, text: &str, ttl_secs: Option<u32>) -> Result<(), xous::Error> {
+        self.copy(ClipKind::Text, text.as_bytes(), ttl_secs)
+    }
+
+    /// Places opaque bytes on the clipboard; see `copy_text()` for `ttl_secs`.
+    pub fn copy_binary(&self, data: &[u8], ttl_secs: Option<u32>) -> Result<(), xous::Error> {
+        self.copy(ClipKind::Binary, data, ttl_secs)
+    }
+
+    fn copy(&self, kind: ClipKind, data: &[u8], ttl_secs: Option<u32>) -> Result<(), xous::Error> {
+        if data.len() > CLIPBOARD_MAX_LEN {
+            return Err(xous::Error::OutOfMemory);
+        }
+        let mut item = ClipItem::new(&self.app);
+        item.kind = kind;
+        item.data[..data.len()].copy_from_slice(data);
+        item.len = data.len() as u32;
+        item.ttl_secs = ttl_secs.unwrap_or(0);
+        let mut buf = Buffer::into_buf(item).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::Copy.to_u32().unwrap())?;
+        let item = buf.to_original::<ClipItem, _>().or(Err(xous::Error::InternalError))?;
+        if !item.granted {
+            return Err(xous::Error::AccessDenied);
+        }
+        Ok(())
+    }
+
+    /// Returns the clipboard contents, or `None` if it is empty. This may block on the user being asked
+    /// for permission; `AccessDenied` is returned if they refuse.
+    pub fn paste(&self) -> Result<Option<ClipContent>, xous::Error> {
+        let item = ClipItem::new(&self.app);
+        let mut buf = Buffer::into_buf(item).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::Paste.to_u32().unwrap())?;
+        let item = buf.to_original::<ClipItem, _>().or(Err(xous::Error::InternalError))?;
+        if !item.granted {
+            return Err(xous::Error::AccessDenied);
+        }
+        let data = &item.data[..item.len as usize];
+        match item.kind {
+            ClipKind::Empty => Ok(None),
+            ClipKind::Text => Ok(Some(ClipContent::Text(String::from_utf8_lossy(data).into_owned()))),
+            ClipKind::Binary => Ok(Some(ClipContent::Binary(data.to_vec()))),
+        }
+    }
+
+    /// Empties the clipboard. Only the app that placed the item, or one that was allowed to paste it,
+    /// may clear it; `AccessDenied` is returned otherwise.
+    pub fn clear(&self) -> Result<(), xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::Clear.to_usize().unwrap(), 0, 0, 0, 0),
+        )? {
+            xous::Result::Scalar1(0) => Ok(()),
+            xous::Result::Scalar1(_) => Err(xous::Error::AccessDenied),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+}
+
+use core::sync::atomic::{AtomicU32, Ordering};
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Clipboard {
+    fn drop(&mut self) {
+        // the connection to the server side must be reference counted, so that multiple instances of this
+        // object within a single process do not end up de-allocating the CID on other threads before they
+        // go out of scope.
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,291 @@
+mod api;
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use api::*;
+use locales::t;
+use num_traits::*;
+use xous::{CID, PID};
+use xous_ipc::Buffer;
+
+/// The item on the clipboard, and what is known about who may see it
+struct Held {
+    item: ClipItem,
+    /// process that placed the item
+    owner: Option<PID>,
+    /// other processes the user has already allowed to paste this item
+    granted: Vec<PID>,
+    /// bumped on every change, so that a stale expiry doesn't clear a newer item
+    generation: usize,
+}
+
+impl Held {
+    fn clear(&mut self) {
+        // overwrite, rather than just drop, as the item may have been a secret
+        self.item.data.fill(0);
+        self.item.len = 0;
+        self.item.kind = ClipKind::Empty;
+        self.item.app.clear();
+        self.owner = None;
+        self.granted.clear();
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Whether `pid` placed the item, or was allowed to paste it
+    fn may_clear(&self, pid: Option<PID>) -> bool {
+        self.item.kind == ClipKind::Empty
+            || self.owner == pid
+            || pid.map(|pid| self.granted.contains(&pid)).unwrap_or(false)
+    }
+}
+
+/// The name each process goes by in the permission prompt. A process is held to the first name it
+/// gives, and a name given by one process can't be taken by another, so an app can't pose as one that
+/// has already used the clipboard.
+#[derive(Default)]
+struct Names {
+    by_pid: HashMap<PID, String>,
+}
+
+impl Names {
+    /// The name `pid` goes by, or `None` if `claimed` isn't its name to give
+    fn resolve(&mut self, pid: Option<PID>, claimed: &str) -> Option<String> {
+        let pid = pid?;
+        if let Some(name) = self.by_pid.get(&pid) {
+            return if name == claimed { Some(name.clone()) } else { None };
+        }
+        if claimed.is_empty() || self.by_pid.values().any(|name| name == claimed) {
+            return None;
+        }
+        self.by_pid.insert(pid, claimed.to_string());
+        Some(claimed.to_string())
+    }
+}
+
+/// The deadline of the item that expires next, and its generation
+type Expiry = Arc<(Mutex<Option<(Instant, usize)>>, Condvar)>;
+
+/// Sends `Expire` for each deadline as it passes. A single thread serves every item, as a new item
+/// simply replaces the deadline of the previous one.
+fn expiry_worker(expiry: Expiry, self_cid: CID) {
+    let (lock, cvar) = &*expiry;
+    let mut next = lock.lock().unwrap();
+    loop {
+        match *next {
+            None => next = cvar.wait(next).unwrap(),
+            Some((deadline, generation)) => {
+                let now = Instant::now();
+                if now < deadline {
+                    next = cvar.wait_timeout(next, deadline - now).unwrap().0;
+                    continue;
+                }
+                *next = None;
+                xous::send_message(
+                    self_cid,
+                    xous::Message::new_scalar(Opcode::Expire.to_usize().unwrap(), generation, 0, 0, 0),
+                )
+                .ok();
+            }
+        }
+    }
+}
+
+fn kind_name(kind: ClipKind) -> &'static str {
+    match kind {
+        ClipKind::Text => t!("clipboard.text", locales::LANG),
+        _ => t!("clipboard.binary", locales::LANG),
+    }
+}
+
+fn confirm(modals: &modals::Modals, prompt: &str) -> bool {
+    modals.add_list_item(t!("clipboard.yes", locales::LANG)).expect("modals error");
+    modals.add_list_item(t!("clipboard.no", locales::LANG)).expect("modals error");
+    match modals.get_radiobutton(prompt) {
+        Ok(response) => response.as_str() == t!("clipboard.yes", locales::LANG),
+        _ => false,
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let clipboard_sid = xns.register_name(api::SERVER_NAME_CLIPBOARD, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", clipboard_sid);
+    let self_cid = xous::connect(clipboard_sid).unwrap();
+    let my_pid = xous::process::id();
+
+    let modals = modals::Modals::new(&xns).expect("can't connect to Modals server");
+    let mut held = Held { item: ClipItem::new(""), owner: None, granted: Vec::new(), generation: 0 };
+    let mut names = Names::default();
+    let expiry: Expiry = Arc::new((Mutex::new(None), Condvar::new()));
+    std::thread::spawn({
+        let expiry = expiry.clone();
+        move || expiry_worker(expiry, self_cid)
+    });
+
+    loop {
+        let mut msg = xous::receive_message(clipboard_sid).unwrap();
+        let from_self = msg.sender.pid().map(|p| p.get() as u32) == Some(my_pid);
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Copy) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("clipboard Copy was not a memory message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(mut item) = buffer.to_original::<ClipItem, _>() else {
+                    log::error!("couldn't deserialize a clipboard item");
+                    continue;
+                };
+                let Some(app) = names.resolve(msg.sender.pid(), item.app.to_str()) else {
+                    log::warn!("refused a copy under the name {}", item.app);
+                    item.data.fill(0);
+                    item.granted = false;
+                    buffer.replace(item).ok();
+                    continue;
+                };
+                held.clear();
+                held.item = item;
+                held.item.app = xous_ipc::String::from_str(&app);
+                held.item.len = held.item.len.min(CLIPBOARD_MAX_LEN as u32);
+                held.owner = msg.sender.pid();
+                log::info!(
+                    "{} copied {:?}, {} bytes, ttl {}s",
+                    held.item.app,
+                    held.item.kind,
+                    held.item.len,
+                    held.item.ttl_secs
+                );
+                let (lock, cvar) = &*expiry;
+                *lock.lock().unwrap() = if held.item.ttl_secs != 0 {
+                    let ttl = Duration::from_secs(held.item.ttl_secs as u64);
+                    Some((Instant::now() + ttl, held.generation))
+                } else {
+                    None
+                };
+                cvar.notify_all();
+                // the copy stays with us: don't hand the secret back in the reply
+                item.data.fill(0);
+                item.granted = true;
+                buffer.replace(item).ok();
+            }
+            Some(Opcode::Paste) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("clipboard Paste was not a memory message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(mut request) = buffer.to_original::<ClipItem, _>() else {
+                    log::error!("couldn't deserialize a clipboard request");
+                    continue;
+                };
+                let reader = msg.sender.pid();
+                let granted = match names.resolve(reader, request.app.to_str()) {
+                    None => {
+                        log::warn!("refused a paste under the name {}", request.app);
+                        false
+                    }
+                    Some(app) => {
+                        held.item.kind == ClipKind::Empty
+                            || held.owner == reader
+                            || reader.map(|pid| held.granted.contains(&pid)).unwrap_or(false)
+                            || confirm(
+                                &modals,
+                                &t!("clipboard.paste_prompt", locales::LANG)
+                                    .replace("{reader}", &app)
+                                    .replace("{kind}", kind_name(held.item.kind))
+                                    .replace("{owner}", held.item.app.to_str()),
+                            )
+                    }
+                };
+                if granted {
+                    if let Some(pid) = reader {
+                        if held.owner != reader && !held.granted.contains(&pid) {
+                            held.granted.push(pid);
+                        }
+                    }
+                    let app = request.app;
+                    request = held.item;
+                    // the reader learns who placed the item, but not the TTL
+                    request.ttl_secs = 0;
+                    request.granted = true;
+                    log::info!("{} pasted {:?} placed by {}", app, held.item.kind, held.item.app);
+                } else {
+                    log::info!("{} was refused the clipboard", request.app);
+                    request.granted = false;
+                }
+                buffer.replace(request).ok();
+            }
+            Some(Opcode::Clear) => {
+                if held.may_clear(msg.sender.pid()) {
+                    held.clear();
+                    xous::return_scalar(msg.sender, 0).ok();
+                } else {
+                    log::warn!("refused to clear an item for a process that can't see it");
+                    xous::return_scalar(msg.sender, 1).ok();
+                }
+            }
+            Some(Opcode::Expire) => xous::msg_scalar_unpack!(msg, generation, _, _, _, {
+                if from_self && generation == held.generation {
+                    log::info!("clipboard item expired");
+                    held.clear();
+                }
+            }),
+            Some(Opcode::Quit) => {
+                if !from_self {
+                    log::warn!("ignoring Quit from another process");
+                    continue;
+                }
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => {
+                log::error!("couldn't convert opcode: {:?}", msg);
+            }
+        }
+    }
+    // clean up our program
+    held.clear();
+    log::trace!("main loop exit, destroying servers");
+    xns.unregister_server(clipboard_sid).unwrap();
+    xous::destroy_server(clipboard_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pid(n: u8) -> Option<PID> { PID::new(n) }
+
+    #[test]
+    fn names_are_held_to_their_first_claim() {
+        let mut names = Names::default();
+        assert_eq!(names.resolve(pid(5), "vault"), Some("vault".to_string()));
+        assert_eq!(names.resolve(pid(5), "vault"), Some("vault".to_string()));
+        // another process can't take the name, and the first can't change it
+        assert_eq!(names.resolve(pid(6), "vault"), None);
+        assert_eq!(names.resolve(pid(5), "shellchat"), None);
+        assert_eq!(names.resolve(pid(6), "shellchat"), Some("shellchat".to_string()));
+        assert_eq!(names.resolve(pid(7), ""), None);
+        assert_eq!(names.resolve(None, "anonymous"), None);
+    }
+
+    #[test]
+    fn only_the_owner_or_grantees_may_clear() {
+        let mut held = Held { item: ClipItem::new("vault"), owner: pid(5), granted: vec![], generation: 0 };
+        held.item.kind = ClipKind::Text;
+        assert!(held.may_clear(pid(5)));
+        assert!(!held.may_clear(pid(6)));
+        held.granted.push(PID::new(6).unwrap());
+        assert!(held.may_clear(pid(6)));
+        held.clear();
+        // an empty clipboard may be cleared by anyone
+        assert!(held.may_clear(pid(7)));
+    }
+}
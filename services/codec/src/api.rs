@@ -51,6 +51,12 @@ pub(crate) enum Opcode {
 
     /// Suspend/resume callback
     SuspendResume,
+
+    /// queue a sequence of tones, mixed over any audio that is playing (memory message, `ToneSequence`)
+    PlayTones,
+
+    /// stop the tones that are playing, and drop the ones that are queued
+    StopTones,
 }
 
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
@@ -86,6 +92,39 @@ pub(crate) struct ScalarHook {
                          * caller before hooking. */
 }
 
+/// Sample rate of the audio stream, in Hz
+pub const SAMPLE_RATE_HZ: u32 = 8000;
+/// Maximum number of tones in a `ToneSequence`
+pub const MAX_TONES: usize = 32;
+
+/// One note of an alert sound. The envelope ramps the volume up over `attack_ms`, and back down over the
+/// last `release_ms` of the tone; short ramps avoid audible clicks at the start and end. A `freq_hz` of 0
+/// is a rest.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone, Default)]
+pub struct Tone {
+    pub freq_hz: u16,
+    pub duration_ms: u16,
+    /// peak volume, from 0 (silent) to 255 (half of full scale, leaving headroom for other audio)
+    pub volume: u8,
+    pub attack_ms: u16,
+    pub release_ms: u16,
+}
+impl Tone {
+    /// A tone at a moderate volume, with a short envelope
+    pub fn new(freq_hz: u16, duration_ms: u16) -> Tone {
+        Tone { freq_hz, duration_ms, volume: 128, attack_ms: 5, release_ms: 20 }
+    }
+
+    /// A silence of `duration_ms`, for spacing the tones of a sequence
+    pub fn rest(duration_ms: u16) -> Tone { Tone { duration_ms, ..Default::default() } }
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ToneSequence {
+    pub tones: [Tone; MAX_TONES],
+    pub len: u32,
+}
+
 //////////////////////////////////////////////////////////////////////////////////////
 
 pub const ZERO_PCM: u16 = 0x0; // assumes 2's compliment. 0x8000 otherwise.
@@ -128,6 +128,30 @@ impl Codec {
         .map(|_| ())
     }
 
+    /// Plays `tones` one after the other, after any tones that are still playing. Tones are mixed over
+    /// whatever audio is already streaming, without interrupting it; if nothing is streaming, the codec is
+    /// powered up for as long as the tones last.
+    pub fn play_tones(&self, tones: &[Tone]) -> Result<(), xous::Error> {
+        if tones.len() > MAX_TONES {
+            return Err(xous::Error::OutOfMemory);
+        }
+        let mut sequence = ToneSequence { tones: [Tone::default(); MAX_TONES], len: tones.len() as u32 };
+        sequence.tones[..tones.len()].copy_from_slice(tones);
+        let buf = Buffer::into_buf(sequence).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, Opcode::PlayTones.to_u32().unwrap()).map(|_| ())
+    }
+
+    /// Plays a single tone of `freq_hz` for `duration_ms`.
+    pub fn beep(&self, freq_hz: u16, duration_ms: u16) -> Result<(), xous::Error> {
+        self.play_tones(&[Tone::new(freq_hz, duration_ms)])
+    }
+
+    /// Stops the tones that are playing, and drops the ones that are queued.
+    pub fn stop_tones(&self) -> Result<(), xous::Error> {
+        send_message(self.conn, Message::new_scalar(Opcode::StopTones.to_usize().unwrap(), 0, 0, 0, 0))
+            .map(|_| ())
+    }
+
     pub fn is_running(&self) -> Result<bool, xous::Error> {
         match send_message(
             self.conn,
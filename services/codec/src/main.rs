@@ -3,6 +3,7 @@
 
 mod api;
 mod backend;
+mod tone;
 use api::*;
 use backend::Codec;
 use log::info;
@@ -17,6 +18,35 @@ struct ScalarCallback {
     cb_to_client_id: u32,
 }
 
+/// Powers up the codec if need be, and starts a stream of its own to play the queued tones. Returns `true`
+/// if the codec was powered up for the purpose.
+fn start_tone_stream(
+    codec: &mut Codec,
+    tones: &mut tone::ToneMixer,
+    ticktimer: &ticktimer_server::Ticktimer,
+) -> bool {
+    let powered_up = !codec.is_on();
+    if powered_up {
+        codec.power(true);
+        ticktimer.sleep_ms(2).unwrap();
+    }
+    if !codec.is_init() {
+        codec.init();
+    }
+    fill_tone_frames(codec, tones);
+    codec.audio_i2s_start();
+    powered_up
+}
+
+/// Queues frames of tones, for as long as there is room and there are tones to play.
+fn fill_tone_frames(codec: &mut Codec, tones: &mut tone::ToneMixer) {
+    while codec.free_play_frames() > 0 && !tones.is_idle() {
+        let mut frame = [ZERO_PCM as u32 | (ZERO_PCM as u32) << 16; FIFO_DEPTH];
+        tones.mix(&mut frame);
+        codec.nq_play_frame(frame).ok();
+    }
+}
+
 fn main() -> ! {
     let stack_size = 256 * 1024;
     std::thread::Builder::new().stack_size(stack_size).spawn(wrapped_main).unwrap().join().unwrap()
@@ -58,6 +88,11 @@ fn wrapped_main() -> ! {
     let mut speaker_analog_gain_db: f32 = -6.0;
     let mut headphone_analog_gain_db: f32 = -15.0;
     let mut audio_cb_conns: [Option<ScalarCallback>; 32] = [None; 32];
+    let mut tones = tone::ToneMixer::new();
+    // set while the stream is only playing tones, rather than audio from a client
+    let mut tone_stream = false;
+    // set if the codec was powered up to play tones, so it is powered down again once they are done
+    let mut tone_powered = false;
     loop {
         let mut msg = xous::receive_message(codec_sid).unwrap();
         let op: Option<api::Opcode> = FromPrimitive::from_usize(msg.body.id());
@@ -72,6 +107,12 @@ fn wrapped_main() -> ! {
                 codec.power(false);
             }),
             Some(api::Opcode::Setup8kStereo) => xous::msg_scalar_unpack!(msg, _, _, _, _, {
+                if tone_stream {
+                    // a client is taking over the stream; any remaining tones are mixed into its frames
+                    codec.audio_i2s_stop();
+                    tone_stream = false;
+                    tone_powered = false;
+                }
                 log::trace!("turning on codec power");
                 codec.power(true);
                 log::trace!("waiting for power up");
@@ -80,7 +121,11 @@ fn wrapped_main() -> ! {
                 codec.init();
             }),
             Some(api::Opcode::ResumeStream) => xous::msg_scalar_unpack!(msg, _, _, _, _, {
-                if codec.is_on() && codec.is_init() {
+                if tone_stream {
+                    // the stream is already running: the client just takes it over
+                    tone_stream = false;
+                    tone_powered = false;
+                } else if codec.is_on() && codec.is_init() {
                     codec.audio_i2s_start();
                 } else {
                     log::error!("attempted to resume a stream on an unitialized codec, ignoring!")
@@ -93,6 +138,11 @@ fn wrapped_main() -> ! {
                         xous::yield_slice();
                     }
                     codec.audio_i2s_stop();
+                    if !tones.is_idle() {
+                        // carry on with any tones that were mixed into the client's audio
+                        tone_stream = true;
+                        start_tone_stream(&mut codec, &mut tones, &ticktimer);
+                    }
                 } else {
                     log::error!("attempted to pause a stream on an uninitialized codec, ignoring!")
                 }
@@ -100,6 +150,10 @@ fn wrapped_main() -> ! {
             Some(api::Opcode::AbortStream) => xous::msg_scalar_unpack!(msg, _, _, _, _, {
                 if codec.is_on() && codec.is_init() && codec.is_live() {
                     codec.audio_i2s_stop();
+                    if !tones.is_idle() {
+                        tone_stream = true;
+                        start_tone_stream(&mut codec, &mut tones, &ticktimer);
+                    }
                 } else {
                     log::error!("attempted to abort a stream on an uninitialized codec, ignoring!")
                 }
@@ -133,6 +187,8 @@ fn wrapped_main() -> ! {
                             }
                         }
                         if codec.free_play_frames() > 0 {
+                            let mut frame = frame;
+                            tones.mix(&mut frame);
                             codec.nq_play_frame(frame).unwrap(); // throw away the result because we know this must succeed
                         } else {
                             // TODO: need to define a behavior when we have a play overrun. Do we:
@@ -168,7 +224,39 @@ fn wrapped_main() -> ! {
             }
             Some(api::Opcode::AnotherFrame) => xous::msg_scalar_unpack!(msg, _rdcount, _wrcount, _, _, {
                 //log::trace!("A rd {} wr {}", rdcount, wrcount);
-                send_event(&audio_cb_conns, codec.free_play_frames(), codec.available_rec_frames());
+                if tone_stream {
+                    fill_tone_frames(&mut codec, &mut tones);
+                    if tones.is_idle() && !codec.can_play() {
+                        codec.audio_i2s_stop();
+                        if tone_powered {
+                            codec.power(false);
+                        }
+                        tone_stream = false;
+                        tone_powered = false;
+                    }
+                } else {
+                    send_event(&audio_cb_conns, codec.free_play_frames(), codec.available_rec_frames());
+                }
+            }),
+            Some(api::Opcode::PlayTones) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let sequence = buffer.to_original::<codec::api::ToneSequence, _>().unwrap();
+                tones.queue(&sequence.tones[..(sequence.len as usize).min(MAX_TONES)]);
+                if !codec.is_live() {
+                    tone_stream = true;
+                    tone_powered = start_tone_stream(&mut codec, &mut tones, &ticktimer);
+                }
+            }
+            Some(api::Opcode::StopTones) => xous::msg_scalar_unpack!(msg, _, _, _, _, {
+                tones.stop();
+                if tone_stream {
+                    codec.audio_i2s_stop();
+                    if tone_powered {
+                        codec.power(false);
+                    }
+                    tone_stream = false;
+                    tone_powered = false;
+                }
             }),
             Some(api::Opcode::SetSpeakerVolume) => xous::msg_scalar_unpack!(msg, op, gain_code, _, _, {
                 match FromPrimitive::from_usize(op) {
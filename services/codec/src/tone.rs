@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+
+use codec::{Tone, FIFO_DEPTH, SAMPLE_RATE_HZ};
+
+/// Tones queued beyond this are dropped, so a misbehaving app can't keep the codec busy indefinitely
+const MAX_QUEUED_TONES: usize = 128;
+const SINE_TABLE_LEN: usize = 256;
+
+/// A tone being played, in samples
+struct Playing {
+    tone: Tone,
+    /// phase advance per sample, where a full cycle is 2^32
+    phase_step: u32,
+    total: u32,
+    attack: u32,
+    release: u32,
+    elapsed: u32,
+}
+impl Playing {
+    fn new(tone: Tone) -> Playing {
+        let samples = |ms: u16| (ms as u32 * SAMPLE_RATE_HZ) / 1000;
+        Playing {
+            tone,
+            phase_step: ((tone.freq_hz as u64) << 32).checked_div(SAMPLE_RATE_HZ as u64).unwrap_or(0) as u32,
+            total: samples(tone.duration_ms),
+            attack: samples(tone.attack_ms),
+            release: samples(tone.release_ms),
+            elapsed: 0,
+        }
+    }
+
+    /// The envelope at the current sample, out of 256
+    fn envelope(&self) -> i32 {
+        let remaining = self.total - self.elapsed;
+        let mut env = 256;
+        if self.elapsed < self.attack {
+            env = env.min((self.elapsed * 256 / self.attack) as i32);
+        }
+        if remaining < self.release {
+            env = env.min((remaining * 256 / self.release) as i32);
+        }
+        env
+    }
+}
+
+/// Synthesizes queued tones, and mixes them into the frames on their way to the codec.
+pub struct ToneMixer {
+    sine: [i16; SINE_TABLE_LEN],
+    queue: VecDeque<Tone>,
+    playing: Option<Playing>,
+    phase: u32,
+}
+impl ToneMixer {
+    pub fn new() -> ToneMixer {
+        let mut sine = [0i16; SINE_TABLE_LEN];
+        for (i, s) in sine.iter_mut().enumerate() {
+            *s = ((i as f32 * 2.0 * core::f32::consts::PI / SINE_TABLE_LEN as f32).sin() * i16::MAX as f32)
+                as i16;
+        }
+        ToneMixer { sine, queue: VecDeque::new(), playing: None, phase: 0 }
+    }
+
+    pub fn queue(&mut self, tones: &[Tone]) {
+        for &tone in tones {
+            if self.queue.len() >= MAX_QUEUED_TONES {
+                log::warn!("tone queue is full, dropping tones");
+                break;
+            }
+            self.queue.push_back(tone);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.queue.clear();
+        self.playing = None;
+    }
+
+    pub fn is_idle(&self) -> bool { self.playing.is_none() && self.queue.is_empty() }
+
+    /// Adds the next frame's worth of tones to `frame`, saturating rather than wrapping where the sum is
+    /// too loud.
+    pub fn mix(&mut self, frame: &mut [u32; FIFO_DEPTH]) {
+        for stereo_sample in frame.iter_mut() {
+            if !matches!(&self.playing, Some(p) if p.elapsed < p.total) {
+                match self.queue.pop_front() {
+                    Some(tone) => {
+                        self.phase = 0;
+                        self.playing = Some(Playing::new(tone));
+                    }
+                    None => {
+                        self.playing = None;
+                        return;
+                    }
+                }
+            }
+            let playing = self.playing.as_mut().unwrap();
+            if playing.total == 0 {
+                // an empty tone: move on to the next one
+                continue;
+            }
+            let sine = self.sine[(self.phase >> 24) as usize] as i32;
+            // full volume is half of full scale
+            let sample = (sine * playing.envelope() / 256 * playing.tone.volume as i32 / 512) as i16;
+            self.phase = self.phase.wrapping_add(playing.phase_step);
+            playing.elapsed += 1;
+
+            let left = (*stereo_sample & 0xFFFF) as u16 as i16;
+            let right = (*stereo_sample >> 16) as u16 as i16;
+            *stereo_sample = left.saturating_add(sample) as u16 as u32
+                | (right.saturating_add(sample) as u16 as u32) << 16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A frame holding `left` and `right` in every stereo sample
+    fn frame(left: i16, right: i16) -> [u32; FIFO_DEPTH] {
+        [left as u16 as u32 | (right as u16 as u32) << 16; FIFO_DEPTH]
+    }
+
+    fn channels(stereo_sample: u32) -> (i16, i16) {
+        ((stereo_sample & 0xFFFF) as u16 as i16, (stereo_sample >> 16) as u16 as i16)
+    }
+
+    /// Duration of exactly one frame of samples
+    const FRAME_MS: u16 = (FIFO_DEPTH as u32 * 1000 / SAMPLE_RATE_HZ) as u16;
+
+    #[test]
+    fn idle_mixer_leaves_frames_alone() {
+        let mut mixer = ToneMixer::new();
+        assert!(mixer.is_idle());
+        let mut f = frame(1234, -1234);
+        mixer.mix(&mut f);
+        assert_eq!(f, frame(1234, -1234));
+    }
+
+    #[test]
+    fn tone_is_mixed_into_both_channels() {
+        let mut mixer = ToneMixer::new();
+        mixer.queue(&[Tone::new(1000, FRAME_MS)]);
+        let mut f = frame(1000, -1000);
+        mixer.mix(&mut f);
+        let mut sounded = false;
+        for &s in f.iter() {
+            let (left, right) = channels(s);
+            // the same tone sample is added to what was already playing on each channel
+            assert_eq!(left - 1000, right + 1000);
+            sounded |= left != 1000;
+        }
+        assert!(sounded);
+    }
+
+    #[test]
+    fn tone_lasts_its_duration() {
+        let mut mixer = ToneMixer::new();
+        mixer.queue(&[Tone::new(440, FRAME_MS)]);
+        let mut f = frame(0, 0);
+        mixer.mix(&mut f);
+        assert!(!mixer.is_idle());
+        let mut f = frame(0, 0);
+        mixer.mix(&mut f);
+        assert_eq!(f, frame(0, 0));
+        assert!(mixer.is_idle());
+    }
+
+    #[test]
+    fn envelope_and_volume_bound_the_level() {
+        let mut mixer = ToneMixer::new();
+        let tone = Tone { freq_hz: 500, duration_ms: FRAME_MS, volume: 255, attack_ms: 10, release_ms: 10 };
+        mixer.queue(&[tone]);
+        let mut f = frame(0, 0);
+        mixer.mix(&mut f);
+        let levels: Vec<i32> = f.iter().map(|&s| (channels(s).0 as i32).abs()).collect();
+        // full volume is half of full scale
+        assert!(levels.iter().all(|&l| l <= i16::MAX as i32 / 2));
+        // the attack ramps up from silence, and the release ramps down to it
+        let peak = *levels.iter().max().unwrap();
+        assert!(levels[..4].iter().all(|&l| l < peak / 4));
+        assert!(levels[FIFO_DEPTH - 4..].iter().all(|&l| l < peak / 4));
+    }
+
+    #[test]
+    fn loud_mixes_saturate() {
+        let mut mixer = ToneMixer::new();
+        mixer.queue(&[Tone { volume: 255, ..Tone::new(1000, FRAME_MS) }]);
+        let mut f = frame(i16::MAX, i16::MIN);
+        mixer.mix(&mut f);
+        for &s in f.iter() {
+            let (left, right) = channels(s);
+            assert!(left > 0 && right < 0, "wrapped around: {} {}", left, right);
+        }
+    }
+
+    #[test]
+    fn sequences_play_in_order() {
+        let mut mixer = ToneMixer::new();
+        mixer.queue(&[Tone::rest(FRAME_MS), Tone::new(1000, FRAME_MS)]);
+        let mut f = frame(0, 0);
+        mixer.mix(&mut f);
+        assert_eq!(f, frame(0, 0));
+        mixer.mix(&mut f);
+        assert_ne!(f, frame(0, 0));
+    }
+
+    #[test]
+    fn queue_is_bounded_and_stop_clears_it() {
+        let mut mixer = ToneMixer::new();
+        let tones = vec![Tone::new(1000, 10); MAX_QUEUED_TONES + 10];
+        mixer.queue(&tones);
+        assert_eq!(mixer.queue.len(), MAX_QUEUED_TONES);
+        mixer.stop();
+        assert!(mixer.is_idle());
+        let mut f = frame(0, 0);
+        mixer.mix(&mut f);
+        assert_eq!(f, frame(0, 0));
+    }
+}
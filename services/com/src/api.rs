@@ -13,6 +13,45 @@ pub const WF200_SSID_MAX_LEN: usize = 32;
 
 // extra 30 bytes for the header over 1500
 pub const NET_MTU: usize = 1530;
+
+/// The version of the COM protocol this side of the link speaks. The link runs at the lower of this and
+/// the version the EC reports in the capability handshake.
+pub const COM_PROTOCOL_VERSION: u16 = 1;
+
+/// What the EC firmware on the other end of the link can do, as found by the capability handshake at boot.
+/// Firmware that predates the handshake is given the features its version tag implies, and protocol 0.
+///
+/// A COM opcode that needs something new from the EC gets a feature bit here, and is only sent to an EC
+/// that has it; against older firmware it does nothing or reports an error, rather than sending the EC a
+/// verb it doesn't know.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct EcCapabilities {
+    pub protocol: u16,
+    pub features: u32,
+}
+impl EcCapabilities {
+    /// `LINK_PING`, for confirming the link is in sync after a reset
+    pub const LINK_PING: u32 = 1 << 0;
+    /// `FLASH_VERIFY`, for reading back EC flash
+    pub const FLASH_VERIFY: u32 = 1 << 1;
+    /// SSID scan results as strings, and the binary WLAN status; not in the firmware shipped before tagging
+    pub const WLAN_STATUS_V2: u32 = 1 << 2;
+    /// `WF200_DEBUG`, for the WF200 allocator and config diagnostics
+    pub const WF200_DEBUG: u32 = 1 << 3;
+    /// Interrupts for charger plug and unplug, low battery and over-temperature
+    pub const POWER_EVENTS: u32 = 1 << 4;
+    /// Reading the battery temperature from the gas gauge
+    pub const TEMPERATURE: u32 = 1 << 5;
+    /// Every feature this version of the COM server knows how to use
+    pub const KNOWN: u32 = Self::LINK_PING
+        | Self::FLASH_VERIFY
+        | Self::WLAN_STATUS_V2
+        | Self::WF200_DEBUG
+        | Self::POWER_EVENTS
+        | Self::TEMPERATURE;
+
+    pub fn has(&self, feature: u32) -> bool { self.features & feature == feature }
+}
 #[derive(Debug, Default, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct BattStats {
     /// instantaneous voltage in mV
@@ -69,6 +108,23 @@ pub(crate) struct SsidReturn {
 impl Default for SsidReturn {
     fn default() -> Self { SsidReturn { list: [SsidRecord::default(); 8] } }
 }
+/// An access point, as seen over the recent scans. The EC's scan records only carry the name and rssi, so
+/// the channel and security type of an AP aren't known until it is joined.
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct SurveyRecord {
+    pub name: xous_ipc::String<32>,
+    /// the negative of the rssi in dBm, like `SsidRecord::rssi`, as of the last time the AP was seen
+    pub rssi: u8,
+    /// time since the AP was last seen
+    pub age_ms: u32,
+}
+pub const MAX_SURVEY_RECORDS: usize = 24;
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Default)]
+pub(crate) struct SurveyReturn {
+    pub list: [Option<SurveyRecord>; MAX_SURVEY_RECORDS],
+    /// time since the survey was last refreshed, if it ever was
+    pub age_ms: Option<u32>,
+}
 #[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct WlanStatusIpc {
     pub ssid: Option<SsidRecord>,
@@ -273,6 +329,24 @@ pub(crate) enum Opcode {
 
     /// gets more details on the latest interrupt
     IntFetchVector = 49,
+
+    /// Return the cached survey of access points seen by recent scans, without scanning
+    SsidSurvey = 50,
+
+    /// Set the interval of the background survey scans, in ms; 0 stops them
+    SetSurveyInterval = 51,
+
+    /// [internal] refresh the survey and start the next scan
+    SurveyPump = 52,
+
+    /// Fetch the protocol version and features agreed with the EC, as {protocol, feature bitmap}
+    EcCapabilities = 53,
+
+    /// request for a listener to power events
+    RegisterPowerEventListener = 54,
+
+    /// Fetch the battery temperature, as {1 if it was read, tenths of a kelvin}
+    EcTemperature = 55,
 }
 
 /// These enums indicate what kind of callback type we're sending.
@@ -282,6 +356,21 @@ pub(crate) enum Callback {
     BattStats,
     /// Server is quitting, drop connections
     Drop,
+    /// A `PowerEvent` raised by the EC
+    PowerEvent,
+}
+
+/// Changes in power state that the EC reports by interrupt as they happen, for `Com::hook_power_events()`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub enum PowerEvent {
+    ChargerPlugged,
+    ChargerUnplugged,
+    /// The battery has run down to the EC's low-battery threshold
+    BatteryLow,
+    /// The battery is about to cut out; reported by all EC firmware
+    BatteryCritical,
+    /// The EC has throttled charging because the battery or charger is too hot
+    Thermal,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
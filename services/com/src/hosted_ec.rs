@@ -0,0 +1,382 @@
+//! A scriptable stand-in for the EC, so that hosted mode can exercise the battery, charging and Wi-Fi
+//! behaviors that otherwise need hardware.
+//!
+//! The emulated state is driven by text commands, one per line. They are read from the file named by
+//! `XOUS_COM_SCRIPT` once at boot, and from any TCP client connecting to `127.0.0.1:$XOUS_COM_PORT`,
+//! e.g. `echo "link down" | nc -q0 127.0.0.1 7900`.
+//!
+//! ```text
+//! battery <soc %> [<mV> [<mA>]]   set the gas gauge readings; a negative current is a discharge
+//! charging on|off                 plug or unplug the charger, raising the matching power interrupt
+//! low                             raise the low battery interrupt
+//! critical                        raise the battery critical interrupt
+//! thermal                         raise the over-temperature interrupt
+//! temperature <°C>                set the battery temperature reported by the gas gauge
+//! ssid <rssi> <name>              add an access point to the scan results; rssi is in -dBm, e.g. 45
+//! ssid clear                      empty the scan results
+//! link up [<name>]                associate and get a DHCP lease; defaults to the SSID the OS asked for
+//! link down                       drop the association
+//! sleep <ms>                      pause before the next command
+//! ```
+//! Blank lines and lines starting with `#` are ignored.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+use com_rs::serdes::{Ipv4Conf, StringSer, STR_32_WORDS, STR_64_WORDS};
+use com_rs::*;
+
+use crate::api::{BattStats, EcCapabilities, COM_PROTOCOL_VERSION};
+use crate::{
+    CAPS_MAGIC, INT_BATTERY_LOW, INT_CHARGER_PLUGGED, INT_CHARGER_UNPLUGGED, INT_THERMAL, LINK_CAPS_VERB,
+    LINK_TEMP_VERB,
+};
+
+/// The EC reports up to this many access points per scan
+const SSID_RECORDS: usize = 8;
+/// Each access point is reported as an rssi/length word, followed by up to 32 bytes of name
+const SSID_RECORD_WORDS: usize = 17;
+/// Network frame verbs carry the frame length in their lower bits
+const NET_FRAME_LEN_MASK: u16 = 0x07FF;
+
+struct EcState {
+    stats: BattStats,
+    charging: bool,
+    /// battery temperature in tenths of a kelvin
+    temperature: u16,
+    /// scan results, as (rssi, name)
+    ssids: Vec<(u8, String)>,
+    scanned: bool,
+    /// the SSID most recently set by the OS, in its wire encoding
+    requested_ssid: Vec<u16>,
+    /// the access point we're associated with, as (rssi, name)
+    link: Option<(u8, String)>,
+    int_mask: u16,
+    int_pending: u16,
+}
+
+pub struct EmulatedEc {
+    state: Mutex<EcState>,
+    llio: llio::Llio,
+}
+
+impl EmulatedEc {
+    /// Creates the emulated EC, and starts serving its script file and control socket, if configured.
+    pub fn start() -> Arc<EmulatedEc> {
+        let xns = xous_names::XousNames::new().unwrap();
+        let ec = Arc::new(EmulatedEc {
+            state: Mutex::new(EcState {
+                stats: BattStats { voltage: 3950, current: -110, soc: 85, remaining_capacity: 850 },
+                charging: false,
+                temperature: 2982,
+                ssids: Vec::new(),
+                scanned: false,
+                requested_ssid: Vec::new(),
+                link: None,
+                int_mask: 0,
+                int_pending: 0,
+            }),
+            llio: llio::Llio::new(&xns),
+        });
+
+        if let Ok(script) = std::env::var("XOUS_COM_SCRIPT") {
+            let ec = ec.clone();
+            std::thread::spawn(move || match std::fs::read_to_string(&script) {
+                Ok(lines) => {
+                    for (number, line) in lines.lines().enumerate() {
+                        if let Err(e) = ec.run(line) {
+                            log::error!("{}:{}: {}", script, number + 1, e);
+                        }
+                    }
+                }
+                Err(e) => log::error!("couldn't read COM script {}: {:?}", script, e),
+            });
+        }
+        if let Ok(port) = std::env::var("XOUS_COM_PORT") {
+            match TcpListener::bind(("127.0.0.1", port.parse::<u16>().unwrap_or(0))) {
+                Ok(listener) => {
+                    log::info!("HOSTED: COM control socket on {:?}", listener.local_addr());
+                    let ec = ec.clone();
+                    std::thread::spawn(move || {
+                        for stream in listener.incoming().flatten() {
+                            let ec = ec.clone();
+                            std::thread::spawn(move || ec.serve(stream));
+                        }
+                    });
+                }
+                Err(e) => log::error!("couldn't open the COM control socket on port {}: {:?}", port, e),
+            }
+        }
+        ec
+    }
+
+    fn serve(&self, stream: TcpStream) {
+        let mut reply = match stream.try_clone() {
+            Ok(reply) => reply,
+            Err(_) => return,
+        };
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => return,
+            };
+            let response = match self.run(&line) {
+                Ok(()) => "ok\n".to_string(),
+                Err(e) => format!("error: {}\n", e),
+            };
+            if reply.write_all(response.as_bytes()).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Executes one line of the control language.
+    pub fn run(&self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let args: Vec<&str> = rest.split_whitespace().collect();
+        let number = |i: usize| -> Result<i32, String> {
+            args.get(i)
+                .ok_or(format!("{} is missing an argument", command))?
+                .parse::<i32>()
+                .map_err(|_| format!("{} expects a number, got {}", command, args[i]))
+        };
+        if command == "sleep" {
+            std::thread::sleep(std::time::Duration::from_millis(number(0)? as u64));
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().unwrap();
+        match command {
+            "battery" => {
+                state.stats.soc = number(0)?.clamp(0, 100) as u8;
+                state.stats.remaining_capacity = state.stats.soc as u16 * 10;
+                if args.len() > 1 {
+                    state.stats.voltage = number(1)? as u16;
+                }
+                if args.len() > 2 {
+                    state.stats.current = number(2)? as i16;
+                }
+            }
+            "charging" => {
+                let charging = match rest {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("charging expects on or off".into()),
+                };
+                if charging != state.charging {
+                    state.charging = charging;
+                    self.raise(
+                        &mut state,
+                        if charging { INT_CHARGER_PLUGGED } else { INT_CHARGER_UNPLUGGED },
+                    );
+                }
+            }
+            "low" => self.raise(&mut state, INT_BATTERY_LOW),
+            "critical" => self.raise(&mut state, INT_BATTERY_CRITICAL),
+            "thermal" => self.raise(&mut state, INT_THERMAL),
+            "temperature" => state.temperature = (number(0)?.clamp(-40, 125) * 10 + 2732) as u16,
+            "ssid" if rest == "clear" => {
+                state.ssids.clear();
+                state.scanned = false;
+            }
+            "ssid" => {
+                let rssi = number(0)?.clamp(0, 110) as u8;
+                let name = rest.split_once(char::is_whitespace).map(|(_, name)| name.trim()).unwrap_or("");
+                if name.is_empty() || name.len() > 32 {
+                    return Err("ssid names must be 1 to 32 bytes long".into());
+                }
+                state.ssids.retain(|(_, n)| n != name);
+                state.ssids.push((rssi, name.to_string()));
+                state.ssids.sort_by_key(|(rssi, _)| *rssi);
+            }
+            "link" if args.first() == Some(&"up") => {
+                let ap = if args.len() > 1 {
+                    let name = rest["up".len()..].trim();
+                    state.ssids.iter().find(|(_, n)| n == name).cloned().unwrap_or((50, name.to_string()))
+                } else {
+                    requested_ap(&state)
+                        .ok_or("the OS hasn't asked to join an access point in the scan list")?
+                };
+                self.connect(&mut state, ap);
+            }
+            "link" if rest == "down" => self.disconnect(&mut state),
+            _ => return Err(format!("unknown command: {}", line)),
+        }
+        Ok(())
+    }
+
+    pub fn battstats(&self) -> BattStats { self.state.lock().unwrap().stats }
+
+    /// How many argument words follow `verb` on the link.
+    pub fn arg_words(verb: u16) -> usize {
+        match verb {
+            LINK_TEMP_VERB => rx.push(state.temperature),
+            v if v == ComState::LINK_SET_INTMASK.verb
+                || v == ComState::LINK_ACK_INTERRUPT.verb
+                || v == ComState::LINK_PING.verb
+                || v == ComState::WF200_RESET.verb =>
+            {
+                1
+            }
+            v if v == ComState::TRNG_SEED.verb => 8,
+            v if v == ComState::WFX_PDS_LINE_SET.verb => 129,
+            v if v == ComState::WLAN_SET_SSID.verb => {
+                StringSer::<STR_32_WORDS>::new().encode("").map(|w| w.len()).unwrap_or(0)
+            }
+            v if v == ComState::WLAN_SET_PASS.verb => {
+                StringSer::<STR_64_WORDS>::new().encode("").map(|w| w.len()).unwrap_or(0)
+            }
+            v if v & !NET_FRAME_LEN_MASK == ComState::NET_FRAME_SEND_0.verb => {
+                ((v & NET_FRAME_LEN_MASK) as usize + 1) / 2
+            }
+            _ => 0,
+        }
+    }
+
+    /// Executes `verb` with its argument words, and returns the words the EC has queued up for reading.
+    pub fn command(&self, verb: u16, args: &[u16]) -> Vec<u16> {
+        let mut state = self.state.lock().unwrap();
+        let mut rx = Vec::new();
+        match verb {
+            v if v == ComState::GAS_GAUGE.verb => {
+                let current = state.stats.current as u16;
+                rx.extend_from_slice(&[current, current, state.stats.voltage, 0]);
+            }
+            v if v == ComState::GG_SOC.verb => rx.push(state.stats.soc as u16),
+            v if v == ComState::GG_REMAINING.verb => rx.push(state.stats.remaining_capacity),
+            v if v == ComState::POWER_CHARGER_STATE.verb => rx.push(state.charging as u16),
+            v if v == ComState::LINK_PING.verb => rx.extend_from_slice(&[!args[0], 0x600d]),
+            LINK_CAPS_VERB => {
+                let features = EcCapabilities::KNOWN;
+                rx.extend_from_slice(&[
+                    CAPS_MAGIC,
+                    COM_PROTOCOL_VERSION,
+                    features as u16,
+                    (features >> 16) as u16,
+                ]);
+            }
+            v if v == ComState::LINK_SET_INTMASK.verb => state.int_mask = args[0],
+            v if v == ComState::LINK_GET_INTMASK.verb => rx.push(state.int_mask),
+            v if v == ComState::LINK_ACK_INTERRUPT.verb => state.int_pending &= !args[0],
+            v if v == ComState::LINK_GET_INTERRUPT.verb => {
+                rx.extend_from_slice(&[state.int_pending & state.int_mask, 0]);
+            }
+            v if v == ComState::SSID_SCAN_ON.verb => {
+                state.scanned = true;
+                self.raise(&mut state, INT_WLAN_SSID_UPDATE | INT_WLAN_SSID_FINISHED);
+            }
+            v if v == ComState::SSID_CHECK.verb => rx.push(state.scanned as u16),
+            v if v == ComState::SSID_FETCH_STR.verb => {
+                let visible = if state.scanned { &state.ssids[..] } else { &[] };
+                for i in 0..SSID_RECORDS {
+                    match visible.get(i) {
+                        Some((rssi, name)) => {
+                            rx.push(*rssi as u16 | (name.len() as u16) << 8);
+                            rx.extend(name_words(name, SSID_RECORD_WORDS - 1));
+                        }
+                        None => rx.extend_from_slice(&[0; SSID_RECORD_WORDS]),
+                    }
+                }
+            }
+            v if v == ComState::WLAN_SET_SSID.verb => state.requested_ssid = args.to_vec(),
+            v if v == ComState::WLAN_JOIN.verb => {
+                if let Some(ap) = requested_ap(&state) {
+                    self.connect(&mut state, ap);
+                }
+            }
+            v if v == ComState::WLAN_LEAVE.verb || v == ComState::WLAN_OFF.verb => {
+                self.disconnect(&mut state)
+            }
+            v if v == ComState::WLAN_GET_RSSI.verb => rx.push(match &state.link {
+                Some((rssi, _)) => 110 - *rssi as u16,
+                None => 0x0100, // error code: no link
+            }),
+            v if v == ComState::WLAN_SYNC_STATE.verb => {
+                if state.link.is_some() {
+                    rx.extend_from_slice(&[LinkState::Connected as u16, DhcpState::Bound as u16]);
+                } else {
+                    rx.extend_from_slice(&[LinkState::Disconnected as u16, DhcpState::Invalid as u16]);
+                }
+            }
+            v if v == ComState::WLAN_GET_IPV4_CONF.verb => rx.extend(ipv4_conf(&state).encode_u16().iter()),
+            v if v == ComState::WLAN_BIN_STATUS.verb => match &state.link {
+                Some((rssi, name)) => {
+                    rx.push(110 - *rssi as u16);
+                    rx.push(LinkState::Connected as u16);
+                    rx.extend(ipv4_conf(&state).encode_u16().iter());
+                    rx.push(name.len() as u16);
+                    rx.extend(name_words(name, SSID_RECORD_WORDS - 1));
+                }
+                None => {
+                    rx.push(0x0100);
+                    rx.push(LinkState::Disconnected as u16);
+                    rx.extend(ipv4_conf(&state).encode_u16().iter());
+                    rx.extend_from_slice(&[0; SSID_RECORD_WORDS]);
+                }
+            },
+            _ => log::trace!("HOSTED: emulated EC ignores verb 0x{:04x}", verb),
+        }
+        rx
+    }
+
+    fn connect(&self, state: &mut EcState, ap: (u8, String)) {
+        log::info!("HOSTED: EC link up to {} at -{}dBm", ap.1, ap.0);
+        state.link = Some(ap);
+        self.raise(state, INT_WLAN_CONNECT_EVENT | INT_WLAN_IPCONF_UPDATE);
+    }
+
+    fn disconnect(&self, state: &mut EcState) {
+        if state.link.take().is_some() {
+            log::info!("HOSTED: EC link down");
+            self.raise(state, INT_WLAN_DISCONNECT | INT_WLAN_IPCONF_UPDATE);
+        }
+    }
+
+    /// Latches `ints`, and pulses the EC interrupt line if any of them are unmasked.
+    fn raise(&self, state: &mut EcState, ints: u16) {
+        state.int_pending |= ints;
+        if state.int_pending & state.int_mask != 0 {
+            self.llio.hosted_com_event().ok();
+        }
+    }
+}
+
+/// The access point the OS most recently asked to join, if it's in range
+fn requested_ap(state: &EcState) -> Option<(u8, String)> {
+    state
+        .ssids
+        .iter()
+        .find(|(_, name)| {
+            StringSer::<STR_32_WORDS>::new().encode(name).map(|w| w.to_vec()).ok().as_ref()
+                == Some(&state.requested_ssid)
+        })
+        .cloned()
+}
+
+fn name_words(name: &str, words: usize) -> Vec<u16> {
+    let mut bytes = name.as_bytes().to_vec();
+    bytes.resize(words * 2, 0);
+    bytes.chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect()
+}
+
+fn ipv4_conf(state: &EcState) -> Ipv4Conf {
+    if state.link.is_some() {
+        Ipv4Conf {
+            dhcp: DhcpState::Bound,
+            mac: [2, 0, 0, 0, 0, 1],
+            addr: [10, 0, 2, 15],
+            gtwy: [10, 0, 2, 2],
+            mask: [255, 255, 255, 0],
+            dns1: [10, 0, 2, 3],
+            dns2: [1, 1, 1, 1],
+        }
+    } else {
+        Ipv4Conf { dhcp: DhcpState::Invalid, mac: [2, 0, 0, 0, 0, 1], ..Ipv4Conf::default() }
+    }
+}
@@ -19,6 +19,7 @@ use xous_semver::SemVer;
 /// this exists in the library user's memory space, so we can have up to one
 /// callback per library user.
 static mut BATTSTATS_CB: Option<fn(BattStats)> = None;
+static mut POWER_EVENT_CB: Option<fn(PowerEvent)> = None;
 
 /// handles callback messages from the COM server, in the library user's process space.
 fn battstats_server(sid0: usize, sid1: usize, sid2: usize, sid3: usize) {
@@ -34,6 +35,13 @@ fn battstats_server(sid0: usize, sid1: usize, sid2: usize, sid3: usize) {
                     }
                 }
             }),
+            Some(Callback::PowerEvent) => msg_scalar_unpack!(msg, event, _, _, _, {
+                unsafe {
+                    if let (Some(cb), Some(event)) = (POWER_EVENT_CB, FromPrimitive::from_usize(event)) {
+                        cb(event)
+                    }
+                }
+            }),
             Some(Callback::Drop) => {
                 break; // this exits the loop and kills the thread
             }
@@ -184,6 +192,32 @@ impl Com {
         }
     }
 
+    /// The protocol version and features agreed with the EC firmware. Apps can check these to hide
+    /// what the EC can't do, rather than call into APIs that will only log an error.
+    pub fn get_ec_capabilities(&self) -> Result<EcCapabilities, Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::EcCapabilities.to_usize().unwrap(), 0, 0, 0, 0),
+        )? {
+            xous::Result::Scalar2(protocol, features) => {
+                Ok(EcCapabilities { protocol: protocol as u16, features: features as u32 })
+            }
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// The battery temperature in thousandths of a degree Celsius, or `None` if the EC can't report it
+    pub fn get_ec_temperature(&self) -> Result<Option<i32>, Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::EcTemperature.to_usize().unwrap(), 0, 0, 0, 0),
+        )? {
+            xous::Result::Scalar2(0, _) => Ok(None),
+            xous::Result::Scalar2(_, deci_kelvin) => Ok(Some(deci_kelvin as i32 * 100 - 273_150)),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
     pub fn send_pds_line(&self, s: &String<512>) -> Result<(), Error> {
         use core::fmt::Write;
         let mut clone_s: String<512> = String::new();
@@ -205,31 +239,53 @@ impl Com {
             return Err(xous::Error::MemoryInUse);
         }
         unsafe { BATTSTATS_CB = Some(cb) };
-        if self.battstats_sid.is_none() {
-            let sid = xous::create_server().unwrap();
-            self.battstats_sid = Some(sid);
-            let sid_tuple = sid.to_u32();
-            xous::create_thread_4(
-                battstats_server,
-                sid_tuple.0 as usize,
-                sid_tuple.1 as usize,
-                sid_tuple.2 as usize,
-                sid_tuple.3 as usize,
-            )
-            .unwrap();
-            xous::send_message(
-                self.conn,
-                Message::new_scalar(
-                    Opcode::RegisterBattStatsListener.to_usize().unwrap(),
+        self.register_listener(Opcode::RegisterBattStatsListener)
+    }
+
+    /// Calls `cb` with each `PowerEvent` as the EC raises it. Returns `false` if the EC firmware can't
+    /// raise them, in which case only `PowerEvent::BatteryCritical` ever arrives, and the caller should
+    /// keep polling for the charger and battery state.
+    pub fn hook_power_events(&mut self, cb: fn(PowerEvent)) -> Result<bool, xous::Error> {
+        if unsafe { POWER_EVENT_CB }.is_some() {
+            return Err(xous::Error::MemoryInUse);
+        }
+        unsafe { POWER_EVENT_CB = Some(cb) };
+        self.register_listener(Opcode::RegisterPowerEventListener)?;
+        Ok(self.get_ec_capabilities()?.has(EcCapabilities::POWER_EVENTS))
+    }
+
+    /// Registers this process's callback server with the COM server for the callbacks of `op`, starting
+    /// the server if this is the first hook
+    fn register_listener(&mut self, op: Opcode) -> Result<(), xous::Error> {
+        let sid = match self.battstats_sid {
+            Some(sid) => sid,
+            None => {
+                let sid = xous::create_server().unwrap();
+                self.battstats_sid = Some(sid);
+                let sid_tuple = sid.to_u32();
+                xous::create_thread_4(
+                    battstats_server,
                     sid_tuple.0 as usize,
                     sid_tuple.1 as usize,
                     sid_tuple.2 as usize,
                     sid_tuple.3 as usize,
-                ),
-            )
-            .unwrap();
-        }
-        Ok(())
+                )
+                .unwrap();
+                sid
+            }
+        };
+        let sid_tuple = sid.to_u32();
+        xous::send_message(
+            self.conn,
+            Message::new_scalar(
+                op.to_usize().unwrap(),
+                sid_tuple.0 as usize,
+                sid_tuple.1 as usize,
+                sid_tuple.2 as usize,
+                sid_tuple.3 as usize,
+            ),
+        )
+        .map(|_| ())
     }
 
     pub fn get_batt_stats_blocking(&mut self) -> Result<BattStats, xous::Error> {
@@ -346,6 +402,26 @@ impl Com {
         Ok(ret)
     }
 
+    /// Returns the access points seen by the scans of the last couple of minutes, strongest first, along
+    /// with the time since the survey was last refreshed. This never waits on a scan, so a picker UI can
+    /// show it right away; keep it fresh with `set_wifi_survey()`.
+    pub fn wifi_survey(&self) -> Result<(Vec<SurveyRecord>, Option<u32>), xous::Error> {
+        let mut buf = Buffer::into_buf(SurveyReturn::default()).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::SsidSurvey.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        let response = buf.to_original::<SurveyReturn, _>().or(Err(xous::Error::InternalError))?;
+        Ok((response.list.iter().filter_map(|r| *r).collect(), response.age_ms))
+    }
+
+    /// Starts passive background scans every `interval_ms` (at least 10 seconds) to keep the survey fresh,
+    /// or stops them if `interval_ms` is 0.
+    pub fn set_wifi_survey(&self, interval_ms: u32) -> Result<(), xous::Error> {
+        send_message(
+            self.conn,
+            Message::new_scalar(Opcode::SetSurveyInterval.to_usize().unwrap(), interval_ms as usize, 0, 0, 0),
+        )
+        .map(|_| ())
+    }
+
     pub fn get_standby_current(&self) -> Result<Option<i16>, xous::Error> {
         if let xous::Result::Scalar2(valid, current) = send_message(
             self.conn,
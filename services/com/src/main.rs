@@ -3,6 +3,9 @@
 
 mod api;
 use core::convert::TryInto;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 use api::*;
 use com_rs::serdes::{Ipv4Conf, StringSer, STR_32_WORDS, STR_64_WORDS};
@@ -14,8 +17,31 @@ use xous_ipc::{Buffer, String};
 
 const LEGACY_REV: u32 = 0x8b5b_8e50; // this is the git rev shipped before we went to version tagging
 const LEGACY_TAG: u32 = 0x00_09_05_00; // this is corresponding tag
+/// The capability handshake: the EC answers with `CAPS_MAGIC`, its protocol version, and its feature
+/// bitmap as two words, low word first. The verb is reserved for this on the EC side; firmware that
+/// predates the handshake ignores it.
+pub(crate) const LINK_CAPS_VERB: u16 = 0x7C00;
+/// Leads the reply to `LINK_CAPS_VERB`, so that an EC which ignored the verb can't be taken for one that
+/// has no features
+pub(crate) const CAPS_MAGIC: u16 = 0xCA95;
+/// Reads the battery temperature from the gas gauge, in tenths of a kelvin. Only sent to an EC that has
+/// `EcCapabilities::TEMPERATURE`.
+pub(crate) const LINK_TEMP_VERB: u16 = 0x7C01;
+/// Power interrupts, in bits of the EC interrupt mask that `com_rs` leaves free. They are only unmasked
+/// on an EC that has `EcCapabilities::POWER_EVENTS`, and only once someone listens for them.
+pub(crate) const INT_CHARGER_PLUGGED: u16 = 0x0400;
+pub(crate) const INT_CHARGER_UNPLUGGED: u16 = 0x0800;
+pub(crate) const INT_BATTERY_LOW: u16 = 0x1000;
+pub(crate) const INT_THERMAL: u16 = 0x2000;
+const INT_POWER: u16 = INT_CHARGER_PLUGGED | INT_CHARGER_UNPLUGGED | INT_BATTERY_LOW | INT_THERMAL;
 const STD_TIMEOUT: u32 = 100;
 const EC_BOOT_WAIT_MS: usize = 3500;
+/// APs not seen for this long are dropped from the survey
+const SURVEY_AGING_MS: u64 = 120_000;
+/// a scan pass takes a few seconds; don't let a survey keep the radio scanning all the time
+const MIN_SURVEY_INTERVAL_MS: u32 = 10_000;
+/// the EC reports up to 8 APs per fetch, each as an rssi byte, a length byte and up to 32 bytes of name
+type SsidRecords = [[u8; 34]; 8];
 #[derive(Debug, Copy, Clone)]
 pub struct WorkRequest {
     work: ComSpec,
@@ -242,23 +268,43 @@ mod implementation {
     }
 }
 
-// a stub to try to avoid breaking hosted mode for as long as possible.
+#[cfg(not(target_os = "xous"))]
+mod hosted_ec;
+
+// hosted mode talks to an emulated EC, which can be scripted to test battery and Wi-Fi behaviors
 #[cfg(not(target_os = "xous"))]
 mod implementation {
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+
     use com_rs::*;
     use log::error;
 
     use crate::api::BattStats;
+    use crate::hosted_ec::EmulatedEc;
     use crate::return_battstats;
     use crate::WorkRequest;
 
     pub struct XousCom {
         pub workqueue: Vec<WorkRequest>,
         busy: bool,
+        ec: Arc<EmulatedEc>,
+        /// a verb whose argument words are still coming in, with the number expected and those received
+        partial: Option<(u16, usize, Vec<u16>)>,
+        /// words queued up by the EC for LINK_READ
+        rx: VecDeque<u16>,
     }
 
     impl XousCom {
-        pub fn new() -> XousCom { XousCom { workqueue: Vec::new(), busy: false } }
+        pub fn new() -> XousCom {
+            XousCom {
+                workqueue: Vec::new(),
+                busy: false,
+                ec: EmulatedEc::start(),
+                partial: None,
+                rx: VecDeque::new(),
+            }
+        }
 
         pub fn init(&mut self) {}
 
@@ -266,15 +312,30 @@ mod implementation {
 
         pub fn resume(&self) {}
 
-        pub fn txrx(&mut self, _tx: u16) -> u16 { 0xDEAD as u16 }
+        pub fn txrx(&mut self, tx: u16) -> u16 {
+            if let Some((verb, len, mut args)) = self.partial.take() {
+                args.push(tx);
+                if args.len() < len {
+                    self.partial = Some((verb, len, args));
+                } else {
+                    self.rx = self.ec.command(verb, &args).into();
+                }
+            } else if tx == ComState::LINK_READ.verb {
+                return self.rx.pop_front().unwrap_or(0xDEAD);
+            } else {
+                match EmulatedEc::arg_words(tx) {
+                    0 => self.rx = self.ec.command(tx, &[]).into(),
+                    len => self.partial = Some((tx, len, Vec::with_capacity(len))),
+                }
+            }
+            0xDEAD as u16
+        }
 
-        pub fn wait_txrx(&mut self, _tx: u16, _timeout: Option<u32>) -> u16 { 0xDEAD as u16 }
+        pub fn wait_txrx(&mut self, tx: u16, _timeout: Option<u32>) -> u16 { self.txrx(tx) }
 
-        pub fn try_wait_txrx(&mut self, _tx: u16, _timeout: u32) -> Option<u16> { None }
+        pub fn try_wait_txrx(&mut self, tx: u16, _timeout: u32) -> Option<u16> { Some(self.txrx(tx)) }
 
-        pub fn get_battstats(&mut self) -> BattStats {
-            BattStats { voltage: 3950, current: -110, soc: 85, remaining_capacity: 850 }
-        }
+        pub fn get_battstats(&mut self) -> BattStats { self.ec.battstats() }
 
         pub fn stby_current(&self) -> Option<i16> { None }
 
@@ -305,7 +366,7 @@ mod implementation {
 
         pub fn get_more_stats(&mut self) -> [u16; 15] {
             let mut ret = [0u16; 15];
-            ret[12] = 3950; // make the oqc test happy
+            ret[12] = self.ec.battstats().voltage; // make the oqc test happy
             ret
         }
 
@@ -377,6 +438,9 @@ fn main() -> ! {
     // create an array to track return connections for battery stats TODO: refactor this to use a Vec instead
     // of static allocations
     let mut battstats_conns: [Option<xous::CID>; 32] = [None; 32];
+    let mut power_conns: [Option<xous::CID>; 8] = [None; 8];
+    // the power interrupts unmasked on top of the ones the net service asks for
+    let mut power_int_mask: u16 = 0;
     // other future notification vectors shall go here
 
     let mut bl_main = 0;
@@ -408,8 +472,17 @@ fn main() -> ! {
         ((rev_msb as u32) << 16) | (rev_lsb as u32)
     };
     let mut ec_tag = { if ec_git_rev == LEGACY_REV { LEGACY_TAG } else { parse_version(&mut com) } };
+    let mut ec_caps = negotiate_caps(&mut com, ec_tag);
+    log::info!("EC protocol {}, features {:08x}", ec_caps.protocol, ec_caps.features);
     let mut desired_int_mask = 0;
 
+    // APs seen by recent scans, as name -> (rssi, time last seen)
+    let mut survey = HashMap::<std::string::String, (u8, u64)>::new();
+    let mut survey_refreshed: Option<u64> = None;
+    let survey_interval = Arc::new(AtomicU32::new(0));
+    let survey_running = Arc::new(AtomicBool::new(false));
+    let survey_cid = xous::connect(com_sid).expect("couldn't create survey pump connection");
+
     trace!("starting main loop");
     loop {
         let mut msg = xous::receive_message(com_sid).unwrap();
@@ -428,7 +501,7 @@ fn main() -> ! {
                 com.resume();
 
                 com.txrx(ComState::LINK_SET_INTMASK.verb);
-                com.txrx(desired_int_mask); // restore interrupts on resume
+                com.txrx(desired_int_mask | power_int_mask); // restore interrupts on resume
 
                 if bl_main != 0 || bl_sec != 0 {
                     // restore the backlight settings, if they are not 0
@@ -443,7 +516,7 @@ fn main() -> ! {
             Some(Opcode::LinkReset) => xous::msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                 com.txrx(ComState::LINK_SYNC.verb);
                 ticktimer.sleep_ms(200).unwrap(); // give some time for the link to reset - the EC does not have a guaranteed latency to respond to a link reset
-                if ec_tag >= u32::from_be_bytes(ComState::LINK_PING.apilevel) {
+                if ec_caps.has(EcCapabilities::LINK_PING) {
                     let mut attempts = 0;
                     let ping_value = 0xaeedu16; // for various reasons, this is a string that is "unlikely" to happen randomly
                     loop {
@@ -572,7 +645,7 @@ fn main() -> ! {
                                 com.txrx(ComState::FLASH_UNLOCK.verb);
                             }
                             api::FlashOp::Verify(addr, data) => {
-                                if ec_tag >= u32::from_be_bytes(ComState::FLASH_VERIFY.apilevel) {
+                                if ec_caps.has(EcCapabilities::FLASH_VERIFY) {
                                     com.txrx(ComState::FLASH_VERIFY.verb);
                                     com.txrx((*addr >> 16) as u16);
                                     com.txrx(*addr as u16);
@@ -623,6 +696,22 @@ fn main() -> ! {
                     error!("RegisterBattStatsListener ran out of space registering callback");
                 }
             }),
+            Some(Opcode::RegisterPowerEventListener) => msg_scalar_unpack!(msg, sid0, sid1, sid2, sid3, {
+                let sid = xous::SID::from_u32(sid0 as _, sid1 as _, sid2 as _, sid3 as _);
+                // find the slot first, so that a listener with nowhere to go doesn't leak a connection
+                match power_conns.iter_mut().find(|entry| entry.is_none()) {
+                    Some(entry) => match xous::connect(sid) {
+                        Ok(cid) => *entry = Some(cid),
+                        Err(e) => error!("RegisterPowerEventListener couldn't connect: {:?}", e),
+                    },
+                    None => error!("RegisterPowerEventListener ran out of space registering callback"),
+                }
+                if ec_caps.has(EcCapabilities::POWER_EVENTS) && power_int_mask == 0 {
+                    power_int_mask = INT_POWER;
+                    com.txrx(ComState::LINK_SET_INTMASK.verb);
+                    com.txrx(desired_int_mask | power_int_mask);
+                }
+            }),
             Some(Opcode::IsCharging) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                 com.txrx(ComState::POWER_CHARGER_STATE.verb);
                 let result = com.wait_txrx(ComState::LINK_READ.verb, Some(STD_TIMEOUT));
@@ -761,15 +850,33 @@ fn main() -> ! {
                         .expect("couldn't return WF200 revision tag");
                 } else {
                     ec_tag = parse_version(&mut com);
+                    // the EC may have been updated since boot
+                    ec_caps = negotiate_caps(&mut com, ec_tag);
                     xous::return_scalar(msg.sender, ec_tag as usize)
                         .expect("couldn't return WF200 revision tag");
                 }
             }
+            Some(Opcode::EcCapabilities) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                xous::return_scalar2(msg.sender, ec_caps.protocol as usize, ec_caps.features as usize)
+                    .expect("couldn't return EC capabilities");
+            }),
+            Some(Opcode::EcTemperature) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                let deci_kelvin = if ec_caps.has(EcCapabilities::TEMPERATURE) {
+                    com.txrx(LINK_TEMP_VERB);
+                    com.wait_txrx(ComState::LINK_READ.verb, Some(STD_TIMEOUT))
+                } else {
+                    0xDDDD
+                };
+                // 0xDDDD is what comes back when the EC is too busy to answer
+                let valid = deci_kelvin != 0xDDDD;
+                xous::return_scalar2(msg.sender, valid as usize, deci_kelvin as usize)
+                    .expect("couldn't return EC temperature");
+            }),
             Some(Opcode::Wf200Reset) => {
                 let start = ticktimer.elapsed_ms();
                 com.txrx(ComState::WF200_RESET.verb);
                 com.txrx(0);
-                if ec_tag >= u32::from_be_bytes(ComState::LINK_PING.apilevel) {
+                if ec_caps.has(EcCapabilities::LINK_PING) {
                     let mut attempts = 0;
                     let ping_value = 0xaeedu16; // for various reasons, this is a string that is "unlikely" to happen randomly
                     loop {
@@ -885,21 +992,14 @@ fn main() -> ! {
                 }
             }
             Some(Opcode::SsidFetchAsStringV2) => {
-                if ec_tag == LEGACY_TAG {
+                if !ec_caps.has(EcCapabilities::WLAN_STATUS_V2) {
                     log::error!("This API is not implemented for legacy EC revs");
                     continue;
                 }
-                com.txrx(ComState::SSID_FETCH_STR.verb);
-                // these sizes are hard-coded constants from the EC firmware. We don't have a good cross-code
-                // base method for sharing these yet, so they are just magic numbers.
-                let mut ssid_list: [[u8; 34]; 8] = [[0; 34]; 8];
-                for record in ssid_list.iter_mut() {
-                    for word in record.chunks_mut(2) {
-                        let data = com.wait_txrx(ComState::LINK_READ.verb, Some(STD_TIMEOUT)) as u16;
-                        word[0] = (data & 0xff) as u8;
-                        word[1] = ((data >> 8) & 0xff) as u8;
-                    }
-                }
+                let ssid_list = fetch_ssids(&mut com);
+                let now = ticktimer.elapsed_ms();
+                update_survey(&mut survey, &ssid_list, now);
+                survey_refreshed = Some(now);
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut ssid_ret =
@@ -914,6 +1014,53 @@ fn main() -> ! {
                 }
                 buffer.replace(ssid_ret).unwrap();
             }
+            Some(Opcode::SsidSurvey) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let now = ticktimer.elapsed_ms();
+                survey.retain(|_, (_, seen)| now - *seen < SURVEY_AGING_MS);
+                let mut aps: Vec<(&std::string::String, &(u8, u64))> = survey.iter().collect();
+                // strongest first; the rssi is the negative of the dBm figure
+                aps.sort_by_key(|(_, (rssi, _))| *rssi);
+                let mut ret = SurveyReturn::default();
+                for ((name, (rssi, seen)), record) in aps.iter().zip(ret.list.iter_mut()) {
+                    *record = Some(SurveyRecord {
+                        name: String::<32>::from_str(name),
+                        rssi: *rssi,
+                        age_ms: (now - seen).min(u32::MAX as u64) as u32,
+                    });
+                }
+                ret.age_ms = survey_refreshed.map(|t| (now - t).min(u32::MAX as u64) as u32);
+                buffer.replace(ret).unwrap();
+            }
+            Some(Opcode::SetSurveyInterval) => msg_scalar_unpack!(msg, interval, _, _, _, {
+                let interval = if interval == 0 { 0 } else { (interval as u32).max(MIN_SURVEY_INTERVAL_MS) };
+                survey_interval.store(interval, Ordering::SeqCst);
+                if interval != 0 {
+                    if ec_caps.has(EcCapabilities::WLAN_STATUS_V2) {
+                        com.txrx(ComState::SSID_SCAN_ON.verb);
+                    }
+                    if !survey_running.swap(true, Ordering::SeqCst) {
+                        std::thread::spawn({
+                            let interval = survey_interval.clone();
+                            let running = survey_running.clone();
+                            move || survey_pump(survey_cid, interval, running)
+                        });
+                    }
+                }
+            }),
+            Some(Opcode::SurveyPump) => msg_scalar_unpack!(msg, _, _, _, _, {
+                if !ec_caps.has(EcCapabilities::WLAN_STATUS_V2) || survey_interval.load(Ordering::SeqCst) == 0
+                {
+                    continue;
+                }
+                // pick up the results of the previous pass, then start the next one
+                let ssid_list = fetch_ssids(&mut com);
+                let now = ticktimer.elapsed_ms();
+                update_survey(&mut survey, &ssid_list, now);
+                survey_refreshed = Some(now);
+                com.txrx(ComState::SSID_SCAN_ON.verb);
+            }),
             Some(Opcode::WlanOn) => {
                 com.txrx(ComState::WLAN_ON.verb);
                 // re-sync the link, because the COM will take about a second to reload the Wifi drivers
@@ -1009,7 +1156,7 @@ fn main() -> ! {
                 com.txrx(ComState::WLAN_LEAVE.verb);
             }
             Some(Opcode::WlanStatus) => {
-                if ec_tag <= LEGACY_TAG {
+                if !ec_caps.has(EcCapabilities::WLAN_STATUS_V2) {
                     log::warn!("Legacy EC detected. Ignoring status request update");
                 } else {
                     let mut buffer =
@@ -1129,7 +1276,7 @@ fn main() -> ! {
             Some(Opcode::IntSetMask) => msg_blocking_scalar_unpack!(msg, mask_val, _, _, _, {
                 desired_int_mask = mask_val as u16;
                 com.txrx(ComState::LINK_SET_INTMASK.verb);
-                com.txrx(mask_val as u16);
+                com.txrx(desired_int_mask | power_int_mask);
                 xous::return_scalar(msg.sender, 1).expect("couldn't ack IntSetMask");
             }),
             Some(Opcode::IntAck) => msg_blocking_scalar_unpack!(msg, ack_val, _, _, _, {
@@ -1148,6 +1295,19 @@ fn main() -> ! {
                 let rxlen = com.wait_txrx(ComState::LINK_READ.verb, Some(STD_TIMEOUT));
                 if let Some(vector) = maybe_vector {
                     log::debug!("vector: 0x{:x}, len: {}", vector, rxlen);
+                    // 0xDDDD is the EC's "no answer" sentinel, not a vector
+                    let power =
+                        if vector == 0xDDDD { 0 } else { vector & (power_int_mask | INT_BATTERY_CRITICAL) };
+                    if power != 0 {
+                        notify_power_events(&mut power_conns, power, &llio);
+                        // the power interrupts are handled here, and never reach the net service, which
+                        // only acks the interrupts it knows
+                        if power & power_int_mask != 0 {
+                            com.txrx(ComState::LINK_ACK_INTERRUPT.verb);
+                            com.txrx(power & power_int_mask);
+                        }
+                    }
+                    let vector = if vector == 0xDDDD { vector } else { vector & !power_int_mask };
                     xous::return_scalar2(msg.sender, vector as _, rxlen as _)
                         .expect("couldn't return IntFetchVector");
                 } else {
@@ -1172,7 +1332,7 @@ fn main() -> ! {
                 let mut alloc_oversize_16 = [0u16; 2];
                 let mut control = 0;
                 let mut alloc_free_count = 0;
-                if ec_tag >= u32::from_be_bytes(ComState::WF200_DEBUG.apilevel) {
+                if ec_caps.has(EcCapabilities::WF200_DEBUG) {
                     com.txrx(ComState::WF200_DEBUG.verb);
                     config_16[0] = com.wait_txrx(ComState::LINK_READ.verb, Some(STD_TIMEOUT));
                     config_16[1] = com.wait_txrx(ComState::LINK_READ.verb, Some(STD_TIMEOUT));
@@ -1252,4 +1412,136 @@ fn parse_version(com: &mut crate::implementation::XousCom) -> u32 {
         | (((ver.maj & 0xff) as u32) << 24)
 }
 
+/// Runs the capability handshake. An EC that doesn't answer it is given the features implied by its version
+/// tag, `ec_tag`.
+fn negotiate_caps(com: &mut crate::implementation::XousCom, ec_tag: u32) -> EcCapabilities {
+    com.txrx(LINK_CAPS_VERB);
+    let mut reply = [0u16; 4];
+    for w in reply.iter_mut() {
+        *w = com.wait_txrx(ComState::LINK_READ.verb, Some(STD_TIMEOUT));
+    }
+    if reply[0] == CAPS_MAGIC {
+        EcCapabilities {
+            protocol: reply[1].min(COM_PROTOCOL_VERSION),
+            // features added after this server was built are of no use to it
+            features: (reply[2] as u32 | (reply[3] as u32) << 16) & EcCapabilities::KNOWN,
+        }
+    } else {
+        let mut features = 0;
+        if ec_tag >= u32::from_be_bytes(ComState::LINK_PING.apilevel) {
+            features |= EcCapabilities::LINK_PING;
+        }
+        if ec_tag >= u32::from_be_bytes(ComState::FLASH_VERIFY.apilevel) {
+            features |= EcCapabilities::FLASH_VERIFY;
+        }
+        if ec_tag != LEGACY_TAG {
+            features |= EcCapabilities::WLAN_STATUS_V2;
+        }
+        if ec_tag >= u32::from_be_bytes(ComState::WF200_DEBUG.apilevel) {
+            features |= EcCapabilities::WF200_DEBUG;
+        }
+        EcCapabilities { protocol: 0, features }
+    }
+}
+
+/// Tells the listeners about the power interrupts in `ints`. If the charger was both plugged and unplugged
+/// since the last fetch, the event that matches how it is now comes last.
+fn notify_power_events(conns: &mut [Option<CID>], ints: u16, llio: &llio::Llio) {
+    let mut events = Vec::new();
+    let charger = INT_CHARGER_PLUGGED | INT_CHARGER_UNPLUGGED;
+    if ints & charger == charger {
+        if llio.is_plugged_in() {
+            events.extend_from_slice(&[PowerEvent::ChargerUnplugged, PowerEvent::ChargerPlugged]);
+        } else {
+            events.extend_from_slice(&[PowerEvent::ChargerPlugged, PowerEvent::ChargerUnplugged]);
+        }
+    } else if ints & INT_CHARGER_PLUGGED != 0 {
+        events.push(PowerEvent::ChargerPlugged);
+    } else if ints & INT_CHARGER_UNPLUGGED != 0 {
+        events.push(PowerEvent::ChargerUnplugged);
+    }
+    if ints & INT_BATTERY_LOW != 0 {
+        events.push(PowerEvent::BatteryLow);
+    }
+    if ints & INT_BATTERY_CRITICAL != 0 {
+        events.push(PowerEvent::BatteryCritical);
+    }
+    if ints & INT_THERMAL != 0 {
+        events.push(PowerEvent::Thermal);
+    }
+    for event in events {
+        log::info!("power event: {:?}", event);
+        for entry in conns.iter_mut() {
+            if let Some(conn) = *entry {
+                match xous::try_send_message(
+                    conn,
+                    xous::Message::new_scalar(
+                        api::Callback::PowerEvent.to_usize().unwrap(),
+                        event.to_usize().unwrap(),
+                        0,
+                        0,
+                        0,
+                    ),
+                ) {
+                    Ok(_) => {}
+                    // the listener has quit
+                    Err(xous::Error::ServerNotFound) => *entry = None,
+                    Err(e) => log::warn!("couldn't send {:?} to a listener: {:?}", event, e),
+                }
+            }
+        }
+    }
+}
+
+fn fetch_ssids(com: &mut crate::implementation::XousCom) -> SsidRecords {
+    com.txrx(ComState::SSID_FETCH_STR.verb);
+    // these sizes are hard-coded constants from the EC firmware. We don't have a good cross-code
+    // base method for sharing these yet, so they are just magic numbers.
+    let mut ssid_list: SsidRecords = [[0; 34]; 8];
+    for record in ssid_list.iter_mut() {
+        for word in record.chunks_mut(2) {
+            let data = com.wait_txrx(ComState::LINK_READ.verb, Some(STD_TIMEOUT)) as u16;
+            word[0] = (data & 0xff) as u8;
+            word[1] = ((data >> 8) & 0xff) as u8;
+        }
+    }
+    ssid_list
+}
+
+/// Folds a fetch of scan records into the survey, and retires the APs that haven't been seen in a while
+fn update_survey(survey: &mut HashMap<std::string::String, (u8, u64)>, records: &SsidRecords, now: u64) {
+    for raw in records.iter() {
+        let len = if raw[1] < 32 { raw[1] as usize } else { 32 };
+        if len == 0 {
+            continue;
+        }
+        if let Ok(name) = core::str::from_utf8(&raw[2..2 + len]) {
+            survey.insert(name.to_string(), (raw[0], now));
+        }
+    }
+    survey.retain(|_, (_, seen)| now - *seen < SURVEY_AGING_MS);
+}
+
+/// Asks the main loop to refresh the survey every `interval` ms, until the interval is set to 0
+fn survey_pump(cid: CID, interval: Arc<AtomicU32>, running: Arc<AtomicBool>) {
+    let tt = ticktimer_server::Ticktimer::new().unwrap();
+    loop {
+        let ms = interval.load(Ordering::SeqCst);
+        if ms == 0 {
+            running.store(false, Ordering::SeqCst);
+            // a new interval could have been set between the load and the store, with nobody to serve it
+            if interval.load(Ordering::SeqCst) == 0 || running.swap(true, Ordering::SeqCst) {
+                break;
+            }
+            continue;
+        }
+        tt.sleep_ms(ms as usize).unwrap();
+        xous::try_send_message(
+            cid,
+            xous::Message::new_scalar(Opcode::SurveyPump.to_usize().unwrap(), 0, 0, 0, 0),
+        )
+        .ok();
+    }
+}
+
 fn from_le_words(words: [u16; 2]) -> u32 { words[0] as u32 | (words[1] as u32) << 16 }
@@ -0,0 +1,185 @@
+pub(crate) const SERVER_NAME_CONTACTS: &str = "_Contacts_";
+
+/// The longest name a contact can have, in bytes
+pub const CONTACT_NAME_LEN: usize = 64;
+/// The longest handle, such as an email address or a chat id, in bytes
+pub const HANDLE_LEN: usize = 64;
+/// The longest label of a handle or a key, such as "email" or "matrix", in bytes
+pub const LABEL_LEN: usize = 16;
+/// The longest postal address, in bytes; the lines are separated by ", "
+pub const ADDRESS_LEN: usize = 128;
+/// The longest key fingerprint, in bytes. A SHA-256 fingerprint fills it; an OpenPGP v4 one takes 20.
+pub const FINGERPRINT_LEN: usize = 32;
+pub const MAX_HANDLES: usize = 4;
+pub const MAX_KEYS: usize = 2;
+/// The most contacts returned by one `Opcode::List` request; the client asks again for more
+pub const MAX_LISTED: usize = 32;
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// Stores a new contact, and returns its id (memory message, `ContactRequest`)
+    Add = 0,
+    /// Replaces the contact with the same id (memory message, `ContactRequest`)
+    Update = 1,
+    /// Deletes a contact (blocking scalar, arg1 = id, returns 1 if it was there)
+    Delete = 2,
+    /// Reads the contact with the id of the request (memory message, `ContactRequest`)
+    Get = 3,
+    /// The ids and names of the contacts (memory message, `ContactList`)
+    List = 4,
+    /// The contact with a handle (memory message, `FindRequest`)
+    FindHandle = 5,
+    /// The contact with a key fingerprint (memory message, `FindRequest`)
+    FindKey = 6,
+    /// Starts or stops reading vCards from the USB serial port (blocking scalar, arg1 = 1 to start, 0 to
+    /// stop, returns 1 while reading)
+    SerialImport = 7,
+    /// Writes every contact as a vCard to the USB serial port (blocking scalar, returns the number
+    /// written, and 1 as the second value if the PDDB isn't mounted)
+    SerialExport = 8,
+    /// Exits the server
+    Quit = 9,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub enum ContactError {
+    /// The contacts are kept in the PDDB, which isn't mounted yet
+    NotMounted,
+    /// The PDDB could not be read or written
+    Storage,
+    /// The name is empty
+    BadContact,
+    /// There is no contact with that id, handle or key
+    NotFound,
+    /// The user didn't let the contacts leave the device
+    Declined,
+}
+
+/// A way to reach a contact, such as ("email", "alice@example.org") or ("matrix", "@alice:example.org").
+/// Labels are lower case; "email" and "tel" are the ones vCards have their own properties for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Handle {
+    pub label: xous_ipc::String<LABEL_LEN>,
+    pub value: xous_ipc::String<HANDLE_LEN>,
+}
+
+/// The fingerprint of a contact's public key, such as an OpenPGP key or a chat identity key
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct KeyFingerprint {
+    pub label: xous_ipc::String<LABEL_LEN>,
+    pub fingerprint: [u8; FINGERPRINT_LEN],
+    /// the number of bytes of `fingerprint` in use
+    pub len: u8,
+}
+
+/// A person or organisation. Other apps keep the `id` rather than a copy of the details, so that a
+/// change made here shows up everywhere.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Contact {
+    /// assigned by the store when the contact is added; 0 until then
+    pub id: u32,
+    pub name: xous_ipc::String<CONTACT_NAME_LEN>,
+    pub handles: [Option<Handle>; MAX_HANDLES],
+    pub keys: [Option<KeyFingerprint>; MAX_KEYS],
+    pub address: xous_ipc::String<ADDRESS_LEN>,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct ContactRequest {
+    pub contact: Contact,
+    pub error: Option<ContactError>,
+}
+
+/// One line of the contact list
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct ContactName {
+    pub id: u32,
+    pub name: xous_ipc::String<CONTACT_NAME_LEN>,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct ContactList {
+    /// the number of contacts to pass over, for the requests after the first
+    pub skip: u32,
+    pub names: [Option<ContactName>; MAX_LISTED],
+    /// set if there are contacts after the ones returned
+    pub more: bool,
+    pub error: Option<ContactError>,
+}
+
+/// A search for the contact with a handle (only `value` is compared, without regard to case) or a key
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct FindRequest {
+    pub handle: xous_ipc::String<HANDLE_LEN>,
+    pub fingerprint: [u8; FINGERPRINT_LEN],
+    pub len: u8,
+    pub contact: Option<Contact>,
+    pub error: Option<ContactError>,
+}
+
+impl Contact {
+    /// A contact with nothing but a name. A `name` longer than `CONTACT_NAME_LEN` bytes is cut short.
+    pub fn new(name: &str) -> Self {
+        Contact {
+            id: 0,
+            name: xous_ipc::String::from_str(truncate(name, CONTACT_NAME_LEN)),
+            handles: [None; MAX_HANDLES],
+            keys: [None; MAX_KEYS],
+            address: xous_ipc::String::new(),
+        }
+    }
+
+    /// Adds a handle, such as `("email", "alice@example.org")`. Returns `false` if all `MAX_HANDLES` are
+    /// in use. Labels are kept in lower case; values longer than `HANDLE_LEN` bytes are cut short.
+    pub fn add_handle(&mut self, label: &str, value: &str) -> bool {
+        match self.handles.iter_mut().find(|h| h.is_none()) {
+            Some(slot) => {
+                *slot = Some(Handle {
+                    label: xous_ipc::String::from_str(truncate(&label.to_ascii_lowercase(), LABEL_LEN)),
+                    value: xous_ipc::String::from_str(truncate(value, HANDLE_LEN)),
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds a key fingerprint. Returns `false` if all `MAX_KEYS` are in use, or `fingerprint` is longer
+    /// than `FINGERPRINT_LEN`.
+    pub fn add_key(&mut self, label: &str, fingerprint: &[u8]) -> bool {
+        if fingerprint.is_empty() || fingerprint.len() > FINGERPRINT_LEN {
+            return false;
+        }
+        match self.keys.iter_mut().find(|k| k.is_none()) {
+            Some(slot) => {
+                let mut key = KeyFingerprint {
+                    label: xous_ipc::String::from_str(truncate(&label.to_ascii_lowercase(), LABEL_LEN)),
+                    fingerprint: [0; FINGERPRINT_LEN],
+                    len: fingerprint.len() as u8,
+                };
+                key.fingerprint[..fingerprint.len()].copy_from_slice(fingerprint);
+                *slot = Some(key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether `handle` is one of the contact's handles, without regard to case
+    pub fn has_handle(&self, handle: &str) -> bool {
+        self.handles.iter().flatten().any(|h| h.value.as_str().unwrap_or("").eq_ignore_ascii_case(handle))
+    }
+}
+
+impl KeyFingerprint {
+    pub fn fingerprint(&self) -> &[u8] { &self.fingerprint[..(self.len as usize).min(FINGERPRINT_LEN)] }
+}
+
+/// The longest start of `text` that fits in `len` bytes
+pub(crate) fn truncate(text: &str, len: usize) -> &str {
+    let mut end = text.len().min(len);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
@@ -0,0 +1,163 @@
+#![doc = include_str!("../README.md")]
+
+pub mod api;
+pub use api::*;
+use num_traits::*;
+use xous::{send_message, Message, CID};
+use xous_ipc::Buffer;
+
+/// Client API to the address book. The service is only in images built with `--service contacts`, so
+/// `new()` fails rather than waits when it is absent.
+pub struct Contacts {
+    conn: CID,
+}
+impl Contacts {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection(api::SERVER_NAME_CONTACTS)?;
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(Contacts { conn })
+    }
+
+    /// Stores `contact`, and returns the id it is given. The `id` of `contact` is ignored.
+    pub fn add(&self, contact: Contact) -> Result<Result<u32, ContactError>, xous::Error> {
+        self.request(Opcode::Add, contact).map(|response| response.map(|c| c.id))
+    }
+
+    /// Replaces the contact with the same `id` as `contact`
+    pub fn update(&self, contact: Contact) -> Result<Result<(), ContactError>, xous::Error> {
+        self.request(Opcode::Update, contact).map(|response| response.map(|_| ()))
+    }
+
+    /// Deletes the contact with `id`. Returns `false` if there was no such contact, or the PDDB isn't
+    /// mounted.
+    pub fn delete(&self, id: u32) -> Result<bool, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::Delete.to_usize().unwrap(), id as usize, 0, 0, 0),
+        )? {
+            xous::Result::Scalar1(deleted) => Ok(deleted != 0),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// The contact with `id`
+    pub fn get(&self, id: u32) -> Result<Result<Contact, ContactError>, xous::Error> {
+        let mut contact = Contact::new("");
+        contact.id = id;
+        self.request(Opcode::Get, contact)
+    }
+
+    /// The id and name of every contact, in order of name
+    pub fn list(&self) -> Result<Result<Vec<ContactName>, ContactError>, xous::Error> {
+        let mut names: Vec<ContactName> = Vec::new();
+        loop {
+            let list =
+                ContactList { skip: names.len() as u32, names: [None; MAX_LISTED], more: false, error: None };
+            let mut buf = Buffer::into_buf(list).or(Err(xous::Error::InternalError))?;
+            buf.lend_mut(self.conn, Opcode::List.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+            let response = buf.to_original::<ContactList, _>().or(Err(xous::Error::InternalError))?;
+            if let Some(e) = response.error {
+                return Ok(Err(e));
+            }
+            names.extend(response.names.iter().flatten());
+            if !response.more {
+                return Ok(Ok(names));
+            }
+        }
+    }
+
+    /// The contact with `handle`, such as an email address or a chat id, compared without regard to case.
+    /// This is how an app that only knows how to reach someone finds out who they are.
+    pub fn find_handle(&self, handle: &str) -> Result<Result<Contact, ContactError>, xous::Error> {
+        let request = FindRequest {
+            handle: xous_ipc::String::from_str(truncate(handle, HANDLE_LEN)),
+            fingerprint: [0; FINGERPRINT_LEN],
+            len: 0,
+            contact: None,
+            error: None,
+        };
+        self.find(Opcode::FindHandle, request)
+    }
+
+    /// The contact with a key whose fingerprint is `fingerprint`
+    pub fn find_key(&self, fingerprint: &[u8]) -> Result<Result<Contact, ContactError>, xous::Error> {
+        if fingerprint.len() > FINGERPRINT_LEN {
+            return Ok(Err(ContactError::NotFound));
+        }
+        let mut request = FindRequest {
+            handle: xous_ipc::String::new(),
+            fingerprint: [0; FINGERPRINT_LEN],
+            len: fingerprint.len() as u8,
+            contact: None,
+            error: None,
+        };
+        request.fingerprint[..fingerprint.len()].copy_from_slice(fingerprint);
+        self.find(Opcode::FindKey, request)
+    }
+
+    /// Starts reading vCard (.vcf) files from the USB serial port, and switches the USB port to serial to
+    /// do so. Each contact is added as it arrives. A vCard file has no end that can be told apart from a
+    /// pause, so the import goes on until `stop_serial_import()`. Returns `true` if the service is reading.
+    pub fn start_serial_import(&self) -> Result<bool, xous::Error> { self.serial_import(true) }
+
+    /// Ends an import started by `start_serial_import()`; a notification says how many contacts came in
+    pub fn stop_serial_import(&self) -> Result<bool, xous::Error> { self.serial_import(false) }
+
+    /// Writes every contact to the USB serial port as a vCard 4.0, once the user has allowed it on the
+    /// device, and returns how many were written. The USB port goes back to its previous core afterwards.
+    pub fn serial_export(&self) -> Result<Result<u32, ContactError>, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::SerialExport.to_usize().unwrap(), 0, 0, 0, 0),
+        )? {
+            xous::Result::Scalar2(written, 0) => Ok(Ok(written as u32)),
+            xous::Result::Scalar2(_, 2) => Ok(Err(ContactError::Declined)),
+            xous::Result::Scalar2(_, _) => Ok(Err(ContactError::NotMounted)),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    fn serial_import(&self, start: bool) -> Result<bool, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::SerialImport.to_usize().unwrap(), start as usize, 0, 0, 0),
+        )? {
+            xous::Result::Scalar1(reading) => Ok(reading != 0),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    fn request(&self, op: Opcode, contact: Contact) -> Result<Result<Contact, ContactError>, xous::Error> {
+        let mut buf =
+            Buffer::into_buf(ContactRequest { contact, error: None }).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, op.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        let response = buf.to_original::<ContactRequest, _>().or(Err(xous::Error::InternalError))?;
+        match response.error {
+            Some(e) => Ok(Err(e)),
+            None => Ok(Ok(response.contact)),
+        }
+    }
+
+    fn find(&self, op: Opcode, request: FindRequest) -> Result<Result<Contact, ContactError>, xous::Error> {
+        let mut buf = Buffer::into_buf(request).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, op.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        let response = buf.to_original::<FindRequest, _>().or(Err(xous::Error::InternalError))?;
+        match (response.contact, response.error) {
+            (_, Some(e)) => Ok(Err(e)),
+            (Some(contact), None) => Ok(Ok(contact)),
+            (None, None) => Ok(Err(ContactError::NotFound)),
+        }
+    }
+}
+
+use core::sync::atomic::{AtomicU32, Ordering};
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Contacts {
+    fn drop(&mut self) {
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
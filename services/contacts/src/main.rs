@@ -0,0 +1,227 @@
+mod api;
+use api::*;
+mod store;
+mod vcard;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use locales::t;
+use num_traits::*;
+use usb_device_xous::{UsbDeviceType, UsbHid};
+use xous::{msg_blocking_scalar_unpack, CID};
+use xous_ipc::Buffer;
+
+/// Where the import summary comes from, in the notification center
+const NOTIFICATION_SOURCE: &str = "contacts";
+
+/// Asks the user to let the address book go out over USB. Any process can ask for an export, and the
+/// host can't learn anything about the contacts without it.
+fn confirm_export(modals: &modals::Modals, count: usize) -> bool {
+    modals
+        .add_list(vec![t!("contacts.yes", locales::LANG), t!("contacts.no", locales::LANG)])
+        .expect("couldn't build confirmation dialog");
+    let prompt = t!("contacts.export_prompt", locales::LANG).replace("{count}", &count.to_string());
+    match modals.get_radiobutton(&prompt) {
+        Ok(response) => response.as_str() == t!("contacts.yes", locales::LANG),
+        _ => false,
+    }
+}
+
+/// Switches the USB port back to `previous`, the core it was on before the serial port was needed
+fn restore_core(usb: &UsbHid, previous: Option<UsbDeviceType>) {
+    if let Some(previous) = previous.filter(|&core| core != UsbDeviceType::Serial) {
+        if usb.ensure_core(previous).is_err() {
+            log::warn!("failed to switch USB back from serial");
+        }
+    }
+}
+
+/// Adds every contact of the vCard files coming in on the USB serial port, until `reading` is cleared
+fn read_serial(reading: Arc<AtomicBool>, self_cid: CID) {
+    let usb = UsbHid::new();
+    let mut reader = vcard::VcardReader::new();
+    let (mut imported, mut failed) = (0, 0);
+    while reading.load(Ordering::SeqCst) {
+        let line = usb.serial_wait_ascii(Some('\n'));
+        if !reading.load(Ordering::SeqCst) {
+            break;
+        }
+        if let Some(contact) = reader.feed(&line) {
+            let mut buf = Buffer::into_buf(ContactRequest { contact, error: None }).unwrap();
+            buf.lend_mut(self_cid, Opcode::Add.to_u32().unwrap()).unwrap();
+            match buf.to_original::<ContactRequest, _>().unwrap().error {
+                None => imported += 1,
+                Some(e) => {
+                    log::warn!("couldn't add an imported contact: {:?}", e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    log::info!("imported {} contacts, {} failed", imported, failed);
+    let mut text = t!("contacts.imported", locales::LANG).replace("{count}", &imported.to_string());
+    if failed > 0 {
+        text.push_str(&t!("contacts.import_failed", locales::LANG).replace("{count}", &failed.to_string()));
+    }
+    let xns = xous_names::XousNames::new().unwrap();
+    status::Status::new(&xns).unwrap().post_notification(NOTIFICATION_SOURCE, &text, true).ok();
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let contacts_sid = xns.register_name(api::SERVER_NAME_CONTACTS, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", contacts_sid);
+    let self_cid = xous::connect(contacts_sid).unwrap();
+
+    let modals = modals::Modals::new(&xns).expect("can't connect to Modals server");
+    let usb = UsbHid::new();
+    let mut store = store::ContactStore::new();
+    // set while a thread is reading vCards from the serial port
+    let serial_reading = Arc::new(AtomicBool::new(false));
+    // the USB core to go back to once the import is over
+    let mut import_core: Option<UsbDeviceType> = None;
+
+    loop {
+        let mut msg = xous::receive_message(contacts_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Add) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut request = buffer.to_original::<ContactRequest, _>().unwrap();
+                match store.add(request.contact) {
+                    Ok(id) => request.contact.id = id,
+                    Err(e) => request.error = Some(e),
+                }
+                buffer.replace(request).unwrap();
+            }
+            Some(Opcode::Update) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut request = buffer.to_original::<ContactRequest, _>().unwrap();
+                request.error = store.update(request.contact).err();
+                buffer.replace(request).unwrap();
+            }
+            Some(Opcode::Delete) => msg_blocking_scalar_unpack!(msg, id, _, _, _, {
+                let deleted = store.delete(id as u32).unwrap_or(false);
+                xous::return_scalar(msg.sender, deleted as usize).ok();
+            }),
+            Some(Opcode::Get) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut request = buffer.to_original::<ContactRequest, _>().unwrap();
+                match store.get(request.contact.id) {
+                    Ok(contact) => request.contact = contact,
+                    Err(e) => request.error = Some(e),
+                }
+                buffer.replace(request).unwrap();
+            }
+            Some(Opcode::List) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut list = buffer.to_original::<ContactList, _>().unwrap();
+                match store.contacts() {
+                    Ok(contacts) => {
+                        let skip = list.skip as usize;
+                        for (slot, contact) in list.names.iter_mut().zip(contacts.iter().skip(skip)) {
+                            *slot = Some(ContactName { id: contact.id, name: contact.name });
+                        }
+                        list.more = contacts.len() > skip + MAX_LISTED;
+                    }
+                    Err(e) => list.error = Some(e),
+                }
+                buffer.replace(list).unwrap();
+            }
+            Some(Opcode::FindHandle) | Some(Opcode::FindKey) => {
+                let by_key = msg.body.id() == Opcode::FindKey.to_usize().unwrap();
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut request = buffer.to_original::<FindRequest, _>().unwrap();
+                let found = if by_key {
+                    store.find_key(&request.fingerprint[..(request.len as usize).min(FINGERPRINT_LEN)])
+                } else {
+                    store.find_handle(request.handle.as_str().unwrap_or(""))
+                };
+                match found {
+                    Ok(contact) => request.contact = Some(contact),
+                    Err(e) => request.error = Some(e),
+                }
+                buffer.replace(request).unwrap();
+            }
+            Some(Opcode::SerialImport) => msg_blocking_scalar_unpack!(msg, start, _, _, _, {
+                if start != 0 && !serial_reading.load(Ordering::SeqCst) {
+                    let previous = usb.get_current_core().ok();
+                    match usb.ensure_core(UsbDeviceType::Serial) {
+                        Ok(()) => {
+                            import_core = previous;
+                            serial_reading.store(true, Ordering::SeqCst);
+                            std::thread::spawn({
+                                let reading = serial_reading.clone();
+                                move || read_serial(reading, self_cid)
+                            });
+                            log::info!("reading vCards from the serial port");
+                        }
+                        Err(e) => log::warn!("failed to switch USB to serial: {:?}", e),
+                    }
+                } else if start == 0 && serial_reading.swap(false, Ordering::SeqCst) {
+                    // releases the reader blocked on the serial port
+                    usb.serial_flush().ok();
+                    restore_core(&usb, import_core.take());
+                    log::info!("stopped reading vCards");
+                }
+                let reading = serial_reading.load(Ordering::SeqCst);
+                xous::return_scalar(msg.sender, if reading { 1 } else { 0 }).ok();
+            }),
+            Some(Opcode::SerialExport) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                let contacts = match store.contacts() {
+                    Ok(contacts) => contacts,
+                    Err(_) => {
+                        xous::return_scalar2(msg.sender, 0, 1).ok();
+                        continue;
+                    }
+                };
+                if !confirm_export(&modals, contacts.len()) {
+                    log::info!("the user declined a contact export");
+                    xous::return_scalar2(msg.sender, 0, 2).ok();
+                    continue;
+                }
+                let mut written = 0;
+                let previous = usb.get_current_core().ok();
+                match usb.ensure_core(UsbDeviceType::Serial) {
+                    Ok(()) => {
+                        for contact in contacts.iter() {
+                            match usb.serial_write(&vcard::to_vcard(contact)) {
+                                Ok(()) => written += 1,
+                                Err(e) => log::warn!("couldn't write contact {}: {:?}", contact.id, e),
+                            }
+                        }
+                        log::info!("wrote {} contacts to the serial port", written);
+                        // an import in progress still needs the serial port
+                        if !serial_reading.load(Ordering::SeqCst) {
+                            restore_core(&usb, previous);
+                        }
+                    }
+                    Err(e) => log::warn!("failed to switch USB to serial: {:?}", e),
+                }
+                xous::return_scalar2(msg.sender, written, 0).ok();
+            }),
+            Some(Opcode::Quit) => {
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+    if serial_reading.swap(false, Ordering::SeqCst) {
+        usb.serial_flush().ok();
+        restore_core(&usb, import_core.take());
+    }
+    xns.unregister_server(contacts_sid).unwrap();
+    xous::destroy_server(contacts_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
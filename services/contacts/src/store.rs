@@ -0,0 +1,241 @@
+//! The contacts, kept in the PDDB: one key per contact, named by its id, in the `contacts` dictionary.
+//! The contacts in a secret basis come and go with it.
+
+use std::io::{ErrorKind, Read, Write};
+
+use crate::api::*;
+
+const CONTACT_DICT: &str = "contacts";
+
+/// A contact, and the basis it is stored in
+struct Stored {
+    basis: String,
+    contact: Contact,
+}
+
+pub(crate) struct ContactStore {
+    pddb: pddb::Pddb,
+    pddb_poller: pddb::PddbMountPoller,
+    /// read from the PDDB on first use, and again whenever a basis is opened or closed
+    contacts: Option<Vec<Stored>>,
+    /// the open bases when `contacts` was read
+    bases: Vec<String>,
+}
+impl ContactStore {
+    pub(crate) fn new() -> Self {
+        ContactStore {
+            pddb: pddb::Pddb::new(),
+            pddb_poller: pddb::PddbMountPoller::new(),
+            contacts: None,
+            bases: Vec::new(),
+        }
+    }
+
+    /// Every contact, in order of name
+    pub(crate) fn contacts(&mut self) -> Result<Vec<&Contact>, ContactError> {
+        let mut contacts: Vec<&Contact> = self.stored()?.iter().map(|s| &s.contact).collect();
+        contacts.sort_by_cached_key(|c| (c.name.as_str().unwrap_or("").to_lowercase(), c.id));
+        Ok(contacts)
+    }
+
+    pub(crate) fn get(&mut self, id: u32) -> Result<Contact, ContactError> {
+        self.stored()?.iter().find(|s| s.contact.id == id).map(|s| s.contact).ok_or(ContactError::NotFound)
+    }
+
+    /// The contact with `handle`, without regard to case
+    pub(crate) fn find_handle(&mut self, handle: &str) -> Result<Contact, ContactError> {
+        self.stored()?
+            .iter()
+            .find(|s| s.contact.has_handle(handle))
+            .map(|s| s.contact)
+            .ok_or(ContactError::NotFound)
+    }
+
+    pub(crate) fn find_key(&mut self, fingerprint: &[u8]) -> Result<Contact, ContactError> {
+        self.stored()?
+            .iter()
+            .find(|s| s.contact.keys.iter().flatten().any(|k| k.fingerprint() == fingerprint))
+            .map(|s| s.contact)
+            .ok_or(ContactError::NotFound)
+    }
+
+    /// Stores `contact` under a new id, in the basis opened last, and returns the id
+    pub(crate) fn add(&mut self, mut contact: Contact) -> Result<u32, ContactError> {
+        check(&contact)?;
+        contact.id = self.stored()?.iter().map(|s| s.contact.id).max().unwrap_or(0) + 1;
+        self.write(None, &contact)?;
+        // read again on next use, to learn which basis the PDDB put it in
+        self.contacts = None;
+        Ok(contact.id)
+    }
+
+    /// Replaces the contact with the id of `contact`, in the basis it is stored in
+    pub(crate) fn update(&mut self, contact: Contact) -> Result<(), ContactError> {
+        check(&contact)?;
+        let basis = match self.stored()?.iter().find(|s| s.contact.id == contact.id) {
+            Some(stored) => stored.basis.clone(),
+            None => return Err(ContactError::NotFound),
+        };
+        // the key is made again, so that a shorter contact leaves nothing of the longer one behind
+        self.pddb.delete_key(CONTACT_DICT, &contact.id.to_string(), Some(&basis)).ok();
+        self.write(Some(&basis), &contact)?;
+        for stored in self.contacts.get_or_insert_with(Vec::new).iter_mut() {
+            if stored.contact.id == contact.id {
+                stored.contact = contact;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the contact with `id`, and returns `false` if there was none
+    pub(crate) fn delete(&mut self, id: u32) -> Result<bool, ContactError> {
+        let basis = match self.stored()?.iter().find(|s| s.contact.id == id) {
+            Some(stored) => stored.basis.clone(),
+            None => return Ok(false),
+        };
+        match self.pddb.delete_key(CONTACT_DICT, &id.to_string(), Some(&basis)) {
+            Ok(()) => {
+                self.pddb.sync().ok();
+                self.contacts.get_or_insert_with(Vec::new).retain(|s| s.contact.id != id);
+                Ok(true)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => {
+                log::warn!("couldn't delete contact {}: {:?}", id, e);
+                Err(ContactError::Storage)
+            }
+        }
+    }
+
+    fn stored(&mut self) -> Result<&[Stored], ContactError> {
+        if !self.pddb_poller.is_mounted_nonblocking() {
+            return Err(ContactError::NotMounted);
+        }
+        let bases = self.pddb.list_basis();
+        if self.contacts.is_none() || bases != self.bases {
+            self.contacts = Some(self.load(&bases));
+            self.bases = bases;
+        }
+        Ok(self.contacts.as_deref().unwrap_or_default())
+    }
+
+    fn write(&self, basis: Option<&str>, contact: &Contact) -> Result<(), ContactError> {
+        let text = to_text(contact);
+        self.pddb
+            .get(CONTACT_DICT, &contact.id.to_string(), basis, true, true, Some(text.len()), None::<fn()>)
+            .and_then(|mut key| key.write_all(text.as_bytes()))
+            .and_then(|_| self.pddb.sync())
+            .map_err(|e| {
+                log::warn!("couldn't store contact {}: {:?}", contact.id, e);
+                ContactError::Storage
+            })
+    }
+
+    fn load(&self, bases: &[String]) -> Vec<Stored> {
+        let mut contacts = Vec::new();
+        for basis in bases.iter() {
+            let keys = match self.pddb.list_keys(CONTACT_DICT, Some(basis)) {
+                Ok(keys) => keys,
+                // no contacts in this basis
+                Err(_) => continue,
+            };
+            for name in keys.iter() {
+                let mut text = String::new();
+                let read = self
+                    .pddb
+                    .get(CONTACT_DICT, name, Some(basis), false, false, None, None::<fn()>)
+                    .and_then(|mut key| key.read_to_string(&mut text));
+                match (read, name.parse()) {
+                    (Ok(_), Ok(id)) => match from_text(id, &text) {
+                        Some(contact) => contacts.push(Stored { basis: basis.clone(), contact }),
+                        None => log::warn!("contact {} can't be read, skipping it", name),
+                    },
+                    _ => log::warn!("contact {} can't be read, skipping it", name),
+                }
+            }
+        }
+        contacts
+    }
+}
+
+/// A contact needs a name, and every field has to fit on one line of `to_text()`
+fn check(contact: &Contact) -> Result<(), ContactError> {
+    let mut labels: Vec<&str> =
+        contact.handles.iter().flatten().map(|h| h.label.as_str().unwrap_or("")).collect();
+    labels.extend(contact.keys.iter().flatten().map(|k| k.label.as_str().unwrap_or("")));
+    let mut fields = vec![contact.name.as_str().unwrap_or(""), contact.address.as_str().unwrap_or("")];
+    fields.extend(contact.handles.iter().flatten().map(|h| h.value.as_str().unwrap_or("")));
+    if fields[0].trim().is_empty()
+        || labels.iter().any(|l| l.is_empty() || l.contains(':'))
+        || fields.iter().chain(labels.iter()).any(|f| f.contains('\n'))
+    {
+        return Err(ContactError::BadContact);
+    }
+    Ok(())
+}
+
+/// A contact as "field=value" lines; a field that can hold more than one value is there once for each
+fn to_text(contact: &Contact) -> String {
+    let mut text = format!("name={}\n", contact.name.as_str().unwrap_or(""));
+    for handle in contact.handles.iter().flatten() {
+        text.push_str(&format!(
+            "handle={}:{}\n",
+            handle.label.as_str().unwrap_or(""),
+            handle.value.as_str().unwrap_or("")
+        ));
+    }
+    for key in contact.keys.iter().flatten() {
+        let hex: String = key.fingerprint().iter().map(|b| format!("{:02x}", b)).collect();
+        text.push_str(&format!("key={}:{}\n", key.label.as_str().unwrap_or(""), hex));
+    }
+    text.push_str(&format!("address={}\n", contact.address.as_str().unwrap_or("")));
+    text
+}
+
+fn from_text(id: u32, text: &str) -> Option<Contact> {
+    let mut contact = Contact::new("");
+    contact.id = id;
+    for line in text.lines() {
+        let (field, value) = line.split_once('=')?;
+        match field {
+            "name" => contact.name = xous_ipc::String::from_str(truncate(value, CONTACT_NAME_LEN)),
+            "handle" => {
+                let (label, value) = value.split_once(':')?;
+                contact.add_handle(label, value);
+            }
+            "key" => {
+                let (label, hex) = value.split_once(':')?;
+                let fingerprint = (0..hex.len())
+                    .step_by(2)
+                    .map(|i| hex.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+                    .collect::<Option<Vec<u8>>>()?;
+                contact.add_key(label, &fingerprint);
+            }
+            "address" => contact.address = xous_ipc::String::from_str(truncate(value, ADDRESS_LEN)),
+            _ => {}
+        }
+    }
+    Some(contact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip() {
+        let mut contact = Contact::new("Bob = Robert");
+        contact.id = 3;
+        contact.add_handle("email", "bob@example.org");
+        contact.add_handle("matrix", "@bob:example.org");
+        contact.add_key("chat", &[0xa5; 32]);
+        contact.address = xous_ipc::String::from_str("1 Road, Town");
+        assert_eq!(check(&contact), Ok(()));
+        assert_eq!(from_text(3, &to_text(&contact)), Some(contact));
+        assert_eq!(from_text(3, "name=x\nkey=openpgp:abc"), None);
+
+        contact.add_handle("a:b", "c");
+        assert_eq!(check(&contact), Err(ContactError::BadContact));
+        assert_eq!(check(&Contact::new(" ")), Err(ContactError::BadContact));
+    }
+}
@@ -0,0 +1,360 @@
+//! Reads contacts out of vCard files (RFC 6350, and the 3.0 and 2.1 files phones still write), one
+//! line at a time as they come in over the serial port, and writes contacts out as vCard 4.0.
+//!
+//! Only what a contact can keep is read: FN (or N, if there is no FN), EMAIL, TEL, IMPP, the first ADR,
+//! and the KEYs given as OpenPGP fingerprints. Photos, groups and the rest are passed over.
+
+use std::fmt::Write;
+
+use crate::api::*;
+
+/// Keys with this label are written as `KEY:openpgp4fpr:...`; the others get a property of their own
+const OPENPGP: &str = "openpgp";
+/// The property for fingerprints that vCard has no URI scheme for, such as chat identity keys
+const FINGERPRINT_PROPERTY: &str = "X-XOUS-FINGERPRINT";
+/// The longest line written, in bytes, not counting the line break
+const FOLD_AT: usize = 75;
+
+/// A contact whose BEGIN:VCARD has been read, but not yet its END:VCARD
+#[derive(Default)]
+struct Partial {
+    name: String,
+    /// put together from N, in case there is no FN
+    structured_name: String,
+    handles: Vec<(String, String)>,
+    keys: Vec<(String, Vec<u8>)>,
+    address: String,
+}
+
+pub(crate) struct VcardReader {
+    /// the content line being put together out of folded lines
+    line: String,
+    contact: Option<Partial>,
+}
+impl VcardReader {
+    pub(crate) fn new() -> Self { VcardReader { line: String::new(), contact: None } }
+
+    /// Takes the next line of the file, and returns the contact it completes, if any
+    pub(crate) fn feed(&mut self, raw: &str) -> Option<Contact> {
+        let raw = raw.trim_end_matches(|c| c == '\r' || c == '\n');
+        // a line that starts with white space carries on the one before
+        if let Some(rest) = raw.strip_prefix(|c| c == ' ' || c == '\t') {
+            self.line.push_str(rest);
+            return None;
+        }
+        let complete = std::mem::replace(&mut self.line, raw.to_string());
+        let contact = self.content_line(&complete);
+        // nothing carries on the END:VCARD line, and there may be no line after the last one
+        if raw.eq_ignore_ascii_case("END:VCARD") {
+            self.line.clear();
+            return contact.or_else(|| self.content_line(raw));
+        }
+        contact
+    }
+
+    fn content_line(&mut self, line: &str) -> Option<Contact> {
+        let (name, params, value) = split_line(line)?;
+        // "item1.EMAIL" is an EMAIL in a group
+        let name = name.rsplit('.').next().unwrap_or(name).to_ascii_uppercase();
+        match (name.as_str(), value.trim().to_ascii_uppercase().as_str()) {
+            ("BEGIN", "VCARD") => self.contact = Some(Partial::default()),
+            ("END", "VCARD") => return self.contact.take().and_then(|partial| partial.finish()),
+            _ => {}
+        }
+        let contact = self.contact.as_mut()?;
+        match name.as_str() {
+            "FN" => contact.name = unescape(value),
+            "N" => {
+                // family; given; additional; prefixes; suffixes
+                let parts: Vec<String> = split_components(value).iter().map(|p| unescape(p)).collect();
+                let order = [3, 1, 2, 0, 4];
+                let words: Vec<&str> = order
+                    .iter()
+                    .filter_map(|i| parts.get(*i))
+                    .map(|p| p.trim())
+                    .filter(|p| !p.is_empty())
+                    .collect();
+                contact.structured_name = words.join(" ");
+            }
+            "EMAIL" => contact.handles.push(("email".to_string(), unescape(value))),
+            "TEL" => {
+                let value = unescape(value);
+                let number = value.strip_prefix("tel:").unwrap_or(&value);
+                contact.handles.push(("tel".to_string(), number.to_string()));
+            }
+            "IMPP" => {
+                // "xmpp:alice@example.org": the scheme says what sort of handle it is
+                let value = unescape(value);
+                match value.split_once(':') {
+                    Some((scheme, handle)) => {
+                        contact.handles.push((scheme.to_ascii_lowercase(), handle.to_string()))
+                    }
+                    None => contact.handles.push(("im".to_string(), value)),
+                }
+            }
+            "ADR" if contact.address.is_empty() => {
+                let parts: Vec<String> = split_components(value)
+                    .iter()
+                    .map(|p| unescape(p).trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect();
+                contact.address = parts.join(", ");
+            }
+            "KEY" => match value.trim().strip_prefix("openpgp4fpr:").and_then(parse_hex) {
+                Some(fingerprint) => contact.keys.push((OPENPGP.to_string(), fingerprint)),
+                None => log::info!("skipping a key that isn't an OpenPGP fingerprint"),
+            },
+            FINGERPRINT_PROPERTY => {
+                let label = params
+                    .split(';')
+                    .find_map(|p| p.strip_prefix("TYPE="))
+                    .map(|label| label.trim_matches('"').to_ascii_lowercase());
+                if let (Some(label), Some(fingerprint)) = (label, parse_hex(value.trim())) {
+                    contact.keys.push((label, fingerprint));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+impl Partial {
+    fn finish(self) -> Option<Contact> {
+        let name = if self.name.trim().is_empty() { self.structured_name } else { self.name };
+        let name = name.trim();
+        if name.is_empty() {
+            log::warn!("skipping a vCard without a name");
+            return None;
+        }
+        let mut contact = Contact::new(name);
+        for (label, value) in self.handles.iter() {
+            if !value.trim().is_empty() && !contact.add_handle(label, value.trim()) {
+                log::warn!("{} has more than {} handles; skipping {}", name, MAX_HANDLES, value);
+            }
+        }
+        for (label, fingerprint) in self.keys.iter() {
+            if !contact.add_key(label, fingerprint) {
+                log::warn!("{} has more than {} keys, or one that is too long; skipping it", name, MAX_KEYS);
+            }
+        }
+        contact.address = xous_ipc::String::from_str(truncate(&self.address, ADDRESS_LEN));
+        Some(contact)
+    }
+}
+
+/// `contact` as a vCard 4.0, with CRLF line breaks
+pub(crate) fn to_vcard(contact: &Contact) -> String {
+    let mut lines = vec!["BEGIN:VCARD".to_string(), "VERSION:4.0".to_string()];
+    lines.push(format!("FN:{}", escape(contact.name.as_str().unwrap_or(""))));
+    for handle in contact.handles.iter().flatten() {
+        let (label, value) = (handle.label.as_str().unwrap_or(""), handle.value.as_str().unwrap_or(""));
+        lines.push(match label {
+            "email" => format!("EMAIL:{}", escape(value)),
+            "tel" => format!("TEL;VALUE=uri:tel:{}", value),
+            _ => format!("IMPP:{}:{}", label, value),
+        });
+    }
+    let address = contact.address.as_str().unwrap_or("");
+    if !address.is_empty() {
+        // the whole address goes in the street, since it isn't kept in parts
+        lines.push(format!("ADR:;;{};;;;", escape(address)));
+    }
+    for key in contact.keys.iter().flatten() {
+        let label = key.label.as_str().unwrap_or("");
+        let mut hex = String::new();
+        for byte in key.fingerprint().iter() {
+            write!(hex, "{:02X}", byte).unwrap();
+        }
+        lines.push(match label {
+            OPENPGP => format!("KEY:openpgp4fpr:{}", hex),
+            _ => format!("{};TYPE={}:{}", FINGERPRINT_PROPERTY, label, hex),
+        });
+    }
+    lines.push("END:VCARD".to_string());
+    let mut card = String::new();
+    for line in lines.iter() {
+        fold(&mut card, line);
+    }
+    card
+}
+
+/// Appends `line` to `card`, broken into lines of no more than `FOLD_AT` bytes
+fn fold(card: &mut String, line: &str) {
+    let mut rest = line;
+    let mut limit = FOLD_AT;
+    while rest.len() > limit {
+        let mut end = limit;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        card.push_str(&rest[..end]);
+        card.push_str("\r\n ");
+        rest = &rest[end..];
+        // the space that starts the next line counts towards it
+        limit = FOLD_AT - 1;
+    }
+    card.push_str(rest);
+    card.push_str("\r\n");
+}
+
+/// Splits "NAME;PARAM=a;PARAM=b:value" into its name, its parameters and its value. A ':' inside a
+/// quoted parameter value is not the end of the parameters.
+fn split_line(line: &str) -> Option<(&str, String, &str)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let (name, params) = head.split_once(';').unwrap_or((head, ""));
+    Some((name, params.to_ascii_uppercase(), value))
+}
+
+/// Splits a structured value, such as that of N or ADR, at the ';'s that aren't escaped
+fn split_components(value: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut escaped) = (0, false);
+    for (i, c) in value.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            ';' if !escaped => {
+                parts.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => escaped = false,
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Undoes the escapes of a text value; line breaks become ", ", since every field is one line
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => text.push_str(", "),
+                Some(escaped) => text.push(escaped),
+                None => {}
+            },
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+fn escape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ',' | ';') {
+            text.push('\\');
+        }
+        text.push(c);
+    }
+    text
+}
+
+/// Hex digits, which may be broken up by spaces or colons, as bytes
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = value
+        .chars()
+        .filter(|c| *c != ' ' && *c != ':')
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()?;
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return None;
+    }
+    Some(digits.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VCF: &str = "BEGIN:VCARD\r
+VERSION:3.0\r
+N:Doe;Jane;;Dr.;\r
+FN:Jane Doe\r
+item1.EMAIL;TYPE=INTERNET:jane@example.org\r
+TEL;TYPE=CELL:+1 555 0100\r
+IMPP:xmpp:jane@jabber.example.org\r
+ADR;TYPE=HOME:;;1 Main St\\, Apt 2;Springfield;;12345;USA\r
+KEY:openpgp4fpr:ABCDEF0123456789ABCDEF0123456789ABCDEF01\r
+PHOTO;ENCODING=b;TYPE=JPEG:AAAA\r
+ BBBB\r
+END:VCARD\r
+BEGIN:VCARD\r
+VERSION:2.1\r
+N:Smith;John\r
+TEL:555-0199\r
+END:VCARD\r
+BEGIN:VCARD\r
+VERSION:4.0\r
+EMAIL:nobody@example.org\r
+END:VCARD\r
+";
+
+    fn read(vcf: &str) -> Vec<Contact> {
+        let mut reader = VcardReader::new();
+        vcf.split_inclusive('\n').filter_map(|line| reader.feed(line)).collect()
+    }
+
+    fn handles(contact: &Contact) -> Vec<(&str, &str)> {
+        contact
+            .handles
+            .iter()
+            .flatten()
+            .map(|h| (h.label.as_str().unwrap(), h.value.as_str().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_vcards() {
+        let contacts = read(VCF);
+        assert_eq!(contacts.len(), 2);
+
+        let jane = &contacts[0];
+        assert_eq!(jane.name.as_str().unwrap(), "Jane Doe");
+        assert_eq!(
+            handles(jane),
+            vec![("email", "jane@example.org"), ("tel", "+1 555 0100"), ("xmpp", "jane@jabber.example.org")]
+        );
+        assert_eq!(jane.address.as_str().unwrap(), "1 Main St, Apt 2, Springfield, 12345, USA");
+        let key = jane.keys[0].unwrap();
+        assert_eq!(key.label.as_str().unwrap(), "openpgp");
+        assert_eq!(key.fingerprint().len(), 20);
+        assert_eq!(key.fingerprint()[..2], [0xAB, 0xCD]);
+
+        // no FN, so the name comes from N
+        assert_eq!(contacts[1].name.as_str().unwrap(), "John Smith");
+        assert_eq!(handles(&contacts[1]), vec![("tel", "555-0199")]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut contact = Contact::new("Ann; the \"Admin\", of the very long name that goes on");
+        contact.add_handle("email", "ann@example.org");
+        contact.add_handle("tel", "+44 20 7946 0000");
+        contact.add_handle("matrix", "@ann:example.org");
+        contact.add_key("openpgp", &[0x12; 20]);
+        contact.add_key("chat", &[0xfe; 32]);
+        contact.address = xous_ipc::String::from_str("10 Downing St, London; UK");
+        let card = to_vcard(&contact);
+        assert!(card.lines().all(|line| line.len() <= FOLD_AT + 1));
+        assert_eq!(read(&card), vec![contact]);
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(parse_hex("0a:FF 10"), Some(vec![0x0a, 0xff, 0x10]));
+        assert_eq!(parse_hex("abc"), None);
+        assert_eq!(parse_hex("zz"), None);
+    }
+}
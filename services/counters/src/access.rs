@@ -0,0 +1,125 @@
+//! Who may increment which counters, and how often.
+//!
+//! The namespace of a counter is its name up to the first `.`, so `fido.sig` is in `fido`. A namespace
+//! belongs to the first process to claim it, or to increment a counter in it, until that process exits;
+//! no one else may increment its counters. The `boot` namespace belongs to the service itself. Anybody
+//! may read any counter.
+//!
+//! Every increment writes a record to FLASH, and the active sector is erased every `RECORDS_PER_SECTOR`
+//! increments, so each namespace is allowed a burst of `BURST` increments, and after that one increment
+//! every `REFILL`. At that rate one namespace wears each sector by about 35 erases a day.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::api::{BOOT_COUNTER, COUNTER_NAME_LEN, CounterError};
+
+/// Increments a namespace may make in a row
+const BURST: u32 = 16;
+/// How long it takes a namespace to earn back one increment
+const REFILL: Duration = Duration::from_secs(10);
+
+/// The namespace of the counter called `name`
+pub fn namespace(name: &str) -> &str { name.split('.').next().unwrap_or(name) }
+
+struct Allowance {
+    increments: u32,
+    since: Instant,
+}
+
+/// `H` tells processes apart; it is `Holder` in the service
+pub struct Access<H> {
+    owners: HashMap<String, H>,
+    allowances: HashMap<String, Allowance>,
+}
+
+impl<H: Copy + Eq> Access<H> {
+    pub fn new() -> Self { Access { owners: HashMap::new(), allowances: HashMap::new() } }
+
+    /// Gives the namespace of `name` to `holder`, unless it belongs to another process that hasn't
+    /// exited. Succeeds if `holder` already has it.
+    pub fn claim(&mut self, name: &str, holder: H, exited: impl Fn(H) -> bool) -> Result<(), CounterError> {
+        if name.is_empty() || name.len() > COUNTER_NAME_LEN || name.contains('\0') {
+            return Err(CounterError::BadName);
+        }
+        let namespace = namespace(name);
+        if namespace == BOOT_COUNTER {
+            return Err(CounterError::NotOwner);
+        }
+        match self.owners.get(namespace) {
+            Some(&owner) if owner == holder => Ok(()),
+            Some(&owner) if !exited(owner) => Err(CounterError::NotOwner),
+            _ => {
+                self.owners.insert(namespace.to_string(), holder);
+                Ok(())
+            }
+        }
+    }
+
+    /// Takes one increment from the allowance of the namespace of `name`
+    pub fn spend(&mut self, name: &str, now: Instant) -> Result<(), CounterError> {
+        let allowance = self
+            .allowances
+            .entry(namespace(name).to_string())
+            .or_insert(Allowance { increments: BURST, since: now });
+        let earned = now.saturating_duration_since(allowance.since).as_secs() / REFILL.as_secs();
+        if earned > 0 {
+            allowance.increments = (allowance.increments as u64 + earned).min(BURST as u64) as u32;
+            allowance.since += REFILL * earned as u32;
+        }
+        if allowance.increments == 0 {
+            return Err(CounterError::RateLimited);
+        }
+        if allowance.increments == BURST {
+            // a full allowance earns nothing while it waits
+            allowance.since = now;
+        }
+        allowance.increments -= 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespaces_belong_to_the_first_claimer() {
+        let mut access = Access::new();
+        let alive = |_| false;
+        assert_eq!(namespace("fido.sig"), "fido");
+        assert_eq!(namespace("rollback"), "rollback");
+        assert_eq!(access.claim("fido", 1, alive), Ok(()));
+        assert_eq!(access.claim("fido.sig", 1, alive), Ok(()));
+        assert_eq!(access.claim("fido.sig", 2, alive), Err(CounterError::NotOwner));
+        assert_eq!(access.claim("fidox", 2, alive), Ok(()));
+        assert_eq!(access.claim("boot", 1, alive), Err(CounterError::NotOwner));
+        assert_eq!(access.claim("boot.x", 1, alive), Err(CounterError::NotOwner));
+        assert_eq!(access.claim("", 1, alive), Err(CounterError::BadName));
+        // once the owner has exited, the namespace is free again
+        assert_eq!(access.claim("fido.sig", 2, |holder| holder == 1), Ok(()));
+        assert_eq!(access.claim("fido.sig", 1, alive), Err(CounterError::NotOwner));
+    }
+
+    #[test]
+    fn increments_are_rate_limited() {
+        let mut access = Access::<u8>::new();
+        let start = Instant::now();
+        for _ in 0..BURST {
+            assert_eq!(access.spend("fido.sig", start), Ok(()));
+        }
+        assert_eq!(access.spend("fido.sig", start), Err(CounterError::RateLimited));
+        assert_eq!(access.spend("fido.other", start), Err(CounterError::RateLimited));
+        // other namespaces have their own allowance
+        assert_eq!(access.spend("rollback", start), Ok(()));
+
+        assert_eq!(access.spend("fido.sig", start + REFILL / 2), Err(CounterError::RateLimited));
+        assert_eq!(access.spend("fido.sig", start + REFILL), Ok(()));
+        assert_eq!(access.spend("fido.sig", start + REFILL), Err(CounterError::RateLimited));
+        let later = start + REFILL * 1000;
+        for _ in 0..BURST {
+            assert_eq!(access.spend("fido.sig", later), Ok(()));
+        }
+        assert_eq!(access.spend("fido.sig", later), Err(CounterError::RateLimited));
+    }
+}
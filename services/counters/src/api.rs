@@ -0,0 +1,46 @@
+pub(crate) const SERVER_NAME_COUNTERS: &str = "_Monotonic counters_";
+
+/// The longest name a counter can have, in bytes
+pub const COUNTER_NAME_LEN: usize = 24;
+/// The most counters that can exist at once. Counters are never deleted, so pick names with care.
+pub const MAX_COUNTERS: usize = 64;
+/// Incremented by the service once per boot, before anybody can connect to it
+pub const BOOT_COUNTER: &str = "boot";
+/// The namespace of the FIDO signature counters, which the vault claims at boot
+pub const FIDO_NAMESPACE: &str = "fido";
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// Increments a counter, and returns the new value (memory message, `CounterRequest`). Claims the
+    /// counter's namespace for the caller if no one holds it.
+    Increment = 0,
+    /// Reads a counter without changing it (memory message, `CounterRequest`)
+    Get = 1,
+    /// Exits the server; only taken from the server itself
+    Quit = 2,
+    /// Claims the namespace of the name in the request for the caller (memory message, `CounterRequest`)
+    Claim = 3,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub enum CounterError {
+    /// The name is empty, longer than `COUNTER_NAME_LEN`, or has a NUL in it
+    BadName,
+    /// There are already `MAX_COUNTERS` counters
+    Full,
+    /// The counter has reached `u32::MAX`, and can't go any further
+    Saturated,
+    /// The FLASH could not be written; the counter is unchanged
+    Storage,
+    /// The counter's namespace belongs to another process
+    NotOwner,
+    /// The counter's namespace has used up its increments for now; try again later
+    RateLimited,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct CounterRequest {
+    pub name: xous_ipc::String<COUNTER_NAME_LEN>,
+    pub value: u32,
+    pub error: Option<CounterError>,
+}
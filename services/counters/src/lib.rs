@@ -0,0 +1,70 @@
+#![doc = include_str!("../README.md")]
+
+pub mod api;
+pub use api::*;
+use num_traits::*;
+use xous::CID;
+use xous_ipc::Buffer;
+
+/// Client API to the monotonic counters. A counter is created by its first increment; until then it
+/// reads as 0.
+///
+/// A counter's namespace is its name up to the first `.`. Only the process that holds a namespace may
+/// increment its counters: the first process to claim it, or to increment a counter in it, holds it
+/// until it exits. Increments are rate limited per namespace, to spare the FLASH.
+pub struct Counters {
+    conn: CID,
+}
+impl Counters {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_COUNTERS)?;
+        Ok(Counters { conn })
+    }
+
+    /// Increments the counter called `name`, and returns its new value. The value is in FLASH by the
+    /// time this returns, so it will not be handed out again, even if the power is cut right after.
+    pub fn increment(&self, name: &str) -> Result<Result<u32, CounterError>, xous::Error> {
+        self.request(Opcode::Increment, name)
+    }
+
+    /// Claims the namespace of `name` for this process, so that no other process can increment its
+    /// counters. Fails with `NotOwner` if another process holds it.
+    pub fn claim(&self, name: &str) -> Result<Result<(), CounterError>, xous::Error> {
+        self.request(Opcode::Claim, name).map(|r| r.map(|_| ()))
+    }
+
+    /// The current value of the counter called `name`: the last value `increment()` returned for it.
+    pub fn get(&self, name: &str) -> Result<Result<u32, CounterError>, xous::Error> {
+        self.request(Opcode::Get, name)
+    }
+
+    /// The number of times the device has booted, counting this one
+    pub fn boot_count(&self) -> Result<Result<u32, CounterError>, xous::Error> { self.get(BOOT_COUNTER) }
+
+    fn request(&self, op: Opcode, name: &str) -> Result<Result<u32, CounterError>, xous::Error> {
+        if name.is_empty() || name.len() > COUNTER_NAME_LEN || name.contains('\0') {
+            return Ok(Err(CounterError::BadName));
+        }
+        let request = CounterRequest { name: xous_ipc::String::from_str(name), value: 0, error: None };
+        let mut buf = Buffer::into_buf(request).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, op.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        let response = buf.to_original::<CounterRequest, _>().or(Err(xous::Error::InternalError))?;
+        match response.error {
+            Some(e) => Ok(Err(e)),
+            None => Ok(Ok(response.value)),
+        }
+    }
+}
+
+use core::sync::atomic::{AtomicU32, Ordering};
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Counters {
+    fn drop(&mut self) {
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
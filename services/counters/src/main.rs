@@ -0,0 +1,141 @@
+mod access;
+mod api;
+use api::*;
+mod holder;
+mod store;
+use access::Access;
+use holder::*;
+use num_traits::*;
+#[cfg(target_os = "xous")]
+use store::Flash;
+use store::Store;
+use xous_ipc::Buffer;
+
+/// Two erase sectors, right after the early settings page. Like the early settings, they are outside
+/// of the PDDB and of every image, so updates and PDDB re-creation keep them.
+#[cfg(target_os = "xous")]
+const COUNTERS_LOC: u32 = xous::EARLY_SETTINGS + 0x1000;
+
+/// Nothing else maps the region, so only this server and the SPINOR server, whose clients are
+/// restricted, can change it.
+#[cfg(target_os = "xous")]
+struct SpinorFlash {
+    region: xous::MemoryRange,
+    spinor: spinor::Spinor,
+}
+#[cfg(target_os = "xous")]
+impl Flash for SpinorFlash {
+    fn region(&self) -> &[u8] {
+        // Safety: all values of `[u8]` are valid
+        unsafe { self.region.as_slice() }
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), CounterError> {
+        // `patch` writes without an erase when the bytes written to are blank
+        self.spinor.patch(self.region(), COUNTERS_LOC, data, offset as u32).map_err(|e| {
+            log::error!("couldn't write counters at {:x}: {:?}", offset, e);
+            CounterError::Storage
+        })
+    }
+
+    // a patch of all ones just erases the sector
+    fn erase(&mut self, offset: usize) -> Result<(), CounterError> {
+        self.write(offset, &[0xFF; store::SECTOR_LEN])
+    }
+}
+
+#[cfg(target_os = "xous")]
+fn flash(xns: &xous_names::XousNames) -> SpinorFlash {
+    let region = xous::syscall::map_memory(
+        xous::MemoryAddress::new((COUNTERS_LOC + xous::FLASH_PHYS_BASE) as usize),
+        None,
+        store::REGION_LEN,
+        xous::MemoryFlags::R,
+    )
+    .expect("couldn't map the counters region");
+    SpinorFlash { region, spinor: spinor::Spinor::new(xns).unwrap() }
+}
+
+/// Hosted mode has no FLASH; the counters start over every run
+#[cfg(not(target_os = "xous"))]
+fn flash(_xns: &xous_names::XousNames) -> store::RamFlash { store::RamFlash::new() }
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let mut store = Store::new(flash(&xns)).expect("couldn't load the counters");
+    // counted before the server is registered, so that nobody reads the count of the previous boot
+    match store.increment(BOOT_COUNTER) {
+        Ok(boots) => log::info!("boot number {}", boots),
+        Err(e) => log::error!("couldn't count this boot: {:?}", e),
+    }
+
+    let counters_sid = xns.register_name(api::SERVER_NAME_COUNTERS, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", counters_sid);
+
+    let mut access = Access::new();
+    loop {
+        let mut msg = xous::receive_message(counters_sid).unwrap();
+        let holder = holder_of(&msg);
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Increment) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut request = buffer.to_original::<CounterRequest, _>().unwrap();
+                let name = request.name.as_str().unwrap_or("");
+                let result = match holder {
+                    Some(holder) => access
+                        .claim(name, holder, has_exited)
+                        .and_then(|_| access.spend(name, std::time::Instant::now()))
+                        .and_then(|_| store.increment(name)),
+                    None => Err(CounterError::NotOwner),
+                };
+                match result {
+                    Ok(value) => request.value = value,
+                    Err(e) => {
+                        log::warn!("couldn't increment {}: {:?}", request.name, e);
+                        request.error = Some(e);
+                    }
+                }
+                buffer.replace(request).unwrap();
+            }
+            Some(Opcode::Claim) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut request = buffer.to_original::<CounterRequest, _>().unwrap();
+                let result = match holder {
+                    Some(holder) => access.claim(request.name.as_str().unwrap_or(""), holder, has_exited),
+                    None => Err(CounterError::NotOwner),
+                };
+                if let Err(e) = result {
+                    log::warn!("couldn't claim {}: {:?}", request.name, e);
+                    request.error = Some(e);
+                }
+                buffer.replace(request).unwrap();
+            }
+            Some(Opcode::Get) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut request = buffer.to_original::<CounterRequest, _>().unwrap();
+                request.value = store.get(request.name.as_str().unwrap_or(""));
+                buffer.replace(request).unwrap();
+            }
+            Some(Opcode::Quit) => {
+                if msg.sender.pid().map(|pid| pid.get() as u32) != Some(xous::process::id()) {
+                    log::warn!("Quit is only taken from the counters server itself");
+                    continue;
+                }
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+    xns.unregister_server(counters_sid).unwrap();
+    xous::destroy_server(counters_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
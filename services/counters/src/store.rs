@@ -0,0 +1,321 @@
+//! The counters are kept as a log of records in two FLASH sectors, of which one is active at a time.
+//!
+//! A sector starts with a header, and is followed by 32-byte records:
+//!
+//!   header: magic `XCTR` | version u32 | sequence u32 | check u32
+//!   record: name, zero padded to `COUNTER_NAME_LEN` | value u32 | check u32
+//!
+//! all little-endian, where the check is FNV-1a over the bytes before it. An increment appends a record
+//! with the new value into erased FLASH, so nothing is ever erased or overwritten while the active sector
+//! has room; the value of a counter is the largest in any valid record for it. A record torn by a power
+//! cut fails its check, and is skipped, along with its slot.
+//!
+//! Once the active sector is full, the other one is erased, a record with the value of each counter is
+//! written into it, and then its header, with the next sequence number. The header goes last, so that
+//! until the new sector is complete it isn't valid, and the old one is still used; after, the valid
+//! header with the higher sequence number wins.
+
+use std::collections::HashMap;
+
+use crate::api::{COUNTER_NAME_LEN, CounterError, MAX_COUNTERS};
+
+pub const SECTOR_LEN: usize = 4096;
+pub const REGION_LEN: usize = 2 * SECTOR_LEN;
+
+const MAGIC: [u8; 4] = *b"XCTR";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 16;
+const RECORD_LEN: usize = COUNTER_NAME_LEN + 8;
+const RECORDS_PER_SECTOR: usize = (SECTOR_LEN - HEADER_LEN) / RECORD_LEN;
+
+/// The FLASH the counters are kept in: `REGION_LEN` bytes, which must behave like NOR FLASH
+pub trait Flash {
+    /// The contents of the region
+    fn region(&self) -> &[u8];
+    /// Writes `data` at `offset` into the region. Only ever called on erased bytes.
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), CounterError>;
+    /// Erases the `SECTOR_LEN` bytes at `offset` into the region
+    fn erase(&mut self, offset: usize) -> Result<(), CounterError>;
+}
+
+/// A region in RAM, for hosted mode and tests. Like NOR FLASH, a write can only clear bits, so it is
+/// refused unless the bytes written to are erased.
+#[cfg(not(target_os = "xous"))]
+pub struct RamFlash(pub Vec<u8>);
+#[cfg(not(target_os = "xous"))]
+impl RamFlash {
+    pub fn new() -> Self { RamFlash(vec![0xFF; REGION_LEN]) }
+}
+#[cfg(not(target_os = "xous"))]
+impl Flash for RamFlash {
+    fn region(&self) -> &[u8] { &self.0 }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), CounterError> {
+        let target = &mut self.0[offset..offset + data.len()];
+        if target.iter().any(|&b| b != 0xFF) {
+            return Err(CounterError::Storage);
+        }
+        target.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn erase(&mut self, offset: usize) -> Result<(), CounterError> {
+        self.0[offset..offset + SECTOR_LEN].fill(0xFF);
+        Ok(())
+    }
+}
+
+pub struct Store<F: Flash> {
+    flash: F,
+    counters: HashMap<String, u32>,
+    /// offset of the active sector into the region
+    active: usize,
+    sequence: u32,
+    /// the first free record slot in the active sector
+    next: usize,
+}
+
+impl<F: Flash> Store<F> {
+    /// Loads the counters from `flash`, formatting it if neither sector has a valid header
+    pub fn new(flash: F) -> Result<Self, CounterError> {
+        let mut store = Store { flash, counters: HashMap::new(), active: 0, sequence: 0, next: 0 };
+        let headers = [header(store.sector(0)), header(store.sector(SECTOR_LEN))];
+        match headers {
+            [None, None] => {
+                log::info!("no counters found, formatting");
+                store.flash.erase(0)?;
+                store.flash.write(0, &header_bytes(0))?;
+            }
+            [Some(first), Some(second)] if second > first => {
+                store.active = SECTOR_LEN;
+                store.sequence = second;
+            }
+            [None, Some(second)] => {
+                store.active = SECTOR_LEN;
+                store.sequence = second;
+            }
+            [Some(first), _] => store.sequence = first,
+        }
+        store.load();
+        Ok(store)
+    }
+
+    /// The value of the counter called `name`; 0 if it was never incremented
+    pub fn get(&self, name: &str) -> u32 { self.counters.get(name).copied().unwrap_or(0) }
+
+    /// Increments the counter called `name`, returning the new value once it is in FLASH
+    pub fn increment(&mut self, name: &str) -> Result<u32, CounterError> {
+        if name.is_empty() || name.len() > COUNTER_NAME_LEN || name.contains('\0') {
+            return Err(CounterError::BadName);
+        }
+        let value = match self.counters.get(name) {
+            Some(&u32::MAX) => return Err(CounterError::Saturated),
+            Some(&value) => value + 1,
+            None if self.counters.len() >= MAX_COUNTERS => return Err(CounterError::Full),
+            None => 1,
+        };
+        if self.next == RECORDS_PER_SECTOR {
+            self.compact()?;
+        }
+        let offset = self.active + HEADER_LEN + self.next * RECORD_LEN;
+        // the slot is used up even if the write fails part way
+        self.next += 1;
+        self.flash.write(offset, &record_bytes(name, value))?;
+        self.counters.insert(name.to_string(), value);
+        Ok(value)
+    }
+
+    fn sector(&self, offset: usize) -> &[u8] { &self.flash.region()[offset..offset + SECTOR_LEN] }
+
+    fn load(&mut self) {
+        let sector = &self.flash.region()[self.active..self.active + SECTOR_LEN];
+        for (index, slot) in sector[HEADER_LEN..].chunks_exact(RECORD_LEN).enumerate() {
+            if slot.iter().all(|&b| b == 0xFF) {
+                continue;
+            }
+            self.next = index + 1;
+            match record(slot) {
+                Some((name, value)) => {
+                    let current = self.counters.entry(name).or_insert(value);
+                    *current = (*current).max(value);
+                }
+                None => log::warn!("skipping damaged counter record {}", index),
+            }
+        }
+    }
+
+    /// Moves the counters to the other sector
+    fn compact(&mut self) -> Result<(), CounterError> {
+        let other = SECTOR_LEN - self.active;
+        self.flash.erase(other)?;
+        let mut names: Vec<&String> = self.counters.keys().collect();
+        names.sort();
+        for (index, name) in names.iter().enumerate() {
+            self.flash
+                .write(other + HEADER_LEN + index * RECORD_LEN, &record_bytes(name, self.counters[*name]))?;
+        }
+        self.flash.write(other, &header_bytes(self.sequence.wrapping_add(1)))?;
+        log::info!("counters moved to sector at {:x}", other);
+        self.active = other;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.next = names.len();
+        Ok(())
+    }
+}
+
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c_9dc5u32, |hash, &b| (hash ^ b as u32).wrapping_mul(0x0100_0193))
+}
+
+fn header_bytes(sequence: u32) -> [u8; HEADER_LEN] {
+    let mut header = [0u8; HEADER_LEN];
+    header[..4].copy_from_slice(&MAGIC);
+    header[4..8].copy_from_slice(&VERSION.to_le_bytes());
+    header[8..12].copy_from_slice(&sequence.to_le_bytes());
+    let check = fnv1a(&header[..12]);
+    header[12..].copy_from_slice(&check.to_le_bytes());
+    header
+}
+
+/// The sequence number of a sector, if its header is valid
+fn header(sector: &[u8]) -> Option<u32> {
+    let header = &sector[..HEADER_LEN];
+    if header[..4] != MAGIC
+        || u32::from_le_bytes(header[4..8].try_into().unwrap()) != VERSION
+        || u32::from_le_bytes(header[12..].try_into().unwrap()) != fnv1a(&header[..12])
+    {
+        return None;
+    }
+    Some(u32::from_le_bytes(header[8..12].try_into().unwrap()))
+}
+
+fn record_bytes(name: &str, value: u32) -> [u8; RECORD_LEN] {
+    let mut record = [0u8; RECORD_LEN];
+    record[..name.len()].copy_from_slice(name.as_bytes());
+    record[COUNTER_NAME_LEN..COUNTER_NAME_LEN + 4].copy_from_slice(&value.to_le_bytes());
+    let check = fnv1a(&record[..COUNTER_NAME_LEN + 4]);
+    record[COUNTER_NAME_LEN + 4..].copy_from_slice(&check.to_le_bytes());
+    record
+}
+
+fn record(slot: &[u8]) -> Option<(String, u32)> {
+    let check = u32::from_le_bytes(slot[COUNTER_NAME_LEN + 4..].try_into().unwrap());
+    if check != fnv1a(&slot[..COUNTER_NAME_LEN + 4]) {
+        return None;
+    }
+    let name = &slot[..COUNTER_NAME_LEN];
+    let len = name.iter().position(|&b| b == 0).unwrap_or(COUNTER_NAME_LEN);
+    if len == 0 || name[len..].iter().any(|&b| b != 0) {
+        return None;
+    }
+    let name = std::str::from_utf8(&name[..len]).ok()?;
+    Some((
+        name.to_string(),
+        u32::from_le_bytes(slot[COUNTER_NAME_LEN..COUNTER_NAME_LEN + 4].try_into().unwrap()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cuts the power after `budget` more bytes have been written: the write in progress is left torn,
+    /// and nothing after it gets to FLASH.
+    struct TornFlash {
+        ram: RamFlash,
+        budget: usize,
+    }
+    impl Flash for TornFlash {
+        fn region(&self) -> &[u8] { self.ram.region() }
+
+        fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), CounterError> {
+            let len = data.len().min(self.budget);
+            self.ram.write(offset, &data[..len])?;
+            self.budget -= len;
+            if len < data.len() { Err(CounterError::Storage) } else { Ok(()) }
+        }
+
+        fn erase(&mut self, offset: usize) -> Result<(), CounterError> {
+            if self.budget == 0 {
+                return Err(CounterError::Storage);
+            }
+            self.ram.erase(offset)
+        }
+    }
+
+    #[test]
+    fn counts_and_persists() {
+        let mut store = Store::new(RamFlash::new()).unwrap();
+        assert_eq!(store.get("fido"), 0);
+        assert_eq!(store.increment("fido"), Ok(1));
+        assert_eq!(store.increment("fido"), Ok(2));
+        assert_eq!(store.increment("boot"), Ok(1));
+
+        let store = Store::new(store.flash).unwrap();
+        assert_eq!(store.get("fido"), 2);
+        assert_eq!(store.get("boot"), 1);
+    }
+
+    #[test]
+    fn survives_compaction() {
+        let mut store = Store::new(RamFlash::new()).unwrap();
+        for i in 1..=(3 * RECORDS_PER_SECTOR as u32) {
+            assert_eq!(store.increment("fido"), Ok(i));
+            if i % 10 == 0 {
+                assert_eq!(store.increment("boot"), Ok(i / 10));
+            }
+        }
+        let store = Store::new(store.flash).unwrap();
+        assert_eq!(store.get("fido"), 3 * RECORDS_PER_SECTOR as u32);
+        assert_eq!(store.get("boot"), 3 * RECORDS_PER_SECTOR as u32 / 10);
+    }
+
+    #[test]
+    fn rejects_bad_names_and_limits() {
+        let mut store = Store::new(RamFlash::new()).unwrap();
+        assert_eq!(store.increment(""), Err(CounterError::BadName));
+        assert_eq!(store.increment("a\0b"), Err(CounterError::BadName));
+        assert_eq!(store.increment(&"x".repeat(COUNTER_NAME_LEN + 1)), Err(CounterError::BadName));
+        assert_eq!(store.increment(&"x".repeat(COUNTER_NAME_LEN)), Ok(1));
+        for i in 1..MAX_COUNTERS {
+            assert_eq!(store.increment(&format!("c{}", i)), Ok(1));
+        }
+        assert_eq!(store.increment("one too many"), Err(CounterError::Full));
+        // with every counter in use, compaction still leaves room to count
+        for _ in 0..2 * RECORDS_PER_SECTOR {
+            store.increment("c1").unwrap();
+        }
+
+        let mut flash = RamFlash::new();
+        flash.write(0, &header_bytes(0)).unwrap();
+        flash.write(HEADER_LEN, &record_bytes("max", u32::MAX)).unwrap();
+        let mut store = Store::new(flash).unwrap();
+        assert_eq!(store.increment("max"), Err(CounterError::Saturated));
+        assert_eq!(store.get("max"), u32::MAX);
+    }
+
+    #[test]
+    fn never_goes_back_after_power_loss() {
+        // cut the power at every byte of a run that goes through two compactions
+        let increments = 2 * RECORDS_PER_SECTOR + 5;
+        for budget in (0..(increments + 2 * MAX_COUNTERS) * RECORD_LEN).step_by(7) {
+            let mut store = Store::new(RamFlash::new()).unwrap();
+            store.increment("other").unwrap();
+            let flash = TornFlash { ram: store.flash, budget };
+            let mut store = Store::new(flash).unwrap();
+            let mut returned = 0;
+            for _ in 0..increments {
+                match store.increment("fido") {
+                    Ok(value) => returned = value,
+                    Err(_) => break,
+                }
+            }
+
+            let mut store = Store::new(store.flash.ram).unwrap();
+            let value = store.get("fido");
+            assert_eq!(value, returned, "power cut after {} bytes", budget);
+            assert_eq!(store.get("other"), 1);
+            assert_eq!(store.increment("fido"), Ok(value + 1));
+        }
+    }
+}
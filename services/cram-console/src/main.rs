@@ -71,6 +71,7 @@ pub fn pump_thread(conn: usize, pump_run: Arc<AtomicBool>) {
 
 fn main() {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
 
     #[cfg(feature = "hwtest")]
@@ -211,6 +212,10 @@ fn main() {
         .register_observer(SERVER_NAME_STATUS, StatusOpcode::Keypress.to_u32().unwrap() as usize);
 
     let _modals = modals::Modals::new(&xns).unwrap();
+    // the clock idle policy belongs to whoever sets it first; claim it before any app can
+    cram_hal_service::ClockManager::new()
+        .set_idle_policy(cram_hal_service::ClockIdlePolicy::Delayed(1000))
+        .expect("couldn't claim the clock idle policy");
 
     shell::start_shell();
 
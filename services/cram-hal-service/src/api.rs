@@ -41,6 +41,42 @@ pub enum Opcode {
     // blocking scalar
     ConfigureUdmaEvent = 8,
 
+    /// Claim a PWM channel for the calling process (blocking scalar)
+    ClaimPwm = 9,
+    /// Release a PWM channel, driving it low (blocking scalar)
+    ReleasePwm = 10,
+    /// Set the frequency of the timer of a PWM channel, and start it (blocking scalar)
+    SetPwmFrequency = 11,
+    /// Set the duty cycle of a PWM channel (blocking scalar)
+    SetPwmDuty = 12,
+    /// Ramp the duty cycle of a PWM channel over a time (blocking scalar)
+    RampPwmDuty = 13,
+
+    /// Claim a SPI master controller and one of its chip selects for the calling process, and turn
+    /// on its UDMA clock (blocking scalar). SPIM0 and SPIM1 are reserved for the display and swap RAM.
+    /// A claim is released when the process that holds it exits.
+    ClaimSpim = 14,
+    /// Release a SPI master controller, and turn off its UDMA clock (blocking scalar)
+    ReleaseSpim = 15,
+
+    /// Take a reference on a UDMA peripheral clock, turning it on (blocking scalar). References are
+    /// released when the process that holds them exits.
+    ClockAcquire = 16,
+    /// Release a reference on a UDMA peripheral clock held by the caller (blocking scalar)
+    ClockRelease = 17,
+    /// Return the enabled, referenced and idle peripheral clocks as bitmasks (blocking scalar)
+    ClockStatus = 18,
+    /// Set the policy for gating clocks that are no longer referenced (blocking scalar). Only the first
+    /// process to set it, the power manager, may set it again.
+    SetClockIdlePolicy = 19,
+    /// Gate the clocks that have been idle long enough; sent by the server to itself (scalar)
+    ClockIdleSweep = 20,
+
+    /// Copy within a lent region by DMA (memory mutable lend)
+    DmaCopy = 21,
+    /// Fill part of a lent region with a word by DMA (memory mutable lend)
+    DmaFill = 22,
+
     /// Exit server
     Quit = 255,
 
@@ -94,6 +130,56 @@ pub enum MemoryLcdOpcode {
     SetCallbackServer = 3,
 }
 
+/// Outcome of a PWM call, returned in arg1 of the reply
+#[derive(Debug, Copy, Clone, PartialEq, Eq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+#[repr(usize)]
+pub enum PwmResult {
+    Ok = 0,
+    /// the timer or channel does not exist, or the frequency can't be reached
+    OutOfRange = 1,
+    /// the channel is held by another process
+    InUse = 2,
+    /// the channel has not been claimed by the caller
+    NotClaimed = 3,
+    /// another process holds a channel on the same timer, so its frequency can't be changed
+    TimerShared = 4,
+}
+
+/// A region lent with `DmaCopy` or `DmaFill` starts with a header of four `u32`: the source offset, the
+/// destination offset, the length, and the fill value. Offsets and length are in bytes, counted from the
+/// end of the header, and must be multiples of four.
+pub const DMA_HEADER_LEN: usize = 16;
+
+/// When a peripheral clock is gated, once its last reference has been released
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClockIdlePolicy {
+    /// gate the clock right away
+    Immediate,
+    /// gate the clock once it has gone this many milliseconds without a reference
+    Delayed(u32),
+    /// leave the clock on
+    Never,
+}
+
+impl ClockIdlePolicy {
+    pub fn to_scalar(&self) -> (usize, usize) {
+        match self {
+            ClockIdlePolicy::Immediate => (0, 0),
+            ClockIdlePolicy::Delayed(ms) => (1, *ms as usize),
+            ClockIdlePolicy::Never => (2, 0),
+        }
+    }
+
+    pub fn from_scalar(kind: usize, ms: usize) -> Option<Self> {
+        match kind {
+            0 => Some(ClockIdlePolicy::Immediate),
+            1 => Some(ClockIdlePolicy::Delayed(ms as u32)),
+            2 => Some(ClockIdlePolicy::Never),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct IoxConfigMessage {
     pub port: iox::IoxPort,
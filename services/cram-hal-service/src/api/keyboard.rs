@@ -93,6 +93,19 @@ pub(crate) enum KeyboardOpcode {
 
     /// a blocking key listener - blocks until a key is hit
     BlockingKeyListener = 9,
+
+    /// grab or release the keyboard on behalf of an app (blocking scalar: 0 to release, or 1 + `GrabPolicy`)
+    SetInputGrab = 15,
+}
+
+/// Sets who else hears about keystrokes while an app has grabbed the keyboard. Mirrors the keyboard
+/// service's `GrabPolicy`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub enum GrabPolicy {
+    /// the observer is still told that a key was hit, but not which one
+    NotifyObservers = 0,
+    /// the observer isn't notified either
+    Exclusive = 1,
 }
 
 // this structure is used to register a keyboard listener. Currently, we only accept
@@ -0,0 +1,142 @@
+use core::sync::atomic::Ordering;
+
+pub use cramium_hal::udma::PeriphId;
+use num_traits::*;
+
+pub use crate::api::ClockIdlePolicy;
+use crate::{Opcode, SERVER_NAME_CRAM_HAL};
+
+fn connect() -> xous::CID {
+    crate::REFCOUNT.fetch_add(1, Ordering::Relaxed);
+    let xns = xous_names::XousNames::new().unwrap();
+    xns.request_connection(SERVER_NAME_CRAM_HAL).expect("Couldn't connect to Cramium HAL server")
+}
+
+fn disconnect(conn: xous::CID) {
+    // de-allocate myself. It's unsafe because we are responsible to make sure nobody else is using the
+    // connection.
+    if crate::REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+        unsafe {
+            xous::disconnect(conn).unwrap();
+        }
+    }
+}
+
+/// A reference on a UDMA peripheral clock. The clock is on while any process holds a reference on it;
+/// once the last one is dropped, it is gated according to the idle policy.
+pub struct ClockRef {
+    conn: xous::CID,
+    periph: PeriphId,
+}
+
+impl ClockRef {
+    /// Fails with `AccessDenied` if the server refuses the reference.
+    pub fn acquire(periph: PeriphId) -> Result<Self, xous::Error> {
+        let conn = connect();
+        match xous::send_message(
+            conn,
+            xous::Message::new_blocking_scalar(
+                Opcode::ClockAcquire.to_usize().unwrap(),
+                periph as u32 as usize,
+                0,
+                0,
+                0,
+            ),
+        ) {
+            Ok(xous::Result::Scalar5(_, 1, _, _, _)) => Ok(ClockRef { conn, periph }),
+            Ok(_) => {
+                disconnect(conn);
+                Err(xous::Error::AccessDenied)
+            }
+            Err(e) => {
+                disconnect(conn);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn periph(&self) -> PeriphId { self.periph }
+}
+
+impl Drop for ClockRef {
+    fn drop(&mut self) {
+        xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(
+                Opcode::ClockRelease.to_usize().unwrap(),
+                self.periph as u32 as usize,
+                0,
+                0,
+                0,
+            ),
+        )
+        .expect("Couldn't release clock");
+        disconnect(self.conn);
+    }
+}
+
+/// The state of a UDMA peripheral clock
+#[derive(Debug, Copy, Clone)]
+pub struct ClockDomain {
+    pub periph: PeriphId,
+    /// the clock is on
+    pub enabled: bool,
+    /// a process holds a reference on the clock
+    pub referenced: bool,
+    /// the clock has no references, and is waiting for the idle policy to gate it
+    pub idle: bool,
+}
+
+pub struct ClockManager {
+    conn: xous::CID,
+}
+
+impl ClockManager {
+    pub fn new() -> Self { ClockManager { conn: connect() } }
+
+    /// The state of every UDMA peripheral clock
+    pub fn domains(&self) -> Vec<ClockDomain> {
+        match xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(Opcode::ClockStatus.to_usize().unwrap(), 0, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar5(_, enabled, referenced, idle, _)) => PeriphId::ALL
+                .iter()
+                .map(|&periph| {
+                    let bit = periph as u32 as usize;
+                    ClockDomain {
+                        periph,
+                        enabled: enabled & bit != 0,
+                        referenced: referenced & bit != 0,
+                        idle: idle & bit != 0,
+                    }
+                })
+                .collect(),
+            _ => panic!("Internal Error: Couldn't get clock status"),
+        }
+    }
+
+    /// Sets when clocks are gated once their last reference is released. The policy applies to all
+    /// processes, so only the power manager may set it: the first process to set it, which is done at
+    /// boot. Anyone else gets `AccessDenied`.
+    pub fn set_idle_policy(&self, policy: ClockIdlePolicy) -> Result<(), xous::Error> {
+        let (kind, ms) = policy.to_scalar();
+        match xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(
+                Opcode::SetClockIdlePolicy.to_usize().unwrap(),
+                kind,
+                ms,
+                0,
+                0,
+            ),
+        )? {
+            xous::Result::Scalar5(_, 1, _, _, _) => Ok(()),
+            _ => Err(xous::Error::AccessDenied),
+        }
+    }
+}
+
+impl Drop for ClockManager {
+    fn drop(&mut self) { disconnect(self.conn); }
+}
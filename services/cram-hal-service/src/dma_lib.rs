@@ -0,0 +1,179 @@
+use core::sync::atomic::Ordering;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Mutex, OnceLock};
+
+use num_traits::*;
+
+use crate::{api::DMA_HEADER_LEN, Opcode, SERVER_NAME_CRAM_HAL};
+
+const PAGE_SIZE: usize = 4096;
+
+/// A buffer that the DMA engine copies and fills within.
+///
+/// The engine works on physical pages, so both ends of a transfer have to be in the same region, which is
+/// lent to the HAL server for the duration of the transfer. A transfer is worth its setup for buffers of a
+/// few kilobytes and up, such as frame buffers; smaller copies are faster on the CPU.
+pub struct DmaRegion {
+    conn: xous::CID,
+    range: xous::MemoryRange,
+}
+
+impl DmaRegion {
+    /// Allocates a region with room for `len` bytes of data
+    pub fn new(len: usize) -> Result<Self, xous::Error> {
+        let size = (len + DMA_HEADER_LEN + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        let range = xous::syscall::map_memory(None, None, size, xous::MemoryFlags::R | xous::MemoryFlags::W)?;
+        let xns = xous_names::XousNames::new().unwrap();
+        let conn =
+            xns.request_connection(SERVER_NAME_CRAM_HAL).expect("Couldn't connect to Cramium HAL server");
+        crate::REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(DmaRegion { conn, range })
+    }
+
+    /// Length of the data, in bytes
+    pub fn len(&self) -> usize { self.range.len() - DMA_HEADER_LEN }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn as_slice(&self) -> &[u32] {
+        // safety: the data follows the header, is word aligned and lives as long as `self`
+        unsafe {
+            core::slice::from_raw_parts(
+                (self.range.as_ptr() as usize + DMA_HEADER_LEN) as *const u32,
+                self.len() / 4,
+            )
+        }
+    }
+
+    pub fn as_slice_mut(&mut self) -> &mut [u32] {
+        // safety: the data follows the header, is word aligned and lives as long as `self`
+        unsafe {
+            core::slice::from_raw_parts_mut(
+                (self.range.as_mut_ptr() as usize + DMA_HEADER_LEN) as *mut u32,
+                self.len() / 4,
+            )
+        }
+    }
+
+    fn run(&mut self, op: Opcode, src: usize, dst: usize, len: usize, value: u32) -> Result<(), xous::Error> {
+        if len == 0 {
+            return Ok(());
+        }
+        // safety: the header is at the start of the region
+        let header = unsafe { core::slice::from_raw_parts_mut(self.range.as_mut_ptr() as *mut u32, 4) };
+        header.copy_from_slice(&[src as u32, dst as u32, len as u32, value]);
+        match xous::send_message(
+            self.conn,
+            xous::Message::new_lend_mut(op.to_usize().unwrap(), self.range, None, None),
+        )? {
+            xous::Result::MemoryReturned(_, None) => Ok(()),
+            xous::Result::MemoryReturned(_, Some(code)) => Err(xous::Error::from_usize(code.get())),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Copies `len` bytes from offset `src` to offset `dst` of the data, and waits for the copy to finish.
+    ///
+    /// Offsets and length are in bytes, and must be multiples of four. Fails with `BadAlignment` if they
+    /// aren't, or `InvalidLimit` if either range is out of the region or the ranges overlap.
+    pub fn copy(&mut self, src: usize, dst: usize, len: usize) -> Result<(), xous::Error> {
+        self.run(Opcode::DmaCopy, src, dst, len, 0)
+    }
+
+    /// Fills `len` bytes from offset `dst` of the data with `value`, and waits for the fill to finish.
+    pub fn fill(&mut self, dst: usize, len: usize, value: u32) -> Result<(), xous::Error> {
+        self.run(Opcode::DmaFill, 0, dst, len, value)
+    }
+
+    /// Starts a copy, and returns without waiting for it. The region is handed back by `DmaPending::wait()`.
+    ///
+    /// If `notify` is given, a scalar message with the opcode is sent on the connection once the copy is
+    /// done, with 0 in `arg1` on success or the error code otherwise.
+    ///
+    /// The copies are made one after the other by a single thread, started with the first copy, which
+    /// blocks on the HAL while each copy runs.
+    pub fn start_copy(
+        self,
+        src: usize,
+        dst: usize,
+        len: usize,
+        notify: Option<(xous::CID, usize)>,
+    ) -> DmaPending {
+        let (done, result) = channel();
+        let job = CopyJob { region: self, src, dst, len, notify, done };
+        COPY_WORKER
+            .get_or_init(|| Mutex::new(start_copy_worker()))
+            .lock()
+            .unwrap()
+            .send(job)
+            .expect("DMA copy worker exited");
+        DmaPending { result, finished: None }
+    }
+}
+
+/// A copy handed to the worker started by `start_copy_worker()`
+struct CopyJob {
+    region: DmaRegion,
+    src: usize,
+    dst: usize,
+    len: usize,
+    notify: Option<(xous::CID, usize)>,
+    done: Sender<(DmaRegion, Result<(), xous::Error>)>,
+}
+
+static COPY_WORKER: OnceLock<Mutex<Sender<CopyJob>>> = OnceLock::new();
+
+fn start_copy_worker() -> Sender<CopyJob> {
+    let (jobs, job_queue) = channel::<CopyJob>();
+    std::thread::spawn(move || {
+        while let Ok(CopyJob { mut region, src, dst, len, notify, done }) = job_queue.recv() {
+            let result = region.copy(src, dst, len);
+            if let Some((cid, opcode)) = notify {
+                let code = match result {
+                    Ok(()) => 0,
+                    Err(e) => e.to_usize(),
+                };
+                xous::send_message(cid, xous::Message::new_scalar(opcode, code, 0, 0, 0)).ok();
+            }
+            // if the `DmaPending` was dropped, so is the region
+            done.send((region, result)).ok();
+        }
+    });
+    jobs
+}
+
+impl Drop for DmaRegion {
+    fn drop(&mut self) {
+        xous::syscall::unmap_memory(self.range).ok();
+        // de-allocate myself. It's unsafe because we are responsible to make sure nobody else is using the
+        // connection.
+        if crate::REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
+
+/// A copy started by `DmaRegion::start_copy()`
+pub struct DmaPending {
+    result: Receiver<(DmaRegion, Result<(), xous::Error>)>,
+    finished: Option<(DmaRegion, Result<(), xous::Error>)>,
+}
+
+impl DmaPending {
+    pub fn is_done(&mut self) -> bool {
+        if self.finished.is_none() {
+            self.finished = self.result.try_recv().ok();
+        }
+        self.finished.is_some()
+    }
+
+    /// Waits for the copy to finish, and returns the region with the result of the copy
+    pub fn wait(mut self) -> (DmaRegion, Result<(), xous::Error>) {
+        match self.finished.take() {
+            Some(finished) => finished,
+            None => self.result.recv().expect("DMA copy worker exited"),
+        }
+    }
+}
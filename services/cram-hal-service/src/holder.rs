@@ -0,0 +1,16 @@
+//! Claims on the HAL's peripherals are held by processes. A PID is reused once its process exits, so a
+//! process is remembered by its PID together with the PID's generation.
+
+/// A process, told apart from later processes that are given its PID
+pub(crate) type Holder = (u8, u32);
+
+/// The sender of `msg`, if the kernel says who it is
+pub(crate) fn holder_of(msg: &xous::MessageEnvelope) -> Option<Holder> {
+    let pid = msg.sender.pid()?;
+    xous::process_generation(pid).ok().map(|generation| (pid.get(), generation))
+}
+
+/// Whether `holder` has exited. Its PID may since have gone to another process.
+pub(crate) fn has_exited(holder: Holder) -> bool {
+    xous::PID::new(holder.0).map_or(true, |pid| xous::process_generation(pid) != Ok(holder.1))
+}
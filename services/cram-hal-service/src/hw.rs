@@ -1 +1,4 @@
+pub mod clocks;
+pub mod dma;
 pub mod keyboard;
+pub mod pwm;
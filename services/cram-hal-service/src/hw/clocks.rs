@@ -0,0 +1,183 @@
+//! Reference counted gating of the UDMA peripheral clocks.
+//!
+//! A clock is kept on while any client holds a reference on it, and is gated by the idle policy once
+//! the last reference is released. Clocks that were on at boot, or that were turned on with
+//! `ConfigureUdmaClock`, are pinned: their users don't track them, so they are never gated, even once a
+//! client has taken a reference on one and released it. A client that exits without releasing its
+//! references has them released the next time the clocks are asked about.
+//!
+//! The power domains and the bus clock gates of the SoC have no register description in the generated
+//! `utra`, so they are not managed here.
+
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Sender};
+use std::time::{Duration, Instant};
+
+use cramium_hal::udma::{GlobalConfig, PeriphId};
+use num_traits::*;
+
+use crate::api::{ClockIdlePolicy, Opcode};
+use crate::holder::*;
+
+pub const DEFAULT_IDLE_POLICY: ClockIdlePolicy = ClockIdlePolicy::Delayed(1000);
+
+pub struct ClockState {
+    /// references held on each clock, by client
+    refs: HashMap<(u32, Holder), u32>,
+    /// clocks with no references, and when their last reference was released
+    idle_since: HashMap<u32, Instant>,
+    /// gate bits of the clocks that are never gated
+    pinned: u32,
+    policy: ClockIdlePolicy,
+    /// the power manager, which is the first process to set the policy; no one else may change it
+    policy_owner: Option<Holder>,
+    /// asks the sweep thread to sweep after a delay
+    sweep_after: Sender<Duration>,
+}
+
+impl ClockState {
+    /// `server` is a connection to the HAL server, which the sweep thread sends `ClockIdleSweep` on. The
+    /// clocks that are on now are pinned.
+    pub fn new(server: xous::CID, udma_global: &GlobalConfig) -> Self {
+        let (sweep_after, delays) = channel::<Duration>();
+        std::thread::spawn(move || {
+            while let Ok(delay) = delays.recv() {
+                std::thread::sleep(delay);
+                xous::send_message(
+                    server,
+                    xous::Message::new_scalar(Opcode::ClockIdleSweep.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .ok();
+            }
+        });
+        ClockState {
+            refs: HashMap::new(),
+            idle_since: HashMap::new(),
+            pinned: udma_global.raw_clock_map(),
+            policy: DEFAULT_IDLE_POLICY,
+            policy_owner: None,
+            sweep_after,
+        }
+    }
+
+    /// Keeps `periph` from ever being gated, for a clock turned on by hand, or lets it be gated again once
+    /// it has been turned off by hand
+    pub fn set_pinned(&mut self, periph: PeriphId, pinned: bool) {
+        let bit = periph as u32;
+        if pinned {
+            self.pinned |= bit;
+            self.idle_since.remove(&bit);
+        } else {
+            self.pinned &= !bit;
+        }
+    }
+
+    pub fn acquire(&mut self, udma_global: &mut GlobalConfig, periph: PeriphId, holder: Holder) {
+        let bit = periph as u32;
+        *self.refs.entry((bit, holder)).or_insert(0) += 1;
+        self.idle_since.remove(&bit);
+        if !udma_global.is_clock_set(periph) {
+            log::debug!("clock on: {:?}", periph);
+            udma_global.clock_on(periph);
+        }
+    }
+
+    /// Drops one of the caller's references. Returns false if it held none.
+    pub fn release(&mut self, udma_global: &mut GlobalConfig, periph: PeriphId, holder: Holder) -> bool {
+        let bit = periph as u32;
+        match self.refs.get_mut(&(bit, holder)) {
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                self.refs.remove(&(bit, holder));
+            }
+            None => return false,
+        }
+        self.idle_if_unheld(udma_global, bit);
+        true
+    }
+
+    /// Drops every reference held by a process that has exited
+    pub fn release_exited(&mut self, udma_global: &mut GlobalConfig) {
+        let mut released = 0;
+        self.refs.retain(|&(bit, holder), _| {
+            if has_exited(holder) {
+                log::info!("PID {} exited holding a reference on clock {:x}", holder.0, bit);
+                released |= bit;
+                false
+            } else {
+                true
+            }
+        });
+        for shift in 0..u32::BITS {
+            if released & (1 << shift) != 0 {
+                self.idle_if_unheld(udma_global, 1 << shift);
+            }
+        }
+    }
+
+    /// Starts the idle policy on the clock at `bit`, if no one holds a reference on it
+    fn idle_if_unheld(&mut self, udma_global: &mut GlobalConfig, bit: u32) {
+        if self.held() & bit != 0 || self.pinned & bit != 0 {
+            return;
+        }
+        self.idle_since.insert(bit, Instant::now());
+        match self.policy {
+            ClockIdlePolicy::Immediate => self.sweep(udma_global, Instant::now()),
+            ClockIdlePolicy::Delayed(ms) => {
+                self.sweep_after.send(Duration::from_millis(ms as u64)).ok();
+            }
+            ClockIdlePolicy::Never => {}
+        }
+    }
+
+    /// Gate bits of the clocks that any client holds a reference on
+    pub fn held(&self) -> u32 { self.refs.keys().fold(0, |mask, (bit, _)| mask | bit) }
+
+    /// Gate bits of the clocks that are waiting for the idle policy to gate them
+    pub fn idle(&self) -> u32 { self.idle_since.keys().fold(0, |mask, bit| mask | bit) }
+
+    /// Sets the idle policy, if `holder` is the power manager. Returns whether it was set.
+    pub fn set_policy(
+        &mut self,
+        udma_global: &mut GlobalConfig,
+        policy: ClockIdlePolicy,
+        holder: Holder,
+    ) -> bool {
+        if *self.policy_owner.get_or_insert(holder) != holder {
+            log::warn!("PID {} tried to set the clock idle policy", holder.0);
+            return false;
+        }
+        log::info!("clock idle policy: {:?}", policy);
+        self.policy = policy;
+        match policy {
+            ClockIdlePolicy::Immediate => self.sweep(udma_global, Instant::now()),
+            ClockIdlePolicy::Delayed(ms) if !self.idle_since.is_empty() => {
+                self.sweep_after.send(Duration::from_millis(ms as u64)).ok();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Gates the clocks that have been idle for as long as the policy asks
+    pub fn sweep(&mut self, udma_global: &mut GlobalConfig, now: Instant) {
+        let delay = match self.policy {
+            ClockIdlePolicy::Immediate => Duration::ZERO,
+            ClockIdlePolicy::Delayed(ms) => Duration::from_millis(ms as u64),
+            ClockIdlePolicy::Never => return,
+        };
+        self.idle_since.retain(|&bit, since| {
+            if now.duration_since(*since) < delay {
+                return true;
+            }
+            if self.pinned & bit != 0 {
+                return false;
+            }
+            if let Some(periph) = FromPrimitive::from_u32(bit) {
+                log::debug!("clock gated: {:?}", periph);
+                udma_global.clock_off(periph);
+            }
+            false
+        });
+    }
+}
@@ -0,0 +1,124 @@
+//! Memory to memory copies and fills, offloaded to the PL230 DMA engine.
+//!
+//! The UDMA engine only moves data between peripherals and memory, so copies go to the PL230 instead.
+//! Clients lend the region to work on, which keeps the engine within memory the caller owns; the pages
+//! of the lent region are translated to physical addresses in this process. Jobs run on their own
+//! thread, so the server keeps answering while a copy is in flight, and the lend is returned to the
+//! caller once its job is done.
+//!
+//! The swapper copies pages from within the kernel's swap handler, where it can't wait on a server, so
+//! swap page moves are not offloaded.
+
+use std::sync::mpsc::{channel, Sender};
+
+use utralib::*;
+use xous_pl230::{ControlChannels, Pl230, MAX_CYCLE_TRANSFERS};
+
+use crate::api::DMA_HEADER_LEN;
+
+const PAGE_SIZE: usize = 4096;
+/// The channel used for memory to memory transfers
+const MEM_CHANNEL: usize = 0;
+/// Offset of the fill word in the control page, past the primary and alternate control structures
+const FILL_WORD_OFFSET: usize = 256;
+
+/// Flushes the L1 data cache, so the engine and the CPU see the same memory
+fn flush_dcache() {
+    // safety: this is a cache maintenance instruction, with no effect on program state
+    unsafe {
+        #[rustfmt::skip]
+        core::arch::asm!(
+            ".word 0x500F",
+            "nop",
+            "nop",
+            "nop",
+            "nop",
+            "fence",
+        );
+    }
+}
+
+/// Starts the thread that runs DMA jobs. `control_virt` and `control_phys` locate a page of IFRAM that
+/// is reserved for the engine's control structure.
+///
+/// Each job is the envelope of a `DmaCopy` or `DmaFill` message. When it is done, `valid` of the lent
+/// region is set to 0 on success or the error code otherwise, and the region is returned.
+pub fn start_dma_worker(control_virt: usize, control_phys: usize) -> Sender<(xous::MessageEnvelope, bool)> {
+    let (jobs, job_queue) = channel::<(xous::MessageEnvelope, bool)>();
+    std::thread::spawn(move || {
+        let mut pl230 = Pl230::new();
+        // safety: the page is reserved for the control structure, and is aligned as it is a page
+        let cc = unsafe { &mut *(control_virt as *mut ControlChannels) };
+        *cc = ControlChannels::default();
+        pl230.csr.wo(utra::pl230::CTRLBASEPTR, control_phys as u32);
+        pl230.csr.wfo(utra::pl230::CFG_MASTER_ENABLE, 1);
+        while let Ok((mut envelope, fill)) = job_queue.recv() {
+            if let Some(mem) = envelope.body.memory_message_mut() {
+                let result = run(&mut pl230, cc, control_virt, control_phys, mem.buf, fill);
+                mem.offset = None;
+                mem.valid = match result {
+                    Ok(()) => None,
+                    Err(e) => xous::MemorySize::new(e.to_usize()),
+                };
+            }
+            // dropping the envelope returns the region to the caller
+        }
+    });
+    jobs
+}
+
+fn run(
+    pl230: &mut Pl230,
+    cc: &mut ControlChannels,
+    control_virt: usize,
+    control_phys: usize,
+    region: xous::MemoryRange,
+    fill: bool,
+) -> Result<(), xous::Error> {
+    if region.len() < DMA_HEADER_LEN {
+        return Err(xous::Error::InvalidLimit);
+    }
+    // safety: the region is at least as long as the header, and pages are aligned for u32
+    let header = unsafe { core::slice::from_raw_parts(region.as_ptr() as *const u32, 4) };
+    let (src, dst, len, value) = (header[0] as usize, header[1] as usize, header[2] as usize, header[3]);
+    let data_len = region.len() - DMA_HEADER_LEN;
+    if src % 4 != 0 || dst % 4 != 0 || len % 4 != 0 {
+        return Err(xous::Error::BadAlignment);
+    }
+    if dst.checked_add(len).map(|end| end > data_len).unwrap_or(true) {
+        return Err(xous::Error::InvalidLimit);
+    }
+    if !fill {
+        if src.checked_add(len).map(|end| end > data_len).unwrap_or(true) {
+            return Err(xous::Error::InvalidLimit);
+        }
+        // the engine copies upwards, so overlapping ranges would be clobbered
+        if src < dst + len && dst < src + len {
+            return Err(xous::Error::InvalidLimit);
+        }
+    } else {
+        // safety: the fill word is past the control structures, within the reserved page
+        unsafe { ((control_virt + FILL_WORD_OFFSET) as *mut u32).write_volatile(value) };
+    }
+
+    let data = region.as_ptr() as usize + DMA_HEADER_LEN;
+    flush_dcache();
+    let mut done = 0;
+    while done < len {
+        // a cycle can't cross a page, as the pages of the region need not be contiguous
+        let dst_virt = data + dst + done;
+        let mut chunk = (len - done).min(PAGE_SIZE - dst_virt % PAGE_SIZE).min(MAX_CYCLE_TRANSFERS * 4);
+        let src_phys = if fill {
+            control_phys + FILL_WORD_OFFSET
+        } else {
+            let src_virt = data + src + done;
+            chunk = chunk.min(PAGE_SIZE - src_virt % PAGE_SIZE);
+            xous::syscall::virt_to_phys(src_virt & !(PAGE_SIZE - 1))? + src_virt % PAGE_SIZE
+        };
+        let dst_phys = xous::syscall::virt_to_phys(dst_virt & !(PAGE_SIZE - 1))? + dst_virt % PAGE_SIZE;
+        pl230.mem_cycle(cc, MEM_CHANNEL, src_phys as u32, dst_phys as u32, chunk / 4, fill);
+        done += chunk;
+    }
+    flush_dcache();
+    Ok(())
+}
@@ -45,6 +45,8 @@ fn keyboard_service() {
     let mut listener_op: Option<usize> = None;
     let mut observer_conn: Option<CID> = None;
     let mut observer_op: Option<usize> = None;
+    // set while an app has grabbed the keyboard
+    let mut input_grab: Option<GrabPolicy> = None;
 
     let mut esc_index: Option<usize> = None;
     let mut esc_chars = [0u8; 16];
@@ -91,6 +93,10 @@ fn keyboard_service() {
                     }
                 }
             }
+            Some(KeyboardOpcode::SetInputGrab) => msg_blocking_scalar_unpack!(msg, arg, _, _, _, {
+                input_grab = if arg == 0 { None } else { FromPrimitive::from_usize(arg - 1) };
+                xous::return_scalar(msg.sender, 0).expect("couldn't ack input grab");
+            }),
             Some(KeyboardOpcode::SelectKeyMap) => {
                 todo!();
             }
@@ -178,7 +184,10 @@ fn keyboard_service() {
                     }
                 }
 
-                if observer_conn.is_some() && observer_op.is_some() {
+                if observer_conn.is_some()
+                    && observer_op.is_some()
+                    && input_grab != Some(GrabPolicy::Exclusive)
+                {
                     log::trace!("sending observer key");
                     xous::try_send_message(
                         observer_conn.unwrap(),
@@ -187,11 +196,13 @@ fn keyboard_service() {
                     .ok();
                 }
 
-                for listener in blocking_listener.drain(..) {
-                    // we must unblock anyways once the key is hit; even if the key is invalid,
-                    // send the invalid key. The receiving library function will clean this up into a
-                    // nil-response vector.
-                    xous::return_scalar2(listener, key as u32 as usize, 0).unwrap();
+                if input_grab.is_none() {
+                    for listener in blocking_listener.drain(..) {
+                        // we must unblock anyways once the key is hit; even if the key is invalid,
+                        // send the invalid key. The receiving library function will clean this up into a
+                        // nil-response vector.
+                        xous::return_scalar2(listener, key as u32 as usize, 0).unwrap();
+                    }
                 }
             }),
             Some(KeyboardOpcode::HandlerTrigger) => {
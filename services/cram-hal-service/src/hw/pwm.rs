@@ -0,0 +1,155 @@
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use cramium_hal::pwm::{Pwm, PWM_CHANNELS, PWM_TIMERS};
+
+use crate::api::PwmResult;
+use crate::holder::{has_exited, Holder};
+
+/// How often the duty cycle of a ramping channel is stepped
+const RAMP_STEP: Duration = Duration::from_millis(10);
+/// How often channels are checked for holders that have exited, while any channel is held
+const EXIT_CHECK: Duration = Duration::from_secs(1);
+
+struct Ramp {
+    timer: usize,
+    channel: usize,
+    from: u32,
+    to: u32,
+    start: Instant,
+    duration: Duration,
+}
+
+/// The PWM block, the owner of each channel, and the ramps in progress
+pub struct PwmState {
+    pub pwm: Pwm,
+    owners: [[Option<Holder>; PWM_CHANNELS]; PWM_TIMERS],
+    ramps: Vec<Ramp>,
+}
+
+impl PwmState {
+    pub fn new(pwm: Pwm) -> Self {
+        PwmState { pwm, owners: [[None; PWM_CHANNELS]; PWM_TIMERS], ramps: Vec::new() }
+    }
+
+    /// Claims a channel for a process. Fails if another process holds it; a channel whose holder has
+    /// exited is released first.
+    pub fn claim(&mut self, timer: usize, channel: usize, holder: Holder) -> PwmResult {
+        if timer >= PWM_TIMERS || channel >= PWM_CHANNELS {
+            return PwmResult::OutOfRange;
+        }
+        self.release_exited();
+        match self.owners[timer][channel] {
+            Some(owner) if owner != holder => PwmResult::InUse,
+            _ => {
+                self.owners[timer][channel] = Some(holder);
+                PwmResult::Ok
+            }
+        }
+    }
+
+    /// Releases a channel, driving it low. The timer is stopped once none of its channels are held.
+    pub fn release(&mut self, timer: usize, channel: usize) {
+        self.cancel_ramp(timer, channel);
+        self.pwm.set_duty(timer, channel, 0);
+        self.owners[timer][channel] = None;
+        if self.owners[timer].iter().all(|owner| owner.is_none()) {
+            self.pwm.enable(timer, false);
+        }
+    }
+
+    /// Releases the channels held by processes that have exited, so that they don't keep driving their
+    /// outputs. Returns true if any channel is still held.
+    pub fn release_exited(&mut self) -> bool {
+        let mut held = false;
+        for timer in 0..PWM_TIMERS {
+            for channel in 0..PWM_CHANNELS {
+                match self.owners[timer][channel] {
+                    Some(owner) if has_exited(owner) => {
+                        log::info!("PWM {}:{} released, as PID {} has exited", timer, channel, owner.0);
+                        self.release(timer, channel);
+                    }
+                    Some(_) => held = true,
+                    None => (),
+                }
+            }
+        }
+        held
+    }
+
+    /// Checks that a channel exists and is held by the caller, if the caller is known
+    pub fn check(&self, timer: usize, channel: usize, holder: Option<Holder>) -> PwmResult {
+        if timer >= PWM_TIMERS || channel >= PWM_CHANNELS {
+            PwmResult::OutOfRange
+        } else if holder.is_none() || self.owners[timer][channel] != holder {
+            PwmResult::NotClaimed
+        } else {
+            PwmResult::Ok
+        }
+    }
+
+    /// True if no other process holds a channel on the timer, so its frequency can be changed
+    pub fn owns_timer(&self, timer: usize, holder: Option<Holder>) -> bool {
+        self.owners[timer]
+            .iter()
+            .all(|owner| owner.map_or(true, |owner| Some(owner) == holder || has_exited(owner)))
+    }
+
+    /// Stops any ramp running on a channel, leaving it at the duty cycle it reached
+    pub fn cancel_ramp(&mut self, timer: usize, channel: usize) {
+        self.ramps.retain(|r| !(r.timer == timer && r.channel == channel));
+    }
+
+    /// Starts ramping a channel from its current duty cycle to `to`, replacing any ramp in progress
+    pub fn ramp(&mut self, timer: usize, channel: usize, to: u32, duration: Duration) {
+        self.cancel_ramp(timer, channel);
+        let from = self.pwm.duty(timer, channel);
+        self.ramps.push(Ramp { timer, channel, from, to, start: Instant::now(), duration });
+    }
+
+    /// Moves each ramp on to where it should be at `now`, and drops the ones that are done
+    fn step(&mut self, now: Instant) {
+        let pwm = &mut self.pwm;
+        self.ramps.retain(|r| {
+            let elapsed = now.duration_since(r.start);
+            if elapsed >= r.duration {
+                pwm.set_duty(r.timer, r.channel, r.to);
+                false
+            } else {
+                let span = r.to as i64 - r.from as i64;
+                let duty = r.from as i64 + span * elapsed.as_millis() as i64 / r.duration.as_millis() as i64;
+                pwm.set_duty(r.timer, r.channel, duty as u32);
+                true
+            }
+        });
+    }
+}
+
+/// Starts the thread that steps ramps, and that releases the channels of processes that have exited.
+/// Send on the returned channel after starting a ramp, to wake the thread if it is idle.
+pub fn start_ramp_thread(state: Arc<Mutex<PwmState>>) -> Sender<()> {
+    let (wake, waker) = channel();
+    std::thread::spawn(move || {
+        loop {
+            let (idle, held) = {
+                let mut state = state.lock().unwrap();
+                state.step(Instant::now());
+                let idle = state.ramps.is_empty();
+                (idle, idle && state.release_exited())
+            };
+            if held {
+                if let Err(RecvTimeoutError::Disconnected) = waker.recv_timeout(EXIT_CHECK) {
+                    break;
+                }
+            } else if idle {
+                if waker.recv().is_err() {
+                    break;
+                }
+            } else {
+                std::thread::sleep(RAMP_STEP);
+            }
+        }
+    });
+    wake
+}
@@ -2,6 +2,7 @@ use num_traits::*;
 use xous::{send_message, Message};
 use xous_ipc::{Buffer, String};
 
+pub use crate::api::keyboard::GrabPolicy;
 use crate::api::keyboard::*;
 
 #[derive(Debug)]
@@ -41,6 +42,19 @@ impl Keyboard {
         Ok(())
     }
 
+    /// Grabs the keyboard, or releases the grab with `None`. Only the GAM should call this.
+    pub fn set_input_grab(&self, grab: Option<GrabPolicy>) -> Result<(), xous::Error> {
+        let arg = match grab {
+            Some(policy) => 1 + policy.to_usize().unwrap(),
+            None => 0,
+        };
+        send_message(
+            self.conn,
+            Message::new_blocking_scalar(KeyboardOpcode::SetInputGrab.to_usize().unwrap(), arg, 0, 0, 0),
+        )
+        .map(|_| ())
+    }
+
     pub fn set_keymap(&self, map: KeyMap) -> Result<(), xous::Error> {
         send_message(
             self.conn,
@@ -1,11 +1,19 @@
 pub mod api;
+pub mod clock_lib;
+pub mod dma_lib;
 pub mod iox_lib;
 pub mod keyboard;
+pub mod pwm_lib;
+pub mod spim_lib;
 pub mod trng;
 
 use api::Opcode;
 use cramium_hal::udma::{EventChannel, PeriphEventType, PeriphId};
+pub use clock_lib::*;
+pub use dma_lib::*;
 pub use iox_lib::*;
+pub use pwm_lib::*;
+pub use spim_lib::*;
 use num_traits::*;
 
 /// Do not change this constant, it is hard-coded into libraries in order to break
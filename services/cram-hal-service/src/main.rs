@@ -1,11 +1,17 @@
 mod api;
+mod holder;
 mod hw;
 
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
 use api::*;
 use cramium_hal::{
     iox,
+    pwm::{Pwm, PWM_DUTY_MAX},
     udma::{EventChannel, GlobalConfig, PeriphId},
 };
+use holder::*;
 #[cfg(feature = "quantum-timer")]
 use utralib::utra;
 #[cfg(feature = "quantum-timer")]
@@ -35,6 +41,12 @@ fn timer_tick(_irq_no: usize, arg: *mut usize) {
     ptimer.irq_csr.wo(utra::irqarray18::EV_PENDING, ptimer.irq_csr.r(utra::irqarray18::EV_PENDING));
 }
 
+/// The UDMA clock gates of the SPI master controllers, by channel number
+const SPIM_PERIPHS: [PeriphId; 4] = [PeriphId::Spim0, PeriphId::Spim1, PeriphId::Spim2, PeriphId::Spim3];
+/// SPIM0 and SPIM1 drive the display and the swap RAM. The graphics server and the swapper map them
+/// directly, without claiming them, so they are never handed out.
+const SPIM_RESERVED: [bool; 4] = [true, true, false, false];
+
 fn try_alloc(ifram_allocs: &mut Vec<Option<Sender>>, size: usize, sender: Sender) -> Option<usize> {
     let mut size_pages = size / 4096;
     if size % 4096 != 0 {
@@ -133,6 +145,32 @@ fn main() {
     )
     .expect("couldn't map UDMA global control");
     let mut udma_global = GlobalConfig::new(udma_global_csr.as_mut_ptr() as *mut u32);
+    let mut clocks = hw::clocks::ClockState::new(xous::connect(sid).unwrap(), &udma_global);
+
+    let pwm_page = xous::syscall::map_memory(
+        xous::MemoryAddress::new(utralib::generated::HW_PWM_BASE),
+        None,
+        4096,
+        xous::MemoryFlags::R | xous::MemoryFlags::W,
+    )
+    .expect("couldn't claim the PWM hardware page");
+    let pwm = Arc::new(Mutex::new(hw::pwm::PwmState::new(Pwm::new(pwm_page.as_mut_ptr() as *mut u32))));
+    let ramp_wake = hw::pwm::start_ramp_thread(pwm.clone());
+    // owner and chip select of each SPI master controller. A controller's registers and buffers can
+    // only be mapped by one process, so all of its chip selects go to the same process.
+    let mut spim_owners: [Option<(Holder, usize)>; 4] = [None; 4];
+    // the DMA engine's control structure lives in a page of IFRAM, which is kept for it
+    let dma_control_phys = utralib::generated::HW_IFRAM1_MEM
+        + try_alloc(&mut ifram_allocs[1], 4096, Sender::from_usize(usize::MAX))
+            .expect("couldn't reserve IFRAM for DMA control");
+    let dma_control = xous::syscall::map_memory(
+        xous::MemoryAddress::new(dma_control_phys),
+        None,
+        4096,
+        xous::MemoryFlags::R | xous::MemoryFlags::W,
+    )
+    .expect("couldn't map DMA control page");
+    let dma_jobs = hw::dma::start_dma_worker(dma_control.as_ptr() as usize, dma_control_phys);
 
     let mut pio_ss = xous_pio::PioSharedState::new();
     // map and enable the interrupt for the PIO system timer
@@ -311,6 +349,7 @@ fn main() {
                 if let Some(scalar) = msg.body.scalar_message() {
                     let periph: PeriphId = num_traits::FromPrimitive::from_usize(scalar.arg1).unwrap();
                     let enable = if scalar.arg2 != 0 { true } else { false };
+                    clocks.set_pinned(periph, enable);
                     if enable {
                         udma_global.clock_on(periph);
                     } else {
@@ -330,6 +369,192 @@ fn main() {
                     udma_global.map_event_with_offset(periph, event_offset, to_channel);
                 }
             }
+            Opcode::ClaimPwm => {
+                let holder = holder_of(msg);
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let result = match holder {
+                        Some(holder) => pwm.lock().unwrap().claim(scalar.arg1, scalar.arg2, holder),
+                        None => PwmResult::InUse,
+                    };
+                    scalar.arg1 = result as usize;
+                }
+            }
+            Opcode::ReleasePwm => {
+                let holder = holder_of(msg);
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let (timer, channel) = (scalar.arg1, scalar.arg2);
+                    let mut pwm = pwm.lock().unwrap();
+                    let result = pwm.check(timer, channel, holder);
+                    if result == PwmResult::Ok {
+                        pwm.release(timer, channel);
+                    }
+                    scalar.arg1 = result as usize;
+                }
+            }
+            Opcode::SetPwmFrequency => {
+                let holder = holder_of(msg);
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let (timer, channel, freq_hz) = (scalar.arg1, scalar.arg2, scalar.arg3 as u32);
+                    let mut pwm = pwm.lock().unwrap();
+                    let mut result = pwm.check(timer, channel, holder);
+                    if result == PwmResult::Ok && !pwm.owns_timer(timer, holder) {
+                        result = PwmResult::TimerShared;
+                    }
+                    // responds with the frequency actually set in arg2
+                    scalar.arg2 = 0;
+                    if result == PwmResult::Ok {
+                        match pwm.pwm.set_frequency(timer, freq_hz) {
+                            Some(actual) => {
+                                pwm.pwm.enable(timer, true);
+                                scalar.arg2 = actual as usize;
+                            }
+                            None => result = PwmResult::OutOfRange,
+                        }
+                    }
+                    scalar.arg1 = result as usize;
+                }
+            }
+            Opcode::SetPwmDuty => {
+                let holder = holder_of(msg);
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let (timer, channel, duty) = (scalar.arg1, scalar.arg2, scalar.arg3 as u32);
+                    let mut pwm = pwm.lock().unwrap();
+                    let result = pwm.check(timer, channel, holder);
+                    if result == PwmResult::Ok {
+                        pwm.cancel_ramp(timer, channel);
+                        pwm.pwm.set_duty(timer, channel, duty.min(PWM_DUTY_MAX));
+                    }
+                    scalar.arg1 = result as usize;
+                }
+            }
+            Opcode::RampPwmDuty => {
+                let holder = holder_of(msg);
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let (timer, channel, duty) = (scalar.arg1, scalar.arg2, scalar.arg3 as u32);
+                    let duration = Duration::from_millis(scalar.arg4 as u64);
+                    let mut pwm = pwm.lock().unwrap();
+                    let result = pwm.check(timer, channel, holder);
+                    if result == PwmResult::Ok {
+                        pwm.ramp(timer, channel, duty.min(PWM_DUTY_MAX), duration);
+                        ramp_wake.send(()).ok();
+                    }
+                    scalar.arg1 = result as usize;
+                }
+            }
+            Opcode::ClaimSpim => {
+                let holder = holder_of(msg);
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let (channel, cs) = (scalar.arg1, scalar.arg2);
+                    // responds with 1 in arg1 if the claim succeeded, 0 if the controller is taken
+                    // or does not exist
+                    scalar.arg1 = 0;
+                    if let Some(holder) = holder.filter(|_| channel < spim_owners.len() && cs < 4) {
+                        // a claim outlives its owner only until someone else wants the controller; the
+                        // owner's clock reference is dropped along with its other references
+                        if spim_owners[channel].map_or(false, |(owner, _)| has_exited(owner)) {
+                            log::info!("SPIM{} owner exited, releasing its claim", channel);
+                            spim_owners[channel] = None;
+                            clocks.release_exited(&mut udma_global);
+                        }
+                        match spim_owners[channel] {
+                            _ if SPIM_RESERVED[channel] => {
+                                log::warn!("SPIM{} is reserved, claim by PID {} denied", channel, holder.0);
+                            }
+                            Some((owner, owner_cs)) => {
+                                log::info!(
+                                    "SPIM{} claim by PID {} denied, held by PID {} on CS{}",
+                                    channel,
+                                    holder.0,
+                                    owner.0,
+                                    owner_cs
+                                );
+                            }
+                            None => {
+                                spim_owners[channel] = Some((holder, cs));
+                                clocks.acquire(&mut udma_global, SPIM_PERIPHS[channel], holder);
+                                scalar.arg1 = 1;
+                            }
+                        }
+                    }
+                }
+            }
+            Opcode::ReleaseSpim => {
+                let holder = holder_of(msg);
+                if let Some(scalar) = msg.body.scalar_message() {
+                    let channel = scalar.arg1;
+                    if let Some(holder) = holder.filter(|&holder| {
+                        channel < spim_owners.len()
+                            && spim_owners[channel].map(|(owner, _)| owner) == Some(holder)
+                    }) {
+                        spim_owners[channel] = None;
+                        clocks.release(&mut udma_global, SPIM_PERIPHS[channel], holder);
+                    }
+                }
+            }
+            Opcode::ClockAcquire => {
+                let holder = holder_of(msg);
+                clocks.release_exited(&mut udma_global);
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    // responds with 1 in arg1 if the reference was taken, 0 if there is no such clock
+                    // or the caller can't be told apart from other processes
+                    match (num_traits::FromPrimitive::from_usize(scalar.arg1), holder) {
+                        (Some(periph), Some(holder)) => {
+                            clocks.acquire(&mut udma_global, periph, holder);
+                            scalar.arg1 = 1;
+                        }
+                        _ => scalar.arg1 = 0,
+                    }
+                }
+            }
+            Opcode::ClockRelease => {
+                let holder = holder_of(msg);
+                clocks.release_exited(&mut udma_global);
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    // responds with 1 in arg1 if a reference was released, 0 if the caller held none
+                    scalar.arg1 = match (num_traits::FromPrimitive::from_usize(scalar.arg1), holder) {
+                        (Some(periph), Some(holder)) => {
+                            clocks.release(&mut udma_global, periph, holder) as usize
+                        }
+                        _ => 0,
+                    };
+                }
+            }
+            Opcode::ClockStatus => {
+                clocks.release_exited(&mut udma_global);
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    scalar.arg1 = udma_global.raw_clock_map() as usize;
+                    scalar.arg2 = clocks.held() as usize;
+                    scalar.arg3 = clocks.idle() as usize;
+                }
+            }
+            Opcode::SetClockIdlePolicy => {
+                let holder = holder_of(msg);
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    // responds with 1 in arg1 if the policy was set, 0 if it is malformed or the caller
+                    // isn't the power manager
+                    scalar.arg1 = match (ClockIdlePolicy::from_scalar(scalar.arg1, scalar.arg2), holder) {
+                        (Some(policy), Some(holder)) => {
+                            clocks.set_policy(&mut udma_global, policy, holder) as usize
+                        }
+                        _ => 0,
+                    };
+                }
+            }
+            Opcode::ClockIdleSweep => {
+                if msg.sender.pid().map(|pid| pid.get() as u32) != Some(xous::process::id()) {
+                    log::warn!("ClockIdleSweep is only sent by the HAL itself");
+                    continue;
+                }
+                clocks.release_exited(&mut udma_global);
+                clocks.sweep(&mut udma_global, std::time::Instant::now());
+            }
+            Opcode::DmaCopy | Opcode::DmaFill => {
+                if msg.body.memory_message().is_some() {
+                    // the caller stays blocked on the lend until the worker is done with it
+                    let fill = matches!(opcode, Opcode::DmaFill);
+                    dma_jobs.send((msg_opt.take().unwrap(), fill)).unwrap();
+                }
+            }
             Opcode::InvalidCall => {
                 log::error!("Invalid opcode received: {:?}", msg);
             }
@@ -0,0 +1,88 @@
+use core::sync::atomic::Ordering;
+
+pub use cramium_hal::pwm::{PWM_CHANNELS, PWM_DUTY_MAX, PWM_TIMERS};
+use num_traits::*;
+
+use crate::{api::PwmResult, Opcode, SERVER_NAME_CRAM_HAL};
+
+/// A PWM output channel, held by this process until it is dropped.
+///
+/// The four channels of a timer share its frequency. Only a process that holds every claimed channel
+/// of a timer may change the frequency; otherwise `set_frequency()` fails with `AccessDenied`, and the
+/// channel runs at whatever frequency the timer was set to.
+///
+/// Routing the channel to a pin is done separately, with `IoxHal::setup_io_pin()`.
+pub struct PwmChannel {
+    conn: xous::CID,
+    timer: usize,
+    channel: usize,
+}
+
+impl PwmChannel {
+    /// Claims `channel` of `timer` for this process. Fails with `AccessDenied` if another process holds
+    /// it, or `InvalidLimit` if it does not exist.
+    pub fn claim(timer: usize, channel: usize) -> Result<Self, xous::Error> {
+        let xns = xous_names::XousNames::new().unwrap();
+        let conn =
+            xns.request_connection(SERVER_NAME_CRAM_HAL).expect("Couldn't connect to Cramium HAL server");
+        crate::REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        let pwm = PwmChannel { conn, timer, channel };
+        // on failure, dropping `pwm` releases nothing, as the server only releases channels the caller holds
+        pwm.call(Opcode::ClaimPwm, 0, 0)?;
+        Ok(pwm)
+    }
+
+    fn call(&self, op: Opcode, arg3: usize, arg4: usize) -> Result<usize, xous::Error> {
+        match xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(op.to_usize().unwrap(), self.timer, self.channel, arg3, arg4),
+        ) {
+            Ok(xous::Result::Scalar5(_, result, value, _, _)) => {
+                match FromPrimitive::from_usize(result).unwrap_or(PwmResult::OutOfRange) {
+                    PwmResult::Ok => Ok(value),
+                    PwmResult::OutOfRange => Err(xous::Error::InvalidLimit),
+                    PwmResult::InUse | PwmResult::NotClaimed | PwmResult::TimerShared => {
+                        Err(xous::Error::AccessDenied)
+                    }
+                }
+            }
+            Ok(_) => Err(xous::Error::InternalError),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sets the frequency of the channel's timer, and starts it. Duty cycles are kept.
+    ///
+    /// Returns the frequency actually set, which is the nearest the timer can reach.
+    pub fn set_frequency(&self, freq_hz: u32) -> Result<u32, xous::Error> {
+        self.call(Opcode::SetPwmFrequency, freq_hz as usize, 0).map(|actual| actual as u32)
+    }
+
+    /// Sets the duty cycle, in parts per `PWM_DUTY_MAX`, stopping any ramp in progress.
+    pub fn set_duty(&self, duty: u32) -> Result<(), xous::Error> {
+        self.call(Opcode::SetPwmDuty, duty as usize, 0).map(|_| ())
+    }
+
+    /// Moves the duty cycle from where it is to `duty` over `duration_ms`, in steps of about 10 ms. The
+    /// ramp runs in the HAL server: this returns as soon as it has started.
+    pub fn ramp(&self, duty: u32, duration_ms: u32) -> Result<(), xous::Error> {
+        self.call(Opcode::RampPwmDuty, duty as usize, duration_ms as usize).map(|_| ())
+    }
+
+    pub fn timer(&self) -> usize { self.timer }
+
+    pub fn channel(&self) -> usize { self.channel }
+}
+
+impl Drop for PwmChannel {
+    fn drop(&mut self) {
+        self.call(Opcode::ReleasePwm, 0, 0).ok();
+        // de-allocate myself. It's unsafe because we are responsible to make sure nobody else is using the
+        // connection.
+        if crate::REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
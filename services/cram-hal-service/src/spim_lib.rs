@@ -0,0 +1,155 @@
+use core::sync::atomic::Ordering;
+
+use cramium_hal::udma::{Spim, SpimChannel, SpimClkPha, SpimClkPol, SpimCs};
+use num_traits::*;
+
+use crate::{Opcode, SERVER_NAME_CRAM_HAL};
+
+/// The clock that the SPI clock is divided down from
+const UDMA_CLOCK_HZ: u32 = 50_000_000;
+/// Cycles to wait between CS assert and data start, and between data stop and CS de-assert
+const CS_WAIT_CYCLES: u8 = 2;
+
+/// A SPI master controller, held by this process until it is dropped.
+///
+/// Transfers run by UDMA out of IFRAM buffers that are allocated when the controller is claimed, so
+/// a driver for an external display, flash or sensor can be a normal service. The controller's pins
+/// have to be routed to it separately, with `IoxHal::setup_io_pin()`.
+///
+/// Claims only arbitrate between users of this API: the controllers the display and the swapper are
+/// built to use are set up at boot without a claim, and must not be claimed.
+pub struct SpimMaster {
+    conn: xous::CID,
+    channel: SpimChannel,
+    spim: Spim,
+}
+
+impl SpimMaster {
+    /// Claims a SPI master controller for this process, and sets it up.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - the controller. Channels 0 and 1 are kept for the display and swap RAM.
+    /// * `cs` - the chip select line of the controller that is driven for each transfer
+    /// * `freq_hz` - the SPI clock, which is rounded down to the nearest the divider can reach
+    /// * `pol`, `pha` - the SPI mode
+    /// * `buf_len` - the size of each of the tx and rx DMA buffers. Longer transfers are split into chunks of
+    ///   this size, with CS held asserted across them.
+    ///
+    /// # Returns
+    ///
+    /// * AccessDenied if another process holds the controller, or it is reserved
+    /// * InvalidLimit if the clock can't be reached, or the buffer is empty or too large
+    /// * OutOfMemory if the IFRAM buffers can't be allocated
+    pub fn claim(
+        channel: SpimChannel,
+        cs: SpimCs,
+        freq_hz: u32,
+        pol: SpimClkPol,
+        pha: SpimClkPha,
+        buf_len: usize,
+    ) -> Result<Self, xous::Error> {
+        if freq_hz == 0 || freq_hz > UDMA_CLOCK_HZ / 2 || UDMA_CLOCK_HZ / (2 * freq_hz) >= 256 {
+            return Err(xous::Error::InvalidLimit);
+        }
+        if buf_len == 0 || buf_len >= 65536 {
+            return Err(xous::Error::InvalidLimit);
+        }
+        let xns = xous_names::XousNames::new().unwrap();
+        let conn =
+            xns.request_connection(SERVER_NAME_CRAM_HAL).expect("Couldn't connect to Cramium HAL server");
+        match xous::send_message(
+            conn,
+            xous::Message::new_blocking_scalar(
+                Opcode::ClaimSpim.to_usize().unwrap(),
+                channel as usize,
+                cs as usize,
+                0,
+                0,
+            ),
+        ) {
+            Ok(xous::Result::Scalar5(_, 1, _, _, _)) => {}
+            Ok(_) => return Err(xous::Error::AccessDenied),
+            Err(e) => return Err(e),
+        }
+        crate::REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        // safety: the UDMA clock for the controller was turned on by the claim, and the claim keeps
+        // other users of this API off of it
+        let spim = unsafe {
+            Spim::new(
+                channel,
+                freq_hz,
+                UDMA_CLOCK_HZ,
+                pol,
+                pha,
+                cs,
+                CS_WAIT_CYCLES,
+                CS_WAIT_CYCLES,
+                None,
+                buf_len,
+                buf_len,
+                None,
+            )
+        };
+        match spim {
+            Some(spim) => Ok(SpimMaster { conn, channel, spim }),
+            None => {
+                Self::release(conn, channel);
+                Err(xous::Error::OutOfMemory)
+            }
+        }
+    }
+
+    fn release(conn: xous::CID, channel: SpimChannel) {
+        xous::send_message(
+            conn,
+            xous::Message::new_blocking_scalar(
+                Opcode::ReleaseSpim.to_usize().unwrap(),
+                channel as usize,
+                0,
+                0,
+                0,
+            ),
+        )
+        .expect("Couldn't release SPI controller");
+        // de-allocate myself. It's unsafe because we are responsible to make sure nobody else is using the
+        // connection.
+        if crate::REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(conn).unwrap();
+            }
+        }
+    }
+
+    /// Full-duplex transfer: sends `tx` while receiving the same number of bytes into `rx`, with CS
+    /// asserted for the whole transfer. Blocks until the transfer is done.
+    ///
+    /// Panics: if `tx` and `rx` are not the same length.
+    pub fn transfer(&mut self, tx: &[u8], rx: &mut [u8]) { self.spim.transfer(tx, rx) }
+
+    /// Sends `tx`, discarding whatever is received. Blocks until the transfer is done.
+    pub fn write(&mut self, tx: &[u8]) {
+        self.spim.tx_data_async(tx, true, false);
+        while self.spim.is_tx_busy() {
+            xous::yield_slice();
+        }
+    }
+
+    /// Receives into `rx`, sending all ones. Blocks until the transfer is done.
+    pub fn read(&mut self, rx: &mut [u8]) {
+        let tx = vec![0xFFu8; rx.len()];
+        self.spim.transfer(&tx, rx)
+    }
+
+    pub fn channel(&self) -> SpimChannel { self.channel }
+}
+
+impl Drop for SpimMaster {
+    fn drop(&mut self) {
+        // safety: the `Spim` is dropped right after this, and the controller is released
+        unsafe {
+            self.spim.unmap_csr();
+        }
+        Self::release(self.conn, self.channel);
+    }
+}
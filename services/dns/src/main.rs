@@ -3,6 +3,7 @@
 
 mod api;
 mod time; // why is this here? because it's the only place it'll fit. :-/
+mod tz;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::io::ErrorKind;
@@ -286,9 +287,14 @@ impl Message {
     }
 }
 
+/// How long a name looked up through the proxy is kept, as the proxy gives no TTL
+const PROXY_LOOKUP_TTL_SECS: u32 = 300;
+
 pub struct Resolver {
     /// DnsServerManager is a service of the Net crate that automatically updates the DNS server list
     mgr: net::protocols::DnsServerManager,
+    /// tells us when names are to be looked up by the proxy rather than a DNS server
+    netmgr: net::NetManager,
     socket: UdpSocket,
     buf: [u8; DNS_PKT_MAX_LEN],
     trng: trng::Trng,
@@ -309,6 +315,7 @@ impl Resolver {
         Resolver {
             mgr: net::protocols::DnsServerManager::register(&xns)
                 .expect("Couldn't register the DNS server list auto-manager"),
+            netmgr: net::NetManager::new(),
             socket,
             buf: [0; DNS_PKT_MAX_LEN],
             trng,
@@ -333,6 +340,12 @@ impl Resolver {
     pub fn trng_u32(&self) -> u32 { self.trng.get_u32().unwrap() }
 
     pub fn resolve(&mut self, name: &str) -> Result<HashMap<IpAddr, u32>, DnsResponseCode> {
+        // while everything goes through Tor, so must the lookups; the net server drops DNS queries then
+        if let Ok(Some(proxy)) = self.netmgr.socks_proxy() {
+            if proxy.resolves_names {
+                return Self::resolve_through(proxy.addr, name);
+            }
+        }
         if let Some(dns_address) = self.mgr.get_random() {
             let dns_port = 53;
             let server = SocketAddr::new(dns_address, dns_port);
@@ -365,6 +378,19 @@ impl Resolver {
             Err(DnsResponseCode::NoServerSpecified)
         }
     }
+
+    /// Looks `name` up with the SOCKS5 RESOLVE extension of the proxy at `proxy`
+    fn resolve_through(proxy: SocketAddr, name: &str) -> Result<HashMap<IpAddr, u32>, DnsResponseCode> {
+        let proxy = net::protocols::Socks5::new(net::SocksProxy { addr: proxy, credentials: None });
+        match proxy.resolve(name) {
+            Ok(addr) => Ok(HashMap::from([(addr, PROXY_LOOKUP_TTL_SECS)])),
+            Err(e) if e.kind() == ErrorKind::Other => Err(DnsResponseCode::NameError),
+            Err(e) => {
+                log::debug!("lookup through the proxy failed: {:?}", e);
+                Err(DnsResponseCode::NetworkError)
+            }
+        }
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -452,6 +478,7 @@ fn fill_error(mut env: xous::MessageEnvelope, code: DnsResponseCode) -> Option<(
 
 fn main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
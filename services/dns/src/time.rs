@@ -81,6 +81,8 @@ pub(crate) enum TimeOp {
     WallClockTimeInit = 6,
     /// Self-poll for PDDB mount
     PddbMountPoll = 7,
+    /// Re-reads the display time zone from the user preferences
+    ReloadTimezone = 8,
 }
 
 /// Do not modify the discriminants in this structure. They are used in `libstd` directly.
@@ -358,10 +360,19 @@ pub fn start_time_server() {
                     None
                 })
                 .unwrap_or_default();
+            // when a time zone is selected, its rules take precedence over the fixed `tz_offset_ms`
+            #[cfg(not(feature = "minimal-testing"))]
+            let pddb = pddb::Pddb::new();
+            #[cfg(not(feature = "minimal-testing"))]
+            crate::tz::seed_tzdata(&pddb);
+            #[cfg(not(feature = "minimal-testing"))]
+            let mut tz_rule = load_tz_rule(&prefs, &pddb);
             #[cfg(feature = "minimal-testing")]
             let mut utc_offset_ms = 0;
             #[cfg(feature = "minimal-testing")]
             let mut tz_offset_ms = 0;
+            #[cfg(feature = "minimal-testing")]
+            let mut tz_rule: Option<crate::tz::TzRule> = None;
 
             log::debug!("offset_key: {}", utc_offset_ms / 1000);
             log::debug!("tz_key: {}", tz_offset_ms / 1000);
@@ -417,18 +428,20 @@ pub fn start_time_server() {
                             "current offset {}",
                             (start_rtc_secs as i64 * 1000i64 + (tt.elapsed_ms() - start_tt_ms) as i64) / 1000
                         );
-                        let t = start_rtc_secs as i64 * 1000i64
+                        let utc = start_rtc_secs as i64 * 1000i64
                             + (tt.elapsed_ms() - start_tt_ms) as i64
-                            + utc_offset_ms
-                            + tz_offset_ms;
+                            + utc_offset_ms;
+                        let t = utc + tz_rule.map_or(tz_offset_ms, |rule| rule.offset_ms_at(utc));
                         if t < 0 {
                             log::warn!(
                                 "Time was negative, recovering from time setting error by clearing utc and timezone offsets to 0."
                             );
                             prefs.set_utc_offset(0).ok();
                             prefs.set_timezone_offset(0).ok();
+                            prefs.set_timezone_name(String::new()).ok();
                             utc_offset_ms = 0;
                             tz_offset_ms = 0;
+                            tz_rule = None;
                         }
                         log::trace!("local since epoch {}", t / 1000);
                         xous::return_scalar2(
@@ -463,13 +476,35 @@ pub fn start_time_server() {
                             log::warn!("Requested timezone offset {} is out of bounds, ignoring!", tz_ms);
                             continue;
                         } else {
+                            // a fixed offset replaces any selected time zone
                             tz_offset_ms = tz_ms;
+                            tz_rule = None;
                             #[cfg(not(feature = "minimal-testing"))]
                             prefs.set_timezone_offset(tz_ms).unwrap_or_else(|err| {
                                 log::error!("cannot set timezone offset: {:?}", err);
                             });
+                            #[cfg(not(feature = "minimal-testing"))]
+                            prefs.set_timezone_name(String::new()).unwrap_or_else(|err| {
+                                log::error!("cannot clear timezone name: {:?}", err);
+                            });
                         }
                     }),
+                    Some(TimeOp::ReloadTimezone) => {
+                        #[cfg(not(feature = "minimal-testing"))]
+                        {
+                            tz_rule = load_tz_rule(&prefs, &pddb);
+                            if let Some(rule) = tz_rule {
+                                // keep the fixed offset current, as a fallback for an unusable zone
+                                let utc = start_rtc_secs as i64 * 1000i64
+                                    + (tt.elapsed_ms() - start_tt_ms) as i64
+                                    + utc_offset_ms;
+                                tz_offset_ms = rule.offset_ms_at(utc);
+                                prefs.set_timezone_offset(tz_offset_ms).unwrap_or_else(|err| {
+                                    log::error!("cannot set timezone offset: {:?}", err);
+                                });
+                            }
+                        }
+                    }
                     Some(TimeOp::WallClockTimeInit) => xous::msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                         if utc_offset_ms == 0 {
                             xous::return_scalar(msg.sender, 0).unwrap();
@@ -505,6 +540,20 @@ pub fn start_time_server() {
     });
 }
 
+/// The rules of the time zone selected in the user preferences, if any.
+#[cfg(not(feature = "minimal-testing"))]
+fn load_tz_rule(prefs: &userprefs::Manager, pddb: &pddb::Pddb) -> Option<crate::tz::TzRule> {
+    let zone = prefs.timezone_name_or_default().unwrap_or_default();
+    if zone.is_empty() {
+        return None;
+    }
+    let rule = crate::tz::lookup(pddb, &zone);
+    if rule.is_none() {
+        log::warn!("no rules for time zone {}, using the fixed offset instead", zone);
+    }
+    rule
+}
+
 #[allow(dead_code)]
 fn is_rtc_invalid(settings: &[u8]) -> bool {
     ((settings[CTL3] & 0xE0) != RTC_PWR_MODE) // power switchover setting should be initialized
@@ -576,6 +625,7 @@ pub(crate) fn start_time_ux() {
             let trng = trng::Trng::new(&xns).unwrap();
 
             let prefs = userprefs::Manager::new();
+            let pddb = pddb::Pddb::new();
 
             loop {
                 let msg = xous::receive_message(sid).unwrap();
@@ -588,7 +638,7 @@ pub(crate) fn start_time_ux() {
                             continue;
                         }
                         let mut tz_set = false;
-                        let mut tz_offset_ms = match prefs.timezone_offset() {
+                        let tz_offset_ms = match prefs.timezone_offset() {
                             Ok(offset) => match offset {
                                 Some(data) => {
                                     tz_set = true;
@@ -607,30 +657,15 @@ pub(crate) fn start_time_ux() {
 
                         // note that we don't do an "else" here because we also want to catch the case of
                         // a key exists, but nothing was written to it (length of key was 0 or inappropriate)
-                        if !tz_set {
-                            log::info!("{}RTC.TZ,{}", xous::BOOKEND_START, xous::BOOKEND_END);
-                            let tz_str = modals
-                                .alert_builder(t!("rtc.timezone", locales::LANG))
-                                .field(None, Some(tz_ux_validator))
-                                .build()
-                                .expect("couldn't get timezone")
-                                .first();
-                            let tz = simple_kilofloat_parse(tz_str.as_str())
-                                .expect("pre-validated input failed to re-parse!");
-                            log::info!("got tz offset {}", tz);
-                            tz_offset_ms = (tz * 3600) as i64;
-                            xous::send_message(
-                                timeserver_cid,
-                                Message::new_scalar(
-                                    crate::time::TimeOp::SetTzOffsetMs.to_usize().unwrap(),
-                                    (tz_offset_ms >> 32) as usize,
-                                    (tz_offset_ms & 0xFFFF_FFFF) as usize,
-                                    0,
-                                    0,
-                                ),
-                            )
-                            .expect("couldn't set timezone");
-                        }
+                        let tz_setting = if !tz_set {
+                            timezone_ux(&modals, &prefs, &pddb, timeserver_cid)
+                        } else {
+                            let zone = prefs.timezone_name_or_default().unwrap_or_default();
+                            match crate::tz::lookup(&pddb, &zone) {
+                                Some(rule) if !zone.is_empty() => TzSetting::Zone(rule),
+                                _ => TzSetting::FixedMs(tz_offset_ms),
+                            }
+                        };
 
                         // see if we want to try to use NTP or not
                         log::info!("{}RTC.NTP,{}", xous::BOOKEND_START, xous::BOOKEND_END);
@@ -744,23 +779,20 @@ pub(crate) fn start_time_ux() {
                         }
 
                         log::info!("Setting time: {}/{}/{} {}:{}:{}", months, days, years, hours, mins, secs);
-                        let new_dt = chrono::FixedOffset::east_opt((tz_offset_ms / 1000) as i32)
-                            .unwrap()
-                            .with_ymd_and_hms(
-                                years as i32 + 2000,
-                                months as u32,
-                                days as u32,
-                                hours as u32,
-                                mins as u32,
-                                secs as u32,
-                            )
-                            .unwrap();
+                        let local_ms =
+                            NaiveDate::from_ymd_opt(years as i32 + 2000, months as u32, days as u32)
+                                .and_then(|date| date.and_hms_opt(hours as u32, mins as u32, secs as u32))
+                                .unwrap()
+                                .and_utc()
+                                .timestamp_millis();
+                        // the offset depends on the date when the time zone observes daylight saving time
+                        let utc_ms = local_ms - tz_setting.offset_ms_for_local(local_ms);
                         xous::send_message(
                             timeserver_cid,
                             Message::new_scalar(
                                 crate::time::TimeOp::SetUtcTimeMs.to_usize().unwrap(),
-                                ((new_dt.timestamp_millis() as u64) >> 32) as usize,
-                                (new_dt.timestamp_millis() as u64 & 0xFFFF_FFFF) as usize,
+                                ((utc_ms as u64) >> 32) as usize,
+                                (utc_ms as u64 & 0xFFFF_FFFF) as usize,
                                 0,
                                 0,
                             ),
@@ -774,28 +806,7 @@ pub(crate) fn start_time_ux() {
                                 .expect("couldn't show notification");
                             continue;
                         }
-
-                        let tz_str = modals
-                            .alert_builder(t!("rtc.timezone", locales::LANG))
-                            .field(None, Some(tz_ux_validator))
-                            .build()
-                            .expect("couldn't get timezone")
-                            .first();
-                        let tz = simple_kilofloat_parse(tz_str.as_str())
-                            .expect("pre-validated input failed to re-parse!");
-                        log::info!("got tz offset {}", tz);
-                        let tzoff_ms = (tz * 3600) as i64;
-                        xous::send_message(
-                            timeserver_cid,
-                            Message::new_scalar(
-                                crate::time::TimeOp::SetTzOffsetMs.to_usize().unwrap(),
-                                (tzoff_ms >> 32) as usize,
-                                (tzoff_ms & 0xFFFF_FFFF) as usize,
-                                0,
-                                0,
-                            ),
-                        )
-                        .expect("couldn't set timezone");
+                        timezone_ux(&modals, &prefs, &pddb, timeserver_cid);
                     }),
                     Some(crate::TimeUxOp::Quit) => {
                         xous::return_scalar(msg.sender, 0).unwrap();
@@ -811,6 +822,110 @@ pub(crate) fn start_time_ux() {
     });
 }
 
+/// The display time zone, as chosen by the user
+enum TzSetting {
+    Zone(crate::tz::TzRule),
+    FixedMs(i64),
+}
+impl TzSetting {
+    fn offset_ms_for_local(&self, local_ms: i64) -> i64 {
+        match self {
+            TzSetting::Zone(rule) => rule.offset_ms_for_local(local_ms),
+            TzSetting::FixedMs(offset) => *offset,
+        }
+    }
+}
+
+/// Asks the user for their time zone: either a zone from tzdata, picked by region and then city, or a
+/// fixed offset from UTC. The choice is forwarded to the time server.
+fn timezone_ux(
+    modals: &modals::Modals,
+    prefs: &userprefs::Manager,
+    pddb: &pddb::Pddb,
+    timeserver_cid: xous::CID,
+) -> TzSetting {
+    let zones = crate::tz::zone_names(pddb);
+    let mut regions: Vec<String> =
+        zones.iter().map(|z| z.split('/').next().unwrap_or(z).to_string()).collect();
+    regions.dedup();
+    regions.push(t!("rtc.tz_fixed", locales::LANG).to_string());
+    log::info!("{}RTC.TZREGION,{}", xous::BOOKEND_START, xous::BOOKEND_END);
+    let region = pick_paged(modals, t!("rtc.tz_region", locales::LANG), &regions).unwrap_or_default();
+
+    let in_region: Vec<&String> =
+        zones.iter().filter(|z| z.as_str() == region || z.starts_with(&format!("{}/", region))).collect();
+    let zone = match in_region.len() {
+        0 => None,
+        1 => Some(in_region[0].clone()),
+        _ => {
+            let cities: Vec<String> = in_region.iter().map(|z| zone_city(z)).collect();
+            let city = pick_paged(modals, t!("rtc.tz_city", locales::LANG), &cities).unwrap_or_default();
+            in_region.iter().find(|z| zone_city(z) == city).map(|z| z.to_string())
+        }
+    };
+    if let Some(zone) = zone {
+        if let Some(rule) = crate::tz::lookup(pddb, &zone) {
+            log::info!("selected time zone {}", zone);
+            prefs.set_timezone_name(zone).unwrap_or_else(|err| {
+                log::error!("cannot set timezone name: {:?}", err);
+            });
+            xous::send_message(
+                timeserver_cid,
+                Message::new_scalar(crate::time::TimeOp::ReloadTimezone.to_usize().unwrap(), 0, 0, 0, 0),
+            )
+            .expect("couldn't set timezone");
+            return TzSetting::Zone(rule);
+        }
+    }
+
+    log::info!("{}RTC.TZ,{}", xous::BOOKEND_START, xous::BOOKEND_END);
+    let tz_str = modals
+        .alert_builder(t!("rtc.timezone", locales::LANG))
+        .field(None, Some(tz_ux_validator))
+        .build()
+        .expect("couldn't get timezone")
+        .first();
+    let tz = simple_kilofloat_parse(tz_str.as_str()).expect("pre-validated input failed to re-parse!");
+    log::info!("got tz offset {}", tz);
+    let tz_offset_ms = (tz * 3600) as i64;
+    xous::send_message(
+        timeserver_cid,
+        Message::new_scalar(
+            crate::time::TimeOp::SetTzOffsetMs.to_usize().unwrap(),
+            (tz_offset_ms >> 32) as usize,
+            (tz_offset_ms & 0xFFFF_FFFF) as usize,
+            0,
+            0,
+        ),
+    )
+    .expect("couldn't set timezone");
+    TzSetting::FixedMs(tz_offset_ms)
+}
+
+/// Radio button lists have to fit on the screen, so longer lists are offered a page at a time.
+fn pick_paged(modals: &modals::Modals, prompt: &str, items: &[String]) -> Option<String> {
+    const ITEMS_PER_PAGE: usize = 8;
+    let more = t!("rtc.tz_more", locales::LANG);
+    let pages = (items.len() + ITEMS_PER_PAGE - 1) / ITEMS_PER_PAGE;
+    let mut page = 0;
+    loop {
+        for item in items.iter().skip(page * ITEMS_PER_PAGE).take(ITEMS_PER_PAGE) {
+            modals.add_list_item(item).expect("couldn't build radio item list");
+        }
+        if pages > 1 {
+            modals.add_list_item(more).expect("couldn't build radio item list");
+        }
+        match modals.get_radiobutton(prompt) {
+            Ok(choice) if pages > 1 && choice == more => page = (page + 1) % pages,
+            Ok(choice) => return Some(choice),
+            Err(_) => return None,
+        }
+    }
+}
+
+/// The part of a zone name after its region, for display, e.g. "Argentina/Buenos Aires"
+fn zone_city(zone: &str) -> String { zone.split_once('/').map_or(zone, |(_, city)| city).replace('_', " ") }
+
 // RTC Ux helper functions
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub(crate) enum ValidatorOp {
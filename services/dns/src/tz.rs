@@ -0,0 +1,502 @@
+/// Timezone rules, so that local time follows daylight saving time transitions instead of a fixed
+/// offset from UTC.
+///
+/// Zones are stored in the PDDB as IANA zone names mapped to the POSIX TZ rule that tzdata gives for
+/// the zone's current rules, e.g. "America/New_York" -> "EST5EDT,M3.2.0,M11.1.0". The dictionary is
+/// seeded from `BUILTIN_ZONES` on mount, and entries in the PDDB take precedence over the built-in
+/// ones, so a zone whose rules have changed can be fixed up without an update.
+use std::io::{Read, Write};
+
+use chrono::prelude::*;
+
+/// Dictionary that holds the timezone rules, keyed by IANA zone name
+pub(crate) const TZDATA_DICT: &str = "sys.tzdata";
+
+/// A subset of tzdata, covering the most populous zones of each region.
+pub(crate) const BUILTIN_ZONES: &[(&str, &str)] = &[
+    ("UTC", "UTC0"),
+    ("Africa/Cairo", "EET-2EEST,M4.5.5/0,M10.5.4/24"),
+    ("Africa/Johannesburg", "SAST-2"),
+    ("Africa/Lagos", "WAT-1"),
+    ("Africa/Nairobi", "EAT-3"),
+    ("America/Anchorage", "AKST9AKDT,M3.2.0,M11.1.0"),
+    ("America/Argentina/Buenos_Aires", "<-03>3"),
+    ("America/Bogota", "<-05>5"),
+    ("America/Chicago", "CST6CDT,M3.2.0,M11.1.0"),
+    ("America/Denver", "MST7MDT,M3.2.0,M11.1.0"),
+    ("America/Halifax", "AST4ADT,M3.2.0,M11.1.0"),
+    ("America/Lima", "<-05>5"),
+    ("America/Los_Angeles", "PST8PDT,M3.2.0,M11.1.0"),
+    ("America/Mexico_City", "CST6"),
+    ("America/New_York", "EST5EDT,M3.2.0,M11.1.0"),
+    ("America/Phoenix", "MST7"),
+    ("America/Santiago", "<-04>4<-03>,M9.1.6/24,M4.1.6/24"),
+    ("America/Sao_Paulo", "<-03>3"),
+    ("America/St_Johns", "NST3:30NDT,M3.2.0,M11.1.0"),
+    ("America/Toronto", "EST5EDT,M3.2.0,M11.1.0"),
+    ("America/Vancouver", "PST8PDT,M3.2.0,M11.1.0"),
+    ("Asia/Bangkok", "<+07>-7"),
+    ("Asia/Dhaka", "<+06>-6"),
+    ("Asia/Dubai", "<+04>-4"),
+    ("Asia/Hong_Kong", "HKT-8"),
+    ("Asia/Jakarta", "WIB-7"),
+    ("Asia/Jerusalem", "IST-2IDT,M3.4.4/26,M10.5.0"),
+    ("Asia/Karachi", "PKT-5"),
+    ("Asia/Kathmandu", "<+0545>-5:45"),
+    ("Asia/Kolkata", "IST-5:30"),
+    ("Asia/Manila", "PST-8"),
+    ("Asia/Riyadh", "<+03>-3"),
+    ("Asia/Seoul", "KST-9"),
+    ("Asia/Shanghai", "CST-8"),
+    ("Asia/Singapore", "<+08>-8"),
+    ("Asia/Taipei", "CST-8"),
+    ("Asia/Tehran", "<+0330>-3:30"),
+    ("Asia/Tokyo", "JST-9"),
+    ("Australia/Adelaide", "ACST-9:30ACDT,M10.1.0,M4.1.0/3"),
+    ("Australia/Brisbane", "AEST-10"),
+    ("Australia/Melbourne", "AEST-10AEDT,M10.1.0,M4.1.0/3"),
+    ("Australia/Perth", "AWST-8"),
+    ("Australia/Sydney", "AEST-10AEDT,M10.1.0,M4.1.0/3"),
+    ("Europe/Amsterdam", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Athens", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Berlin", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Dublin", "IST-1GMT0,M10.5.0,M3.5.0/1"),
+    ("Europe/Helsinki", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Istanbul", "<+03>-3"),
+    ("Europe/Kyiv", "EET-2EEST,M3.5.0/3,M10.5.0/4"),
+    ("Europe/Lisbon", "WET0WEST,M3.5.0/1,M10.5.0"),
+    ("Europe/London", "GMT0BST,M3.5.0/1,M10.5.0"),
+    ("Europe/Madrid", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Moscow", "MSK-3"),
+    ("Europe/Paris", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Rome", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Stockholm", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Warsaw", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Europe/Zurich", "CET-1CEST,M3.5.0,M10.5.0/3"),
+    ("Pacific/Auckland", "NZST-12NZDT,M9.5.0,M4.1.0/3"),
+    ("Pacific/Honolulu", "HST10"),
+];
+
+/// The day of the year on which a daylight saving time transition happens
+#[derive(Debug, Copy, Clone)]
+enum TransitionDay {
+    /// `Mm.w.d`: day `d` (0 = Sunday) of week `w` (1-5, 5 being the last) of month `m`
+    MonthWeekDay { month: u32, week: u32, weekday: u32 },
+    /// `Jn`: day 1-365, where February 29 is never counted
+    Julian(u32),
+    /// `n`: day 0-365, where February 29 is counted in leap years
+    ZeroBased(u32),
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Transition {
+    day: TransitionDay,
+    /// local time of the transition, in seconds since midnight. May be negative or past 24 hours.
+    time: i64,
+}
+impl Transition {
+    /// The transition in `year`, in seconds since EPOCH, where the local time is `offset` seconds east of
+    /// UTC.
+    fn utc_secs(&self, year: i32, offset: i64) -> Option<i64> {
+        let date = match self.day {
+            TransitionDay::MonthWeekDay { month, week, weekday } => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let first_weekday = first.weekday().num_days_from_sunday();
+                let mut day = 1 + (7 + weekday - first_weekday) % 7 + (week - 1) * 7;
+                let days_in_month = first.checked_add_months(chrono::Months::new(1))?.pred_opt()?.day();
+                while day > days_in_month {
+                    day -= 7;
+                }
+                NaiveDate::from_ymd_opt(year, month, day)?
+            }
+            TransitionDay::Julian(n) => {
+                let date = NaiveDate::from_yo_opt(year, n)?;
+                let leap_year = NaiveDate::from_ymd_opt(year, 2, 29).is_some();
+                if leap_year && n >= 60 { date.succ_opt()? } else { date }
+            }
+            TransitionDay::ZeroBased(n) => NaiveDate::from_yo_opt(year, n + 1)?,
+        };
+        Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp() + self.time - offset)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Dst {
+    /// seconds east of UTC while daylight saving time is in effect
+    offset: i64,
+    start: Transition,
+    end: Transition,
+}
+
+/// A timezone, as described by a POSIX TZ rule
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct TzRule {
+    /// seconds east of UTC outside of daylight saving time
+    std_offset: i64,
+    dst: Option<Dst>,
+}
+impl TzRule {
+    /// Parses a POSIX TZ rule, e.g. "CET-1CEST,M3.5.0,M10.5.0/3". Returns `None` if the rule is malformed.
+    pub(crate) fn parse(rule: &str) -> Option<TzRule> {
+        let mut p = Parser { s: rule.as_bytes(), pos: 0 };
+        p.name()?;
+        // POSIX offsets count hours west of UTC
+        let std_offset = -p.time()?;
+        if p.done() {
+            return Some(TzRule { std_offset, dst: None });
+        }
+        p.name()?;
+        let offset = if p.done() || p.peek() == Some(b',') { std_offset + 3600 } else { -p.time()? };
+        // zones that omit the transition rules follow the US rules
+        let (start, end) = if p.done() {
+            (
+                Transition { day: TransitionDay::MonthWeekDay { month: 3, week: 2, weekday: 0 }, time: 7200 },
+                Transition {
+                    day: TransitionDay::MonthWeekDay { month: 11, week: 1, weekday: 0 },
+                    time: 7200,
+                },
+            )
+        } else {
+            p.expect(b',')?;
+            let start = p.transition()?;
+            p.expect(b',')?;
+            let end = p.transition()?;
+            (start, end)
+        };
+        if !p.done() {
+            return None;
+        }
+        Some(TzRule { std_offset, dst: Some(Dst { offset, start, end }) })
+    }
+
+    /// The offset from UTC, in milliseconds, at `utc_ms` since EPOCH.
+    pub(crate) fn offset_ms_at(&self, utc_ms: i64) -> i64 {
+        let dst = match self.dst {
+            Some(dst) => dst,
+            None => return self.std_offset * 1000,
+        };
+        let utc_secs = utc_ms.div_euclid(1000);
+        let year = match DateTime::from_timestamp(utc_secs + self.std_offset, 0) {
+            Some(dt) => dt.year(),
+            None => return self.std_offset * 1000,
+        };
+        // the start of daylight saving time is given in standard time, and the end in daylight time
+        let (start, end) =
+            match (dst.start.utc_secs(year, self.std_offset), dst.end.utc_secs(year, dst.offset)) {
+                (Some(start), Some(end)) => (start, end),
+                _ => return self.std_offset * 1000,
+            };
+        let in_dst = if start < end {
+            utc_secs >= start && utc_secs < end
+        } else {
+            // southern hemisphere: daylight saving time spans the new year
+            utc_secs < end || utc_secs >= start
+        };
+        if in_dst { dst.offset * 1000 } else { self.std_offset * 1000 }
+    }
+
+    /// The offset from UTC, in milliseconds, for a local wall clock time expressed as milliseconds since
+    /// EPOCH. Local times that are skipped or repeated by a transition take the offset in effect after it.
+    pub(crate) fn offset_ms_for_local(&self, local_ms: i64) -> i64 {
+        let (std, dst) = match self.dst {
+            Some(dst) => (self.std_offset * 1000, dst.offset * 1000),
+            None => return self.std_offset * 1000,
+        };
+        let valid = |offset: i64| self.offset_ms_at(local_ms - offset) == offset;
+        match (valid(std), valid(dst)) {
+            (true, false) => std,
+            (false, true) => dst,
+            // repeated when the clocks go back: the later instant has the smaller offset
+            (true, true) => std.min(dst),
+            // skipped when the clocks go forward, to the larger offset
+            (false, false) => std.max(dst),
+        }
+    }
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> { self.s.get(self.pos).copied() }
+
+    fn done(&self) -> bool { self.pos >= self.s.len() }
+
+    fn expect(&mut self, c: u8) -> Option<()> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// A zone abbreviation: either three or more letters, or anything enclosed in angle brackets
+    fn name(&mut self) -> Option<()> {
+        if self.peek() == Some(b'<') {
+            let close = self.s[self.pos..].iter().position(|&c| c == b'>')?;
+            self.pos += close + 1;
+            return Some(());
+        }
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+        if self.pos - start >= 3 { Some(()) } else { None }
+    }
+
+    fn number(&mut self) -> Option<i64> {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        core::str::from_utf8(&self.s[start..self.pos]).ok()?.parse().ok()
+    }
+
+    /// `[+|-]hh[:mm[:ss]]`, in seconds
+    fn time(&mut self) -> Option<i64> {
+        let sign = match self.peek() {
+            Some(b'-') => {
+                self.pos += 1;
+                -1
+            }
+            Some(b'+') => {
+                self.pos += 1;
+                1
+            }
+            _ => 1,
+        };
+        let mut secs = self.number()? * 3600;
+        if self.expect(b':').is_some() {
+            secs += self.number()? * 60;
+            if self.expect(b':').is_some() {
+                secs += self.number()?;
+            }
+        }
+        Some(sign * secs)
+    }
+
+    /// `date[/time]`, where the time defaults to 02:00
+    fn transition(&mut self) -> Option<Transition> {
+        let day = match self.peek()? {
+            b'M' => {
+                self.pos += 1;
+                let month = self.number()? as u32;
+                self.expect(b'.')?;
+                let week = self.number()? as u32;
+                self.expect(b'.')?;
+                let weekday = self.number()? as u32;
+                if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+                    return None;
+                }
+                TransitionDay::MonthWeekDay { month, week, weekday }
+            }
+            b'J' => {
+                self.pos += 1;
+                let n = self.number()? as u32;
+                if !(1..=365).contains(&n) {
+                    return None;
+                }
+                TransitionDay::Julian(n)
+            }
+            _ => {
+                let n = self.number()? as u32;
+                if n > 365 {
+                    return None;
+                }
+                TransitionDay::ZeroBased(n)
+            }
+        };
+        let time = if self.expect(b'/').is_some() { self.time()? } else { 7200 };
+        Some(Transition { day, time })
+    }
+}
+
+/// Adds any built-in zones that are missing from the PDDB.
+pub(crate) fn seed_tzdata(pddb: &pddb::Pddb) {
+    let existing = pddb.list_keys(TZDATA_DICT, Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS)).unwrap_or_default();
+    let mut added = false;
+    for (zone, rule) in BUILTIN_ZONES.iter() {
+        if existing.iter().any(|k| k == zone) {
+            continue;
+        }
+        match pddb.get(
+            TZDATA_DICT,
+            zone,
+            Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS),
+            true,
+            true,
+            Some(rule.len()),
+            None::<fn()>,
+        ) {
+            Ok(mut key) => {
+                key.write_all(rule.as_bytes()).ok();
+                added = true;
+            }
+            Err(e) => log::warn!("couldn't store tzdata for {}: {:?}", zone, e),
+        }
+    }
+    if added {
+        pddb.sync().ok();
+    }
+}
+
+/// The names of all the zones that are known, sorted.
+pub(crate) fn zone_names(pddb: &pddb::Pddb) -> Vec<String> {
+    let mut zones = pddb.list_keys(TZDATA_DICT, Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS)).unwrap_or_default();
+    for (zone, _) in BUILTIN_ZONES.iter() {
+        if !zones.iter().any(|k| k == zone) {
+            zones.push(zone.to_string());
+        }
+    }
+    zones.sort();
+    zones
+}
+
+/// Looks up the rule for `zone`, preferring the PDDB's copy of tzdata over the built-in one.
+pub(crate) fn lookup(pddb: &pddb::Pddb, zone: &str) -> Option<TzRule> {
+    let stored = pddb
+        .get(TZDATA_DICT, zone, Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS), false, false, None, None::<fn()>)
+        .ok()
+        .and_then(|mut key| {
+            let mut rule = String::new();
+            key.read_to_string(&mut rule).ok().map(|_| rule)
+        });
+    if let Some(rule) = stored {
+        match TzRule::parse(rule.trim_end_matches('\0')) {
+            Some(rule) => return Some(rule),
+            None => log::warn!("malformed tzdata for {}: {}", zone, rule),
+        }
+    }
+    BUILTIN_ZONES.iter().find(|(name, _)| *name == zone).and_then(|(_, rule)| TzRule::parse(rule))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_MS: i64 = 3_600_000;
+
+    /// milliseconds since EPOCH of the given UTC (or local, for `offset_ms_for_local`) time
+    fn ms(year: i32, month: u32, day: u32, hour: u32, min: u32) -> i64 {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, min, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis()
+    }
+
+    #[test]
+    fn parse_fixed_offsets() {
+        let offset = |rule: &str| TzRule::parse(rule).unwrap().offset_ms_at(0);
+        assert_eq!(offset("UTC0"), 0);
+        assert_eq!(offset("JST-9"), 9 * HOUR_MS);
+        assert_eq!(offset("HST10"), -10 * HOUR_MS);
+        assert_eq!(offset("IST-5:30"), 5 * HOUR_MS + HOUR_MS / 2);
+        assert_eq!(offset("<+0545>-5:45"), 5 * HOUR_MS + 45 * 60_000);
+        assert_eq!(offset("<-03>3"), -3 * HOUR_MS);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_rules() {
+        for rule in [
+            "",
+            "UT0",
+            "EST",
+            "<+03-3",
+            "EST5EDT,M3.2.0",
+            "EST5EDT,M13.2.0,M11.1.0",
+            "EST5EDT,M3.6.0,M11.1.0",
+            "EST5EDT,M3.2.7,M11.1.0",
+            "EST5EDT,J0,J365",
+            "EST5EDT,0,366",
+            "EST5EDT,M3.2.0,M11.1.0junk",
+        ] {
+            assert!(TzRule::parse(rule).is_none(), "accepted {:?}", rule);
+        }
+        assert!(BUILTIN_ZONES.iter().all(|(_, rule)| TzRule::parse(rule).is_some()));
+    }
+
+    #[test]
+    fn us_transitions() {
+        let ny = TzRule::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        // 2024-03-10 02:00 EST and 2024-11-03 02:00 EDT
+        assert_eq!(ny.offset_ms_at(ms(2024, 3, 10, 6, 59)), -5 * HOUR_MS);
+        assert_eq!(ny.offset_ms_at(ms(2024, 3, 10, 7, 0)), -4 * HOUR_MS);
+        assert_eq!(ny.offset_ms_at(ms(2024, 11, 3, 5, 59)), -4 * HOUR_MS);
+        assert_eq!(ny.offset_ms_at(ms(2024, 11, 3, 6, 0)), -5 * HOUR_MS);
+        // the DST offset defaults to an hour ahead, and the transitions to the US rules
+        let defaults = TzRule::parse("EST5EDT").unwrap();
+        assert_eq!(defaults.offset_ms_at(ms(2024, 3, 10, 7, 0)), -4 * HOUR_MS);
+        assert_eq!(defaults.offset_ms_at(ms(2024, 11, 3, 6, 0)), -5 * HOUR_MS);
+    }
+
+    #[test]
+    fn last_week_of_month() {
+        let london = TzRule::parse("GMT0BST,M3.5.0/1,M10.5.0").unwrap();
+        // the last Sundays of March and October, 2024 and 2025
+        assert_eq!(london.offset_ms_at(ms(2024, 3, 31, 0, 59)), 0);
+        assert_eq!(london.offset_ms_at(ms(2024, 3, 31, 1, 0)), HOUR_MS);
+        assert_eq!(london.offset_ms_at(ms(2024, 10, 27, 0, 59)), HOUR_MS);
+        assert_eq!(london.offset_ms_at(ms(2024, 10, 27, 1, 0)), 0);
+        assert_eq!(london.offset_ms_at(ms(2025, 3, 30, 1, 0)), HOUR_MS);
+        assert_eq!(london.offset_ms_at(ms(2025, 10, 26, 1, 0)), 0);
+    }
+
+    #[test]
+    fn southern_hemisphere() {
+        let sydney = TzRule::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(sydney.offset_ms_at(ms(2024, 1, 15, 0, 0)), 11 * HOUR_MS);
+        assert_eq!(sydney.offset_ms_at(ms(2024, 7, 15, 0, 0)), 10 * HOUR_MS);
+        // 2024-04-07 03:00 AEDT and 2024-10-06 02:00 AEST
+        assert_eq!(sydney.offset_ms_at(ms(2024, 4, 6, 15, 59)), 11 * HOUR_MS);
+        assert_eq!(sydney.offset_ms_at(ms(2024, 4, 6, 16, 0)), 10 * HOUR_MS);
+        assert_eq!(sydney.offset_ms_at(ms(2024, 10, 5, 15, 59)), 10 * HOUR_MS);
+        assert_eq!(sydney.offset_ms_at(ms(2024, 10, 5, 16, 0)), 11 * HOUR_MS);
+    }
+
+    #[test]
+    fn negative_dst() {
+        // Ireland's standard time is summer time, with GMT in winter
+        let dublin = TzRule::parse("IST-1GMT0,M10.5.0,M3.5.0/1").unwrap();
+        assert_eq!(dublin.offset_ms_at(ms(2024, 1, 15, 0, 0)), 0);
+        assert_eq!(dublin.offset_ms_at(ms(2024, 7, 15, 0, 0)), HOUR_MS);
+        assert_eq!(dublin.offset_ms_at(ms(2024, 3, 31, 1, 0)), HOUR_MS);
+        assert_eq!(dublin.offset_ms_at(ms(2024, 10, 27, 1, 0)), 0);
+    }
+
+    #[test]
+    fn transition_times_past_midnight() {
+        // Jerusalem goes forward at 02:00 on the Friday before the last Sunday of March, given as Thursday
+        // 26:00
+        let jerusalem = TzRule::parse("IST-2IDT,M3.4.4/26,M10.5.0").unwrap();
+        assert_eq!(jerusalem.offset_ms_at(ms(2024, 3, 28, 23, 59)), 2 * HOUR_MS);
+        assert_eq!(jerusalem.offset_ms_at(ms(2024, 3, 29, 0, 0)), 3 * HOUR_MS);
+    }
+
+    #[test]
+    fn julian_days() {
+        let at =
+            |day: TransitionDay, year: i32| Transition { day, time: 0 }.utc_secs(year, 0).unwrap() * 1000;
+        // February 29 is never counted by `Jn`, but is by `n`
+        assert_eq!(at(TransitionDay::Julian(60), 2024), ms(2024, 3, 1, 0, 0));
+        assert_eq!(at(TransitionDay::Julian(60), 2023), ms(2023, 3, 1, 0, 0));
+        assert_eq!(at(TransitionDay::ZeroBased(59), 2024), ms(2024, 2, 29, 0, 0));
+        assert_eq!(at(TransitionDay::ZeroBased(59), 2023), ms(2023, 3, 1, 0, 0));
+        assert_eq!(at(TransitionDay::Julian(365), 2024), ms(2024, 12, 31, 0, 0));
+    }
+
+    #[test]
+    fn local_times() {
+        let ny = TzRule::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        assert_eq!(ny.offset_ms_for_local(ms(2024, 1, 15, 12, 0)), -5 * HOUR_MS);
+        assert_eq!(ny.offset_ms_for_local(ms(2024, 7, 15, 12, 0)), -4 * HOUR_MS);
+        // skipped and repeated local times take the offset in effect after the transition
+        assert_eq!(ny.offset_ms_for_local(ms(2024, 3, 10, 2, 30)), -4 * HOUR_MS);
+        assert_eq!(ny.offset_ms_for_local(ms(2024, 11, 3, 1, 30)), -5 * HOUR_MS);
+        let fixed = TzRule::parse("IST-5:30").unwrap();
+        assert_eq!(fixed.offset_ms_for_local(ms(2024, 3, 10, 2, 30)), 5 * HOUR_MS + HOUR_MS / 2);
+    }
+}
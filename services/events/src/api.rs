@@ -0,0 +1,97 @@
+pub(crate) const SERVER_NAME_EVENTS: &str = "_Event store_";
+
+/// The longest title an event can have, in bytes
+pub const EVENT_TITLE_LEN: usize = 96;
+/// The most occurrences returned by one `Opcode::Occurrences` request; the client asks again for more
+pub const MAX_OCCURRENCES: usize = 16;
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// Stores a new event, and returns its id (memory message, `EventRequest`)
+    Add = 0,
+    /// Deletes an event (blocking scalar, arg1 = id, returns 1 if it was there)
+    Delete = 1,
+    /// The occurrences of events in a span of time (memory message, `OccurrenceQuery`)
+    Occurrences = 2,
+    /// Starts or stops reading an iCalendar file from the USB serial port (blocking scalar, arg1 = 1 to
+    /// start, 0 to stop, returns 1 while reading)
+    SerialImport = 3,
+    /// Sends out the reminders that have come due; from the service's own timer (scalar)
+    Check = 4,
+    /// Exits the server
+    Quit = 5,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub enum EventError {
+    /// The events are kept in the PDDB, which isn't mounted yet
+    NotMounted,
+    /// The PDDB could not be read or written
+    Storage,
+    /// The title is empty, or the recurrence has an interval of 0
+    BadEvent,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    /// On the same day of every month. Months without that day, such as the 31st in April, are skipped.
+    Monthly,
+    /// On the same day of every year; an event on February 29th only happens in leap years
+    Yearly,
+}
+
+/// How an event repeats, after the RRULE of iCalendar
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Recurrence {
+    pub freq: Frequency,
+    /// 1 for every day, week, month or year; 2 for every other one, and so on
+    pub interval: u32,
+    /// no occurrences start after this time, in the same units as `Event::start`
+    pub until: Option<i64>,
+    /// the number of occurrences, counting the first
+    pub count: Option<u32>,
+}
+
+/// An event in the calendar. Times are local time, in seconds since 1970-01-01 00:00 as if the local time
+/// zone were UTC: a "floating" time, which stays at the same time of day when the time zone changes.
+/// `chrono::NaiveDateTime::from_timestamp_opt(start, 0)` turns it into a date and time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Event {
+    /// assigned by the store when the event is added; 0 until then
+    pub id: u32,
+    pub title: xous_ipc::String<EVENT_TITLE_LEN>,
+    pub start: i64,
+    pub duration_mins: u32,
+    pub recurrence: Option<Recurrence>,
+    /// a notification is posted this many minutes before each occurrence
+    pub reminder_mins: Option<u32>,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct EventRequest {
+    pub event: Event,
+    pub error: Option<EventError>,
+}
+
+/// One time that an event happens
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Occurrence {
+    pub id: u32,
+    pub title: xous_ipc::String<EVENT_TITLE_LEN>,
+    pub start: i64,
+    pub duration_mins: u32,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct OccurrenceQuery {
+    pub from: i64,
+    pub to: i64,
+    /// the number of occurrences to pass over, for the requests after the first
+    pub skip: u32,
+    pub occurrences: [Option<Occurrence>; MAX_OCCURRENCES],
+    /// set if there are occurrences after the ones returned
+    pub more: bool,
+    pub error: Option<EventError>,
+}
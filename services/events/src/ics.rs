@@ -0,0 +1,374 @@
+//! Reads the events out of an iCalendar (RFC 5545) file, one line at a time as it comes in over the
+//! serial port.
+//!
+//! Only what the store can keep is read: the SUMMARY, DTSTART, DTEND or DURATION, the FREQ, INTERVAL,
+//! UNTIL and COUNT of an RRULE, and the TRIGGER of the first VALARM. There is no time zone database, so
+//! times with a TZID are taken to be in the device's time zone; times in UTC are moved into it.
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::api::*;
+
+/// The longest content line, once unfolded, that is taken. Long DESCRIPTIONs fit; a file with a longer
+/// line is rejected, rather than kept in memory however long it grows.
+const MAX_LINE_LEN: usize = 16 * 1024;
+
+/// An event whose VEVENT has started, but not yet ended
+#[derive(Default)]
+struct Partial {
+    title: String,
+    start: Option<i64>,
+    /// set if DTSTART is a date without a time
+    all_day: bool,
+    end: Option<i64>,
+    duration: Option<i64>,
+    recurrence: Option<Recurrence>,
+    reminder_mins: Option<u32>,
+    /// inside a VALARM
+    in_alarm: bool,
+}
+
+pub(crate) struct IcsReader {
+    /// the content line being put together out of folded lines
+    line: String,
+    event: Option<Partial>,
+    /// local time less UTC, in seconds
+    utc_offset: i64,
+    done: bool,
+    rejected: bool,
+}
+impl IcsReader {
+    pub(crate) fn new(utc_offset: i64) -> Self {
+        IcsReader { line: String::new(), event: None, utc_offset, done: false, rejected: false }
+    }
+
+    /// Set once the END:VCALENDAR line has been read, or the file has been rejected
+    pub(crate) fn is_done(&self) -> bool { self.done }
+
+    /// Set if a line ran past `MAX_LINE_LEN`. Nothing after it is read.
+    pub(crate) fn is_rejected(&self) -> bool { self.rejected }
+
+    /// Takes the next line of the file, and returns the event it completes, if any
+    pub(crate) fn feed(&mut self, raw: &str) -> Option<Event> {
+        if self.done {
+            return None;
+        }
+        let raw = raw.trim_end_matches(|c| c == '\r' || c == '\n');
+        // a line that starts with white space carries on the one before
+        let rest = raw.strip_prefix(|c| c == ' ' || c == '\t');
+        let len = rest.map_or(raw.len(), |rest| self.line.len() + rest.len());
+        if len > MAX_LINE_LEN {
+            log::warn!("a line runs past {} bytes, rejecting the file", MAX_LINE_LEN);
+            self.line.clear();
+            self.event = None;
+            self.done = true;
+            self.rejected = true;
+            return None;
+        }
+        if let Some(rest) = rest {
+            self.line.push_str(rest);
+            return None;
+        }
+        let complete = std::mem::replace(&mut self.line, raw.to_string());
+        let event = self.content_line(&complete);
+        // nothing carries on the last line, so it is taken as it is
+        if raw.eq_ignore_ascii_case("END:VCALENDAR") {
+            self.line.clear();
+            self.done = true;
+        }
+        event
+    }
+
+    fn content_line(&mut self, line: &str) -> Option<Event> {
+        let (name, params, value) = split_line(line)?;
+        let name = name.to_ascii_uppercase();
+        match (name.as_str(), value.to_ascii_uppercase().as_str()) {
+            ("BEGIN", "VEVENT") => self.event = Some(Partial::default()),
+            ("END", "VEVENT") => return self.event.take().and_then(|partial| partial.finish()),
+            ("BEGIN", "VALARM") => self.event.as_mut()?.in_alarm = true,
+            ("END", "VALARM") => self.event.as_mut()?.in_alarm = false,
+            _ => {}
+        }
+        let utc_offset = self.utc_offset;
+        let event = self.event.as_mut()?;
+        if event.in_alarm {
+            // only alarms relative to the start, and only the first
+            if name == "TRIGGER" && event.reminder_mins.is_none() && !params.contains("RELATED=END") {
+                if let Some(before) = parse_duration(value).filter(|secs| *secs <= 0) {
+                    event.reminder_mins = Some((-before / 60) as u32);
+                }
+            }
+            return None;
+        }
+        match name.as_str() {
+            "SUMMARY" => event.title = unescape(value),
+            "DTSTART" => {
+                event.start = parse_time(value, utc_offset);
+                event.all_day = value.trim().len() == 8;
+            }
+            "DTEND" => event.end = parse_time(value, utc_offset),
+            "DURATION" => event.duration = parse_duration(value),
+            "RRULE" => event.recurrence = parse_rule(value, utc_offset),
+            _ => {}
+        }
+        None
+    }
+}
+
+impl Partial {
+    fn finish(self) -> Option<Event> {
+        let start = match self.start {
+            Some(start) => start,
+            None => {
+                log::warn!("skipping an event without a start: {}", self.title);
+                return None;
+            }
+        };
+        let length = match (self.end, self.duration) {
+            (Some(end), _) => end - start,
+            (None, Some(duration)) => duration,
+            // RFC 5545: a day for an all-day event, otherwise none
+            (None, None) => {
+                if self.all_day {
+                    24 * 60 * 60
+                } else {
+                    0
+                }
+            }
+        };
+        let mut end = self.title.len().min(EVENT_TITLE_LEN);
+        while !self.title.is_char_boundary(end) {
+            end -= 1;
+        }
+        let title = if end == 0 { "-" } else { &self.title[..end] };
+        Some(Event {
+            id: 0,
+            title: xous_ipc::String::from_str(title),
+            start,
+            duration_mins: (length.max(0) / 60) as u32,
+            recurrence: self.recurrence,
+            reminder_mins: self.reminder_mins,
+        })
+    }
+}
+
+/// Splits "NAME;PARAM=a;PARAM=b:value" into its name, its parameters and its value. A ':' inside a
+/// quoted parameter value is not the end of the parameters.
+fn split_line(line: &str) -> Option<(&str, String, &str)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let (name, params) = head.split_once(';').unwrap_or((head, ""));
+    Some((name, params.to_ascii_uppercase(), value))
+}
+
+/// Undoes the escapes of a TEXT value; line breaks become spaces, since titles are one line
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => text.push(' '),
+                Some(escaped) => text.push(escaped),
+                None => {}
+            },
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+/// "20240501", "20240501T090000" or "20240501T090000Z", as local time
+fn parse_time(value: &str, utc_offset: i64) -> Option<i64> {
+    let value = value.trim();
+    if value.len() == 8 {
+        return Some(NaiveDate::parse_from_str(value, "%Y%m%d").ok()?.and_hms_opt(0, 0, 0)?.timestamp());
+    }
+    let (local, utc) = match value.strip_suffix(|c| c == 'Z' || c == 'z') {
+        Some(local) => (local, true),
+        None => (value, false),
+    };
+    let time = NaiveDateTime::parse_from_str(local, "%Y%m%dT%H%M%S").ok()?.timestamp();
+    Some(if utc { time + utc_offset } else { time })
+}
+
+/// "PT1H30M", "P1D", "-PT15M" or "P2W", in seconds
+fn parse_duration(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (sign, rest) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut secs = 0i64;
+    let mut number = String::new();
+    for c in rest.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let unit = match c {
+                    'W' => 7 * 24 * 60 * 60,
+                    'D' => 24 * 60 * 60,
+                    'H' => 60 * 60,
+                    'M' => 60,
+                    _ => 1,
+                };
+                secs = secs.checked_add(number.parse::<i64>().ok()?.checked_mul(unit)?)?;
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+    Some(sign * secs)
+}
+
+/// "FREQ=WEEKLY;INTERVAL=2;UNTIL=20241231T000000Z". Rules that pick days (BYDAY and the like) are
+/// imported as the plain FREQ, which is a warning rather than an error, because the first occurrence
+/// and most of the others are usually right.
+fn parse_rule(value: &str, utc_offset: i64) -> Option<Recurrence> {
+    let mut recurrence = Recurrence { freq: Frequency::Daily, interval: 1, until: None, count: None };
+    let mut freq = None;
+    for part in value.split(';') {
+        let (key, value) = part.split_once('=')?;
+        match key.to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                freq = match value.to_ascii_uppercase().as_str() {
+                    "DAILY" => Some(Frequency::Daily),
+                    "WEEKLY" => Some(Frequency::Weekly),
+                    "MONTHLY" => Some(Frequency::Monthly),
+                    "YEARLY" => Some(Frequency::Yearly),
+                    other => {
+                        log::warn!("can't repeat {}, importing the first occurrence only", other);
+                        return None;
+                    }
+                }
+            }
+            "INTERVAL" => recurrence.interval = value.parse().ok().filter(|i| *i > 0)?,
+            "UNTIL" => recurrence.until = Some(parse_time(value, utc_offset)?),
+            "COUNT" => recurrence.count = Some(value.parse().ok()?),
+            "WKST" => {}
+            other => log::warn!("ignoring {} in a repeat rule", other),
+        }
+    }
+    recurrence.freq = freq?;
+    Some(recurrence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:1@example.com\r
+DTSTART;TZID=\"Europe/Paris\":20240501T090000\r
+DTEND;TZID=\"Europe/Paris\":20240501T100000\r
+SUMMARY:Team meeting\\, weekly with a long title that goes on past the end o\r
+ f the line\r
+RRULE:FREQ=WEEKLY;INTERVAL=2;COUNT=10\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+TRIGGER:-PT15M\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20240704\r
+SUMMARY:Holiday\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20240102T230000Z\r
+DURATION:PT45M\r
+SUMMARY:Call\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn read(utc_offset: i64) -> (Vec<Event>, bool) {
+        let mut reader = IcsReader::new(utc_offset);
+        let events = ICS.split_inclusive('\n').filter_map(|line| reader.feed(line)).collect();
+        (events, reader.is_done())
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> i64 {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(h, min, 0).unwrap().timestamp()
+    }
+
+    #[test]
+    fn reads_events() {
+        let (events, done) = read(3600);
+        assert!(done);
+        assert_eq!(events.len(), 3);
+
+        let meeting = &events[0];
+        assert_eq!(
+            meeting.title.as_str().unwrap(),
+            "Team meeting, weekly with a long title that goes on past the end of the line"
+        );
+        assert_eq!(meeting.start, at(2024, 5, 1, 9, 0));
+        assert_eq!(meeting.duration_mins, 60);
+        assert_eq!(
+            meeting.recurrence,
+            Some(Recurrence { freq: Frequency::Weekly, interval: 2, until: None, count: Some(10) })
+        );
+        assert_eq!(meeting.reminder_mins, Some(15));
+
+        assert_eq!(events[1].start, at(2024, 7, 4, 0, 0));
+        assert_eq!(events[1].duration_mins, 24 * 60);
+        assert_eq!(events[1].reminder_mins, None);
+
+        // UTC moved to local time, an hour ahead
+        assert_eq!(events[2].start, at(2024, 1, 3, 0, 0));
+        assert_eq!(events[2].duration_mins, 45);
+    }
+
+    #[test]
+    fn rejects_overlong_lines() {
+        let mut reader = IcsReader::new(0);
+        reader.feed("BEGIN:VCALENDAR\r\n");
+        reader.feed("BEGIN:VEVENT\r\n");
+        reader.feed("DTSTART:20240501T090000\r\n");
+        reader.feed("DESCRIPTION:\r\n");
+        let fold = format!(" {}\r\n", "x".repeat(1000));
+        for _ in 0..MAX_LINE_LEN / 1000 {
+            reader.feed(&fold);
+        }
+        assert!(!reader.is_done());
+        reader.feed(&fold);
+        assert!(reader.is_done() && reader.is_rejected());
+        assert_eq!(reader.feed("END:VEVENT\r\n"), None);
+
+        let mut reader = IcsReader::new(0);
+        reader.feed(&"x".repeat(MAX_LINE_LEN + 1));
+        assert!(reader.is_rejected());
+    }
+
+    #[test]
+    fn durations_and_rules() {
+        assert_eq!(parse_duration("PT1H30M"), Some(90 * 60));
+        assert_eq!(parse_duration("-P1DT2H"), Some(-26 * 60 * 60));
+        assert_eq!(parse_duration("P2W"), Some(14 * 24 * 60 * 60));
+        assert_eq!(parse_duration("1H"), None);
+        assert_eq!(parse_duration("P99999999999999W"), None);
+        assert_eq!(parse_duration("P9223372036854775807DT1S"), None);
+        assert_eq!(parse_rule("FREQ=HOURLY", 0), None);
+        assert_eq!(parse_rule("INTERVAL=0;FREQ=DAILY", 0), None);
+        assert_eq!(
+            parse_rule("FREQ=MONTHLY;BYDAY=1MO;UNTIL=20241231", 0),
+            Some(Recurrence {
+                freq: Frequency::Monthly,
+                interval: 1,
+                until: Some(at(2024, 12, 31, 0, 0)),
+                count: None
+            })
+        );
+    }
+}
@@ -0,0 +1,125 @@
+#![doc = include_str!("../README.md")]
+
+pub mod api;
+pub use api::*;
+use num_traits::*;
+use xous::{send_message, Message, CID};
+use xous_ipc::Buffer;
+
+/// Client API to the event store. The service is only in images built with `--service events`, so
+/// `new()` fails rather than waits when it is absent.
+pub struct Events {
+    conn: CID,
+}
+impl Events {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection(api::SERVER_NAME_EVENTS)?;
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(Events { conn })
+    }
+
+    /// Stores `event`, and returns the id it is given. The `id` of `event` is ignored.
+    pub fn add(&self, event: Event) -> Result<Result<u32, EventError>, xous::Error> {
+        let mut buf =
+            Buffer::into_buf(EventRequest { event, error: None }).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::Add.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        let response = buf.to_original::<EventRequest, _>().or(Err(xous::Error::InternalError))?;
+        match response.error {
+            Some(e) => Ok(Err(e)),
+            None => Ok(Ok(response.event.id)),
+        }
+    }
+
+    /// Deletes the event with `id`, with all of its occurrences. Returns `false` if there was no such
+    /// event, or the PDDB isn't mounted.
+    pub fn delete(&self, id: u32) -> Result<bool, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::Delete.to_usize().unwrap(), id as usize, 0, 0, 0),
+        )? {
+            xous::Result::Scalar1(deleted) => Ok(deleted != 0),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Every occurrence of every event that overlaps the time from `from` up to `to`, in order of their
+    /// start. Times are as in `Event::start`.
+    pub fn occurrences(
+        &self,
+        from: i64,
+        to: i64,
+    ) -> Result<Result<Vec<Occurrence>, EventError>, xous::Error> {
+        let mut occurrences: Vec<Occurrence> = Vec::new();
+        loop {
+            let query = OccurrenceQuery {
+                from,
+                to,
+                skip: occurrences.len() as u32,
+                occurrences: [None; MAX_OCCURRENCES],
+                more: false,
+                error: None,
+            };
+            let mut buf = Buffer::into_buf(query).or(Err(xous::Error::InternalError))?;
+            buf.lend_mut(self.conn, Opcode::Occurrences.to_u32().unwrap())
+                .or(Err(xous::Error::InternalError))?;
+            let response = buf.to_original::<OccurrenceQuery, _>().or(Err(xous::Error::InternalError))?;
+            if let Some(e) = response.error {
+                return Ok(Err(e));
+            }
+            occurrences.extend(response.occurrences.iter().flatten());
+            if !response.more {
+                return Ok(Ok(occurrences));
+            }
+        }
+    }
+
+    /// Starts reading an iCalendar (.ics) file from the USB serial port, and switches the USB port to
+    /// serial to do so once the user allows it on the device. Each VEVENT is added as it arrives, and a
+    /// notification says how many were imported once the file ends; the USB port then goes back to its
+    /// previous core. Returns `true` if the service is reading.
+    pub fn start_serial_import(&self) -> Result<bool, xous::Error> { self.serial_import(true) }
+
+    /// Gives up on an import started by `start_serial_import()`. Events that arrived before are kept, and
+    /// the USB port goes back to its previous core.
+    pub fn stop_serial_import(&self) -> Result<bool, xous::Error> { self.serial_import(false) }
+
+    fn serial_import(&self, start: bool) -> Result<bool, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::SerialImport.to_usize().unwrap(), start as usize, 0, 0, 0),
+        )? {
+            xous::Result::Scalar1(reading) => Ok(reading != 0),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+}
+
+impl Event {
+    /// A one-off event with no reminder. A `title` longer than `EVENT_TITLE_LEN` bytes is cut short.
+    pub fn new(title: &str, start: i64, duration_mins: u32) -> Self {
+        let mut end = title.len().min(EVENT_TITLE_LEN);
+        while !title.is_char_boundary(end) {
+            end -= 1;
+        }
+        Event {
+            id: 0,
+            title: xous_ipc::String::from_str(&title[..end]),
+            start,
+            duration_mins,
+            recurrence: None,
+            reminder_mins: None,
+        }
+    }
+}
+
+use core::sync::atomic::{AtomicU32, Ordering};
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Events {
+    fn drop(&mut self) {
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+mod api;
+use api::*;
+mod ics;
+mod recur;
+mod store;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::NaiveDateTime;
+use locales::t;
+use num_traits::*;
+use usb_device_xous::{UsbDeviceType, UsbHid};
+use xous::{msg_blocking_scalar_unpack, CID};
+use xous_ipc::Buffer;
+
+/// How often the reminders are checked against the clock
+const CHECK_INTERVAL_MS: usize = 30_000;
+/// Where reminders come from, in the notification center
+const NOTIFICATION_SOURCE: &str = "events";
+
+fn local_now(localtime: &mut llio::LocalTime) -> Option<i64> {
+    localtime.get_local_time_ms().map(|ms| (ms / 1000) as i64)
+}
+
+/// Local time less UTC, in seconds, to bring times given in UTC into local time
+fn utc_offset(localtime: &mut llio::LocalTime) -> i64 {
+    let utc = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64);
+    match (local_now(localtime), utc) {
+        // time zones are whole quarter hours; the two clocks were read a moment apart
+        (Some(local), Ok(utc)) => (local - utc + 450).div_euclid(900) * 900,
+        _ => {
+            log::warn!("the time zone isn't set, so UTC times are taken as local");
+            0
+        }
+    }
+}
+
+/// Asks the user to let the USB port go over to serial for an import. Any process can ask for one, and
+/// it takes the port away from FIDO while it lasts.
+fn confirm_import(modals: &modals::Modals) -> bool {
+    modals
+        .add_list(vec![t!("events.yes", locales::LANG), t!("events.no", locales::LANG)])
+        .expect("couldn't build confirmation dialog");
+    match modals.get_radiobutton(t!("events.import_prompt", locales::LANG)) {
+        Ok(response) => response.as_str() == t!("events.yes", locales::LANG),
+        _ => false,
+    }
+}
+
+/// Switches the USB port back to `previous`, the core it was on before the import
+fn restore_core(usb: &UsbHid, previous: Option<UsbDeviceType>) {
+    if let Some(previous) = previous.filter(|&core| core != UsbDeviceType::Serial) {
+        if usb.ensure_core(previous).is_err() {
+            log::warn!("failed to switch USB back from serial");
+        }
+    }
+}
+
+/// Adds every event of the iCalendar file coming in on the USB serial port, until the file ends or
+/// `reading` is cleared. Whoever clears `reading` switches the USB port back to `previous`.
+fn read_serial(reading: Arc<AtomicBool>, self_cid: CID, utc_offset: i64, previous: Option<UsbDeviceType>) {
+    let usb = UsbHid::new();
+    let mut reader = ics::IcsReader::new(utc_offset);
+    let (mut imported, mut failed) = (0, 0);
+    while reading.load(Ordering::SeqCst) && !reader.is_done() {
+        let line = usb.serial_wait_ascii(Some('\n'));
+        if !reading.load(Ordering::SeqCst) {
+            break;
+        }
+        if let Some(event) = reader.feed(&line) {
+            let mut buf = Buffer::into_buf(EventRequest { event, error: None }).unwrap();
+            buf.lend_mut(self_cid, Opcode::Add.to_u32().unwrap()).unwrap();
+            match buf.to_original::<EventRequest, _>().unwrap().error {
+                None => imported += 1,
+                Some(e) => {
+                    log::warn!("couldn't add an imported event: {:?}", e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    if reading.swap(false, Ordering::SeqCst) {
+        restore_core(&usb, previous);
+    }
+    if reader.is_done() {
+        log::info!("imported {} events, {} failed", imported, failed);
+        let mut text = t!("events.imported", locales::LANG).replace("{count}", &imported.to_string());
+        if failed > 0 {
+            text.push_str(&t!("events.import_failed", locales::LANG).replace("{count}", &failed.to_string()));
+        }
+        if reader.is_rejected() {
+            text.push_str(t!("events.import_rejected", locales::LANG));
+        }
+        let xns = xous_names::XousNames::new().unwrap();
+        status::Status::new(&xns).unwrap().post_notification(NOTIFICATION_SOURCE, &text, true).ok();
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let events_sid = xns.register_name(api::SERVER_NAME_EVENTS, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", events_sid);
+    let self_cid = xous::connect(events_sid).unwrap();
+
+    let modals = modals::Modals::new(&xns).expect("can't connect to Modals server");
+    let usb = UsbHid::new();
+    let status = status::Status::new(&xns).unwrap();
+    let mut localtime = llio::LocalTime::new();
+    let mut store = store::EventStore::new();
+    // set while a thread is reading an iCalendar file from the serial port
+    let serial_reading = Arc::new(AtomicBool::new(false));
+    // the USB core to go back to once the import is over
+    let mut import_core: Option<UsbDeviceType> = None;
+    // the reminders due up to this time have been sent
+    let mut last_check: Option<i64> = None;
+
+    std::thread::spawn(move || {
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        loop {
+            tt.sleep_ms(CHECK_INTERVAL_MS).ok();
+            xous::send_message(
+                self_cid,
+                xous::Message::new_scalar(Opcode::Check.to_usize().unwrap(), 0, 0, 0, 0),
+            )
+            .ok();
+        }
+    });
+
+    loop {
+        let mut msg = xous::receive_message(events_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Add) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut request = buffer.to_original::<EventRequest, _>().unwrap();
+                match store.add(request.event) {
+                    Ok(id) => request.event.id = id,
+                    Err(e) => request.error = Some(e),
+                }
+                buffer.replace(request).unwrap();
+            }
+            Some(Opcode::Delete) => msg_blocking_scalar_unpack!(msg, id, _, _, _, {
+                let deleted = store.delete(id as u32).unwrap_or(false);
+                xous::return_scalar(msg.sender, deleted as usize).ok();
+            }),
+            Some(Opcode::Occurrences) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut query = buffer.to_original::<OccurrenceQuery, _>().unwrap();
+                match store.events() {
+                    Ok(events) => {
+                        let mut all: Vec<Occurrence> = events
+                            .iter()
+                            .flat_map(|e| {
+                                recur::starts_between(e, query.from, query.to).into_iter().map(move |start| {
+                                    Occurrence {
+                                        id: e.id,
+                                        title: e.title,
+                                        start,
+                                        duration_mins: e.duration_mins,
+                                    }
+                                })
+                            })
+                            .collect();
+                        all.sort_by_key(|o| (o.start, o.id));
+                        let skip = query.skip as usize;
+                        for (slot, occurrence) in query.occurrences.iter_mut().zip(all.iter().skip(skip)) {
+                            *slot = Some(*occurrence);
+                        }
+                        query.more = all.len() > skip + MAX_OCCURRENCES;
+                    }
+                    Err(e) => query.error = Some(e),
+                }
+                buffer.replace(query).unwrap();
+            }
+            Some(Opcode::SerialImport) => msg_blocking_scalar_unpack!(msg, start, _, _, _, {
+                if start != 0 && !serial_reading.load(Ordering::SeqCst) {
+                    if !confirm_import(&modals) {
+                        log::info!("the user declined a calendar import");
+                        xous::return_scalar(msg.sender, 0).ok();
+                        continue;
+                    }
+                    let previous = usb.get_current_core().ok();
+                    match usb.ensure_core(UsbDeviceType::Serial) {
+                        Ok(()) => {
+                            import_core = previous;
+                            serial_reading.store(true, Ordering::SeqCst);
+                            std::thread::spawn({
+                                let reading = serial_reading.clone();
+                                let utc_offset = utc_offset(&mut localtime);
+                                move || read_serial(reading, self_cid, utc_offset, previous)
+                            });
+                            log::info!("reading an iCalendar file from the serial port");
+                        }
+                        Err(e) => log::warn!("failed to switch USB to serial: {:?}", e),
+                    }
+                } else if start == 0 && serial_reading.swap(false, Ordering::SeqCst) {
+                    // releases the reader blocked on the serial port
+                    usb.serial_flush().ok();
+                    restore_core(&usb, import_core.take());
+                    log::info!("stopped reading the iCalendar file");
+                }
+                let reading = serial_reading.load(Ordering::SeqCst);
+                xous::return_scalar(msg.sender, if reading { 1 } else { 0 }).ok();
+            }),
+            Some(Opcode::Check) => {
+                let now = match local_now(&mut localtime) {
+                    Some(now) => now,
+                    None => continue,
+                };
+                let events = match store.events() {
+                    Ok(events) => events,
+                    // reminders that come due before the PDDB is mounted aren't sent
+                    Err(_) => continue,
+                };
+                let since = last_check.replace(now).unwrap_or(now);
+                for event in events.iter() {
+                    let lead = match event.reminder_mins {
+                        Some(mins) => mins as i64 * 60,
+                        None => continue,
+                    };
+                    // the occurrences whose reminder time is after the last check, up to now
+                    for start in recur::starts_between(event, since + lead + 1, now + lead + 1)
+                        .into_iter()
+                        .filter(|start| *start > since + lead)
+                    {
+                        let time = NaiveDateTime::from_timestamp_opt(start, 0)
+                            .map(|t| t.format("%H:%M").to_string())
+                            .unwrap_or_default();
+                        let text = t!("events.reminder", locales::LANG)
+                            .replace("{title}", event.title.as_str().unwrap_or(""))
+                            .replace("{time}", &time);
+                        status
+                            .post_notification(NOTIFICATION_SOURCE, &text, true)
+                            .unwrap_or_else(|e| log::warn!("couldn't post a reminder: {:?}", e));
+                    }
+                }
+            }
+            Some(Opcode::Quit) => {
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+    if serial_reading.swap(false, Ordering::SeqCst) {
+        usb.serial_flush().ok();
+        restore_core(&usb, import_core.take());
+    }
+    xns.unregister_server(events_sid).unwrap();
+    xous::destroy_server(events_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
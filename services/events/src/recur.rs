@@ -0,0 +1,142 @@
+//! Expands the recurrence of an event into the times it happens.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
+
+use crate::api::*;
+
+/// A bound on the occurrences looked at for one event, so that a rule that never yields one in the span
+/// asked about (ie the 31st of every other month, from a month with 30 days) can't loop forever.
+const MAX_STEPS: u32 = 100_000;
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// The start times of the occurrences of `event` that overlap the time from `from` up to `to`, in
+/// order. An event with no duration overlaps the span if it starts within it.
+pub(crate) fn starts_between(event: &Event, from: i64, to: i64) -> Vec<i64> {
+    let length = (event.duration_mins as i64 * 60).max(1);
+    let overlaps = |start: i64| start < to && start + length > from;
+    let rule = match event.recurrence {
+        Some(rule) => rule,
+        None => return if overlaps(event.start) { vec![event.start] } else { Vec::new() },
+    };
+    let first = match NaiveDateTime::from_timestamp_opt(event.start, 0) {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+    let interval = rule.interval.max(1);
+    // daily and weekly occurrences are evenly spaced, and every one of them counts, so the ones that
+    // end before `from` can be stepped over in one go
+    let mut step = match rule.freq {
+        Frequency::Daily | Frequency::Weekly => {
+            let days = if rule.freq == Frequency::Daily { 1 } else { 7 };
+            ((from - length - event.start) / (days * DAY_SECS * interval as i64)).max(0) as u32
+        }
+        Frequency::Monthly | Frequency::Yearly => 0,
+    };
+    let mut counted = step;
+    let mut starts = Vec::new();
+    for _ in 0..MAX_STEPS {
+        if rule.count.map_or(false, |count| counted >= count) {
+            break;
+        }
+        let next = advance(first, rule.freq, step.saturating_mul(interval));
+        step += 1;
+        // a month without the day of the event has no occurrence, and doesn't count towards `count`
+        let start = match next {
+            Some(next) => next.timestamp(),
+            None => continue,
+        };
+        if start >= to || rule.until.map_or(false, |until| start > until) {
+            break;
+        }
+        counted += 1;
+        if overlaps(start) {
+            starts.push(start);
+        }
+    }
+    starts
+}
+
+/// `first` moved on by `units` days, weeks, months or years, or `None` if there is no such date
+fn advance(first: NaiveDateTime, freq: Frequency, units: u32) -> Option<NaiveDateTime> {
+    match freq {
+        Frequency::Daily => first.checked_add_signed(Duration::days(units as i64)),
+        Frequency::Weekly => first.checked_add_signed(Duration::weeks(units as i64)),
+        Frequency::Monthly => add_months(first, units as i64),
+        Frequency::Yearly => add_months(first, units as i64 * 12),
+    }
+}
+
+/// The same day and time, `months` later; `None` if that month is too short for the day
+fn add_months(first: NaiveDateTime, months: i64) -> Option<NaiveDateTime> {
+    let month = first.year() as i64 * 12 + first.month0() as i64 + months;
+    let year = i32::try_from(month.div_euclid(12)).ok()?;
+    let date = NaiveDate::from_ymd_opt(year, month.rem_euclid(12) as u32 + 1, first.day())?;
+    Some(date.and_time(first.time()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> i64 {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(h, min, 0).unwrap().timestamp()
+    }
+
+    fn event(start: i64, duration_mins: u32, recurrence: Option<Recurrence>) -> Event {
+        Event {
+            id: 1,
+            title: xous_ipc::String::from_str("test"),
+            start,
+            duration_mins,
+            recurrence,
+            reminder_mins: None,
+        }
+    }
+
+    fn rule(freq: Frequency, interval: u32, until: Option<i64>, count: Option<u32>) -> Option<Recurrence> {
+        Some(Recurrence { freq, interval, until, count })
+    }
+
+    #[test]
+    fn one_off_overlap() {
+        let e = event(at(2024, 5, 1, 9, 0), 60, None);
+        assert_eq!(starts_between(&e, at(2024, 5, 1, 0, 0), at(2024, 5, 2, 0, 0)), vec![e.start]);
+        // still going at the start of the span
+        assert_eq!(starts_between(&e, at(2024, 5, 1, 9, 30), at(2024, 5, 2, 0, 0)), vec![e.start]);
+        assert!(starts_between(&e, at(2024, 5, 1, 10, 0), at(2024, 5, 2, 0, 0)).is_empty());
+        assert!(starts_between(&e, at(2024, 4, 1, 0, 0), e.start).is_empty());
+    }
+
+    #[test]
+    fn weekly_skips_ahead() {
+        let e = event(at(2020, 1, 6, 9, 0), 30, rule(Frequency::Weekly, 2, None, None));
+        let starts = starts_between(&e, at(2024, 5, 1, 0, 0), at(2024, 6, 1, 0, 0));
+        assert_eq!(starts, vec![at(2024, 5, 6, 9, 0), at(2024, 5, 20, 9, 0)]);
+    }
+
+    #[test]
+    fn count_and_until() {
+        let e = event(at(2024, 1, 1, 8, 0), 0, rule(Frequency::Daily, 1, None, Some(3)));
+        assert_eq!(starts_between(&e, at(2024, 1, 1, 0, 0), at(2025, 1, 1, 0, 0)).len(), 3);
+        let e = event(at(2024, 1, 1, 8, 0), 0, rule(Frequency::Daily, 1, Some(at(2024, 1, 5, 8, 0)), None));
+        assert_eq!(starts_between(&e, at(2024, 1, 3, 0, 0), at(2025, 1, 1, 0, 0)).len(), 3);
+    }
+
+    #[test]
+    fn short_months_and_leap_years() {
+        let e = event(at(2024, 1, 31, 12, 0), 60, rule(Frequency::Monthly, 1, None, Some(4)));
+        let starts = starts_between(&e, at(2024, 1, 1, 0, 0), at(2025, 1, 1, 0, 0));
+        assert_eq!(
+            starts,
+            vec![
+                at(2024, 1, 31, 12, 0),
+                at(2024, 3, 31, 12, 0),
+                at(2024, 5, 31, 12, 0),
+                at(2024, 7, 31, 12, 0)
+            ]
+        );
+        let e = event(at(2024, 2, 29, 0, 0), 24 * 60, rule(Frequency::Yearly, 1, None, None));
+        let starts = starts_between(&e, at(2025, 1, 1, 0, 0), at(2030, 1, 1, 0, 0));
+        assert_eq!(starts, vec![at(2028, 2, 29, 0, 0)]);
+    }
+}
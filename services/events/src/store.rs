@@ -0,0 +1,191 @@
+//! The events, kept in the PDDB: one key per event, named by its id, in the `events` dictionary. The
+//! events in a secret basis come and go with it.
+
+use std::io::{ErrorKind, Read, Write};
+
+use crate::api::*;
+
+const EVENT_DICT: &str = "events";
+
+pub(crate) struct EventStore {
+    pddb: pddb::Pddb,
+    pddb_poller: pddb::PddbMountPoller,
+    /// read from the PDDB on first use, and again whenever a basis is opened or closed
+    events: Option<Vec<Event>>,
+    /// the open bases when `events` was read
+    bases: Vec<String>,
+}
+impl EventStore {
+    pub(crate) fn new() -> Self {
+        EventStore {
+            pddb: pddb::Pddb::new(),
+            pddb_poller: pddb::PddbMountPoller::new(),
+            events: None,
+            bases: Vec::new(),
+        }
+    }
+
+    pub(crate) fn events(&mut self) -> Result<&[Event], EventError> {
+        if !self.pddb_poller.is_mounted_nonblocking() {
+            return Err(EventError::NotMounted);
+        }
+        let bases = self.pddb.list_basis();
+        if self.events.is_none() || bases != self.bases {
+            self.events = Some(self.load());
+            self.bases = bases;
+        }
+        Ok(self.events.as_deref().unwrap_or_default())
+    }
+
+    /// Stores `event` under a new id, and returns the id
+    pub(crate) fn add(&mut self, mut event: Event) -> Result<u32, EventError> {
+        let title = event.title.as_str().unwrap_or("");
+        if title.is_empty() || event.recurrence.map_or(false, |r| r.interval == 0) {
+            return Err(EventError::BadEvent);
+        }
+        event.id = self.events()?.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        let text = to_text(&event);
+        self.pddb
+            .get(EVENT_DICT, &event.id.to_string(), None, true, true, Some(text.len()), None::<fn()>)
+            .and_then(|mut key| key.write_all(text.as_bytes()))
+            .and_then(|_| self.pddb.sync())
+            .map_err(|e| {
+                log::warn!("couldn't store event {}: {:?}", event.id, e);
+                EventError::Storage
+            })?;
+        self.events.get_or_insert_with(Vec::new).push(event);
+        Ok(event.id)
+    }
+
+    /// Deletes the event with `id`, and returns `false` if there was none
+    pub(crate) fn delete(&mut self, id: u32) -> Result<bool, EventError> {
+        self.events()?;
+        match self.pddb.delete_key(EVENT_DICT, &id.to_string(), None) {
+            Ok(()) => {
+                self.pddb.sync().ok();
+                self.events.get_or_insert_with(Vec::new).retain(|e| e.id != id);
+                Ok(true)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => {
+                log::warn!("couldn't delete event {}: {:?}", id, e);
+                Err(EventError::Storage)
+            }
+        }
+    }
+
+    fn load(&self) -> Vec<Event> {
+        let keys = match self.pddb.list_keys(EVENT_DICT, None) {
+            Ok(keys) => keys,
+            // no events yet
+            Err(_) => return Vec::new(),
+        };
+        keys.iter()
+            .filter_map(|name| {
+                let mut text = String::new();
+                let mut key = self.pddb.get(EVENT_DICT, name, None, false, false, None, None::<fn()>).ok()?;
+                key.read_to_string(&mut text).ok()?;
+                let event = from_text(name.parse().ok()?, &text);
+                if event.is_none() {
+                    log::warn!("event {} can't be read, skipping it", name);
+                }
+                event
+            })
+            .collect()
+    }
+}
+
+/// An event as "field=value" lines. The title goes last, so that nothing it holds can be taken for
+/// another field.
+fn to_text(event: &Event) -> String {
+    let mut text = format!("start={}\nduration={}\n", event.start, event.duration_mins);
+    if let Some(rule) = event.recurrence {
+        let freq = match rule.freq {
+            Frequency::Daily => "daily",
+            Frequency::Weekly => "weekly",
+            Frequency::Monthly => "monthly",
+            Frequency::Yearly => "yearly",
+        };
+        text.push_str(&format!("freq={}\ninterval={}\n", freq, rule.interval));
+        if let Some(until) = rule.until {
+            text.push_str(&format!("until={}\n", until));
+        }
+        if let Some(count) = rule.count {
+            text.push_str(&format!("count={}\n", count));
+        }
+    }
+    if let Some(reminder) = event.reminder_mins {
+        text.push_str(&format!("remind={}\n", reminder));
+    }
+    text.push_str(&format!("title={}", event.title.as_str().unwrap_or("")));
+    text
+}
+
+fn from_text(id: u32, text: &str) -> Option<Event> {
+    let mut event = Event {
+        id,
+        title: xous_ipc::String::new(),
+        start: 0,
+        duration_mins: 0,
+        recurrence: None,
+        reminder_mins: None,
+    };
+    let mut rule = Recurrence { freq: Frequency::Daily, interval: 1, until: None, count: None };
+    let mut freq = None;
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let (field, value) = line.split_once('=')?;
+        match field {
+            "start" => event.start = value.parse().ok()?,
+            "duration" => event.duration_mins = value.parse().ok()?,
+            "freq" => {
+                freq = Some(match value {
+                    "daily" => Frequency::Daily,
+                    "weekly" => Frequency::Weekly,
+                    "monthly" => Frequency::Monthly,
+                    "yearly" => Frequency::Yearly,
+                    _ => return None,
+                })
+            }
+            "interval" => rule.interval = value.parse().ok()?,
+            "until" => rule.until = Some(value.parse().ok()?),
+            "count" => rule.count = Some(value.parse().ok()?),
+            "remind" => event.reminder_mins = Some(value.parse().ok()?),
+            "title" => {
+                // titles are a single line, but the rest is kept just in case
+                let rest: Vec<&str> = std::iter::once(value).chain(lines.by_ref()).collect();
+                event.title = xous_ipc::String::from_str(rest.join(" "));
+            }
+            _ => {}
+        }
+    }
+    event.recurrence = freq.map(|freq| Recurrence { freq, ..rule });
+    Some(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip() {
+        let mut event = Event {
+            id: 7,
+            title: xous_ipc::String::from_str("Rent = due"),
+            start: 1_714_554_000,
+            duration_mins: 30,
+            recurrence: Some(Recurrence {
+                freq: Frequency::Monthly,
+                interval: 1,
+                until: Some(1_800_000_000),
+                count: None,
+            }),
+            reminder_mins: Some(24 * 60),
+        };
+        assert_eq!(from_text(7, &to_text(&event)), Some(event));
+        event.recurrence = None;
+        event.reminder_mins = None;
+        assert_eq!(from_text(7, &to_text(&event)), Some(event));
+        assert_eq!(from_text(7, "start=soon\ntitle=x"), None);
+    }
+}
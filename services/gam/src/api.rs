@@ -75,6 +75,41 @@ pub struct SwitchToApp {
     pub app_name: String<128>,
 }
 
+/// An input method engine: a service, registered with xous-names under `server`, that implements the
+/// `ime_plugin_api::PredictionApi` opcodes. Text typed into a chat-style context is fed to the context's
+/// engine, and the engine's candidates are offered in the prediction area.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ImeEngine {
+    pub server: String<64>,
+    /// human-readable name shown to the user when picking an engine
+    pub label: String<64>,
+}
+pub const MAX_IME_ENGINES: usize = 8;
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ImeEngineRegistration {
+    /// app token of the registering context; engines can only be offered by apps known to the GAM
+    pub token: [u32; 4],
+    pub engine: ImeEngine,
+    pub accepted: bool,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ImeEngineList {
+    pub engines: [Option<ImeEngine>; MAX_IME_ENGINES],
+    /// the server name of the engine in use by the focused context, if it takes text input
+    pub selected: Option<String<64>>,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ImeEngineSelect {
+    /// the app token of the focused context, which is the only one that can switch its engine
+    pub token: [u32; 4],
+    /// server name of a registered engine, or `None` to go back to the engine the app registered with
+    pub server: Option<String<64>>,
+    pub accepted: bool,
+}
+
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
 pub enum UxType {
     Chat,
@@ -89,8 +124,8 @@ pub struct UxRegistration {
                                 * attempt is detected */
     pub ux_type: UxType,
     pub predictor: Option<String<64>>, /* optional specification for an IME prediction engine to use. This
-                                        * can be updated later on, or None and a default engine will be
-                                        * provided. */
+                                        * can be updated later on with `SelectImeEngine`, or None and a
+                                        * default engine will be provided. */
 
     // Callbacks:
     /// SID ofserver for callbacks from the GAM. Note this is a disclosure of the SID, which is normally a
@@ -227,6 +262,29 @@ pub(crate) enum Opcode {
     /// Register a name that can acquire a token. This is only intended to be used with pre-registered apps
     #[cfg(feature = "unsafe-app-loading")]
     RegisterName = 34,
+
+    /// Request that keyboard input go exclusively to the caller's context, e.g. for password entry
+    RequestInputGrab = 35,
+    /// Release an input grab. Grabs are also released when the holder loses focus or disconnects.
+    ReleaseInputGrab = 36,
+
+    /// Open an offscreen frame on the focused app's content canvas: screen flushes are held back until the
+    /// frame is presented, so everything drawn in between reaches the LCD at once.
+    BeginFrame = 37,
+    /// Close an offscreen frame and flush it to the screen.
+    PresentFrame = 38,
+
+    /// Offer an input method engine for selection by the user
+    RegisterImeEngine = 39,
+    /// List the registered input method engines
+    ListImeEngines = 40,
+    /// Switch the focused context over to another input method engine
+    SelectImeEngine = 41,
+
+    /// Put the screensaver up over the focused app, if the focus rules allow it
+    RaiseScreensaver = 42,
+    /// Take the screensaver down and return focus to the app it covered
+    DismissScreensaver = 43,
 }
 
 // small wart -- we have to reset the size of a modal to max size for resize computations
@@ -238,6 +296,22 @@ pub enum ActivationResult {
     Success,
     Failure,
 }
+/// Sets whether the keyboard observer (which drives e.g. the backlight) is still told that a key was hit
+/// during an input grab. Raw scancode listeners are always cut off during a grab.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone, Eq, PartialEq)]
+pub enum InputGrabPolicy {
+    /// the observer learns that a key was hit, but not which one
+    NotifyObservers,
+    /// the observer learns nothing, not even the timing of keystrokes
+    Exclusive,
+}
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct InputGrabRequest {
+    pub(crate) token: [u32; 4],
+    pub(crate) policy: InputGrabPolicy,
+    pub(crate) granted: bool,
+}
+
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub(crate) struct GamActivation {
     pub(crate) name: xous_ipc::String<128>,
@@ -40,6 +40,8 @@ pub(crate) enum MenuMgrOp {
     AddItem,
     InsertItem(usize),
     DeleteItem,
+    /// renames the item to the given name
+    RenameItem(xous_ipc::String<64>),
     SetIndex(usize),
     Quit,
     // response must be one of these
@@ -68,6 +68,9 @@ pub(crate) struct UxContext {
     pub layout: UxLayout,
     /// what prediction engine is being used
     pub predictor: Option<String<64>>,
+    /// the prediction engine the app registered with; `predictor` reverts to this when the user goes back
+    /// to the default input method
+    pub default_predictor: Option<String<64>>,
     /// a putative human-readable name given to the context. The name itself is stored in the TokenManager,
     /// not in this struct. Passed to the TokenManager to compute a trust level; add the app's name to
     /// tokens.rs EXPECTED_BOOT_CONTEXTS if you want this to succeed.
@@ -99,6 +102,14 @@ pub(crate) struct UxContext {
 }
 pub(crate) const BOOT_CONTEXT_TRUSTLEVEL: u8 = 254;
 
+/// A request by a context to receive keyboard input exclusively.
+struct InputGrab {
+    token: [u32; 4],
+    /// the trust level of the context at the time of the request
+    priority: u8,
+    policy: InputGrabPolicy,
+}
+
 /*
   For now, app focus from menus is cooperative (menu items must relinquish focus).
   However, later on, I think it would be good to implement a press-hold to feature to
@@ -121,6 +132,12 @@ pub(crate) struct ContextManager {
     tt: ticktimer_server::Ticktimer,
     /// used to suppress the main menu from activating until the boot PIN has been requested
     allow_mainmenu: bool,
+    /// outstanding input grabs, in the order they were granted
+    input_grabs: Vec<InputGrab>,
+    /// the app the screensaver is covering, while it is up
+    screensaver_covered: Option<[u32; 4]>,
+    /// input method engines the user can pick from
+    ime_engines: Vec<ImeEngine>,
 }
 impl ContextManager {
     pub fn new(xns: &xous_names::XousNames) -> Self {
@@ -144,6 +161,12 @@ impl ContextManager {
             trng: trng::Trng::new(&xns).expect("couldn't connect to trng"),
             tt: ticktimer_server::Ticktimer::new().unwrap(),
             allow_mainmenu: false,
+            input_grabs: Vec::new(),
+            screensaver_covered: None,
+            ime_engines: vec![ImeEngine {
+                server: String::from_str(ime_plugin_api::SERVER_NAME_IME_PLUGIN_SHELL),
+                label: String::from_str("shell"),
+            }],
         }
     }
 
@@ -177,6 +200,7 @@ impl ContextManager {
                     let ux_context = UxContext {
                         layout: UxLayout::ChatLayout(chatlayout),
                         predictor: registration.predictor,
+                        default_predictor: registration.predictor,
                         app_token: token,
                         gam_token: [
                             trng.get_u32().unwrap(),
@@ -206,6 +230,7 @@ impl ContextManager {
                     let ux_context = UxContext {
                         layout: UxLayout::MenuLayout(menulayout),
                         predictor: None,
+                        default_predictor: None,
                         app_token: token,
                         gam_token: [
                             trng.get_u32().unwrap(),
@@ -243,6 +268,7 @@ impl ContextManager {
                     let ux_context = UxContext {
                         layout: UxLayout::ModalLayout(modallayout),
                         predictor: None,
+                        default_predictor: None,
                         app_token: token,
                         gam_token: [
                             trng.get_u32().unwrap(),
@@ -278,6 +304,7 @@ impl ContextManager {
                     let ux_context = UxContext {
                         layout: UxLayout::Framebuffer(raw_fb),
                         predictor: None,
+                        default_predictor: None,
                         app_token: token,
                         gam_token: [
                             trng.get_u32().unwrap(),
@@ -400,6 +427,7 @@ impl ContextManager {
     ) -> Result<(), xous::Error> {
         // log::set_max_level(log::LevelFilter::Trace);
         self.notify_app_switch(token).ok();
+        let previous_focus = self.focused_context;
 
         let mut leaving_visibility: bool = false;
         let stack_on_entry = self.context_stack.len();
@@ -494,39 +522,14 @@ impl ContextManager {
             if let Some(context) = maybe_new_focus {
                 if context.predictor.is_some() {
                     // only hook up the IMEF if a predictor is selected for this context
-                    let descriptor = ImefDescriptor {
-                        input_canvas: if let Some(gr) = context
-                            .layout
-                            .get_gids()
-                            .iter()
-                            .filter(|&gr| gr.canvas_type == CanvasType::ChatInput)
-                            .next()
-                        {
-                            Some(gr.gid)
-                        } else {
-                            None
-                        },
-                        prediction_canvas: if let Some(gr) = context
-                            .layout
-                            .get_gids()
-                            .iter()
-                            .filter(|&gr| gr.canvas_type == CanvasType::ChatPreditive)
-                            .next()
-                        {
-                            Some(gr.gid)
-                        } else {
-                            None
-                        },
-                        predictor: context.predictor,
-                        token: context.gam_token,
-                        predictor_token: context.pred_token,
-                    };
                     log::debug!(
                         "context gam token: {:x?}, pred token: {:x?}",
                         context.gam_token,
                         context.pred_token
                     );
-                    self.imef.connect_backend(descriptor).expect("couldn't connect IMEF to the current app");
+                    self.imef
+                        .connect_backend(imef_descriptor(context))
+                        .expect("couldn't connect IMEF to the current app");
                     self.imef_active = true;
                 } else {
                     self.imef_active = false;
@@ -559,6 +562,12 @@ impl ContextManager {
                 self.last_context = self.focused_context;
                 self.focused_context = Some(last_token);
             }
+            // an input grab only lasts as long as the grabbing context keeps focus
+            if let Some(previous) = previous_focus {
+                if self.focused_context != Some(previous) {
+                    self.release_input_grab_held_by(previous);
+                }
+            }
             log::trace!("context stack: {:x?}", self.context_stack);
             if self.context_stack.len() > 1 {
                 // we've now got a stack of contexts, start stashing copies
@@ -601,6 +610,10 @@ impl ContextManager {
         gfx: &graphics_server::Gfx,
         canvases: &mut HashMap<Gid, Canvas>,
     ) -> Result<(), xous::Error> {
+        // a context that gives up focus is done with the keyboard, too
+        if let Some(focused) = self.focused_context {
+            self.release_input_grab_held_by(focused);
+        }
         if let Some(last) = self.last_context {
             self.activate(gfx, canvases, last, false)
         } else {
@@ -729,15 +742,94 @@ impl ContextManager {
 
     pub(crate) fn allow_mainmenu(&mut self) { self.allow_mainmenu = true; }
 
+    /// Grants an input grab to the context holding `token`, if it has focus and no context with a higher
+    /// trust level already holds one. Among grabs of equal trust, the most recent one is in effect.
+    pub(crate) fn request_input_grab(
+        &mut self,
+        token: [u32; 4],
+        policy: InputGrabPolicy,
+        canvases: &HashMap<Gid, Canvas>,
+    ) -> bool {
+        if self.focused_context != Some(token) {
+            log::warn!("input grab requested by {:?} without focus, denied", self.tm.lookup_name(&token));
+            return false;
+        }
+        let priority = match self.get_context_by_token(token) {
+            Some(context) => context
+                .layout
+                .get_gids()
+                .iter()
+                .filter_map(|gr| canvases.get(&gr.gid))
+                .map(|canvas| canvas.trust_level())
+                .max()
+                .unwrap_or(0),
+            None => {
+                log::warn!("input grab requested with an unknown token, denied");
+                return false;
+            }
+        };
+        if self.input_grabs.iter().any(|g| g.token != token && g.priority > priority) {
+            log::info!(
+                "input grab by {:?} denied, a more trusted context holds the keyboard",
+                self.tm.lookup_name(&token)
+            );
+            return false;
+        }
+        log::debug!("input grab granted to {:?}", self.tm.lookup_name(&token));
+        self.input_grabs.retain(|g| g.token != token);
+        self.input_grabs.push(InputGrab { token, priority, policy });
+        self.update_input_grab();
+        true
+    }
+
+    pub(crate) fn release_input_grab(&mut self, token: [u32; 4]) {
+        self.input_grabs.retain(|g| g.token != token);
+        self.update_input_grab();
+    }
+
+    /// Releases the grab held by the context with `token`, if it holds one.
+    fn release_input_grab_held_by(&mut self, token: [u32; 4]) {
+        if self.input_grabs.iter().any(|g| g.token == token) {
+            log::debug!("releasing input grab of {:?}", self.tm.lookup_name(&token));
+            self.release_input_grab(token);
+        }
+    }
+
+    /// The grab in effect, if any.
+    fn input_grab(&self) -> Option<&InputGrab> { self.input_grabs.iter().max_by_key(|g| g.priority) }
+
+    /// Tells the keyboard service which listeners may still see keystrokes. Grabs are taken for entering
+    /// secrets, so the IMEF is also told not to feed what is typed under one back to the predictor.
+    fn update_input_grab(&self) {
+        let grab = self.input_grab().map(|g| match g.policy {
+            InputGrabPolicy::NotifyObservers => keyboard::GrabPolicy::NotifyObservers,
+            InputGrabPolicy::Exclusive => keyboard::GrabPolicy::Exclusive,
+        });
+        self.kbd.set_input_grab(grab).expect("couldn't set keyboard input grab");
+        self.imef.set_private_mode(grab.is_some()).expect("couldn't set IMEF private mode");
+    }
+
     pub(crate) fn key_event(
         &mut self,
         keys: [char; 4],
         gfx: &graphics_server::Gfx,
         canvases: &mut HashMap<Gid, Canvas>,
     ) {
+        // while the keyboard is grabbed, keys only go to the grabbing context. Grabs are only granted to,
+        // and released along with, the focused context, so this is a backstop.
+        if let Some(grab) = self.input_grab() {
+            if Some(grab.token) != self.focused_context {
+                log::debug!("keyboard is grabbed by a context without focus, dropping keys");
+                return;
+            }
+        }
         // only pop up the menu if the primary key hit is the menu key (search just the first entry of keys);
-        // reject multi-key hits only pop up the menu if it isn't already popped up
-        if keys[0] == '∴' {
+        // reject multi-key hits only pop up the menu if it isn't already popped up. A key hit on the
+        // screensaver only dismisses it, so it doesn't bring up the menu either.
+        if keys[0] == '∴'
+            && self.input_grab().is_none()
+            && self.focused_context != self.find_app_token_by_name(gam::SCREENSAVER_NAME)
+        {
             if let Some(context) = self.get_context_by_token(self.focused_context.unwrap()) {
                 if context.layout.behavior() == LayoutBehavior::App {
                     log::info!("allow_mainmenu: {:?}", self.allow_mainmenu);
@@ -766,9 +858,10 @@ impl ContextManager {
 
         // forward the keyboard hits to the current context
         log::debug!("forwarding raw key event");
+        let mut disconnected = None;
         if let Some(context) = self.focused_context() {
             if let Some(rawkeys_id) = context.rawkeys_id {
-                xous::send_message(
+                match xous::send_message(
                     context.listener,
                     xous::Message::new_scalar(
                         rawkeys_id as usize,
@@ -777,10 +870,18 @@ impl ContextManager {
                         keys[2] as u32 as usize,
                         keys[3] as u32 as usize,
                     ),
-                )
-                .expect("couldn't forward raw keys onto context listener");
+                ) {
+                    Ok(_) => (),
+                    // the context's process has gone away; it can't hold on to the keyboard anymore
+                    Err(xous::Error::ServerNotFound) => disconnected = Some(context.app_token),
+                    Err(e) => panic!("couldn't forward raw keys onto context listener: {:?}", e),
+                }
             }
         }
+        if let Some(token) = disconnected {
+            log::warn!("{:?} disconnected", self.tm.lookup_name(&token));
+            self.release_input_grab_held_by(token);
+        }
     }
 
     fn focused_context(&'_ self) -> Option<&'_ UxContext> {
@@ -804,6 +905,61 @@ impl ContextManager {
         }
     }
 
+    /// Adds an engine to the ones the user can pick from. Re-registering a server replaces its entry.
+    pub(crate) fn register_ime_engine(&mut self, registration: &ImeEngineRegistration) -> bool {
+        if !self.tm.is_token_valid(registration.token) {
+            log::warn!("IME engine registration with an invalid token, ignored: {:?}", registration.engine);
+            return false;
+        }
+        self.ime_engines.retain(|e| e.server != registration.engine.server);
+        if self.ime_engines.len() >= MAX_IME_ENGINES {
+            log::warn!("no room to register IME engine {:?}", registration.engine);
+            return false;
+        }
+        log::info!("registered IME engine {:?}", registration.engine);
+        self.ime_engines.push(registration.engine);
+        true
+    }
+
+    pub(crate) fn list_ime_engines(&self) -> ImeEngineList {
+        let mut list = ImeEngineList { engines: Default::default(), selected: None };
+        for (dest, engine) in list.engines.iter_mut().zip(self.ime_engines.iter()) {
+            *dest = Some(*engine);
+        }
+        list.selected = self.focused_context().and_then(|c| c.predictor);
+        list
+    }
+
+    /// Switches the focused context to the registered engine `server`, or back to the one the app
+    /// registered with if `None`. Only the focused context itself can ask for this, as identified by
+    /// `token`, and only contexts that take text input through the IMEF can switch.
+    pub(crate) fn select_ime_engine(&mut self, token: [u32; 4], server: Option<String<64>>) -> bool {
+        if self.focused_context != Some(token) {
+            log::warn!("IME engine selection from a context that isn't in focus, ignored");
+            return false;
+        }
+        if let Some(s) = &server {
+            if !self.ime_engines.iter().any(|e| e.server == *s) {
+                log::warn!("attempt to select unregistered IME engine {}", s);
+                return false;
+            }
+        }
+        let context = match self.focused_context_mut() {
+            Some(context) if context.default_predictor.is_some() => context,
+            _ => return false,
+        };
+        let predictor = server.or(context.default_predictor);
+        if predictor != context.predictor {
+            context.predictor = predictor;
+            // API tokens are handed out by the engine, so the old one means nothing to the new engine
+            context.pred_token = None;
+            let descriptor = imef_descriptor(context);
+            self.imef.connect_backend(descriptor).expect("couldn't connect IMEF to the new engine");
+            self.imef.redraw(true).expect("couldn't redraw IMEF");
+        }
+        true
+    }
+
     pub(crate) fn toggle_menu_mode(&mut self, token: [u32; 4]) {
         if let Some(context) = self.contexts.get_mut(&token) {
             context.imef_menu_mode = !context.imef_menu_mode;
@@ -834,4 +990,78 @@ impl ContextManager {
         }
         Err(xous::Error::ProcessNotFound)
     }
+
+    /// Puts the screensaver up over the focused context. It only ever covers an app: never a menu or a
+    /// modal the user may be in the middle of, nothing while the keyboard is grabbed, and nothing before
+    /// the boot PIN is in. Returns `true` if the screensaver went up.
+    pub(crate) fn raise_screensaver(
+        &mut self,
+        token: [u32; 4],
+        gfx: &graphics_server::Gfx,
+        canvases: &mut HashMap<Gid, Canvas>,
+    ) -> bool {
+        if self.find_app_token_by_name(gam::SCREENSAVER_NAME) != Some(token) {
+            log::warn!("screensaver raise requested with the wrong token, denied");
+            return false;
+        }
+        if !self.allow_mainmenu || self.input_grab().is_some() {
+            return false;
+        }
+        let covered = match self.focused_context {
+            Some(focused) if focused != token => focused,
+            _ => return false,
+        };
+        match self.get_context_by_token(covered) {
+            Some(context) if context.layout.behavior() == LayoutBehavior::App => {}
+            _ => return false,
+        }
+        if self.activate(gfx, canvases, token, true).is_err() {
+            return false;
+        }
+        self.screensaver_covered = Some(covered);
+        true
+    }
+
+    /// Takes the screensaver down, and gives focus back to the app it covered.
+    pub(crate) fn dismiss_screensaver(
+        &mut self,
+        token: [u32; 4],
+        gfx: &graphics_server::Gfx,
+        canvases: &mut HashMap<Gid, Canvas>,
+    ) -> Result<(), xous::Error> {
+        if self.find_app_token_by_name(gam::SCREENSAVER_NAME) != Some(token) {
+            return Err(xous::Error::AccessDenied);
+        }
+        // if something else took the focus from the screensaver in the meantime, leave it be
+        if self.focused_context != Some(token) {
+            return Ok(());
+        }
+        match self.screensaver_covered.take() {
+            Some(app) => self.activate(gfx, canvases, app, false),
+            None => self.revert_focus(gfx, canvases),
+        }
+    }
+}
+
+/// Describes how the IMEF should hook up to `context`: its input and prediction canvases, and the engine.
+fn imef_descriptor(context: &UxContext) -> ImefDescriptor {
+    ImefDescriptor {
+        input_canvas: if let Some(gr) =
+            context.layout.get_gids().iter().filter(|&gr| gr.canvas_type == CanvasType::ChatInput).next()
+        {
+            Some(gr.gid)
+        } else {
+            None
+        },
+        prediction_canvas: if let Some(gr) =
+            context.layout.get_gids().iter().filter(|&gr| gr.canvas_type == CanvasType::ChatPreditive).next()
+        {
+            Some(gr.gid)
+        } else {
+            None
+        },
+        predictor: context.predictor,
+        token: context.gam_token,
+        predictor_token: context.pred_token,
+    }
 }
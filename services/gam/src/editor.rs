@@ -0,0 +1,603 @@
+//! A multi-line text editor, for apps such as note-taking or config editing that show a page of text on
+//! their own canvas.
+//!
+//! [`TextEditor`] keeps the text in a [`GapBuffer`], wraps it at word boundaries to the width of the area
+//! it is drawn in, and handles the keys an app receives as raw keys: the arrows move the cursor, backspace
+//! deletes, enter starts a new line, and '∴' starts and ends a selection. Cut, copy, paste, undo and redo
+//! are methods, for the app to hang off its menu.
+//!
+//! The text is drawn in `GlyphStyle::Monospace`, so that where each character lands is known without
+//! asking the graphics server. Characters that fall back to the CJK and emoji fonts are counted as two
+//! cells wide, which is close but not exact.
+//!
+//! ```ignore
+//! let mut editor = TextEditor::new(&note);
+//! // for each raw key
+//! if editor.key(key) {
+//!     let mut frame = gam.offscreen(canvas)?;
+//!     editor.draw(&mut frame, area)?;
+//!     frame.present()?;
+//! }
+//! ```
+
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+use graphics_server::api::{DrawStyle, TextBounds};
+
+use crate::{GlyphStyle, Line, Offscreen, PixelColor, Point, Rectangle, TextView};
+
+/// The horizontal pitch of `GlyphStyle::Monospace`: 7 pixels of glyph and 1 of kerning
+const CELL_WIDTH: i16 = 8;
+/// The height of `GlyphStyle::Monospace`, and a pixel between lines
+const LINE_HEIGHT: i16 = 16;
+/// Space left clear inside the area the editor is drawn in
+const MARGIN: i16 = 4;
+/// The number of changes that can be undone; older ones are forgotten
+const UNDO_DEPTH: usize = 100;
+
+/// Text kept with a gap at the last place it was edited, so that typing and deleting there costs the same
+/// however long the text is. Positions count `char`s, not bytes.
+#[derive(Debug, Clone, Default)]
+pub struct GapBuffer {
+    chars: Vec<char>,
+    /// the unused part of `chars`
+    gap: Range<usize>,
+}
+
+impl GapBuffer {
+    pub fn new() -> Self { GapBuffer { chars: Vec::new(), gap: 0..0 } }
+
+    pub fn len(&self) -> usize { self.chars.len() - self.gap.len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn char_at(&self, at: usize) -> Option<char> {
+        if at < self.gap.start { Some(self.chars[at]) } else { self.chars.get(at + self.gap.len()).copied() }
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.chars[..self.gap.start].iter().chain(self.chars[self.gap.end..].iter()).copied()
+    }
+
+    /// Inserts `text` before the char at `at`, or at the end if `at` is past it
+    pub fn insert(&mut self, at: usize, text: &str) {
+        let count = text.chars().count();
+        self.move_gap(at.min(self.len()));
+        if self.gap.len() < count {
+            self.grow(count);
+        }
+        for (slot, c) in self.chars[self.gap.start..].iter_mut().zip(text.chars()) {
+            *slot = c;
+        }
+        self.gap.start += count;
+    }
+
+    /// Removes the chars in `range`, and returns them
+    pub fn remove(&mut self, range: Range<usize>) -> String {
+        let end = range.end.min(self.len());
+        let start = range.start.min(end);
+        self.move_gap(start);
+        let removed = self.chars[self.gap.end..self.gap.end + end - start].iter().collect();
+        self.gap.end += end - start;
+        removed
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> String {
+        let end = range.end.min(self.len());
+        let start = range.start.min(end);
+        self.chars().skip(start).take(end - start).collect()
+    }
+
+    fn move_gap(&mut self, to: usize) {
+        if to < self.gap.start {
+            let moved = self.gap.start - to;
+            self.chars.copy_within(to..self.gap.start, self.gap.end - moved);
+            self.gap = to..self.gap.end - moved;
+        } else if to > self.gap.start {
+            let moved = to - self.gap.start;
+            self.chars.copy_within(self.gap.end..self.gap.end + moved, self.gap.start);
+            self.gap = to..self.gap.end + moved;
+        }
+    }
+
+    /// Makes the gap at least `at_least` long; the buffer grows by half again, so that long runs of
+    /// typing don't copy the text each time
+    fn grow(&mut self, at_least: usize) {
+        let extra = at_least.max(self.chars.len() / 2).max(64);
+        let tail = self.chars.len() - self.gap.end;
+        self.chars.resize(self.chars.len() + extra, '\0');
+        let len = self.chars.len();
+        self.chars.copy_within(self.gap.end..self.gap.end + tail, len - tail);
+        self.gap.end = len - tail;
+    }
+}
+
+impl From<&str> for GapBuffer {
+    fn from(text: &str) -> Self {
+        let mut buffer = GapBuffer::new();
+        buffer.insert(0, text);
+        buffer
+    }
+}
+
+impl fmt::Display for GapBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.chars() {
+            f.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+/// One change, as undo needs it: `removed` was replaced by `inserted`, at `at`
+#[derive(Debug, Clone)]
+struct Change {
+    at: usize,
+    removed: String,
+    inserted: String,
+    /// where the cursor was before the change
+    cursor: usize,
+}
+
+/// The state of a multi-line editor: the text, the cursor and selection, and the undo history. See the
+/// module docs for how an app drives it.
+#[derive(Debug)]
+pub struct TextEditor {
+    text: GapBuffer,
+    cursor: usize,
+    /// the end of the selection that stays put while the cursor moves, if a selection is being made
+    anchor: Option<usize>,
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+    /// set while keys are typed or deleted in a row, so that they are undone a word at a time
+    joining: bool,
+    /// the column the cursor keeps to as it moves up and down past shorter lines
+    goal: Option<usize>,
+    /// the width the text is wrapped to, in cells, as of the last `draw()`
+    columns: usize,
+    /// the first line shown
+    top: usize,
+    modified: bool,
+}
+
+impl TextEditor {
+    pub fn new(text: &str) -> Self {
+        TextEditor {
+            text: GapBuffer::from(text),
+            cursor: 0,
+            anchor: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            joining: false,
+            goal: None,
+            columns: 40,
+            top: 0,
+            modified: false,
+        }
+    }
+
+    pub fn text(&self) -> String { self.text.to_string() }
+
+    /// Replaces all of the text, and forgets the undo history
+    pub fn set_text(&mut self, text: &str) {
+        *self = TextEditor { columns: self.columns, ..TextEditor::new(text) }
+    }
+
+    /// Set once the text is changed, until `set_unmodified()`; for apps to know whether to save
+    pub fn is_modified(&self) -> bool { self.modified }
+
+    pub fn set_unmodified(&mut self) { self.modified = false; }
+
+    /// The position of the cursor, in chars from the start of the text
+    pub fn cursor(&self) -> usize { self.cursor }
+
+    pub fn set_cursor(&mut self, at: usize) {
+        self.cursor = at.min(self.text.len());
+        self.goal = None;
+        self.joining = false;
+    }
+
+    /// The chars selected, if a selection is being made
+    pub fn selection(&self) -> Option<Range<usize>> {
+        self.anchor.map(|anchor| anchor.min(self.cursor)..anchor.max(self.cursor))
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        self.selection().filter(|s| !s.is_empty()).map(|s| self.text.slice(s))
+    }
+
+    /// Starts a selection at the cursor, or drops the one being made. The cursor keys then move one end of
+    /// the selection.
+    pub fn toggle_selection(&mut self) {
+        self.anchor = match self.anchor {
+            Some(_) => None,
+            None => Some(self.cursor),
+        };
+        self.joining = false;
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.text.len();
+        self.joining = false;
+    }
+
+    /// Puts `text` in place of the selection, or at the cursor if nothing is selected; this is paste
+    pub fn insert(&mut self, text: &str) {
+        let range = self.selection().unwrap_or(self.cursor..self.cursor);
+        self.replace(range, text, false);
+    }
+
+    /// Removes the selection, and returns it
+    pub fn cut(&mut self) -> Option<String> {
+        let text = self.selected_text()?;
+        self.insert("");
+        Some(text)
+    }
+
+    /// Undoes the last change, and returns `false` if there was none
+    pub fn undo(&mut self) -> bool {
+        let change = match self.undo.pop() {
+            Some(change) => change,
+            None => return false,
+        };
+        self.text.remove(change.at..change.at + change.inserted.chars().count());
+        self.text.insert(change.at, &change.removed);
+        self.after_history(change.cursor);
+        self.redo.push(change);
+        true
+    }
+
+    /// Makes the last undone change again, and returns `false` if there was none
+    pub fn redo(&mut self) -> bool {
+        let change = match self.redo.pop() {
+            Some(change) => change,
+            None => return false,
+        };
+        self.text.remove(change.at..change.at + change.removed.chars().count());
+        self.text.insert(change.at, &change.inserted);
+        self.after_history(change.at + change.inserted.chars().count());
+        self.undo.push(change);
+        true
+    }
+
+    /// Acts on a raw key, and returns `true` if the editor needs drawing again
+    pub fn key(&mut self, key: char) -> bool {
+        match key {
+            '←' => self.move_to(self.cursor.saturating_sub(1)),
+            '→' => self.move_to((self.cursor + 1).min(self.text.len())),
+            '↑' => self.move_line(false),
+            '↓' => self.move_line(true),
+            '∴' => self.toggle_selection(),
+            '\u{8}' => self.backspace(),
+            '\u{d}' | '\n' => self.type_char('\n'),
+            c if c.is_control() => return false,
+            c => self.type_char(c),
+        }
+        true
+    }
+
+    /// Draws the text into `area` of the frame's canvas, scrolled so that the cursor shows. The text is
+    /// wrapped to the width of `area`.
+    pub fn draw(&mut self, frame: &mut Offscreen, area: Rectangle) -> Result<(), xous::Error> {
+        let origin = Point::new(area.tl.x + MARGIN, area.tl.y + MARGIN);
+        self.columns = ((area.br.x - origin.x - MARGIN) / CELL_WIDTH).max(1) as usize;
+        let rows = ((area.br.y - origin.y - MARGIN) / LINE_HEIGHT).max(1) as usize;
+        let chars: Vec<char> = self.text.chars().collect();
+        let lines = wrap(&chars, self.columns);
+        let row = line_of(&lines, self.cursor);
+        // scrolls just far enough to bring the cursor into view
+        if row < self.top {
+            self.top = row;
+        } else if row >= self.top + rows {
+            self.top = row + 1 - rows;
+        }
+
+        let light = DrawStyle::new(PixelColor::Light, PixelColor::Light, 1);
+        let dark = DrawStyle::new(PixelColor::Dark, PixelColor::Dark, 1);
+        frame.draw_rectangle(Rectangle::new_with_style(area.tl, area.br, light))?;
+        let x_of = |line: &Range<usize>, at: usize| {
+            origin.x + cells(&chars[line.start..at.clamp(line.start, line.end)]) as i16 * CELL_WIDTH
+        };
+        let selection = self.selection();
+        for (i, line) in lines.iter().enumerate().skip(self.top).take(rows) {
+            let y = origin.y + (i - self.top) as i16 * LINE_HEIGHT;
+            let text: String = chars[line.clone()].iter().map(|&c| if c == '\t' { ' ' } else { c }).collect();
+            // the space a line is wrapped after can hang past the edge
+            let text = text.trim_end();
+            if !text.is_empty() {
+                let mut tv = TextView::new(
+                    frame.gid(),
+                    TextBounds::BoundingBox(Rectangle::new(
+                        Point::new(origin.x, y),
+                        Point::new(area.br.x, y + LINE_HEIGHT),
+                    )),
+                );
+                tv.style = GlyphStyle::Monospace;
+                tv.margin = Point::new(0, 0);
+                tv.draw_border = false;
+                tv.clear_area = false;
+                write!(tv, "{}", text).ok();
+                frame.post_textview(&mut tv)?;
+            }
+            if let Some(selected) = selection.as_ref() {
+                let (start, end) = (selected.start.max(line.start), selected.end.min(line.end));
+                if start < end {
+                    let underline = y + LINE_HEIGHT - 1;
+                    frame.draw_line(Line::new_with_style(
+                        Point::new(x_of(line, start), underline),
+                        Point::new(x_of(line, end) - 1, underline),
+                        dark,
+                    ))?;
+                }
+            }
+            if i == row {
+                // in the kerning pixel before the char the cursor is at
+                let x = (x_of(line, self.cursor) - 1).max(area.tl.x);
+                frame.draw_line(Line::new_with_style(
+                    Point::new(x, y),
+                    Point::new(x, y + LINE_HEIGHT - 2),
+                    dark,
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn move_to(&mut self, at: usize) {
+        self.cursor = at;
+        self.goal = None;
+        self.joining = false;
+    }
+
+    /// Moves the cursor to the line below or above, as close as it can to the column it keeps to
+    fn move_line(&mut self, down: bool) {
+        let chars: Vec<char> = self.text.chars().collect();
+        let lines = wrap(&chars, self.columns);
+        let row = line_of(&lines, self.cursor);
+        let goal = self.goal.unwrap_or_else(|| cells(&chars[lines[row].start..self.cursor]));
+        let target = match (down, row) {
+            (true, row) if row + 1 < lines.len() => row + 1,
+            (false, row) if row > 0 => row - 1,
+            // past the first or last line, to the start or end of the text
+            (true, _) => return self.move_to(chars.len()),
+            (false, _) => return self.move_to(0),
+        };
+        let line = &lines[target];
+        // the last char of a wrapped line is where the next line starts, so the cursor stops before it
+        let soft = target + 1 < lines.len() && lines[target + 1].start == line.end;
+        let last = if soft { line.end - 1 } else { line.end };
+        let mut at = line.start;
+        let mut width = 0;
+        while at < last && width + cells(&chars[at..at + 1]) <= goal {
+            width += cells(&chars[at..at + 1]);
+            at += 1;
+        }
+        self.move_to(at);
+        self.goal = Some(goal);
+    }
+
+    fn type_char(&mut self, c: char) {
+        let range = self.selection().unwrap_or(self.cursor..self.cursor);
+        // a new undo step starts at each word
+        let after_word = self
+            .undo
+            .last()
+            .and_then(|change| change.inserted.chars().last())
+            .map_or(false, |before| !before.is_whitespace());
+        let join = self.joining && range.is_empty() && !(c.is_whitespace() && after_word);
+        self.replace(range, c.encode_utf8(&mut [0; 4]), join);
+        self.joining = true;
+    }
+
+    fn backspace(&mut self) {
+        match self.selection().filter(|s| !s.is_empty()) {
+            Some(selected) => self.replace(selected, "", false),
+            None if self.cursor > 0 => {
+                let join = self.joining;
+                self.replace(self.cursor - 1..self.cursor, "", join);
+                self.joining = true;
+            }
+            None => self.anchor = None,
+        }
+    }
+
+    /// Puts `text` in place of the chars in `range`, and records the change for undo. With `join`, a
+    /// change that carries on from the last one is added to it rather than recorded apart.
+    fn replace(&mut self, range: Range<usize>, text: &str, join: bool) {
+        let removed = self.text.remove(range.clone());
+        self.text.insert(range.start, text);
+        let joined = match self.undo.last_mut() {
+            // typing on from the last char typed
+            Some(last)
+                if join
+                    && removed.is_empty()
+                    && last.removed.is_empty()
+                    && last.at + last.inserted.chars().count() == range.start =>
+            {
+                last.inserted.push_str(text);
+                true
+            }
+            // deleting back from the last char deleted
+            Some(last) if join && text.is_empty() && last.inserted.is_empty() && range.end == last.at => {
+                last.removed.insert_str(0, &removed);
+                last.at = range.start;
+                true
+            }
+            _ => false,
+        };
+        if !joined {
+            self.undo.push(Change {
+                at: range.start,
+                removed,
+                inserted: text.to_string(),
+                cursor: self.cursor,
+            });
+            if self.undo.len() > UNDO_DEPTH {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+        self.cursor = range.start + text.chars().count();
+        self.anchor = None;
+        self.goal = None;
+        self.joining = false;
+        self.modified = true;
+    }
+
+    fn after_history(&mut self, cursor: usize) {
+        self.cursor = cursor.min(self.text.len());
+        self.anchor = None;
+        self.goal = None;
+        self.joining = false;
+        self.modified = true;
+    }
+}
+
+/// The width of `chars` in cells of the monospace font
+fn cells(chars: &[char]) -> usize { chars.iter().map(|&c| if (c as u32) < 0x1100 { 1 } else { 2 }).sum() }
+
+/// Breaks `chars` into the ranges shown on each line, no more than `columns` cells wide. Lines break
+/// after a space where they can, and inside a word that is longer than a line. The '\n' that ends a line
+/// is in neither range, and a space a line breaks after may hang past the edge.
+fn wrap(chars: &[char], columns: usize) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut width = 0;
+    // just after the last space on the line
+    let mut after_space = None;
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '\n' {
+            lines.push(start..i);
+            start = i + 1;
+            width = 0;
+            after_space = None;
+            continue;
+        }
+        let w = cells(&chars[i..i + 1]);
+        if width + w > columns && c != ' ' && i > start {
+            let at = after_space.unwrap_or(i);
+            lines.push(start..at);
+            start = at;
+            width = cells(&chars[start..i]);
+            after_space = None;
+        }
+        width += w;
+        if c == ' ' {
+            after_space = Some(i + 1);
+        }
+    }
+    lines.push(start..chars.len());
+    lines
+}
+
+/// The line the char at `at` is on; a position where a line wraps is at the start of the next line
+fn line_of(lines: &[Range<usize>], at: usize) -> usize {
+    lines.iter().rposition(|line| line.start <= at).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str, columns: usize) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        wrap(&chars, columns).into_iter().map(|r| chars[r].iter().collect()).collect()
+    }
+
+    #[test]
+    fn gap_buffer_edits() {
+        let mut buffer = GapBuffer::from("hello world");
+        buffer.insert(5, ",");
+        buffer.insert(0, "¡");
+        buffer.insert(100, "!");
+        assert_eq!(buffer.to_string(), "¡hello, world!");
+        assert_eq!(buffer.remove(1..7), "hello,");
+        assert_eq!(buffer.to_string(), "¡ world!");
+        assert_eq!(buffer.slice(2..7), "world");
+        assert_eq!(buffer.char_at(0), Some('¡'));
+        assert_eq!(buffer.char_at(8), None);
+        let long = "x".repeat(500);
+        buffer.insert(2, &long);
+        assert_eq!(buffer.len(), 508);
+        assert_eq!(buffer.slice(502..508), "world!");
+    }
+
+    #[test]
+    fn wraps_at_words() {
+        assert_eq!(lines("the quick brown fox", 10), vec!["the quick ", "brown fox"]);
+        assert_eq!(lines("abcdefghijkl mn", 5), vec!["abcde", "fghij", "kl mn"]);
+        assert_eq!(lines("one\n\ntwo\n", 10), vec!["one", "", "two", ""]);
+        // the space the line breaks after hangs past the edge
+        assert_eq!(lines("12345 6789", 5), vec!["12345 ", "6789"]);
+        assert_eq!(lines("日本語の文", 4), vec!["日本", "語の", "文"]);
+    }
+
+    #[test]
+    fn cursor_moves_between_lines() {
+        let mut editor = TextEditor::new("the quick brown fox\nhi\nlast line");
+        editor.columns = 10;
+        editor.set_cursor(7); // "the qui|ck "
+        editor.key('↓');
+        assert_eq!(editor.cursor(), 10 + 7); // "brown f|ox"
+        editor.key('↓');
+        assert_eq!(editor.cursor(), 22); // the end of "hi"
+        editor.key('↓');
+        assert_eq!(editor.cursor(), 23 + 7); // back to the column it keeps to
+        editor.key('↓');
+        assert_eq!(editor.cursor(), editor.text().chars().count());
+        editor.set_cursor(9); // the space a line wraps after
+        editor.key('↑');
+        assert_eq!(editor.cursor(), 0);
+        editor.set_cursor(19);
+        editor.key('↑');
+        assert_eq!(editor.cursor(), 9);
+    }
+
+    #[test]
+    fn undo_a_word_at_a_time() {
+        let mut editor = TextEditor::new("");
+        for c in "hello big world".chars() {
+            editor.key(c);
+        }
+        editor.key('\u{8}');
+        editor.key('\u{8}');
+        assert_eq!(editor.text(), "hello big wor");
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "hello big world");
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "hello big");
+        assert!(editor.undo());
+        assert_eq!(editor.text(), "hello");
+        assert!(editor.redo());
+        assert_eq!(editor.text(), "hello big");
+        assert_eq!(editor.cursor(), 9);
+        editor.key('!');
+        assert!(!editor.redo());
+        assert!(editor.is_modified());
+    }
+
+    #[test]
+    fn selection() {
+        let mut editor = TextEditor::new("copy this text");
+        editor.set_cursor(5);
+        editor.key('∴');
+        for _ in 0..4 {
+            editor.key('→');
+        }
+        assert_eq!(editor.selected_text().as_deref(), Some("this"));
+        assert_eq!(editor.cut().as_deref(), Some("this"));
+        assert_eq!(editor.text(), "copy  text");
+        editor.insert("that");
+        assert_eq!(editor.text(), "copy that text");
+        editor.select_all();
+        editor.key('x');
+        assert_eq!(editor.text(), "x");
+        editor.undo();
+        assert_eq!(editor.text(), "copy that text");
+        editor.undo();
+        editor.undo();
+        assert_eq!(editor.text(), "copy this text");
+    }
+}
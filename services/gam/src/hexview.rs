@@ -0,0 +1,249 @@
+//! A paged hex view of a blob, for apps that need to show the raw bytes of a record, such as a stored
+//! trust anchor, on their own canvas.
+//!
+//! [`HexView`] lays the bytes out eight to a row, as an offset, the bytes in hex, and the bytes as ASCII,
+//! and handles the keys an app receives as raw keys: the arrows move the cursor, a byte or a row at a time.
+//! If the view is made editable, hex digits overwrite the byte at the cursor, a nibble at a time. The view
+//! only changes its own copy of the bytes; writing them back, after asking the user, is up to the app.
+//!
+//! ```ignore
+//! let mut view = HexView::new(&record, 0);
+//! // for each raw key
+//! if view.key(key) {
+//!     let mut frame = gam.offscreen(canvas)?;
+//!     view.draw(&mut frame, area)?;
+//!     frame.present()?;
+//! }
+//! ```
+
+use std::fmt::Write;
+
+use graphics_server::api::{DrawStyle, TextBounds};
+
+use crate::{GlyphStyle, Line, Offscreen, PixelColor, Point, Rectangle, TextView};
+
+/// The number of bytes shown in a row: as many as fit across the screen in `GlyphStyle::Monospace`
+pub const HEX_BYTES_PER_ROW: usize = 8;
+/// The horizontal pitch of `GlyphStyle::Monospace`: 7 pixels of glyph and 1 of kerning
+const CELL_WIDTH: i16 = 8;
+/// The height of `GlyphStyle::Monospace`, and a pixel between lines
+const LINE_HEIGHT: i16 = 16;
+/// Space left clear inside the area the view is drawn in
+const MARGIN: i16 = 4;
+/// The cells before the first byte of a row: the offset, a colon and a space
+const OFFSET_CELLS: usize = 6;
+
+/// Formats one row of a hex dump: the offset of `bytes`, up to `HEX_BYTES_PER_ROW` of them in hex, and
+/// then as ASCII, with a '.' for anything that doesn't print
+pub fn hex_row(offset: u64, bytes: &[u8]) -> String {
+    let mut row = String::new();
+    write!(row, "{:04x}:", offset).unwrap();
+    for b in bytes.iter().take(HEX_BYTES_PER_ROW) {
+        write!(row, " {:02x}", b).unwrap();
+    }
+    for _ in bytes.len().min(HEX_BYTES_PER_ROW)..HEX_BYTES_PER_ROW {
+        row.push_str("   ");
+    }
+    row.push_str("  ");
+    for &b in bytes.iter().take(HEX_BYTES_PER_ROW) {
+        row.push(if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' });
+    }
+    row
+}
+
+pub struct HexView {
+    data: Vec<u8>,
+    /// the offset in the blob of the first byte of `data`, for when only part of it was read
+    base: u64,
+    cursor: usize,
+    /// the first row shown
+    top: usize,
+    /// the number of rows that fitted in the last `draw()`, which is what a page is
+    rows: usize,
+    editable: bool,
+    /// set when the high nibble of the byte at the cursor has been typed, and the low one is next
+    low_nibble: bool,
+    modified: bool,
+}
+
+impl HexView {
+    pub fn new(data: &[u8], base: u64) -> Self {
+        HexView {
+            data: data.to_vec(),
+            base,
+            cursor: 0,
+            top: 0,
+            rows: 1,
+            editable: false,
+            low_nibble: false,
+            modified: false,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] { &self.data }
+
+    pub fn base(&self) -> u64 { self.base }
+
+    /// Lets hex digits overwrite the bytes. The view is read-only until this is called.
+    pub fn set_editable(&mut self, editable: bool) {
+        self.editable = editable;
+        self.low_nibble = false;
+    }
+
+    pub fn is_editable(&self) -> bool { self.editable }
+
+    /// Whether any byte has been typed over since the view was made, or since `set_unmodified()`
+    pub fn is_modified(&self) -> bool { self.modified }
+
+    /// Marks the bytes as saved
+    pub fn set_unmodified(&mut self) { self.modified = false; }
+
+    /// The index in `data()` of the byte at the cursor
+    pub fn cursor(&self) -> usize { self.cursor }
+
+    pub fn set_cursor(&mut self, at: usize) { self.move_to(at); }
+
+    /// Moves a page of rows down, or up if `forward` is `false`, for the app to hang off its menu
+    pub fn page(&mut self, forward: bool) {
+        let step = self.rows * HEX_BYTES_PER_ROW;
+        if forward {
+            self.move_to(self.cursor.saturating_add(step));
+            self.top += self.rows;
+        } else {
+            self.move_to(self.cursor.saturating_sub(step));
+            self.top = self.top.saturating_sub(self.rows);
+        }
+    }
+
+    /// Handles a raw key. Returns `true` if the view needs to be drawn again.
+    pub fn key(&mut self, key: char) -> bool {
+        match key {
+            '←' => self.move_to(self.cursor.saturating_sub(1)),
+            '→' => self.move_to(self.cursor + 1),
+            '↑' => self.move_to(self.cursor.saturating_sub(HEX_BYTES_PER_ROW)),
+            '↓' => {
+                if self.cursor + HEX_BYTES_PER_ROW < self.data.len() {
+                    self.move_to(self.cursor + HEX_BYTES_PER_ROW)
+                }
+            }
+            _ => match key.to_digit(16) {
+                Some(digit) if self.editable && !self.data.is_empty() => self.type_nibble(digit as u8),
+                _ => return false,
+            },
+        }
+        true
+    }
+
+    pub fn draw(&mut self, frame: &mut Offscreen, area: Rectangle) -> Result<(), xous::Error> {
+        let origin = Point::new(area.tl.x + MARGIN, area.tl.y + MARGIN);
+        self.rows = ((area.br.y - origin.y - MARGIN) / LINE_HEIGHT).max(1) as usize;
+        let total = self.data.len().div_ceil(HEX_BYTES_PER_ROW);
+        let row = self.cursor / HEX_BYTES_PER_ROW;
+        // keeps the cursor in view, and doesn't scroll past the last row
+        if row < self.top {
+            self.top = row;
+        } else if row >= self.top + self.rows {
+            self.top = row + 1 - self.rows;
+        }
+        self.top = self.top.min(total.saturating_sub(self.rows));
+
+        let light = DrawStyle::new(PixelColor::Light, PixelColor::Light, 1);
+        let dark = DrawStyle::new(PixelColor::Dark, PixelColor::Dark, 1);
+        frame.draw_rectangle(Rectangle::new_with_style(area.tl, area.br, light))?;
+        for (i, bytes) in self.data.chunks(HEX_BYTES_PER_ROW).enumerate().skip(self.top).take(self.rows) {
+            let y = origin.y + (i - self.top) as i16 * LINE_HEIGHT;
+            let mut tv = TextView::new(
+                frame.gid(),
+                TextBounds::BoundingBox(Rectangle::new(
+                    Point::new(origin.x, y),
+                    Point::new(area.br.x, y + LINE_HEIGHT),
+                )),
+            );
+            tv.style = GlyphStyle::Monospace;
+            tv.margin = Point::new(0, 0);
+            tv.draw_border = false;
+            tv.clear_area = false;
+            write!(tv, "{}", hex_row(self.base + (i * HEX_BYTES_PER_ROW) as u64, bytes)).ok();
+            frame.post_textview(&mut tv)?;
+        }
+        if !self.data.is_empty() {
+            // underlines the byte at the cursor, or just the nibble that is next when editing
+            let column = self.cursor % HEX_BYTES_PER_ROW;
+            let mut start = OFFSET_CELLS + column * 3;
+            let mut width: i16 = 2;
+            if self.editable {
+                start += self.low_nibble as usize;
+                width = 1;
+            }
+            let x = origin.x + start as i16 * CELL_WIDTH;
+            let underline = origin.y + (row - self.top) as i16 * LINE_HEIGHT + LINE_HEIGHT - 1;
+            frame.draw_line(Line::new_with_style(
+                Point::new(x, underline),
+                Point::new(x + width * CELL_WIDTH - 2, underline),
+                dark,
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn move_to(&mut self, at: usize) {
+        self.cursor = at.min(self.data.len().saturating_sub(1));
+        self.low_nibble = false;
+    }
+
+    fn type_nibble(&mut self, digit: u8) {
+        let byte = &mut self.data[self.cursor];
+        if self.low_nibble {
+            *byte = (*byte & 0xf0) | digit;
+            self.move_to(self.cursor + 1);
+        } else {
+            *byte = (*byte & 0x0f) | (digit << 4);
+            self.low_nibble = true;
+        }
+        self.modified = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_layout() {
+        assert_eq!(hex_row(0x10, b"AB\x00\x7f"), "0010: 41 42 00 7f              AB..");
+        assert_eq!(hex_row(0, b"01234567"), "0000: 30 31 32 33 34 35 36 37  01234567");
+        // the first byte sits where `draw()` underlines it
+        assert_eq!(&hex_row(0, b"\xab")[OFFSET_CELLS..OFFSET_CELLS + 2], "ab");
+    }
+
+    #[test]
+    fn cursor_stays_in_data() {
+        let mut view = HexView::new(&[0; 10], 0);
+        view.key('↓');
+        assert_eq!(view.cursor(), 8);
+        view.key('↓');
+        assert_eq!(view.cursor(), 8);
+        view.key('→');
+        view.key('→');
+        assert_eq!(view.cursor(), 9);
+        view.key('↑');
+        assert_eq!(view.cursor(), 1);
+    }
+
+    #[test]
+    fn edit_by_nibble() {
+        let mut view = HexView::new(&[0x12, 0x34], 0);
+        assert!(!view.key('a'));
+        view.set_editable(true);
+        view.key('a');
+        view.key('B');
+        view.key('c');
+        assert_eq!(view.data(), &[0xab, 0xc4]);
+        assert_eq!(view.cursor(), 1);
+        assert!(view.is_modified());
+        // the last byte takes its low nibble without the cursor running off the end
+        view.key('d');
+        assert_eq!(view.data(), &[0xab, 0xcd]);
+        assert_eq!(view.cursor(), 1);
+    }
+}
@@ -10,6 +10,14 @@ pub mod menu;
 pub use menu::*;
 pub mod apps;
 pub use apps::*;
+pub mod offscreen;
+pub use offscreen::*;
+pub mod scroll;
+pub use scroll::*;
+pub mod editor;
+pub use editor::*;
+pub mod hexview;
+pub use hexview::*;
 #[cfg(feature = "ditherpunk")]
 pub mod bitmap;
 use api::Opcode; // if you prefer to map the api into your local namespace
@@ -44,6 +52,8 @@ pub const APP_NAME_SHELLCHAT: &'static str = "shellchat";
 pub const APP_MENU_NAME: &'static str = "app menu";
 pub const WIFI_MENU_NAME: &'static str = "WLAN menu";
 pub const PREFERENCES_MENU_NAME: &'static str = "Preferences menu";
+/// Only registered by images built with the screensaver service, see the `screensaver` feature.
+pub const SCREENSAVER_NAME: &'static str = "screensaver";
 
 /// UX context registry. Names here are authorized by the GAM to have Canvases.
 #[cfg(not(feature = "cramium-soc"))]
@@ -59,6 +69,8 @@ pub const EXPECTED_BOOT_CONTEXTS: &[&'static str] = &[
     APP_MENU_NAME,
     WIFI_MENU_NAME,
     PREFERENCES_MENU_NAME,
+    #[cfg(feature = "screensaver")]
+    SCREENSAVER_NAME,
 ];
 #[cfg(feature = "cramium-soc")]
 pub const EXPECTED_BOOT_CONTEXTS: &[&'static str] = &[
@@ -71,6 +83,8 @@ pub const EXPECTED_BOOT_CONTEXTS: &[&'static str] = &[
     PDDB_MODAL_NAME,
     PDDB_MENU_NAME,
     ROOTKEY_MODAL_NAME,
+    #[cfg(feature = "screensaver")]
+    SCREENSAVER_NAME,
 ];
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -112,6 +126,12 @@ impl Gam {
             .map(|_| ())
     }
 
+    /// Opens an offscreen frame on `gid`. Everything drawn through the returned [`Offscreen`] is held back
+    /// from the LCD until [`Offscreen::present`] is called, so a clear-and-redraw of a complex screen shows
+    /// up in one step instead of flickering through its intermediate states. `gid` must be the content
+    /// canvas of the app in focus, or the frame doesn't hold anything back.
+    pub fn offscreen(&self, gid: Gid) -> Result<Offscreen<'_>, xous::Error> { Offscreen::begin(self, gid) }
+
     /// Inform the GAM that the main menu can be activated. This blocks until the message has been delivered
     /// to the GAM.
     pub fn allow_mainmenu(&self) -> Result<(), xous::Error> {
@@ -475,6 +495,118 @@ impl Gam {
 
     pub fn raise_modal(&self, modal_name: &str) -> Result<(), xous::Error> { self.raise_menu(modal_name) }
 
+    /// Requests that keyboard input go only to the context holding `token`, for example while a password
+    /// is entered. The context must have focus. Lower-trust listeners stop seeing keystrokes until the grab
+    /// is released, either with `release_input_grab()` or when the context loses focus or goes away.
+    /// What is typed under a grab isn't learned by the input method's predictor. Returns `false` if the
+    /// context doesn't have focus, or a context with a higher trust level holds a grab.
+    pub fn request_input_grab(&self, token: [u32; 4], policy: InputGrabPolicy) -> Result<bool, xous::Error> {
+        let request = InputGrabRequest { token, policy, granted: false };
+        let mut buf = Buffer::into_buf(request).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::RequestInputGrab.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let result = buf.to_original::<InputGrabRequest, _>().or(Err(xous::Error::InternalError))?;
+        Ok(result.granted)
+    }
+
+    pub fn release_input_grab(&self, token: [u32; 4]) -> Result<(), xous::Error> {
+        send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                Opcode::ReleaseInputGrab.to_usize().unwrap(),
+                token[0] as usize,
+                token[1] as usize,
+                token[2] as usize,
+                token[3] as usize,
+            ),
+        )
+        .map(|_| ())
+    }
+
+    /// Puts the screensaver context holding `token` up over the focused app. The GAM refuses if a menu
+    /// or a modal has the focus, if the keyboard is grabbed, or before the boot PIN is in; returns
+    /// whether the screensaver went up.
+    pub fn raise_screensaver(&self, token: [u32; 4]) -> Result<bool, xous::Error> {
+        let response = send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                Opcode::RaiseScreensaver.to_usize().unwrap(),
+                token[0] as usize,
+                token[1] as usize,
+                token[2] as usize,
+                token[3] as usize,
+            ),
+        )?;
+        if let xous::Result::Scalar1(raised) = response {
+            Ok(raised != 0)
+        } else {
+            Err(xous::Error::InternalError)
+        }
+    }
+
+    /// Takes the screensaver down, and gives the focus back to the app it covered.
+    pub fn dismiss_screensaver(&self, token: [u32; 4]) -> Result<(), xous::Error> {
+        let response = send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                Opcode::DismissScreensaver.to_usize().unwrap(),
+                token[0] as usize,
+                token[1] as usize,
+                token[2] as usize,
+                token[3] as usize,
+            ),
+        )?;
+        match response {
+            xous::Result::Scalar1(0) => Ok(()),
+            _ => Err(xous::Error::AccessDenied),
+        }
+    }
+
+    /// Offers the input method engine served under `server` (e.g. T9-style prediction, an emoji picker,
+    /// Pinyin) for the user to switch to. `token` is the app token of the registering context. Returns
+    /// `false` if the GAM refused the registration.
+    pub fn register_ime_engine(
+        &self,
+        token: [u32; 4],
+        server: &str,
+        label: &str,
+    ) -> Result<bool, xous::Error> {
+        let registration = ImeEngineRegistration {
+            token,
+            engine: ImeEngine { server: String::from_str(server), label: String::from_str(label) },
+            accepted: false,
+        };
+        let mut buf = Buffer::into_buf(registration).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::RegisterImeEngine.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let result = buf.to_original::<ImeEngineRegistration, _>().or(Err(xous::Error::InternalError))?;
+        Ok(result.accepted)
+    }
+
+    /// Returns the registered input method engines, and the server name of the one the focused context
+    /// is using.
+    pub fn list_ime_engines(&self) -> Result<(Vec<ImeEngine>, Option<String<64>>), xous::Error> {
+        let list = ImeEngineList { engines: Default::default(), selected: None };
+        let mut buf = Buffer::into_buf(list).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::ListImeEngines.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let list = buf.to_original::<ImeEngineList, _>().or(Err(xous::Error::InternalError))?;
+        Ok((list.engines.iter().filter_map(|e| *e).collect(), list.selected))
+    }
+
+    /// Switches the focused context to the registered input method engine `server`, or back to the engine
+    /// the app registered with if `None`. `token` is the app token of the caller, which must be the
+    /// context in focus. Returns `false` if the engine isn't registered, the caller isn't in focus, or the
+    /// focused context doesn't take text input.
+    pub fn select_ime_engine(&self, token: [u32; 4], server: Option<&str>) -> Result<bool, xous::Error> {
+        let select = ImeEngineSelect { token, server: server.map(|s| String::from_str(s)), accepted: false };
+        let mut buf = Buffer::into_buf(select).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::SelectImeEngine.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let result = buf.to_original::<ImeEngineSelect, _>().or(Err(xous::Error::InternalError))?;
+        Ok(result.accepted)
+    }
+
     /// this is a one-way door, once you've set it, you can't unset it.
     pub fn set_devboot(&self, enable: bool) -> Result<(), xous::Error> {
         let ena = if enable { 1 } else { 0 };
@@ -26,6 +26,8 @@ use xous_ipc::{Buffer, String};
 
 /// This sets the initial app focus on boot
 const INITIAL_APP_FOCUS: &'static str = gam::APP_NAME_SHELLCHAT;
+/// An offscreen frame that hasn't been presented after this long stops holding back screen flushes
+const FRAME_TIMEOUT_MS: u64 = 500;
 
 static CB_TO_MAIN_CONN: AtomicU32 = AtomicU32::new(0);
 fn imef_cb(s: String<4000>) {
@@ -46,6 +48,7 @@ fn main() -> ! {
 }
 fn wrapped_main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     info!("my PID is {}", xous::process::id());
 
@@ -120,6 +123,8 @@ fn wrapped_main() -> ! {
 
     let mut powerdown_requested = false;
     let mut last_time: u64 = ticktimer.elapsed_ms();
+    // the offscreen frame being drawn by the focused app, and when it was opened
+    let mut open_frame: Option<(Gid, u64)> = None;
     let mut did_test = false; // allow one go at the test pattern
     log::trace!("entering main loop");
 
@@ -200,6 +205,20 @@ fn wrapped_main() -> ! {
                         continue; // don't allow any redraws if a powerdown is requested
                     }
                     let elapsed_time = ticktimer.elapsed_ms();
+                    if let Some((gid, opened)) = open_frame {
+                        if context_mgr.focused_app().and_then(|t| context_mgr.get_content_canvas(t))
+                            != Some(gid)
+                        {
+                            log::info!("offscreen frame on {:?} lost focus, releasing it", gid);
+                            open_frame = None;
+                        } else if elapsed_time - opened > FRAME_TIMEOUT_MS {
+                            log::warn!("offscreen frame on {:?} was never presented, releasing it", gid);
+                            open_frame = None;
+                        } else {
+                            // the flush goes out with the frame, when it's presented
+                            continue;
+                        }
+                    }
                     if elapsed_time - last_time > gam::RATE_LIMIT_MS as u64 {
                         // rate limit updates, no point in going faster than the eye can see
                         last_time = elapsed_time;
@@ -223,6 +242,38 @@ fn wrapped_main() -> ! {
                     }
                 })
             }
+            Some(Opcode::BeginFrame) => {
+                msg_scalar_unpack!(msg, g0, g1, g2, g3, {
+                    let gid = Gid::new([g0 as _, g1 as _, g2 as _, g3 as _]);
+                    // flushes are held back for everyone, so only the app in focus gets to do this
+                    if context_mgr.focused_app().and_then(|t| context_mgr.get_content_canvas(t))
+                        == Some(gid)
+                    {
+                        // opening the frame again doesn't push back its deadline
+                        if open_frame.map(|(g, _)| g) != Some(gid) {
+                            open_frame = Some((gid, ticktimer.elapsed_ms()));
+                        }
+                    } else {
+                        info!("attempt to open a frame on a canvas that isn't in focus, ignored.");
+                    }
+                });
+            }
+            Some(Opcode::PresentFrame) => {
+                msg_scalar_unpack!(msg, g0, g1, g2, g3, {
+                    let gid = Gid::new([g0 as _, g1 as _, g2 as _, g3 as _]);
+                    if open_frame.map(|(g, _)| g) == Some(gid) {
+                        open_frame = None;
+                        // put the frame up right away: bypass the rate limiter on the next Redraw, which
+                        // arrives behind anything else already queued for us
+                        last_time = 0;
+                        xous::send_message(
+                            CB_TO_MAIN_CONN.load(Ordering::Relaxed),
+                            xous::Message::new_scalar(Opcode::Redraw.to_usize().unwrap(), 0, 0, 0, 0),
+                        )
+                        .expect("couldn't request redraw of offscreen frame");
+                    }
+                });
+            }
             Some(Opcode::SetDebugLevel) => msg_blocking_scalar_unpack!(msg, level, _, _, _, {
                 match level {
                     0 => log::set_max_level(log::LevelFilter::Info),
@@ -793,6 +844,57 @@ fn wrapped_main() -> ! {
                 gfx.set_devboot(true).ok(); // indicate to users that we are no longer in a codebase that is exclusively trusted code
                 context_mgr.register_name(registration.name.to_str(), &registration.auth_token);
             }
+            Some(Opcode::RequestInputGrab) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut request = buffer.to_original::<InputGrabRequest, _>().unwrap();
+                request.granted = context_mgr.request_input_grab(request.token, request.policy, &canvases);
+                buffer.replace(request).unwrap();
+            }
+            Some(Opcode::ReleaseInputGrab) => msg_blocking_scalar_unpack!(msg, t0, t1, t2, t3, {
+                context_mgr.release_input_grab([t0 as u32, t1 as u32, t2 as u32, t3 as u32]);
+                xous::return_scalar(msg.sender, 0).ok();
+            }),
+            Some(Opcode::RaiseScreensaver) => msg_blocking_scalar_unpack!(msg, t0, t1, t2, t3, {
+                let token = [t0 as u32, t1 as u32, t2 as u32, t3 as u32];
+                let raised = context_mgr.raise_screensaver(token, &gfx, &mut canvases);
+                xous::return_scalar(msg.sender, raised as usize).ok();
+            }),
+            Some(Opcode::DismissScreensaver) => msg_blocking_scalar_unpack!(msg, t0, t1, t2, t3, {
+                let token = [t0 as u32, t1 as u32, t2 as u32, t3 as u32];
+                match context_mgr.dismiss_screensaver(token, &gfx, &mut canvases) {
+                    Ok(_) => xous::return_scalar(msg.sender, 0).ok(),
+                    Err(e) => {
+                        log::warn!("failed to dismiss the screensaver: {:?}", e);
+                        xous::return_scalar(msg.sender, 1).ok()
+                    }
+                };
+            }),
+            Some(Opcode::RegisterImeEngine) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut registration = buffer.to_original::<ImeEngineRegistration, _>().unwrap();
+                registration.accepted = context_mgr.register_ime_engine(&registration);
+                buffer.replace(registration).unwrap();
+            }
+            Some(Opcode::ListImeEngines) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                buffer.replace(context_mgr.list_ime_engines()).unwrap();
+            }
+            Some(Opcode::SelectImeEngine) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("SelectImeEngine was not a memory message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(mut select) = buffer.to_original::<ImeEngineSelect, _>() else {
+                    log::error!("couldn't deserialize a SelectImeEngine request");
+                    continue;
+                };
+                select.accepted = context_mgr.select_ime_engine(select.token, select.server);
+                buffer.replace(select).unwrap();
+            }
             Some(Opcode::Quit) => break,
             None => {
                 log::error!("unhandled message {:?}", msg);
@@ -9,6 +9,7 @@ use xous_ipc::{Buffer, String};
 
 use crate::api::*;
 use crate::Gam;
+use crate::ScrollAccel;
 use crate::{forwarding_thread, MsgForwarder};
 #[derive(Debug)]
 pub struct Menu<'a> {
@@ -25,6 +26,8 @@ pub struct Menu<'a> {
     pub canvas_width: Option<i16>,
     pub helper_data: Option<Buffer<'a>>,
     pub name: std::string::String,
+    /// speeds up the arrow keys when they are held
+    scroll: ScrollAccel,
     #[cfg(feature = "tts")]
     pub tts: TtsFrontend,
 }
@@ -81,6 +84,7 @@ impl<'a> Menu<'a> {
             canvas_width: None,
             helper_data: None,
             name: std::string::String::from(name),
+            scroll: ScrollAccel::new(),
             #[cfg(feature = "tts")]
             tts,
         }
@@ -204,6 +208,21 @@ impl<'a> Menu<'a> {
         if len_before > self.items.len() { true } else { false }
     }
 
+    /// Renames the item called `old` to `new`, e.g. after the language changes. An item that doesn't apply
+    /// in the new locale is deleted. Returns false if there is no item called `old`.
+    pub fn rename_item(&mut self, old: &str, new: String<64>) -> bool {
+        if new.as_str().unwrap() == "🔇" {
+            return self.delete_item(old);
+        }
+        match self.items.iter_mut().find(|candidate| candidate.name.as_str().unwrap() == old) {
+            Some(item) => {
+                item.name = new;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn draw_item(&self, index: i16, with_marker: bool) {
         use core::fmt::Write;
         let canvas_size = self.gam.get_canvas_bounds(self.canvas).unwrap();
@@ -313,6 +332,34 @@ impl<'a> Menu<'a> {
         }
     }
 
+    /// Moves the selection up by `lines`, stopping at the first item. A single line wraps around from the
+    /// first item to the last, as `prev_item()` does.
+    pub fn prev_items(&mut self, lines: usize) {
+        if lines <= 1 {
+            self.prev_item();
+        } else {
+            self.move_marker(self.index.saturating_sub(lines));
+        }
+    }
+
+    /// Moves the selection down by `lines`, stopping at the last item. A single line wraps around from the
+    /// last item to the first, as `next_item()` does.
+    pub fn next_items(&mut self, lines: usize) {
+        if lines <= 1 {
+            self.next_item();
+        } else {
+            self.move_marker((self.index + lines).min(self.num_items() - 1));
+        }
+    }
+
+    fn move_marker(&mut self, index: usize) {
+        if index != self.index {
+            self.draw_item(self.index as i16, false);
+            self.index = index;
+            self.draw_item(self.index as i16, true);
+        }
+    }
+
     pub fn redraw(&mut self) {
         // for now, just draw a black rectangle
         log::trace!("menu redraw");
@@ -411,12 +458,15 @@ impl<'a> Menu<'a> {
                     log::trace!("got right arrow");
                 }
                 '↑' => {
-                    self.prev_item();
+                    // the whole menu is on screen, so a page is all of it
+                    let lines = self.scroll.lines(k, self.num_items());
+                    self.prev_items(lines);
                     log::trace!("menu redraw## up key");
                     self.gam.redraw().unwrap();
                 }
                 '↓' => {
-                    self.next_item();
+                    let lines = self.scroll.lines(k, self.num_items());
+                    self.next_items(lines);
                     log::trace!("menu redraw## down key");
                     self.gam.redraw().unwrap();
                 }
@@ -464,6 +514,24 @@ impl MenuMatic {
         if ret.op == MenuMgrOp::Ok { true } else { false }
     }
 
+    pub fn rename_item(&self, old_name: &str, new_name: &str) -> bool {
+        let mm = MenuManagement {
+            item: MenuItem {
+                name: String::from_str(old_name),
+                // the rest are ignored
+                action_conn: None,
+                action_opcode: 0,
+                action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+                close_on_select: false,
+            },
+            op: MenuMgrOp::RenameItem(String::from_str(new_name)),
+        };
+        let mut buf = Buffer::into_buf(mm).expect("Couldn't convert to memory structure");
+        buf.lend_mut(self.cid, 0).expect("Couldn't issue management opcode");
+        let ret = buf.to_original::<MenuManagement, _>().unwrap();
+        if ret.op == MenuMgrOp::Ok { true } else { false }
+    }
+
     pub fn set_index(&self, index: usize) {
         let op = MenuManagement {
             item: MenuItem {
@@ -583,6 +651,14 @@ pub fn menu_matic(
                             }
                             buffer.replace(mgmt).unwrap();
                         }
+                        MenuMgrOp::RenameItem(new_name) => {
+                            if !menu.lock().unwrap().rename_item(mgmt.item.name.as_str().unwrap(), new_name) {
+                                mgmt.op = MenuMgrOp::Err;
+                            } else {
+                                mgmt.op = MenuMgrOp::Ok;
+                            }
+                            buffer.replace(mgmt).unwrap();
+                        }
                         MenuMgrOp::SetIndex(index) => {
                             log::info!("setting menu index {}", index);
                             menu.lock().unwrap().set_index(index);
@@ -14,6 +14,8 @@ mod progressbar;
 pub use progressbar::*;
 mod consoleinput;
 pub use consoleinput::*;
+mod form;
+pub use form::*;
 #[cfg(feature = "ditherpunk")]
 mod image;
 #[cfg(feature = "ditherpunk")]
@@ -45,6 +47,7 @@ pub enum ActionType {
     #[cfg(feature = "ditherpunk")]
     Image,
     ConsoleInput,
+    Form,
 }
 
 #[enum_dispatch]
@@ -216,6 +219,13 @@ pub struct Modal<'a> {
     bot_memoized_height: Option<i16>,
 }
 
+fn grab_for_password(gam: &Gam, authtoken: [u32; 4], name: &str) {
+    match gam.request_input_grab(authtoken, InputGrabPolicy::NotifyObservers) {
+        Ok(true) => (),
+        _ => log::warn!("Couldn't grab the keyboard for password entry in {}", name),
+    }
+}
+
 fn recompute_canvas(modal: &mut Modal, top_text: Option<&str>, bot_text: Option<&str>, style: GlyphStyle) {
     // we need to set a "max" size to our modal box, so that the text computations don't fail later on
     let current_bounds = modal.gam.get_canvas_bounds(modal.canvas).expect("couldn't get current bounds");
@@ -404,11 +414,23 @@ impl<'a> Modal<'a> {
 
     pub fn activate(&self) {
         const POLL_DELAY_MS: usize = 857;
+        // keep keystrokes away from lower-trust listeners while a password is typed. Only a focused context
+        // can grab the keyboard, so the grab is requested once the modal is raised; it is released when the
+        // text entry relinquishes focus.
+        let grab_input = match self.action {
+            ActionType::TextEntry(_) => self.action.is_password(),
+            _ => false,
+        };
         match self.gam.raise_modal(self.name.to_str()) {
-            Ok(_) => (),
+            Ok(_) => {
+                if grab_input {
+                    grab_for_password(&self.gam, self.authtoken, self.name.to_str());
+                }
+            }
             Err(_) => {
                 std::thread::spawn({
                     let name = self.name.clone();
+                    let authtoken = self.authtoken;
                     move || {
                         let xns = xous_names::XousNames::new().unwrap();
                         let ticktimer = ticktimer_server::Ticktimer::new().unwrap();
@@ -418,6 +440,9 @@ impl<'a> Modal<'a> {
                             log::info!("Couldn't raise {}; retrying...", name);
                             ticktimer.sleep_ms(POLL_DELAY_MS).unwrap();
                         }
+                        if grab_input {
+                            grab_for_password(&gam, authtoken, name.to_str());
+                        }
                         log::info!("Success! exiting polling thread.");
                     }
                 });
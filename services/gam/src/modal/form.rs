@@ -0,0 +1,353 @@
+use core::fmt::Write;
+
+use graphics_server::api::*;
+use locales::t;
+#[cfg(feature = "tts")]
+use tts_frontend::TtsFrontend;
+use xous_ipc::{Buffer, String};
+
+use crate::*;
+
+/// The most fields a form can have; each one takes a line of the modal
+pub const MAX_FORM_FIELDS: usize = 8;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub enum FormFieldKind {
+    /// free-form text
+    Text,
+    /// a whole number, possibly negative; other keys are ignored
+    Number,
+    /// on or off, toggled with select
+    CheckBox,
+    /// one of a list of choices, picked with left/right
+    Radio,
+}
+
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct FormField {
+    pub label: ItemName,
+    pub kind: FormFieldKind,
+    /// the contents of a `Text` or `Number` field
+    pub text: String<256>,
+    /// the state of a `CheckBox` field
+    pub checked: bool,
+    /// the choices of a `Radio` field, and the index of the one picked
+    pub choices: [Option<ItemName>; MAX_ITEMS],
+    pub selected: u32,
+}
+impl FormField {
+    fn new(label: &str, kind: FormFieldKind) -> Self {
+        FormField {
+            label: ItemName::new(label),
+            kind,
+            text: String::new(),
+            checked: false,
+            choices: [None; MAX_ITEMS],
+            selected: 0,
+        }
+    }
+
+    pub fn text(label: &str, initial: &str) -> Self {
+        let mut field = FormField::new(label, FormFieldKind::Text);
+        field.text.append(initial).ok();
+        field
+    }
+
+    pub fn number(label: &str, initial: Option<i64>) -> Self {
+        let mut field = FormField::new(label, FormFieldKind::Number);
+        if let Some(n) = initial {
+            write!(field.text, "{}", n).ok();
+        }
+        field
+    }
+
+    pub fn checkbox(label: &str, checked: bool) -> Self {
+        let mut field = FormField::new(label, FormFieldKind::CheckBox);
+        field.checked = checked;
+        field
+    }
+
+    /// Only the first `MAX_ITEMS` choices are kept.
+    pub fn radio(label: &str, choices: &[&str], selected: usize) -> Self {
+        let mut field = FormField::new(label, FormFieldKind::Radio);
+        if choices.len() > MAX_ITEMS {
+            log::warn!("form field '{}' has more than {} choices, dropping the rest", label, MAX_ITEMS);
+        }
+        for (slot, choice) in field.choices.iter_mut().zip(choices.iter()) {
+            *slot = Some(ItemName::new(choice));
+        }
+        field.selected = selected.min(field.choice_count().saturating_sub(1)) as u32;
+        field
+    }
+
+    pub fn label(&self) -> &str { self.label.as_str() }
+
+    /// The text of a `Text` or `Number` field, or the picked choice of a `Radio` field. `CheckBox` fields
+    /// are empty.
+    pub fn as_str(&self) -> &str {
+        match self.kind {
+            FormFieldKind::Radio => {
+                self.choices.get(self.selected as usize).and_then(|c| c.as_ref()).map_or("", |c| c.as_str())
+            }
+            _ => self.text.as_str().unwrap_or(""),
+        }
+    }
+
+    /// The value of a `Number` field, if it holds one
+    pub fn as_number(&self) -> Option<i64> { self.text.as_str().ok()?.parse::<i64>().ok() }
+
+    pub fn is_checked(&self) -> bool { self.checked }
+
+    /// The index of the picked choice of a `Radio` field
+    pub fn choice(&self) -> usize { self.selected as usize }
+
+    fn choice_count(&self) -> usize { self.choices.iter().filter(|c| c.is_some()).count() }
+}
+
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct FormPayload(pub [Option<FormField>; MAX_FORM_FIELDS]);
+impl FormPayload {
+    pub fn new() -> Self { FormPayload([None; MAX_FORM_FIELDS]) }
+
+    /// Adds `field` after the others; returns false if the form is full.
+    pub fn add(&mut self, field: FormField) -> bool {
+        match self.0.iter_mut().find(|f| f.is_none()) {
+            Some(slot) => {
+                *slot = Some(field);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize { self.0.iter().filter(|f| f.is_some()).count() }
+
+    pub fn get(&self, index: usize) -> Option<&FormField> { self.0.get(index)?.as_ref() }
+
+    pub fn fields(&self) -> impl Iterator<Item = &FormField> { self.0.iter().filter_map(|f| f.as_ref()) }
+}
+
+/// Several labelled fields in one modal. Up/down move between the fields and the "OK" line below them;
+/// text and numbers are typed into their field, select toggles check boxes, and left/right pick radio
+/// choices. Select on the "OK" line sends the whole `FormPayload` back.
+#[derive(Debug)]
+pub struct Form {
+    pub action_conn: xous::CID,
+    pub action_opcode: u32,
+    pub action_payload: FormPayload,
+    /// the field the cursor is on; `action_payload.len()` is the "OK" line
+    pub select_index: i16,
+    gam: crate::Gam,
+    #[cfg(feature = "tts")]
+    pub tts: TtsFrontend,
+}
+impl Form {
+    pub fn new(action_conn: xous::CID, action_opcode: u32, action_payload: FormPayload) -> Self {
+        #[cfg(feature = "tts")]
+        let tts = TtsFrontend::new(&xous_names::XousNames::new().unwrap()).unwrap();
+        Form {
+            action_conn,
+            action_opcode,
+            action_payload,
+            select_index: 0,
+            gam: crate::Gam::new(&xous_names::XousNames::new().unwrap()).unwrap(),
+            #[cfg(feature = "tts")]
+            tts,
+        }
+    }
+
+    fn selected(&mut self) -> Option<&mut FormField> {
+        self.action_payload.0.get_mut(self.select_index as usize)?.as_mut()
+    }
+}
+impl ActionApi for Form {
+    fn set_action_opcode(&mut self, op: u32) { self.action_opcode = op }
+
+    fn height(&self, glyph_height: i16, margin: i16, _modal: &Modal) -> i16 {
+        // fields +1 blank line +1 "Okay" message
+        let line_count = (self.action_payload.len() + 1 + 1) as i16;
+        line_count * glyph_height + 2 * margin
+    }
+
+    fn redraw(&self, at_height: i16, modal: &Modal) {
+        let mut tv = TextView::new(modal.canvas, TextBounds::BoundingBox(Rectangle::new_coords(0, 0, 1, 1)));
+        tv.ellipsis = true;
+        tv.style = modal.style;
+        tv.invert = false;
+        tv.draw_border = false;
+        tv.margin = Point::new(0, 0);
+        tv.insertion = None;
+
+        let cursor_x = modal.margin;
+        let select_x = modal.margin + 20;
+        let text_x = modal.margin + 20 + 20;
+
+        let emoji_slop = 2; // tweaked for a non-emoji glyph
+
+        let mut cur_y = at_height;
+        let field_count = self.action_payload.len() as i16;
+        for (index, field) in self.action_payload.fields().enumerate() {
+            let is_selected = index as i16 == self.select_index;
+            if is_selected {
+                #[cfg(feature = "tts")]
+                {
+                    self.tts.tts_simple(field.label()).unwrap();
+                }
+                tv.text.clear();
+                tv.bounds_computed = None;
+                tv.bounds_hint = TextBounds::BoundingBox(Rectangle::new(
+                    Point::new(cursor_x, cur_y - emoji_slop),
+                    Point::new(cursor_x + 36, cur_y - emoji_slop + 36),
+                ));
+                write!(tv, "\u{25B6}").unwrap(); // right arrow
+                modal.gam.post_textview(&mut tv).expect("couldn't post tv");
+            }
+            if field.kind == FormFieldKind::CheckBox && field.checked {
+                tv.text.clear();
+                tv.bounds_computed = None;
+                tv.bounds_hint = TextBounds::BoundingBox(Rectangle::new(
+                    Point::new(select_x, cur_y - emoji_slop),
+                    Point::new(select_x + 36, cur_y + modal.line_height),
+                ));
+                write!(tv, "\u{d7}").unwrap(); // multiplication sign
+                modal.gam.post_textview(&mut tv).expect("couldn't post tv");
+            }
+            tv.text.clear();
+            tv.bounds_computed = None;
+            tv.bounds_hint = TextBounds::BoundingBox(Rectangle::new(
+                Point::new(text_x, cur_y),
+                Point::new(modal.canvas_width - modal.margin, cur_y + modal.line_height),
+            ));
+            match field.kind {
+                FormFieldKind::Text | FormFieldKind::Number => {
+                    write!(tv, "{}: {}{}", field.label(), field.as_str(), if is_selected { "_" } else { "" })
+                        .unwrap()
+                }
+                FormFieldKind::CheckBox => write!(tv, "{}", field.label()).unwrap(),
+                FormFieldKind::Radio => {
+                    if is_selected {
+                        write!(tv, "{}: \u{2190} {} \u{2192}", field.label(), field.as_str()).unwrap()
+                    } else {
+                        write!(tv, "{}: {}", field.label(), field.as_str()).unwrap()
+                    }
+                }
+            }
+            modal.gam.post_textview(&mut tv).expect("couldn't post tv");
+            cur_y += modal.line_height;
+        }
+        // blank line above OK
+        cur_y += modal.line_height;
+        if self.select_index == field_count {
+            tv.text.clear();
+            tv.bounds_computed = None;
+            tv.bounds_hint = TextBounds::BoundingBox(Rectangle::new(
+                Point::new(cursor_x, cur_y - emoji_slop),
+                Point::new(cursor_x + 36, cur_y - emoji_slop + 36),
+            ));
+            write!(tv, "\u{25B6}").unwrap();
+            modal.gam.post_textview(&mut tv).expect("couldn't post tv");
+            #[cfg(feature = "tts")]
+            {
+                self.tts.tts_blocking(t!("radio.select_and_close", locales::LANG)).unwrap();
+            }
+        }
+        tv.text.clear();
+        tv.bounds_computed = None;
+        tv.bounds_hint = TextBounds::BoundingBox(Rectangle::new(
+            Point::new(text_x, cur_y),
+            Point::new(modal.canvas_width - modal.margin, cur_y + modal.line_height),
+        ));
+        write!(tv, "{}", t!("radio.select_and_close", locales::LANG)).unwrap();
+        modal.gam.post_textview(&mut tv).expect("couldn't post tv");
+
+        // divider lines
+        modal
+            .gam
+            .draw_line(
+                modal.canvas,
+                Line::new_with_style(
+                    Point::new(modal.margin, at_height),
+                    Point::new(modal.canvas_width - modal.margin, at_height),
+                    DrawStyle::new(PixelColor::Dark, PixelColor::Dark, 1),
+                ),
+            )
+            .expect("couldn't draw entry line");
+    }
+
+    fn key_action(&mut self, k: char) -> Option<ValidatorErr> {
+        log::trace!("key_action: {}", k);
+        let field_count = self.action_payload.len() as i16;
+        match k {
+            '↑' => {
+                if self.select_index > 0 {
+                    self.select_index -= 1;
+                }
+            }
+            '↓' => {
+                if self.select_index < field_count {
+                    // the last index is the "OK" line
+                    self.select_index += 1;
+                }
+            }
+            '←' | '→' => {
+                if let Some(field) = self.selected() {
+                    if field.kind == FormFieldKind::Radio {
+                        let count = field.choice_count().max(1) as u32;
+                        field.selected = if k == '→' {
+                            (field.selected + 1) % count
+                        } else {
+                            (field.selected + count - 1) % count
+                        };
+                    }
+                }
+            }
+            '∴' | '\u{d}' => match self.selected().map(|field| field.kind) {
+                Some(FormFieldKind::CheckBox) => {
+                    let field = self.selected().unwrap();
+                    field.checked = !field.checked;
+                }
+                Some(FormFieldKind::Radio) => {
+                    let field = self.selected().unwrap();
+                    field.selected = (field.selected + 1) % field.choice_count().max(1) as u32;
+                }
+                // enter in a text field goes on to the next one, as in a paper form
+                Some(FormFieldKind::Text) | Some(FormFieldKind::Number) => self.select_index += 1,
+                None => {
+                    // the OK line: relinquish focus before returning the result
+                    self.gam.relinquish_focus().unwrap();
+                    xous::yield_slice();
+
+                    let buf =
+                        Buffer::into_buf(self.action_payload).expect("couldn't convert message to payload");
+                    buf.send(self.action_conn, self.action_opcode)
+                        .map(|_| ())
+                        .expect("couldn't send action message");
+                    return None;
+                }
+            },
+            '\u{0}' => {
+                // ignore null messages
+            }
+            '\u{8}' => {
+                if let Some(field) = self.selected() {
+                    if field.kind == FormFieldKind::Text || field.kind == FormFieldKind::Number {
+                        field.text.pop();
+                    }
+                }
+            }
+            _ => {
+                if let Some(field) = self.selected() {
+                    let accept = match field.kind {
+                        FormFieldKind::Text => !k.is_control(),
+                        FormFieldKind::Number => k.is_ascii_digit() || (k == '-' && field.text.is_empty()),
+                        _ => false,
+                    };
+                    if accept && field.text.push(k).is_err() {
+                        log::warn!("form field '{}' is full", field.label());
+                    }
+                }
+            }
+        }
+        None
+    }
+}
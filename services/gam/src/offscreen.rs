@@ -0,0 +1,108 @@
+//! Offscreen drawing for apps that repaint a whole screen at a time.
+//!
+//! Normally every `Redraw` pushes whatever is in the frame buffer to the LCD, so an app that clears its
+//! canvas and then draws a few dozen objects can be caught half-way by a flush (its own, or one triggered
+//! by the status bar) and flickers. Drawing through an [`Offscreen`] instead holds back all flushes until
+//! the frame is presented; the frame buffer acts as the back buffer and the LCD as the front buffer.
+//!
+//! ```ignore
+//! let mut frame = gam.offscreen(gid)?;
+//! frame.clear()?;
+//! frame.draw_rectangle(rect)?;
+//! frame.post_textview(&mut tv)?;
+//! frame.present()?;
+//! ```
+//!
+//! Only the content canvas of the app in focus can have a frame open. The GAM gives up on a frame that is
+//! not presented in a timely fashion, or whose app loses focus, so a stuck app cannot freeze the screen.
+
+use num_traits::*;
+use xous::{send_message, Message};
+
+use crate::api::Opcode;
+#[cfg(feature = "ditherpunk")]
+use crate::Bitmap;
+use crate::{Circle, Gam, GamObjectList, Gid, Line, Rectangle, RoundedRectangle, TextView};
+
+/// A frame being drawn on a canvas, out of sight until it is presented. Dropping the frame without
+/// presenting it presents whatever was drawn so far.
+pub struct Offscreen<'a> {
+    gam: &'a Gam,
+    gid: Gid,
+    open: bool,
+}
+impl<'a> Offscreen<'a> {
+    pub(crate) fn begin(gam: &'a Gam, gid: Gid) -> Result<Self, xous::Error> {
+        frame_message(gam, Opcode::BeginFrame, gid)?;
+        Ok(Offscreen { gam, gid, open: true })
+    }
+
+    pub fn gid(&self) -> Gid { self.gid }
+
+    /// Fills the whole canvas with the background colour.
+    pub fn clear(&mut self) -> Result<(), xous::Error> {
+        frame_message(self.gam, Opcode::ClearCanvas, self.gid)
+    }
+
+    pub fn draw_line(&mut self, line: Line) -> Result<(), xous::Error> { self.gam.draw_line(self.gid, line) }
+
+    pub fn draw_rectangle(&mut self, rect: Rectangle) -> Result<(), xous::Error> {
+        self.gam.draw_rectangle(self.gid, rect)
+    }
+
+    pub fn draw_rounded_rectangle(&mut self, rr: RoundedRectangle) -> Result<(), xous::Error> {
+        self.gam.draw_rounded_rectangle(self.gid, rr)
+    }
+
+    pub fn draw_circle(&mut self, circ: Circle) -> Result<(), xous::Error> {
+        self.gam.draw_circle(self.gid, circ)
+    }
+
+    #[cfg(feature = "ditherpunk")]
+    pub fn draw_bitmap(&mut self, bm: &Bitmap) -> Result<(), xous::Error> {
+        self.gam.draw_bitmap(self.gid, bm)
+    }
+
+    /// Draws a list of objects. The list must target the same canvas as the frame.
+    pub fn draw_list(&mut self, list: GamObjectList) -> Result<(), xous::Error> {
+        if list.canvas != self.gid {
+            return Err(xous::Error::AccessDenied);
+        }
+        self.gam.draw_list(list)
+    }
+
+    /// Renders a TextView. The TextView must target the same canvas as the frame.
+    pub fn post_textview(&mut self, tv: &mut TextView) -> Result<(), xous::Error> {
+        if tv.get_canvas_gid() != self.gid {
+            return Err(xous::Error::AccessDenied);
+        }
+        self.gam.post_textview(tv)
+    }
+
+    /// Closes the frame and puts everything drawn into it on the screen at once.
+    pub fn present(mut self) -> Result<(), xous::Error> {
+        self.open = false;
+        frame_message(self.gam, Opcode::PresentFrame, self.gid)
+    }
+}
+impl<'a> Drop for Offscreen<'a> {
+    fn drop(&mut self) {
+        if self.open {
+            frame_message(self.gam, Opcode::PresentFrame, self.gid).ok();
+        }
+    }
+}
+
+fn frame_message(gam: &Gam, op: Opcode, gid: Gid) -> Result<(), xous::Error> {
+    send_message(
+        gam.conn(),
+        Message::new_scalar(
+            op.to_usize().unwrap(),
+            gid.gid()[0] as _,
+            gid.gid()[1] as _,
+            gid.gid()[2] as _,
+            gid.gid()[3] as _,
+        ),
+    )
+    .map(|_| ())
+}
@@ -0,0 +1,104 @@
+//! Accelerated scrolling for lists driven by the arrow keys.
+//!
+//! A held arrow key is repeated by the keyboard every 50 ms or so. One line per repeat is fine in a
+//! ten-item menu, but it takes a long while to get through a few hundred vault entries. `ScrollAccel`
+//! watches the timing of the arrow keys, and turns a held key into steps that grow the longer it is
+//! held, and into whole pages once it has been held for `PAGE_HOLD_MS`.
+
+use std::time::Instant;
+
+/// Arrow keys closer together than this are taken as the repeats of a held key. It is well above the
+/// keyboard's repeat interval, to allow for lists that are slow to redraw.
+pub const REPEAT_GAP_MS: u64 = 250;
+/// How long an arrow key is held before every repeat moves a whole page
+pub const PAGE_HOLD_MS: u64 = 1500;
+/// How long a key is held before each repeat moves by the given number of lines; shorter holds move one
+const ACCEL_STEPS: [(u64, usize); 2] = [(400, 2), (900, 4)];
+
+#[derive(Debug)]
+pub struct ScrollAccel {
+    /// what the times below count from
+    epoch: Instant,
+    /// the arrow key being held, when it was pressed, and when it last repeated
+    held: Option<(char, u64, u64)>,
+}
+
+impl ScrollAccel {
+    pub fn new() -> Self { ScrollAccel { epoch: Instant::now(), held: None } }
+
+    /// The number of lines to move for `key`, out of a list showing `page` lines at a time. Keys other
+    /// than '↑' and '↓' return 1, and end any acceleration.
+    pub fn lines(&mut self, key: char, page: usize) -> usize {
+        let now = self.epoch.elapsed().as_millis() as u64;
+        self.lines_at(key, page, now)
+    }
+
+    /// `lines()`, at the time `now` in ms
+    pub fn lines_at(&mut self, key: char, page: usize, now: u64) -> usize {
+        if key != '↑' && key != '↓' {
+            self.held = None;
+            return 1;
+        }
+        let pressed = match self.held {
+            Some((k, pressed, last)) if k == key && now.saturating_sub(last) < REPEAT_GAP_MS => pressed,
+            _ => now,
+        };
+        self.held = Some((key, pressed, now));
+        let held_ms = now.saturating_sub(pressed);
+        if held_ms >= PAGE_HOLD_MS {
+            return page.max(1);
+        }
+        ACCEL_STEPS
+            .iter()
+            .rev()
+            .find(|(after_ms, _)| held_ms >= *after_ms)
+            .map_or(1, |(_, lines)| *lines)
+            .min(page.max(1))
+    }
+}
+
+impl Default for ScrollAccel {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the lines moved by a key held from `from_ms` for `for_ms`, repeating every 50 ms
+    fn held(key: char, from_ms: u64, for_ms: u64, page: usize, accel: &mut ScrollAccel) -> Vec<usize> {
+        (from_ms..=from_ms + for_ms).step_by(50).map(|t| accel.lines_at(key, page, t)).collect()
+    }
+
+    #[test]
+    fn taps_move_one_line() {
+        let mut accel = ScrollAccel::new();
+        for t in [0, 300, 600, 900, 1200, 1500, 1800] {
+            assert_eq!(accel.lines_at('↓', 10, t), 1);
+        }
+    }
+
+    #[test]
+    fn held_key_accelerates_to_pages() {
+        let mut accel = ScrollAccel::new();
+        let steps = held('↓', 0, 2000, 10, &mut accel);
+        assert_eq!(steps[0], 1);
+        assert_eq!(steps[7], 1); // 350 ms
+        assert_eq!(steps[8], 2); // 400 ms
+        assert_eq!(steps[18], 4); // 900 ms
+        assert_eq!(steps[30], 10); // 1500 ms
+        assert!(steps.windows(2).all(|w| w[0] <= w[1]));
+        // the steps never get longer than a page
+        assert!(held('↑', 0, 1000, 3, &mut ScrollAccel::new()).iter().all(|&s| s <= 3));
+    }
+
+    #[test]
+    fn other_keys_and_reversal_reset() {
+        let mut accel = ScrollAccel::new();
+        held('↓', 0, 1000, 10, &mut accel);
+        assert_eq!(accel.lines_at('↑', 10, 1050), 1);
+        assert_eq!(held('↑', 1100, 1000, 10, &mut accel).last(), Some(&4));
+        assert_eq!(accel.lines_at('a', 10, 2150), 1);
+        assert_eq!(accel.lines_at('↑', 10, 2200), 1);
+    }
+}
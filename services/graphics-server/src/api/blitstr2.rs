@@ -28,8 +28,9 @@ pub struct GlyphSprite {
     pub invert: bool,
     // drawn an insertion point after this character
     pub insert: bool,
-    // 2x flag for the back-end rendering (wide/high should be pre-computed to match this)
-    pub double: bool,
+    // integer scale factor for the back-end rendering, 1 for native size (wide/high should be
+    // pre-computed to match this)
+    pub scale: u8,
     // flag for 32-bit wide glyph sets
     pub large: bool,
 }
@@ -9,6 +9,10 @@ pub enum GlyphStyle {
     Large = 5,
     ExtraLarge = 6,
     Tall = 7,
+    /// 3x of regular, for numbers that must be read at arm's length
+    Huge = 8,
+    /// 4x of regular
+    Giant = 9,
 }
 
 /// Convert number to style for use with register-based message passing sytems
@@ -24,6 +28,8 @@ impl From<usize> for GlyphStyle {
             5 => GlyphStyle::Large,
             6 => GlyphStyle::ExtraLarge,
             7 => GlyphStyle::Tall,
+            8 => GlyphStyle::Huge,
+            9 => GlyphStyle::Giant,
             _ => GlyphStyle::Regular,
         }
     }
@@ -42,6 +48,8 @@ impl From<GlyphStyle> for usize {
             GlyphStyle::Large => 5,
             GlyphStyle::ExtraLarge => 6,
             GlyphStyle::Tall => 7,
+            GlyphStyle::Huge => 8,
+            GlyphStyle::Giant => 9,
         }
     }
 }
@@ -61,5 +69,7 @@ pub fn glyph_to_height_hint(g: GlyphStyle) -> usize {
         GlyphStyle::Large => 24,      // 2x of small
         GlyphStyle::ExtraLarge => 30, // 2x of regular
         GlyphStyle::Tall => 19,
+        GlyphStyle::Huge => 45,  // 3x of regular
+        GlyphStyle::Giant => 60, // 4x of regular
     }
 }
@@ -0,0 +1,118 @@
+//! Test automation for hosted mode: injects key presses and reads back the rendered screen, so UI
+//! widgets can get pixel-level regression tests.
+//!
+//! Set `XOUS_AUTOMATION_PORT` to serve the channel on `127.0.0.1:$XOUS_AUTOMATION_PORT`. Commands are
+//! one per line:
+//!
+//! ```text
+//! key <key>      press one key: a single character, or one of up, down, left, right, select, enter,
+//!                backspace, space, f1, f2, f3, f4
+//! type <text>    type each character of <text>
+//! frame          read back the screen as a binary PBM (P4) image, as currently shown in the window
+//! ```
+//! `key` and `type` answer `ok` or `error: <reason>` on a line of their own; `frame` answers with the
+//! image, which is self-delimiting.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+use crate::api::{LINES, WIDTH};
+
+/// Starts serving the automation channel if `XOUS_AUTOMATION_PORT` is set. `native_buffer` is the
+/// rendered screen, with each pixel set to `background` where the display is blank.
+pub fn start(kbd: Arc<keyboard::Keyboard>, native_buffer: Arc<Mutex<Vec<u32>>>, background: u32) {
+    let port = match std::env::var("XOUS_AUTOMATION_PORT") {
+        Ok(port) => port,
+        Err(_) => return,
+    };
+    let listener = match TcpListener::bind(("127.0.0.1", port.parse::<u16>().unwrap_or(0))) {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("couldn't open the automation socket on port {}: {:?}", port, e);
+            return;
+        }
+    };
+    log::info!("GFX|hosted: automation channel on {:?}", listener.local_addr());
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let kbd = kbd.clone();
+            let native_buffer = native_buffer.clone();
+            std::thread::spawn(move || serve(stream, &kbd, &native_buffer, background));
+        }
+    });
+}
+
+fn serve(stream: TcpStream, kbd: &keyboard::Keyboard, native_buffer: &Mutex<Vec<u32>>, background: u32) {
+    let mut reply = match stream.try_clone() {
+        Ok(reply) => reply,
+        Err(_) => return,
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        let (command, arg) = line.split_once(' ').unwrap_or((line.trim(), ""));
+        let response = match command {
+            "key" => match key(arg.trim()) {
+                Some(c) => {
+                    kbd.hostmode_inject_key(c);
+                    b"ok\n".to_vec()
+                }
+                None => format!("error: unknown key {}\n", arg).into_bytes(),
+            },
+            "type" => {
+                for c in arg.chars() {
+                    kbd.hostmode_inject_key(c);
+                }
+                b"ok\n".to_vec()
+            }
+            "frame" => frame(&native_buffer.lock().unwrap(), background),
+            _ => format!("error: unknown command {}\n", command).into_bytes(),
+        };
+        if reply.write_all(&response).is_err() {
+            return;
+        }
+    }
+}
+
+/// Maps a key name to the character the keyboard server delivers for it
+fn key(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(c);
+    }
+    match name {
+        "up" => Some('↑'),
+        "down" => Some('↓'),
+        "left" => Some('←'),
+        "right" => Some('→'),
+        "select" => Some('∴'),
+        "enter" => Some('\u{000d}'),
+        "backspace" => Some('\u{0008}'),
+        "space" => Some(' '),
+        "f1" => Some('\u{0011}'),
+        "f2" => Some('\u{0012}'),
+        "f3" => Some('\u{0013}'),
+        "f4" => Some('\u{0014}'),
+        _ => None,
+    }
+}
+
+/// Encodes the screen as a binary PBM, where a set bit is a dark pixel
+fn frame(native_buffer: &[u32], background: u32) -> Vec<u8> {
+    let mut pbm = format!("P4\n{} {}\n", WIDTH, LINES).into_bytes();
+    for row in native_buffer.chunks(WIDTH as usize) {
+        for pixels in row.chunks(8) {
+            let mut byte = 0u8;
+            for (bit, &pixel) in pixels.iter().enumerate() {
+                if pixel != background {
+                    byte |= 0x80 >> bit;
+                }
+            }
+            pbm.push(byte);
+        }
+    }
+    pbm
+}
@@ -190,32 +190,42 @@ impl XousDisplay {
 
     pub fn screen_size(&self) -> Point { Point::new(FB_WIDTH_PIXELS as i16, FB_LINES as i16) }
 
+    /// Pushes the lines that changed since the last redraw to the LCD.
+    ///
+    /// Drawing operations flag every line they touch as dirty, but a canvas is usually redrawn
+    /// wholesale to update just a few pixels, like a blinking cursor. `hwfb` always holds what the
+    /// panel shows, so dirty lines are compared against it, and only the ones whose pixels actually
+    /// differ are copied over and sent.
     pub fn redraw(&mut self) {
         let mut busy_count = 0;
         let mut dirty_count = 0;
+        let mut changed_count = 0;
         while self.busy() {
             xous::yield_slice();
             busy_count += 1;
         }
-        let fb: *mut [u32; FB_SIZE] = self.fb.as_mut_ptr() as *mut [u32; FB_SIZE];
-        let hwfb: *mut [u32; FB_SIZE] = self.hwfb.as_mut_ptr() as *mut [u32; FB_SIZE];
-        for words in 0..FB_SIZE {
-            unsafe {
-                (*hwfb)[words] = (*fb)[words];
+        // Safety: `u32` contains no undefined values
+        let fb: &mut [u32] = unsafe { &mut self.fb.as_slice_mut::<u32>()[..FB_SIZE] };
+        let hwfb: &mut [u32] = unsafe { &mut self.hwfb.as_slice_mut::<u32>()[..FB_SIZE] };
+        for (line, shown) in fb.chunks_mut(FB_WIDTH_WORDS).zip(hwfb.chunks_mut(FB_WIDTH_WORDS)) {
+            // anything flagged in `hwfb` went out with the previous update, and is now on the panel
+            if shown[FB_WIDTH_WORDS - 1] & 0xFFFF_0000 != 0 {
+                shown[FB_WIDTH_WORDS - 1] &= 0x0000_FFFF;
             }
-        }
-        self.update_dirty();
-        // clear all the dirty bits, under the theory that it's time-wise cheaper on average
-        // to visit every line and clear the dirty bits than it is to do an update_all()
-        for lines in 0..FB_LINES {
-            if unsafe { (*fb)[lines * FB_WIDTH_WORDS + (FB_WIDTH_WORDS - 1)] & 0xFFFF_0000 } != 0x0 {
+            if line[FB_WIDTH_WORDS - 1] & 0xFFFF_0000 != 0 {
+                line[FB_WIDTH_WORDS - 1] &= 0x0000_FFFF;
                 dirty_count += 1;
-            }
-            unsafe {
-                (*fb)[lines * FB_WIDTH_WORDS + (FB_WIDTH_WORDS - 1)] &= 0x0000_FFFF;
+                if line != shown {
+                    shown.copy_from_slice(line);
+                    shown[FB_WIDTH_WORDS - 1] |= 0x1_0000;
+                    changed_count += 1;
+                }
             }
         }
-        log::trace!("redraw {}/{}", busy_count, dirty_count);
+        if changed_count != 0 {
+            self.update_dirty();
+        }
+        log::trace!("redraw {}/{}/{}", busy_count, dirty_count, changed_count);
     }
 
     pub fn native_buffer(&mut self) -> &mut [u32; FB_SIZE] {
@@ -223,11 +233,20 @@ impl XousDisplay {
     }
 
     pub fn blit_screen(&mut self, bmp: &[u32]) {
+        while self.busy() {}
         let framebuffer = self.fb.as_mut_ptr() as *mut u32;
+        let hwfb = self.hwfb.as_mut_ptr() as *mut u32;
 
         for words in 0..FB_SIZE {
+            // the whole screen is sent, so `hwfb` needs no dirty flags to stay in step with the panel
+            let pixels = if words % FB_WIDTH_WORDS == FB_WIDTH_WORDS - 1 {
+                bmp[words] & 0x0000_FFFF
+            } else {
+                bmp[words]
+            };
             unsafe {
-                framebuffer.add(words).write_volatile(bmp[words]);
+                framebuffer.add(words).write_volatile(pixels);
+                hwfb.add(words).write_volatile(pixels);
             }
         }
         self.update_all();
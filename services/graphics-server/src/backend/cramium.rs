@@ -65,7 +65,7 @@
 
 use core::mem::size_of;
 
-use cram_hal_service::IoxHal;
+use cram_hal_service::{DmaRegion, IoxHal};
 use cramium_hal::udma::PeriphId;
 use cramium_hal::{iox, udma};
 
@@ -79,6 +79,11 @@ pub const FB_SIZE: usize = FB_WIDTH_WORDS * FB_LINES; // 44 bytes by 536 lines
 
 const CONFIG_CLOCK_FREQUENCY: u32 = 50_000_000;
 
+// word offsets of the frame buffers in the DMA region
+const FB_OFFSET: usize = 0;
+const SHOWN_OFFSET: usize = FB_SIZE;
+const SRFB_OFFSET: usize = 2 * FB_SIZE;
+
 pub struct MainThreadToken(());
 
 pub enum Never {}
@@ -91,8 +96,13 @@ pub fn claim_main_thread(f: impl FnOnce(MainThreadToken) -> Never + Send + 'stat
 }
 
 pub struct XousDisplay {
-    fb: [u32; FB_SIZE],
-    srfb: [u32; FB_SIZE],
+    /// Holds the frame buffer at `FB_OFFSET`, a copy of what the panel shows at `SHOWN_OFFSET` and the
+    /// stashed frame buffer at `SRFB_OFFSET`, so that whole frames can be copied between them by DMA. The
+    /// region is lent to the HAL for each copy, so the buffers are only ever borrowed from it in between.
+    ///
+    /// A line of the shown copy whose last word has any of the top 16 bits set has never been sent, so
+    /// its contents are unknown.
+    dma: DmaRegion,
     next_free_line: usize,
     spim: udma::Spim,
     devboot: bool,
@@ -220,13 +230,11 @@ impl XousDisplay {
             .expect("Couldn't allocate SPI channel for LCD")
         };
 
-        let mut display = XousDisplay {
-            fb: [0xFFFF_FFFFu32; FB_SIZE],
-            srfb: [0xFFFF_FFFFu32; FB_SIZE],
-            spim,
-            next_free_line: 0,
-            devboot: false,
-        };
+        let mut dma =
+            DmaRegion::new(3 * FB_SIZE * size_of::<u32>()).expect("Couldn't allocate frame buffers");
+        dma.as_slice_mut().fill(0xFFFF_FFFF);
+
+        let mut display = XousDisplay { dma, spim, next_free_line: 0, devboot: false };
 
         // initialize the DMA buffer with valid mode/address lines & blank data
         for line in 0..FB_LINES {
@@ -268,19 +276,45 @@ impl XousDisplay {
         self.spim.into_raw_parts()
     }
 
+    fn fb(&self) -> &[u32] { &self.dma.as_slice()[FB_OFFSET..FB_OFFSET + FB_SIZE] }
+
+    fn fb_mut(&mut self) -> &mut [u32] { &mut self.dma.as_slice_mut()[FB_OFFSET..FB_OFFSET + FB_SIZE] }
+
+    /// The frame buffer and the copy of what the panel shows, borrowed together
+    fn fb_and_shown(&mut self) -> (&mut [u32], &mut [u32]) {
+        let (fb, rest) = self.dma.as_slice_mut()[FB_OFFSET..].split_at_mut(FB_SIZE);
+        let shown = SHOWN_OFFSET - FB_OFFSET - FB_SIZE;
+        (fb, &mut rest[shown..shown + FB_SIZE])
+    }
+
+    /// Copies `words` words between the frame buffers by DMA, falling back to the CPU if DMA fails
+    fn copy_words(&mut self, src: usize, dst: usize, words: usize) {
+        if let Err(e) =
+            self.dma.copy(src * size_of::<u32>(), dst * size_of::<u32>(), words * size_of::<u32>())
+        {
+            log::warn!("DMA copy failed ({:?}), copying with the CPU", e);
+            self.dma.as_slice_mut().copy_within(src..src + words, dst);
+        }
+    }
+
     pub fn stash(&mut self) {
-        self.srfb.copy_from_slice(&self.fb);
+        self.copy_words(FB_OFFSET, SRFB_OFFSET, FB_SIZE);
 
+        let srfb = &mut self.dma.as_slice_mut()[SRFB_OFFSET..SRFB_OFFSET + FB_SIZE];
         for lines in 0..FB_LINES {
             // set the dirty bits prior to stashing the frame buffer
-            self.srfb[lines * FB_WIDTH_WORDS + (FB_WIDTH_WORDS - 1)] |= 0x1_0000;
+            srfb[lines * FB_WIDTH_WORDS + (FB_WIDTH_WORDS - 1)] |= 0x1_0000;
         }
     }
 
     pub fn pop(&mut self) {
         // skip copying the status bar, so that the status info is not overwritten by the pop.
         // this is "fixed" at 34 pixels high (2 * Tall glyph height hint) per line 79 in gam/src/main.rs
-        self.fb[FB_WIDTH_WORDS * 34..FB_SIZE].copy_from_slice(&self.srfb[FB_WIDTH_WORDS * 34..FB_SIZE]);
+        self.copy_words(
+            SRFB_OFFSET + FB_WIDTH_WORDS * 34,
+            FB_OFFSET + FB_WIDTH_WORDS * 34,
+            FB_SIZE - FB_WIDTH_WORDS * 34,
+        );
         self.redraw();
     }
 
@@ -296,57 +330,62 @@ impl XousDisplay {
 
     pub fn screen_size(&self) -> Point { Point::new(FB_WIDTH_PIXELS as i16, FB_LINES as i16) }
 
+    /// Sends the lines that changed since the last redraw to the LCD.
+    ///
+    /// Drawing operations flag every line they touch as dirty, but a canvas is usually redrawn
+    /// wholesale to update just a few pixels, like a blinking cursor. Dirty lines are compared against
+    /// `shown`, and only the ones whose pixels actually differ are queued for DMA.
     pub fn redraw(&mut self) {
         let mut busy_count = 0;
         let mut dirty_count = 0;
+        let mut changed_count = 0;
         while self.busy() {
             xous::yield_slice();
             busy_count += 1;
         }
-        // check if a line is dirty; if so, copy it to the DMA buffer, then mark it as clean.
         for line_no in 0..FB_LINES {
-            // check an immutably borrowed copy of the soft framebuffer to see if the line is dirty,
-            // and store the result.
-            let is_dirty = if self.fb[line_no * FB_WIDTH_WORDS + (FB_WIDTH_WORDS - 1)] & 0xFFFF_0000 != 0x0 {
-                true
-            } else {
-                false
-            };
-            // dirty check is split from the line update to avoid re-borrowing the immutable borrow that was
-            // needed to check the dirty state.
-            if is_dirty {
-                // this borrows self to copy the line data to the DMA buffer
+            let line = line_no * FB_WIDTH_WORDS..(line_no + 1) * FB_WIDTH_WORDS;
+            let (fb, shown) = self.fb_and_shown();
+            if fb[line.end - 1] & 0xFFFF_0000 == 0x0 {
+                continue;
+            }
+            fb[line.end - 1] &= 0x0000_FFFF;
+            dirty_count += 1;
+            if fb[line.clone()] != shown[line.clone()] {
+                shown[line.clone()].copy_from_slice(&fb[line]);
                 self.copy_line_to_dma(line_no);
-                // this borrows self.fb to clear the dirty flag on the soft framebuffer
-                // this code is safe because u32 is representable on the system
-                self.fb[line_no * FB_WIDTH_WORDS + (FB_WIDTH_WORDS - 1)] &= 0x0000_FFFF;
-                dirty_count += 1;
+                changed_count += 1;
             }
         }
         self.update_dirty();
-        log::trace!("redraw {}/{}", busy_count, dirty_count);
+        log::trace!("redraw {}/{}/{}", busy_count, dirty_count, changed_count);
     }
 
     pub fn native_buffer(&mut self) -> &mut [u32; FB_SIZE] {
-        unsafe { &mut *(self.fb.as_mut_ptr() as *mut [u32; FB_SIZE]) }
+        unsafe { &mut *(self.fb_mut().as_mut_ptr() as *mut [u32; FB_SIZE]) }
     }
 
     pub fn blit_screen(&mut self, bmp: &[u32]) {
         // this code is safe because u32 is representable on the system
         // copy to the soft frame buffer
-        self.fb[..bmp.len()].copy_from_slice(bmp);
+        self.fb_mut()[..bmp.len()].copy_from_slice(bmp);
         // now copy for DMA
         for line_no in 0..FB_LINES {
+            // every line is sent, so none of them need to be flagged as dirty anymore
+            self.fb_mut()[(line_no + 1) * FB_WIDTH_WORDS - 1] &= 0x0000_FFFF;
             self.copy_line_to_dma(line_no);
         }
+        // the lines went to the SPI buffer, so the panel transfer started here doesn't read the frame
+        // buffers, and `shown` is brought up to date by the PL230 while the SPI DMA sends the lines out
         self.update_dirty();
+        self.copy_words(FB_OFFSET, SHOWN_OFFSET, FB_SIZE);
 
         while self.busy() {}
     }
 
     pub fn as_slice(&self) -> &[u32] {
         // Safety: all values of `[u32]` are valid
-        &self.fb[..FB_SIZE]
+        self.fb()
     }
 
     /// Beneath this line are pure-HAL layer, and should not be user-visible
@@ -363,7 +402,7 @@ impl XousDisplay {
                 | (((src_line as u32) << 6) | 0b001) << 16;
         // now copy the data
         hwfb[(self.next_free_line + 1) * FB_WIDTH_WORDS..(self.next_free_line + 2) * FB_WIDTH_WORDS]
-            .copy_from_slice(&self.fb[src_line * FB_WIDTH_WORDS..(src_line + 1) * FB_WIDTH_WORDS]);
+            .copy_from_slice(&self.dma.as_slice()[FB_OFFSET + src_line * FB_WIDTH_WORDS..][..FB_WIDTH_WORDS]);
         if self.devboot && src_line == 7 {
             for w in hwfb
                 [(self.next_free_line + 1) * FB_WIDTH_WORDS..(self.next_free_line + 2) * FB_WIDTH_WORDS]
@@ -406,8 +445,12 @@ impl XousDisplay {
 
     pub fn set_devboot(&mut self, ena: bool) {
         // one-way door (set-only)
-        if ena {
+        if ena && !self.devboot {
             self.devboot = ena;
+            // the devboot hash is applied as line 7 is sent, so force it out again
+            let (fb, shown) = self.fb_and_shown();
+            fb[8 * FB_WIDTH_WORDS - 1] |= 0x1_0000;
+            shown[8 * FB_WIDTH_WORDS - 1] |= 0xFFFF_0000;
         }
     }
 }
@@ -76,7 +76,7 @@ struct MinifbThread {
 }
 
 struct XousKeyboardHandler {
-    kbd: keyboard::Keyboard,
+    kbd: Arc<keyboard::Keyboard>,
     left_shift: bool,
     right_shift: bool,
 }
@@ -169,7 +169,9 @@ impl MinifbThread {
         window.set_target_fps(MAX_FPS);
 
         let xns = xous_names::XousNames::new().unwrap();
-        let kbd = keyboard::Keyboard::new(&xns).expect("GFX|hosted can't connect to KBD for emulation");
+        let kbd =
+            Arc::new(keyboard::Keyboard::new(&xns).expect("GFX|hosted can't connect to KBD for emulation"));
+        super::automation::start(Arc::clone(&kbd), Arc::clone(&self.native_buffer), DARK_COLOUR);
         let keyboard_handler = Box::new(XousKeyboardHandler { kbd, left_shift: false, right_shift: false });
         window.set_input_callback(keyboard_handler);
 
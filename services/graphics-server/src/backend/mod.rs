@@ -1,4 +1,6 @@
 #[cfg(all(not(target_os = "xous")))]
+mod automation;
+#[cfg(all(not(target_os = "xous")))]
 mod minifb;
 #[cfg(all(not(target_os = "xous")))]
 pub use crate::backend::minifb::*;
@@ -17,7 +17,7 @@ pub const NULL_GLYPH_SPRITE: GlyphSprite = GlyphSprite {
     ch: '\u{0}',
     invert: false,
     insert: false,
-    double: false,
+    scale: 1,
     large: false,
 };
 
@@ -309,6 +309,134 @@ pub fn xor_glyph_2x(fb: &mut FrBuf, p: &Point, gs: GlyphSprite, xor: bool, cr: C
     }
 }
 
+/// Largest scale factor `xor_glyph_scaled()` renders; 16px sprites become 64px, one u64 per row
+pub const MAX_SCALE: usize = 4;
+
+/// Blit a glyph scaled up by `gs.scale` with XOR at point; caller is responsible for word wrap.
+///
+/// This takes 16px sprites as input, like xor_glyph(), and blits them `gs.scale` times as large, up to
+/// `MAX_SCALE`. Plain pixel replication looks like a staircase at 3x and up, so the diagonals are
+/// smoothed on the way; see `scale_rows()`.
+pub fn xor_glyph_scaled(fb: &mut FrBuf, p: &Point, gs: GlyphSprite, xor: bool, cr: ClipRect) {
+    const SPRITE_PX: i16 = 16;
+    const SPRITE_WORDS: i16 = 8;
+    if gs.glyph.len() < SPRITE_WORDS as usize {
+        // Fail silently if the glyph slice was too small
+        return;
+    }
+    let scale = gs.scale as i16;
+    if scale < 1 || scale > MAX_SCALE as i16 {
+        return;
+    }
+    let high = gs.high as i16 / scale;
+    let wide = (gs.wide as i16 / scale).max(1);
+    if high > SPRITE_PX || wide > SPRITE_PX {
+        // Fail silently if glyph height or width is out of spec
+        return;
+    }
+    let x0 = p.x;
+    let x1 = p.x + wide * scale - 1;
+    if x0 >= cr.max.x as i16 || x1 < cr.min.x as i16 {
+        log::trace!("out the side");
+        return;
+    }
+    // like the other blitters, glyphs that cross the edges of the screen are not drawn
+    if x0 < 0 || x1 >= WIDTH as i16 {
+        log::trace!("absolute x/y fail");
+        return;
+    }
+    let rows = scale_rows(gs.glyph, high as usize, scale as usize);
+    // mask off the columns that fall outside the clip rectangle, relative to the first word written
+    let first_word = x0 >> 5;
+    let xa = x0.max(cr.min.x as i16) - (first_word << 5);
+    let xb = x1.min(cr.max.x as i16 - 1) - (first_word << 5);
+    let clip_mask = ((1u128 << (xb + 1)) - 1) & !((1u128 << xa) - 1);
+
+    let mut row_base = p.y * WORDS_PER_LINE as i16;
+    let row_upper_limit = cr.max.y as i16 * WORDS_PER_LINE as i16;
+    let row_lower_limit = cr.min.y as i16 * WORDS_PER_LINE as i16;
+    for row in rows.iter().take((high * scale) as usize) {
+        if row_base >= row_upper_limit {
+            // Clip anything that would run off the end of the frame buffer
+            break;
+        }
+        if row_base >= row_lower_limit {
+            let bits = ((*row as u128) << (x0 & 0x1f)) & clip_mask;
+            // a 64px row spans at most three words
+            for w in 0..3 {
+                let word = (bits >> (w * 32)) as u32;
+                if word != 0 {
+                    let index = (row_base + first_word + w as i16) as usize;
+                    if xor {
+                        fb[index] ^= word;
+                    } else {
+                        fb[index] &= 0xffff_ffff ^ word;
+                    }
+                }
+            }
+            fb[(row_base as usize + WORDS_PER_LINE - 1) as usize] |= 0x1_0000; // set the dirty bit on the line
+        }
+        // Advance destination offset using + instead of * to maybe save some CPU cycles
+        row_base += WORDS_PER_LINE as i16;
+    }
+}
+
+/// Scales the first `high` rows of a 16px sprite up by `scale`, into one u64 per output row, with the
+/// leftmost pixel in the LSB as in the frame buffer.
+///
+/// Each source pixel becomes a `scale` x `scale` block. A clear pixel whose neighbours on two adjacent
+/// sides are set, and whose other two neighbours are clear, is the inside of a diagonal step: its
+/// block gets the triangle in that corner filled, which turns staircases into 45 degree edges. This is
+/// the rule of the EPX/Scale2x family, generalized to larger blocks.
+pub fn scale_rows(glyph: &[u32], high: usize, scale: usize) -> [u64; 16 * MAX_SCALE] {
+    let mut rows = [0u64; 16 * MAX_SCALE];
+    let pixel = |x: i32, y: i32| -> bool {
+        if x < 0 || y < 0 || x >= 16 || y >= high as i32 {
+            false
+        } else {
+            (glyph[y as usize >> 1] >> (((y as u32 & 1) << 4) + x as u32)) & 1 != 0
+        }
+    };
+    let s = scale as i32;
+    for y in 0..high as i32 {
+        for x in 0..16 {
+            if pixel(x, y) {
+                let block = ((1u64 << s) - 1) << (x * s);
+                for row in rows[(y * s) as usize..((y + 1) * s) as usize].iter_mut() {
+                    *row |= block;
+                }
+                continue;
+            }
+            if s < 2 {
+                continue;
+            }
+            let (up, down, left, right) =
+                (pixel(x, y - 1), pixel(x, y + 1), pixel(x - 1, y), pixel(x + 1, y));
+            // (corner is set, the sides that must be clear for it to be a step, corner x, corner y)
+            for (set, clear, cx, cy) in [
+                (up && left, !down && !right, 0, 0),
+                (up && right, !down && !left, s - 1, 0),
+                (down && left, !up && !right, 0, s - 1),
+                (down && right, !up && !left, s - 1, s - 1),
+            ] {
+                if !(set && clear) {
+                    continue;
+                }
+                // fill the pixels of the block that are closer to the corner than the block's
+                // anti-diagonal, which is a triangle with legs of `s - 1` pixels
+                for j in 0..s {
+                    for i in 0..s {
+                        if (i - cx).abs() + (j - cy).abs() < s - 1 {
+                            rows[(y * s + j) as usize] |= 1u64 << (x * s + i);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    rows
+}
+
 /// Lookup table to speed up 2x scaling by expanding u8 index to u16 value
 pub const LUT_2X: [u16; 256] = [
     0b0000000000000000,
@@ -568,3 +696,43 @@ pub const LUT_2X: [u16; 256] = [
     0b1111111111111100,
     0b1111111111111111,
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Packs rows of a sprite, given as strings with '#' for set pixels, the way the fonts store them
+    fn sprite(rows: &[&str]) -> [u32; 8] {
+        let mut glyph = [0u32; 8];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    glyph[y >> 1] |= 1 << (((y & 1) << 4) + x);
+                }
+            }
+        }
+        glyph
+    }
+
+    fn render(rows: &[u64], high: usize, wide: usize) -> Vec<String> {
+        rows[..high]
+            .iter()
+            .map(|row| (0..wide).map(|x| if row >> x & 1 != 0 { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn square_scales_by_replication() {
+        let glyph = sprite(&["##", "##"]);
+        assert_eq!(render(&scale_rows(&glyph, 2, 3), 6, 7), vec!["######."; 6]);
+    }
+
+    #[test]
+    fn diagonal_is_smoothed() {
+        let glyph = sprite(&["#.", ".#"]);
+        assert_eq!(
+            render(&scale_rows(&glyph, 2, 3), 6, 6),
+            vec!["###...", "####..", "#####.", ".#####", "..####", "...###"]
+        );
+    }
+}
@@ -30,7 +30,7 @@ pub fn small_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: false,
+                    scale: 1,
                     large: false,
                 }),
                 false => Err(0),
@@ -54,7 +54,7 @@ pub fn regular_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: false,
+                    scale: 1,
                     large: false,
                 }),
                 false => Err(0),
@@ -78,7 +78,7 @@ pub fn large_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: true,
+                    scale: 2,
                     large: false,
                 }),
                 false => Err(0),
@@ -102,7 +102,7 @@ pub fn extra_large_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: true,
+                    scale: 2,
                     large: false,
                 }),
                 false => Err(0),
@@ -112,6 +112,15 @@ pub fn extra_large_glyph(ch: char) -> Result<GlyphSprite, usize> {
     }
 }
 
+pub fn huge_glyph(ch: char) -> Result<GlyphSprite, usize> { scaled_regular_glyph(ch, 3) }
+
+pub fn giant_glyph(ch: char) -> Result<GlyphSprite, usize> { scaled_regular_glyph(ch, 4) }
+
+/// The regular font at 3x and up, for text that has to be read from a distance, like codes and clocks
+fn scaled_regular_glyph(ch: char, scale: u8) -> Result<GlyphSprite, usize> {
+    regular_glyph(ch).map(|gs| GlyphSprite { wide: gs.wide * scale, high: gs.high * scale, scale, ..gs })
+}
+
 pub fn bold_glyph(ch: char) -> Result<GlyphSprite, usize> {
     match bold::CODEPOINTS.binary_search(&(ch as u32)) {
         Ok(n) => {
@@ -126,7 +135,7 @@ pub fn bold_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: false,
+                    scale: 1,
                     large: false,
                 }),
                 false => Err(0),
@@ -150,7 +159,7 @@ pub fn mono_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: false,
+                    scale: 1,
                     large: false,
                 }),
                 false => Err(0),
@@ -174,7 +183,7 @@ pub fn tall_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: false,
+                    scale: 1,
                     large: true,
                 }),
                 false => Err(0),
@@ -198,7 +207,7 @@ pub fn emoji_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: false,
+                    scale: 1,
                     large: false,
                 }),
                 false => Err(0),
@@ -222,7 +231,7 @@ pub fn emoji_large_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: true,
+                    scale: 2,
                     large: false,
                 }),
                 false => Err(0),
@@ -246,7 +255,7 @@ pub fn zh_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: false,
+                    scale: 1,
                     large: false,
                 }),
                 false => Err(0),
@@ -270,7 +279,7 @@ pub fn ja_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: false,
+                    scale: 1,
                     large: false,
                 }),
                 false => Err(0),
@@ -294,7 +303,7 @@ pub fn kr_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     ch,
                     invert: false,
                     insert: false,
-                    double: false,
+                    scale: 1,
                     large: false,
                 }),
                 false => Err(0),
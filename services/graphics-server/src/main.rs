@@ -162,6 +162,7 @@ fn main() -> ! {
 }
 fn wrapped_main(main_thread_token: backend::MainThreadToken) -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
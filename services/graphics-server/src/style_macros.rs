@@ -21,6 +21,12 @@ macro_rules! style_wrapper {
             GlyphStyle::Tall => {
                 $rule!(tall_glyph, emoji_glyph, $ch)
             }
+            GlyphStyle::Huge => {
+                $rule!(huge_glyph, emoji_large_glyph, $ch)
+            }
+            GlyphStyle::Giant => {
+                $rule!(giant_glyph, emoji_large_glyph, $ch)
+            }
             // default to regular
             _ => {
                 $rule!(regular_glyph, emoji_glyph, $ch)
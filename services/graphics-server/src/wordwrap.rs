@@ -148,7 +148,7 @@ impl ComposedType {
                             glyph.invert ^ invert,
                             cr,
                         );
-                    } else if !glyph.double {
+                    } else if glyph.scale <= 1 {
                         blitstr2::xor_glyph(
                             frbuf,
                             &Point::new(maybe_x, maybe_y),
@@ -156,7 +156,7 @@ impl ComposedType {
                             glyph.invert ^ invert,
                             cr,
                         );
-                    } else {
+                    } else if glyph.scale == 2 {
                         blitstr2::xor_glyph_2x(
                             frbuf,
                             &Point::new(maybe_x, maybe_y),
@@ -164,6 +164,14 @@ impl ComposedType {
                             glyph.invert ^ invert,
                             cr,
                         );
+                    } else {
+                        blitstr2::xor_glyph_scaled(
+                            frbuf,
+                            &Point::new(maybe_x, maybe_y),
+                            *glyph,
+                            glyph.invert ^ invert,
+                            cr,
+                        );
                     }
                     if glyph.insert {
                         // log::info!("insert at {},{}", glyph.ch, strpos - 1);
@@ -625,7 +633,7 @@ fn tsw_debug(tsw: &TypesetWord) {
 
 /// Find glyph for char using latin regular, emoji, ja, zh, and kr font data
 pub fn style_glyph(ch: char, base_style: &GlyphStyle) -> GlyphSprite {
-    match locales::LANG {
+    match locales::LANG.get() {
         "zh" => {
             style_wrapper!(zh_rules, base_style, ch)
         }
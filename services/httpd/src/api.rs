@@ -0,0 +1,30 @@
+pub(crate) const SERVER_NAME_HTTPD: &str = "_HTTP status server_";
+
+/// Port served when none is given
+pub const HTTPD_DEFAULT_PORT: u16 = 8080;
+/// Length of the API token, in hex characters
+pub const HTTPD_TOKEN_LEN: usize = 32;
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// Start serving on the port in arg1, and resume serving after every boot. Blocking scalar, returns 0
+    /// or a `xous::Error`.
+    Enable = 0,
+    /// Stop serving, and stay stopped after a reboot. Blocking scalar.
+    Disable = 1,
+    /// The port being served, or 0 if the server is off. Blocking scalar.
+    Port = 2,
+    /// Read the API token, creating it if there is none. Memory, lend_mut of `Token`.
+    Token = 3,
+    /// Internal: the PDDB is mounted, so a previous `Enable` can be resumed. Scalar.
+    Resume = 4,
+    /// Exits the server
+    Quit = 5,
+}
+
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Token {
+    /// replace the token with a new random one, so that clients holding the old one are locked out
+    pub renew: bool,
+    pub token: xous_ipc::String<HTTPD_TOKEN_LEN>,
+}
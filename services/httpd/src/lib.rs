@@ -0,0 +1,79 @@
+pub mod api;
+pub use api::*;
+use num_traits::*;
+use xous::{CID, Message, send_message};
+use xous_ipc::Buffer;
+
+/// Client API to the HTTP status server, to switch it on or off and to manage its API token. The server
+/// takes a single connection, which is meant for shellchat.
+#[derive(Debug)]
+pub struct Httpd {
+    conn: CID,
+}
+impl Httpd {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_HTTPD)?;
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(Httpd { conn })
+    }
+
+    /// Starts serving on `port`. The server keeps being started on every boot, once the PDDB is mounted,
+    /// until it is `disable()`d.
+    pub fn enable(&self, port: u16) -> Result<(), xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::Enable.to_usize().unwrap(), port as usize, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar1(0)) => Ok(()),
+            Ok(xous::Result::Scalar1(code)) => Err(xous::Error::from_usize(code)),
+            Ok(_) => Err(xous::Error::InternalError),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stops serving, and forgets to serve after a reboot
+    pub fn disable(&self) -> Result<(), xous::Error> {
+        send_message(self.conn, Message::new_blocking_scalar(Opcode::Disable.to_usize().unwrap(), 0, 0, 0, 0))
+            .map(|_| ())
+    }
+
+    /// The port being served, or `None` if the server is off
+    pub fn port(&self) -> Result<Option<u16>, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::Port.to_usize().unwrap(), 0, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar1(0)) => Ok(None),
+            Ok(xous::Result::Scalar1(port)) => Ok(Some(port as u16)),
+            Ok(_) => Err(xous::Error::InternalError),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The token that clients must present to use the API. With `renew`, a new random token replaces
+    /// the old one first.
+    pub fn token(&self, renew: bool) -> Result<String, xous::Error> {
+        let token = Token { renew, token: xous_ipc::String::new() };
+        let mut buf = Buffer::into_buf(token).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::Token.to_u32().unwrap())?;
+        let token = buf.to_original::<Token, _>().or(Err(xous::Error::InternalError))?;
+        match token.token.as_str() {
+            Ok(s) if s.len() > 0 => Ok(s.to_string()),
+            _ => Err(xous::Error::AccessDenied),
+        }
+    }
+}
+
+use core::sync::atomic::{AtomicU32, Ordering};
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Httpd {
+    fn drop(&mut self) {
+        // now de-allocate myself. It's unsafe because we are responsible to make sure nobody else is using
+        // the connection.
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,182 @@
+mod api;
+use api::*;
+mod server;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+use num_traits::*;
+use xous_ipc::Buffer;
+
+/// PDDB dict holding the server settings
+const HTTPD_DICT: &str = "httpd";
+/// The port to serve on. The key is absent while the server is off.
+const PORT_KEY: &str = "port";
+const TOKEN_KEY: &str = "token";
+
+fn read_setting(pddb: &pddb::Pddb, key: &str) -> Option<String> {
+    let mut value = Vec::new();
+    match pddb.get(HTTPD_DICT, key, None, false, false, None, None::<fn()>) {
+        Ok(mut pddb_key) => pddb_key.read_to_end(&mut value).ok()?,
+        Err(_) => return None,
+    };
+    String::from_utf8(value).ok()
+}
+
+fn write_setting(pddb: &pddb::Pddb, key: &str, value: &str) -> std::io::Result<()> {
+    // delete key first to ensure data in a prior longer key is gone
+    pddb.delete_key(HTTPD_DICT, key, None).ok();
+    let mut pddb_key = pddb.get(HTTPD_DICT, key, None, true, true, None, None::<fn()>)?;
+    pddb_key.write_all(value.as_bytes())?;
+    pddb.sync()
+}
+
+/// Returns the API token, creating a new random one if there is none or `renew` is set.
+fn load_token(pddb: &pddb::Pddb, trng: &trng::Trng, renew: bool) -> Option<String> {
+    if !renew {
+        if let Some(token) = read_setting(pddb, TOKEN_KEY).filter(|token| token.len() == HTTPD_TOKEN_LEN) {
+            return Some(token);
+        }
+    }
+    let mut token = String::with_capacity(HTTPD_TOKEN_LEN);
+    while token.len() < HTTPD_TOKEN_LEN {
+        token.push_str(&format!("{:016x}", trng.get_u64().ok()?));
+    }
+    token.truncate(HTTPD_TOKEN_LEN);
+    match write_setting(pddb, TOKEN_KEY, &token) {
+        Ok(()) => Some(token),
+        Err(e) => {
+            log::warn!("couldn't store the API token: {:?}", e);
+            None
+        }
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    // the one connection is shellchat's: Enable, Disable and Token control the server and hand out its
+    // API token, so no other process may connect
+    let httpd_sid = xns.register_name(api::SERVER_NAME_HTTPD, Some(1)).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", httpd_sid);
+    let self_cid = xous::connect(httpd_sid).unwrap();
+    // a server that parses requests from the network is the likeliest to fall over
+    let supervisor = supervisor::Supervisor::new(&xns).unwrap();
+    match supervisor.supervise("httpd", supervisor::RestartPolicy::Backoff) {
+        Ok(policy) => log::info!("supervised, {:?} on exit", policy),
+        Err(e) => log::warn!("couldn't sign up for supervision: {:?}", e),
+    }
+
+    let pddb = pddb::Pddb::new();
+    let trng = trng::Trng::new(&xns).unwrap();
+    // shared with the listener thread, so that a renewed token takes effect right away
+    let token = Arc::new(Mutex::new(String::new()));
+    let mut running: Option<server::Server> = None;
+
+    // the settings can only be read once the PDDB is mounted
+    std::thread::spawn(move || {
+        let pddb = pddb::Pddb::new();
+        pddb.is_mounted_blocking();
+        xous::send_message(
+            self_cid,
+            xous::Message::new_scalar(Opcode::Resume.to_usize().unwrap(), 0, 0, 0, 0),
+        )
+        .ok();
+    });
+
+    loop {
+        let mut msg = xous::receive_message(httpd_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Resume) => {
+                if let Some(port) = read_setting(&pddb, PORT_KEY).and_then(|port| port.parse::<u16>().ok()) {
+                    match load_token(&pddb, &trng, false) {
+                        Some(t) => {
+                            *token.lock().unwrap() = t;
+                            match server::Server::start(port, token.clone()) {
+                                Ok(server) => running = Some(server),
+                                Err(e) => log::warn!("couldn't resume serving on port {}: {:?}", port, e),
+                            }
+                        }
+                        None => log::warn!("no API token, not resuming"),
+                    }
+                }
+            }
+            Some(Opcode::Enable) => xous::msg_blocking_scalar_unpack!(msg, port, _, _, _, {
+                if let Some(server) = running.take() {
+                    server.stop();
+                }
+                let result = if port == 0 || port > u16::MAX as usize {
+                    xous::Error::InvalidLimit.to_usize()
+                } else {
+                    match load_token(&pddb, &trng, false) {
+                        Some(t) => {
+                            *token.lock().unwrap() = t;
+                            match server::Server::start(port as u16, token.clone()) {
+                                Ok(server) => {
+                                    running = Some(server);
+                                    write_setting(&pddb, PORT_KEY, &port.to_string()).ok();
+                                    0
+                                }
+                                Err(e) => {
+                                    log::warn!("couldn't serve on port {}: {:?}", port, e);
+                                    xous::Error::InternalError.to_usize()
+                                }
+                            }
+                        }
+                        // the token can't be kept without a mounted PDDB
+                        None => xous::Error::AccessDenied.to_usize(),
+                    }
+                };
+                xous::return_scalar(msg.sender, result).ok();
+            }),
+            Some(Opcode::Disable) => {
+                if let Some(server) = running.take() {
+                    server.stop();
+                }
+                pddb.delete_key(HTTPD_DICT, PORT_KEY, None).ok();
+                pddb.sync().ok();
+                xous::return_scalar(msg.sender, 0).ok();
+            }
+            Some(Opcode::Port) => {
+                let port = running.as_ref().map(|server| server.port()).unwrap_or(0);
+                xous::return_scalar(msg.sender, port as usize).ok();
+            }
+            Some(Opcode::Token) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("httpd Token was not a memory message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(mut request) = buffer.to_original::<Token, _>() else {
+                    log::error!("couldn't deserialize a Token request");
+                    continue;
+                };
+                request.token.clear();
+                if let Some(t) = load_token(&pddb, &trng, request.renew) {
+                    request.token.append(&t).ok();
+                    *token.lock().unwrap() = t;
+                }
+                buffer.replace(request).ok();
+            }
+            Some(Opcode::Quit) => {
+                log::warn!("Quit received, goodbye world!");
+                supervisor.release().ok();
+                break;
+            }
+            None => {
+                log::error!("couldn't convert opcode: {:?}", msg);
+            }
+        }
+    }
+    // clean up our program
+    if let Some(server) = running.take() {
+        server.stop();
+    }
+    log::trace!("main loop exit, destroying servers");
+    xns.unregister_server(httpd_sid).unwrap();
+    xous::destroy_server(httpd_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use locales::t;
+
+/// Requests with a longer head are refused; no route takes a body
+const MAX_REQUEST_BYTES: usize = 2048;
+/// A client that takes longer than this to send its request is dropped, so that it can't hold up the
+/// others
+const CLIENT_TIMEOUT_MS: u64 = 5000;
+/// After a failed authentication, the API refuses the same client for this long, to slow down token
+/// guessing
+const AUTH_FAIL_LOCKOUT_MS: u64 = 1000;
+/// The most clients whose failed authentications are remembered at once
+const AUTH_FAIL_CLIENTS_MAX: usize = 64;
+/// name the server posts status bar notifications under
+const NOTIFICATION_SOURCE: &str = "httpd";
+
+/// A listener thread serving the status page and API on one port
+pub(crate) struct Server {
+    port: u16,
+    stop: Arc<AtomicBool>,
+}
+
+impl Server {
+    pub(crate) fn start(port: u16, token: Arc<Mutex<String>>) -> std::io::Result<Server> {
+        let listener = TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port)))?;
+        let stop = Arc::new(AtomicBool::new(false));
+        std::thread::spawn({
+            let stop = stop.clone();
+            move || serve(listener, token, stop)
+        });
+        log::info!("serving on port {}", port);
+        Ok(Server { port, stop })
+    }
+
+    pub(crate) fn port(&self) -> u16 { self.port }
+
+    pub(crate) fn stop(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // wake the listener out of accept(), so that it sees the stop flag
+        TcpStream::connect(SocketAddr::from(([127, 0, 0, 1], self.port))).ok();
+        log::info!("stopped serving on port {}", self.port);
+    }
+}
+
+fn serve(listener: TcpListener, token: Arc<Mutex<String>>, stop: Arc<AtomicBool>) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let tt = ticktimer_server::Ticktimer::new().unwrap();
+    let status = status::Status::new(&xns).unwrap();
+    let mut com = com::Com::new(&xns).unwrap();
+    let mut lockout = Lockout::default();
+    for stream in listener.incoming() {
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle(stream, &token, &tt, &status, &mut com, &mut lockout) {
+                    log::debug!("client error: {:?}", e);
+                }
+            }
+            Err(e) => log::warn!("accept failed: {:?}", e),
+        }
+    }
+}
+
+/// Only clients on the local network are served: the device is never meant to be reachable from the
+/// internet, even if a router forwards the port.
+fn on_lan(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_link_local() || ip.is_loopback(),
+        IpAddr::V6(ip) => ip.is_loopback(),
+    }
+}
+
+/// The clients that recently failed to authenticate, and when they may try again. A locked out client
+/// is refused straight away, rather than kept waiting, so that it can't hold up the others.
+#[derive(Default)]
+struct Lockout {
+    until_ms: HashMap<IpAddr, u64>,
+}
+
+impl Lockout {
+    fn allows(&self, peer: IpAddr, now_ms: u64) -> bool {
+        self.until_ms.get(&peer).map(|&until| now_ms >= until).unwrap_or(true)
+    }
+
+    fn failed(&mut self, peer: IpAddr, now_ms: u64) {
+        self.until_ms.retain(|_, until| *until > now_ms);
+        if self.until_ms.len() >= AUTH_FAIL_CLIENTS_MAX && !self.until_ms.contains_key(&peer) {
+            // forget the client whose lockout ends soonest
+            if let Some(first) = self.until_ms.iter().min_by_key(|(_, until)| **until).map(|(ip, _)| *ip) {
+                self.until_ms.remove(&first);
+            }
+        }
+        self.until_ms.insert(peer, now_ms + AUTH_FAIL_LOCKOUT_MS);
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    bearer: Option<String>,
+}
+
+fn read_request(stream: &mut impl Read) -> std::io::Result<Option<Request>> {
+    let start = Instant::now();
+    let mut head = Vec::new();
+    let mut buf = [0u8; 256];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if start.elapsed() > Duration::from_millis(CLIENT_TIMEOUT_MS) {
+            return Ok(None);
+        }
+        let len = stream.read(&mut buf)?;
+        if len == 0 || head.len() + len > MAX_REQUEST_BYTES {
+            return Ok(None);
+        }
+        head.extend_from_slice(&buf[..len]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or("").split(' ');
+    let (method, path) = match (request_line.next(), request_line.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Ok(None),
+    };
+    let bearer = lines.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.trim().eq_ignore_ascii_case("authorization") {
+            return None;
+        }
+        let (scheme, credentials) = value.trim().split_once(' ')?;
+        if scheme.eq_ignore_ascii_case("bearer") { Some(credentials.trim().to_string()) } else { None }
+    });
+    Ok(Some(Request { method, path, bearer }))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> std::io::Result<()> {
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    if status.starts_with("401") {
+        response.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+    response.push_str("\r\n");
+    response.push_str(body);
+    stream.write_all(response.as_bytes())
+}
+
+/// Compares in constant time, so that the token can't be recovered from response timings
+fn token_matches(presented: &str, token: &str) -> bool {
+    if token.is_empty() || presented.len() != token.len() {
+        return false;
+    }
+    presented.bytes().zip(token.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn handle(
+    mut stream: TcpStream,
+    token: &Mutex<String>,
+    tt: &ticktimer_server::Ticktimer,
+    status: &status::Status,
+    com: &mut com::Com,
+    lockout: &mut Lockout,
+) -> std::io::Result<()> {
+    let peer = stream.peer_addr()?.ip();
+    if !on_lan(peer) {
+        log::warn!("refused {}, which is not on the local network", peer);
+        return Ok(());
+    }
+    stream.set_read_timeout(Some(Duration::from_millis(CLIENT_TIMEOUT_MS)))?;
+    let request = match read_request(&mut stream)? {
+        Some(request) => request,
+        None => return respond(&mut stream, "400 Bad Request", "text/plain", "bad request\n"),
+    };
+    log::info!("{} {} {}", peer, request.method, request.path);
+
+    let now_ms = tt.elapsed_ms();
+    let allowed = lockout.allows(peer, now_ms);
+    let authorized = match &request.bearer {
+        Some(presented) if allowed => token_matches(presented, &token.lock().unwrap()),
+        _ => false,
+    };
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/status") => respond(&mut stream, "200 OK", "application/json", &status_json(tt, com)),
+        (_, "/status") => respond(&mut stream, "405 Method Not Allowed", "text/plain", "use GET\n"),
+        (_, "/api/logs") | (_, "/api/backup") if !allowed => {
+            respond(&mut stream, "429 Too Many Requests", "text/plain", "try again later\n")
+        }
+        (_, "/api/logs") | (_, "/api/backup") if !authorized => {
+            log::warn!("{} failed to authenticate", peer);
+            lockout.failed(peer, now_ms);
+            respond(&mut stream, "401 Unauthorized", "text/plain", "token required\n")
+        }
+        ("GET", "/api/logs") => {
+            notify(status, &t!("httpd.notify_logs", locales::LANG).replace("{peer}", &peer.to_string()));
+            respond(&mut stream, "200 OK", "text/plain; charset=utf-8", &read_log())
+        }
+        ("POST", "/api/backup") => {
+            notify(status, &t!("httpd.notify_backup", locales::LANG).replace("{peer}", &peer.to_string()));
+            match status.prepare_backup() {
+                Ok(()) => {
+                    respond(&mut stream, "202 Accepted", "text/plain", "confirm the backup on the device\n")
+                }
+                Err(e) => {
+                    log::warn!("couldn't request a backup: {:?}", e);
+                    respond(&mut stream, "503 Service Unavailable", "text/plain", "backup unavailable\n")
+                }
+            }
+        }
+        (_, "/api/backup") => respond(&mut stream, "405 Method Not Allowed", "text/plain", "use POST\n"),
+        (_, "/api/logs") => respond(&mut stream, "405 Method Not Allowed", "text/plain", "use GET\n"),
+        _ => respond(&mut stream, "404 Not Found", "text/plain", "not found\n"),
+    }
+}
+
+/// Every use of the API is shown on the device, so that the owner learns of it
+fn notify(status: &status::Status, text: &str) {
+    if let Err(e) = status.post_notification(NOTIFICATION_SOURCE, text, true) {
+        log::warn!("couldn't post notification: {:?}", e);
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn status_json(tt: &ticktimer_server::Ticktimer, com: &mut com::Com) -> String {
+    let battery = match com.get_batt_stats_blocking() {
+        Ok(stats) => format!(
+            "{{\"soc\":{},\"voltage_mv\":{},\"current_ma\":{},\"remaining_mah\":{}}}",
+            stats.soc, stats.voltage, stats.current, stats.remaining_capacity
+        ),
+        Err(_) => "null".to_string(),
+    };
+    format!(
+        "{{\"version\":\"{}\",\"uptime_ms\":{},\"battery\":{}}}\n",
+        json_escape(&tt.get_version()),
+        tt.elapsed_ms(),
+        battery
+    )
+}
+
+/// The messages in the log server's RAM buffer, oldest first
+fn read_log() -> String {
+    let mut log = String::new();
+    log_server::read_all(|level, text| log.push_str(&format!("{:<5} {}\n", level, text))).ok();
+    log
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr { s.parse().unwrap() }
+
+    #[test]
+    fn only_lan_clients_are_served() {
+        for lan in ["192.168.1.20", "10.0.0.1", "172.16.5.5", "169.254.1.1", "127.0.0.1", "::1"] {
+            assert!(on_lan(ip(lan)), "{}", lan);
+        }
+        for wan in ["8.8.8.8", "172.32.0.1", "2001:db8::1"] {
+            assert!(!on_lan(ip(wan)), "{}", wan);
+        }
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        let token = "0123456789abcdef0123456789abcdef";
+        assert!(token_matches(token, token));
+        assert!(!token_matches("0123456789abcdef0123456789abcdee", token));
+        assert!(!token_matches("0123456789abcdef", token));
+        // no token has been made yet
+        assert!(!token_matches("", ""));
+    }
+
+    #[test]
+    fn requests_are_parsed() {
+        let mut raw: &[u8] = b"GET /api/logs HTTP/1.1\r\nHost: x\r\nauthorization:  bearer abc \r\n\r\n";
+        let request = read_request(&mut raw).unwrap().unwrap();
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("GET", "/api/logs"));
+        assert_eq!(request.bearer.as_deref(), Some("abc"));
+
+        let mut raw: &[u8] = b"GET /status HTTP/1.1\r\nAuthorization: Basic abc\r\n\r\n";
+        assert_eq!(read_request(&mut raw).unwrap().unwrap().bearer, None);
+    }
+
+    #[test]
+    fn bad_requests_are_refused() {
+        // cut short
+        let mut raw: &[u8] = b"GET /status HTTP/1.1\r\n";
+        assert!(read_request(&mut raw).unwrap().is_none());
+        // no path
+        let mut raw: &[u8] = b"GET\r\n\r\n";
+        assert!(read_request(&mut raw).unwrap().is_none());
+        // too long
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_REQUEST_BYTES));
+        assert!(read_request(&mut long.as_bytes()).unwrap().is_none());
+    }
+
+    #[test]
+    fn failed_clients_are_locked_out_for_a_while() {
+        let mut lockout = Lockout::default();
+        let (guesser, other) = (ip("192.168.1.20"), ip("192.168.1.21"));
+        assert!(lockout.allows(guesser, 0));
+        lockout.failed(guesser, 100);
+        assert!(!lockout.allows(guesser, 100 + AUTH_FAIL_LOCKOUT_MS - 1));
+        assert!(lockout.allows(guesser, 100 + AUTH_FAIL_LOCKOUT_MS));
+        // other clients aren't held up
+        assert!(lockout.allows(other, 100));
+    }
+
+    #[test]
+    fn lockouts_are_bounded() {
+        let mut lockout = Lockout::default();
+        for n in 0..(AUTH_FAIL_CLIENTS_MAX as u32 + 10) {
+            lockout.failed(IpAddr::from((0x0a00_0000 + n).to_be_bytes()), n as u64);
+        }
+        assert_eq!(lockout.until_ms.len(), AUTH_FAIL_CLIENTS_MAX);
+    }
+
+    #[test]
+    fn json_is_escaped() {
+        assert_eq!(json_escape("a\"b\\c\nd"), "a\\\"b\\\\c\\u000ad");
+    }
+}
@@ -61,6 +61,12 @@ struct InputTracker {
 
     /// if set to true, the F1-F4 keys work as menu selects, and not as predictive inputs
     menu_mode: bool,
+    /// set while a secret such as a password is being entered, so the predictor doesn't learn from it
+    private: bool,
+    /// index of the history entry recalled into the input line with the up/down keys, if any
+    recall_index: Option<u32>,
+    /// what was typed before history recall started, restored when stepping back down past the newest entry
+    recall_saved: String,
 
     /// render the predictions. Slightly awkward because this code comes from before we had libstd
     pred_options: [Option<String>; MAX_PREDICTION_OPTIONS],
@@ -88,6 +94,9 @@ impl InputTracker {
             was_grown: false,
             pred_options: Default::default(),
             menu_mode: false,
+            private: false,
+            recall_index: None,
+            recall_saved: String::new(),
             #[cfg(feature = "tts")]
             tts: TtsFrontend::new(xns).unwrap(),
         }
@@ -135,6 +144,19 @@ impl InputTracker {
 
     pub fn set_menu_mode(&mut self, mode: bool) { self.menu_mode = mode; }
 
+    pub fn set_private_mode(&mut self, private: bool) { self.private = private; }
+
+    /// Tells the predictor that `s` was entered, so it can learn from it; unless it's a secret
+    fn feedback_picked(&self, s: &str) {
+        if self.private {
+            return;
+        }
+        if let Some(pred) = self.predictor {
+            pred.feedback_picked(xous_ipc::String::<4000>::from_str(s))
+                .expect("couldn't send feedback to predictor");
+        }
+    }
+
     pub fn clear_area(&mut self) -> Result<(), xous::Error> {
         if let Some(pc) = self.pred_canvas {
             let pc_bounds: Point =
@@ -271,6 +293,41 @@ impl InputTracker {
         }
     }
 
+    /// Replaces the contents of the input line, leaving the insertion point at the end.
+    fn replace_line(&mut self, s: &str) {
+        self.line.clear();
+        self.line.push_str(s);
+        self.characters = self.line.chars().count();
+        self.insertion = self.characters;
+        self.last_trigger_char = Some(self.characters);
+        self.pred_phrase.clear();
+        self.can_unpick = false;
+    }
+
+    /// Recalls the `index`th most recent entry of the predictor's history into the input line. Only
+    /// predictors that are triggered solely by newlines (such as the shell) predict whole lines, so
+    /// recall is not available for other predictors. Returns `true` if an entry was recalled.
+    fn recall_history(&mut self, index: u32, api_token: [u32; 4]) -> bool {
+        let line_predictor =
+            self.pred_triggers.map_or(false, |t| t.newline && !t.punctuation && !t.whitespace);
+        if !line_predictor {
+            return false;
+        }
+        if let Some(pred) = self.predictor {
+            if let Some(entry) =
+                pred.get_prediction(index, api_token).expect("couldn't query prediction engine")
+            {
+                if self.recall_index.is_none() {
+                    self.recall_saved = self.line.clone();
+                }
+                self.replace_line(entry.as_str().unwrap_or("UTF-8 Error"));
+                self.recall_index = Some(index);
+                return true;
+            }
+        }
+        false
+    }
+
     pub fn update(
         &mut self,
         newkeys: [char; 4],
@@ -297,6 +354,10 @@ impl InputTracker {
                 if debug1 {
                     info!("got key '{}'", k);
                 }
+                if k != '↑' && k != '↓' && k != '\u{0000}' {
+                    // any other key ends history recall, leaving the recalled line in place for editing
+                    self.recall_index = None;
+                }
                 match k {
                     '\u{0000}' => (),
                     '←' => {
@@ -329,6 +390,16 @@ impl InputTracker {
                     }
                     '↑' => {
                         if !self.menu_mode {
+                            // from the front of the text box, or if already recalling, step back through
+                            // the history. The predictor filters its history by what was typed, so this
+                            // also searches the history for earlier lines containing the typed text.
+                            if self.insertion == 0 || self.recall_index.is_some() {
+                                let index = self.recall_index.map_or(0, |i| i + 1);
+                                if self.recall_history(index, api_token) {
+                                    do_redraw = true;
+                                    continue;
+                                }
+                            }
                             // bring the insertion point to the front of the text box
                             self.insertion = 0;
                             do_redraw = true;
@@ -341,6 +412,17 @@ impl InputTracker {
                     }
                     '↓' => {
                         if !self.menu_mode {
+                            // step forward through the recalled history, restoring the original line after
+                            // the newest entry
+                            if let Some(index) = self.recall_index {
+                                if index == 0 || !self.recall_history(index - 1, api_token) {
+                                    let saved = core::mem::take(&mut self.recall_saved);
+                                    self.replace_line(&saved);
+                                    self.recall_index = None;
+                                }
+                                do_redraw = true;
+                                continue;
+                            }
                             // bring insertion point to the very end of the text box
                             self.insertion = self.characters;
                             do_redraw = true;
@@ -477,15 +559,9 @@ impl InputTracker {
 
                         if let Some(trigger) = self.pred_triggers {
                             if trigger.newline {
-                                self.predictor
-                                    .unwrap()
-                                    .feedback_picked(xous_ipc::String::<4000>::from_str(&self.line))
-                                    .expect("couldn't send feedback to predictor");
+                                self.feedback_picked(&self.line);
                             } else if trigger.punctuation {
-                                self.predictor
-                                    .unwrap()
-                                    .feedback_picked(xous_ipc::String::<4000>::from_str(&self.pred_phrase))
-                                    .expect("couldn't send feedback to predictor");
+                                self.feedback_picked(&self.pred_phrase);
                             }
                         }
                         self.can_unpick = false;
@@ -526,12 +602,7 @@ impl InputTracker {
                         if let Some(trigger) = self.pred_triggers {
                             if trigger.whitespace && k.is_ascii_whitespace() {
                                 if self.pred_phrase.len() > 0 {
-                                    self.predictor
-                                        .unwrap()
-                                        .feedback_picked(xous_ipc::String::<4000>::from_str(
-                                            &self.pred_phrase,
-                                        ))
-                                        .expect("couldn't send feedback to predictor");
+                                    self.feedback_picked(&self.pred_phrase);
                                     self.pred_phrase.clear();
                                     self.can_unpick = true;
                                     update_predictor = true;
@@ -539,12 +610,7 @@ impl InputTracker {
                                 self.last_trigger_char = Some(self.insertion);
                             } else if trigger.punctuation && k.is_ascii_punctuation() {
                                 if self.pred_phrase.len() > 0 {
-                                    self.predictor
-                                        .unwrap()
-                                        .feedback_picked(xous_ipc::String::<4000>::from_str(
-                                            &self.pred_phrase,
-                                        ))
-                                        .expect("couldn't send feedback to predictor");
+                                    self.feedback_picked(&self.pred_phrase);
                                     self.pred_phrase.clear();
                                     self.can_unpick = true;
                                     update_predictor = true;
@@ -698,7 +764,8 @@ impl InputTracker {
             }
 
             if update_predictor {
-                if self.pred_phrase.len() > 0 || self.menu_mode {
+                // an empty line is also sent, so predictors that filter on the input are reset
+                if self.pred_phrase.len() > 0 || self.menu_mode || self.characters == 0 {
                     if let Some(pred) = self.predictor {
                         pred.set_input(xous_ipc::String::<4000>::from_str(&self.pred_phrase))
                             .expect("couldn't update predictor with current input");
@@ -806,6 +873,7 @@ fn main() -> ! {
     let dbglistener = false;
     let dbgcanvas = false;
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     info!("my PID is {}", xous::process::id());
 
@@ -975,6 +1043,9 @@ fn main() -> ! {
                     tracker.set_menu_mode(false);
                 }
             }),
+            Some(ImefOpcode::SetPrivateMode) => msg_scalar_unpack!(msg, arg, _, _, _, {
+                tracker.set_private_mode(arg != 0);
+            }),
             Some(ImefOpcode::Quit) => {
                 log::error!("recevied quit, goodbye!");
                 break;
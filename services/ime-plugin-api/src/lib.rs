@@ -250,6 +250,9 @@ pub enum ImefOpcode {
     /// set menu mode -- this turns the predictor area into a menu buttons-like behavior
     SetMenuMode,
 
+    /// set private mode -- while set, what is entered is a secret, so it isn't fed back to the predictor
+    SetPrivateMode,
+
     /// force a redraw of the UI
     Redraw,
 
@@ -278,9 +281,13 @@ pub trait ImeFrontEndApi {
     fn conn(&self) -> xous::CID;
     fn getop_process_keys(&self) -> u32;
     fn set_menu_mode(&self, mode: bool) -> Result<(), xous::Error>;
+    fn set_private_mode(&self, private: bool) -> Result<(), xous::Error>;
 }
 
 pub const SERVER_NAME_IME_FRONT: &str = "_IME front end_";
+/// The shell plugin is the default prediction engine. Its name lives here so the GAM can offer it without
+/// depending on the plugin.
+pub const SERVER_NAME_IME_PLUGIN_SHELL: &str = "_IME shell plugin_";
 static mut INPUT_CB: Option<fn(String<4000>)> = None;
 
 pub struct ImeFrontEnd {
@@ -363,6 +370,20 @@ impl ImeFrontEndApi for ImeFrontEnd {
         .map(|_| ())
     }
 
+    fn set_private_mode(&self, private: bool) -> Result<(), xous::Error> {
+        xous::send_message(
+            self.cid,
+            xous::Message::new_scalar(
+                ImefOpcode::SetPrivateMode.to_usize().unwrap(),
+                if private { 1 } else { 0 },
+                0,
+                0,
+                0,
+            ),
+        )
+        .map(|_| ())
+    }
+
     fn hook_listener_callback(&mut self, cb: fn(String<4000>)) -> Result<(), xous::Error> {
         if unsafe { INPUT_CB }.is_some() {
             return Err(xous::Error::MemoryInUse); // can't hook it twice
@@ -0,0 +1,300 @@
+//! The user dictionary: words learned from the lines the user enters, offered as completions of the
+//! word being typed. There is one dictionary per language, kept in the PDDB under `USERDICT_DICT`.
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+use ime_plugin_shell::USERDICT_DICT;
+
+/// most words kept per language; the least used ones make room for new ones
+const MAX_WORDS: usize = 2000;
+/// shorter words aren't worth completing
+const MIN_WORD_CHARS: usize = 3;
+/// longer "words" are most likely pasted data, not something to learn
+const MAX_WORD_CHARS: usize = 32;
+/// lines learned between saves to the PDDB
+const SAVE_INTERVAL: usize = 8;
+/// how often to look for the PDDB server until it shows up; some images don't have one at all
+const PDDB_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+pub(crate) struct Dictionary {
+    /// connections to the PDDB, once its server is running
+    pddb: Option<(pddb::Pddb, pddb::PddbMountPoller)>,
+    last_probe: Option<Instant>,
+    /// language the words belong to, once they've been loaded from the PDDB
+    lang: Option<&'static str>,
+    words: HashMap<String, u32>,
+    /// lines learned since the last save
+    unsaved: usize,
+}
+
+impl Dictionary {
+    pub(crate) fn new() -> Self {
+        Dictionary { pddb: None, last_probe: None, lang: None, words: HashMap::new(), unsaved: 0 }
+    }
+
+    /// Loads the dictionary of the current language, if it isn't loaded and the PDDB is mounted. Words
+    /// learned before the PDDB was mounted are kept.
+    pub(crate) fn sync_lang(&mut self) {
+        let lang = locales::LANG.get();
+        if self.lang == Some(lang) || !self.is_mounted() {
+            return;
+        }
+        if self.lang.is_some() {
+            // the user switched languages: put away the words of the old one
+            self.flush();
+            self.words.clear();
+        }
+        match self.pddb().cached_read(USERDICT_DICT, lang, None) {
+            Ok(data) => {
+                merge(&mut self.words, &String::from_utf8_lossy(&data));
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => log::warn!("couldn't read the {} user dictionary: {:?}", lang, e),
+        }
+        self.lang = Some(lang);
+        self.trim();
+    }
+
+    /// Counts the words in a line the user entered
+    pub(crate) fn learn(&mut self, line: &str) {
+        self.sync_lang();
+        self.count(line);
+        self.unsaved += 1;
+        if self.unsaved >= SAVE_INTERVAL {
+            self.save();
+        }
+    }
+
+    /// Returns `line` with its last, partially typed word completed in each of the ways the dictionary
+    /// knows of, most used first.
+    pub(crate) fn completions(&mut self, line: &str) -> Vec<String> {
+        self.sync_lang();
+        self.complete(line)
+    }
+
+    fn count(&mut self, line: &str) {
+        for word in learnable_words(line) {
+            *self.words.entry(word.to_string()).or_insert(0) += 1;
+        }
+        self.trim();
+    }
+
+    fn complete(&self, line: &str) -> Vec<String> {
+        let start =
+            line.char_indices().rev().find(|&(_, c)| !is_word_char(c)).map_or(0, |(i, c)| i + c.len_utf8());
+        let prefix = &line[start..];
+        if prefix.is_empty() {
+            return Vec::new();
+        }
+        self.ranked(prefix)
+            .into_iter()
+            .filter(|(word, _)| word.len() > prefix.len())
+            .map(|(word, _)| format!("{}{}", &line[..start], word))
+            .collect()
+    }
+
+    /// Returns the words starting with `prefix`, with their counts, most used first
+    pub(crate) fn ranked(&self, prefix: &str) -> Vec<(String, u32)> {
+        let mut words: Vec<(String, u32)> = self
+            .words
+            .iter()
+            .filter(|(word, _)| word.starts_with(prefix))
+            .map(|(word, &count)| (word.clone(), count))
+            .collect();
+        words.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        words
+    }
+
+    pub(crate) fn forget(&mut self, word: &str) -> bool {
+        self.sync_lang();
+        if self.words.remove(word).is_some() {
+            self.save();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Writes every word to the export key, and returns how many were written
+    pub(crate) fn export(&mut self) -> Result<usize, Error> {
+        self.sync_lang();
+        let lang = self.lang.ok_or(Error::new(ErrorKind::NotConnected, "PDDB not mounted"))?;
+        let name = format!("{}.export", lang);
+        let text = self.serialize();
+        match self.pddb().delete_key(USERDICT_DICT, &name, None) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+        self.pddb()
+            .get(USERDICT_DICT, &name, None, true, true, Some(text.len()), None::<fn()>)?
+            .write_all(text.as_bytes())?;
+        self.pddb().sync()?;
+        Ok(self.words.len())
+    }
+
+    /// Merges the words in the import key, deletes the key, and returns how many words it held
+    pub(crate) fn import(&mut self) -> Result<usize, Error> {
+        self.sync_lang();
+        let lang = self.lang.ok_or(Error::new(ErrorKind::NotConnected, "PDDB not mounted"))?;
+        let name = format!("{}.import", lang);
+        let mut data = Vec::new();
+        self.pddb()
+            .get(USERDICT_DICT, &name, None, false, false, None, None::<fn()>)?
+            .read_to_end(&mut data)?;
+        let count = merge(&mut self.words, &String::from_utf8_lossy(&data));
+        self.pddb().delete_key(USERDICT_DICT, &name, None)?;
+        self.trim();
+        self.save();
+        Ok(count)
+    }
+
+    /// Saves any words learned since the last save
+    pub(crate) fn flush(&mut self) {
+        if self.unsaved > 0 {
+            self.save();
+        }
+    }
+
+    /// Stores the words in the PDDB. Does nothing until the dictionary has been loaded, so the stored
+    /// words are never replaced by the few learned before the PDDB was mounted.
+    pub(crate) fn save(&mut self) {
+        if let Some(lang) = self.lang {
+            match self.pddb().cached_write(USERDICT_DICT, lang, None, self.serialize().as_bytes()) {
+                Ok(()) => {
+                    self.pddb().sync().ok();
+                    self.unsaved = 0;
+                }
+                Err(e) => log::warn!("couldn't save the {} user dictionary: {:?}", lang, e),
+            }
+        }
+    }
+
+    /// Connects to the PDDB if its server has come up since the last look, without ever waiting on it
+    fn is_mounted(&mut self) -> bool {
+        if self.pddb.is_none() {
+            if self.last_probe.map_or(false, |t| t.elapsed() < PDDB_PROBE_INTERVAL) {
+                return false;
+            }
+            self.last_probe = Some(Instant::now());
+            // the poller is registered after the main PDDB server, so connecting to the latter won't block
+            if let Some(poller) = pddb::PddbMountPoller::try_new() {
+                self.pddb = Some((pddb::Pddb::new(), poller));
+            }
+        }
+        self.pddb.as_ref().map_or(false, |(_, poller)| poller.is_mounted_nonblocking())
+    }
+
+    /// Only valid once the dictionary has been loaded, which requires a PDDB connection
+    fn pddb(&self) -> &pddb::Pddb { &self.pddb.as_ref().expect("PDDB not connected").0 }
+
+    fn serialize(&self) -> String {
+        let mut text = String::new();
+        for (word, count) in self.ranked("") {
+            text.push_str(&format!("{} {}\n", word, count));
+        }
+        text
+    }
+
+    /// Drops the least used words once there are too many
+    fn trim(&mut self) {
+        if self.words.len() > MAX_WORDS {
+            for (word, _) in self.ranked("").into_iter().skip(MAX_WORDS) {
+                self.words.remove(&word);
+            }
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool { c.is_alphanumeric() || c == '\'' || c == '-' }
+
+/// The words of `line` worth learning: not too short or long, and not just a number
+fn learnable_words(line: &str) -> impl Iterator<Item = &str> {
+    line.split(|c: char| !is_word_char(c)).filter(|word| {
+        let chars = word.chars().count();
+        chars >= MIN_WORD_CHARS && chars <= MAX_WORD_CHARS && !word.chars().all(|c| c.is_numeric())
+    })
+}
+
+/// Adds the words in `text`, one per line and optionally followed by a count, to `words`. Returns the
+/// number of words read.
+fn merge(words: &mut HashMap<String, u32>, text: &str) -> usize {
+    let mut merged = 0;
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        if let Some(word) = fields.next() {
+            let count = fields.next().and_then(|c| c.parse::<u32>().ok()).unwrap_or(1);
+            let entry = words.entry(word.to_string()).or_insert(0);
+            *entry = entry.saturating_add(count);
+            merged += 1;
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary(lines: &[&str]) -> Dictionary {
+        let mut dict = Dictionary::new();
+        for line in lines {
+            dict.count(line);
+        }
+        dict
+    }
+
+    #[test]
+    fn learns_only_plausible_words() {
+        let long = "x".repeat(MAX_WORD_CHARS + 1);
+        let line = format!("an owl's 12345 well-known 2fa {} nest", long);
+        let words: Vec<&str> = learnable_words(&line).collect();
+        assert_eq!(words, ["owl's", "well-known", "2fa", "nest"]);
+    }
+
+    #[test]
+    fn ranks_by_use_then_alphabetically() {
+        let dict = dictionary(&["bravo alpha", "charlie alpha", "delta"]);
+        let ranked = dict.ranked("");
+        assert_eq!(ranked[0], ("alpha".to_string(), 2));
+        let names: Vec<&str> = ranked.iter().map(|(w, _)| w.as_str()).collect();
+        assert_eq!(names, ["alpha", "bravo", "charlie", "delta"]);
+        assert_eq!(dict.ranked("ch"), [("charlie".to_string(), 1)]);
+    }
+
+    #[test]
+    fn completes_the_last_word() {
+        let dict = dictionary(&["hello help", "help"]);
+        assert_eq!(dict.complete("say hel"), ["say help", "say hello"]);
+        // a word that is already complete isn't offered again
+        assert!(dict.complete("say help").is_empty());
+        // nothing is offered after a word break
+        assert!(dict.complete("say ").is_empty());
+    }
+
+    #[test]
+    fn trims_the_least_used_words() {
+        let mut dict = dictionary(&["keeper keeper"]);
+        for i in 0..MAX_WORDS {
+            dict.count(&format!("word{}", i));
+        }
+        assert_eq!(dict.words.len(), MAX_WORDS);
+        assert!(dict.words.contains_key("keeper"));
+    }
+
+    #[test]
+    fn merges_counts_and_bare_words() {
+        let mut words = HashMap::new();
+        words.insert("alpha".to_string(), 1);
+        let merged = merge(&mut words, "alpha 4\nbravo\n\ncharlie x\ndelta 4294967295\n");
+        assert_eq!(merged, 4);
+        assert_eq!(words["alpha"], 5);
+        assert_eq!(words["bravo"], 1);
+        assert_eq!(words["charlie"], 1);
+        words.insert("echo".to_string(), u32::MAX);
+        merge(&mut words, "echo 2");
+        assert_eq!(words["echo"], u32::MAX);
+    }
+}
@@ -1,6 +1,112 @@
 #![cfg_attr(target_os = "none", no_std)]
 
-pub const SERVER_NAME_IME_PLUGIN_SHELL: &str = "_IME shell plugin_";
-
-// just inherit all the default from the ime_plugin_api
+// just inherit all the default from the ime_plugin_api, including SERVER_NAME_IME_PLUGIN_SHELL
 pub use ime_plugin_api::*;
+use num_traits::ToPrimitive;
+use xous::{send_message, Message, CID};
+use xous_ipc::{Buffer, String};
+
+/// PDDB dictionary holding the user dictionaries. The words for each language are kept in a key named
+/// after the language code, as text with one `word count` pair per line. `<lang>.export` receives
+/// exports in the same format, and `<lang>.import` is merged in on import; its lines may omit the count.
+pub const USERDICT_DICT: &str = "ime.userdict";
+
+/// Opcodes for managing the user dictionary. They are numbered clear of `ime_plugin_api::Opcode`, which
+/// the plugin also serves. The dictionary holds what the user typed, so only the process that claimed it
+/// first may manage it; that is the shell, which claims it at boot.
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub enum DictOpcode {
+    /// list learned words, most used first
+    ListWords = 0x100, //(UserWords),
+    /// forget a learned word
+    ForgetWord, //(ForgetWord),
+    /// write the dictionary to the export key; returns the number of words written
+    Export,
+    /// merge the import key into the dictionary; returns the number of words merged
+    Import,
+    /// become the manager of the dictionary; returns 1 if the caller is the manager
+    Claim,
+    /// internal: the user switched languages
+    LanguageChanged,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct UserWords {
+    /// only words starting with this are listed
+    pub prefix: String<64>,
+    /// `word count` pairs, one per line, as many as fit
+    pub words: String<3000>,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct ForgetWord {
+    pub word: String<64>,
+    pub found: bool,
+}
+
+/// Manages the words the shell plugin has learned for the current language
+pub struct UserDictionary {
+    conn: CID,
+}
+impl UserDictionary {
+    /// Claims the dictionary for the calling process. Fails if the shell plugin isn't running, as in images
+    /// that use another default engine, and with `AccessDenied` if another process already claimed it.
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection(SERVER_NAME_IME_PLUGIN_SHELL)?;
+        match send_message(
+            conn,
+            Message::new_blocking_scalar(DictOpcode::Claim.to_usize().unwrap(), 0, 0, 0, 0),
+        )? {
+            xous::Result::Scalar1(1) => Ok(UserDictionary { conn }),
+            _ => Err(xous::Error::AccessDenied),
+        }
+    }
+
+    /// Returns the learned words starting with `prefix` and how often each was used, most used first.
+    /// Long lists are truncated.
+    pub fn list(&self, prefix: &str) -> Result<Vec<(std::string::String, u32)>, xous::Error> {
+        let request = UserWords { prefix: String::from_str(prefix), words: String::new() };
+        let mut buf = Buffer::into_buf(request).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, DictOpcode::ListWords.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let response = buf.to_original::<UserWords, _>().or(Err(xous::Error::InternalError))?;
+        Ok(response
+            .words
+            .as_str()
+            .or(Err(xous::Error::InvalidString))?
+            .lines()
+            .filter_map(|line| {
+                let (word, count) = line.rsplit_once(' ')?;
+                Some((word.to_string(), count.parse().ok()?))
+            })
+            .collect())
+    }
+
+    /// Removes `word` from the dictionary. Returns `false` if it wasn't in there.
+    pub fn forget(&self, word: &str) -> Result<bool, xous::Error> {
+        let request = ForgetWord { word: String::from_str(word), found: false };
+        let mut buf = Buffer::into_buf(request).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, DictOpcode::ForgetWord.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let response = buf.to_original::<ForgetWord, _>().or(Err(xous::Error::InternalError))?;
+        Ok(response.found)
+    }
+
+    /// Writes the dictionary to the `<lang>.export` key of `USERDICT_DICT`, and returns the number of
+    /// words written.
+    pub fn export(&self) -> Result<usize, xous::Error> { self.transfer(DictOpcode::Export) }
+
+    /// Merges the words in the `<lang>.import` key of `USERDICT_DICT` into the dictionary, deleting the
+    /// key afterwards, and returns the number of words merged.
+    pub fn import(&self) -> Result<usize, xous::Error> { self.transfer(DictOpcode::Import) }
+
+    fn transfer(&self, op: DictOpcode) -> Result<usize, xous::Error> {
+        match send_message(self.conn, Message::new_blocking_scalar(op.to_usize().unwrap(), 0, 0, 0, 0))? {
+            xous::Result::Scalar2(1, count) => Ok(count),
+            // the PDDB isn't mounted yet
+            xous::Result::Scalar2(0, 0) => Err(xous::Error::UseBeforeInit),
+            xous::Result::Scalar2(0, 2) => Err(xous::Error::AccessDenied),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+}
@@ -1,12 +1,14 @@
 #![cfg_attr(target_os = "none", no_std)]
 #![cfg_attr(target_os = "none", no_main)]
 
+mod dictionary;
 use std::collections::HashMap;
 
-use ime_plugin_api::*;
+use dictionary::Dictionary;
+use ime_plugin_shell::*;
 use log::{error, info};
-use num_traits::FromPrimitive;
-use xous::msg_scalar_unpack;
+use num_traits::{FromPrimitive, ToPrimitive};
+use xous::{msg_blocking_scalar_unpack, msg_scalar_unpack};
 use xous_ipc::{Buffer, String};
 
 fn main() -> ! {
@@ -20,10 +22,22 @@ fn main() -> ! {
         .register_name(ime_plugin_shell::SERVER_NAME_IME_PLUGIN_SHELL, None)
         .expect("can't register server");
     log::trace!("registered with NS -- {:?}", ime_sh_sid);
+    // the user dictionary is kept per language, so swap it out when the language changes
+    locales::follow_language(Some((
+        xous::connect(ime_sh_sid).unwrap(),
+        DictOpcode::LanguageChanged.to_usize().unwrap(),
+    )));
 
     let mut history_store: HashMap<[u32; 4], Vec<String<64>>> = HashMap::new();
     let mut active_history: Option<([u32; 4], Vec<String<64>>)> = None;
-    let history_max = 4;
+    // the history can be stepped through with the up/down keys, so keep more than the four predictions shown
+    let history_max = 32;
+    // the current input line; predictions are limited to history entries that contain it
+    let mut filter = std::string::String::new();
+    // words learned from the entered lines, for completing the word being typed
+    let mut dictionary = Dictionary::new();
+    // the process that may read back and edit the dictionary: the first one to claim it
+    let mut dict_manager: Option<u32> = None;
 
     /*
         use core::fmt::Write as CoreWriter;
@@ -70,6 +84,8 @@ fn main() -> ! {
             }
             Some(Opcode::Release) => msg_scalar_unpack!(msg, t0, t1, t2, t3, {
                 let token = [t0 as u32, t1 as u32, t2 as u32, t3 as u32];
+                filter.clear();
+                dictionary.flush();
                 if let Some((t, h)) = active_history.take() {
                     if t == token {
                         history_store.insert(token, h);
@@ -81,10 +97,13 @@ fn main() -> ! {
                 }
             }),
             Some(Opcode::Input) => {
-                // shell does nothing with the input, it only keeps track of
-                // the picked results
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let s = buffer.as_flat::<String<4000>, _>().unwrap();
+                filter.clear();
+                filter.push_str(s.as_str());
             }
             Some(Opcode::Picked) => {
+                filter.clear();
                 if let Some((_token, history)) = &mut active_history {
                     let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
                     let s = buffer.as_flat::<String<4000>, _>().unwrap();
@@ -94,6 +113,9 @@ fn main() -> ! {
                     use core::fmt::Write;
                     write!(local_s, "{}", s.as_str()).expect("overflowed history variable");
                     log::trace!("storing history value | {}", s.as_str());
+                    dictionary.learn(s.as_str());
+                    // re-entering a line moves it to the front of the history instead of duplicating it
+                    history.retain(|h| h.as_str() != local_s.as_str());
                     if history.len() == history_max {
                         history.remove(0);
                     }
@@ -111,35 +133,31 @@ fn main() -> ! {
                     if *token == prediction.api_token {
                         log::trace!("querying prediction index {}", prediction.index);
                         log::trace!("{:?}", prediction);
-                        if history.len() > 0 && ((prediction.index as usize) < history.len()) {
-                            let mut index = prediction.index;
-                            if index >= history.len() as u32 {
-                                index = history.len() as u32 - 1;
-                            }
-                            let mut i = 1;
-                            for &s in history.iter() {
-                                // iterator is from oldest to newest, so do some math to go from newest to
-                                // oldest TIL: there is a .rev() feature in
-                                // iterators. Next time maybe use that instead.
-                                if (history.len() as u32 - i) == index {
-                                    // decompose the string into a character-by-character sequence
-                                    // and then stuff byte-by-byte, as fits, into the return array
-                                    prediction.string.clear();
-                                    for ch in s.as_str().unwrap().chars() {
-                                        if let Ok(_) = prediction.string.push(ch) {
-                                            // it's ok, carry on.
-                                        } else {
-                                            // we ran out of space, stop copying
-                                            break;
-                                        }
-                                    }
-                                    prediction.valid = true;
+                        // newest to oldest, skipping entries that don't match what has been typed so far;
+                        // then the line with its last word completed from the user dictionary
+                        if let Some(s) = history
+                            .iter()
+                            .rev()
+                            .filter_map(|h| h.as_str().ok())
+                            .filter(|h| h.contains(filter.as_str()))
+                            .map(|h| h.to_string())
+                            .chain(dictionary.completions(&filter))
+                            .nth(prediction.index as usize)
+                        {
+                            // decompose the string into a character-by-character sequence
+                            // and then stuff byte-by-byte, as fits, into the return array
+                            prediction.string.clear();
+                            for ch in s.chars() {
+                                if let Ok(_) = prediction.string.push(ch) {
+                                    // it's ok, carry on.
+                                } else {
+                                    // we ran out of space, stop copying
                                     break;
                                 }
-                                i = i + 1;
                             }
+                            prediction.valid = true;
                         } else {
-                            // there is no history
+                            // there is no matching history
                             prediction.valid = false;
                             log::trace!("no prediction found");
                         }
@@ -177,9 +195,81 @@ fn main() -> ! {
                     break;
                 }
             }
-            None => {
-                error!("unknown Opcode");
-            }
+            None => match FromPrimitive::from_usize(msg.body.id()) {
+                Some(DictOpcode::Claim) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                    let pid = msg.sender.pid().map(|p| p.get() as u32);
+                    if dict_manager.is_none() {
+                        dict_manager = pid;
+                    }
+                    xous::return_scalar(msg.sender, if dict_manager == pid { 1 } else { 0 })
+                        .expect("couldn't return Claim");
+                }),
+                // the words can be read back and edited only by the manager
+                Some(_)
+                    if msg.sender.pid().map(|p| p.get() as u32) != dict_manager
+                        && msg.body.id() != DictOpcode::LanguageChanged.to_usize().unwrap() =>
+                {
+                    log::warn!(
+                        "user dictionary request from {:?}, which isn't its manager",
+                        msg.sender.pid()
+                    );
+                    if msg.body.is_blocking() && msg.body.scalar_message().is_some() {
+                        xous::return_scalar2(msg.sender, 0, 2).ok();
+                    }
+                }
+                Some(DictOpcode::ListWords) => {
+                    let Some(mem) = msg.body.memory_message_mut() else {
+                        log::error!("ListWords was not a memory message");
+                        continue;
+                    };
+                    let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                    let Ok(mut request) = buffer.to_original::<UserWords, _>() else {
+                        log::error!("couldn't deserialize ListWords");
+                        continue;
+                    };
+                    dictionary.sync_lang();
+                    use core::fmt::Write;
+                    for (word, count) in dictionary.ranked(request.prefix.as_str().unwrap_or("")) {
+                        if write!(request.words, "{} {}\n", word, count).is_err() {
+                            break;
+                        }
+                    }
+                    buffer.replace(request).ok();
+                }
+                Some(DictOpcode::ForgetWord) => {
+                    let Some(mem) = msg.body.memory_message_mut() else {
+                        log::error!("ForgetWord was not a memory message");
+                        continue;
+                    };
+                    let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                    let Ok(mut request) = buffer.to_original::<ForgetWord, _>() else {
+                        log::error!("couldn't deserialize ForgetWord");
+                        continue;
+                    };
+                    request.found = dictionary.forget(request.word.as_str().unwrap_or(""));
+                    buffer.replace(request).ok();
+                }
+                Some(DictOpcode::Export) | Some(DictOpcode::Import) => {
+                    let result = if msg.body.id() == DictOpcode::Export.to_usize().unwrap() {
+                        dictionary.export()
+                    } else {
+                        dictionary.import()
+                    };
+                    let (ok, value) = match result {
+                        Ok(count) => (1, count),
+                        Err(e) if e.kind() == std::io::ErrorKind::NotConnected => (0, 0),
+                        Err(e) => {
+                            log::warn!("user dictionary transfer failed: {:?}", e);
+                            (0, 1)
+                        }
+                    };
+                    xous::return_scalar2(msg.sender, ok, value).ok();
+                }
+                Some(DictOpcode::LanguageChanged) => dictionary.sync_lang(),
+                None => {
+                    error!("unknown Opcode");
+                }
+            },
         }
     }
     log::trace!("main loop exit, destroying servers");
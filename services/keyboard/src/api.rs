@@ -1,6 +1,9 @@
 use std::fmt::Display;
 
 pub const SERVER_NAME_KBD: &str = "_Matrix keyboard driver_";
+/// Public server for apps to query the active layout. The main keyboard server only admits a fixed
+/// number of trusted connections.
+pub const SERVER_NAME_KBD_LAYOUT: &str = "_Keyboard layout_";
 
 #[allow(dead_code)]
 #[derive(Debug, Default, Copy, Clone)]
@@ -78,7 +81,9 @@ pub(crate) enum Opcode {
     /// request for raw keyups/downs
     RegisterRawListener = 3,
 
-    /// request for updates for *when* keyboard is pressed
+    /// request for updates for *when* keyboard is pressed, and for long-press and double-tap events. The
+    /// observer receives a scalar message with a `KeyEventKind` in arg1. Which key was involved is never
+    /// reported, as observers don't hold focus and must not learn what is typed.
     RegisterKeyObserver = 12,
 
     /// set repeat delay, rate; both in ms
@@ -102,6 +107,45 @@ pub(crate) enum Opcode {
 
     /// Suspend/resume callback
     SuspendResume = 10,
+
+    /// set the key remapping and macro configuration (memory message, `KeyConfigIpc`)
+    SetKeyConfig = 13,
+
+    /// get the active layout (memory message, `KeyLayoutIpc`)
+    GetLayout = 14,
+
+    /// grab or release the keyboard on behalf of an app (blocking scalar: 0 to release, or 1 + `GrabPolicy`)
+    SetInputGrab = 15,
+}
+
+/// Sets who else hears about keystrokes while an app has grabbed the keyboard, e.g. for password entry.
+/// During a grab, keys are only sent to the listener (the GAM); raw scancode listeners, blocking listeners
+/// and macros that send messages get nothing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub enum GrabPolicy {
+    /// the observer is still told that a key was hit, but not which one, so that e.g. the backlight keeps
+    /// working
+    NotifyObservers = 0,
+    /// the observer isn't notified either, so that not even the timing of keystrokes is revealed
+    Exclusive = 1,
+}
+
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub(crate) enum LayoutOpcode {
+    /// get the active layout (memory message, `KeyLayoutIpc`)
+    GetLayout = 0,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub(crate) struct KeyConfigIpc {
+    pub config: xous_ipc::String<{ crate::keyconfig::MAX_KEY_CONFIG_LEN }>,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub(crate) struct KeyLayoutIpc {
+    pub map: KeyMap,
+    /// summary of the remaps and macro triggers, as given by `KeyConfig::summary()`
+    pub summary: xous_ipc::String<{ crate::keyconfig::MAX_KEY_CONFIG_LEN }>,
 }
 
 // this structure is used to register a keyboard listener. Currently, we only accept
@@ -123,6 +167,16 @@ impl RowCol {
     pub fn new(r: u8, c: u8) -> RowCol { RowCol { r, c } }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub enum KeyEventKind {
+    /// a key went up or down
+    Press = 0,
+    /// a key was held past the repeat delay before it was released
+    LongPress = 1,
+    /// a key was tapped twice in quick succession
+    DoubleTap = 2,
+}
+
 #[derive(Debug)]
 pub struct KeyRawStates {
     pub keydowns: Vec<RowCol>,
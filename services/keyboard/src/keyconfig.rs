@@ -0,0 +1,266 @@
+//! User-configurable key remapping and macros.
+//!
+//! The configuration is a short text, so that it can be stored in the PDDB and edited by hand. Entries are
+//! separated by newlines or `;`, and blank entries and entries starting with `#` are ignored:
+//!
+//! ```text
+//! remap <from> <to>                          # <from> types <to> instead
+//! hold <key> text <text to type>             # long-pressing <key> types the text
+//! hold <key> message <opcode> <server name>  # long-pressing <key> sends a scalar message to a server
+//! chord <key1> <key2> text <text to type>    # pressing two keys together types the text
+//! chord <key1> <key2> message <opcode> <server name>
+//! ```
+//!
+//! Message macros are limited to the targets in `MACRO_MESSAGE_TARGETS`: the keyboard server is trusted
+//! by every service that listens to it, so a macro must not be able to send arbitrary opcodes to arbitrary
+//! servers on the configuration author's behalf.
+//!
+//! Keys are given as a single character, as `U+xxxx`, or by one of the names `F1`-`F4`, `up`, `down`,
+//! `left`, `right`, `menu`, `space`, `enter` and `backspace`. As `;` separates entries, it can't be used
+//! in the text of a macro.
+//!
+//! The status service loads the configuration from the PDDB key `keyboard:config` once the PDDB is
+//! mounted, and reloads it from the preferences menu.
+
+use std::fmt::{Display, Formatter};
+
+/// Maximum length of the configuration text.
+pub const MAX_KEY_CONFIG_LEN: usize = 2048;
+
+const KEY_NAMES: [(&str, char); 12] = [
+    ("F1", '\u{0011}'),
+    ("F2", '\u{0012}'),
+    ("F3", '\u{0013}'),
+    ("F4", '\u{0014}'),
+    ("up", '↑'),
+    ("down", '↓'),
+    ("left", '←'),
+    ("right", '→'),
+    ("menu", '∴'),
+    ("space", ' '),
+    ("enter", '\u{000d}'),
+    ("backspace", '\u{0008}'),
+];
+
+/// The (server name, opcode) pairs that message macros may send. All of them are scalar messages that
+/// take no arguments and only do what the user could do from a menu.
+pub const MACRO_MESSAGE_TARGETS: [(&str, usize); 3] = [
+    // quick_settings::Opcode::Show
+    ("_Quick settings_", 2),
+    // keyboard_backlight::Opcode::EnableAuto
+    ("_Keyboard backlight_", 0),
+    // keyboard_backlight::Opcode::DisableAuto
+    ("_Keyboard backlight_", 1),
+];
+
+fn parse_key(token: &str) -> Option<char> {
+    if let Some((_, c)) = KEY_NAMES.iter().find(|(n, _)| *n == token) {
+        return Some(*c);
+    }
+    if let Some(hex) = token.strip_prefix("U+") {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Formats a key so that `parse_key` can read it back.
+struct KeyName(char);
+impl Display for KeyName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match KEY_NAMES.iter().find(|(_, c)| *c == self.0) {
+            Some((name, _)) => write!(f, "{}", name),
+            None if self.0.is_control() || self.0 == '#' || self.0 == ';' => {
+                write!(f, "U+{:04X}", self.0 as u32)
+            }
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MacroTrigger {
+    /// the key is held down past the repeat delay
+    Hold(char),
+    /// both keys are down at the same time, in either order
+    Chord(char, char),
+}
+impl MacroTrigger {
+    /// Chords match regardless of the order the keys are given in.
+    fn overlaps(&self, other: &MacroTrigger) -> bool {
+        match (self, other) {
+            (MacroTrigger::Chord(a, b), MacroTrigger::Chord(c, d)) => {
+                (a == c && b == d) || (a == d && b == c)
+            }
+            _ => self == other,
+        }
+    }
+}
+impl Display for MacroTrigger {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MacroTrigger::Hold(k) => write!(f, "hold {}", KeyName(*k)),
+            MacroTrigger::Chord(a, b) => write!(f, "chord {} {}", KeyName(*a), KeyName(*b)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MacroAction {
+    /// type out the text, as if it were entered on the keyboard
+    Text(String),
+    /// send a scalar message with the given opcode to a server
+    Message { opcode: usize, server: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMacro {
+    pub trigger: MacroTrigger,
+    pub action: MacroAction,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyConfigError {
+    /// entry number, counting from 1
+    pub entry: usize,
+    pub reason: &'static str,
+}
+impl Display for KeyConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "keyboard config entry {}: {}", self.entry, self.reason)
+    }
+}
+
+/// A parsed key remapping and macro configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyConfig {
+    pub remaps: Vec<(char, char)>,
+    pub macros: Vec<KeyMacro>,
+}
+impl KeyConfig {
+    pub fn parse(text: &str) -> Result<KeyConfig, KeyConfigError> {
+        let mut config = KeyConfig::default();
+        for (i, entry) in text.split(|c| c == '\n' || c == ';').enumerate() {
+            let entry = entry.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            let err = |reason| KeyConfigError { entry: i + 1, reason };
+            let (verb, rest) = entry.split_once(' ').unwrap_or((entry, ""));
+            let mut tokens = rest.trim_start().splitn(2, ' ');
+            let mut next_key = || tokens.next().and_then(parse_key).ok_or(err("invalid key"));
+            let (trigger, action) = match verb {
+                "remap" => {
+                    let from = next_key()?;
+                    let to = next_key()?;
+                    config.remaps.retain(|(f, _)| *f != from);
+                    config.remaps.push((from, to));
+                    continue;
+                }
+                "hold" => (MacroTrigger::Hold(next_key()?), tokens.next().unwrap_or("")),
+                "chord" => {
+                    let a = next_key()?;
+                    let (b, action) = match tokens.next().map(|r| r.split_once(' ').unwrap_or((r, ""))) {
+                        Some((b, action)) => (parse_key(b).ok_or(err("invalid key"))?, action),
+                        None => return Err(err("invalid key")),
+                    };
+                    if a == b {
+                        return Err(err("a chord needs two different keys"));
+                    }
+                    (MacroTrigger::Chord(a, b), action)
+                }
+                _ => return Err(err("unknown entry, expected remap, hold or chord")),
+            };
+            let action = match action.split_once(' ') {
+                Some(("text", text)) if !text.is_empty() => MacroAction::Text(text.to_string()),
+                Some(("message", target)) => match target.trim().split_once(' ') {
+                    Some((opcode, server)) => {
+                        let opcode = opcode.parse().map_err(|_| err("invalid opcode"))?;
+                        let server = server.trim();
+                        if !MACRO_MESSAGE_TARGETS.contains(&(server, opcode)) {
+                            return Err(err("message target is not allowed"));
+                        }
+                        MacroAction::Message { opcode, server: server.to_string() }
+                    }
+                    None => return Err(err("message needs an opcode and a server name")),
+                },
+                _ => return Err(err("expected a text or message action")),
+            };
+            config.macros.retain(|m| !m.trigger.overlaps(&trigger));
+            config.macros.push(KeyMacro { trigger, action });
+        }
+        Ok(config)
+    }
+
+    pub fn is_empty(&self) -> bool { self.remaps.is_empty() && self.macros.is_empty() }
+
+    /// Returns the character that `c` is remapped to.
+    pub fn remap(&self, c: char) -> char {
+        self.remaps.iter().find(|(from, _)| *from == c).map_or(c, |(_, to)| *to)
+    }
+
+    pub fn hold_macro(&self, key: char) -> Option<&MacroAction> {
+        self.macros.iter().find(|m| m.trigger == MacroTrigger::Hold(key)).map(|m| &m.action)
+    }
+
+    pub fn chord_macro(&self, a: char, b: char) -> Option<&MacroAction> {
+        self.macros.iter().find(|m| m.trigger.overlaps(&MacroTrigger::Chord(a, b))).map(|m| &m.action)
+    }
+
+    /// Formats the remaps and macro triggers, leaving out what the macros do, as the text of a macro may
+    /// be sensitive.
+    pub fn summary(&self) -> String {
+        let mut s = String::new();
+        for (from, to) in self.remaps.iter() {
+            s.push_str(&format!("remap {} {}\n", KeyName(*from), KeyName(*to)));
+        }
+        for m in self.macros.iter() {
+            s.push_str(&format!("{}\n", m.trigger));
+        }
+        s
+    }
+}
+impl Display for KeyConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (from, to) in self.remaps.iter() {
+            writeln!(f, "remap {} {}", KeyName(*from), KeyName(*to))?;
+        }
+        for m in self.macros.iter() {
+            match &m.action {
+                MacroAction::Text(text) => writeln!(f, "{} text {}", m.trigger, text)?,
+                MacroAction::Message { opcode, server } => {
+                    writeln!(f, "{} message {} {}", m.trigger, opcode, server)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The active keyboard layout, as reported to apps.
+#[derive(Debug, Clone)]
+pub struct KeyLayout {
+    pub map: crate::api::KeyMap,
+    pub remaps: Vec<(char, char)>,
+    /// the keys that trigger macros. What the macros do is not disclosed.
+    pub macro_triggers: Vec<MacroTrigger>,
+}
+impl KeyLayout {
+    /// Reconstructs the layout from the output of `KeyConfig::summary()`.
+    pub(crate) fn from_summary(map: crate::api::KeyMap, summary: &str) -> KeyLayout {
+        let mut layout = KeyLayout { map, remaps: Vec::new(), macro_triggers: Vec::new() };
+        for line in summary.lines() {
+            let keys: Vec<char> = line.split(' ').skip(1).filter_map(parse_key).collect();
+            match (line.split(' ').next(), keys.as_slice()) {
+                (Some("remap"), &[from, to]) => layout.remaps.push((from, to)),
+                (Some("hold"), &[key]) => layout.macro_triggers.push(MacroTrigger::Hold(key)),
+                (Some("chord"), &[a, b]) => layout.macro_triggers.push(MacroTrigger::Chord(a, b)),
+                _ => log::warn!("unrecognized layout summary line: {}", line),
+            }
+        }
+        layout
+    }
+}
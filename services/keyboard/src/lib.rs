@@ -3,8 +3,10 @@
 use num_traits::*;
 
 pub mod api;
+pub mod keyconfig;
 
 pub use api::*;
+pub use keyconfig::*;
 use xous::{send_message, Message};
 use xous_ipc::{Buffer, String};
 
@@ -54,6 +56,20 @@ impl Keyboard {
             .map(|_| ())
     }
 
+    /// Grabs the keyboard, or releases the grab with `None`. Only the GAM should call this; apps request a
+    /// grab through the GAM, which arbitrates between them.
+    pub fn set_input_grab(&self, grab: Option<GrabPolicy>) -> Result<(), xous::Error> {
+        let arg = match grab {
+            Some(policy) => 1 + policy.to_usize().unwrap(),
+            None => 0,
+        };
+        send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::SetInputGrab.to_usize().unwrap(), arg, 0, 0, 0),
+        )
+        .map(|_| ())
+    }
+
     pub fn set_keymap(&self, map: KeyMap) -> Result<(), xous::Error> {
         send_message(
             self.conn,
@@ -72,6 +88,21 @@ impl Keyboard {
         }
     }
 
+    /// Sets the key remapping and macro configuration; see the `keyconfig` module for the format. The
+    /// configuration is checked before it is sent, so that errors can be reported to the caller.
+    pub fn set_key_config(&self, config: &str) -> Result<(), KeyConfigError> {
+        KeyConfig::parse(config)?;
+        if config.len() > MAX_KEY_CONFIG_LEN {
+            return Err(KeyConfigError { entry: 0, reason: "configuration is too long" });
+        }
+        let kc = KeyConfigIpc { config: String::from_str(config) };
+        let buf = Buffer::into_buf(kc).unwrap();
+        buf.lend(self.conn, Opcode::SetKeyConfig.to_u32().unwrap()).expect("couldn't set key config");
+        Ok(())
+    }
+
+    pub fn get_layout(&self) -> Result<KeyLayout, xous::Error> { get_layout(self.conn, Opcode::GetLayout) }
+
     /// Blocks until a key is hit. Does not block the keyboard server, just the caller.
     /// Returns a `Vec::<char>`, as the user can press more than one key at a time.
     /// The specific order of a simultaneous key hit event is not defined.
@@ -118,6 +149,43 @@ impl Keyboard {
     }
 }
 
+fn get_layout<T: ToPrimitive>(conn: xous::CID, opcode: T) -> Result<KeyLayout, xous::Error> {
+    let req = KeyLayoutIpc { map: KeyMap::Undefined, summary: String::new() };
+    let mut buf = Buffer::into_buf(req).or(Err(xous::Error::InternalError))?;
+    buf.lend_mut(conn, opcode.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+    let layout = buf.to_original::<KeyLayoutIpc, _>().or(Err(xous::Error::InternalError))?;
+    Ok(KeyLayout::from_summary(layout.map, layout.summary.as_str().unwrap_or("")))
+}
+
+/// Read-only access to the keyboard layout, for apps that can't connect to the keyboard server itself.
+#[derive(Debug)]
+pub struct KeyboardLayout {
+    conn: xous::CID,
+}
+impl KeyboardLayout {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        LAYOUT_REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_KBD_LAYOUT)?;
+        Ok(KeyboardLayout { conn })
+    }
+
+    /// Returns the active key map, remaps, and the keys that trigger macros.
+    pub fn get_layout(&self) -> Result<KeyLayout, xous::Error> {
+        get_layout(self.conn, LayoutOpcode::GetLayout)
+    }
+}
+
+static LAYOUT_REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for KeyboardLayout {
+    fn drop(&mut self) {
+        if LAYOUT_REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
+
 use core::sync::atomic::{AtomicU32, Ordering};
 static REFCOUNT: AtomicU32 = AtomicU32::new(0);
 impl Drop for Keyboard {
@@ -4,7 +4,11 @@
 mod api;
 use api::*;
 mod mappings;
+// the configuration parser is shared with the library, which checks configurations before sending them
+use keyboard::keyconfig;
+use keyconfig::*;
 
+use std::collections::HashMap;
 #[cfg(feature = "rawserial")]
 use std::collections::VecDeque;
 
@@ -14,6 +18,8 @@ use xous::{msg_blocking_scalar_unpack, msg_scalar_unpack, MessageSender, CID};
 use xous_ipc::Buffer;
 #[cfg(feature = "rawserial")]
 const BLOCKING_QUEUE_LEN: usize = 128;
+/// maximum number of services that may observe key events
+const MAX_OBSERVERS: usize = 4;
 
 #[cfg(any(feature = "precursor", feature = "renode"))]
 mod implementation {
@@ -25,12 +31,15 @@ mod implementation {
     use utralib::generated::*;
     use xous::CID;
 
+    use crate::keyconfig::*;
     use crate::mappings::*;
     use crate::{api::*, KeyRawStates, RowCol};
 
     /// note: the code is structured to use at most 16 rows or 16 cols
     const KBD_ROWS: usize = 9;
     const KBD_COLS: usize = 10;
+    /// maximum time in ms between the releases of two taps on a key for them to count as a double tap
+    const DOUBLE_TAP_MS: u64 = 300;
 
     pub(crate) struct Keyboard {
         conn: CID,
@@ -73,6 +82,18 @@ mod implementation {
         pub debug: usize,
         /// access to settings stored in the first few sectors of FLASH
         early_settings: early_settings::EarlySettings,
+        /// user-configured remaps and macros
+        key_config: KeyConfig,
+        /// unshifted keys currently held down, for matching chord macros
+        down_keys: Vec<char>,
+        /// keys that were consumed by a chord macro, whose keyups should not produce a character
+        suppressed: Vec<char>,
+        /// macros that send messages are fired from the main loop, as (server name, opcode)
+        macro_messages: Vec<(String, usize)>,
+        /// the last key released without being held, and when, for detecting double taps
+        last_tap: Option<(char, u64)>,
+        /// long-press and double-tap events for the observers
+        key_events: Vec<KeyEventKind>,
     }
 
     fn handle_kbd(_irq_no: usize, arg: *mut usize) {
@@ -196,6 +217,12 @@ mod implementation {
                 susres: RegManager::new(csr.as_mut_ptr() as *mut u32),
                 debug: 0,
                 early_settings: ea,
+                key_config: KeyConfig::default(),
+                down_keys: Vec::new(),
+                suppressed: Vec::new(),
+                macro_messages: Vec::new(),
+                last_tap: None,
+                key_events: Vec::new(),
             }
         }
 
@@ -237,6 +264,9 @@ mod implementation {
             self.chord_captured = false;
             self.chord_active = 0;
             self.chord = [[false; KBD_COLS]; KBD_ROWS];
+            self.down_keys.clear();
+            self.suppressed.clear();
+            self.last_tap = None;
 
             // ensure interrupts are re-enabled -- this could /shouldn't/ be necessary but we're having
             // some strange resume behavior, trying to see if this resolves it.
@@ -259,6 +289,39 @@ mod implementation {
             self.map
         }
 
+        pub(crate) fn set_key_config(&mut self, config: KeyConfig) { self.key_config = config; }
+
+        pub(crate) fn key_config(&self) -> &KeyConfig { &self.key_config }
+
+        /// Returns the message-sending macros fired since the last call.
+        pub(crate) fn take_macro_messages(&mut self) -> Vec<(String, usize)> {
+            std::mem::take(&mut self.macro_messages)
+        }
+
+        /// Returns the long-press and double-tap events classified since the last call.
+        pub(crate) fn take_key_events(&mut self) -> Vec<KeyEventKind> { std::mem::take(&mut self.key_events) }
+
+        /// Classifies the release of `key` as a long press, the second of a double tap, or neither.
+        fn classify_release(&mut self, key: char, hold: bool, now: u64) {
+            if hold {
+                self.key_events.push(KeyEventKind::LongPress);
+                self.last_tap = None;
+            } else if matches!(self.last_tap, Some((k, t)) if k == key && now - t <= DOUBLE_TAP_MS) {
+                self.key_events.push(KeyEventKind::DoubleTap);
+                self.last_tap = None;
+            } else {
+                self.last_tap = Some((key, now));
+            }
+        }
+
+        /// Text macros are typed out through `text`; message macros are queued for the main loop.
+        fn fire_macro(&mut self, action: MacroAction, text: &mut Vec<char>) {
+            match action {
+                MacroAction::Text(t) => text.extend(t.chars()),
+                MacroAction::Message { opcode, server } => self.macro_messages.push((server, opcode)),
+            }
+        }
+
         pub(crate) fn set_repeat(&mut self, rate: u32, delay: u32) {
             self.rate = rate;
             self.delay = delay;
@@ -498,6 +561,8 @@ mod implementation {
              to determine if a "hold" modifier applies
             */
             let mut ks: Vec<char> = Vec::new();
+            // text typed by macros, which is not subject to remapping
+            let mut macro_text: Vec<char> = Vec::new();
 
             // first check for shift and alt keys
             for rc in krs.keydowns.iter() {
@@ -585,7 +650,23 @@ mod implementation {
                     KeyMap::Qwertz => map_qwertz(rc),
                     _ => ScanCode { key: None, shift: None, hold: None, alt: None },
                 };
-                if code.hold == None && !((rc.r == 5) && (rc.c == 2))
+                if let Some(key) = code.key {
+                    let chord = self.down_keys.iter().copied().find_map(|other| {
+                        self.key_config.chord_macro(other, key).map(|action| (other, action.clone()))
+                    });
+                    if let Some((other, action)) = chord {
+                        self.fire_macro(action, &mut macro_text);
+                        self.suppressed.push(other);
+                        self.suppressed.push(key);
+                        if self.repeating_key == Some(other) {
+                            self.repeating_key = None;
+                        }
+                        continue;
+                    }
+                    self.down_keys.push(key);
+                }
+                let has_hold_macro = code.key.map_or(false, |key| self.key_config.hold_macro(key).is_some());
+                if code.hold == None && !has_hold_macro && !((rc.r == 5) && (rc.c == 2))
                 // scan code for the menu key
                 {
                     // if there isn't a pre-defined meaning if the key is held *and* it's not the menu key:
@@ -626,6 +707,20 @@ mod implementation {
                         }
                     }
                 }
+                if let Some(key) = code.key {
+                    self.down_keys.retain(|&k| k != key);
+                    if let Some(index) = self.suppressed.iter().position(|&k| k == key) {
+                        self.suppressed.remove(index);
+                        continue;
+                    }
+                    self.classify_release(key, hold, now);
+                    if hold {
+                        if let Some(action) = self.key_config.hold_macro(key).cloned() {
+                            self.fire_macro(action, &mut macro_text);
+                            continue;
+                        }
+                    }
+                }
 
                 match self.map {
                     KeyMap::Azerty => {
@@ -691,6 +786,10 @@ mod implementation {
                 }
             }
 
+            if !self.key_config.is_empty() {
+                ks = ks.into_iter().map(|c| self.key_config.remap(c)).collect();
+                ks.append(&mut macro_text);
+            }
             ks
         }
 
@@ -710,6 +809,7 @@ mod implementation {
         rate: u32,
         delay: u32,
         chord_interval: u32,
+        key_config: KeyConfig,
         pub debug: u32,
     }
 
@@ -721,6 +821,7 @@ mod implementation {
                 rate: 20,
                 delay: 200,
                 chord_interval: 50,
+                key_config: KeyConfig::default(),
                 debug: 0,
             }
         }
@@ -741,6 +842,14 @@ mod implementation {
 
         pub fn track_keys(&mut self, _rs: &KeyRawStates) -> Vec<char> { Vec::new() }
 
+        pub fn set_key_config(&mut self, config: KeyConfig) { self.key_config = config; }
+
+        pub fn key_config(&self) -> &KeyConfig { &self.key_config }
+
+        pub fn take_macro_messages(&mut self) -> Vec<(String, usize)> { Vec::new() }
+
+        pub fn take_key_events(&mut self) -> Vec<KeyEventKind> { Vec::new() }
+
         pub fn set_repeat(&mut self, rate: u32, delay: u32) {
             self.rate = rate;
             self.delay = delay;
@@ -787,15 +896,37 @@ fn main() -> ! {
     let mut susres = susres::Susres::new(None, &xns, Opcode::SuspendResume as u32, self_cid)
         .expect("couldn't create suspend/resume object");
 
+    // apps may query the layout through a separate server that admits any number of connections. Requests
+    // are forwarded to the main loop, which owns the keyboard state.
+    let layout_sid =
+        xns.register_name(api::SERVER_NAME_KBD_LAYOUT, None).expect("can't register layout server");
+    std::thread::spawn(move || {
+        loop {
+            let msg = xous::receive_message(layout_sid).unwrap();
+            match FromPrimitive::from_usize(msg.body.id()) {
+                Some(LayoutOpcode::GetLayout) => {
+                    msg.forward(self_cid, Opcode::GetLayout as _).expect("couldn't forward layout request");
+                }
+                None => log::error!("couldn't convert opcode: {:?}", msg),
+            }
+        }
+    });
+    // connections to servers targeted by message macros
+    let mut macro_conns = HashMap::<String, CID>::new();
+
     // start a thread that can ping the keyboard loop when a key is held down
     let ticktimer = ticktimer_server::Ticktimer::new().unwrap();
+    // the ticktimer only takes user input from the first process to report it, so claim it before any app
+    // runs
+    ticktimer.report_activity(ticktimer_server::api::Activity::UserInput);
 
     let mut listener_conn: Option<CID> = None;
     let mut listener_op: Option<usize> = None;
     let mut raw_listener_conn: Option<CID> = None;
     let mut raw_listener_op: Option<u32> = None;
-    let mut observer_conn: Option<CID> = None;
-    let mut observer_op: Option<usize> = None;
+    let mut observers = Vec::<(CID, usize)>::new();
+    // set while an app has grabbed the keyboard
+    let mut input_grab: Option<GrabPolicy> = None;
 
     let mut vibe = false;
     let llio = llio::Llio::new(&xns);
@@ -872,20 +1003,23 @@ fn main() -> ! {
             Some(Opcode::RegisterKeyObserver) => {
                 let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
                 let kr = buffer.as_flat::<KeyboardRegistration, _>().unwrap();
-                if observer_conn.is_none() {
+                if observers.len() < MAX_OBSERVERS {
                     match xns.request_connection_blocking(kr.server_name.as_str()) {
                         Ok(cid) => {
-                            observer_conn = Some(cid);
-                            observer_op = Some(kr.listener_op_id as usize);
-                        }
-                        Err(e) => {
-                            log::error!("couldn't connect to observer: {:?}", e);
-                            observer_conn = None;
-                            observer_op = None;
+                            observers.retain(|&(c, _)| c != cid);
+                            observers.push((cid, kr.listener_op_id as usize));
                         }
+                        Err(e) => log::error!("couldn't connect to observer: {:?}", e),
                     }
+                } else {
+                    log::error!("too many key observers, ignoring {}", kr.server_name.as_str());
                 }
             }
+            Some(Opcode::SetInputGrab) => msg_blocking_scalar_unpack!(msg, arg, _, _, _, {
+                input_grab = if arg == 0 { None } else { FromPrimitive::from_usize(arg - 1) };
+                log::info!("input grab: {:?}", input_grab);
+                xous::return_scalar(msg.sender, 0).expect("couldn't ack input grab");
+            }),
             Some(Opcode::SelectKeyMap) => {
                 msg_scalar_unpack!(msg, km, _, _, _, { kbd.set_map(KeyMap::from(km)) })
             }
@@ -898,6 +1032,30 @@ fn main() -> ! {
             Some(Opcode::SetChordInterval) => msg_scalar_unpack!(msg, delay, _, _, _, {
                 kbd.set_chord_interval(delay as u32);
             }),
+            Some(Opcode::SetKeyConfig) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let kc = buffer.to_original::<KeyConfigIpc, _>().unwrap();
+                match KeyConfig::parse(kc.config.as_str().unwrap_or("")) {
+                    Ok(config) => {
+                        log::info!(
+                            "key config set: {} remaps, {} macros",
+                            config.remaps.len(),
+                            config.macros.len()
+                        );
+                        kbd.set_key_config(config);
+                    }
+                    Err(e) => log::warn!("ignoring key config: {}", e),
+                }
+            }
+            Some(Opcode::GetLayout) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let layout = KeyLayoutIpc {
+                    map: kbd.get_map(),
+                    summary: xous_ipc::String::from_str(&kbd.key_config().summary()),
+                };
+                buffer.replace(layout).expect("couldn't return layout");
+            }
             Some(Opcode::InjectKey) => msg_scalar_unpack!(msg, k, _, _, _, {
                 // key substitutions to help things work better
                 // 1b5b317e = home
@@ -907,6 +1065,7 @@ fn main() -> ! {
                 // 1b5b42 = down
                 log::trace!("{:x} {}", k, kbd.debug);
                 kbd.debug = 0;
+                ticktimer.report_activity(ticktimer_server::api::Activity::UserInput);
 
                 #[cfg(feature = "rawserial")]
                 {
@@ -1000,28 +1159,29 @@ fn main() -> ! {
                     }
                 }
 
-                if observer_conn.is_some() && observer_op.is_some() {
-                    log::trace!("sending observer key");
-                    xous::try_send_message(
-                        observer_conn.unwrap(),
-                        xous::Message::new_scalar(observer_op.unwrap(), 0, 0, 0, 0),
-                    )
-                    .ok();
+                if input_grab != Some(GrabPolicy::Exclusive) {
+                    notify_observers(&observers, KeyEventKind::Press);
                 }
 
                 #[cfg(all(feature = "debuginject", not(feature = "rawserial")))]
-                for listener in blocking_listener.drain(..) {
-                    // we must unblock anyways once the key is hit; even if the key is invalid,
-                    // send the invalid key. The receiving library function will clean this up into a
-                    // nil-response vector.
-                    xous::return_scalar2(listener, key as u32 as usize, 0).unwrap();
+                if input_grab.is_none() {
+                    for listener in blocking_listener.drain(..) {
+                        // we must unblock anyways once the key is hit; even if the key is invalid,
+                        // send the invalid key. The receiving library function will clean this up into a
+                        // nil-response vector.
+                        xous::return_scalar2(listener, key as u32 as usize, 0).unwrap();
+                    }
                 }
             }),
             Some(Opcode::HandlerTrigger) => {
                 let rawstates = kbd.update();
+                if rawstates.keydowns.len() > 0 || rawstates.keyups.len() > 0 {
+                    ticktimer.report_activity(ticktimer_server::api::Activity::UserInput);
+                }
 
                 if raw_listener_conn.is_some()
                     && raw_listener_op.is_some()
+                    && input_grab.is_none()
                     && (rawstates.keydowns.len() > 0 || rawstates.keyups.len() > 0)
                 {
                     // manual serialization of KeyRawStates, because rkyv can't derive a serializer.
@@ -1042,13 +1202,8 @@ fn main() -> ! {
                         .expect("couldn't send raw scancodes");
                 }
 
-                if observer_conn.is_some() && observer_op.is_some() {
-                    log::trace!("sending observer key");
-                    xous::try_send_message(
-                        observer_conn.unwrap(),
-                        xous::Message::new_scalar(observer_op.unwrap(), 0, 0, 0, 0),
-                    )
-                    .ok();
+                if input_grab != Some(GrabPolicy::Exclusive) {
+                    notify_observers(&observers, KeyEventKind::Press);
                 }
 
                 // interpret scancodes
@@ -1058,10 +1213,39 @@ fn main() -> ! {
                     KeyMap::Braille => kbd.track_chord(&rawstates),
                     _ => kbd.track_keys(&rawstates),
                 };
+                // the timing of long presses and double taps says more about what is typed than plain
+                // keypresses do, so they are withheld during any grab
+                for event in kbd.take_key_events() {
+                    if input_grab.is_none() {
+                        notify_observers(&observers, event);
+                    }
+                }
+                for (server, opcode) in kbd.take_macro_messages() {
+                    if input_grab.is_some() {
+                        log::info!("input is grabbed, not sending key macro message to {}", server);
+                        continue;
+                    }
+                    if !macro_conns.contains_key(&server) {
+                        match xns.request_connection(&server) {
+                            Ok(cid) => {
+                                macro_conns.insert(server.clone(), cid);
+                            }
+                            Err(e) => {
+                                log::warn!("key macro couldn't connect to {}: {:?}", server, e);
+                                continue;
+                            }
+                        }
+                    }
+                    xous::try_send_message(
+                        macro_conns[&server],
+                        xous::Message::new_scalar(opcode, 0, 0, 0, 0),
+                    )
+                    .ok();
+                }
 
                 // send keys, if any
                 // handle the blocking listeners
-                if kc.len() > 0 {
+                if kc.len() > 0 && input_grab.is_none() {
                     for listener in blocking_listener.drain(..) {
                         xous::return_scalar2(
                             listener,
@@ -1130,6 +1314,13 @@ fn main() -> ! {
     xous::terminate_process(0)
 }
 
+fn notify_observers(observers: &[(CID, usize)], kind: KeyEventKind) {
+    for &(conn, op) in observers.iter() {
+        log::trace!("sending observer {:?}", kind);
+        xous::try_send_message(conn, xous::Message::new_scalar(op, kind.to_usize().unwrap(), 0, 0, 0)).ok();
+    }
+}
+
 #[cfg(not(feature = "rawserial"))]
 fn esc_match(esc_chars: &[u8]) -> Result<Option<char>, ()> {
     let mut extended = Vec::<u8>::new();
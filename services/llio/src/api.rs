@@ -4,6 +4,10 @@ mod i2c_api;
 pub use i2c_api::*;
 mod rtc_api;
 pub use rtc_api::*;
+mod gpio_api;
+pub use gpio_api::*;
+mod haptics_api;
+pub use haptics_api::*;
 
 // ///////////////////// UART TYPE
 #[allow(dead_code)] // we use this constant, but only in the `bin` view (not `lib`), so clippy complains, but this seems more discoverable here.
@@ -93,6 +97,8 @@ pub(crate) enum Opcode {
 
     /// vibe motor
     Vibe, //(VibePattern),
+    /// turns the vibe motor on or off; used by the haptics server to play patterns
+    VibeSet, //(bool),
 
     /// not tested -- xadc
     AdcVbus,
@@ -127,9 +133,9 @@ pub(crate) enum Opcode {
     /// SuspendResume callback
     SuspendResume,
 
-    /// sets a wake-up alarm. This forces the SoC into power-on state, if it happens to be off.
-    /// primarily used to trigger cold reboots, but could have other reasons
-    SetWakeupAlarm, //(u8, TimeUnits),
+    /// sets a wake-up alarm. This forces the SoC into power-on state, if it happens to be off, or
+    /// resumes it from suspend. Used to trigger cold reboots, and by the alarm clock.
+    SetWakeupAlarm, //(u8, WakeupUnit),
     /// clear any wakeup alarms that have been set
     ClearWakeupAlarm,
     /// sets an RTC alarm. This just triggers a regular interrupt, no other side-effect
@@ -0,0 +1,55 @@
+// ///////////////////// GPIO header
+/// Public server through which apps use the spare GPIO pins on the expansion header
+pub(crate) const SERVER_NAME_GPIO_HEADER: &str = "_GPIO header_";
+/// Number of GPIO pins on the header. Pins are referred to by bitmasks, bit 0 being GPIO0.
+pub const GPIO_HEADER_PINS: usize = 8;
+/// Maximum length of the app name shown in the permission prompt
+pub const GPIO_APP_NAME_LEN: usize = 32;
+
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub(crate) enum GpioHeaderOpcode {
+    /// claim pins for the exclusive use of the calling process, prompting the user for permission the
+    /// first time a process asks (memory message, `GpioClaim`)
+    Claim,
+    /// release pins, returning them to inputs with their edge events disabled (blocking scalar, pin mask)
+    Release,
+    /// set the claimed pins in the first mask that are also in the second as outputs, the rest as inputs
+    /// (blocking scalar)
+    SetDirection,
+    /// drive the claimed output pins in the first mask to the levels in the second (blocking scalar)
+    Write,
+    /// read the levels of the claimed pins (blocking scalar, returns a pin mask)
+    Read,
+    /// register where the caller's edge events are sent (memory message, `ScalarHook`)
+    EdgeSubscribe,
+    /// enable edge events on the claimed pins in the first mask, on the falling edge for those that are
+    /// also in the second and on the rising edge for the others. An empty mask disables events. (blocking
+    /// scalar)
+    SetEdgeEvents,
+    /// register the service that prompts the user for permission; only the first registration is
+    /// accepted (memory message, `GpioApprover`)
+    SetApprover,
+    /// edge events from the LLIO interrupt handler (scalar, pending pin mask)
+    EdgeEvent,
+    Quit,
+}
+
+/// A request for pins on the header
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct GpioClaim {
+    /// name of the app asking, shown to the user in the permission prompt
+    pub app: xous_ipc::String<GPIO_APP_NAME_LEN>,
+    pub pins: u8,
+    /// PID of the app asking; set by the server before the claim is passed to the approver
+    pub pid: u8,
+    /// set by the server
+    pub granted: bool,
+}
+
+/// Where permission requests are sent: a `GpioClaim` is lent to `sid` with message `id`, and the
+/// approver answers by setting `granted`.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct GpioApprover {
+    pub sid: (u32, u32, u32, u32),
+    pub id: u32,
+}
@@ -0,0 +1,47 @@
+// ///////////////////// Haptics
+/// Public server that plays vibration patterns for apps and the notification center
+pub(crate) const SERVER_NAME_HAPTICS: &str = "_Haptics_";
+/// Maximum number of pulses in a `HapticPattern`
+pub const MAX_HAPTIC_PULSES: usize = 16;
+
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub(crate) enum HapticsOpcode {
+    /// play a pattern, replacing any that is playing (memory message, `HapticPattern`)
+    Play,
+    /// stop the pattern that is playing
+    Stop,
+    /// what the hardware can do (blocking scalar, returns `HAPTIC_CAP_*` flags)
+    Capabilities,
+    /// turn the motor on or off, if the pattern of the given generation is still current; internal
+    Step,
+    Quit,
+}
+
+/// Set if there is a vibration motor at all
+pub(crate) const HAPTIC_CAP_MOTOR: usize = 0b01;
+/// Set if the motor's intensity can be varied
+pub(crate) const HAPTIC_CAP_INTENSITY: usize = 0b10;
+
+/// One pulse of a vibration pattern: the motor runs for `on_ms`, then rests for `off_ms`.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone, Default)]
+pub struct HapticPulse {
+    pub on_ms: u16,
+    pub off_ms: u16,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct HapticPattern {
+    pub pulses: [HapticPulse; MAX_HAPTIC_PULSES],
+    pub len: u32,
+    /// from 0 to 255; motors that can't vary their intensity run at full strength for any non-zero value
+    pub intensity: u8,
+}
+
+/// What the haptics hardware of the device can do
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HapticCapabilities {
+    /// there is a vibration motor; without one, patterns are silently ignored
+    pub motor: bool,
+    /// the motor's intensity can be varied
+    pub intensity: bool,
+}
@@ -2,6 +2,11 @@ use rkyv::{Archive, Deserialize, Serialize};
 
 // ///////////////////// I2C
 pub(crate) const SERVER_NAME_I2C: &str = "_Threaded I2C manager_";
+/// Public server through which user-space drivers reach devices on the I2C bus
+pub(crate) const SERVER_NAME_I2C_DEVICES: &str = "_I2C device registry_";
+/// Bus addresses of the devices used by built-in drivers (the audio codec and the RTC), which can't be
+/// claimed by user-space drivers
+pub const I2C_RESERVED_ADDRS: [u8; 2] = [0x18, 0x68];
 // a small book-keeping struct used to report back to I2C requestors as to the status of a transaction
 #[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Eq, PartialEq)]
 pub enum I2cStatus {
@@ -25,6 +30,8 @@ pub enum I2cStatus {
     ResponseWriteOk,
     /// interrupt handler error
     ResponseInterruptError,
+    /// the bus address is not claimed by the caller
+    ResponseNotClaimed,
 }
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub(crate) enum I2cCallback {
@@ -89,3 +96,23 @@ pub struct I2cResult {
     pub rxlen: u32,
     pub status: I2cStatus,
 }
+
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub(crate) enum I2cRegistryOpcode {
+    /// claim a bus address for the exclusive use of the calling process (blocking scalar)
+    Claim,
+    /// release a claimed bus address (blocking scalar)
+    Release,
+    /// run an `I2cTransaction` on a bus address claimed by the caller, returning an `I2cResult`
+    Transact,
+    /// probe the bus for devices, returning an `I2cScan`
+    Scan,
+    Quit,
+}
+
+/// The result of a bus scan
+#[derive(Debug, Copy, Clone, Archive, Serialize, Deserialize)]
+pub struct I2cScan {
+    /// indexed by 7-bit bus address
+    pub present: [bool; 128],
+}
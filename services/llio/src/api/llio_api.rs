@@ -25,6 +25,25 @@ impl From<VibePattern> for usize {
     }
 }
 
+// ////////////////////////////// WAKEUP ALARM
+/// The unit of a wake-up alarm's delay. The RTC counts down at most `u8::MAX` of them, so the longer
+/// units reach further ahead, at a coarser resolution.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub enum WakeupUnit {
+    Seconds = 0,
+    Minutes = 1,
+    Hours = 2,
+}
+impl WakeupUnit {
+    pub fn secs(&self) -> u64 {
+        match self {
+            WakeupUnit::Seconds => 1,
+            WakeupUnit::Minutes => 60,
+            WakeupUnit::Hours => 3600,
+        }
+    }
+}
+
 // ////////////////////////////// CLOCK GATING (placeholder)
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub enum ClockMode {
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use num_traits::*;
+use xous::{msg_blocking_scalar_unpack, msg_scalar_unpack, Message, CID};
+use xous_ipc::Buffer;
+
+use crate::api::*;
+use crate::holder::*;
+
+/// Where a process's edge events are sent, as set up by `GpioHeader::hook_edge_events()`
+struct EdgeHook {
+    server_to_cb_cid: CID,
+    cb_to_client_cid: CID,
+    cb_to_client_id: u32,
+}
+
+/// Returns the mask of the pins claimed by `holder`, or none if the sender is unknown
+fn owned(owners: &[Option<Holder>; GPIO_HEADER_PINS], holder: Option<Holder>) -> u8 {
+    owners.iter().enumerate().fold(0, |mask, (pin, owner)| {
+        if owner.is_some() && *owner == holder { mask | 1 << pin } else { mask }
+    })
+}
+
+/// Asks the approver whether the process making `claim` may use the header. Claims are refused if no
+/// approver has registered, as there is then no way to ask the user.
+fn ask_approver(approver: Option<(CID, u32)>, claim: GpioClaim) -> bool {
+    let (cid, id) = match approver {
+        Some(approver) => approver,
+        None => {
+            log::warn!("no GPIO approver is registered; refusing claim from PID {}", claim.pid);
+            return false;
+        }
+    };
+    let mut buf = match Buffer::into_buf(claim) {
+        Ok(buf) => buf,
+        Err(_) => return false,
+    };
+    if buf.lend_mut(cid, id).is_err() {
+        return false;
+    }
+    buf.to_original::<GpioClaim, _>().map(|c| c.granted).unwrap_or(false)
+}
+
+/// Lends the spare GPIO pins on the expansion header to apps. Each pin is claimed by one process at a time,
+/// and a process may only claim pins once the user has allowed it to. The prompt is shown by an approver,
+/// normally the status service, as the LLIO can't depend on the UX services. Edge events are passed on to
+/// the processes that own the pins as scalar messages. When a process exits, its pins, its approval and
+/// its edge events go with it, rather than to whichever process is given its PID next.
+pub(crate) fn gpio_header_thread(header_sid: xous::SID) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let mut llio = llio::Llio::new(&xns);
+    let self_cid = xous::connect(header_sid).unwrap();
+    llio.hook_gpio_event_callback(GpioHeaderOpcode::EdgeEvent.to_u32().unwrap(), self_cid).unwrap();
+    let my_pid = xous::process::id();
+
+    let mut owners: [Option<Holder>; GPIO_HEADER_PINS] = [None; GPIO_HEADER_PINS];
+    // processes that the user has allowed to use the header
+    let mut approved: Vec<Holder> = Vec::new();
+    let mut approver: Option<(CID, u32)> = None;
+    let mut hooks: HashMap<Holder, EdgeHook> = HashMap::new();
+    // shadows of the GPIO registers, which are written as a whole
+    let mut drive = 0u8;
+    let mut output = 0u8;
+    let mut int_mask = 0u8;
+    let mut falling = 0u8;
+
+    loop {
+        let mut msg = xous::receive_message(header_sid).unwrap();
+        let opcode: Option<GpioHeaderOpcode> = FromPrimitive::from_usize(msg.body.id());
+        let holder = holder_of(&msg);
+        let sender = holder.map(|h| h.0).unwrap_or_default();
+
+        // forget the processes that have exited; edge events come in too quickly to check on each one
+        if !matches!(opcode, Some(GpioHeaderOpcode::EdgeEvent)) {
+            let gone = owners.iter().enumerate().fold(0u8, |mask, (pin, owner)| {
+                if owner.map_or(false, has_exited) { mask | 1 << pin } else { mask }
+            });
+            if gone != 0 {
+                log::info!("releasing GPIO pins {:08b} of exited processes", gone);
+                for (pin, owner) in owners.iter_mut().enumerate() {
+                    if gone & (1 << pin) != 0 {
+                        *owner = None;
+                    }
+                }
+                drive &= !gone;
+                int_mask &= !gone;
+                llio.gpio_data_direction(drive).ok();
+                llio.gpio_int_mask(int_mask).ok();
+                llio.gpio_int_ena(int_mask).ok();
+            }
+            approved.retain(|&h| !has_exited(h));
+            hooks.retain(|&h, hook| {
+                if has_exited(h) {
+                    unsafe {
+                        xous::disconnect(hook.server_to_cb_cid).ok();
+                    }
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        match opcode {
+            Some(GpioHeaderOpcode::Claim) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("Claim sent as the wrong kind of message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(mut claim) = buffer.to_original::<GpioClaim, _>() else {
+                    log::error!("Claim sent with a malformed claim");
+                    continue;
+                };
+                claim.pid = sender;
+                claim.granted = false;
+                if let Some(holder) = holder {
+                    let free = owners.iter().enumerate().all(|(pin, owner)| {
+                        claim.pins & (1 << pin) == 0 || owner.map_or(true, |o| o == holder)
+                    });
+                    claim.granted = free && (approved.contains(&holder) || ask_approver(approver, claim));
+                    if claim.granted {
+                        log::info!("PID {} ({}) claimed GPIO pins {:08b}", sender, claim.app, claim.pins);
+                        if !approved.contains(&holder) {
+                            approved.push(holder);
+                        }
+                        for (pin, owner) in owners.iter_mut().enumerate() {
+                            if claim.pins & (1 << pin) != 0 {
+                                *owner = Some(holder);
+                            }
+                        }
+                    }
+                }
+                buffer.replace(claim).ok();
+            }
+            Some(GpioHeaderOpcode::Release) => msg_blocking_scalar_unpack!(msg, pins, _, _, _, {
+                let mask = pins as u8 & owned(&owners, holder);
+                for (pin, owner) in owners.iter_mut().enumerate() {
+                    if mask & (1 << pin) != 0 {
+                        *owner = None;
+                    }
+                }
+                // released pins go back to being quiet inputs
+                drive &= !mask;
+                int_mask &= !mask;
+                llio.gpio_data_direction(drive).ok();
+                llio.gpio_int_mask(int_mask).ok();
+                llio.gpio_int_ena(int_mask).ok();
+                if let Some(holder) = holder.filter(|&h| owned(&owners, Some(h)) == 0) {
+                    if let Some(hook) = hooks.remove(&holder) {
+                        unsafe {
+                            xous::disconnect(hook.server_to_cb_cid).ok();
+                        }
+                    }
+                }
+                xous::return_scalar(msg.sender, 1).unwrap();
+            }),
+            Some(GpioHeaderOpcode::SetDirection) => msg_blocking_scalar_unpack!(msg, pins, outputs, _, _, {
+                let mask = pins as u8 & owned(&owners, holder);
+                drive = (drive & !mask) | (outputs as u8 & mask);
+                llio.gpio_data_direction(drive).ok();
+                xous::return_scalar(msg.sender, 1).unwrap();
+            }),
+            Some(GpioHeaderOpcode::Write) => msg_blocking_scalar_unpack!(msg, pins, levels, _, _, {
+                let mask = pins as u8 & owned(&owners, holder);
+                output = (output & !mask) | (levels as u8 & mask);
+                llio.gpio_data_out(output).ok();
+                xous::return_scalar(msg.sender, 1).unwrap();
+            }),
+            Some(GpioHeaderOpcode::Read) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                let levels = llio.gpio_data_in().unwrap_or(0) & owned(&owners, holder);
+                xous::return_scalar(msg.sender, levels as usize).unwrap();
+            }),
+            Some(GpioHeaderOpcode::EdgeSubscribe) => {
+                let Some(mem) = msg.body.memory_message() else {
+                    log::error!("EdgeSubscribe sent as the wrong kind of message");
+                    continue;
+                };
+                let buffer = unsafe { Buffer::from_memory_message(mem) };
+                let Ok(hookdata) = buffer.to_original::<ScalarHook, _>() else {
+                    log::error!("EdgeSubscribe sent with a malformed hook");
+                    continue;
+                };
+                // only processes the user has allowed onto the header get its events
+                let Some(holder) = holder.filter(|h| approved.contains(h)) else {
+                    log::warn!("PID {} asked for GPIO edge events without being allowed to", sender);
+                    continue;
+                };
+                let (s0, s1, s2, s3) = hookdata.sid;
+                match xous::connect(xous::SID::from_u32(s0, s1, s2, s3)) {
+                    Ok(server_to_cb_cid) => {
+                        let hook = EdgeHook {
+                            server_to_cb_cid,
+                            cb_to_client_cid: hookdata.cid,
+                            cb_to_client_id: hookdata.id,
+                        };
+                        if let Some(old) = hooks.insert(holder, hook) {
+                            unsafe {
+                                xous::disconnect(old.server_to_cb_cid).ok();
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("couldn't connect to edge event callback of PID {}: {:?}", sender, e)
+                    }
+                }
+            }
+            Some(GpioHeaderOpcode::SetEdgeEvents) => {
+                msg_blocking_scalar_unpack!(msg, pins, on_falling, _, _, {
+                    let mask = owned(&owners, holder);
+                    int_mask = (int_mask & !mask) | (pins as u8 & mask);
+                    falling = (falling & !mask) | (on_falling as u8 & mask);
+                    llio.gpio_int_as_falling(falling).ok();
+                    llio.gpio_int_mask(int_mask).ok();
+                    llio.gpio_int_ena(int_mask).ok();
+                    xous::return_scalar(msg.sender, 1).unwrap();
+                })
+            }
+            Some(GpioHeaderOpcode::SetApprover) => {
+                let Some(mem) = msg.body.memory_message() else {
+                    log::error!("SetApprover sent as the wrong kind of message");
+                    continue;
+                };
+                let buffer = unsafe { Buffer::from_memory_message(mem) };
+                let Ok(registration) = buffer.to_original::<GpioApprover, _>() else {
+                    log::error!("SetApprover sent with a malformed registration");
+                    continue;
+                };
+                if approver.is_none() {
+                    let (s0, s1, s2, s3) = registration.sid;
+                    match xous::connect(xous::SID::from_u32(s0, s1, s2, s3)) {
+                        Ok(cid) => approver = Some((cid, registration.id)),
+                        Err(e) => log::error!("couldn't connect to GPIO approver: {:?}", e),
+                    }
+                } else {
+                    log::warn!("PID {} tried to replace the GPIO approver; ignoring", sender);
+                }
+            }
+            Some(GpioHeaderOpcode::EdgeEvent) => msg_scalar_unpack!(msg, pending, _, _, _, {
+                // events only come from our own interrupt hook
+                if sender as u32 != my_pid {
+                    continue;
+                }
+                for (&holder, hook) in hooks.iter() {
+                    let pins = pending as u8 & int_mask & owned(&owners, Some(holder));
+                    if pins != 0 {
+                        // drop events rather than stall the header if an app can't keep up
+                        xous::try_send_message(
+                            hook.server_to_cb_cid,
+                            Message::new_scalar(
+                                EventCallback::Event.to_usize().unwrap(),
+                                hook.cb_to_client_cid as usize,
+                                hook.cb_to_client_id as usize,
+                                pins as usize,
+                                0,
+                            ),
+                        )
+                        .ok();
+                    }
+                }
+            }),
+            Some(GpioHeaderOpcode::Quit) => {
+                // only llio itself may stop the header
+                if sender as u32 != my_pid {
+                    log::warn!("Quit sent to the GPIO header by PID {}", sender);
+                    continue;
+                }
+                log::warn!("Quit received on GPIO header");
+                break;
+            }
+            None => log::error!("unknown GPIO header opcode: {:?}", msg),
+        }
+    }
+    xns.unregister_server(header_sid).unwrap();
+    xous::destroy_server(header_sid).unwrap();
+}
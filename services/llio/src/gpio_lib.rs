@@ -0,0 +1,182 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use num_traits::*;
+use xous::{send_message, Message, CID};
+use xous_ipc::Buffer;
+
+use crate::api::*;
+use crate::llio_lib::{drop_conn, gpio_cb_server};
+
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+
+/// An app's handle on pins of the GPIO expansion header, for hardware hacks that don't need a driver in
+/// the kernel. Pins are referred to by bitmasks, bit 0 being GPIO0. The pins are claimed for the exclusive
+/// use of the calling process until the handle is dropped, at which point they return to being inputs.
+#[derive(Debug)]
+pub struct GpioHeader {
+    conn: CID,
+    pins: u8,
+    cb_sid: Option<xous::SID>,
+}
+impl GpioHeader {
+    /// Claims `pins`. The first time a process asks for pins, the user is asked whether to allow `app` to
+    /// use the header, so this may block for a while. Fails with `AccessDenied` if the user refuses, or if
+    /// any of the pins are claimed by another process.
+    pub fn claim(xns: &xous_names::XousNames, app: &str, pins: u8) -> Result<Self, xous::Error> {
+        if app.len() > GPIO_APP_NAME_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        let conn =
+            xns.request_connection_blocking(SERVER_NAME_GPIO_HEADER).expect("Can't connect to GPIO header");
+        // construct the handle first, so that the connection is cleaned up by `Drop` on failure. Releasing
+        // pins that aren't ours is ignored by the server.
+        let mut header = GpioHeader { conn, pins: 0, cb_sid: None };
+        let claim = GpioClaim { app: xous_ipc::String::from_str(app), pins, pid: 0, granted: false };
+        let mut buf = Buffer::into_buf(claim).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(conn, GpioHeaderOpcode::Claim.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        if buf.to_original::<GpioClaim, _>().or(Err(xous::Error::InternalError))?.granted {
+            header.pins = pins;
+            Ok(header)
+        } else {
+            Err(xous::Error::AccessDenied)
+        }
+    }
+
+    /// The pins held by this handle
+    pub fn pins(&self) -> u8 { self.pins }
+
+    /// Makes the pins in `outputs` outputs, and the others held by this handle inputs. All pins start out
+    /// as inputs.
+    pub fn set_direction(&self, outputs: u8) -> Result<(), xous::Error> {
+        send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                GpioHeaderOpcode::SetDirection.to_usize().unwrap(),
+                self.pins as usize,
+                outputs as usize,
+                0,
+                0,
+            ),
+        )
+        .map(|_| ())
+    }
+
+    /// Drives the output pins in `pins` to the levels in `levels`, leaving the other outputs as they are.
+    pub fn write(&self, pins: u8, levels: u8) -> Result<(), xous::Error> {
+        send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                GpioHeaderOpcode::Write.to_usize().unwrap(),
+                (pins & self.pins) as usize,
+                levels as usize,
+                0,
+                0,
+            ),
+        )
+        .map(|_| ())
+    }
+
+    /// Returns the levels of the pins held by this handle.
+    pub fn read(&self) -> Result<u8, xous::Error> {
+        let response = send_message(
+            self.conn,
+            Message::new_blocking_scalar(GpioHeaderOpcode::Read.to_usize().unwrap(), 0, 0, 0, 0),
+        )?;
+        if let xous::Result::Scalar1(levels) = response {
+            Ok(levels as u8 & self.pins)
+        } else {
+            Err(xous::Error::InternalError)
+        }
+    }
+
+    /// Has edge events sent to `cid` as scalar messages with ID `id`, the first argument being the mask
+    /// of the pins that had an edge. Events are enabled with `set_edge_events()`.
+    ///
+    /// Events can be missed while the CPU is in its WFI power saving mode, so apps that rely on them
+    /// should turn WFI off for as long as they are listening.
+    pub fn hook_edge_events(&mut self, id: u32, cid: CID) -> Result<(), xous::Error> {
+        if self.cb_sid.is_some() {
+            return Err(xous::Error::MemoryInUse); // can't hook it twice
+        }
+        let sid = xous::create_server().unwrap();
+        self.cb_sid = Some(sid);
+        let sid_tuple = sid.to_u32();
+        xous::create_thread_4(
+            gpio_cb_server,
+            sid_tuple.0 as usize,
+            sid_tuple.1 as usize,
+            sid_tuple.2 as usize,
+            sid_tuple.3 as usize,
+        )
+        .unwrap();
+        let hookdata = ScalarHook { sid: sid_tuple, id, cid };
+        let buf = Buffer::into_buf(hookdata).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, GpioHeaderOpcode::EdgeSubscribe.to_u32().unwrap()).map(|_| ())
+    }
+
+    /// Enables edge events on `pins`: on the falling edge for the pins that are also in `falling`, and on
+    /// the rising edge for the others. Events on any other pins held by this handle are disabled. The
+    /// hardware can only watch one edge per pin.
+    pub fn set_edge_events(&self, pins: u8, falling: u8) -> Result<(), xous::Error> {
+        send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                GpioHeaderOpcode::SetEdgeEvents.to_usize().unwrap(),
+                (pins & self.pins) as usize,
+                falling as usize,
+                0,
+                0,
+            ),
+        )
+        .map(|_| ())
+    }
+
+    /// Registers the server that asks the user for permission on behalf of the GPIO header. Only the
+    /// first registration is accepted; this is done by the status service at boot.
+    pub fn register_approver(
+        xns: &xous_names::XousNames,
+        sid: xous::SID,
+        id: u32,
+    ) -> Result<(), xous::Error> {
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        let conn =
+            xns.request_connection_blocking(SERVER_NAME_GPIO_HEADER).expect("Can't connect to GPIO header");
+        let approver = GpioApprover { sid: sid.to_u32(), id };
+        let result = Buffer::into_buf(approver)
+            .or(Err(xous::Error::InternalError))
+            .and_then(|buf| buf.lend(conn, GpioHeaderOpcode::SetApprover.to_u32().unwrap()).map(|_| ()));
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(conn).ok();
+            }
+        }
+        result
+    }
+}
+
+impl Drop for GpioHeader {
+    fn drop(&mut self) {
+        if self.pins != 0 {
+            send_message(
+                self.conn,
+                Message::new_blocking_scalar(
+                    GpioHeaderOpcode::Release.to_usize().unwrap(),
+                    self.pins as usize,
+                    0,
+                    0,
+                    0,
+                ),
+            )
+            .ok();
+        }
+        if let Some(sid) = self.cb_sid.take() {
+            drop_conn(sid);
+        }
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).ok();
+            }
+        }
+    }
+}
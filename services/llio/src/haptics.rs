@@ -0,0 +1,137 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use num_traits::*;
+use xous::{msg_blocking_scalar_unpack, msg_scalar_unpack, Message, CID};
+use xous_ipc::Buffer;
+
+use crate::api::*;
+
+fn set_motor(llio_cid: CID, on: bool) {
+    xous::send_message(
+        llio_cid,
+        Message::new_scalar(Opcode::VibeSet.to_usize().unwrap(), if on { 1 } else { 0 }, 0, 0, 0),
+    )
+    .ok();
+}
+
+/// The pattern waiting to be played, and the generation it was queued at. A newer pattern or a `Stop` bumps
+/// the generation, which cuts the one that is playing short.
+struct Player {
+    generation: u32,
+    pending: Option<HapticPattern>,
+}
+
+/// Plays patterns one at a time on a single thread, so the server stays free to take a new pattern. Each step
+/// goes back through the server, which drops the steps of patterns that have been superseded.
+fn pattern_worker(player: Arc<(Mutex<Player>, Condvar)>, self_cid: CID) {
+    let (lock, cvar) = &*player;
+    let step = |gen: u32, on: bool| {
+        xous::send_message(
+            self_cid,
+            Message::new_scalar(HapticsOpcode::Step.to_usize().unwrap(), gen as usize, on as usize, 0, 0),
+        )
+        .ok();
+    };
+    loop {
+        let (pattern, gen) = {
+            let mut state = lock.lock().unwrap();
+            loop {
+                match state.pending.take() {
+                    Some(pattern) => break (pattern, state.generation),
+                    None => state = cvar.wait(state).unwrap(),
+                }
+            }
+        };
+        'pattern: for pulse in pattern.pulses[..(pattern.len as usize).min(MAX_HAPTIC_PULSES)].iter() {
+            for (on, ms) in [(true, pulse.on_ms), (false, pulse.off_ms)] {
+                // the lock isn't held while stepping, as the server takes it to check the step
+                step(gen, on);
+                // waits out the pulse, unless a newer pattern or a stop comes in first
+                let (state, _) = cvar
+                    .wait_timeout_while(lock.lock().unwrap(), Duration::from_millis(ms as u64), |p| {
+                        p.generation == gen
+                    })
+                    .unwrap();
+                if state.generation != gen {
+                    break 'pattern;
+                }
+            }
+        }
+        step(gen, false);
+    }
+}
+
+/// Plays vibration patterns on behalf of apps, so they don't need to drive the motor themselves. The
+/// Precursor's motor can only be turned on and off, so the intensity of a pattern is not supported.
+pub(crate) fn haptics_thread(haptics_sid: xous::SID, llio_cid: CID) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let self_cid = xous::connect(haptics_sid).unwrap();
+    let my_pid = xous::process::id();
+    // the generation is bumped whenever a pattern starts or stops, so that the steps of a superseded pattern
+    // are ignored
+    let player = Arc::new((Mutex::new(Player { generation: 0, pending: None }), Condvar::new()));
+    std::thread::spawn({
+        let player = player.clone();
+        move || pattern_worker(player, self_cid)
+    });
+    // bumps the generation, and hands `pattern` to the worker
+    let replace = |pattern: Option<HapticPattern>| {
+        let (lock, cvar) = &*player;
+        let mut state = lock.lock().unwrap();
+        state.generation = state.generation.wrapping_add(1);
+        state.pending = pattern;
+        cvar.notify_all();
+    };
+    let caps = if cfg!(any(feature = "precursor", feature = "renode")) { HAPTIC_CAP_MOTOR } else { 0 };
+
+    loop {
+        let msg = xous::receive_message(haptics_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(HapticsOpcode::Play) => {
+                let Some(mem) = msg.body.memory_message() else {
+                    log::error!("haptics Play was not a memory message");
+                    continue;
+                };
+                let buffer = unsafe { Buffer::from_memory_message(mem) };
+                let Ok(pattern) = buffer.to_original::<HapticPattern, _>() else {
+                    log::error!("couldn't deserialize a haptic pattern");
+                    continue;
+                };
+                set_motor(llio_cid, false);
+                if caps & HAPTIC_CAP_MOTOR == 0 {
+                    log::info!("no vibe motor; ignoring a pattern of {} pulses", pattern.len);
+                    replace(None);
+                    continue;
+                }
+                replace(if pattern.intensity == 0 { None } else { Some(pattern) });
+            }
+            Some(HapticsOpcode::Stop) => msg_scalar_unpack!(msg, _, _, _, _, {
+                replace(None);
+                set_motor(llio_cid, false);
+            }),
+            Some(HapticsOpcode::Capabilities) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                xous::return_scalar(msg.sender, caps).unwrap();
+            }),
+            Some(HapticsOpcode::Step) => msg_scalar_unpack!(msg, gen, on, _, _, {
+                // steps only come from our own pattern threads
+                if msg.sender.pid().map(|p| p.get() as u32) == Some(my_pid)
+                    && gen as u32 == player.0.lock().unwrap().generation
+                {
+                    set_motor(llio_cid, on != 0);
+                }
+            }),
+            Some(HapticsOpcode::Quit) => {
+                log::warn!("Quit received on haptics server");
+                break;
+            }
+            None => log::error!("unknown haptics opcode: {:?}", msg),
+        }
+    }
+    set_motor(llio_cid, false);
+    unsafe {
+        xous::disconnect(self_cid).ok();
+    }
+    xns.unregister_server(haptics_sid).unwrap();
+    xous::destroy_server(haptics_sid).unwrap();
+}
@@ -0,0 +1,81 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use num_traits::*;
+use xous::{send_message, Message, CID};
+use xous_ipc::Buffer;
+
+use crate::api::*;
+
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+
+/// Plays vibration patterns, for apps and the notification center to get the user's attention. Check
+/// `capabilities()` before relying on vibration: not all hardware has a motor.
+#[derive(Debug)]
+pub struct Haptics {
+    conn: CID,
+}
+impl Haptics {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        let conn = xns.request_connection_blocking(SERVER_NAME_HAPTICS).expect("Can't connect to Haptics");
+        Ok(Haptics { conn })
+    }
+
+    pub fn capabilities(&self) -> Result<HapticCapabilities, xous::Error> {
+        let response = send_message(
+            self.conn,
+            Message::new_blocking_scalar(HapticsOpcode::Capabilities.to_usize().unwrap(), 0, 0, 0, 0),
+        )?;
+        if let xous::Result::Scalar1(caps) = response {
+            Ok(HapticCapabilities {
+                motor: caps & HAPTIC_CAP_MOTOR != 0,
+                intensity: caps & HAPTIC_CAP_INTENSITY != 0,
+            })
+        } else {
+            Err(xous::Error::InternalError)
+        }
+    }
+
+    /// Plays `pulses` at `intensity` (0-255), replacing any pattern that is playing. Returns as soon as the
+    /// pattern has started.
+    pub fn play(&self, pulses: &[HapticPulse], intensity: u8) -> Result<(), xous::Error> {
+        if pulses.len() > MAX_HAPTIC_PULSES {
+            return Err(xous::Error::OutOfMemory);
+        }
+        let mut pattern = HapticPattern {
+            pulses: [HapticPulse::default(); MAX_HAPTIC_PULSES],
+            len: pulses.len() as u32,
+            intensity,
+        };
+        pattern.pulses[..pulses.len()].copy_from_slice(pulses);
+        let buf = Buffer::into_buf(pattern).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, HapticsOpcode::Play.to_u32().unwrap()).map(|_| ())
+    }
+
+    /// Plays one of the standard patterns at full intensity.
+    pub fn vibe(&self, pattern: VibePattern) -> Result<(), xous::Error> {
+        let pulses: &[HapticPulse] = match pattern {
+            VibePattern::Short => &[HapticPulse { on_ms: 80, off_ms: 0 }],
+            VibePattern::Long => &[HapticPulse { on_ms: 1000, off_ms: 0 }],
+            VibePattern::Double => {
+                &[HapticPulse { on_ms: 150, off_ms: 250 }, HapticPulse { on_ms: 150, off_ms: 0 }]
+            }
+        };
+        self.play(pulses, 255)
+    }
+
+    pub fn stop(&self) -> Result<(), xous::Error> {
+        send_message(self.conn, Message::new_scalar(HapticsOpcode::Stop.to_usize().unwrap(), 0, 0, 0, 0))
+            .map(|_| ())
+    }
+}
+
+impl Drop for Haptics {
+    fn drop(&mut self) {
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
@@ -125,6 +125,25 @@ impl I2c {
         }
     }
 
+    /// Runs a transaction as given, and returns its result whatever the status. Used by the device registry
+    /// to pass on transactions from user-space drivers.
+    pub fn i2c_transact(&mut self, transaction: I2cTransaction) -> Result<I2cResult, xous::Error> {
+        let mut buf = Buffer::into_buf(transaction).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, I2cOpcode::I2cTxRx.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        buf.to_original::<I2cResult, _>().or(Err(xous::Error::InternalError))
+    }
+
+    /// Returns `true` if a device acknowledges a one-byte read at `dev`. A read is used rather than an
+    /// empty write, as it can't change the state of a device.
+    pub fn i2c_probe(&mut self, dev: u8) -> bool {
+        let mut transaction = I2cTransaction::new();
+        transaction.bus_addr = dev;
+        transaction.rxbuf = Some([0; I2C_MAX_LEN]);
+        transaction.rxlen = 1;
+        transaction.timeout_ms = self.timeout_ms;
+        matches!(self.i2c_transact(transaction), Ok(I2cResult { status: I2cStatus::ResponseReadOk, .. }))
+    }
+
     /// initiate an i2c read, but for devices that don't support repeated starts, such as the AB-RTCMC-32.768
     pub fn i2c_read_no_repeated_start(
         &mut self,
@@ -174,3 +193,151 @@ impl Drop for I2c {
         }
     }
 }
+
+static DEVICE_REFCOUNT: AtomicU32 = AtomicU32::new(0);
+
+/// A user-space driver's handle on a device on the I2C bus, such as an add-on sensor on the internal
+/// header. The device is claimed for the exclusive use of the calling process until the handle is dropped,
+/// so drivers in different processes can't interleave transactions with each other. Each transaction is
+/// atomic on the bus with respect to the built-in drivers.
+#[derive(Debug)]
+pub struct I2cDevice {
+    conn: CID,
+    addr: u8,
+    timeout_ms: u32,
+}
+impl I2cDevice {
+    /// Claims the device at the 7-bit bus address `addr`. Fails with `AccessDenied` if the address belongs
+    /// to a built-in driver or is claimed by another process. The claim is released when this is dropped,
+    /// or if the process exits without dropping it.
+    pub fn claim(xns: &xous_names::XousNames, addr: u8) -> Result<Self, xous::Error> {
+        if addr > 0x7F {
+            return Err(xous::Error::BadAddress);
+        }
+        DEVICE_REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        let conn =
+            xns.request_connection_blocking(SERVER_NAME_I2C_DEVICES).expect("Can't connect to I2C registry");
+        // construct the handle first, so that the connection is cleaned up by `Drop` on failure. The
+        // registry ignores releases of claims that belong to another process.
+        let device = I2cDevice { conn, addr, timeout_ms: 150 };
+        let response = xous::send_message(
+            conn,
+            xous::Message::new_blocking_scalar(
+                I2cRegistryOpcode::Claim.to_usize().unwrap(),
+                addr as usize,
+                0,
+                0,
+                0,
+            ),
+        )?;
+        if let xous::Result::Scalar1(1) = response { Ok(device) } else { Err(xous::Error::AccessDenied) }
+    }
+
+    pub fn set_timeout(&mut self, timeout_ms: u32) { self.timeout_ms = timeout_ms; }
+
+    fn transact(
+        &self,
+        tx: &[u8],
+        rx: Option<&mut [u8]>,
+        use_repeated_start: bool,
+    ) -> Result<(), xous::Error> {
+        if tx.len() > I2C_MAX_LEN || rx.as_ref().map_or(0, |r| r.len()) > I2C_MAX_LEN {
+            return Err(xous::Error::OutOfMemory);
+        }
+        let mut transaction = I2cTransaction::new();
+        transaction.bus_addr = self.addr;
+        if !tx.is_empty() {
+            let mut txbuf = [0; I2C_MAX_LEN];
+            txbuf[..tx.len()].copy_from_slice(tx);
+            transaction.txbuf = Some(txbuf);
+            transaction.txlen = tx.len() as u32;
+        }
+        if let Some(rx) = rx.as_ref() {
+            transaction.rxbuf = Some([0; I2C_MAX_LEN]);
+            transaction.rxlen = rx.len() as u32;
+        }
+        transaction.timeout_ms = self.timeout_ms;
+        transaction.use_repeated_start = use_repeated_start;
+
+        let mut buf = Buffer::into_buf(transaction).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, I2cRegistryOpcode::Transact.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let result = buf.to_original::<I2cResult, _>().unwrap();
+        match result.status {
+            I2cStatus::ResponseWriteOk => Ok(()),
+            I2cStatus::ResponseReadOk => {
+                if let Some(rx) = rx {
+                    for (&src, dst) in result.rxbuf[..result.rxlen as usize].iter().zip(rx.iter_mut()) {
+                        *dst = src;
+                    }
+                }
+                Ok(())
+            }
+            I2cStatus::ResponseNotClaimed => Err(xous::Error::AccessDenied),
+            I2cStatus::ResponseNack => Err(xous::Error::ServerNotFound),
+            I2cStatus::ResponseTimeout | I2cStatus::ResponseBusy => Err(xous::Error::Timeout),
+            _ => {
+                log::error!("I2C error: {:?}", result);
+                Err(xous::Error::InternalError)
+            }
+        }
+    }
+
+    /// Writes `data` to the device.
+    ///
+    /// Fails with `ServerNotFound` if the device doesn't acknowledge, `Timeout` if the bus is stuck, and
+    /// `AccessDenied` if the claim was lost. The same goes for reads.
+    pub fn write(&self, data: &[u8]) -> Result<(), xous::Error> { self.transact(data, None, true) }
+
+    /// Reads `data.len()` bytes from the device.
+    pub fn read(&self, data: &mut [u8]) -> Result<(), xous::Error> { self.transact(&[], Some(data), true) }
+
+    /// Writes `tx`, then reads `rx.len()` bytes after a repeated start, as is usual for reading registers.
+    /// Set `use_repeated_start` to `false` for devices that need a stop in between.
+    pub fn write_read(&self, tx: &[u8], rx: &mut [u8], use_repeated_start: bool) -> Result<(), xous::Error> {
+        self.transact(tx, Some(rx), use_repeated_start)
+    }
+}
+
+impl Drop for I2cDevice {
+    fn drop(&mut self) {
+        xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(
+                I2cRegistryOpcode::Release.to_usize().unwrap(),
+                self.addr as usize,
+                0,
+                0,
+                0,
+            ),
+        )
+        .ok();
+        if DEVICE_REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).ok();
+            }
+        }
+    }
+}
+
+/// Probes the I2C bus, and returns the 7-bit addresses of the devices that responded. Devices used by
+/// built-in drivers aren't probed, and are always listed.
+pub fn i2c_scan(xns: &xous_names::XousNames) -> Result<Vec<u8>, xous::Error> {
+    DEVICE_REFCOUNT.fetch_add(1, Ordering::Relaxed);
+    let conn =
+        xns.request_connection_blocking(SERVER_NAME_I2C_DEVICES).expect("Can't connect to I2C registry");
+    let result = Buffer::into_buf(I2cScan { present: [false; 128] })
+        .or(Err(xous::Error::InternalError))
+        .and_then(|mut buf| {
+            buf.lend_mut(conn, I2cRegistryOpcode::Scan.to_u32().unwrap())
+                .or(Err(xous::Error::InternalError))?;
+            buf.to_original::<I2cScan, _>().or(Err(xous::Error::InternalError))
+        });
+    if DEVICE_REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+        unsafe {
+            xous::disconnect(conn).ok();
+        }
+    }
+    let scan = result?;
+    Ok((0..128u8).filter(|&addr| scan.present[addr as usize]).collect())
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use num_traits::*;
+use xous::msg_blocking_scalar_unpack;
+use xous_ipc::Buffer;
+
+use crate::api::*;
+use crate::holder::*;
+
+/// Lowest and highest addresses probed by a scan; the others are reserved by the I2C specification
+const SCAN_FIRST_ADDR: u8 = 0x08;
+const SCAN_LAST_ADDR: u8 = 0x77;
+
+/// Arbitrates access to the I2C bus for user-space drivers. Each device address can be claimed by one
+/// process at a time, and the devices used by the built-in drivers can't be claimed at all. Every
+/// transaction is run with the I2C mutex held, so it can't be interleaved with those of the built-in
+/// drivers. A claim whose holder has exited is released the next time another process asks for it.
+pub(crate) fn i2c_registry_thread(registry_sid: xous::SID) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let mut i2c = llio::I2c::new(&xns);
+    // bus address -> the claiming process
+    let mut claims: HashMap<u8, Holder> = HashMap::new();
+
+    loop {
+        let mut msg = xous::receive_message(registry_sid).unwrap();
+        let sender = holder_of(&msg);
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(I2cRegistryOpcode::Claim) => msg_blocking_scalar_unpack!(msg, addr, _, _, _, {
+                let addr = addr as u8;
+                let granted = match sender {
+                    None => false,
+                    Some(sender) if I2C_RESERVED_ADDRS.contains(&addr) => {
+                        log::warn!("PID {} tried to claim reserved I2C address {:x}", sender.0, addr);
+                        false
+                    }
+                    Some(sender) => match claims.get(&addr) {
+                        Some(&holder) if holder == sender => true,
+                        Some(&holder) if !has_exited(holder) => false,
+                        _ => {
+                            if let Some(holder) = claims.get(&addr) {
+                                log::info!("PID {} exited holding I2C address {:x}", holder.0, addr);
+                            }
+                            log::info!("PID {} claimed I2C address {:x}", sender.0, addr);
+                            claims.insert(addr, sender);
+                            true
+                        }
+                    }
+                };
+                xous::return_scalar(msg.sender, if granted { 1 } else { 0 }).unwrap();
+            }),
+            Some(I2cRegistryOpcode::Release) => msg_blocking_scalar_unpack!(msg, addr, _, _, _, {
+                let addr = addr as u8;
+                if sender.is_some() && claims.get(&addr) == sender.as_ref() {
+                    log::info!(
+                        "PID {} released I2C address {:x}",
+                        sender.map(|s| s.0).unwrap_or_default(),
+                        addr
+                    );
+                    claims.remove(&addr);
+                }
+                xous::return_scalar(msg.sender, 1).unwrap();
+            }),
+            Some(I2cRegistryOpcode::Transact) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("Transact sent as the wrong kind of message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(transaction) = buffer.to_original::<llio::I2cTransaction, _>() else {
+                    log::error!("Transact sent with a malformed transaction");
+                    continue;
+                };
+                let result = if sender.is_none() || claims.get(&transaction.bus_addr) != sender.as_ref() {
+                    llio::I2cResult {
+                        rxbuf: [0; I2C_MAX_LEN],
+                        rxlen: 0,
+                        status: llio::I2cStatus::ResponseNotClaimed,
+                    }
+                } else {
+                    i2c.i2c_mutex_acquire();
+                    let result = i2c.i2c_transact(transaction);
+                    i2c.i2c_mutex_release();
+                    result.unwrap_or(llio::I2cResult {
+                        rxbuf: [0; I2C_MAX_LEN],
+                        rxlen: 0,
+                        status: llio::I2cStatus::ResponseInterruptError,
+                    })
+                };
+                buffer.replace(result).unwrap();
+            }
+            Some(I2cRegistryOpcode::Scan) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("Scan sent as the wrong kind of message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let mut scan = I2cScan { present: [false; 128] };
+                for addr in SCAN_FIRST_ADDR..=SCAN_LAST_ADDR {
+                    if I2C_RESERVED_ADDRS.contains(&addr) {
+                        // don't disturb the built-in drivers' devices, which are always fitted
+                        scan.present[addr as usize] = true;
+                        continue;
+                    }
+                    // release the mutex between probes, so the built-in drivers aren't held off for the
+                    // whole scan
+                    i2c.i2c_mutex_acquire();
+                    scan.present[addr as usize] = i2c.i2c_probe(addr);
+                    i2c.i2c_mutex_release();
+                }
+                buffer.replace(scan).unwrap();
+            }
+            Some(I2cRegistryOpcode::Quit) => {
+                // only llio itself may stop the registry
+                if msg.sender.pid().map(|pid| pid.get() as u32) != Some(xous::process::id()) {
+                    log::warn!("Quit sent to the I2C registry by another process");
+                    continue;
+                }
+                log::warn!("Quit received on I2C registry");
+                break;
+            }
+            None => log::error!("unknown I2C registry opcode: {:?}", msg),
+        }
+    }
+    xns.unregister_server(registry_sid).unwrap();
+    xous::destroy_server(registry_sid).unwrap();
+}
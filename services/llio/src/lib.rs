@@ -6,7 +6,11 @@ pub mod api;
 pub use api::*;
 
 pub mod i2c_lib;
-pub use i2c_lib::I2c;
+pub use i2c_lib::{i2c_scan, I2c, I2cDevice};
+pub mod gpio_lib;
+pub use gpio_lib::GpioHeader;
+pub mod haptics_lib;
+pub use haptics_lib::Haptics;
 pub mod llio_lib;
 use core::sync::atomic::{AtomicU32, Ordering};
 
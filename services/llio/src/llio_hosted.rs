@@ -64,6 +64,10 @@ impl Llio {
         log::info!("Imagine your keyboard vibrating: {:?}", pattern);
     }
 
+    pub fn vibe_set(&self, on: bool) {
+        log::debug!("Imagine your keyboard vibrating: {}", on);
+    }
+
     pub fn xadc_vbus(&self) -> u16 {
         2 // some small but non-zero value to represent typical noise
     }
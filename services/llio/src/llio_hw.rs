@@ -439,6 +439,10 @@ impl Llio {
         }
     }
 
+    pub fn vibe_set(&mut self, on: bool) {
+        self.power_csr.wfo(utra::power::VIBE_VIBE, if on { 1 } else { 0 });
+    }
+
     /// this vibrates until the device sleeps
     #[allow(dead_code)]
     pub fn tts_sleep_indicate(&mut self) { self.power_csr.wfo(utra::power::VIBE_VIBE, 1); }
@@ -223,6 +223,13 @@ impl Llio {
             .map(|_| ())
     }
 
+    /// Hosted mode has no EC interrupt line, so the emulated EC in the COM server signals through here.
+    #[cfg(not(target_os = "xous"))]
+    pub fn hosted_com_event(&self) -> Result<(), xous::Error> {
+        send_message(self.conn, Message::new_scalar(Opcode::EventComHappened.to_usize().unwrap(), 0, 0, 0, 0))
+            .map(|_| ())
+    }
+
     /// Called by the updater routine when EC is ready to run. Note: no authenticity check is done, anyone
     /// could call this and troll the system.
     pub fn set_ec_ready(&self, ready: bool) {
@@ -257,7 +264,8 @@ impl Llio {
     }
 
     /// GPIO IRQ hook. When using this, ensure that the WFI power saving mode is turned off.
-    /// Otherwise interrupts that hit during power save mode can be missed.
+    /// Otherwise interrupts that hit during power save mode can be missed. The mask of the pins that
+    /// raised the interrupt is passed as the first argument of the message.
     pub fn hook_gpio_event_callback(&mut self, id: u32, cid: CID) -> Result<(), xous::Error> {
         log::info!(
             "If relying on GPIO interrupts, WFI power saving must be turned off. Interrupts that hit while powersaving can be missed."
@@ -379,6 +387,48 @@ impl Llio {
         }
     }
 
+    pub fn gpio_data_out(&self, d: u8) -> Result<(), xous::Error> {
+        send_message(
+            self.conn,
+            Message::new_scalar(Opcode::GpioDataOut.to_usize().unwrap(), d as usize, 0, 0, 0),
+        )
+        .map(|_| ())
+    }
+
+    pub fn gpio_data_in(&self) -> Result<u8, xous::Error> {
+        let response = send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::GpioDataIn.to_usize().unwrap(), 0, 0, 0, 0),
+        )?;
+        if let xous::Result::Scalar1(d) = response { Ok(d as u8) } else { Err(xous::Error::InternalError) }
+    }
+
+    /// Selects the pins that can raise interrupts
+    pub fn gpio_int_mask(&self, mask: u8) -> Result<(), xous::Error> {
+        send_message(
+            self.conn,
+            Message::new_scalar(Opcode::GpioIntMask.to_usize().unwrap(), mask as usize, 0, 0, 0),
+        )
+        .map(|_| ())
+    }
+
+    /// Selects the pins that interrupt on a falling edge; the others interrupt on a rising edge
+    pub fn gpio_int_as_falling(&self, mask: u8) -> Result<(), xous::Error> {
+        send_message(
+            self.conn,
+            Message::new_scalar(Opcode::GpioIntAsFalling.to_usize().unwrap(), mask as usize, 0, 0, 0),
+        )
+        .map(|_| ())
+    }
+
+    pub fn gpio_int_ena(&self, mask: u8) -> Result<(), xous::Error> {
+        send_message(
+            self.conn,
+            Message::new_scalar(Opcode::GpioIntEna.to_usize().unwrap(), mask as usize, 0, 0, 0),
+        )
+        .map(|_| ())
+    }
+
     pub fn gpio_data_direction(&self, dir: u8) -> Result<(), xous::Error> {
         send_message(
             self.conn,
@@ -444,6 +494,35 @@ impl Llio {
         .map(|_| ())
     }
 
+    /// Sets a wakeup alarm up to 255 hours ahead. Delays over 255 seconds are counted in minutes, and over
+    /// 255 minutes in hours, rounding down, so the alarm may go off early; the delay actually set is
+    /// returned, and the caller should set another alarm for the rest.
+    pub fn set_wakeup_alarm_in(
+        &self,
+        delay: core::time::Duration,
+    ) -> Result<core::time::Duration, xous::Error> {
+        let secs = delay.as_secs();
+        let unit = if secs <= u8::MAX as u64 {
+            WakeupUnit::Seconds
+        } else if secs / WakeupUnit::Minutes.secs() <= u8::MAX as u64 {
+            WakeupUnit::Minutes
+        } else {
+            WakeupUnit::Hours
+        };
+        let count = (secs / unit.secs()).clamp(1, u8::MAX as u64);
+        send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                Opcode::SetWakeupAlarm.to_usize().unwrap(),
+                count as usize,
+                unit.to_usize().unwrap(),
+                0,
+                0,
+            ),
+        )?;
+        Ok(core::time::Duration::from_secs(count * unit.secs()))
+    }
+
     pub fn clear_wakeup_alarm(&self) -> Result<(), xous::Error> {
         send_message(
             self.conn,
@@ -472,7 +551,7 @@ impl Llio {
     }
 }
 
-fn drop_conn(sid: xous::SID) {
+pub(crate) fn drop_conn(sid: xous::SID) {
     let cid = xous::connect(sid).unwrap();
     xous::send_message(cid, Message::new_scalar(EventCallback::Drop.to_usize().unwrap(), 0, 0, 0, 0))
         .unwrap();
@@ -539,14 +618,15 @@ fn com_cb_server(sid0: usize, sid1: usize, sid2: usize, sid3: usize) {
 
 /// handles callback messages that indicate a GPIO interrupt has happened, in the library user's process
 /// space.
-fn gpio_cb_server(sid0: usize, sid1: usize, sid2: usize, sid3: usize) {
+pub(crate) fn gpio_cb_server(sid0: usize, sid1: usize, sid2: usize, sid3: usize) {
     let sid = xous::SID::from_u32(sid0 as u32, sid1 as u32, sid2 as u32, sid3 as u32);
     loop {
         let msg = xous::receive_message(sid).unwrap();
         match FromPrimitive::from_usize(msg.body.id()) {
-            Some(EventCallback::Event) => msg_scalar_unpack!(msg, cid, id, _, _, {
-                // directly pass the scalar message onto the CID with the ID memorized in the original hook
-                send_message(cid as u32, Message::new_scalar(id, 0, 0, 0, 0)).unwrap();
+            Some(EventCallback::Event) => msg_scalar_unpack!(msg, cid, id, pins, _, {
+                // directly pass the scalar message onto the CID with the ID memorized in the original hook,
+                // along with the pins that had the event
+                send_message(cid as u32, Message::new_scalar(id, pins, 0, 0, 0)).unwrap();
             }),
             Some(EventCallback::Drop) => {
                 break; // this exits the loop and kills the thread
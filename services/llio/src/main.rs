@@ -3,7 +3,11 @@
 
 mod api;
 use api::*;
+mod gpio_header;
+mod haptics;
+mod holder;
 mod i2c;
+mod i2c_registry;
 #[cfg(any(feature = "precursor", feature = "renode"))]
 mod llio_hw;
 #[cfg(any(feature = "precursor", feature = "renode"))]
@@ -251,6 +255,7 @@ fn main() -> ! {
     let gpio_base = crate::log_init();
 
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
@@ -276,14 +281,15 @@ fn main() -> ! {
     // - codec
     // - time server
     // - llio
+    // - the device registry
     // I2C can be used to set time, which can have security implications; we are more strict on counting who
     // can have access to this resource.
     #[cfg(all(any(feature = "precursor", feature = "renode"), not(feature = "dvt")))]
-    let i2c_sid = xns.register_name(api::SERVER_NAME_I2C, Some(3)).expect("can't register I2C thread");
+    let i2c_sid = xns.register_name(api::SERVER_NAME_I2C, Some(4)).expect("can't register I2C thread");
     #[cfg(all(any(feature = "precursor", feature = "renode"), feature = "dvt"))] // dvt build has less in it
-    let i2c_sid = xns.register_name(api::SERVER_NAME_I2C, Some(2)).expect("can't register I2C thread");
+    let i2c_sid = xns.register_name(api::SERVER_NAME_I2C, Some(3)).expect("can't register I2C thread");
     #[cfg(not(target_os = "xous"))]
-    let i2c_sid = xns.register_name(api::SERVER_NAME_I2C, Some(1)).expect("can't register I2C thread");
+    let i2c_sid = xns.register_name(api::SERVER_NAME_I2C, Some(2)).expect("can't register I2C thread");
 
     // Create a new llio object
     let handler_conn = xous::connect(llio_sid).expect("can't create IRQ handler connection");
@@ -301,6 +307,31 @@ fn main() -> ! {
             i2c_thread(i2c_sid, unsafe_power_csr, wfi_state);
         }
     });
+    // user-space drivers for add-on devices reach the bus through the registry, which checks their claims
+    let registry_sid =
+        xns.register_name(api::SERVER_NAME_I2C_DEVICES, None).expect("can't register I2C device registry");
+    let _ = thread::spawn({
+        move || {
+            i2c_registry::i2c_registry_thread(registry_sid);
+        }
+    });
+    // apps reach the pins on the expansion header through a server that checks the user's permission
+    let header_sid =
+        xns.register_name(api::SERVER_NAME_GPIO_HEADER, None).expect("can't register GPIO header server");
+    let _ = thread::spawn({
+        move || {
+            gpio_header::gpio_header_thread(header_sid);
+        }
+    });
+    // apps play vibration patterns through the haptics server, which drives the motor through the main loop
+    let haptics_sid =
+        xns.register_name(api::SERVER_NAME_HAPTICS, None).expect("can't register haptics server");
+    let _ = thread::spawn({
+        let llio_cid = xous::connect(llio_sid).expect("couldn't connect haptics to the main loop");
+        move || {
+            haptics::haptics_thread(haptics_sid, llio_cid);
+        }
+    });
 
     if cfg!(feature = "wfi_off") {
         log::warn!("WFI is overridden at boot -- automatic power savings is OFF!");
@@ -476,6 +507,9 @@ fn main() -> ! {
             Some(Opcode::Vibe) => msg_scalar_unpack!(msg, pattern, _, _, _, {
                 llio.vibe(pattern.into());
             }),
+            Some(Opcode::VibeSet) => msg_scalar_unpack!(msg, on, _, _, _, {
+                llio.vibe_set(on != 0);
+            }),
             Some(Opcode::AdcVbus) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                 xous::return_scalar(msg.sender, llio.xadc_vbus() as _).expect("couldn't return Xadc");
             }),
@@ -569,24 +603,25 @@ fn main() -> ! {
             Some(Opcode::EventEcIsReady) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                 xous::return_scalar(msg.sender, if ec_ready { 1 } else { 0 }).ok();
             }),
-            Some(Opcode::SetWakeupAlarm) => msg_blocking_scalar_unpack!(msg, delay, _, _, _, {
+            Some(Opcode::SetWakeupAlarm) => msg_blocking_scalar_unpack!(msg, delay, unit, _, _, {
                 if delay > u8::MAX as usize {
-                    log::error!("Wakeup must be no longer than {} secs in the future", u8::MAX);
+                    log::error!("Wakeup must be no more than {} units in the future", u8::MAX);
                     xous::return_scalar(msg.sender, 1).expect("couldn't return to caller");
                     continue;
                 }
-                let seconds = delay as u8;
+                let count = delay as u8;
+                let clk = match FromPrimitive::from_usize(unit) {
+                    Some(WakeupUnit::Minutes) => TimerClk::CLK_60_S,
+                    Some(WakeupUnit::Hours) => TimerClk::CLK_3600_S,
+                    _ => TimerClk::CLK_1_S,
+                };
                 i2c.i2c_mutex_acquire();
-                // set clock units to 1 second, output pulse length to ~218ms
+                // set clock units, output pulse length to ~218ms
                 // and program the elapsed time (TIMERB_CLK is followed by TIMERB)
-                i2c.i2c_write(
-                    ABRTCMC_I2C_ADR,
-                    ABRTCMC_TIMERB_CLK,
-                    &[(TimerClk::CLK_1_S | TimerClk::PULSE_218_MS).bits()],
-                )
-                .expect("RTC access error");
+                i2c.i2c_write(ABRTCMC_I2C_ADR, ABRTCMC_TIMERB_CLK, &[(clk | TimerClk::PULSE_218_MS).bits()])
+                    .expect("RTC access error");
                 // program elapsed time
-                i2c.i2c_write(ABRTCMC_I2C_ADR, ABRTCMC_TIMERB, &[seconds]).expect("RTC access error");
+                i2c.i2c_write(ABRTCMC_I2C_ADR, ABRTCMC_TIMERB, &[count]).expect("RTC access error");
                 // enable timerb countdown interrupt, also clears any prior interrupt flag
                 let control2 = (Control2::COUNTDOWN_B_INT).bits();
                 i2c.i2c_write(ABRTCMC_I2C_ADR, ABRTCMC_CONTROL2, &[control2]).expect("RTC access error");
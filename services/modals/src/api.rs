@@ -36,6 +36,18 @@ pub struct ManagedPromptWithTextResponse {
     pub growable: bool,
 }
 
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ManagedForm {
+    pub token: [u32; 4],
+    pub prompt: xous_ipc::String<1024>,
+    /// the fields, with the values they start with
+    pub form: FormPayload,
+    /// why the last submission of the form was refused, shown below it
+    pub error: Option<xous_ipc::String<256>>,
+    /// the field the cursor starts on
+    pub focus: u32,
+}
+
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
 pub struct ManagedNotification {
     pub token: [u32; 4],
@@ -106,6 +118,9 @@ pub(crate) enum Opcode {
     Bip39Return = 33, // ----- note op number
     SliderReturn = 34,
     Slider = 35,
+    /// ask for several labelled fields in one dialog; acknowledged with TextResponseValid
+    PromptWithForm = 36,
+    FormReturn = 37,
     /// display an image
     #[cfg(feature = "ditherpunk")]
     Image = 3,
@@ -118,7 +133,7 @@ pub(crate) enum Opcode {
 
     /// ask a question, get a free-form answer back
     PromptWithTextResponse = 6,
-    /// must be used by the PromptWithTextResponse caller to acknowledge correct input
+    /// must be used by the PromptWithTextResponse and PromptWithForm callers to acknowledge correct input
     TextResponseValid = 7,
 
     // these are non-blocking calls
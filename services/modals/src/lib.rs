@@ -18,6 +18,8 @@ use xous::{send_message, Message, CID};
 use xous_ipc::Buffer;
 
 pub type TextValidationFn = fn(TextEntryPayload) -> Option<ValidatorErr>;
+/// Checks a form's fields against each other, once each field has passed its own validator
+pub type FormValidationFn = fn(&FormPayload) -> Option<ValidatorErr>;
 
 pub struct AlertModalBuilder<'a> {
     prompt: String,
@@ -170,6 +172,121 @@ impl<'a> AlertModalBuilder<'a> {
     }
 }
 
+/// Builds a dialog of several labelled fields, returned together once they all validate. Fields appear
+/// in the order they are added; at most `MAX_FORM_FIELDS` can be added.
+pub struct FormBuilder<'a> {
+    prompt: String,
+    fields: Vec<FormField>,
+    validators: Vec<Option<TextValidationFn>>,
+    form_validator: Option<FormValidationFn>,
+    modals: &'a Modals,
+}
+
+impl<'a> FormBuilder<'a> {
+    /// A text field, starting out with `initial`. The validator is given the field's text.
+    pub fn text(
+        &mut self,
+        label: &str,
+        initial: Option<&str>,
+        validator: Option<TextValidationFn>,
+    ) -> &mut Self {
+        self.fields.push(FormField::text(label, initial.unwrap_or("")));
+        self.validators.push(validator);
+        self
+    }
+
+    /// A field that only takes digits and a leading '-'. Use `FormField::as_number()` on the result; the
+    /// validator is given the field's text.
+    pub fn number(
+        &mut self,
+        label: &str,
+        initial: Option<i64>,
+        validator: Option<TextValidationFn>,
+    ) -> &mut Self {
+        self.fields.push(FormField::number(label, initial));
+        self.validators.push(validator);
+        self
+    }
+
+    pub fn checkbox(&mut self, label: &str, checked: bool) -> &mut Self {
+        self.fields.push(FormField::checkbox(label, checked));
+        self.validators.push(None);
+        self
+    }
+
+    /// A choice of one of `choices`, starting on the one at `selected`. At most `MAX_ITEMS` choices are
+    /// shown.
+    pub fn radio(&mut self, label: &str, choices: &[&str], selected: usize) -> &mut Self {
+        self.fields.push(FormField::radio(label, choices, selected));
+        self.validators.push(None);
+        self
+    }
+
+    /// Sets a check that looks at all the fields together, for rules that span fields.
+    pub fn validator(&mut self, validator: FormValidationFn) -> &mut Self {
+        self.form_validator = Some(validator);
+        self
+    }
+
+    /// Shows the form until it is submitted with every field valid. An invalid submission shows the form
+    /// again as it was left, with the error below it and the cursor on the field at fault.
+    pub fn build(&self) -> Result<FormPayload, xous::Error> {
+        if self.fields.is_empty() || self.fields.len() > MAX_FORM_FIELDS {
+            log::error!("a form needs from 1 to {} fields, got {}", MAX_FORM_FIELDS, self.fields.len());
+            return Err(xous::Error::InvalidLimit);
+        }
+        let mut form = FormPayload::new();
+        for field in self.fields.iter() {
+            form.add(*field);
+        }
+        self.modals.lock();
+        let mut spec = ManagedForm {
+            token: self.modals.token,
+            prompt: xous_ipc::String::from_str(&self.prompt),
+            form,
+            error: None,
+            focus: 0,
+        };
+        loop {
+            let mut buf = Buffer::into_buf(spec).or(Err(xous::Error::InternalError))?;
+            buf.lend_mut(self.modals.conn, Opcode::PromptWithForm.to_u32().unwrap())
+                .or(Err(xous::Error::InternalError))?;
+            let response = match buf.to_original::<FormPayload, _>() {
+                Ok(response) => response,
+                _ => {
+                    // acknowledge anyway, so that the modals server doesn't get stuck on this error
+                    self.modals.text_response_valid();
+                    self.modals.unlock();
+                    return Err(xous::Error::InternalError);
+                }
+            };
+            let field_err = self.validators.iter().enumerate().find_map(|(index, validator)| {
+                let field = response.get(index)?;
+                let validator = (*validator)?;
+                let err = validator(TextEntryPayload::new_with_fields(field.text, None))?;
+                Some((index, err))
+            });
+            let err = field_err.or_else(|| {
+                let err = self.form_validator?(&response)?;
+                Some((spec.focus as usize, err))
+            });
+            match err {
+                Some((index, err)) => {
+                    // show the form again as it was left, with the cursor on the field at fault
+                    spec.form = response;
+                    spec.error = Some(err);
+                    spec.focus = index as u32;
+                }
+                None => {
+                    self.modals.text_response_valid();
+                    self.modals.unlock();
+                    return Ok(response);
+                }
+            }
+        }
+    }
+}
+
 pub struct Modals {
     conn: CID,
     token: [u32; 4],
@@ -199,6 +316,35 @@ impl Modals {
         }
     }
 
+    /// Several labelled fields (text, numbers, check boxes and radio choices) in one dialog; see
+    /// `FormBuilder`.
+    ///
+    /// # Examples
+    /// ```
+    /// use modals::Modals;
+    /// use xous_names::XousNames;
+    /// let xns = XousNames::new().unwrap();
+    /// let modals = Modals::new(&xns).unwrap();
+    /// let form = modals
+    ///     .form_builder("New account")
+    ///     .text("Name", None, None)
+    ///     .number("PIN length", Some(6), None)
+    ///     .radio("Type", &["Personal", "Work"], 0)
+    ///     .checkbox("Remember me", false)
+    ///     .build()
+    ///     .unwrap();
+    /// let name = form.get(0).unwrap().as_str();
+    /// ```
+    pub fn form_builder(&self, prompt: &str) -> FormBuilder {
+        FormBuilder {
+            prompt: String::from(prompt),
+            fields: vec![],
+            validators: vec![],
+            form_validator: None,
+            modals: self,
+        }
+    }
+
     /// Text/QR code notification modal dialog.
     ///
     /// - `qrtext` turns submitted text into a qr code.
@@ -903,6 +1049,21 @@ impl Modals {
 
     fn unlock(&self) { self.have_lock.set(false); }
 
+    /// Tells the server that a text entry or form response checked out, so it can move on to the next caller
+    fn text_response_valid(&self) {
+        send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                Opcode::TextResponseValid.to_usize().unwrap(),
+                self.token[0] as _,
+                self.token[1] as _,
+                self.token[2] as _,
+                self.token[3] as _,
+            ),
+        )
+        .expect("couldn't acknowledge text entry");
+    }
+
     /// - needed for use with `dynamic_notification_blocking_listener`
     /// - see `dynamic_notification_blocking_listener` for a code example.
     pub fn conn(&self) -> CID { self.conn }
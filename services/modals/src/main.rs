@@ -23,7 +23,7 @@
 /// 7. once you are sure you're finished, call `token_lock = next_lock(&mut work_queue);` to pull any waiting
 ///    work from the work queue
 ///
-/// Between 5 & 7 is where the TextEntry is weird: because you can "fail" on the return,
+/// Between 5 & 7 is where the TextEntry (and the Form) is weird: because you can "fail" on the return,
 /// it doesn't automatically do step 7. It's an extra step that the library implementation
 /// does after it does the text validation on its side, once it validates the caller sends
 /// a `TextResponseValid` message which pumps the work queue.
@@ -53,6 +53,7 @@ enum RendererState {
     RunRadio(ManagedPromptWithFixedResponse),
     RunCheckBox(ManagedPromptWithFixedResponse),
     RunText(ManagedPromptWithTextResponse),
+    RunForm(ManagedForm),
     RunProgress(ManagedProgress),
     RunNotification(ManagedNotification),
     RunBip39(ManagedBip39),
@@ -75,6 +76,7 @@ fn main() -> ! {
 }
 fn wrapped_main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
@@ -225,6 +227,26 @@ fn wrapped_main() -> ! {
                 )
                 .expect("couldn't initiate UX op");
             }
+            Some(Opcode::PromptWithForm) => {
+                let spec = {
+                    let mut buffer =
+                        unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                    let spec = buffer.to_original::<ManagedForm, _>().unwrap();
+                    if spec.token != token_lock.unwrap_or(default_nonce) {
+                        log::warn!("Attempt to access modals without a mutex lock. Ignoring.");
+                        buffer.replace(FormPayload::new()).unwrap();
+                        continue;
+                    }
+                    spec
+                };
+                op = RendererState::RunForm(spec);
+                dr = Some(msg);
+                send_message(
+                    renderer_cid,
+                    Message::new_scalar(Opcode::InitiateOp.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .expect("couldn't initiate UX op");
+            }
             Some(Opcode::Notification) => {
                 let spec = {
                     let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
@@ -473,6 +495,26 @@ fn wrapped_main() -> ! {
                         renderer_modal.activate();
                         log::debug!("should be active!");
                     }
+                    RendererState::RunForm(config) => {
+                        #[cfg(feature = "tts")]
+                        tts.tts_simple(config.prompt.as_str().unwrap()).unwrap();
+                        let mut form = gam::modal::Form::new(
+                            renderer_cid,
+                            Opcode::FormReturn.to_u32().unwrap(),
+                            config.form,
+                        );
+                        form.select_index = config.focus as i16;
+                        let error = config.error.as_ref().map(|e| e.as_str().unwrap_or("UTF-8 error"));
+                        renderer_modal.modify(
+                            Some(ActionType::Form(form)),
+                            Some(config.prompt.as_str().unwrap()),
+                            false,
+                            error,
+                            error.is_none(),
+                            Some(DEFAULT_STYLE),
+                        );
+                        renderer_modal.activate();
+                    }
                     RendererState::RunNotification(config) => {
                         let mut notification = gam::modal::Notification::new(
                             renderer_cid,
@@ -816,6 +858,34 @@ fn wrapped_main() -> ! {
                     );
                 }
             },
+            Some(Opcode::FormReturn) => match op {
+                RendererState::RunForm(_config) => {
+                    let buf = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                    let form = buf.to_original::<FormPayload, _>().unwrap();
+                    if let Some(mut origin) = dr.take() {
+                        let mut response = unsafe {
+                            Buffer::from_memory_message_mut(origin.body.memory_message_mut().unwrap())
+                        };
+                        response.replace(form).unwrap();
+                        op = RendererState::None;
+                        // as with text entry, the work queue moves on once the caller has validated the form
+                    } else {
+                        log::error!("Ux routine returned but no origin was recorded");
+                        panic!("Ux routine returned but no origin was recorded");
+                    }
+                }
+                RendererState::None => {
+                    log::warn!("Form detected a fat finger event, ignoring.")
+                }
+                _ => {
+                    log::error!(
+                        "UX return opcode does not match our current operation in flight. This is a serious internal error."
+                    );
+                    panic!(
+                        "UX return opcode does not match our current operation in flight. This is a serious internal error."
+                    );
+                }
+            },
             Some(Opcode::TextResponseValid) => msg_blocking_scalar_unpack!(msg, t0, t1, t2, t3, {
                 let incoming_token = [t0 as u32, t1 as u32, t2 as u32, t3 as u32];
                 if incoming_token != token_lock.unwrap_or(default_nonce) {
@@ -40,6 +40,18 @@ pub fn spawn_test() {
                     .build()
             );
 
+            // 0a. form test
+            log::info!(
+                "form data: {:#?}",
+                modals
+                    .form_builder("One of each kind of field. Select OK to close.")
+                    .text("Animal", Some("zebra"), None)
+                    .number("Legs", Some(4), None)
+                    .radio("Pick one", &RADIO_TEST, 2)
+                    .checkbox("Happy", true)
+                    .build()
+            );
+
             // 1. test progress bar
             // The start and end items are deliberately structured to be not zero-indexed; the use of PDDB_LOC
             // is just a convenient global constant.
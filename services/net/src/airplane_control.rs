@@ -0,0 +1,30 @@
+use num_traits::*;
+use xous::{msg_blocking_scalar_unpack, send_message, Message, CID};
+
+use crate::api::*;
+
+/// Serves `SERVER_NAME_NET_AIRPLANE`, which only the status service can connect to, and hands airplane
+/// mode switches on to the main loop. The main loop takes them only from this thread, so no other process
+/// can turn the radio back on while the status bar shows airplane mode.
+pub(crate) fn airplane_control_thread(net_conn: CID) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let airplane_sid =
+        xns.register_name(SERVER_NAME_NET_AIRPLANE, Some(1)).expect("can't register airplane control server");
+    loop {
+        let msg = xous::receive_message(airplane_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(AirplaneOpcode::SetAirplaneMode) => msg_blocking_scalar_unpack!(msg, on, _, _, _, {
+                let result = match send_message(
+                    net_conn,
+                    Message::new_blocking_scalar(Opcode::SetAirplaneMode.to_usize().unwrap(), on, 0, 0, 0),
+                ) {
+                    Ok(xous::Result::Scalar1(result)) => result,
+                    Ok(_) => xous::Error::InternalError.to_usize(),
+                    Err(e) => e.to_usize(),
+                };
+                xous::return_scalar(msg.sender, result).ok();
+            }),
+            None => log::error!("couldn't convert airplane control opcode: {:?}", msg),
+        }
+    }
+}
@@ -7,6 +7,7 @@ pub use ping::NetPingCallback;
 pub(crate) use tcp::*;
 
 pub mod rkyv_enum;
+pub(crate) mod socks;
 use std::fmt;
 use std::fmt::Debug;
 use std::io::Write;
@@ -23,8 +24,22 @@ use xous_semver::SemVer;
 
 // note: this name cannot be changed, because it is baked into `libstd`
 pub(crate) const SERVER_NAME_NET: &str = "_Middleware Network Server_";
+/// Sets the device-wide proxy. It takes a connection from shellchat, and one from the Tor service in
+/// images that have it, all made at boot; see `PROXY_CONTROLLERS`.
+pub(crate) const SERVER_NAME_NET_PROXY: &str = "_Net proxy control_";
+/// Switches airplane mode. It takes one connection, which the status service makes at boot.
+pub(crate) const SERVER_NAME_NET_AIRPLANE: &str = "_Net airplane control_";
+/// The processes that may set the device-wide proxy: shellchat, and the Tor service when it is built in
+#[cfg(not(feature = "tor"))]
+pub(crate) const PROXY_CONTROLLERS: u32 = 1;
+#[cfg(feature = "tor")]
+pub(crate) const PROXY_CONTROLLERS: u32 = 2;
 #[allow(dead_code)]
 pub const AP_DICT_NAME: &'static str = "wlan.networks";
+/// Known networks that are on a metered data plan, such as a phone hotspot. The key names are SSIDs, and
+/// a network is metered if it has a key here.
+#[allow(dead_code)]
+pub const METERED_DICT_NAME: &'static str = "wlan.metered";
 
 #[allow(dead_code)]
 /// minimum revision required for compatibility with Net crate
@@ -361,8 +376,53 @@ pub(crate) enum Opcode {
     StdTcpStreamShutdown = 46,
 
     LoopbackRx = 47,
+
+    /// Subscribe to online/offline transitions of the connection manager
+    SubscribeOnline = 48,
+    UnsubOnline = 49,
+
+    /// Quick settings: the Wi-Fi radio (blocking scalar, returns 1 if on) and turning it on or off
+    /// (blocking scalar, arg1 = 1 for on, returns the resulting state)
+    QuickWifi = 50,
+    QuickSetWifi = 51,
+    /// Airplane mode, as switched by the status service (blocking scalar, arg1 = 1 for on). While it is
+    /// on the radio is off and requests to start it are ignored; turning it off puts the radio and the
+    /// connection manager back the way they were. Only taken from the airplane control thread, which
+    /// forwards `AirplaneOpcode`s.
+    SetAirplaneMode = 52,
+
+    /// Route every libstd TCP connection through a SOCKS5 proxy, or stop doing so (memory message,
+    /// `SocksProxyIpc`). Only taken from the proxy control thread, which forwards `ProxyOpcode`s.
+    SetSocksProxy = 53,
+    /// The proxy in use, without its credentials (memory message, `SocksProxyStatusIpc`)
+    GetSocksProxy = 54,
+    /// Connections from a process are never proxied (blocking scalar, arg1 = PID, arg2 = its generation).
+    /// Only taken from the proxy control thread, once the user has allowed it.
+    SocksProxyBypass = 55,
+
+    /// Whether the network we're connected to is metered (blocking scalar, returns 1 if so)
+    IsMetered = 56,
+    /// A network was marked metered or unmetered in `METERED_DICT_NAME` (scalar)
+    MeteredChanged = 57,
     // do not use any numbers higher than 0x8000 as that is reserved for the nonblocking flag
 }
+
+/// Opcodes of the proxy control server, `SERVER_NAME_NET_PROXY`
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug, PartialEq, Eq)]
+pub(crate) enum ProxyOpcode {
+    /// Route every libstd TCP connection through a SOCKS5 proxy, or stop doing so (memory message,
+    /// `SocksProxyIpc`)
+    SetSocksProxy = 0,
+    /// Connections from the calling process are never proxied, if the user allows it; for the process
+    /// that is the proxy (blocking scalar, returns 1 if allowed)
+    SocksProxyBypass = 1,
+}
+/// Opcodes of the airplane control server, `SERVER_NAME_NET_AIRPLANE`
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug, PartialEq, Eq)]
+pub(crate) enum AirplaneOpcode {
+    /// Turn airplane mode on or off (blocking scalar, arg1 = 1 for on, returns 0 or a `xous::Error`)
+    SetAirplaneMode = 0,
+}
 #[allow(dead_code)]
 pub(crate) const NONBLOCKING_FLAG: usize = 0x8000; // when set, modulates a Peek or Read to be nonblocking
 
@@ -408,6 +468,13 @@ pub(crate) struct WifiStateSubscription {
     pub opcode: u32,
 }
 
+/// Delivered to online subscribers; `Update` carries 1 in arg1 when online, 0 when offline
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub(crate) enum OnlineCallback {
+    Update,
+    Drop,
+}
+
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub(crate) enum NetCallback {
     Ping,
@@ -440,6 +507,49 @@ impl From<SocketAddr> for NetSocketAddr {
         NetSocketAddr { addr: NetIpAddr::from(other), port: other.port() }
     }
 }
+impl From<NetSocketAddr> for SocketAddr {
+    fn from(other: NetSocketAddr) -> SocketAddr { SocketAddr::new(IpAddr::from(other.addr), other.port) }
+}
+
+/// Maximum length of a SOCKS5 user name or password
+pub const SOCKS_CREDENTIAL_LEN: usize = 255;
+
+/// A SOCKS5 proxy, e.g. Tor's or a corporate one. It can carry every outbound TCP connection (see
+/// `ProxyControl::set_socks_proxy()`), or a single one (see `protocols::Socks5`).
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocksProxy {
+    pub addr: SocketAddr,
+    /// User name and password, for proxies that want them
+    pub credentials: Option<(String, String)>,
+}
+
+/// The proxy that TCP connections go through, as anyone may see it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SocksProxyStatus {
+    pub addr: SocketAddr,
+    /// Whether a user name and password are given to the proxy
+    pub authenticated: bool,
+    /// Whether names are resolved by the proxy too; DNS queries can't be sent while this is so
+    pub resolves_names: bool,
+}
+
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone)]
+pub(crate) struct SocksProxyStatusIpc {
+    pub(crate) proxy: Option<NetSocketAddr>,
+    pub(crate) authenticated: bool,
+    pub(crate) resolves_names: bool,
+}
+
+#[derive(Debug, Archive, Serialize, Deserialize, Copy, Clone)]
+pub(crate) struct SocksProxyIpc {
+    /// None to connect directly
+    pub(crate) proxy: Option<NetSocketAddr>,
+    pub(crate) username: Option<xous_ipc::String<SOCKS_CREDENTIAL_LEN>>,
+    pub(crate) password: xous_ipc::String<SOCKS_CREDENTIAL_LEN>,
+    /// The proxy resolves names as well, and DNS queries are dropped
+    pub(crate) resolve_names: bool,
+}
 
 #[allow(dead_code)]
 pub fn ipaddress_to_ipaddr(other: IpAddress) -> IpAddr {
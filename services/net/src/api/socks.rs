@@ -0,0 +1,203 @@
+//! The SOCKS5 wire format (RFC 1928), with user name/password authentication (RFC 1929). Shared by the
+//! proxying done for every libstd connection in the net server, and by the per-connection client in
+//! `protocols::socks`.
+
+use std::fmt;
+use std::net::IpAddr;
+
+const SOCKS_VERSION: u8 = 5;
+const AUTH_VERSION: u8 = 1;
+const METHOD_NONE: u8 = 0;
+const METHOD_USER_PASS: u8 = 2;
+const METHOD_UNACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 1;
+/// Tor's extension, which looks a name up and answers with the address in place of the bound one
+#[allow(dead_code)]
+const CMD_RESOLVE: u8 = 0xf0;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+/// Where the proxy is asked to connect to
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Target<'a> {
+    Ip(IpAddr),
+    /// Resolved by the proxy, which is what keeps names from leaking past Tor
+    #[allow(dead_code)] // only the client in `protocols` has names; the server is given addresses
+    Domain(&'a str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SocksError {
+    /// The reply isn't SOCKS5
+    Protocol,
+    /// The proxy wants credentials, and none were given
+    AuthRequired,
+    /// The proxy turned the credentials down
+    AuthFailed,
+    /// The proxy couldn't, or wouldn't, make the connection; holds the reply code
+    Refused(u8),
+    /// The user name, password or host name is longer than 255 bytes
+    TooLong,
+}
+
+impl fmt::Display for SocksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocksError::Protocol => write!(f, "not a SOCKS5 proxy"),
+            SocksError::AuthRequired => write!(f, "proxy requires authentication"),
+            SocksError::AuthFailed => write!(f, "proxy rejected the credentials"),
+            SocksError::Refused(code) => write!(
+                f,
+                "proxy refused the connection: {}",
+                match code {
+                    1 => "general failure",
+                    2 => "not allowed by ruleset",
+                    3 => "network unreachable",
+                    4 => "host unreachable",
+                    5 => "connection refused",
+                    6 => "TTL expired",
+                    7 => "command not supported",
+                    8 => "address type not supported",
+                    _ => "unknown error",
+                }
+            ),
+            SocksError::TooLong => write!(f, "name or credential longer than 255 bytes"),
+        }
+    }
+}
+
+/// The methods offered: none, and user name/password if we have credentials
+pub(crate) fn greeting(with_auth: bool) -> Vec<u8> {
+    if with_auth {
+        vec![SOCKS_VERSION, 2, METHOD_NONE, METHOD_USER_PASS]
+    } else {
+        vec![SOCKS_VERSION, 1, METHOD_NONE]
+    }
+}
+
+/// Parses the two byte method selection. Returns true if the proxy wants credentials.
+pub(crate) fn method_reply(reply: &[u8; 2], with_auth: bool) -> Result<bool, SocksError> {
+    if reply[0] != SOCKS_VERSION {
+        return Err(SocksError::Protocol);
+    }
+    match reply[1] {
+        METHOD_NONE => Ok(false),
+        METHOD_USER_PASS if with_auth => Ok(true),
+        METHOD_USER_PASS | METHOD_UNACCEPTABLE => Err(SocksError::AuthRequired),
+        _ => Err(SocksError::Protocol),
+    }
+}
+
+pub(crate) fn auth_request(username: &str, password: &str) -> Result<Vec<u8>, SocksError> {
+    if username.len() > 255 || password.len() > 255 {
+        return Err(SocksError::TooLong);
+    }
+    let mut request = Vec::with_capacity(3 + username.len() + password.len());
+    request.push(AUTH_VERSION);
+    request.push(username.len() as u8);
+    request.extend_from_slice(username.as_bytes());
+    request.push(password.len() as u8);
+    request.extend_from_slice(password.as_bytes());
+    Ok(request)
+}
+
+pub(crate) fn auth_reply(reply: &[u8; 2]) -> Result<(), SocksError> {
+    match reply {
+        [AUTH_VERSION, 0] => Ok(()),
+        [AUTH_VERSION, _] => Err(SocksError::AuthFailed),
+        _ => Err(SocksError::Protocol),
+    }
+}
+
+pub(crate) fn connect_request(target: Target, port: u16) -> Result<Vec<u8>, SocksError> {
+    request(CMD_CONNECT, target, port)
+}
+
+/// Asks the proxy to look `name` up; the reply is read like a connect reply
+#[allow(dead_code)] // only the client in `protocols` resolves names
+pub(crate) fn resolve_request(name: &str) -> Result<Vec<u8>, SocksError> {
+    request(CMD_RESOLVE, Target::Domain(name), 0)
+}
+
+fn request(cmd: u8, target: Target, port: u16) -> Result<Vec<u8>, SocksError> {
+    let mut request = vec![SOCKS_VERSION, cmd, 0];
+    match target {
+        Target::Ip(IpAddr::V4(ip)) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Target::Ip(IpAddr::V6(ip)) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+        Target::Domain(name) => {
+            if name.len() > 255 {
+                return Err(SocksError::TooLong);
+            }
+            request.push(ATYP_DOMAIN);
+            request.push(name.len() as u8);
+            request.extend_from_slice(name.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    Ok(request)
+}
+
+/// The connect reply carries the address the proxy bound, so its length depends on the address type.
+/// Given the first five bytes, returns the length of the whole reply.
+pub(crate) fn connect_reply_len(head: &[u8; 5]) -> Result<usize, SocksError> {
+    if head[0] != SOCKS_VERSION {
+        return Err(SocksError::Protocol);
+    }
+    if head[1] != 0 {
+        return Err(SocksError::Refused(head[1]));
+    }
+    // version, reply, reserved, address type, then the address and a two byte port
+    match head[3] {
+        ATYP_IPV4 => Ok(4 + 4 + 2),
+        ATYP_IPV6 => Ok(4 + 16 + 2),
+        ATYP_DOMAIN => Ok(4 + 1 + head[4] as usize + 2),
+        _ => Err(SocksError::Protocol),
+    }
+}
+
+/// The address in a whole reply, which for RESOLVE is the one the name resolved to
+#[allow(dead_code)] // only the client in `protocols` resolves names
+pub(crate) fn reply_address(reply: &[u8]) -> Option<IpAddr> {
+    match reply.get(3)? {
+        &ATYP_IPV4 => <[u8; 4]>::try_from(reply.get(4..8)?).ok().map(IpAddr::from),
+        &ATYP_IPV6 => <[u8; 16]>::try_from(reply.get(4..20)?).ok().map(IpAddr::from),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_requests_carry_the_name() {
+        assert_eq!(
+            resolve_request("tor.eff.org").unwrap(),
+            [&[5, 0xf0, 0, 3, 11][..], b"tor.eff.org", &[0, 0]].concat()
+        );
+        assert_eq!(resolve_request(&"a".repeat(256)), Err(SocksError::TooLong));
+    }
+
+    #[test]
+    fn resolve_replies_give_the_address() {
+        let v4 = [5, 0, 0, ATYP_IPV4, 192, 0, 2, 1, 0, 0];
+        assert_eq!(connect_reply_len(&v4[..5].try_into().unwrap()), Ok(v4.len()));
+        assert_eq!(reply_address(&v4), Some(IpAddr::from([192, 0, 2, 1])));
+        let mut v6 = vec![5, 0, 0, ATYP_IPV6];
+        v6.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        v6.extend_from_slice(&[0, 0]);
+        assert_eq!(reply_address(&v6), Some("2001:db8::1".parse().unwrap()));
+        // a name is no answer, and neither is a reply cut short
+        assert_eq!(reply_address(&[5, 0, 0, ATYP_DOMAIN, 3, b'a', b'b', b'c', 0, 0]), None);
+        assert_eq!(reply_address(&v4[..6]), None);
+        // a lookup that failed
+        assert_eq!(connect_reply_len(&[5, 4, 0, ATYP_IPV4, 0]), Err(SocksError::Refused(4)));
+    }
+}
@@ -24,6 +24,17 @@ const INTERVALS_BEFORE_RETRY: usize = 3; // how many poll intervals we'll wait b
 const SCAN_COUNT_MAX: usize = 5;
 const SSID_SCAN_AGING_THRESHOLD: Duration = Duration::from_secs(5); // time before a scan is considered "stale" and needs to be redone
 const SSID_RESULT_AGING_THRESHOLD: Duration = Duration::from_secs(60); // time before an individual scan result is retired for being "too rarely seen"
+/// wait before retrying an AP after its first failed connection attempt; doubles with every further failure
+const BACKOFF_BASE: Duration = Duration::from_secs(10);
+const BACKOFF_MAX: Duration = Duration::from_secs(600);
+/// signal strength, in -dBm, past which the link is weak enough to look for a better AP
+const ROAM_RSSI_THRESHOLD: u8 = 78;
+/// how much stronger, in dB, another known AP has to be before we leave the current one for it
+const ROAM_HYSTERESIS_DB: u8 = 10;
+/// polls the link has to stay weak before we look for another AP, so a momentary dip doesn't drop it
+const ROAM_WEAK_INTERVALS: usize = 3;
+/// minimum time between roams, so two APs of similar strength don't bounce us back and forth
+const ROAM_COOLDOWN: Duration = Duration::from_secs(120);
 
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 pub(crate) enum ConnectionManagerOpcode {
@@ -35,10 +46,15 @@ pub(crate) enum ConnectionManagerOpcode {
     WifiOn,
     SubscribeWifiStats,
     UnsubWifiStats,
+    SubscribeOnline,
+    UnsubOnline,
     FetchSsidList,
     ComInt,
     SuspendResume,
     EcReset,
+    AirplaneOn,
+    AirplaneOff,
+    MeteredChanged,
     Quit,
 }
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
@@ -85,12 +101,38 @@ impl PartialOrd for SsidOrdByRssi {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { Some(self.cmp(other)) }
 }
 
+/// Failed connection attempts to an AP. Every failure doubles the wait before the AP is tried again, so
+/// an AP with a stale password or at the edge of range doesn't hog the connection attempts.
+struct Backoff {
+    failures: u32,
+    retry_at: Instant,
+}
+impl Backoff {
+    fn new() -> Self { Backoff { failures: 0, retry_at: Instant::now() } }
+
+    /// Records a failure, and returns how long the AP will be left alone
+    fn fail(&mut self) -> Duration {
+        let delay = BACKOFF_BASE.saturating_mul(1 << self.failures.min(16)).min(BACKOFF_MAX);
+        self.failures += 1;
+        self.retry_at = Instant::now() + delay;
+        delay
+    }
+
+    fn ready(&self) -> bool { Instant::now() >= self.retry_at }
+}
+
+/// Whether the Wi-Fi radio has been turned on; it powers up with the EC
+pub(crate) static WIFI_ON: AtomicBool = AtomicBool::new(true);
+/// Whether we're connected to a network marked metered in `METERED_DICT_NAME`
+pub(crate) static METERED: AtomicBool = AtomicBool::new(false);
+
 pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU32>) {
     let tt = ticktimer_server::Ticktimer::new().unwrap();
     let xns = xous_names::XousNames::new().unwrap();
     let mut com = com::Com::new(&xns).unwrap();
     let netmgr = net::NetManager::new();
     let pddb = pddb::Pddb::new();
+    let thermal = thermal::Thermal::new(&xns).unwrap();
     let self_cid = xous::connect(sid).unwrap();
     // give the system some time to boot before trying to run a check on the EC minimum version, as it is in
     // reset on boot
@@ -118,6 +160,14 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
     let mut ssid_attempted = HashSet::<String>::new();
     let mut wait_count = 0;
     let mut scan_count = 0;
+    let mut online_subscribers = HashMap::<xous::CID, WifiStateSubscription>::new();
+    let mut ssid_backoff = HashMap::<String, Backoff>::new();
+    // the AP we last tried to join, or are connected to
+    let mut current_ssid: Option<String> = None;
+    let mut weak_intervals = 0;
+    let mut last_roam: Option<Instant> = None;
+    // while in airplane mode: whether the radio was on, and whether we were running, before it started
+    let mut airplane_resume: Option<(bool, bool)> = None;
 
     let run_sid = xous::create_server().unwrap();
     let run_cid = xous::connect(run_sid).unwrap();
@@ -211,6 +261,7 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
     loop {
         let mut msg = xous::receive_message(sid).unwrap();
         log::trace!("got msg: {:?}", msg);
+        let state_before = wifi_state;
         match FromPrimitive::from_usize(msg.body.id()) {
             Some(ConnectionManagerOpcode::SuspendResume) => xous::msg_scalar_unpack!(msg, _, _, _, _, {
                 // this doesn't follow the usual "suspender" pattern. In fact, we don't do anything special on
@@ -266,6 +317,7 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
                         // wifi was manually forced "off", leave it off; presume connection manager is also
                         // stopped.
                         wifi_state = WifiState::Off;
+                        WIFI_ON.store(false, Ordering::SeqCst);
                     }
                     _ => {
                         // should approximately be a "disconnected" state.
@@ -478,9 +530,12 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
                                         }
                                         SsidScanState::Idle(_last_scan_time) => {
                                             scan_count = 0;
-                                            if let Some(ssid) =
-                                                get_next_ssid(&mut ssid_list, &mut ssid_attempted, ap_list)
-                                            {
+                                            if let Some(ssid) = get_next_ssid(
+                                                &mut ssid_list,
+                                                &mut ssid_attempted,
+                                                ap_list,
+                                                &ssid_backoff,
+                                            ) {
                                                 let mut wpa_pw_file = pddb
                                                     .get(
                                                         AP_DICT_NAME,
@@ -501,6 +556,7 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
                                                     com.wlan_set_pass(pw).expect("couldn't set password");
                                                     com.wlan_join().expect("couldn't issue join command");
                                                     wifi_state = WifiState::Connecting;
+                                                    current_ssid = Some(ssid.clone());
                                                 }
                                             } else {
                                                 // no SSIDs available, scan again
@@ -592,6 +648,50 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
                 if wifi_state == WifiState::Connected {
                     if let Some(ssid_stats) = wifi_stats_cache.ssid.as_mut() {
                         let rssi_u8 = com.wlan_get_rssi().ok().unwrap_or(255);
+                        if rssi_u8 > ROAM_RSSI_THRESHOLD {
+                            weak_intervals += 1;
+                        } else {
+                            weak_intervals = 0;
+                        }
+                        // looking for a better AP is put off while the device is running hot
+                        if weak_intervals > 0
+                            && weak_intervals % ROAM_WEAK_INTERVALS == 0
+                            && last_roam.map_or(true, |t| t.elapsed() > ROAM_COOLDOWN)
+                            && !thermal.throttled().unwrap_or(false)
+                        {
+                            let known = pddb.list_keys(AP_DICT_NAME, None).unwrap_or_default();
+                            if let Some(better) = roam_candidate(
+                                &ssid_list,
+                                &ssid_backoff,
+                                &known,
+                                current_ssid.as_deref(),
+                                rssi_u8,
+                            ) {
+                                log::info!(
+                                    "link to {:?} is weak (-{}dBm), roaming to {}",
+                                    current_ssid,
+                                    rssi_u8,
+                                    better
+                                );
+                                com.wlan_leave().expect("couldn't issue leave command");
+                                netmgr.reset();
+                                // steer the next connection attempt away from the AP we're leaving
+                                ssid_attempted.clear();
+                                if let Some(ssid) = current_ssid.as_ref() {
+                                    ssid_attempted.insert(ssid.clone());
+                                }
+                                wifi_state = WifiState::Disconnected;
+                                last_roam = Some(Instant::now());
+                                weak_intervals = 0;
+                            } else if let SsidScanState::Idle(last_scan_time) = scan_state {
+                                // refresh the signal strengths of the other APs for the next look
+                                if last_scan_time.elapsed() > SSID_SCAN_AGING_THRESHOLD {
+                                    com.set_ssid_scanning(true).unwrap();
+                                    scan_state = SsidScanState::Scanning;
+                                    scan_count = 0;
+                                }
+                            }
+                        }
                         // only send an update if the RSSI changed
                         if ssid_stats.rssi != rssi_u8 {
                             ssid_stats.rssi = rssi_u8;
@@ -642,6 +742,29 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
                     }
                 })
             }
+            Some(ConnectionManagerOpcode::SubscribeOnline) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let sub = buffer.to_original::<WifiStateSubscription, _>().unwrap();
+                let sub_cid = xous::connect(xous::SID::from_array(sub.sid))
+                    .expect("couldn't connect to online subscriber callback");
+                online_subscribers.insert(sub_cid, sub);
+                // new subscribers learn the current state right away
+                notify_online(sub_cid, wifi_state == WifiState::Connected);
+            }
+            Some(ConnectionManagerOpcode::UnsubOnline) => {
+                msg_blocking_scalar_unpack!(msg, s0, s1, s2, s3, {
+                    let sid = [s0 as u32, s1 as u32, s2 as u32, s3 as u32];
+                    let valid_sid =
+                        online_subscribers.iter().find(|(_, sub)| sub.sid == sid).map(|(&cid, _)| cid);
+                    xous::return_scalar(msg.sender, 1).expect("couldn't ack unsub");
+                    if let Some(cid) = valid_sid {
+                        online_subscribers.remove(&cid);
+                        unsafe {
+                            xous::disconnect(cid).ok();
+                        }
+                    }
+                })
+            }
             Some(ConnectionManagerOpcode::FetchSsidList) => {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
@@ -706,6 +829,7 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
             }),
             Some(ConnectionManagerOpcode::WifiOnAndRun) => msg_scalar_unpack!(msg, _, _, _, _, {
                 com.wlan_set_on().expect("couldn't turn on wifi");
+                WIFI_ON.store(true, Ordering::SeqCst);
                 wifi_state = WifiState::Disconnected;
                 ssid_list.clear();
                 com.set_ssid_scanning(true).unwrap();
@@ -742,6 +866,7 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
             }),
             Some(ConnectionManagerOpcode::WifiOn) => msg_scalar_unpack!(msg, _, _, _, _, {
                 com.wlan_set_on().expect("couldn't turn on wifi");
+                WIFI_ON.store(true, Ordering::SeqCst);
                 wifi_state = WifiState::Disconnected;
                 ssid_list.clear();
                 com.set_ssid_scanning(false).unwrap();
@@ -788,6 +913,7 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
 
                 tt.sleep_ms(250).unwrap(); // give a moment to clean-up after leave before turning things off
                 com.wlan_set_off().expect("couldn't turn off wifi");
+                WIFI_ON.store(false, Ordering::SeqCst);
                 wifi_stats_cache = WlanStatus {
                     ssid: None,
                     link_state: com_rs::LinkState::ResetHold,
@@ -814,6 +940,41 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
                 intervals_without_activity = 0;
                 scan_count = 0;
             }),
+            Some(ConnectionManagerOpcode::AirplaneOn) => msg_scalar_unpack!(msg, _, _, _, _, {
+                if airplane_resume.is_none() {
+                    airplane_resume = Some((WIFI_ON.load(Ordering::SeqCst), run.load(Ordering::SeqCst)));
+                    try_send_message(
+                        self_cid,
+                        Message::new_scalar(
+                            ConnectionManagerOpcode::DisconnectAndStop.to_usize().unwrap(),
+                            0,
+                            0,
+                            0,
+                            0,
+                        ),
+                    )
+                    .ok();
+                }
+            }),
+            Some(ConnectionManagerOpcode::AirplaneOff) => msg_scalar_unpack!(msg, _, _, _, _, {
+                let resume_op = match airplane_resume.take() {
+                    Some((true, true)) => Some(ConnectionManagerOpcode::WifiOnAndRun),
+                    Some((true, false)) => Some(ConnectionManagerOpcode::WifiOn),
+                    _ => None,
+                };
+                if let Some(op) = resume_op {
+                    try_send_message(self_cid, Message::new_scalar(op.to_usize().unwrap(), 0, 0, 0, 0)).ok();
+                }
+            }),
+            Some(ConnectionManagerOpcode::MeteredChanged) => msg_scalar_unpack!(msg, _, _, _, _, {
+                if wifi_state == WifiState::Connected {
+                    let metered = current_ssid.as_deref().map_or(false, |ssid| is_metered(&pddb, ssid));
+                    if METERED.swap(metered, Ordering::SeqCst) != metered {
+                        log::info!("connection is now {}", if metered { "metered" } else { "unmetered" });
+                        resend_wifi_stats(&status_subscribers, wifi_stats_cache);
+                    }
+                }
+            }),
             Some(ConnectionManagerOpcode::Quit) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                 send_message(
                     run_cid,
@@ -829,6 +990,42 @@ pub(crate) fn connection_manager(sid: xous::SID, activity_interval: Arc<AtomicU3
                 log::error!("couldn't convert opcode: {:?}", msg);
             }
         }
+
+        if wifi_state != state_before {
+            log::debug!("wifi state {:?} -> {:?}", state_before, wifi_state);
+            match (state_before, wifi_state) {
+                (
+                    WifiState::Connecting | WifiState::WaitDhcp,
+                    WifiState::Retry | WifiState::InvalidAp | WifiState::InvalidAuth | WifiState::Error,
+                ) => {
+                    if let Some(ssid) = current_ssid.take() {
+                        let delay = ssid_backoff.entry(ssid.clone()).or_insert_with(Backoff::new).fail();
+                        log::info!("couldn't connect to {}, leaving it alone for {}s", ssid, delay.as_secs());
+                    }
+                }
+                (_, WifiState::Connected) => {
+                    if let Some(ssid) = current_ssid.as_ref() {
+                        ssid_backoff.remove(ssid);
+                    }
+                    weak_intervals = 0;
+                }
+                _ => (),
+            }
+            if (state_before == WifiState::Connected) != (wifi_state == WifiState::Connected) {
+                // set before the subscribers hear we're online, so that they see the right value
+                let metered = wifi_state == WifiState::Connected
+                    && current_ssid.as_deref().map_or(false, |ssid| is_metered(&pddb, ssid));
+                if METERED.swap(metered, Ordering::SeqCst) != metered {
+                    if metered {
+                        log::info!("{:?} is metered, background transfers will wait", current_ssid);
+                    }
+                    resend_wifi_stats(&status_subscribers, wifi_stats_cache);
+                }
+                for &sub in online_subscribers.keys() {
+                    notify_online(sub, wifi_state == WifiState::Connected);
+                }
+            }
+        }
     }
     unsafe { xous::disconnect(self_cid).ok() };
     xous::destroy_server(sid).unwrap();
@@ -838,6 +1035,7 @@ fn get_next_ssid(
     ssid_list_map: &mut HashMap<String, SsidOrdByRssi>,
     ssid_attempted: &mut HashSet<String>,
     ap_list: HashSet<String>,
+    backoff: &HashMap<String, Backoff>,
 ) -> Option<String> {
     log::trace!("ap_list: {:?}", ap_list);
     log::trace!("ssid_list: {:?}", ssid_list_map);
@@ -854,6 +1052,8 @@ fn get_next_ssid(
         all_candidate_list.insert(String::from(c));
     }
     log::trace!("intersection: {:?}", all_candidate_list);
+    // APs that failed recently sit out until their backoff expires
+    all_candidate_list.retain(|c| backoff.get(c).map_or(true, |b| b.ready()));
 
     log::trace!("ssids already attempted: {:?}", ssid_attempted);
     // 2. find the complement of ssid_attempted and candidate_list
@@ -866,7 +1066,7 @@ fn get_next_ssid(
     log::trace!("untried_candidates: {:?}", untried_candidate_list);
 
     if untried_candidate_list.len() > 0 {
-        if let Some(candidate) = untried_candidate_list.into_iter().next() {
+        if let Some(candidate) = strongest(untried_candidate_list.iter(), ssid_list_map) {
             ssid_attempted.insert(candidate.to_string());
             log::debug!("SSID connect attempt: {:?}", candidate);
             Some(candidate.to_string())
@@ -878,7 +1078,7 @@ fn get_next_ssid(
         // clear the ssid_attempted list and start from scratch
         log::debug!("Exhausted all candidates, starting over again...");
         ssid_attempted.clear();
-        if let Some(candidate) = all_candidate_list.iter().next() {
+        if let Some(candidate) = strongest(all_candidate_list.iter(), ssid_list_map) {
             ssid_attempted.insert(candidate.to_string());
             log::debug!("SSID connect attempt: {:?}", candidate);
             Some(candidate.to_string())
@@ -892,3 +1092,57 @@ fn get_next_ssid(
         }
     }
 }
+
+/// Returns the candidate with the strongest signal (the smallest -dBm figure)
+fn strongest<'a>(
+    candidates: impl Iterator<Item = &'a String>,
+    ssid_list: &HashMap<String, SsidOrdByRssi>,
+) -> Option<String> {
+    candidates.min_by_key(|c| ssid_list.get(*c).map_or(u8::MAX, |s| s.rssi)).cloned()
+}
+
+/// Picks a visible known AP that is enough stronger than the one we're on to be worth switching to
+fn roam_candidate(
+    ssid_list: &HashMap<String, SsidOrdByRssi>,
+    backoff: &HashMap<String, Backoff>,
+    known: &[String],
+    current: Option<&str>,
+    rssi: u8,
+) -> Option<String> {
+    ssid_list
+        .values()
+        .filter(|s| Some(s.ssid.as_str()) != current && known.contains(&s.ssid))
+        .filter(|s| backoff.get(&s.ssid).map_or(true, |b| b.ready()))
+        .filter(|s| s.rssi.saturating_add(ROAM_HYSTERESIS_DB) < rssi)
+        .min()
+        .map(|s| s.ssid.clone())
+}
+
+/// Whether the known network `ssid` is marked metered
+fn is_metered(pddb: &pddb::Pddb, ssid: &str) -> bool {
+    pddb.list_keys(METERED_DICT_NAME, None).map_or(false, |keys| keys.iter().any(|key| key == ssid))
+}
+
+/// Sends the stats again, for the status bar to redraw whether the connection is metered; it learned the
+/// stats before we looked
+fn resend_wifi_stats(subscribers: &HashMap<xous::CID, WifiStateSubscription>, stats: WlanStatus) {
+    for &sub in subscribers.keys() {
+        let buf = Buffer::into_buf(com::WlanStatusIpc::from_status(stats))
+            .or(Err(xous::Error::InternalError))
+            .unwrap();
+        match buf.send(sub, WifiStateCallback::Update.to_u32().unwrap()) {
+            Err(e) => log::warn!("Couldn't update wifi state subscriber: {:?}", e),
+            _ => (),
+        }
+    }
+}
+
+fn notify_online(sub: xous::CID, online: bool) {
+    match try_send_message(
+        sub,
+        Message::new_scalar(OnlineCallback::Update.to_usize().unwrap(), if online { 1 } else { 0 }, 0, 0, 0),
+    ) {
+        Err(e) => log::warn!("Couldn't update online subscriber: {:?}", e),
+        _ => (),
+    }
+}
@@ -52,6 +52,8 @@ pub struct NetManager {
     netconn: NetConn,
     wifi_state_cid: Option<CID>,
     wifi_state_sid: Option<xous::SID>,
+    online_cid: Option<CID>,
+    online_sid: Option<xous::SID>,
 }
 impl NetManager {
     pub fn new() -> NetManager {
@@ -60,6 +62,8 @@ impl NetManager {
                 .expect("can't connect to Net Server"),
             wifi_state_cid: None,
             wifi_state_sid: None,
+            online_cid: None,
+            online_sid: None,
         }
     }
 
@@ -176,6 +180,78 @@ impl NetManager {
         Ok(())
     }
 
+    /// Subscribes to online/offline transitions, so services that need the network don't have to poll the
+    /// link state. `opcode` is sent to `return_cid` as a scalar message whose first argument is 1 when the
+    /// device comes online (joined to an AP with an address) and 0 when it goes offline. The current state
+    /// is sent right away.
+    pub fn online_subscribe(&mut self, return_cid: CID, opcode: u32) -> Result<(), xous::Error> {
+        if self.online_cid.is_none() {
+            let onetime_sid = xous::create_server().unwrap();
+            let sub = WifiStateSubscription { sid: onetime_sid.to_array(), opcode };
+            let buf = Buffer::into_buf(sub).or(Err(xous::Error::InternalError))?;
+            buf.send(self.netconn.conn(), Opcode::SubscribeOnline.to_u32().unwrap())
+                .or(Err(xous::Error::InternalError))?;
+            self.online_cid = Some(xous::connect(onetime_sid).unwrap());
+            self.online_sid = Some(onetime_sid);
+            let _ = std::thread::spawn({
+                let onetime_sid = onetime_sid.clone();
+                move || {
+                    loop {
+                        let msg = xous::receive_message(onetime_sid).unwrap();
+                        match FromPrimitive::from_usize(msg.body.id()) {
+                            Some(OnlineCallback::Update) => xous::msg_scalar_unpack!(msg, online, _, _, _, {
+                                send_message(
+                                    return_cid,
+                                    Message::new_scalar(opcode as usize, online, 0, 0, 0),
+                                )
+                                .expect("couldn't forward online update");
+                            }),
+                            Some(OnlineCallback::Drop) => {
+                                xous::return_scalar(msg.sender, 1).unwrap();
+                                break;
+                            }
+                            _ => {
+                                log::error!("got unknown opcode: {:?}", msg);
+                            }
+                        }
+                    }
+                    xous::destroy_server(onetime_sid).unwrap();
+                }
+            });
+            Ok(())
+        } else {
+            // you can only hook this once per object
+            Err(xous::Error::ServerExists)
+        }
+    }
+
+    /// If we're not already subscribed, returns without error.
+    pub fn online_unsubscribe(&mut self) -> Result<(), xous::Error> {
+        if let Some(handler) = self.online_cid.take() {
+            if let Some(sid) = self.online_sid.take() {
+                let s = sid.to_array();
+                send_message(
+                    self.netconn.conn(),
+                    Message::new_blocking_scalar(
+                        Opcode::UnsubOnline.to_usize().unwrap(),
+                        s[0] as usize,
+                        s[1] as usize,
+                        s[2] as usize,
+                        s[3] as usize,
+                    ),
+                )
+                .expect("couldn't unsubscribe");
+            }
+            send_message(
+                handler,
+                Message::new_blocking_scalar(OnlineCallback::Drop.to_usize().unwrap(), 0, 0, 0, 0),
+            )
+            .ok();
+            unsafe { xous::disconnect(handler).ok() };
+        }
+        Ok(())
+    }
+
     pub fn wifi_get_ssid_list(&self) -> Result<(Vec<SsidRecord>, ScanState), xous::Error> {
         let alloc = SsidList::default();
         let mut buf = Buffer::into_buf(alloc).map_err(|_| xous::Error::InternalError)?;
@@ -229,7 +305,154 @@ impl NetManager {
         )
         .map(|_| ())
     }
+
+    /// The proxy that TCP connections go through, if any. It is set with `ProxyControl`.
+    pub fn socks_proxy(&self) -> Result<Option<SocksProxyStatus>, xous::Error> {
+        let status = SocksProxyStatusIpc { proxy: None, authenticated: false, resolves_names: false };
+        let mut buf = Buffer::into_buf(status).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.netconn.conn(), Opcode::GetSocksProxy.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let status = buf.to_original::<SocksProxyStatusIpc, _>().or(Err(xous::Error::InternalError))?;
+        Ok(status.proxy.map(|proxy| SocksProxyStatus {
+            addr: std::net::SocketAddr::from(proxy),
+            authenticated: status.authenticated,
+            resolves_names: status.resolves_names,
+        }))
+    }
+
+    /// Whether the connection is metered, e.g. a phone hotspot on a data plan. Background transfers, such
+    /// as update downloads and sync, should wait for an unmetered network; transfers the user asked for
+    /// can go ahead.
+    pub fn is_metered(&self) -> Result<bool, xous::Error> {
+        match send_message(
+            self.netconn.conn(),
+            Message::new_blocking_scalar(Opcode::IsMetered.to_usize().unwrap(), 0, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar1(metered)) => Ok(metered != 0),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Marks the known network `ssid` as metered or not. If it's the network we're connected to, the
+    /// change applies right away.
+    pub fn set_metered(&self, ssid: &str, metered: bool) -> Result<(), xous::Error> {
+        let pddb = pddb::Pddb::new();
+        if metered {
+            pddb.get(METERED_DICT_NAME, ssid, Some(".System"), true, true, None, None::<fn()>)
+                .or(Err(xous::Error::InternalError))?;
+        } else {
+            match pddb.delete_key(METERED_DICT_NAME, ssid, None) {
+                Ok(_) => (),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                Err(_) => return Err(xous::Error::InternalError),
+            }
+        }
+        pddb.sync().or(Err(xous::Error::InternalError))?;
+        send_message(
+            self.netconn.conn(),
+            Message::new_scalar(Opcode::MeteredChanged.to_usize().unwrap(), 0, 0, 0, 0),
+        )
+        .map(|_| ())
+    }
 }
 impl Drop for NetManager {
-    fn drop(&mut self) { self.wifi_state_unsubscribe().unwrap(); }
+    fn drop(&mut self) {
+        self.wifi_state_unsubscribe().unwrap();
+        self.online_unsubscribe().unwrap();
+    }
+}
+
+/// Switches airplane mode. This is for the status service, which owns the airplane mode switch and tells
+/// other services about it; the net server takes one connection for it, made at boot.
+pub struct AirplaneControl {
+    conn: CID,
+}
+impl AirplaneControl {
+    /// Fails if the connection has already been handed out
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_NET_AIRPLANE)?;
+        Ok(AirplaneControl { conn })
+    }
+
+    /// Turns airplane mode on or off. While it is on the radio is off and requests to start it are
+    /// ignored; turning it off puts the radio and the connection manager back the way they were.
+    pub fn set_airplane_mode(&self, on: bool) -> Result<(), xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                AirplaneOpcode::SetAirplaneMode.to_usize().unwrap(),
+                on as usize,
+                0,
+                0,
+                0,
+            ),
+        ) {
+            Ok(xous::Result::Scalar1(0)) => Ok(()),
+            Ok(xous::Result::Scalar1(e)) => Err(xous::Error::from_usize(e)),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+}
+
+/// Sets the device-wide proxy. Sending all of the device's traffic somewhere else is not for any app to
+/// decide, so the net server takes only as many of these as there are processes meant to hold one
+/// (shellchat, and the Tor service when it is built in), and they must be made at boot.
+pub struct ProxyControl {
+    conn: CID,
+}
+impl ProxyControl {
+    /// Fails if every connection has already been handed out
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_NET_PROXY)?;
+        Ok(ProxyControl { conn })
+    }
+
+    /// Routes every TCP connection made through libstd, TLS included, through a SOCKS5 proxy; `None`
+    /// goes back to connecting directly. Loopback connections are always made directly. The proxy is
+    /// given the address that the name was resolved to locally; use `protocols::Socks5` to have the
+    /// proxy resolve names instead. The setting is not kept across reboots.
+    pub fn set_socks_proxy(&self, proxy: Option<&SocksProxy>) -> Result<(), xous::Error> {
+        self.send_proxy(proxy, false)
+    }
+
+    /// Like `set_socks_proxy()`, for a proxy that must see every lookup as well, such as Tor. The DNS
+    /// server resolves names through the proxy with the SOCKS5 RESOLVE extension that Tor has, and DNS
+    /// queries from anywhere else are dropped, so names can't leak around it.
+    pub fn set_resolving_socks_proxy(&self, proxy: &SocksProxy) -> Result<(), xous::Error> {
+        self.send_proxy(Some(proxy), true)
+    }
+
+    fn send_proxy(&self, proxy: Option<&SocksProxy>, resolve_names: bool) -> Result<(), xous::Error> {
+        let (username, password) = match proxy.and_then(|proxy| proxy.credentials.as_ref()) {
+            Some((username, password)) => {
+                if username.len() > SOCKS_CREDENTIAL_LEN || password.len() > SOCKS_CREDENTIAL_LEN {
+                    return Err(xous::Error::InvalidString);
+                }
+                (Some(xous_ipc::String::from_str(username)), xous_ipc::String::from_str(password))
+            }
+            None => (None, xous_ipc::String::new()),
+        };
+        let config = SocksProxyIpc {
+            proxy: proxy.map(|proxy| NetSocketAddr::from(proxy.addr)),
+            username,
+            password,
+            resolve_names,
+        };
+        let buf = Buffer::into_buf(config).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, ProxyOpcode::SetSocksProxy.to_u32().unwrap()).map(|_| ())
+    }
+
+    /// Makes every TCP connection from this process skip the proxy, if the user allows it when asked.
+    /// This is for a proxy running on the device, such as a Tor client, whose own connections would
+    /// otherwise loop back into it. Returns whether it was allowed; it lasts until the process exits.
+    pub fn socks_proxy_bypass(&self) -> Result<bool, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(ProxyOpcode::SocksProxyBypass.to_usize().unwrap(), 0, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar1(allowed)) => Ok(allowed != 0),
+            Ok(_) => Err(xous::Error::InternalError),
+            Err(e) => Err(e),
+        }
+    }
 }
@@ -14,8 +14,11 @@ use com::api::{ComIntSources, Ipv4Conf};
 use num_traits::*;
 use std_tcplistener::*;
 
+mod airplane_control;
 mod connection_manager;
 mod device;
+mod proxy_control;
+mod socks_proxy;
 
 #[cfg(test)]
 mod tests;
@@ -23,13 +26,14 @@ mod tests;
 use core::num::NonZeroU64;
 use core::sync::atomic::{AtomicU16, AtomicU32, Ordering};
 use std::cmp::Ordering as CmpOrdering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::sync::mpsc::{channel, Sender};
 use std::sync::Arc;
 use std::thread;
 
 use byteorder::{ByteOrder, NetworkEndian};
+use locales::t;
 use smoltcp::iface::SocketHandle;
 use smoltcp::iface::{Config, Interface, SocketSet};
 use smoltcp::phy::{Device, Tracer};
@@ -53,6 +57,10 @@ const PING_IDENT: u16 = 0x22b;
 /// number saves power. In general, most network events create an interrupt
 /// so the poll interval should be OK to be set quite high.
 const NET_DEFAULT_POLL_MS: u64 = 900;
+/// Network activity is reported to the ticktimer at most this often
+const ACTIVITY_REPORT_INTERVAL_MS: u64 = 1000;
+/// The most seconds between the heartbeats the name server tracks our liveness by
+const NET_HEARTBEAT_SECS: u32 = 10;
 
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 enum WaitOp {
@@ -135,6 +143,7 @@ fn set_com_ints(com_int_list: &mut Vec<ComIntSources>) {
 
 fn main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
@@ -142,11 +151,18 @@ fn main() -> ! {
     let net_sid = xns.register_name(api::SERVER_NAME_NET, None).expect("can't register server");
     let net_conn = xous::connect(net_sid).unwrap();
     log::trace!("registered with NS -- {:?}", net_sid);
+    // lets apps (and the `alive` shell command) tell a dead network stack from a slow one
+    xns.start_heartbeat(net_sid, NET_HEARTBEAT_SECS).ok();
 
     // bring the EC into a sane state for the network -- that is, reset the EC
     let mut llio = llio::Llio::new(&xns);
     let com = com::Com::new(&xns).unwrap();
     let timer = ticktimer_server::Ticktimer::new().unwrap();
+    // the ticktimer only takes network activity from the first process to report it, so claim it before any
+    // app runs
+    timer.report_activity(ticktimer_server::api::Activity::Network);
+    // when network activity was last reported to the ticktimer
+    let mut last_activity_report = 0u64;
 
     // we need a trng for port numbers
     let mut trng = trng::Trng::new(&xns).unwrap();
@@ -263,8 +279,12 @@ fn main() -> ! {
             u16, /* fd */
             u16, /* local_port */
             u16, /* remote_port */
+            Option<socks_proxy::Handshake>,
         )>,
     > = Vec::new();
+    // when set, libstd TCP connections go through this SOCKS5 proxy, except the ones from these processes
+    let mut socks_proxy: Option<socks_proxy::ProxyConfig> = None;
+    let mut socks_bypass: HashSet<proxy_control::Holder> = HashSet::new();
 
     // When a client issues an Accept request, it gets placed here for later processing.
     let mut tcp_accept_waiting: Vec<Option<AcceptingSocket>> = Vec::new();
@@ -311,6 +331,28 @@ fn main() -> ! {
         }
     });
 
+    // the Wi-Fi switch on the status quick settings page. Registration waits on the status server, so it
+    // is done off the main loop.
+    thread::spawn(move || {
+        let xns = xous_names::XousNames::new().unwrap();
+        let quick_settings = quick_settings::QuickSettings::new(&xns).unwrap();
+        quick_settings
+            .register(
+                "net.wifi",
+                t!("net.quick.wifi", locales::LANG),
+                net_sid,
+                Opcode::QuickWifi.to_u32().unwrap(),
+                Opcode::QuickSetWifi.to_u32().unwrap(),
+            )
+            .unwrap_or_else(|e| log::warn!("couldn't register quick setting: {:?}", e));
+    });
+    // only the processes that hold a proxy control connection may set the device-wide proxy
+    thread::spawn(move || proxy_control::proxy_control_thread(net_conn));
+    // likewise, only the status service may switch airplane mode
+    thread::spawn(move || airplane_control::airplane_control_thread(net_conn));
+    // set by the status service, which owns airplane mode
+    let mut airplane = false;
+
     let mut cid_to_disconnect: Option<CID> = None;
 
     let (core_tx, core_rx) = channel();
@@ -604,6 +646,7 @@ fn main() -> ! {
                 // Pick a random local port using the system's TRNG
                 let local_port = (trng.get_u32().unwrap() % 16384 + 49152) as u16;
                 let pid = msg.sender.pid();
+                let bypassed = proxy_control::holder_of(&msg).map_or(false, |h| socks_bypass.contains(&h));
 
                 std_tcp_connect(
                     msg,
@@ -612,6 +655,7 @@ fn main() -> ! {
                     &mut sockets,
                     &mut tcp_connect_waiting,
                     process_sockets.entry(pid).or_default(),
+                    socks_proxy.as_ref().filter(|_| !bypassed),
                 );
                 xous::try_send_message(
                     net_conn,
@@ -1008,7 +1052,10 @@ fn main() -> ! {
             Some(Opcode::StdUdpTx) => {
                 log::debug!("StdUdpTx");
                 let pid = msg.sender.pid();
-                std_udp_tx(msg, &mut iface, &mut sockets, process_sockets.entry(pid).or_default());
+                // names go to a proxy that resolves them, and nowhere else
+                let block_dns = socks_proxy.as_ref().map_or(false, |proxy| proxy.resolve_names)
+                    && !proxy_control::holder_of(&msg).map_or(false, |h| socks_bypass.contains(&h));
+                std_udp_tx(msg, &mut iface, &mut sockets, process_sockets.entry(pid).or_default(), block_dns);
                 xous::try_send_message(
                     net_conn,
                     Message::new_scalar(Opcode::NetPump.to_usize().unwrap(), 0, 0, 0, 0),
@@ -1268,32 +1315,53 @@ fn main() -> ! {
                 } else {
                     log::debug!("Socket readiness changed");
                 }
+                // idle policies work in seconds, so spare the ticktimer a report on every packet
+                if now >= last_activity_report + ACTIVITY_REPORT_INTERVAL_MS {
+                    timer.report_activity(ticktimer_server::api::Activity::Network);
+                    last_activity_report = now;
+                }
 
                 // Connect calls take time to establish. This block checks to see if connections
                 // have been made and issues callbacks as necessary.
                 // log::trace!("pump: tcpconnect");
                 for connection in tcp_connect_waiting.iter_mut() {
                     let socket;
-                    let (env, _handle, fd, local_port, remote_port) = {
+                    let mut proxy_error = None;
+                    let (env, _handle, fd, local_port, remote_port, _) = {
                         // If the connection is blank, or if it's still waiting to get
                         // connected, don't do anything.
                         match connection {
                             &mut None => continue,
                             Some(s) => {
-                                socket = sockets.get::<tcp::Socket>(s.1);
+                                socket = sockets.get_mut::<tcp::Socket>(s.1);
                                 log::debug!("connect state: {:?}", socket.state());
                                 if socket.state() == smoltcp::socket::tcp::State::SynSent
                                     || socket.state() == smoltcp::socket::tcp::State::SynReceived
                                 {
                                     continue;
                                 }
+                                // connected to a proxy: wait for it to reach the target
+                                if let Some(handshake) = s.5.as_mut() {
+                                    if socket.state() == smoltcp::socket::tcp::State::Established {
+                                        match handshake.advance(socket, now) {
+                                            socks_proxy::Progress::Pending => continue,
+                                            socks_proxy::Progress::Done => (),
+                                            socks_proxy::Progress::Failed(e) => {
+                                                socket.abort();
+                                                proxy_error = Some(e);
+                                            }
+                                        }
+                                    }
+                                }
                             }
                         }
                         connection.take().unwrap()
                     };
 
                     log::debug!("tcp state is {:?}", socket.state());
-                    if socket.state() == smoltcp::socket::tcp::State::Established {
+                    if let Some(e) = proxy_error {
+                        respond_with_error(env, e);
+                    } else if socket.state() == smoltcp::socket::tcp::State::Established {
                         respond_with_connected(env, fd, local_port, remote_port);
                     } else {
                         respond_with_error(env, NetError::TimedOut);
@@ -1889,6 +1957,14 @@ fn main() -> ! {
                 msg.forward(cm_cid, connection_manager::ConnectionManagerOpcode::UnsubWifiStats as _)
                     .expect("couldn't forward unsub request");
             }
+            Some(Opcode::SubscribeOnline) => {
+                msg.forward(cm_cid, connection_manager::ConnectionManagerOpcode::SubscribeOnline as _)
+                    .expect("couldn't forward subscription request");
+            }
+            Some(Opcode::UnsubOnline) => {
+                msg.forward(cm_cid, connection_manager::ConnectionManagerOpcode::UnsubOnline as _)
+                    .expect("couldn't forward unsub request");
+            }
             Some(Opcode::FetchSsidList) => {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
@@ -1903,6 +1979,10 @@ fn main() -> ! {
                 buffer.replace(ret_list).expect("couldn't return config");
             }
             Some(Opcode::ConnMgrStartStop) => msg_scalar_unpack!(msg, code, _, _, _, {
+                if airplane && (code == 1 || code == 3 || code == 4) {
+                    log::info!("airplane mode, ignoring start/stop code {}", code);
+                    continue;
+                }
                 let cm_op = match code {
                     0 => connection_manager::ConnectionManagerOpcode::Stop.to_usize().unwrap(),
                     1 => connection_manager::ConnectionManagerOpcode::Run.to_usize().unwrap(),
@@ -1924,6 +2004,132 @@ fn main() -> ! {
                     _ => (),
                 };
             }),
+            Some(Opcode::QuickWifi) => {
+                let on = connection_manager::WIFI_ON.load(Ordering::SeqCst);
+                xous::return_scalar(msg.sender, if on { 1 } else { 0 }).ok();
+            }
+            Some(Opcode::QuickSetWifi) => msg_blocking_scalar_unpack!(msg, on, _, _, _, {
+                let mut state = connection_manager::WIFI_ON.load(Ordering::SeqCst);
+                if airplane {
+                    log::info!("airplane mode, leaving the radio off");
+                } else {
+                    let cm_op = if on != 0 {
+                        connection_manager::ConnectionManagerOpcode::WifiOnAndRun
+                    } else {
+                        connection_manager::ConnectionManagerOpcode::DisconnectAndStop
+                    };
+                    match try_send_message(cm_cid, Message::new_scalar(cm_op.to_usize().unwrap(), 0, 0, 0, 0))
+                    {
+                        Ok(_) => state = on != 0,
+                        Err(e) => log::warn!("QuickSetWifi: couldn't reach the connection manager: {:?}", e),
+                    }
+                }
+                xous::return_scalar(msg.sender, if state { 1 } else { 0 }).ok();
+            }),
+            Some(Opcode::SetSocksProxy) => {
+                if msg.sender.pid().map(|pid| pid.get() as u32) != Some(xous::process::id()) {
+                    log::warn!(
+                        "SetSocksProxy sent by {:?} rather than the proxy control thread",
+                        msg.sender.pid()
+                    );
+                    continue;
+                }
+                let Some(mem) = msg.body.memory_message() else {
+                    log::error!("SetSocksProxy sent as the wrong kind of message");
+                    continue;
+                };
+                let buffer = unsafe { Buffer::from_memory_message(mem) };
+                let Ok(config) = buffer.to_original::<SocksProxyIpc, _>() else {
+                    log::error!("SetSocksProxy sent with a malformed proxy");
+                    continue;
+                };
+                socks_proxy = config.proxy.map(|proxy| socks_proxy::ProxyConfig {
+                    addr: IpAddress::from(std::net::IpAddr::from(proxy.addr)),
+                    port: proxy.port,
+                    credentials: config.username.map(|username| {
+                        (username.to_str().to_string(), config.password.to_str().to_string())
+                    }),
+                    resolve_names: config.resolve_names,
+                });
+                match config.proxy {
+                    Some(proxy) => log::info!("TCP connections now go through the proxy at {}", proxy.addr),
+                    None => log::info!("TCP connections are now made directly"),
+                }
+            }
+            Some(Opcode::GetSocksProxy) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("GetSocksProxy sent as the wrong kind of message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                // the credentials stay here: the proxy is for everyone to see, but not to log in to
+                let status = SocksProxyStatusIpc {
+                    proxy: socks_proxy
+                        .as_ref()
+                        .map(|proxy| NetSocketAddr { addr: NetIpAddr::from(proxy.addr), port: proxy.port }),
+                    authenticated: socks_proxy.as_ref().map_or(false, |proxy| proxy.credentials.is_some()),
+                    resolves_names: socks_proxy.as_ref().map_or(false, |proxy| proxy.resolve_names),
+                };
+                buffer.replace(status).ok();
+            }
+            Some(Opcode::SocksProxyBypass) => msg_blocking_scalar_unpack!(msg, pid, generation, _, _, {
+                if msg.sender.pid().map(|pid| pid.get() as u32) == Some(xous::process::id()) {
+                    // a process that has exited can't use its bypass, and a later one given its PID won't
+                    // match it, but don't let them pile up
+                    socks_bypass.retain(|&holder| !proxy_control::has_exited(holder));
+                    log::info!("connections from PID {} skip the proxy", pid);
+                    socks_bypass.insert((pid as u8, generation as u32));
+                } else {
+                    log::warn!(
+                        "SocksProxyBypass sent by {:?} rather than the proxy control thread",
+                        msg.sender.pid()
+                    );
+                }
+                xous::return_scalar(msg.sender, 0).expect("couldn't return SocksProxyBypass");
+            }),
+            Some(Opcode::IsMetered) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                let metered = connection_manager::METERED.load(Ordering::SeqCst);
+                xous::return_scalar(msg.sender, if metered { 1 } else { 0 }).ok();
+            }),
+            Some(Opcode::MeteredChanged) => msg_scalar_unpack!(msg, _, _, _, _, {
+                try_send_message(
+                    cm_cid,
+                    Message::new_scalar(
+                        connection_manager::ConnectionManagerOpcode::MeteredChanged.to_usize().unwrap(),
+                        0,
+                        0,
+                        0,
+                        0,
+                    ),
+                )
+                .ok();
+            }),
+            Some(Opcode::SetAirplaneMode) => msg_blocking_scalar_unpack!(msg, on, _, _, _, {
+                if msg.sender.pid().map(|pid| pid.get() as u32) != Some(xous::process::id()) {
+                    log::warn!(
+                        "SetAirplaneMode sent by {:?} rather than the airplane control thread",
+                        msg.sender.pid()
+                    );
+                    xous::return_scalar(msg.sender, xous::Error::AccessDenied.to_usize()).ok();
+                    continue;
+                }
+                let cm_op = if on != 0 {
+                    connection_manager::ConnectionManagerOpcode::AirplaneOn
+                } else {
+                    connection_manager::ConnectionManagerOpcode::AirplaneOff
+                };
+                match try_send_message(cm_cid, Message::new_scalar(cm_op.to_usize().unwrap(), 0, 0, 0, 0)) {
+                    Ok(_) => {
+                        airplane = on != 0;
+                        log::info!("airplane mode {}", if airplane { "on" } else { "off" });
+                        xous::return_scalar(msg.sender, 0).ok();
+                    }
+                    Err(e) => {
+                        log::warn!("SetAirplaneMode: couldn't reach the connection manager: {:?}", e);
+                        xous::return_scalar(msg.sender, e.to_usize()).ok();
+                    }
+                }
+            }),
             Some(Opcode::Reset) => {
                 // reset the DHCP address
                 IPV4_ADDRESS.store(0, Ordering::SeqCst);
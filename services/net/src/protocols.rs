@@ -9,3 +9,6 @@ pub use dns_hosted::*;
 
 pub mod ping;
 pub use ping::*;
+
+pub mod socks;
+pub use socks::*;
@@ -0,0 +1,99 @@
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::time::Duration;
+
+use crate::api::socks::{self, SocksError, Target};
+use crate::api::SocksProxy;
+
+/// A SOCKS5 client for one connection at a time, for when only some traffic should go through a proxy.
+/// Unlike the proxy set with `ProxyControl::set_socks_proxy()`, it hands host names to the proxy to
+/// resolve, which is what Tor wants so that the lookups don't leak.
+///
+/// ```no_run
+/// let proxy = net::SocksProxy { addr: "10.0.0.2:9050".parse().unwrap(), credentials: None };
+/// let mut stream = net::protocols::Socks5::new(proxy).connect("example.org", 80)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// The stream that comes back is an ordinary `TcpStream`, so TLS can be run over it as over any other.
+pub struct Socks5 {
+    proxy: SocksProxy,
+    timeout: Duration,
+}
+
+impl Socks5 {
+    pub fn new(proxy: SocksProxy) -> Socks5 { Socks5 { proxy, timeout: Duration::from_secs(30) } }
+
+    /// How long reaching the proxy, and the proxy reaching the target, may each take. 30 seconds by
+    /// default; Tor circuits can be slow to build.
+    pub fn timeout(mut self, timeout: Duration) -> Socks5 {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Connects to `host`, which is a name or an address, through the proxy
+    pub fn connect(&self, host: &str, port: u16) -> Result<TcpStream, Error> {
+        let mut stream = self.open()?;
+        let target = match host.parse::<IpAddr>() {
+            Ok(ip) => Target::Ip(ip),
+            Err(_) => Target::Domain(host),
+        };
+        stream.write_all(&socks::connect_request(target, port).map_err(to_io_error)?)?;
+        // the address the proxy bound isn't of use to us
+        read_reply(&mut stream)?;
+
+        stream.set_read_timeout(None)?;
+        log::debug!("connected to {}:{} through the proxy at {}", host, port, self.proxy.addr);
+        Ok(stream)
+    }
+
+    /// Looks `name` up through the proxy, with the RESOLVE extension that Tor has. Other proxies refuse
+    /// it, as a command they don't support.
+    pub fn resolve(&self, name: &str) -> Result<IpAddr, Error> {
+        let mut stream = self.open()?;
+        stream.write_all(&socks::resolve_request(name).map_err(to_io_error)?)?;
+        let reply = read_reply(&mut stream)?;
+        socks::reply_address(&reply)
+            .ok_or(Error::new(ErrorKind::InvalidData, "proxy answered the lookup without an address"))
+    }
+
+    /// Connects to the proxy, and logs in if it has credentials
+    fn open(&self) -> Result<TcpStream, Error> {
+        let mut stream = TcpStream::connect_timeout(&self.proxy.addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+
+        let with_auth = self.proxy.credentials.is_some();
+        stream.write_all(&socks::greeting(with_auth))?;
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply)?;
+        if socks::method_reply(&reply, with_auth).map_err(to_io_error)? {
+            let (username, password) = self.proxy.credentials.as_ref().unwrap();
+            stream.write_all(&socks::auth_request(username, password).map_err(to_io_error)?)?;
+            stream.read_exact(&mut reply)?;
+            socks::auth_reply(&reply).map_err(to_io_error)?;
+        }
+        Ok(stream)
+    }
+}
+
+/// Reads a whole connect or resolve reply
+fn read_reply(stream: &mut TcpStream) -> Result<Vec<u8>, Error> {
+    let mut head = [0u8; 5];
+    stream.read_exact(&mut head)?;
+    let len = socks::connect_reply_len(&head).map_err(to_io_error)?;
+    let mut reply = vec![0u8; len];
+    reply[..head.len()].copy_from_slice(&head);
+    stream.read_exact(&mut reply[head.len()..])?;
+    Ok(reply)
+}
+
+fn to_io_error(e: SocksError) -> Error {
+    let kind = match e {
+        SocksError::Protocol => ErrorKind::InvalidData,
+        SocksError::AuthRequired | SocksError::AuthFailed => ErrorKind::PermissionDenied,
+        SocksError::Refused(5) => ErrorKind::ConnectionRefused,
+        SocksError::Refused(_) => ErrorKind::Other,
+        SocksError::TooLong => ErrorKind::InvalidInput,
+    };
+    Error::new(kind, e.to_string())
+}
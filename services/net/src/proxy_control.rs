@@ -0,0 +1,84 @@
+use locales::t;
+use num_traits::*;
+use xous::{send_message, Message, CID};
+use xous_ipc::Buffer;
+
+use crate::api::*;
+
+/// A process, told apart from later processes that are given its PID
+pub(crate) type Holder = (u8, u32);
+
+/// The sender of `msg`, if the kernel says who it is
+pub(crate) fn holder_of(msg: &xous::MessageEnvelope) -> Option<Holder> {
+    let pid = msg.sender.pid()?;
+    xous::process_generation(pid).ok().map(|generation| (pid.get(), generation))
+}
+
+/// Whether `holder` has exited. Its PID may since have gone to another process.
+pub(crate) fn has_exited(holder: Holder) -> bool {
+    xous::PID::new(holder.0).map_or(true, |pid| xous::process_generation(pid) != Ok(holder.1))
+}
+
+/// Connecting around a proxy that everything else goes through is up to the user
+fn confirm_bypass(modals: &modals::Modals) -> bool {
+    modals.add_list_item(t!("net.proxy.yes", locales::LANG)).expect("modals error");
+    modals.add_list_item(t!("net.proxy.no", locales::LANG)).expect("modals error");
+    match modals.get_radiobutton(t!("net.proxy.bypass_prompt", locales::LANG)) {
+        Ok(response) => response.as_str() == t!("net.proxy.yes", locales::LANG),
+        Err(_) => false,
+    }
+}
+
+/// Serves `SERVER_NAME_NET_PROXY`, which only the processes meant to set the device-wide proxy can
+/// connect to, and hands their requests on to the main loop. The main loop takes proxy changes only from
+/// this thread.
+pub(crate) fn proxy_control_thread(net_conn: CID) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let proxy_sid = xns
+        .register_name(SERVER_NAME_NET_PROXY, Some(PROXY_CONTROLLERS))
+        .expect("can't register proxy control server");
+    let modals = modals::Modals::new(&xns).expect("can't connect to Modals server");
+    loop {
+        let msg = xous::receive_message(proxy_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(ProxyOpcode::SetSocksProxy) => {
+                let Some(mem) = msg.body.memory_message() else {
+                    log::error!("SetSocksProxy sent as the wrong kind of message");
+                    continue;
+                };
+                let buffer = unsafe { Buffer::from_memory_message(mem) };
+                let Ok(config) = buffer.to_original::<SocksProxyIpc, _>() else {
+                    log::error!("SetSocksProxy sent with a malformed proxy");
+                    continue;
+                };
+                let forward = Buffer::into_buf(config).expect("couldn't forward the proxy");
+                forward.lend(net_conn, Opcode::SetSocksProxy.to_u32().unwrap()).ok();
+            }
+            Some(ProxyOpcode::SocksProxyBypass) => {
+                if !msg.body.is_blocking() {
+                    log::error!("SocksProxyBypass sent as the wrong kind of message");
+                    continue;
+                }
+                let allowed = match holder_of(&msg) {
+                    Some(holder) if confirm_bypass(&modals) => {
+                        log::info!("the user let PID {} skip the proxy", holder.0);
+                        send_message(
+                            net_conn,
+                            Message::new_blocking_scalar(
+                                Opcode::SocksProxyBypass.to_usize().unwrap(),
+                                holder.0 as usize,
+                                holder.1 as usize,
+                                0,
+                                0,
+                            ),
+                        )
+                        .is_ok()
+                    }
+                    _ => false,
+                };
+                xous::return_scalar(msg.sender, allowed as usize).ok();
+            }
+            None => log::error!("couldn't convert proxy control opcode: {:?}", msg),
+        }
+    }
+}
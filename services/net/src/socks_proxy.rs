@@ -0,0 +1,163 @@
+use smoltcp::socket::tcp;
+use smoltcp::wire::IpAddress;
+
+use crate::api::socks::{self, SocksError, Target};
+use crate::*;
+
+/// How long the proxy has to answer, once the connection to it is up
+const HANDSHAKE_TIMEOUT_MS: u64 = 30_000;
+
+/// The proxy that libstd TCP connections are routed through, as set with `SetSocksProxy`
+pub(crate) struct ProxyConfig {
+    pub(crate) addr: IpAddress,
+    pub(crate) port: u16,
+    pub(crate) credentials: Option<(String, String)>,
+    /// Names are resolved by the proxy, so DNS queries are dropped
+    pub(crate) resolve_names: bool,
+}
+
+impl ProxyConfig {
+    /// Loopback connections, and the ones to the proxy itself, are made directly
+    pub(crate) fn applies_to(&self, address: IpAddress, port: u16) -> bool {
+        !address.is_loopback() && !(address == self.addr && port == self.port)
+    }
+}
+
+enum Stage {
+    Greeting,
+    Method,
+    Auth,
+    /// the first five bytes of the connect reply, which give its length
+    ConnectHead,
+    /// the rest of the connect reply, this many bytes
+    ConnectTail(usize),
+}
+
+pub(crate) enum Progress {
+    Pending,
+    Done,
+    Failed(NetError),
+}
+
+/// The SOCKS5 exchange on a connection to the proxy. The pump advances it once the connection is
+/// established, and the caller of connect only hears back when the proxy has reached the target.
+pub(crate) struct Handshake {
+    stage: Stage,
+    address: IpAddress,
+    port: u16,
+    credentials: Option<(String, String)>,
+    rx: Vec<u8>,
+    deadline: Option<u64>,
+}
+
+impl Handshake {
+    pub(crate) fn new(proxy: &ProxyConfig, address: IpAddress, port: u16) -> Self {
+        Handshake {
+            stage: Stage::Greeting,
+            address,
+            port,
+            credentials: proxy.credentials.clone(),
+            rx: Vec::new(),
+            deadline: None,
+        }
+    }
+
+    pub(crate) fn advance(&mut self, socket: &mut tcp::Socket, now: u64) -> Progress {
+        let deadline = *self.deadline.get_or_insert(now + HANDSHAKE_TIMEOUT_MS);
+        loop {
+            let next = match self.stage {
+                Stage::Greeting => self
+                    .send(socket, &socks::greeting(self.credentials.is_some()))
+                    .map(|_| Some(Stage::Method)),
+                Stage::Method => match self.receive(socket, 2) {
+                    Some(reply) => {
+                        match socks::method_reply(&[reply[0], reply[1]], self.credentials.is_some()) {
+                            Ok(true) => {
+                                let (username, password) = self.credentials.as_ref().unwrap();
+                                match socks::auth_request(username, password) {
+                                    Ok(request) => self.send(socket, &request).map(|_| Some(Stage::Auth)),
+                                    Err(e) => Err(fail(e)),
+                                }
+                            }
+                            Ok(false) => self.send_connect(socket),
+                            Err(e) => Err(fail(e)),
+                        }
+                    }
+                    None => Ok(None),
+                },
+                Stage::Auth => match self.receive(socket, 2) {
+                    Some(reply) => match socks::auth_reply(&[reply[0], reply[1]]) {
+                        Ok(()) => self.send_connect(socket),
+                        Err(e) => Err(fail(e)),
+                    },
+                    None => Ok(None),
+                },
+                Stage::ConnectHead => match self.receive(socket, 5) {
+                    Some(head) => {
+                        match socks::connect_reply_len(&[head[0], head[1], head[2], head[3], head[4]]) {
+                            Ok(len) => Ok(Some(Stage::ConnectTail(len - 5))),
+                            Err(e) => Err(fail(e)),
+                        }
+                    }
+                    None => Ok(None),
+                },
+                Stage::ConnectTail(len) => match self.receive(socket, len) {
+                    Some(_bound_address) => {
+                        log::debug!("proxy connected to {}:{}", self.address, self.port);
+                        return Progress::Done;
+                    }
+                    None => Ok(None),
+                },
+            };
+            match next {
+                Ok(Some(stage)) => self.stage = stage,
+                Ok(None) => break,
+                Err(e) => return Progress::Failed(e),
+            }
+        }
+        if now >= deadline {
+            log::warn!("proxy didn't answer within {} ms", HANDSHAKE_TIMEOUT_MS);
+            Progress::Failed(NetError::TimedOut)
+        } else {
+            Progress::Pending
+        }
+    }
+
+    fn send_connect(&mut self, socket: &mut tcp::Socket) -> Result<Option<Stage>, NetError> {
+        match socks::connect_request(Target::Ip(ipaddress_to_ipaddr(self.address)), self.port) {
+            Ok(request) => self.send(socket, &request).map(|_| Some(Stage::ConnectHead)),
+            Err(e) => Err(fail(e)),
+        }
+    }
+
+    fn send(&mut self, socket: &mut tcp::Socket, data: &[u8]) -> Result<(), NetError> {
+        match socket.send_slice(data) {
+            Ok(len) if len == data.len() => Ok(()),
+            _ => {
+                log::warn!("couldn't queue a request to the proxy");
+                Err(NetError::LibraryError)
+            }
+        }
+    }
+
+    /// Collects `len` bytes of a reply. Nothing past the reply is read, as data from the target can
+    /// follow right behind it.
+    fn receive(&mut self, socket: &mut tcp::Socket, len: usize) -> Option<Vec<u8>> {
+        if self.rx.len() < len {
+            let mut chunk = vec![0u8; len - self.rx.len()];
+            if let Ok(received) = socket.recv_slice(&mut chunk) {
+                self.rx.extend_from_slice(&chunk[..received]);
+            }
+        }
+        if self.rx.len() >= len { Some(core::mem::take(&mut self.rx)) } else { None }
+    }
+}
+
+fn fail(e: SocksError) -> NetError {
+    log::warn!("socks: {}", e);
+    match e {
+        // network or host unreachable
+        SocksError::Refused(3) | SocksError::Refused(4) => NetError::Unaddressable,
+        _ => NetError::LibraryError,
+    }
+}
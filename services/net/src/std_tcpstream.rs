@@ -12,8 +12,11 @@ pub(crate) fn std_tcp_connect(
     local_port: u16,
     iface: &mut Interface,
     sockets: &mut SocketSet,
-    tcp_connect_waiting: &mut Vec<Option<(xous::MessageEnvelope, SocketHandle, u16, u16, u16)>>,
+    tcp_connect_waiting: &mut Vec<
+        Option<(xous::MessageEnvelope, SocketHandle, u16, u16, u16, Option<socks_proxy::Handshake>)>,
+    >,
     our_sockets: &mut Vec<Option<SocketHandle>>,
+    proxy: Option<&socks_proxy::ProxyConfig>,
 ) {
     // Ignore nonblocking and scalar messages
     let body = match msg.body.memory_message_mut() {
@@ -37,6 +40,14 @@ pub(crate) fn std_tcp_connect(
         }
     };
 
+    // with a proxy set, the connection is made to the proxy, which is then asked for the real one
+    let (handshake, connect_to) = match proxy {
+        Some(proxy) if proxy.applies_to(address, remote_port) => {
+            (Some(socks_proxy::Handshake::new(proxy, address, remote_port)), (proxy.addr, proxy.port))
+        }
+        _ => (None, (address, remote_port)),
+    };
+
     // initiates a new connection to a remote server consisting of an (Address:Port) tuple.
     // multiple connections can exist to a server, and they are further differentiated by the return port
     let tcp_rx_buffer = tcp::SocketBuffer::new(vec![0; TCP_BUFFER_SIZE]);
@@ -48,7 +59,7 @@ pub(crate) fn std_tcp_connect(
     let tcp_socket = sockets.get_mut::<tcp::Socket>(handle);
 
     // Attempt to connect, returning the error if there is one
-    if let Err(e) = tcp_socket.connect(cx, (address, remote_port), local_port).map_err(|e| match e {
+    if let Err(e) = tcp_socket.connect(cx, connect_to, local_port).map_err(|e| match e {
         smoltcp::socket::tcp::ConnectError::InvalidState => NetError::SocketInUse,
         smoltcp::socket::tcp::ConnectError::Unaddressable => NetError::Unaddressable,
     }) {
@@ -62,7 +73,7 @@ pub(crate) fn std_tcp_connect(
     // Add the socket onto the list of sockets waiting to connect, since the connection will
     // take time.
     let idx = insert_or_append(our_sockets, handle) as u16;
-    insert_or_append(tcp_connect_waiting, (msg, handle, idx, local_port, remote_port));
+    insert_or_append(tcp_connect_waiting, (msg, handle, idx, local_port, remote_port, handshake));
     log::debug!("connect waiting now: {}, {:?} {:?} {:?}", idx, handle, local_port, remote_port);
 }
 
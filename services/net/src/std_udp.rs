@@ -3,6 +3,9 @@ use ticktimer_server::Ticktimer;
 
 use crate::*;
 
+/// Queries to this port are dropped while the proxy resolves names
+const DNS_PORT: u16 = 53;
+
 /// Overall architecture for libstd UDP implementation.
 ///
 /// Sockets are stored in the PID/SocketHandle HashMap `process_sockets` (this is shared with TCP)
@@ -185,6 +188,7 @@ pub(crate) fn std_udp_tx(
     iface: &mut Interface,
     sockets: &mut SocketSet,
     our_sockets: &Vec<Option<SocketHandle>>,
+    block_dns: bool,
 ) {
     // unpack meta
     let connection_handle_index = (msg.body.id() >> 16) & 0xffff;
@@ -215,6 +219,11 @@ pub(crate) fn std_udp_tx(
         }
     };
     let len = u16::from_le_bytes([bytes[19], bytes[20]]);
+    if block_dns && remote_port == DNS_PORT {
+        log::info!("dropped a DNS query to {:?}: names are resolved by the proxy", address);
+        std_failure(msg, NetError::Unaddressable);
+        return;
+    }
     // attempt the tx
     log::debug!(
         "udp tx to fd {} -> {:?}:{} {:?}",
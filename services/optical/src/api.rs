@@ -0,0 +1,67 @@
+pub(crate) const SERVER_NAME_OPTICAL: &str = "_Optical input_";
+/// Delivers scans. Only one connection is allowed, which the vault takes at boot: a scan may carry a secret.
+pub(crate) const SERVER_NAME_OPTICAL_RECEIVER: &str = "_Optical scan receiver_";
+
+/// Largest decoded payload delivered, in bytes. A version 40 QR code holds at most 2953 bytes, but the
+/// payloads apps act on (otpauth URIs, wifi credentials, addresses) are far shorter.
+pub const SCAN_MAX_LEN: usize = 1024;
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// A decoder has a payload for the subscribers. Memory, send of `Scan`.
+    Submit = 2,
+    /// Read scans from a scanner on the USB serial port (arg1 = 1), or stop (arg1 = 0). Starting asks the
+    /// user first. Blocking scalar, returns 1 if the scanner is being read.
+    SerialScanner = 3,
+    /// Exits the server
+    Quit = 4,
+}
+
+/// Opcodes of the receiver server
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum ReceiverOpcode {
+    /// Deliver every decoded scan to a server. Memory, send of `Subscription`.
+    Subscribe = 0,
+    /// Stop delivering scans to the server whose SID is in arg1-4. Blocking scalar.
+    Unsubscribe = 1,
+    /// Exits the receiver server; only accepted from the optical server itself
+    Quit = 2,
+}
+
+/// Where a scan came from
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub enum ScanSource {
+    /// A camera on the device, with an on-device decoder
+    Camera,
+    /// A scanner attached to the USB port, which sends each decoded code as a line of text
+    Serial,
+    /// Typed in, or injected for testing
+    Other,
+}
+
+/// One decoded code, as delivered to subscribers
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Scan {
+    pub source: ScanSource,
+    pub data: [u8; SCAN_MAX_LEN],
+    pub len: u32,
+}
+impl Scan {
+    pub fn new(source: ScanSource, payload: &[u8]) -> Self {
+        let len = payload.len().min(SCAN_MAX_LEN);
+        let mut data = [0u8; SCAN_MAX_LEN];
+        data[..len].copy_from_slice(&payload[..len]);
+        Scan { source, data, len: len as u32 }
+    }
+
+    pub fn payload(&self) -> &[u8] { &self.data[..(self.len as usize).min(SCAN_MAX_LEN)] }
+
+    /// The payload as text, if it is UTF-8
+    pub fn text(&self) -> Option<&str> { core::str::from_utf8(self.payload()).ok() }
+}
+
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct Subscription {
+    pub sid: [u32; 4],
+    pub opcode: u32,
+}
@@ -0,0 +1,103 @@
+pub mod api;
+pub use api::*;
+use num_traits::*;
+use xous::{CID, Message, SID, send_message};
+use xous_ipc::Buffer;
+
+pub struct Optical {
+    conn: CID,
+}
+impl Optical {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_OPTICAL)?;
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(Optical { conn })
+    }
+
+    /// Hands a decoded payload to the subscribers. This is the entry point for decoders: a camera driver
+    /// on hardware that has one, or anything else that turns codes into bytes.
+    pub fn submit(&self, source: ScanSource, payload: &[u8]) -> Result<(), xous::Error> {
+        if payload.len() > SCAN_MAX_LEN {
+            return Err(xous::Error::OutOfMemory);
+        }
+        let buf = Buffer::into_buf(Scan::new(source, payload)).or(Err(xous::Error::InternalError))?;
+        buf.send(self.conn, Opcode::Submit.to_u32().unwrap()).map(|_| ())
+    }
+
+    /// Starts or stops reading a scanner on the USB serial port. This takes the USB port over from
+    /// whatever else was using it, so starting is confirmed with the user first. Returns whether the
+    /// scanner is being read.
+    pub fn serial_scanner(&self, enable: bool) -> Result<bool, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                Opcode::SerialScanner.to_usize().unwrap(),
+                if enable { 1 } else { 0 },
+                0,
+                0,
+                0,
+            ),
+        ) {
+            Ok(xous::Result::Scalar1(running)) => Ok(running != 0),
+            Ok(_) => Err(xous::Error::InternalError),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The receiving end of the scans. The receiver server takes a single connection, so only one process can
+/// ever hold this: the vault, which creates it at boot. The connection is never given up, as it couldn't
+/// be requested again.
+pub struct ScanReceiver {
+    conn: CID,
+}
+impl ScanReceiver {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_OPTICAL_RECEIVER)?;
+        Ok(ScanReceiver { conn })
+    }
+
+    /// Every scan, from any source, is sent to `sid` as a memory message with id `opcode`, until
+    /// `unsubscribe()`. Use `scan_from_message()` to read it.
+    pub fn subscribe(&self, sid: SID, opcode: u32) -> Result<(), xous::Error> {
+        let sub = Subscription { sid: sid.to_array(), opcode };
+        let buf = Buffer::into_buf(sub).or(Err(xous::Error::InternalError))?;
+        buf.send(self.conn, ReceiverOpcode::Subscribe.to_u32().unwrap()).map(|_| ())
+    }
+
+    pub fn unsubscribe(&self, sid: SID) -> Result<(), xous::Error> {
+        let s = sid.to_array();
+        send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                ReceiverOpcode::Unsubscribe.to_usize().unwrap(),
+                s[0] as usize,
+                s[1] as usize,
+                s[2] as usize,
+                s[3] as usize,
+            ),
+        )
+        .map(|_| ())
+    }
+}
+
+/// Reads the `Scan` out of a message delivered to a subscriber
+pub fn scan_from_message(msg: &xous::MessageEnvelope) -> Option<Scan> {
+    let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message()?) };
+    buffer.to_original::<Scan, _>().ok()
+}
+
+use core::sync::atomic::{AtomicU32, Ordering};
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Optical {
+    fn drop(&mut self) {
+        // the connection to the server side must be reference counted, so that multiple instances of this
+        // object within a single process do not end up de-allocating the CID on other threads before they
+        // go out of scope.
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
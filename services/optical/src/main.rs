@@ -0,0 +1,208 @@
+mod api;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use api::*;
+use locales::t;
+use num_traits::*;
+use usb_device_xous::{UsbDeviceType, UsbHid};
+use xous::CID;
+use xous_ipc::Buffer;
+
+/// Hands every line a scanner on the USB serial port sends to the server, until `reading` is cleared
+fn read_serial(reading: Arc<AtomicBool>, self_cid: CID) {
+    let usb = UsbHid::new();
+    while reading.load(Ordering::SeqCst) {
+        let line = usb.serial_wait_ascii(Some('\n'));
+        if !reading.load(Ordering::SeqCst) {
+            break;
+        }
+        // scanners end a code with CR, LF or both
+        let payload = line.trim_end_matches(|c| c == '\r' || c == '\n');
+        if payload.is_empty() {
+            continue;
+        }
+        let Ok(buf) = Buffer::into_buf(Scan::new(ScanSource::Serial, payload.as_bytes())) else {
+            log::error!("couldn't serialize a serial scan");
+            continue;
+        };
+        buf.send(self_cid, Opcode::Submit.to_u32().unwrap()).ok();
+    }
+}
+
+/// The servers scans are delivered to, by the connection to each
+type Subscribers = Arc<Mutex<HashMap<CID, Subscription>>>;
+
+/// Serves the receiver name, which only the vault holds, so that no other process can have scans
+/// delivered to it.
+fn receiver_thread(receiver_sid: xous::SID, subscribers: Subscribers) {
+    let my_pid = xous::process::id();
+    loop {
+        let msg = xous::receive_message(receiver_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(ReceiverOpcode::Subscribe) => {
+                let Some(mem) = msg.body.memory_message() else {
+                    log::error!("optical Subscribe was not a memory message");
+                    continue;
+                };
+                let buffer = unsafe { Buffer::from_memory_message(mem) };
+                let Ok(sub) = buffer.to_original::<Subscription, _>() else {
+                    log::error!("couldn't deserialize a scan subscription");
+                    continue;
+                };
+                let mut subscribers = subscribers.lock().unwrap();
+                if subscribers.values().any(|s| s.sid == sub.sid) {
+                    continue;
+                }
+                match xous::connect(xous::SID::from_array(sub.sid)) {
+                    Ok(cid) => {
+                        subscribers.insert(cid, sub);
+                    }
+                    Err(e) => log::warn!("couldn't connect to scan subscriber: {:?}", e),
+                }
+            }
+            Some(ReceiverOpcode::Unsubscribe) => xous::msg_blocking_scalar_unpack!(msg, s0, s1, s2, s3, {
+                let sid = [s0 as u32, s1 as u32, s2 as u32, s3 as u32];
+                let mut subscribers = subscribers.lock().unwrap();
+                let cid = subscribers.iter().find(|(_, sub)| sub.sid == sid).map(|(&cid, _)| cid);
+                xous::return_scalar(msg.sender, 0).ok();
+                if let Some(cid) = cid {
+                    subscribers.remove(&cid);
+                    unsafe {
+                        xous::disconnect(cid).ok();
+                    }
+                }
+            }),
+            Some(ReceiverOpcode::Quit) => {
+                if msg.sender.pid().map(|p| p.get() as u32) == Some(my_pid) {
+                    break;
+                }
+                log::warn!("ignoring Quit from another process");
+            }
+            None => log::error!("couldn't convert receiver opcode: {:?}", msg),
+        }
+    }
+    xous::destroy_server(receiver_sid).ok();
+}
+
+/// Asks the user before handing the USB port to a serial scanner
+fn confirm_serial(modals: &modals::Modals) -> bool {
+    modals.add_list_item(t!("optical.yes", locales::LANG)).expect("modals error");
+    modals.add_list_item(t!("optical.no", locales::LANG)).expect("modals error");
+    match modals.get_radiobutton(t!("optical.serial_prompt", locales::LANG)) {
+        Ok(response) => response.as_str() == t!("optical.yes", locales::LANG),
+        _ => false,
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let optical_sid = xns.register_name(api::SERVER_NAME_OPTICAL, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", optical_sid);
+    let self_cid = xous::connect(optical_sid).unwrap();
+    let receiver_sid =
+        xns.register_name(api::SERVER_NAME_OPTICAL_RECEIVER, Some(1)).expect("can't register server");
+    let receiver_cid = xous::connect(receiver_sid).unwrap();
+    let subscribers: Subscribers = Arc::new(Mutex::new(HashMap::new()));
+    std::thread::spawn({
+        let subscribers = subscribers.clone();
+        move || receiver_thread(receiver_sid, subscribers)
+    });
+
+    let usb = UsbHid::new();
+    let modals = modals::Modals::new(&xns).expect("can't connect to Modals server");
+    // set while a thread is reading a scanner on the serial port
+    let serial_reading = Arc::new(AtomicBool::new(false));
+
+    loop {
+        let msg = xous::receive_message(optical_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Submit) => {
+                let Some(mem) = msg.body.memory_message() else {
+                    log::error!("optical Submit was not a memory message");
+                    continue;
+                };
+                let buffer = unsafe { Buffer::from_memory_message(mem) };
+                let Ok(scan) = buffer.to_original::<Scan, _>() else {
+                    log::error!("couldn't deserialize a scan");
+                    continue;
+                };
+                let subscribers = subscribers.lock().unwrap();
+                // the payload may be a secret (ie a TOTP seed), so only its size is logged
+                log::info!(
+                    "{:?} scan of {} bytes, to {} subscribers",
+                    scan.source,
+                    scan.len,
+                    subscribers.len()
+                );
+                for (&cid, sub) in subscribers.iter() {
+                    let Ok(buf) = Buffer::into_buf(scan) else {
+                        log::error!("couldn't serialize a scan");
+                        break;
+                    };
+                    if let Err(e) = buf.send(cid, sub.opcode) {
+                        log::warn!("couldn't deliver scan to subscriber: {:?}", e);
+                    }
+                }
+            }
+            Some(Opcode::SerialScanner) => xous::msg_blocking_scalar_unpack!(msg, enable, _, _, _, {
+                if enable != 0 && !serial_reading.load(Ordering::SeqCst) {
+                    // any process may ask, but the USB port is only taken over if the user agrees
+                    if !confirm_serial(&modals) {
+                        log::info!("the user declined the serial scanner");
+                    } else {
+                        match usb.ensure_core(UsbDeviceType::Serial) {
+                            Ok(()) => {
+                                serial_reading.store(true, Ordering::SeqCst);
+                                std::thread::spawn({
+                                    let reading = serial_reading.clone();
+                                    move || read_serial(reading, self_cid)
+                                });
+                                log::info!("reading the serial scanner");
+                            }
+                            Err(e) => log::warn!("failed to switch USB to serial: {:?}", e),
+                        }
+                    }
+                } else if enable == 0 && serial_reading.swap(false, Ordering::SeqCst) {
+                    // releases the reader blocked on the serial port
+                    usb.serial_flush().ok();
+                    log::info!("stopped reading the serial scanner");
+                }
+                let reading = serial_reading.load(Ordering::SeqCst);
+                xous::return_scalar(msg.sender, if reading { 1 } else { 0 }).ok();
+            }),
+            Some(Opcode::Quit) => {
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => {
+                log::error!("couldn't convert opcode: {:?}", msg);
+            }
+        }
+    }
+    // clean up our program
+    if serial_reading.swap(false, Ordering::SeqCst) {
+        usb.serial_flush().ok();
+    }
+    xous::send_message(
+        receiver_cid,
+        xous::Message::new_scalar(ReceiverOpcode::Quit.to_usize().unwrap(), 0, 0, 0, 0),
+    )
+    .ok();
+    for (cid, _) in subscribers.lock().unwrap().drain() {
+        unsafe {
+            xous::disconnect(cid).ok();
+        }
+    }
+    xns.unregister_server(receiver_sid).unwrap();
+    log::trace!("main loop exit, destroying servers");
+    xns.unregister_server(optical_sid).unwrap();
+    xous::destroy_server(optical_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
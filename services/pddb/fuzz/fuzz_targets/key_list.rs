@@ -0,0 +1,12 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // a bulk delete request, as the server receives it from another process
+    if let Ok(keys) = pddb::unpack_key_list(data) {
+        // leave room for the zero that ends the list
+        let mut packed = vec![0u8; data.len() + 1];
+        pddb::pack_key_list(&keys, &mut packed).unwrap();
+        assert_eq!(pddb::unpack_key_list(&packed).unwrap(), keys);
+    }
+});
@@ -1,8 +1,10 @@
+mod key_list;
 mod rkyv_enum;
 use core::ops::{Deref, DerefMut};
 use std::num::NonZeroU32;
 
 use bitfield::bitfield;
+pub use key_list::*;
 pub use rkyv_enum::*;
 
 // on the "[allow(dead_code)]" directives: these constants are used to define the PDDB, and are
@@ -248,6 +250,28 @@ pub(crate) enum Opcode {
     /// Prune the cache. Used mainly for diagnostics.
     Prune = 56,
 
+    /// Internal: advances the `TimeOutSecs` retention timers by the time since the last tick, locking
+    /// any basis whose time is up. Only accepted from the PDDB's own process.
+    AutoLockTick = 57,
+
+    /// Configure the auto-lock setting of an unlocked basis
+    MenuAutoLock = 58,
+
+    /// Asks for the passwords of the bases that were locked on the last suspend
+    ResumeUnlock = 59,
+
+    /// Renames a key within its dictionary, in place
+    RenameKey = 60,
+
+    /// Renames a dictionary, in place
+    RenameDict = 61,
+
+    /// Computes, or checks, the integrity tag of a key's data
+    KeyIntegrityTag = 62,
+
+    /// Estimates the bytes of data that can still be stored (blocking scalar)
+    FreeSpace = 63,
+
     /// This key type could not be decoded
     InvalidOpcode = u32::MAX as _,
 }
@@ -285,6 +309,8 @@ pub enum PddbRequestCode {
     Uninit = 10,
     DuplicateEntry = 11,
     BulkRead = 12,
+    /// the key's data doesn't match the integrity tag it was checked against
+    IntegrityError = 13,
 }
 // enum def is in rkyv_enum module
 impl BasisRetentionPolicy {
@@ -292,7 +318,8 @@ impl BasisRetentionPolicy {
         match self {
             BasisRetentionPolicy::Persist => 0,
             BasisRetentionPolicy::ClearAfterSleeps(sleeps) => *sleeps,
-            //BasisRetentionPolicy::TimeOutSecs(secs) => *secs,
+            // counts up the seconds elapsed since the unlock
+            BasisRetentionPolicy::TimeOutSecs(_secs) => 0,
         }
     }
 }
@@ -345,6 +372,34 @@ pub(crate) struct PddbKeyList {
     pub end: bool,
 }
 
+/// A structure for renaming a key, or a whole dictionary
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct PddbRename {
+    pub basis_specified: bool,
+    pub basis: xous_ipc::String<BASIS_NAME_LEN>,
+    pub dict: xous_ipc::String<DICT_NAME_LEN>,
+    /// ignored when renaming a dictionary
+    pub key: xous_ipc::String<KEY_NAME_LEN>,
+    /// the new name of the key or the dictionary; dictionary names are the longer of the two
+    pub new_name: xous_ipc::String<DICT_NAME_LEN>,
+    pub result: PddbRequestCode,
+}
+
+/// Length of a key integrity tag, an HMAC-SHA256
+pub const KEY_TAG_LEN: usize = 32;
+/// A structure for computing or checking the integrity tag of a key
+#[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct PddbKeyTag {
+    pub basis_specified: bool,
+    pub basis: xous_ipc::String<BASIS_NAME_LEN>,
+    pub dict: xous_ipc::String<DICT_NAME_LEN>,
+    pub key: xous_ipc::String<KEY_NAME_LEN>,
+    /// when `verify` is set, the tag to check the key against; otherwise, the computed tag on return
+    pub tag: [u8; KEY_TAG_LEN],
+    pub verify: bool,
+    pub result: PddbRequestCode,
+}
+
 /// A structure for bulk deletion of keys
 pub(crate) const MAX_PDDB_DELETE_LEN: usize = 3800; // approximate limit, might be a little higher if we cared to calculate it out
 #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
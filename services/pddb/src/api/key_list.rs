@@ -0,0 +1,88 @@
+//! The packed key list of a bulk delete. Each key name is a u8 length followed by that many bytes of
+//! UTF-8, and a zero length, or the end of the buffer, ends the list. The server unpacks lists sent by
+//! any process, so every length is checked against the buffer and the key name limit.
+
+use std::io::{Error, ErrorKind, Result};
+
+use super::KEY_NAME_LEN;
+
+/// Packs `keys` into `data`, which must be zeroed, leaving at least one zero byte to end the list
+#[allow(dead_code)]
+pub fn pack_key_list<S: AsRef<str>>(keys: &[S], data: &mut [u8]) -> Result<()> {
+    let mut index = 0;
+    for key in keys {
+        let key = key.as_ref().as_bytes();
+        if key.len() > (KEY_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "one of the key names is too long"));
+        }
+        if key.len() + 1 + index >= data.len() {
+            return Err(Error::new(ErrorKind::OutOfMemory, "Key list total size exceeds MAX_PDDBKLISTLEN"));
+        }
+        data[index] = key.len() as u8; // KEY_NAME_LEN is shorter than u8::MAX
+        index += 1;
+        data[index..index + key.len()].copy_from_slice(key);
+        index += key.len();
+    }
+    Ok(())
+}
+
+/// Unpacks the key names in `data`
+#[allow(dead_code)]
+pub fn unpack_key_list(data: &[u8]) -> Result<Vec<String>> {
+    let mut keys = Vec::new();
+    let mut index = 0;
+    while let Some(&len) = data.get(index) {
+        if len == 0 {
+            break;
+        }
+        let len = len as usize;
+        index += 1;
+        if len > (KEY_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidData, "key name too long"));
+        }
+        let name = data
+            .get(index..index + len)
+            .ok_or(Error::new(ErrorKind::InvalidData, "key list runs past the end of the buffer"))?;
+        let name =
+            std::str::from_utf8(name).or(Err(Error::new(ErrorKind::InvalidData, "key name is not utf-8")))?;
+        keys.push(name.to_string());
+        index += len;
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_lists_round_trip() {
+        let longest = "k".repeat(KEY_NAME_LEN - 1);
+        let keys = ["one", "ключ", longest.as_str()];
+        let mut data = [0u8; 256];
+        pack_key_list(&keys, &mut data).unwrap();
+        assert_eq!(unpack_key_list(&data).unwrap(), keys);
+    }
+
+    #[test]
+    fn full_lists_are_refused() {
+        let mut data = [0u8; 8];
+        assert!(pack_key_list(&["123456"], &mut data).is_ok());
+        let mut data = [0u8; 8];
+        assert_eq!(pack_key_list(&["1234567"], &mut data).unwrap_err().kind(), ErrorKind::OutOfMemory);
+    }
+
+    #[test]
+    fn bad_lists_are_rejected() {
+        // a length that runs off the end of the buffer
+        assert!(unpack_key_list(&[3, b'a', b'b']).is_err());
+        // a length longer than any key name
+        let mut data = vec![KEY_NAME_LEN as u8];
+        data.extend(std::iter::repeat(b'a').take(KEY_NAME_LEN));
+        assert!(unpack_key_list(&data).is_err());
+        // invalid UTF-8
+        assert!(unpack_key_list(&[2, 0xC3, 0x28, 0]).is_err());
+        // a list that fills the buffer, with no zero to end it
+        assert_eq!(unpack_key_list(&[2, b'a', b'b']).unwrap(), ["ab"]);
+    }
+}
@@ -16,7 +16,9 @@
 pub enum BasisRetentionPolicy {
     Persist,
     ClearAfterSleeps(u32),
-    //TimeOutSecs(u32),
+    /// Locks the basis once it has been unlocked for the given number of seconds. Time spent
+    /// suspended does not count toward the timeout.
+    TimeOutSecs(u32),
 }
 
 #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Eq, PartialEq)]
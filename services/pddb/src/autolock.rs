@@ -0,0 +1,228 @@
+use std::io::{ErrorKind, Result};
+
+use gam::modal::*;
+use locales::t;
+
+use crate::api::*;
+use crate::backend::*;
+
+// The auto-lock setting lives inside the basis it applies to, and not in the .System basis:
+// a list of secret basis names kept in the .System basis would defeat their deniability.
+const AUTOLOCK_DICT: &str = "pddb.autolock";
+const AUTOLOCK_KEY: &str = "policy";
+/// interval at which the `TimeOutSecs` retention timers are checked
+pub(crate) const AUTOLOCK_TICK_SECS: u32 = 15;
+/// longest auto-lock timeout that can be configured from the menu, in minutes
+const AUTOLOCK_MAX_MINUTES: u32 = 24 * 60;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum AutoLock {
+    /// The basis stays unlocked until it is closed explicitly.
+    Never,
+    /// The basis is locked on suspend, and its password is asked for again on resume.
+    OnSuspend,
+    /// The basis is locked once it has been unlocked for the given number of minutes.
+    AfterMinutes(u32),
+}
+impl AutoLock {
+    pub(crate) fn policy(&self) -> BasisRetentionPolicy {
+        match self {
+            AutoLock::Never => BasisRetentionPolicy::Persist,
+            AutoLock::OnSuspend => BasisRetentionPolicy::ClearAfterSleeps(1),
+            AutoLock::AfterMinutes(minutes) => BasisRetentionPolicy::TimeOutSecs(minutes * 60),
+        }
+    }
+
+    /// The setting as it is recorded in the basis; `None` is recorded by removing the record.
+    fn record(&self) -> Option<u32> {
+        match self {
+            AutoLock::Never => None,
+            AutoLock::OnSuspend => Some(0),
+            AutoLock::AfterMinutes(minutes) => Some(*minutes),
+        }
+    }
+
+    fn from_record(record: u32) -> AutoLock {
+        match record {
+            0 => AutoLock::OnSuspend,
+            minutes => AutoLock::AfterMinutes(minutes),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            AutoLock::Never => t!("pddb.autolock.never", locales::LANG).to_string(),
+            AutoLock::OnSuspend => t!("pddb.autolock.suspend", locales::LANG).to_string(),
+            AutoLock::AfterMinutes(minutes) => {
+                t!("pddb.autolock.after_minutes", locales::LANG).replace("{minutes}", &minutes.to_string())
+            }
+        }
+    }
+}
+
+/// Turns the ticktimer's clock into the whole seconds the retention timers are advanced by, carrying
+/// the remainder over, so that the timers follow the time that actually passed however often they are
+/// ticked.
+pub(crate) struct AutoLockClock {
+    last_ms: u64,
+}
+impl AutoLockClock {
+    pub(crate) fn new(now_ms: u64) -> Self { AutoLockClock { last_ms: now_ms } }
+
+    pub(crate) fn elapsed_secs(&mut self, now_ms: u64) -> u32 {
+        let secs = now_ms.saturating_sub(self.last_ms) / 1000;
+        self.last_ms += secs * 1000;
+        secs.min(u32::MAX as u64) as u32
+    }
+}
+
+/// Reads the auto-lock setting of a mounted basis. A basis that was never configured is not auto-locked.
+pub(crate) fn autolock_get(hw: &mut PddbOs, basis_cache: &mut BasisCache, basis_name: &str) -> AutoLock {
+    let mut data = [0u8; 4];
+    match basis_cache.key_read(hw, AUTOLOCK_DICT, AUTOLOCK_KEY, &mut data, None, Some(basis_name)) {
+        Ok(len) if len == data.len() => AutoLock::from_record(u32::from_le_bytes(data)),
+        _ => AutoLock::Never,
+    }
+}
+
+/// Records the auto-lock setting of a mounted basis, and applies it to the basis right away.
+pub(crate) fn autolock_set(
+    hw: &mut PddbOs,
+    basis_cache: &mut BasisCache,
+    basis_name: &str,
+    setting: AutoLock,
+) -> Result<()> {
+    if let Some(record) = setting.record() {
+        basis_cache.key_update(
+            hw,
+            AUTOLOCK_DICT,
+            AUTOLOCK_KEY,
+            &record.to_le_bytes(),
+            None,
+            None,
+            Some(basis_name),
+            true,
+        )?;
+    } else {
+        match basis_cache.key_remove(hw, AUTOLOCK_DICT, AUTOLOCK_KEY, Some(basis_name), false) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+    }
+    basis_cache.sync(hw, Some(basis_name), false)?;
+    basis_cache.set_policy(basis_name, setting.policy())
+}
+
+/// Walks the user through picking an unlocked secret basis and its auto-lock setting.
+pub(crate) fn autolock_menu(modals: &modals::Modals, hw: &mut PddbOs, basis_cache: &mut BasisCache) {
+    let bases: Vec<String> =
+        basis_cache.basis_list().into_iter().filter(|name| name != PDDB_DEFAULT_SYSTEM_BASIS).collect();
+    let basis_name = match bases.len() {
+        0 => {
+            modals
+                .show_notification(t!("pddb.autolock.nobasis", locales::LANG), None)
+                .expect("couldn't show notification");
+            return;
+        }
+        1 => bases[0].to_string(),
+        _ => {
+            for name in bases.iter() {
+                modals.add_list_item(name).expect("couldn't build radio item list");
+            }
+            match modals.get_radiobutton(t!("pddb.autolock.which", locales::LANG)) {
+                Ok(name) => name,
+                _ => return,
+            }
+        }
+    };
+
+    let current = autolock_get(hw, basis_cache, &basis_name);
+    let prompt = t!("pddb.autolock.prompt", locales::LANG)
+        .replace("{basis}", &basis_name)
+        .replace("{current}", &current.describe());
+    let options = [
+        t!("pddb.autolock.never", locales::LANG),
+        t!("pddb.autolock.suspend", locales::LANG),
+        t!("pddb.autolock.minutes", locales::LANG),
+    ];
+    for option in options.iter() {
+        modals.add_list_item(option).expect("couldn't build radio item list");
+    }
+    let setting = match modals.get_radiobutton(&prompt) {
+        Ok(choice) if choice.as_str() == options[0] => AutoLock::Never,
+        Ok(choice) if choice.as_str() == options[1] => AutoLock::OnSuspend,
+        Ok(choice) if choice.as_str() == options[2] => {
+            match modals
+                .alert_builder(t!("pddb.autolock.minutes_prompt", locales::LANG))
+                .field(None, Some(minutes_validator))
+                .build()
+            {
+                Ok(minutes) => AutoLock::AfterMinutes(
+                    minutes.first().as_str().parse::<u32>().expect("pre-validated input failed to re-parse!"),
+                ),
+                _ => return,
+            }
+        }
+        _ => return,
+    };
+
+    match autolock_set(hw, basis_cache, &basis_name, setting) {
+        Ok(_) => modals
+            .show_notification(
+                &t!("pddb.autolock.updated", locales::LANG)
+                    .replace("{basis}", &basis_name)
+                    .replace("{current}", &setting.describe()),
+                None,
+            )
+            .expect("couldn't show notification"),
+        Err(e) => {
+            log::error!("couldn't set auto-lock of {}: {:?}", basis_name, e);
+            modals
+                .show_notification(t!("pddb.autolock.failed", locales::LANG), None)
+                .expect("couldn't show notification");
+        }
+    }
+}
+
+fn minutes_validator(input: TextEntryPayload) -> Option<ValidatorErr> {
+    match input.as_str().parse::<u32>() {
+        Ok(minutes) if minutes >= 1 && minutes <= AUTOLOCK_MAX_MINUTES => None,
+        _ => Some(ValidatorErr::from_str(t!("pddb.autolock.minutes_err", locales::LANG))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_survive_their_record() {
+        for setting in
+            [AutoLock::OnSuspend, AutoLock::AfterMinutes(1), AutoLock::AfterMinutes(AUTOLOCK_MAX_MINUTES)]
+        {
+            assert_eq!(AutoLock::from_record(setting.record().unwrap()), setting);
+        }
+        assert_eq!(AutoLock::Never.record(), None);
+    }
+
+    #[test]
+    fn settings_map_to_retention_policies() {
+        assert!(matches!(AutoLock::Never.policy(), BasisRetentionPolicy::Persist));
+        assert!(matches!(AutoLock::OnSuspend.policy(), BasisRetentionPolicy::ClearAfterSleeps(1)));
+        assert!(matches!(AutoLock::AfterMinutes(5).policy(), BasisRetentionPolicy::TimeOutSecs(300)));
+    }
+
+    #[test]
+    fn clock_counts_the_time_that_passed() {
+        let mut clock = AutoLockClock::new(10_000);
+        // ticking faster than once a second doesn't speed the timers up
+        assert_eq!(clock.elapsed_secs(10_400), 0);
+        assert_eq!(clock.elapsed_secs(10_800), 0);
+        assert_eq!(clock.elapsed_secs(11_200), 1);
+        // the remainder is carried over
+        assert_eq!(clock.elapsed_secs(25_500), 14);
+        assert_eq!(clock.elapsed_secs(26_000), 1);
+        // a clock that went backwards advances nothing
+        assert_eq!(clock.elapsed_secs(1_000), 0);
+    }
+}
@@ -11,6 +11,8 @@ use std::iter::IntoIterator;
 use aes::cipher::generic_array::GenericArray;
 use aes::Aes256;
 use aes_gcm_siv::{aead::KeyInit, Aes256GcmSiv};
+use hkdf::hmac::{Hmac, Mac};
+use zeroize::Zeroize;
 
 use super::*;
 /// # The Organization of Basis Data
@@ -730,6 +732,116 @@ impl BasisCache {
         }
     }
 
+    /// Renames `key` in `dict` to `new_key`. The key keeps its descriptor slot and its data, so the rename
+    /// is a single rewrite of the descriptor page: there is no moment where both names, or neither, are
+    /// on disk.
+    pub(crate) fn key_rename(
+        &mut self,
+        hw: &mut PddbOs,
+        dict: &str,
+        key: &str,
+        new_key: &str,
+        basis_name: Option<&str>,
+    ) -> Result<()> {
+        if let Some(basis_index) = self.select_basis(basis_name) {
+            let basis = &mut self.cache[basis_index];
+            if !basis.ensure_dict_in_cache(hw, dict) {
+                return Err(Error::new(ErrorKind::NotFound, "dictionary not found"));
+            }
+            if basis.dicts.get(dict).and_then(|d| d.keys.get(new_key)).map_or(false, |k| !k.flags.valid()) {
+                // a key of the new name was deleted but not yet synced; flush the deletion first, so the
+                // stale descriptor is erased and doesn't come back under the renamed key
+                basis.dict_sync(hw, dict, false)?;
+            }
+            if let Some(dict_entry) = basis.dicts.get_mut(dict) {
+                dict_entry.key_rename(hw, &mut basis.v2p_map, &basis.cipher, key, new_key)?;
+                basis.age = basis.age.saturating_add(1);
+                basis.clean = false;
+                basis.dict_sync(hw, dict, false)?;
+                basis.basis_sync(hw);
+                basis.pt_sync(hw);
+                Ok(())
+            } else {
+                Err(Error::new(ErrorKind::NotFound, "dictionary not found"))
+            }
+        } else {
+            Err(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted."))
+        }
+    }
+
+    /// Renames `dict` to `new_dict`. Only the dictionary header, which holds the name, is rewritten; the
+    /// keys stay where they are, as they are found by the dictionary's index and not its name.
+    pub(crate) fn dict_rename(
+        &mut self,
+        hw: &mut PddbOs,
+        dict: &str,
+        new_dict: &str,
+        basis_name: Option<&str>,
+    ) -> Result<()> {
+        DictName::try_from_str(new_dict)?;
+        if let Some(basis_index) = self.select_basis(basis_name) {
+            let basis = &mut self.cache[basis_index];
+            if basis.ensure_dict_in_cache(hw, new_dict) {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    "a dictionary with the new name already exists",
+                ));
+            }
+            if !basis.ensure_dict_in_cache(hw, dict) {
+                return Err(Error::new(ErrorKind::NotFound, "dictionary not found"));
+            }
+            let mut dict_entry = basis.dicts.remove(dict).expect("entry was ensured, but somehow missing");
+            // the header shares its page with the first keys, which the sync rewrites from the cache
+            dict_entry.fill(hw, &basis.v2p_map, &basis.cipher, false);
+            dict_entry.age = dict_entry.age.saturating_add(1);
+            dict_entry.clean = false;
+            basis.dicts.insert(String::from(new_dict), dict_entry);
+            basis.age = basis.age.saturating_add(1);
+            basis.clean = false;
+            basis.dict_sync(hw, new_dict, false)?;
+            basis.basis_sync(hw);
+            basis.pt_sync(hw);
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted."))
+        }
+    }
+
+    /// Computes the integrity tag of a key: an HMAC-SHA256 over the dictionary name, the key name and the
+    /// key's data, keyed with a secret derived from the data key of the basis holding the key. Only that
+    /// basis can reproduce the tag, and the tag changes if the data is altered or moved to another name.
+    pub(crate) fn key_tag(
+        &mut self,
+        hw: &mut PddbOs,
+        dict: &str,
+        key: &str,
+        basis_name: Option<&str>,
+    ) -> Result<[u8; KEY_TAG_LEN]> {
+        let attr = self.key_attributes(hw, dict, key, basis_name)?;
+        let mut data = vec![0u8; attr.len];
+        if self.key_read(hw, dict, key, &mut data, None, Some(&attr.basis))? != attr.len {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "key data was shorter than its length"));
+        }
+        let basis = match self.cache.iter().find(|b| b.name == attr.basis) {
+            Some(basis) => basis,
+            None => return Err(Error::new(ErrorKind::NotFound, "basis not found")),
+        };
+        let mut tag_key = [0u8; 32];
+        hkdf::Hkdf::<sha2::Sha256>::new(None, basis.key.as_slice())
+            .expand(b"pddb key integrity", &mut tag_key)
+            .expect("invalid length specified for HKDF");
+        let mut mac =
+            <Hmac<sha2::Sha256> as Mac>::new_from_slice(&tag_key).expect("HMAC takes keys of any length");
+        tag_key.zeroize();
+        // names are length-prefixed, so that no two name pairs hash the same
+        for name in [dict, key] {
+            mac.update(&(name.len() as u32).to_le_bytes());
+            mac.update(name.as_bytes());
+        }
+        mac.update(&data);
+        Ok(mac.finalize().into_bytes().into())
+    }
+
     /// Updates a key in a dictionary; if it doesn't exist, creates it. User can specify a basis,
     /// or rely upon the auto-basis select algorithm.
     pub(crate) fn key_update(
@@ -1192,7 +1304,9 @@ impl BasisCache {
         Ok(())
     }
 
-    pub(crate) fn suspend(&mut self, hw: &mut PddbOs) {
+    /// Syncs the cache and locks every basis whose retention policy expires on this suspend. Returns
+    /// the names of the bases that were locked.
+    pub(crate) fn suspend(&mut self, hw: &mut PddbOs) -> Vec<String> {
         self.sync(hw, None, false).expect("couldn't sync on suspend");
         let mut lock_list = Vec::<String>::new();
         for basis in self.cache.iter_mut() {
@@ -1203,20 +1317,52 @@ impl BasisCache {
                     if basis.policy_state >= sleeps {
                         lock_list.push(basis.name.clone());
                     }
-                } /*
-                  BasisRetentionPolicy::TimeOutSecs(secs) => {
-                      if secs < basis.policy_state {
-                          lock_list.push(basis.name.clone());
-                      }
-                  }*/
+                }
+                // timeouts are only advanced by `expire()`
+                BasisRetentionPolicy::TimeOutSecs(_secs) => (),
             }
         }
-        for basis in lock_list {
-            log::info!("unmounting basis on sleep: {}", &basis);
-            self.basis_unmount(hw, &basis).ok();
+        for basis in lock_list.iter() {
+            log::info!("unmounting basis on sleep: {}", basis);
+            self.basis_unmount(hw, basis).ok();
         }
+        lock_list
     }
 
+    /// Advances the timers of bases with a `TimeOutSecs` policy by `elapsed_secs`, and locks the
+    /// ones whose time is up. Returns the names of the bases that were locked.
+    pub(crate) fn expire(&mut self, hw: &mut PddbOs, elapsed_secs: u32) -> Vec<String> {
+        let mut lock_list = Vec::<String>::new();
+        for basis in self.cache.iter_mut() {
+            if let BasisRetentionPolicy::TimeOutSecs(secs) = basis.policy {
+                basis.policy_state = basis.policy_state.saturating_add(elapsed_secs);
+                if basis.policy_state >= secs {
+                    lock_list.push(basis.name.clone());
+                }
+            }
+        }
+        for basis in lock_list.iter() {
+            log::info!("unmounting basis on timeout: {}", basis);
+            self.basis_unmount(hw, basis).ok();
+        }
+        lock_list
+    }
+
+    /// Replaces the retention policy of a mounted basis, restarting its retention state.
+    pub(crate) fn set_policy(&mut self, basis_name: &str, policy: BasisRetentionPolicy) -> Result<()> {
+        for basis in self.cache.iter_mut() {
+            if basis.name == basis_name {
+                basis.policy = policy;
+                basis.policy_state = policy.derive_init_state();
+                return Ok(());
+            }
+        }
+        Err(Error::new(ErrorKind::NotFound, "Requested basis not found, or PDDB not mounted."))
+    }
+
+    /// Estimates how many bytes of data can still be stored; see `PddbOs::free_space_estimate()`.
+    pub(crate) fn free_space(&self, hw: &PddbOs) -> usize { hw.free_space_estimate(&self.cache) }
+
     /// returns a relative measure of cache size. It is not absolutely accurate as
     /// overhead is not accounted for, but the actual data cached is relatively correct.
     pub(crate) fn cache_size(&mut self) -> usize {
@@ -1172,6 +1172,41 @@ impl DictCacheEntry {
         // if there's no key....we're done!
     }
 
+    /// Renames key `old` to `new`. Only the key's descriptor changes: the data stays where it is, and the
+    /// descriptor keeps its slot, so the next `dict_sync` swaps the name over in a single page write. A key
+    /// that was deleted under the name `new` must be synced away before this is called.
+    pub(crate) fn key_rename(
+        &mut self,
+        hw: &mut PddbOs,
+        v2p_map: &mut HashMap<VirtAddr, PhysPage>,
+        cipher: &Aes256GcmSiv,
+        old: &str,
+        new: &str,
+    ) -> Result<()> {
+        KeyName::try_from_str(new)?;
+        if self.ensure_key_entry(hw, v2p_map, cipher, new) {
+            return Err(Error::new(ErrorKind::AlreadyExists, "a key with the new name already exists"));
+        }
+        if !self.ensure_key_entry(hw, v2p_map, cipher, old)
+            || !self.keys.get(old).map_or(false, |k| k.flags.valid())
+        {
+            return Err(Error::new(ErrorKind::NotFound, "key not found"));
+        }
+        let mut kcache = self.keys.remove(old).expect("key was ensured, but somehow missing");
+        if let Some(small_index) = small_storage_index_from_key(&kcache, self.index) {
+            // the small pool refers to its keys by name
+            if let Some(name) = self.small_pool[small_index].contents.iter_mut().find(|s| *s == old) {
+                *name = String::from(new);
+            }
+        }
+        kcache.age = kcache.age.saturating_add(1);
+        kcache.clean = false;
+        self.keys.insert(String::from(new), kcache);
+        self.age = self.age.saturating_add(1);
+        self.clean = false;
+        Ok(())
+    }
+
     /// used to remove a key from the dictionary, syncing 0's to the disk in the key's place
     /// sort of less relevant now that the large keys have a paranoid mode; probably this routine should
     /// actually be a higher-level function that catches the paranoid request and does an "update" of 0's
@@ -4,6 +4,7 @@ use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::io::SeekFrom;
 use std::mem::MaybeUninit;
+use std::path::PathBuf;
 use std::sync::Once;
 
 use crate::api::*;
@@ -20,17 +21,34 @@ struct FlashSingleton {
     disk: File,
 }
 
+/// Where the PDDB image lives if `XOUS_PDDB_IMAGE` is not set. Hosted mode runs from the `kernel`
+/// directory, so this is `tools/pddb-images/hosted.bin` in the source tree.
+const DEFAULT_IMAGE_PATH: &str = "../tools/pddb-images/hosted.bin";
+
+/// The host file that backs the emulated FLASH. Its contents persist between runs, so keys and
+/// certificates provisioned in one session are still there in the next; delete it to start over.
+fn image_path() -> PathBuf {
+    std::env::var_os("XOUS_PDDB_IMAGE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_IMAGE_PATH))
+}
+
 fn flashmem() -> &'static mut FlashSingleton {
     static mut SINGLETON: MaybeUninit<FlashSingleton> = MaybeUninit::uninit();
     static ONCE: Once = Once::new();
 
     unsafe {
         ONCE.call_once(|| {
+            let path = image_path();
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).expect("Can't create the directory for the PDDB image file");
+            }
+            log::info!("PDDB image file: {}", path.display());
             let mut disk = OpenOptions::new()
                 .read(true)
                 .write(true)
                 .create(true)
-                .open("../tools/pddb-images/hosted.bin")
+                .open(&path)
                 .expect("Can't open a PDDB image file for writing");
 
             let mut memory = Vec::<u8>::with_capacity(PDDB_A_LEN);
@@ -38,7 +56,7 @@ fn flashmem() -> &'static mut FlashSingleton {
                 for _ in 0..PDDB_A_LEN {
                     memory.push(0xFF);
                 }
-                disk.write(&memory).expect("couldn't create initial disk image");
+                disk.write_all(&memory).expect("couldn't create initial disk image");
             } else {
                 match disk.read_to_end(&mut memory) {
                     Ok(bytes_read) => {
@@ -48,6 +66,11 @@ fn flashmem() -> &'static mut FlashSingleton {
                                 bytes_read,
                                 PDDB_A_LEN
                             );
+                            // pad or trim the image to size, so offsets in memory and on disk agree
+                            memory.resize(PDDB_A_LEN, 0xFF);
+                            disk.set_len(PDDB_A_LEN as u64).expect("couldn't resize PDDB disk image");
+                            disk.seek(SeekFrom::Start(0)).expect("couldn't seek PDDB");
+                            disk.write_all(&memory).expect("couldn't resize PDDB disk image");
                         }
                     }
                     _ => {
@@ -94,7 +117,7 @@ impl EmuStorage {
     pub fn dump_fs(&self, name: &Option<String>) {
         let defaultname = String::from("pddb");
         let rootname = name.as_ref().unwrap_or(&defaultname);
-        let mut f = File::create(format!("../tools/pddb-images/{}.bin", rootname)).unwrap();
+        let mut f = File::create(image_path().with_file_name(format!("{}.bin", rootname))).unwrap();
         f.write_all(flashmem().memory.as_slice()).unwrap();
         f.flush().unwrap();
     }
@@ -102,7 +125,7 @@ impl EmuStorage {
     pub fn dump_keys(&self, known_keys: &[KeyExport], name: &Option<String>) {
         let defaultname = String::from("pddb");
         let rootname = name.as_ref().unwrap_or(&defaultname);
-        let mut f = File::create(format!("../tools/pddb-images/{}.key", rootname)).unwrap();
+        let mut f = File::create(image_path().with_file_name(format!("{}.key", rootname))).unwrap();
         f.write_all(&(known_keys.len() as u32).to_le_bytes()).unwrap();
         for key in known_keys {
             f.write_all(&key.basis_name).unwrap();
@@ -132,7 +155,7 @@ impl HostedSpinor {
             *dst = src;
         }
         flashmem().disk.seek(SeekFrom::Start(offset as u64)).expect("couldn't seek PDDB");
-        flashmem().disk.write(data).expect("couldn't write PDDB");
+        flashmem().disk.write_all(data).expect("couldn't write PDDB");
         Ok(())
     }
 
@@ -143,12 +166,12 @@ impl HostedSpinor {
         {
             *b = 0xFF;
         }
-        flashmem().disk.seek(SeekFrom::Start(start as u64)).expect("couldn't seek PDDB");
+        flashmem().disk.seek(SeekFrom::Start((start - xous::PDDB_LOC) as u64)).expect("couldn't seek PDDB");
         let mut blank = Vec::<u8>::with_capacity(len as usize);
         for _ in 0..len {
             blank.push(0xFF);
         }
-        flashmem().disk.write(&blank).expect("couldn't write PDDB");
+        flashmem().disk.write_all(&blank).expect("couldn't write PDDB");
         Ok(())
     }
 }
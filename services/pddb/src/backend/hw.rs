@@ -1424,6 +1424,26 @@ impl PddbOs {
     /// returns a count of the number of pages in the fspace cache
     pub fn fast_space_len(&self) -> usize { self.fspace_cache.len() }
 
+    /// Estimates how many bytes of data can still be stored: the pages used by neither the open Basis nor
+    /// the FSCB, times the data a page holds. No user interaction is needed, so the pages of any Basis that
+    /// is locked count as free, as they do for every allocation made while it is locked.
+    pub(crate) fn free_space_estimate(&self, cache: &Vec<BasisCacheEntry>) -> usize {
+        let total_pages = (PDDB_A_LEN - self.data_phys_base.as_usize()) / PAGE_SIZE;
+        let mut used = std::collections::HashSet::new();
+        for entry in cache.iter() {
+            used.extend(entry.v2p_map.values().map(|pp| pp.page_number()));
+        }
+        used.extend(
+            self.fspace_cache
+                .iter()
+                .filter(|pp| {
+                    pp.space_state() == SpaceState::Used || pp.space_state() == SpaceState::MaybeUsed
+                })
+                .map(|pp| pp.page_number()),
+        );
+        total_pages.saturating_sub(used.len()) * VPAGE_SIZE
+    }
+
     /// Normally, the fspace_log_next_addr is just incremented, but when it hits the end of the
     /// page, it's set to None. This function will do a modestly expensive scan of the FSCB area
     /// to try and either find another partially filled page, or a completely empty page.
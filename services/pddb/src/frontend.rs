@@ -1,2 +1,4 @@
 pub mod pddbkey;
 pub use pddbkey::*;
+pub mod cache;
+pub use cache::{WritePolicy, DEFAULT_CACHE_CAPACITY};
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+/// Governs when writes made with `Pddb::cached_write()` reach the PDDB
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WritePolicy {
+    /// Every write goes to the PDDB right away; the cache only saves reads.
+    WriteThrough,
+    /// Writes are held in RAM until `Pddb::flush()` or `Pddb::sync_all()` is called, or until they are
+    /// evicted to make room. Whatever is still dirty is written back when the `Pddb` object is dropped,
+    /// but a crash or power loss before then loses it.
+    WriteBack,
+}
+
+/// By default the cache holds up to this many bytes of key data
+pub const DEFAULT_CACHE_CAPACITY: usize = 32 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    pub(crate) dict: String,
+    pub(crate) key: String,
+    pub(crate) basis: Option<String>,
+}
+
+impl CacheKey {
+    pub(crate) fn new(dict: &str, key: &str, basis: Option<&str>) -> Self {
+        CacheKey { dict: dict.to_string(), key: key.to_string(), basis: basis.map(|b| b.to_string()) }
+    }
+}
+
+struct CacheEntry {
+    data: Vec<u8>,
+    dirty: bool,
+    last_use: u64,
+}
+
+/// Whole-key copies of PDDB data, evicted least-recently-used first once they exceed the capacity.
+pub(crate) struct KeyCache {
+    entries: HashMap<CacheKey, CacheEntry>,
+    policy: WritePolicy,
+    capacity: usize,
+    size: usize,
+    clock: u64,
+}
+
+impl KeyCache {
+    pub(crate) fn new(policy: WritePolicy, capacity: usize) -> Self {
+        KeyCache { entries: HashMap::new(), policy, capacity, size: 0, clock: 0 }
+    }
+
+    pub(crate) fn policy(&self) -> WritePolicy { self.policy }
+
+    pub(crate) fn configure(&mut self, policy: WritePolicy, capacity: usize) {
+        self.policy = policy;
+        self.capacity = capacity;
+    }
+
+    pub(crate) fn get(&mut self, key: &CacheKey) -> Option<Vec<u8>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.entries.get_mut(key).map(|entry| {
+            entry.last_use = clock;
+            entry.data.clone()
+        })
+    }
+
+    pub(crate) fn insert(&mut self, key: CacheKey, data: Vec<u8>, dirty: bool) {
+        self.clock += 1;
+        self.size += data.len();
+        if let Some(old) = self.entries.insert(key, CacheEntry { data, dirty, last_use: self.clock }) {
+            self.size -= old.data.len();
+        }
+    }
+
+    /// Drops one key; any unwritten data in it is discarded.
+    pub(crate) fn remove(&mut self, key: &CacheKey) {
+        if let Some(old) = self.entries.remove(key) {
+            self.size -= old.data.len();
+        }
+    }
+
+    /// Drops every key of a dictionary; any unwritten data in them is discarded.
+    pub(crate) fn remove_dict(&mut self, dict: &str, basis: Option<&str>) {
+        let size = &mut self.size;
+        self.entries.retain(|k, entry| {
+            let matches = k.dict == dict && (basis.is_none() || k.basis.as_deref() == basis);
+            if matches {
+                *size -= entry.data.len();
+            }
+            !matches
+        });
+    }
+
+    /// Drops every key, and returns the dirty ones, which must be written back by the caller.
+    pub(crate) fn clear(&mut self) -> Vec<(CacheKey, Vec<u8>)> {
+        let dirty = self
+            .entries
+            .drain()
+            .filter(|(_, entry)| entry.dirty)
+            .map(|(key, entry)| (key, entry.data))
+            .collect();
+        self.size = 0;
+        dirty
+    }
+
+    /// Returns copies of the dirty keys. They stay dirty until the caller has written them back, and
+    /// calls `mark_clean()`.
+    pub(crate) fn dirty(&self) -> Vec<(CacheKey, Vec<u8>)> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.dirty)
+            .map(|(key, entry)| (key.clone(), entry.data.clone()))
+            .collect()
+    }
+
+    /// Records that `data` was written back to `key`. If the key was changed since, it stays dirty.
+    pub(crate) fn mark_clean(&mut self, key: &CacheKey, data: &[u8]) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            if entry.data == data {
+                entry.dirty = false;
+            }
+        }
+    }
+
+    /// Takes back a dirty key that couldn't be written back after it left the cache, so that it isn't
+    /// lost. A newer copy of the key, if there is one, wins.
+    pub(crate) fn restore_dirty(&mut self, key: CacheKey, data: Vec<u8>) {
+        if !self.entries.contains_key(&key) {
+            self.insert(key, data, true);
+        }
+    }
+
+    /// Evicts keys until the cache fits its capacity, and returns the dirty ones among them, which must be
+    /// written back by the caller.
+    pub(crate) fn evict(&mut self) -> Vec<(CacheKey, Vec<u8>)> {
+        let mut dirty = Vec::new();
+        while self.size > self.capacity {
+            let oldest = match self.entries.iter().min_by_key(|(_, entry)| entry.last_use) {
+                Some((key, _)) => key.clone(),
+                None => break,
+            };
+            let entry = self.entries.remove(&oldest).unwrap();
+            self.size -= entry.data.len();
+            if entry.dirty {
+                dirty.push((oldest, entry.data));
+            }
+        }
+        dirty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let mut cache = KeyCache::new(WritePolicy::WriteBack, 8);
+        let (a, b, c) =
+            (CacheKey::new("d", "a", None), CacheKey::new("d", "b", None), CacheKey::new("d", "c", None));
+        cache.insert(a.clone(), vec![0; 4], false);
+        cache.insert(b.clone(), vec![1; 4], true);
+        assert_eq!(cache.get(&a), Some(vec![0; 4]));
+        cache.insert(c.clone(), vec![2; 4], false);
+        // b is the oldest now, and has to be written back
+        assert_eq!(cache.evict(), vec![(b.clone(), vec![1; 4])]);
+        assert_eq!(cache.get(&b), None);
+        assert_eq!(cache.get(&a), Some(vec![0; 4]));
+        assert_eq!(cache.get(&c), Some(vec![2; 4]));
+    }
+
+    #[test]
+    fn test_cache_dirty_tracking() {
+        let mut cache = KeyCache::new(WritePolicy::WriteBack, 1024);
+        let (a, b) = (CacheKey::new("d", "a", None), CacheKey::new("e", "b", Some("basis")));
+        cache.insert(a.clone(), vec![0; 4], true);
+        cache.insert(b.clone(), vec![1; 4], false);
+        assert_eq!(cache.dirty(), vec![(a.clone(), vec![0; 4])]);
+        // nothing is clean until it has been written back
+        assert_eq!(cache.dirty(), vec![(a.clone(), vec![0; 4])]);
+        cache.mark_clean(&a, &[0; 4]);
+        assert!(cache.dirty().is_empty());
+        cache.insert(a.clone(), vec![2; 8], true);
+        cache.remove_dict("d", None);
+        assert_eq!(cache.get(&a), None);
+        assert!(cache.clear().is_empty());
+        assert_eq!(cache.get(&b), None);
+    }
+
+    #[test]
+    fn test_cache_keeps_failed_write_backs() {
+        let mut cache = KeyCache::new(WritePolicy::WriteBack, 1024);
+        let a = CacheKey::new("d", "a", None);
+        cache.insert(a.clone(), vec![0; 4], true);
+        // the key changed while the old data was being written back
+        let written = cache.dirty();
+        cache.insert(a.clone(), vec![1; 4], true);
+        cache.mark_clean(&a, &written[0].1);
+        assert_eq!(cache.dirty(), vec![(a.clone(), vec![1; 4])]);
+
+        // a key that was evicted or cleared, but couldn't be written, comes back dirty
+        let lost = cache.clear();
+        assert_eq!(cache.get(&a), None);
+        for (key, data) in lost {
+            cache.restore_dirty(key, data);
+        }
+        assert_eq!(cache.dirty(), vec![(a.clone(), vec![1; 4])]);
+        // unless a newer copy was written in the meantime
+        cache.insert(a.clone(), vec![2; 4], true);
+        cache.restore_dirty(a.clone(), vec![1; 4]);
+        assert_eq!(cache.get(&a), Some(vec![2; 4]));
+    }
+}
@@ -5,12 +5,15 @@ pub use api::*;
 pub mod frontend;
 use core::sync::atomic::{AtomicU32, Ordering};
 use std::collections::HashMap;
-use std::io::{Error, ErrorKind, Result};
+use std::io::{Error, ErrorKind, Read, Result, Write};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
 
+use frontend::cache::CacheKey;
 pub use frontend::*;
+pub mod picker;
+pub use picker::*;
 use num_traits::*;
 use xous::{msg_scalar_unpack, send_message, Message, CID, SID};
 use xous_ipc::Buffer;
@@ -47,6 +50,15 @@ impl PddbMountPoller {
         PddbMountPoller { conn }
     }
 
+    /// Like `new()`, but returns `None` instead of waiting when the PDDB server isn't running yet (or
+    /// isn't part of the image at all).
+    pub fn try_new() -> Option<Self> {
+        let xns = xous_names::XousNames::new().unwrap();
+        let conn = xns.request_connection(api::SERVER_NAME_PDDB_POLLER).ok()?;
+        POLLER_REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Some(PddbMountPoller { conn })
+    }
+
     /// This call is guaranteed to *never* block and return the instantaneous state of the PDDB, even if the
     /// server itself is currently busy processing other requests. This has to be done with a separate
     /// server from the main one, because the main server will block during the mount operations, as it
@@ -108,6 +120,8 @@ pub struct Pddb {
     /// These are temporary fields only to be used by the consistency check feature.
     key_count: RefCell<u32>,
     found_key_count: RefCell<u32>,
+    /// RAM copies of keys accessed with `cached_read()` and `cached_write()`
+    cache: RefCell<frontend::cache::KeyCache>,
 }
 impl Pddb {
     pub fn new() -> Self {
@@ -126,6 +140,10 @@ impl Pddb {
             // These are record the result of the most recent call to list_keys()
             key_count: RefCell::new(0),
             found_key_count: RefCell::new(0),
+            cache: RefCell::new(frontend::cache::KeyCache::new(
+                WritePolicy::WriteThrough,
+                DEFAULT_CACHE_CAPACITY,
+            )),
         }
     }
 
@@ -375,6 +393,7 @@ impl Pddb {
         if basis_name.len() > BASIS_NAME_LEN - 1 {
             return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
         }
+        self.invalidate_cache_for_basis_change();
         let mgmt = PddbBasisRequest {
             name: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis_name),
             code: PddbRequestCode::Open,
@@ -403,6 +422,7 @@ impl Pddb {
         if basis_name.len() > BASIS_NAME_LEN - 1 {
             return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
         }
+        self.invalidate_cache_for_basis_change();
         let mgmt = PddbBasisRequest {
             name: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis_name),
             code: PddbRequestCode::Close,
@@ -429,6 +449,7 @@ impl Pddb {
         if basis_name.len() > BASIS_NAME_LEN - 1 {
             return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
         }
+        self.invalidate_cache_for_basis_change();
         let mgmt = PddbBasisRequest {
             name: xous_ipc::String::<BASIS_NAME_LEN>::from_str(basis_name),
             code: PddbRequestCode::Delete,
@@ -550,6 +571,11 @@ impl Pddb {
 
     /// deletes a key within the dictionary
     pub fn delete_key(&self, dict_name: &str, key_name: &str, basis_name: Option<&str>) -> Result<()> {
+        self.cache.borrow_mut().remove(&CacheKey::new(dict_name, key_name, basis_name));
+        self.delete_key_uncached(dict_name, key_name, basis_name)
+    }
+
+    fn delete_key_uncached(&self, dict_name: &str, key_name: &str, basis_name: Option<&str>) -> Result<()> {
         if key_name.len() > (KEY_NAME_LEN - 1) {
             return Err(Error::new(ErrorKind::InvalidInput, "key name too long"));
         }
@@ -607,6 +633,9 @@ impl Pddb {
         if key_list.len() == 0 {
             return Ok(());
         }
+        for key_name in key_list.iter() {
+            self.cache.borrow_mut().remove(&CacheKey::new(dict_name, key_name, basis_name));
+        }
         if dict_name.len() > (DICT_NAME_LEN - 1) {
             return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
         }
@@ -625,24 +654,7 @@ impl Pddb {
             retcode: PddbRetcode::Uninit,
             data: [0u8; MAX_PDDB_DELETE_LEN],
         };
-        let mut index = 0;
-        for keyname in key_list {
-            if keyname.len() > (KEY_NAME_LEN - 1) {
-                return Err(Error::new(ErrorKind::InvalidInput, "one of the key names is too long"));
-            }
-            if keyname.len() + 1 + index < MAX_PDDB_DELETE_LEN {
-                assert!(keyname.len() < u8::MAX as usize); // this should always be true due to other limits in the PDDB
-                request.data[index] = keyname.len() as u8;
-                index += 1;
-                request.data[index..index + keyname.len()].copy_from_slice(keyname.as_bytes());
-                index += keyname.len();
-            } else {
-                return Err(Error::new(
-                    ErrorKind::OutOfMemory,
-                    "Key list total size exceeds MAX_PDDBKLISTLEN",
-                ));
-            }
-        }
+        pack_key_list(&key_list, &mut request.data)?;
         let mut buf =
             Buffer::into_buf(request).or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
         buf.lend_mut(self.conn, Opcode::DictBulkDelete.to_u32().unwrap())
@@ -666,6 +678,7 @@ impl Pddb {
         if dict_name.len() > (DICT_NAME_LEN - 1) {
             return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
         }
+        self.cache.borrow_mut().remove_dict(dict_name, basis_name);
         let bname = if let Some(bname) = basis_name {
             if bname.len() > BASIS_NAME_LEN - 1 {
                 return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
@@ -706,6 +719,163 @@ impl Pddb {
         }
     }
 
+    /// Renames a key within its dictionary. The key keeps its data and its place in the PDDB, and is
+    /// renamed in a single write, so a power loss leaves it under one name or the other; handles that are
+    /// open on the key follow it to the new name. Fails with `AlreadyExists` if `new_key_name` is taken.
+    pub fn rename_key(
+        &self,
+        dict_name: &str,
+        key_name: &str,
+        new_key_name: &str,
+        basis_name: Option<&str>,
+    ) -> Result<()> {
+        if key_name.len() > (KEY_NAME_LEN - 1) || new_key_name.len() > (KEY_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "key name too long"));
+        }
+        // anything still cached under the old name goes out before the name changes
+        self.flush()?;
+        self.cache.borrow_mut().remove(&CacheKey::new(dict_name, key_name, basis_name));
+        self.rename(Opcode::RenameKey, dict_name, key_name, new_key_name, basis_name)
+    }
+
+    /// Renames a dictionary, keeping all its keys. Like `rename_key()`, the rename is a single write,
+    /// and open handles follow it. Fails with `AlreadyExists` if `new_dict_name` is taken.
+    pub fn rename_dict(&self, dict_name: &str, new_dict_name: &str, basis_name: Option<&str>) -> Result<()> {
+        if new_dict_name.len() > (DICT_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+        }
+        self.flush()?;
+        self.cache.borrow_mut().remove_dict(dict_name, basis_name);
+        self.rename(Opcode::RenameDict, dict_name, "", new_dict_name, basis_name)
+    }
+
+    fn rename(
+        &self,
+        op: Opcode,
+        dict_name: &str,
+        key_name: &str,
+        new_name: &str,
+        basis_name: Option<&str>,
+    ) -> Result<()> {
+        if dict_name.len() > (DICT_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+        }
+        let bname = if let Some(bname) = basis_name {
+            if bname.len() > BASIS_NAME_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+            }
+            xous_ipc::String::<BASIS_NAME_LEN>::from_str(bname)
+        } else {
+            xous_ipc::String::<BASIS_NAME_LEN>::new()
+        };
+        let request = PddbRename {
+            basis_specified: basis_name.is_some(),
+            basis: bname,
+            dict: xous_ipc::String::<DICT_NAME_LEN>::from_str(dict_name),
+            key: xous_ipc::String::<KEY_NAME_LEN>::from_str(key_name),
+            new_name: xous_ipc::String::<DICT_NAME_LEN>::from_str(new_name),
+            result: PddbRequestCode::Uninit,
+        };
+        let mut buf =
+            Buffer::into_buf(request).or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.conn, op.to_u32().unwrap())
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+
+        let response = buf.to_original::<PddbRename, _>().unwrap();
+        match response.result {
+            PddbRequestCode::NoErr => Ok(()),
+            PddbRequestCode::NotFound => {
+                Err(Error::new(ErrorKind::NotFound, "Dictionary or key was not found"))
+            }
+            PddbRequestCode::DuplicateEntry => {
+                Err(Error::new(ErrorKind::AlreadyExists, "The new name is already in use"))
+            }
+            _ => Err(Error::new(ErrorKind::Other, "Internal error")),
+        }
+    }
+
+    /// Returns the integrity tag of a key: an HMAC over its dictionary, name and data, keyed by a secret
+    /// of the basis that holds it. Applications that need to notice silent corruption or the rollback of
+    /// a record can keep the tag of what they last wrote, and check reads against it with
+    /// `verify_key()`. The tag is computed over the data as it is now in the PDDB, so dirty cached
+    /// writes are flushed first.
+    pub fn key_tag(
+        &self,
+        dict_name: &str,
+        key_name: &str,
+        basis_name: Option<&str>,
+    ) -> Result<[u8; KEY_TAG_LEN]> {
+        self.flush()?;
+        let response = self.key_tag_request(dict_name, key_name, basis_name, None)?;
+        match response.result {
+            PddbRequestCode::NoErr => Ok(response.tag),
+            PddbRequestCode::NotFound => {
+                Err(Error::new(ErrorKind::NotFound, "Dictionary or key was not found"))
+            }
+            _ => Err(Error::new(ErrorKind::Other, "Internal error")),
+        }
+    }
+
+    /// Checks a key against a tag from `key_tag()`, and fails with `InvalidData` if the key no longer
+    /// matches it. The comparison is done by the PDDB, in constant time.
+    pub fn verify_key(
+        &self,
+        dict_name: &str,
+        key_name: &str,
+        basis_name: Option<&str>,
+        tag: &[u8; KEY_TAG_LEN],
+    ) -> Result<()> {
+        self.flush()?;
+        let response = self.key_tag_request(dict_name, key_name, basis_name, Some(tag))?;
+        match response.result {
+            PddbRequestCode::NoErr => Ok(()),
+            PddbRequestCode::NotFound => {
+                Err(Error::new(ErrorKind::NotFound, "Dictionary or key was not found"))
+            }
+            PddbRequestCode::IntegrityError => {
+                Err(Error::new(ErrorKind::InvalidData, "Key does not match its integrity tag"))
+            }
+            _ => Err(Error::new(ErrorKind::Other, "Internal error")),
+        }
+    }
+
+    fn key_tag_request(
+        &self,
+        dict_name: &str,
+        key_name: &str,
+        basis_name: Option<&str>,
+        verify: Option<&[u8; KEY_TAG_LEN]>,
+    ) -> Result<PddbKeyTag> {
+        if key_name.len() > (KEY_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "key name too long"));
+        }
+        if dict_name.len() > (DICT_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+        }
+        let bname = if let Some(bname) = basis_name {
+            if bname.len() > BASIS_NAME_LEN - 1 {
+                return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+            }
+            xous_ipc::String::<BASIS_NAME_LEN>::from_str(bname)
+        } else {
+            xous_ipc::String::<BASIS_NAME_LEN>::new()
+        };
+        let request = PddbKeyTag {
+            basis_specified: basis_name.is_some(),
+            basis: bname,
+            dict: xous_ipc::String::<DICT_NAME_LEN>::from_str(dict_name),
+            key: xous_ipc::String::<KEY_NAME_LEN>::from_str(key_name),
+            tag: verify.copied().unwrap_or([0u8; KEY_TAG_LEN]),
+            verify: verify.is_some(),
+            result: PddbRequestCode::Uninit,
+        };
+        let mut buf =
+            Buffer::into_buf(request).or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        buf.lend_mut(self.conn, Opcode::KeyIntegrityTag.to_u32().unwrap())
+            .or(Err(Error::new(ErrorKind::Other, "Xous internal error")))?;
+        Ok(buf.to_original::<PddbKeyTag, _>().unwrap())
+    }
+
     pub fn sync(&self) -> Result<()> {
         let response = send_message(
             self.conn,
@@ -726,6 +896,129 @@ impl Pddb {
         }
     }
 
+    /// Sets the write policy and the capacity, in bytes of key data, of the RAM cache used by
+    /// `cached_read()` and `cached_write()`. The default is write-through, with
+    /// `DEFAULT_CACHE_CAPACITY` bytes. Switching to write-through writes back anything dirty.
+    pub fn set_cache_policy(&self, policy: WritePolicy, capacity: usize) -> Result<()> {
+        self.cache.borrow_mut().configure(policy, capacity);
+        if policy == WritePolicy::WriteThrough {
+            self.flush()?;
+        }
+        let evicted = self.cache.borrow_mut().evict();
+        self.write_back(evicted)
+    }
+
+    /// Returns the entire contents of a key, from the RAM cache if it's there. This is meant for keys
+    /// that a process reads over and over, and that no other process modifies.
+    ///
+    /// Keys are cached under the exact `basis_name` given, and the cache only learns about basis changes
+    /// made through this object. A process that caches keys without naming a basis should call
+    /// `invalidate_cache()` from its basis change callback. Reading a key with `get()` after writing it
+    /// with `cached_write()` needs a `flush()` in between.
+    pub fn cached_read(&self, dict_name: &str, key_name: &str, basis_name: Option<&str>) -> Result<Vec<u8>> {
+        let cache_key = CacheKey::new(dict_name, key_name, basis_name);
+        if let Some(data) = self.cache.borrow_mut().get(&cache_key) {
+            return Ok(data);
+        }
+        let mut data = Vec::new();
+        self.get(dict_name, key_name, basis_name, false, false, None, None::<fn()>)?
+            .read_to_end(&mut data)?;
+        self.cache.borrow_mut().insert(cache_key, data.clone(), false);
+        let evicted = self.cache.borrow_mut().evict();
+        self.write_back(evicted)?;
+        Ok(data)
+    }
+
+    /// Replaces the entire contents of a key, creating the dictionary and the key if needed. Under
+    /// `WritePolicy::WriteBack`, the data stays in RAM until it is flushed or evicted.
+    pub fn cached_write(
+        &self,
+        dict_name: &str,
+        key_name: &str,
+        basis_name: Option<&str>,
+        data: &[u8],
+    ) -> Result<()> {
+        // reject bad names now, rather than when the data is written back
+        if key_name.len() > (KEY_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "key name too long"));
+        }
+        if dict_name.len() > (DICT_NAME_LEN - 1) {
+            return Err(Error::new(ErrorKind::InvalidInput, "dictionary name too long"));
+        }
+        if basis_name.map(|b| b.len() > BASIS_NAME_LEN - 1).unwrap_or(false) {
+            return Err(Error::new(ErrorKind::InvalidInput, "basis name too long"));
+        }
+        let cache_key = CacheKey::new(dict_name, key_name, basis_name);
+        let policy = self.cache.borrow().policy();
+        match policy {
+            WritePolicy::WriteThrough => {
+                self.write_key(&cache_key, data)?;
+                self.cache.borrow_mut().insert(cache_key, data.to_vec(), false);
+            }
+            WritePolicy::WriteBack => self.cache.borrow_mut().insert(cache_key, data.to_vec(), true),
+        }
+        let evicted = self.cache.borrow_mut().evict();
+        self.write_back(evicted)
+    }
+
+    /// Writes every dirty key in the RAM cache back to the PDDB. Like any other write, the data may
+    /// still sit in the PDDB's buffers afterwards; `sync_all()` commits it to FLASH as well.
+    pub fn flush(&self) -> Result<()> {
+        let dirty = self.cache.borrow().dirty();
+        self.write_back(dirty)
+    }
+
+    /// `flush()` followed by `sync()`
+    pub fn sync_all(&self) -> Result<()> {
+        self.flush()?;
+        self.sync()
+    }
+
+    /// Empties the RAM cache, writing back anything dirty first. Keys that couldn't be written back stay
+    /// in the cache, so that a later `flush()` can retry them.
+    pub fn invalidate_cache(&self) -> Result<()> {
+        let dirty = self.cache.borrow_mut().clear();
+        self.write_back(dirty)
+    }
+
+    fn invalidate_cache_for_basis_change(&self) {
+        // dirty keys go to the bases they were written under, while those are still open
+        if let Err(e) = self.invalidate_cache() {
+            log::warn!("couldn't write back cached keys before a basis change: {:?}", e);
+        }
+    }
+
+    /// Writes back dirty keys from the cache. Every key is attempted, and the first error is returned.
+    /// A key is only marked clean once it has been written; one that couldn't be written stays dirty in
+    /// the cache, even if it was on its way out.
+    fn write_back(&self, entries: Vec<(CacheKey, Vec<u8>)>) -> Result<()> {
+        let mut result = Ok(());
+        for (key, data) in entries {
+            match self.write_key(&key, &data) {
+                Ok(()) => self.cache.borrow_mut().mark_clean(&key, &data),
+                Err(e) => {
+                    log::error!("couldn't write back {}:{}: {:?}", key.dict, key.key, e);
+                    self.cache.borrow_mut().restore_dirty(key, data);
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn write_key(&self, key: &CacheKey, data: &[u8]) -> Result<()> {
+        // keys don't shrink on write, so the old one is deleted first
+        match self.delete_key_uncached(&key.dict, &key.key, key.basis.as_deref()) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+        self.get(&key.dict, &key.key, key.basis.as_deref(), true, true, Some(data.len()), None::<fn()>)?
+            .write_all(data)
+    }
+
     /// cleans up inconsistencies in the PDDB. fsck-like.
     pub fn sync_cleanup(&self) -> Result<()> {
         let response = send_message(
@@ -977,6 +1270,18 @@ impl Pddb {
         .expect("couldn't send FlushSpaceUpdate");
     }
 
+    /// Estimates how many bytes of data can still be stored. The pages of any Basis that is locked count
+    /// as free, so this is an upper bound when not every Basis is unlocked.
+    pub fn free_space(&self) -> Result<usize> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::FreeSpace.to_usize().unwrap(), 0, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar1(free)) => Ok(free),
+            _ => Err(Error::new(ErrorKind::Other, "Xous internal error")),
+        }
+    }
+
     /// Manually prune the PDDB cache.
     /// Mostly provided for force-triggering for testing; normally this is done automatically
     pub fn manual_prune(&self) {
@@ -1305,6 +1610,9 @@ impl Pddb {
 
 impl Drop for Pddb {
     fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            log::error!("cached keys were lost: {:?}", e);
+        }
         if let Some(cb_sid) = self.cb.take() {
             let handle = self.cb_handle.take().unwrap(); // we guarantee this is always set when cb is set
             let cid = xous::connect(cb_sid).unwrap();
@@ -373,6 +373,8 @@ mod ux;
 use ux::*;
 mod menu;
 use menu::*;
+mod autolock;
+use autolock::*;
 
 mod libstd;
 
@@ -403,6 +405,7 @@ use rkyv::{
     ser::{serializers::BufferSerializer, Serializer},
     Deserialize,
 };
+use subtle::ConstantTimeEq;
 use xous::{msg_blocking_scalar_unpack, send_message, Message};
 use xous_ipc::Buffer;
 
@@ -459,6 +462,7 @@ fn main() -> ! {
 
 fn wrapped_main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
@@ -581,6 +585,21 @@ fn wrapped_main() -> ! {
             }
         }
     });
+    // a thread to advance the auto-lock timers
+    let _ = thread::spawn({
+        let my_cid = my_cid.clone();
+        move || {
+            let tt = ticktimer_server::Ticktimer::new().unwrap();
+            loop {
+                tt.sleep_ms(AUTOLOCK_TICK_SECS as usize * 1000).unwrap();
+                send_message(
+                    my_cid,
+                    Message::new_scalar(Opcode::AutoLockTick.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .expect("couldn't send auto-lock tick");
+            }
+        }
+    });
     // main server loop
     let mut key_list: Option<BTreeSet<String>> = None; // storage for key lists
     let mut key_token: Option<[u32; 4]> = None;
@@ -599,6 +618,9 @@ fn wrapped_main() -> ! {
     // track the basis monitor requester.
     let mut basis_monitor_notifications = Vec::<xous::MessageEnvelope>::new();
 
+    // bases locked on the last suspend, whose passwords are to be asked for again
+    let mut relock_pending = Vec::<String>::new();
+
     // track heap usage
     let mut initial_heap: usize = 0;
     let mut latest_heap: usize = 0;
@@ -623,6 +645,8 @@ fn wrapped_main() -> ! {
     }
 
     let tt = ticktimer_server::Ticktimer::new().unwrap();
+    // the auto-lock timers are advanced by the time we measure ourselves, not by what a tick claims
+    let mut autolock_clock = AutoLockClock::new(tt.elapsed_ms());
     // turn on (or off) performance profiling, if the feature is enabled
     #[cfg(feature = "perfcounter")]
     pddb_os.set_use_perf(true);
@@ -637,8 +661,24 @@ fn wrapped_main() -> ! {
         log::debug!("{:x?}", op);
         match op {
             Opcode::SuspendResume => xous::msg_scalar_unpack!(msg, token, _, _, _, {
-                basis_cache.suspend(&mut pddb_os);
+                let locked = basis_cache.suspend(&mut pddb_os);
                 susres.suspend_until_resume(token).expect("couldn't execute suspend/resume");
+                if locked.len() > 0 {
+                    if basis_monitor_notifications.len() > 0 {
+                        notify_basis_change(&mut basis_monitor_notifications, basis_cache.basis_list());
+                    }
+                    for name in locked {
+                        if !relock_pending.contains(&name) {
+                            relock_pending.push(name);
+                        }
+                    }
+                    // the password prompts are deferred so the resume sequence isn't held up by the user
+                    send_message(
+                        my_cid,
+                        Message::new_scalar(Opcode::ResumeUnlock.to_usize().unwrap(), 0, 0, 0, 0),
+                    )
+                    .expect("couldn't queue resume unlock");
+                }
             }),
             Opcode::IsEfuseSecured => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                 if pddb_os.is_efuse_secured() {
@@ -931,78 +971,18 @@ fn wrapped_main() -> ! {
                 let mut mgmt = buffer.to_original::<PddbBasisRequest, _>().unwrap();
                 match mgmt.code {
                     PddbRequestCode::Open => {
-                        let mut finished = false;
-                        while !finished {
-                            let request = BasisRequestPassword { db_name: mgmt.name, plaintext_pw: None };
-                            let mut buf = Buffer::into_buf(request).unwrap();
-                            buf.lend_mut(pw_cid, PwManagerOpcode::RequestPassword.to_u32().unwrap()).unwrap();
-                            let ret = buf.to_original::<BasisRequestPassword, _>().unwrap();
-                            if let Some(pw) = ret.plaintext_pw {
-                                if let Some(basis) = basis_cache.basis_unlock(
-                                    &mut pddb_os,
-                                    mgmt.name.as_str().expect("name is not valid utf-8"),
-                                    pw.as_str().expect("password was not valid utf-8"),
-                                    mgmt.policy.unwrap_or(BasisRetentionPolicy::Persist),
-                                ) {
-                                    if basis_cache.basis_contains(&basis.name) {
-                                        basis_cache
-                                            .basis_unmount(&mut pddb_os, &basis.name)
-                                            .expect("couldn't unmount previously mounted basis of same name");
-                                        modals
-                                            .show_notification(
-                                                t!("pddb.unmount_previous", locales::LANG),
-                                                None,
-                                            )
-                                            .expect("notification failed");
-                                    }
-                                    basis_cache.basis_add(basis);
-                                    finished = true;
-                                    log::info!(
-                                        "{}PDDB.UNLOCKOK,{},{}",
-                                        xous::BOOKEND_START,
-                                        mgmt.name.as_str().unwrap(),
-                                        xous::BOOKEND_END
-                                    );
-                                    if basis_monitor_notifications.len() > 0 {
-                                        notify_basis_change(
-                                            &mut basis_monitor_notifications,
-                                            basis_cache.basis_list(),
-                                        );
-                                    }
-                                    mgmt.code = PddbRequestCode::NoErr;
-                                } else {
-                                    log::info!(
-                                        "{}PDDB.BADPASS,{},{}",
-                                        xous::BOOKEND_START,
-                                        mgmt.name.as_str().unwrap(),
-                                        xous::BOOKEND_END
-                                    );
-                                    modals
-                                        .add_list_item(t!("pddb.yes", locales::LANG))
-                                        .expect("couldn't build radio item list");
-                                    modals
-                                        .add_list_item(t!("pddb.no", locales::LANG))
-                                        .expect("couldn't build radio item list");
-                                    match modals.get_radiobutton(t!("pddb.badpass", locales::LANG)) {
-                                        Ok(response) => {
-                                            if response.as_str() == t!("pddb.yes", locales::LANG) {
-                                                finished = false;
-                                                // this will cause just another go-around
-                                            } else if response.as_str() == t!("pddb.no", locales::LANG) {
-                                                finished = true;
-                                                mgmt.code = PddbRequestCode::AccessDenied; // this will cause a return of AccessDenied
-                                            } else {
-                                                panic!("Got unexpected return from radiobutton");
-                                            }
-                                        }
-                                        _ => panic!("get_radiobutton failed"),
-                                    }
-                                    xous::yield_slice(); // allow a redraw to happen before repeating the request
-                                }
-                            } else {
-                                finished = true;
-                                log::error!("internal error in basis unlock, aborting!");
-                            }
+                        mgmt.code = basis_unlock_prompt(
+                            &mut pddb_os,
+                            &mut basis_cache,
+                            &modals,
+                            pw_cid,
+                            mgmt.name.as_str().expect("name is not valid utf-8"),
+                            mgmt.policy,
+                        );
+                        if matches!(mgmt.code, PddbRequestCode::NoErr)
+                            && basis_monitor_notifications.len() > 0
+                        {
+                            notify_basis_change(&mut basis_monitor_notifications, basis_cache.basis_list());
                         }
                     }
                     _ => {
@@ -1317,29 +1297,15 @@ fn wrapped_main() -> ! {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut req = buffer.to_original::<PddbDeleteList, _>().unwrap();
-                let mut key_list = Vec::<String>::new();
-                // the [u8] data is structured as a packed list of u8-len + u8 data slice. The max length of
-                // a PDDB key name is guaranteed to be shorter than a u8. If the length field is 0, then this
-                // particular response has no more data in it to read.
-                let mut index = 0;
-                while req.data[index] != 0 && index < MAX_PDDB_DELETE_LEN {
-                    let strlen = req.data[index] as usize;
-                    index += 1;
-                    if strlen + index >= MAX_PDDB_DELETE_LEN {
-                        log::error!("Logic error in key list, index would be out of bounds. Aborting");
+                let key_list = match unpack_key_list(&req.data) {
+                    Ok(key_list) => key_list,
+                    Err(e) => {
+                        log::error!("Bad key list, aborting: {:?}", e);
                         req.retcode = PddbRetcode::InternalError;
-                        break;
+                        buffer.replace(req).ok();
+                        continue;
                     }
-                    let key = String::from(
-                        std::str::from_utf8(&req.data[index..index + strlen]).unwrap_or("UTF8 error"),
-                    );
-                    key_list.push(key);
-                    index += strlen;
-                }
-                if req.retcode == PddbRetcode::InternalError {
-                    buffer.replace(req).ok();
-                    continue;
-                }
+                };
                 log::info!("Deleting key list: {:?}", key_list);
                 let start = tt.elapsed_ms();
                 let bname = if req.basis_specified { Some(req.basis.as_str().unwrap()) } else { None };
@@ -1416,6 +1382,82 @@ fn wrapped_main() -> ! {
                     }
                 }
             }
+            Opcode::RenameKey | Opcode::RenameDict => {
+                let renaming_key = matches!(op, Opcode::RenameKey);
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbRename, _>().unwrap();
+                let bname = if req.basis_specified { Some(req.basis.as_str().unwrap()) } else { None };
+                let dict = req.dict.as_str().expect("dict utf-8 decode error");
+                let key = req.key.as_str().expect("key utf-8 decode error");
+                let new_name = req.new_name.as_str().expect("new name utf-8 decode error");
+                let result = if renaming_key {
+                    basis_cache.key_rename(&mut pddb_os, dict, key, new_name, bname)
+                } else {
+                    basis_cache.dict_rename(&mut pddb_os, dict, new_name, bname)
+                };
+                match result {
+                    Ok(_) => {
+                        // open handles follow the rename, so they keep working
+                        let renamed = |rec_dict: &str, rec_key: &str, rec_basis: &Option<String>| {
+                            rec_dict == dict
+                                && (!renaming_key || rec_key == key)
+                                && (rec_basis.is_none() || rec_basis.as_deref() == bname)
+                        };
+                        for rec in token_dict.values_mut() {
+                            if renamed(&rec.dict, &rec.key, &rec.basis) {
+                                if renaming_key {
+                                    rec.key = new_name.to_string();
+                                } else {
+                                    rec.dict = new_name.to_string();
+                                }
+                            }
+                        }
+                        for fd in fd_mapping.values_mut().flat_map(|fds| fds.iter_mut().flatten()) {
+                            if renamed(&fd.dict, &fd.key, &fd.basis) {
+                                if renaming_key {
+                                    fd.key = new_name.to_string();
+                                } else {
+                                    fd.dict = new_name.to_string();
+                                }
+                            }
+                        }
+                        req.result = PddbRequestCode::NoErr;
+                    }
+                    Err(e) => match e.kind() {
+                        std::io::ErrorKind::NotFound => req.result = PddbRequestCode::NotFound,
+                        std::io::ErrorKind::AlreadyExists => req.result = PddbRequestCode::DuplicateEntry,
+                        _ => req.result = PddbRequestCode::InternalError,
+                    },
+                }
+                buffer.replace(req).unwrap();
+            }
+            Opcode::KeyIntegrityTag => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut req = buffer.to_original::<PddbKeyTag, _>().unwrap();
+                let bname = if req.basis_specified { Some(req.basis.as_str().unwrap()) } else { None };
+                let dict = req.dict.as_str().expect("dict utf-8 decode error");
+                let key = req.key.as_str().expect("key utf-8 decode error");
+                match basis_cache.key_tag(&mut pddb_os, dict, key, bname) {
+                    Ok(tag) => {
+                        if !req.verify {
+                            req.tag = tag;
+                            req.result = PddbRequestCode::NoErr;
+                        } else if tag.ct_eq(&req.tag).into() {
+                            req.result = PddbRequestCode::NoErr;
+                        } else {
+                            log::warn!("integrity tag mismatch on {}:{}", dict, key);
+                            req.result = PddbRequestCode::IntegrityError;
+                        }
+                    }
+                    Err(e) => match e.kind() {
+                        std::io::ErrorKind::NotFound => req.result = PddbRequestCode::NotFound,
+                        _ => req.result = PddbRequestCode::InternalError,
+                    },
+                }
+                buffer.replace(req).unwrap();
+            }
             Opcode::CreateDictStd => {
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
@@ -2128,6 +2170,36 @@ fn wrapped_main() -> ! {
                 }
                 modals.show_notification(&note, None).expect("couldn't show basis list");
             }
+            Opcode::MenuAutoLock => {
+                autolock_menu(&modals, &mut pddb_os, &mut basis_cache);
+            }
+            Opcode::AutoLockTick => xous::msg_scalar_unpack!(msg, _, _, _, _, {
+                // only our own tick thread may advance the timers
+                if msg.sender.pid().map(|p| p.get() as u32) != Some(xous::process::id()) {
+                    log::warn!("ignoring an auto-lock tick from another process");
+                    continue;
+                }
+                let elapsed_secs = autolock_clock.elapsed_secs(tt.elapsed_ms());
+                let locked = basis_cache.expire(&mut pddb_os, elapsed_secs);
+                if locked.len() > 0 && basis_monitor_notifications.len() > 0 {
+                    notify_basis_change(&mut basis_monitor_notifications, basis_cache.basis_list());
+                }
+            }),
+            Opcode::ResumeUnlock => {
+                while let Some(name) = relock_pending.pop() {
+                    if basis_cache.basis_contains(&name) {
+                        // already unlocked again by other means
+                        continue;
+                    }
+                    if matches!(
+                        basis_unlock_prompt(&mut pddb_os, &mut basis_cache, &modals, pw_cid, &name, None),
+                        PddbRequestCode::NoErr
+                    ) && basis_monitor_notifications.len() > 0
+                    {
+                        notify_basis_change(&mut basis_monitor_notifications, basis_cache.basis_list());
+                    }
+                }
+            }
             Opcode::MenuChangePin => {
                 if basis_cache.basis_count() == 0 {
                     modals
@@ -2162,6 +2234,9 @@ fn wrapped_main() -> ! {
                 pddb_os.reset_dont_ask_init();
                 xous::return_scalar(msg.sender, 1).ok();
             }
+            Opcode::FreeSpace => {
+                xous::return_scalar(msg.sender, basis_cache.free_space(&pddb_os)).ok();
+            }
             Opcode::Prune => {
                 log::info!(
                     "PDDB prune manual request: {} heap, {} cache",
@@ -2695,6 +2770,69 @@ fn notify_of_disconnect(
     }
 }
 
+/// Asks for the password of `basis_name` until the basis unlocks or the user gives up, and mounts it.
+/// If no `policy` is requested, the auto-lock setting stored in the basis is applied.
+fn basis_unlock_prompt(
+    pddb_os: &mut PddbOs,
+    basis_cache: &mut BasisCache,
+    modals: &modals::Modals,
+    pw_cid: xous::CID,
+    basis_name: &str,
+    policy: Option<BasisRetentionPolicy>,
+) -> PddbRequestCode {
+    loop {
+        let request =
+            BasisRequestPassword { db_name: xous_ipc::String::from_str(basis_name), plaintext_pw: None };
+        let mut buf = Buffer::into_buf(request).unwrap();
+        buf.lend_mut(pw_cid, PwManagerOpcode::RequestPassword.to_u32().unwrap()).unwrap();
+        let ret = buf.to_original::<BasisRequestPassword, _>().unwrap();
+        if let Some(pw) = ret.plaintext_pw {
+            if let Some(basis) = basis_cache.basis_unlock(
+                pddb_os,
+                basis_name,
+                pw.as_str().expect("password was not valid utf-8"),
+                policy.unwrap_or(BasisRetentionPolicy::Persist),
+            ) {
+                if basis_cache.basis_contains(&basis.name) {
+                    basis_cache
+                        .basis_unmount(pddb_os, &basis.name)
+                        .expect("couldn't unmount previously mounted basis of same name");
+                    modals
+                        .show_notification(t!("pddb.unmount_previous", locales::LANG), None)
+                        .expect("notification failed");
+                }
+                basis_cache.basis_add(basis);
+                if policy.is_none() {
+                    let setting = autolock_get(pddb_os, basis_cache, basis_name);
+                    basis_cache.set_policy(basis_name, setting.policy()).ok();
+                }
+                log::info!("{}PDDB.UNLOCKOK,{},{}", xous::BOOKEND_START, basis_name, xous::BOOKEND_END);
+                return PddbRequestCode::NoErr;
+            } else {
+                log::info!("{}PDDB.BADPASS,{},{}", xous::BOOKEND_START, basis_name, xous::BOOKEND_END);
+                modals.add_list_item(t!("pddb.yes", locales::LANG)).expect("couldn't build radio item list");
+                modals.add_list_item(t!("pddb.no", locales::LANG)).expect("couldn't build radio item list");
+                match modals.get_radiobutton(t!("pddb.badpass", locales::LANG)) {
+                    Ok(response) => {
+                        if response.as_str() == t!("pddb.yes", locales::LANG) {
+                            // this will cause just another go-around
+                        } else if response.as_str() == t!("pddb.no", locales::LANG) {
+                            return PddbRequestCode::AccessDenied;
+                        } else {
+                            panic!("Got unexpected return from radiobutton");
+                        }
+                    }
+                    _ => panic!("get_radiobutton failed"),
+                }
+                xous::yield_slice(); // allow a redraw to happen before repeating the request
+            }
+        } else {
+            log::error!("internal error in basis unlock, aborting!");
+            return PddbRequestCode::InternalError;
+        }
+    }
+}
+
 fn notify_basis_change(
     basis_monitor_notifications: &mut Vec<xous::MessageEnvelope>,
     basis_list: Vec<String>,
@@ -22,6 +22,13 @@ pub(crate) fn pddb_menu(conn: xous::CID) {
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
+    menu_items.push(MenuItem {
+        name: String::from_str(t!("pddb.menu.autolock", locales::LANG)),
+        action_conn: Some(conn),
+        action_opcode: Opcode::MenuAutoLock.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
     menu_items.push(MenuItem {
         name: String::from_str(t!("mainmenu.closemenu", locales::LANG)),
         action_conn: None,
@@ -0,0 +1,256 @@
+//! A standard dialog for choosing a dictionary and a key in the PDDB, so that apps which import or
+//! export keys share one way of getting around the database.
+//!
+//! The picker is a sequence of `modals` lists: first the dictionaries, then the keys of the one chosen.
+//! Each list can be narrowed to the names that start with a prefix, and, if the app allows it, offers
+//! to make a new dictionary or key.
+//!
+//! ```ignore
+//! let picked = pddb::KeyPicker::new(&pddb, &modals)
+//!     .dict_prefix("backup.")
+//!     .allow_create(true)
+//!     .pick()?;
+//! if let Some(picked) = picked {
+//!     let key = pddb.get(&picked.dict, &picked.key, None, true, picked.created, None, None::<fn()>)?;
+//! }
+//! ```
+
+use std::io::Result;
+
+use locales::t;
+use modals::Modals;
+
+use crate::api::{DICT_NAME_LEN, KEY_NAME_LEN};
+use crate::Pddb;
+
+/// What the user chose in a `KeyPicker`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PickedKey {
+    pub dict: String,
+    pub key: String,
+    /// set if the key was named by the user rather than chosen from the list, so it may not exist yet
+    pub created: bool,
+}
+
+/// The choices in a list other than the names themselves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Back,
+    Filter,
+    Create,
+    Cancel,
+}
+
+/// A picker for a dictionary and key, set up with the builder methods and shown with `pick()`
+pub struct KeyPicker<'a> {
+    pddb: &'a Pddb,
+    modals: &'a Modals,
+    basis: Option<String>,
+    /// the dictionary to pick a key in, skipping the list of dictionaries
+    dict: Option<String>,
+    /// only dictionaries whose names start with this are listed
+    dict_prefix: String,
+    allow_create: bool,
+}
+
+impl<'a> KeyPicker<'a> {
+    pub fn new(pddb: &'a Pddb, modals: &'a Modals) -> Self {
+        KeyPicker { pddb, modals, basis: None, dict: None, dict_prefix: String::new(), allow_create: false }
+    }
+
+    /// Only lists what is in `basis`, rather than in all of the open bases
+    pub fn basis(mut self, basis: &str) -> Self {
+        self.basis = Some(basis.to_string());
+        self
+    }
+
+    /// Goes straight to the keys of `dict`, with no way back to the other dictionaries
+    pub fn dict(mut self, dict: &str) -> Self {
+        self.dict = Some(dict.to_string());
+        self
+    }
+
+    /// Only lists the dictionaries whose names start with `prefix`; a new dictionary is given this prefix,
+    /// and can't be made if the prefix leaves no room for a name
+    pub fn dict_prefix(mut self, prefix: &str) -> Self {
+        self.dict_prefix = prefix.to_string();
+        self
+    }
+
+    /// Offers to make a new dictionary or key. A new key isn't made by the picker; it is returned with
+    /// `created` set, for the app to make.
+    pub fn allow_create(mut self, allow: bool) -> Self {
+        self.allow_create = allow;
+        self
+    }
+
+    /// Shows the picker, and returns the key chosen, or `None` if the user cancelled
+    pub fn pick(&self) -> Result<Option<PickedKey>> {
+        loop {
+            let (dict, dict_created) = match &self.dict {
+                Some(dict) => (dict.clone(), false),
+                None => match self.pick_dict()? {
+                    Some(picked) => picked,
+                    None => return Ok(None),
+                },
+            };
+            match self.pick_key(&dict, dict_created)? {
+                Some(picked) => return Ok(Some(picked)),
+                // back to the dictionaries, unless there is only the one
+                None if self.dict.is_none() => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// The dictionary chosen, and whether it was named by the user; `None` on cancel
+    fn pick_dict(&self) -> Result<Option<(String, bool)>> {
+        let mut filter = String::new();
+        loop {
+            let mut dicts: Vec<String> = self
+                .pddb
+                .list_dict(self.basis.as_deref())?
+                .into_iter()
+                .filter(|d| d.starts_with(&self.dict_prefix))
+                .collect();
+            dicts.sort();
+            let prompt = t!("pddb.picker.dicts", locales::LANG);
+            // a prefix that fills the whole name leaves no room for a new dict
+            let max = (DICT_NAME_LEN - 1).saturating_sub(self.dict_prefix.len());
+            match self.choose(prompt, &dicts, &filter, false, self.allow_create && max > 0)? {
+                Ok(name) => return Ok(Some((name, false))),
+                Err(Action::Filter) => filter = self.ask_filter(&filter),
+                Err(Action::Create) => {
+                    if let Some(name) = self.ask_name(t!("pddb.picker.new_dict_prompt", locales::LANG), max) {
+                        let name = format!("{}{}", self.dict_prefix, name);
+                        if !dicts.contains(&name) {
+                            return Ok(Some((name, true)));
+                        }
+                        self.notify_exists(&name);
+                    }
+                }
+                Err(_) => return Ok(None),
+            }
+        }
+    }
+
+    /// The key chosen in `dict`; `None` on cancel or back
+    fn pick_key(&self, dict: &str, dict_created: bool) -> Result<Option<PickedKey>> {
+        let mut filter = String::new();
+        loop {
+            let mut keys =
+                if dict_created { Vec::new() } else { self.pddb.list_keys(dict, self.basis.as_deref())? };
+            keys.sort();
+            let prompt = t!("pddb.picker.keys", locales::LANG).replace("{dict}", dict);
+            match self.choose(&prompt, &keys, &filter, self.dict.is_none(), self.allow_create)? {
+                Ok(key) => return Ok(Some(PickedKey { dict: dict.to_string(), key, created: false })),
+                Err(Action::Filter) => filter = self.ask_filter(&filter),
+                Err(Action::Create) => {
+                    if let Some(key) =
+                        self.ask_name(t!("pddb.picker.new_key_prompt", locales::LANG), KEY_NAME_LEN - 1)
+                    {
+                        if !keys.contains(&key) {
+                            return Ok(Some(PickedKey { dict: dict.to_string(), key, created: true }));
+                        }
+                        self.notify_exists(&key);
+                    }
+                }
+                Err(_) => return Ok(None),
+            }
+        }
+    }
+
+    /// Lists the `names` that start with `filter`, after the actions, and returns the name or the action
+    /// chosen. The create action is offered only if `create` is set.
+    fn choose(
+        &self,
+        prompt: &str,
+        names: &[String],
+        filter: &str,
+        back: bool,
+        create: bool,
+    ) -> Result<std::result::Result<String, Action>> {
+        let mut actions = Vec::new();
+        if back {
+            actions.push((Action::Back, t!("pddb.picker.back", locales::LANG).to_string()));
+        }
+        let filter_label = match filter.is_empty() {
+            true => t!("pddb.picker.filter", locales::LANG).to_string(),
+            false => t!("pddb.picker.filter_set", locales::LANG).replace("{prefix}", filter),
+        };
+        actions.push((Action::Filter, filter_label));
+        if create {
+            let label = match back || self.dict.is_some() {
+                true => t!("pddb.picker.new_key", locales::LANG),
+                false => t!("pddb.picker.new_dict", locales::LANG),
+            };
+            actions.push((Action::Create, label.to_string()));
+        }
+        actions.push((Action::Cancel, t!("pddb.picker.cancel", locales::LANG).to_string()));
+
+        let shown = filtered(names, filter);
+        let mut items: Vec<&str> = actions.iter().map(|(_, label)| label.as_str()).collect();
+        items.extend(shown.iter().map(|name| name.as_str()));
+        self.modals.add_list(items).map_err(|_| std::io::Error::from(std::io::ErrorKind::Other))?;
+        let choice = self
+            .modals
+            .get_radiobutton(prompt)
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::Other))?;
+        // the action labels are marked so that they don't read like a dict or key name
+        match actions.iter().find(|(_, label)| *label == choice) {
+            Some((action, _)) => Ok(Err(*action)),
+            None => Ok(Ok(choice)),
+        }
+    }
+
+    /// Asks for the prefix to narrow the list to; an empty one shows everything
+    fn ask_filter(&self, current: &str) -> String {
+        let placeholder = if current.is_empty() { None } else { Some(current.to_string()) };
+        match self
+            .modals
+            .alert_builder(t!("pddb.picker.filter_prompt", locales::LANG))
+            .field_placeholder_persist(placeholder, None)
+            .build()
+        {
+            Ok(entry) => entry.first().as_str().to_string(),
+            Err(_) => current.to_string(),
+        }
+    }
+
+    /// Asks for the name of a new dictionary or key, of at most `max` bytes; `None` if it isn't usable
+    fn ask_name(&self, prompt: &str, max: usize) -> Option<String> {
+        let entry = self.modals.alert_builder(prompt).field(None, None).build().ok()?;
+        let name = entry.first().as_str().trim().to_string();
+        if name.is_empty() || name.len() > max {
+            let text = t!("pddb.picker.bad_name", locales::LANG).replace("{max}", &max.to_string());
+            self.modals.show_notification(&text, None).ok();
+            return None;
+        }
+        Some(name)
+    }
+
+    fn notify_exists(&self, name: &str) {
+        let text = t!("pddb.picker.exists", locales::LANG).replace("{name}", name);
+        self.modals.show_notification(&text, None).ok();
+    }
+}
+
+/// The `names` that start with `prefix`, ignoring case
+fn filtered<'b>(names: &'b [String], prefix: &str) -> Vec<&'b String> {
+    let prefix = prefix.to_lowercase();
+    names.iter().filter(|name| name.to_lowercase().starts_with(&prefix)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_by_prefix() {
+        let names: Vec<String> =
+            ["backup.2024", "Backup.old", "vault.passwords", "b"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(filtered(&names, "backup"), vec!["backup.2024", "Backup.old"]);
+        assert_eq!(filtered(&names, "").len(), 4);
+        assert!(filtered(&names, "wallet").is_empty());
+    }
+}
@@ -423,6 +423,65 @@ pub(crate) fn delete_pattern(
     Ok(())
 }
 
+/// Renames a key and then its dictionary, checks that the data comes along and that a taken name is
+/// refused, and renames both back so the host-side checks still find them where they expect.
+pub(crate) fn rename_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    const NEW_KEY: &'static str = "renamed key";
+    const NEW_DICT: &'static str = "renamed dict";
+    let dict_list: BTreeSet<String> = basis_cache.dict_list(hw, None).into_iter().collect();
+    let dict = dict_list.iter().next().expect("no dictionaries to rename").to_string();
+    let (key_list, _, _) = basis_cache.key_list(hw, &dict, None)?;
+    let key = key_list.iter().next().expect("no keys to rename").to_string();
+    let len = basis_cache.key_attributes(hw, &dict, &key, None)?.len;
+    let mut before = vec![0u8; len];
+    basis_cache.key_read(hw, &dict, &key, &mut before, None, None)?;
+
+    log::info!("renaming {}:{} to {}", dict, key, NEW_KEY);
+    basis_cache.key_rename(hw, &dict, &key, NEW_KEY, None)?;
+    let (renamed_list, _, _) = basis_cache.key_list(hw, &dict, None)?;
+    assert!(renamed_list.contains(NEW_KEY) && !renamed_list.contains(&key), "key was not renamed");
+    assert_eq!(renamed_list.len(), key_list.len(), "key rename changed the number of keys");
+    let mut after = vec![0u8; len];
+    basis_cache.key_read(hw, &dict, NEW_KEY, &mut after, None, None)?;
+    assert!(before == after, "renamed key has different data");
+    if let Some(other) = key_list.iter().nth(1) {
+        let refused = basis_cache.key_rename(hw, &dict, NEW_KEY, other, None);
+        assert!(refused.is_err(), "key was renamed over an existing key");
+    }
+    basis_cache.key_rename(hw, &dict, NEW_KEY, &key, None)?;
+
+    log::info!("renaming {} to {}", dict, NEW_DICT);
+    basis_cache.dict_rename(hw, &dict, NEW_DICT, None)?;
+    let renamed_dicts = basis_cache.dict_list(hw, None);
+    assert!(renamed_dicts.contains(NEW_DICT) && !renamed_dicts.contains(&dict), "dict was not renamed");
+    let (moved_list, _, _) = basis_cache.key_list(hw, NEW_DICT, None)?;
+    assert!(moved_list == key_list, "renamed dict has different keys");
+    basis_cache.key_read(hw, NEW_DICT, &key, &mut after, None, None)?;
+    assert!(before == after, "key in renamed dict has different data");
+    basis_cache.dict_rename(hw, NEW_DICT, &dict, None)?;
+    Ok(())
+}
+
+/// Checks that integrity tags are stable, and differ between keys and between names of the same key.
+pub(crate) fn integrity_test(hw: &mut PddbOs, basis_cache: &mut BasisCache) -> Result<()> {
+    let dict_list: BTreeSet<String> = basis_cache.dict_list(hw, None).into_iter().collect();
+    let dict = dict_list.iter().next().expect("no dictionaries to tag").to_string();
+    let (key_list, _, _) = basis_cache.key_list(hw, &dict, None)?;
+    let mut keys = key_list.iter();
+    let key = keys.next().expect("no keys to tag").to_string();
+    let tag = basis_cache.key_tag(hw, &dict, &key, None)?;
+    assert!(tag == basis_cache.key_tag(hw, &dict, &key, None)?, "integrity tag is not stable");
+    if let Some(other) = keys.next() {
+        assert!(tag != basis_cache.key_tag(hw, &dict, other, None)?, "two keys have the same tag");
+    }
+    basis_cache.key_rename(hw, &dict, &key, "tagged key", None)?;
+    let moved = basis_cache.key_tag(hw, &dict, "tagged key", None)?;
+    basis_cache.key_rename(hw, &dict, "tagged key", &key, None)?;
+    assert!(tag != moved, "renamed key has the same tag");
+    assert!(tag == basis_cache.key_tag(hw, &dict, &key, None)?, "tag changed on a rename and back");
+    Ok(())
+}
+
 pub(crate) fn list_all(hw: &mut PddbOs, basis_cache: &mut BasisCache) {
     #[cfg(feature = "deterministic")]
     let mut dict_list = BTreeSet::<String>::new();
@@ -556,6 +615,12 @@ pub(crate) fn ci_tests(pddb_os: &mut PddbOs) -> Result<()> {
         pddb_os.dbg_dump(Some("dachecke4".to_string()), None);
         test_prune(pddb_os, &mut basis_cache);
 
+        log::info!("Doing rename test");
+        rename_test(pddb_os, &mut basis_cache)?;
+        pddb_os.dbg_dump(Some("renamee".to_string()), None);
+        log::info!("Doing integrity tag test");
+        integrity_test(pddb_os, &mut basis_cache)?;
+
         let mut pre_list = HashSet::<String>::new();
         for dict in basis_cache.dict_list(pddb_os, None).iter() {
             let (key_list, _, _) = basis_cache.key_list(pddb_os, dict, None).unwrap();
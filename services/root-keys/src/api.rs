@@ -1,7 +1,10 @@
+#[allow(dead_code)] // verification is for the library's users; the server only signs
+mod attestation;
 mod rkyv_enum;
 use core::mem::size_of;
 use core::ops::{Deref, DerefMut};
 
+pub use attestation::*;
 pub use rkyv_enum::*;
 
 pub(crate) const SERVER_NAME_KEYS: &str = "_Root key server and update manager_";
@@ -10,6 +13,11 @@ pub(crate) const SIG_LOADER_VERSION: u32 = 1; // standard ed25519 signature
 #[allow(dead_code)]
 pub(crate) const SIG_KERNEL_VERSION: u32 = 2; // ed25519ph signature
 
+/// The kernel region is sized for two kernels. An OS update stages its kernel in the upper half, which
+/// ends at the backup block; root keys checks it and copies it over the running kernel.
+pub const KERNEL_STAGING_OFFSET: u32 = xous::KERNEL_LEN / 2;
+pub const KERNEL_STAGING_LEN: u32 = xous::KERNEL_BACKUP_OFFSET - KERNEL_STAGING_OFFSET;
+
 #[allow(dead_code)]
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 pub(crate) enum Opcode {
@@ -116,6 +124,24 @@ pub(crate) enum Opcode {
     EfuseRun = 49,
     #[cfg(feature = "efuse")]
     EfusePasswordReturn = 50,
+
+    /// Returns the enclave to the uninitialized state, as part of a factory reset, once the user has typed
+    /// the confirmation word
+    Zeroize = 51,
+
+    /// Sign an attestation of the running firmware, and show it for export by QR code or USB
+    UxAttest = 52,
+    UxAttestPasswordReturn = 53,
+    UxAttestRun = 54,
+
+    /// Import an externally generated signing key, with a policy for its use
+    UxImportKey = 55,
+    UxImportKeyPasswordReturn = 56,
+    UxImportKeyRun = 57,
+
+    /// Install the kernel staged at `KERNEL_STAGING_OFFSET`, once its signature checks out and the user
+    /// has confirmed
+    UxInstallStagedKernel = 58,
 }
 
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive, PartialEq, Eq)]
@@ -146,6 +172,59 @@ pub enum RootkeyResult {
     StateError = 4,
 }
 
+/// Marks the KEYROM policy word of an imported key as in use, as the unused words of a KEYROM are not
+/// guaranteed to be zero.
+const IMPORTED_POLICY_MAGIC: u32 = 0x4b49_0000;
+const IMPORTED_POLICY_UPDATABLE: u32 = 1 << 0;
+const IMPORTED_POLICY_EXPORTABLE: u32 = 1 << 1;
+const IMPORTED_POLICY_ATTEST: u32 = 1 << 2;
+const IMPORTED_POLICY_VERIFY_FIRMWARE: u32 = 1 << 3;
+
+/// What an imported signing key may be used for, fixed when it is imported and enforced by `keys`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ImportedKeyPolicy {
+    /// The key can be replaced by a later import. Otherwise, only a factory reset removes it.
+    pub updatable: bool,
+    /// The key goes into backups. Otherwise it is left out of them, and a restored device won't have it.
+    pub exportable: bool,
+    /// Attestations are signed with this key, instead of the self-signing key
+    pub attest: bool,
+    /// The public key is installed as the third-party key, so the loader and gateware checks accept
+    /// firmware signed with this key
+    pub verify_firmware: bool,
+}
+impl ImportedKeyPolicy {
+    pub fn to_word(&self) -> u32 {
+        let mut word = IMPORTED_POLICY_MAGIC;
+        if self.updatable {
+            word |= IMPORTED_POLICY_UPDATABLE;
+        }
+        if self.exportable {
+            word |= IMPORTED_POLICY_EXPORTABLE;
+        }
+        if self.attest {
+            word |= IMPORTED_POLICY_ATTEST;
+        }
+        if self.verify_firmware {
+            word |= IMPORTED_POLICY_VERIFY_FIRMWARE;
+        }
+        word
+    }
+
+    /// Returns `None` if the word doesn't hold a policy, ie no key has been imported
+    pub fn from_word(word: u32) -> Option<ImportedKeyPolicy> {
+        if word & 0xffff_0000 != IMPORTED_POLICY_MAGIC {
+            return None;
+        }
+        Some(ImportedKeyPolicy {
+            updatable: word & IMPORTED_POLICY_UPDATABLE != 0,
+            exportable: word & IMPORTED_POLICY_EXPORTABLE != 0,
+            attest: word & IMPORTED_POLICY_ATTEST != 0,
+            verify_firmware: word & IMPORTED_POLICY_VERIFY_FIRMWARE != 0,
+        })
+    }
+}
+
 /// AES operation definitions
 use keyboard::KeyMap;
 use zeroize::Zeroize;
@@ -283,7 +362,7 @@ pub enum BackupLanguage {
 }
 impl Default for BackupLanguage {
     fn default() -> Self {
-        match locales::LANG {
+        match locales::LANG.get() {
             "en" => BackupLanguage::En,
             "en-tts" => BackupLanguage::EnTts,
             "ja" => BackupLanguage::Ja,
@@ -0,0 +1,139 @@
+use std::convert::TryInto;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+
+/// Prepended to the signed bytes, so that an attestation can't be passed off as any other signature made
+/// with the self-signing key (ie over a kernel or loader), nor the other way around
+pub const ATTESTATION_CONTEXT: &[u8] = b"xous root-keys attestation";
+pub const ATTESTATION_VERSION: u32 = 1;
+/// Length of an encoded attestation, signature included
+pub const ATTESTATION_LEN: usize = 4 + 32 + 16 + 64 + 4 + 32 + 64;
+
+/// A statement, signed by the device's self-signing key (or an imported key), of the firmware it is
+/// running. A remote party that knows the device's public key can check that it boots the firmware they
+/// expect, and from the boot count, that it hasn't been rebooted (ie into other firmware and back) since
+/// the last attestation.
+///
+/// The encoding is the fields in order, with integers little-endian; the signature is over
+/// `ATTESTATION_CONTEXT` followed by every field but the signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attestation {
+    pub version: u32,
+    /// The key that signed: the self-signing key, which also signs the kernel and the loader, or an
+    /// imported key whose policy allows attestation
+    pub pubkey: [u8; 32],
+    /// The running Xous version, from the ticktimer, serialized as in `BackupHeader`
+    pub xous_ver: [u8; 16],
+    /// The SHA-512 of the root filesystem (the kernel and initial processes), measured by the loader
+    pub fs_hash: [u8; 64],
+    /// Boots of the device so far, this one included
+    pub boot_count: u32,
+    /// Chosen by the party asking, so that an old attestation can't be replayed; all zeros if none was
+    pub nonce: [u8; 32],
+    pub signature: [u8; 64],
+}
+
+impl Attestation {
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(ATTESTATION_CONTEXT.len() + ATTESTATION_LEN - 64);
+        bytes.extend_from_slice(ATTESTATION_CONTEXT);
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(&self.pubkey);
+        bytes.extend_from_slice(&self.xous_ver);
+        bytes.extend_from_slice(&self.fs_hash);
+        bytes.extend_from_slice(&self.boot_count.to_le_bytes());
+        bytes.extend_from_slice(&self.nonce);
+        bytes
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.signed_bytes().split_off(ATTESTATION_CONTEXT.len());
+        bytes.extend_from_slice(&self.signature);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Attestation> {
+        if bytes.len() != ATTESTATION_LEN {
+            return None;
+        }
+        let (version, rest) = bytes.split_at(4);
+        let (pubkey, rest) = rest.split_at(32);
+        let (xous_ver, rest) = rest.split_at(16);
+        let (fs_hash, rest) = rest.split_at(64);
+        let (boot_count, rest) = rest.split_at(4);
+        let (nonce, signature) = rest.split_at(32);
+        Some(Attestation {
+            version: u32::from_le_bytes(version.try_into().unwrap()),
+            pubkey: pubkey.try_into().unwrap(),
+            xous_ver: xous_ver.try_into().unwrap(),
+            fs_hash: fs_hash.try_into().unwrap(),
+            boot_count: u32::from_le_bytes(boot_count.try_into().unwrap()),
+            nonce: nonce.try_into().unwrap(),
+            signature: signature.try_into().unwrap(),
+        })
+    }
+
+    /// The encoding in upper case hex, which QR codes carry in alphanumeric mode
+    pub fn to_hex(&self) -> String { hex::encode_upper(self.to_bytes()) }
+
+    pub fn from_hex(text: &str) -> Option<Attestation> {
+        hex::decode(text.trim()).ok().and_then(|bytes| Attestation::from_bytes(&bytes))
+    }
+
+    /// Checks the signature against `pubkey`. This only shows that the holder of the key made the
+    /// statement: the hash and version still have to be compared with the expected firmware's, and the
+    /// nonce with the one that was asked for.
+    pub fn verify(&self, pubkey: &[u8; 32]) -> bool {
+        if self.version != ATTESTATION_VERSION || &self.pubkey != pubkey {
+            return false;
+        }
+        match VerifyingKey::from_bytes(pubkey) {
+            Ok(vk) => vk.verify_strict(&self.signed_bytes(), &Signature::from_bytes(&self.signature)).is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use super::*;
+
+    fn signed(key: &SigningKey) -> Attestation {
+        let mut attestation = Attestation {
+            version: ATTESTATION_VERSION,
+            pubkey: key.verifying_key().to_bytes(),
+            xous_ver: [1u8; 16],
+            fs_hash: [2u8; 64],
+            boot_count: 42,
+            nonce: [3u8; 32],
+            signature: [0u8; 64],
+        };
+        attestation.signature = key.sign(&attestation.signed_bytes()).to_bytes();
+        attestation
+    }
+
+    #[test]
+    fn round_trip() {
+        let attestation = signed(&SigningKey::from_bytes(&[7u8; 32]));
+        assert_eq!(attestation.to_bytes().len(), ATTESTATION_LEN);
+        assert_eq!(Attestation::from_hex(&attestation.to_hex()), Some(attestation.clone()));
+        assert_eq!(Attestation::from_bytes(&attestation.to_bytes()[1..]), None);
+    }
+
+    #[test]
+    fn verifies_only_the_signed_statement() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let pubkey = key.verifying_key().to_bytes();
+        let attestation = signed(&key);
+        assert!(attestation.verify(&pubkey));
+
+        let mut tampered = attestation.clone();
+        tampered.boot_count += 1;
+        assert!(!tampered.verify(&pubkey));
+
+        let other = SigningKey::from_bytes(&[8u8; 32]).verifying_key().to_bytes();
+        assert!(!attestation.verify(&other));
+    }
+}
@@ -67,6 +67,10 @@ struct PasswordCache {
     hashed_update_pw_valid: u32,
     fpga_key: [u8; 32],
     fpga_key_valid: u32,
+    /// an imported signing key, in the plain, waiting to be patched into the KEYROM
+    import_key: [u8; 32],
+    /// the `ImportedKeyPolicy` of `import_key` as a KEYROM word; zero if no import is pending
+    import_policy: u32,
 }
 
 #[repr(C)]
@@ -89,6 +93,9 @@ impl KeyRomLocs {
     const FPGA_KEY: u8 = 0x00;
     const FPGA_MIN_REV: u8 = 0xfc;
     const GLOBAL_ROLLBACK: u8 = 0xfe;
+    const IMPORTED_POLICY: u8 = 0x40;
+    const IMPORTED_PRIVKEY: u8 = 0x30;
+    const IMPORTED_PUBKEY: u8 = 0x38;
     const LOADER_MIN_REV: u8 = 0xfd;
     const PEPPER: u8 = 0xf8;
     const SELFSIGN_PRIVKEY: u8 = 0x08;
@@ -346,6 +353,12 @@ impl<'a> RootKeys {
 
     pub fn kernel_hash(&self) -> Sha512Prehash {
         let mut ph = Sha512Prehash::new();
+        ph.set_prehash(self.fs_hash());
+        ph
+    }
+
+    /// The hash of the root filesystem, as computed by the loader and passed on in the environment
+    fn fs_hash(&self) -> [u8; 64] {
         if let Some(hash_str) =
             std::env::vars().find(|(key, _value)| key == "ROOT_FILESYSTEM_HASH").map(|(_key, value)| value)
         {
@@ -354,8 +367,7 @@ impl<'a> RootKeys {
                 hex::decode(hash_str).expect("Couldn't decode loader hash from environment variables!");
             for &b in hash.iter() {
                 if b != 0 {
-                    ph.set_prehash(hash[..64].try_into().unwrap());
-                    return ph;
+                    return hash[..64].try_into().unwrap();
                 }
             }
         } else {
@@ -724,6 +736,11 @@ impl<'a> RootKeys {
                         *p = 0;
                     }
                     (*pcache_ptr).fpga_key_valid = 0;
+
+                    for p in (*pcache_ptr).import_key.iter_mut() {
+                        *p = 0;
+                    }
+                    (*pcache_ptr).import_policy = 0;
                 }
             }
         }
@@ -1615,6 +1632,9 @@ impl<'a> RootKeys {
 
             // stage the keyrom data for patching
             self.populate_sensitive_data();
+            if update_type == UpdateType::KeyImport {
+                self.stage_key_import()?;
+            }
             if update_type == UpdateType::BbramProvision || update_type == UpdateType::EfuseProvision {
                 if self.is_initialized() {
                     // make a backup copy of the old key, so we can use it to decrypt the gateware before
@@ -2120,6 +2140,183 @@ impl<'a> RootKeys {
         ret
     }
 
+    /// Decrypts a signing keypair held in the KEYROM with the cached update password. The self-signing key
+    /// is stored as the root of its anti-rollback chain, so `rollback` derives the current key from it.
+    fn update_keypair(
+        &mut self,
+        privkey: u8,
+        pubkey: u8,
+        rollback: bool,
+    ) -> Result<SigningKey, RootkeyResult> {
+        let pcache: &mut PasswordCache =
+            unsafe { &mut *(self.pass_cache.as_mut_ptr() as *mut PasswordCache) };
+        if pcache.hashed_update_pw_valid == 0 {
+            self.purge_password(PasswordType::Update);
+            log::error!("no password was set going into the signing routine");
+            return Err(RootkeyResult::KeyError);
+        }
+        let mut keypair_bytes: [u8; ed25519_dalek::KEYPAIR_LENGTH] = [0; ed25519_dalek::KEYPAIR_LENGTH];
+        let enc_signing_key = self.read_key_256(privkey);
+        for (key, (&enc_key, &pw)) in keypair_bytes[..ed25519_dalek::SECRET_KEY_LENGTH]
+            .iter_mut()
+            .zip(enc_signing_key.iter().zip(pcache.hashed_update_pw.iter()))
+        {
+            *key = enc_key ^ pw;
+        }
+        if rollback {
+            self.compute_key_rollback(&mut keypair_bytes[..ed25519_dalek::SECRET_KEY_LENGTH]);
+        }
+        let public_key = self.read_key_256(pubkey);
+        keypair_bytes[ed25519_dalek::SECRET_KEY_LENGTH..].copy_from_slice(&public_key);
+        // Keypair zeroizes the secret key on drop. A wrong password gives a secret key that doesn't
+        // match the public key, which this catches.
+        let keypair = SigningKey::from_keypair_bytes(&keypair_bytes);
+        for b in keypair_bytes.iter_mut() {
+            *b = 0;
+        }
+        match keypair {
+            Ok(keypair) => Ok(keypair),
+            Err(e) => {
+                log::warn!("update password was not correct ({:?})", e);
+                self.purge_password(PasswordType::Update);
+                Err(RootkeyResult::KeyError)
+            }
+        }
+    }
+
+    /// Signs an attestation of the running firmware, with the imported key if its policy allows, and
+    /// otherwise with the self-signing key. The update password has to be in the cache.
+    pub fn attest(&mut self, nonce: [u8; 32], boot_count: u32) -> Result<Attestation, RootkeyResult> {
+        self.xous_init_interlock();
+
+        let keypair = match self.imported_key_policy() {
+            Some(policy) if policy.attest => {
+                self.update_keypair(KeyRomLocs::IMPORTED_PRIVKEY, KeyRomLocs::IMPORTED_PUBKEY, false)?
+            }
+            _ => self.update_keypair(KeyRomLocs::SELFSIGN_PRIVKEY, KeyRomLocs::SELFSIGN_PUBKEY, true)?,
+        };
+        let mut attestation = Attestation {
+            version: ATTESTATION_VERSION,
+            pubkey: keypair.verifying_key().to_bytes(),
+            xous_ver: self.ticktimer.get_version_semver().into(),
+            fs_hash: self.fs_hash(),
+            boot_count,
+            nonce,
+            signature: [0u8; 64],
+        };
+        attestation.signature = keypair.sign(&attestation.signed_bytes()).to_bytes();
+        log::info!("attestation signed for boot {}", boot_count);
+
+        if self.update_password_policy == PasswordRetentionPolicy::AlwaysPurge {
+            self.purge_password(PasswordType::Update);
+        }
+        Ok(attestation)
+    }
+
+    /// Returns the policy of the imported signing key, or `None` if no key has been imported
+    pub fn imported_key_policy(&mut self) -> Option<ImportedKeyPolicy> {
+        self.keyrom.wfo(utra::keyrom::ADDRESS_ADDRESS, KeyRomLocs::IMPORTED_POLICY as u32);
+        ImportedKeyPolicy::from_word(self.keyrom.rf(utra::keyrom::DATA_DATA))
+    }
+
+    /// Checks an externally generated signing key and its policy against the key it would replace, and
+    /// holds it in the password cache for the `KeyImport` gateware update, which patches it into the
+    /// KEYROM. Returns the public key, for the user to compare with the one they expect.
+    pub fn prepare_key_import(
+        &mut self,
+        secret: &[u8; 32],
+        policy: ImportedKeyPolicy,
+    ) -> Result<[u8; 32], RootkeyResult> {
+        self.xous_init_interlock();
+        if !self.is_initialized() {
+            return Err(RootkeyResult::StateError);
+        }
+        let current = self.imported_key_policy();
+        if current.map(|p| !p.updatable).unwrap_or(false) {
+            log::warn!("the imported key can't be replaced, by its policy");
+            return Err(RootkeyResult::StateError);
+        }
+        if policy.verify_firmware {
+            // the third-party key slot can be taken over only if it's empty, or if it was filled by the
+            // key being replaced
+            let third_party = self.read_key_256(KeyRomLocs::THIRDPARTY_PUBKEY);
+            let ours = current.map(|p| p.verify_firmware).unwrap_or(false)
+                && third_party == self.read_key_256(KeyRomLocs::IMPORTED_PUBKEY);
+            if third_party.iter().any(|&b| b != 0) && !ours {
+                log::warn!("a third-party key is already installed, not replacing it");
+                return Err(RootkeyResult::StateError);
+            }
+        }
+        let public_key = SigningKey::from_bytes(secret).verifying_key().to_bytes();
+
+        let pcache: &mut PasswordCache =
+            unsafe { &mut *(self.pass_cache.as_mut_ptr() as *mut PasswordCache) };
+        pcache.import_key.copy_from_slice(secret);
+        pcache.import_policy = policy.to_word();
+        Ok(public_key)
+    }
+
+    /// Writes the pending key import into the staged KEYROM. The secret key is encrypted with the update
+    /// password, as the self-signing key is.
+    fn stage_key_import(&mut self) -> Result<(), RootkeyResult> {
+        let pcache: &mut PasswordCache =
+            unsafe { &mut *(self.pass_cache.as_mut_ptr() as *mut PasswordCache) };
+        let policy = match ImportedKeyPolicy::from_word(pcache.import_policy) {
+            Some(policy) => policy,
+            None => {
+                log::error!("no key import is pending");
+                return Err(RootkeyResult::StateError);
+            }
+        };
+        if pcache.hashed_update_pw_valid == 0 {
+            log::error!("no password was set going into the key import");
+            return Err(RootkeyResult::KeyError);
+        }
+        let public_key = SigningKey::from_bytes(&pcache.import_key).verifying_key().to_bytes();
+        // a third-party key that was installed for the key being replaced goes with it
+        let release_third_party = !policy.verify_firmware
+            && self.imported_key_policy().map(|p| p.verify_firmware).unwrap_or(false)
+            && self.read_key_256(KeyRomLocs::THIRDPARTY_PUBKEY)
+                == self.read_key_256(KeyRomLocs::IMPORTED_PUBKEY);
+
+        let mut staged = self.sensitive_data.borrow_mut();
+        let keyrom = unsafe { staged.as_slice_mut::<u32>() };
+        for (dst, (key, pw)) in keyrom
+            [KeyRomLocs::IMPORTED_PRIVKEY as usize..KeyRomLocs::IMPORTED_PRIVKEY as usize + 8]
+            .iter_mut()
+            .zip(pcache.import_key.chunks(4).zip(pcache.hashed_update_pw.chunks(4)))
+        {
+            *dst = u32::from_be_bytes(key.try_into().unwrap()) ^ u32::from_be_bytes(pw.try_into().unwrap());
+        }
+        for (dst, src) in keyrom
+            [KeyRomLocs::IMPORTED_PUBKEY as usize..KeyRomLocs::IMPORTED_PUBKEY as usize + 8]
+            .iter_mut()
+            .zip(public_key.chunks(4))
+        {
+            *dst = u32::from_be_bytes(src.try_into().unwrap());
+        }
+        keyrom[KeyRomLocs::IMPORTED_POLICY as usize] = policy.to_word();
+        for (dst, src) in keyrom
+            [KeyRomLocs::THIRDPARTY_PUBKEY as usize..KeyRomLocs::THIRDPARTY_PUBKEY as usize + 8]
+            .iter_mut()
+            .zip(public_key.chunks(4))
+        {
+            if policy.verify_firmware {
+                *dst = u32::from_be_bytes(src.try_into().unwrap());
+            } else if release_third_party {
+                *dst = 0;
+            }
+        }
+        drop(staged);
+
+        for b in pcache.import_key.iter_mut() {
+            *b = 0;
+        }
+        pcache.import_policy = 0;
+        log::info!("imported key staged: {:x?}, {:?}", public_key, policy);
+        Ok(())
+    }
+
     pub fn test(&mut self, rootkeys_modal: &mut Modal, main_cid: xous::CID) -> Result<(), RootkeyResult> {
         let mut progress_action =
             Slider::new(main_cid, Opcode::UxGutter.to_u32().unwrap(), 0, 100, 10, Some("%"), 0, true, true);
@@ -3000,6 +3197,173 @@ impl<'a> RootKeys {
         sigtype
     }
 
+    /// Maps the kernel that an update staged at `KERNEL_STAGING_OFFSET`. The updater unmaps it before
+    /// handing it over to us.
+    fn map_staged_kernel(&self) -> Result<xous::MemoryRange, RootkeyResult> {
+        xous::syscall::map_memory(
+            Some(
+                NonZeroUsize::new(
+                    (xous::KERNEL_LOC + xous::FLASH_PHYS_BASE + KERNEL_STAGING_OFFSET) as usize,
+                )
+                .unwrap(),
+            ),
+            None,
+            KERNEL_STAGING_LEN as usize,
+            xous::MemoryFlags::R,
+        )
+        .map_err(|e| {
+            log::error!("couldn't map the staged kernel; is the updater still holding it? {:?}", e);
+            RootkeyResult::StateError
+        })
+    }
+
+    /// Checks a staged kernel the same way the loader checks the boot kernel, so that an update that
+    /// wouldn't boot is never installed. Returns the key that signed it, and the length of the image
+    /// including its signature block.
+    fn check_kernel_image(&mut self, staged: &[u8]) -> (SignatureResult, usize) {
+        let sig_rec: &SignatureInFlash =
+            unsafe { (staged.as_ptr() as *const SignatureInFlash).as_ref().unwrap() };
+        let signed_len = sig_rec.signed_len as usize;
+        if signed_len < 8 || signed_len > staged.len() - SIGBLOCK_SIZE as usize {
+            log::error!("staged kernel has an invalid length: {}", signed_len);
+            return (SignatureResult::MalformedSignature, 0);
+        }
+        let image = &staged[SIGBLOCK_SIZE as usize..SIGBLOCK_SIZE as usize + signed_len];
+        let protected_version = u32::from_le_bytes(image[signed_len - 8..signed_len - 4].try_into().unwrap());
+        let protected_len = u32::from_le_bytes(image[signed_len - 4..].try_into().unwrap());
+        if sig_rec.version != SIG_KERNEL_VERSION
+            || protected_version != SIG_KERNEL_VERSION
+            || protected_len as usize != signed_len - 4
+        {
+            log::error!(
+                "staged kernel signature record doesn't match its image: version {}/{}, len {}/{}",
+                sig_rec.version,
+                protected_version,
+                signed_len - 4,
+                protected_len
+            );
+            return (SignatureResult::MalformedSignature, 0);
+        }
+        let sig = Signature::from_bytes(&sig_rec.signature);
+        let mut hasher = Sha512Hw::new();
+        hasher.update(image);
+        let mut prehash = Sha512Prehash::new();
+        prehash.set_prehash(hasher.finalize().as_slice().try_into().unwrap());
+
+        // same order as the loader: the most trusted key that has been set wins
+        for &keyloc in
+            [KeyRomLocs::SELFSIGN_PUBKEY, KeyRomLocs::THIRDPARTY_PUBKEY, KeyRomLocs::DEVELOPER_PUBKEY].iter()
+        {
+            let pubkey_bytes = self.read_key_256(keyloc);
+            if pubkey_bytes.iter().all(|&b| b == 0) {
+                continue;
+            }
+            let pubkey = match VerifyingKey::from_bytes(&pubkey_bytes) {
+                Ok(pubkey) => pubkey,
+                Err(_) => return (SignatureResult::InvalidPubKey, 0),
+            };
+            if pubkey.verify_prehashed(prehash.clone(), None, &sig).is_ok() {
+                let sigtype = match keyloc {
+                    KeyRomLocs::SELFSIGN_PUBKEY => SignatureResult::SelfSignOk,
+                    KeyRomLocs::THIRDPARTY_PUBKEY => SignatureResult::ThirdPartyOk,
+                    _ => SignatureResult::DevKeyOk,
+                };
+                return (sigtype, SIGBLOCK_SIZE as usize + signed_len);
+            }
+        }
+        (SignatureResult::Invalid, 0)
+    }
+
+    /// Checks the signature of the kernel an update has staged, without installing it
+    pub fn check_staged_kernel(&mut self) -> SignatureResult {
+        let staged_mr = match self.map_staged_kernel() {
+            Ok(mr) => mr,
+            Err(_) => return SignatureResult::Invalid,
+        };
+        let (result, _len) = self.check_kernel_image(unsafe { staged_mr.as_slice::<u8>() });
+        xous::syscall::unmap_memory(staged_mr).expect("couldn't unmap the staged kernel");
+        result
+    }
+
+    /// Copies the staged kernel over the boot kernel. The signature is checked again right before the copy,
+    /// rather than relying on the check made before the user was asked; the loader checks it once more on
+    /// the next boot.
+    ///
+    /// As with a gateware update, a power loss during the copy leaves the device needing a USB recovery,
+    /// so suspend is held off while it runs.
+    pub fn install_staged_kernel(
+        &mut self,
+        rootkeys_modal: &mut Modal,
+        main_cid: xous::CID,
+    ) -> Result<(), RootkeyResult> {
+        let staged_mr = self.map_staged_kernel()?;
+        let staged = unsafe { staged_mr.as_slice::<u8>() };
+        let ret = match self.check_kernel_image(staged) {
+            (SignatureResult::SelfSignOk, len)
+            | (SignatureResult::ThirdPartyOk, len)
+            | (SignatureResult::DevKeyOk, len) => {
+                let mut progress_action = Slider::new(
+                    main_cid,
+                    Opcode::UxGutter.to_u32().unwrap(),
+                    0,
+                    100,
+                    10,
+                    Some("%"),
+                    0,
+                    true,
+                    true,
+                );
+                progress_action.set_is_password(true);
+                rootkeys_modal.modify(
+                    Some(ActionType::Slider(progress_action)),
+                    Some(t!("rootkeys.install_kernel.installing", locales::LANG)),
+                    false,
+                    None,
+                    true,
+                    None,
+                );
+                rootkeys_modal.activate();
+                xous::yield_slice(); // give some time to the GAM to render
+                let mut pb = ProgressBar::new(rootkeys_modal, &mut progress_action);
+                pb.set_percentage(1);
+                pb.rebase_subtask_work(0, len as u32);
+                self.susres.set_suspendable(false).expect("couldn't block suspend");
+                let mut ret = Ok(());
+                for (i, sector) in staged[..len].chunks(SIGBLOCK_SIZE as usize).enumerate() {
+                    if let Err(e) = self.overwrite_kernel_sector(i as u32 * SIGBLOCK_SIZE, sector) {
+                        log::error!("couldn't write kernel sector {}: {:?}", i, e);
+                        ret = Err(RootkeyResult::FlashError);
+                        break;
+                    }
+                    pb.increment_work(sector.len() as u32);
+                }
+                self.susres.set_suspendable(true).expect("couldn't allow suspend");
+                pb.set_percentage(100);
+                ret
+            }
+            _ => {
+                log::error!("staged kernel failed its signature check");
+                Err(RootkeyResult::IntegrityError)
+            }
+        };
+        xous::syscall::unmap_memory(staged_mr).expect("couldn't unmap the staged kernel");
+        ret
+    }
+
+    /// Erases and rewrites the boot kernel sector at `offset` with `data`, padded with 0xFF. Most of the
+    /// boot kernel isn't mapped, so `patch()` can't be shown its current contents; instead it gets a
+    /// stand-in that differs from the data in every byte and is never 0xFF, which forces a full erase and
+    /// write of the sector.
+    fn overwrite_kernel_sector(&self, offset: u32, data: &[u8]) -> Result<(), spinor::SpinorError> {
+        let mut sector = [0xFFu8; SIGBLOCK_SIZE as usize];
+        sector[..data.len()].copy_from_slice(data);
+        let mut stand_in = [0u8; SIGBLOCK_SIZE as usize];
+        for (s, &d) in stand_in.iter_mut().zip(sector.iter()) {
+            *s = if d == 0 { 1 } else { 0 };
+        }
+        self.spinor.patch(&stand_in, self.kernel_base + offset, &sector, 0)
+    }
+
     pub fn fetch_gw_metadata(&self, region_enum: GatewareRegion) -> MetadataInFlash {
         let region = match region_enum {
             GatewareRegion::Boot => self.gateware(),
@@ -3291,6 +3655,16 @@ impl<'a> RootKeys {
                     self.keyrom.wfo(utra::keyrom::ADDRESS_ADDRESS, i);
                     backup_rom.0[i as usize] = self.keyrom.rf(utra::keyrom::DATA_DATA);
                 }
+                // an imported key that may not leave the device is left out, policy and all
+                if self.imported_key_policy().map(|p| !p.exportable).unwrap_or(false) {
+                    for w in backup_rom.0
+                        [KeyRomLocs::IMPORTED_PRIVKEY as usize..KeyRomLocs::IMPORTED_PUBKEY as usize + 8]
+                        .iter_mut()
+                    {
+                        *w = 0;
+                    }
+                    backup_rom.0[KeyRomLocs::IMPORTED_POLICY as usize] = 0;
+                }
                 // we're done with the password now, clear all the temps
                 self.purge_password(PasswordType::Update);
                 for b in keypair_bytes.iter_mut() {
@@ -3389,4 +3763,26 @@ impl<'a> RootKeys {
             .patch(self.kernel_backup(), self.kernel_backup_base(), &blank, 0)
             .expect("couldn't erase backup region");
     }
+
+    /// Discards the root keys by copying the staged gateware verbatim over the boot gateware, and erases
+    /// the backup block and all cached secrets. The new gateware takes effect on the next cold boot.
+    ///
+    /// Key initialization leaves a backup of the factory (null-key) gateware in the staging area, but
+    /// BBRAM/eFuse provisioning leaves a keyed one there; so, the staging area is checked to decrypt
+    /// with the null key before anything is touched, and `KeyError` is returned if it does not.
+    pub fn zeroize(&mut self) -> Result<(), RootkeyResult> {
+        let dummy_key: [u8; 32] = [0; 32];
+        let mut probe = BitstreamOracle::new(&dummy_key, &dummy_key, self.staging(), self.staging_base())?;
+        probe.clear();
+        self.make_gateware_backup(None, true)?;
+        if self.gateware() != self.staging() {
+            log::error!("boot gateware does not match the staged gateware after zeroize");
+            return Err(RootkeyResult::IntegrityError);
+        }
+        self.erase_backup();
+        self.purge_password(PasswordType::Boot);
+        self.purge_password(PasswordType::Update);
+        self.purge_sensitive_data();
+        Ok(())
+    }
 }
@@ -51,6 +51,10 @@ impl RootKeys {
 
     pub fn get_try_selfsign_op(&self) -> u32 { Opcode::UxSelfSignXous.to_u32().unwrap() }
 
+    pub fn get_attest_op(&self) -> u32 { Opcode::UxAttest.to_u32().unwrap() }
+
+    pub fn get_import_key_op(&self) -> u32 { Opcode::UxImportKey.to_u32().unwrap() }
+
     /// this initiates an attempt to update passwords. User must unlock their device first, and can cancel out
     /// if not expected.
     pub fn try_update_password(&mut self, _which: PasswordType) -> Result<(), xous::Error> {
@@ -162,6 +166,44 @@ impl RootKeys {
         .expect("couldn't send message to root keys");
     }
 
+    /// Discards the root keys, returning the device to the uninitialized state after the next cold
+    /// boot. The user has to type a confirmation word first; returns `false` if they didn't.
+    pub fn zeroize(&self) -> Result<bool, RootkeyResult> {
+        let response = send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::Zeroize.to_usize().unwrap(), 0, 0, 0, 0),
+        )
+        .expect("couldn't send message to root keys");
+        match response {
+            xous::Result::Scalar2(1, zeroized) => Ok(zeroized != 0),
+            xous::Result::Scalar2(_, 0) => Err(RootkeyResult::AlignmentError),
+            xous::Result::Scalar2(_, 1) => Err(RootkeyResult::KeyError),
+            xous::Result::Scalar2(_, 2) => Err(RootkeyResult::IntegrityError),
+            xous::Result::Scalar2(_, 3) => Err(RootkeyResult::FlashError),
+            _ => Err(RootkeyResult::StateError),
+        }
+    }
+
+    /// Asks the user to install the kernel an update staged at `KERNEL_STAGING_OFFSET`, and copies it over
+    /// the running kernel if they agree. It must be signed with a key the loader accepts, and it takes
+    /// effect on the next boot. The caller has to unmap the staging area first. Returns `false` if the
+    /// user declined.
+    pub fn install_staged_kernel(&self) -> Result<bool, RootkeyResult> {
+        let response = send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::UxInstallStagedKernel.to_usize().unwrap(), 0, 0, 0, 0),
+        )
+        .expect("couldn't send message to root keys");
+        match response {
+            xous::Result::Scalar2(1, installed) => Ok(installed != 0),
+            xous::Result::Scalar2(_, 0) => Err(RootkeyResult::AlignmentError),
+            xous::Result::Scalar2(_, 1) => Err(RootkeyResult::KeyError),
+            xous::Result::Scalar2(_, 2) => Err(RootkeyResult::IntegrityError),
+            xous::Result::Scalar2(_, 3) => Err(RootkeyResult::FlashError),
+            _ => Err(RootkeyResult::StateError),
+        }
+    }
+
     pub fn do_reset_dont_ask_init(&self) {
         send_message(
             self.conn,
@@ -29,6 +29,9 @@ mod implementation;
 use implementation::*;
 /// used by the bbram helper/console protocol to indicate the start of a console message
 const CONSOLE_SENTINEL: &'static str = "CONS_SENTINEL|";
+/// The word the user has to type before the root keys are zeroized. It is not translated; the prompt
+/// spells it out.
+const ZEROIZE_CONFIRM_WORD: &'static str = "DESTROY";
 
 #[cfg(any(feature = "precursor", feature = "renode"))]
 mod bcrypt;
@@ -55,6 +58,8 @@ pub(crate) enum UpdateType {
     Restore,
     #[allow(dead_code)]
     EfuseProvision,
+    /// Re-provisions the staged gateware with an imported signing key added to the KEYROM
+    KeyImport,
 }
 
 /// An "easily" parseable metadata structure in flash. There's nothing that guarantees the authenticity
@@ -245,6 +250,21 @@ mod implementation {
             self.fake_progress(rootkeys_modal, main_cid, t!("rootkeys.init.signing_kernel", locales::LANG))
         }
 
+        pub fn attest(&mut self, _nonce: [u8; 32], _boot_count: u32) -> Result<Attestation, RootkeyResult> {
+            // there's no self-signing key to attest with in hosted mode
+            Err(RootkeyResult::StateError)
+        }
+
+        pub fn imported_key_policy(&mut self) -> Option<ImportedKeyPolicy> { None }
+
+        pub fn prepare_key_import(
+            &mut self,
+            _secret: &[u8; 32],
+            _policy: ImportedKeyPolicy,
+        ) -> Result<[u8; 32], RootkeyResult> {
+            Err(RootkeyResult::StateError)
+        }
+
         pub fn purge_password(&mut self, _ptype: PasswordType) {}
 
         pub fn purge_user_password(&mut self, _ptype: AesRootkeyType) {}
@@ -276,6 +296,17 @@ mod implementation {
             SignatureResult::DevKeyOk
         }
 
+        pub fn check_staged_kernel(&mut self) -> SignatureResult { SignatureResult::DevKeyOk }
+
+        pub fn install_staged_kernel(
+            &mut self,
+            _rootkeys_modal: &mut Modal,
+            _main_cid: xous::CID,
+        ) -> Result<(), RootkeyResult> {
+            log::info!("hosted mode: not installing the staged kernel");
+            Ok(())
+        }
+
         pub fn is_pcache_update_password_valid(&self) -> bool { false }
 
         pub fn is_pcache_boot_password_valid(&self) -> bool {
@@ -554,6 +585,8 @@ mod implementation {
 
         pub fn erase_backup(&mut self) {}
 
+        pub fn zeroize(&mut self) -> Result<(), RootkeyResult> { Ok(()) }
+
         pub fn read_backup_header(&mut self) -> Option<BackupHeader> { None }
 
         pub fn get_backup_key(&mut self) -> Option<(backups::BackupKey, backups::KeyRomExport)> { None }
@@ -575,6 +608,7 @@ fn main() -> ! {
     use crate::implementation::RootKeys;
 
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
 
@@ -586,8 +620,9 @@ fn main() -> ! {
           1. Shellchat for test initiation
           2. Main menu -> trigger initialization
           3. PDDB
+          4. Updater, to install a staged kernel
     */
-    let keys_sid = xns.register_name(api::SERVER_NAME_KEYS, Some(3)).expect("can't register server");
+    let keys_sid = xns.register_name(api::SERVER_NAME_KEYS, Some(4)).expect("can't register server");
 
     let mut keys = RootKeys::new();
     log::info!("Boot FPGA key source: {:?}", keys.fpga_key_source());
@@ -1459,6 +1494,250 @@ fn main() -> ! {
                     }
                 }
             }
+            Some(Opcode::UxAttest) => {
+                if keys.is_pcache_update_password_valid() {
+                    send_message(
+                        main_cid,
+                        xous::Message::new_scalar(Opcode::UxAttestRun.to_usize().unwrap(), 0, 0, 0, 0),
+                    )
+                    .expect("couldn't send action message");
+                } else {
+                    keys.set_ux_password_type(Some(PasswordType::Update));
+                    password_action.set_action_opcode(Opcode::UxAttestPasswordReturn.to_u32().unwrap());
+                    rootkeys_modal.modify(
+                        Some(ActionType::TextEntry(password_action.clone())),
+                        Some(t!("rootkeys.get_signing_password", locales::LANG)),
+                        false,
+                        None,
+                        true,
+                        None,
+                    );
+                    #[cfg(feature = "tts")]
+                    tts.tts_blocking(t!("rootkeys.get_signing_password", locales::LANG)).unwrap();
+                    rootkeys_modal.activate();
+                }
+            }
+            Some(Opcode::UxAttestPasswordReturn) => {
+                let mut buf = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let plaintext_pw = buf.to_original::<gam::modal::TextEntryPayloads, _>().unwrap();
+
+                keys.hash_and_save_password(plaintext_pw.first().as_str(), false);
+                plaintext_pw.first().volatile_clear(); // ensure the data is destroyed after sending to the keys enclave
+                buf.volatile_clear();
+
+                send_message(
+                    main_cid,
+                    xous::Message::new_scalar(Opcode::UxAttestRun.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .expect("couldn't send action message");
+            }
+            Some(Opcode::UxAttestRun) => {
+                keys.set_ux_password_type(None);
+                let nonce = match modals
+                    .alert_builder(t!("rootkeys.attest.nonce", locales::LANG))
+                    .field(None, Some(nonce_validator))
+                    .build()
+                {
+                    Ok(entry) => parse_nonce(entry.first().as_str()).unwrap_or([0u8; 32]),
+                    Err(_) => continue,
+                };
+                let boot_count = match counters::Counters::new(&xns).and_then(|c| c.boot_count()) {
+                    Ok(Ok(count)) => count,
+                    e => {
+                        log::warn!("couldn't read the boot count: {:?}", e);
+                        0
+                    }
+                };
+                match keys.attest(nonce, boot_count) {
+                    Ok(attestation) => {
+                        let text = attestation.to_hex();
+                        let version = ticktimer.get_version();
+                        modals
+                            .show_notification(
+                                &t!("rootkeys.attest.show", locales::LANG)
+                                    .replace("{version}", version.lines().next().unwrap_or(""))
+                                    .replace("{boots}", &boot_count.to_string()),
+                                Some(&text),
+                            )
+                            .expect("modals error");
+                        modals.add_list_item(t!("rootkeys.gwup.yes", locales::LANG)).expect("modals error");
+                        modals.add_list_item(t!("rootkeys.gwup.no", locales::LANG)).expect("modals error");
+                        match modals.get_radiobutton(t!("rootkeys.attest.usb", locales::LANG)) {
+                            Ok(response) if response == t!("rootkeys.gwup.yes", locales::LANG) => {
+                                let usbd = usb_device_xous::UsbHid::new();
+                                if let Err(e) = usbd.send_str(&format!("{}\n", text)) {
+                                    log::warn!("couldn't type the attestation: {:?}", e);
+                                    modals
+                                        .show_notification(
+                                            t!("rootkeys.attest.usb_fail", locales::LANG),
+                                            None,
+                                        )
+                                        .expect("modals error");
+                                }
+                            }
+                            _ => (),
+                        }
+                    }
+                    Err(RootkeyResult::KeyError) => {
+                        // probably a bad password, purge it, so the user can try again
+                        keys.purge_password(PasswordType::Update);
+                        modals
+                            .show_notification(t!("rootkeys.init.fail_key", locales::LANG), None)
+                            .expect("modals error");
+                    }
+                    Err(_) => {
+                        modals
+                            .show_notification(t!("rootkeys.wrong_state", locales::LANG), None)
+                            .expect("modals error");
+                    }
+                }
+            }
+            Some(Opcode::UxImportKey) => {
+                if !keys.is_initialized() {
+                    modals
+                        .show_notification(t!("rootkeys.wrong_state", locales::LANG), None)
+                        .expect("modals error");
+                    continue;
+                }
+                if keys.imported_key_policy().map(|p| !p.updatable).unwrap_or(false) {
+                    modals
+                        .show_notification(t!("rootkeys.import.locked", locales::LANG), None)
+                        .expect("modals error");
+                    continue;
+                }
+                if keys.is_pcache_update_password_valid() {
+                    send_message(
+                        main_cid,
+                        xous::Message::new_scalar(Opcode::UxImportKeyRun.to_usize().unwrap(), 0, 0, 0, 0),
+                    )
+                    .expect("couldn't send action message");
+                } else {
+                    keys.set_ux_password_type(Some(PasswordType::Update));
+                    password_action.set_action_opcode(Opcode::UxImportKeyPasswordReturn.to_u32().unwrap());
+                    rootkeys_modal.modify(
+                        Some(ActionType::TextEntry(password_action.clone())),
+                        Some(t!("rootkeys.get_signing_password", locales::LANG)),
+                        false,
+                        None,
+                        true,
+                        None,
+                    );
+                    #[cfg(feature = "tts")]
+                    tts.tts_blocking(t!("rootkeys.get_signing_password", locales::LANG)).unwrap();
+                    rootkeys_modal.activate();
+                }
+            }
+            Some(Opcode::UxImportKeyPasswordReturn) => {
+                let mut buf = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let plaintext_pw = buf.to_original::<gam::modal::TextEntryPayloads, _>().unwrap();
+
+                keys.hash_and_save_password(plaintext_pw.first().as_str(), false);
+                plaintext_pw.first().volatile_clear(); // ensure the data is destroyed after sending to the keys enclave
+                buf.volatile_clear();
+
+                send_message(
+                    main_cid,
+                    xous::Message::new_scalar(Opcode::UxImportKeyRun.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .expect("couldn't send action message");
+            }
+            Some(Opcode::UxImportKeyRun) => {
+                keys.set_ux_password_type(None);
+                let mut secret = match modals
+                    .alert_builder(t!("rootkeys.import.key", locales::LANG))
+                    .field(None, Some(import_key_validator))
+                    .build()
+                {
+                    Ok(entry) => match parse_import_key(entry.first().as_str()) {
+                        Some(secret) => secret,
+                        None => continue,
+                    },
+                    Err(_) => continue,
+                };
+                let options = [
+                    t!("rootkeys.import.updatable", locales::LANG),
+                    t!("rootkeys.import.exportable", locales::LANG),
+                    t!("rootkeys.import.attest", locales::LANG),
+                    t!("rootkeys.import.verify_firmware", locales::LANG),
+                ];
+                for option in options.iter() {
+                    modals.add_list_item(option).expect("modals error");
+                }
+                let chosen =
+                    modals.get_checkbox(t!("rootkeys.import.policy", locales::LANG)).unwrap_or_default();
+                let policy = ImportedKeyPolicy {
+                    updatable: chosen.iter().any(|c| c == options[0]),
+                    exportable: chosen.iter().any(|c| c == options[1]),
+                    attest: chosen.iter().any(|c| c == options[2]),
+                    verify_firmware: chosen.iter().any(|c| c == options[3]),
+                };
+                let prepared = keys.prepare_key_import(&secret, policy);
+                for b in secret.iter_mut() {
+                    *b = 0;
+                }
+                let public_key = match prepared {
+                    Ok(public_key) => public_key,
+                    Err(_) => {
+                        modals
+                            .show_notification(t!("rootkeys.import.refused", locales::LANG), None)
+                            .expect("modals error");
+                        continue;
+                    }
+                };
+                // the user checks the public key against the one recorded at the ceremony
+                modals.add_list_item(t!("rootkeys.gwup.yes", locales::LANG)).expect("modals error");
+                modals.add_list_item(t!("rootkeys.gwup.no", locales::LANG)).expect("modals error");
+                let confirm = t!("rootkeys.import.confirm", locales::LANG)
+                    .replace("{pubkey}", &hex::encode(public_key));
+                match modals.get_radiobutton(&confirm) {
+                    Ok(response) if response == t!("rootkeys.gwup.yes", locales::LANG) => (),
+                    _ => {
+                        // drops the pending import along with the password
+                        keys.purge_password(PasswordType::Update);
+                        continue;
+                    }
+                }
+
+                let result =
+                    keys.do_gateware_update(&mut rootkeys_modal, &modals, main_cid, UpdateType::KeyImport);
+                // the stop emoji, when sent to the slider action bar in progress mode, will cause it to close
+                // and relinquish focus
+                rootkeys_modal.key_event(['🛑', '\u{0000}', '\u{0000}', '\u{0000}']);
+
+                match result {
+                    Ok(_) => {
+                        // the KEYROM is part of the gateware, so the key is in use after a reboot
+                        send_message(
+                            main_cid,
+                            xous::Message::new_scalar(Opcode::UxTryReboot.to_usize().unwrap(), 0, 0, 0, 0),
+                        )
+                        .expect("couldn't initiate dialog box");
+                    }
+                    Err(RootkeyResult::KeyError) => {
+                        // probably a bad password, purge it, so the user can try again
+                        keys.purge_password(PasswordType::Update);
+                        modals
+                            .show_notification(t!("rootkeys.init.fail_key", locales::LANG), None)
+                            .expect("modals error");
+                    }
+                    Err(RootkeyResult::IntegrityError) => {
+                        modals
+                            .show_notification(t!("rootkeys.init.fail_verify", locales::LANG), None)
+                            .expect("modals error");
+                    }
+                    Err(RootkeyResult::FlashError) => {
+                        modals
+                            .show_notification(t!("rootkeys.init.fail_burn", locales::LANG), None)
+                            .expect("modals error");
+                    }
+                    Err(_) => {
+                        keys.purge_password(PasswordType::Update);
+                        modals
+                            .show_notification(t!("rootkeys.wrong_state", locales::LANG), None)
+                            .expect("modals error");
+                    }
+                }
+            }
             Some(Opcode::UxAesEnsurePassword) => msg_blocking_scalar_unpack!(msg, key_index, _, _, _, {
                 if key_index as u8 == AesRootkeyType::User0.to_u8().unwrap() {
                     if keys.is_pcache_boot_password_valid() {
@@ -1753,6 +2032,63 @@ fn main() -> ! {
                     xous::return_scalar(msg.sender, 0).unwrap();
                 }
             }),
+            Some(Opcode::UxInstallStagedKernel) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                let prompt = match keys.check_staged_kernel() {
+                    SignatureResult::SelfSignOk | SignatureResult::ThirdPartyOk => {
+                        t!("rootkeys.install_kernel.confirm", locales::LANG).to_string()
+                    }
+                    SignatureResult::DevKeyOk => format!(
+                        "{}\n\n{}",
+                        t!("rootkeys.install_kernel.devkey", locales::LANG),
+                        t!("rootkeys.install_kernel.confirm", locales::LANG)
+                    ),
+                    _ => {
+                        modals
+                            .show_notification(
+                                t!("rootkeys.install_kernel.bad_signature", locales::LANG),
+                                None,
+                            )
+                            .expect("modals error");
+                        xous::return_scalar2(msg.sender, 0, RootkeyResult::IntegrityError as usize).ok();
+                        continue;
+                    }
+                };
+                modals.add_list_item(t!("rootkeys.gwup.yes", locales::LANG)).expect("modals error");
+                modals.add_list_item(t!("rootkeys.gwup.no", locales::LANG)).expect("modals error");
+                match modals.get_radiobutton(&prompt) {
+                    Ok(response) if response == t!("rootkeys.gwup.yes", locales::LANG) => {}
+                    Ok(_) => {
+                        xous::return_scalar2(msg.sender, 1, 0).ok();
+                        continue;
+                    }
+                    _ => {
+                        log::error!("modals error, aborting");
+                        xous::return_scalar2(msg.sender, 0, RootkeyResult::StateError as usize).ok();
+                        continue;
+                    }
+                }
+
+                let result = keys.install_staged_kernel(&mut rootkeys_modal, main_cid);
+                // the stop emoji, when sent to the slider action bar in progress mode, will cause it to close
+                // and relinquish focus
+                rootkeys_modal.key_event(['🛑', '\u{0000}', '\u{0000}', '\u{0000}']);
+                match result {
+                    Ok(()) => {
+                        xous::return_scalar2(msg.sender, 1, 1).ok();
+                    }
+                    Err(e) => {
+                        let note = match e {
+                            RootkeyResult::IntegrityError => {
+                                t!("rootkeys.install_kernel.bad_signature", locales::LANG)
+                            }
+                            RootkeyResult::FlashError => t!("rootkeys.init.fail_burn", locales::LANG),
+                            _ => t!("rootkeys.wrong_state", locales::LANG),
+                        };
+                        modals.show_notification(note, None).expect("modals error");
+                        xous::return_scalar2(msg.sender, 0, e as usize).ok();
+                    }
+                }
+            }),
             Some(Opcode::ShouldPromptForUpdate) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                 if keys.should_prompt_for_update() {
                     xous::return_scalar(msg.sender, 1).unwrap();
@@ -2204,6 +2540,24 @@ fn main() -> ! {
                 keys.erase_backup();
                 xous::return_scalar(msg.sender, 1).ok();
             }
+            Some(Opcode::Zeroize) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                // any process can ask for this, so the confirmation is taken here rather than trusted to
+                // the caller
+                let typed = modals
+                    .alert_builder(
+                        &t!("rootkeys.zeroize.confirm", locales::LANG).replace("{word}", ZEROIZE_CONFIRM_WORD),
+                    )
+                    .field(None, None)
+                    .build();
+                if !typed.map(|p| p.first().as_str() == ZEROIZE_CONFIRM_WORD).unwrap_or(false) {
+                    xous::return_scalar2(msg.sender, 1, 0).ok();
+                    continue;
+                }
+                match keys.zeroize() {
+                    Ok(()) => xous::return_scalar2(msg.sender, 1, 1).ok(),
+                    Err(e) => xous::return_scalar2(msg.sender, 0, e as usize).ok(),
+                };
+            }),
             Some(Opcode::IsZeroKey) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                 let query = keys.is_zero_key();
                 if let Some(q) = query {
@@ -2390,3 +2744,45 @@ fn main() -> ! {
     log::trace!("quitting");
     xous::terminate_process(0)
 }
+
+/// The nonce is up to 32 bytes of hex; shorter ones are padded with zeros, and none at all is all zeros
+fn parse_nonce(text: &str) -> Option<[u8; 32]> {
+    let bytes = hex::decode(text.trim()).ok()?;
+    if bytes.len() > 32 {
+        return None;
+    }
+    let mut nonce = [0u8; 32];
+    nonce[..bytes.len()].copy_from_slice(&bytes);
+    Some(nonce)
+}
+
+fn nonce_validator(input: TextEntryPayload) -> Option<xous_ipc::String<256>> {
+    match parse_nonce(input.as_str()) {
+        Some(_) => None,
+        None => Some(xous_ipc::String::<256>::from_str(t!("rootkeys.attest.bad_nonce", locales::LANG))),
+    }
+}
+
+/// An imported key is the 32-byte ed25519 secret key, in hex
+fn parse_import_key(text: &str) -> Option<[u8; 32]> {
+    let mut bytes = hex::decode(text.trim()).ok()?;
+    let mut secret = [0u8; 32];
+    let parsed = if bytes.len() == secret.len() {
+        secret.copy_from_slice(&bytes);
+        Some(secret)
+    } else {
+        None
+    };
+    bytes.iter_mut().for_each(|b| *b = 0);
+    parsed
+}
+
+fn import_key_validator(input: TextEntryPayload) -> Option<xous_ipc::String<256>> {
+    match parse_import_key(input.as_str()) {
+        Some(mut secret) => {
+            secret.iter_mut().for_each(|b| *b = 0);
+            None
+        }
+        None => Some(xous_ipc::String::<256>::from_str(t!("rootkeys.import.bad_key", locales::LANG))),
+    }
+}
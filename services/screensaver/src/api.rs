@@ -0,0 +1,15 @@
+pub(crate) const SERVER_NAME_SCREENSAVER: &str = "_Screensaver_";
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// The GAM asks for the screen to be drawn again
+    Redraw = 0,
+    /// A key was hit while the screensaver had the focus. Scalar, the keys as in `gam::UxRegistration`.
+    Keys = 1,
+    /// The screensaver gained or lost the focus. Scalar, arg1 = `gam::FocusState`.
+    FocusChange = 2,
+    /// Internal: time to check the idle time, and to bring the clock up to date
+    Tick = 3,
+    /// Exits the server
+    Quit = 4,
+}
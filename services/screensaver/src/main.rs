@@ -0,0 +1,239 @@
+mod api;
+use core::fmt::Write;
+
+use api::*;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use graphics_server::api::GlyphStyle;
+use graphics_server::{DrawStyle, Gid, PixelColor, Point, Rectangle, TextBounds, TextView};
+use locales::t;
+use num_traits::*;
+use ticktimer_server::api::Activity;
+
+/// Minutes without input before the screensaver comes up, until the preferences say otherwise
+const DEFAULT_TIMEOUT_MINS: u64 = 5;
+/// How often the idle time is checked, and the clock brought up to date
+const TICK_MS: usize = 5_000;
+/// The preferences are read again every this many ticks, to pick up changes
+const PREFS_TICKS: u32 = 12;
+/// The clock moves down by this many pixels each minute, and back up after `DRIFT_STEPS` minutes. The
+/// memory LCD holds an image without refreshing it, and a pixel held in one state for hours can leave
+/// a faint trace.
+const DRIFT_PX: i16 = 6;
+const DRIFT_STEPS: u64 = 8;
+
+struct Screensaver {
+    gam: gam::Gam,
+    token: [u32; 4],
+    content: Gid,
+    screensize: Point,
+    localtime: llio::LocalTime,
+    llio: llio::Llio,
+    com: com::Com,
+    /// whether the screensaver has the focus
+    up: bool,
+    /// the minute the screen was last drawn for, if it is up to date
+    drawn: Option<u64>,
+}
+
+impl Screensaver {
+    fn new(xns: &xous_names::XousNames, sid: xous::SID) -> Self {
+        let gam = gam::Gam::new(xns).expect("can't connect to GAM");
+        let token = gam
+            .register_ux(gam::UxRegistration {
+                app_name: xous_ipc::String::<128>::from_str(gam::SCREENSAVER_NAME),
+                ux_type: gam::UxType::Framebuffer,
+                predictor: None,
+                listener: sid.to_array(),
+                redraw_id: Opcode::Redraw.to_u32().unwrap(),
+                gotinput_id: None,
+                audioframe_id: None,
+                rawkeys_id: Some(Opcode::Keys.to_u32().unwrap()),
+                focuschange_id: Some(Opcode::FocusChange.to_u32().unwrap()),
+            })
+            .expect("couldn't register GAM UX")
+            .expect("the GAM has no screensaver context; was it built without the `screensaver` feature?");
+        let content = gam.request_content_canvas(token).expect("couldn't get content canvas");
+        let screensize = gam.get_canvas_bounds(content).expect("couldn't get canvas dimensions");
+        Screensaver {
+            gam,
+            token,
+            content,
+            screensize,
+            localtime: llio::LocalTime::new(),
+            llio: llio::Llio::new(xns),
+            com: com::Com::new(xns).expect("can't connect to COM"),
+            up: false,
+            drawn: None,
+        }
+    }
+
+    /// Draws the clock, the date and the battery level, on a blank screen
+    fn draw(&mut self) {
+        let now = self.localtime.get_local_time_ms();
+        let minute = now.map(|ms| ms / 60_000);
+        let drift = (minute.unwrap_or(0) % DRIFT_STEPS) as i16 * DRIFT_PX;
+
+        self.gam
+            .draw_rectangle(
+                self.content,
+                Rectangle::new_with_style(
+                    Point::new(0, 0),
+                    self.screensize,
+                    DrawStyle { fill_color: Some(PixelColor::Light), stroke_color: None, stroke_width: 0 },
+                ),
+            )
+            .expect("can't clear content area");
+
+        let top = self.screensize.y / 4 + drift;
+        let mut clock = TextView::new(
+            self.content,
+            TextBounds::CenteredTop(Rectangle::new(
+                Point::new(0, top),
+                Point::new(self.screensize.x, self.screensize.y),
+            )),
+        );
+        clock.draw_border = false;
+        clock.clear_area = false;
+        let mut date = TextView::new(
+            self.content,
+            TextBounds::CenteredTop(Rectangle::new(
+                Point::new(0, top + 72),
+                Point::new(self.screensize.x, self.screensize.y),
+            )),
+        );
+        date.draw_border = false;
+        date.clear_area = false;
+        date.style = GlyphStyle::Large;
+        if let Some(ms) = now {
+            // as in the status bar, the local time is dressed up as UTC
+            let dt = DateTime::<Utc>::from_naive_utc_and_offset(
+                NaiveDateTime::from_timestamp_opt(ms as i64 / 1000, 0).unwrap(),
+                Utc,
+            );
+            clock.style = GlyphStyle::Giant;
+            write!(clock.text, "{}", dt.format("%H:%M")).ok();
+            write!(date.text, "{}", dt.format("%Y-%m-%d")).ok();
+        } else {
+            clock.style = GlyphStyle::Large;
+            write!(clock.text, "{}", t!("screensaver.time_not_set", locales::LANG)).ok();
+        }
+        self.gam.post_textview(&mut clock).expect("couldn't render the clock");
+        self.gam.post_textview(&mut date).expect("couldn't render the date");
+
+        let mut battery = TextView::new(
+            self.content,
+            TextBounds::CenteredBot(Rectangle::new(
+                Point::new(0, 0),
+                Point::new(self.screensize.x, self.screensize.y - 8),
+            )),
+        );
+        battery.draw_border = false;
+        battery.clear_area = false;
+        match self.com.get_batt_stats_blocking() {
+            Ok(stats) => write!(battery.text, "{}%", stats.soc).ok(),
+            Err(e) => {
+                log::warn!("couldn't read the battery: {:?}", e);
+                write!(battery.text, "--%").ok()
+            }
+        };
+        if self.llio.is_plugged_in() {
+            write!(battery.text, " {}", t!("screensaver.charging", locales::LANG)).ok();
+        }
+        self.gam.post_textview(&mut battery).expect("couldn't render the battery level");
+
+        self.gam.redraw().expect("couldn't redraw screen");
+        self.drawn = minute;
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let sid = xns.register_name(api::SERVER_NAME_SCREENSAVER, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", sid);
+    let mut screensaver = Screensaver::new(&xns, sid);
+
+    let ticktimer = ticktimer_server::Ticktimer::new().unwrap();
+    let pddb = pddb::PddbMountPoller::new();
+    let prefs = userprefs::Manager::new();
+    let mut timeout_mins = DEFAULT_TIMEOUT_MINS;
+    let mut ticks = 0u32;
+
+    let self_cid = xous::connect(sid).unwrap();
+    std::thread::spawn(move || {
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        loop {
+            tt.sleep_ms(TICK_MS).unwrap();
+            xous::send_message(
+                self_cid,
+                xous::Message::new_scalar(Opcode::Tick.to_usize().unwrap(), 0, 0, 0, 0),
+            )
+            .ok();
+        }
+    });
+
+    loop {
+        let msg = xous::receive_message(sid).unwrap();
+        log::debug!("message: {:?}", msg);
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Redraw) => {
+                if screensaver.up {
+                    screensaver.draw();
+                }
+            }
+            Some(Opcode::Keys) => {
+                // any key wakes the app that was covered; the GAM doesn't pass the key on to it
+                if screensaver.up {
+                    screensaver
+                        .gam
+                        .dismiss_screensaver(screensaver.token)
+                        .unwrap_or_else(|e| log::warn!("couldn't dismiss the screensaver: {:?}", e));
+                }
+            }
+            Some(Opcode::FocusChange) => xous::msg_scalar_unpack!(msg, state, _, _, _, {
+                screensaver.up = match gam::FocusState::convert_focus_change(state) {
+                    gam::FocusState::Foreground => true,
+                    gam::FocusState::Background => false,
+                };
+                // the GAM asks for a redraw once the focus has moved
+                screensaver.drawn = None;
+            }),
+            Some(Opcode::Tick) => {
+                if ticks % PREFS_TICKS == 0 && pddb.is_mounted_nonblocking() {
+                    timeout_mins = prefs
+                        .screensaver_timeout_or_value(DEFAULT_TIMEOUT_MINS)
+                        .unwrap_or(DEFAULT_TIMEOUT_MINS);
+                }
+                ticks = ticks.wrapping_add(1);
+                let idle_mins = ticktimer.idle_ms(Activity::UserInput) / 60_000;
+                if screensaver.up {
+                    // the memory LCD keeps its image, so the screen is only touched when the minute changes
+                    let minute = screensaver.localtime.get_local_time_ms().map(|ms| ms / 60_000);
+                    if minute != screensaver.drawn {
+                        screensaver.draw();
+                    }
+                } else if timeout_mins != 0 && idle_mins >= timeout_mins {
+                    // the GAM decides whether the focused context may be covered; it refuses menus, modals
+                    // and grabbed keyboards, and then this is simply tried again on the next tick
+                    match screensaver.gam.raise_screensaver(screensaver.token) {
+                        Ok(true) => log::debug!("screensaver up"),
+                        Ok(false) => log::trace!("the GAM kept the screensaver down"),
+                        Err(e) => log::warn!("couldn't raise the screensaver: {:?}", e),
+                    }
+                }
+            }
+            Some(Opcode::Quit) => {
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+    xns.unregister_server(sid).unwrap();
+    xous::destroy_server(sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
@@ -45,6 +45,8 @@ pub struct CommonEnv {
     com: com::Com,
     ticktimer: ticktimer_server::Ticktimer,
     gam: gam::Gam,
+    /// shellchat's app token on the GAM, for the few commands that change our own context
+    gam_token: [u32; 4],
     cb_registrations: HashMap<u32, String<256>>,
     trng: Trng,
     netmgr: net::NetManager,
@@ -91,6 +93,8 @@ mod echo;
 use echo::*;
 mod sleep;
 use sleep::*;
+mod run;
+use run::*;
 mod sensors;
 use sensors::*;
 // mod callback; use callback::*;
@@ -102,6 +106,14 @@ mod ssid;
 use ssid::*;
 mod ver;
 use ver::*;
+mod top;
+use top::*;
+mod alive;
+use alive::*;
+mod prof;
+use prof::*;
+mod log_cmd;
+use log_cmd::*;
 //mod audio;    use audio::*; // this command is currently contra-indicated with PDDB, as the test audio
 // currently overlaps the PDDB space. We'll fix this eventually, but for now, let's switch to PDDB mode.
 mod backlight;
@@ -127,19 +139,60 @@ mod net_cmd;
 use net_cmd::*;
 mod pddb_cmd;
 use pddb_cmd::*;
+mod hexdump;
+use hexdump::*;
+mod crashlog_cmd;
+use crashlog_cmd::*;
 mod usb;
 use usb::*;
+mod ime;
+use ime::*;
+mod dict;
+use dict::*;
+mod clip;
+use clip::*;
+mod scan;
+use scan::*;
 
 #[cfg(not(feature = "no-codec"))]
 mod test;
 #[cfg(not(feature = "no-codec"))]
 use test::*;
+#[cfg(not(feature = "no-codec"))]
+mod beep;
+#[cfg(not(feature = "no-codec"))]
+use beep::*;
 
 #[cfg(feature = "tts")]
 mod tts;
 #[cfg(feature = "tts")]
 use tts::*;
 
+#[cfg(feature = "updater")]
+mod update;
+#[cfg(feature = "updater")]
+use update::*;
+#[cfg(feature = "tor")]
+mod tor_cmd;
+#[cfg(feature = "tor")]
+use tor_cmd::*;
+#[cfg(feature = "bench")]
+mod bench_cmd;
+#[cfg(feature = "bench")]
+use bench_cmd::*;
+#[cfg(feature = "events")]
+mod events_cmd;
+#[cfg(feature = "events")]
+use events_cmd::*;
+#[cfg(feature = "contacts")]
+mod contacts_cmd;
+#[cfg(feature = "contacts")]
+use contacts_cmd::*;
+#[cfg(feature = "httpd")]
+mod httpd_cmd;
+#[cfg(feature = "httpd")]
+use httpd_cmd::*;
+
 #[cfg(feature = "benchmarks")]
 mod engine;
 #[cfg(feature = "benchmarks")]
@@ -158,6 +211,8 @@ use aes_cmd::*;
 pub struct CmdEnv {
     common_env: CommonEnv,
     lastverb: String<256>,
+    /// set if the verb of the most recently dispatched command line did not match any command
+    unknown_verb: bool,
     ///// 2. declare storage for your command here.
     sleep_cmd: Sleep,
     sensors_cmd: Sensors,
@@ -174,15 +229,35 @@ pub struct CmdEnv {
     jtag_cmd: JtagCmd,
     net_cmd: NetCmd,
     pddb_cmd: PddbCmd,
+    hexdump_cmd: HexDump,
+    crashlog_cmd: CrashLog,
     wlan_cmd: Wlan,
     usb_cmd: Usb,
+    dict_cmd: Dict,
+    clip_cmd: Clip,
+    scan_cmd: Scan,
 
     #[cfg(not(feature = "no-codec"))]
     test_cmd: Test,
+    #[cfg(not(feature = "no-codec"))]
+    beep_cmd: Beep,
 
     #[cfg(feature = "tts")]
     tts_cmd: Tts,
 
+    #[cfg(feature = "updater")]
+    update_cmd: Update,
+    #[cfg(feature = "tor")]
+    tor_cmd: TorCmd,
+    #[cfg(feature = "bench")]
+    bench_cmd: BenchCmd,
+    #[cfg(feature = "events")]
+    events_cmd: EventsCmd,
+    #[cfg(feature = "contacts")]
+    contacts_cmd: ContactsCmd,
+    #[cfg(feature = "httpd")]
+    httpd_cmd: HttpdCmd,
+
     #[cfg(feature = "hashtest")]
     sha_cmd: Sha,
     #[cfg(feature = "aestests")]
@@ -192,7 +267,7 @@ pub struct CmdEnv {
     //fcc_cmd: Fcc,
 }
 impl CmdEnv {
-    pub fn new(xns: &xous_names::XousNames) -> CmdEnv {
+    pub fn new(xns: &xous_names::XousNames, gam_token: [u32; 4]) -> CmdEnv {
         let ticktimer = ticktimer_server::Ticktimer::new().expect("Couldn't connect to Ticktimer");
         #[cfg(feature = "shellperf")]
         let perf_csr = xous::syscall::map_memory(
@@ -218,6 +293,7 @@ impl CmdEnv {
             com: com::Com::new(&xns).expect("could't connect to COM"),
             ticktimer,
             gam: gam::Gam::new(&xns).expect("couldn't connect to GAM"),
+            gam_token,
             cb_registrations: HashMap::new(),
             trng: Trng::new(&xns).unwrap(),
             xns: xous_names::XousNames::new().unwrap(),
@@ -257,6 +333,7 @@ impl CmdEnv {
         CmdEnv {
             common_env: _common,
             lastverb: String::<256>::new(),
+            unknown_verb: false,
             ///// 3. initialize your storage, by calling new()
             sleep_cmd: {
                 log::debug!("sleep");
@@ -303,6 +380,11 @@ impl CmdEnv {
                 log::debug!("pddb");
                 PddbCmd::new(&xns)
             },
+            hexdump_cmd: HexDump::new(),
+            crashlog_cmd: {
+                log::debug!("crashlog");
+                CrashLog::new()
+            },
             wlan_cmd: {
                 log::debug!("wlan");
                 Wlan::new()
@@ -311,15 +393,32 @@ impl CmdEnv {
                 log::debug!("usb");
                 Usb::new()
             },
+            dict_cmd: Dict::new(&xns),
+            clip_cmd: Clip::new(),
+            scan_cmd: Scan::new(),
 
             #[cfg(not(feature = "no-codec"))]
             test_cmd: {
                 log::debug!("test");
                 Test::new(&xns)
             },
+            #[cfg(not(feature = "no-codec"))]
+            beep_cmd: Beep::new(&xns),
 
             #[cfg(feature = "tts")]
             tts_cmd: Tts::new(&xns),
+            #[cfg(feature = "updater")]
+            update_cmd: Update::new(),
+            #[cfg(feature = "tor")]
+            tor_cmd: TorCmd::new(),
+            #[cfg(feature = "bench")]
+            bench_cmd: BenchCmd::new(),
+            #[cfg(feature = "events")]
+            events_cmd: EventsCmd::new(),
+            #[cfg(feature = "contacts")]
+            contacts_cmd: ContactsCmd::new(),
+            #[cfg(feature = "httpd")]
+            httpd_cmd: HttpdCmd::new(&xns),
 
             #[cfg(feature = "hashtest")]
             sha_cmd: sha,
@@ -331,6 +430,9 @@ impl CmdEnv {
         }
     }
 
+    /// Returns `true` if the most recently dispatched command line invoked a verb that does not exist.
+    pub fn last_verb_unknown(&self) -> bool { self.unknown_verb }
+
     pub fn dispatch(
         &mut self,
         maybe_cmdline: Option<&mut String<1024>>,
@@ -343,9 +445,16 @@ impl CmdEnv {
         let mut backlight_cmd = Backlight {};
         let mut accel_cmd = Accel {};
         let mut console_cmd = Console {};
+        let mut top_cmd = Top {};
+        let mut alive_cmd = Alive {};
+        let mut prof_cmd = Prof {};
+        let mut log_cmd = LogCmd {};
+        let mut ime_cmd = Ime {};
+        let mut run_cmd = Run {};
         let commands: &mut [&mut dyn ShellCmdApi] = &mut [
             ///// 4. add your command to this array, so that it can be looked up and dispatched
             &mut echo_cmd,
+            &mut run_cmd,
             &mut self.sleep_cmd,
             &mut self.sensors_cmd,
             //&mut self.callback_cmd,
@@ -360,17 +469,41 @@ impl CmdEnv {
             &mut self.ecup_cmd,
             &mut self.trng_cmd,
             &mut console_cmd,
+            &mut top_cmd,
+            &mut alive_cmd,
+            &mut prof_cmd,
+            &mut log_cmd,
             // &mut self.memtest_cmd,
             &mut self.keys_cmd,
             &mut self.wlan_cmd,
             &mut self.jtag_cmd,
             &mut self.net_cmd,
             &mut self.pddb_cmd,
+            &mut self.hexdump_cmd,
+            &mut self.crashlog_cmd,
             &mut self.usb_cmd,
+            &mut ime_cmd,
+            &mut self.dict_cmd,
+            &mut self.clip_cmd,
+            &mut self.scan_cmd,
             #[cfg(not(feature = "no-codec"))]
             &mut self.test_cmd,
+            #[cfg(not(feature = "no-codec"))]
+            &mut self.beep_cmd,
             #[cfg(feature = "tts")]
             &mut self.tts_cmd,
+            #[cfg(feature = "updater")]
+            &mut self.update_cmd,
+            #[cfg(feature = "tor")]
+            &mut self.tor_cmd,
+            #[cfg(feature = "bench")]
+            &mut self.bench_cmd,
+            #[cfg(feature = "events")]
+            &mut self.events_cmd,
+            #[cfg(feature = "contacts")]
+            &mut self.contacts_cmd,
+            #[cfg(feature = "httpd")]
+            &mut self.httpd_cmd,
             #[cfg(feature = "hashtest")]
             &mut self.sha_cmd,
             #[cfg(feature = "aestests")]
@@ -381,41 +514,58 @@ impl CmdEnv {
         ];
 
         if let Some(cmdline) = maybe_cmdline {
-            let maybe_verb = tokenize(cmdline);
-
-            let mut cmd_ret: Result<Option<String<1024>>, xous::Error> = Ok(None);
-            if let Some(verb_string) = maybe_verb {
-                let verb = verb_string.to_str();
-
-                // search through the list of commands linearly until one matches,
-                // then run it.
-                let mut match_found = false;
-                for cmd in commands.iter_mut() {
-                    if cmd.matches(verb) {
-                        match_found = true;
-                        cmd_ret = cmd.process(*cmdline, &mut self.common_env);
-                        self.lastverb.clear();
-                        write!(self.lastverb, "{}", verb).expect("SHCH: couldn't record last verb");
-                    };
+            let line = std::string::String::from(cmdline.as_str().unwrap_or(""));
+            let (stages, redirect) = match parse_pipeline(&line) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    write!(ret, "{}", e).ok();
+                    return Ok(Some(ret));
                 }
+            };
 
-                // if none match, create a list of available commands
-                if !match_found {
-                    let mut first = true;
-                    write!(ret, "Commands: ").unwrap();
-                    for cmd in commands.iter() {
-                        if !first {
-                            ret.append(", ")?;
+            // run each stage of the pipeline in turn, appending the output of the previous stage to the
+            // arguments of the next one. Only synchronous output is piped: anything a command reports
+            // later via a callback goes straight to the console.
+            let mut output: Option<String<1024>> = None;
+            self.unknown_verb = false;
+            for stage in stages.iter() {
+                let mut stage_line = String::<1024>::from_str(stage);
+                if let Some(piped) = output.take() {
+                    if stage_line.len() > 0 {
+                        stage_line.push(' ').ok();
+                    }
+                    for ch in piped.to_str().chars() {
+                        if stage_line.push(ch).is_err() {
+                            log::warn!("piped output truncated");
+                            break;
                         }
-                        ret.append(cmd.verb())?;
-                        first = false;
                     }
+                }
+                let mut match_found = true;
+                output = run_command(
+                    commands,
+                    &mut self.common_env,
+                    &mut self.lastverb,
+                    &mut stage_line,
+                    &mut match_found,
+                )?;
+                self.unknown_verb |= !match_found;
+            }
+
+            if let Some((dict, key)) = redirect {
+                if let Some(text) = output {
+                    ret.clear();
+                    match redirect_to_pddb(&dict, &key, text.to_str()) {
+                        Ok(len) => write!(ret, "Wrote {} bytes to {}:{}", len, dict, key).ok(),
+                        Err(e) => write!(ret, "Error writing {}:{}: {:?}", dict, key, e).ok(),
+                    };
                     Ok(Some(ret))
                 } else {
-                    cmd_ret
+                    write!(ret, "No output to redirect to {}:{}", dict, key).ok();
+                    Ok(Some(ret))
                 }
             } else {
-                Ok(None)
+                Ok(output)
             }
         } else if let Some(callback) = maybe_callback {
             let mut cmd_ret: Result<Option<String<1024>>, xous::Error> = Ok(None);
@@ -440,6 +590,135 @@ impl CmdEnv {
     }
 }
 
+/// Looks up the verb of a single command line and runs it. If no command matches, returns a list of
+/// the available commands.
+fn run_command(
+    commands: &mut [&mut dyn ShellCmdApi],
+    env: &mut CommonEnv,
+    lastverb: &mut String<256>,
+    cmdline: &mut String<1024>,
+    match_found: &mut bool,
+) -> Result<Option<String<1024>>, xous::Error> {
+    let mut ret = String::<1024>::new();
+    let maybe_verb = tokenize(cmdline);
+
+    let mut cmd_ret: Result<Option<String<1024>>, xous::Error> = Ok(None);
+    if let Some(verb_string) = maybe_verb {
+        let verb = verb_string.to_str();
+
+        // search through the list of commands linearly until one matches,
+        // then run it.
+        *match_found = false;
+        for cmd in commands.iter_mut() {
+            if cmd.matches(verb) {
+                *match_found = true;
+                cmd_ret = cmd.process(*cmdline, env);
+                lastverb.clear();
+                write!(lastverb, "{}", verb).expect("SHCH: couldn't record last verb");
+            };
+        }
+
+        // if none match, create a list of available commands
+        if !*match_found {
+            let mut first = true;
+            write!(ret, "Commands: ").unwrap();
+            for cmd in commands.iter() {
+                if !first {
+                    ret.append(", ")?;
+                }
+                ret.append(cmd.verb())?;
+                first = false;
+            }
+            Ok(Some(ret))
+        } else {
+            cmd_ret
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+/// Commands whose arguments are free text, such as a passphrase, and are passed on exactly as typed
+const FREE_TEXT_COMMANDS: [&str; 2] = ["wlan setpass", "wlan setssid"];
+
+/// Splits a command line into its `|`-separated stages, and an optional trailing `> dict:key`
+/// redirection of the final output into the PDDB. A quote only opens at the start of a word and only
+/// closes at the end of one, so an apostrophe inside a word is just an apostrophe; `|` and `>` inside
+/// quotes are taken literally, and the quotes are removed from the stage. A line without a `|` or `>`
+/// outside of quotes, or that runs one of the `FREE_TEXT_COMMANDS`, is a single stage, exactly as typed.
+pub fn parse_pipeline(
+    line: &str,
+) -> Result<(Vec<std::string::String>, Option<(std::string::String, std::string::String)>), &'static str> {
+    let as_typed = Ok((vec![line.trim().to_string()], None));
+    let mut words = line.split_whitespace();
+    let command = format!("{} {}", words.next().unwrap_or(""), words.next().unwrap_or(""));
+    if FREE_TEXT_COMMANDS.contains(&command.as_str()) {
+        return as_typed;
+    }
+
+    let mut stages = Vec::new();
+    let mut stage = std::string::String::new();
+    let mut target: Option<std::string::String> = None;
+    let mut quote: Option<char> = None;
+    let mut piped = false;
+    let chars: Vec<char> = line.chars().collect();
+    let is_break = |c: char| c.is_whitespace() || c == '|' || c == '>';
+    for (i, &ch) in chars.iter().enumerate() {
+        let word_start = i == 0 || is_break(chars[i - 1]);
+        let word_end = i + 1 == chars.len() || is_break(chars[i + 1]);
+        match (quote, ch) {
+            (Some(q), c) if c == q && word_end => quote = None,
+            (None, '"') | (None, '\'') if word_start => quote = Some(ch),
+            (None, '|') if target.is_none() => {
+                piped = true;
+                stages.push(core::mem::take(&mut stage));
+            }
+            (None, '>') if target.is_none() => {
+                piped = true;
+                target = Some(std::string::String::new());
+            }
+            (None, '>') | (None, '|') => return Err("Redirect target must be of the form `dict:key`"),
+            (_, c) => match target.as_mut() {
+                Some(t) => t.push(c),
+                None => stage.push(c),
+            },
+        }
+    }
+    if !piped {
+        return as_typed;
+    }
+    if quote.is_some() {
+        return Err("Unterminated quote");
+    }
+    stages.push(stage);
+    let stages: Vec<std::string::String> = stages.iter().map(|s| s.trim().to_string()).collect();
+    if stages.len() > 1 && stages.iter().any(|s| s.len() == 0) {
+        return Err("Empty command in pipeline");
+    }
+    let redirect = match target {
+        Some(target) => match target.trim().split_once(':') {
+            Some((dict, key)) if dict.len() > 0 && key.len() > 0 && !key.contains(char::is_whitespace) => {
+                Some((dict.to_string(), key.to_string()))
+            }
+            _ => return Err("Redirect target must be of the form `dict:key`"),
+        },
+        None => None,
+    };
+    Ok((stages, redirect))
+}
+
+/// Replaces the contents of `dict:key` in the PDDB with `text`, returning the number of bytes written.
+fn redirect_to_pddb(dict: &str, key: &str, text: &str) -> std::io::Result<usize> {
+    use std::io::Write;
+    let pddb = pddb::Pddb::new();
+    // delete any prior contents, so a shorter output doesn't leave stale data at the end of the key
+    pddb.delete_key(dict, key, None).ok();
+    let mut pddb_key = pddb.get(dict, key, None, true, true, Some(text.len()), None::<fn()>)?;
+    let len = pddb_key.write(text.as_bytes())?;
+    pddb.sync().ok();
+    Ok(len)
+}
+
 /// extract the first token, as delimited by spaces
 /// modifies the incoming line by removing the token and returning the remainder
 /// returns the found token
@@ -469,3 +748,46 @@ pub fn tokenize(line: &mut String<1024>) -> Option<String<1024>> {
     write!(line, "{}", retline.as_str().unwrap()).unwrap();
     if token.len() > 0 { Some(token) } else { None }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stages(line: &str) -> Vec<std::string::String> { parse_pipeline(line).unwrap().0 }
+
+    #[test]
+    fn lines_without_operators_are_left_alone() {
+        assert_eq!(stages(r#"echo a"b"c"#), vec![r#"echo a"b"c"#]);
+        assert_eq!(stages("echo Bob's WiFi"), vec!["echo Bob's WiFi"]);
+        assert_eq!(stages("echo 'quoted | pipe'"), vec!["echo 'quoted | pipe'"]);
+        assert_eq!(stages("  ver  "), vec!["ver"]);
+    }
+
+    #[test]
+    fn free_text_is_passed_as_typed() {
+        assert_eq!(stages("wlan setssid Bob's WiFi"), vec!["wlan setssid Bob's WiFi"]);
+        assert_eq!(stages("wlan setpass p|ss>w\"rd'"), vec!["wlan setpass p|ss>w\"rd'"]);
+        assert_eq!(stages("wlan  setpass a > b:c"), vec!["wlan  setpass a > b:c"]);
+        assert_eq!(parse_pipeline("wlan setpass a > b:c").unwrap().1, None);
+    }
+
+    #[test]
+    fn pipes_and_redirects() {
+        assert_eq!(stages("echo hi | sha"), vec!["echo hi", "sha"]);
+        assert_eq!(stages(r#"echo "a|b" | sha"#), vec!["echo a|b", "sha"]);
+        assert_eq!(stages("echo Bob's | sha"), vec!["echo Bob's", "sha"]);
+        let (stages, redirect) = parse_pipeline("ver > notes:version").unwrap();
+        assert_eq!(stages, vec!["ver"]);
+        assert_eq!(redirect, Some(("notes".to_string(), "version".to_string())));
+        assert_eq!(parse_pipeline("echo 'a>b' > x:y").unwrap().0, vec!["echo a>b"]);
+    }
+
+    #[test]
+    fn rejects_malformed_pipelines() {
+        assert!(parse_pipeline("a | | b").is_err());
+        assert!(parse_pipeline("a > nokey").is_err());
+        assert!(parse_pipeline("a > x:y | b").is_err());
+        assert!(parse_pipeline("echo 'open | sha").is_ok());
+        assert!(parse_pipeline("echo 'open' | 'sha").is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+/// Reports what the name server knows of a server's health, for servers that send it heartbeats.
+#[derive(Debug)]
+pub struct Alive {}
+
+impl<'a> ShellCmdApi<'a> for Alive {
+    cmd_api!(alive);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        use core::fmt::Write;
+        let mut ret = String::<1024>::new();
+        let helpstring = "alive <server name>";
+
+        // server names may contain spaces, so the whole argument is the name
+        let name = args.as_str().unwrap().trim();
+        if name.is_empty() {
+            write!(ret, "{}", helpstring).unwrap();
+            return Ok(Some(ret));
+        }
+        match env.xns.liveness(name)? {
+            None => write!(ret, "{} does not send heartbeats", name).unwrap(),
+            Some(liveness) => {
+                write!(
+                    ret,
+                    "{} is {} for {}s\nlast heartbeat {}s ago, {} restarts",
+                    name,
+                    if liveness.up { "up" } else { "down" },
+                    liveness.since_secs,
+                    liveness.last_heartbeat_secs,
+                    liveness.restarts
+                )
+                .unwrap();
+                if !liveness.registered {
+                    write!(ret, "\nnot registered").unwrap();
+                }
+            }
+        }
+        Ok(Some(ret))
+    }
+}
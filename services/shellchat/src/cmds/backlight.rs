@@ -1,3 +1,4 @@
+use keyboard_backlight::KeyboardBacklight;
 use xous_ipc::String;
 
 use crate::{CommonEnv, ShellCmdApi};
@@ -17,7 +18,7 @@ impl<'a> ShellCmdApi<'a> for Backlight {
     ) -> Result<Option<String<1024>>, xous::Error> {
         use core::fmt::Write;
         let mut ret = String::<1024>::new();
-        let helpstring = "backlight [on] [off] [0-5]";
+        let helpstring = "backlight [on] [off] [0-5] [auto on|off] [timeout <secs>] [blink [count]]";
 
         let mut tokens = args.as_str().unwrap().split(' ');
 
@@ -57,6 +58,34 @@ impl<'a> ShellCmdApi<'a> for Backlight {
                     env.com.set_backlight(255, 128).unwrap();
                     write!(ret, "backlight 5").unwrap();
                 }
+                "auto" => {
+                    let kbl = KeyboardBacklight::new(&env.xns)?;
+                    match tokens.next() {
+                        Some("on") => {
+                            kbl.enable_auto()?;
+                            write!(ret, "automatic backlight on").unwrap();
+                        }
+                        Some("off") => {
+                            kbl.disable_auto()?;
+                            write!(ret, "automatic backlight off").unwrap();
+                        }
+                        _ => write!(ret, "{}", helpstring).unwrap(),
+                    }
+                }
+                "timeout" => match tokens.next().and_then(|secs| secs.parse::<u32>().ok()) {
+                    Some(secs) => match KeyboardBacklight::new(&env.xns)?.set_timeout(secs) {
+                        Ok(()) => write!(ret, "backlight timeout {}s", secs).unwrap(),
+                        Err(e) => write!(ret, "couldn't set timeout: {:?}", e).unwrap(),
+                    },
+                    None => write!(ret, "{}", helpstring).unwrap(),
+                },
+                "blink" => {
+                    let count = tokens.next().and_then(|count| count.parse::<u32>().ok()).unwrap_or(3);
+                    match KeyboardBacklight::new(&env.xns)?.blink(count, 250) {
+                        Ok(()) => write!(ret, "blinking {} times", count).unwrap(),
+                        Err(e) => write!(ret, "couldn't blink: {:?}", e).unwrap(),
+                    }
+                }
                 _ => {
                     write!(ret, "{}", helpstring).unwrap();
                 }
@@ -0,0 +1,55 @@
+use codec::Tone;
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+/// Plays tones through the codec's tone mixer, e.g. to try out alert sounds.
+#[derive(Debug)]
+pub struct Beep {
+    codec: codec::Codec,
+}
+impl Beep {
+    pub fn new(xns: &xous_names::XousNames) -> Self { Beep { codec: codec::Codec::new(xns).unwrap() } }
+}
+
+impl<'a> ShellCmdApi<'a> for Beep {
+    cmd_api!(beep);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        _env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        use core::fmt::Write;
+        let mut ret = String::<1024>::new();
+        let helpstring = "beep [frequency Hz] [duration ms], beep chime, beep stop";
+
+        let mut tokens = args.as_str().unwrap().split(' ').filter(|t| !t.is_empty());
+        match tokens.next() {
+            Some("chime") => {
+                // a rising arpeggio, as a sample notification sound
+                let tones = [Tone::new(523, 120), Tone::new(659, 120), Tone::new(784, 240)];
+                match self.codec.play_tones(&tones) {
+                    Ok(_) => write!(ret, "Playing chime").unwrap(),
+                    Err(e) => write!(ret, "Couldn't play tones: {:?}", e).unwrap(),
+                }
+            }
+            Some("stop") => {
+                self.codec.stop_tones().ok();
+                write!(ret, "Tones stopped").unwrap();
+            }
+            first => {
+                let freq = first.map_or(Ok(880), |f| f.parse::<u16>());
+                let duration = tokens.next().map_or(Ok(200), |d| d.parse::<u16>());
+                match (freq, duration) {
+                    (Ok(freq), Ok(duration)) => match self.codec.beep(freq, duration) {
+                        Ok(_) => write!(ret, "Beeping at {}Hz for {}ms", freq, duration).unwrap(),
+                        Err(e) => write!(ret, "Couldn't play tones: {:?}", e).unwrap(),
+                    },
+                    _ => write!(ret, "{}", helpstring).unwrap(),
+                }
+            }
+        }
+        Ok(Some(ret))
+    }
+}
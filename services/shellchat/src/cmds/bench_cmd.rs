@@ -0,0 +1,135 @@
+use core::fmt::Write;
+
+use num_traits::*;
+use xous_bench::{Bench, BenchError, Benchmark, Measurement};
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+#[derive(Debug)]
+pub struct BenchCmd {
+    cb_id: Option<u32>,
+}
+impl BenchCmd {
+    pub fn new() -> BenchCmd { BenchCmd { cb_id: None } }
+}
+
+impl<'a> ShellCmdApi<'a> for BenchCmd {
+    cmd_api!(bench);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "bench [all|<name>...] [host <tls host>]\nall skips the network ones\nbenchmarks: ";
+
+        let bench = match Bench::new(&env.xns) {
+            Ok(bench) => bench,
+            Err(_) => {
+                write!(ret, "Benchmark service is not in this image").unwrap();
+                return Ok(Some(ret));
+            }
+        };
+        let mut benchmarks = Vec::new();
+        let mut host: Option<std::string::String> = None;
+        let mut tokens = args.as_str().unwrap().split(' ').filter(|t| !t.is_empty());
+        let mut usage = false;
+        while let Some(token) = tokens.next() {
+            match token {
+                "all" => benchmarks.extend(Benchmark::ALL.iter().filter(|b| !b.uses_network())),
+                "host" => match tokens.next() {
+                    Some(h) => host = Some(h.to_string()),
+                    None => usage = true,
+                },
+                name => match Benchmark::ALL.iter().find(|b| b.name() == name) {
+                    Some(b) => benchmarks.push(*b),
+                    None => usage = true,
+                },
+            }
+        }
+        if usage {
+            write!(ret, "{}", helpstring).unwrap();
+            for b in Benchmark::ALL.iter() {
+                write!(ret, "{} ", b.name()).unwrap();
+            }
+            return Ok(Some(ret));
+        }
+        if benchmarks.is_empty() {
+            // connecting out is opt-in: those are only run when named
+            benchmarks.extend(Benchmark::ALL.iter().filter(|b| !b.uses_network()));
+        }
+
+        let cb_id = match self.cb_id {
+            Some(id) => id,
+            None => {
+                let id = env.register_handler(String::<256>::from_str(self.verb()));
+                self.cb_id = Some(id);
+                id
+            }
+        };
+        // the benchmarks take a while, so they run on their own thread, and report each result back
+        // through the callback as it comes in
+        std::thread::spawn(move || {
+            let xns = xous_names::XousNames::new().unwrap();
+            let callback_conn = xns.request_connection_blocking(crate::SERVER_NAME_SHELLCHAT).unwrap();
+            for benchmark in benchmarks {
+                let (code, measurement) = match bench.run(benchmark, host.as_deref()) {
+                    Ok(Ok(m)) => (0, m),
+                    Ok(Err(e)) => (e.to_usize().unwrap(), Measurement { iterations: 0, elapsed_us: 0 }),
+                    Err(_) => {
+                        (BenchError::Failed.to_usize().unwrap(), Measurement { iterations: 0, elapsed_us: 0 })
+                    }
+                };
+                xous::send_message(
+                    callback_conn,
+                    xous::Message::new_scalar(
+                        cb_id as usize,
+                        benchmark.to_usize().unwrap(),
+                        code,
+                        measurement.iterations as usize,
+                        measurement.elapsed_us as usize,
+                    ),
+                )
+                .unwrap();
+            }
+        });
+
+        let version = env.ticktimer.get_version();
+        write!(ret, "Benchmarking {}", version.lines().next().unwrap_or("")).unwrap();
+        Ok(Some(ret))
+    }
+
+    fn callback(
+        &mut self,
+        msg: &xous::MessageEnvelope,
+        _env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        xous::msg_scalar_unpack!(msg, benchmark, code, iterations, elapsed_us, {
+            let benchmark: Benchmark = match FromPrimitive::from_usize(benchmark) {
+                Some(b) => b,
+                None => return Ok(None),
+            };
+            // 0 is success; the `BenchError`s start at 1
+            match FromPrimitive::from_usize(code) {
+                None => {
+                    let measurement =
+                        Measurement { iterations: iterations as u32, elapsed_us: elapsed_us as u64 };
+                    write!(ret, "{}", benchmark.report(&measurement)).unwrap();
+                    log::info!(
+                        "{}BENCH,{},{}us/op,{}",
+                        xous::BOOKEND_START,
+                        benchmark.name(),
+                        measurement.us_per_iteration(),
+                        xous::BOOKEND_END
+                    );
+                }
+                Some(BenchError::Unavailable) => write!(ret, "{}: not available", benchmark.name()).unwrap(),
+                Some(BenchError::Failed) => write!(ret, "{}: failed", benchmark.name()).unwrap(),
+            }
+        });
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,62 @@
+use core::fmt::Write;
+
+use clipboard::{ClipContent, Clipboard};
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+#[derive(Debug)]
+pub struct Clip {}
+impl Clip {
+    pub fn new() -> Clip { Clip {} }
+}
+
+impl<'a> ShellCmdApi<'a> for Clip {
+    cmd_api!(clip);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "clip [copy <text>] [ttl <secs> <text>] [paste] [clear]";
+
+        let clipboard = Clipboard::new(&env.xns, "shellchat")?;
+        let mut tokens = args.as_str().unwrap().splitn(2, ' ');
+        match (tokens.next(), tokens.next()) {
+            (Some("copy"), Some(text)) => match clipboard.copy_text(text, None) {
+                Ok(()) => write!(ret, "Copied {} bytes", text.len()).unwrap(),
+                Err(e) => write!(ret, "Couldn't copy: {:?}", e).unwrap(),
+            },
+            (Some("ttl"), Some(rest)) => {
+                let mut rest = rest.splitn(2, ' ');
+                match (rest.next().and_then(|s| s.parse::<u32>().ok()), rest.next()) {
+                    (Some(ttl), Some(text)) => match clipboard.copy_text(text, Some(ttl)) {
+                        Ok(()) => write!(ret, "Copied {} bytes, clears in {}s", text.len(), ttl).unwrap(),
+                        Err(e) => write!(ret, "Couldn't copy: {:?}", e).unwrap(),
+                    },
+                    _ => write!(ret, "{}", helpstring).unwrap(),
+                }
+            }
+            (Some("paste"), _) => match clipboard.paste() {
+                Ok(Some(ClipContent::Text(text))) => write!(ret, "{}", text).unwrap(),
+                Ok(Some(ClipContent::Binary(data))) => {
+                    write!(ret, "{} bytes of binary data", data.len()).unwrap()
+                }
+                Ok(None) => write!(ret, "Clipboard is empty").unwrap(),
+                Err(xous::Error::AccessDenied) => write!(ret, "Paste was not allowed").unwrap(),
+                Err(e) => write!(ret, "Couldn't paste: {:?}", e).unwrap(),
+            },
+            (Some("clear"), _) => match clipboard.clear() {
+                Ok(()) => write!(ret, "Clipboard cleared").unwrap(),
+                Err(xous::Error::AccessDenied) => {
+                    write!(ret, "Only the app that copied the item can clear it").unwrap()
+                }
+                Err(e) => write!(ret, "Couldn't clear: {:?}", e).unwrap(),
+            },
+            _ => write!(ret, "{}", helpstring).unwrap(),
+        }
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,94 @@
+use core::fmt::Write;
+
+use contacts::{ContactError, Contacts};
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+#[derive(Debug)]
+pub struct ContactsCmd {}
+impl ContactsCmd {
+    pub fn new() -> ContactsCmd { ContactsCmd {} }
+}
+
+impl<'a> ShellCmdApi<'a> for ContactsCmd {
+    cmd_api!(contacts);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "contacts [list] [show <id>] [import] [stop] [export] [delete <id>]";
+
+        let contacts = match Contacts::new(&env.xns) {
+            Ok(contacts) => contacts,
+            Err(_) => {
+                write!(ret, "Contacts service is not in this image").unwrap();
+                return Ok(Some(ret));
+            }
+        };
+        let mut tokens = args.as_str().unwrap().split(' ').filter(|t| !t.is_empty());
+        match (tokens.next(), tokens.next().map(|id| id.parse::<u32>())) {
+            (Some("import"), _) => match contacts.start_serial_import()? {
+                true => {
+                    write!(ret, "Send the .vcf file over the USB serial port, then `contacts stop`").unwrap()
+                }
+                false => write!(ret, "Couldn't switch the USB port to serial").unwrap(),
+            },
+            (Some("stop"), _) => {
+                contacts.stop_serial_import()?;
+                write!(ret, "Import stopped").unwrap();
+            }
+            (Some("export"), _) => match contacts.serial_export()? {
+                Ok(written) => write!(ret, "Wrote {} contacts to the USB serial port", written).unwrap(),
+                Err(ContactError::NotMounted) => write!(ret, "The PDDB isn't mounted").unwrap(),
+                Err(ContactError::Declined) => write!(ret, "Export declined").unwrap(),
+                Err(e) => write!(ret, "Couldn't export the contacts: {:?}", e).unwrap(),
+            },
+            (Some("delete"), Some(Ok(id))) => match contacts.delete(id)? {
+                true => write!(ret, "Deleted contact {}", id).unwrap(),
+                false => write!(ret, "No contact {}", id).unwrap(),
+            },
+            (Some("show"), Some(Ok(id))) => match contacts.get(id)? {
+                Ok(contact) => {
+                    writeln!(ret, "{}", contact.name).unwrap();
+                    for handle in contact.handles.iter().flatten() {
+                        writeln!(ret, "{}: {}", handle.label, handle.value).unwrap();
+                    }
+                    for key in contact.keys.iter().flatten() {
+                        write!(ret, "{} key: ", key.label).unwrap();
+                        for byte in key.fingerprint().iter() {
+                            write!(ret, "{:02X}", byte).unwrap();
+                        }
+                        writeln!(ret).unwrap();
+                    }
+                    if !contact.address.as_str().unwrap_or("").is_empty() {
+                        write!(ret, "{}", contact.address).unwrap();
+                    }
+                }
+                Err(ContactError::NotFound) => write!(ret, "No contact {}", id).unwrap(),
+                Err(ContactError::NotMounted) => write!(ret, "The PDDB isn't mounted").unwrap(),
+                Err(e) => write!(ret, "Couldn't read the contact: {:?}", e).unwrap(),
+            },
+            (Some("list"), None) | (None, None) => match contacts.list()? {
+                Ok(names) if names.is_empty() => write!(ret, "No contacts").unwrap(),
+                Ok(names) => {
+                    for name in names.iter() {
+                        let line = format!("{} [{}]\n", name.name, name.id);
+                        // leaves the rest off once the output is full
+                        if ret.len() + line.len() > 1024 {
+                            break;
+                        }
+                        write!(ret, "{}", line).unwrap();
+                    }
+                }
+                Err(ContactError::NotMounted) => write!(ret, "The PDDB isn't mounted").unwrap(),
+                Err(e) => write!(ret, "Couldn't read the contacts: {:?}", e).unwrap(),
+            },
+            _ => write!(ret, "{}", helpstring).unwrap(),
+        }
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::Read;
+
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+/// Where the status service stores crash reports
+const CRASHLOG_DICT: &str = "sys.crashlog";
+
+/// Lists, shows and clears the crash reports stored in the PDDB.
+pub struct CrashLog {
+    pddb: pddb::Pddb,
+}
+impl CrashLog {
+    pub fn new() -> Self { CrashLog { pddb: pddb::Pddb::new() } }
+
+    /// Returns the names of the stored reports, oldest first.
+    fn reports(&self) -> Vec<std::string::String> {
+        let mut keys = self.pddb.list_keys(CRASHLOG_DICT, None).unwrap_or_default();
+        keys.sort();
+        keys
+    }
+
+    fn read(&self, name: &str) -> Option<std::string::String> {
+        let mut key = self.pddb.get(CRASHLOG_DICT, name, None, false, false, None, None::<fn()>).ok()?;
+        let mut data = Vec::new();
+        key.read_to_end(&mut data).ok()?;
+        Some(std::string::String::from_utf8_lossy(&data).to_string())
+    }
+}
+
+impl<'a> ShellCmdApi<'a> for CrashLog {
+    cmd_api!(crashlog);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        _env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        use core::fmt::Write;
+        let mut ret = String::<1024>::new();
+        let helpstring = "crashlog [list], crashlog show <number>, crashlog clear";
+
+        let mut tokens = args.as_str().unwrap().split(' ').filter(|t| !t.is_empty());
+        match tokens.next() {
+            None | Some("list") => {
+                let reports = self.reports();
+                if reports.is_empty() {
+                    write!(ret, "No crash reports").unwrap();
+                }
+                // newest first, as many as fit
+                for name in reports.iter().rev() {
+                    let summary = self.read(name).unwrap_or_default();
+                    let line = format!(
+                        "{}: {}\n",
+                        name.trim_start_matches('0'),
+                        summary.lines().take(2).collect::<Vec<&str>>().join(", ")
+                    );
+                    if ret.len() + line.len() > 1024 {
+                        break;
+                    }
+                    write!(ret, "{}", line).unwrap();
+                }
+            }
+            Some("show") => match tokens.next().and_then(|n| n.parse::<u32>().ok()) {
+                Some(number) => match self.read(&format!("{:06}", number)) {
+                    Some(report) => {
+                        // the output is limited in length: the start of a panic message is what matters most
+                        let mut end = report.len().min(1024);
+                        while !report.is_char_boundary(end) {
+                            end -= 1;
+                        }
+                        write!(ret, "{}", &report[..end]).unwrap();
+                    }
+                    None => write!(ret, "No crash report {}", number).unwrap(),
+                },
+                None => write!(ret, "{}", helpstring).unwrap(),
+            },
+            Some("clear") => {
+                let reports = self.reports();
+                let count = reports.len();
+                match self.pddb.delete_key_list(CRASHLOG_DICT, reports, None) {
+                    Ok(_) => {
+                        self.pddb.sync().ok();
+                        write!(ret, "Deleted {} crash reports", count).unwrap();
+                    }
+                    Err(e) => write!(ret, "Couldn't delete crash reports: {:?}", e).unwrap(),
+                }
+            }
+            _ => write!(ret, "{}", helpstring).unwrap(),
+        }
+        Ok(Some(ret))
+    }
+}
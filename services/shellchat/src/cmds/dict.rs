@@ -0,0 +1,101 @@
+use core::fmt::Write;
+
+use ime_plugin_shell::UserDictionary;
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+pub struct Dict {
+    /// claimed at boot, so no other process can read back what was typed; claimed again on first use
+    /// if the plugin wasn't up yet, and never in images that don't run the shell plugin
+    dict: Option<UserDictionary>,
+}
+impl Dict {
+    pub fn new(xns: &xous_names::XousNames) -> Self { Dict { dict: UserDictionary::new(xns).ok() } }
+}
+
+impl<'a> ShellCmdApi<'a> for Dict {
+    cmd_api!(dict);
+
+    // inserts boilerplate for command API
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "dict [list [prefix]] [forget <word>] [export] [import]";
+
+        if self.dict.is_none() {
+            match UserDictionary::new(&env.xns) {
+                Ok(dict) => self.dict = Some(dict),
+                Err(xous::Error::AccessDenied) => {
+                    write!(ret, "The user dictionary is managed by another process").unwrap();
+                    return Ok(Some(ret));
+                }
+                Err(_) => {
+                    write!(ret, "The user dictionary isn't available in this image").unwrap();
+                    return Ok(Some(ret));
+                }
+            }
+        }
+        let dict = self.dict.as_ref().unwrap();
+
+        let mut tokens = args.as_str().unwrap().split(' ');
+
+        if let Some(sub_cmd) = tokens.next() {
+            match sub_cmd {
+                "list" => {
+                    let words = dict.list(tokens.next().unwrap_or(""))?;
+                    if words.is_empty() {
+                        write!(ret, "No words learned").unwrap();
+                    }
+                    for (word, count) in words {
+                        if write!(ret, "{} ({})\n", word, count).is_err() {
+                            break;
+                        }
+                    }
+                }
+                "forget" => {
+                    if let Some(word) = tokens.next() {
+                        if dict.forget(word)? {
+                            write!(ret, "Forgot {}", word).unwrap();
+                        } else {
+                            write!(ret, "{} wasn't in the dictionary", word).unwrap();
+                        }
+                    } else {
+                        write!(ret, "dict forget <word>").unwrap();
+                    }
+                }
+                "export" => match dict.export() {
+                    Ok(count) => write!(
+                        ret,
+                        "Exported {} words to {}:{}.export",
+                        count,
+                        ime_plugin_shell::USERDICT_DICT,
+                        locales::LANG
+                    )
+                    .unwrap(),
+                    Err(xous::Error::UseBeforeInit) => write!(ret, "Mount the PDDB first").unwrap(),
+                    Err(e) => write!(ret, "Export failed: {:?}", e).unwrap(),
+                },
+                "import" => match dict.import() {
+                    Ok(count) => write!(ret, "Imported {} words", count).unwrap(),
+                    Err(xous::Error::UseBeforeInit) => write!(ret, "Mount the PDDB first").unwrap(),
+                    Err(_) => write!(
+                        ret,
+                        "Import failed; put the words in {}:{}.import, one per line",
+                        ime_plugin_shell::USERDICT_DICT,
+                        locales::LANG
+                    )
+                    .unwrap(),
+                },
+                _ => write!(ret, "{}", helpstring).unwrap(),
+            }
+        } else {
+            write!(ret, "{}", helpstring).unwrap();
+        }
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,89 @@
+use core::fmt::Write;
+
+use chrono::NaiveDateTime;
+use events::{EventError, Events};
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+#[derive(Debug)]
+pub struct EventsCmd {}
+impl EventsCmd {
+    pub fn new() -> EventsCmd { EventsCmd {} }
+}
+
+impl<'a> ShellCmdApi<'a> for EventsCmd {
+    cmd_api!(events);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "events [list <days>] [import] [stop] [delete <id>]";
+
+        let events = match Events::new(&env.xns) {
+            Ok(events) => events,
+            Err(_) => {
+                write!(ret, "Event service is not in this image").unwrap();
+                return Ok(Some(ret));
+            }
+        };
+        let mut tokens = args.as_str().unwrap().split(' ').filter(|t| !t.is_empty());
+        match (tokens.next(), tokens.next()) {
+            (Some("import"), _) => match events.start_serial_import()? {
+                true => write!(ret, "Send the .ics file over the USB serial port").unwrap(),
+                false => write!(ret, "Import declined, or the USB port couldn't switch to serial").unwrap(),
+            },
+            (Some("stop"), _) => {
+                events.stop_serial_import()?;
+                write!(ret, "Import stopped").unwrap();
+            }
+            (Some("delete"), Some(id)) => match id.parse::<u32>() {
+                Ok(id) => match events.delete(id)? {
+                    true => write!(ret, "Deleted event {}", id).unwrap(),
+                    false => write!(ret, "No event {}", id).unwrap(),
+                },
+                Err(_) => write!(ret, "{}", helpstring).unwrap(),
+            },
+            (Some("list"), days) | (None, days) => {
+                let days = match days.map(|d| d.parse::<i64>()) {
+                    None => 7,
+                    Some(Ok(days)) => days,
+                    Some(Err(_)) => {
+                        write!(ret, "{}", helpstring).unwrap();
+                        return Ok(Some(ret));
+                    }
+                };
+                let now = match llio::LocalTime::new().get_local_time_ms() {
+                    Some(ms) => (ms / 1000) as i64,
+                    None => {
+                        write!(ret, "The time isn't set").unwrap();
+                        return Ok(Some(ret));
+                    }
+                };
+                match events.occurrences(now, now + days * 24 * 60 * 60)? {
+                    Ok(occurrences) if occurrences.is_empty() => write!(ret, "Nothing coming up").unwrap(),
+                    Ok(occurrences) => {
+                        for o in occurrences.iter() {
+                            let when = NaiveDateTime::from_timestamp_opt(o.start, 0)
+                                .map(|t| t.format("%a %d %b %H:%M").to_string())
+                                .unwrap_or_default();
+                            let line = format!("{} {} [{}]\n", when, o.title.as_str().unwrap_or(""), o.id);
+                            // leaves the rest off once the output is full
+                            if ret.len() + line.len() > 1024 {
+                                break;
+                            }
+                            write!(ret, "{}", line).unwrap();
+                        }
+                    }
+                    Err(EventError::NotMounted) => write!(ret, "The PDDB isn't mounted").unwrap(),
+                    Err(e) => write!(ret, "Couldn't read the events: {:?}", e).unwrap(),
+                }
+            }
+            _ => write!(ret, "{}", helpstring).unwrap(),
+        }
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,178 @@
+use core::fmt::Write as FmtWrite;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use gam::{hex_row, HEX_BYTES_PER_ROW};
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+/// The most that is read for one page; what fits in the output is less than this
+const PAGE_LEN: u64 = 512;
+/// The most that can be written at once, so that the old and new bytes fit in the confirmation
+const MAX_WRITE: usize = 64;
+
+pub struct HexDump {
+    pddb: pddb::Pddb,
+}
+impl HexDump {
+    pub fn new() -> Self { HexDump { pddb: pddb::Pddb::new() } }
+
+    /// Overwrites `bytes` of `dict:key` at `offset`, once the user agrees to it
+    fn write(
+        &self,
+        ret: &mut String<1024>,
+        env: &CommonEnv,
+        dict: &str,
+        keyname: &str,
+        offset: u64,
+        bytes: &[u8],
+    ) -> Result<(), xous::Error> {
+        if bytes.len() > MAX_WRITE {
+            write!(ret, "Can write at most {} bytes at once", MAX_WRITE).unwrap();
+            return Ok(());
+        }
+        let mut key = match self.pddb.get(dict, keyname, None, false, false, None, None::<fn()>) {
+            Ok(key) => key,
+            Err(e) => {
+                write!(ret, "{}:{} not found or other error: {:?}", dict, keyname, e).unwrap();
+                return Ok(());
+            }
+        };
+        let len = key.attributes().map(|attr| attr.len as u64).unwrap_or(0);
+        // only patches what is there: a blob that grows isn't a record any more
+        if offset.checked_add(bytes.len() as u64).map_or(true, |end| end > len) {
+            write!(ret, "{}:{} is {} bytes; the write would run past the end", dict, keyname, len).unwrap();
+            return Ok(());
+        }
+        let mut old = vec![0u8; bytes.len()];
+        if key.seek(SeekFrom::Start(offset)).and_then(|_| key.read_exact(&mut old)).is_err() {
+            write!(ret, "Error reading {}:{}", dict, keyname).unwrap();
+            return Ok(());
+        }
+
+        let modals = modals::Modals::new(&env.xns)?;
+        let mut prompt = format!("Overwrite {} bytes of {}:{}?\n", bytes.len(), dict, keyname);
+        for (i, (was, is)) in old.chunks(HEX_BYTES_PER_ROW).zip(bytes.chunks(HEX_BYTES_PER_ROW)).enumerate() {
+            let at = offset + (i * HEX_BYTES_PER_ROW) as u64;
+            writeln!(prompt, "-{}\n+{}", hex_row(at, was), hex_row(at, is)).unwrap();
+        }
+        modals.add_list(vec!["Write", "Cancel"])?;
+        if modals.get_radiobutton(&prompt)? != "Write" {
+            write!(ret, "Nothing written").unwrap();
+            return Ok(());
+        }
+        match key.seek(SeekFrom::Start(offset)).and_then(|_| key.write_all(bytes)) {
+            Ok(()) => {
+                self.pddb.sync().ok();
+                write!(ret, "Wrote {} bytes at {} of {}:{}", bytes.len(), offset, dict, keyname).unwrap();
+            }
+            Err(e) => write!(ret, "Error writing {}:{}: {:?}", dict, keyname, e).unwrap(),
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ShellCmdApi<'a> for HexDump {
+    cmd_api!(hexdump);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "hexdump <dict:key> [offset len]\nhexdump <dict:key> write <offset> <hex bytes>";
+
+        let mut tokens = args.as_str().unwrap().split(' ').filter(|t| !t.is_empty());
+        let Some((dict, keyname)) = tokens.next().and_then(|d| d.split_once(':')) else {
+            write!(ret, "{}", helpstring).unwrap();
+            return Ok(Some(ret));
+        };
+        let rest: Vec<&str> = tokens.collect();
+        match rest.as_slice() {
+            ["write", offset, hex @ ..] if !hex.is_empty() => {
+                match (parse_number(offset), parse_hex(&hex.concat())) {
+                    (Some(offset), Some(bytes)) => {
+                        self.write(&mut ret, env, dict, keyname, offset, &bytes)?
+                    }
+                    _ => write!(ret, "{}", helpstring).unwrap(),
+                }
+            }
+            [] | [_, _] => {
+                let (offset, len) = match rest.as_slice() {
+                    [offset, len] => match (parse_number(offset), parse_number(len)) {
+                        (Some(offset), Some(len)) => (offset, len),
+                        _ => {
+                            write!(ret, "{}", helpstring).unwrap();
+                            return Ok(Some(ret));
+                        }
+                    },
+                    _ => (0, PAGE_LEN),
+                };
+                // an offset and length that add up past u64::MAX are no span of any key
+                let Some(span_end) = offset.checked_add(len) else {
+                    write!(ret, "{}", helpstring).unwrap();
+                    return Ok(Some(ret));
+                };
+                let mut key = match self.pddb.get(dict, keyname, None, false, false, None, None::<fn()>) {
+                    Ok(key) => key,
+                    Err(e) => {
+                        write!(ret, "{}:{} not found or other error: {:?}", dict, keyname, e).unwrap();
+                        return Ok(Some(ret));
+                    }
+                };
+                let total = key.attributes().map(|attr| attr.len as u64).unwrap_or(0);
+                let mut readbuf = vec![0u8; len.min(PAGE_LEN) as usize];
+                let readlen = match key.seek(SeekFrom::Start(offset)).and_then(|_| key.read(&mut readbuf)) {
+                    Ok(readlen) => readlen,
+                    Err(e) => {
+                        write!(ret, "Error reading {}:{}: {:?}", dict, keyname, e).unwrap();
+                        return Ok(Some(ret));
+                    }
+                };
+                writeln!(ret, "{}:{} {} of {} bytes", dict, keyname, offset, total).unwrap();
+                // leave room for the pointer to the next page
+                let limit = 1024 - 64;
+                let mut shown = 0;
+                for (i, chunk) in readbuf[..readlen].chunks(HEX_BYTES_PER_ROW).enumerate() {
+                    let row = hex_row(offset + (i * HEX_BYTES_PER_ROW) as u64, chunk);
+                    if ret.len() + row.len() + 1 > limit {
+                        break;
+                    }
+                    writeln!(ret, "{}", row).unwrap();
+                    shown += chunk.len() as u64;
+                }
+                let end = total.min(span_end);
+                if offset + shown < end {
+                    write!(
+                        ret,
+                        "[next: hexdump {}:{} {} {}]",
+                        dict,
+                        keyname,
+                        offset + shown,
+                        end - offset - shown
+                    )
+                    .ok();
+                }
+            }
+            _ => write!(ret, "{}", helpstring).unwrap(),
+        }
+        Ok(Some(ret))
+    }
+}
+
+/// Parses a decimal number, or a hex one starting with `0x`
+fn parse_number(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Parses pairs of hex digits into bytes
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok()).collect()
+}
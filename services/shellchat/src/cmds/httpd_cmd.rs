@@ -0,0 +1,76 @@
+use core::fmt::Write;
+
+use httpd::{HTTPD_DEFAULT_PORT, Httpd};
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+#[derive(Debug)]
+pub struct HttpdCmd {
+    // connected once, at boot: the server takes a single connection, so that only shellchat controls it
+    httpd: Httpd,
+}
+impl HttpdCmd {
+    pub fn new(xns: &xous_names::XousNames) -> HttpdCmd {
+        HttpdCmd { httpd: Httpd::new(xns).expect("can't connect to the HTTP status server") }
+    }
+}
+
+impl<'a> ShellCmdApi<'a> for HttpdCmd {
+    cmd_api!(httpd);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        _env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "httpd [status] [on [port]] [off] [token] [newtoken]";
+
+        let httpd = &self.httpd;
+        let mut tokens = args.as_str().unwrap().split(' ');
+        match (tokens.next(), tokens.next()) {
+            (Some("on"), port) => {
+                let port = match port {
+                    Some(port) => match port.parse::<u16>() {
+                        Ok(port) => port,
+                        Err(_) => {
+                            write!(ret, "{}", helpstring).unwrap();
+                            return Ok(Some(ret));
+                        }
+                    },
+                    None => HTTPD_DEFAULT_PORT,
+                };
+                match httpd.enable(port) {
+                    Ok(()) => write!(
+                        ret,
+                        "Serving on port {}, also after a reboot.\nAPI token: {}",
+                        port,
+                        httpd.token(false)?
+                    )
+                    .unwrap(),
+                    Err(xous::Error::AccessDenied) => write!(ret, "Mount the PDDB first").unwrap(),
+                    Err(e) => write!(ret, "Couldn't serve on port {}: {:?}", port, e).unwrap(),
+                }
+            }
+            (Some("off"), _) => {
+                httpd.disable()?;
+                write!(ret, "HTTP server off").unwrap();
+            }
+            (Some("token"), _) => match httpd.token(false) {
+                Ok(token) => write!(ret, "API token: {}", token).unwrap(),
+                Err(e) => write!(ret, "Couldn't read the token: {:?}", e).unwrap(),
+            },
+            (Some("newtoken"), _) => match httpd.token(true) {
+                Ok(token) => write!(ret, "New API token: {}", token).unwrap(),
+                Err(e) => write!(ret, "Couldn't renew the token: {:?}", e).unwrap(),
+            },
+            (Some("status"), _) | (Some(""), _) | (None, _) => match httpd.port()? {
+                Some(port) => write!(ret, "Serving on port {}", port).unwrap(),
+                None => write!(ret, "HTTP server off").unwrap(),
+            },
+            _ => write!(ret, "{}", helpstring).unwrap(),
+        }
+        Ok(Some(ret))
+    }
+}
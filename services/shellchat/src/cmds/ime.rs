@@ -0,0 +1,64 @@
+use core::fmt::Write;
+
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+#[derive(Debug)]
+pub struct Ime {}
+
+impl<'a> ShellCmdApi<'a> for Ime {
+    cmd_api!(ime);
+
+    // inserts boilerplate for command API
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "ime [list] [use <engine>] [default]";
+
+        let mut tokens = args.as_str().unwrap().split(' ');
+
+        if let Some(sub_cmd) = tokens.next() {
+            match sub_cmd {
+                "list" => {
+                    let (engines, selected) = env.gam.list_ime_engines()?;
+                    for engine in engines.iter() {
+                        let marker = if Some(engine.server) == selected { "*" } else { " " };
+                        write!(ret, "{} {} ({})\n", marker, engine.label, engine.server).unwrap();
+                    }
+                }
+                "use" => {
+                    let name = String::<64>::from_str(&tokens.collect::<Vec<&str>>().join(" "));
+                    let (engines, _) = env.gam.list_ime_engines()?;
+                    // engines can be picked by label or by server name
+                    match engines.iter().find(|e| e.label == name || e.server == name) {
+                        Some(engine) => {
+                            let server = engine.server.as_str().unwrap();
+                            if env.gam.select_ime_engine(env.gam_token, Some(server))? {
+                                write!(ret, "Input method is now {}", engine.label).unwrap();
+                            } else {
+                                write!(ret, "Couldn't switch to {}", engine.label).unwrap();
+                            }
+                        }
+                        None => write!(ret, "No input method named {}; try `ime list`", name).unwrap(),
+                    }
+                }
+                "default" => {
+                    if env.gam.select_ime_engine(env.gam_token, None)? {
+                        write!(ret, "Input method reverted to the default").unwrap();
+                    } else {
+                        write!(ret, "Couldn't revert the input method").unwrap();
+                    }
+                }
+                _ => write!(ret, "{}", helpstring).unwrap(),
+            }
+        } else {
+            write!(ret, "{}", helpstring).unwrap();
+        }
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,94 @@
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+/// Number of messages shown by a bare `log`
+const LOG_DEFAULT_LINES: usize = 8;
+
+/// Shows the recent messages kept by the log server, and adjusts its severity filters.
+#[derive(Debug)]
+pub struct LogCmd {}
+
+/// Reads every message kept in the log server's RAM buffer, oldest first.
+fn read_log() -> Vec<(log::Level, std::string::String)> {
+    let mut lines = Vec::new();
+    log_server::read_all(|level, text| lines.push((level, text.to_string()))).ok();
+    lines
+}
+
+impl<'a> ShellCmdApi<'a> for LogCmd {
+    cmd_api!(log);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        _env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        use core::fmt::Write;
+        let mut ret = String::<1024>::new();
+        let helpstring = "log [lines] [errors|warnings], log filter <module|*> <off|error|warn|info|debug|trace>, log unfilter <module|*>";
+
+        let mut tokens = args.as_str().unwrap().split(' ').filter(|t| !t.is_empty());
+        match tokens.next() {
+            Some("filter") => match (tokens.next(), tokens.next().map(|l| l.parse::<log::LevelFilter>())) {
+                (Some(module), Some(Ok(level))) => {
+                    let module = if module == "*" { "" } else { module };
+                    match log_server::set_filter(module, level) {
+                        Ok(_) => write!(ret, "Showing {} and above for '{}'", level, module).unwrap(),
+                        Err(e) => write!(ret, "Couldn't set filter: {:?}", e).unwrap(),
+                    }
+                }
+                _ => write!(ret, "{}", helpstring).unwrap(),
+            },
+            Some("unfilter") => match tokens.next() {
+                Some(module) => {
+                    let module = if module == "*" { "" } else { module };
+                    match log_server::remove_filter(module) {
+                        Ok(_) => write!(ret, "Removed filter for '{}'", module).unwrap(),
+                        Err(e) => write!(ret, "Couldn't remove filter: {:?}", e).unwrap(),
+                    }
+                }
+                None => write!(ret, "{}", helpstring).unwrap(),
+            },
+            first => {
+                let mut count = LOG_DEFAULT_LINES;
+                let mut max_level = log::Level::Trace;
+                for token in first.into_iter().chain(tokens) {
+                    match token {
+                        "errors" => max_level = log::Level::Error,
+                        "warnings" => max_level = log::Level::Warn,
+                        _ => match token.parse::<usize>() {
+                            Ok(n) if n > 0 => count = n,
+                            _ => {
+                                write!(ret, "{}", helpstring).unwrap();
+                                return Ok(Some(ret));
+                            }
+                        },
+                    }
+                }
+                let lines: Vec<std::string::String> = read_log()
+                    .iter()
+                    .filter(|(level, _)| *level <= max_level)
+                    .map(|(level, text)| format!("{} {}\n", level, text))
+                    .collect();
+                if lines.is_empty() {
+                    write!(ret, "The log is empty").unwrap();
+                }
+                // the output is limited in length, so show as many of the newest lines as fit
+                let mut shown = 0;
+                let mut len = 0;
+                for line in lines.iter().rev().take(count) {
+                    if len + line.len() > 1024 {
+                        break;
+                    }
+                    len += line.len();
+                    shown += 1;
+                }
+                for line in lines[lines.len() - shown..].iter() {
+                    write!(ret, "{}", line).unwrap();
+                }
+            }
+        }
+        Ok(Some(ret))
+    }
+}
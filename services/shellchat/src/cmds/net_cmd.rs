@@ -17,10 +17,7 @@ use xous_ipc::String;
 #[cfg(feature = "ditherpunk")]
 use {gam::DecodePng, std::str::FromStr};
 #[cfg(feature = "websocket")]
-use {
-    tls::Tls,
-    tungstenite::{stream::MaybeTlsStream, WebSocket},
-};
+use websocket::WebSocket;
 
 use crate::{CommonEnv, ShellCmdApi};
 
@@ -28,10 +25,14 @@ pub struct NetCmd {
     callback_id: Option<u32>,
     callback_conn: u32,
     dns: dns::Dns,
+    /// claimed at boot, as the net server only takes a fixed number of these
+    proxy: Option<net::ProxyControl>,
     #[cfg(any(feature = "precursor", feature = "renode"))]
     ping: Option<net::protocols::Ping>,
+    /// statistics for the current run of pings, reported once the run is done
+    ping_stats: Option<RoundTripStats>,
     #[cfg(feature = "websocket")]
-    ws: Option<WebSocket<MaybeTlsStream<TcpStream>>>,
+    ws: Option<WebSocket>,
     #[cfg(feature = "shellperf")]
     perfbuf: xous::MemoryRange,
 }
@@ -50,8 +51,12 @@ impl NetCmd {
             callback_id: None,
             callback_conn: xns.request_connection_blocking(crate::SERVER_NAME_SHELLCHAT).unwrap(),
             dns: dns::Dns::new(&xns).unwrap(),
+            proxy: net::ProxyControl::new(&xns)
+                .map_err(|e| log::error!("couldn't claim control of the proxy: {:?}", e))
+                .ok(),
             #[cfg(any(feature = "precursor", feature = "renode"))]
             ping: None,
+            ping_stats: None,
             #[cfg(feature = "websocket")]
             ws: None,
             #[cfg(feature = "shellperf")]
@@ -85,10 +90,10 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
         use core::fmt::Write;
         let mut ret = String::<1024>::new();
         #[cfg(any(feature = "precursor", feature = "renode"))]
-        let helpstring = "net [udp [rx socket] [tx dest socket]] [ping [host] [count]] [tcpget host/path]";
+        let helpstring = "net [udp [rx socket] [tx dest socket]] [ping [host] [count]] [tcpget host/path]\n[tcpconnect host:port [count]] [tls bench host [count]]\n[socks [off | host:port [user password]]]";
         // no ping in hosted mode -- why would you need it? we're using the host's network connection.
         #[cfg(not(target_os = "xous"))]
-        let helpstring = "net [udp [port]] [count]] [tcpget host/path]";
+        let helpstring = "net [udp [port]] [count]] [tcpget host/path]\n[tcpconnect host:port [count]] [tls bench host [count]]\n[socks [off | host:port [user password]]]";
 
         let mut tokens = args.as_str().unwrap().split(' ');
 
@@ -183,6 +188,103 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
                         write!(ret, "Usage: tcpget bunniefoo.com/bunnie/test.txt").unwrap();
                     }
                 }
+                "tcpconnect" => {
+                    // times name resolution and TCP connection setup separately, to help tell a slow radio
+                    // or DNS server apart from a slow path to the host
+                    let target = tokens.next().and_then(|t| t.rsplit_once(':'));
+                    let count = tokens
+                        .next()
+                        .map_or(Ok(3), |c| c.parse::<u32>())
+                        .map(|c| c.min(MAX_TCPCONNECT_COUNT));
+                    match (target, count) {
+                        (Some((host, port_str)), Ok(count)) if count > 0 => match port_str.parse::<u16>() {
+                            Ok(port) => {
+                                let start = Instant::now();
+                                match self.dns.lookup(host) {
+                                    Ok(ipaddr) => {
+                                        let dns_ms = start.elapsed().as_millis();
+                                        let addr = std::net::SocketAddr::new(IpAddr::from(ipaddr), port);
+                                        let mut stats = RoundTripStats::new(count);
+                                        let mut last_err = None;
+                                        for _ in 0..count {
+                                            let start = Instant::now();
+                                            match TcpStream::connect_timeout(
+                                                &addr,
+                                                Duration::from_millis(10_000),
+                                            ) {
+                                                Ok(stream) => {
+                                                    stats.record(start.elapsed().as_millis() as usize);
+                                                    stream.shutdown(std::net::Shutdown::Both).ok();
+                                                }
+                                                Err(e) => last_err = Some(e),
+                                            }
+                                        }
+                                        write!(
+                                            ret,
+                                            "{} ({:?}): dns {} ms\nconnect: {}",
+                                            host, ipaddr, dns_ms, stats
+                                        )
+                                        .ok();
+                                        if let Some(e) = last_err {
+                                            write!(ret, "\nlast error: {:?}", e).ok();
+                                        }
+                                        log::info!(
+                                            "{}NET.TCPCONNECT,{},{},{},{},{}",
+                                            xous::BOOKEND_START,
+                                            dns_ms,
+                                            stats.received,
+                                            stats.min_text(),
+                                            stats.avg_ms(),
+                                            xous::BOOKEND_END
+                                        );
+                                    }
+                                    Err(e) => write!(ret, "DNS lookup of {} failed: {:?}", host, e).unwrap(),
+                                }
+                            }
+                            Err(_) => write!(ret, "Invalid port: {}", port_str).unwrap(),
+                        },
+                        _ => write!(ret, "Usage: net tcpconnect host:port [count]").unwrap(),
+                    }
+                }
+                "socks" => match (tokens.next(), self.proxy.as_ref()) {
+                    (None, _) => match env.netmgr.socks_proxy() {
+                        Ok(Some(proxy)) if proxy.authenticated => {
+                            write!(ret, "TCP goes through {}, with a password", proxy.addr).unwrap()
+                        }
+                        Ok(Some(proxy)) => write!(ret, "TCP goes through {}", proxy.addr).unwrap(),
+                        Ok(None) => write!(ret, "TCP connects directly").unwrap(),
+                        Err(e) => write!(ret, "couldn't get the proxy: {:?}", e).unwrap(),
+                    },
+                    (Some(_), None) => write!(ret, "The proxy can't be set from the shell").unwrap(),
+                    (Some("off"), Some(proxy_control)) => match proxy_control.set_socks_proxy(None) {
+                        Ok(()) => write!(ret, "TCP connects directly").unwrap(),
+                        Err(e) => write!(ret, "couldn't clear the proxy: {:?}", e).unwrap(),
+                    },
+                    (Some(target), Some(proxy_control)) => {
+                        let credentials = match (tokens.next(), tokens.next()) {
+                            (Some(username), Some(password)) => {
+                                Some((username.to_string(), password.to_string()))
+                            }
+                            _ => None,
+                        };
+                        match target.rsplit_once(':').map(|(host, port)| (host, port.parse::<u16>())) {
+                            Some((host, Ok(port))) => match self.dns.lookup(host) {
+                                Ok(ipaddr) => {
+                                    let proxy = net::SocksProxy {
+                                        addr: std::net::SocketAddr::new(IpAddr::from(ipaddr), port),
+                                        credentials,
+                                    };
+                                    match proxy_control.set_socks_proxy(Some(&proxy)) {
+                                        Ok(()) => write!(ret, "TCP now goes through {}", proxy.addr).unwrap(),
+                                        Err(e) => write!(ret, "couldn't set the proxy: {:?}", e).unwrap(),
+                                    }
+                                }
+                                Err(e) => write!(ret, "DNS lookup of {} failed: {:?}", host, e).unwrap(),
+                            },
+                            _ => write!(ret, "Usage: net socks [off | host:port [user password]]").unwrap(),
+                        }
+                    }
+                },
                 "server" => {
                     // this is adapted from https://doc.rust-lang.org/book/ch20-03-graceful-shutdown-and-cleanup.html
                     thread::spawn({
@@ -493,59 +595,41 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
                 "ws" => {
                     if self.ws.is_none() {
                         let server = "wss://awake.noskills.club/ws";
-                        let url = url::Url::parse(server).expect("Can't parse");
-                        log::info!("attempting websocket connection to {}", url.as_str());
-                        let host = url.host_str().expect("failed to extract host from url");
-                        match TcpStream::connect((host, 443)) {
-                            Ok(sock) => {
-                                log::info!("tcp connected to {host}");
-                                let xtls = Tls::new();
-                                match xtls.stream_owned(host, sock) {
-                                    Ok(tls_stream) => {
-                                        log::info!("tls configured");
-                                        match tungstenite::client(url, tls_stream) {
-                                            Ok((_socket, response)) => {
-                                                log::info!("Websocket connected to: {}", server);
-                                                log::info!("Response HTTP code: {}", response.status());
-                                                log::info!("Response contains the following headers:");
-                                                for (ref header, _value) in response.headers() {
-                                                    log::info!("* {}", header);
-                                                }
-                                            }
-                                            Err(e) => {
-                                                log::info!("failed to connect websocket: {}", e)
-                                            }
-                                        }
-                                    }
-                                    Err(e) => log::warn!("failed to configure tls: {e}"),
-                                }
-                            }
-                            Err(e) => log::warn!("failed to connect tcp: {e}"),
+                        log::info!("attempting websocket connection to {}", server);
+                        match WebSocket::connect(server) {
+                            Ok(socket) => self.ws = Some(socket),
+                            Err(e) => write!(ret, "failed to connect websocket: {}", e).unwrap(),
                         }
                     }
                     let mut err = false;
                     if let Some(socket) = &mut self.ws {
                         let mut val = String::<1024>::new();
                         join_tokens(&mut val, &mut tokens);
-                        if val.len() > 0 {
-                            socket.send(tungstenite::Message::Text(val.as_str().unwrap().into())).unwrap();
-                        } else {
-                            socket.send(tungstenite::Message::Text("Hello WebSocket".into())).unwrap();
+                        let text = if val.len() > 0 { val.as_str().unwrap() } else { "Hello WebSocket" };
+                        let mut reply = socket.send_text(text).and_then(|_| socket.recv());
+                        // allow a few polls for the echo to come back
+                        for _ in 0..4 {
+                            match reply {
+                                Ok(None) => reply = socket.recv(),
+                                _ => break,
+                            }
                         }
-                        match socket.read() {
-                            Ok(msg) => {
+                        match reply {
+                            Ok(Some(msg)) => {
                                 log::info!("Received: {:?}", msg);
                                 write!(ret, "Rx: {:?}", msg).ok();
                             }
+                            Ok(None) => write!(ret, "No reply yet").unwrap(),
                             Err(e) => {
                                 log::info!("got ws error: {:?}, quitting", e);
                                 err = true;
-                                socket.close(None).ok();
                             }
                         }
                     }
                     if err {
-                        self.ws.take();
+                        if let Some(socket) = self.ws.take() {
+                            socket.close();
+                        }
                         write!(ret, "\nWeb socket session closed.").ok();
                     }
                 }
@@ -736,6 +820,7 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
                                 if let Some(count_str) = tokens.next() {
                                     let count = count_str.parse::<u32>().unwrap();
                                     if let Some(pinger) = &self.ping {
+                                        self.ping_stats = Some(RoundTripStats::new(count));
                                         pinger.ping_spawn_thread(IpAddr::from(ipaddr), count as usize, 1000);
                                         write!(ret, "Sending {} pings to {} ({:?})", count, name, ipaddr)
                                             .unwrap();
@@ -806,12 +891,19 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
                         let timestamp = *arg4;
                         match FromPrimitive::from_usize(op & 0xFF) {
                             Some(NetPingCallback::Drop) => {
-                                // write!(ret, "Info: All pending pings done").unwrap();
-                                // ignore the message, just creates visual noise
-                                return Ok(None);
+                                // summarize a run of pings; a single ping needs no summary, and the message
+                                // would just create visual noise
+                                if let Some(stats) = self.ping_stats.take() {
+                                    write!(ret, "Ping {:?}: {}", addr, stats).unwrap();
+                                } else {
+                                    return Ok(None);
+                                }
                             }
                             Some(NetPingCallback::NoErr) => match addr {
                                 IpAddr::V4(_) => {
+                                    if let Some(stats) = self.ping_stats.as_mut() {
+                                        stats.record(timestamp);
+                                    }
                                     write!(
                                         ret,
                                         "Pong from {:?} seq {} received: {} ms",
@@ -858,6 +950,47 @@ impl<'a> ShellCmdApi<'a> for NetCmd {
     }
 }
 
+/// Most connections `net tcpconnect` makes; larger counts are cut down to this, so that a typo can't tie
+/// up the shell for hours
+const MAX_TCPCONNECT_COUNT: u32 = 100;
+
+/// Round-trip time statistics for a series of probes.
+struct RoundTripStats {
+    sent: u32,
+    received: u32,
+    min_ms: usize,
+    max_ms: usize,
+    total_ms: usize,
+}
+impl RoundTripStats {
+    fn new(sent: u32) -> Self {
+        RoundTripStats { sent, received: 0, min_ms: usize::MAX, max_ms: 0, total_ms: 0 }
+    }
+
+    fn record(&mut self, rtt_ms: usize) {
+        self.received += 1;
+        self.min_ms = self.min_ms.min(rtt_ms);
+        self.max_ms = self.max_ms.max(rtt_ms);
+        self.total_ms += rtt_ms;
+    }
+
+    fn avg_ms(&self) -> usize { if self.received > 0 { self.total_ms / self.received as usize } else { 0 } }
+
+    /// The shortest round trip, or "n/a" when nothing came back
+    fn min_text(&self) -> std::string::String {
+        if self.received > 0 { self.min_ms.to_string() } else { "n/a".to_string() }
+    }
+}
+impl core::fmt::Display for RoundTripStats {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}/{} ok", self.received, self.sent)?;
+        if self.received > 0 {
+            write!(f, ", min/avg/max {}/{}/{} ms", self.min_ms, self.avg_ms(), self.max_ms)?;
+        }
+        Ok(())
+    }
+}
+
 enum Responses {
     Uptime,
     NotFound,
@@ -67,9 +67,9 @@ impl<'a> ShellCmdApi<'a> for PddbCmd {
     ) -> Result<Option<String<1024>>, xous::Error> {
         let mut ret = String::<1024>::new();
         #[cfg(not(feature = "pddbtest"))]
-        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [write] [writeover] [query] [copy] [dictdelete] [keydelete] [churn] [flush] [sync]";
+        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [write] [writeover] [query] [copy] [dictdelete] [keydelete] [churn] [flush] [sync]\n[ls] [cat] [cp] [rm]";
         #[cfg(feature = "pddbtest")]
-        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [write] [writeover] [query] [copy] [dictdelete] [keydelete] [churn] [flush] [sync]\n[test]";
+        let helpstring = "pddb [basislist] [basiscreate] [basisunlock] [basislock] [basisdelete] [default]\n[dictlist] [keylist] [write] [writeover] [query] [copy] [dictdelete] [keydelete] [churn] [flush] [sync]\n[ls] [cat] [cp] [rm]\n[test]";
 
         let mut tokens = args.as_str().unwrap().split(' ');
         if let Some(sub_cmd) = tokens.next() {
@@ -217,7 +217,7 @@ impl<'a> ShellCmdApi<'a> for PddbCmd {
                         }
                     })()
                 }
-                "copy" => (|| {
+                "copy" | "cp" => (|| {
                     let Some(srcdescriptor) = tokens.next() else {
                         write!(ret, "Usage is copy 'dict:key' 'dict:key' (missing destination)").unwrap();
                         return;
@@ -314,7 +314,7 @@ impl<'a> ShellCmdApi<'a> for PddbCmd {
                         write!(ret, "Missing query of form 'dict:key'").unwrap();
                     }
                 }
-                "keydelete" => {
+                "keydelete" | "rm" => {
                     if let Some(descriptor) = tokens.next() {
                         if let Some((dict, keyname)) = descriptor.split_once(':') {
                             match self.pddb.delete_key(dict, keyname, None) {
@@ -385,6 +385,112 @@ impl<'a> ShellCmdApi<'a> for PddbCmd {
                         write!(ret, "Missing dictionary name").unwrap();
                     }
                 }
+                "ls" => {
+                    if let Some(dict) = tokens.next() {
+                        match self.pddb.list_keys(dict, None) {
+                            Ok(mut list) => {
+                                list.sort();
+                                write!(ret, "{} keys in {}:", list.len(), dict).unwrap();
+                                for (i, keyname) in list.iter().enumerate() {
+                                    let len = self
+                                        .pddb
+                                        .get(dict, keyname, None, false, false, None, None::<fn()>)
+                                        .and_then(|key| key.attributes())
+                                        .map(|attr| attr.len);
+                                    let entry = match len {
+                                        Ok(len) => format!("\n{} ({} bytes)", keyname, len),
+                                        Err(_) => format!("\n{} (unreadable)", keyname),
+                                    };
+                                    // leave room for the note about how many keys were not shown
+                                    if ret.len() + entry.len() > 1024 - 24 {
+                                        write!(ret, "\n...and {} more", list.len() - i).ok();
+                                        break;
+                                    }
+                                    write!(ret, "{}", entry).unwrap();
+                                }
+                            }
+                            Err(e) => write!(ret, "{} does not exist or other error: {:?}", dict, e).unwrap(),
+                        }
+                    } else {
+                        match self.pddb.list_dict(None) {
+                            Ok(mut list) => {
+                                list.sort();
+                                write!(ret, "{} dicts:", list.len()).unwrap();
+                                for (i, dict) in list.iter().enumerate() {
+                                    let entry = match self.pddb.list_keys(dict, None) {
+                                        Ok(keys) => format!("\n{} ({} keys)", dict, keys.len()),
+                                        Err(_) => format!("\n{}", dict),
+                                    };
+                                    if ret.len() + entry.len() > 1024 - 24 {
+                                        write!(ret, "\n...and {} more", list.len() - i).ok();
+                                        break;
+                                    }
+                                    write!(ret, "{}", entry).unwrap();
+                                }
+                            }
+                            Err(e) => write!(ret, "Error encountered listing dictionaries: {:?}", e).unwrap(),
+                        }
+                    }
+                }
+                "cat" => (|| {
+                    let Some(descriptor) = tokens.next() else {
+                        write!(ret, "usage: pddb cat dict:key [hex|utf8] [offset]").unwrap();
+                        return;
+                    };
+                    let Some((dict, keyname)) = descriptor.split_once(':') else {
+                        write!(ret, "Key must be of form 'dict:key'").unwrap();
+                        return;
+                    };
+                    let mut view = None;
+                    let mut offset = 0u64;
+                    for token in tokens {
+                        match token {
+                            "hex" | "utf8" => view = Some(token),
+                            _ => match token.parse::<u64>() {
+                                Ok(o) => offset = o,
+                                Err(_) => {
+                                    write!(ret, "usage: pddb cat dict:key [hex|utf8] [offset]").unwrap();
+                                    return;
+                                }
+                            },
+                        }
+                    }
+                    let mut key = match self.pddb.get(dict, keyname, None, false, false, None, None::<fn()>) {
+                        Ok(key) => key,
+                        Err(e) => {
+                            write!(ret, "{}:{} not found or other error: {:?}", dict, keyname, e).unwrap();
+                            return;
+                        }
+                    };
+                    let len = key.attributes().map(|attr| attr.len as u64).unwrap_or(0);
+                    let mut readbuf = [0u8; 960];
+                    let readlen = match key.seek(SeekFrom::Start(offset)).and_then(|_| key.read(&mut readbuf))
+                    {
+                        Ok(readlen) => readlen,
+                        Err(e) => {
+                            write!(ret, "Error reading {}:{}: {:?}", dict, keyname, e).unwrap();
+                            return;
+                        }
+                    };
+                    let data = &readbuf[..readlen];
+                    // without an explicit view, show text as text and anything else as hex. The read can end
+                    // partway through a multi-byte character, which doesn't make the data any less text.
+                    let is_text = match std::str::from_utf8(data) {
+                        Ok(_) => true,
+                        Err(e) => e.error_len().is_none() && data.len() - e.valid_up_to() < 4,
+                    };
+                    let view = view.unwrap_or(if is_text { "utf8" } else { "hex" });
+                    write!(ret, "{}:{} {} of {} bytes\n", dict, keyname, offset, len).unwrap();
+                    let shown = if view == "hex" {
+                        write_hexdump(&mut ret, data, offset)
+                    } else {
+                        write_utf8(&mut ret, data)
+                    };
+                    if offset + (shown as u64) < len {
+                        write!(ret, "\n[next: pddb cat {} {} {}]", descriptor, view, offset + shown as u64)
+                            .ok();
+                    }
+                })(),
                 "dictlist" => {
                     match self.pddb.list_dict(None) {
                         Ok(list) => {
@@ -1582,6 +1688,56 @@ fn make_vector(basis_number: usize, vtype: VectorType) -> Vec<u8> {
     vector
 }
 
+/// Writes a hex dump of `data`, 8 bytes per line with a printable ASCII column, into `ret`, stopping
+/// before the buffer would overflow. Returns the number of bytes that were shown.
+fn write_hexdump(ret: &mut String<1024>, data: &[u8], offset: u64) -> usize {
+    // leave room for the pointer to the next page
+    let limit = 1024 - 64;
+    let mut shown = 0;
+    for (i, chunk) in data.chunks(gam::HEX_BYTES_PER_ROW).enumerate() {
+        let mut line = gam::hex_row(offset + (i * gam::HEX_BYTES_PER_ROW) as u64, chunk);
+        line.push('\n');
+        if ret.len() + line.len() > limit {
+            break;
+        }
+        write!(ret, "{}", line).unwrap();
+        shown += chunk.len();
+    }
+    shown
+}
+
+/// Writes `data` as text into `ret`, replacing invalid sequences, stopping before the buffer would
+/// overflow. Returns the number of bytes that were shown.
+fn write_utf8(ret: &mut String<1024>, data: &[u8]) -> usize {
+    let limit = 1024 - 64;
+    let mut shown = 0;
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (valid, invalid_len) = match std::str::from_utf8(rest) {
+            Ok(valid) => (valid, 0),
+            Err(e) => (
+                std::str::from_utf8(&rest[..e.valid_up_to()]).unwrap(),
+                // a character cut off at the end of the read is shown on the next page instead
+                e.error_len().unwrap_or(0),
+            ),
+        };
+        for c in valid.chars() {
+            if ret.len() + c.len_utf8() > limit {
+                return shown;
+            }
+            write!(ret, "{}", c).unwrap();
+            shown += c.len_utf8();
+        }
+        if invalid_len == 0 || ret.len() + 3 > limit {
+            break;
+        }
+        write!(ret, "{}", char::REPLACEMENT_CHARACTER).unwrap();
+        shown += invalid_len;
+        rest = &rest[valid.len() + invalid_len..];
+    }
+    shown
+}
+
 fn join_tokens<'a>(buf: &mut String<1024>, tokens: impl Iterator<Item = &'a str>) {
     for (i, tok) in tokens.enumerate() {
         if i == 0 {
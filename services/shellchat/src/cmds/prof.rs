@@ -0,0 +1,96 @@
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+/// Largest PID listed when no PID is given
+const PROF_MAX_PID: u8 = 64;
+/// Shown when the kernel only hands out our own counts
+const PROF_DENIED: &str = "Other processes need a kernel built with --kernel-feature message-profile";
+
+/// Shows what the kernel knows about the messages of each process, and, for processes built with a
+/// heap profiler, their heap. Run it twice and compare: a count that only ever grows while the device
+/// sits idle is a leak.
+#[derive(Debug)]
+pub struct Prof {}
+
+impl<'a> ShellCmdApi<'a> for Prof {
+    cmd_api!(prof);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        use core::fmt::Write;
+        let mut ret = String::<1024>::new();
+        let helpstring = "prof [pid]";
+
+        match args.as_str().unwrap().split(' ').next() {
+            None | Some("") => {
+                write!(ret, "PID    sent    recv srv queue out\n").unwrap();
+                let mut denied = false;
+                for pid in (1..=PROF_MAX_PID).filter_map(xous::PID::new) {
+                    let counts = xous::process_messages(pid);
+                    denied |= counts == Err(xous::Error::AccessDenied);
+                    if let Ok(m) = counts {
+                        write!(
+                            ret,
+                            "{:>3} {:>7} {:>7} {:>3} {:>5} {:>3}\n",
+                            pid.get(),
+                            m.sent,
+                            m.received,
+                            m.servers,
+                            m.queued,
+                            m.outstanding
+                        )
+                        .unwrap();
+                    }
+                }
+                if denied {
+                    write!(ret, "{}", PROF_DENIED).unwrap();
+                }
+            }
+            Some(pid) => {
+                let pid = match pid.parse::<u8>().ok().and_then(xous::PID::new) {
+                    Some(pid) => pid,
+                    None => {
+                        write!(ret, "{}", helpstring).unwrap();
+                        return Ok(Some(ret));
+                    }
+                };
+                let m = match xous::process_messages(pid) {
+                    Ok(m) => m,
+                    Err(xous::Error::AccessDenied) => {
+                        write!(ret, "{}", PROF_DENIED).unwrap();
+                        return Ok(Some(ret));
+                    }
+                    Err(_) => {
+                        write!(ret, "No process {}", pid.get()).unwrap();
+                        return Ok(Some(ret));
+                    }
+                };
+                write!(
+                    ret,
+                    "PID {}\nmessages: {} sent, {} received\nservers: {}, {} queued, {} not yet returned\n",
+                    pid.get(),
+                    m.sent,
+                    m.received,
+                    m.servers,
+                    m.queued,
+                    m.outstanding
+                )
+                .unwrap();
+                match alloc_profile::heap_stats(&env.xns, pid.get() as u32) {
+                    Ok(h) => write!(
+                        ret,
+                        "heap: {} bytes live, {} peak\n{} allocations, {} frees",
+                        h.live_bytes, h.peak_bytes, h.allocations, h.frees
+                    )
+                    .unwrap(),
+                    Err(_) => write!(ret, "heap: not profiled").unwrap(),
+                }
+            }
+        }
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,25 @@
+use core::fmt::Write;
+
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+/// `run <dict:key>` is carried out by the REPL itself, so that each step of the script is shown as it
+/// runs; see `script.rs`. The command is here so that it is listed with the others, and to explain
+/// itself when it shows up where the REPL doesn't look for it, ie in a pipe.
+#[derive(Debug)]
+pub struct Run {}
+
+impl<'a> ShellCmdApi<'a> for Run {
+    cmd_api!(run);
+
+    fn process(
+        &mut self,
+        _args: String<1024>,
+        _env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        write!(ret, "usage: run <dict:key>, on a line of its own").unwrap();
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,44 @@
+use core::fmt::Write;
+
+use optical::{Optical, ScanSource};
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+#[derive(Debug)]
+pub struct Scan {}
+impl Scan {
+    pub fn new() -> Scan { Scan {} }
+}
+
+impl<'a> ShellCmdApi<'a> for Scan {
+    cmd_api!(scan);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "scan [serial on|off] [inject <payload>]";
+
+        let optical = Optical::new(&env.xns)?;
+        let mut tokens = args.as_str().unwrap().splitn(2, ' ');
+        match (tokens.next(), tokens.next()) {
+            (Some("serial"), Some(state)) if state == "on" || state == "off" => {
+                match optical.serial_scanner(state == "on") {
+                    Ok(true) => write!(ret, "Reading scans from the USB serial port").unwrap(),
+                    Ok(false) => write!(ret, "Not reading the USB serial port").unwrap(),
+                    Err(e) => write!(ret, "Couldn't set up the serial scanner: {:?}", e).unwrap(),
+                }
+            }
+            // stands in for a scanner, to try out the apps that take scans
+            (Some("inject"), Some(payload)) => match optical.submit(ScanSource::Other, payload.as_bytes()) {
+                Ok(()) => write!(ret, "Submitted {} bytes", payload.len()).unwrap(),
+                Err(e) => write!(ret, "Couldn't submit: {:?}", e).unwrap(),
+            },
+            _ => write!(ret, "{}", helpstring).unwrap(),
+        }
+        Ok(Some(ret))
+    }
+}
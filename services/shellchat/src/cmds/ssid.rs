@@ -21,7 +21,7 @@ impl<'a> ShellCmdApi<'a> for Ssid {
         env: &mut CommonEnv,
     ) -> Result<Option<String<1024>>, xous::Error> {
         let mut ret = String::<1024>::new();
-        let helpstring = "ssid [scan]";
+        let helpstring = "ssid [scan] [survey [on <secs>|off]]";
 
         let mut tokens = args.as_str().unwrap().split(' ');
         if self.cb_id.is_none() {
@@ -47,6 +47,28 @@ impl<'a> ShellCmdApi<'a> for Ssid {
                     }
                     write!(ret, "Scan state: {:?}\n", state).unwrap();
                 }
+                "survey" => match tokens.next() {
+                    Some("on") => {
+                        let secs = tokens.next().and_then(|t| t.parse::<u32>().ok()).unwrap_or(30);
+                        env.com.set_wifi_survey(secs.saturating_mul(1000)).unwrap();
+                        write!(ret, "Surveying every {}s (10s at the least)", secs).unwrap();
+                    }
+                    Some("off") => {
+                        env.com.set_wifi_survey(0).unwrap();
+                        write!(ret, "Survey stopped").unwrap();
+                    }
+                    _ => {
+                        let (aps, age) = env.com.wifi_survey().unwrap();
+                        write!(ret, "RSSI reported in dBm:\n").unwrap();
+                        for ap in aps {
+                            write!(ret, "-{} {} ({}s ago)\n", ap.rssi, ap.name, ap.age_ms / 1000).unwrap();
+                        }
+                        match age {
+                            Some(ms) => write!(ret, "Survey refreshed {}s ago", ms / 1000).unwrap(),
+                            None => write!(ret, "No survey yet; try `ssid survey on`").unwrap(),
+                        }
+                    }
+                },
                 _ => {
                     write!(ret, "{}", helpstring).unwrap();
                 }
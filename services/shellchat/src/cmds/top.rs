@@ -0,0 +1,50 @@
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+/// Default sampling interval
+const TOP_INTERVAL_MS: usize = 1000;
+/// Number of processes listed
+const TOP_LINES: usize = 12;
+
+/// Lists the processes that were switched in the most over a sampling interval. The kernel doesn't keep
+/// time, so activations stand in for run time: a process that keeps waking up is what keeps the CPU from
+/// idling. Shellchat and the ticktimer always show up, as they take the samples.
+#[derive(Debug)]
+pub struct Top {}
+
+impl<'a> ShellCmdApi<'a> for Top {
+    cmd_api!(top);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        use core::fmt::Write;
+        let mut ret = String::<1024>::new();
+        let helpstring = "top [interval ms]";
+
+        let interval = match args.as_str().unwrap().split(' ').next() {
+            None | Some("") => TOP_INTERVAL_MS,
+            Some(ms) => match ms.parse::<usize>() {
+                Ok(ms) if ms > 0 => ms,
+                _ => {
+                    write!(ret, "{}", helpstring).unwrap();
+                    return Ok(Some(ret));
+                }
+            },
+        };
+        let before = env.ticktimer.process_activations()?;
+        env.ticktimer.sleep_ms(interval).unwrap();
+        let after = env.ticktimer.process_activations()?;
+
+        let rates = after.rates_since(&before);
+        let total = rates.iter().map(|(_, rate)| *rate).sum::<u32>().max(1);
+        write!(ret, "{} processes, {} ms sample\nPID  act/s  share\n", rates.len(), interval).unwrap();
+        for (pid, rate) in rates.iter().take(TOP_LINES) {
+            write!(ret, "{:>3} {:>6} {:>5}%\n", pid, rate, rate * 100 / total).unwrap();
+        }
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,57 @@
+use core::fmt::Write;
+
+use tor::{Tor, TorState};
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+#[derive(Debug)]
+pub struct TorCmd {}
+impl TorCmd {
+    pub fn new() -> TorCmd { TorCmd {} }
+}
+
+impl<'a> ShellCmdApi<'a> for TorCmd {
+    cmd_api!(tor);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "tor [status] [all on|off]";
+
+        let tor = match Tor::new(&env.xns) {
+            Ok(tor) => tor,
+            Err(_) => {
+                write!(ret, "Tor service is not in this image").unwrap();
+                return Ok(Some(ret));
+            }
+        };
+        let mut tokens = args.as_str().unwrap().split(' ');
+        match (tokens.next(), tokens.next()) {
+            (Some("all"), Some(setting)) if setting == "on" || setting == "off" => {
+                match tor.set_route_all(setting == "on")? {
+                    true => write!(ret, "All TCP connections go through Tor").unwrap(),
+                    false => write!(ret, "Only connections made through the SOCKS endpoint use Tor").unwrap(),
+                }
+            }
+            (Some("status"), _) | (Some(""), _) | (None, _) => {
+                match tor.state()? {
+                    (TorState::Waiting, _) => write!(ret, "Waiting for the PDDB").unwrap(),
+                    (TorState::Bootstrapping, percent) => write!(ret, "Bootstrapping, {}%", percent).unwrap(),
+                    (TorState::Ready, _) => {
+                        write!(ret, "Ready, SOCKS5 on {}", Tor::socks_proxy().addr).unwrap()
+                    }
+                    (TorState::Failed, _) => write!(ret, "Bootstrap failed, see the log").unwrap(),
+                }
+                if tor.route_all()? {
+                    write!(ret, "\nAll TCP connections go through Tor").unwrap();
+                }
+            }
+            _ => write!(ret, "{}", helpstring).unwrap(),
+        }
+        Ok(Some(ret))
+    }
+}
@@ -0,0 +1,66 @@
+use core::fmt::Write;
+
+use xous_ipc::String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+#[derive(Debug)]
+pub struct Update {}
+impl Update {
+    pub fn new() -> Update { Update {} }
+}
+
+impl<'a> ShellCmdApi<'a> for Update {
+    cmd_api!(update);
+
+    fn process(
+        &mut self,
+        args: String<1024>,
+        env: &mut CommonEnv,
+    ) -> Result<Option<String<1024>>, xous::Error> {
+        let mut ret = String::<1024>::new();
+        let helpstring = "update [check] [install] [url <manifest url>] [key <hex>] [pin]";
+
+        let updater = match updater::Updater::new(&env.xns) {
+            Ok(updater) => updater,
+            Err(_) => {
+                write!(ret, "Updater service is not in this image").unwrap();
+                return Ok(Some(ret));
+            }
+        };
+        let mut tokens = args.as_str().unwrap().split(' ');
+        match tokens.next() {
+            Some("check") => match updater.check() {
+                Ok((current, Some(available))) => {
+                    write!(ret, "Running {}\nUpdate available: {}", current, available).unwrap()
+                }
+                Ok((current, None)) => write!(ret, "Running {}\nUp to date", current).unwrap(),
+                Err(e) => write!(ret, "Check failed: {}", e).unwrap(),
+            },
+            Some("install") => {
+                updater.install()?;
+                write!(ret, "Starting update...").unwrap();
+            }
+            Some("url") => match tokens.next() {
+                Some(url) => match updater.configure(Some(url), None) {
+                    Ok(pins) => write!(ret, "Source set, {} certificate(s) pinned", pins).unwrap(),
+                    Err(e) => write!(ret, "Couldn't set source: {}", e).unwrap(),
+                },
+                None => write!(ret, "{}", helpstring).unwrap(),
+            },
+            Some("key") => match tokens.next() {
+                Some(key) => match updater.configure(None, Some(key)) {
+                    Ok(pins) => write!(ret, "Signing key set, {} certificate(s) pinned", pins).unwrap(),
+                    Err(e) => write!(ret, "Couldn't set key: {}", e).unwrap(),
+                },
+                None => write!(ret, "{}", helpstring).unwrap(),
+            },
+            Some("pin") => match updater.configure(None, None) {
+                Ok(pins) => write!(ret, "{} certificate(s) pinned", pins).unwrap(),
+                Err(e) => write!(ret, "Couldn't pin source: {}", e).unwrap(),
+            },
+            _ => write!(ret, "{}", helpstring).unwrap(),
+        }
+        Ok(Some(ret))
+    }
+}
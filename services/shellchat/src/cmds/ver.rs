@@ -37,7 +37,9 @@ impl<'a> ShellCmdApi<'a> for Ver {
                         ec_ver.extra,
                         xous::BOOKEND_END
                     );
-                    write!(ret, "EC sw tag: {}", ec_ver.to_string()).unwrap();
+                    write!(ret, "EC sw tag: {}\n", ec_ver.to_string()).unwrap();
+                    let caps = env.com.get_ec_capabilities().unwrap();
+                    write!(ret, "COM protocol: {}, features: {:08x}", caps.protocol, caps.features).unwrap();
                 }
                 "wf200" => {
                     let wf_ver = env.com.get_wf200_fw_rev().unwrap();
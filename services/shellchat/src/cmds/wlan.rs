@@ -24,6 +24,7 @@ wlan shell command:
         and password, otherwise NOP
 - leave: if joined, disconnect from AP
 - status: get wlan radio status (power state? connected? AP info?)
+- metered [on|off] ...: mark saved AP ... as metered or not; with no args, whether the connection is metered
 */
 impl<'a> ShellCmdApi<'a> for Wlan {
     cmd_api!(wlan);
@@ -36,7 +37,7 @@ impl<'a> ShellCmdApi<'a> for Wlan {
         env: &mut CommonEnv,
     ) -> Result<Option<String<1024>>, xous::Error> {
         let mut ret = String::<1024>::new();
-        let helpstring = "wlan [on] [off] [setssid ...] [setpass ...] [join] [leave] [status] [save] [known]";
+        let helpstring = "wlan [on] [off] [setssid ...] [setpass ...] [join] [leave] [status] [save] [known] [metered [on|off] ...]";
         let mut show_help = false;
 
         let mut tokens = args.as_str().unwrap().split(' ');
@@ -160,6 +161,26 @@ impl<'a> ShellCmdApi<'a> for Wlan {
                         }
                     }
                 }
+                "metered" => match tokens.next() {
+                    Some(setting) if setting == "on" || setting == "off" => {
+                        let ssid = tokens.collect::<Vec<&str>>().join(" ");
+                        if ssid.is_empty() {
+                            show_help = true;
+                        } else {
+                            let _ = match env.netmgr.set_metered(&ssid, setting == "on") {
+                                Ok(_) => write!(ret, "{} metered: {}", ssid, setting),
+                                Err(e) => write!(ret, "Error: {:?}", e),
+                            };
+                        }
+                    }
+                    Some(_) => show_help = true,
+                    None => {
+                        let _ = match env.netmgr.is_metered() {
+                            Ok(metered) => write!(ret, "connection metered: {}", metered),
+                            Err(e) => write!(ret, "Error: {:?}", e),
+                        };
+                    }
+                },
                 "join" => {
                     let _ = match env.com.wlan_join() {
                         Ok(_) => {
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+
+/// PDDB location of the persisted command history
+const HISTORY_DICT: &str = "shellchat";
+const HISTORY_KEY: &str = "history";
+/// Number of commands retained in the history
+const MAX_HISTORY: usize = 100;
+/// Number of entries shown by a bare `history` command
+const HISTORY_LIST_LEN: usize = 10;
+
+/// Shellchat's command history, persisted in the PDDB so it survives reboots.
+pub(crate) struct CommandHistory {
+    /// oldest command is at the front
+    entries: VecDeque<String>,
+    /// number of commands ever recorded, used to give each entry a stable number for `!N`
+    base: usize,
+    loaded: bool,
+}
+impl CommandHistory {
+    pub fn new() -> Self { CommandHistory { entries: VecDeque::new(), base: 0, loaded: false } }
+
+    pub fn is_loaded(&self) -> bool { self.loaded }
+
+    /// Loads the persisted history from the PDDB. Commands entered before the PDDB was mounted are kept,
+    /// and are ordered after the persisted ones.
+    pub fn load(&mut self) {
+        let pddb = pddb::Pddb::new();
+        if let Ok(mut key) = pddb.get(HISTORY_DICT, HISTORY_KEY, None, false, false, None, None::<fn()>) {
+            let mut data = Vec::new();
+            if key.read_to_end(&mut data).is_ok() {
+                let text = String::from_utf8_lossy(&data);
+                let mut persisted: VecDeque<String> =
+                    text.lines().filter(|l| !l.is_empty()).map(|l| l.to_string()).collect();
+                persisted.append(&mut self.entries);
+                self.entries = persisted;
+                while self.entries.len() > MAX_HISTORY {
+                    self.entries.pop_front();
+                }
+            }
+        }
+        self.loaded = true;
+    }
+
+    /// Writes the history into the PDDB's cache. This deliberately doesn't sync: committing every
+    /// command line to flash would wear it out, so the history is persisted with the next sync, which
+    /// happens after any command that writes to the PDDB, or when the PDDB is otherwise flushed.
+    fn save(&self) {
+        let pddb = pddb::Pddb::new();
+        let mut text = String::new();
+        for entry in self.entries.iter() {
+            text.push_str(entry);
+            text.push('\n');
+        }
+        // delete any prior contents, so a shorter history doesn't leave stale data at the end of the key
+        pddb.delete_key(HISTORY_DICT, HISTORY_KEY, None).ok();
+        match pddb.get(HISTORY_DICT, HISTORY_KEY, None, true, true, Some(text.len()), None::<fn()>) {
+            Ok(mut key) => {
+                key.write_all(text.as_bytes()).ok();
+            }
+            Err(e) => log::warn!("couldn't save command history: {:?}", e),
+        }
+    }
+
+    /// Records a command. Consecutive duplicates are only recorded once.
+    pub fn push(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || self.entries.back().map(|l| l.as_str()) == Some(line) {
+            return;
+        }
+        if self.entries.len() >= MAX_HISTORY {
+            self.entries.pop_front();
+            self.base += 1;
+        }
+        self.entries.push_back(line.to_string());
+        if self.loaded {
+            self.save();
+        }
+    }
+
+    /// Expands a `!!`, `!N` or `!prefix` history reference into the command it refers to.
+    pub fn expand(&self, reference: &str) -> Option<String> {
+        let reference = reference.strip_prefix('!')?;
+        if reference == "!" {
+            self.entries.back().cloned()
+        } else if let Ok(number) = reference.parse::<usize>() {
+            number.checked_sub(self.base + 1).and_then(|index| self.entries.get(index)).cloned()
+        } else if reference.len() > 0 {
+            self.entries.iter().rev().find(|l| l.starts_with(reference)).cloned()
+        } else {
+            None
+        }
+    }
+
+    /// Lists the most recent commands, or with a search term, the most recent commands containing it.
+    pub fn list(&self, search: Option<&str>) -> String {
+        let mut matches: Vec<String> = self
+            .entries
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, l)| search.map_or(true, |s| l.contains(s)))
+            .take(HISTORY_LIST_LEN)
+            .map(|(index, l)| format!("{}: {}", index + self.base + 1, l))
+            .collect();
+        if matches.is_empty() {
+            return "No matching history".to_string();
+        }
+        // show oldest first, so the most recent match is closest to the input line
+        matches.reverse();
+        matches.join("\n")
+    }
+}
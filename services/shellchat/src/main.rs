@@ -50,11 +50,15 @@ mod oqc_test;
 #[cfg(feature = "nettest")]
 mod nettests;
 
+mod history;
+mod script;
 #[cfg(feature = "tracking-alloc")]
 use std::alloc::System;
 
+use history::*;
 #[cfg(target_os = "xous")] // only draw "please wait" when not in hosted mode
 use locales::t;
+use script::*;
 #[cfg(feature = "tracking-alloc")]
 use tracking_allocator::{AllocationGroupId, AllocationTracker, Allocator};
 #[cfg(feature = "tts")]
@@ -142,6 +146,8 @@ struct Repl {
 
     // command environment
     env: CmdEnv,
+    // commands entered, persisted across reboots
+    cmd_history: CommandHistory,
 
     // our security token for making changes to our record on the GAM
     token: [u32; 4],
@@ -189,7 +195,8 @@ impl Repl {
             bubble_margin: Point::new(4, 4),
             bubble_radius: 4,
             bubble_space: 4,
-            env: CmdEnv::new(xns),
+            env: CmdEnv::new(xns, token.unwrap()),
+            cmd_history: CommandHistory::new(),
             token: token.unwrap(),
             #[cfg(feature = "tts")]
             tts: TtsFrontend::new(xns).unwrap(),
@@ -215,11 +222,47 @@ impl Repl {
     /// update the loop, in response to various inputs
     fn update(&mut self, was_callback: bool, init_done: bool) -> Result<(), xous::Error> {
         let debug1 = false;
+        if init_done && !self.cmd_history.is_loaded() {
+            self.cmd_history.load();
+        }
+        // `!` references re-run a command from the history
+        if let Some(local) = self.input.as_ref().filter(|l| l.starts_with('!')) {
+            match self.cmd_history.expand(local.trim()) {
+                Some(expanded) => self.input = Some(expanded),
+                None => {
+                    let input_history = History { text: local.to_string(), is_input: true };
+                    self.circular_push(input_history);
+                    self.circular_push(History { text: "No matching history".to_string(), is_input: false });
+                    self.input = None;
+                    self.redraw(init_done).expect("can't redraw");
+                    return Ok(());
+                }
+            }
+        }
         // if we had an input string, do something
         if let Some(local) = &self.input {
             let input_history = History { text: local.to_string(), is_input: true };
             self.circular_push(input_history);
         }
+        // `history [text]` lists recent commands, or searches for commands containing `text`
+        let history_search = self.input.as_ref().and_then(|local| {
+            let local = local.trim();
+            if local == "history" {
+                Some(None)
+            } else {
+                local.strip_prefix("history ").map(|s| Some(s.trim().to_string()))
+            }
+        });
+        if let Some(search) = history_search {
+            let listing = self.cmd_history.list(search.as_deref());
+            self.circular_push(History { text: listing, is_input: false });
+            self.input = None;
+            self.redraw(init_done).expect("can't redraw");
+            return Ok(());
+        }
+        if let Some(local) = &self.input {
+            self.cmd_history.push(local);
+        }
 
         // AT THIS POINT: if we have other inputs, update accordingly
         // other inputs might be, for example, events that came in from other servers that would
@@ -231,6 +274,17 @@ impl Repl {
             self.redraw(init_done).expect("can't redraw");
         }
 
+        // scripts are run directly by the REPL, so that the output of each step can be shown as it happens
+        let script_descriptor = self.input.as_ref().and_then(|local| {
+            let mut tokens = local.split_whitespace();
+            if tokens.next() == Some("run") { Some(tokens.next().unwrap_or("").to_string()) } else { None }
+        });
+        if let Some(descriptor) = script_descriptor {
+            self.input = None;
+            self.run_script(&descriptor, init_done);
+            return Ok(());
+        }
+
         let mut dirty = true;
         // take the input and pass it on to the various command parsers, and attach result
         if let Some(local) = &self.input {
@@ -285,6 +339,82 @@ impl Repl {
         Ok(())
     }
 
+    /// Executes a script of shellchat commands stored in the PDDB at `descriptor` (`dict:key`). What counts
+    /// as a failed step is set out on `Script`.
+    fn run_script(&mut self, descriptor: &str, init_done: bool) {
+        let mut script = match Script::load(descriptor) {
+            Ok(script) => script,
+            Err(e) => {
+                self.circular_push(History { text: e, is_input: false });
+                self.redraw(init_done).expect("can't redraw");
+                return;
+            }
+        };
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        let mut executed = 0;
+        let mut failures = 0;
+        // what the last command printed, for `expect` to look in
+        let mut output = String::new();
+        while let Some(step) = script.next_step() {
+            let failed = match step {
+                Ok(ScriptStep::Delay(ms)) => {
+                    tt.sleep_ms(ms as usize).ok();
+                    false
+                }
+                Ok(ScriptStep::Expect(text)) => {
+                    let missing = !output.contains(&text);
+                    if missing {
+                        self.circular_push(History { text: format!("expected: {}", text), is_input: false });
+                        self.redraw(init_done).expect("can't redraw");
+                    }
+                    missing
+                }
+                Ok(ScriptStep::Command(cmd)) => {
+                    self.circular_push(History { text: cmd.clone(), is_input: true });
+                    executed += 1;
+                    output.clear();
+                    let failed = match self
+                        .env
+                        .dispatch(Some(&mut xous_ipc::String::<1024>::from_str(&cmd)), None)
+                    {
+                        Ok(Some(res)) => {
+                            output = String::from(res.as_str().unwrap_or("UTF-8 Error"));
+                            self.circular_push(History { text: output.clone(), is_input: false });
+                            self.env.last_verb_unknown()
+                        }
+                        Ok(None) => self.env.last_verb_unknown(),
+                        Err(e) => {
+                            self.circular_push(History { text: format!("Error: {:?}", e), is_input: false });
+                            true
+                        }
+                    };
+                    self.redraw(init_done).expect("can't redraw");
+                    failed
+                }
+                Err(e) => {
+                    self.circular_push(History { text: e, is_input: false });
+                    true
+                }
+            };
+            if failed {
+                failures += 1;
+                if script.abort_on_error {
+                    self.circular_push(History {
+                        text: format!("Script {} aborted after {} commands", script.name, executed),
+                        is_input: false,
+                    });
+                    self.redraw(init_done).expect("can't redraw");
+                    return;
+                }
+            }
+        }
+        self.circular_push(History {
+            text: format!("Script {} done: {} commands, {} failed", script.name, executed, failures),
+            is_input: false,
+        });
+        self.redraw(init_done).expect("can't redraw");
+    }
+
     fn clear_area(&self) {
         self.gam
             .draw_rectangle(
@@ -403,6 +533,7 @@ fn main() -> ! {
 }
 fn wrapped_main() -> ! {
     log_server::init_wait().unwrap();
+    locales::follow_language(None);
     log::set_max_level(log::LevelFilter::Info);
     info!("my PID is {}", xous::process::id());
 
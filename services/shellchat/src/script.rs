@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+use std::io::Read;
+
+/// Largest script that can be loaded from the PDDB.
+const MAX_SCRIPT_LEN: usize = 8192;
+
+/// A single step of a script, as handed back to the REPL to execute.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ScriptStep {
+    /// A shellchat command line
+    Command(String),
+    /// Pause execution for the given number of milliseconds
+    Delay(u64),
+    /// Fail unless the output of the last command contains this text
+    Expect(String),
+}
+
+/// A list of shellchat commands stored in a PDDB key, one per line.
+///
+/// Besides regular commands, a script may contain:
+///   - blank lines and lines starting with `#`, which are ignored
+///   - `sleep <ms>`, which pauses the script. Note that `sleep` with a non-numeric argument is passed on to
+///     the regular `sleep` command.
+///   - `onerror abort` (the default) or `onerror continue`, which sets what happens when a subsequent step
+///     fails
+///   - `expect <text>`, which fails unless the output of the command before it contains `<text>`
+///
+/// A step fails if it is malformed, if it names a command that doesn't exist, if the command returns an
+/// error, or if an `expect` doesn't find its text. Most commands report their errors as text in their
+/// output, which can't be told apart from other output; a script that depends on a command working
+/// follows it with an `expect`. Scripts can't `run` other scripts.
+pub(crate) struct Script {
+    pub name: String,
+    lines: VecDeque<String>,
+    pub abort_on_error: bool,
+}
+impl Script {
+    /// Loads a script from a `dict:key` descriptor.
+    pub fn load(descriptor: &str) -> Result<Script, String> {
+        let (dict, key) = match descriptor.split_once(':') {
+            Some((dict, key)) if dict.len() > 0 && key.len() > 0 => (dict, key),
+            _ => return Err("usage: run <dict:key>".to_string()),
+        };
+        let pddb = pddb::Pddb::new();
+        let mut pddb_key = pddb
+            .get(dict, key, None, false, false, None, None::<fn()>)
+            .map_err(|e| format!("{} not found or other error: {:?}", descriptor, e))?;
+        let mut data = Vec::new();
+        pddb_key.read_to_end(&mut data).map_err(|e| format!("Error reading {}: {:?}", descriptor, e))?;
+        if data.len() > MAX_SCRIPT_LEN {
+            return Err(format!(
+                "Script {} is too long ({} > {} bytes)",
+                descriptor,
+                data.len(),
+                MAX_SCRIPT_LEN
+            ));
+        }
+        let text =
+            String::from_utf8(data).map_err(|_| format!("Script {} is not valid UTF-8", descriptor))?;
+        Ok(Script::parse(descriptor, &text))
+    }
+
+    fn parse(name: &str, text: &str) -> Script {
+        Script {
+            name: name.to_string(),
+            lines: text.lines().map(|l| l.trim().to_string()).collect(),
+            abort_on_error: true,
+        }
+    }
+
+    /// Returns the next step to execute, or `None` when the script is done. Comments and
+    /// directives are consumed here and never returned to the caller.
+    pub fn next_step(&mut self) -> Option<Result<ScriptStep, String>> {
+        while let Some(line) = self.lines.pop_front() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut tokens = line.split_whitespace();
+            match (tokens.next(), tokens.next(), tokens.next()) {
+                (Some("onerror"), Some("abort"), None) => self.abort_on_error = true,
+                (Some("onerror"), Some("continue"), None) => self.abort_on_error = false,
+                (Some("onerror"), _, _) => {
+                    return Some(Err(format!("Bad directive '{}': use `onerror abort|continue`", line)));
+                }
+                (Some("sleep"), Some(arg), None) if arg.parse::<u64>().is_ok() => {
+                    return Some(Ok(ScriptStep::Delay(arg.parse::<u64>().unwrap())));
+                }
+                (Some("expect"), Some(_), _) => {
+                    let text = line["expect".len()..].trim_start();
+                    return Some(Ok(ScriptStep::Expect(text.to_string())));
+                }
+                (Some("expect"), None, _) => {
+                    return Some(Err("Bad directive 'expect': use `expect <text>`".to_string()));
+                }
+                (Some("run"), _, _) => {
+                    return Some(Err(format!("'{}': scripts can't run other scripts", line)));
+                }
+                _ => return Some(Ok(ScriptStep::Command(line))),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(text: &str) -> Vec<Result<ScriptStep, String>> {
+        let mut script = Script::parse("test:script", text);
+        std::iter::from_fn(|| script.next_step()).collect()
+    }
+
+    fn command(line: &str) -> Result<ScriptStep, String> { Ok(ScriptStep::Command(line.to_string())) }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        assert_eq!(
+            steps("# set up\n\n  ver  \n\t# indented comment\necho a  b\n"),
+            vec![command("ver"), command("echo a  b")]
+        );
+        assert!(steps("").is_empty());
+    }
+
+    #[test]
+    fn sleep_pauses_only_with_a_number() {
+        assert_eq!(
+            steps("sleep 250\nsleep now\nsleep 1 2"),
+            vec![Ok(ScriptStep::Delay(250)), command("sleep now"), command("sleep 1 2")]
+        );
+    }
+
+    #[test]
+    fn onerror_sets_the_policy() {
+        let mut script = Script::parse("test:script", "onerror continue\nver\nonerror abort\nver");
+        assert!(script.abort_on_error);
+        assert_eq!(script.next_step(), Some(command("ver")));
+        assert!(!script.abort_on_error);
+        assert_eq!(script.next_step(), Some(command("ver")));
+        assert!(script.abort_on_error);
+        assert_eq!(script.next_step(), None);
+        assert!(steps("onerror ignore")[0].is_err());
+        assert!(steps("onerror")[0].is_err());
+    }
+
+    #[test]
+    fn expect_keeps_its_text() {
+        assert_eq!(
+            steps("wlan status\nexpect   connected to  home"),
+            vec![command("wlan status"), Ok(ScriptStep::Expect("connected to  home".to_string()))]
+        );
+        assert!(steps("expect")[0].is_err());
+        // only the directive itself, not commands that start the same way
+        assert_eq!(steps("expected"), vec![command("expected")]);
+    }
+
+    #[test]
+    fn scripts_cant_run_scripts() {
+        let steps = steps("run other:script\nver");
+        assert!(steps[0].is_err());
+        assert_eq!(steps[1], command("ver"));
+    }
+}
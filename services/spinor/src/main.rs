@@ -817,9 +817,12 @@ fn main() -> ! {
           - keystore
           - PDDB
           - keyboard (for updating the key map setting, which needs to be loaded upstream of the PDDB)
+          - counters (for the monotonic counters, which must survive the PDDB being re-created)
+          - updater (for staging downloaded kernels; root-keys is the one that installs them)
+          - status (for erasing the PDDB in a factory reset)
     */
     #[cfg(any(feature = "precursor", feature = "renode"))]
-    let spinor_sid = xns.register_name(api::SERVER_NAME_SPINOR, Some(5)).expect("can't register server");
+    let spinor_sid = xns.register_name(api::SERVER_NAME_SPINOR, Some(8)).expect("can't register server");
     #[cfg(not(target_os = "xous"))]
     let spinor_sid = xns.register_name(api::SERVER_NAME_SPINOR, None).expect("can't register server"); // hosted mode we don't care about security of the spinor server
     log::trace!("registered with NS -- {:?}", spinor_sid);
@@ -0,0 +1,557 @@
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::prelude::*;
+use gam::modal::{TextEntryPayload, ValidatorErr};
+use locales::t;
+use num_traits::*;
+use xous::{Message, msg_scalar_unpack, send_message};
+
+use crate::api::*;
+use crate::indicators::Indicators;
+
+/// PDDB location of the alarms, one per line in the format of `Alarm::to_line()`. They are kept in the
+/// system basis, so that they don't come and go with the other bases.
+const ALARM_DICT: &str = "sys.alarms";
+const ALARM_KEY: &str = "list";
+const MAX_ALARMS: usize = 8;
+/// How often the alarms are checked against the clock
+const CHECK_INTERVAL_MS: usize = 10_000;
+/// An alarm that is found to be this late, because the device was off, is skipped rather than rung
+const MISSED_GRACE_MINUTES: i64 = 30;
+const SNOOZE_MINUTES: i64 = 9;
+/// The tone and vibe stop after this long if nobody answers; the alarm stays up until dismissed
+const RING_TIMEOUT_MS: u64 = 5 * 60 * 1000;
+/// Time between repeats of the ring pattern
+const RING_PERIOD_MS: usize = 2000;
+const INDICATOR_ID: &str = "status.alarm";
+const INDICATOR_ICON: char = '\u{23F0}';
+
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub(crate) enum AlarmOp {
+    /// Compare the alarms with the clock, and ring any that are due
+    Check,
+    /// Show the alarm list, to add, change and delete alarms
+    Edit,
+    /// Suspend/resume callback; arms the RTC to wake the device for the next alarm
+    SuspendResume,
+    Quit,
+}
+
+/// An alarm at a time of day, on some days of the week or just once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Alarm {
+    hour: u32,
+    minute: u32,
+    /// bit 0 is Monday through bit 6 for Sunday; an alarm with no days rings once, and then turns off
+    days: u8,
+    enabled: bool,
+}
+impl Alarm {
+    /// The first time after `after` that the alarm rings, if it is on
+    fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        if !self.enabled {
+            return None;
+        }
+        let time = NaiveTime::from_hms_opt(self.hour, self.minute, 0)?;
+        // eight days, so that a weekly alarm whose time has just passed today is found again next week
+        (0..8)
+            .map(|d| (after.date() + chrono::Duration::days(d)).and_time(time))
+            .filter(|t| *t > after)
+            .find(|t| self.days == 0 || self.days & (1 << t.weekday().num_days_from_monday()) != 0)
+    }
+
+    /// "07:30 MTWTF--", followed by "on" or "off"
+    fn to_line(&self) -> String {
+        format!(
+            "{:02}:{:02} {} {}",
+            self.hour,
+            self.minute,
+            day_letters(self.days),
+            if self.enabled { "on" } else { "off" }
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Alarm> {
+        let mut fields = line.split_whitespace();
+        let (hour, minute) = parse_time(fields.next()?)?;
+        let days = fields
+            .next()?
+            .chars()
+            .enumerate()
+            .fold(0u8, |days, (i, c)| if c != '-' && i < 7 { days | 1 << i } else { days });
+        let enabled = fields.next()? == "on";
+        Some(Alarm { hour, minute, days, enabled })
+    }
+
+    /// The alarm as it is shown in the list
+    fn describe(&self) -> String {
+        let repeat = match self.days {
+            0 => t!("alarms.once", locales::LANG).to_string(),
+            0b111_1111 => t!("alarms.daily", locales::LANG).to_string(),
+            0b001_1111 => t!("alarms.weekdays", locales::LANG).to_string(),
+            0b110_0000 => t!("alarms.weekends", locales::LANG).to_string(),
+            days => day_letters(days),
+        };
+        let state =
+            if self.enabled { t!("alarms.on", locales::LANG) } else { t!("alarms.off", locales::LANG) };
+        format!("{:02}:{:02} {} ({})", self.hour, self.minute, repeat, state)
+    }
+}
+
+/// Turns off the once-only alarms that fell due in `(last, now]`, and tells whether any of them is to be
+/// rung: alarms more than `MISSED_GRACE_MINUTES` late are skipped. Returns (ring, changed), `changed`
+/// being set if an alarm was turned off.
+fn sweep(alarms: &mut [Alarm], last: NaiveDateTime, now: NaiveDateTime) -> (bool, bool) {
+    let missed = now - chrono::Duration::minutes(MISSED_GRACE_MINUTES);
+    let mut due = false;
+    let mut changed = false;
+    for alarm in alarms.iter_mut() {
+        if let Some(time) = alarm.next_after(last).filter(|t| *t <= now) {
+            if time > missed {
+                due = true;
+            } else {
+                log::warn!("skipping an alarm for {}, it is too late to ring it", time);
+            }
+            if alarm.days == 0 {
+                alarm.enabled = false;
+                changed = true;
+            }
+        }
+    }
+    (due, changed)
+}
+
+fn day_letters(days: u8) -> String {
+    "MTWTFSS".chars().enumerate().map(|(i, c)| if days & (1 << i) != 0 { c } else { '-' }).collect()
+}
+
+/// Parses "7:30" or "07:30"
+fn parse_time(text: &str) -> Option<(u32, u32)> {
+    let (hour, minute) = text.trim().split_once(':')?;
+    let (hour, minute) = (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?);
+    if hour < 24 && minute < 60 { Some((hour, minute)) } else { None }
+}
+
+fn time_validator(input: TextEntryPayload) -> Option<ValidatorErr> {
+    match parse_time(input.as_str()) {
+        Some(_) => None,
+        None => Some(ValidatorErr::from_str(t!("alarms.time_err", locales::LANG))),
+    }
+}
+
+struct AlarmClock {
+    pddb: pddb::Pddb,
+    pddb_poller: pddb::PddbMountPoller,
+    modals: modals::Modals,
+    indicators: Arc<Mutex<Indicators>>,
+    tt: ticktimer_server::Ticktimer,
+    localtime: llio::LocalTime,
+    /// `None` until the PDDB is mounted and the alarms are read from it
+    alarms: Option<Vec<Alarm>>,
+    /// a snoozed alarm rings again at this time
+    snoozed: Option<NaiveDateTime>,
+    /// the time of the last check; alarms that fall after it are due at the next one
+    last_check: Option<NaiveDateTime>,
+}
+impl AlarmClock {
+    fn now(&mut self) -> Option<NaiveDateTime> {
+        let ms = self.localtime.get_local_time_ms()?;
+        NaiveDateTime::from_timestamp_opt((ms / 1000) as i64, 0)
+    }
+
+    /// Reads the alarms once the PDDB is mounted; returns `false` until then
+    fn ensure_loaded(&mut self) -> bool {
+        if self.alarms.is_none() && self.pddb_poller.is_mounted_nonblocking() {
+            let mut text = String::new();
+            if let Ok(mut key) = self.pddb.get(
+                ALARM_DICT,
+                ALARM_KEY,
+                Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS),
+                false,
+                false,
+                None,
+                None::<fn()>,
+            ) {
+                key.read_to_string(&mut text).ok();
+            }
+            self.alarms = Some(text.lines().filter_map(Alarm::from_line).collect());
+            self.update_indicator();
+        }
+        self.alarms.is_some()
+    }
+
+    fn save(&mut self) {
+        let text: String = self.alarms.iter().flatten().map(|a| a.to_line() + "\n").collect();
+        let basis = Some(pddb::PDDB_DEFAULT_SYSTEM_BASIS);
+        // keys don't shrink on write, so the old list is deleted first
+        self.pddb.delete_key(ALARM_DICT, ALARM_KEY, basis).ok();
+        match self.pddb.get(ALARM_DICT, ALARM_KEY, basis, true, true, Some(text.len()), None::<fn()>) {
+            Ok(mut key) => {
+                key.write_all(text.as_bytes()).ok();
+                self.pddb.sync().ok();
+            }
+            Err(e) => log::warn!("couldn't save alarms: {:?}", e),
+        }
+        self.update_indicator();
+    }
+
+    /// The next time any alarm rings, counting a snoozed one
+    fn next_alarm(&mut self) -> Option<NaiveDateTime> {
+        let now = self.now()?;
+        let alarms = self.alarms.as_ref()?;
+        alarms.iter().filter_map(|a| a.next_after(now)).chain(self.snoozed).min()
+    }
+
+    /// Shows the hour of the next alarm in the status bar, or nothing if none is set
+    fn update_indicator(&mut self) {
+        match self.next_alarm() {
+            Some(next) => self.indicators.lock().unwrap().set(
+                Indicator {
+                    id: xous_ipc::String::from_str(INDICATOR_ID),
+                    icon: INDICATOR_ICON as u32,
+                    text: xous_ipc::String::from_str(&format!("{:02}", next.hour())),
+                    priority: u8::MAX / 2,
+                    expiry_ms: None,
+                },
+                None,
+                self.tt.elapsed_ms(),
+            ),
+            None => self.indicators.lock().unwrap().clear(INDICATOR_ID, None),
+        };
+    }
+
+    fn check(&mut self) {
+        if !self.ensure_loaded() {
+            return;
+        }
+        let now = match self.now() {
+            Some(now) => now,
+            None => return,
+        };
+        let last = self.last_check.replace(now).unwrap_or(now);
+        if now < last {
+            // the clock was set back
+            return;
+        }
+        let (ringing, changed) = sweep(self.alarms.as_deref_mut().unwrap_or_default(), last, now);
+        let due = ringing || matches!(self.snoozed, Some(t) if t <= now);
+        if changed {
+            self.save();
+        }
+        if due {
+            self.snoozed = None;
+            self.ring(now);
+        }
+        self.update_indicator();
+    }
+
+    /// Rings until the user snoozes or dismisses the alarm
+    fn ring(&mut self, now: NaiveDateTime) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let ringer = std::thread::spawn({
+            let stop = stop.clone();
+            move || ring_until(stop)
+        });
+        let snooze = t!("alarms.snooze", locales::LANG);
+        self.modals.add_list_item(snooze).ok();
+        self.modals.add_list_item(t!("alarms.dismiss", locales::LANG)).ok();
+        let prompt = format!("{} {:02}:{:02}", t!("alarms.ringing", locales::LANG), now.hour(), now.minute());
+        let response = self.modals.get_radiobutton(&prompt);
+        stop.store(true, Ordering::SeqCst);
+        ringer.join().ok();
+        if let Ok(choice) = response {
+            if choice == snooze {
+                // counted from when the alarm is answered, not from when it started ringing
+                self.snoozed = self.now().map(|t| t + chrono::Duration::minutes(SNOOZE_MINUTES));
+            }
+        }
+    }
+
+    /// Shows the list of alarms, and the actions on the one picked, until the user closes it
+    fn edit(&mut self) {
+        if !self.ensure_loaded() {
+            self.modals.show_notification(t!("alarms.not_mounted", locales::LANG), None).ok();
+            return;
+        }
+        loop {
+            let alarms = self.alarms.clone().unwrap_or_default();
+            let items: Vec<String> =
+                alarms.iter().enumerate().map(|(i, a)| format!("{}. {}", i + 1, a.describe())).collect();
+            for item in items.iter() {
+                self.modals.add_list_item(item).ok();
+            }
+            if alarms.len() < MAX_ALARMS {
+                self.modals.add_list_item(t!("alarms.add", locales::LANG)).ok();
+            }
+            self.modals.add_list_item(t!("alarms.close", locales::LANG)).ok();
+            let choice = match self.modals.get_radiobutton(t!("alarms.title", locales::LANG)) {
+                Ok(choice) => choice,
+                Err(_) => return,
+            };
+            if choice == t!("alarms.add", locales::LANG) {
+                let alarm = self.alarm_form(None);
+                self.alarms.get_or_insert_with(Vec::new).push(alarm);
+                self.save();
+            } else if let Some(index) = items.iter().position(|item| *item == choice) {
+                self.edit_one(index);
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn edit_one(&mut self, index: usize) {
+        let alarm = match self.alarms.as_ref().and_then(|a| a.get(index)) {
+            Some(alarm) => *alarm,
+            None => return,
+        };
+        let toggle = if alarm.enabled {
+            t!("alarms.turn_off", locales::LANG)
+        } else {
+            t!("alarms.turn_on", locales::LANG)
+        };
+        self.modals.add_list_item(toggle).ok();
+        self.modals.add_list_item(t!("alarms.change", locales::LANG)).ok();
+        self.modals.add_list_item(t!("alarms.delete", locales::LANG)).ok();
+        self.modals.add_list_item(t!("alarms.back", locales::LANG)).ok();
+        let choice = match self.modals.get_radiobutton(&alarm.describe()) {
+            Ok(choice) => choice,
+            Err(_) => return,
+        };
+        let alarms = self.alarms.get_or_insert_with(Vec::new);
+        if choice == toggle {
+            alarms[index].enabled = !alarm.enabled;
+        } else if choice == t!("alarms.change", locales::LANG) {
+            let changed = self.alarm_form(Some(alarm));
+            self.alarms.get_or_insert_with(Vec::new)[index] = changed;
+        } else if choice == t!("alarms.delete", locales::LANG) {
+            alarms.remove(index);
+        } else {
+            return;
+        }
+        self.save();
+    }
+
+    /// Asks for the time and days of an alarm, starting from `initial`. The alarm comes back turned on.
+    fn alarm_form(&self, initial: Option<Alarm>) -> Alarm {
+        let initial = initial.unwrap_or(Alarm { hour: 7, minute: 0, days: 0, enabled: true });
+        let time = format!("{:02}:{:02}", initial.hour, initial.minute);
+        let days = [
+            t!("alarms.monday", locales::LANG),
+            t!("alarms.tuesday", locales::LANG),
+            t!("alarms.wednesday", locales::LANG),
+            t!("alarms.thursday", locales::LANG),
+            t!("alarms.friday", locales::LANG),
+            t!("alarms.saturday", locales::LANG),
+            t!("alarms.sunday", locales::LANG),
+        ];
+        let mut builder = self.modals.form_builder(t!("alarms.form", locales::LANG));
+        builder.text(t!("alarms.time", locales::LANG), Some(&time), Some(time_validator));
+        for (i, day) in days.iter().enumerate() {
+            builder.checkbox(day, initial.days & (1 << i) != 0);
+        }
+        match builder.build() {
+            Ok(form) => {
+                let (hour, minute) = form
+                    .get(0)
+                    .and_then(|f| parse_time(f.as_str()))
+                    .unwrap_or((initial.hour, initial.minute));
+                let days = form
+                    .fields()
+                    .skip(1)
+                    .enumerate()
+                    .fold(0u8, |days, (i, f)| if f.is_checked() { days | 1 << i } else { days });
+                Alarm { hour, minute, days, enabled: true }
+            }
+            Err(e) => {
+                log::warn!("couldn't show the alarm form: {:?}", e);
+                initial
+            }
+        }
+    }
+
+    /// Arms the RTC to wake the device for the next alarm, or disarms it if there is none. Long delays
+    /// are rounded down, so the device may wake early; it is armed again for the rest on resume.
+    fn arm_wakeup(&mut self, llio: &llio::Llio) {
+        let wait = match (self.next_alarm(), self.now()) {
+            (Some(next), Some(now)) => (next - now).to_std().unwrap_or_default(),
+            _ => {
+                llio.clear_wakeup_alarm().ok();
+                return;
+            }
+        };
+        match llio.set_wakeup_alarm_in(wait) {
+            Ok(set) => {
+                log::info!("waking in {}s for the next alarm, due in {}s", set.as_secs(), wait.as_secs())
+            }
+            Err(e) => log::warn!("couldn't set the wakeup alarm: {:?}", e),
+        }
+    }
+}
+
+/// Plays the alarm tone and vibe until `stop` is set, or for `RING_TIMEOUT_MS`
+fn ring_until(stop: Arc<AtomicBool>) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let tt = ticktimer_server::Ticktimer::new().unwrap();
+    let haptics = llio::Haptics::new(&xns).unwrap();
+    #[cfg(not(feature = "no-codec"))]
+    let codec = codec::Codec::new(&xns).unwrap();
+    let start = tt.elapsed_ms();
+    while !stop.load(Ordering::SeqCst) && tt.elapsed_ms() - start < RING_TIMEOUT_MS {
+        #[cfg(not(feature = "no-codec"))]
+        codec
+            .play_tones(&[
+                codec::Tone::new(880, 150),
+                codec::Tone::rest(100),
+                codec::Tone::new(880, 150),
+                codec::Tone::rest(100),
+                codec::Tone::new(880, 150),
+            ])
+            .ok();
+        haptics.vibe(llio::VibePattern::Double).ok();
+        tt.sleep_ms(RING_PERIOD_MS).ok();
+    }
+    #[cfg(not(feature = "no-codec"))]
+    codec.stop_tones().ok();
+    haptics.stop().ok();
+}
+
+/// Keeps the alarms, rings them, and wakes the device from sleep for them. The list is edited from the
+/// main menu, which sends `AlarmOp::Edit`.
+pub(crate) fn alarm_server(sid: xous::SID, indicators: Arc<Mutex<Indicators>>) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let llio = llio::Llio::new(&xns);
+    let cid = xous::connect(sid).unwrap();
+    let mut susres = susres::Susres::new(
+        Some(susres::SuspendOrder::Normal),
+        &xns,
+        AlarmOp::SuspendResume.to_u32().unwrap(),
+        cid,
+    )
+    .expect("couldn't create suspend/resume object");
+    let mut clock = AlarmClock {
+        pddb: pddb::Pddb::new(),
+        pddb_poller: pddb::PddbMountPoller::new(),
+        modals: modals::Modals::new(&xns).unwrap(),
+        indicators,
+        tt: ticktimer_server::Ticktimer::new().unwrap(),
+        localtime: llio::LocalTime::new(),
+        alarms: None,
+        snoozed: None,
+        last_check: None,
+    };
+    std::thread::spawn(move || {
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        loop {
+            tt.sleep_ms(CHECK_INTERVAL_MS).ok();
+            send_message(cid, Message::new_scalar(AlarmOp::Check.to_usize().unwrap(), 0, 0, 0, 0)).ok();
+        }
+    });
+    loop {
+        let msg = xous::receive_message(sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(AlarmOp::Check) => clock.check(),
+            Some(AlarmOp::Edit) => {
+                clock.tt.sleep_ms(100).ok(); // yield for a moment to allow the previous menu to close
+                clock.edit();
+            }
+            Some(AlarmOp::SuspendResume) => msg_scalar_unpack!(msg, token, _, _, _, {
+                clock.arm_wakeup(&llio);
+                susres.suspend_until_resume(token).expect("couldn't execute suspend/resume");
+                llio.clear_wakeup_alarm().ok();
+                // whatever fell due while asleep rings now
+                clock.check();
+                // the wake may have come early, as long delays are rounded down: set the alarm again for
+                // the rest, in case the device goes back to sleep before the next suspend arms it
+                clock.arm_wakeup(&llio);
+            }),
+            Some(AlarmOp::Quit) => {
+                xous::return_scalar(msg.sender, 1).ok();
+                break;
+            }
+            None => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2024-01-01 is a Monday
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn alarm(hour: u32, minute: u32, days: u8) -> Alarm { Alarm { hour, minute, days, enabled: true } }
+
+    #[test]
+    fn next_after_follows_the_weekdays() {
+        let weekdays = alarm(7, 30, 0b001_1111);
+        // Monday before, at and after the time
+        assert_eq!(weekdays.next_after(at(1, 7, 0)), Some(at(1, 7, 30)));
+        assert_eq!(weekdays.next_after(at(1, 7, 30)), Some(at(2, 7, 30)));
+        // Friday evening skips the weekend
+        assert_eq!(weekdays.next_after(at(5, 8, 0)), Some(at(8, 7, 30)));
+        let once = alarm(6, 0, 0);
+        assert_eq!(once.next_after(at(3, 5, 0)), Some(at(3, 6, 0)));
+        assert_eq!(once.next_after(at(3, 7, 0)), Some(at(4, 6, 0)));
+        assert_eq!(Alarm { enabled: false, ..once }.next_after(at(3, 5, 0)), None);
+    }
+
+    #[test]
+    fn weekly_alarm_is_found_a_week_on() {
+        // Monday only, just after the time: next Monday is seven days and a bit away
+        let mondays = alarm(7, 30, 0b000_0001);
+        assert_eq!(mondays.next_after(at(1, 7, 31)), Some(at(8, 7, 30)));
+        assert_eq!(mondays.next_after(at(1, 23, 59)), Some(at(8, 7, 30)));
+        let sundays = alarm(0, 0, 0b100_0000);
+        assert_eq!(sundays.next_after(at(7, 0, 0)), Some(at(14, 0, 0)));
+    }
+
+    #[test]
+    fn line_round_trip() {
+        for alarm in [
+            alarm(7, 30, 0b001_1111),
+            alarm(0, 0, 0),
+            Alarm { enabled: false, ..alarm(23, 59, 0b110_0000) },
+            alarm(12, 5, 0b111_1111),
+        ] {
+            assert_eq!(Alarm::from_line(&alarm.to_line()), Some(alarm));
+        }
+        assert_eq!(alarm(7, 30, 0b001_1111).to_line(), "07:30 MTWTF-- on");
+        assert_eq!(Alarm::from_line("24:00 ------- on"), None);
+        assert_eq!(Alarm::from_line("07:30"), None);
+    }
+
+    #[test]
+    fn sweep_rings_due_alarms() {
+        let mut alarms = [alarm(7, 30, 0b111_1111)];
+        assert_eq!(sweep(&mut alarms, at(1, 7, 29), at(1, 7, 29)), (false, false));
+        assert_eq!(sweep(&mut alarms, at(1, 7, 29), at(1, 7, 30)), (true, false));
+        // already rung at the last check
+        assert_eq!(sweep(&mut alarms, at(1, 7, 30), at(1, 7, 40)), (false, false));
+        assert!(alarms[0].enabled);
+    }
+
+    #[test]
+    fn sweep_skips_missed_alarms() {
+        let mut alarms = [alarm(7, 30, 0b111_1111)];
+        // the device was off: just within the grace period it still rings, past it it doesn't
+        assert_eq!(sweep(&mut alarms, at(1, 6, 0), at(1, 7, 59)), (true, false));
+        assert_eq!(sweep(&mut alarms, at(1, 6, 0), at(1, 8, 0)), (false, false));
+    }
+
+    #[test]
+    fn sweep_turns_off_once_only_alarms() {
+        let mut alarms = [alarm(7, 30, 0), alarm(9, 0, 0)];
+        assert_eq!(sweep(&mut alarms, at(1, 7, 0), at(1, 7, 30)), (true, true));
+        assert!(!alarms[0].enabled);
+        assert!(alarms[1].enabled);
+        // a missed one is turned off too
+        assert_eq!(sweep(&mut alarms, at(1, 7, 30), at(1, 12, 0)), (false, true));
+        assert!(!alarms[1].enabled);
+    }
+}
@@ -0,0 +1,95 @@
+/// Public name of the status bar API server. This is separate from the main status server, which
+/// only admits a fixed number of trusted connections (keyboard and USB observers).
+pub const SERVER_NAME_STATUS_API: &str = "_Status bar API_";
+
+/// Maximum length of the identifier used to refer to an indicator.
+pub const INDICATOR_ID_LEN: usize = 32;
+/// Number of characters of text that are rendered next to an indicator's icon.
+pub const INDICATOR_TEXT_CHARS: usize = 2;
+/// Maximum number of indicators that are rendered at once; lower priority indicators are hidden
+/// until a slot frees up.
+pub const MAX_VISIBLE_INDICATORS: usize = 4;
+/// Maximum number of indicators tracked by the status bar at any one time.
+pub const MAX_INDICATORS: usize = 16;
+
+/// Maximum length of the name of the service or app that posts a notification.
+pub const NOTIFICATION_SOURCE_LEN: usize = 32;
+/// Maximum length of a notification's text.
+pub const NOTIFICATION_TEXT_LEN: usize = 256;
+/// Number of persistent notifications retained in the notification center. When full, the oldest
+/// notification is discarded to make room.
+pub const MAX_NOTIFICATIONS: usize = 32;
+
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub(crate) enum ApiOpcode {
+    /// Create or update an indicator (memory message, `Indicator`)
+    SetIndicator = 0,
+    /// Remove an indicator (memory message, `IndicatorId`)
+    ClearIndicator = 1,
+    /// Post a notification (memory message, `Notification`)
+    PostNotification = 2,
+    /// Clear the notifications the sender posted under a source (memory message, `NotificationSource`)
+    ClearNotifications = 3,
+    /// Start the backup flow; the user is asked to confirm on the device (scalar)
+    PrepareBackup = 4,
+    /// Whether airplane mode is on (blocking scalar, returns 1 if on)
+    AirplaneMode = 5,
+    /// Turn airplane mode on or off (blocking scalar, arg1 = 1 for on, returns the resulting state). Only
+    /// taken from the status bar's own quick settings page; anyone else just gets the current state.
+    SetAirplaneMode = 6,
+    /// Be told when airplane mode changes (memory message, `AirplaneSubscription`). There can be up to 16
+    /// subscribers.
+    SubscribeAirplaneMode = 7,
+    /// Stop being told (memory message, `AirplaneSubscription`; only the `sid` is used)
+    UnsubscribeAirplaneMode = 8,
+    /// Exit the server
+    Quit = 255,
+}
+
+/// A small status bar indicator contributed by another service: a single glyph icon followed by up
+/// to two characters of text, e.g. a lock icon with "VP" for a VPN that is up.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct Indicator {
+    /// Unique identifier for the indicator, e.g. "net.vpn". Setting an indicator with an
+    /// existing id replaces it.
+    pub id: xous_ipc::String<INDICATOR_ID_LEN>,
+    /// Unicode codepoint of the icon glyph. Invalid codepoints are rendered without an icon.
+    pub icon: u32,
+    /// Short text rendered after the icon. Only the first `INDICATOR_TEXT_CHARS` characters are shown.
+    pub text: xous_ipc::String<16>,
+    /// Higher priority indicators are drawn first, and are the last to be hidden for lack of space.
+    pub priority: u8,
+    /// If specified, the indicator is automatically removed after this many milliseconds.
+    pub expiry_ms: Option<u32>,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct IndicatorId {
+    pub id: xous_ipc::String<INDICATOR_ID_LEN>,
+}
+
+/// A notification posted to the status bar.
+///
+/// All notifications are briefly flashed in the status bar. Persistent notifications are also
+/// retained in the notification center, where they can be reviewed and cleared by the user;
+/// transient notifications are forgotten once they are no longer displayed.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct Notification {
+    /// Name of the service or app posting the notification, e.g. "vault"
+    pub source: xous_ipc::String<NOTIFICATION_SOURCE_LEN>,
+    pub text: xous_ipc::String<NOTIFICATION_TEXT_LEN>,
+    pub persistent: bool,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct NotificationSource {
+    pub source: xous_ipc::String<NOTIFICATION_SOURCE_LEN>,
+}
+
+/// A server that wants to know when airplane mode changes. It is sent a scalar message with `opcode`
+/// as its id and arg1 set to 1 when airplane mode turns on, 0 when it turns off.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct AirplaneSubscription {
+    pub sid: [u32; 4],
+    pub opcode: u32,
+}
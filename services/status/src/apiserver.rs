@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use num_traits::*;
+
+use crate::api::*;
+use crate::holder::*;
+use crate::indicators::Indicators;
+use crate::notifications::*;
+
+const AIRPLANE_INDICATOR_ID: &str = "status.airplane";
+const AIRPLANE_INDICATOR_ICON: char = '\u{2708}';
+const METERED_INDICATOR_ID: &str = "status.metered";
+const METERED_INDICATOR_ICON: char = '\u{21C5}';
+/// Each airplane mode subscriber holds a connection from the status server, so there can only be so many
+const MAX_AIRPLANE_SUBSCRIBERS: usize = 16;
+
+/// The argument of a memory message, or `None` if `msg` isn't one or doesn't hold a `$t`
+macro_rules! decode {
+    ($msg:expr, $t:ty) => {
+        match $msg.body.memory_message() {
+            Some(mem) => unsafe { xous_ipc::Buffer::from_memory_message(mem) }.to_original::<$t, _>().ok(),
+            None => None,
+        }
+    };
+}
+
+/// Handles requests from other services to contribute indicators and notifications to the status bar.
+pub(crate) fn api_server(
+    sid: xous::SID,
+    indicators: Arc<Mutex<Indicators>>,
+    notifications: Arc<Mutex<NotificationCenter>>,
+    status_cid: xous::CID,
+) {
+    let tt = ticktimer_server::Ticktimer::new().unwrap();
+    let mut localtime = llio::LocalTime::new();
+    let xns = xous_names::XousNames::new().unwrap();
+    let haptics = llio::Haptics::new(&xns).unwrap();
+    let has_motor = haptics.capabilities().map(|caps| caps.motor).unwrap_or(false);
+    // the net server takes airplane mode switches only over this connection, made at boot
+    let airplane_control = net::AirplaneControl::new(&xns).expect("can't take the airplane mode switch");
+    // airplane mode starts off on every boot
+    let mut airplane = false;
+    let mut airplane_subscribers: HashMap<[u32; 4], (xous::CID, u32)> = HashMap::new();
+    loop {
+        let msg = xous::receive_message(sid).unwrap();
+        let opcode: Option<ApiOpcode> = FromPrimitive::from_usize(msg.body.id());
+        // everything that isn't the status bar itself is told apart by its PID; a sender the kernel
+        // doesn't name can't hold anything
+        let Some(sender) = holder_of(&msg) else {
+            log::warn!("status API request {:?} from an unknown sender", opcode);
+            continue;
+        };
+        match opcode {
+            Some(ApiOpcode::SetIndicator) => {
+                let Some(indicator) = decode!(msg, Indicator) else {
+                    log::warn!("malformed SetIndicator from PID {}", sender.0);
+                    continue;
+                };
+                if !indicators.lock().unwrap().set(indicator, Some(sender), tt.elapsed_ms()) {
+                    log::warn!("PID {} can't set indicator {}", sender.0, indicator.id);
+                }
+            }
+            Some(ApiOpcode::ClearIndicator) => {
+                let Some(indicator_id) = decode!(msg, IndicatorId) else {
+                    log::warn!("malformed ClearIndicator from PID {}", sender.0);
+                    continue;
+                };
+                let id = indicator_id.id.as_str().unwrap_or("UTF-8 Error");
+                if !indicators.lock().unwrap().clear(id, Some(sender)) {
+                    log::warn!("PID {} can't clear indicator {}", sender.0, id);
+                }
+            }
+            Some(ApiOpcode::PostNotification) => {
+                let Some(notification) = decode!(msg, Notification) else {
+                    log::warn!("malformed PostNotification from PID {}", sender.0);
+                    continue;
+                };
+                let now = tt.elapsed_ms();
+                if notification.persistent && has_motor {
+                    // a short buzz, for notifications that wait to be reviewed
+                    haptics.vibe(llio::VibePattern::Short).ok();
+                }
+                let unread = {
+                    let mut nc = notifications.lock().unwrap();
+                    nc.post(notification, Some(sender), localtime.get_local_time_ms(), now);
+                    nc.unread()
+                };
+                update_unread_indicator(&indicators, unread, now);
+            }
+            Some(ApiOpcode::ClearNotifications) => {
+                let Some(ns) = decode!(msg, NotificationSource) else {
+                    log::warn!("malformed ClearNotifications from PID {}", sender.0);
+                    continue;
+                };
+                let unread = {
+                    let mut nc = notifications.lock().unwrap();
+                    nc.clear_source(ns.source.as_str().unwrap_or("UTF-8 Error"), Some(sender));
+                    nc.unread()
+                };
+                update_unread_indicator(&indicators, unread, tt.elapsed_ms());
+            }
+            Some(ApiOpcode::PrepareBackup) => {
+                log::info!("backup requested by {:?}", msg.sender.pid());
+                xous::send_message(
+                    status_cid,
+                    xous::Message::new_scalar(
+                        crate::StatusOpcode::PrepareBackup.to_usize().unwrap(),
+                        0,
+                        0,
+                        0,
+                        0,
+                    ),
+                )
+                .ok();
+            }
+            Some(ApiOpcode::AirplaneMode) => {
+                xous::return_scalar(msg.sender, if airplane { 1 } else { 0 }).ok();
+            }
+            Some(ApiOpcode::SetAirplaneMode) => xous::msg_blocking_scalar_unpack!(msg, on, _, _, _, {
+                let on = on != 0;
+                if sender.0 as u32 != xous::process::id() {
+                    // the switch is on the quick settings page, which the status bar serves itself
+                    log::warn!("PID {} can't switch airplane mode", sender.0);
+                } else if on != airplane {
+                    match airplane_control.set_airplane_mode(on) {
+                        Ok(()) => {
+                            airplane = on;
+                            update_airplane_indicator(&indicators, airplane, tt.elapsed_ms());
+                            for (&sid, &(cid, opcode)) in airplane_subscribers.iter() {
+                                if let Err(e) = xous::try_send_message(
+                                    cid,
+                                    xous::Message::new_scalar(opcode as usize, on as usize, 0, 0, 0),
+                                ) {
+                                    log::warn!("couldn't tell {:x?} about airplane mode: {:?}", sid, e);
+                                }
+                            }
+                        }
+                        Err(e) => log::warn!("couldn't switch airplane mode: {:?}", e),
+                    }
+                }
+                xous::return_scalar(msg.sender, if airplane { 1 } else { 0 }).ok();
+            }),
+            Some(ApiOpcode::SubscribeAirplaneMode) => {
+                let Some(sub) = decode!(msg, AirplaneSubscription) else {
+                    log::warn!("malformed SubscribeAirplaneMode from PID {}", sender.0);
+                    continue;
+                };
+                match airplane_subscribers.get_mut(&sub.sid) {
+                    Some(entry) => entry.1 = sub.opcode,
+                    None if airplane_subscribers.len() >= MAX_AIRPLANE_SUBSCRIBERS => {
+                        log::warn!("too many airplane mode subscribers, PID {} not added", sender.0)
+                    }
+                    None => match xous::connect(xous::SID::from_array(sub.sid)) {
+                        Ok(cid) => {
+                            airplane_subscribers.insert(sub.sid, (cid, sub.opcode));
+                        }
+                        Err(e) => log::warn!("couldn't connect to airplane mode subscriber: {:?}", e),
+                    },
+                }
+            }
+            Some(ApiOpcode::UnsubscribeAirplaneMode) => {
+                let Some(sub) = decode!(msg, AirplaneSubscription) else {
+                    log::warn!("malformed UnsubscribeAirplaneMode from PID {}", sender.0);
+                    continue;
+                };
+                if let Some((cid, _)) = airplane_subscribers.remove(&sub.sid) {
+                    unsafe { xous::disconnect(cid).ok() };
+                }
+            }
+            Some(ApiOpcode::Quit) => {
+                if sender.0 as u32 != xous::process::id() {
+                    log::warn!("Quit is only taken from the status bar itself");
+                    xous::return_scalar(msg.sender, 0).ok();
+                    continue;
+                }
+                xous::return_scalar(msg.sender, 1).ok();
+                break;
+            }
+            None => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+}
+
+fn update_airplane_indicator(indicators: &Arc<Mutex<Indicators>>, on: bool, now: u64) {
+    if on {
+        indicators.lock().unwrap().set(
+            Indicator {
+                id: xous_ipc::String::from_str(AIRPLANE_INDICATOR_ID),
+                icon: AIRPLANE_INDICATOR_ICON as u32,
+                text: xous_ipc::String::new(),
+                priority: u8::MAX - 1,
+                expiry_ms: None,
+            },
+            None,
+            now,
+        );
+    } else {
+        indicators.lock().unwrap().clear(AIRPLANE_INDICATOR_ID, None);
+    }
+}
+
+/// Shows that the Wi-Fi connection is metered, so background transfers are waiting for another network
+pub(crate) fn update_metered_indicator(indicators: &Arc<Mutex<Indicators>>, metered: bool, now: u64) {
+    if metered {
+        indicators.lock().unwrap().set(
+            Indicator {
+                id: xous_ipc::String::from_str(METERED_INDICATOR_ID),
+                icon: METERED_INDICATOR_ICON as u32,
+                text: xous_ipc::String::new(),
+                priority: u8::MAX / 2,
+                expiry_ms: None,
+            },
+            None,
+            now,
+        );
+    } else {
+        indicators.lock().unwrap().clear(METERED_INDICATOR_ID, None);
+    }
+}
+
+/// Shows the count of unread notifications as a status bar indicator, or removes the indicator if
+/// there is nothing unread.
+pub(crate) fn update_unread_indicator(indicators: &Arc<Mutex<Indicators>>, unread: usize, now: u64) {
+    if unread == 0 {
+        indicators.lock().unwrap().clear(UNREAD_INDICATOR_ID, None);
+    } else {
+        let count = if unread > 9 { "9+".to_string() } else { format!("{}", unread) };
+        indicators.lock().unwrap().set(
+            Indicator {
+                id: xous_ipc::String::from_str(UNREAD_INDICATOR_ID),
+                icon: UNREAD_INDICATOR_ICON as u32,
+                text: xous_ipc::String::from_str(&count),
+                priority: u8::MAX,
+                expiry_ms: None,
+            },
+            None,
+            now,
+        );
+    }
+}
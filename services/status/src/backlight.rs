@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use keyboard_backlight::api::*;
+use num_traits::*;
+use xous::{msg_scalar_unpack, send_message, Message};
+
+use crate::StatusOpcode;
+
+/// Handles requests from other services to adjust the automatic keyboard backlight. The backlight
+/// state lives in the main status loop, so requests that change it are forwarded there.
+pub(crate) fn backlight_server(sid: xous::SID, status_cid: xous::CID, duration_secs: Arc<AtomicU32>) {
+    loop {
+        let msg = xous::receive_message(sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::EnableAuto) => forward(status_cid, StatusOpcode::EnableAutomaticBacklight, 0, 0),
+            Some(Opcode::DisableAuto) => forward(status_cid, StatusOpcode::DisableAutomaticBacklight, 0, 0),
+            Some(Opcode::SetTimeout) => msg_scalar_unpack!(msg, secs, _, _, _, {
+                // takes effect the next time the backlight is lit, same as a change to the preference
+                duration_secs.store((secs as u32).clamp(1, MAX_TIMEOUT_SECS), Ordering::SeqCst);
+            }),
+            Some(Opcode::Blink) => msg_scalar_unpack!(msg, count, on_ms, _, _, {
+                forward(
+                    status_cid,
+                    StatusOpcode::BlinkBacklight,
+                    (count as u32).clamp(1, MAX_BLINKS) as usize,
+                    (on_ms as u32).clamp(MIN_BLINK_MS, MAX_BLINK_MS) as usize,
+                );
+            }),
+            Some(Opcode::Quit) => {
+                if msg.sender.pid().map(|pid| pid.get() as u32) != Some(xous::process::id()) {
+                    log::warn!("Quit from another process ignored");
+                    continue;
+                }
+                xous::return_scalar(msg.sender, 1).ok();
+                break;
+            }
+            None => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+}
+
+fn forward(status_cid: xous::CID, op: StatusOpcode, arg1: usize, arg2: usize) {
+    send_message(status_cid, Message::new_scalar(op.to_usize().unwrap(), arg1, arg2, 0, 0))
+        .map(|_| ())
+        .unwrap_or_else(|e| log::error!("couldn't forward backlight request: {:?}", e));
+}
+
+/// Starts the thread that blinks the backlight, and returns the channel to send it blinks on, as
+/// (count, on time in ms). A blink in progress is called off by the next one, which starts over.
+pub(crate) fn start_blinker(com: com::Com) -> Sender<(usize, usize)> {
+    let (tx, rx) = unbounded();
+    std::thread::spawn(move || {
+        let mut next = rx.recv().ok();
+        while let Some((count, on_ms)) = next {
+            next = blink(&com, &rx, count, on_ms);
+        }
+    });
+    tx
+}
+
+/// Blinks the backlight `count` times, then leaves it off. Returns the next blink asked for, which
+/// may have cut this one short.
+fn blink(
+    com: &com::Com,
+    rx: &Receiver<(usize, usize)>,
+    count: usize,
+    on_ms: usize,
+) -> Option<(usize, usize)> {
+    let period = Duration::from_millis(on_ms as u64);
+    for _ in 0..count {
+        for (main, secondary) in [(255, 128), (0, 0)] {
+            com.set_backlight(main, secondary).ok();
+            match rx.recv_timeout(period) {
+                Ok(next) => return Some(next),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+    rx.recv().ok()
+}
@@ -0,0 +1,100 @@
+use std::io::Write;
+
+use chrono::prelude::*;
+
+/// PDDB dictionary that holds the crash reports, one per key. Keys are numbered so that they sort
+/// oldest first.
+const CRASHLOG_DICT: &str = "sys.crashlog";
+/// Number of reports kept; the oldest are deleted to make room for new ones
+const MAX_CRASH_REPORTS: usize = 16;
+/// How often the log server is checked for new reports
+const CRASHLOG_POLL_MS: usize = 5000;
+
+fn format_report(
+    kind: log_server::api::CrashKind,
+    pid: u32,
+    text: &str,
+    version: &str,
+    local_time_ms: Option<u64>,
+    uptime_ms: u64,
+) -> String {
+    let what = match kind {
+        log_server::api::CrashKind::Watchdog => "watchdog".to_string(),
+        _ => format!("panic in PID {}", pid),
+    };
+    let time = match local_time_ms {
+        Some(ms) => chrono::DateTime::<Utc>::from_naive_utc_and_offset(
+            NaiveDateTime::from_timestamp_opt(ms as i64 / 1000, 0).unwrap_or_default(),
+            chrono::offset::Utc,
+        )
+        .format("%Y-%m-%d %H:%M ")
+        .to_string(),
+        None => String::new(),
+    };
+    format!(
+        "{}\ntime: {}+{}:{:02}:{:02}\nversion: {}\n{}\n",
+        what,
+        time,
+        uptime_ms / 3_600_000,
+        (uptime_ms / 60_000) % 60,
+        (uptime_ms / 1000) % 60,
+        version,
+        text
+    )
+}
+
+fn store_report(pddb: &pddb::Pddb, report: &str) {
+    let mut keys: Vec<u32> = pddb
+        .list_keys(CRASHLOG_DICT, None)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|k| k.parse::<u32>().ok())
+        .collect();
+    keys.sort();
+    let name = format!("{:06}", keys.last().map_or(1, |n| n + 1));
+    match pddb.get(CRASHLOG_DICT, &name, None, true, true, Some(report.len()), None::<fn()>) {
+        Ok(mut key) => {
+            key.write_all(report.as_bytes()).ok();
+        }
+        Err(e) => {
+            log::warn!("couldn't store crash report: {:?}", e);
+            return;
+        }
+    }
+    // make room, counting the report just stored
+    while keys.len() + 1 > MAX_CRASH_REPORTS {
+        let oldest = keys.remove(0);
+        pddb.delete_key(CRASHLOG_DICT, &format!("{:06}", oldest), None).ok();
+    }
+    pddb.sync().ok();
+}
+
+/// Moves the crash reports held by the log server into the PDDB, so that failures in the field can be
+/// diagnosed after the fact. Panics carry the panic message and where it happened; Xous doesn't unwind,
+/// so there is no deeper backtrace. Reports made before the PDDB is mounted wait in the log server.
+pub(crate) fn crashlog_thread() {
+    let pddb = pddb::Pddb::new();
+    let tt = ticktimer_server::Ticktimer::new().unwrap();
+    let mut localtime = llio::LocalTime::new();
+    pddb.is_mounted_blocking();
+    let version = tt.get_version().lines().next().unwrap_or_default().to_string();
+    loop {
+        let mut report = None;
+        log_server::take_crash_report(|kind, pid, text| {
+            report = Some(format_report(
+                kind,
+                pid,
+                text,
+                &version,
+                localtime.get_local_time_ms(),
+                tt.elapsed_ms(),
+            ));
+        })
+        .ok();
+        match report {
+            // check again right away, in case several crashes are waiting
+            Some(report) => store_report(&pddb, &report),
+            None => tt.sleep_ms(CRASHLOG_POLL_MS).unwrap(),
+        }
+    }
+}
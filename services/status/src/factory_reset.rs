@@ -0,0 +1,132 @@
+use std::sync::{Arc, Mutex};
+
+use keyboard::KeyMap;
+use locales::t;
+use root_keys::api::RootkeyResult;
+use root_keys::RootKeys;
+
+/// The word the user has to type to get past the second confirmation. It is not translated; the prompt
+/// spells it out.
+const CONFIRM_WORD: &str = "ERASE";
+
+fn confirm(modals: &modals::Modals, prompt: &str) -> bool {
+    modals.add_list_item(t!("rootkeys.gwup.yes", locales::LANG)).expect("couldn't build radio item list");
+    modals.add_list_item(t!("rootkeys.gwup.no", locales::LANG)).expect("couldn't build radio item list");
+    match modals.get_radiobutton(prompt) {
+        Ok(response) => response.as_str() == t!("rootkeys.gwup.yes", locales::LANG),
+        _ => false,
+    }
+}
+
+fn report_line(report: &mut String, step: &str, ok: Option<bool>) {
+    let mark = match ok {
+        Some(true) => "✔",
+        Some(false) => "✘",
+        None => "-",
+    };
+    report.push_str(&format!("\n{} {}", mark, step));
+}
+
+/// Walks the user through returning the device to its out-of-box state, then reboots. The steps are
+/// ordered so that the only one that can refuse (the root keys, which also ask for their own typed
+/// confirmation) runs before anything is erased, and a final pass reads back what it can and reports on
+/// each step.
+///
+/// Runs on its own thread, as it blocks on modals and on long FLASH operations. `spinor` is connected at
+/// boot, as the spinor server only hands out a fixed number of connections.
+pub(crate) fn factory_reset(keys: Arc<Mutex<RootKeys>>, spinor: Arc<spinor::Spinor>) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let modals = modals::Modals::new(&xns).unwrap();
+
+    // 1st confirmation: what is about to happen
+    if !confirm(&modals, t!("reset.confirm", locales::LANG)) {
+        return;
+    }
+    // 2nd confirmation: type the confirmation word, so a stray key press can't get past the first one
+    let typed = modals
+        .alert_builder(&t!("reset.type_word", locales::LANG).replace("{word}", CONFIRM_WORD))
+        .field(None, None)
+        .build();
+    if !typed.map(|p| p.first().as_str() == CONFIRM_WORD).unwrap_or(false) {
+        modals.show_notification(t!("reset.aborted", locales::LANG), None).ok();
+        return;
+    }
+    log::info!("{}FACTORY_RESET.START,{}", xous::BOOKEND_START, xous::BOOKEND_END);
+
+    // root-keys asks for the 3rd confirmation, only if there are root keys to lose
+    let initialized = keys.lock().unwrap().is_initialized().unwrap_or(true);
+    let keys_ok = if initialized {
+        match keys.lock().unwrap().zeroize() {
+            Ok(true) => Some(true),
+            Ok(false) => {
+                modals.show_notification(t!("reset.aborted", locales::LANG), None).ok();
+                return;
+            }
+            Err(RootkeyResult::KeyError) => {
+                // the staged gateware is not a factory image; nothing has been touched yet
+                modals.show_notification(t!("reset.keys_refused", locales::LANG), None).ok();
+                return;
+            }
+            Err(e) => {
+                log::error!("root key zeroization failed: {:?}", e);
+                Some(false)
+            }
+        }
+    } else {
+        None
+    };
+
+    // stop the network, so that nothing reconnects and writes to the PDDB behind our back
+    net::NetManager::new().connection_manager_wifi_off_and_stop().ok();
+
+    // the PDDB is erased outright rather than basis by basis: this also takes out secret bases that
+    // were never unlocked, and a blank PDDB is offered for formatting on the next boot.
+    let pddb = pddb::Pddb::new();
+    if !pddb.try_unmount() {
+        log::warn!("PDDB did not unmount cleanly; erasing anyways");
+    }
+    pddb.pddb_halt();
+    let pddb_ok = match spinor.bulk_erase(xous::PDDB_LOC, xous::PDDB_LEN) {
+        Ok(()) => true,
+        Err(e) => {
+            log::error!("PDDB erase failed: {:?}", e);
+            false
+        }
+    };
+
+    // settings held outside of the PDDB: the EC's Wi-Fi state, and the early settings page
+    let mut com = com::Com::new(&xns).expect("couldn't connect to COM");
+    com.set_wifi_survey(0).ok();
+    com.wlan_leave().ok();
+    com.wlan_set_ssid("").ok();
+    com.wlan_set_pass("").ok();
+    com.wlan_set_off().ok();
+    let early_settings = early_settings::EarlySettings::new(&xns).unwrap();
+    early_settings.set_keymap(KeyMap::Qwerty.into()).ok();
+    early_settings.set_early_sleep(false).ok();
+
+    // verification pass: read back whatever can be read back
+    let settings_ok = early_settings.get_keymap().map(|m| m == usize::from(KeyMap::Qwerty)).unwrap_or(false)
+        && !early_settings.early_sleep().unwrap_or(true)
+        && com.wlan_status().map(|s| s.link_state != com_rs::LinkState::Connected).unwrap_or(false);
+
+    let mut report = t!("reset.report", locales::LANG).to_string();
+    report_line(&mut report, t!("reset.step_keys", locales::LANG), keys_ok);
+    report_line(&mut report, t!("reset.step_pddb", locales::LANG), Some(pddb_ok));
+    report_line(&mut report, t!("reset.step_settings", locales::LANG), Some(settings_ok));
+    report.push_str("\n\n");
+    report.push_str(t!("reset.reboot", locales::LANG));
+    log::info!(
+        "{}FACTORY_RESET.DONE,{:?},{},{}{}",
+        xous::BOOKEND_START,
+        keys_ok,
+        pddb_ok,
+        settings_ok,
+        xous::BOOKEND_END
+    );
+    modals.show_notification(&report, None).ok();
+
+    // a full SoC reboot, so the zeroized gateware is loaded
+    let susres = susres::Susres::new_without_hook(&xns).unwrap();
+    susres.reboot(true).expect("couldn't reboot");
+}
@@ -0,0 +1,33 @@
+use locales::t;
+use xous_ipc::Buffer;
+
+/// Asks the user whether apps may use the pins on the GPIO expansion header. The LLIO, which owns the
+/// pins, can't show dialogs itself, so it forwards each process's first claim here.
+pub(crate) fn gpio_prompt_thread() {
+    let xns = xous_names::XousNames::new().unwrap();
+    let modals = modals::Modals::new(&xns).unwrap();
+    // a private server: only the LLIO is told where it is
+    let sid = xous::create_server().unwrap();
+    llio::GpioHeader::register_approver(&xns, sid, 0).expect("couldn't register as the GPIO approver");
+    loop {
+        let mut msg = xous::receive_message(sid).unwrap();
+        let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+        let mut claim = buffer.to_original::<llio::GpioClaim, _>().unwrap();
+        let pins: Vec<String> = (0..llio::GPIO_HEADER_PINS)
+            .filter(|pin| claim.pins & (1 << pin) != 0)
+            .map(|pin| pin.to_string())
+            .collect();
+        let prompt = t!("gpio.prompt", locales::LANG)
+            .replace("{app}", claim.app.as_str().unwrap_or("?"))
+            .replace("{pid}", &claim.pid.to_string())
+            .replace("{pins}", &pins.join(", "));
+        modals.add_list_item(t!("gpio.allow", locales::LANG)).ok();
+        modals.add_list_item(t!("gpio.deny", locales::LANG)).ok();
+        claim.granted = match modals.get_radiobutton(&prompt) {
+            Ok(choice) => choice == t!("gpio.allow", locales::LANG),
+            Err(_) => false,
+        };
+        log::info!("GPIO header access for {} (PID {}) granted: {}", claim.app, claim.pid, claim.granted);
+        buffer.replace(claim).unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+use crate::api::*;
+use crate::holder::*;
+
+/// Indicator ids with this prefix belong to the status bar itself
+const OWN_PREFIX: &str = "status.";
+
+/// An indicator as tracked by the status bar, with its expiry converted to an absolute deadline.
+struct ActiveIndicator {
+    id: String,
+    /// the process that set the indicator; `None` for the status bar itself
+    owner: Option<Holder>,
+    icon: Option<char>,
+    text: String,
+    priority: u8,
+    /// deadline in ticktimer milliseconds
+    deadline: Option<u64>,
+}
+
+/// The set of indicators contributed by other services. Rendering is done by the main status loop;
+/// this just keeps the list ordered and pruned, and tracks whether it changed since the last redraw.
+///
+/// An indicator belongs to the process that set it, and only that process can replace or clear it,
+/// until it exits.
+pub(crate) struct Indicators {
+    list: Vec<ActiveIndicator>,
+    changed: bool,
+}
+impl Indicators {
+    pub(crate) fn new() -> Self { Indicators { list: Vec::new(), changed: false } }
+
+    /// Sets an indicator for `owner`, or for the status bar itself if it is `None`. Returns false if the
+    /// indicator belongs to someone else.
+    pub(crate) fn set(&mut self, indicator: Indicator, owner: Option<Holder>, now: u64) -> bool {
+        let id = indicator.id.as_str().unwrap_or("UTF-8 Error").to_string();
+        if !self.may_change(&id, owner) {
+            return false;
+        }
+        let active = ActiveIndicator {
+            owner,
+            icon: char::from_u32(indicator.icon),
+            text: indicator.text.as_str().unwrap_or("").chars().take(INDICATOR_TEXT_CHARS).collect(),
+            priority: indicator.priority,
+            deadline: indicator.expiry_ms.map(|ms| now + ms as u64),
+            id,
+        };
+        self.list.retain(|i| i.id != active.id);
+        if self.list.len() >= MAX_INDICATORS {
+            log::warn!("Indicator list full, dropping request to add {}", active.id);
+            return true;
+        }
+        // keep the list sorted by descending priority; equal priorities render in order of arrival
+        let position = self.list.iter().position(|i| i.priority < active.priority).unwrap_or(self.list.len());
+        self.list.insert(position, active);
+        self.changed = true;
+        true
+    }
+
+    /// Clears an indicator of `owner`, or of the status bar itself if it is `None`. Returns false if the
+    /// indicator belongs to someone else.
+    pub(crate) fn clear(&mut self, id: &str, owner: Option<Holder>) -> bool {
+        if !self.may_change(id, owner) {
+            return false;
+        }
+        let len = self.list.len();
+        self.list.retain(|i| i.id != id);
+        if self.list.len() != len {
+            self.changed = true;
+        }
+        true
+    }
+
+    fn may_change(&self, id: &str, owner: Option<Holder>) -> bool {
+        if owner.is_some() && id.starts_with(OWN_PREFIX) {
+            return false;
+        }
+        match self.list.iter().find(|i| i.id == id) {
+            Some(existing) => {
+                existing.owner == owner || existing.owner.map_or(false, |holder| has_exited(holder))
+            }
+            None => true,
+        }
+    }
+
+    /// Removes any indicators whose deadline has passed.
+    pub(crate) fn prune(&mut self, now: u64) {
+        let len = self.list.len();
+        self.list.retain(|i| if let Some(deadline) = i.deadline { deadline > now } else { true });
+        if self.list.len() != len {
+            self.changed = true;
+        }
+    }
+
+    /// Returns `true` if the indicator set changed since the last call.
+    pub(crate) fn take_changed(&mut self) -> bool {
+        let changed = self.changed;
+        self.changed = false;
+        changed
+    }
+
+    /// Renders the highest priority indicators into a compact string suitable for the status bar. The
+    /// status bar is also where security notes are shown, so the indicators of other processes are
+    /// shown by their icon alone: their text could pass for a note.
+    pub(crate) fn render(&self) -> String {
+        let mut s = String::new();
+        for indicator in self.list.iter().take(MAX_VISIBLE_INDICATORS) {
+            if !s.is_empty() {
+                s.push(' ');
+            }
+            if let Some(icon) = indicator.icon {
+                s.push(icon);
+            }
+            if indicator.owner.is_none() {
+                s.push_str(&indicator.text);
+            }
+        }
+        s
+    }
+}
@@ -0,0 +1,51 @@
+use locales::broadcast::*;
+
+/// Hosts the language broadcast. The language is selected by the status service, from the user
+/// preferences once the PDDB is mounted and from the preferences menu, and is pushed out to every
+/// process that follows it.
+pub(crate) fn language_server(sid: xous::SID) {
+    let my_pid = xous::process::id();
+    let mut subscribers: Vec<xous::CID> = Vec::new();
+    // `usize::MAX` until the user's selection is known; followers stay in their default language
+    let mut current = usize::MAX;
+    loop {
+        let msg = xous::receive_message(sid).unwrap();
+        match msg.body.id() {
+            LANGUAGE_OP_SUBSCRIBE => xous::msg_blocking_scalar_unpack!(msg, s0, s1, s2, s3, {
+                let subscriber = xous::SID::from_u32(s0 as u32, s1 as u32, s2 as u32, s3 as u32);
+                match xous::connect(subscriber) {
+                    Ok(cid) => subscribers.push(cid),
+                    Err(e) => log::warn!("couldn't connect to a language subscriber: {:?}", e),
+                }
+                xous::return_scalar(msg.sender, current).unwrap();
+            }),
+            LANGUAGE_OP_SET => xous::msg_scalar_unpack!(msg, index, _, _, _, {
+                if msg.sender.pid().map(|p| p.get() as u32) != Some(my_pid) {
+                    log::warn!("ignoring a language change from PID {:?}", msg.sender.pid());
+                    continue;
+                }
+                if index >= locales::LANGUAGES.len() || index == current {
+                    continue;
+                }
+                log::info!("switching language to {}", locales::LANGUAGES[index]);
+                current = index;
+                for &cid in subscribers.iter() {
+                    xous::send_message(cid, xous::Message::new_scalar(LANGUAGE_OP_CHANGED, index, 0, 0, 0))
+                        .ok();
+                }
+            }),
+            _ => log::error!("unknown language broadcast opcode: {:?}", msg),
+        }
+    }
+}
+
+/// Selects `lang` for every process that follows the language. Only works from within the status
+/// service.
+pub(crate) fn select_language(language_cid: xous::CID, lang: &str) {
+    match locales::lang_index(lang) {
+        Some(index) => {
+            xous::send_message(language_cid, xous::Message::new_scalar(LANGUAGE_OP_SET, index, 0, 0, 0)).ok();
+        }
+        None => log::warn!("no translations for language {}", lang),
+    }
+}
@@ -0,0 +1,143 @@
+#![cfg_attr(target_os = "none", no_std)]
+
+pub mod api;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+pub use api::*;
+use num_traits::*;
+use xous_ipc::Buffer;
+
+/// Client API to the status bar, for services that want to contribute content to it.
+#[derive(Debug)]
+pub struct Status {
+    conn: xous::CID,
+}
+impl Status {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        let conn =
+            xns.request_connection_blocking(api::SERVER_NAME_STATUS_API).expect("Can't connect to Status");
+        Ok(Status { conn })
+    }
+
+    /// Creates or replaces the indicator identified by `id`. `icon` is a single glyph, and only
+    /// the first two characters of `text` are shown. Indicators with a higher `priority` are drawn
+    /// leftmost and are the last to be hidden when the bar is full. If `expiry_ms` is specified,
+    /// the indicator removes itself after that many milliseconds.
+    ///
+    /// An indicator belongs to the process that set it: until that process exits, no other process can
+    /// replace or clear it, and ids starting with `status.` are kept for the status bar. The status bar
+    /// also shows security notes, so only the icon of an indicator set by another process is drawn.
+    pub fn set_indicator(
+        &self,
+        id: &str,
+        icon: char,
+        text: &str,
+        priority: u8,
+        expiry_ms: Option<u32>,
+    ) -> Result<(), xous::Error> {
+        if id.len() > INDICATOR_ID_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        let truncated: String = text.chars().take(INDICATOR_TEXT_CHARS).collect();
+        let indicator = Indicator {
+            id: xous_ipc::String::from_str(id),
+            icon: icon as u32,
+            text: xous_ipc::String::from_str(&truncated),
+            priority,
+            expiry_ms,
+        };
+        let buf = Buffer::into_buf(indicator).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, ApiOpcode::SetIndicator.to_u32().unwrap()).map(|_| ())
+    }
+
+    /// Removes the indicator identified by `id`. Removing an indicator that does not exist is not an
+    /// error.
+    pub fn clear_indicator(&self, id: &str) -> Result<(), xous::Error> {
+        if id.len() > INDICATOR_ID_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        let indicator_id = IndicatorId { id: xous_ipc::String::from_str(id) };
+        let buf = Buffer::into_buf(indicator_id).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, ApiOpcode::ClearIndicator.to_u32().unwrap()).map(|_| ())
+    }
+
+    /// Posts a notification from `source`. The status bar briefly flashes that a notification arrived;
+    /// if `persistent` is set, it is also kept in the notification center until the user clears it.
+    /// Text longer than `NOTIFICATION_TEXT_LEN` bytes is rejected.
+    pub fn post_notification(&self, source: &str, text: &str, persistent: bool) -> Result<(), xous::Error> {
+        if source.len() > NOTIFICATION_SOURCE_LEN || text.len() > NOTIFICATION_TEXT_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        let notification = Notification {
+            source: xous_ipc::String::from_str(source),
+            text: xous_ipc::String::from_str(text),
+            persistent,
+        };
+        let buf = Buffer::into_buf(notification).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, ApiOpcode::PostNotification.to_u32().unwrap()).map(|_| ())
+    }
+
+    /// Removes the notifications this process posted from `source` from the notification center, e.g.
+    /// once the user has read the messages they referred to. Notifications posted by other processes
+    /// are left alone, even if they use the same `source`.
+    pub fn clear_notifications(&self, source: &str) -> Result<(), xous::Error> {
+        if source.len() > NOTIFICATION_SOURCE_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        let ns = NotificationSource { source: xous_ipc::String::from_str(source) };
+        let buf = Buffer::into_buf(ns).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, ApiOpcode::ClearNotifications.to_u32().unwrap()).map(|_| ())
+    }
+
+    /// Starts the same backup flow as "Prepare for backup" in the main menu. The user still has to
+    /// confirm the backup on the device, so this only saves them the trip through the menu.
+    pub fn prepare_backup(&self) -> Result<(), xous::Error> {
+        xous::send_message(
+            self.conn,
+            xous::Message::new_scalar(ApiOpcode::PrepareBackup.to_usize().unwrap(), 0, 0, 0, 0),
+        )
+        .map(|_| ())
+    }
+
+    /// Whether airplane mode is on. Only the user switches it, on the quick settings page; turning it on
+    /// powers down the Wi-Fi radio and stops the connection manager, and turning it off puts both back
+    /// the way they were.
+    pub fn airplane_mode(&self) -> Result<bool, xous::Error> {
+        match xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(ApiOpcode::AirplaneMode.to_usize().unwrap(), 0, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar1(on)) => Ok(on != 0),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Asks for a scalar message with id `opcode` to be sent to `sid` whenever airplane mode changes;
+    /// see `AirplaneSubscription`. Subscribing again with the same `sid` replaces the opcode. The request
+    /// is dropped once there are 16 subscribers.
+    pub fn airplane_mode_subscribe(&self, sid: xous::SID, opcode: u32) -> Result<(), xous::Error> {
+        let sub = AirplaneSubscription { sid: sid.to_array(), opcode };
+        let buf = Buffer::into_buf(sub).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, ApiOpcode::SubscribeAirplaneMode.to_u32().unwrap()).map(|_| ())
+    }
+
+    pub fn airplane_mode_unsubscribe(&self, sid: xous::SID) -> Result<(), xous::Error> {
+        let sub = AirplaneSubscription { sid: sid.to_array(), opcode: 0 };
+        let buf = Buffer::into_buf(sub).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, ApiOpcode::UnsubscribeAirplaneMode.to_u32().unwrap()).map(|_| ())
+    }
+}
+
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Status {
+    fn drop(&mut self) {
+        // now de-allocate myself. It's unsafe because we are responsible to make sure nobody else is using
+        // the connection.
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use locales::t;
+
+/// Messages shown per page of the viewer
+const LINES_PER_PAGE: usize = 6;
+/// Longer messages are cut short, so that a page fits in the modal's prompt
+const MAX_LINE_LEN: usize = 140;
+
+/// Reads all of the messages kept in the log server's RAM buffer, oldest first.
+fn read_log() -> Vec<(log::Level, String)> {
+    let mut lines = Vec::new();
+    log_server::read_all(|level, text| lines.push((level, text.to_string()))).ok();
+    lines
+}
+
+fn format_line(level: log::Level, text: &str) -> String {
+    let mut end = text.len().min(MAX_LINE_LEN);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let level = match level {
+        log::Level::Error => "ERR",
+        log::Level::Warn => "WARN",
+        log::Level::Info => "INFO",
+        log::Level::Debug => "DBG",
+        log::Level::Trace => "TRCE",
+    };
+    format!("{} {}{}", level, &text[..end], if end < text.len() { "…" } else { "" })
+}
+
+/// Shows the recent log messages a page at a time, newest first, so that they can be reviewed without a
+/// debug UART.
+pub(crate) fn show_log(modals: &modals::Modals) {
+    let lines = read_log();
+    let mut warnings_only = false;
+    // number of pages back from the newest
+    let mut page = 0;
+    loop {
+        let shown: Vec<String> = lines
+            .iter()
+            .filter(|(level, _)| !warnings_only || *level <= log::Level::Warn)
+            .map(|(level, text)| format_line(*level, text))
+            .collect();
+        if shown.is_empty() && !warnings_only {
+            modals.show_notification(t!("logview.empty", locales::LANG), None).ok();
+            return;
+        }
+        let pages = (shown.len() + LINES_PER_PAGE - 1) / LINES_PER_PAGE;
+        page = page.min(pages.saturating_sub(1));
+        let end = shown.len() - page * LINES_PER_PAGE;
+        let text = if shown.is_empty() {
+            t!("logview.empty", locales::LANG).to_string()
+        } else {
+            format!(
+                "{}/{}\n{}",
+                pages - page,
+                pages,
+                shown[end.saturating_sub(LINES_PER_PAGE)..end].join("\n")
+            )
+        };
+
+        if page + 1 < pages {
+            modals.add_list_item(t!("logview.older", locales::LANG)).ok();
+        }
+        if page > 0 {
+            modals.add_list_item(t!("logview.newer", locales::LANG)).ok();
+        }
+        let filter_item = if warnings_only {
+            t!("logview.all_levels", locales::LANG)
+        } else {
+            t!("logview.warnings_only", locales::LANG)
+        };
+        modals.add_list_item(filter_item).ok();
+        modals.add_list_item(t!("logview.close", locales::LANG)).ok();
+        match modals.get_radiobutton(&text) {
+            Ok(choice) if choice == t!("logview.older", locales::LANG) => page += 1,
+            Ok(choice) if choice == t!("logview.newer", locales::LANG) => page -= 1,
+            Ok(choice) if choice == filter_item => {
+                warnings_only = !warnings_only;
+                page = 0;
+            }
+            _ => return,
+        }
+    }
+}
@@ -7,8 +7,21 @@ use mainmenu::*;
 mod appmenu;
 use appmenu::*;
 mod app_autogen;
+mod alarms;
+mod api;
+mod apiserver;
+mod backlight;
+mod crashlog;
 mod ecup;
+mod factory_reset;
+mod gpio_prompt;
+mod holder;
+mod indicators;
+mod language;
+mod logview;
+mod notifications;
 mod preferences;
+mod quicksettings;
 mod wifi;
 
 use core::fmt::Write;
@@ -28,9 +41,12 @@ use graphics_server::*;
 use locales::t;
 use num_traits::*;
 use root_keys::api::{BackupKeyboardLayout, BackupOp};
-use xous::{msg_scalar_unpack, send_message, Message, CID};
+use xous::{msg_blocking_scalar_unpack, msg_scalar_unpack, send_message, Message, CID};
 
-use crate::preferences::{percentage_to_db, PrefsMenuUpdateOp};
+use crate::api::*;
+use crate::indicators::Indicators;
+use crate::notifications::NotificationCenter;
+use crate::preferences::{load_key_config, percentage_to_db, PrefsMenuUpdateOp};
 
 const SERVER_NAME_STATUS_GID: &str = "_Status bar GID receiver_";
 const SERVER_NAME_STATUS: &str = "_Status_";
@@ -79,6 +95,8 @@ pub(crate) enum StatusOpcode {
     EnableAutomaticBacklight,
     /// Disables automatic backlight handling.
     DisableAutomaticBacklight,
+    /// Blinks the backlight (scalar: count, on time in ms)
+    BlinkBacklight,
     /// Reloads preference variables from PDDB. Called by preferences manager when a variable is updated.
     /// The usage may not be consistent, because this was patched in after the initial architecture was set
     /// up.
@@ -90,15 +108,66 @@ pub(crate) enum StatusOpcode {
     BatteryDisconnect,
     /// for returning wifi stats
     WifiStats,
+    /// a `com::PowerEvent` raised by the EC
+    PowerEvent,
+    /// the `thermal::ThermalLevel` has changed
+    ThermalLevel,
 
     /// Forces EC update
     ForceEcUpdate,
 
     /// Raise the preferences menu
     Preferences,
+    /// Review and clear notifications
+    Notifications,
+    /// Add, change and delete alarms
+    Alarms,
+    /// List what is keeping the device from sleeping
+    Inhibitors,
+    /// Page through the recent log messages
+    ViewLog,
+    /// The language has changed; redraw the status bar in it
+    LanguageChanged,
+    /// Guided factory reset
+    FactoryReset,
+    /// Raise the quick settings page
+    QuickSettings,
+    /// Quick setting getters (blocking scalar, returns 1 if on) and setters (blocking scalar, arg1 = 1 for
+    /// on, returns the resulting state)
+    QuickAutobacklight,
+    QuickSetAutobacklight,
+    QuickAutolock,
+    QuickSetAutolock,
     Quit,
 }
 
+/// Describes what is keeping the device from sleeping, or returns `None` if nothing is.
+fn describe_inhibitors(susres: &susres::Susres) -> Option<String> {
+    let inhibitors = susres.inhibitors().unwrap_or_default();
+    if inhibitors.is_empty() {
+        return None;
+    }
+    let mut description = t!("inhibitors.title", locales::LANG).to_string();
+    for inhibitor in inhibitors {
+        description.push('\n');
+        // the name is the one the holder was loaded with, and the PID tells apart copies of one program
+        let holder = if inhibitor.name.len() == 0 {
+            t!("inhibitors.unnamed", locales::LANG).replace("{pid}", &inhibitor.pid.to_string())
+        } else {
+            t!("inhibitors.holder", locales::LANG)
+                .replace("{name}", inhibitor.name.as_str().unwrap_or(""))
+                .replace("{pid}", &inhibitor.pid.to_string())
+        };
+        // a process that denied suspend with `set_suspendable(false)` has no handle
+        if inhibitor.handle == 0 {
+            description.push_str(&t!("inhibitors.denied", locales::LANG).replace("{holder}", &holder));
+        } else {
+            description.push_str(&format!("{}: {}", holder, inhibitor.reason));
+        }
+    }
+    Some(description)
+}
+
 static mut CB_TO_MAIN_CONN: Option<CID> = None;
 fn battstats_cb(stats: BattStats) {
     if let Some(cb_to_main_conn) = unsafe { CB_TO_MAIN_CONN } {
@@ -116,17 +185,68 @@ fn battstats_cb(stats: BattStats) {
         .unwrap();
     }
 }
+/// Posts a persistent notification on behalf of the status bar itself
+fn post_own_notification(
+    notifications: &Arc<Mutex<NotificationCenter>>,
+    indicators: &Arc<Mutex<Indicators>>,
+    localtime: &mut llio::LocalTime,
+    source: &str,
+    text: &str,
+    now: u64,
+) {
+    let unread = {
+        let mut n = notifications.lock().unwrap();
+        n.post(
+            Notification {
+                source: xous_ipc::String::from_str(source),
+                text: xous_ipc::String::from_str(text),
+                persistent: true,
+            },
+            None,
+            localtime.get_local_time_ms(),
+            now,
+        );
+        n.unread()
+    };
+    apiserver::update_unread_indicator(indicators, unread, now);
+}
+
+/// The main and secondary backlight brightness, dimmed when the device is running hot
+fn backlight_levels(thermal_level: thermal::ThermalLevel) -> (u8, u8) {
+    match thermal_level {
+        thermal::ThermalLevel::Normal | thermal::ThermalLevel::Warm => (255, 128),
+        thermal::ThermalLevel::Hot => (128, 64),
+        thermal::ThermalLevel::Critical => (64, 32),
+    }
+}
+
+fn power_event_cb(event: PowerEvent) {
+    if let Some(cb_to_main_conn) = unsafe { CB_TO_MAIN_CONN } {
+        send_message(
+            cb_to_main_conn,
+            xous::Message::new_scalar(
+                StatusOpcode::PowerEvent.to_usize().unwrap(),
+                event.to_usize().unwrap(),
+                0,
+                0,
+                0,
+            ),
+        )
+        .unwrap();
+    }
+}
 
 pub fn pump_thread(
     conn: usize,
     pump_run: Arc<AtomicBool>,
-    last_key_hit_secs: Arc<AtomicU32>,
+    autosleep_reset_secs: Arc<AtomicU32>,
     autosleep_duration_mins: Arc<AtomicU32>,
     reboot_on_autosleep: Arc<AtomicBool>,
 ) {
     let ticktimer = ticktimer_server::Ticktimer::new().unwrap();
     let xns = xous_names::XousNames::new().unwrap();
     let llio = llio::Llio::new(&xns);
+    let usb_hid = usb_device_xous::UsbHid::new();
     let mut last_power_state = llio.is_plugged_in();
     loop {
         if pump_run.load(Ordering::Relaxed) {
@@ -146,18 +266,23 @@ pub fn pump_thread(
         if last_power_state != cur_power_state {
             log::debug!("power state change detected, resetting timer");
             // power state changed. Consider this a "key press" for the purposes of auto-events.
-            last_key_hit_secs.store((ticktimer.elapsed_ms() / 1000) as u32, Ordering::SeqCst);
+            autosleep_reset_secs.store((ticktimer.elapsed_ms() / 1000) as u32, Ordering::SeqCst);
             last_power_state = cur_power_state;
         }
 
         let asdm = autosleep_duration_mins.load(Ordering::SeqCst);
         if asdm != 0 {
+            // the keyboard reports every key hit to the ticktimer; only it may, so resets are kept here
+            let reset_ms = autosleep_reset_secs.load(Ordering::SeqCst) as u64 * 1000;
+            let since_reset_ms = ticktimer.elapsed_ms().saturating_sub(reset_ms);
             let last_key_hit_duration_mins =
-                ((ticktimer.elapsed_ms() / 1000) as u32 - last_key_hit_secs.load(Ordering::SeqCst)) / 60;
+                (ticktimer.idle_ms(ticktimer_server::api::Activity::UserInput).min(since_reset_ms) / 60_000)
+                    as u32;
             if last_key_hit_duration_mins >= asdm {
                 log::debug!("autosleep duration hit, trying to sleep");
-                if cur_power_state == false {
-                    // is_plugged_in() is false
+                // a host that has gone to sleep is plugged in, but can't charge the device
+                if cur_power_state == false || usb_hid.is_host_suspended() {
+                    // is_plugged_in() is false, or the host is asleep
                     if reboot_on_autosleep.load(Ordering::SeqCst) {
                         log::info!("Autolocking...");
                         send_message(
@@ -182,6 +307,10 @@ pub fn pump_thread(
         // TODO: autounmount
     }
 }
+#[cfg(feature = "alloc-profile")]
+#[global_allocator]
+static ALLOC: alloc_profile::Profiler<std::alloc::System> = alloc_profile::Profiler::system();
+
 fn main() -> ! {
     #[cfg(not(feature = "ditherpunk"))]
     wrapped_main();
@@ -195,6 +324,8 @@ fn wrapped_main() -> ! {
     log_server::init_wait().unwrap();
     log::set_max_level(log::LevelFilter::Info);
     log::info!("my PID is {}", xous::process::id());
+    #[cfg(feature = "alloc-profile")]
+    ALLOC.serve();
 
     // ------------------ acquire the status canvas GID
     let xns = xous_names::XousNames::new().unwrap();
@@ -232,21 +363,22 @@ fn wrapped_main() -> ! {
     unsafe { CB_TO_MAIN_CONN = Some(cb_cid) };
     let pump_run = Arc::new(AtomicBool::new(false));
     // allocate shared variables for automatic timers that get polled in the pump thread
-    let last_key_hit_secs = Arc::new(AtomicU32::new(0)); // rolls over in 126 years. Can't AtomicU64 on a 32-bit platform.
+    // when autosleep was last put off by something other than a key hit, in seconds since boot
+    let autosleep_reset_secs = Arc::new(AtomicU32::new(0));
     let autosleep_duration_mins = Arc::new(AtomicU32::new(0));
     let reboot_on_autosleep = Arc::new(AtomicBool::new(false));
     let autobacklight_duration_secs = Arc::new(AtomicU32::new(0));
     let pump_conn = xous::connect(status_sid).unwrap();
     let _ = thread::spawn({
         let pump_run = pump_run.clone();
-        let last_key_hit_secs = last_key_hit_secs.clone();
+        let autosleep_reset_secs = autosleep_reset_secs.clone();
         let autosleep_duration_mins = autosleep_duration_mins.clone();
         let reboot_on_autosleep = reboot_on_autosleep.clone();
         move || {
             pump_thread(
                 pump_conn as _,
                 pump_run,
-                last_key_hit_secs,
+                autosleep_reset_secs,
                 autosleep_duration_mins,
                 reboot_on_autosleep,
             );
@@ -362,6 +494,30 @@ fn wrapped_main() -> ! {
     let sec_notes = Arc::new(Mutex::new(HashMap::new()));
     let mut last_sec_note_index = 0;
     let mut last_sec_note_size = 0;
+
+    // ------------------------ start the public API server for status bar indicators and notifications
+    let indicators = Arc::new(Mutex::new(Indicators::new()));
+    let notifications = Arc::new(Mutex::new(NotificationCenter::new()));
+    let api_sid = xns.register_name(SERVER_NAME_STATUS_API, None).unwrap();
+    let _ = thread::spawn({
+        let indicators = indicators.clone();
+        let notifications = notifications.clone();
+        let status_cid = xous::connect(status_sid).unwrap();
+        move || {
+            apiserver::api_server(api_sid, indicators, notifications, status_cid);
+        }
+    });
+    // ------------------------ start the alarm clock, which shows the next alarm as an indicator
+    let alarm_sid = xous::create_server().unwrap();
+    let alarm_cid = xous::connect(alarm_sid).unwrap();
+    let _ = thread::spawn({
+        let indicators = indicators.clone();
+        move || alarms::alarm_server(alarm_sid, indicators)
+    });
+    // ------------------------ start the language broadcast, to switch every process's language
+    let language_sid = xns.register_name(locales::broadcast::SERVER_NAME_LANGUAGE, None).unwrap();
+    let language_cid = xous::connect(language_sid).unwrap();
+    let _ = thread::spawn(move || language::language_server(language_sid));
     if !debug_locked {
         sec_notes
             .lock()
@@ -378,6 +534,9 @@ fn wrapped_main() -> ! {
             .expect("couldn't connect to root_keys to query initialization state"),
     ));
     let mut com = com::Com::new(&xns).expect("|status: can't connect to COM");
+    // only used by a factory reset, but the spinor server hands out a fixed number of connections, so claim
+    // one now
+    let spinor = Arc::new(spinor::Spinor::new(&xns).expect("couldn't connect to spinor"));
 
     // ---------------------------- build menus
     // used to hide time when the PDDB is not mounted
@@ -388,6 +547,9 @@ fn wrapped_main() -> ! {
     let main_menu_sid = xous::create_server().unwrap();
     let status_cid = xous::connect(status_sid).unwrap();
     let menu_manager = create_main_menu(keys.clone(), main_menu_sid, status_cid, &com);
+    // the language the main menu's items are named in
+    let mut menu_lang = locales::LANG.get();
+    locales::follow_language(Some((status_cid, StatusOpcode::LanguageChanged.to_usize().unwrap())));
     create_app_menu(xous::connect(status_sid).unwrap());
     let kbd = Arc::new(Mutex::new(keyboard::Keyboard::new(&xns).unwrap()));
 
@@ -409,6 +571,49 @@ fn wrapped_main() -> ! {
     let autobacklight_thread_already_running = Arc::new(Mutex::new(false));
     let thread_conn = xous::connect(status_sid).unwrap();
 
+    // ------------------------ start the public API server for the keyboard backlight
+    let backlight_sid = xns.register_name(keyboard_backlight::SERVER_NAME_KEYBOARD_BACKLIGHT, None).unwrap();
+    let _ = thread::spawn({
+        let backlight_cid = xous::connect(status_sid).unwrap();
+        let autobacklight_duration_secs = autobacklight_duration_secs.clone();
+        move || {
+            backlight::backlight_server(backlight_sid, backlight_cid, autobacklight_duration_secs);
+        }
+    });
+    let blinker = backlight::start_blinker(com::Com::new(&xns).unwrap());
+
+    // ------------------------ start the quick settings page, and put our own settings on it
+    let quick_settings_sid = xns.register_name(quick_settings::SERVER_NAME_QUICK_SETTINGS, None).unwrap();
+    let _ = thread::spawn(move || quicksettings::quick_settings_server(quick_settings_sid));
+    let quick_settings = quick_settings::QuickSettings::new(&xns).unwrap();
+    quick_settings
+        .register(
+            "status.autobacklight",
+            t!("quicksettings.autobacklight", locales::LANG),
+            status_sid,
+            StatusOpcode::QuickAutobacklight.to_u32().unwrap(),
+            StatusOpcode::QuickSetAutobacklight.to_u32().unwrap(),
+        )
+        .unwrap_or_else(|e| log::warn!("couldn't register quick setting: {:?}", e));
+    quick_settings
+        .register(
+            "status.autolock",
+            t!("quicksettings.autolock", locales::LANG),
+            status_sid,
+            StatusOpcode::QuickAutolock.to_u32().unwrap(),
+            StatusOpcode::QuickSetAutolock.to_u32().unwrap(),
+        )
+        .unwrap_or_else(|e| log::warn!("couldn't register quick setting: {:?}", e));
+    quick_settings
+        .register(
+            "status.airplane",
+            t!("quicksettings.airplane", locales::LANG),
+            api_sid,
+            ApiOpcode::AirplaneMode.to_u32().unwrap(),
+            ApiOpcode::SetAirplaneMode.to_u32().unwrap(),
+        )
+        .unwrap_or_else(|e| log::warn!("couldn't register quick setting: {:?}", e));
+
     let prefs_sid = xous::create_server().unwrap();
     let prefs_cid = xous::connect(prefs_sid).unwrap();
     preferences::start_background_thread(prefs_sid, status_cid);
@@ -681,6 +886,9 @@ fn wrapped_main() -> ! {
     log::debug!("syncing with COM");
     com.ping(0).unwrap(); // this will block until the COM is ready to take events
     com.hook_batt_stats(battstats_cb).expect("|status: couldn't hook callback for events from COM");
+    // if the EC reports the charger coming and going, there is no need to poll for it
+    let power_events = com.hook_power_events(power_event_cb).unwrap_or(false);
+    log::info!("EC power events: {}", power_events);
     // prime the loop
     com.req_batt_stats().expect("Can't get battery stats from COM");
 
@@ -688,6 +896,11 @@ fn wrapped_main() -> ! {
     log::debug!("subscribe to wifi updates");
     netmgr.wifi_state_subscribe(cb_cid, StatusOpcode::WifiStats.to_u32().unwrap()).unwrap();
     let mut wifi_status: WlanStatus = WlanStatus::from_ipc(WlanStatusIpc::default());
+    log::debug!("subscribe to thermal updates");
+    thermal::Thermal::new(&xns)
+        .and_then(|thermal| thermal.subscribe(status_sid, StatusOpcode::ThermalLevel.to_u32().unwrap()))
+        .unwrap_or_else(|e| log::warn!("couldn't subscribe to thermal updates: {:?}", e));
+    let mut thermal_level = thermal::ThermalLevel::Normal;
 
     #[cfg(feature = "tts")]
     thread::spawn({
@@ -781,6 +994,11 @@ fn wrapped_main() -> ! {
         }
     });
 
+    // moves crash reports from the log server into the PDDB once it is mounted
+    std::thread::spawn(crashlog::crashlog_thread);
+    // asks the user before apps get to use the GPIO expansion header
+    std::thread::spawn(gpio_prompt::gpio_prompt_thread);
+
     /*
     This thread handles preference loading.
     It'll wait until PDDB is ready to load stuff off the preference
@@ -790,6 +1008,7 @@ fn wrapped_main() -> ! {
         let autosleep_duration_mins = autosleep_duration_mins.clone();
         let reboot_on_autosleep = reboot_on_autosleep.clone();
         let autobacklight_duration_secs = autobacklight_duration_secs.clone();
+        let kbd = kbd.clone();
         move || {
             let pddb = pddb::Pddb::new();
             let prefs = prefs_thread_clone.lock().unwrap();
@@ -845,7 +1064,16 @@ fn wrapped_main() -> ! {
                 xous::Result::Ok
             });
 
-            // keyboard mapping is restored directly by the keyboard hardware
+            // keyboard mapping is restored directly by the keyboard hardware, but remaps and macros live in
+            // the PDDB
+            load_key_config(&kbd.lock().unwrap())
+                .unwrap_or_else(|error| log::error!("cannot load key config: {}", error));
+
+            // until now, every process has been running in the build's default language
+            if !all_prefs.language.is_empty() {
+                language::select_language(language_cid, &all_prefs.language);
+            }
+
             #[cfg(not(feature = "no-codec"))]
             {
                 log::info!("audio enable state: {}", all_prefs.audio_enabled);
@@ -1119,11 +1347,157 @@ fn wrapped_main() -> ! {
                 let buffer =
                     unsafe { xous_ipc::Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
                 wifi_status = WlanStatus::from_ipc(buffer.to_original::<com::WlanStatusIpc, _>().unwrap());
+                let metered = wifi_status.link_state == com_rs::LinkState::Connected
+                    && netmgr.is_metered().unwrap_or(false);
+                apiserver::update_metered_indicator(&indicators, metered, ticktimer.elapsed_ms());
             }
+            Some(StatusOpcode::PowerEvent) => msg_scalar_unpack!(msg, event, _, _, _, {
+                let event: PowerEvent = FromPrimitive::from_usize(event).unwrap();
+                log::info!("power event: {:?}", event);
+                let text = match event {
+                    PowerEvent::ChargerPlugged => {
+                        if !com.is_charging().unwrap_or(true) {
+                            com.request_charging().expect("couldn't send charge request");
+                        }
+                        None
+                    }
+                    PowerEvent::ChargerUnplugged => None,
+                    PowerEvent::BatteryLow => Some(t!("power.battery_low", locales::LANG)),
+                    PowerEvent::BatteryCritical => Some(t!("power.battery_critical", locales::LANG)),
+                    PowerEvent::Thermal => Some(t!("power.thermal", locales::LANG)),
+                };
+                if let Some(text) = text {
+                    post_own_notification(
+                        &notifications,
+                        &indicators,
+                        &mut localtime,
+                        t!("power.source", locales::LANG),
+                        text,
+                        ticktimer.elapsed_ms(),
+                    );
+                }
+                // show the new state right away, rather than at the next poll
+                com.req_batt_stats().expect("Can't get battery stats from COM");
+            }),
+            Some(StatusOpcode::ThermalLevel) => msg_scalar_unpack!(msg, level, _, _, _, {
+                let level: thermal::ThermalLevel = FromPrimitive::from_usize(level).unwrap();
+                let text = match level {
+                    thermal::ThermalLevel::Hot if thermal_level < level => {
+                        Some(t!("thermal.hot", locales::LANG))
+                    }
+                    thermal::ThermalLevel::Critical => Some(t!("thermal.critical", locales::LANG)),
+                    _ => None,
+                };
+                thermal_level = level;
+                if *autobacklight_thread_already_running.lock().unwrap() {
+                    // dim or restore a backlight that is already lit
+                    let (main, secondary) = backlight_levels(thermal_level);
+                    com.set_backlight(main, secondary).expect("cannot set backlight on");
+                }
+                if let Some(text) = text {
+                    post_own_notification(
+                        &notifications,
+                        &indicators,
+                        &mut localtime,
+                        t!("thermal.source", locales::LANG),
+                        text,
+                        ticktimer.elapsed_ms(),
+                    );
+                }
+            }),
             Some(StatusOpcode::Preferences) => {
                 ticktimer.sleep_ms(100).ok(); // yield for a moment to allow the previous menu to close
                 gam.raise_menu(gam::PREFERENCES_MENU_NAME).unwrap();
             }
+            Some(StatusOpcode::Notifications) => {
+                ticktimer.sleep_ms(100).ok(); // yield for a moment to allow the previous menu to close
+                let (history, is_empty) = {
+                    let mut n = notifications.lock().unwrap();
+                    n.mark_read();
+                    // leave some headroom in the modal's 1024-byte message for the title
+                    (n.format_history(960), n.is_empty())
+                };
+                apiserver::update_unread_indicator(&indicators, 0, ticktimer.elapsed_ms());
+                if is_empty {
+                    modals.show_notification(t!("notifications.empty", locales::LANG), None).ok();
+                } else {
+                    modals.show_notification(&history, None).ok();
+                    modals.add_list_item(t!("notifications.clear", locales::LANG)).ok();
+                    modals.add_list_item(t!("notifications.keep", locales::LANG)).ok();
+                    match modals.get_radiobutton(t!("notifications.clear_prompt", locales::LANG)) {
+                        Ok(response) if response.as_str() == t!("notifications.clear", locales::LANG) => {
+                            notifications.lock().unwrap().clear_all();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Some(StatusOpcode::Alarms) => {
+                send_message(
+                    alarm_cid,
+                    Message::new_scalar(alarms::AlarmOp::Edit.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .ok();
+            }
+            Some(StatusOpcode::Inhibitors) => {
+                ticktimer.sleep_ms(100).ok(); // yield for a moment to allow the previous menu to close
+                match describe_inhibitors(&susres) {
+                    Some(description) => modals.show_notification(&description, None).ok(),
+                    None => modals.show_notification(t!("inhibitors.none", locales::LANG), None).ok(),
+                };
+            }
+            Some(StatusOpcode::ViewLog) => {
+                ticktimer.sleep_ms(100).ok(); // yield for a moment to allow the previous menu to close
+                logview::show_log(&modals);
+            }
+            Some(StatusOpcode::FactoryReset) => {
+                // threaded, as the reset blocks on modals and on long FLASH operations
+                thread::spawn({
+                    let keys = keys.clone();
+                    let spinor = spinor.clone();
+                    move || factory_reset::factory_reset(keys, spinor)
+                });
+            }
+            Some(StatusOpcode::QuickSettings) => {
+                quick_settings.show().ok();
+            }
+            Some(StatusOpcode::QuickAutobacklight) => {
+                let on = *autobacklight_enabled.lock().unwrap();
+                xous::return_scalar(msg.sender, if on { 1 } else { 0 }).ok();
+            }
+            Some(StatusOpcode::QuickSetAutobacklight) => msg_blocking_scalar_unpack!(msg, on, _, _, _, {
+                // same as changing the preference: takes effect now, and on every boot
+                let on = on != 0;
+                match prefs.lock().unwrap().set_autobacklight_on_boot(on) {
+                    Ok(()) => {
+                        let op = if on {
+                            StatusOpcode::EnableAutomaticBacklight
+                        } else {
+                            StatusOpcode::DisableAutomaticBacklight
+                        };
+                        send_message(cb_cid, Message::new_scalar(op.to_usize().unwrap(), 0, 0, 0, 0)).ok();
+                        xous::return_scalar(msg.sender, if on { 1 } else { 0 }).ok();
+                    }
+                    Err(e) => {
+                        log::warn!("couldn't save the automatic backlight preference: {:?}", e);
+                        let was = *autobacklight_enabled.lock().unwrap();
+                        xous::return_scalar(msg.sender, if was { 1 } else { 0 }).ok();
+                    }
+                }
+            }),
+            Some(StatusOpcode::QuickAutolock) => {
+                let on = reboot_on_autosleep.load(Ordering::SeqCst);
+                xous::return_scalar(msg.sender, if on { 1 } else { 0 }).ok();
+            }
+            Some(StatusOpcode::QuickSetAutolock) => msg_blocking_scalar_unpack!(msg, on, _, _, _, {
+                let on = on != 0;
+                match prefs.lock().unwrap().set_reboot_on_autosleep(on) {
+                    Ok(()) => reboot_on_autosleep.store(on, Ordering::SeqCst),
+                    Err(e) => log::warn!("couldn't save the auto-lock preference: {:?}", e),
+                }
+                let on = reboot_on_autosleep.load(Ordering::SeqCst);
+                xous::return_scalar(msg.sender, if on { 1 } else { 0 }).ok();
+            }),
             Some(StatusOpcode::Pump) => {
                 let elapsed_time = ticktimer.elapsed_ms();
                 {
@@ -1154,8 +1528,18 @@ fn wrapped_main() -> ! {
 
                 // update the security status, if any
                 let (is_locked, force_update) = usb_hid.debug_usb(None).unwrap();
+                let indicators_changed = {
+                    let mut i = indicators.lock().unwrap();
+                    i.prune(elapsed_time);
+                    i.take_changed()
+                };
+                let notifications_changed = {
+                    let mut n = notifications.lock().unwrap();
+                    n.flash(elapsed_time);
+                    n.take_changed()
+                };
                 if (debug_locked != is_locked)
-                    || force_update || secnotes_force_redraw
+                    || force_update || secnotes_force_redraw || indicators_changed || notifications_changed
                     || sec_notes.lock().unwrap().len() != last_sec_note_size
                     || /*(sec_notes.lock().unwrap().len() > 1) // force the redraw periodically to clean up any tb overflow from uptime
                         &&*/ ((stats_phase % secnotes_interval) == 0)
@@ -1181,7 +1565,14 @@ fn wrapped_main() -> ! {
                     }
 
                     security_tv.clear_str();
-                    if last_sec_note_size > 0 {
+                    let indicator_str = indicators.lock().unwrap().render();
+                    if !indicator_str.is_empty() {
+                        write!(&mut security_tv, "{} | ", indicator_str).unwrap();
+                    }
+                    if let Some(flash) = notifications.lock().unwrap().flash(elapsed_time) {
+                        // a newly posted notification takes precedence over the rotating security notes
+                        write!(&mut security_tv, "{}", flash).unwrap();
+                    } else if last_sec_note_size > 0 {
                         for (index, v) in sec_notes.lock().unwrap().values().enumerate() {
                             if index == last_sec_note_index {
                                 write!(&mut security_tv, "{}", v.as_str()).unwrap();
@@ -1208,7 +1599,7 @@ fn wrapped_main() -> ! {
                 if (stats_phase % batt_interval) == (batt_interval - 1) {
                     com.req_batt_stats().expect("Can't get battery stats from COM");
                 }
-                if (stats_phase % charger_pump_interval) == 1 {
+                if !power_events && (stats_phase % charger_pump_interval) == 1 {
                     // stagger periodic tasks
                     // confirm that the charger is in the right state.
                     if stats.soc < 95 || stats.remaining_capacity < 1000 {
@@ -1320,6 +1711,13 @@ fn wrapped_main() -> ! {
                 send_message(cb_cid, Message::new_scalar(StatusOpcode::Pump.to_usize().unwrap(), 0, 0, 0, 0))
                     .expect("couldn't trigger status update");
             }
+            Some(StatusOpcode::LanguageChanged) => {
+                rename_main_menu(&menu_manager, menu_lang);
+                menu_lang = locales::LANG.get();
+                secnotes_force_redraw = true;
+                send_message(cb_cid, Message::new_scalar(StatusOpcode::Pump.to_usize().unwrap(), 0, 0, 0, 0))
+                    .expect("couldn't trigger status update");
+            }
             Some(StatusOpcode::SwitchToApp) => msg_scalar_unpack!(msg, index, _, _, _, {
                 ticktimer.sleep_ms(100).ok();
                 let app_name = app_autogen::app_index_to_name(index).expect("app index not found");
@@ -1335,19 +1733,23 @@ fn wrapped_main() -> ! {
                     .expect("couldn't trigger status update");
             }),
             Some(StatusOpcode::TrySuspend) => {
-                if llio.is_plugged_in() {
+                if llio.is_plugged_in() && !usb_hid.is_host_suspended() {
                     modals
                         .show_notification(t!("mainmenu.cant_sleep", locales::LANG), None)
                         .expect("couldn't notify that power is plugged in");
                 } else {
-                    // reset the last key hit timer, so that when we wake up we get a full timeout period
-                    last_key_hit_secs.store((ticktimer.elapsed_ms() / 1000) as u32, Ordering::SeqCst);
+                    // reset the autosleep timer, so that when we wake up we get a full timeout period
+                    autosleep_reset_secs.store((ticktimer.elapsed_ms() / 1000) as u32, Ordering::SeqCst);
                     // log::set_max_level(log::LevelFilter::Debug);
                     match susres.initiate_suspend() {
                         Ok(_) => {}
-                        Err(xous::Error::Timeout) => {
-                            modals.show_notification(t!("suspend.fail", locales::LANG), None).unwrap();
-                        }
+                        Err(xous::Error::Timeout) => match describe_inhibitors(&susres) {
+                            // say what is holding the device awake, rather than asking for a retry
+                            Some(description) => modals.show_notification(&description, None).unwrap(),
+                            None => {
+                                modals.show_notification(t!("suspend.fail", locales::LANG), None).unwrap()
+                            }
+                        },
                         Err(_e) => {
                             panic!("Unhandled error on suspend request");
                         }
@@ -1403,9 +1805,6 @@ fn wrapped_main() -> ! {
             }
 
             Some(StatusOpcode::Keypress) => {
-                // this will roll over in 126 years of uptime. meh?
-                last_key_hit_secs.store((ticktimer.elapsed_ms() / 1000) as u32, Ordering::SeqCst);
-
                 if !*autobacklight_enabled.lock().unwrap() {
                     log::trace!("ignoring keypress, automatic backlight is disabled");
                     continue;
@@ -1428,7 +1827,8 @@ fn wrapped_main() -> ! {
                             10
                         };
 
-                        com.set_backlight(255, 128).expect("cannot set backlight on");
+                        let (main, secondary) = backlight_levels(thermal_level);
+                        com.set_backlight(main, secondary).expect("cannot set backlight on");
                         std::thread::spawn({
                             let rx = rx.clone();
                             move || turn_lights_on(rx, thread_conn, abl_timeout)
@@ -1436,9 +1836,18 @@ fn wrapped_main() -> ! {
                     }
                 }
             }
+            Some(StatusOpcode::BlinkBacklight) => msg_scalar_unpack!(msg, count, on_ms, _, _, {
+                if *autobacklight_thread_already_running.lock().unwrap() {
+                    // the timer thread turns the lights off as it exits, and is restarted by the next
+                    // keypress
+                    tx.send(BacklightThreadOps::Stop).unwrap();
+                }
+                blinker.send((count, on_ms)).ok();
+            }),
             Some(StatusOpcode::TurnLightsOn) => {
                 log::trace!("turning lights on");
-                com.set_backlight(255, 128).expect("cannot set backlight on");
+                let (main, secondary) = backlight_levels(thermal_level);
+                com.set_backlight(main, secondary).expect("cannot set backlight on");
             }
             Some(StatusOpcode::TurnLightsOff) => {
                 log::trace!("turning lights off");
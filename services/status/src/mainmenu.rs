@@ -63,6 +63,46 @@ pub fn create_main_menu(
         close_on_select: true,
     });
 
+    menuitems.push(MenuItem {
+        name: String::from_str(t!("mainmenu.quicksettings", locales::LANG)),
+        action_conn: Some(status_conn),
+        action_opcode: StatusOpcode::QuickSettings.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+
+    menuitems.push(MenuItem {
+        name: String::from_str(t!("mainmenu.notifications", locales::LANG)),
+        action_conn: Some(status_conn),
+        action_opcode: StatusOpcode::Notifications.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+
+    menuitems.push(MenuItem {
+        name: String::from_str(t!("mainmenu.alarms", locales::LANG)),
+        action_conn: Some(status_conn),
+        action_opcode: StatusOpcode::Alarms.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+
+    menuitems.push(MenuItem {
+        name: String::from_str(t!("mainmenu.inhibitors", locales::LANG)),
+        action_conn: Some(status_conn),
+        action_opcode: StatusOpcode::Inhibitors.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+
+    menuitems.push(MenuItem {
+        name: String::from_str(t!("mainmenu.view_log", locales::LANG)),
+        action_conn: Some(status_conn),
+        action_opcode: StatusOpcode::ViewLog.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+
     let key_init = keys.lock().unwrap().is_initialized().unwrap();
     if !key_init {
         menuitems.push(MenuItem {
@@ -113,6 +153,22 @@ pub fn create_main_menu(
             action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
             close_on_select: true,
         });
+
+        menuitems.push(MenuItem {
+            name: String::from_str(t!("mainmenu.attest", locales::LANG)),
+            action_conn: Some(key_conn),
+            action_opcode: keys.lock().unwrap().get_attest_op(),
+            action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+            close_on_select: true,
+        });
+
+        menuitems.push(MenuItem {
+            name: String::from_str(t!("mainmenu.import_key", locales::LANG)),
+            action_conn: Some(key_conn),
+            action_opcode: keys.lock().unwrap().get_import_key_op(),
+            action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+            close_on_select: true,
+        });
     }
 
     menuitems.push(MenuItem {
@@ -144,6 +200,14 @@ pub fn create_main_menu(
         });
     }
 
+    menuitems.push(MenuItem {
+        name: String::from_str(t!("mainmenu.factory_reset", locales::LANG)),
+        action_conn: Some(status_conn),
+        action_opcode: StatusOpcode::FactoryReset.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+
     menuitems.push(MenuItem {
         name: String::from_str(t!("mainmenu.lockdevice", locales::LANG)),
         action_conn: Some(status_conn),
@@ -169,3 +233,38 @@ pub fn create_main_menu(
 
     menu_matic(menuitems, MAIN_MENU_NAME, Some(menu_management_sid)).unwrap()
 }
+
+/// Renames the items of the main menu from `old_lang` into the language that was just selected.
+pub fn rename_main_menu(menu: &MenuMatic, old_lang: &str) {
+    // items that aren't in the menu, like the backlight items, are skipped by the menu
+    macro_rules! rename {
+        ($($key:tt),* $(,)?) => {
+            $( menu.rename_item(t!($key, old_lang), t!($key, locales::LANG)); )*
+        };
+    }
+    rename!(
+        "mainmenu.backlighton",
+        "mainmenu.backlightoff",
+        "mainmenu.sleep",
+        "mainmenu.app",
+        "mainmenu.preferences",
+        "mainmenu.quicksettings",
+        "mainmenu.notifications",
+        "mainmenu.alarms",
+        "mainmenu.inhibitors",
+        "mainmenu.view_log",
+        "mainmenu.init_keys",
+        "mainmenu.provision_gateware",
+        "mainmenu.force_ecup",
+        "mainmenu.selfsign",
+        "mainmenu.attest",
+        "mainmenu.import_key",
+        "mainmenu.pddb",
+        "mainmenu.backup_key",
+        "mainmenu.prep_backup",
+        "mainmenu.factory_reset",
+        "mainmenu.lockdevice",
+        "mainmenu.battery_disconnect",
+        "mainmenu.closemenu",
+    );
+}
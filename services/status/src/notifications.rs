@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+
+use chrono::prelude::*;
+use locales::t;
+
+use crate::api::*;
+use crate::holder::{has_exited, Holder};
+
+/// How long a newly posted notification is flashed in the status bar.
+pub(crate) const NOTIFICATION_FLASH_MS: u64 = 5000;
+/// Indicator id used to show the count of unread notifications.
+pub(crate) const UNREAD_INDICATOR_ID: &str = "status.notifications";
+/// Envelope glyph used for the unread notification indicator.
+pub(crate) const UNREAD_INDICATOR_ICON: char = '\u{2709}';
+
+pub(crate) struct StoredNotification {
+    /// the process that posted the notification; `None` for the status bar itself
+    owner: Option<Holder>,
+    source: String,
+    text: String,
+    /// local time in ms since EPOCH, if the time was set when the notification arrived
+    local_time_ms: Option<u64>,
+    /// ticktimer time in ms, used when there is no local time
+    uptime_ms: u64,
+}
+
+/// Recent notifications posted by other services. Transient notifications are only kept long enough
+/// to be flashed on the status bar; persistent ones are kept in `history` until cleared.
+pub(crate) struct NotificationCenter {
+    /// newest notification is at the front
+    history: VecDeque<StoredNotification>,
+    unread: usize,
+    /// text to flash on the status bar, and the ticktimer deadline at which it stops being shown
+    flash: Option<(String, u64)>,
+    changed: bool,
+}
+impl NotificationCenter {
+    pub(crate) fn new() -> Self {
+        NotificationCenter { history: VecDeque::new(), unread: 0, flash: None, changed: false }
+    }
+
+    /// Posts a notification from `owner`, or from the status bar itself if it is `None`. The flash shares
+    /// the line with the security notes, so only the status bar's own notifications are flashed in full;
+    /// the text of the others is only shown in the notification center.
+    pub(crate) fn post(
+        &mut self,
+        notification: Notification,
+        owner: Option<Holder>,
+        local_time_ms: Option<u64>,
+        now: u64,
+    ) {
+        let source = notification.source.as_str().unwrap_or("UTF-8 Error").to_string();
+        let text = notification.text.as_str().unwrap_or("UTF-8 Error").to_string();
+        let flash = match owner {
+            None => format!("{}: {}", source, text),
+            Some(_) => t!("secnote.notification", locales::LANG).to_string(),
+        };
+        self.flash = Some((flash, now + NOTIFICATION_FLASH_MS));
+        if notification.persistent {
+            if self.history.len() >= MAX_NOTIFICATIONS {
+                self.history.pop_back();
+            }
+            self.history.push_front(StoredNotification {
+                owner,
+                source,
+                text,
+                local_time_ms,
+                uptime_ms: now,
+            });
+            self.unread = (self.unread + 1).min(self.history.len());
+        }
+        self.changed = true;
+    }
+
+    /// Removes the persistent notifications that `owner` posted from `source`. The source is just a name
+    /// any process can give, so a process can only clear what it posted itself, or what a process that
+    /// has since exited left behind under the same source.
+    pub(crate) fn clear_source(&mut self, source: &str, owner: Option<Holder>) {
+        let len = self.history.len();
+        self.history
+            .retain(|n| n.source != source || !(n.owner == owner || n.owner.map_or(false, has_exited)));
+        if self.history.len() != len {
+            self.unread = self.unread.min(self.history.len());
+            self.changed = true;
+        }
+    }
+
+    pub(crate) fn clear_all(&mut self) {
+        self.history.clear();
+        self.unread = 0;
+        self.flash = None;
+        self.changed = true;
+    }
+
+    pub(crate) fn mark_read(&mut self) {
+        if self.unread != 0 {
+            self.unread = 0;
+            self.changed = true;
+        }
+    }
+
+    pub(crate) fn unread(&self) -> usize { self.unread }
+
+    pub(crate) fn is_empty(&self) -> bool { self.history.is_empty() }
+
+    /// Returns the text to flash on the status bar, if any. Expires the flash once its deadline passes.
+    pub(crate) fn flash(&mut self, now: u64) -> Option<&str> {
+        if let Some((_, deadline)) = &self.flash {
+            if *deadline <= now {
+                self.flash = None;
+                self.changed = true;
+            }
+        }
+        self.flash.as_ref().map(|(text, _)| text.as_str())
+    }
+
+    /// Returns `true` if the notification state changed since the last call.
+    pub(crate) fn take_changed(&mut self) -> bool {
+        let changed = self.changed;
+        self.changed = false;
+        changed
+    }
+
+    /// Formats the history, newest first, into a string of no more than `max_len` bytes.
+    pub(crate) fn format_history(&self, max_len: usize) -> String {
+        let mut s = String::new();
+        for n in self.history.iter() {
+            let timestamp = if let Some(ms) = n.local_time_ms {
+                let dt = chrono::DateTime::<Utc>::from_naive_utc_and_offset(
+                    NaiveDateTime::from_timestamp_opt(ms as i64 / 1000, 0).unwrap_or_default(),
+                    chrono::offset::Utc,
+                );
+                dt.format("%H:%M %m/%d").to_string()
+            } else {
+                format!(
+                    "+{}:{:02}:{:02}",
+                    n.uptime_ms / 3_600_000,
+                    (n.uptime_ms / 60_000) % 60,
+                    (n.uptime_ms / 1000) % 60
+                )
+            };
+            let entry = format!("{} [{}]\n{}\n\n", timestamp, n.source, n.text);
+            if s.len() + entry.len() > max_len {
+                break;
+            }
+            s.push_str(&entry);
+        }
+        s
+    }
+}
@@ -6,6 +6,10 @@ use userprefs::Manager;
 
 use crate::wifi;
 
+/// PDDB location of the key remaps and macros, in the format described in `keyboard::keyconfig`
+const KEY_CONFIG_DICT: &str = "keyboard";
+const KEY_CONFIG_KEY: &str = "config";
+
 pub trait PrefHandler {
     // If handle() returns true, it has handled the operation.
     fn handle(&mut self, op: usize) -> bool;
@@ -23,6 +27,7 @@ enum DevicePrefsOp {
     AutoUnmountTimeout,
     RebootOnAutoSleep,
     KeyboardLayout,
+    KeyboardConfig,
     WLANMenu,
     SetTime,
     SetTimezone,
@@ -30,10 +35,13 @@ enum DevicePrefsOp {
     AudioOff,
     HeadsetVolume,
     EarpieceVolume,
+    Language,
+    ScreensaverTimeout,
 
     // Those are reserved for internal use
     UpdateMenuAudioEnabled = 399,
     UpdateMenuAudioDisabled,
+    UpdateMenuLanguage,
 }
 
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive, PartialEq, PartialOrd)]
@@ -41,31 +49,42 @@ pub enum PrefsMenuUpdateOp {
     // Those are reserved for internal use
     UpdateMenuAudioEnabled = 399,
     UpdateMenuAudioDisabled,
+    UpdateMenuLanguage,
 }
 
-impl Display for DevicePrefsOp {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+impl DevicePrefsOp {
+    /// The name of the menu item in `lang`
+    fn name(&self, lang: &str) -> &'static str {
         match self {
-            Self::AutobacklightOnBoot => write!(f, "{}", t!("prefs.autobacklight_enable", locales::LANG)),
-            Self::AutobacklightTimeout => write!(f, "{}", t!("prefs.autobacklight_duration", locales::LANG)),
-            Self::AutoSleepTimeout => write!(f, "{}", t!("prefs.autosleep_duration", locales::LANG)),
-            Self::RebootOnAutoSleep => write!(f, "{}", t!("prefs.autosleep_reboot", locales::LANG)),
-            Self::ConnectKnownNetworksOnBoot => write!(f, "{}", t!("prefs.wifi_connect_auto", locales::LANG)),
-            Self::WifiKill => write!(f, "{}", t!("prefs.wifi_kill", locales::LANG)),
-            Self::KeyboardLayout => write!(f, "{}", t!("prefs.keyboard_layout", locales::LANG)),
-            Self::WLANMenu => write!(f, "{}", t!("prefs.wifi_setting", locales::LANG)),
-            Self::SetTime => write!(f, "{}", t!("mainmenu.set_rtc", locales::LANG)),
-            Self::SetTimezone => write!(f, "{}", t!("mainmenu.set_tz", locales::LANG)),
-            Self::AudioOn => write!(f, "{}", t!("prefs.enable_audio", locales::LANG)),
-            Self::AudioOff => write!(f, "{}", t!("prefs.disable_audio", locales::LANG)),
-            Self::HeadsetVolume => write!(f, "{}", t!("prefs.headphone_volume", locales::LANG)),
-            Self::EarpieceVolume => write!(f, "{}", t!("prefs.speaker_volume", locales::LANG)),
+            Self::AutobacklightOnBoot => t!("prefs.autobacklight_enable", lang),
+            Self::AutobacklightTimeout => t!("prefs.autobacklight_duration", lang),
+            Self::AutoSleepTimeout => t!("prefs.autosleep_duration", lang),
+            Self::RebootOnAutoSleep => t!("prefs.autosleep_reboot", lang),
+            Self::ConnectKnownNetworksOnBoot => t!("prefs.wifi_connect_auto", lang),
+            Self::WifiKill => t!("prefs.wifi_kill", lang),
+            Self::KeyboardLayout => t!("prefs.keyboard_layout", lang),
+            Self::KeyboardConfig => t!("prefs.keyboard_config", lang),
+            Self::WLANMenu => t!("prefs.wifi_setting", lang),
+            Self::SetTime => t!("mainmenu.set_rtc", lang),
+            Self::SetTimezone => t!("mainmenu.set_tz", lang),
+            Self::AudioOn => t!("prefs.enable_audio", lang),
+            Self::AudioOff => t!("prefs.disable_audio", lang),
+            Self::HeadsetVolume => t!("prefs.headphone_volume", lang),
+            Self::EarpieceVolume => t!("prefs.speaker_volume", lang),
+            Self::Language => t!("prefs.language", lang),
+            Self::ScreensaverTimeout => t!("prefs.screensaver_duration", lang),
 
             _ => unimplemented!("should not end up here!"),
         }
     }
 }
 
+impl Display for DevicePrefsOp {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name(locales::LANG.get()))
+    }
+}
+
 #[derive(Debug)]
 enum DevicePrefsError {
     PrefsError(userprefs::Error),
@@ -104,6 +123,9 @@ struct DevicePrefs {
     menu_global_conn: xous::CID,
     status_cid: xous::CID,
     netmgr: net::NetManager,
+    language_cid: xous::CID,
+    /// the language the menu items are named in
+    menu_lang: &'static str,
 }
 
 impl PrefHandler for DevicePrefs {
@@ -111,10 +133,14 @@ impl PrefHandler for DevicePrefs {
         if match FromPrimitive::from_usize(op) {
             Some(other) => {
                 let other: PrefsMenuUpdateOp = other;
-                #[cfg(not(feature = "no-codec"))]
                 match other {
+                    PrefsMenuUpdateOp::UpdateMenuLanguage => self.rename_menu(),
+                    #[cfg(not(feature = "no-codec"))]
                     PrefsMenuUpdateOp::UpdateMenuAudioEnabled => self.alter_menu_audio_off(),
+                    #[cfg(not(feature = "no-codec"))]
                     PrefsMenuUpdateOp::UpdateMenuAudioDisabled => self.alter_menu_audio_on(),
+                    #[cfg(feature = "no-codec")]
+                    _ => {}
                 }
                 true
             }
@@ -171,6 +197,7 @@ impl PrefHandler for DevicePrefs {
         });
 
         self.menu = gam::menu_matic(menus, gam::PREFERENCES_MENU_NAME, Some(self.menu_manager_sid));
+        self.menu_lang = locales::LANG.get();
     }
 }
 
@@ -196,6 +223,10 @@ impl DevicePrefs {
             menu_global_conn: menu_conn,
             status_cid: status_conn,
             netmgr: net::NetManager::new(),
+            language_cid: xns
+                .request_connection_blocking(locales::broadcast::SERVER_NAME_LANGUAGE)
+                .expect("can't connect to language broadcast"),
+            menu_lang: locales::LANG.get(),
         }
     }
 
@@ -210,7 +241,10 @@ impl DevicePrefs {
             AutobacklightTimeout,
             AutoSleepTimeout,
             RebootOnAutoSleep,
+            #[cfg(feature = "screensaver")]
+            ScreensaverTimeout,
             KeyboardLayout,
+            KeyboardConfig,
             // Note: this vec sets the order of items in the preferences menu
             // The CI system assumes that the time setting items are always at
             // the bottom of the preferences menu, in this particular order.
@@ -234,6 +268,7 @@ impl DevicePrefs {
         } else {
             ret.push(AudioOn)
         }
+        ret.push(Language);
 
         ret
     }
@@ -248,7 +283,9 @@ impl DevicePrefs {
             AutobacklightTimeout => self.autobacklight_timeout(),
             AutoSleepTimeout => self.autosleep_timeout(),
             RebootOnAutoSleep => self.reboot_on_autosleep(),
+            ScreensaverTimeout => self.screensaver_timeout(),
             KeyboardLayout => self.keyboard_layout(),
+            KeyboardConfig => self.keyboard_config(),
             WLANMenu => self.wlan_menu(),
             SetTime => self.set_time_menu(),
             SetTimezone => self.set_timezone_menu(),
@@ -260,6 +297,7 @@ impl DevicePrefs {
             HeadsetVolume => self.headset_volume(),
             #[cfg(not(feature = "no-codec"))]
             EarpieceVolume => self.earpiece_volume(),
+            Language => self.language(),
 
             _ => unimplemented!("should not end up here!"),
         };
@@ -378,6 +416,28 @@ impl DevicePrefs {
         Ok(self.up.set_autosleep_timeout(new_timeout)?)
     }
 
+    fn screensaver_timeout(&self) -> Result<(), DevicePrefsError> {
+        // the screensaver service picks the new value up by itself
+        let cv = self.up.screensaver_timeout_or_value(5)?; // the default of the screensaver service
+
+        let raw_timeout = self
+            .modals
+            .alert_builder(t!("prefs.screensaver_duration_in_mins", locales::LANG))
+            .field(
+                Some(cv.to_string()),
+                Some(|tf| match tf.as_str().parse::<u64>() {
+                    Ok(_) => None,
+                    Err(_) => Some(xous_ipc::String::from_str(t!("prefs.autobacklight_err", locales::LANG))),
+                }),
+            )
+            .build()
+            .unwrap();
+
+        let new_timeout = raw_timeout.first().as_str().parse::<u64>().unwrap(); // we know this is a number, we checked with validator;
+
+        Ok(self.up.set_screensaver_timeout(new_timeout)?)
+    }
+
     fn reboot_on_autosleep(&self) -> Result<(), DevicePrefsError> {
         let cv = self.up.reboot_on_autosleep_or_default()?;
 
@@ -479,6 +539,59 @@ impl DevicePrefs {
         Ok(())
     }
 
+    fn language(&mut self) -> Result<(), DevicePrefsError> {
+        // languages are offered by their own names, so they can be found from any language. "en-tts" is
+        // left out, because speech needs a build with TTS.
+        let languages = [("en", "English"), ("fr", "Français"), ("ja", "日本語"), ("zh", "中文")];
+        let current = languages.iter().find(|(code, _)| locales::LANG == *code).map_or("", |(_, name)| *name);
+
+        self.modals.add_list(languages.iter().map(|(_, name)| *name).collect()).unwrap();
+        let new_result = self
+            .modals
+            .get_radiobutton(&format!("{} {}", t!("prefs.current_setting", locales::LANG), current))
+            .unwrap();
+
+        if let Some((code, _)) = languages.iter().find(|(_, name)| *name == new_result.as_str()) {
+            self.up.set_language(code.to_string())?;
+            crate::language::select_language(self.language_cid, code);
+        }
+
+        Ok(())
+    }
+
+    /// Renames the menu items into the language that was just selected.
+    fn rename_menu(&mut self) {
+        use DevicePrefsOp::*;
+        let Some(menu) = self.menu.as_ref() else {
+            return;
+        };
+        // items that aren't in the menu right now, like the audio items that are swapped in and out, are
+        // skipped by the menu
+        for op in [
+            WLANMenu,
+            ConnectKnownNetworksOnBoot,
+            WifiKill,
+            AutobacklightOnBoot,
+            AutobacklightTimeout,
+            AutoSleepTimeout,
+            RebootOnAutoSleep,
+            ScreensaverTimeout,
+            KeyboardLayout,
+            KeyboardConfig,
+            SetTime,
+            SetTimezone,
+            AudioOn,
+            AudioOff,
+            HeadsetVolume,
+            EarpieceVolume,
+            Language,
+        ] {
+            menu.rename_item(op.name(self.menu_lang), op.name(locales::LANG.get()));
+        }
+        menu.rename_item(t!("mainmenu.closemenu", self.menu_lang), t!("mainmenu.closemenu", locales::LANG));
+        self.menu_lang = locales::LANG.get();
+    }
+
     fn keyboard_layout(&mut self) -> Result<(), DevicePrefsError> {
         let kl: usize = self.kbd.get_keymap().unwrap().into();
 
@@ -505,6 +618,15 @@ impl DevicePrefs {
         Ok(())
     }
 
+    fn keyboard_config(&mut self) -> Result<(), DevicePrefsError> {
+        match load_key_config(&self.kbd) {
+            Ok(()) => self.modals.show_notification(t!("prefs.keyboard_config_loaded", locales::LANG), None),
+            Err(e) => self.modals.show_notification(&e, None),
+        }?;
+
+        Ok(())
+    }
+
     #[cfg(not(feature = "no-codec"))]
     fn audio_on(&mut self) -> Result<(), DevicePrefsError> {
         self.codec.setup_8k_stream()?;
@@ -632,6 +754,20 @@ impl DevicePrefs {
     }
 }
 
+/// Loads the key remaps and macros stored in the PDDB into the keyboard service. If there is no
+/// configuration stored, any previous one is cleared.
+pub fn load_key_config(kbd: &keyboard::Keyboard) -> Result<(), String> {
+    use std::io::Read;
+
+    let pddb = pddb::Pddb::new();
+    let mut config = String::new();
+    if let Ok(mut key) = pddb.get(KEY_CONFIG_DICT, KEY_CONFIG_KEY, None, false, false, None, None::<fn()>) {
+        key.read_to_string(&mut config)
+            .map_err(|e| format!("{}:{}: {:?}", KEY_CONFIG_DICT, KEY_CONFIG_KEY, e))?;
+    }
+    kbd.set_key_config(&config).map_err(|e| e.to_string())
+}
+
 pub fn percentage_to_db(value: u32) -> i32 {
     let negated_val = 100 - value;
 
@@ -687,6 +823,7 @@ fn run_menu_thread(sid: xous::SID, status_cid: xous::CID) {
     for handler in handlers.iter_mut() {
         handler.claim_menumatic_menu(menu_conn);
     }
+    locales::follow_language(Some((menu_conn, PrefsMenuUpdateOp::UpdateMenuLanguage.to_usize().unwrap())));
 
     loop {
         let msg = xous::receive_message(sid).unwrap();
@@ -0,0 +1,273 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+use locales::t;
+use num_traits::*;
+use quick_settings::api::*;
+use xous::{send_message, Message};
+
+use crate::holder::{has_exited, holder_of, Holder};
+
+/// How long the page waits for the owner of a setting to answer before leaving the setting off
+const OWNER_TIMEOUT: Duration = Duration::from_millis(500);
+
+struct Entry {
+    setting: QuickSetting,
+    /// the process that registered the setting, the only one that may replace or remove it
+    owner: Holder,
+}
+impl Entry {
+    fn id(&self) -> &str { self.setting.id.as_str().unwrap_or("UTF-8 Error") }
+
+    fn label(&self) -> &str { self.setting.label.as_str().unwrap_or("UTF-8 Error") }
+}
+
+/// Talks to the server that owns one or more settings. The calls are blocking scalars that the page can't
+/// put a timeout on, so they are made from a thread of their own, and the page only waits `OWNER_TIMEOUT`
+/// for the answer. An owner that hasn't answered the last call is skipped until it does.
+struct Owner {
+    calls: Sender<(u32, usize)>,
+    answers: Receiver<Option<usize>>,
+    waiting: Cell<bool>,
+}
+impl Owner {
+    fn new(sid: [u32; 4]) -> Result<Self, xous::Error> {
+        let conn = xous::connect(xous::SID::from_array(sid))?;
+        let (calls, call_queue) = channel::<(u32, usize)>();
+        let (answer, answers) = channel();
+        std::thread::spawn(move || {
+            while let Ok((opcode, arg)) = call_queue.recv() {
+                let result =
+                    match send_message(conn, Message::new_blocking_scalar(opcode as usize, arg, 0, 0, 0)) {
+                        Ok(xous::Result::Scalar1(value)) => Some(value),
+                        other => {
+                            log::warn!("unexpected answer from the owner of a quick setting: {:?}", other);
+                            None
+                        }
+                    };
+                if answer.send(result).is_err() {
+                    break;
+                }
+            }
+            // the owner's settings are all gone
+            unsafe { xous::disconnect(conn).ok() };
+        });
+        Ok(Owner { calls, answers, waiting: Cell::new(false) })
+    }
+
+    fn call(&self, opcode: u32, arg: usize) -> Option<usize> {
+        if self.waiting.get() {
+            // the answer to the last call is stale by now, but it shows the owner is back
+            self.answers.try_recv().ok()?;
+            self.waiting.set(false);
+        }
+        self.calls.send((opcode, arg)).ok()?;
+        match self.answers.recv_timeout(OWNER_TIMEOUT) {
+            Ok(result) => result,
+            Err(_) => {
+                self.waiting.set(true);
+                None
+            }
+        }
+    }
+}
+
+/// Checks that `holder` may put `setting` on the page: its id must not belong to another process, and its
+/// label must not be taken by another setting, as the page tells settings apart by their labels.
+fn may_register(entries: &[Entry], setting: &QuickSetting, holder: Holder) -> Result<(), xous::Error> {
+    let id = setting.id.as_str().unwrap_or("UTF-8 Error");
+    let label = setting.label.as_str().unwrap_or("UTF-8 Error");
+    for entry in entries.iter() {
+        if entry.id() == id {
+            if entry.owner != holder {
+                return Err(xous::Error::AccessDenied);
+            }
+        } else if entry.label() == label {
+            return Err(xous::Error::ServerExists);
+        }
+    }
+    if entries.len() >= MAX_QUICK_SETTINGS && !entries.iter().any(|e| e.id() == id) {
+        return Err(xous::Error::OutOfMemory);
+    }
+    Ok(())
+}
+
+/// Keeps the settings other services have put on the quick settings page, and renders it.
+///
+/// A rejected `Register` or `Unregister` hands the region back with the error code in `valid`.
+pub(crate) fn quick_settings_server(sid: xous::SID) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let modals = modals::Modals::new(&xns).unwrap();
+    let tt = ticktimer_server::Ticktimer::new().unwrap();
+    // in the order they were registered, which is the order they are shown in
+    let mut entries: Vec<Entry> = Vec::new();
+    // one per owning server, shared by all of its settings
+    let mut owners: HashMap<[u32; 4], Owner> = HashMap::new();
+    loop {
+        let mut msg = xous::receive_message(sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Register) => {
+                let Some(mem) = msg.body.memory_message() else {
+                    log::error!("Register sent as the wrong kind of message");
+                    continue;
+                };
+                let decoded =
+                    unsafe { xous_ipc::Buffer::from_memory_message(mem) }.to_original::<QuickSetting, _>();
+                let Ok(setting) = decoded else {
+                    log::error!("Register sent with a malformed setting");
+                    continue;
+                };
+                let id = setting.id.as_str().unwrap_or("UTF-8 Error").to_string();
+                // settings whose owner has exited are taken off the page, freeing their ids and labels
+                entries.retain(|e| !has_exited(e.owner));
+                owners.retain(|sid, _| entries.iter().any(|e| e.setting.sid == *sid));
+                let result = match holder_of(&msg) {
+                    Some(holder) => may_register(&entries, &setting, holder).and_then(|()| {
+                        if !owners.contains_key(&setting.sid) {
+                            owners.insert(setting.sid, Owner::new(setting.sid)?);
+                        }
+                        Ok(holder)
+                    }),
+                    None => Err(xous::Error::AccessDenied),
+                };
+                let result = match result {
+                    Ok(owner) => {
+                        if let Some(entry) = entries.iter_mut().find(|e| e.id() == id) {
+                            *entry = Entry { setting, owner };
+                        } else {
+                            log::info!("quick setting registered: {}", id);
+                            entries.push(Entry { setting, owner });
+                        }
+                        Ok(())
+                    }
+                    Err(e) => {
+                        log::warn!("quick setting {} not registered: {:?}", id, e);
+                        Err(e)
+                    }
+                };
+                reply(&mut msg, result);
+            }
+            Some(Opcode::Unregister) => {
+                let Some(mem) = msg.body.memory_message() else {
+                    log::error!("Unregister sent as the wrong kind of message");
+                    continue;
+                };
+                let decoded =
+                    unsafe { xous_ipc::Buffer::from_memory_message(mem) }.to_original::<QuickSettingId, _>();
+                let Ok(setting_id) = decoded else {
+                    log::error!("Unregister sent with a malformed id");
+                    continue;
+                };
+                let id = setting_id.id.as_str().unwrap_or("UTF-8 Error");
+                let holder = holder_of(&msg);
+                let result = match entries.iter().position(|e| e.id() == id) {
+                    Some(index)
+                        if Some(entries[index].owner) != holder && !has_exited(entries[index].owner) =>
+                    {
+                        log::warn!("PID {:?} can't remove quick setting {}", holder.map(|h| h.0), id);
+                        Err(xous::Error::AccessDenied)
+                    }
+                    Some(index) => {
+                        entries.remove(index);
+                        owners.retain(|sid, _| entries.iter().any(|e| e.setting.sid == *sid));
+                        Ok(())
+                    }
+                    None => Ok(()),
+                };
+                reply(&mut msg, result);
+            }
+            Some(Opcode::Show) => {
+                tt.sleep_ms(100).ok(); // yield for a moment to allow the previous menu to close
+                show(&modals, &entries, &owners);
+            }
+            Some(Opcode::Quit) => {
+                if msg.sender.pid().map(|pid| pid.get() as u32) != Some(xous::process::id()) {
+                    log::warn!("Quit from another process ignored");
+                    continue;
+                }
+                xous::return_scalar(msg.sender, 1).ok();
+                break;
+            }
+            None => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+}
+
+/// Puts the outcome of a `Register` or `Unregister` in the region that goes back to the caller.
+fn reply(msg: &mut xous::MessageEnvelope, result: Result<(), xous::Error>) {
+    if let Some(mem) = msg.body.memory_message_mut() {
+        mem.offset = None;
+        mem.valid = match result {
+            Ok(()) => None,
+            Err(e) => xous::MemorySize::new(e.to_usize()),
+        };
+    }
+}
+
+fn get(owners: &HashMap<[u32; 4], Owner>, entry: &Entry) -> Option<bool> {
+    match owners.get(&entry.setting.sid)?.call(entry.setting.get_opcode, 0) {
+        Some(state) => Some(state != 0),
+        None => {
+            log::warn!("couldn't read quick setting {}", entry.id());
+            None
+        }
+    }
+}
+
+fn set(owners: &HashMap<[u32; 4], Owner>, entry: &Entry, on: bool) {
+    let arg = if on { 1 } else { 0 };
+    match owners.get(&entry.setting.sid).and_then(|owner| owner.call(entry.setting.set_opcode, arg)) {
+        Some(state) if (state != 0) != on => {
+            log::info!("quick setting {} refused the change", entry.id())
+        }
+        Some(_) => {
+            log::info!("quick setting {} is now {}", entry.id(), on)
+        }
+        None => log::warn!("couldn't change quick setting {}", entry.id()),
+    }
+}
+
+/// Lists every setting with its current state; picking one flips it, and the list is shown again with
+/// the new states until the user closes it.
+fn show(modals: &modals::Modals, entries: &[Entry], owners: &HashMap<[u32; 4], Owner>) {
+    if entries.is_empty() {
+        modals.show_notification(t!("quicksettings.empty", locales::LANG), None).ok();
+        return;
+    }
+    loop {
+        // settings whose owner doesn't answer are left off the page
+        let states: Vec<(&Entry, bool)> =
+            entries.iter().filter_map(|entry| get(owners, entry).map(|state| (entry, state))).collect();
+        let items: Vec<String> = states
+            .iter()
+            .map(|(entry, state)| {
+                format!(
+                    "{}: {}",
+                    entry.label(),
+                    if *state {
+                        t!("quicksettings.on", locales::LANG)
+                    } else {
+                        t!("quicksettings.off", locales::LANG)
+                    }
+                )
+            })
+            .collect();
+        for item in items.iter() {
+            modals.add_list_item(item).ok();
+        }
+        modals.add_list_item(t!("quicksettings.close", locales::LANG)).ok();
+        let choice = match modals.get_radiobutton(t!("quicksettings.title", locales::LANG)) {
+            Ok(choice) => choice,
+            Err(_) => return,
+        };
+        match items.iter().position(|item| *item == choice) {
+            Some(index) => {
+                let (entry, state) = states[index];
+                set(owners, entry, !state);
+            }
+            None => return,
+        }
+    }
+}
@@ -14,6 +14,7 @@ pub enum WlanManOp {
     AddNetworkManually,
     KnownNetworks,
     DeleteNetwork,
+    MeteredNetwork,
 }
 
 impl Display for WlanManOp {
@@ -24,6 +25,7 @@ impl Display for WlanManOp {
             Self::Status => write!(f, "{}", t!("wlan.status", locales::LANG)),
             Self::DeleteNetwork => write!(f, "{}", t!("wlan.delete", locales::LANG)),
             Self::KnownNetworks => write!(f, "{}", t!("wlan.list_known", locales::LANG)),
+            Self::MeteredNetwork => write!(f, "{}", t!("wlan.metered", locales::LANG)),
         }
     }
 }
@@ -119,7 +121,7 @@ impl WLANMan {
     pub fn actions(&self) -> Vec<WlanManOp> {
         use WlanManOp::*;
 
-        vec![ScanForNetworks, Status, AddNetworkManually, KnownNetworks, DeleteNetwork]
+        vec![ScanForNetworks, Status, AddNetworkManually, KnownNetworks, MeteredNetwork, DeleteNetwork]
     }
 
     #[allow(dead_code)] // just in case we need this later
@@ -357,10 +359,45 @@ impl WLANMan {
         self.pddb
             .delete_key(net::AP_DICT_NAME, &ssid_to_be_deleted, None)
             .map_err(|e| WLANError::PDDBIoError(e))?;
+        self.netmgr.set_metered(&ssid_to_be_deleted, false)?;
 
         self.pddb.sync().map_err(|e| WLANError::PDDBIoError(e))
     }
 
+    fn metered_network(&mut self) -> Result<(), WLANError> {
+        let networks = match self.pddb.list_keys(net::AP_DICT_NAME, None) {
+            Ok(list) => list,
+            Err(_) => Vec::new(),
+        };
+
+        if networks.is_empty() {
+            self.modals.show_notification(t!("wlan.no_known_networks", locales::LANG), None).unwrap();
+            return Ok(());
+        }
+
+        let cancel_item = t!("wlan.cancel", locales::LANG);
+        self.modals.add_list(networks.iter().map(|s| s.as_str()).collect()).unwrap();
+        self.modals.add_list_item(cancel_item).unwrap();
+
+        let ssid = self.modals.get_radiobutton(t!("wlan.choose_metered", locales::LANG)).unwrap();
+
+        if ssid.eq(cancel_item) {
+            return Ok(());
+        }
+
+        let metered = self.pddb.list_keys(net::METERED_DICT_NAME, None).unwrap_or_default().contains(&ssid);
+        let form = self
+            .modals
+            .form_builder(&t!("wlan.metered_prompt", locales::LANG).replace("{ssid}", &ssid))
+            .checkbox(t!("wlan.metered_checkbox", locales::LANG), metered)
+            .build()?;
+        let now_metered = form.get(0).map_or(metered, |field| field.is_checked());
+        if now_metered != metered {
+            self.netmgr.set_metered(&ssid, now_metered)?;
+        }
+        Ok(())
+    }
+
     fn consume_menu_action(&mut self, action: WlanManOp) {
         let resp = match action {
             WlanManOp::AddNetworkManually => self.add_new_ssid(),
@@ -368,6 +405,7 @@ impl WLANMan {
             WlanManOp::Status => self.network_status(),
             WlanManOp::DeleteNetwork => self.delete_network(),
             WlanManOp::KnownNetworks => self.known_networks(),
+            WlanManOp::MeteredNetwork => self.metered_network(),
         };
 
         resp.unwrap_or_else(|error| self.show_error_modal(error));
@@ -0,0 +1,48 @@
+pub(crate) const SERVER_NAME_SUPERVISOR: &str = "_Process supervisor_";
+
+/// Longest name a supervised service may be known by
+pub const SERVICE_NAME_LEN: usize = 32;
+/// Longest command line that a service can be restarted with
+pub const COMMAND_LEN: usize = 256;
+
+/// First delay before a service under `RestartPolicy::Backoff` is restarted
+pub const BACKOFF_MIN_SECS: u32 = 1;
+/// Longest delay before a service under `RestartPolicy::Backoff` is restarted
+pub const BACKOFF_MAX_SECS: u32 = 64;
+/// A service that stays up this long is no longer counted as crashing, and its backoff starts over
+pub const STABLE_SECS: u32 = 60;
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// Start supervising the calling process. Memory, lend_mut of `Supervision`; the policy in effect
+    /// is written back, as a restart policy is downgraded to `Never` where processes can't be restarted.
+    Supervise = 0,
+    /// Stop supervising the calling process. Blocking scalar.
+    Release = 1,
+    /// Internal: check on the supervised processes. Scalar, only accepted from our own process.
+    Poll = 2,
+    /// Exits the server. Only accepted from our own process.
+    Quit = 3,
+}
+
+/// What the supervisor does when a service exits or faults
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub enum RestartPolicy {
+    /// Leave it stopped, and only report that it went away
+    Never,
+    /// Restart it right away
+    Always,
+    /// Restart it after a delay that doubles with each crash in a row, from `BACKOFF_MIN_SECS` up to
+    /// `BACKOFF_MAX_SECS`
+    Backoff,
+}
+
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Supervision {
+    /// Name the service is reported under
+    pub name: xous_ipc::String<SERVICE_NAME_LEN>,
+    pub policy: RestartPolicy,
+    /// Command line that starts the service again. Only hosted mode starts processes from a command
+    /// line; on hardware, services are loaded once from the boot image and this is left empty.
+    pub command: xous_ipc::String<COMMAND_LEN>,
+}
@@ -0,0 +1,68 @@
+pub mod api;
+pub use api::*;
+use num_traits::*;
+use xous::{send_message, Message, CID};
+use xous_ipc::Buffer;
+
+/// Client API to the supervisor, for services that want to be restarted when they exit or fault.
+pub struct Supervisor {
+    conn: CID,
+}
+impl Supervisor {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_SUPERVISOR)?;
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(Supervisor { conn })
+    }
+
+    /// Asks the supervisor to watch the calling process, and to handle it by `policy` if it exits or
+    /// faults. Exits are reported in the notification center under `name`. A restarted service calls
+    /// this again as it starts up, which keeps its count of crashes in a row.
+    ///
+    /// Returns the policy in effect: services can only be restarted in hosted mode, so on hardware a
+    /// restart policy comes back as `RestartPolicy::Never`, and exits are only reported.
+    pub fn supervise(&self, name: &str, policy: RestartPolicy) -> Result<RestartPolicy, xous::Error> {
+        if name.len() > SERVICE_NAME_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        // in hosted mode, the process is restarted the way it was started
+        #[cfg(not(target_os = "xous"))]
+        let command = std::env::args().collect::<Vec<String>>().join(" ");
+        #[cfg(target_os = "xous")]
+        let command = String::new();
+        if command.len() > COMMAND_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        let supervision = Supervision {
+            name: xous_ipc::String::from_str(name),
+            policy,
+            command: xous_ipc::String::from_str(&command),
+        };
+        let mut buf = Buffer::into_buf(supervision).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::Supervise.to_u32().unwrap())?;
+        let supervision = buf.to_original::<Supervision, _>().or(Err(xous::Error::InternalError))?;
+        Ok(supervision.policy)
+    }
+
+    /// Stops supervising the calling process. Call this before exiting on purpose, so that the exit
+    /// is not taken for a crash.
+    pub fn release(&self) -> Result<(), xous::Error> {
+        send_message(self.conn, Message::new_blocking_scalar(Opcode::Release.to_usize().unwrap(), 0, 0, 0, 0))
+            .map(|_| ())
+    }
+}
+
+use core::sync::atomic::{AtomicU32, Ordering};
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        // the connection to the server side must be reference counted, so that multiple instances of this
+        // object within a single process do not end up de-allocating the CID on other threads before they
+        // go out of scope.
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,357 @@
+mod api;
+use std::sync::mpsc::Sender;
+
+use api::*;
+use locales::t;
+use num_traits::*;
+use xous::PID;
+use xous_ipc::Buffer;
+
+/// The supervised processes are checked on once a second
+const POLL_INTERVAL_MS: usize = 1000;
+/// Source that exits are posted under in the notification center
+const NOTIFICATION_SOURCE: &str = "supervisor";
+
+enum State {
+    /// The service is up as `pid`, and has been for `up_secs`
+    Running { pid: PID, activations: u32, up_secs: u32 },
+    /// The service is down, and will be restarted in `restart_in` seconds
+    Waiting { restart_in: u32 },
+}
+
+struct Supervised {
+    supervision: Supervision,
+    state: State,
+    /// exits in a row, each before the service had been up for `STABLE_SECS`
+    crashes: u32,
+}
+
+/// Ways to look at and start processes, which the tests stand in for
+trait Processes {
+    /// The number of times `pid` has been switched to, or an error if there is no such process
+    fn activations(&self, pid: PID) -> Result<u32, xous::Error>;
+    /// Starts a service again
+    fn spawn(&self, supervision: &Supervision) -> Result<PID, xous::Error>;
+}
+
+struct Kernel {}
+
+impl Processes for Kernel {
+    fn activations(&self, pid: PID) -> Result<u32, xous::Error> { xous::process_activations(pid) }
+
+    /// Starts a service again from the command line it was started with.
+    #[cfg(not(target_os = "xous"))]
+    fn spawn(&self, supervision: &Supervision) -> Result<PID, xous::Error> {
+        if supervision.command.len() == 0 {
+            return Err(xous::Error::InvalidString);
+        }
+        xous::create_process(xous::ProcessArgs::new(
+            supervision.name.to_str(),
+            supervision.command.to_str().to_string(),
+        ))
+        .map(|process| process.pid)
+    }
+
+    /// Not reached: on hardware, services are loaded from the boot image, which is not kept once they
+    /// are running, so restart policies are downgraded to `Never` by `effective_policy()`.
+    #[cfg(target_os = "xous")]
+    fn spawn(&self, _supervision: &Supervision) -> Result<PID, xous::Error> {
+        Err(xous::Error::UnhandledSyscall)
+    }
+}
+
+/// The policy a service is held to, given whether processes can be restarted here
+fn effective_policy(policy: RestartPolicy, can_restart: bool) -> RestartPolicy {
+    if can_restart { policy } else { RestartPolicy::Never }
+}
+
+/// Starts the service again. Returns false if it could not be started, and is no longer supervised.
+fn restart(
+    service: &mut Supervised,
+    name: &str,
+    processes: &impl Processes,
+    notify: &Sender<String>,
+) -> bool {
+    match processes.spawn(&service.supervision) {
+        Ok(pid) => {
+            log::info!("restarted {} as PID {}", name, pid);
+            service.state = State::Running { pid, activations: 0, up_secs: 0 };
+            true
+        }
+        Err(e) => {
+            log::error!("couldn't restart {}: {:?}", name, e);
+            notify.send(t!("supervisor.restart_failed", locales::LANG).replace("{name}", name)).ok();
+            false
+        }
+    }
+}
+
+/// Checks on a service; called once a second. Returns false once the service is no longer supervised.
+fn poll(service: &mut Supervised, processes: &impl Processes, notify: &Sender<String>) -> bool {
+    let name = service.supervision.name.to_str().to_string();
+    match service.state {
+        State::Running { pid, activations, up_secs } => match processes.activations(pid) {
+            // the count starts over from zero if the PID now belongs to a new process
+            Ok(count) if count >= activations => {
+                if up_secs + 1 == STABLE_SECS {
+                    service.crashes = 0;
+                }
+                service.state = State::Running { pid, activations: count, up_secs: up_secs + 1 };
+                true
+            }
+            _ => {
+                log::warn!("{} (PID {}) went away after {}s", name, pid, up_secs);
+                service.crashes += 1;
+                match service.supervision.policy {
+                    RestartPolicy::Never => {
+                        notify.send(t!("supervisor.stopped", locales::LANG).replace("{name}", &name)).ok();
+                        false
+                    }
+                    RestartPolicy::Always => {
+                        let restarted = restart(service, &name, processes, notify);
+                        if restarted {
+                            notify
+                                .send(t!("supervisor.restarted", locales::LANG).replace("{name}", &name))
+                                .ok();
+                        }
+                        restarted
+                    }
+                    RestartPolicy::Backoff => {
+                        let delay = (BACKOFF_MIN_SECS << (service.crashes - 1).min(16)).min(BACKOFF_MAX_SECS);
+                        notify
+                            .send(
+                                t!("supervisor.restarting", locales::LANG)
+                                    .replace("{name}", &name)
+                                    .replace("{secs}", &delay.to_string()),
+                            )
+                            .ok();
+                        service.state = State::Waiting { restart_in: delay };
+                        true
+                    }
+                }
+            }
+        },
+        State::Waiting { restart_in } if restart_in > 1 => {
+            service.state = State::Waiting { restart_in: restart_in - 1 };
+            true
+        }
+        State::Waiting { .. } => restart(service, &name, processes, notify),
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let supervisor_sid = xns.register_name(api::SERVER_NAME_SUPERVISOR, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", supervisor_sid);
+    let self_cid = xous::connect(supervisor_sid).unwrap();
+
+    // The status bar comes up well after us, so exits are posted to the notification center from a
+    // thread that can wait for it.
+    let (notify, notifications) = std::sync::mpsc::channel::<String>();
+    std::thread::spawn(move || {
+        let xns = xous_names::XousNames::new().unwrap();
+        let status = status::Status::new(&xns).expect("can't connect to the status bar");
+        for text in notifications {
+            status.post_notification(NOTIFICATION_SOURCE, &text, true).ok();
+        }
+    });
+    std::thread::spawn(move || {
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        loop {
+            tt.sleep_ms(POLL_INTERVAL_MS).unwrap();
+            xous::send_message(
+                self_cid,
+                xous::Message::new_scalar(Opcode::Poll.to_usize().unwrap(), 0, 0, 0, 0),
+            )
+            .ok();
+        }
+    });
+
+    let processes = Kernel {};
+    let my_pid = xous::process::id();
+    let mut supervised: Vec<Supervised> = Vec::new();
+    loop {
+        let mut msg = xous::receive_message(supervisor_sid).unwrap();
+        let from_self = msg.sender.pid().map(|p| p.get() as u32) == Some(my_pid);
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Supervise) => {
+                let Some(pid) = msg.sender.pid() else {
+                    continue;
+                };
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("Supervise was not a memory message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(mut supervision) = buffer.to_original::<Supervision, _>() else {
+                    log::error!("couldn't deserialize a supervision request");
+                    continue;
+                };
+                supervision.policy = effective_policy(supervision.policy, cfg!(not(target_os = "xous")));
+                buffer.replace(supervision).ok();
+                log::info!("supervising {} as PID {}, {:?}", supervision.name, pid, supervision.policy);
+                let state =
+                    State::Running { pid, activations: processes.activations(pid).unwrap_or(0), up_secs: 0 };
+                // a restarted service signs up again under the same name, and keeps its crash count
+                match supervised.iter_mut().find(|service| service.supervision.name == supervision.name) {
+                    Some(service) => {
+                        service.supervision = supervision;
+                        service.state = state;
+                    }
+                    None => supervised.push(Supervised { supervision, state, crashes: 0 }),
+                }
+            }
+            Some(Opcode::Release) => {
+                let sender = msg.sender.pid();
+                supervised.retain(
+                    |service| !matches!(service.state, State::Running { pid, .. } if Some(pid) == sender),
+                );
+                xous::return_scalar(msg.sender, 0).ok();
+            }
+            Some(Opcode::Poll) => {
+                if from_self {
+                    supervised.retain_mut(|service| poll(service, &processes, &notify));
+                }
+            }
+            Some(Opcode::Quit) => {
+                if !from_self {
+                    log::warn!("ignoring Quit from another process");
+                    continue;
+                }
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => {
+                log::error!("couldn't convert opcode: {:?}", msg);
+            }
+        }
+    }
+    // clean up our program
+    log::trace!("main loop exit, destroying servers");
+    xns.unregister_server(supervisor_sid).unwrap();
+    xous::destroy_server(supervisor_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::sync::mpsc::Receiver;
+
+    use super::*;
+
+    /// A process table: live PIDs and their activations, and the PID the next spawn gets
+    struct Fake {
+        live: RefCell<HashMap<u8, u32>>,
+        next_pid: Cell<u8>,
+    }
+
+    impl Fake {
+        fn new() -> Fake { Fake { live: RefCell::new(HashMap::from([(10, 0)])), next_pid: Cell::new(20) } }
+
+        fn tick(&self, pid: u8) { *self.live.borrow_mut().get_mut(&pid).unwrap() += 1; }
+
+        fn kill(&self, pid: u8) { self.live.borrow_mut().remove(&pid); }
+    }
+
+    impl Processes for Fake {
+        fn activations(&self, pid: PID) -> Result<u32, xous::Error> {
+            self.live.borrow().get(&pid.get()).copied().ok_or(xous::Error::ProcessNotFound)
+        }
+
+        fn spawn(&self, _supervision: &Supervision) -> Result<PID, xous::Error> {
+            let pid = self.next_pid.get();
+            self.next_pid.set(pid + 1);
+            self.live.borrow_mut().insert(pid, 0);
+            Ok(PID::new(pid).unwrap())
+        }
+    }
+
+    fn service(policy: RestartPolicy) -> (Supervised, Sender<String>, Receiver<String>) {
+        let supervision = Supervision {
+            name: xous_ipc::String::from_str("test"),
+            policy,
+            command: xous_ipc::String::from_str("test"),
+        };
+        let state = State::Running { pid: PID::new(10).unwrap(), activations: 0, up_secs: 0 };
+        let (notify, notifications) = std::sync::mpsc::channel();
+        (Supervised { supervision, state, crashes: 0 }, notify, notifications)
+    }
+
+    fn running_pid(service: &Supervised) -> Option<u8> {
+        match service.state {
+            State::Running { pid, .. } => Some(pid.get()),
+            State::Waiting { .. } => None,
+        }
+    }
+
+    #[test]
+    fn live_services_are_left_alone() {
+        let fake = Fake::new();
+        let (mut service, notify, notifications) = service(RestartPolicy::Always);
+        for _ in 0..5 {
+            fake.tick(10);
+            assert!(poll(&mut service, &fake, &notify));
+        }
+        assert_eq!(running_pid(&service), Some(10));
+        assert!(notifications.try_recv().is_err());
+    }
+
+    #[test]
+    fn never_reports_and_lets_go() {
+        let fake = Fake::new();
+        let (mut service, notify, notifications) = service(RestartPolicy::Never);
+        fake.kill(10);
+        assert!(!poll(&mut service, &fake, &notify));
+        assert!(notifications.try_recv().is_ok());
+        assert_eq!(fake.next_pid.get(), 20);
+    }
+
+    #[test]
+    fn always_restarts_right_away() {
+        let fake = Fake::new();
+        let (mut service, notify, notifications) = service(RestartPolicy::Always);
+        fake.kill(10);
+        assert!(poll(&mut service, &fake, &notify));
+        assert_eq!(running_pid(&service), Some(20));
+        assert!(notifications.try_recv().is_ok());
+    }
+
+    #[test]
+    fn backoff_doubles_until_the_service_is_stable() {
+        let fake = Fake::new();
+        let (mut service, notify, _notifications) = service(RestartPolicy::Backoff);
+        let mut pid = 10;
+        for delay in [1, 2, 4, 8] {
+            fake.kill(pid);
+            assert!(poll(&mut service, &fake, &notify));
+            for _ in 0..delay {
+                assert_eq!(running_pid(&service), None);
+                assert!(poll(&mut service, &fake, &notify));
+            }
+            pid = running_pid(&service).unwrap();
+        }
+        // once it has been up for STABLE_SECS, the backoff starts over
+        for _ in 0..STABLE_SECS {
+            assert!(poll(&mut service, &fake, &notify));
+        }
+        assert_eq!(service.crashes, 0);
+        fake.kill(pid);
+        assert!(poll(&mut service, &fake, &notify));
+        assert!(matches!(service.state, State::Waiting { restart_in: 1 }));
+    }
+
+    #[test]
+    fn restart_policies_need_a_way_to_restart() {
+        for policy in [RestartPolicy::Never, RestartPolicy::Always, RestartPolicy::Backoff] {
+            assert_eq!(effective_policy(policy, true), policy);
+            assert_eq!(effective_policy(policy, false), RestartPolicy::Never);
+        }
+    }
+}
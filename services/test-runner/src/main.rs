@@ -0,0 +1,44 @@
+mod report;
+mod suites;
+
+use report::{Outcome, Summary};
+
+/// Time for the rest of the image to boot before the first test runs
+const BOOT_WAIT_MS: usize = 2000;
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let tt = ticktimer_server::Ticktimer::new().unwrap();
+    tt.sleep_ms(BOOT_WAIT_MS).unwrap();
+
+    #[cfg(feature = "usb-serial")]
+    let usb = usb_device_xous::UsbHid::new();
+    #[cfg(feature = "usb-serial")]
+    if let Err(e) = usb.ensure_core(usb_device_xous::UsbDeviceType::Serial) {
+        log::warn!("couldn't switch USB to serial, results only go to the UART: {:?}", e);
+    }
+    let emit = |line: String| {
+        log::info!("{}", line);
+        #[cfg(feature = "usb-serial")]
+        usb.serial_write(&format!("{}\r\n", line)).ok();
+    };
+
+    emit(report::begin(suites::TESTS.len()));
+    let mut summary = Summary::default();
+    for test in suites::TESTS {
+        let start = tt.elapsed_ms();
+        let outcome = std::thread::spawn(test.run)
+            .join()
+            .unwrap_or_else(|_| Outcome::Fail("the test panicked".to_string()));
+        let elapsed_ms = tt.elapsed_ms() - start;
+        summary.record(&outcome);
+        emit(report::result(test.suite, test.name, &outcome, elapsed_ms));
+    }
+    emit(report::end(&summary));
+
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
@@ -0,0 +1,105 @@
+//! The lines the runner reports. Each is wrapped in the `BOOKEND` markers, so that a script reading the
+//! UART can pick them out from the rest of the log, and is a comma-separated record:
+//!
+//! ```text
+//! TEST,BEGIN,<tests>
+//! TEST,<PASS|FAIL|SKIP>,<suite>::<name>,<elapsed ms>,<reason>
+//! TEST,END,<passed>,<failed>,<skipped>
+//! ```
+
+use xous::{BOOKEND_END, BOOKEND_START};
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Pass,
+    Fail(String),
+    /// the test can't run in this image, e.g. because a service it needs is missing
+    Skip(String),
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+impl Summary {
+    pub fn record(&mut self, outcome: &Outcome) {
+        match outcome {
+            Outcome::Pass => self.passed += 1,
+            Outcome::Fail(_) => self.failed += 1,
+            Outcome::Skip(_) => self.skipped += 1,
+        }
+    }
+}
+
+/// Keeps a reason from breaking up the record it is in
+fn field(text: &str) -> String {
+    text.chars().map(|c| if c == ',' || c.is_control() { ' ' } else { c }).collect()
+}
+
+pub(crate) fn begin(tests: usize) -> String {
+    format!("{}TEST,BEGIN,{}{}", BOOKEND_START, tests, BOOKEND_END)
+}
+
+pub(crate) fn result(suite: &str, name: &str, outcome: &Outcome, elapsed_ms: u64) -> String {
+    let (status, reason) = match outcome {
+        Outcome::Pass => ("PASS", ""),
+        Outcome::Fail(reason) => ("FAIL", reason.as_str()),
+        Outcome::Skip(reason) => ("SKIP", reason.as_str()),
+    };
+    format!(
+        "{}TEST,{},{}::{},{},{}{}",
+        BOOKEND_START,
+        status,
+        suite,
+        name,
+        elapsed_ms,
+        field(reason),
+        BOOKEND_END
+    )
+}
+
+pub(crate) fn end(summary: &Summary) -> String {
+    format!(
+        "{}TEST,END,{},{},{}{}",
+        BOOKEND_START, summary.passed, summary.failed, summary.skipped, BOOKEND_END
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(line: &str) -> Vec<&str> {
+        line.strip_prefix(BOOKEND_START).unwrap().strip_suffix(BOOKEND_END).unwrap().split(',').collect()
+    }
+
+    #[test]
+    fn results_have_a_fixed_number_of_fields() {
+        let pass = result("ipc", "scalar", &Outcome::Pass, 3);
+        assert_eq!(record(&pass), ["TEST", "PASS", "ipc::scalar", "3", ""]);
+        let fail = result("pddb", "round_trip", &Outcome::Fail("read 3, wrote 4".to_string()), 120);
+        assert_eq!(record(&fail), ["TEST", "FAIL", "pddb::round_trip", "120", "read 3  wrote 4"]);
+    }
+
+    #[test]
+    fn reasons_stay_on_one_line() {
+        let skip = result("pddb", "round_trip", &Outcome::Skip("not\nmounted".to_string()), 0);
+        assert!(!skip.contains('\n'));
+        assert_eq!(record(&skip)[4], "not mounted");
+    }
+
+    #[test]
+    fn summary_counts_each_outcome() {
+        let mut summary = Summary::default();
+        for outcome in
+            [Outcome::Pass, Outcome::Pass, Outcome::Fail(String::new()), Outcome::Skip(String::new())]
+        {
+            summary.record(&outcome);
+        }
+        assert_eq!(summary, Summary { passed: 2, failed: 1, skipped: 1 });
+        assert_eq!(record(&end(&summary)), ["TEST", "END", "2", "1", "1"]);
+        assert_eq!(record(&begin(4)), ["TEST", "BEGIN", "4"]);
+    }
+}
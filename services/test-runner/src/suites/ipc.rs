@@ -0,0 +1,71 @@
+use xous::{Message, MessageEnvelope};
+use xous_ipc::Buffer;
+
+use crate::report::Outcome;
+
+type Text = xous_ipc::String<64>;
+
+/// Runs `handler` on the first message sent to a server of its own, and returns a connection to it
+fn serve_once(handler: fn(MessageEnvelope)) -> (xous::SID, xous::CID, std::thread::JoinHandle<()>) {
+    let sid = xous::create_server().unwrap();
+    let server = std::thread::spawn(move || handler(xous::receive_message(sid).unwrap()));
+    (sid, xous::connect(sid).unwrap(), server)
+}
+
+fn shut_down(sid: xous::SID, cid: xous::CID, server: std::thread::JoinHandle<()>) {
+    server.join().unwrap();
+    unsafe { xous::disconnect(cid).ok() };
+    xous::destroy_server(sid).ok();
+}
+
+pub(crate) fn blocking_scalar() -> Outcome {
+    let (sid, cid, server) = serve_once(|msg| {
+        if let Some(scalar) = msg.body.scalar_message() {
+            xous::return_scalar(msg.sender, scalar.arg1 + scalar.arg2).unwrap();
+        }
+    });
+    let result = xous::send_message(cid, Message::new_blocking_scalar(0, 2, 3, 0, 0));
+    shut_down(sid, cid, server);
+    match result {
+        Ok(xous::Result::Scalar1(5)) => Outcome::Pass,
+        other => Outcome::Fail(format!("asked for 2 + 3, got {:?}", other)),
+    }
+}
+
+pub(crate) fn lend_mut() -> Outcome {
+    let (sid, cid, server) = serve_once(|mut msg| {
+        let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+        let text = buffer.to_original::<Text, _>().unwrap();
+        buffer.replace(Text::from_str(&text.as_str().unwrap().to_uppercase())).unwrap();
+    });
+    let mut buf = Buffer::into_buf(Text::from_str("ping")).unwrap();
+    let sent = buf.lend_mut(cid, 0);
+    shut_down(sid, cid, server);
+    if let Err(e) = sent {
+        return Outcome::Fail(format!("couldn't lend the buffer: {:?}", e));
+    }
+    match buf.to_original::<Text, _>() {
+        Ok(text) if text.as_str() == Ok("PING") => Outcome::Pass,
+        Ok(text) => Outcome::Fail(format!("lent \"ping\", got back {:?}", text.as_str())),
+        Err(_) => Outcome::Fail("the buffer came back corrupted".to_string()),
+    }
+}
+
+pub(crate) fn name_lookup() -> Outcome {
+    const NAME: &str = "_test-runner probe_";
+    let xns = xous_names::XousNames::new().unwrap();
+    let sid = match xns.register_name(NAME, None) {
+        Ok(sid) => sid,
+        Err(e) => return Outcome::Fail(format!("couldn't register a name: {:?}", e)),
+    };
+    let outcome = match xns.request_connection(NAME) {
+        Ok(cid) => {
+            unsafe { xous::disconnect(cid).ok() };
+            Outcome::Pass
+        }
+        Err(e) => Outcome::Fail(format!("couldn't look up a registered name: {:?}", e)),
+    };
+    xns.unregister_server(sid).ok();
+    xous::destroy_server(sid).ok();
+    outcome
+}
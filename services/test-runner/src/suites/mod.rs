@@ -0,0 +1,27 @@
+//! The registered tests. A new test is a `fn() -> Outcome` in the module for its suite, plus an entry in
+//! `TESTS`. Each one runs in a thread of its own, so a test that panics is reported as a failure rather
+//! than taking the runner down with it.
+
+mod ipc;
+mod storage;
+mod ticktimer;
+
+use crate::report::Outcome;
+
+pub(crate) struct Test {
+    pub suite: &'static str,
+    pub name: &'static str,
+    pub run: fn() -> Outcome,
+}
+
+/// Every test the runner knows about, in the order they run
+pub(crate) const TESTS: &[Test] = &[
+    Test { suite: "ticktimer", name: "elapsed_is_monotonic", run: ticktimer::elapsed_is_monotonic },
+    Test { suite: "ticktimer", name: "sleep_waits_long_enough", run: ticktimer::sleep_waits_long_enough },
+    Test { suite: "ticktimer", name: "sleepers_wake_in_order", run: ticktimer::sleepers_wake_in_order },
+    Test { suite: "ipc", name: "blocking_scalar", run: ipc::blocking_scalar },
+    Test { suite: "ipc", name: "lend_mut", run: ipc::lend_mut },
+    Test { suite: "ipc", name: "name_lookup", run: ipc::name_lookup },
+    Test { suite: "pddb", name: "key_round_trip", run: storage::key_round_trip },
+    Test { suite: "pddb", name: "missing_key_is_not_found", run: storage::missing_key_is_not_found },
+];
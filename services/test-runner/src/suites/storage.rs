@@ -0,0 +1,82 @@
+use std::io::{ErrorKind, Read, Write};
+use std::sync::OnceLock;
+
+use pddb::{Pddb, PddbMountPoller};
+use ticktimer_server::Ticktimer;
+
+use crate::report::Outcome;
+
+const DICT: &str = "test-runner";
+/// how long the PDDB server has to show up before the image is taken not to have one
+const LOOKUP_WAIT_MS: u64 = 5_000;
+/// how long to wait for someone to mount it
+const MOUNT_WAIT_MS: u64 = 30_000;
+const POLL_MS: usize = 500;
+
+/// Waits for the PDDB to be mounted, once for all the tests. `Err` says why the tests can't run.
+fn mounted() -> Result<(), String> {
+    static MOUNTED: OnceLock<Result<(), String>> = OnceLock::new();
+    MOUNTED
+        .get_or_init(|| {
+            let tt = Ticktimer::new().unwrap();
+            let start = tt.elapsed_ms();
+            let poller = loop {
+                if let Some(poller) = PddbMountPoller::try_new() {
+                    break poller;
+                }
+                if tt.elapsed_ms() - start > LOOKUP_WAIT_MS {
+                    return Err("the PDDB isn't in this image".to_string());
+                }
+                tt.sleep_ms(POLL_MS).unwrap();
+            };
+            while !poller.is_mounted_nonblocking() {
+                if tt.elapsed_ms() - start > MOUNT_WAIT_MS {
+                    return Err("the PDDB wasn't mounted".to_string());
+                }
+                tt.sleep_ms(POLL_MS).unwrap();
+            }
+            Ok(())
+        })
+        .clone()
+}
+
+pub(crate) fn key_round_trip() -> Outcome {
+    const KEY: &str = "round-trip";
+    if let Err(reason) = mounted() {
+        return Outcome::Skip(reason);
+    }
+    let pddb = Pddb::new();
+    let data: Vec<u8> = (0..=255).collect();
+    pddb.delete_key(DICT, KEY, None).ok();
+    let written = pddb
+        .get(DICT, KEY, None, true, true, Some(data.len()), None::<fn()>)
+        .and_then(|mut key| key.write_all(&data))
+        .and_then(|_| pddb.sync());
+    if let Err(e) = written {
+        return Outcome::Fail(format!("couldn't write the key: {}", e));
+    }
+    let mut read = Vec::new();
+    let outcome = match pddb
+        .get(DICT, KEY, None, false, false, None, None::<fn()>)
+        .and_then(|mut key| key.read_to_end(&mut read))
+    {
+        Ok(_) if read == data => Outcome::Pass,
+        Ok(_) => Outcome::Fail(format!("wrote {} bytes, read back {} that differ", data.len(), read.len())),
+        Err(e) => Outcome::Fail(format!("couldn't read the key back: {}", e)),
+    };
+    pddb.delete_key(DICT, KEY, None).ok();
+    pddb.sync().ok();
+    outcome
+}
+
+pub(crate) fn missing_key_is_not_found() -> Outcome {
+    if let Err(reason) = mounted() {
+        return Outcome::Skip(reason);
+    }
+    let pddb = Pddb::new();
+    match pddb.get(DICT, "never-written", None, true, false, None, None::<fn()>) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Outcome::Pass,
+        Err(e) => Outcome::Fail(format!("expected NotFound, got {:?}", e.kind())),
+        Ok(_) => Outcome::Fail("opened a key that was never written".to_string()),
+    }
+}
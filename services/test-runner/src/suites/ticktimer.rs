@@ -0,0 +1,56 @@
+use std::sync::mpsc;
+
+use ticktimer_server::Ticktimer;
+
+use crate::report::Outcome;
+
+pub(crate) fn elapsed_is_monotonic() -> Outcome {
+    let tt = Ticktimer::new().unwrap();
+    let mut last = tt.elapsed_ms();
+    for _ in 0..100 {
+        xous::yield_slice();
+        let now = tt.elapsed_ms();
+        if now < last {
+            return Outcome::Fail(format!("time went back from {}ms to {}ms", last, now));
+        }
+        last = now;
+    }
+    Outcome::Pass
+}
+
+pub(crate) fn sleep_waits_long_enough() -> Outcome {
+    const SLEEP_MS: u64 = 100;
+    let tt = Ticktimer::new().unwrap();
+    let start = tt.elapsed_ms();
+    tt.sleep_ms(SLEEP_MS as usize).unwrap();
+    let slept = tt.elapsed_ms() - start;
+    if slept < SLEEP_MS {
+        return Outcome::Fail(format!("asked for {}ms, woke after {}ms", SLEEP_MS, slept));
+    }
+    Outcome::Pass
+}
+
+/// Several threads asleep at once are woken in the order their timeouts run out, not the order they went
+/// to sleep in
+pub(crate) fn sleepers_wake_in_order() -> Outcome {
+    let (tx, rx) = mpsc::channel();
+    let sleepers: Vec<_> = [300, 100, 200]
+        .into_iter()
+        .map(|ms| {
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                Ticktimer::new().unwrap().sleep_ms(ms).unwrap();
+                tx.send(ms).unwrap();
+            })
+        })
+        .collect();
+    drop(tx);
+    for sleeper in sleepers {
+        sleeper.join().unwrap();
+    }
+    let order: Vec<usize> = rx.iter().collect();
+    if order != [100, 200, 300] {
+        return Outcome::Fail(format!("woke in the order {:?}", order));
+    }
+    Outcome::Pass
+}
@@ -0,0 +1,60 @@
+pub(crate) const SERVER_NAME_THERMAL: &str = "_Thermal monitor_";
+
+/// How hot the device is running, going by whichever sensor is hottest compared to its limits
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub enum ThermalLevel {
+    Normal = 0,
+    /// Nothing is throttled yet, but the device is getting there
+    Warm = 1,
+    /// Background jobs should wait, and the backlight is dimmed
+    Hot = 2,
+    /// As for `Hot`, and the user is told to let the device cool down
+    Critical = 3,
+}
+
+/// The latest readings of the sensors, in thousandths of a degree Celsius
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Readings {
+    /// the SoC die, as measured by the FPGA's XADC
+    pub soc: i32,
+    /// the battery, as measured by the gas gauge on the EC; `None` if the EC firmware can't report it
+    pub battery: Option<i32>,
+    pub level: ThermalLevel,
+}
+
+/// A server that wants to know when the `ThermalLevel` changes. It is sent a scalar message with
+/// `opcode` as its id and arg1 set to the new level.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ThermalSubscription {
+    pub sid: [u32; 4],
+    pub opcode: u32,
+}
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// The latest readings (memory message, `Readings`)
+    Readings = 0,
+    /// The current level (blocking scalar, returns the `ThermalLevel`)
+    Level = 1,
+    /// Be told when the level changes (memory message, `ThermalSubscription`)
+    Subscribe = 2,
+    /// Stop being told (memory message, `ThermalSubscription`; only the `sid` is used)
+    Unsubscribe = 3,
+    /// [internal] read the sensors again
+    Pump = 4,
+    /// Exits the server
+    Quit = 5,
+}
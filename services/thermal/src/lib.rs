@@ -0,0 +1,68 @@
+#![doc = include_str!("../README.md")]
+
+pub mod api;
+pub use api::*;
+use num_traits::*;
+use xous::{send_message, Message, CID};
+use xous_ipc::Buffer;
+
+pub struct Thermal {
+    conn: CID,
+}
+impl Thermal {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_THERMAL)?;
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(Thermal { conn })
+    }
+
+    /// The readings taken the last time the sensors were polled
+    pub fn readings(&self) -> Result<Readings, xous::Error> {
+        let readings = Readings { soc: 0, battery: None, level: ThermalLevel::Normal };
+        let mut buf = Buffer::into_buf(readings).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::Readings.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        buf.to_original::<Readings, _>().or(Err(xous::Error::InternalError))
+    }
+
+    pub fn level(&self) -> Result<ThermalLevel, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::Level.to_usize().unwrap(), 0, 0, 0, 0),
+        )? {
+            xous::Result::Scalar1(level) => {
+                FromPrimitive::from_usize(level).ok_or(xous::Error::InternalError)
+            }
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Whether background work, such as scans and syncs that nobody is waiting on, should be put off
+    /// until the device cools down
+    pub fn throttled(&self) -> Result<bool, xous::Error> { Ok(self.level()? >= ThermalLevel::Hot) }
+
+    /// Asks for a scalar message with id `opcode` to be sent to `sid` whenever the level changes; see
+    /// `ThermalSubscription`. Subscribing again with the same `sid` replaces the opcode.
+    pub fn subscribe(&self, sid: xous::SID, opcode: u32) -> Result<(), xous::Error> {
+        let sub = ThermalSubscription { sid: sid.to_array(), opcode };
+        let buf = Buffer::into_buf(sub).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, Opcode::Subscribe.to_u32().unwrap()).map(|_| ())
+    }
+
+    pub fn unsubscribe(&self, sid: xous::SID) -> Result<(), xous::Error> {
+        let sub = ThermalSubscription { sid: sid.to_array(), opcode: 0 };
+        let buf = Buffer::into_buf(sub).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, Opcode::Unsubscribe.to_u32().unwrap()).map(|_| ())
+    }
+}
+
+use core::sync::atomic::{AtomicU32, Ordering};
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Thermal {
+    fn drop(&mut self) {
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+mod api;
+use api::*;
+mod policy;
+
+use std::collections::HashMap;
+
+use num_traits::*;
+use xous::{msg_blocking_scalar_unpack, msg_scalar_unpack, Message};
+use xous_ipc::Buffer;
+
+/// How often the sensors are read. The EC is asked over the COM link, so this is kept slow.
+const SENSOR_INTERVAL_MS: usize = 10_000;
+
+/// The SoC die temperature in thousandths of a degree Celsius, from a raw XADC reading
+fn xadc_to_millicelsius(raw: u16) -> i32 { ((raw as i32 * 12304) - 27_315_000) / 100 }
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let thermal_sid = xns.register_name(api::SERVER_NAME_THERMAL, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", thermal_sid);
+    let self_cid = xous::connect(thermal_sid).unwrap();
+
+    let llio = llio::Llio::new(&xns);
+    let com = com::Com::new(&xns).unwrap();
+    let mut policy = policy::Policy::new();
+    let mut readings = Readings { soc: 0, battery: None, level: ThermalLevel::Normal };
+    let mut subscribers: HashMap<[u32; 4], (xous::CID, u32)> = HashMap::new();
+
+    std::thread::spawn(move || {
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        loop {
+            if xous::send_message(self_cid, Message::new_scalar(Opcode::Pump.to_usize().unwrap(), 0, 0, 0, 0))
+                .is_err()
+            {
+                break;
+            }
+            tt.sleep_ms(SENSOR_INTERVAL_MS).unwrap();
+        }
+    });
+
+    loop {
+        let msg = xous::receive_message(thermal_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Readings) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                buffer.replace(readings).unwrap();
+            }
+            Some(Opcode::Level) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                xous::return_scalar(msg.sender, readings.level.to_usize().unwrap()).ok();
+            }),
+            Some(Opcode::Subscribe) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let sub = buffer.to_original::<ThermalSubscription, _>().unwrap();
+                match subscribers.get_mut(&sub.sid) {
+                    Some(entry) => entry.1 = sub.opcode,
+                    None => match xous::connect(xous::SID::from_array(sub.sid)) {
+                        Ok(cid) => {
+                            subscribers.insert(sub.sid, (cid, sub.opcode));
+                        }
+                        Err(e) => log::warn!("couldn't connect to thermal subscriber: {:?}", e),
+                    },
+                }
+            }
+            Some(Opcode::Unsubscribe) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let sub = buffer.to_original::<ThermalSubscription, _>().unwrap();
+                if let Some((cid, _)) = subscribers.remove(&sub.sid) {
+                    unsafe { xous::disconnect(cid).ok() };
+                }
+            }
+            Some(Opcode::Pump) => msg_scalar_unpack!(msg, _, _, _, _, {
+                let soc = match llio.adc_temperature() {
+                    Ok(raw) => xadc_to_millicelsius(raw),
+                    Err(e) => {
+                        log::warn!("couldn't read the SoC temperature: {:?}", e);
+                        continue;
+                    }
+                };
+                let battery = com.get_ec_temperature().unwrap_or(None);
+                let level = policy.update(soc, battery);
+                log::debug!("SoC {}m°C, battery {:?}m°C: {:?}", soc, battery, level);
+                if level != readings.level {
+                    log::info!(
+                        "thermal level {:?} -> {:?} (SoC {}m°C, battery {:?}m°C)",
+                        readings.level,
+                        level,
+                        soc,
+                        battery
+                    );
+                    for (&sid, &(cid, opcode)) in subscribers.iter() {
+                        if let Err(e) = xous::try_send_message(
+                            cid,
+                            Message::new_scalar(opcode as usize, level.to_usize().unwrap(), 0, 0, 0),
+                        ) {
+                            log::warn!("couldn't tell {:x?} about the thermal level: {:?}", sid, e);
+                        }
+                    }
+                }
+                readings = Readings { soc, battery, level };
+            }),
+            Some(Opcode::Quit) => {
+                xous::return_scalar(msg.sender, 1).ok();
+                break;
+            }
+            None => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+    for (_, (cid, _)) in subscribers.drain() {
+        unsafe { xous::disconnect(cid).ok() };
+    }
+    xns.unregister_server(thermal_sid).unwrap();
+    xous::destroy_server(thermal_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
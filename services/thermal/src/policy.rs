@@ -0,0 +1,93 @@
+use crate::api::ThermalLevel;
+
+/// How far a sensor has to cool below a limit before its level drops, so that a reading that hovers
+/// around a limit doesn't flip the level back and forth
+const HYSTERESIS: i32 = 3_000;
+
+/// The temperatures at which a sensor is `Warm`, `Hot` and `Critical`, in thousandths of a degree Celsius
+pub(crate) struct Limits {
+    warm: i32,
+    hot: i32,
+    critical: i32,
+}
+
+/// The FPGA is rated to 85°C; leave it some margin
+pub(crate) const SOC_LIMITS: Limits = Limits { warm: 65_000, hot: 75_000, critical: 85_000 };
+/// Li-ion cells shouldn't be charged above 45°C, and shouldn't be run much above 55°C
+pub(crate) const BATTERY_LIMITS: Limits = Limits { warm: 40_000, hot: 45_000, critical: 55_000 };
+
+impl Limits {
+    fn crossed(&self, temp: i32) -> ThermalLevel {
+        if temp >= self.critical {
+            ThermalLevel::Critical
+        } else if temp >= self.hot {
+            ThermalLevel::Hot
+        } else if temp >= self.warm {
+            ThermalLevel::Warm
+        } else {
+            ThermalLevel::Normal
+        }
+    }
+
+    /// The level of a sensor reading `temp`, which was at `previous`
+    pub(crate) fn level(&self, temp: i32, previous: ThermalLevel) -> ThermalLevel {
+        let level = self.crossed(temp);
+        if level >= previous { level } else { self.crossed(temp + HYSTERESIS).min(previous) }
+    }
+}
+
+/// Tracks the level of each sensor; the device is at the level of the worst one
+pub(crate) struct Policy {
+    soc: ThermalLevel,
+    battery: ThermalLevel,
+}
+
+impl Policy {
+    pub(crate) fn new() -> Self { Policy { soc: ThermalLevel::Normal, battery: ThermalLevel::Normal } }
+
+    /// Takes new readings and returns the level. A battery reading of `None` leaves the battery at the
+    /// level it was.
+    pub(crate) fn update(&mut self, soc: i32, battery: Option<i32>) -> ThermalLevel {
+        self.soc = SOC_LIMITS.level(soc, self.soc);
+        if let Some(battery) = battery {
+            self.battery = BATTERY_LIMITS.level(battery, self.battery);
+        }
+        self.level()
+    }
+
+    pub(crate) fn level(&self) -> ThermalLevel { self.soc.max(self.battery) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_rise_at_the_limits() {
+        let mut policy = Policy::new();
+        assert_eq!(policy.update(40_000, Some(25_000)), ThermalLevel::Normal);
+        assert_eq!(policy.update(65_000, Some(25_000)), ThermalLevel::Warm);
+        // the battery is hotter relative to its own limits
+        assert_eq!(policy.update(65_000, Some(46_000)), ThermalLevel::Hot);
+        assert_eq!(policy.update(90_000, None), ThermalLevel::Critical);
+    }
+
+    #[test]
+    fn levels_fall_with_hysteresis() {
+        let mut policy = Policy::new();
+        assert_eq!(policy.update(76_000, None), ThermalLevel::Hot);
+        // just under the limit isn't cool enough
+        assert_eq!(policy.update(74_000, None), ThermalLevel::Hot);
+        assert_eq!(policy.update(71_000, None), ThermalLevel::Warm);
+        // a sudden drop goes straight down
+        assert_eq!(policy.update(30_000, None), ThermalLevel::Normal);
+    }
+
+    #[test]
+    fn missing_battery_reading_keeps_its_level() {
+        let mut policy = Policy::new();
+        assert_eq!(policy.update(30_000, Some(50_000)), ThermalLevel::Hot);
+        assert_eq!(policy.update(30_000, None), ThermalLevel::Hot);
+        assert_eq!(policy.update(30_000, Some(20_000)), ThermalLevel::Normal);
+    }
+}
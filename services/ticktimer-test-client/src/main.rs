@@ -6,6 +6,9 @@ use std::time::Duration;
 
 use log::info;
 
+#[cfg(feature = "stress")]
+mod stress;
+
 fn main() -> ! {
     log_server::init_wait().unwrap();
     log::set_max_level(log::LevelFilter::Trace);
@@ -21,6 +24,9 @@ fn main() -> ! {
     #[cfg(feature = "susres-testing")]
     let susres = susres::Susres::new_without_hook(&xns).unwrap();
 
+    #[cfg(feature = "stress")]
+    stress::run();
+
     for i in 0.. {
         info!("Loop #{}, waiting {} ms", i, DELAY_MS);
         sleep(Duration::from_millis(DELAY_MS));
@@ -0,0 +1,91 @@
+//! Stress benchmark for the ticktimer's sleep queue.
+//!
+//! Many threads hold timeouts at once, as they do when dozens of services are running: half of them
+//! sleep, and the other half wait on condvars with timeouts. Some waiters are notified before their timeout
+//! expires, which removes it from the middle of the queue, and the rest time out. Each round reports how
+//! late the sleepers were woken, which grows with the cost of managing the queue.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::info;
+
+const SLEEPERS: usize = 32;
+const WAITERS: usize = 32;
+const ROUNDS: usize = 20;
+const SLEEPS_PER_ROUND: usize = 10;
+
+pub fn run() {
+    let notify = Arc::new((Mutex::new(0usize), Condvar::new()));
+    for round in 0..ROUNDS {
+        let start = Instant::now();
+        let mut threads = Vec::new();
+        for i in 0..SLEEPERS {
+            threads.push(thread::spawn(move || {
+                let mut worst = Duration::ZERO;
+                let mut total = Duration::ZERO;
+                for j in 0..SLEEPS_PER_ROUND {
+                    // spread the requests so that they interleave in the queue
+                    let request = Duration::from_millis(5 + ((i * 7 + j * 13) % 40) as u64);
+                    let before = Instant::now();
+                    thread::sleep(request);
+                    let late = before.elapsed().saturating_sub(request);
+                    worst = worst.max(late);
+                    total += late;
+                }
+                (worst, total)
+            }));
+        }
+        let mut waiters = Vec::new();
+        for i in 0..WAITERS {
+            let notify = notify.clone();
+            waiters.push(thread::spawn(move || {
+                let (lock, cvar) = &*notify;
+                let mut generation = lock.lock().unwrap();
+                let seen = *generation;
+                while *generation == seen {
+                    let timeout = Duration::from_millis(20 + (i % 50) as u64);
+                    let (next, _) = cvar.wait_timeout(generation, timeout).unwrap();
+                    generation = next;
+                }
+            }));
+        }
+        // keep notifying the waiters until they have all seen a notification
+        let waiters_done = Arc::new(Mutex::new(false));
+        let notifier = {
+            let notify = notify.clone();
+            let waiters_done = waiters_done.clone();
+            thread::spawn(move || {
+                while !*waiters_done.lock().unwrap() {
+                    thread::sleep(Duration::from_millis(3));
+                    let (lock, cvar) = &*notify;
+                    *lock.lock().unwrap() += 1;
+                    cvar.notify_one();
+                }
+            })
+        };
+
+        let mut worst = Duration::ZERO;
+        let mut total = Duration::ZERO;
+        for t in threads {
+            let (w, t) = t.join().unwrap();
+            worst = worst.max(w);
+            total += t;
+        }
+        for w in waiters {
+            w.join().unwrap();
+        }
+        *waiters_done.lock().unwrap() = true;
+        notifier.join().unwrap();
+
+        info!(
+            "stress round {}: {} sleeps, mean lateness {} us, worst {} ms, round took {} ms",
+            round,
+            SLEEPERS * SLEEPS_PER_ROUND,
+            total.as_micros() / (SLEEPERS * SLEEPS_PER_ROUND) as u128,
+            worst.as_millis(),
+            start.elapsed().as_millis()
+        );
+    }
+}
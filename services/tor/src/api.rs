@@ -0,0 +1,31 @@
+pub(crate) const SERVER_NAME_TOR: &str = "_Tor client_";
+
+/// The loopback port of the SOCKS5 endpoint
+pub const TOR_SOCKS_PORT: u16 = 9050;
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// The `TorState`, and how far bootstrapping has got in percent. Blocking scalar, returns Scalar2.
+    State = 0,
+    /// Whether every TCP connection on the device goes through Tor. Blocking scalar, returns 1 or 0.
+    RouteAll = 1,
+    /// Sends every TCP connection through Tor, or stops doing so. Blocking scalar, arg1 = 1 for on;
+    /// returns the resulting state.
+    SetRouteAll = 2,
+    /// Internal: bootstrapping has progressed. Scalar, arg1 = `TorState`, arg2 = percent.
+    Progress = 3,
+    /// Exits the server
+    Quit = 4,
+}
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TorState {
+    /// Waiting for the PDDB, where the directory cache and the guard state are kept
+    Waiting = 0,
+    /// Fetching the directory and building the first circuits
+    Bootstrapping = 1,
+    /// The SOCKS endpoint is serving
+    Ready = 2,
+    /// Bootstrapping failed; the reason is in the log
+    Failed = 3,
+}
@@ -0,0 +1,245 @@
+//! The Tor client proper: bootstraps with arti, then serves SOCKS5 CONNECT and RESOLVE requests from
+//! processes on the device over Tor. Runs on its own thread, with a single threaded tokio runtime.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use arti_client::config::TorClientConfigBuilder;
+use arti_client::{HasKind, TorAddr, TorClient};
+use futures::StreamExt;
+use num_traits::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tor_rtcompat::PreferredRuntime;
+
+use crate::api::*;
+
+/// PDDB dicts, reached through `std::fs`, that arti keeps its guard state and directory cache in
+const STATE_DIR: &str = "tor.state";
+const CACHE_DIR: &str = "tor.cache";
+
+const SOCKS_VERSION: u8 = 5;
+const METHOD_NONE: u8 = 0;
+const METHOD_UNACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 1;
+/// Tor's extension: look a name up, and answer with its address in place of the bound one
+const CMD_RESOLVE: u8 = 0xf0;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+const REPLY_SUCCEEDED: u8 = 0;
+const REPLY_GENERAL_FAILURE: u8 = 1;
+const REPLY_HOST_UNREACHABLE: u8 = 4;
+const REPLY_COMMAND_NOT_SUPPORTED: u8 = 7;
+const REPLY_ADDRESS_NOT_SUPPORTED: u8 = 8;
+
+fn report(main_cid: xous::CID, state: TorState, percent: u8) {
+    xous::send_message(
+        main_cid,
+        xous::Message::new_scalar(
+            Opcode::Progress.to_usize().unwrap(),
+            state.to_usize().unwrap(),
+            percent as usize,
+            0,
+            0,
+        ),
+    )
+    .ok();
+}
+
+pub(crate) fn run(main_cid: xous::CID) {
+    pddb::Pddb::new().is_mounted_blocking();
+    report(main_cid, TorState::Bootstrapping, 0);
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async move {
+        let mut config = TorClientConfigBuilder::from_directories(STATE_DIR, CACHE_DIR);
+        // the PDDB has no notion of file ownership or modes for arti to check
+        config.storage().permissions().dangerously_trust_everyone();
+        let config = match config.build() {
+            Ok(config) => config,
+            Err(e) => {
+                log::error!("bad Tor configuration: {}", e);
+                report(main_cid, TorState::Failed, 0);
+                return;
+            }
+        };
+        let client = match TorClient::builder().config(config).create_unbootstrapped() {
+            Ok(client) => client,
+            Err(e) => {
+                log::error!("couldn't create the Tor client: {}", e);
+                report(main_cid, TorState::Failed, 0);
+                return;
+            }
+        };
+        let mut events = client.bootstrap_events();
+        tokio::spawn(async move {
+            while let Some(status) = events.next().await {
+                if !status.ready_for_traffic() {
+                    report(main_cid, TorState::Bootstrapping, (status.as_frac() * 100.0) as u8);
+                }
+            }
+        });
+        if let Err(e) = client.bootstrap().await {
+            log::error!("Tor bootstrap failed: {}", e);
+            report(main_cid, TorState::Failed, 0);
+            return;
+        }
+
+        let listener = match TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], TOR_SOCKS_PORT))).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::error!("couldn't listen on port {}: {:?}", TOR_SOCKS_PORT, e);
+                report(main_cid, TorState::Failed, 0);
+                return;
+            }
+        };
+        log::info!("Tor is up, SOCKS5 on 127.0.0.1:{}", TOR_SOCKS_PORT);
+        report(main_cid, TorState::Ready, 100);
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    // only loopback is bound, but be sure the endpoint never serves the network
+                    if !peer.ip().is_loopback() {
+                        log::warn!("refused SOCKS client {}", peer);
+                        continue;
+                    }
+                    let client = client.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = serve(&client, stream).await {
+                            log::debug!("SOCKS client error: {:?}", e);
+                        }
+                    });
+                }
+                Err(e) => log::warn!("accept failed: {:?}", e),
+            }
+        }
+    });
+}
+
+async fn reply(stream: &mut TcpStream, code: u8) -> std::io::Result<()> {
+    // the bound address is meaningless over Tor, so it is left as 0.0.0.0:0
+    stream.write_all(&[SOCKS_VERSION, code, 0, ATYP_IPV4, 0, 0, 0, 0, 0, 0]).await
+}
+
+async fn reply_with_address(stream: &mut TcpStream, addr: IpAddr) -> std::io::Result<()> {
+    let mut reply = vec![SOCKS_VERSION, REPLY_SUCCEEDED, 0];
+    match addr {
+        IpAddr::V4(ip) => {
+            reply.push(ATYP_IPV4);
+            reply.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            reply.push(ATYP_IPV6);
+            reply.extend_from_slice(&ip.octets());
+        }
+    }
+    reply.extend_from_slice(&[0, 0]);
+    stream.write_all(&reply).await
+}
+
+fn protocol_error(what: &str) -> std::io::Error { std::io::Error::new(std::io::ErrorKind::InvalidData, what) }
+
+/// One SOCKS5 client: no authentication, and CONNECT or RESOLVE
+async fn serve(client: &TorClient<PreferredRuntime>, mut stream: TcpStream) -> std::io::Result<()> {
+    let mut head = [0u8; 2];
+    stream.read_exact(&mut head).await?;
+    if head[0] != SOCKS_VERSION {
+        return Err(protocol_error("not SOCKS5"));
+    }
+    let mut methods = vec![0u8; head[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&METHOD_NONE) {
+        stream.write_all(&[SOCKS_VERSION, METHOD_UNACCEPTABLE]).await?;
+        return Err(protocol_error("client insists on authentication"));
+    }
+    stream.write_all(&[SOCKS_VERSION, METHOD_NONE]).await?;
+
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await?;
+    if request[0] != SOCKS_VERSION {
+        return Err(protocol_error("not SOCKS5"));
+    }
+    if request[1] == CMD_RESOLVE {
+        return resolve(client, stream, request[3]).await;
+    }
+    if request[1] != CMD_CONNECT {
+        return reply(&mut stream, REPLY_COMMAND_NOT_SUPPORTED).await;
+    }
+    // addresses were looked up outside of Tor, so arti has to be told that it is fine to use them
+    let target = match request[3] {
+        ATYP_IPV4 => {
+            let mut ip = [0u8; 4];
+            stream.read_exact(&mut ip).await?;
+            let port = stream.read_u16().await?;
+            TorAddr::dangerously_from((IpAddr::from(Ipv4Addr::from(ip)), port))
+        }
+        ATYP_IPV6 => {
+            let mut ip = [0u8; 16];
+            stream.read_exact(&mut ip).await?;
+            let port = stream.read_u16().await?;
+            TorAddr::dangerously_from((IpAddr::from(Ipv6Addr::from(ip)), port))
+        }
+        ATYP_DOMAIN => {
+            let len = stream.read_u8().await?;
+            let mut name = vec![0u8; len as usize];
+            stream.read_exact(&mut name).await?;
+            let port = stream.read_u16().await?;
+            match String::from_utf8(name) {
+                Ok(name) => TorAddr::from((name, port)),
+                Err(_) => return reply(&mut stream, REPLY_ADDRESS_NOT_SUPPORTED).await,
+            }
+        }
+        _ => return reply(&mut stream, REPLY_ADDRESS_NOT_SUPPORTED).await,
+    };
+    let target = match target {
+        Ok(target) => target,
+        Err(e) => {
+            log::debug!("unusable target: {}", e);
+            return reply(&mut stream, REPLY_ADDRESS_NOT_SUPPORTED).await;
+        }
+    };
+
+    match client.connect(target).await {
+        Ok(mut tor_stream) => {
+            reply(&mut stream, REPLY_SUCCEEDED).await?;
+            tokio::io::copy_bidirectional(&mut stream, &mut tor_stream).await.map(|_| ())
+        }
+        Err(e) => {
+            log::info!("couldn't connect over Tor: {}", e);
+            let code = match e.kind() {
+                arti_client::ErrorKind::RemoteHostNotFound
+                | arti_client::ErrorKind::RemoteNetworkFailed
+                | arti_client::ErrorKind::RemoteConnectionRefused => REPLY_HOST_UNREACHABLE,
+                _ => REPLY_GENERAL_FAILURE,
+            };
+            reply(&mut stream, code).await
+        }
+    }
+}
+
+/// Looks a name up over Tor, for the DNS server while every connection goes through Tor
+async fn resolve(
+    client: &TorClient<PreferredRuntime>,
+    mut stream: TcpStream,
+    atyp: u8,
+) -> std::io::Result<()> {
+    if atyp != ATYP_DOMAIN {
+        return reply(&mut stream, REPLY_ADDRESS_NOT_SUPPORTED).await;
+    }
+    let len = stream.read_u8().await?;
+    let mut name = vec![0u8; len as usize];
+    stream.read_exact(&mut name).await?;
+    // the port means nothing to a lookup
+    stream.read_u16().await?;
+    let Ok(name) = String::from_utf8(name) else {
+        return reply(&mut stream, REPLY_ADDRESS_NOT_SUPPORTED).await;
+    };
+    match client.resolve(&name).await {
+        Ok(addrs) if !addrs.is_empty() => reply_with_address(&mut stream, addrs[0]).await,
+        Ok(_) => reply(&mut stream, REPLY_HOST_UNREACHABLE).await,
+        Err(e) => {
+            log::debug!("a lookup over Tor failed: {}", e);
+            reply(&mut stream, REPLY_HOST_UNREACHABLE).await
+        }
+    }
+}
@@ -0,0 +1,80 @@
+pub mod api;
+pub use api::*;
+use num_traits::*;
+use xous::{CID, Message, send_message};
+
+/// Client API to the Tor service. The service is only in images built with `--service tor`, so `new()`
+/// fails rather than waits when it is absent.
+pub struct Tor {
+    conn: CID,
+}
+impl Tor {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection(api::SERVER_NAME_TOR)?;
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(Tor { conn })
+    }
+
+    /// The state of the client, and how far bootstrapping has got in percent
+    pub fn state(&self) -> Result<(TorState, u8), xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::State.to_usize().unwrap(), 0, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar2(state, percent)) => {
+                Ok((FromPrimitive::from_usize(state).ok_or(xous::Error::InternalError)?, percent as u8))
+            }
+            Ok(_) => Err(xous::Error::InternalError),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The SOCKS5 endpoint, for use with `net::protocols::Socks5`. Only processes on the device can
+    /// reach it.
+    pub fn socks_proxy() -> net::SocksProxy {
+        net::SocksProxy {
+            addr: std::net::SocketAddr::from(([127, 0, 0, 1], TOR_SOCKS_PORT)),
+            credentials: None,
+        }
+    }
+
+    /// Whether every TCP connection on the device is sent through Tor
+    pub fn route_all(&self) -> Result<bool, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::RouteAll.to_usize().unwrap(), 0, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar1(on)) => Ok(on != 0),
+            Ok(_) => Err(xous::Error::InternalError),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sends every TCP connection on the device through Tor, by making the SOCKS endpoint the net
+    /// server's proxy, and has names looked up over Tor too. Turning it on asks the user to let the Tor
+    /// client's own connections skip the proxy; if they don't, it stays off.
+    pub fn set_route_all(&self, on: bool) -> Result<bool, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::SetRouteAll.to_usize().unwrap(), on as usize, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar1(on)) => Ok(on != 0),
+            Ok(_) => Err(xous::Error::InternalError),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+use core::sync::atomic::{AtomicU32, Ordering};
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Tor {
+    fn drop(&mut self) {
+        // now de-allocate myself. It's unsafe because we are responsible to make sure nobody else is using
+        // the connection.
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
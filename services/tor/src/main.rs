@@ -0,0 +1,119 @@
+mod api;
+use api::*;
+mod client;
+
+use locales::t;
+use num_traits::*;
+use xous::msg_blocking_scalar_unpack;
+
+/// id of the status bar indicator
+const INDICATOR_ID: &str = "tor";
+/// concentric rings, the closest glyph to an onion
+const INDICATOR_ICON: char = '\u{25CE}';
+const INDICATOR_PRIORITY: u8 = 100;
+
+/// The indicator shows bootstrapping progress, and goes bare once circuits can be built
+fn show_indicator(status: &status::Status, state: TorState, percent: u8) {
+    let text = match state {
+        TorState::Waiting => String::new(),
+        TorState::Bootstrapping => format!("{}", percent.min(99)),
+        TorState::Ready => String::new(),
+        TorState::Failed => "!".to_string(),
+    };
+    status
+        .set_indicator(INDICATOR_ID, INDICATOR_ICON, &text, INDICATOR_PRIORITY, None)
+        .unwrap_or_else(|e| log::warn!("couldn't set the status indicator: {:?}", e));
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let tor_sid = xns.register_name(api::SERVER_NAME_TOR, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", tor_sid);
+    let self_cid = xous::connect(tor_sid).unwrap();
+
+    // the net server takes only as many proxy controllers as the image has, and only at boot
+    let proxy_control = net::ProxyControl::new(&xns).expect("couldn't claim control of the proxy");
+    let status = status::Status::new(&xns).unwrap();
+    let (mut state, mut percent) = (TorState::Waiting, 0u8);
+    let mut route_all = false;
+    // whether the user has let our own connections skip the proxy, which they must when every connection is
+    // routed through Tor, lest the ones to the relays loop back into the SOCKS endpoint
+    let mut bypassing = false;
+    show_indicator(&status, state, percent);
+
+    std::thread::spawn(move || client::run(self_cid));
+    // registering waits on the quick settings server, which the main loop must not
+    std::thread::spawn(move || {
+        let xns = xous_names::XousNames::new().unwrap();
+        let quick_settings = quick_settings::QuickSettings::new(&xns).unwrap();
+        quick_settings
+            .register(
+                "tor.route_all",
+                t!("tor.quick.route_all", locales::LANG),
+                tor_sid,
+                Opcode::RouteAll.to_u32().unwrap(),
+                Opcode::SetRouteAll.to_u32().unwrap(),
+            )
+            .unwrap_or_else(|e| log::warn!("couldn't register quick setting: {:?}", e));
+    });
+
+    loop {
+        let msg = xous::receive_message(tor_sid).unwrap();
+        log::debug!("message: {:?}", msg);
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::State) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                xous::return_scalar2(msg.sender, state.to_usize().unwrap(), percent as usize)
+                    .expect("couldn't return State");
+            }),
+            Some(Opcode::RouteAll) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                xous::return_scalar(msg.sender, route_all as usize).expect("couldn't return RouteAll");
+            }),
+            Some(Opcode::SetRouteAll) => msg_blocking_scalar_unpack!(msg, on, _, _, _, {
+                let on = on != 0;
+                if on && !bypassing {
+                    bypassing = proxy_control.socks_proxy_bypass().unwrap_or(false);
+                }
+                let proxy = tor::Tor::socks_proxy();
+                let result = if !on {
+                    proxy_control.set_socks_proxy(None)
+                } else if bypassing {
+                    // while everything goes over Tor, so do the lookups
+                    proxy_control.set_resolving_socks_proxy(&proxy)
+                } else {
+                    // our connections to the relays would loop back into the SOCKS endpoint
+                    Err(xous::Error::AccessDenied)
+                };
+                match result {
+                    Ok(()) => {
+                        log::info!("routing every connection through Tor: {}", on);
+                        route_all = on;
+                    }
+                    Err(e) => log::warn!("couldn't set the proxy: {:?}", e),
+                }
+                xous::return_scalar(msg.sender, route_all as usize).expect("couldn't return SetRouteAll");
+            }),
+            Some(Opcode::Progress) => xous::msg_scalar_unpack!(msg, new_state, new_percent, _, _, {
+                state = FromPrimitive::from_usize(new_state).unwrap_or(TorState::Failed);
+                percent = new_percent as u8;
+                show_indicator(&status, state, percent);
+            }),
+            Some(Opcode::Quit) => {
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+    if route_all {
+        proxy_control.set_socks_proxy(None).ok();
+    }
+    status.clear_indicator(INDICATOR_ID).ok();
+    xns.unregister_server(tor_sid).unwrap();
+    xous::destroy_server(tor_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
@@ -0,0 +1,54 @@
+pub(crate) const SERVER_NAME_UPDATER: &str = "_OS updater_";
+
+/// PDDB dict holding the updater configuration
+pub const UPDATER_DICT: &str = "updater.config";
+/// URL of the signed update manifest
+pub const UPDATER_SOURCE_KEY: &str = "source";
+/// hex-encoded ed25519 public key that update manifests must be signed with
+pub const UPDATER_SIGNING_KEY: &str = "signing_key";
+/// newline-separated `tls.trusted` keys of the trust-anchors pinned for the source host
+pub const UPDATER_PINS_KEY: &str = "pins";
+/// manifest location used until the user configures another one
+pub const DEFAULT_MANIFEST_URL: &str = "https://ci.betrusted.io/releases/latest/update.manifest";
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// Fetch the manifest and report if a newer version is available. Memory, lend_mut of `UpdateCheck`.
+    Check = 0,
+    /// Download, verify and stage an update, then walk the user through installing it and rebooting.
+    /// Scalar, non-blocking: all further interaction happens through modals.
+    Install = 1,
+    /// Change the manifest source and/or the signing key. Memory, lend_mut of `UpdaterConfig`.
+    Configure = 2,
+    /// Exits the server
+    Quit = 3,
+    /// [internal] the next block of a DFU download, from the relay thread. Memory, lend_mut of `DfuBlock`.
+    DfuBlock = 4,
+    /// [internal] the host has sent the whole DFU download. Blocking scalar, returns the `DfuStatus`.
+    DfuEnd = 5,
+    /// [internal] the host gave up on the DFU download. Scalar.
+    DfuAbort = 6,
+    /// [internal] asks the user to install a DFU download that checked out. Scalar.
+    DfuInstall = 7,
+}
+
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct UpdateCheck {
+    /// version of the running system
+    pub current: xous_ipc::String<64>,
+    /// version offered by the manifest, if it is newer than `current`
+    pub available: Option<xous_ipc::String<64>>,
+    pub error: Option<xous_ipc::String<128>>,
+}
+
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct UpdaterConfig {
+    /// new manifest URL. The trust-anchors its host currently offers (and that are already
+    /// trusted in the `tls.trusted` store) become the pins for all subsequent downloads.
+    pub source: Option<xous_ipc::String<256>>,
+    /// new hex-encoded ed25519 public key for manifest signatures
+    pub signing_key: Option<xous_ipc::String<64>>,
+    /// return value: number of trust-anchors pinned for the source host
+    pub pins: u32,
+    pub error: Option<xous_ipc::String<128>>,
+}
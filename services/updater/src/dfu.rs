@@ -0,0 +1,148 @@
+//! Updates pushed over USB with `dfu-util`. The download is the signed update manifest, followed by the
+//! kernel image:
+//!
+//! ```text
+//! cat update.manifest xous.img > update.dfu
+//! dfu-util -w -R -D update.dfu
+//! ```
+//!
+//! The manifest is the same one that network updates use. Its URL goes unused here, but its length and
+//! hash are what delimit the kernel and check it. Nothing is written until the manifest's signature has
+//! been verified, and the kernel is then streamed into the staging area as it arrives, just like a
+//! downloaded one.
+
+use usb_device_xous::{DFU_TRANSFER_SIZE, DfuStatus};
+
+use crate::manifest::{Manifest, MAX_MANIFEST_LEN};
+use crate::stage::{KernelWriter, Staging};
+
+/// A block of a DFU download, relayed from the USB stack to the main loop, which owns the staging slot
+#[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct DfuBlock {
+    pub data: [u8; DFU_TRANSFER_SIZE],
+    pub len: u16,
+    /// return value: the `DfuStatus` of the block
+    pub status: u8,
+}
+
+enum Phase {
+    /// collecting the manifest, which ends with its `signature` line
+    Manifest(Vec<u8>),
+    Kernel {
+        manifest: Manifest,
+        writer: KernelWriter,
+    },
+    /// the kernel has arrived and checks out
+    Complete(Manifest),
+}
+
+pub(crate) struct DfuDownload {
+    phase: Phase,
+}
+
+/// The length of the manifest at the start of `data`, once all of it has arrived
+fn manifest_len(data: &[u8]) -> Option<usize> {
+    let sig_start = data.windows(11).position(|w| w == b"\nsignature ")? + 1;
+    data[sig_start..].iter().position(|&b| b == b'\n').map(|end| sig_start + end + 1)
+}
+
+impl DfuDownload {
+    pub fn new() -> Self { DfuDownload { phase: Phase::Manifest(Vec::new()) } }
+
+    /// Takes the next block of the download. `verify` checks the manifest once it is complete. After an
+    /// error the download starts over, as the host has to.
+    pub fn feed(
+        &mut self,
+        data: &[u8],
+        staging: &mut Staging,
+        verify: impl FnOnce(&str) -> Result<Manifest, DfuStatus>,
+    ) -> Result<(), DfuStatus> {
+        let result = self.advance(data, staging, verify);
+        if result.is_err() {
+            self.phase = Phase::Manifest(Vec::new());
+        }
+        result
+    }
+
+    fn advance(
+        &mut self,
+        data: &[u8],
+        staging: &mut Staging,
+        verify: impl FnOnce(&str) -> Result<Manifest, DfuStatus>,
+    ) -> Result<(), DfuStatus> {
+        let mut phase = std::mem::replace(&mut self.phase, Phase::Manifest(Vec::new()));
+        let rest;
+        let mut data = data;
+        if let Phase::Manifest(mut text) = phase {
+            text.extend_from_slice(data);
+            let len = match manifest_len(&text) {
+                Some(len) => len,
+                None if text.len() > MAX_MANIFEST_LEN => return Err(DfuStatus::ErrTarget),
+                None => {
+                    self.phase = Phase::Manifest(text);
+                    return Ok(());
+                }
+            };
+            let manifest = verify(std::str::from_utf8(&text[..len]).or(Err(DfuStatus::ErrTarget))?)?;
+            let writer = staging.begin_kernel(&manifest.kernel).map_err(|e| {
+                log::warn!("DFU: {}", e);
+                DfuStatus::ErrAddress
+            })?;
+            log::info!("DFU: receiving {}", manifest.version);
+            rest = text.split_off(len);
+            data = &rest;
+            phase = Phase::Kernel { manifest, writer };
+        }
+        if let Phase::Kernel { manifest, mut writer } = phase {
+            let take = writer.remaining().min(data.len());
+            staging.write_kernel(&mut writer, &data[..take]).map_err(|e| {
+                log::warn!("DFU: {}", e);
+                DfuStatus::ErrWrite
+            })?;
+            data = &data[take..];
+            if writer.remaining() > 0 {
+                self.phase = Phase::Kernel { manifest, writer };
+                return Ok(());
+            }
+            staging.finish_kernel(writer).map_err(|e| {
+                log::warn!("DFU: {}", e);
+                DfuStatus::ErrVerify
+            })?;
+            phase = Phase::Complete(manifest);
+        }
+        if !data.is_empty() {
+            log::warn!("DFU: {} bytes past the end of the update", data.len());
+            return Err(DfuStatus::ErrAddress);
+        }
+        self.phase = phase;
+        Ok(())
+    }
+
+    /// Ends the download, returning the manifest of a complete and verified update. Its kernel is in the
+    /// staging area.
+    pub fn finish(&mut self) -> Result<Manifest, DfuStatus> {
+        match std::mem::replace(&mut self.phase, Phase::Manifest(Vec::new())) {
+            Phase::Complete(manifest) => Ok(manifest),
+            _ => Err(DfuStatus::ErrNotDone),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_end_of_the_manifest() {
+        let manifest = b"xous-update 1\nversion v0.9.16\nsignature abcd\n";
+        let mut data = manifest.to_vec();
+        data.extend_from_slice(b"\x7fELF kernel");
+        assert_eq!(manifest_len(&data), Some(manifest.len()));
+    }
+
+    #[test]
+    fn waits_for_the_whole_signature_line() {
+        assert_eq!(manifest_len(b"xous-update 1\nversion v0.9.16\n"), None);
+        assert_eq!(manifest_len(b"xous-update 1\nversion v0.9.16\nsignature ab"), None);
+    }
+}
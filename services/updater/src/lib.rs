@@ -0,0 +1,69 @@
+pub mod api;
+pub use api::*;
+use num_traits::*;
+use xous::{send_message, Message, CID};
+use xous_ipc::Buffer;
+
+pub struct Updater {
+    conn: CID,
+}
+impl Updater {
+    /// The updater is not part of the default image, so this does not block waiting for it:
+    /// `ServerNotFound` is returned if the image was built without `--service updater`.
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection(api::SERVER_NAME_UPDATER)?;
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(Updater { conn })
+    }
+
+    /// Fetches the manifest and returns `(running version, newer version if any)`.
+    pub fn check(&self) -> Result<(String, Option<String>), String> {
+        let check = UpdateCheck { current: xous_ipc::String::new(), available: None, error: None };
+        let mut buf = Buffer::into_buf(check).or(Err("internal error".to_string()))?;
+        buf.lend_mut(self.conn, Opcode::Check.to_u32().unwrap()).or(Err("internal error".to_string()))?;
+        let check = buf.to_original::<UpdateCheck, _>().or(Err("internal error".to_string()))?;
+        if let Some(e) = check.error {
+            return Err(e.to_str().to_string());
+        }
+        Ok((check.current.to_str().to_string(), check.available.map(|v| v.to_str().to_string())))
+    }
+
+    /// Starts the interactive download/verify/install/reboot flow and returns immediately.
+    pub fn install(&self) -> Result<(), xous::Error> {
+        send_message(self.conn, Message::new_scalar(Opcode::Install.to_usize().unwrap(), 0, 0, 0, 0))
+            .map(|_| ())
+    }
+
+    /// Sets the manifest source and/or signing key. Returns the number of trust-anchors that were
+    /// pinned for the source host.
+    pub fn configure(&self, source: Option<&str>, signing_key: Option<&str>) -> Result<u32, String> {
+        let config = UpdaterConfig {
+            source: source.map(|s| xous_ipc::String::from_str(s)),
+            signing_key: signing_key.map(|k| xous_ipc::String::from_str(k)),
+            pins: 0,
+            error: None,
+        };
+        let mut buf = Buffer::into_buf(config).or(Err("internal error".to_string()))?;
+        buf.lend_mut(self.conn, Opcode::Configure.to_u32().unwrap()).or(Err("internal error".to_string()))?;
+        let config = buf.to_original::<UpdaterConfig, _>().or(Err("internal error".to_string()))?;
+        match config.error {
+            Some(e) => Err(e.to_str().to_string()),
+            None => Ok(config.pins),
+        }
+    }
+}
+
+use core::sync::atomic::{AtomicU32, Ordering};
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Updater {
+    fn drop(&mut self) {
+        // the connection to the server side must be reference counted, so that multiple instances of this
+        // object within a single process do not end up de-allocating the CID on other threads before they
+        // go out of scope.
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
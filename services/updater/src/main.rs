@@ -0,0 +1,438 @@
+mod api;
+use api::*;
+mod dfu;
+mod manifest;
+use manifest::*;
+mod stage;
+
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::sync::Arc;
+
+use ed25519_dalek::VerifyingKey;
+use locales::t;
+use num_traits::*;
+use tls::xtls::PinnedTlsConnector;
+use usb_device_xous::{DfuRequest, DfuStatus, UsbDeviceType};
+use xous::{msg_blocking_scalar_unpack, msg_scalar_unpack, Message};
+use xous_ipc::Buffer;
+use xous_semver::SemVer;
+
+/// Everything needed to talk to the update source; re-read from the PDDB on every request so that
+/// configuration changes take effect without restarting the service.
+struct Source {
+    url: String,
+    key: VerifyingKey,
+    agent: ureq::Agent,
+}
+
+fn read_config(pddb: &pddb::Pddb, key: &str) -> Option<String> {
+    let mut data = Vec::new();
+    pddb.get(UPDATER_DICT, key, None, false, false, None, None::<fn()>).ok()?.read_to_end(&mut data).ok()?;
+    String::from_utf8(data).ok()
+}
+
+fn write_config(pddb: &pddb::Pddb, key: &str, value: &str) -> Result<(), Error> {
+    match pddb.delete_key(UPDATER_DICT, key, None) {
+        Ok(()) => (),
+        Err(e) if e.kind() == ErrorKind::NotFound => (),
+        Err(e) => return Err(e),
+    }
+    pddb.get(UPDATER_DICT, key, None, true, true, Some(value.len()), None::<fn()>)?
+        .write_all(value.as_bytes())?;
+    pddb.sync()
+}
+
+/// Extracts the host from an `https://` URL
+fn host_of(url: &str) -> Option<&str> {
+    let rest = url.strip_prefix("https://")?;
+    let authority = rest.split('/').next()?;
+    authority.split(':').next().filter(|h| !h.is_empty())
+}
+
+fn signing_key(pddb: &pddb::Pddb) -> Result<VerifyingKey, &'static str> {
+    let key_hex = read_config(pddb, UPDATER_SIGNING_KEY).ok_or("no update signing key configured")?;
+    let key_bytes: [u8; 32] = hex::decode(key_hex.trim())
+        .ok()
+        .and_then(|k| k.try_into().ok())
+        .ok_or("malformed update signing key")?;
+    VerifyingKey::from_bytes(&key_bytes).or(Err("malformed update signing key"))
+}
+
+fn source(pddb: &pddb::Pddb) -> Result<Source, String> {
+    let url = read_config(pddb, UPDATER_SOURCE_KEY).unwrap_or(DEFAULT_MANIFEST_URL.to_string());
+    let key = signing_key(pddb)?;
+    let pins: Vec<String> = read_config(pddb, UPDATER_PINS_KEY)
+        .map(|p| p.lines().map(|l| l.to_string()).filter(|l| !l.is_empty()).collect())
+        .unwrap_or_default();
+    if pins.is_empty() {
+        return Err("the update source has no pinned certificates".to_string());
+    }
+    let agent = ureq::builder().tls_connector(Arc::new(PinnedTlsConnector { pins })).build();
+    Ok(Source { url, key, agent })
+}
+
+fn fetch_manifest(source: &Source) -> Result<Manifest, String> {
+    let response = source.agent.get(&source.url).call().map_err(|e| format!("{e}"))?;
+    let mut text = String::new();
+    response
+        .into_reader()
+        .take(MAX_MANIFEST_LEN as u64)
+        .read_to_string(&mut text)
+        .map_err(|e| format!("{e}"))?;
+    Manifest::verify(&text, &source.key).map_err(|e| e.to_string())
+}
+
+fn running_version(tt: &ticktimer_server::Ticktimer) -> (String, SemVer) {
+    let version = tt.get_version().lines().next().unwrap_or("").to_string();
+    let semver =
+        SemVer::from_str(&version).unwrap_or(SemVer { maj: 0, min: 0, rev: 0, extra: 0, commit: None });
+    (version, semver)
+}
+
+fn confirm(modals: &modals::Modals, prompt: &str) -> bool {
+    modals.add_list_item(t!("updater.yes", locales::LANG)).expect("modals error");
+    modals.add_list_item(t!("updater.no", locales::LANG)).expect("modals error");
+    match modals.get_radiobutton(prompt) {
+        Ok(response) => response.as_str() == t!("updater.yes", locales::LANG),
+        _ => false,
+    }
+}
+
+/// The loader can only be checked and written by root-keys' USB update flow, so updates that carry one
+/// are refused rather than installed without it
+fn check_no_loader(manifest: &Manifest) -> Result<(), &'static str> {
+    match manifest.loader {
+        Some(_) => Err("this update includes a new loader, which has to be installed over USB"),
+        None => Ok(()),
+    }
+}
+
+/// The interactive update flow. Only returns if the update was declined or failed; on success the
+/// device reboots into the new system.
+fn install(
+    pddb: &pddb::Pddb,
+    modals: &modals::Modals,
+    staging: &mut stage::Staging,
+    rootkeys: &root_keys::RootKeys,
+    tt: &ticktimer_server::Ticktimer,
+    susres: &susres::Susres,
+) -> Result<(), String> {
+    let source = source(pddb)?;
+    let manifest = fetch_manifest(&source)?;
+    check_no_loader(&manifest)?;
+    let (_, current) = running_version(tt);
+    if manifest.semver <= current {
+        modals.show_notification(t!("updater.up_to_date", locales::LANG), None).ok();
+        return Ok(());
+    }
+    if !confirm(modals, &format!("{}\n{}", t!("updater.available", locales::LANG), manifest.version)) {
+        return Ok(());
+    }
+    // the user asked for the update, but on a data plan it's worth knowing how big it is first
+    if net::NetManager::new().is_metered().unwrap_or(false) {
+        let prompt = t!("updater.metered_prompt", locales::LANG)
+            .replace("{size}", &format!("{}", manifest.kernel.len / 1024));
+        if !confirm(modals, &prompt) {
+            return Ok(());
+        }
+    }
+
+    modals
+        .start_progress(t!("updater.downloading", locales::LANG), 0, manifest.kernel.len as u32, 0)
+        .expect("couldn't raise progress bar");
+    let staged = source
+        .agent
+        .get(&manifest.kernel.url)
+        .call()
+        .map_err(|e| format!("{e}"))
+        .and_then(|response| {
+            staging
+                .stage_kernel(&manifest.kernel, &mut response.into_reader(), |n| {
+                    modals.update_progress(n as u32).ok();
+                })
+                .map_err(|e| e.to_string())
+        });
+    modals.finish_progress().expect("couldn't dismiss progress bar");
+    staged?;
+    install_staged(modals, staging, rootkeys, susres, &manifest)
+}
+
+/// Hands a staged kernel to root-keys, which checks its signature, asks the user to install it, and
+/// copies it over the boot kernel; then reboots into it. Only returns if the user declined, or if
+/// installing failed.
+fn install_staged(
+    modals: &modals::Modals,
+    staging: &mut stage::Staging,
+    rootkeys: &root_keys::RootKeys,
+    susres: &susres::Susres,
+    manifest: &Manifest,
+) -> Result<(), String> {
+    staging.release();
+    match rootkeys.install_staged_kernel() {
+        Ok(true) => (),
+        // the staged kernel is simply left in the staging area; the next attempt overwrites it
+        Ok(false) => return Ok(()),
+        Err(e) => {
+            // root-keys has already told the user why
+            log::warn!("root-keys didn't install the staged kernel: {:?}", e);
+            return Ok(());
+        }
+    }
+    log::info!("installed {}, rebooting", manifest.version);
+    modals.show_notification(t!("updater.rebooting", locales::LANG), None).ok();
+    susres.reboot(true).expect("couldn't reboot");
+    Ok(())
+}
+
+/// Checks the manifest at the start of a DFU download: it has to be signed with the configured key, and
+/// newer than the running version, just like one fetched over the network
+fn dfu_manifest(pddb: &pddb::Pddb, text: &str, current: &SemVer) -> Result<Manifest, DfuStatus> {
+    if !pddb.is_mounted_nonblocking() {
+        log::warn!("DFU update refused: the PDDB is not mounted, so there is no signing key");
+        return Err(DfuStatus::ErrFile);
+    }
+    let key = signing_key(pddb).map_err(|e| {
+        log::warn!("DFU update refused: {}", e);
+        DfuStatus::ErrFile
+    })?;
+    let manifest = Manifest::verify(text, &key).map_err(|e| {
+        log::warn!("DFU update refused: {}", e);
+        DfuStatus::ErrFile
+    })?;
+    check_no_loader(&manifest).map_err(|e| {
+        log::warn!("DFU update refused: {}", e);
+        DfuStatus::ErrFile
+    })?;
+    if manifest.semver <= *current {
+        log::warn!("DFU update refused: {} is not newer than the running version", manifest.version);
+        return Err(DfuStatus::ErrFile);
+    }
+    Ok(manifest)
+}
+
+/// Relays DFU downloads from the USB stack to the main loop, one block at a time
+fn relay_dfu(updater_cid: xous::CID) {
+    let usb = usb_device_xous::UsbHid::new();
+    loop {
+        let status = match usb.dfu_wait() {
+            Ok(DfuRequest::Block(data)) => {
+                let mut block = dfu::DfuBlock {
+                    data: [0; usb_device_xous::DFU_TRANSFER_SIZE],
+                    len: data.len() as u16,
+                    status: DfuStatus::ErrUnknown as u8,
+                };
+                block.data[..data.len()].copy_from_slice(&data);
+                let mut buf = Buffer::into_buf(block).expect("couldn't relay DFU block");
+                buf.lend_mut(updater_cid, Opcode::DfuBlock.to_u32().unwrap())
+                    .expect("couldn't relay DFU block");
+                let block = buf.to_original::<dfu::DfuBlock, _>().unwrap();
+                FromPrimitive::from_u8(block.status).unwrap_or(DfuStatus::ErrUnknown)
+            }
+            Ok(DfuRequest::End) => match xous::send_message(
+                updater_cid,
+                Message::new_blocking_scalar(Opcode::DfuEnd.to_usize().unwrap(), 0, 0, 0, 0),
+            ) {
+                Ok(xous::Result::Scalar1(status)) => {
+                    FromPrimitive::from_usize(status).unwrap_or(DfuStatus::ErrUnknown)
+                }
+                _ => DfuStatus::ErrUnknown,
+            },
+            Ok(DfuRequest::Abort) => {
+                xous::send_message(
+                    updater_cid,
+                    Message::new_scalar(Opcode::DfuAbort.to_usize().unwrap(), 0, 0, 0, 0),
+                )
+                .ok();
+                continue;
+            }
+            Err(e) => {
+                log::warn!("not taking updates over DFU: {:?}", e);
+                break;
+            }
+        };
+        usb.dfu_complete(status).ok();
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let updater_sid = xns.register_name(api::SERVER_NAME_UPDATER, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", updater_sid);
+
+    let tt = ticktimer_server::Ticktimer::new().unwrap();
+    let modals = modals::Modals::new(&xns).expect("can't connect to Modals server");
+    let susres = susres::Susres::new_without_hook(&xns).expect("couldn't connect to susres");
+    let pddb = pddb::Pddb::new();
+    let mut staging = stage::Staging::new(&xns);
+    let rootkeys = root_keys::RootKeys::new(&xns, None).expect("couldn't connect to root-keys");
+    let self_cid = xous::connect(updater_sid).unwrap();
+    let mut download = dfu::DfuDownload::new();
+    // a DFU download that has been checked, waiting for the user to install it
+    let mut dfu_staged: Option<Manifest> = None;
+    std::thread::spawn(move || relay_dfu(self_cid));
+
+    loop {
+        let mut msg = xous::receive_message(updater_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Check) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("Check was not a memory message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(mut check) = buffer.to_original::<UpdateCheck, _>() else {
+                    log::error!("couldn't deserialize an update check");
+                    continue;
+                };
+                pddb.is_mounted_blocking();
+                let (version, current) = running_version(&tt);
+                check.current = xous_ipc::String::from_str(&version);
+                match source(&pddb).and_then(|source| fetch_manifest(&source)) {
+                    Ok(manifest) => {
+                        if manifest.semver > current {
+                            check.available = Some(xous_ipc::String::from_str(&manifest.version));
+                        }
+                    }
+                    Err(e) => check.error = Some(xous_ipc::String::from_str(&e)),
+                }
+                buffer.replace(check).ok();
+            }
+            Some(Opcode::Install) => {
+                pddb.is_mounted_blocking();
+                if let Err(e) = install(&pddb, &modals, &mut staging, &rootkeys, &tt, &susres) {
+                    log::warn!("update failed: {}", e);
+                    modals
+                        .show_notification(&format!("{}\n{}", t!("updater.failed", locales::LANG), e), None)
+                        .ok();
+                }
+            }
+            Some(Opcode::Configure) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("Configure was not a memory message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(mut config) = buffer.to_original::<UpdaterConfig, _>() else {
+                    log::error!("couldn't deserialize an updater configuration");
+                    continue;
+                };
+                pddb.is_mounted_blocking();
+                // any process can ask, so the user has to see and approve what would be trusted
+                let mut prompt = t!("updater.configure_prompt", locales::LANG).to_string();
+                if let Some(url) = &config.source {
+                    prompt.push_str(&format!("\n{}", url.to_str()));
+                }
+                if let Some(key) = &config.signing_key {
+                    prompt.push_str(&format!("\n{}", key.to_str()));
+                }
+                if !confirm(&modals, &prompt) {
+                    config.error = Some(xous_ipc::String::from_str("declined by the user"));
+                    buffer.replace(config).ok();
+                    continue;
+                }
+                if let Some(key) = config.signing_key {
+                    let valid = hex::decode(key.to_str()).map(|k| k.len() == 32).unwrap_or(false);
+                    if !valid {
+                        config.error = Some(xous_ipc::String::from_str("signing key must be 64 hex digits"));
+                    } else if let Err(e) = write_config(&pddb, UPDATER_SIGNING_KEY, key.to_str()) {
+                        config.error = Some(xous_ipc::String::from_str(&format!("{e}")));
+                    }
+                }
+                if config.error.is_none() {
+                    let url = match config.source {
+                        Some(url) => url.to_str().to_string(),
+                        None => {
+                            read_config(&pddb, UPDATER_SOURCE_KEY).unwrap_or(DEFAULT_MANIFEST_URL.to_string())
+                        }
+                    };
+                    // (re-)pin the host: only trust-anchors the user already trusts can become pins
+                    let pinned = host_of(&url)
+                        .ok_or(Error::new(ErrorKind::InvalidInput, "source must be an https:// URL"))
+                        .and_then(|host| tls::Tls::new().pins(host))
+                        .and_then(|pins| {
+                            if pins.is_empty() {
+                                return Err(Error::new(
+                                    ErrorKind::PermissionDenied,
+                                    "the source host offers no trusted certificates; trust one with `net tls probe <host>`",
+                                ));
+                            }
+                            write_config(&pddb, UPDATER_SOURCE_KEY, &url)?;
+                            write_config(&pddb, UPDATER_PINS_KEY, &pins.join("\n"))?;
+                            Ok(pins.len())
+                        });
+                    match pinned {
+                        Ok(count) => config.pins = count as u32,
+                        Err(e) => config.error = Some(xous_ipc::String::from_str(&format!("{e}"))),
+                    }
+                }
+                buffer.replace(config).ok();
+            }
+            Some(Opcode::DfuBlock) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut block = buffer.to_original::<dfu::DfuBlock, _>().unwrap();
+                let (_, current) = running_version(&tt);
+                let data = &block.data[..(block.len as usize).min(block.data.len())];
+                block.status =
+                    match download.feed(data, &mut staging, |text| dfu_manifest(&pddb, text, &current)) {
+                        Ok(()) => DfuStatus::Ok as u8,
+                        Err(status) => status as u8,
+                    };
+                buffer.replace(block).unwrap();
+            }
+            Some(Opcode::DfuEnd) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                let status = match download.finish() {
+                    Ok(staged) => {
+                        dfu_staged = Some(staged);
+                        // asking the user has to wait until the host has been told the download is good
+                        xous::send_message(
+                            self_cid,
+                            Message::new_scalar(Opcode::DfuInstall.to_usize().unwrap(), 0, 0, 0, 0),
+                        )
+                        .ok();
+                        DfuStatus::Ok
+                    }
+                    Err(status) => status,
+                };
+                xous::return_scalar(msg.sender, status as usize).ok();
+            }),
+            Some(Opcode::DfuAbort) => msg_scalar_unpack!(msg, _, _, _, _, {
+                log::info!("DFU download abandoned by the host");
+                download = dfu::DfuDownload::new();
+            }),
+            Some(Opcode::DfuInstall) => msg_scalar_unpack!(msg, _, _, _, _, {
+                if let Some(manifest) = dfu_staged.take() {
+                    if let Err(e) = install_staged(&modals, &mut staging, &rootkeys, &susres, &manifest) {
+                        log::warn!("update failed: {}", e);
+                        modals
+                            .show_notification(
+                                &format!("{}\n{}", t!("updater.failed", locales::LANG), e),
+                                None,
+                            )
+                            .ok();
+                    }
+                    // still running, so the update wasn't installed: leave DFU mode
+                    usb_device_xous::UsbHid::new().ensure_core(UsbDeviceType::FidoKbd).ok();
+                }
+            }),
+            Some(Opcode::Quit) => {
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => {
+                log::error!("couldn't convert opcode: {:?}", msg);
+            }
+        }
+    }
+    // clean up our program
+    log::trace!("main loop exit, destroying servers");
+    xns.unregister_server(updater_sid).unwrap();
+    xous::destroy_server(updater_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
@@ -0,0 +1,135 @@
+//! Update manifests are small line-oriented text files, so they can be produced by a shell script in CI
+//! and inspected by eye:
+//!
+//! ```text
+//! xous-update 1
+//! version v0.9.16-12-gdeadbeef
+//! kernel https://ci.betrusted.io/releases/latest/xous.img 1327104 <sha512 hex>
+//! loader https://ci.betrusted.io/releases/latest/loader.bin 106496 <sha512 hex>
+//! signature <ed25519 signature hex>
+//! ```
+//!
+//! The signature covers every byte of the manifest that precedes the `signature` line. The `loader`
+//! line is optional; `kernel` is mandatory. A loader can only be installed over USB, so the updater
+//! refuses manifests that list one.
+
+use std::convert::TryInto;
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use xous_semver::SemVer;
+
+const MANIFEST_MAGIC: &str = "xous-update 1";
+/// upper bound on the size of a manifest we are willing to download
+pub(crate) const MAX_MANIFEST_LEN: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Image {
+    pub url: String,
+    pub len: usize,
+    pub sha512: [u8; 64],
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Manifest {
+    pub version: String,
+    pub semver: SemVer,
+    pub kernel: Image,
+    pub loader: Option<Image>,
+}
+
+impl Manifest {
+    /// Parses `text` and checks its signature against `key`. Nothing in the manifest is trusted
+    /// (not even its syntax) until the signature has been verified.
+    pub fn verify(text: &str, key: &VerifyingKey) -> Result<Manifest, &'static str> {
+        let sig_start = text.find("\nsignature ").ok_or("manifest is not signed")? + 1;
+        let (signed, sig_line) = text.split_at(sig_start);
+        let sig_hex = sig_line["signature ".len()..].trim();
+        let sig_bytes: [u8; 64] =
+            hex::decode(sig_hex).or(Err("malformed signature"))?.try_into().or(Err("malformed signature"))?;
+        key.verify(signed.as_bytes(), &Signature::from_bytes(&sig_bytes))
+            .or(Err("manifest signature does not verify"))?;
+        Self::parse(signed)
+    }
+
+    fn parse(signed: &str) -> Result<Manifest, &'static str> {
+        let mut lines = signed.lines();
+        if lines.next().map(|l| l.trim()) != Some(MANIFEST_MAGIC) {
+            return Err("unrecognized manifest format");
+        }
+        let mut version = None;
+        let mut kernel = None;
+        let mut loader = None;
+        for line in lines {
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("version") => version = tokens.next().map(|v| v.to_string()),
+                Some("kernel") => kernel = Some(parse_image(&mut tokens)?),
+                Some("loader") => loader = Some(parse_image(&mut tokens)?),
+                // blank lines and unknown records are skipped, so the format can grow new optional fields
+                _ => {}
+            }
+        }
+        let version = version.ok_or("manifest has no version")?;
+        let semver = SemVer::from_str(&version).or(Err("malformed version"))?;
+        Ok(Manifest { version, semver, kernel: kernel.ok_or("manifest has no kernel")?, loader })
+    }
+}
+
+fn parse_image<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<Image, &'static str> {
+    let url = tokens.next().ok_or("image has no url")?;
+    if !url.starts_with("https://") {
+        return Err("images must be fetched over https");
+    }
+    let len = tokens.next().and_then(|l| l.parse::<usize>().ok()).ok_or("malformed image length")?;
+    let sha512: [u8; 64] = tokens
+        .next()
+        .and_then(|h| hex::decode(h).ok())
+        .and_then(|h| h.try_into().ok())
+        .ok_or("malformed image hash")?;
+    Ok(Image { url: url.to_string(), len, sha512 })
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use super::*;
+
+    fn body() -> String {
+        format!(
+            "{}\nversion v0.9.16-12-gdeadbeef\nkernel https://example.com/xous.img 4096 {}\n",
+            MANIFEST_MAGIC,
+            hex::encode([0x5au8; 64])
+        )
+    }
+
+    fn sign(body: &str, key: &SigningKey) -> String {
+        format!("{}signature {}\n", body, hex::encode(key.sign(body.as_bytes()).to_bytes()))
+    }
+
+    #[test]
+    fn verifies_and_parses() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let m = Manifest::verify(&sign(&body(), &key), &key.verifying_key()).unwrap();
+        assert_eq!(m.version, "v0.9.16-12-gdeadbeef");
+        assert_eq!(m.kernel.len, 4096);
+        assert_eq!(m.kernel.sha512, [0x5au8; 64]);
+        assert!(m.loader.is_none());
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let tampered = sign(&body(), &key).replace("4096", "8192");
+        assert!(Manifest::verify(&tampered, &key.verifying_key()).is_err());
+        let other = SigningKey::from_bytes(&[8u8; 32]);
+        assert!(Manifest::verify(&sign(&body(), &other), &key.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn rejects_plain_http() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let insecure = body().replace("https://", "http://");
+        assert!(Manifest::verify(&sign(&insecure, &key), &key.verifying_key()).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! The kernel region is sized for two kernels (see `xous::KERNEL_LEN`); the running kernel lives in the
+//! lower half, and the upper half is used here to stage a downloaded kernel. The last page of the region
+//! holds the root-keys backup block and is kept out of the staging area.
+//!
+//! The updater never writes the boot kernel itself. Once a kernel is staged and verified against the
+//! manifest, the staging area is unmapped and handed to `root-keys`, which checks its signature the same
+//! way the loader does, asks the user, and copies it over the boot kernel.
+
+use std::io::Read;
+
+use root_keys::api::KERNEL_STAGING_LEN;
+use sha2::{Digest, Sha512};
+use spinor::Spinor;
+
+use crate::manifest::Image;
+
+const SECTOR: usize = 4096;
+
+/// A kernel being staged from data pushed to us; see `Staging::begin_kernel()`
+pub(crate) struct KernelWriter {
+    len: usize,
+    sha512: [u8; 64],
+    hasher: Sha512,
+    /// received, but not yet written; always less than a sector
+    sector: Vec<u8>,
+    /// bytes written to the staging area so far
+    offset: usize,
+}
+
+impl KernelWriter {
+    /// bytes of the image still to come
+    pub fn remaining(&self) -> usize { self.len - self.offset - self.sector.len() }
+
+    pub fn received(&self) -> usize { self.offset + self.sector.len() }
+}
+
+pub(crate) struct Staging {
+    #[cfg_attr(not(target_os = "xous"), allow(dead_code))]
+    spinor: Spinor,
+    /// read-only view of the staging area, mapped while a kernel is being staged. No other process maps
+    /// it then; `release()` unmaps it so that root-keys can take it over.
+    #[cfg(target_os = "xous")]
+    staging: Option<xous::MemoryRange>,
+    /// no FLASH in hosted mode, so the staging area is simulated in RAM
+    #[cfg(not(target_os = "xous"))]
+    staging: Vec<u8>,
+}
+
+impl Staging {
+    pub fn new(xns: &xous_names::XousNames) -> Staging {
+        let spinor = Spinor::new(xns).expect("couldn't connect to spinor");
+        #[cfg(target_os = "xous")]
+        let staging = None;
+        #[cfg(not(target_os = "xous"))]
+        let staging = vec![0xFFu8; KERNEL_STAGING_LEN as usize];
+        Staging { spinor, staging }
+    }
+
+    #[cfg(target_os = "xous")]
+    fn map(&mut self) -> Result<(), &'static str> {
+        if self.staging.is_none() {
+            self.staging = Some(
+                xous::syscall::map_memory(
+                    Some(
+                        core::num::NonZeroUsize::new(
+                            (xous::FLASH_PHYS_BASE + xous::KERNEL_LOC + root_keys::api::KERNEL_STAGING_OFFSET)
+                                as usize,
+                        )
+                        .unwrap(),
+                    ),
+                    None,
+                    KERNEL_STAGING_LEN as usize,
+                    xous::MemoryFlags::R,
+                )
+                .or(Err("couldn't map in the kernel staging area"))?,
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "xous"))]
+    fn map(&mut self) -> Result<(), &'static str> { Ok(()) }
+
+    /// Unmaps the staging area, so that root-keys can map it to install the kernel in it
+    #[cfg(target_os = "xous")]
+    pub fn release(&mut self) {
+        if let Some(staging) = self.staging.take() {
+            xous::syscall::unmap_memory(staging).expect("couldn't unmap the kernel staging area");
+        }
+    }
+
+    #[cfg(not(target_os = "xous"))]
+    pub fn release(&mut self) {}
+
+    fn staged(&self) -> &[u8] {
+        #[cfg(target_os = "xous")]
+        return self.staging.as_ref().map(|mr| unsafe { mr.as_slice::<u8>() }).unwrap_or(&[]);
+        #[cfg(not(target_os = "xous"))]
+        return &self.staging;
+    }
+
+    /// Streams `image` from `reader` into the staging area, hashing as it goes. `progress` is called with
+    /// the number of bytes staged so far. Returns an error if the stream is short, the hash does not match,
+    /// or the FLASH read-back differs from what was downloaded.
+    pub fn stage_kernel(
+        &mut self,
+        image: &Image,
+        reader: &mut impl Read,
+        mut progress: impl FnMut(usize),
+    ) -> Result<(), &'static str> {
+        let mut writer = self.begin_kernel(image)?;
+        let mut buf = [0u8; SECTOR];
+        while writer.remaining() > 0 {
+            let chunk = writer.remaining().min(SECTOR);
+            reader.read_exact(&mut buf[..chunk]).or(Err("download was interrupted"))?;
+            self.write_kernel(&mut writer, &buf[..chunk])?;
+            progress(writer.received());
+        }
+        self.finish_kernel(writer)
+    }
+
+    /// Starts staging `image` from data that is pushed to us a piece at a time, rather than read from a
+    /// stream; see `write_kernel()` and `finish_kernel()`.
+    pub fn begin_kernel(&mut self, image: &Image) -> Result<KernelWriter, &'static str> {
+        if image.len > KERNEL_STAGING_LEN as usize {
+            return Err("kernel is too large for the staging area");
+        }
+        self.map()?;
+        Ok(KernelWriter {
+            len: image.len,
+            sha512: image.sha512,
+            hasher: Sha512::new(),
+            sector: Vec::with_capacity(SECTOR),
+            offset: 0,
+        })
+    }
+
+    /// Takes the next piece of the kernel, and writes it to the staging area a sector at a time
+    pub fn write_kernel(&mut self, writer: &mut KernelWriter, mut data: &[u8]) -> Result<(), &'static str> {
+        if data.len() > writer.remaining() {
+            return Err("more data than the kernel image");
+        }
+        while !data.is_empty() {
+            let take = (SECTOR - writer.sector.len()).min(data.len());
+            writer.sector.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if writer.sector.len() == SECTOR || writer.remaining() == 0 {
+                let len = writer.sector.len();
+                writer.hasher.update(&writer.sector);
+                // the last sector is padded to an even length for the DDR interface
+                if len & 1 != 0 {
+                    writer.sector.push(0xFF);
+                }
+                self.write_staging(writer.offset, &writer.sector)?;
+                writer.offset += len;
+                writer.sector.clear();
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the whole kernel arrived, and that it matches its hash both as received and as read
+    /// back from FLASH
+    pub fn finish_kernel(&self, writer: KernelWriter) -> Result<(), &'static str> {
+        if writer.remaining() > 0 {
+            return Err("download was interrupted");
+        }
+        if writer.hasher.finalize().as_slice() != &writer.sha512[..] {
+            return Err("kernel hash mismatch");
+        }
+        // re-hash from FLASH, so what gets handed to root-keys is exactly what was checked
+        let staged = self.staged().get(..writer.len).ok_or("the kernel staging area was released")?;
+        if Sha512::digest(staged).as_slice() != &writer.sha512[..] {
+            return Err("staged kernel failed read-back verification");
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "xous")]
+    fn write_staging(&mut self, offset: usize, data: &[u8]) -> Result<(), &'static str> {
+        let staging = self.staging.as_ref().ok_or("the kernel staging area was released")?;
+        self.spinor
+            .patch(
+                unsafe { staging.as_slice::<u8>() },
+                xous::KERNEL_LOC + root_keys::api::KERNEL_STAGING_OFFSET,
+                data,
+                offset as u32,
+            )
+            .or(Err("couldn't write the staging area"))
+    }
+
+    #[cfg(not(target_os = "xous"))]
+    fn write_staging(&mut self, offset: usize, data: &[u8]) -> Result<(), &'static str> {
+        self.staging[offset..offset + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+}
@@ -31,6 +31,8 @@ pub(crate) enum Opcode {
     RegisterUsbObserver = 11,
     /// Modify log level
     SetLogLevel = 12,
+    /// Returns 1 if a host that had configured the device has since suspended the bus
+    IsHostSuspended = 13,
 
     /// Send a U2F message
     U2fTx = 128,
@@ -39,6 +41,20 @@ pub(crate) enum Opcode {
     /// A bump from the timeout process to check if U2fRx has timed out
     U2fRxTimeout = 130,
 
+    /// Send the answer to a CCID APDU
+    CcidTx = 131,
+    /// Blocks the caller, waiting for an APDU sent to the CCID smart card slot
+    CcidRxDeferred = 132,
+    /// A bump from the time extension process, while an APDU is being worked on
+    CcidTimeExtension = 133,
+
+    /// Blocks the caller, waiting for the next step of a DFU download
+    DfuRxDeferred = 134,
+    /// Report the outcome of a DFU block, or of the whole download
+    DfuComplete = 135,
+    /// A bump from the DFU idle timer, while in DFU mode; only taken from this server
+    DfuIdleCheck = 136,
+
     /// Query if the HID driver was able to start
     IsSocCompatible = 256,
 
@@ -82,6 +98,8 @@ pub(crate) enum Opcode {
     /// Suspend/resume callback
     #[cfg(any(feature = "renode", feature = "precursor", feature = "hosted"))]
     SuspendResume = 2049,
+    /// Wake a sleeping host that has something waiting for it
+    RemoteWakeup = 2050,
     /// Exits the server
     Quit = 4096,
 
@@ -106,6 +124,65 @@ pub struct U2fMsgIpc {
     pub timeout_ms: Option<u64>,
 }
 
+/// Short APDUs only: a 5-byte header, up to 255 bytes of data and Le. Responses are at most 258 bytes.
+pub const APDU_MAX_LEN: usize = 261;
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct ApduIpc {
+    /// A command APDU from the host, or the response to it
+    pub data: [u8; APDU_MAX_LEN],
+    pub len: u16,
+    /// Ties a response to its command
+    pub id: u32,
+    /// Encodes the state of the message; shared with the U2F interface
+    pub code: U2fCode,
+}
+
+/// DFU blocks are limited by the buffer of the control endpoint
+pub const DFU_TRANSFER_SIZE: usize = 128;
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone, Eq, PartialEq)]
+pub enum DfuEvent {
+    /// Set by the listener when it asks for the next event
+    Wait,
+    /// The next block of the download
+    Block,
+    /// The host has sent the whole download
+    End,
+    /// The host gave up on the download, or the device was reset in the middle of it
+    Abort,
+    /// Another process already listens for DFU downloads
+    Denied,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct DfuIpc {
+    /// The block, for `DfuEvent::Block`
+    pub data: [u8; DFU_TRANSFER_SIZE],
+    pub len: u16,
+    pub event: DfuEvent,
+}
+
+/// The outcome of a DFU block or download, as reported to the host. The values are the bStatus codes
+/// of the DFU 1.1 spec.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub enum DfuStatus {
+    Ok = 0x00,
+    /// The download is not an image for this device
+    ErrTarget = 0x01,
+    /// The download is for this device, but failed a check, e.g. its signature
+    ErrFile = 0x02,
+    /// The image couldn't be written
+    ErrWrite = 0x03,
+    /// The image didn't read back as written
+    ErrVerify = 0x07,
+    /// The download is longer than the image it announced
+    ErrAddress = 0x08,
+    /// The download ended before the whole image was sent
+    ErrNotDone = 0x09,
+    ErrUnknown = 0x0E,
+}
+
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone, Eq, PartialEq)]
 pub enum U2fCode {
     Tx,
@@ -127,6 +204,8 @@ pub enum UsbDeviceType {
     MassStorage = 3,
     Serial = 4,
     HIDv2 = 5,
+    /// DFU mode, which only the host can switch the device to
+    Dfu = 6,
 }
 use std::convert::TryFrom;
 
@@ -142,6 +221,7 @@ impl TryFrom<usize> for UsbDeviceType {
             3 => Ok(UsbDeviceType::MassStorage),
             4 => Ok(UsbDeviceType::Serial),
             5 => Ok(UsbDeviceType::HIDv2),
+            6 => Ok(UsbDeviceType::Dfu),
             _ => Err("Invalid UsbDeviceType specifier"),
         }
     }
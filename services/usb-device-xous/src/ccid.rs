@@ -0,0 +1,557 @@
+//! A USB CCID (smart card reader) class with one slot and a card that is always inserted. The card is
+//! implemented by whichever server listens for APDUs (the vault app's OpenPGP card): APDUs from the host
+//! are collected with `take_apdu()`, and the answers are sent back with `respond()`. While the card is
+//! working on an APDU, e.g. waiting for the user to confirm a signature, `time_extension()` keeps the
+//! host from giving up on it.
+//!
+//! Every APDU is tagged with a transfer ID, so that an answer computed before a bus reset can't be
+//! mistaken for the answer to an APDU that arrived after it.
+//!
+//! Only short APDUs are exchanged; larger data is handled by the card with command chaining and
+//! GET RESPONSE, as announced in the ATR.
+//!
+//! The reader's side of the protocol lives in `Slot`, which only deals in bytes, so that it can be
+//! tested without a USB controller. `CcidClass` moves those bytes over the endpoints.
+
+use std::collections::VecDeque;
+
+use usb_device::Result;
+use usb_device::class_prelude::*;
+
+use crate::api::APDU_MAX_LEN;
+
+pub(crate) const USB_CLASS_CCID: u8 = 0x0B;
+const CCID_FUNCTIONAL_DESCRIPTOR: u8 = 0x21;
+const PACKET_LEN: usize = 64;
+/// bulk OUT transfers can be chained into up to 512 bytes by the controller
+const READ_BUF_LEN: usize = 512;
+const HEADER_LEN: usize = 10;
+const MAX_MESSAGE_LEN: usize = HEADER_LEN + APDU_MAX_LEN;
+
+const PC_TO_RDR_SET_PARAMETERS: u8 = 0x61;
+const PC_TO_RDR_ICC_POWER_ON: u8 = 0x62;
+const PC_TO_RDR_ICC_POWER_OFF: u8 = 0x63;
+const PC_TO_RDR_GET_SLOT_STATUS: u8 = 0x65;
+const PC_TO_RDR_GET_PARAMETERS: u8 = 0x6C;
+const PC_TO_RDR_RESET_PARAMETERS: u8 = 0x6D;
+const PC_TO_RDR_XFR_BLOCK: u8 = 0x6F;
+const RDR_TO_PC_DATA_BLOCK: u8 = 0x80;
+const RDR_TO_PC_SLOT_STATUS: u8 = 0x81;
+const RDR_TO_PC_PARAMETERS: u8 = 0x82;
+const RDR_TO_PC_NOTIFY_SLOT_CHANGE: u8 = 0x50;
+
+/// bStatus: ICC present and active
+const STATUS_ACTIVE: u8 = 0x00;
+/// bStatus: ICC present and inactive
+const STATUS_INACTIVE: u8 = 0x01;
+/// bStatus: no ICC present
+const STATUS_NO_ICC: u8 = 0x02;
+const STATUS_FAILED: u8 = 0x40;
+const STATUS_TIME_EXTENSION: u8 = 0x80;
+const ERROR_CMD_NOT_SUPPORTED: u8 = 0x00;
+const ERROR_BAD_LENGTH: u8 = 0x01;
+const ERROR_SLOT_DOES_NOT_EXIST: u8 = 0x05;
+const ERROR_ICC_MUTE: u8 = 0xFE;
+const ERROR_CMD_SLOT_BUSY: u8 = 0xE0;
+
+/// T=1, with the OpenPGP card's historical bytes: no extended length, command chaining supported
+const ATR: [u8; 14] = [0x3B, 0x8A, 0x01, 0x00, 0x31, 0xC5, 0x73, 0xC0, 0x01, 0x80, 0x05, 0x90, 0x00, 0xD8];
+/// bmFindexDindex, bmTCCKST1, bGuardTimeT1, bmWaitingIntegersT1, bClockStop, bIFSC, bNadValue
+const T1_PARAMETERS: [u8; 7] = [0x11, 0x10, 0x00, 0x4D, 0x00, 0xFE, 0x00];
+
+#[rustfmt::skip]
+const CCID_DESCRIPTOR: [u8; 52] = [
+    0x10, 0x01,             // bcdCCID 1.10
+    0x00,                   // bMaxSlotIndex
+    0x07,                   // bVoltageSupport: 5V, 3V, 1.8V
+    0x02, 0x00, 0x00, 0x00, // dwProtocols: T=1
+    0xA0, 0x0F, 0x00, 0x00, // dwDefaultClock: 4MHz
+    0xA0, 0x0F, 0x00, 0x00, // dwMaximumClock
+    0x00,                   // bNumClockSupported
+    0x80, 0x25, 0x00, 0x00, // dwDataRate: 9600 bps
+    0x80, 0x25, 0x00, 0x00, // dwMaxDataRate
+    0x00,                   // bNumDataRatesSupported
+    0xFE, 0x00, 0x00, 0x00, // dwMaxIFSD
+    0x00, 0x00, 0x00, 0x00, // dwSynchProtocols
+    0x00, 0x00, 0x00, 0x00, // dwMechanical
+    0xFE, 0x00, 0x02, 0x00, // dwFeatures: automatic everything, short APDU level exchange
+    (MAX_MESSAGE_LEN & 0xFF) as u8, (MAX_MESSAGE_LEN >> 8) as u8, 0x00, 0x00, // dwMaxCCIDMessageLength
+    0xFF,                   // bClassGetResponse: echo the APDU class
+    0xFF,                   // bClassEnvelope
+    0x00, 0x00,             // wLcdLayout: none
+    0x00,                   // bPINSupport: none
+    0x01,                   // bMaxCCIDBusySlots
+];
+
+/// The slot and its card, as seen by the host: takes in bulk OUT transfers, and queues the messages to
+/// send back on the bulk IN endpoint.
+pub(crate) struct Slot {
+    powered: bool,
+    rx: Vec<u8>,
+    /// bytes of a message that was too long that have yet to arrive, and are to be thrown away
+    discard: usize,
+    outgoing: VecDeque<Vec<u8>>,
+    /// transfer ID and sequence number of the XfrBlock the card is working on
+    pending: Option<(u32, u8)>,
+    apdu: Option<(u32, Vec<u8>)>,
+    next_id: u32,
+}
+
+impl Slot {
+    pub(crate) fn new() -> Slot {
+        Slot {
+            powered: false,
+            rx: Vec::new(),
+            discard: 0,
+            outgoing: VecDeque::new(),
+            pending: None,
+            apdu: None,
+            next_id: 0,
+        }
+    }
+
+    /// Returns the transfer ID and the APDU the host sent, if one arrived since the last call
+    pub(crate) fn take_apdu(&mut self) -> Option<(u32, Vec<u8>)> { self.apdu.take() }
+
+    /// The transfer ID of the APDU the card is working on, if any
+    pub(crate) fn pending_id(&self) -> Option<u32> { self.pending.map(|(id, _)| id) }
+
+    /// Sends the card's answer to the APDU with transfer ID `id`
+    pub(crate) fn respond(&mut self, id: u32, response: &[u8]) {
+        match self.pending {
+            Some((pending_id, seq)) if pending_id == id => {
+                self.pending = None;
+                self.queue(RDR_TO_PC_DATA_BLOCK, seq, STATUS_ACTIVE, 0, 0, response);
+            }
+            _ => log::warn!("CCID response to a stale APDU, dropping it"),
+        }
+    }
+
+    /// Asks the host for more time to answer the pending APDU. Returns false if there is none.
+    pub(crate) fn time_extension(&mut self) -> bool {
+        match self.pending {
+            Some((_, seq)) => {
+                // bError holds the multiplier of the block waiting time
+                self.queue(RDR_TO_PC_DATA_BLOCK, seq, STATUS_TIME_EXTENSION, 1, 0, &[]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Gives up on the APDU with transfer ID `id`, telling the host that the card went mute. A late answer
+    /// to it is dropped as stale.
+    pub(crate) fn abandon(&mut self, id: u32) {
+        if let Some((pending_id, seq)) = self.pending {
+            if pending_id == id {
+                log::warn!("CCID APDU {} was not answered in time, abandoning it", id);
+                self.pending = None;
+                if self.apdu.as_ref().map(|(apdu_id, _)| *apdu_id) == Some(id) {
+                    self.apdu = None;
+                }
+                self.queue(RDR_TO_PC_DATA_BLOCK, seq, STATUS_FAILED | STATUS_ACTIVE, ERROR_ICC_MUTE, 0, &[]);
+            }
+        }
+    }
+
+    /// The next message to send to the host
+    pub(crate) fn next_message(&mut self) -> Option<Vec<u8>> { self.outgoing.pop_front() }
+
+    pub(crate) fn reset(&mut self) {
+        self.powered = false;
+        self.rx.clear();
+        self.discard = 0;
+        self.outgoing.clear();
+        self.pending = None;
+        self.apdu = None;
+    }
+
+    fn queue(&mut self, message_type: u8, seq: u8, status: u8, error: u8, param: u8, data: &[u8]) {
+        let mut message = Vec::with_capacity(HEADER_LEN + data.len());
+        message.push(message_type);
+        message.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        message.extend_from_slice(&[0, seq, status, error, param]);
+        message.extend_from_slice(data);
+        self.outgoing.push_back(message);
+    }
+
+    /// Takes in a chunk of a bulk OUT transfer. `end_of_transfer` is set if the chunk ended with a short
+    /// packet, after which the host starts a new message.
+    pub(crate) fn receive(&mut self, mut data: &[u8], end_of_transfer: bool) {
+        if self.discard > 0 {
+            let skip = self.discard.min(data.len());
+            self.discard -= skip;
+            data = &data[skip..];
+        }
+        self.rx.extend_from_slice(data);
+        while self.rx.len() >= HEADER_LEN {
+            let len = u32::from_le_bytes([self.rx[1], self.rx[2], self.rx[3], self.rx[4]]) as usize;
+            if len > APDU_MAX_LEN {
+                // drop this message and only this message, so that the next one is parsed from its header
+                let seq = self.rx[6];
+                log::warn!("CCID message too long: {}", len);
+                let total = HEADER_LEN.saturating_add(len);
+                let have = self.rx.len().min(total);
+                self.rx.drain(..have);
+                self.discard = total - have;
+                self.queue(
+                    RDR_TO_PC_SLOT_STATUS,
+                    seq,
+                    STATUS_FAILED | STATUS_ACTIVE,
+                    ERROR_BAD_LENGTH,
+                    0,
+                    &[],
+                );
+                continue;
+            }
+            if self.rx.len() < HEADER_LEN + len {
+                break;
+            }
+            let message: Vec<u8> = self.rx.drain(..HEADER_LEN + len).collect();
+            self.handle(&message);
+        }
+        if end_of_transfer {
+            // whatever the header claimed, the host has moved on to the next message
+            self.discard = 0;
+            if !self.rx.is_empty() {
+                log::warn!("CCID transfer ended with {} bytes of an incomplete message", self.rx.len());
+                self.rx.clear();
+            }
+        }
+    }
+
+    fn handle(&mut self, message: &[u8]) {
+        let (message_type, slot, seq) = (message[0], message[5], message[6]);
+        let status = if self.powered { STATUS_ACTIVE } else { STATUS_INACTIVE };
+        if slot != 0 {
+            self.queue(
+                RDR_TO_PC_SLOT_STATUS,
+                seq,
+                STATUS_FAILED | STATUS_NO_ICC,
+                ERROR_SLOT_DOES_NOT_EXIST,
+                0,
+                &[],
+            );
+            return;
+        }
+        if self.pending.is_some() {
+            self.queue(RDR_TO_PC_SLOT_STATUS, seq, STATUS_FAILED | status, ERROR_CMD_SLOT_BUSY, 0, &[]);
+            return;
+        }
+        match message_type {
+            PC_TO_RDR_ICC_POWER_ON => {
+                self.powered = true;
+                self.queue(RDR_TO_PC_DATA_BLOCK, seq, STATUS_ACTIVE, 0, 0, &ATR);
+            }
+            PC_TO_RDR_ICC_POWER_OFF => {
+                self.powered = false;
+                self.queue(RDR_TO_PC_SLOT_STATUS, seq, STATUS_INACTIVE, 0, 0, &[]);
+            }
+            PC_TO_RDR_GET_SLOT_STATUS => self.queue(RDR_TO_PC_SLOT_STATUS, seq, status, 0, 0, &[]),
+            PC_TO_RDR_GET_PARAMETERS | PC_TO_RDR_SET_PARAMETERS | PC_TO_RDR_RESET_PARAMETERS => {
+                // the parameters are fixed; bProtocolNum is T=1
+                self.queue(RDR_TO_PC_PARAMETERS, seq, status, 0, 1, &T1_PARAMETERS)
+            }
+            PC_TO_RDR_XFR_BLOCK => {
+                if self.powered {
+                    let id = self.next_id;
+                    self.next_id = self.next_id.wrapping_add(1);
+                    self.pending = Some((id, seq));
+                    self.apdu = Some((id, message[HEADER_LEN..].to_vec()));
+                } else {
+                    self.queue(RDR_TO_PC_DATA_BLOCK, seq, STATUS_FAILED | status, ERROR_ICC_MUTE, 0, &[]);
+                }
+            }
+            _ => {
+                log::debug!("unsupported CCID message {:x}", message_type);
+                self.queue(
+                    RDR_TO_PC_SLOT_STATUS,
+                    seq,
+                    STATUS_FAILED | status,
+                    ERROR_CMD_NOT_SUPPORTED,
+                    0,
+                    &[],
+                )
+            }
+        }
+    }
+}
+
+pub(crate) struct CcidClass<'a, B: UsbBus> {
+    iface: InterfaceNumber,
+    read_ep: EndpointOut<'a, B>,
+    write_ep: EndpointIn<'a, B>,
+    notify_ep: EndpointIn<'a, B>,
+    /// the card is present from the start, so the host is told as soon as it configures us
+    notify_pending: bool,
+    slot: Slot,
+    tx: Vec<u8>,
+    /// bytes of `tx` sent so far; past the end once the message has been terminated
+    tx_offset: usize,
+    tx_in_flight: bool,
+}
+
+impl<B: UsbBus> CcidClass<'_, B> {
+    pub(crate) fn new(alloc: &UsbBusAllocator<B>) -> CcidClass<'_, B> {
+        CcidClass {
+            iface: alloc.interface(),
+            read_ep: alloc.bulk(PACKET_LEN as u16),
+            write_ep: alloc.bulk(PACKET_LEN as u16),
+            notify_ep: alloc.interrupt(8, 255),
+            notify_pending: true,
+            slot: Slot::new(),
+            tx: Vec::new(),
+            tx_offset: 1,
+            tx_in_flight: false,
+        }
+    }
+
+    /// Returns the transfer ID and the APDU the host sent, if one arrived since the last call
+    pub(crate) fn take_apdu(&mut self) -> Option<(u32, Vec<u8>)> { self.slot.take_apdu() }
+
+    /// The transfer ID of the APDU the card is working on, if any
+    pub(crate) fn pending_id(&self) -> Option<u32> { self.slot.pending_id() }
+
+    /// Sends the card's answer to the APDU with transfer ID `id`
+    pub(crate) fn respond(&mut self, id: u32, response: &[u8]) {
+        self.slot.respond(id, response);
+        self.kick();
+    }
+
+    /// Asks the host for more time to answer the pending APDU. Returns false if there is none.
+    pub(crate) fn time_extension(&mut self) -> bool {
+        let pending = self.slot.time_extension();
+        self.kick();
+        pending
+    }
+
+    /// Gives up on the APDU with transfer ID `id`, if the card is still working on it
+    pub(crate) fn abandon(&mut self, id: u32) {
+        self.slot.abandon(id);
+        self.kick();
+    }
+
+    fn kick(&mut self) {
+        if !self.tx_in_flight {
+            self.flush();
+        }
+    }
+
+    /// Writes the next packet, if there is anything left to write. Every message ends with a short
+    /// packet, which is a zero-length one if the message is a multiple of the packet length.
+    fn flush(&mut self) {
+        if self.tx_offset > self.tx.len() {
+            match self.slot.next_message() {
+                Some(message) => {
+                    self.tx = message;
+                    self.tx_offset = 0;
+                }
+                None => return,
+            }
+        }
+        let end = (self.tx_offset + PACKET_LEN).min(self.tx.len());
+        match self.write_ep.write(&self.tx[self.tx_offset..end]) {
+            Ok(_) => {
+                self.tx_in_flight = true;
+                self.tx_offset = if end - self.tx_offset < PACKET_LEN { self.tx.len() + 1 } else { end };
+            }
+            Err(UsbError::WouldBlock) => (),
+            Err(e) => log::error!("CCID write error: {:?}", e),
+        }
+    }
+
+    fn read(&mut self) {
+        let mut packet = [0u8; READ_BUF_LEN];
+        loop {
+            match self.read_ep.read(&mut packet) {
+                // a read that isn't a whole number of packets ended with a short packet
+                Ok(len) => self.slot.receive(&packet[..len], len % PACKET_LEN != 0 || len == 0),
+                Err(UsbError::WouldBlock) => break,
+                Err(e) => {
+                    log::error!("CCID read error: {:?}", e);
+                    break;
+                }
+            }
+        }
+        self.kick();
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for CcidClass<'_, B> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        writer.interface(self.iface, USB_CLASS_CCID, 0, 0)?;
+        writer.write(CCID_FUNCTIONAL_DESCRIPTOR, &CCID_DESCRIPTOR)?;
+        writer.endpoint(&self.read_ep)?;
+        writer.endpoint(&self.write_ep)?;
+        writer.endpoint(&self.notify_ep)
+    }
+
+    fn reset(&mut self) {
+        self.notify_pending = true;
+        self.slot.reset();
+        self.tx.clear();
+        self.tx_offset = 1;
+        self.tx_in_flight = false;
+    }
+
+    fn poll(&mut self) {
+        if self.notify_pending {
+            // slot 0: card present, and changed
+            if self.notify_ep.write(&[RDR_TO_PC_NOTIFY_SLOT_CHANGE, 0x03]).is_ok() {
+                self.notify_pending = false;
+            }
+        }
+        self.kick();
+    }
+
+    fn endpoint_out(&mut self, addr: EndpointAddress) {
+        if addr == self.read_ep.address() {
+            self.read();
+        }
+    }
+
+    fn endpoint_in_complete(&mut self, addr: EndpointAddress) {
+        if addr == self.write_ep.address() {
+            self.tx_in_flight = false;
+            self.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(message_type: u8, seq: u8, data: &[u8]) -> Vec<u8> {
+        let mut m = vec![message_type];
+        m.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        m.extend_from_slice(&[0, seq, 0, 0, 0]);
+        m.extend_from_slice(data);
+        m
+    }
+
+    /// Message type, sequence number, status, error and data of each message the slot sent
+    fn sent(slot: &mut Slot) -> Vec<(u8, u8, u8, u8, Vec<u8>)> {
+        let mut messages = Vec::new();
+        while let Some(m) = slot.next_message() {
+            let len = u32::from_le_bytes([m[1], m[2], m[3], m[4]]) as usize;
+            assert_eq!(m.len(), HEADER_LEN + len);
+            messages.push((m[0], m[6], m[7], m[8], m[HEADER_LEN..].to_vec()));
+        }
+        messages
+    }
+
+    fn powered_slot() -> Slot {
+        let mut slot = Slot::new();
+        slot.receive(&message(PC_TO_RDR_ICC_POWER_ON, 0, &[]), true);
+        assert_eq!(sent(&mut slot), [(RDR_TO_PC_DATA_BLOCK, 0, STATUS_ACTIVE, 0, ATR.to_vec())]);
+        slot
+    }
+
+    #[test]
+    fn test_xfr_block_round_trip() {
+        let mut slot = powered_slot();
+        let apdu = [0x00, 0xA4, 0x04, 0x00, 0x06, 0xD2, 0x76, 0x00, 0x01, 0x24, 0x01];
+        slot.receive(&message(PC_TO_RDR_XFR_BLOCK, 1, &apdu), true);
+        let (id, received) = slot.take_apdu().unwrap();
+        assert_eq!(received, apdu);
+        assert_eq!(slot.pending_id(), Some(id));
+        assert!(sent(&mut slot).is_empty());
+
+        // the host is kept waiting, then gets the answer with the sequence number of its request
+        assert!(slot.time_extension());
+        slot.respond(id, &[0x90, 0x00]);
+        assert_eq!(slot.pending_id(), None);
+        assert!(!slot.time_extension());
+        assert_eq!(
+            sent(&mut slot),
+            [
+                (RDR_TO_PC_DATA_BLOCK, 1, STATUS_TIME_EXTENSION, 1, vec![]),
+                (RDR_TO_PC_DATA_BLOCK, 1, STATUS_ACTIVE, 0, vec![0x90, 0x00]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_message_split_across_reads() {
+        let mut slot = powered_slot();
+        let m = message(PC_TO_RDR_XFR_BLOCK, 2, &[0xAA; 100]);
+        slot.receive(&m[..PACKET_LEN], false);
+        assert!(slot.take_apdu().is_none());
+        slot.receive(&m[PACKET_LEN..], true);
+        assert_eq!(slot.take_apdu().unwrap().1, [0xAA; 100]);
+    }
+
+    #[test]
+    fn test_stale_and_abandoned_responses() {
+        let mut slot = powered_slot();
+        slot.receive(&message(PC_TO_RDR_XFR_BLOCK, 3, &[0x00, 0xCA, 0x00, 0x6E, 0x00]), true);
+        let (id, _) = slot.take_apdu().unwrap();
+        // a second command while the card is busy is turned away
+        slot.receive(&message(PC_TO_RDR_GET_SLOT_STATUS, 4, &[]), true);
+        assert_eq!(sent(&mut slot), [(RDR_TO_PC_SLOT_STATUS, 4, STATUS_FAILED, ERROR_CMD_SLOT_BUSY, vec![])]);
+
+        slot.abandon(id.wrapping_add(1));
+        assert_eq!(slot.pending_id(), Some(id));
+        slot.abandon(id);
+        assert_eq!(slot.pending_id(), None);
+        slot.respond(id, &[0x90, 0x00]);
+        assert_eq!(
+            sent(&mut slot),
+            [(RDR_TO_PC_DATA_BLOCK, 3, STATUS_FAILED | STATUS_ACTIVE, ERROR_ICC_MUTE, vec![])]
+        );
+    }
+
+    #[test]
+    fn test_oversized_message_is_skipped() {
+        let mut slot = powered_slot();
+        let mut stream = message(PC_TO_RDR_XFR_BLOCK, 5, &[0x55; APDU_MAX_LEN + 1]);
+        stream.extend_from_slice(&message(PC_TO_RDR_GET_SLOT_STATUS, 6, &[]));
+        // the oversized message arrives a packet at a time, and the next one is still understood
+        let mut chunks = stream.chunks(PACKET_LEN).peekable();
+        while let Some(chunk) = chunks.next() {
+            slot.receive(chunk, chunks.peek().is_none());
+        }
+        assert!(slot.take_apdu().is_none());
+        assert_eq!(
+            sent(&mut slot),
+            [
+                (RDR_TO_PC_SLOT_STATUS, 5, STATUS_FAILED | STATUS_ACTIVE, ERROR_BAD_LENGTH, vec![]),
+                (RDR_TO_PC_SLOT_STATUS, 6, STATUS_ACTIVE, 0, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bogus_length_ends_with_the_transfer() {
+        let mut slot = powered_slot();
+        let mut bogus = message(PC_TO_RDR_XFR_BLOCK, 7, &[]);
+        bogus[1..5].copy_from_slice(&u32::MAX.to_le_bytes());
+        slot.receive(&bogus, true);
+        slot.receive(&message(PC_TO_RDR_GET_SLOT_STATUS, 8, &[]), true);
+        assert_eq!(
+            sent(&mut slot),
+            [
+                (RDR_TO_PC_SLOT_STATUS, 7, STATUS_FAILED | STATUS_ACTIVE, ERROR_BAD_LENGTH, vec![]),
+                (RDR_TO_PC_SLOT_STATUS, 8, STATUS_ACTIVE, 0, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unpowered_and_missing_slots() {
+        let mut slot = Slot::new();
+        slot.receive(&message(PC_TO_RDR_XFR_BLOCK, 9, &[0x00, 0xCA, 0x00, 0x6E, 0x00]), true);
+        let mut other_slot = message(PC_TO_RDR_GET_SLOT_STATUS, 10, &[]);
+        other_slot[5] = 1;
+        slot.receive(&other_slot, true);
+        assert!(slot.take_apdu().is_none());
+        assert_eq!(
+            sent(&mut slot),
+            [
+                (RDR_TO_PC_DATA_BLOCK, 9, STATUS_FAILED | STATUS_INACTIVE, ERROR_ICC_MUTE, vec![]),
+                (RDR_TO_PC_SLOT_STATUS, 10, STATUS_FAILED | STATUS_NO_ICC, ERROR_SLOT_DOES_NOT_EXIST, vec![]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! A USB DFU 1.1 class. The same class provides both halves of the protocol:
+//!
+//! - in `Runtime` mode it is one more interface of the FIDO + keyboard device. It is left out of the
+//!   configuration until `enable()` is called, once something listens for DFU downloads. All it does is
+//!   accept DFU_DETACH, after which `take_detach()` tells the driver to re-enumerate in DFU mode.
+//! - in `Dfu` mode it is the only interface of the device, and takes downloads. Each block from the host is
+//!   handed to whichever server listens for DFU downloads (the updater) with `take_event()`, and the host is
+//!   told to keep polling until the listener reports back with `complete()`.
+//!
+//! Uploads are not supported. The device is manifestation tolerant: once the listener has checked the
+//! whole download it stays in DFU mode and reports the outcome, and installing the image is up to the
+//! listener. `take_leave()` returns to the runtime device once the session is over: on a bus reset after
+//! the host started talking DFU (`dfu-util -R`), an abort of a download, once the host has been told a
+//! download failed, or when the driver gives up on an idle host with `time_out()`.
+
+use usb_device::Result;
+use usb_device::class_prelude::*;
+use usb_device::control::{Recipient, Request, RequestType};
+
+use crate::api::{DfuEvent, DfuStatus, DFU_TRANSFER_SIZE};
+
+const USB_CLASS_APPLICATION_SPECIFIC: u8 = 0xFE;
+const DFU_SUBCLASS: u8 = 0x01;
+const DFU_PROTOCOL_RUNTIME: u8 = 0x01;
+const DFU_PROTOCOL_DFU: u8 = 0x02;
+const DFU_FUNCTIONAL_DESCRIPTOR: u8 = 0x21;
+
+const DFU_DETACH: u8 = 0x00;
+const DFU_DNLOAD: u8 = 0x01;
+const DFU_GETSTATUS: u8 = 0x03;
+const DFU_CLRSTATUS: u8 = 0x04;
+const DFU_GETSTATE: u8 = 0x05;
+const DFU_ABORT: u8 = 0x06;
+
+const STATE_APP_IDLE: u8 = 0;
+const STATE_APP_DETACH: u8 = 1;
+const STATE_DFU_IDLE: u8 = 2;
+const STATE_DNLOAD_SYNC: u8 = 3;
+const STATE_DNBUSY: u8 = 4;
+const STATE_DNLOAD_IDLE: u8 = 5;
+const STATE_MANIFEST_SYNC: u8 = 6;
+const STATE_MANIFEST: u8 = 7;
+const STATE_ERROR: u8 = 10;
+
+/// bwPollTimeout while the listener works on a block. Writing FLASH takes a few milliseconds per
+/// sector, so this keeps the host from asking too often without slowing the download much.
+const BUSY_POLL_MS: u32 = 5;
+/// bwPollTimeout while the listener checks the whole download
+const MANIFEST_POLL_MS: u32 = 500;
+
+#[rustfmt::skip]
+const DFU_DESCRIPTOR: [u8; 7] = [
+    0x0D,                   // bmAttributes: bitWillDetach, bitManifestationTolerant, bitCanDnload
+    0x88, 0x13,             // wDetachTimeOut: 5000ms
+    (DFU_TRANSFER_SIZE & 0xFF) as u8, (DFU_TRANSFER_SIZE >> 8) as u8, // wTransferSize
+    0x10, 0x01,             // bcdDFUVersion 1.1
+];
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub(crate) enum DfuMode {
+    Runtime,
+    Dfu,
+}
+
+pub(crate) struct DfuClass {
+    iface: InterfaceNumber,
+    mode: DfuMode,
+    state: u8,
+    status: DfuStatus,
+    /// the listener has yet to `complete()` the last block, or the end of the download
+    busy: bool,
+    event: Option<(DfuEvent, Vec<u8>)>,
+    detach: bool,
+    /// the runtime interface is in the configuration
+    enabled: bool,
+    /// the host has sent a DFU request since the device entered DFU mode, so a bus reset ends the session
+    session: bool,
+    /// the host has sent a DFU request since the last `take_activity()`
+    activity: bool,
+    leave: bool,
+}
+
+impl DfuClass {
+    pub(crate) fn new<B: UsbBus>(alloc: &UsbBusAllocator<B>, mode: DfuMode) -> DfuClass {
+        DfuClass {
+            iface: alloc.interface(),
+            mode,
+            state: if mode == DfuMode::Runtime { STATE_APP_IDLE } else { STATE_DFU_IDLE },
+            status: DfuStatus::Ok,
+            busy: false,
+            event: None,
+            detach: false,
+            enabled: false,
+            session: false,
+            activity: false,
+            leave: false,
+        }
+    }
+
+    /// Puts the runtime interface in the configuration. The host only sees it once the device is
+    /// re-enumerated.
+    pub(crate) fn enable(&mut self) { self.enabled = true; }
+
+    pub(crate) fn is_enabled(&self) -> bool { self.enabled }
+
+    /// Returns true once, after the host asked the runtime interface to switch to DFU mode
+    pub(crate) fn take_detach(&mut self) -> bool { core::mem::take(&mut self.detach) }
+
+    /// Returns true once, when the DFU session is over and the device should go back to the runtime device
+    pub(crate) fn take_leave(&mut self) -> bool {
+        if self.leave {
+            self.session = false;
+        }
+        core::mem::take(&mut self.leave)
+    }
+
+    /// Returns whether the host sent any DFU request since the last call
+    pub(crate) fn take_activity(&mut self) -> bool { core::mem::take(&mut self.activity) }
+
+    /// Ends the session of a host that went quiet, letting go of any partial download
+    pub(crate) fn time_out(&mut self) {
+        if self.busy || matches!(self.state, STATE_DNLOAD_SYNC | STATE_DNLOAD_IDLE | STATE_MANIFEST_SYNC) {
+            self.event = Some((DfuEvent::Abort, Vec::new()));
+        }
+        self.state = STATE_DFU_IDLE;
+        self.status = DfuStatus::Ok;
+        self.busy = false;
+        self.leave = true;
+    }
+
+    /// Returns the next thing the listener has to act on, if the host did anything since the last call
+    pub(crate) fn take_event(&mut self) -> Option<(DfuEvent, Vec<u8>)> { self.event.take() }
+
+    /// Reports the outcome of the last `Block` or `End` event to the host
+    pub(crate) fn complete(&mut self, status: DfuStatus) {
+        if !self.busy {
+            log::warn!("DFU status without a block to complete, dropping it");
+            return;
+        }
+        self.busy = false;
+        if status != DfuStatus::Ok {
+            log::warn!("DFU download failed: {:?}", status);
+            self.status = status;
+            self.state = STATE_ERROR;
+        }
+    }
+
+    fn dnload(&mut self, data: &[u8]) -> bool {
+        match (self.state, data.len()) {
+            (STATE_DFU_IDLE, 0) => {
+                self.fail(DfuStatus::ErrNotDone);
+                false
+            }
+            (STATE_DFU_IDLE, _) | (STATE_DNLOAD_IDLE, _) if !data.is_empty() => {
+                self.busy = true;
+                self.event = Some((DfuEvent::Block, data.to_vec()));
+                self.state = STATE_DNLOAD_SYNC;
+                true
+            }
+            (STATE_DNLOAD_IDLE, _) => {
+                self.busy = true;
+                self.event = Some((DfuEvent::End, Vec::new()));
+                self.state = STATE_MANIFEST_SYNC;
+                true
+            }
+            _ => {
+                self.fail(DfuStatus::ErrUnknown);
+                false
+            }
+        }
+    }
+
+    fn fail(&mut self, status: DfuStatus) {
+        self.status = status;
+        self.state = STATE_ERROR;
+    }
+
+    /// bStatus, bwPollTimeout, bState and iString, as returned by DFU_GETSTATUS. Moves on from the
+    /// states that only last until the host asks for the status.
+    fn get_status(&mut self) -> [u8; 6] {
+        let (state, poll_ms) = match self.state {
+            STATE_DNLOAD_SYNC if self.busy => (STATE_DNBUSY, BUSY_POLL_MS),
+            STATE_DNLOAD_SYNC => {
+                self.state = STATE_DNLOAD_IDLE;
+                (STATE_DNLOAD_IDLE, 0)
+            }
+            STATE_MANIFEST_SYNC if self.busy => (STATE_MANIFEST, MANIFEST_POLL_MS),
+            STATE_MANIFEST_SYNC => {
+                self.state = STATE_DFU_IDLE;
+                (STATE_DFU_IDLE, 0)
+            }
+            STATE_ERROR => {
+                // the host has been told; there is no retrying a download that failed part way
+                self.leave = true;
+                (STATE_ERROR, 0)
+            }
+            state => (state, 0),
+        };
+        let poll = poll_ms.to_le_bytes();
+        [self.status as u8, poll[0], poll[1], poll[2], state, 0]
+    }
+
+    /// Whether `request` is for this interface. Notes the host's activity if it is.
+    fn is_ours(&mut self, request: &Request) -> bool {
+        let ours = (self.mode == DfuMode::Dfu || self.enabled)
+            && request.request_type == RequestType::Class
+            && request.recipient == Recipient::Interface
+            && request.index == u8::from(self.iface) as u16;
+        if ours && self.mode == DfuMode::Dfu {
+            self.session = true;
+            self.activity = true;
+        }
+        ours
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for DfuClass {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        let protocol = match self.mode {
+            // it is the last interface of the device, so leaving it out doesn't leave a gap
+            DfuMode::Runtime if !self.enabled => return Ok(()),
+            DfuMode::Runtime => DFU_PROTOCOL_RUNTIME,
+            DfuMode::Dfu => DFU_PROTOCOL_DFU,
+        };
+        writer.interface(self.iface, USB_CLASS_APPLICATION_SPECIFIC, DFU_SUBCLASS, protocol)?;
+        writer.write(DFU_FUNCTIONAL_DESCRIPTOR, &DFU_DESCRIPTOR)
+    }
+
+    fn reset(&mut self) {
+        match self.mode {
+            DfuMode::Runtime => {
+                self.state = STATE_APP_IDLE;
+            }
+            DfuMode::Dfu => {
+                if self.busy || matches!(self.state, STATE_DNLOAD_SYNC | STATE_DNLOAD_IDLE) {
+                    // the listener has to let go of the partial download
+                    self.event = Some((DfuEvent::Abort, Vec::new()));
+                }
+                // the resets of the enumeration that brought the device into DFU mode come before the
+                // host's first DFU request; any later one ends the session
+                if self.session {
+                    self.leave = true;
+                }
+                self.state = STATE_DFU_IDLE;
+                self.status = DfuStatus::Ok;
+                self.busy = false;
+            }
+        }
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let request = *xfer.request();
+        if !self.is_ours(&request) {
+            return;
+        }
+        let accepted = match (self.mode, request.request) {
+            (DfuMode::Runtime, DFU_DETACH) => {
+                self.state = STATE_APP_DETACH;
+                self.detach = true;
+                true
+            }
+            (DfuMode::Dfu, DFU_DNLOAD) => self.dnload(xfer.data()),
+            (DfuMode::Dfu, DFU_CLRSTATUS) if self.state == STATE_ERROR => {
+                self.state = STATE_DFU_IDLE;
+                self.status = DfuStatus::Ok;
+                true
+            }
+            (DfuMode::Dfu, DFU_ABORT) if !self.busy => {
+                if self.state != STATE_DFU_IDLE {
+                    self.event = Some((DfuEvent::Abort, Vec::new()));
+                    self.leave = true;
+                }
+                self.state = STATE_DFU_IDLE;
+                true
+            }
+            _ => false,
+        };
+        if accepted {
+            xfer.accept().ok()
+        } else {
+            xfer.reject().ok()
+        };
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        let request = *xfer.request();
+        if !self.is_ours(&request) {
+            return;
+        }
+        match request.request {
+            DFU_GETSTATUS => {
+                let status = self.get_status();
+                xfer.accept_with(&status).ok();
+            }
+            DFU_GETSTATE => {
+                xfer.accept_with(&[self.state]).ok();
+            }
+            _ => {
+                xfer.reject().ok();
+            }
+        }
+    }
+}
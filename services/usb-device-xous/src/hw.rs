@@ -170,6 +170,10 @@ impl SpinalUsbMgmt {
         self.connect_device_core(self.sr_core);
     }
 
+    /// Signals remote wakeup to a suspended host. The UDC has no way to drive resume signalling onto the
+    /// bus, so this always fails, and the devices built on it don't offer remote wakeup to the host.
+    pub fn remote_wakeup(&mut self) -> bool { false }
+
     #[allow(dead_code)]
     pub fn descriptor_from_status(&self, ep_status: &UdcEpStatus) -> SpinalUdcDescriptor {
         SpinalUdcDescriptor::new(unsafe {
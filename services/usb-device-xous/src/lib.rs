@@ -15,6 +15,17 @@ pub use xous_usb_hid::device::fido::RawFidoReport;
 pub use xous_usb_hid::device::keyboard::KeyboardLedsReport;
 pub use xous_usb_hid::page::Keyboard as UsbKeyCode;
 
+/// The next step of a DFU download; see `UsbHid::dfu_wait()`
+#[derive(Debug)]
+pub enum DfuRequest {
+    /// The next block of the download
+    Block(Vec<u8>),
+    /// The host has sent the whole download, and waits for it to be checked
+    End,
+    /// The host gave up on the download; forget what was received so far
+    Abort,
+}
+
 #[derive(Debug)]
 pub struct UsbHid {
     conn: CID,
@@ -123,6 +134,7 @@ impl UsbHid {
                 #[cfg(feature = "mass-storage")]
                 3 => Ok(UsbDeviceType::MassStorage),
                 4 => Ok(UsbDeviceType::Serial),
+                6 => Ok(UsbDeviceType::Dfu),
                 _ => Err(xous::Error::InternalError),
             },
             _ => panic!("Internal error: illegal return type"),
@@ -195,6 +207,18 @@ impl UsbHid {
         }
     }
 
+    /// Whether the device is plugged into a host that has gone to sleep. Unlike a `Suspend` status, this
+    /// is never true on a charger, which leaves the bus idle without ever configuring the device.
+    pub fn is_host_suspended(&self) -> bool {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::IsHostSuspended.to_usize().unwrap(), 0, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar1(suspended)) => suspended != 0,
+            _ => panic!("Internal error: illegal return type"),
+        }
+    }
+
     /// Sends up to three keyboard codes at once as defined by USB HID usage tables;
     /// see See [Universal Serial Bus (USB) HID Usage Tables Version 1.12](<https://www.usb.org/sites/default/files/documents/hut1_12v2.pdf>):
     /// If the vector is empty, you get an all-key-up situation
@@ -309,6 +333,70 @@ impl UsbHid {
         }
     }
 
+    /// Blocks until the host sends an APDU to the CCID smart card slot, and returns it along with the ID
+    /// to pass to `ccid_respond()`. The slot is locked to the first process that uses it.
+    pub fn ccid_wait_apdu(&self) -> Result<(u32, Vec<u8>), xous::Error> {
+        let req = ApduIpc { data: [0; APDU_MAX_LEN], len: 0, id: 0, code: U2fCode::RxWait };
+        let mut buf = Buffer::into_buf(req).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::CcidRxDeferred.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let ack = buf.to_original::<ApduIpc, _>().unwrap();
+        match ack.code {
+            U2fCode::RxAck => Ok((ack.id, ack.data[..(ack.len as usize).min(APDU_MAX_LEN)].to_vec())),
+            U2fCode::Denied => Err(xous::Error::AccessDenied),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Sends the response to the APDU with the given ID
+    pub fn ccid_respond(&self, id: u32, response: &[u8]) -> Result<(), xous::Error> {
+        if response.len() > APDU_MAX_LEN {
+            return Err(xous::Error::OutOfMemory);
+        }
+        let mut req = ApduIpc { data: [0; APDU_MAX_LEN], len: response.len() as u16, id, code: U2fCode::Tx };
+        req.data[..response.len()].copy_from_slice(response);
+        let mut buf = Buffer::into_buf(req).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::CcidTx.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        let ack = buf.to_original::<ApduIpc, _>().unwrap();
+        match ack.code {
+            U2fCode::TxAck => Ok(()),
+            U2fCode::Denied => Err(xous::Error::AccessDenied),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Blocks until the host sends the next block of a DFU download, finishes the download, or gives up
+    /// on it. Every `Block` and `End` must be answered with `dfu_complete()`; the host is kept waiting
+    /// until then. The DFU interface is locked to the first process that uses it, and the host is only
+    /// offered DFU once that process has called this.
+    pub fn dfu_wait(&self) -> Result<DfuRequest, xous::Error> {
+        let req = DfuIpc { data: [0; DFU_TRANSFER_SIZE], len: 0, event: DfuEvent::Wait };
+        let mut buf = Buffer::into_buf(req).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::DfuRxDeferred.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let ack = buf.to_original::<DfuIpc, _>().unwrap();
+        match ack.event {
+            DfuEvent::Block => Ok(DfuRequest::Block(ack.data[..ack.len as usize].to_vec())),
+            DfuEvent::End => Ok(DfuRequest::End),
+            DfuEvent::Abort => Ok(DfuRequest::Abort),
+            DfuEvent::Denied => Err(xous::Error::AccessDenied),
+            DfuEvent::Wait => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Reports the outcome of the last `Block` or `End` to the host. Anything but `DfuStatus::Ok` fails
+    /// the download.
+    pub fn dfu_complete(&self, status: DfuStatus) -> Result<(), xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::DfuComplete.to_usize().unwrap(), status as usize, 0, 0, 0),
+        )? {
+            xous::Result::Scalar1(1) => Ok(()),
+            xous::Result::Scalar1(_) => Err(xous::Error::AccessDenied),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
     /// Blocks until an ASCII string terminated by `delimiter` is received on serial; if `None`, it
     /// will return as soon as a character (or series of characters) have been received (thus the return
     /// `String` will be piecemeal)
@@ -341,6 +429,14 @@ impl UsbHid {
             .map(|_| ())
     }
 
+    /// Blocks until `s` has been written to the serial port. Characters are dropped if the USB core is
+    /// not currently the serial device.
+    pub fn serial_write(&self, s: &str) -> Result<(), xous::Error> {
+        let send = UsbString { s: xous_ipc::String::from_str(s), sent: None };
+        let buf = Buffer::into_buf(send).or(Err(xous::Error::InternalError))?;
+        buf.send(self.conn, Opcode::LogString.to_u32().unwrap()).map(|_| ())
+    }
+
     /// Inject serial input over USB to the debug console. Dangerous!
     /// This will also override/discard any existing hooked listeners.
     pub fn serial_console_input_injection(&self) {
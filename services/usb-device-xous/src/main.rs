@@ -27,6 +27,10 @@ use spinal_udc::*;
 ))]
 mod apps_block_device;
 
+#[cfg(all(feature = "ccid", any(feature = "precursor", feature = "renode", feature = "cramium-soc")))]
+mod ccid;
+#[cfg(any(feature = "precursor", feature = "renode", feature = "cramium-soc"))]
+mod dfu;
 #[cfg(any(feature = "precursor", feature = "renode", feature = "cramium-soc"))]
 mod hid;
 #[cfg(not(target_os = "xous"))]
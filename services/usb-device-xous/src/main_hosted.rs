@@ -37,6 +37,8 @@ pub(crate) fn main_hosted() -> ! {
             ).unwrap();
         }
         let mut fido_listener: Option<xous::MessageEnvelope> = None;
+        let mut ccid_listener: Option<xous::MessageEnvelope> = None;
+        let mut dfu_listener: Option<xous::MessageEnvelope> = None;
         loop {
             let msg = xous::receive_message(usbdev_sid).unwrap();
             match FromPrimitive::from_usize(msg.body.id()) {
@@ -49,6 +51,12 @@ pub(crate) fn main_hosted() -> ! {
                     // block any rx requests forever
                     fido_listener = Some(msg);
                 }
+                Some(Opcode::CcidRxDeferred) => {
+                    ccid_listener = Some(msg);
+                }
+                Some(Opcode::DfuRxDeferred) => {
+                    dfu_listener = Some(msg);
+                }
                 Some(Opcode::IsSocCompatible) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                     xous::return_scalar(msg.sender, 0).expect("couldn't return compatibility status")
                 }),
@@ -72,7 +80,7 @@ pub(crate) fn main_hosted() -> ! {
                 }
             }
         }
-        log::info!("consuming listener: {:?}", fido_listener);
+        log::info!("consuming listeners: {:?} {:?} {:?}", fido_listener, ccid_listener, dfu_listener);
     }
 
     let view = Arc::new(AtomicUsize::new(0));
@@ -90,6 +98,10 @@ pub(crate) fn main_hosted() -> ! {
     // statement)
     let mut fido_listener_pid: Option<NonZeroU8> = None;
     let mut fido_rx_queue = VecDeque::<[u8; 64]>::new();
+    // there is no smart card reader in hosted mode, so APDUs never arrive
+    let mut _ccid_listener: Option<xous::MessageEnvelope> = None;
+    // nor a DFU interface, so downloads never arrive either
+    let mut _dfu_listener: Option<xous::MessageEnvelope> = None;
 
     let mut lockstatus_force_update = true; // some state to track if we've been through a susupend/resume, to help out the status thread with its UX update after a restart-from-cold
 
@@ -197,6 +209,22 @@ pub(crate) fn main_hosted() -> ! {
                 }
                 buffer.replace(u2f_ipc).unwrap();
             }
+            Some(Opcode::CcidRxDeferred) => {
+                _ccid_listener = Some(msg);
+            }
+            Some(Opcode::CcidTx) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut apdu_ipc = buffer.to_original::<ApduIpc, _>().unwrap();
+                apdu_ipc.code = U2fCode::TxAck;
+                buffer.replace(apdu_ipc).unwrap();
+            }
+            Some(Opcode::DfuRxDeferred) => {
+                _dfu_listener = Some(msg);
+            }
+            Some(Opcode::DfuComplete) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                xous::return_scalar(msg.sender, 1).unwrap();
+            }),
             Some(Opcode::UsbIrqHandler) => {}
             Some(Opcode::SwitchCores) => msg_blocking_scalar_unpack!(msg, core, _, _, _, {
                 if core == 1 {
@@ -270,6 +298,9 @@ pub(crate) fn main_hosted() -> ! {
             Some(Opcode::LinkStatus) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                 xous::return_scalar(msg.sender, 0).unwrap();
             }),
+            Some(Opcode::IsHostSuspended) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                xous::return_scalar(msg.sender, 0).unwrap();
+            }),
             Some(Opcode::SendKeyCode) => {
                 xous::return_scalar(msg.sender, 1).unwrap();
             }
@@ -4,6 +4,8 @@ use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::collections::VecDeque;
 use std::convert::TryInto;
 use std::sync::Arc;
+#[cfg(feature = "ccid")]
+use std::sync::Mutex;
 
 #[cfg(all(not(feature = "minimal"), any(feature = "cramium-soc")))]
 use cram_hal_service::api::KeyMap;
@@ -43,6 +45,12 @@ use crate::*;
 /// Time allowed for switchover between device core types. It's longer because some hosts
 /// get really confused when you have the same VID/PID show up with a different set of endpoints.
 const EXTENDED_CORE_RESET_MS: usize = 4000;
+/// Time allowed for the status stage of a DFU request before the device is re-enumerated in another mode
+const DFU_DETACH_GRACE_MS: usize = 100;
+/// A host that sends no DFU request for this long is given up on, and the device leaves DFU mode. While a
+/// block is being written the host polls for the status every few milliseconds, so this is only reached
+/// by a host that went away.
+const DFU_IDLE_TIMEOUT_MS: usize = 30_000;
 #[derive(Eq, PartialEq, Debug)]
 #[repr(usize)]
 enum Views {
@@ -52,6 +60,7 @@ enum Views {
     MassStorage = 2,
     Serial = 3,
     HIDv2 = 4,
+    Dfu = 5,
 }
 
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
@@ -61,6 +70,13 @@ enum TimeoutOp {
     Quit,
 }
 
+#[cfg(feature = "ccid")]
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+enum TimeExtensionOp {
+    Pump,
+    InvalidCall,
+}
+
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 enum TrngOp {
     Pump,
@@ -122,6 +138,8 @@ pub(crate) fn main_hw() -> ! {
                 ).unwrap();
             }
             let mut fido_listener: Option<xous::MessageEnvelope> = None;
+            let mut ccid_listener: Option<xous::MessageEnvelope> = None;
+            let mut dfu_listener: Option<xous::MessageEnvelope> = None;
             loop {
                 let msg = xous::receive_message(usbdev_sid).unwrap();
                 match FromPrimitive::from_usize(msg.body.id()) {
@@ -134,6 +152,12 @@ pub(crate) fn main_hw() -> ! {
                         // block any rx requests forever
                         fido_listener = Some(msg);
                     }
+                    Some(Opcode::CcidRxDeferred) => {
+                        ccid_listener = Some(msg);
+                    }
+                    Some(Opcode::DfuRxDeferred) => {
+                        dfu_listener = Some(msg);
+                    }
                     Some(Opcode::IsSocCompatible) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
                         xous::return_scalar(msg.sender, 0).expect("couldn't return compatibility status")
                     }),
@@ -157,7 +181,7 @@ pub(crate) fn main_hw() -> ! {
                     }
                 }
             }
-            log::info!("consuming listener: {:?}", fido_listener);
+            log::info!("consuming listeners: {:?} {:?} {:?}", fido_listener, ccid_listener, dfu_listener);
         }
     }
     #[cfg(feature = "minimal")]
@@ -326,6 +350,14 @@ pub(crate) fn main_hw() -> ! {
     let serial_dev = usbwrapper.clone();
     #[cfg(any(feature = "renode", feature = "precursor"))]
     serial_dev.init();
+    // and for DFU mode, which the FIDO + keyboard device switches to when the host asks for it
+    #[cfg(any(feature = "renode", feature = "precursor"))]
+    let dfu_dev = SpinalUsbDevice::new(usbdev_sid, usb.clone(), csr.clone());
+    #[cfg(feature = "cramium-soc")]
+    // safety: this is safe because we allocated ifram_range to have the same physical and virtual addresses
+    let dfu_dev = usbwrapper.clone();
+    #[cfg(any(feature = "renode", feature = "precursor"))]
+    dfu_dev.init();
 
     // register a suspend/resume listener
     #[cfg(any(feature = "renode", feature = "precursor", feature = "hosted"))]
@@ -339,21 +371,30 @@ pub(crate) fn main_hw() -> ! {
         .add_device(NKROBootKeyboardConfig::default())
         .add_device(RawFidoConfig::default())
         .build(&usb_alloc);
+    // smart card reader, for the OpenPGP card
+    #[cfg(feature = "ccid")]
+    let mut ccid = ccid::CcidClass::new(&usb_alloc);
+    // DFU runtime interface, so `dfu-util` can switch the device to DFU mode for an update
+    let mut dfu_runtime = dfu::DfuClass::new(&usb_alloc, dfu::DfuMode::Runtime);
 
     let mut usb_dev = UsbDeviceBuilder::new(&usb_alloc, UsbVidPid(0x1209, 0x3613))
         .manufacturer("Kosagi")
         .product("Precursor")
         .serial_number(&serial_number)
+        .supports_remote_wakeup(cfg!(feature = "cramium-soc"))
         .build();
 
     // FIDO only
     let fido_alloc = UsbBusAllocator::new(usb_fido_dev);
     let mut fido_class = UsbHidClassBuilder::new().add_device(RawFidoConfig::default()).build(&fido_alloc);
+    #[cfg(feature = "ccid")]
+    let mut fido_ccid = ccid::CcidClass::new(&fido_alloc);
 
     let mut fido_dev = UsbDeviceBuilder::new(&fido_alloc, UsbVidPid(0x1209, 0x3613))
         .manufacturer("Kosagi")
         .product("Precursor")
         .serial_number(&serial_number)
+        .supports_remote_wakeup(cfg!(feature = "cramium-soc"))
         .build();
 
     // Mass storage
@@ -394,6 +435,16 @@ pub(crate) fn main_hw() -> ! {
         .self_powered(false)
         .max_power(500)
         .build();
+
+    // DFU mode
+    let dfu_alloc = UsbBusAllocator::new(dfu_dev);
+    let mut dfu_class = dfu::DfuClass::new(&dfu_alloc, dfu::DfuMode::Dfu);
+    let mut dfu_device = UsbDeviceBuilder::new(&dfu_alloc, UsbVidPid(0x1209, 0x3613))
+        .manufacturer("Kosagi")
+        .product("Precursor DFU")
+        .serial_number(&serial_number)
+        .build();
+
     let mut serial_listener: Option<xous::MessageEnvelope> = None;
     let mut serial_listen_mode: SerialListenMode = SerialListenMode::NoListener;
     let mut serial_buf = Vec::<u8>::new();
@@ -483,9 +534,30 @@ pub(crate) fn main_hw() -> ! {
     // statement)
     let mut fido_listener_pid: Option<NonZeroU8> = None;
     let mut fido_rx_queue = VecDeque::<[u8; 64]>::new();
+    // the CCID slot is locked to its first user the same way. The host only sends one APDU at a time.
+    #[cfg(feature = "ccid")]
+    let mut ccid_listener: Option<xous::MessageEnvelope> = None;
+    #[cfg(feature = "ccid")]
+    let mut ccid_listener_pid: Option<NonZeroU8> = None;
+    #[cfg(feature = "ccid")]
+    let mut ccid_rx: Option<(u32, Vec<u8>)> = None;
+    // and so is the DFU interface. The host waits for each block to be completed before it sends the next.
+    let mut dfu_listener: Option<xous::MessageEnvelope> = None;
+    let mut dfu_listener_pid: Option<NonZeroU8> = None;
+    let mut dfu_rx: Option<(DfuEvent, Vec<u8>)> = None;
 
     let mut lockstatus_force_update = true; // some state to track if we've been through a suspend/resume, to help out the status thread with its UX update after a restart-from-cold
     let mut was_suspend = true;
+    // a host that configured the device and then suspended the bus is asleep. A charger also leaves the
+    // bus idle, but never configures the device, so it doesn't count.
+    let mut host_configured = false;
+    let mut host_suspended = false;
+    // remote wakeup is signalled once per suspend; if the host doesn't come back, it isn't asked again
+    let mut wakeup_signalled = false;
+    // an awake host keeps the device awake, so that it doesn't drop off the bus in the middle of a session.
+    // A sleeping one lets it go, and it can sleep too instead of running its battery down.
+    #[cfg(any(feature = "renode", feature = "precursor", feature = "hosted"))]
+    let mut host_inhibitor: Option<susres::SuspendInhibitor> = None;
     let mut autotype_delay_ms = 30;
 
     // event observer connection
@@ -599,6 +671,98 @@ pub(crate) fn main_hw() -> ! {
         }
     });
 
+    // leaves DFU mode if the host stops talking to the device. Started each time the device enters DFU
+    // mode; it runs until the device is out of it.
+    let (dfu_idle_timer, dfu_idle_starts) = std::sync::mpsc::channel::<()>();
+    std::thread::spawn({
+        let cid = cid;
+        move || {
+            let tt = ticktimer_server::Ticktimer::new().unwrap();
+            while dfu_idle_starts.recv().is_ok() {
+                loop {
+                    tt.sleep_ms(DFU_IDLE_TIMEOUT_MS).ok();
+                    match xous::send_message(
+                        cid,
+                        xous::Message::new_blocking_scalar(
+                            Opcode::DfuIdleCheck.to_usize().unwrap(),
+                            0,
+                            0,
+                            0,
+                            0,
+                        ),
+                    ) {
+                        Ok(xous::Result::Scalar1(1)) => {}
+                        _ => break,
+                    }
+                }
+            }
+        }
+    });
+
+    // keeps the host waiting while the CCID listener works on an APDU, e.g. while the user is asked to
+    // confirm a signature. `ccid_pending` mirrors the transfer ID of the APDU the card is working on, and
+    // is cleared when it is answered, abandoned, or lost to a bus reset.
+    #[cfg(feature = "ccid")]
+    let te_server = xous::create_server().unwrap();
+    #[cfg(feature = "ccid")]
+    let te_conn = xous::connect(te_server).unwrap();
+    #[cfg(feature = "ccid")]
+    let ccid_pending: Arc<Mutex<Option<u32>>> = Arc::new(Mutex::new(None));
+    #[cfg(feature = "ccid")]
+    std::thread::spawn({
+        let cid = cid;
+        let ccid_pending = ccid_pending.clone();
+        move || {
+            const TIME_EXTENSION_INTERVAL_MS: usize = 500;
+            // a card that takes longer than this is given up on, so a listener that never answers can't
+            // hold the host forever
+            const CCID_MAX_BUSY_MS: u64 = 120_000;
+            let tt = ticktimer_server::Ticktimer::new().unwrap();
+            loop {
+                let msg = xous::receive_message(te_server).unwrap();
+                match num_traits::FromPrimitive::from_usize(msg.body.id())
+                    .unwrap_or(TimeExtensionOp::InvalidCall)
+                {
+                    TimeExtensionOp::Pump => msg_scalar_unpack!(msg, id, _, _, _, {
+                        // runs for as long as this APDU is pending. Pumps for APDUs that were answered in
+                        // the meantime fall through after one interval.
+                        let id = id as u32;
+                        let start = tt.elapsed_ms();
+                        loop {
+                            tt.sleep_ms(TIME_EXTENSION_INTERVAL_MS).ok();
+                            if *ccid_pending.lock().unwrap() != Some(id) {
+                                break;
+                            }
+                            let abandon = tt.elapsed_ms().saturating_sub(start) >= CCID_MAX_BUSY_MS;
+                            xous::try_send_message(
+                                cid,
+                                xous::Message::new_scalar(
+                                    Opcode::CcidTimeExtension.to_usize().unwrap(),
+                                    id as usize,
+                                    if abandon { 1 } else { 0 },
+                                    0,
+                                    0,
+                                ),
+                            )
+                            .ok();
+                            if abandon {
+                                break;
+                            }
+                        }
+                    }
+                    TimeExtensionOp::InvalidCall => {
+                        log::error!(
+                            "Unknown opcode received in CCID time extension handler: {:?}",
+                            msg.body.id()
+                        );
+                    }
+                }
+            }
+        }
+    });
+    #[cfg(feature = "ccid")]
+    let my_pid = xous::process::id();
+
     log::info!("starting main loop");
     loop {
         let mut msg = xous::receive_message(usbdev_sid).unwrap();
@@ -689,6 +853,10 @@ pub(crate) fn main_hw() -> ! {
                         Err(e) => log::warn!("USB reset on resume failed: {:?}", e),
                         _ => (),
                     },
+                    Views::Dfu => match dfu_device.force_reset() {
+                        Err(e) => log::warn!("USB reset on resume failed: {:?}", e),
+                        _ => (),
+                    },
                 }
                 // resume2 brings us to our last application state
                 usbmgmt.xous_resume2();
@@ -807,19 +975,220 @@ pub(crate) fn main_hw() -> ! {
                         Views::MassStorage => panic!("did not expect u2f tx when in mass storage mode!"),
                         Views::Serial => panic!("did not expect u2f tx while in serial mode!"),
                         Views::HIDv2 => panic!("did not expect u2f tx while in hidv2 mode!"),
+                        Views::Dfu => panic!("did not expect u2f tx while in DFU mode!"),
                     };
                     u2f.write_report(&u2f_msg).ok();
                     log::debug!("sent U2F packet {:x?}", u2f_ipc.data);
+                    if host_suspended {
+                        // a keepalive while an approval is pending, or the response to it: the host has to be
+                        // awake to collect either
+                        xous::try_send_message(
+                            cid,
+                            xous::Message::new_scalar(Opcode::RemoteWakeup.to_usize().unwrap(), 0, 0, 0, 0),
+                        )
+                        .ok();
+                    }
                     u2f_ipc.code = U2fCode::TxAck;
                 } else {
                     u2f_ipc.code = U2fCode::Denied;
                 }
                 buffer.replace(u2f_ipc).unwrap();
             }
+            #[cfg(feature = "ccid")]
+            Some(Opcode::CcidRxDeferred) => {
+                if ccid_listener_pid.is_none() {
+                    ccid_listener_pid = msg.sender.pid();
+                }
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("CcidRxDeferred was not a memory message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(mut apdu_ipc) = buffer.to_original::<ApduIpc, _>() else {
+                    log::error!("couldn't deserialize a CcidRxDeferred request");
+                    continue;
+                };
+                if ccid_listener_pid == msg.sender.pid() {
+                    if let Some((id, apdu)) = ccid_rx.take() {
+                        apdu_ipc.data[..apdu.len()].copy_from_slice(&apdu);
+                        apdu_ipc.len = apdu.len() as u16;
+                        apdu_ipc.id = id;
+                        apdu_ipc.code = U2fCode::RxAck;
+                        buffer.replace(apdu_ipc).unwrap();
+                    } else {
+                        ccid_listener = Some(msg);
+                    }
+                } else {
+                    log::warn!(
+                        "CCID interface capability is locked on first use; additional servers are ignored: {:?}",
+                        msg.sender
+                    );
+                    apdu_ipc.code = U2fCode::Denied;
+                    buffer.replace(apdu_ipc).unwrap();
+                }
+            }
+            #[cfg(feature = "ccid")]
+            Some(Opcode::CcidTx) => {
+                if ccid_listener_pid.is_none() {
+                    ccid_listener_pid = msg.sender.pid();
+                }
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("CcidTx was not a memory message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(mut apdu_ipc) = buffer.to_original::<ApduIpc, _>() else {
+                    log::error!("couldn't deserialize a CcidTx request");
+                    continue;
+                };
+                if ccid_listener_pid != msg.sender.pid() {
+                    apdu_ipc.code = U2fCode::Denied;
+                } else if apdu_ipc.code != U2fCode::Tx || apdu_ipc.len as usize > APDU_MAX_LEN {
+                    log::error!("malformed CCID response: {:?}, {} bytes", apdu_ipc.code, apdu_ipc.len);
+                    apdu_ipc.code = U2fCode::Hangup;
+                } else {
+                    let response = &apdu_ipc.data[..apdu_ipc.len as usize];
+                    match view {
+                        Views::FidoWithKbd => ccid.respond(apdu_ipc.id, response),
+                        Views::FidoOnly => fido_ccid.respond(apdu_ipc.id, response),
+                        _ => log::warn!("CCID response while the smart card reader is not connected"),
+                    }
+                    *ccid_pending.lock().unwrap() = match view {
+                        Views::FidoWithKbd => ccid.pending_id(),
+                        Views::FidoOnly => fido_ccid.pending_id(),
+                        _ => None,
+                    };
+                    if host_suspended {
+                        xous::try_send_message(
+                            cid,
+                            xous::Message::new_scalar(Opcode::RemoteWakeup.to_usize().unwrap(), 0, 0, 0, 0),
+                        )
+                        .ok();
+                    }
+                    apdu_ipc.code = U2fCode::TxAck;
+                }
+                buffer.replace(apdu_ipc).unwrap();
+            }
+            #[cfg(feature = "ccid")]
+            Some(Opcode::CcidTimeExtension) => msg_scalar_unpack!(msg, id, abandon, _, _, {
+                // only our own time extension thread may ask for these
+                if msg.sender.pid().map(|p| p.get() as u32) != Some(my_pid) {
+                    log::warn!("CcidTimeExtension from another process: {:?}", msg.sender);
+                    continue;
+                }
+                let id = id as u32;
+                if abandon != 0 {
+                    match view {
+                        Views::FidoWithKbd => ccid.abandon(id),
+                        Views::FidoOnly => fido_ccid.abandon(id),
+                        _ => (),
+                    }
+                    if ccid_rx.as_ref().map(|(rx_id, _)| *rx_id) == Some(id) {
+                        ccid_rx = None;
+                    }
+                } else {
+                    match view {
+                        Views::FidoWithKbd => ccid.time_extension(),
+                        Views::FidoOnly => fido_ccid.time_extension(),
+                        _ => false,
+                    };
+                }
+                *ccid_pending.lock().unwrap() = match view {
+                    Views::FidoWithKbd => ccid.pending_id(),
+                    Views::FidoOnly => fido_ccid.pending_id(),
+                    _ => None,
+                };
+            }),
+            #[cfg(not(feature = "ccid"))]
+            Some(Opcode::CcidRxDeferred) | Some(Opcode::CcidTx) => {
+                // there is no smart card reader in this build, so the caller can stop asking
+                if let Some(mem) = msg.body.memory_message_mut() {
+                    let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                    if let Ok(mut apdu_ipc) = buffer.to_original::<ApduIpc, _>() {
+                        apdu_ipc.code = U2fCode::Denied;
+                        buffer.replace(apdu_ipc).ok();
+                    }
+                }
+            }
+            #[cfg(not(feature = "ccid"))]
+            Some(Opcode::CcidTimeExtension) => {}
+            Some(Opcode::DfuRxDeferred) => {
+                if dfu_listener_pid.is_none() {
+                    dfu_listener_pid = msg.sender.pid();
+                }
+                if dfu_listener_pid.is_some() && !dfu_runtime.is_enabled() {
+                    // there is something to take an update now, so offer the host the way to send one
+                    log::info!("DFU listener registered, adding the DFU runtime interface");
+                    dfu_runtime.enable();
+                    if view == Views::FidoWithKbd && host_configured {
+                        #[cfg(not(feature = "cramium-soc"))]
+                        {
+                            usbmgmt.ll_reset(true);
+                            tt.sleep_ms(1000).ok();
+                            usbmgmt.ll_connect_device_core(true);
+                            tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
+                            usbmgmt.ll_reset(false);
+                        }
+                        #[cfg(feature = "cramium-soc")]
+                        usb_dev.force_reset().ok();
+                    }
+                }
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut dfu_ipc = buffer.to_original::<DfuIpc, _>().unwrap();
+                if dfu_listener_pid == msg.sender.pid() {
+                    if let Some((event, data)) = dfu_rx.take() {
+                        dfu_ipc.data[..data.len()].copy_from_slice(&data);
+                        dfu_ipc.len = data.len() as u16;
+                        dfu_ipc.event = event;
+                        buffer.replace(dfu_ipc).unwrap();
+                    } else {
+                        dfu_listener = Some(msg);
+                    }
+                } else {
+                    log::warn!(
+                        "DFU interface capability is locked on first use; additional servers are ignored: {:?}",
+                        msg.sender
+                    );
+                    dfu_ipc.event = DfuEvent::Denied;
+                    buffer.replace(dfu_ipc).unwrap();
+                }
+            }
+            Some(Opcode::DfuComplete) => msg_blocking_scalar_unpack!(msg, status, _, _, _, {
+                // the listener registers with `DfuRxDeferred`, before it has anything to complete
+                if dfu_listener_pid.is_some() && dfu_listener_pid == msg.sender.pid() {
+                    dfu_class.complete(FromPrimitive::from_usize(status).unwrap_or(DfuStatus::ErrUnknown));
+                    xous::return_scalar(msg.sender, 1).unwrap();
+                } else {
+                    xous::return_scalar(msg.sender, 0).unwrap();
+                }
+            }),
+            Some(Opcode::DfuIdleCheck) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                let ours = msg.sender.pid().map(|pid| pid.get() as u32) == Some(xous::process::id());
+                if !ours {
+                    log::warn!("DfuIdleCheck is only taken from the DFU idle timer");
+                }
+                let in_dfu = ours && view == Views::Dfu;
+                if in_dfu && !dfu_class.take_activity() {
+                    log::warn!("DFU: the host went quiet, leaving DFU mode");
+                    dfu_class.time_out();
+                    // the switch is made where the device is polled
+                    xous::try_send_message(
+                        cid,
+                        xous::Message::new_scalar(Opcode::UsbIrqHandler.to_usize().unwrap(), 0, 0, 0, 0),
+                    )
+                    .ok();
+                }
+                xous::return_scalar(msg.sender, in_dfu as usize).unwrap();
+            }),
             Some(Opcode::UsbIrqHandler) => {
                 let maybe_u2f = match view {
                     Views::FidoWithKbd => {
-                        if usb_dev.poll(&mut [&mut composite]) {
+                        #[cfg(feature = "ccid")]
+                        let polled = usb_dev.poll(&mut [&mut composite, &mut ccid, &mut dfu_runtime]);
+                        #[cfg(not(feature = "ccid"))]
+                        let polled = usb_dev.poll(&mut [&mut composite, &mut dfu_runtime]);
+                        if polled {
                             match composite.device::<NKROBootKeyboard<_>, _>().read_report() {
                                 Ok(l) => {
                                     log::info!("keyboard LEDs: {:?}", l);
@@ -833,11 +1202,11 @@ pub(crate) fn main_hw() -> ! {
                         }
                     }
                     Views::FidoOnly => {
-                        if fido_dev.poll(&mut [&mut fido_class]) {
-                            Some(fido_class.device::<RawFido<'_, _>, _>())
-                        } else {
-                            None
-                        }
+                        #[cfg(feature = "ccid")]
+                        let polled = fido_dev.poll(&mut [&mut fido_class, &mut fido_ccid]);
+                        #[cfg(not(feature = "ccid"))]
+                        let polled = fido_dev.poll(&mut [&mut fido_class]);
+                        if polled { Some(fido_class.device::<RawFido<'_, _>, _>()) } else { None }
                     }
                     #[cfg(feature = "mass-storage")]
                     Views::MassStorage => {
@@ -968,6 +1337,10 @@ pub(crate) fn main_hw() -> ! {
 
                         None
                     }
+                    Views::Dfu => {
+                        dfu_device.poll(&mut [&mut dfu_class]);
+                        None
+                    }
                 };
                 if let Some(u2f) = maybe_u2f {
                     match u2f.read_report() {
@@ -993,15 +1366,122 @@ pub(crate) fn main_hw() -> ! {
                         Err(e) => log::trace!("U2F ERR: {:?}", e),
                     }
                 }
+                #[cfg(feature = "ccid")]
+                {
+                    let maybe_apdu = match view {
+                        Views::FidoWithKbd => ccid.take_apdu(),
+                        Views::FidoOnly => fido_ccid.take_apdu(),
+                        _ => None,
+                    };
+                    if let Some((id, apdu)) = maybe_apdu {
+                        xous::try_send_message(
+                            te_conn,
+                            xous::Message::new_scalar(
+                                TimeExtensionOp::Pump.to_usize().unwrap(),
+                                id as usize,
+                                0,
+                                0,
+                                0,
+                            ),
+                        )
+                        .ok();
+                        if let Some(mut listener) = ccid_listener.take() {
+                            let mut response = unsafe {
+                                Buffer::from_memory_message_mut(listener.body.memory_message_mut().unwrap())
+                            };
+                            let mut buf = response.to_original::<ApduIpc, _>().unwrap();
+                            buf.data[..apdu.len()].copy_from_slice(&apdu);
+                            buf.len = apdu.len() as u16;
+                            buf.id = id;
+                            buf.code = U2fCode::RxAck;
+                            response.replace(buf).unwrap();
+                        } else {
+                            // the host waits for the answer before sending anything else, so one slot is
+                            // enough
+                            ccid_rx = Some((id, apdu));
+                        }
+                    }
+                    // a bus reset drops the APDU the card was working on, which also stops the time
+                    // extensions for it
+                    *ccid_pending.lock().unwrap() = match view {
+                        Views::FidoWithKbd => ccid.pending_id(),
+                        Views::FidoOnly => fido_ccid.pending_id(),
+                        _ => None,
+                    };
+                }
+                if let Some((event, data)) = dfu_class.take_event() {
+                    if dfu_listener_pid.is_none() && event != DfuEvent::Abort {
+                        log::warn!("DFU download, but nothing in this image takes updates");
+                        dfu_class.complete(DfuStatus::ErrTarget);
+                    } else if let Some(mut listener) = dfu_listener.take() {
+                        let mut response = unsafe {
+                            Buffer::from_memory_message_mut(listener.body.memory_message_mut().unwrap())
+                        };
+                        let mut buf = response.to_original::<DfuIpc, _>().unwrap();
+                        buf.data[..data.len()].copy_from_slice(&data);
+                        buf.len = data.len() as u16;
+                        buf.event = event;
+                        response.replace(buf).unwrap();
+                    } else {
+                        // the host waits for each block to be completed, so one slot is enough; an abort
+                        // supersedes a block that was never picked up
+                        dfu_rx = Some((event, data));
+                    }
+                }
+                if dfu_runtime.take_detach() || dfu_class.take_leave() {
+                    let target = if view == Views::Dfu { Views::FidoWithKbd } else { Views::Dfu };
+                    if target == Views::Dfu {
+                        dfu_class.take_activity();
+                        dfu_idle_timer.send(()).ok();
+                    }
+                    log::info!("DFU: switching to {:?}", target);
+                    // let the host see its request complete before the device drops off the bus
+                    tt.sleep_ms(DFU_DETACH_GRACE_MS).ok();
+                    #[cfg(not(feature = "cramium-soc"))]
+                    {
+                        usbmgmt.ll_reset(true);
+                        tt.sleep_ms(1000).ok();
+                        usbmgmt.ll_connect_device_core(true);
+                        tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
+                        usbmgmt.ll_reset(false);
+                    }
+                    #[cfg(feature = "cramium-soc")]
+                    match target {
+                        Views::Dfu => dfu_device.force_reset().ok(),
+                        _ => usb_dev.force_reset().ok(),
+                    };
+                    view = target;
+                }
 
-                let is_suspend = match view {
-                    Views::FidoWithKbd => usb_dev.state() == UsbDeviceState::Suspend,
-                    Views::FidoOnly => fido_dev.state() == UsbDeviceState::Suspend,
+                let state = match view {
+                    Views::FidoWithKbd => usb_dev.state(),
+                    Views::FidoOnly => fido_dev.state(),
                     #[cfg(feature = "mass-storage")]
-                    Views::MassStorage => ums_device.state() == UsbDeviceState::Suspend,
-                    Views::Serial => serial_device.state() == UsbDeviceState::Suspend,
-                    Views::HIDv2 => hidv2.state() == UsbDeviceState::Suspend,
+                    Views::MassStorage => ums_device.state(),
+                    Views::Serial => serial_device.state(),
+                    Views::HIDv2 => hidv2.state(),
+                    Views::Dfu => dfu_device.state(),
                 };
+                let is_suspend = state == UsbDeviceState::Suspend;
+                match state {
+                    UsbDeviceState::Configured => host_configured = true,
+                    UsbDeviceState::Suspend => {}
+                    _ => host_configured = false,
+                }
+                if host_suspended != (is_suspend && host_configured) {
+                    host_suspended = is_suspend && host_configured;
+                    log::info!("host {}", if host_suspended { "suspended" } else { "resumed" });
+                    wakeup_signalled = false;
+                }
+                #[cfg(any(feature = "renode", feature = "precursor", feature = "hosted"))]
+                if state != UsbDeviceState::Configured {
+                    host_inhibitor = None;
+                } else if host_inhibitor.is_none() {
+                    host_inhibitor = susres
+                        .inhibit("connected to an awake host")
+                        .map_err(|e| log::warn!("couldn't hold off suspend for the host: {:?}", e))
+                        .ok();
+                }
                 if is_suspend {
                     log::info!("suspend detected");
                     if was_suspend == false {
@@ -1076,6 +1556,9 @@ pub(crate) fn main_hw() -> ! {
                         UsbDeviceType::Debug => {
                             log::warn!("No debug core in this target");
                         }
+                        UsbDeviceType::Dfu => {
+                            log::warn!("DFU mode is only entered at the host's request");
+                        }
                     }
                 }
 
@@ -1159,6 +1642,7 @@ pub(crate) fn main_hw() -> ! {
                             }
                         }
                     }
+                    UsbDeviceType::Dfu => log::warn!("DFU mode is only entered at the host's request"),
                 }
                 xous::return_scalar(msg.sender, 0).unwrap();
             }),
@@ -1295,6 +1779,7 @@ pub(crate) fn main_hw() -> ! {
                             }
                         }
                     }
+                    UsbDeviceType::Dfu => log::warn!("DFU mode is only entered at the host's request"),
                 }
                 xous::return_scalar(msg.sender, 0).unwrap();
             }),
@@ -1319,6 +1804,7 @@ pub(crate) fn main_hw() -> ! {
                         Views::HIDv2 => {
                             xous::return_scalar(msg.sender, UsbDeviceType::HIDv2 as usize).unwrap()
                         }
+                        Views::Dfu => xous::return_scalar(msg.sender, UsbDeviceType::Dfu as usize).unwrap(),
                     }
                 } else {
                     xous::return_scalar(msg.sender, UsbDeviceType::Debug as usize).unwrap();
@@ -1378,6 +1864,24 @@ pub(crate) fn main_hw() -> ! {
                     }
                     Views::Serial => xous::return_scalar(msg.sender, serial_device.state() as usize).unwrap(),
                     Views::HIDv2 => xous::return_scalar(msg.sender, hidv2.state() as usize).unwrap(),
+                    Views::Dfu => xous::return_scalar(msg.sender, dfu_device.state() as usize).unwrap(),
+                }
+            }),
+            Some(Opcode::IsHostSuspended) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                xous::return_scalar(msg.sender, if host_suspended { 1 } else { 0 }).unwrap();
+            }),
+            Some(Opcode::RemoteWakeup) => msg_scalar_unpack!(msg, _, _, _, _, {
+                // only the FIDO views offer remote wakeup, and only if the host turned it on
+                let enabled = match view {
+                    Views::FidoWithKbd => usb_dev.remote_wakeup_enabled(),
+                    Views::FidoOnly => fido_dev.remote_wakeup_enabled(),
+                    _ => false,
+                };
+                if host_suspended && enabled && !wakeup_signalled {
+                    #[cfg(feature = "cramium-soc")]
+                    let mut usbmgmt = usbwrapper.core();
+                    wakeup_signalled = usbmgmt.remote_wakeup();
+                    log::info!("remote wakeup signalled: {:?}", wakeup_signalled);
                 }
             }),
             Some(Opcode::SendKeyCode) => msg_blocking_scalar_unpack!(msg, code0, code1, code2, autoup, {
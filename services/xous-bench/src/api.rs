@@ -0,0 +1,117 @@
+pub(crate) const SERVER_NAME_BENCH: &str = "_Benchmarks_";
+
+/// The host the TLS handshake benchmark connects to, unless told otherwise
+pub const DEFAULT_TLS_HOST: &str = "betrusted.io";
+pub const TLS_HOST_LEN: usize = 64;
+
+/// Bytes copied per iteration of `Benchmark::Memcpy`
+pub(crate) const MEMCPY_LEN: usize = 64 * 1024;
+/// Bytes written or read per iteration of the PDDB benchmarks
+pub(crate) const PDDB_LEN: usize = 32 * 1024;
+/// Bytes lent per iteration of `Benchmark::IpcLend`
+pub(crate) const LEND_LEN: usize = 4096;
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub(crate) enum Opcode {
+    /// Runs one benchmark, and returns when it is done (memory message, `BenchRun`)
+    Run = 0,
+    /// Exits the server
+    Quit = 1,
+}
+
+/// The benchmarks. Each always runs the same number of iterations over the same amount of data, so
+/// that results from different releases can be compared.
+#[derive(
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub enum Benchmark {
+    /// A blocking scalar message to another thread, and its return
+    IpcScalar = 0,
+    /// Lending a page to another thread, and getting it back
+    IpcLend = 1,
+    /// Copying 64 KiB within RAM
+    Memcpy = 2,
+    /// Writing 32 KiB to a PDDB key, and syncing it to FLASH
+    PddbWrite = 3,
+    /// Reading 32 KiB from a PDDB key
+    PddbRead = 4,
+    /// A TLS handshake, from the first byte sent to the handshake completing; DNS and TCP set up are
+    /// not counted, but the network round trips are. It goes out to a real host, so it is only run when
+    /// asked for by name; see `Benchmark::uses_network()`.
+    TlsHandshake = 5,
+    /// Generating a 6-digit HMAC-SHA1 TOTP code, with the vault's code generation
+    Totp = 6,
+}
+
+impl Benchmark {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Benchmark::IpcScalar => "ipc.scalar",
+            Benchmark::IpcLend => "ipc.lend",
+            Benchmark::Memcpy => "memcpy",
+            Benchmark::PddbWrite => "pddb.write",
+            Benchmark::PddbRead => "pddb.read",
+            Benchmark::TlsHandshake => "tls.handshake",
+            Benchmark::Totp => "totp",
+        }
+    }
+
+    /// Whether the benchmark connects out over the network. These aren't part of a default run.
+    pub fn uses_network(&self) -> bool { *self == Benchmark::TlsHandshake }
+
+    /// The iterations run, which are fixed so that results stay comparable
+    pub fn iterations(&self) -> u32 {
+        match self {
+            Benchmark::IpcScalar => 10_000,
+            Benchmark::IpcLend => 2_000,
+            Benchmark::Memcpy => 256,
+            Benchmark::PddbWrite => 8,
+            Benchmark::PddbRead => 8,
+            Benchmark::TlsHandshake => 3,
+            Benchmark::Totp => 1_000,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Measurement {
+    pub iterations: u32,
+    /// wall clock time for all the iterations together
+    pub elapsed_us: u64,
+}
+
+#[derive(
+    num_derive::FromPrimitive,
+    num_derive::ToPrimitive,
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    rkyv::Archive,
+    rkyv::Serialize,
+    rkyv::Deserialize,
+)]
+pub enum BenchError {
+    /// Something the benchmark needs isn't there: the PDDB isn't mounted, or the network is down
+    Unavailable = 1,
+    /// The benchmark ran, but got a wrong result or an error
+    Failed = 2,
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub(crate) struct BenchRun {
+    pub benchmark: Benchmark,
+    pub tls_host: xous_ipc::String<TLS_HOST_LEN>,
+    pub measurement: Option<Measurement>,
+    pub error: Option<BenchError>,
+}
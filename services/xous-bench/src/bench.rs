@@ -0,0 +1,176 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::{Duration, Instant};
+
+use num_traits::*;
+use vault_totp::{generate_totp_code, TotpAlgorithm, TotpEntry};
+
+use crate::api::*;
+
+/// The PDDB benchmarks keep their scratch keys in a dictionary of their own, named this with a number
+/// after it that no existing dictionary has. Only that dictionary is deleted afterwards.
+const PDDB_DICT_PREFIX: &str = "xous-bench.";
+
+/// RFC 6238 test key and the 8 digits it gives at T = 59 s, to check the TOTP code before timing it
+const TOTP_SECRET: &[u8] = b"12345678901234567890";
+const TOTP_AT_59: &str = "94287082";
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+enum EchoOp {
+    Scalar = 0,
+    Lend = 1,
+}
+
+/// Starts the thread the IPC benchmarks talk to, and returns a connection to it
+pub(crate) fn start_echo() -> xous::CID {
+    let sid = xous::create_server().unwrap();
+    std::thread::spawn(move || {
+        loop {
+            let msg = xous::receive_message(sid).unwrap();
+            match FromPrimitive::from_usize(msg.body.id()) {
+                Some(EchoOp::Scalar) => xous::return_scalar(msg.sender, 0).unwrap(),
+                // a lent page goes back when the message is dropped
+                Some(EchoOp::Lend) => {}
+                None => log::error!("couldn't convert opcode: {:?}", msg),
+            }
+        }
+    });
+    xous::connect(sid).unwrap()
+}
+
+pub(crate) fn run(benchmark: Benchmark, tls_host: &str, echo: xous::CID) -> Result<Measurement, BenchError> {
+    let iterations = benchmark.iterations();
+    match benchmark {
+        Benchmark::IpcScalar => time(iterations, || {
+            xous::send_message(
+                echo,
+                xous::Message::new_blocking_scalar(EchoOp::Scalar.to_usize().unwrap(), 0, 0, 0, 0),
+            )
+            .map(|_| ())
+            .or(Err(BenchError::Failed))
+        }),
+        Benchmark::IpcLend => {
+            let page =
+                xous::syscall::map_memory(None, None, LEND_LEN, xous::MemoryFlags::R | xous::MemoryFlags::W)
+                    .or(Err(BenchError::Failed))?;
+            let result = time(iterations, || {
+                xous::send_message(
+                    echo,
+                    xous::Message::new_lend(EchoOp::Lend.to_usize().unwrap(), page, None, None),
+                )
+                .map(|_| ())
+                .or(Err(BenchError::Failed))
+            });
+            xous::syscall::unmap_memory(page).ok();
+            result
+        }
+        Benchmark::Memcpy => {
+            let src = vec![0xA5u8; MEMCPY_LEN];
+            let mut dst = vec![0u8; MEMCPY_LEN];
+            time(iterations, || {
+                dst.copy_from_slice(core::hint::black_box(&src));
+                core::hint::black_box(&mut dst);
+                Ok(())
+            })
+        }
+        Benchmark::PddbWrite | Benchmark::PddbRead => pddb(benchmark == Benchmark::PddbWrite, iterations),
+        Benchmark::TlsHandshake => tls_handshake(tls_host, iterations),
+        Benchmark::Totp => {
+            // the vault's own code generation, as it runs for each entry shown
+            let entry = TotpEntry {
+                step_seconds: 30,
+                shared_secret: TOTP_SECRET.to_vec(),
+                digit_count: 8,
+                algorithm: TotpAlgorithm::HmacSha1,
+            };
+            if generate_totp_code(59, &entry).ok().as_deref() != Some(TOTP_AT_59) {
+                log::error!("TOTP code doesn't match RFC 6238");
+                return Err(BenchError::Failed);
+            }
+            let entry = TotpEntry { digit_count: 6, ..entry };
+            let mut t = 0;
+            time(iterations, || {
+                t += 30;
+                core::hint::black_box(generate_totp_code(t, &entry).or(Err(BenchError::Failed))?);
+                Ok(())
+            })
+        }
+    }
+}
+
+fn time(iterations: u32, mut op: impl FnMut() -> Result<(), BenchError>) -> Result<Measurement, BenchError> {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        op()?;
+    }
+    Ok(Measurement { iterations, elapsed_us: start.elapsed().as_micros() as u64 })
+}
+
+fn pddb(write: bool, iterations: u32) -> Result<Measurement, BenchError> {
+    if !pddb::PddbMountPoller::new().is_mounted_nonblocking() {
+        return Err(BenchError::Unavailable);
+    }
+    let pddb = pddb::Pddb::new();
+    let failed = |e: std::io::Error| {
+        log::warn!("PDDB benchmark failed: {:?}", e);
+        BenchError::Failed
+    };
+    let existing = pddb.list_dict(None).map_err(failed)?;
+    let dict =
+        (0..).map(|n| format!("{}{}", PDDB_DICT_PREFIX, n)).find(|name| !existing.contains(name)).unwrap();
+    let result = pddb_run(&pddb, &dict, write, iterations).map_err(failed);
+    pddb.delete_dict(&dict, None).ok();
+    pddb.sync().ok();
+    result
+}
+
+/// Writes go to one key over and over. Reads are from a different key each iteration, all written
+/// before the timing starts: the PDDB doesn't cache keys this large, and reading each key once keeps
+/// what is read out of the CPU's caches too.
+fn pddb_run(pddb: &pddb::Pddb, dict: &str, write: bool, iterations: u32) -> std::io::Result<Measurement> {
+    let data = vec![0x5Au8; PDDB_LEN];
+    let keys = if write { 1 } else { iterations };
+    let mut keys = (0..keys)
+        .map(|n| {
+            let mut key =
+                pddb.get(dict, &format!("scratch{}", n), None, true, true, Some(PDDB_LEN), None::<fn()>)?;
+            key.write_all(&data)?;
+            Ok(key)
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    pddb.sync()?;
+    let mut readback = vec![vec![0u8; PDDB_LEN]; keys.len()];
+    let start = Instant::now();
+    if write {
+        for _ in 0..iterations {
+            keys[0].seek(SeekFrom::Start(0))?;
+            keys[0].write_all(&data)?;
+            pddb.sync()?;
+        }
+    } else {
+        for (key, readback) in keys.iter_mut().zip(readback.iter_mut()) {
+            key.seek(SeekFrom::Start(0))?;
+            key.read_exact(readback)?;
+        }
+    }
+    let elapsed_us = start.elapsed().as_micros() as u64;
+    if !write && readback.iter().any(|r| *r != data) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "read back the wrong data"));
+    }
+    Ok(Measurement { iterations, elapsed_us })
+}
+
+fn tls_handshake(host: &str, iterations: u32) -> Result<Measurement, BenchError> {
+    let tls = tls::Tls::new();
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..iterations {
+        let (stream, diag) = tls.connect(host, 443);
+        match (stream, diag.handshake) {
+            (Ok(_), Some(handshake)) => elapsed += handshake,
+            _ => {
+                log::warn!("TLS benchmark couldn't connect to {}: {:?}", host, diag.failure);
+                return Err(BenchError::Unavailable);
+            }
+        }
+    }
+    Ok(Measurement { iterations, elapsed_us: elapsed.as_micros() as u64 })
+}
@@ -0,0 +1,99 @@
+#![doc = include_str!("../README.md")]
+
+pub mod api;
+pub use api::*;
+use num_traits::*;
+use xous::CID;
+use xous_ipc::Buffer;
+
+/// Client API to the benchmark service. The service is only in images built with
+/// `--service xous-bench`, so `new()` fails rather than waits when it is absent.
+pub struct Bench {
+    conn: CID,
+}
+impl Bench {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection(api::SERVER_NAME_BENCH)?;
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        Ok(Bench { conn })
+    }
+
+    /// Runs `benchmark`, and returns when it is done, which can take several seconds. `tls_host` is
+    /// only used by `Benchmark::TlsHandshake`; `None` picks `DEFAULT_TLS_HOST`.
+    pub fn run(
+        &self,
+        benchmark: Benchmark,
+        tls_host: Option<&str>,
+    ) -> Result<Result<Measurement, BenchError>, xous::Error> {
+        let tls_host = tls_host.unwrap_or(DEFAULT_TLS_HOST);
+        if tls_host.len() > TLS_HOST_LEN {
+            return Err(xous::Error::InvalidString);
+        }
+        let run = BenchRun {
+            benchmark,
+            tls_host: xous_ipc::String::from_str(tls_host),
+            measurement: None,
+            error: None,
+        };
+        let mut buf = Buffer::into_buf(run).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::Run.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        let response = buf.to_original::<BenchRun, _>().or(Err(xous::Error::InternalError))?;
+        match (response.measurement, response.error) {
+            (Some(measurement), _) => Ok(Ok(measurement)),
+            (None, Some(e)) => Ok(Err(e)),
+            (None, None) => Err(xous::Error::InternalError),
+        }
+    }
+}
+
+impl Benchmark {
+    pub const ALL: [Benchmark; 7] = [
+        Benchmark::IpcScalar,
+        Benchmark::IpcLend,
+        Benchmark::Memcpy,
+        Benchmark::PddbWrite,
+        Benchmark::PddbRead,
+        Benchmark::TlsHandshake,
+        Benchmark::Totp,
+    ];
+
+    /// The bytes moved per iteration, for the benchmarks where throughput is what matters
+    pub fn bytes(&self) -> Option<usize> {
+        match self {
+            Benchmark::IpcLend => Some(LEND_LEN),
+            Benchmark::Memcpy => Some(MEMCPY_LEN),
+            Benchmark::PddbWrite | Benchmark::PddbRead => Some(PDDB_LEN),
+            _ => None,
+        }
+    }
+
+    /// One line of a report: the time per iteration, and the throughput where it applies
+    pub fn report(&self, measurement: &Measurement) -> String {
+        let mut line = format!("{:<14} {:>9} us/op", self.name(), measurement.us_per_iteration());
+        if let Some(bytes) = self.bytes() {
+            line.push_str(&format!(" {:>7} KiB/s", measurement.kib_per_s(bytes)));
+        }
+        line.push_str(&format!(" ({}x)", measurement.iterations));
+        line
+    }
+}
+
+impl Measurement {
+    pub fn us_per_iteration(&self) -> u64 { self.elapsed_us / self.iterations.max(1) as u64 }
+
+    pub fn kib_per_s(&self, bytes: usize) -> u64 {
+        (bytes as u64 * self.iterations as u64 * 1_000_000) / (self.elapsed_us.max(1) * 1024)
+    }
+}
+
+use core::sync::atomic::{AtomicU32, Ordering};
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Bench {
+    fn drop(&mut self) {
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+mod api;
+use api::*;
+mod bench;
+use num_traits::*;
+use xous_ipc::Buffer;
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let bench_sid = xns.register_name(api::SERVER_NAME_BENCH, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", bench_sid);
+    let echo = bench::start_echo();
+
+    loop {
+        let mut msg = xous::receive_message(bench_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Run) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut run = buffer.to_original::<BenchRun, _>().unwrap();
+                log::info!("running {}", run.benchmark.name());
+                match bench::run(run.benchmark, run.tls_host.as_str().unwrap_or(DEFAULT_TLS_HOST), echo) {
+                    Ok(measurement) => {
+                        log::info!("{}: {:?}", run.benchmark.name(), measurement);
+                        run.measurement = Some(measurement);
+                    }
+                    Err(e) => {
+                        log::warn!("{} didn't run: {:?}", run.benchmark.name(), e);
+                        run.error = Some(e);
+                    }
+                }
+                buffer.replace(run).unwrap();
+            }
+            Some(Opcode::Quit) => {
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+    xns.unregister_server(bench_sid).unwrap();
+    xous::destroy_server(bench_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
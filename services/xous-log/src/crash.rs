@@ -0,0 +1,70 @@
+use std::collections::VecDeque;
+
+use xous_api_log::api;
+
+/// Reports held until they are taken. Once full, new reports are dropped, as the first crash is usually
+/// the one that explains the others.
+const MAX_PENDING_REPORTS: usize = 4;
+/// Length of the text in a `CrashRecord`
+const MAX_REPORT_LEN: usize = 4084;
+
+/// Crash reports waiting to be taken by a service that can store them in the PDDB.
+pub struct CrashReports {
+    /// panic text collected so far, for each PID that is in the middle of panicking
+    in_progress: Vec<(u32, Vec<u8>)>,
+    pending: VecDeque<(api::CrashKind, u32, Vec<u8>)>,
+}
+
+impl CrashReports {
+    pub fn new() -> Self { CrashReports { in_progress: Vec::new(), pending: VecDeque::new() } }
+
+    pub fn panic_started(&mut self, pid: u32) {
+        self.in_progress.retain(|(p, _)| *p != pid);
+        self.in_progress.push((pid, Vec::new()));
+    }
+
+    pub fn panic_text(&mut self, pid: u32, text: &[u8]) {
+        if let Some((_, report)) = self.in_progress.iter_mut().find(|(p, _)| *p == pid) {
+            let len = text.len().min(MAX_REPORT_LEN.saturating_sub(report.len()));
+            report.extend_from_slice(&text[..len]);
+        }
+    }
+
+    pub fn panic_finished(&mut self, pid: u32) {
+        if let Some(index) = self.in_progress.iter().position(|(p, _)| *p == pid) {
+            let (_, text) = self.in_progress.remove(index);
+            self.push(api::CrashKind::Panic, pid, text);
+        }
+    }
+
+    pub fn watchdog(&mut self) {
+        self.push(
+            api::CrashKind::Watchdog,
+            0,
+            b"The watchdog armed: the ticktimer was not serviced for a whole watchdog period".to_vec(),
+        );
+    }
+
+    fn push(&mut self, kind: api::CrashKind, pid: u32, text: Vec<u8>) {
+        if self.pending.len() < MAX_PENDING_REPORTS {
+            self.pending.push_back((kind, pid, text));
+        }
+    }
+
+    /// Moves the oldest pending report into `record`, or marks it as empty if there is none.
+    pub fn take(&mut self, record: &mut api::CrashRecord) {
+        match self.pending.pop_front() {
+            Some((kind, pid, text)) => {
+                let len = text.len().min(record.text.len());
+                record.kind = kind as u32;
+                record.pid = pid;
+                record.text[..len].copy_from_slice(&text[..len]);
+                record.text_length = len as u32;
+            }
+            None => {
+                record.kind = api::CrashKind::None as u32;
+                record.text_length = 0;
+            }
+        }
+    }
+}
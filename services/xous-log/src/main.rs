@@ -5,6 +5,8 @@ use xous_api_log::api;
 
 #[macro_use]
 mod platform;
+mod crash;
+mod ring;
 
 use core::fmt::Write;
 
@@ -50,6 +52,9 @@ fn reader_thread(arg: usize) {
     #[cfg(feature = "usb")]
     let mut usb_str = xous_ipc::String::<4000>::new();
 
+    let mut ring = ring::LogRing::new();
+    let mut crashes = crash::CrashReports::new();
+
     println!("LOG: my PID is {}", xous::process::id());
     let mut counter: usize = 0;
     loop {
@@ -96,6 +101,18 @@ fn reader_thread(arg: usize) {
 
                         let module_slice = &lr.module[0..lr.module_length as usize];
 
+                        if !ring.passes(module_slice, lr.level) {
+                            continue;
+                        }
+                        ring.push(
+                            lr.level,
+                            format!(
+                                "{}: {}",
+                                String::from_utf8_lossy(module_slice),
+                                String::from_utf8_lossy(args_slice)
+                            ),
+                        );
+
                         write!(output, "{}:", level).ok();
                         for c in module_slice {
                             output.putc(*c);
@@ -179,6 +196,23 @@ fn reader_thread(arg: usize) {
                             usb_send_str(conn, unsafe { std::str::from_utf8_unchecked(buffer) });
                         }
                     }
+                    api::Opcode::SetFilter => {
+                        let fr = unsafe { &*(mem.buf.as_ptr() as *const api::FilterRecord) };
+                        let module = &fr.module[..(fr.module_length as usize).min(fr.module.len())];
+                        ring.set_filter(&String::from_utf8_lossy(module), fr.level);
+                    }
+                    api::Opcode::ReadBuffer => {
+                        // Safe because the buffer was lent mutably, and there are no invalid values in
+                        // the struct.
+                        let buffer = unsafe { &mut *(mem.buf.as_mut_ptr() as *mut api::LogBuffer) };
+                        ring.read(buffer);
+                    }
+                    api::Opcode::TakeCrashReport => {
+                        // Safe because the buffer was lent mutably, and there are no invalid values in
+                        // the struct.
+                        let record = unsafe { &mut *(mem.buf.as_mut_ptr() as *mut api::CrashRecord) };
+                        crashes.take(record);
+                    }
                     _ => {
                         writeln!(output, "Unhandled opcode").unwrap();
                     }
@@ -188,6 +222,7 @@ fn reader_thread(arg: usize) {
                 let sender_pid = sender.pid().unwrap();
                 match scalar.id {
                     1000 => {
+                        crashes.panic_started(sender_pid.get() as u32);
                         writeln!(output, "PANIC in PID {}:", sender_pid).unwrap();
                         #[cfg(feature="usb")]
                         if let Some(conn) = usb_serial {
@@ -215,6 +250,8 @@ fn reader_thread(arg: usize) {
                             *dest = *src;
                         }
                         let total_chars = scalar.id - 1100;
+                        let report_chars = total_chars.min(output_bfr.len());
+                        crashes.panic_text(sender_pid.get() as u32, &output_bfr[..report_chars]);
                         for (idx, c) in output_bfr.iter().enumerate() {
                             if idx >= total_chars {
                                 break;
@@ -230,12 +267,17 @@ fn reader_thread(arg: usize) {
                         }
                     }
                     1200 => {
+                        crashes.panic_finished(sender_pid.get() as u32);
                         writeln!(output, "Terminating process").unwrap();
                         #[cfg(feature="usb")]
                         if let Some(conn) = usb_serial {
                             usb_send_str(conn, "Terminating process");
                         }
                     },
+                    id if id == api::Opcode::WatchdogEvent as usize => {
+                        crashes.watchdog();
+                        writeln!(output, "LOG: watchdog armed").ok();
+                    },
                     2000 => {
                         #[cfg(any(feature="precursor", feature="renode"))]
                         crate::platform::debug::DEFAULT.enable_rx();
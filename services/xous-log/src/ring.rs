@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+
+use xous_api_log::api;
+
+/// Bytes of message text kept in the RAM buffer
+const RING_CAPACITY: usize = 16384;
+/// Longer messages are truncated, so that any message fits in a `LogBuffer`
+const MAX_MESSAGE_LEN: usize = 1024;
+
+/// Recent log messages, kept in RAM so they can be read back on the device instead of only being seen
+/// over the debug UART.
+pub struct LogRing {
+    /// (log level, message) of each kept message; the oldest is at the front
+    entries: VecDeque<(u32, String)>,
+    bytes: usize,
+    /// sequence number that the next message will get
+    next_seq: u32,
+    /// (module prefix, most verbose level shown) pairs
+    filters: Vec<(String, u32)>,
+}
+
+impl LogRing {
+    pub fn new() -> Self { LogRing { entries: VecDeque::new(), bytes: 0, next_seq: 0, filters: Vec::new() } }
+
+    /// Returns `true` if a message at `level` from `module` passes the longest matching filter.
+    pub fn passes(&self, module: &[u8], level: u32) -> bool {
+        self.filters
+            .iter()
+            .filter(|(prefix, _)| module.starts_with(prefix.as_bytes()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(true, |(_, max_level)| level <= *max_level)
+    }
+
+    pub fn set_filter(&mut self, module: &str, level: u32) {
+        self.filters.retain(|(prefix, _)| prefix != module);
+        if level != api::FILTER_REMOVE {
+            self.filters.push((module.to_string(), level));
+        }
+    }
+
+    pub fn push(&mut self, level: u32, text: String) {
+        // one message per line when read back
+        let mut text = text.replace('\n', " ");
+        if text.len() > MAX_MESSAGE_LEN {
+            let mut end = MAX_MESSAGE_LEN;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+        }
+        self.bytes += text.len();
+        self.entries.push_back((level, text));
+        self.next_seq = self.next_seq.wrapping_add(1);
+        while self.bytes > RING_CAPACITY {
+            match self.entries.pop_front() {
+                Some((_, old)) => self.bytes -= old.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Fills `buffer` with as many messages as fit, starting from the sequence number in `buffer.start`.
+    pub fn read(&self, buffer: &mut api::LogBuffer) {
+        let first = self.next_seq.wrapping_sub(self.entries.len() as u32);
+        // skip messages that have dropped out of the buffer. Sequence numbers wrap, so compare distances.
+        let mut seq = if buffer.start.wrapping_sub(first) > self.next_seq.wrapping_sub(first) {
+            first
+        } else {
+            buffer.start
+        };
+        let mut len = 0;
+        for (level, text) in self.entries.iter().skip(seq.wrapping_sub(first) as usize) {
+            let line_len = text.len() + 3;
+            if len + line_len > buffer.data.len() {
+                break;
+            }
+            buffer.data[len] = b'0' + *level as u8;
+            buffer.data[len + 1] = b' ';
+            buffer.data[len + 2..len + 2 + text.len()].copy_from_slice(text.as_bytes());
+            buffer.data[len + line_len - 1] = b'\n';
+            len += line_len;
+            seq = seq.wrapping_add(1);
+        }
+        buffer.data_length = len as u32;
+        buffer.start = seq;
+        buffer.end = self.next_seq;
+    }
+}
@@ -2,6 +2,8 @@
 #![cfg_attr(target_os = "none", no_main)]
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use log::{error, info};
 use num_traits::FromPrimitive;
@@ -102,39 +104,52 @@ I'll take safety over speed in this case.
 Eventually, we shall endeavor to remove Heapless entirely, once we have a `libstd` in place
 and we can use heap-allocated Rust primitives...
 */
+/// A capability token, granted along with a connection. Presenting the token disconnects; the server can
+/// revoke it, and it lapses once its lifetime runs out.
 #[derive(Debug, Copy, Clone)]
+struct Grant {
+    pub token: [u32; 4],
+    pub pid: xous::PID,
+    pub generation: u32, // of the PID, which tells this client apart from later users of the PID
+    pub expires_at: Option<u64>, // in seconds of the clock; if None, the token never expires
+}
+#[derive(Debug, Clone)]
 struct Connection {
     pub sid: xous::SID,
     pub current_conns: u32, // number of unauthenticated (inherently trusted) connections
     pub max_conns: Option<u32>, // if None, unlimited connections allowed
     pub _allow_authenticate: bool,
-    pub _auth_conns: u32,        // number of authenticated connections
-    pub token: Option<[u32; 4]>, // a random number that must be presented to allow for disconnection
+    pub _auth_conns: u32,               // number of authenticated connections
+    pub token_lifetime: Option<u32>,    // seconds a token is valid for; if None, tokens never expire
+    pub grants: Vec<Grant>,             // the tokens of the connections currently granted
+    pub revoked: Vec<(xous::PID, u32)>, // clients, with their generation, refused further connections
+    pub lapsed: Vec<(xous::PID, u32)>,  // clients whose tokens expired, for the server to revoke
+}
+/// The health of a server that sends heartbeats. It is kept by name, so it outlives the server
+/// unregistering and registering again.
+#[derive(Debug, Copy, Clone)]
+struct Liveness {
+    pub interval: u32, // the most seconds the server may go between heartbeats
+    pub up: bool,
+    pub since: u64,          // clock time at which the server came up, or went down
+    pub last_heartbeat: u64, // clock time of the last heartbeat
+    pub restarts: u32,
 }
 #[derive(Debug)]
 struct CheckedHashMap {
     pub map: HashMap<XousServerName, Connection>,
+    pub liveness: HashMap<XousServerName, Liveness>,
 }
 impl CheckedHashMap {
-    pub fn new() -> Self { CheckedHashMap { map: HashMap::new() } }
+    pub fn new() -> Self { CheckedHashMap { map: HashMap::new(), liveness: HashMap::new() } }
 
     pub fn insert(
         &mut self,
         name: XousServerName,
         sid: xous::SID,
         max_conns: Option<u32>,
+        token_lifetime: Option<u32>,
     ) -> Result<(), xous::Error> {
-        let token =
-            // for use with 1-connection servers, provision a one-time use token for disconnects
-            // it will be returned for multi-connection servers as well, but it doesn't have a clear
-            // semantic meaning with multiple connections. However, this exists in particular to
-            // allow clean connect/disconnect in the special case of 1-connection servers that
-            // can be swapped out (such as plugins for IME predictions)
-            Some(
-                xous::create_server_id()
-                    .expect("couldn't create token")
-                    .to_array(),
-            );
         self.map.insert(
             name,
             Connection {
@@ -143,13 +158,16 @@ impl CheckedHashMap {
                 max_conns,
                 _allow_authenticate: false, // for now, we don't support authenticated connections
                 _auth_conns: 0,
-                token,
+                token_lifetime,
+                grants: Vec::new(),
+                revoked: Vec::new(),
+                lapsed: Vec::new(),
             },
         );
         Ok(())
     }
 
-    pub fn remove(&mut self, sid: xous::SID) -> Option<XousServerName> {
+    pub fn remove(&mut self, sid: xous::SID, now: u64) -> Option<XousServerName> {
         // remove is expensive, because we have to do a full search for the sid, which is not our usual key
         // however, for security reasons, you have to let us know your sid (which is a secret) in order to
         // delete your entry; whereas the human-readable name is not at all a secret
@@ -162,6 +180,10 @@ impl CheckedHashMap {
         }
         if let Some(name) = removed_name {
             self.map.remove(&name);
+            if let Some(liveness) = self.liveness.get_mut(&name) {
+                liveness.up = false;
+                liveness.since = now;
+            }
         }
 
         removed_name
@@ -169,41 +191,52 @@ impl CheckedHashMap {
 
     pub fn contains_key(&self, name: &XousServerName) -> bool { self.map.contains_key(name) }
 
-    pub fn connect(&mut self, name: &XousServerName) -> (Option<xous::SID>, Option<[u32; 4]>) {
+    /// Grants `pid`, in its `generation`, a connection to the server with `name`, if it may have one.
+    /// `now` is the time on the clock, from which the token's expiry is set.
+    pub fn connect(
+        &mut self,
+        name: &XousServerName,
+        pid: xous::PID,
+        generation: u32,
+        now: u64,
+    ) -> (Option<xous::SID>, Option<[u32; 4]>) {
         if let Some(entry) = self.map.get_mut(name) {
+            if entry.revoked.contains(&(pid, generation)) {
+                log::warn!("Attempt to connect to {:?} by revoked PID {}", name.to_str(), pid);
+                return (None, None);
+            }
             match entry.max_conns {
                 // single-connection case
                 Some(1) => {
                     if entry.current_conns < 1 {
                         (*entry).current_conns = 1;
-                        (Some(entry.sid), entry.token)
                     } else {
-                        (None, None)
+                        return (None, None);
                     }
                 }
                 Some(max) => {
                     if entry.current_conns < max {
                         (*entry).current_conns += 1;
-                        (Some(entry.sid), entry.token)
                     } else {
                         log::warn!("Attempt to connect, but no connections available: {:?}", name.to_str());
-                        (None, None)
+                        return (None, None);
                     }
                 }
                 _ => {
                     // unlimited connections allowed
                     (*entry).current_conns += 1;
-                    // previously, this did not return an entry.token, but we now do
-                    // because we had to loosen the restriction on the count of connections to
-                    // the IME plugins -- because by essence, a disconnected IME plugin does
-                    // not have its connection table full, and therefore, this would disallow
-                    // root key operations. However, we still want some control over who
-                    // is allowed to initiate the disconnect, so, we've moved the access
-                    // control to the server itself, thus allowing a permissive policy inside
-                    // xous-names.
-                    (Some(entry.sid), entry.token)
                 }
             }
+            // every connection gets its own one-time use token, which lets the client disconnect (as the
+            // swappable IME plugins do), and lets the server revoke it
+            let token = xous::create_server_id().expect("couldn't create token").to_array();
+            entry.grants.push(Grant {
+                token,
+                pid,
+                generation,
+                expires_at: entry.token_lifetime.map(|lifetime| now + lifetime as u64),
+            });
+            (Some(entry.sid), Some(token))
         } else {
             (None, None)
         }
@@ -222,16 +255,29 @@ impl CheckedHashMap {
         trusted_done
     }
 
-    // this function is slightly unsafe because we can't guarantee that the presenter of the SID
-    // has actually discarded the SID. However, we don't currently anticipate using this path a lot.
-    // If it does get used in security-critical routes, it should be refactored to regenerate the SID
-    // and publish it to the server every time a disconnect is called, to ensure that after a disconnection
-    // the caller can never talk to the server again.
-    pub fn disconnect(&mut self, sid: xous::SID) -> Option<XousServerName> {
-        for (name, mapping) in self.map.iter_mut() {
-            if mapping.sid == sid {
-                if mapping.current_conns > 0 {
-                    mapping.current_conns -= 1;
+    // the token is used up by the disconnect, and frees the slot it held against `max_conns`
+    pub fn disconnect_with_token(&mut self, name: &XousServerName, token: [u32; 4]) -> bool {
+        if let Some(entry) = self.map.get_mut(name) {
+            if let Some(index) = entry.grants.iter().position(|grant| grant.token == token) {
+                entry.grants.remove(index);
+                entry.current_conns = entry.current_conns.saturating_sub(1);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Revokes the tokens `pid`, in its `generation`, holds for the server with `sid`, and refuses it
+    /// further connections. Returns the name of the server, if there is one with that SID.
+    pub fn revoke(&mut self, sid: xous::SID, pid: xous::PID, generation: u32) -> Option<XousServerName> {
+        for (name, entry) in self.map.iter_mut() {
+            if entry.sid == sid {
+                let held = entry.grants.len();
+                entry.grants.retain(|grant| (grant.pid, grant.generation) != (pid, generation));
+                entry.current_conns = entry.current_conns.saturating_sub((held - entry.grants.len()) as u32);
+                entry.lapsed.retain(|&lapsed| lapsed != (pid, generation));
+                if !entry.revoked.contains(&(pid, generation)) {
+                    entry.revoked.push((pid, generation));
                 }
                 return Some(*name);
             }
@@ -239,26 +285,94 @@ impl CheckedHashMap {
         None
     }
 
-    // this is a safer version of disconnect. we track servers that allow exactly one connection at a time
-    // and give them a one-time-use token that a connector can use to disconnect.
-    pub fn disconnect_with_token(&mut self, name: &XousServerName, token: [u32; 4]) -> bool {
-        if let Some(entry) = self.map.get_mut(name) {
-            if let Some(old_token) = entry.token {
-                if token == old_token {
-                    if entry.current_conns != 1 {
-                        log::warn!(
-                            "disconnect with token used on system with more than one client; this will cause other disconnects to fail"
-                        );
-                    }
-                    (*entry).current_conns = 0;
-                    // generate the token -- we should never re-use these!
-                    (*entry).token =
-                        Some(xous::create_server_id().expect("couldn't create token").to_array());
-                    return true;
+    /// Removes tokens that have expired by `now`, freeing their slots. The clients left with no token
+    /// for a server are queued for the server to revoke, through `take_lapsed()`.
+    pub fn expire(&mut self, now: u64) {
+        for entry in self.map.values_mut() {
+            let held = entry.grants.len();
+            let mut expired = Vec::new();
+            entry.grants.retain(|grant| {
+                let live = grant.expires_at.map(|at| at > now).unwrap_or(true);
+                if !live {
+                    expired.push((grant.pid, grant.generation));
+                }
+                live
+            });
+            entry.current_conns = entry.current_conns.saturating_sub((held - entry.grants.len()) as u32);
+            // clients that connected more than once share a connection, which stays up while any of
+            // their tokens is live
+            for client in expired {
+                if !entry.grants.iter().any(|grant| (grant.pid, grant.generation) == client)
+                    && !entry.lapsed.contains(&client)
+                {
+                    entry.lapsed.push(client);
                 }
             }
         }
-        false
+    }
+
+    /// Takes the next client whose tokens for the server with `sid` lapsed. Returns `None` if there is
+    /// no server with that SID, and `Some(None)` if no client is waiting to be revoked.
+    pub fn take_lapsed(&mut self, sid: xous::SID) -> Option<Option<(xous::PID, u32)>> {
+        let entry = self.map.values_mut().find(|entry| entry.sid == sid)?;
+        if entry.lapsed.is_empty() { Some(None) } else { Some(Some(entry.lapsed.remove(0))) }
+    }
+
+    /// Whether the clock needs to run: some token can expire, or some server's liveness is tracked
+    pub fn needs_clock(&self) -> bool {
+        !self.liveness.is_empty()
+            || self.map.values().any(|entry| entry.grants.iter().any(|grant| grant.expires_at.is_some()))
+    }
+
+    fn name_of(&self, sid: xous::SID) -> Option<XousServerName> {
+        self.map.iter().find(|(_, entry)| entry.sid == sid).map(|(name, _)| *name)
+    }
+
+    /// Starts tracking the liveness of the server with `sid`; tracking it again counts as a restart.
+    /// Returns false if there is no server with that SID.
+    pub fn register_liveness(&mut self, sid: xous::SID, interval: u32, now: u64) -> bool {
+        let Some(name) = self.name_of(sid) else {
+            return false;
+        };
+        let restarts = self.liveness.get(&name).map(|liveness| liveness.restarts + 1).unwrap_or(0);
+        self.liveness.insert(
+            name,
+            Liveness { interval: interval.max(1), up: true, since: now, last_heartbeat: now, restarts },
+        );
+        true
+    }
+
+    pub fn heartbeat(&mut self, sid: xous::SID, now: u64) {
+        if let Some(liveness) = self.name_of(sid).and_then(|name| self.liveness.get_mut(&name)) {
+            if !liveness.up {
+                liveness.up = true;
+                liveness.since = now;
+            }
+            liveness.last_heartbeat = now;
+        }
+    }
+
+    /// Marks servers that have missed two heartbeats as down, and returns their names.
+    pub fn check_liveness(&mut self, now: u64) -> Vec<XousServerName> {
+        let mut down = Vec::new();
+        for (name, liveness) in self.liveness.iter_mut() {
+            if liveness.up && now - liveness.last_heartbeat > 2 * liveness.interval as u64 {
+                liveness.up = false;
+                liveness.since = now;
+                down.push(*name);
+            }
+        }
+        down
+    }
+
+    pub fn liveness_of(&self, name: &XousServerName, now: u64) -> Option<ServiceLiveness> {
+        self.liveness.get(name).map(|liveness| ServiceLiveness {
+            registered: self.map.contains_key(name),
+            up: liveness.up,
+            since_secs: (now - liveness.since) as u32,
+            last_heartbeat_secs: (now - liveness.last_heartbeat) as u32,
+            restarts: liveness.restarts,
+        })
     }
 }
 
@@ -283,14 +397,16 @@ fn name_from_msg(env: &MessageEnvelope) -> Result<XousServerName, ConnectError>
 fn blocking_connect(
     env: &mut MessageEnvelope,
     name_table: &mut CheckedHashMap,
+    now: u64,
 ) -> Result<ConnectSuccess, ConnectError> {
     let name = name_from_msg(env)?;
     let sender_pid = env.sender.pid().expect("kernel provided us a PID of None");
+    let sender_generation = xous::process_generation(sender_pid).unwrap_or_default();
     log::trace!("BlockingConnect request for '{}' for process {:?}", name, sender_pid);
 
     // If the server already exists, attempt to make the connection. The connection can
     // only succeed if the server is in the name_table.
-    if let (Some(server_sid), token) = name_table.connect(&name) {
+    if let (Some(server_sid), token) = name_table.connect(&name, sender_pid, sender_generation, now) {
         log::trace!(
             "Found entry in the table (sid: {:?}, token: {:?}) -- attempting to call connect_for_process()",
             server_sid,
@@ -310,8 +426,8 @@ fn blocking_connect(
             log::error!("error when making connection, perhaps the server crashed? {:?}", result);
 
             // The server connection process failed inside the kernel for one reason or
-            // another, so remove the entry from the `name_table` and return an error
-            name_table.disconnect(server_sid);
+            // another, so give back the token and return an error
+            name_table.disconnect_with_token(&name, token.unwrap());
             return Err(ConnectError::KernelConnectFailure);
         }
     }
@@ -321,6 +437,12 @@ fn blocking_connect(
     Ok(ConnectSuccess::Wait)
 }
 
+/// The SID carried in the four arguments of a scalar message
+fn sid_from_scalar(env: &MessageEnvelope) -> Option<xous::SID> {
+    let scalar = env.body.scalar_message()?;
+    Some(xous::SID::from_u32(scalar.arg1 as u32, scalar.arg2 as u32, scalar.arg3 as u32, scalar.arg4 as u32))
+}
+
 fn respond_connect_error(mut msg: MessageEnvelope, result: ConnectError) {
     let mem = msg.body.memory_message_mut().unwrap();
     let s = unsafe { core::slice::from_raw_parts_mut(mem.buf.as_mut_ptr() as *mut u32, mem.buf.len() / 4) };
@@ -357,11 +479,40 @@ fn main() -> ! {
     // When a connection is requested but the server does not yet exist, it gets
     // placed into this pool.
     let mut waiting_connections: Vec<MessageEnvelope> = vec![];
+    // Servers waiting for their clients' tokens to lapse, so they can revoke them.
+    let mut lapse_waiters: Vec<MessageEnvelope> = vec![];
 
     // this limits the number of available servers to be requested to 128...!
     //let mut name_table = FnvIndexMap::<XousServerName, xous::SID, 128>::new();
     let mut name_table = CheckedHashMap::new();
 
+    // Token lifetimes and heartbeats are timed in seconds by a thread that ticks while the clock is needed.
+    // The main loop never reads the time itself, as the ticktimer connects through us and must never wait
+    // on us.
+    let mut clock: u64 = 0;
+    let clock_running = Arc::new(AtomicBool::new(false));
+    let clock_thread = {
+        let clock_running = clock_running.clone();
+        std::thread::spawn(move || {
+            let conn = xous::connect(xous::SID::from_bytes(b"xous-name-server").unwrap())
+                .expect("couldn't connect to self");
+            loop {
+                if clock_running.load(Ordering::SeqCst) {
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                    xous::send_message(
+                        conn,
+                        xous::Message::new_scalar(api::Opcode::ClockTick as usize, 0, 0, 0, 0),
+                    )
+                    .ok();
+                } else {
+                    std::thread::park();
+                }
+            }
+        })
+        .thread()
+        .clone()
+    };
+
     info!("started");
     loop {
         let mut msg = xous::receive_message(name_server).unwrap();
@@ -382,7 +533,7 @@ fn main() -> ! {
                 if !name_table.contains_key(&name) {
                     let new_sid = xous::create_server_id().expect("create server failed, maybe OOM?");
                     name_table
-                        .insert(name, new_sid, registration.conn_limit)
+                        .insert(name, new_sid, registration.conn_limit, registration.token_lifetime)
                         .expect("register name failure, maybe out of HashMap capacity?");
                     log::trace!("request successful, SID is {:?}", new_sid);
                     should_connect = true;
@@ -409,7 +560,7 @@ fn main() -> ! {
                     while i >= 0 {
                         if name_from_msg(&waiting_connections[i as usize]) == Ok(name) {
                             let mut msg = waiting_connections.remove(i as usize);
-                            match blocking_connect(&mut msg, &mut name_table) {
+                            match blocking_connect(&mut msg, &mut name_table, clock) {
                                 Err(e) => respond_connect_error(msg, e),
                                 Ok(ConnectSuccess::Connected(cid, disc)) => {
                                     respond_connect_success(msg, cid, disc)
@@ -427,8 +578,17 @@ fn main() -> ! {
             }
             Some(api::Opcode::Unregister) => msg_blocking_scalar_unpack!(msg, s0, s1, s2, s3, {
                 let gid = xous::SID::from_u32(s0 as u32, s1 as u32, s2 as u32, s3 as u32);
-                if let Some(name) = name_table.remove(gid) {
+                if let Some(name) = name_table.remove(gid, clock) {
                     info!("{} server has unregistered", name);
+                    // its revocation thread has nothing more to wait for
+                    lapse_waiters.retain(|waiter| {
+                        if sid_from_scalar(waiter) == Some(gid) {
+                            xous::return_scalar2(waiter.sender, 0, 0).ok();
+                            false
+                        } else {
+                            true
+                        }
+                    });
                     xous::return_scalar(msg.sender, 1).unwrap();
                 } else {
                     log::error!("couldn't unregister {:?}", gid);
@@ -445,7 +605,7 @@ fn main() -> ! {
                     continue;
                 }
 
-                match blocking_connect(&mut msg, &mut name_table) {
+                match blocking_connect(&mut msg, &mut name_table, clock) {
                     Err(e) => respond_connect_error(msg, e),
                     Ok(ConnectSuccess::Connected(cid, disc)) => respond_connect_success(msg, cid, disc),
                     Ok(ConnectSuccess::Wait) => {
@@ -468,8 +628,11 @@ fn main() -> ! {
                 );
                 log::trace!("Lookup request for '{}'", name);
                 let response: api::Return;
-                if let (Some(server_sid), token) = name_table.connect(&name) {
-                    let sender_pid = msg.sender.pid().expect("can't extract sender PID on Lookup");
+                let sender_pid = msg.sender.pid().expect("can't extract sender PID on Lookup");
+                let sender_generation = xous::process_generation(sender_pid).unwrap_or_default();
+                if let (Some(server_sid), token) =
+                    name_table.connect(&name, sender_pid, sender_generation, clock)
+                {
                     match xous::connect_for_process(sender_pid, server_sid).expect("can't broker connection")
                     {
                         xous::Result::ConnectionID(connection_id) => {
@@ -477,6 +640,7 @@ fn main() -> ! {
                             response = api::Return::CID((connection_id, token))
                         }
                         _ => {
+                            name_table.disconnect_with_token(&name, token.unwrap());
                             log::debug!("Can't find request '{}' in table, dumping table:", name);
                             for (_name, conn) in name_table.map.iter() {
                                 log::debug!("{:?}", conn);
@@ -532,11 +696,132 @@ fn main() -> ! {
                 };
                 buffer.replace(response).expect("Can't return buffer");
             }
+            Some(api::Opcode::Revoke) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(revocation) = buffer.to_original::<Revocation, _>() else {
+                    continue;
+                };
+                let sid = xous::SID::from_array(revocation.sid);
+                // the server has already revoked the connections themselves, as only it may
+                let response = match xous::PID::new(revocation.pid) {
+                    Some(pid) => {
+                        if let Some(name) = name_table.revoke(sid, pid, revocation.generation) {
+                            info!("revoked PID {}'s tokens for {}", pid, name);
+                            api::Return::Success
+                        } else {
+                            api::Return::Failure
+                        }
+                    }
+                    None => api::Return::Failure,
+                };
+                buffer.replace(response).expect("Can't return buffer");
+            }
+            Some(api::Opcode::WaitLapsed) => {
+                if !msg.body.is_blocking() {
+                    continue;
+                }
+                let Some(sid) = sid_from_scalar(&msg) else {
+                    continue;
+                };
+                match name_table.take_lapsed(sid) {
+                    Some(Some((pid, generation))) => {
+                        xous::return_scalar2(msg.sender, pid.get() as usize, generation as usize).ok();
+                    }
+                    Some(None) => lapse_waiters.push(msg),
+                    None => {
+                        xous::return_scalar2(msg.sender, 0, 0).ok();
+                    }
+                }
+            }
+            Some(api::Opcode::ClockTick) => {
+                // only our own clock thread may advance the clock
+                if msg.sender.pid().map(|pid| pid.get() as u32) != Some(xous::process::id()) {
+                    continue;
+                }
+                clock += 1;
+                name_table.expire(clock);
+                lapse_waiters.retain(|waiter| {
+                    let Some(sid) = sid_from_scalar(waiter) else {
+                        return false;
+                    };
+                    match name_table.take_lapsed(sid) {
+                        Some(Some((pid, generation))) => {
+                            log::debug!("token of PID {} expired", pid);
+                            xous::return_scalar2(waiter.sender, pid.get() as usize, generation as usize).ok();
+                            false
+                        }
+                        Some(None) => true,
+                        None => {
+                            xous::return_scalar2(waiter.sender, 0, 0).ok();
+                            false
+                        }
+                    }
+                });
+                for name in name_table.check_liveness(clock) {
+                    log::warn!("{} missed its heartbeats, and is now considered down", name);
+                }
+            }
+            Some(api::Opcode::RegisterLiveness) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("RegisterLiveness was not a memory message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Ok(registration) = buffer.to_original::<LivenessRegistration, _>() else {
+                    log::error!("couldn't deserialize a liveness registration");
+                    buffer.replace(api::Return::Failure).ok();
+                    continue;
+                };
+                let sid = xous::SID::from_array(registration.sid);
+                let response = if name_table.register_liveness(sid, registration.interval_secs, clock) {
+                    api::Return::Success
+                } else {
+                    api::Return::Failure
+                };
+                buffer.replace(response).ok();
+            }
+            Some(api::Opcode::Heartbeat) => {
+                if let Some(scalar) = msg.body.scalar_message() {
+                    let sid = xous::SID::from_u32(
+                        scalar.arg1 as u32,
+                        scalar.arg2 as u32,
+                        scalar.arg3 as u32,
+                        scalar.arg4 as u32,
+                    );
+                    name_table.heartbeat(sid, clock);
+                }
+            }
+            Some(api::Opcode::QueryLiveness) => {
+                let Some(mem) = msg.body.memory_message_mut() else {
+                    log::error!("QueryLiveness was not a memory message");
+                    continue;
+                };
+                let mut buffer = unsafe { Buffer::from_memory_message_mut(mem) };
+                let Some(name) = buffer
+                    .to_original::<String<64>, _>()
+                    .ok()
+                    .and_then(|name_string| name_string.as_str().ok().map(XousServerName::from_str))
+                else {
+                    log::error!("couldn't deserialize a liveness query");
+                    buffer.replace(api::Return::Failure).ok();
+                    continue;
+                };
+                buffer.replace(api::Return::Liveness(name_table.liveness_of(&name, clock))).ok();
+            }
             None => {
                 error!("couldn't decode message: {:?}", msg);
                 break;
             }
         }
+
+        // keep the clock running while it is needed
+        let pending = name_table.needs_clock();
+        if !clock_running.swap(pending, Ordering::SeqCst) && pending {
+            clock_thread.unpark();
+        }
     }
     // clean up our program
     log::trace!("main loop exit, destroying servers");
@@ -544,3 +829,62 @@ fn main() -> ! {
     log::trace!("quitting");
     xous::terminate_process(0);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(table: &mut CheckedHashMap, name: &str, id: u32) -> (XousServerName, xous::SID) {
+        let name = XousServerName::from_str(name);
+        let sid = xous::SID::from_u32(id, 0, 0, 0);
+        table.insert(name, sid, None, None).unwrap();
+        (name, sid)
+    }
+
+    #[test]
+    fn liveness_needs_the_sid() {
+        let mut table = CheckedHashMap::new();
+        let (name, _sid) = server(&mut table, "alive", 1);
+        assert!(!table.register_liveness(xous::SID::from_u32(2, 0, 0, 0), 5, 0));
+        assert!(table.liveness_of(&name, 0).is_none());
+        assert!(!table.needs_clock());
+    }
+
+    #[test]
+    fn missed_heartbeats_mark_a_server_down() {
+        let mut table = CheckedHashMap::new();
+        let (name, sid) = server(&mut table, "alive", 1);
+        assert!(table.register_liveness(sid, 5, 0));
+        assert!(table.needs_clock());
+        table.heartbeat(sid, 5);
+        // two missed intervals are tolerated
+        assert!(table.check_liveness(15).is_empty());
+        assert_eq!(table.check_liveness(16), vec![name]);
+        let liveness = table.liveness_of(&name, 20).unwrap();
+        assert!(liveness.registered && !liveness.up);
+        assert_eq!((liveness.since_secs, liveness.last_heartbeat_secs), (4, 15));
+        // a late heartbeat brings it back up
+        table.heartbeat(sid, 21);
+        let liveness = table.liveness_of(&name, 21).unwrap();
+        assert!(liveness.up);
+        assert_eq!(liveness.since_secs, 0);
+    }
+
+    #[test]
+    fn registering_again_counts_a_restart() {
+        let mut table = CheckedHashMap::new();
+        let (name, sid) = server(&mut table, "alive", 1);
+        assert!(table.register_liveness(sid, 5, 0));
+        assert_eq!(table.remove(sid, 3), Some(name));
+        let liveness = table.liveness_of(&name, 4).unwrap();
+        assert!(!liveness.registered && !liveness.up);
+        // the restarted server has a new SID; the old one no longer carries heartbeats
+        let (_, new_sid) = server(&mut table, "alive", 2);
+        table.heartbeat(sid, 5);
+        assert!(!table.liveness_of(&name, 5).unwrap().up);
+        assert!(table.register_liveness(new_sid, 5, 6));
+        let liveness = table.liveness_of(&name, 6).unwrap();
+        assert!(liveness.registered && liveness.up);
+        assert_eq!(liveness.restarts, 1);
+    }
+}
@@ -586,6 +586,20 @@ pub fn timeout_thread(sid0: usize, sid1: usize, sid2: usize, sid3: usize) {
 
 static SHOULD_RESUME: AtomicBool = AtomicBool::new(false);
 
+/// Whether the holder of an inhibitor is still running. An inhibitor held by a process that exited
+/// without dropping it would otherwise keep the system awake forever, and its PID may since have gone
+/// to another process, so the generation has to match too.
+fn holder_exists(pid: u8, generation: u32) -> bool {
+    xous::PID::new(pid).map_or(false, |pid| xous::process_generation(pid) == Ok(generation))
+}
+
+/// The name `pid` was loaded with, which labels it in the list of inhibitors. A name the holder
+/// picked itself could pass for any other service.
+fn holder_name(pid: Option<xous::PID>) -> xous_ipc::String<INHIBITOR_NAME_LEN> {
+    let name = pid.and_then(|pid| xous::process_name(pid).ok());
+    xous_ipc::String::from_str(name.as_ref().map_or("", |name| name.as_str()))
+}
+
 fn main() -> ! {
     // Start the OS timer which is responsible for setting up preemption.
     // os_timer::init();
@@ -631,6 +645,11 @@ fn main() -> ! {
     let mut timeout_pending = false;
     let mut reboot_requested: bool = false;
     let mut allow_suspend = true;
+    // the process that last denied suspend with `set_suspendable(false)`
+    let mut denied_by: Option<xous::PID> = None;
+    // each inhibitor with the generation of its holder's PID
+    let mut inhibitors = Vec::<(Inhibitor, u32)>::new();
+    let mut next_inhibitor_handle: u32 = 1;
 
     let mut suspend_subscribers = Vec::<ScalarCallback>::new();
     let mut current_op_order = crate::api::SuspendOrder::Early;
@@ -780,7 +799,11 @@ fn main() -> ! {
                     }*/
                     // if the 2-second timeout is still pending from a previous suspend, deny the suspend
                     // request. ...just don't suspend that quickly after resuming???
-                    if allow_suspend && !timeout_pending {
+                    inhibitors.retain(|(inhibitor, generation)| holder_exists(inhibitor.pid, *generation));
+                    for (inhibitor, _) in inhibitors.iter() {
+                        log::info!("suspend inhibited by PID {}: {}", inhibitor.pid, inhibitor.reason);
+                    }
+                    if allow_suspend && inhibitors.is_empty() && !timeout_pending {
                         susres_hw.ignore_wfi();
                         suspend_requested = Some(msg.sender);
                         // clear the resume gate
@@ -892,9 +915,68 @@ fn main() -> ! {
                 }),
                 Some(Opcode::SuspendAllow) => {
                     allow_suspend = true;
+                    denied_by = None;
                 }
                 Some(Opcode::SuspendDeny) => {
                     allow_suspend = false;
+                    denied_by = msg.sender.pid();
+                }
+                Some(Opcode::Inhibit) => {
+                    let mut buffer =
+                        unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                    let mut inhibitor = buffer.to_original::<Inhibitor, _>().unwrap();
+                    inhibitors.retain(|(inhibitor, generation)| holder_exists(inhibitor.pid, *generation));
+                    let sender = msg.sender.pid();
+                    let generation = sender.and_then(|pid| xous::process_generation(pid).ok());
+                    inhibitor.pid = sender.map(|pid| pid.get()).unwrap_or(0);
+                    inhibitor.name = holder_name(sender);
+                    inhibitor.handle = 0;
+                    let held = inhibitors.iter().filter(|(held, _)| held.pid == inhibitor.pid).count();
+                    match generation {
+                        Some(_) if inhibitors.len() >= MAX_INHIBITORS => {
+                            log::warn!("PID {} can't inhibit suspend, too many are held", inhibitor.pid)
+                        }
+                        Some(_) if held >= MAX_INHIBITORS_PER_PROCESS => {
+                            log::warn!("PID {} can't inhibit suspend, it holds too many", inhibitor.pid)
+                        }
+                        Some(generation) => {
+                            inhibitor.handle = next_inhibitor_handle;
+                            // 0 is reserved for a refused inhibitor
+                            next_inhibitor_handle = next_inhibitor_handle.checked_add(1).unwrap_or(1);
+                            log::info!("PID {} inhibits suspend: {}", inhibitor.pid, inhibitor.reason);
+                            inhibitors.push((inhibitor, generation));
+                        }
+                        None => log::warn!("an unknown sender can't inhibit suspend"),
+                    }
+                    buffer.replace(inhibitor).unwrap();
+                }
+                Some(Opcode::ReleaseInhibitor) => msg_scalar_unpack!(msg, handle, _, _, _, {
+                    // an inhibitor can only be released by the process that holds it
+                    let sender = msg.sender.pid().map(|pid| pid.get()).unwrap_or(0);
+                    inhibitors.retain(|(inhibitor, _)| {
+                        inhibitor.handle != handle as u32 || inhibitor.pid != sender
+                    });
+                }),
+                Some(Opcode::ListInhibitors) => {
+                    let mut buffer =
+                        unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                    let mut list = InhibitorList { inhibitors: [None; MAX_INHIBITORS + 1] };
+                    inhibitors.retain(|(inhibitor, generation)| holder_exists(inhibitor.pid, *generation));
+                    let denial = if allow_suspend {
+                        None
+                    } else {
+                        Some(Inhibitor {
+                            name: holder_name(denied_by),
+                            reason: xous_ipc::String::new(),
+                            handle: 0,
+                            pid: denied_by.map(|pid| pid.get()).unwrap_or(0),
+                        })
+                    };
+                    let held = inhibitors.iter().map(|(inhibitor, _)| inhibitor);
+                    for (entry, inhibitor) in list.inhibitors.iter_mut().zip(denial.iter().chain(held)) {
+                        *entry = Some(*inhibitor);
+                    }
+                    buffer.replace(list).unwrap();
                 }
                 Some(Opcode::PowerOff) => {
                     susres_hw.force_power_off();
@@ -0,0 +1,79 @@
+use xous::PID;
+use xous_api_ticktimer::api::{Activity, ACTIVITY_KINDS};
+
+/// When each `Activity` was last reported, and which process may report it.
+///
+/// Each kind is bound to the first process that reports it. The services that see the activity (the
+/// keyboard for user input, the net service for the network) report once as they start, before any app
+/// runs, so an app can't keep the device awake by reporting activity that didn't happen.
+pub(crate) struct ActivityLog {
+    /// in ms since boot; boot counts as activity
+    last: [u64; ACTIVITY_KINDS],
+    reporter: [Option<PID>; ACTIVITY_KINDS],
+}
+
+impl ActivityLog {
+    pub(crate) fn new() -> Self { ActivityLog { last: [0; ACTIVITY_KINDS], reporter: [None; ACTIVITY_KINDS] } }
+
+    /// Records `activity` at `now`, if `pid` is the process that reports it. Returns whether it was
+    /// recorded.
+    pub(crate) fn report(&mut self, pid: Option<PID>, activity: Activity, now: u64) -> bool {
+        let Some(pid) = pid else {
+            return false;
+        };
+        let reporter = &mut self.reporter[activity as usize];
+        if *reporter.get_or_insert(pid) != pid {
+            return false;
+        }
+        self.last[activity as usize] = now;
+        true
+    }
+
+    pub(crate) fn idle_ms(&self, activity: Activity, now: u64) -> u64 {
+        now.saturating_sub(self.last[activity as usize])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pid(n: u8) -> Option<PID> { PID::new(n) }
+
+    #[test]
+    fn idle_counts_from_boot_until_reported() {
+        let mut log = ActivityLog::new();
+        assert_eq!(log.idle_ms(Activity::UserInput, 5_000), 5_000);
+        assert!(log.report(pid(4), Activity::UserInput, 6_000));
+        assert_eq!(log.idle_ms(Activity::UserInput, 9_000), 3_000);
+        // the kinds are tracked apart
+        assert_eq!(log.idle_ms(Activity::Network, 9_000), 9_000);
+    }
+
+    #[test]
+    fn only_the_first_reporter_counts() {
+        let mut log = ActivityLog::new();
+        assert!(log.report(pid(4), Activity::UserInput, 1_000));
+        assert!(!log.report(pid(9), Activity::UserInput, 50_000));
+        assert_eq!(log.idle_ms(Activity::UserInput, 60_000), 59_000);
+        // binding one kind doesn't bind the other
+        assert!(log.report(pid(9), Activity::Network, 50_000));
+        assert!(!log.report(pid(4), Activity::Network, 55_000));
+        assert!(log.report(pid(4), Activity::UserInput, 58_000));
+        assert_eq!(log.idle_ms(Activity::UserInput, 60_000), 2_000);
+    }
+
+    #[test]
+    fn anonymous_reports_are_ignored() {
+        let mut log = ActivityLog::new();
+        assert!(!log.report(None, Activity::UserInput, 1_000));
+        assert!(log.report(pid(4), Activity::UserInput, 2_000));
+    }
+
+    #[test]
+    fn clock_never_runs_backwards() {
+        let mut log = ActivityLog::new();
+        log.report(pid(4), Activity::Network, 10_000);
+        assert_eq!(log.idle_ms(Activity::Network, 9_000), 0);
+    }
+}
@@ -6,10 +6,11 @@ use xous_api_ticktimer::*;
 #[cfg(feature = "timestamp")]
 mod version;
 
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use log::{error, info};
 
+mod activity;
 mod platform;
 use platform::implementation::*;
 use platform::*;
@@ -65,7 +66,7 @@ fn main() -> ! {
     // A list of all sleep requests in the system, sorted by the time at which it
     // expires. That is, if a request comes in to sleep for 1000 ms, and the ticktimer
     // is currently at 900, the Request will be `1900`.
-    let mut sleep_heap: BTreeMap<TimeoutExpiry, TimerRequest> = BTreeMap::new();
+    let mut sleep_heap = SleepQueue::new();
 
     // A list of mutexes that should be allowed to run immediately, because the
     // thread they were waiting on has already unlocked the mutex. This occurs
@@ -89,9 +90,18 @@ fn main() -> ! {
     let mut mutex_hash: HashMap<Option<xous::PID>, HashMap<usize, VecDeque<xous::MessageSender>>> =
         HashMap::new();
 
+    // When each `api::Activity` was last reported, and by whom
+    let mut activity_log = activity::ActivityLog::new();
+
     let mut msg_opt = None;
     let mut return_type = 0;
     loop {
+        #[cfg(feature = "watchdog")]
+        if ticktimer.wdt_armed() {
+            // this loop went a whole watchdog period without running: report it, in case the system
+            // doesn't recover before the watchdog fires
+            log_server::report_watchdog_event().ok();
+        }
         #[cfg(feature = "watchdog")]
         ticktimer.reset_wdt();
         //#[cfg(feature = "watchdog")] // for debugging the watchdog
@@ -162,10 +172,7 @@ fn main() -> ! {
                 if notifications_with_timeouts.remove(&sender_id) {
                     // Check to make sure this isn't in the sleep heap. It shouldn't be,
                     // since the timer just fired.
-                    let len_before = sleep_heap.len();
-                    sleep_heap.retain(|_, v| v.sender != sender);
-                    let len_after = sleep_heap.len();
-                    assert!(len_before == len_after);
+                    assert!(sleep_heap.remove(sender).is_none());
 
                     // It should, however, still be in the notify hash. Remove it.
                     let awaiting = notify_table.entry(sender.pid()).or_default().entry(condvar).or_default();
@@ -218,6 +225,48 @@ fn main() -> ! {
                 }
             }
 
+            api::Opcode::GetProcessActivations => {
+                let mut buf = unsafe {
+                    xous_ipc::Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap())
+                };
+                let mut sample =
+                    api::ProcessActivations { elapsed_ms: 0, counts: [None; api::MAX_SAMPLED_PID] };
+                for (i, count) in sample.counts.iter_mut().enumerate() {
+                    // PIDs that are not in use return an error
+                    *count = xous::PID::new(i as u8 + 1).and_then(|pid| xous::process_activations(pid).ok());
+                }
+                sample.elapsed_ms = ticktimer.elapsed_ms();
+                buf.replace(sample).unwrap();
+            }
+
+            api::Opcode::ReportActivity => {
+                if let Some(scalar) = msg.body.scalar_message() {
+                    match <api::Activity as num_traits::FromPrimitive>::from_usize(scalar.arg1) {
+                        Some(activity) => {
+                            if !activity_log.report(msg.sender.pid(), activity, ticktimer.elapsed_ms()) {
+                                log::debug!("ignoring {:?} reported by {:?}", activity, msg.sender.pid());
+                            }
+                        }
+                        None => log::warn!("unknown activity reported: {}", scalar.arg1),
+                    }
+                }
+            }
+
+            api::Opcode::IdleMs => {
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let now = ticktimer.elapsed_ms();
+                    let idle = <api::Activity as num_traits::FromPrimitive>::from_usize(scalar.arg1)
+                        .map(|activity| activity_log.idle_ms(activity, now))
+                        .unwrap_or(now);
+                    scalar.arg1 = (idle & 0xFFFF_FFFF) as usize;
+                    scalar.arg2 = (idle >> 32) as usize;
+                    scalar.id = 0;
+
+                    // API calls expect a `Scalar2` value in response
+                    return_type = 2;
+                }
+            }
+
             api::Opcode::LockMutex => {
                 let Some(scalar) = msg.body.scalar_message_mut() else {
                     log::error!("sender made LockMutex request that was not blocking");
@@ -409,15 +458,8 @@ fn main() -> ! {
                         // removed, in which case we're waiting for a `RecalculateSleep` message
                         // to be processed, or there will be exactly one entry missing.
 
-                        let len_before = sleep_heap.len();
-                        sleep_heap.retain(|_, v| v.sender != entry);
-                        let len_after = sleep_heap.len();
-                        assert!((len_before == len_after) || (len_before == len_after + 1));
-
-                        // If the lengths are not equal, then our entry was in the sleep heap.
-                        // Presumably, it was responded to in the interrim, we just haven't
-                        // recalculated the sleep heap since then.
-                        if len_before != len_after {
+                        // If our entry was in the sleep heap, it hasn't timed out yet.
+                        if sleep_heap.remove(entry).is_some() {
                             // assert!(ticktimer.last_response() != entry);
                             // We removed an item from the sleep heap. This means that the entry
                             // didn't time out, and we should respond to it here.
@@ -492,15 +534,8 @@ fn main() -> ! {
                 for entry in awaiting.drain(..) {
                     // Remove each entry in the timeout set
                     if notifications_with_timeouts.remove(&entry.to_usize()) {
-                        let len_before = sleep_heap.len();
-                        sleep_heap.retain(|_, v| v.sender != entry);
-                        let len_after = sleep_heap.len();
-                        assert!((len_before == len_after) || (len_before == len_after + 1));
-
-                        // If the lengths are not equal, then our entry was in the sleep heap.
-                        // Presumably, it was responded to in the interrim, we just haven't
-                        // recalculated the sleep heap since then.
-                        if len_before != len_after {
+                        // If our entry was in the sleep heap, it hasn't timed out yet.
+                        if sleep_heap.remove(entry).is_some() {
                             assert!(ticktimer.last_response() != entry);
                             altered_sleep_heap = true;
                         } else {
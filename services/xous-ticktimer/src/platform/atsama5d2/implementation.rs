@@ -1,5 +1,3 @@
-use std::collections::BTreeMap;
-
 use atsama5d27::tc::Tc;
 use log::error;
 #[cfg(feature = "debug-print")]
@@ -7,7 +5,7 @@ use log::{info, trace};
 use utralib::*;
 use xous::arch::irq::IrqNumber;
 
-use crate::platform::{TimeoutExpiry, TimerRequest};
+use crate::platform::{SleepQueue, TimerRequest};
 
 const MASTER_CLOCK_SPEED: u32 = 164000000 / 2;
 const TICKS_PER_MS: u32 = MASTER_CLOCK_SPEED / 128 / 1000;
@@ -175,30 +173,27 @@ impl XousTickTimer {
         // TODO: reset watchdog timer
     }
 
+    #[allow(dead_code)]
+    pub fn wdt_armed(&self) -> bool { false }
+
     /// Disable the sleep interrupt and remove the currently-pending sleep item.
     /// If the sleep item has fired, then there will be no existing sleep item
     /// remaining.
-    pub fn stop_sleep(
-        &mut self,
-        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>, // min-heap with Reverse
-    ) {
+    pub fn stop_sleep(&mut self, sleep_heap: &mut SleepQueue) {
         // If there's a sleep request ongoing now, grab it.
         if let Some(current) = self.stop_interrupt() {
             #[cfg(feature = "debug-print")]
             info!("Existing request was {:?}", current);
-            sleep_heap.insert(current.msec, current);
+            sleep_heap.insert(current);
         } else {
             #[cfg(feature = "debug-print")]
             info!("There was no existing sleep() request");
         }
     }
 
-    pub fn start_sleep(
-        &mut self,
-        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>, // min-heap with Reverse
-    ) {
+    pub fn start_sleep(&mut self, sleep_heap: &mut SleepQueue) {
         // If there are items in the sleep heap, take the next item that will expire.
-        if let Some((_msec, next_response)) = sleep_heap.pop_first() {
+        if let Some(next_response) = sleep_heap.pop_first() {
             #[cfg(feature = "debug-print")]
             info!(
                 "scheduling a response at {} to {} (heap: {:?})",
@@ -218,11 +213,7 @@ impl XousTickTimer {
     ///
     /// Note that interrupts are always enabled, which is why we must stop the timer prior to
     /// reordering the list.
-    pub fn recalculate_sleep(
-        &mut self,
-        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>, // min-heap with Reverse
-        new: Option<TimerRequest>,
-    ) {
+    pub fn recalculate_sleep(&mut self, sleep_heap: &mut SleepQueue, new: Option<TimerRequest>) {
         let elapsed = self.elapsed_ms();
         self.stop_sleep(sleep_heap);
         log::trace!("Elapsed: {}", elapsed);
@@ -241,15 +232,11 @@ impl XousTickTimer {
             #[cfg(feature = "debug-print")]
             info!("New sleep request was: {:?}", request);
 
-            // Ensure that each timeout only exists once inside the tree
             request.msec += self.running_counter as i64;
-            while sleep_heap.contains_key(&request.msec) {
-                request.msec += 1;
-            }
 
             #[cfg(feature = "debug-print")]
             info!("Modified, the request was: {:?}", request);
-            sleep_heap.insert(request.msec, request);
+            sleep_heap.insert(request);
         } else {
             #[cfg(feature = "debug-print")]
             info!("No new sleep request");
@@ -1,14 +1,13 @@
 const TICKS_PER_MS: u64 = 1;
 
 use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
-use std::collections::BTreeMap;
 
 #[cfg(feature = "susres")]
 use susres::{RegManager, RegOrField, SuspendResume};
 use utralib::generated::*;
 use xous::definitions::MessageSender;
 
-use crate::TimeoutExpiry;
+use crate::SleepQueue;
 use crate::TimerRequest;
 
 /// Latency slack may be necessary for hardware implementations that can't handle
@@ -223,6 +222,14 @@ impl XousTickTimer {
     #[cfg(feature = "watchdog")]
     pub fn reset_wdt(&mut self) { self.wdt.wfo(utra::wdt::WATCHDOG_RESET_WDT, 1); }
 
+    /// Returns `true` if the watchdog has armed, meaning it went a whole period without being reset and
+    /// will reset the system at the end of the next one.
+    #[allow(dead_code)]
+    #[cfg(feature = "watchdog")]
+    pub fn wdt_armed(&self) -> bool {
+        self.wdt.r(utra::wdt::STATE) & self.wdt.ms(utra::wdt::STATE_DISARMED, 1) == 0
+    }
+
     #[allow(dead_code)]
     #[cfg(feature = "watchdog")]
     pub fn check_wdt(&mut self) {
@@ -278,18 +285,12 @@ impl XousTickTimer {
     ///
     /// The serial number of the ticktimer handler at the point where
     /// interrupts were disabled.
-    pub(crate) fn stop_sleep(
-        &mut self,
-        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>, // min-heap with Reverse
-    ) -> usize {
+    pub(crate) fn stop_sleep(&mut self, sleep_heap: &mut SleepQueue) -> usize {
         // If there's a sleep request ongoing now, grab it.
         if let Some(current) = self.stop_interrupt() {
             #[cfg(feature = "debug-print")]
             log::info!("Existing request was {:?}", current);
-            assert!(
-                sleep_heap.insert(current.msec, current).is_none(),
-                "Existing sleep_heap entry would be overwritten"
-            );
+            assert!(sleep_heap.insert(current), "Sender already has a sleep_heap entry");
         } else {
             #[cfg(feature = "debug-print")]
             log::info!("There was no existing sleep() request");
@@ -297,12 +298,9 @@ impl XousTickTimer {
         TICKTIMER_SEQUENCE_NUMBER.load(Ordering::Relaxed)
     }
 
-    pub(crate) fn start_sleep(
-        &mut self,
-        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>, // min-heap with Reverse
-    ) {
+    pub(crate) fn start_sleep(&mut self, sleep_heap: &mut SleepQueue) {
         // If there are items in the sleep heap, take the next item that will expire.
-        if let Some((_msec, next_response)) = sleep_heap.pop_first() {
+        if let Some(next_response) = sleep_heap.pop_first() {
             #[cfg(feature = "debug-print")]
             log::info!(
                 "scheduling a response at {} to {} (heap: {:?})",
@@ -325,7 +323,7 @@ impl XousTickTimer {
     /// This must be called with the sleep timer already stopped
     pub(crate) unsafe fn recalculate_sleep_offline(
         &mut self,
-        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>,
+        sleep_heap: &mut SleepQueue,
         new: Option<TimerRequest>,
     ) {
         log::trace!("Elapsed: {}", self.elapsed_ms());
@@ -335,18 +333,11 @@ impl XousTickTimer {
             #[cfg(feature = "debug-print")]
             log::info!("New sleep request was: {:?}", request);
 
-            // Ensure that each timeout only exists once inside the tree
             request.msec += self.elapsed_ms() as i64;
-            while sleep_heap.contains_key(&request.msec) {
-                request.msec += 1;
-            }
 
             #[cfg(feature = "debug-print")]
             log::info!("Modified, the request was: {:?}", request);
-            assert!(
-                sleep_heap.insert(request.msec, request).is_none(),
-                "Existing sleep_heap entry would be overwritten"
-            );
+            assert!(sleep_heap.insert(request), "Sender already has a sleep_heap entry");
         } else {
             #[cfg(feature = "debug-print")]
             log::info!("No new sleep request");
@@ -359,11 +350,7 @@ impl XousTickTimer {
     ///
     /// Note that interrupts are always enabled, which is why we must stop the timer prior to
     /// reordering the list.
-    pub(crate) fn recalculate_sleep(
-        &mut self,
-        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>,
-        new: Option<TimerRequest>,
-    ) {
+    pub(crate) fn recalculate_sleep(&mut self, sleep_heap: &mut SleepQueue, new: Option<TimerRequest>) {
         self.stop_sleep(sleep_heap);
         unsafe { self.recalculate_sleep_offline(sleep_heap, new) }
         self.start_sleep(sleep_heap);
@@ -1,4 +1,3 @@
-use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
@@ -7,7 +6,7 @@ use num_traits::ToPrimitive;
 use xous::definitions::MessageSender;
 
 use crate::RequestKind;
-use crate::TimeoutExpiry;
+use crate::SleepQueue;
 use crate::TimerRequest;
 
 /// The Message ID of the last message we responded to
@@ -133,6 +132,9 @@ impl XousTickTimer {
         // dummy function, does nothing
     }
 
+    #[allow(dead_code)]
+    pub fn wdt_armed(&self) -> bool { false }
+
     pub fn register_suspend_listener(&self, _opcode: u32, _cid: xous::CID) -> Result<(), xous::Error> {
         Ok(())
     }
@@ -146,30 +148,21 @@ impl XousTickTimer {
     /// Disable the sleep interrupt and remove the currently-pending sleep item.
     /// If the sleep item has fired, then there will be no existing sleep item
     /// remaining.
-    pub(crate) fn stop_sleep(
-        &mut self,
-        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>, // min-heap with Reverse
-    ) {
+    pub(crate) fn stop_sleep(&mut self, sleep_heap: &mut SleepQueue) {
         // If there's a sleep request ongoing now, grab it.
         if let Some(current) = self.stop_interrupt() {
             #[cfg(feature = "debug-print")]
             log::info!("Existing request was {:?}", current);
-            assert!(
-                sleep_heap.insert(current.msec, current).is_none(),
-                "Existing sleep_heap entry would be overwritten"
-            );
+            assert!(sleep_heap.insert(current), "Sender already has a sleep_heap entry");
         } else {
             #[cfg(feature = "debug-print")]
             log::info!("There was no existing sleep() request");
         }
     }
 
-    pub(crate) fn start_sleep(
-        &mut self,
-        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>, // min-heap with Reverse
-    ) {
+    pub(crate) fn start_sleep(&mut self, sleep_heap: &mut SleepQueue) {
         // If there are items in the sleep heap, take the next item that will expire.
-        if let Some((_msec, next_response)) = sleep_heap.pop_first() {
+        if let Some(next_response) = sleep_heap.pop_first() {
             #[cfg(feature = "debug-print")]
             log::info!(
                 "scheduling a response at {} to {} (heap: {:?})",
@@ -193,7 +186,7 @@ impl XousTickTimer {
     /// reordering the list.
     pub(crate) unsafe fn recalculate_sleep_offline(
         &mut self,
-        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>, // min-heap with Reverse
+        sleep_heap: &mut SleepQueue,
         new: Option<TimerRequest>,
     ) {
         log::trace!("Elapsed: {}", self.elapsed_ms());
@@ -203,29 +196,18 @@ impl XousTickTimer {
             #[cfg(feature = "debug-print")]
             log::info!("New sleep request was: {:?}", request);
 
-            // Ensure that each timeout only exists once inside the tree
             request.msec += self.elapsed_ms() as i64;
-            while sleep_heap.contains_key(&request.msec) {
-                request.msec += 1;
-            }
 
             #[cfg(feature = "debug-print")]
             log::info!("Modified, the request was: {:?}", request);
-            assert!(
-                sleep_heap.insert(request.msec, request).is_none(),
-                "Existing sleep_heap entry would be overwritten"
-            );
+            assert!(sleep_heap.insert(request), "Sender already has a sleep_heap entry");
         } else {
             #[cfg(feature = "debug-print")]
             log::info!("No new sleep request");
         }
     }
 
-    pub(crate) fn recalculate_sleep(
-        &mut self,
-        sleep_heap: &mut BTreeMap<TimeoutExpiry, TimerRequest>, // min-heap with Reverse
-        new: Option<TimerRequest>,
-    ) {
+    pub(crate) fn recalculate_sleep(&mut self, sleep_heap: &mut SleepQueue, new: Option<TimerRequest>) {
         self.stop_sleep(sleep_heap);
         unsafe { self.recalculate_sleep_offline(sleep_heap, new) }
         self.start_sleep(sleep_heap);
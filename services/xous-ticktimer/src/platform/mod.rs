@@ -43,6 +43,9 @@ pub mod cramium;
 #[cfg(any(feature = "cramium-fpga", feature = "cramium-soc"))]
 pub use cramium::*;
 
+mod sleep_queue;
+pub use sleep_queue::SleepQueue;
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
 pub(crate) struct TimeoutExpiry(i64);
 impl TimeoutExpiry {
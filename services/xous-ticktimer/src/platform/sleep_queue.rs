@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use super::TimerRequest;
+
+/// Pending sleep requests, ordered by the time at which they expire.
+///
+/// This is a binary min-heap with an index from each sender to its position in the heap, so the request
+/// of a condvar that is notified before it times out can be removed without searching for it. Inserting,
+/// removing and taking the next request to expire are all O(log n) in the number of sleepers.
+///
+/// Requests that expire at the same time are ordered by sender, so every request keeps its own expiry.
+#[derive(Default)]
+pub struct SleepQueue {
+    heap: Vec<TimerRequest>,
+    /// position of each sender's request in `heap`
+    index: HashMap<usize, usize>,
+}
+
+impl SleepQueue {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn len(&self) -> usize { self.heap.len() }
+
+    pub fn is_empty(&self) -> bool { self.heap.is_empty() }
+
+    /// Adds a request. Returns false, leaving the queue alone, if its sender already has one queued.
+    pub fn insert(&mut self, request: TimerRequest) -> bool {
+        let sender = request.sender.to_usize();
+        if self.index.contains_key(&sender) {
+            return false;
+        }
+        self.heap.push(request);
+        self.index.insert(sender, self.heap.len() - 1);
+        self.sift_up(self.heap.len() - 1);
+        true
+    }
+
+    /// The request that expires next
+    pub fn first(&self) -> Option<&TimerRequest> { self.heap.first() }
+
+    /// Removes and returns the request that expires next
+    pub fn pop_first(&mut self) -> Option<TimerRequest> { self.remove_at(0) }
+
+    /// Removes and returns the request of `sender`, if it has one queued
+    pub fn remove(&mut self, sender: xous::MessageSender) -> Option<TimerRequest> {
+        let position = *self.index.get(&sender.to_usize())?;
+        self.remove_at(position)
+    }
+
+    fn remove_at(&mut self, position: usize) -> Option<TimerRequest> {
+        if position >= self.heap.len() {
+            return None;
+        }
+        let last = self.heap.len() - 1;
+        self.swap(position, last);
+        let request = self.heap.pop().unwrap();
+        self.index.remove(&request.sender.to_usize());
+        if position < self.heap.len() {
+            // the request moved into the hole may belong either above or below it
+            self.sift_down(position);
+            self.sift_up(position);
+        }
+        Some(request)
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.index.insert(self.heap[a].sender.to_usize(), a);
+        self.index.insert(self.heap[b].sender.to_usize(), b);
+    }
+
+    fn sift_up(&mut self, mut position: usize) {
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if self.heap[position] >= self.heap[parent] {
+                break;
+            }
+            self.swap(position, parent);
+            position = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut position: usize) {
+        loop {
+            let mut smallest = position;
+            for child in [2 * position + 1, 2 * position + 2] {
+                if child < self.heap.len() && self.heap[child] < self.heap[smallest] {
+                    smallest = child;
+                }
+            }
+            if smallest == position {
+                break;
+            }
+            self.swap(position, smallest);
+            position = smallest;
+        }
+    }
+}
+
+impl core::fmt::Debug for SleepQueue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut requests: Vec<&TimerRequest> = self.heap.iter().collect();
+        requests.sort();
+        f.debug_list().entries(requests).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::RequestKind;
+
+    fn request(msec: i64, sender: usize) -> TimerRequest {
+        TimerRequest {
+            msec: msec.into(),
+            sender: xous::MessageSender::from_usize(sender),
+            kind: RequestKind::Sleep,
+            data: 0,
+        }
+    }
+
+    #[test]
+    fn pops_in_expiry_order() {
+        let mut queue = SleepQueue::new();
+        for (i, msec) in [50, 10, 40, 10, 30, 20, 10].iter().enumerate() {
+            assert!(queue.insert(request(*msec, i + 1)));
+        }
+        let mut popped = vec![];
+        while let Some(r) = queue.pop_first() {
+            popped.push((r.msec.to_i64(), r.sender.to_usize()));
+        }
+        assert_eq!(popped, vec![(10, 2), (10, 4), (10, 7), (20, 6), (30, 5), (40, 3), (50, 1)]);
+    }
+
+    #[test]
+    fn removes_by_sender() {
+        let mut queue = SleepQueue::new();
+        for sender in 1..=100 {
+            queue.insert(request((sender * 37 % 101) as i64, sender));
+        }
+        for sender in (1..=100).step_by(3) {
+            let removed = queue.remove(xous::MessageSender::from_usize(sender)).unwrap();
+            assert_eq!(removed.sender.to_usize(), sender);
+        }
+        assert!(queue.remove(xous::MessageSender::from_usize(1)).is_none());
+        assert_eq!(queue.len(), 66);
+        let mut last = i64::MIN;
+        while let Some(r) = queue.pop_first() {
+            assert!(r.msec.to_i64() >= last);
+            assert!(r.sender.to_usize() % 3 != 1);
+            last = r.msec.to_i64();
+        }
+    }
+
+    #[test]
+    fn rejects_second_request_from_sender() {
+        let mut queue = SleepQueue::new();
+        assert!(queue.insert(request(10, 1)));
+        assert!(!queue.insert(request(5, 1)));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.first().unwrap().msec.to_i64(), 10);
+    }
+}
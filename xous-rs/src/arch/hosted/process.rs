@@ -109,12 +109,29 @@ impl From<&ProcessStartup> for [usize; 7] {
 }
 
 #[derive(Debug)]
-pub struct ProcessHandle(std::process::Child);
+pub struct ProcessHandle {
+    child: std::process::Child,
+    pub pid: crate::PID,
+}
 
-/// If no connection exists, create a new connection to the server. This means
-/// our parent PID will be PID1. Otherwise, reuse the same connection.
+/// Pick a fresh key for the new process. The kernel binds the key to the PID it
+/// allocates, and the child presents the key when it connects.
 pub fn create_process_pre(_args: &ProcessArgs) -> core::result::Result<ProcessInit, crate::Error> {
-    unimplemented!()
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut key = [0u8; 16];
+    for half in key.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos())
+                .unwrap_or_default(),
+        );
+        half.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    Ok(ProcessInit { key: ProcessKey(key) })
 }
 
 /// Launch a new process with the current PID as the parent.
@@ -144,7 +161,7 @@ pub fn create_process_post(
         .env("XOUS_PROCESS_NAME", process_name_env)
         .env("XOUS_PROCESS_KEY", process_key_env)
         .spawn()
-        .map(ProcessHandle)
+        .map(|child| ProcessHandle { child, pid: startup.pid })
         .map_err(|_| {
             // eprintln!("couldn't start command: {}", e);
             crate::Error::InternalError
@@ -153,7 +170,7 @@ pub fn create_process_post(
 
 pub fn wait_process(mut joiner: ProcessHandle) -> crate::SysCallResult {
     joiner
-        .0
+        .child
         .wait()
         .or(Err(crate::Error::InternalError))
         .and_then(|e| if e.success() { Ok(crate::Result::Ok) } else { Err(crate::Error::UnknownError) })
@@ -10,6 +10,9 @@ pub type Connection = usize;
 
 pub const MAX_CID: usize = 34;
 
+/// The most segments that can be lent in one gathered lend
+pub const MAX_GATHER_SEGMENTS: usize = 16;
+
 pub const FLASH_PHYS_BASE: u32 = 0x2000_0000;
 pub const SOC_REGION_LOC: u32 = 0x0000_0000;
 pub const SOC_REGION_LEN: u32 = 0x00D0_0000; // gw + staging + loader + kernel
@@ -28,6 +31,7 @@ pub const LOADER_FONT_LEN: u32 = 0x0044_0000; // length of font region only
 pub const LOADER_TOTAL_LEN: u32 = LOADER_CODE_LEN + LOADER_FONT_LEN; // code + font
 
 pub const EARLY_SETTINGS: u32 = 0x0097_0000;
+// the two sectors after the early settings, 0x0097_1000-0x0097_2FFF, hold services/counters
 
 pub const KERNEL_LOC: u32 = 0x0098_0000; // kernel start
 pub const KERNEL_LEN: u32 = 0x0140_0000; // max kernel length = 0xA0_0000 * 2 => half the area for backup kernel & updates
@@ -589,6 +593,44 @@ macro_rules! msg_blocking_scalar_unpack {
     }};
 }
 
+/// The message counts of a process, as returned by `process_messages()`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ProcessMessages {
+    /// Messages the process has sent since it started; wraps on overflow
+    pub sent: u32,
+    /// Messages sent to the servers of the process since it started; wraps on overflow
+    pub received: u32,
+    /// Servers the process owns
+    pub servers: usize,
+    /// Messages waiting in its servers' queues for a thread to receive them
+    pub queued: usize,
+    /// Messages its servers have received, and not yet returned or replied to
+    pub outstanding: usize,
+}
+
+/// The longest process name `process_name()` can return
+pub const PROCESS_NAME_LEN: usize = 4 * core::mem::size_of::<usize>();
+
+/// The name of a process, as returned by `process_name()`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ProcessName {
+    bytes: [u8; PROCESS_NAME_LEN],
+    len: usize,
+}
+
+impl ProcessName {
+    pub(crate) fn from_words(len: usize, words: [usize; 4]) -> ProcessName {
+        let mut name = ProcessName { bytes: [0u8; PROCESS_NAME_LEN], len: len.min(PROCESS_NAME_LEN) };
+        for (chunk, word) in name.bytes.chunks_exact_mut(core::mem::size_of::<usize>()).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        name
+    }
+
+    /// The name, or an empty string if the process has none
+    pub fn as_str(&self) -> &str { core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("") }
+}
+
 #[cfg(feature = "swap")]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum AllocAdvice {
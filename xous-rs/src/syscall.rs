@@ -9,8 +9,8 @@ use crate::Exception
 pub use crate::arch::ProcessArgsAsThread;
 use crate::{
     pid_from_usize, CpuID, Error, MemoryAddress, MemoryFlags, MemoryMessage, MemoryRange, MemorySize,
-    MemoryType, Message, MessageEnvelope, MessageSender, ProcessArgs, ProcessInit, Result, ScalarMessage,
-    SysCallResult, ThreadInit, CID, PID, SID, TID,
+    MemoryType, Message, MessageEnvelope, MessageSender, ProcessArgs, ProcessInit, ProcessMessages,
+    ProcessName, Result, ScalarMessage, SysCallResult, ThreadInit, CID, PID, SID, TID,
 };
 
 #[derive(Debug, PartialEq)]
@@ -561,6 +561,100 @@ pub enum SysCall {
     #[cfg(feature = "raw-trng")]
     RawTrng(usize, usize, usize, usize, usize, usize, usize),
 
+    /// Get the number of times a thread of the given process has been switched in
+    /// since boot. The kernel has no time base of its own, so this is the closest
+    /// measure it has of how much a process runs: a process that keeps waking up
+    /// is also the one keeping the CPU out of its idle state.
+    ///
+    /// ## Returns
+    /// Returns a Scalar1 with the number of activations. The count wraps around
+    /// on overflow.
+    ///
+    /// ## Errors
+    ///     * **ProcessNotFound**: The given PID is not in use
+    GetProcessActivations(PID),
+
+    /// Lend several buffers to a server in a single message. The server receives
+    /// an ordinary `Borrow` or `MutableBorrow` whose buffer is the segments laid
+    /// end to end, in order, and returns it as it would any other lend.
+    ///
+    /// ## Arguments
+    ///     * **CID**: The connection to send the message on
+    ///     * **MemoryMessage**: The message to send. Its `buf` is a table of up to `MAX_GATHER_SEGMENTS`
+    ///       segments, each a pair of words holding the address and length of the segment, rather than the
+    ///       payload.
+    ///     * **bool**: `true` if the segments are lent mutably
+    ///
+    /// ## Returns
+    /// Returns a MemoryReturned once the server returns the segments
+    ///
+    /// ## Errors
+    ///     * **ServerNotFound**: The server does not exist so the connection is now invalid
+    ///     * **BadAddress**: The table, or one of the segments, is not owned by the caller
+    ///     * **BadAlignment**: A segment does not start and end on a page boundary
+    ///     * **InvalidLimit**: The table is empty or has too many segments
+    ///     * **OutOfMemory**: Too many gathered lends are outstanding
+    LendGathered(CID, MemoryMessage, bool),
+
+    /// Revoke every connection the given process has to the given server. Each
+    /// of its connection IDs for the server becomes a tombstone, so further
+    /// messages on it fail with `ServerNotFound`, just as if the server had gone
+    /// away. Only the process that owns the server may revoke connections to it.
+    ///
+    /// ## Returns
+    /// Returns a Scalar1 with the number of connections that were revoked
+    ///
+    /// ## Errors
+    ///     * **ServerNotFound**: There is no server with the given SID
+    ///     * **AccessDenied**: The server belongs to another process
+    ///     * **ProcessNotFound**: The given PID is not in use
+    RevokeConnection(PID, SID),
+
+    /// Get the message counts of the given process: how many messages it has
+    /// sent and its servers have received since it started, and what is held in
+    /// its servers' queues right now. Entries that pile up in a queue, or
+    /// servers that keep being created, are what a message or thread leak looks
+    /// like from the kernel.
+    ///
+    /// ## Returns
+    /// Returns a Scalar5 with the messages sent, the messages received, the
+    /// number of servers the process owns, the messages queued to those servers
+    /// and not yet received, and the messages received but not yet returned.
+    /// The first two wrap around on overflow.
+    ///
+    /// Only the caller's own counts are available, unless the kernel was built
+    /// with the `message-profile` feature.
+    ///
+    /// ## Errors
+    ///     * **ProcessNotFound**: The given PID is not in use
+    ///     * **AccessDenied**: The PID is another process', and the kernel isn't
+    ///       built for profiling
+    GetProcessMessages(PID),
+
+    /// Get the generation of the given PID, which changes every time the PID is
+    /// given to a new process. A server that remembers a client by its PID keeps
+    /// the generation alongside it, so that it can tell when the client has
+    /// exited and its PID has gone to someone else.
+    ///
+    /// ## Returns
+    /// Returns a Scalar1 with the generation
+    ///
+    /// ## Errors
+    ///     * **ProcessNotFound**: The given PID is not in use
+    GetProcessGeneration(PID),
+
+    /// Get the name the given process was loaded with. A server that shows its
+    /// clients to the user labels them with this rather than a name they chose
+    /// themselves. Names longer than four words are cut short.
+    ///
+    /// ## Returns
+    /// Returns a Scalar5 with the length of the name in bytes, followed by four
+    /// words of UTF-8. The length is zero if the process has no name.
+    ///
+    /// ## Errors
+    ///     * **ProcessNotFound**: The given PID is not in use
+    GetProcessName(PID),
+
     /// This syscall does not exist. It captures all possible
     /// arguments so detailed analysis can be performed.
     Invalid(usize, usize, usize, usize, usize, usize, usize),
@@ -618,6 +712,12 @@ pub enum SysCallNumber {
     SwapOp = 44,
     #[cfg(feature = "raw-trng")]
     RawTrng = 45,
+    GetProcessActivations = 46,
+    LendGathered = 47,
+    RevokeConnection = 48,
+    GetProcessMessages = 49,
+    GetProcessGeneration = 50,
+    GetProcessName = 51,
 }
 
 impl SysCallNumber {
@@ -673,6 +773,12 @@ impl SysCallNumber {
             44 => SwapOp,
             #[cfg(feature = "raw-trng")]
             45 => RawTrng,
+            46 => GetProcessActivations,
+            47 => LendGathered,
+            48 => RevokeConnection,
+            49 => GetProcessMessages,
+            50 => GetProcessGeneration,
+            51 => GetProcessName,
             _ => Invalid,
         }
     }
@@ -926,6 +1032,44 @@ impl SysCall {
             SysCall::RawTrng(a1, a2, a3, a4, a5, a6, a7) => {
                 [SysCallNumber::RawTrng as usize, *a1, *a2, *a3, *a4, *a5, *a6, *a7]
             }
+            SysCall::GetProcessActivations(pid) => {
+                [SysCallNumber::GetProcessActivations as usize, pid.get() as usize, 0, 0, 0, 0, 0, 0]
+            }
+            SysCall::LendGathered(cid, msg, mutable) => {
+                let m = msg.to_usize();
+                [
+                    SysCallNumber::LendGathered as usize,
+                    *cid as usize,
+                    *mutable as usize,
+                    m[0],
+                    m[1],
+                    m[2],
+                    m[3],
+                    m[4],
+                ]
+            }
+            SysCall::RevokeConnection(pid, sid) => {
+                let s = sid.to_u32();
+                [
+                    SysCallNumber::RevokeConnection as usize,
+                    pid.get() as _,
+                    s.0 as _,
+                    s.1 as _,
+                    s.2 as _,
+                    s.3 as _,
+                    0,
+                    0,
+                ]
+            }
+            SysCall::GetProcessMessages(pid) => {
+                [SysCallNumber::GetProcessMessages as usize, pid.get() as usize, 0, 0, 0, 0, 0, 0]
+            }
+            SysCall::GetProcessGeneration(pid) => {
+                [SysCallNumber::GetProcessGeneration as usize, pid.get() as usize, 0, 0, 0, 0, 0, 0]
+            }
+            SysCall::GetProcessName(pid) => {
+                [SysCallNumber::GetProcessName as usize, pid.get() as usize, 0, 0, 0, 0, 0, 0]
+            }
             SysCall::Invalid(a1, a2, a3, a4, a5, a6, a7) => {
                 [SysCallNumber::Invalid as usize, *a1, *a2, *a3, *a4, *a5, *a6, *a7]
             }
@@ -1080,6 +1224,17 @@ impl SysCall {
             SysCallNumber::SwapOp => SysCall::SwapOp(a1, a2, a3, a4, a5, a6, a7),
             #[cfg(feature = "raw-trng")]
             SysCallNumber::RawTrng => SysCall::RawTrng(a1, a2, a3, a4, a5, a6, a7),
+            SysCallNumber::GetProcessActivations => SysCall::GetProcessActivations(pid_from_usize(a1)?),
+            SysCallNumber::LendGathered => MemoryMessage::from_usize(a3, a4, a5, a6, a7)
+                .map(|m| SysCall::LendGathered(a1.try_into().unwrap(), m, a2 != 0))
+                .unwrap_or_else(|| SysCall::Invalid(a1, a2, a3, a4, a5, a6, a7)),
+            SysCallNumber::RevokeConnection => SysCall::RevokeConnection(
+                pid_from_usize(a1)?,
+                SID::from_u32(a2 as _, a3 as _, a4 as _, a5 as _),
+            ),
+            SysCallNumber::GetProcessMessages => SysCall::GetProcessMessages(pid_from_usize(a1)?),
+            SysCallNumber::GetProcessGeneration => SysCall::GetProcessGeneration(pid_from_usize(a1)?),
+            SysCallNumber::GetProcessName => SysCall::GetProcessName(pid_from_usize(a1)?),
             SysCallNumber::Invalid => SysCall::Invalid(a1, a2, a3, a4, a5, a6, a7),
         })
     }
@@ -1499,6 +1654,22 @@ pub fn try_receive_message(server: SID) -> core::result::Result<Option<MessageEn
     }
 }
 
+/// Revoke the connections that process `pid` has to the server `sid`, which
+/// must belong to the calling process, so that any further message it sends on
+/// them fails with `ServerNotFound`. Returns the number of connections that were
+/// revoked.
+///
+/// # Errors
+///
+/// * **ServerNotFound**: There is no server with the given SID
+/// * **AccessDenied**: The server belongs to another process
+/// * **ProcessNotFound**: The given PID is not in use
+pub fn revoke_connection(pid: PID, sid: SID) -> core::result::Result<usize, Error> {
+    rsyscall(SysCall::RevokeConnection(pid, sid)).and_then(|result| {
+        if let Result::Scalar1(count) = result { Ok(count) } else { Err(Error::InternalError) }
+    })
+}
+
 /// Send a message to a server.  Depending on the message type (move or borrow), it
 /// will either block (borrow) or return immediately (move).
 /// If the message type is `borrow`, then the memory addresses pointed to will be
@@ -1567,6 +1738,90 @@ pub fn send_message(connection: CID, message: Message) -> core::result::Result<R
     }
 }
 
+/// Lend several buffers to a server in one message, without first copying them
+/// into one allocation. The server receives an ordinary lend of `id` whose buffer
+/// is the segments laid end to end, in order, with `offset` and `valid` passed
+/// through. If `mutable` is `true`, changes the server makes are in the segments
+/// once this returns.
+///
+/// Each segment must start and end on a page boundary, and there may be at most
+/// `MAX_GATHER_SEGMENTS` of them. In hosted mode the segments are copied into one
+/// buffer, which is lent instead.
+///
+/// # Errors
+///
+/// * **ServerNotFound**: The server does not exist so the connection is now invalid
+/// * **BadAddress**: One of the segments is not owned by the caller
+/// * **BadAlignment**: A segment does not start and end on a page boundary
+/// * **InvalidLimit**: There are no segments, or too many of them
+/// * **OutOfMemory**: Too many gathered lends are outstanding across the system
+pub fn lend_gathered(
+    connection: CID,
+    id: usize,
+    segments: &[MemoryRange],
+    offset: Option<MemoryAddress>,
+    valid: Option<MemorySize>,
+    mutable: bool,
+) -> core::result::Result<Result, Error> {
+    if segments.is_empty() || segments.len() > crate::MAX_GATHER_SEGMENTS {
+        return Err(Error::InvalidLimit);
+    }
+
+    #[cfg(target_os = "xous")]
+    {
+        let mut table = [[0usize; 2]; crate::MAX_GATHER_SEGMENTS];
+        for (entry, segment) in table.iter_mut().zip(segments) {
+            *entry = [segment.as_ptr() as usize, segment.len()];
+        }
+        let table = unsafe {
+            MemoryRange::new(table.as_ptr() as usize, segments.len() * core::mem::size_of::<[usize; 2]>())
+        }?;
+        let result = rsyscall(SysCall::LendGathered(
+            connection,
+            MemoryMessage { id, buf: table, offset, valid },
+            mutable,
+        ));
+        match result {
+            Ok(Result::MemoryReturned(offset, valid)) => Ok(Result::MemoryReturned(offset, valid)),
+            Err(e) => Err(e),
+            v => panic!("Unexpected return value: {:?}", v),
+        }
+    }
+
+    #[cfg(not(target_os = "xous"))]
+    {
+        let len = segments.iter().map(|segment| segment.len()).sum();
+        let mut buf = map_memory(None, None, len, MemoryFlags::R | MemoryFlags::W)?;
+        let mut start = 0;
+        for segment in segments {
+            unsafe {
+                buf.as_slice_mut::<u8>()[start..start + segment.len()].copy_from_slice(segment.as_slice())
+            };
+            start += segment.len();
+        }
+        let message = MemoryMessage { id, buf, offset, valid };
+        let result = if mutable {
+            send_message(connection, Message::MutableBorrow(message))
+        } else {
+            send_message(connection, Message::Borrow(message))
+        };
+        if mutable && result.is_ok() {
+            let mut start = 0;
+            for segment in segments {
+                let mut segment = *segment;
+                unsafe {
+                    segment
+                        .as_slice_mut::<u8>()
+                        .copy_from_slice(&buf.as_slice::<u8>()[start..start + segment.len()])
+                };
+                start += segment.len();
+            }
+        }
+        unmap_memory(buf)?;
+        result
+    }
+}
+
 pub fn terminate_process(exit_code: u32) -> ! {
     rsyscall(SysCall::TerminateProcess(exit_code)).expect("terminate_process returned an error");
     panic!("process didn't terminate");
@@ -1753,6 +2008,52 @@ pub fn current_pid() -> core::result::Result<PID, Error> {
     })
 }
 
+/// Get the number of times the given process has been switched in since boot.
+/// Sample this twice to see how often a process wakes up over an interval.
+pub fn process_activations(pid: PID) -> core::result::Result<u32, Error> {
+    rsyscall(SysCall::GetProcessActivations(pid)).and_then(|result| {
+        if let Result::Scalar1(count) = result { Ok(count as u32) } else { Err(Error::InternalError) }
+    })
+}
+
+/// Get the message counts of the given process. Sample this twice to see how
+/// busy a process is, or watch `queued` and `servers` grow to find a leak.
+/// Other processes' counts are only available from a kernel built with the
+/// `message-profile` feature, and are refused with `AccessDenied` otherwise.
+pub fn process_messages(pid: PID) -> core::result::Result<ProcessMessages, Error> {
+    rsyscall(SysCall::GetProcessMessages(pid)).and_then(|result| {
+        if let Result::Scalar5(sent, received, servers, queued, outstanding) = result {
+            Ok(ProcessMessages { sent: sent as u32, received: received as u32, servers, queued, outstanding })
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
+/// Get the generation of the given PID. It changes whenever the PID is reused,
+/// so a PID and its generation together name one process for as long as it runs.
+pub fn process_generation(pid: PID) -> core::result::Result<u32, Error> {
+    rsyscall(SysCall::GetProcessGeneration(pid)).and_then(|result| {
+        if let Result::Scalar1(generation) = result {
+            Ok(generation as u32)
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
+/// Get the name the given process was loaded with, which the process itself
+/// can't change. Names longer than `PROCESS_NAME_LEN` bytes are cut short.
+pub fn process_name(pid: PID) -> core::result::Result<ProcessName, Error> {
+    rsyscall(SysCall::GetProcessName(pid)).and_then(|result| {
+        if let Result::Scalar5(len, a, b, c, d) = result {
+            Ok(ProcessName::from_words(len, [a, b, c, d]))
+        } else {
+            Err(Error::InternalError)
+        }
+    })
+}
+
 /// Get the current thread ID
 pub fn current_tid() -> core::result::Result<TID, Error> {
     rsyscall(SysCall::GetThreadId).and_then(|result| {
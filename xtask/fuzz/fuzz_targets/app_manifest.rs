@@ -0,0 +1,27 @@
+#![no_main]
+use std::collections::BTreeMap;
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/app_manifest.rs"]
+mod app_manifest;
+use app_manifest::AppManifest;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(content) = std::str::from_utf8(data) {
+        if let Ok(manifest) = app_manifest::parse_manifest(content) {
+            let _ = app_manifest::i18n(&manifest);
+            let working_set: BTreeMap<String, &AppManifest> =
+                manifest.iter().map(|(app, entry)| (app.to_string(), entry)).collect();
+            let generated = [
+                app_manifest::gam_tokens(&working_set),
+                app_manifest::app_menu(&working_set, working_set.is_empty()),
+            ];
+            // the names pasted into string literals must not end them early
+            for line in generated.iter().flat_map(|rust| rust.lines()) {
+                assert_eq!(line.matches('"').count() % 2, 0, "{}", line);
+            }
+        }
+    }
+});
@@ -11,7 +11,7 @@ use std::{
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Debug)]
-struct AppManifest {
+pub(crate) struct AppManifest {
     context_name: String,
     menu_name: HashMap<String, HashMap<String, String>>,
     submenu: Option<u8>,
@@ -21,14 +21,55 @@ struct Locales {
     locales: HashMap<String, HashMap<String, String>>,
 }
 
+/// Parses the manifest, and checks that everything in it can be pasted into the generated Rust: app
+/// names become parts of identifiers, and the other names end up inside string literals.
+pub(crate) fn parse_manifest(content: &str) -> Result<HashMap<String, AppManifest>, String> {
+    let manifest: HashMap<String, AppManifest> =
+        serde_json::from_str(content).map_err(|e| format!("Cannot parse manifest file: {}", e))?;
+    for (app, entry) in manifest.iter() {
+        if app.is_empty() || !app.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(format!("App name {:?} must be letters, digits and dashes", app));
+        }
+        if entry.menu_name.len() != 1 {
+            return Err(format!("Improper menu name record entry for {}", app));
+        }
+        for name in std::iter::once(&entry.context_name).chain(entry.menu_name.keys()) {
+            if name.chars().any(|c| c == '"' || c == '\\' || c.is_control()) {
+                return Err(format!(
+                    "Name {:?} of {} can't contain quotes, backslashes or control characters",
+                    name, app
+                ));
+            }
+        }
+    }
+    Ok(manifest)
+}
+
 pub(crate) fn generate_app_menus(apps: &Vec<String>) {
     let file = File::open("apps/manifest.json").expect("Failed to open the manifest file");
     let mut reader = std::io::BufReader::new(file);
     let mut content = String::new();
     reader.read_to_string(&mut content).expect("Failed to read the file");
-    let manifest: HashMap<String, AppManifest> =
-        serde_json::from_str(&content).expect("Cannot parse manifest file");
+    let manifest = parse_manifest(&content).unwrap_or_else(|e| panic!("{}", e));
 
+    // output a JSON localizations file, if things have changed
+    overwrite_if_changed(&i18n(&manifest), "apps/i18n.json");
+
+    // output the Rust manifests - tailored just for the apps requested
+    let mut working_set = BTreeMap::<String, &AppManifest>::new();
+    // derive a working_set that is just the apps we requested
+    for app in apps {
+        if let Some(manifest) = manifest.get(app) {
+            working_set.insert(app.to_string(), manifest);
+        }
+    }
+    overwrite_if_changed(&gam_tokens(&working_set), "services/gam/src/apps.rs");
+    let menu = app_menu(&working_set, apps.is_empty());
+    overwrite_if_changed(&menu, "services/status/src/app_autogen.rs");
+    overwrite_if_changed(&menu, "services/cram-console/src/app_autogen.rs");
+}
+
+pub(crate) fn i18n(manifest: &HashMap<String, AppManifest>) -> String {
     // localization file
     // inject all the localization strings into the i18n file, which in theory reduces the churn on other
     // crates that depend on the global i18n file between build variants
@@ -42,20 +83,10 @@ pub(crate) fn generate_app_menus(apps: &Vec<String>) {
             l.insert(name.to_string(), map);
         }
     }
-    // output a JSON localizations file, if things have changed
-    let new_i18n = serde_json::to_string(&l).unwrap();
-    overwrite_if_changed(&new_i18n, "apps/i18n.json");
-
-    // output the Rust manifests - tailored just for the apps requested
-    let mut working_set = BTreeMap::<String, &AppManifest>::new();
-    // derive a working_set that is just the apps we requested
-    for app in apps {
-        if let Some(manifest) = manifest.get(app) {
-            working_set.insert(app.to_string(), manifest);
-        }
-    }
+    serde_json::to_string(&l).unwrap()
+}
 
-    // construct the gam_tokens
+pub(crate) fn gam_tokens(working_set: &BTreeMap<String, &AppManifest>) -> String {
     let mut gam_tokens = String::new();
     writeln!(gam_tokens, "#![cfg_attr(rustfmt, rustfmt_skip)]").unwrap();
     writeln!(gam_tokens, "// This file is auto-generated by xtask/main.rs generate_app_menus()").unwrap();
@@ -92,13 +123,14 @@ pub(crate) fn generate_app_menus(apps: &Vec<String>) {
         }
     }
     writeln!(gam_tokens, "];").unwrap();
-    overwrite_if_changed(&gam_tokens, "services/gam/src/apps.rs");
+    gam_tokens
+}
 
-    // construct the app menu
+pub(crate) fn app_menu(working_set: &BTreeMap<String, &AppManifest>, no_apps: bool) -> String {
     let mut menu = String::new();
     writeln!(menu, "#![cfg_attr(rustfmt, rustfmt_skip)]").unwrap();
     writeln!(menu, "// This file is auto-generated by xtask/main.rs generate_app_menus()").unwrap();
-    if apps.is_empty() {
+    if no_apps {
         writeln!(menu, "// NO APPS SELECTED: suppressing warning messages!").unwrap();
         writeln!(menu, "#![allow(dead_code)]").unwrap();
         writeln!(menu, "#![allow(unused_imports)]").unwrap();
@@ -170,7 +202,6 @@ pub(crate) fn app_menu_items(menu_items: &mut Vec::<MenuItem>, status_conn: u32)
     .unwrap();
     for (index, (_app_name, manifest)) in working_set.iter().enumerate() {
         writeln!(menu, "    menu_items.push(MenuItem {{",).unwrap();
-        assert_eq!(manifest.menu_name.len(), 1, "Improper menu name record entry");
         for name in manifest.menu_name.keys() {
             writeln!(menu, "        name: xous_ipc::String::from_str(t!(\"{}\", locales::LANG)),", name)
                 .unwrap();
@@ -182,8 +213,7 @@ pub(crate) fn app_menu_items(menu_items: &mut Vec::<MenuItem>, status_conn: u32)
         writeln!(menu, "    }});\n",).unwrap();
     }
     writeln!(menu, "}}").unwrap();
-    overwrite_if_changed(&menu, "services/status/src/app_autogen.rs");
-    overwrite_if_changed(&menu, "services/cram-console/src/app_autogen.rs");
+    menu
 }
 
 fn overwrite_if_changed(new_string: &String, old_file: &str) {
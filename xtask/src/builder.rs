@@ -147,6 +147,8 @@ pub(crate) struct Builder {
     /// when Some, specifies a swap region as offset, size
     swap: Option<(u32, u32)>,
     change_target: bool,
+    /// when Some, the host file that backs the PDDB in hosted mode
+    pddb_image: Option<String>,
 }
 
 impl Builder {
@@ -176,6 +178,7 @@ impl Builder {
             no_image: false,
             swap: None,
             change_target: false,
+            pddb_image: None,
         }
     }
 
@@ -233,6 +236,12 @@ impl Builder {
         self
     }
 
+    /// Specify the host file that backs the PDDB in hosted mode. Its contents are kept between runs.
+    pub fn hosted_pddb_image(&mut self, filename: String) -> &mut Builder {
+        self.pddb_image = Some(filename);
+        self
+    }
+
     /// Set a minimum xous version. This is the minimum Xous version necessary to read
     /// the PDDB that is generated by this build. The purpose of this is so that we can
     /// trim migration code out of the PDDB: when we have a breaking change to the PDDB,
@@ -754,7 +763,14 @@ impl Builder {
                     print!(" {}", arg);
                 }
                 println!();
-                let status = Command::new(cargo()).current_dir(dir).args(&hosted_args).status()?;
+                let mut command = Command::new(cargo());
+                if let Some(image) = &self.pddb_image {
+                    // hosted mode runs from the kernel directory, so relative paths are resolved here
+                    let image = std::env::current_dir()?.join(image);
+                    println!("    PDDB image: {}", image.display());
+                    command.env("XOUS_PDDB_IMAGE", image);
+                }
+                let status = command.current_dir(dir).args(&hosted_args).status()?;
                 if !status.success() {
                     return Err("cargo run failed to launch hosted mode".into());
                 }
@@ -1100,7 +1116,7 @@ impl Builder {
                 // stash the existing locale
                 let mut locale_file = OpenOptions::new()
                     .read(true)
-                    .open("locales/src/locale.rs")
+                    .open("locales/src/default_lang.rs")
                     .expect("Can't open locale file for reading");
                 locale_file.read_to_string(&mut self.locale_stash).unwrap();
             }
@@ -1110,9 +1126,9 @@ impl Builder {
                 .write(true)
                 .create(true)
                 .truncate(true)
-                .open("locales/src/locale.rs")
+                .open("locales/src/default_lang.rs")
                 .expect("Can't open locale for modification");
-            writeln!(locale_override, "pub const LANG: &str = \"{}\";", locale).unwrap();
+            writeln!(locale_override, "pub const DEFAULT_LANG: &str = \"{}\";", locale).unwrap();
         }
     }
 
@@ -1123,7 +1139,7 @@ impl Builder {
                 .write(true)
                 .create(true)
                 .truncate(true)
-                .open("locales/src/locale.rs")
+                .open("locales/src/default_lang.rs")
                 .expect("Can't open locale for modification");
             write!(locale_restore, "{}", self.locale_stash).unwrap();
         }
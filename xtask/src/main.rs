@@ -95,8 +95,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "ime-plugin-shell",
             "codec",
             "modals",
+            "clipboard",
+            "optical",
             // security
             "root-keys",
+            "counters",
             "trng",
             "sha2",
             // "engine-25519",
@@ -108,6 +111,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "pddb",
             // usb services
             "usb-device-xous",
+            // restarts services that exit or fault
+            "supervisor",
+            // temperature monitoring and throttling
+            "thermal",
         ],
     ]
     .concat();
@@ -196,6 +203,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     if env::args().filter(|x| x == "--change-target").count() != 0 {
         builder.set_change_target_flag();
     }
+    let pddb_image = get_flag("--pddb-image")?;
+    if !pddb_image.is_empty() {
+        builder.hosted_pddb_image(pddb_image[0].to_string());
+    }
 
     // manage an ugly patch we have to do to selectively configure AES for only cramium-soc targets
     match builder::search_in_file("services/aes/Cargo.toml", "default = []") {
@@ -251,6 +262,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Some("renode-test") => {
             builder.target_renode().add_services(&base_pkgs).add_services(&get_cratespecs());
         }
+        Some("test-image") => {
+            // the test runner reports over the UART; [cratespecs] add whatever else the tests should reach
+            builder.target_renode().add_services(&base_pkgs).add_services(&get_cratespecs());
+            builder.add_service("test-runner", LoaderRegion::Ram);
+            builder.add_loader_feature("renode-bypass");
+        }
         Some("libstd-test") => {
             builder.target_renode().add_services(&base_pkgs).add_services(&get_cratespecs());
             builder.add_loader_feature("renode-bypass");
@@ -640,6 +657,7 @@ fn print_help() {
     [--debug-loader]
     [--offline]
     [--change-target]
+    [--pddb-image [path]]
 
 [cratespecs] is a list of 0 or more items of the following syntax:
    [name]                crate 'name' to be built from local source
@@ -666,6 +684,8 @@ be merged in with explicit app/service treatment with the following flags:
 [--swap offset:size]     Specify a region for swap memory. The behavior of this depends on the target.
 [--change-target]        Used to clean the cached target/*/*/build/SVD_PATH when changing build targets.
                          This will also force a full rebuild every time the flag is specified.
+[--pddb-image path]      Host file that backs the PDDB in hosted mode, so its contents persist between runs.
+                         Defaults to tools/pddb-images/hosted.bin; can also be set with XOUS_PDDB_IMAGE.
 
 - An 'app' must be enumerated in apps/manifest.json.
    A pre-processor configures the launch menu based on the list of specified apps.
@@ -695,6 +715,8 @@ Renode emulation:
  renode-image            Renode user image. Unspecified [cratespecs] are apps
  renode-test             Renode test image. Unspecified [cratespecs] are services
  renode-image-debug      Renode user image with --debug flag set
+ test-image              Renode image that runs the on-target integration tests and reports the results over
+                         the UART. [cratespecs] are services
  libstd-test             Renode test image that includes the minimum packages. [cratespecs] are services
                          Bypasses sig checks, keys locked out.
  libstd-net              Renode test image for testing network functions. Bypasses sig checks, keys locked out.
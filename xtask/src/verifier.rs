@@ -12,12 +12,12 @@ pub fn check_project_consistency() -> Result<(), DynError> {
     // TODO: retire utralib/svd2utra from publication as well
     let check_pkgs = [
         // this set updates with kernel API changes
-        "xous^0.9.63",
-        "xous-ipc^0.9.63",
-        "xous-api-log^0.1.59",
-        "xous-api-names^0.9.61",
-        "xous-api-susres^0.9.59",
-        "xous-api-ticktimer^0.9.59",
+        "xous^0.9.64",
+        "xous-ipc^0.9.64",
+        "xous-api-log^0.1.60",
+        "xous-api-names^0.9.62",
+        "xous-api-susres^0.9.60",
+        "xous-api-ticktimer^0.9.60",
     ];
     // utra/svd2utra changes are downgraded to warnings because these now prefer to pull
     // from the local patch version, so any inconsistency simply indicates we forgot to